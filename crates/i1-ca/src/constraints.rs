@@ -0,0 +1,353 @@
+//! Limits on what an intermediate CA may sign.
+//!
+//! An intermediate handed to a system we only half trust, such as an
+//! automated issuer for honeypot endpoints, should not be able to sign for
+//! anything else. [`CaConstraints`] go into the intermediate's certificate
+//! as its path length and X.509 name constraints, so clients enforce them,
+//! and are checked again here before anything is signed.
+
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+use rcgen::{CidrSubnet, GeneralSubtree, NameConstraints, SanType};
+use serde::{Deserialize, Serialize};
+use x509_parser::extensions::GeneralName;
+
+use crate::CaError;
+
+/// A range of IP addresses, written `192.0.2.0/24`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct IpRange {
+    /// First address in the range
+    addr: IpAddr,
+    /// Leading bits every address in the range shares
+    prefix: u8,
+}
+
+impl IpRange {
+    /// The range of addresses sharing the first `prefix` bits of `addr`.
+    pub fn new(addr: IpAddr, prefix: u8) -> Result<Self, CaError> {
+        let bits = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        if prefix > bits {
+            return Err(CaError::Signing(format!(
+                "prefix /{prefix} is too long for {addr}"
+            )));
+        }
+        let addr = match addr {
+            IpAddr::V4(v4) => Ipv4Addr::from(u32::from(v4) & mask_v4(prefix)).into(),
+            IpAddr::V6(v6) => Ipv6Addr::from(u128::from(v6) & mask_v6(prefix)).into(),
+        };
+        Ok(Self { addr, prefix })
+    }
+
+    /// First address in the range.
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    /// Length of the prefix, in bits.
+    pub fn prefix(&self) -> u8 {
+        self.prefix
+    }
+
+    /// Whether `ip` is in the range. IPv4 and IPv6 never mix.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip) {
+            (IpAddr::V4(base), IpAddr::V4(ip)) => {
+                (u32::from(ip) ^ u32::from(base)) & mask_v4(self.prefix) == 0
+            }
+            (IpAddr::V6(base), IpAddr::V6(ip)) => {
+                (u128::from(ip) ^ u128::from(base)) & mask_v6(self.prefix) == 0
+            }
+            _ => false,
+        }
+    }
+
+    fn subnet(&self) -> CidrSubnet {
+        CidrSubnet::from_addr_prefix(self.addr, self.prefix)
+    }
+}
+
+/// The top `prefix` bits of an IPv4 address set.
+fn mask_v4(prefix: u8) -> u32 {
+    u32::MAX.checked_shl(32 - u32::from(prefix)).unwrap_or(0)
+}
+
+/// The top `prefix` bits of an IPv6 address set.
+fn mask_v6(prefix: u8) -> u128 {
+    u128::MAX.checked_shl(128 - u32::from(prefix)).unwrap_or(0)
+}
+
+impl fmt::Display for IpRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+impl FromStr for IpRange {
+    type Err = CaError;
+
+    /// An address range like `10.0.0.0/8`; a bare address is a range of
+    /// one.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || CaError::Signing(format!("'{s}' is not an IP range"));
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+        let addr: IpAddr = addr.trim().parse().map_err(|_| invalid())?;
+        let prefix = match prefix {
+            Some(prefix) => prefix.trim().parse().map_err(|_| invalid())?,
+            None if addr.is_ipv4() => 32,
+            None => 128,
+        };
+        Self::new(addr, prefix)
+    }
+}
+
+impl TryFrom<String> for IpRange {
+    type Error = CaError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<IpRange> for String {
+    fn from(range: IpRange) -> Self {
+        range.to_string()
+    }
+}
+
+/// What an intermediate CA may sign.
+///
+/// With no permitted names the CA may sign for any name. Once either list
+/// has an entry, every name must be in one: a CA permitted only DNS names
+/// may sign no IP addresses, and the other way round.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CaConstraints {
+    /// CAs allowed below this one; 0 means it signs only leaves
+    #[serde(default)]
+    pub path_len: u8,
+    /// DNS names it may sign; `example.com` covers it and every name under it
+    #[serde(default)]
+    pub permitted_dns: Vec<String>,
+    /// IP ranges it may sign
+    #[serde(default)]
+    pub permitted_ips: Vec<IpRange>,
+}
+
+impl CaConstraints {
+    /// No limits beyond signing only leaves.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allow this many CAs below the intermediate.
+    pub fn path_len(mut self, path_len: u8) -> Self {
+        self.path_len = path_len;
+        self
+    }
+
+    /// Permit a domain and every name under it.
+    pub fn permit_dns(mut self, domain: impl Into<String>) -> Self {
+        self.permitted_dns.push(domain.into());
+        self
+    }
+
+    /// Permit a range of IP addresses.
+    pub fn permit_ip(mut self, range: IpRange) -> Self {
+        self.permitted_ips.push(range);
+        self
+    }
+
+    /// Check if names are limited at all.
+    pub fn limits_names(&self) -> bool {
+        !self.permitted_dns.is_empty() || !self.permitted_ips.is_empty()
+    }
+
+    /// Whether a DNS name, or wildcard, is within a permitted domain.
+    pub fn permits_dns(&self, name: &str) -> bool {
+        if !self.limits_names() {
+            return true;
+        }
+        let name = name.trim_end_matches('.').to_ascii_lowercase();
+        self.permitted_dns.iter().any(|domain| {
+            let domain = domain.trim_matches('.').to_ascii_lowercase();
+            name == domain
+                || name
+                    .strip_suffix(&domain)
+                    .is_some_and(|sub| sub.ends_with('.'))
+        })
+    }
+
+    /// Whether an IP address is within a permitted range.
+    pub fn permits_ip(&self, ip: IpAddr) -> bool {
+        !self.limits_names() || self.permitted_ips.iter().any(|range| range.contains(ip))
+    }
+
+    /// Refuse names `issuer` may not sign, naming the first one found.
+    pub(crate) fn check(&self, issuer: &str, sans: &[SanType]) -> Result<(), CaError> {
+        for san in sans {
+            let refused = match san {
+                SanType::DnsName(name) if !self.permits_dns(name.as_str()) => {
+                    format!("DNS name {}", name.as_str())
+                }
+                SanType::IpAddress(ip) if !self.permits_ip(*ip) => format!("IP address {ip}"),
+                _ => continue,
+            };
+            return Err(CaError::Signing(format!(
+                "{refused} is outside what '{issuer}' may sign ({})",
+                self.permitted()
+            )));
+        }
+        Ok(())
+    }
+
+    /// The permitted names, for messages.
+    fn permitted(&self) -> String {
+        let names: Vec<String> = self
+            .permitted_dns
+            .iter()
+            .cloned()
+            .chain(self.permitted_ips.iter().map(ToString::to_string))
+            .collect();
+        names.join(", ")
+    }
+
+    /// The name constraints extension, if names are limited.
+    ///
+    /// A name type with nothing permitted is excluded outright, as the
+    /// CA/Browser Forum asks of technically constrained CAs: otherwise
+    /// clients would let it through.
+    pub(crate) fn name_constraints(&self) -> Option<NameConstraints> {
+        if !self.limits_names() {
+            return None;
+        }
+        let mut permitted_subtrees: Vec<GeneralSubtree> = self
+            .permitted_dns
+            .iter()
+            .map(|domain| GeneralSubtree::DnsName(domain.trim_matches('.').to_string()))
+            .collect();
+        permitted_subtrees.extend(
+            self.permitted_ips
+                .iter()
+                .map(|range| GeneralSubtree::IpAddress(range.subnet())),
+        );
+
+        let mut excluded_subtrees = Vec::new();
+        if self.permitted_dns.is_empty() {
+            excluded_subtrees.push(GeneralSubtree::DnsName(String::new()));
+        }
+        if self.permitted_ips.is_empty() {
+            excluded_subtrees.push(GeneralSubtree::IpAddress(CidrSubnet::V4([0; 4], [0; 4])));
+            excluded_subtrees.push(GeneralSubtree::IpAddress(CidrSubnet::V6([0; 16], [0; 16])));
+        }
+        Some(NameConstraints {
+            permitted_subtrees,
+            excluded_subtrees,
+        })
+    }
+
+    /// The constraints written in a CA certificate.
+    pub(crate) fn of_certificate(cert_pem: &str) -> Result<Self, CaError> {
+        let der = pem::parse(cert_pem).map_err(|e| CaError::Pem(e.to_string()))?;
+        let (_, cert) = x509_parser::parse_x509_certificate(der.contents())
+            .map_err(|e| CaError::Parsing(e.to_string()))?;
+
+        let mut constraints = Self::new();
+        if let Some(basic) = cert
+            .basic_constraints()
+            .map_err(|e| CaError::Parsing(e.to_string()))?
+        {
+            // No limit in the certificate is as many as we can count
+            constraints.path_len = basic
+                .value
+                .path_len_constraint
+                .map_or(u8::MAX, |len| len.try_into().unwrap_or(u8::MAX));
+        }
+        let names = cert
+            .name_constraints()
+            .map_err(|e| CaError::Parsing(e.to_string()))?;
+        let subtrees = names
+            .and_then(|ext| ext.value.permitted_subtrees.as_ref())
+            .map_or(&[][..], Vec::as_slice);
+        for subtree in subtrees {
+            match &subtree.base {
+                GeneralName::DNSName(domain) => constraints.permitted_dns.push(domain.to_string()),
+                GeneralName::IPAddress(bytes) => constraints.permitted_ips.push(ip_range(bytes)?),
+                _ => {}
+            }
+        }
+        Ok(constraints)
+    }
+}
+
+/// An address and mask, as name constraints encode IP ranges.
+fn ip_range(bytes: &[u8]) -> Result<IpRange, CaError> {
+    let invalid = || CaError::Parsing("malformed IP range in name constraints".to_string());
+    let (addr, mask) = bytes.split_at(bytes.len() / 2);
+    let addr = match addr.len() {
+        4 => IpAddr::from(<[u8; 4]>::try_from(addr).map_err(|_| invalid())?),
+        16 => IpAddr::from(<[u8; 16]>::try_from(addr).map_err(|_| invalid())?),
+        _ => return Err(invalid()),
+    };
+    let prefix = mask.iter().map(|b| b.leading_ones()).sum::<u32>();
+    IpRange::new(addr, prefix.try_into().map_err(|_| invalid())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ip_range() {
+        let range: IpRange = "10.1.2.3/8".parse().unwrap();
+        assert_eq!(range.to_string(), "10.0.0.0/8");
+        assert!(range.contains("10.255.0.1".parse().unwrap()));
+        assert!(!range.contains("11.0.0.1".parse().unwrap()));
+        assert!(!range.contains("::a00:1".parse().unwrap()));
+
+        let one: IpRange = "192.0.2.1".parse().unwrap();
+        assert_eq!(one.prefix(), 32);
+        assert!(one.contains("192.0.2.1".parse().unwrap()));
+        assert!(!one.contains("192.0.2.2".parse().unwrap()));
+
+        let v6: IpRange = "2001:db8::/32".parse().unwrap();
+        assert!(v6.contains("2001:db8:1::1".parse().unwrap()));
+        assert!(!v6.contains("2001:db9::1".parse().unwrap()));
+        let all: IpRange = "0.0.0.0/0".parse().unwrap();
+        assert!(all.contains("203.0.113.9".parse().unwrap()));
+
+        assert!("10.0.0.0/33".parse::<IpRange>().is_err());
+        assert!("example.com/8".parse::<IpRange>().is_err());
+        let json = serde_json::to_string(&range).unwrap();
+        assert_eq!(json, "\"10.0.0.0/8\"");
+        assert_eq!(serde_json::from_str::<IpRange>(&json).unwrap(), range);
+    }
+
+    #[test]
+    fn test_permits() {
+        let open = CaConstraints::new();
+        assert!(open.permits_dns("anything.example"));
+        assert!(open.permits_ip("192.0.2.1".parse().unwrap()));
+
+        let dns_only = CaConstraints::new().permit_dns("honeypot.example");
+        assert!(dns_only.permits_dns("honeypot.example"));
+        assert!(dns_only.permits_dns("Web.Honeypot.Example."));
+        assert!(dns_only.permits_dns("*.honeypot.example"));
+        assert!(!dns_only.permits_dns("evilhoneypot.example"));
+        assert!(!dns_only.permits_dns("example"));
+        assert!(!dns_only.permits_ip("192.0.2.1".parse().unwrap()));
+
+        let ip_only = CaConstraints::new().permit_ip("192.0.2.0/24".parse().unwrap());
+        assert!(ip_only.permits_ip("192.0.2.200".parse().unwrap()));
+        assert!(!ip_only.permits_ip("198.51.100.1".parse().unwrap()));
+        assert!(!ip_only.permits_dns("honeypot.example"));
+    }
+}
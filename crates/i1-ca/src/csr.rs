@@ -0,0 +1,478 @@
+//! Signing certificate requests.
+//!
+//! When the private key has to stay on the machine that uses it, that
+//! machine sends a PKCS#10 request and [`IntermediateCa::sign_csr`] signs
+//! it. A [`SigningPolicy`] decides what a request may ask for; anything
+//! outside it is refused with the reason, never quietly dropped.
+
+use std::net::IpAddr;
+
+use chrono::{Duration, SubsecRound, Utc};
+use rcgen::{
+    CertificateSigningRequestParams, DnType, DnValue, ExtendedKeyUsagePurpose, IsCa,
+    KeyUsagePurpose, PublicKeyData, SanType,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::keystore::sign_with;
+use crate::revocation::distribution_points;
+use crate::serial::serial_number;
+use crate::{CaError, CertificateInfo, CertificateType, IntermediateCa, KeyAlgorithm};
+
+/// What a certificate signing request may ask for.
+///
+/// The default policy allows no names at all, so every name has to be
+/// allowed explicitly.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SigningPolicy {
+    /// DNS names a certificate may carry; `*.example.com` allows any name
+    /// under `example.com`, including wildcards
+    pub allowed_domains: Vec<String>,
+    /// IP addresses a certificate may carry
+    pub allowed_ips: Vec<IpAddr>,
+    /// Common names to refuse; `*` matches any run of characters
+    pub forbidden_cn_patterns: Vec<String>,
+    /// Validity of signed certificates, in days
+    pub validity_days: u32,
+    /// Longest validity this policy signs for
+    pub max_validity_days: u32,
+    /// Where this CA's CRL is published, for signed certificates to
+    /// carry; the CA's own URL if empty
+    #[serde(default)]
+    pub crl_urls: Vec<String>,
+}
+
+impl Default for SigningPolicy {
+    fn default() -> Self {
+        Self {
+            allowed_domains: Vec::new(),
+            allowed_ips: Vec::new(),
+            forbidden_cn_patterns: Vec::new(),
+            validity_days: 90,
+            max_validity_days: 397, // CA/Browser Forum limit
+            crl_urls: Vec::new(),
+        }
+    }
+}
+
+impl SigningPolicy {
+    /// Allow a DNS name, or every name under a domain as `*.example.com`.
+    pub fn allow_domain(mut self, domain: impl Into<String>) -> Self {
+        self.allowed_domains.push(domain.into());
+        self
+    }
+
+    /// Allow an IP address.
+    pub fn allow_ip(mut self, ip: IpAddr) -> Self {
+        self.allowed_ips.push(ip);
+        self
+    }
+
+    /// Refuse common names matching `pattern`.
+    pub fn forbid_cn(mut self, pattern: impl Into<String>) -> Self {
+        self.forbidden_cn_patterns.push(pattern.into());
+        self
+    }
+
+    /// Set validity period.
+    pub fn validity(mut self, days: u32) -> Self {
+        self.validity_days = days;
+        self
+    }
+
+    /// Set the longest validity allowed.
+    pub fn max_validity(mut self, days: u32) -> Self {
+        self.max_validity_days = days;
+        self
+    }
+
+    /// Add a URL this CA's CRL can be fetched from.
+    pub fn crl_url(mut self, url: impl Into<String>) -> Self {
+        self.crl_urls.push(url.into());
+        self
+    }
+
+    /// Whether a requested DNS name is allowed.
+    fn allows_domain(&self, name: &str) -> bool {
+        let name = name.trim_end_matches('.').to_ascii_lowercase();
+        self.allowed_domains.iter().any(|allowed| {
+            let allowed = allowed.trim_end_matches('.').to_ascii_lowercase();
+            match allowed.strip_prefix("*.") {
+                Some(parent) => name
+                    .strip_suffix(parent)
+                    .is_some_and(|sub| sub.len() > 1 && sub.ends_with('.')),
+                None => name == allowed,
+            }
+        })
+    }
+
+    /// The first forbidden pattern `cn` matches.
+    fn forbidden_pattern(&self, cn: &str) -> Option<&str> {
+        let cn = cn.to_ascii_lowercase();
+        self.forbidden_cn_patterns
+            .iter()
+            .find(|pattern| glob_match(&pattern.to_ascii_lowercase(), &cn))
+            .map(String::as_str)
+    }
+}
+
+/// A certificate signed from a request. The key never left the requester.
+#[derive(Debug, Clone)]
+pub struct SignedCert {
+    /// PEM-encoded certificate
+    pub cert_pem: String,
+    /// Full chain (cert + intermediate + root)
+    pub chain_pem: String,
+    /// Metadata
+    pub info: CertificateInfo,
+}
+
+impl IntermediateCa {
+    /// Sign a PEM-encoded PKCS#10 certificate signing request.
+    ///
+    /// The request's subject, subject alternative names and key usages are
+    /// kept as asked. Every name must be allowed by `policy`, and a request
+    /// for CA powers or for usages beyond TLS is refused. The certificate
+    /// expires after the policy's validity, or with this CA if sooner.
+    pub fn sign_csr(&self, csr_pem: &str, policy: SigningPolicy) -> Result<SignedCert, CaError> {
+        let csr = CertificateSigningRequestParams::from_pem(csr_pem)
+            .map_err(|e| CaError::InvalidCsr(e.to_string()))?;
+        let mut params = csr.params;
+        let algorithm =
+            KeyAlgorithm::of_public_key(csr.public_key.algorithm(), csr.public_key.der_bytes())
+                .ok_or_else(|| CaError::InvalidCsr("unsupported key algorithm".to_string()))?;
+
+        let subject = match params.distinguished_name.get(&DnType::CommonName) {
+            Some(value) => Some(dn_text(value)?),
+            None => None,
+        };
+        if let Some(cn) = &subject {
+            if let Some(pattern) = policy.forbidden_pattern(cn) {
+                return Err(CaError::ForbiddenCommonName {
+                    name: cn.clone(),
+                    pattern: pattern.to_string(),
+                });
+            }
+        }
+
+        if params.subject_alt_names.is_empty() {
+            return Err(CaError::InvalidCsr(
+                "no subject alternative names requested".to_string(),
+            ));
+        }
+        for san in &params.subject_alt_names {
+            match san {
+                SanType::DnsName(name) if policy.allows_domain(name.as_str()) => {}
+                SanType::DnsName(name) => {
+                    return Err(CaError::NameNotAllowed(format!(
+                        "DNS name {}",
+                        name.as_str()
+                    )));
+                }
+                SanType::IpAddress(ip) if policy.allowed_ips.contains(ip) => {}
+                SanType::IpAddress(ip) => {
+                    return Err(CaError::NameNotAllowed(format!("IP address {}", ip)));
+                }
+                other => {
+                    return Err(CaError::NameNotAllowed(format!("{:?}", other)));
+                }
+            }
+        }
+        self.constraints()
+            .check(&self.info.subject, &params.subject_alt_names)?;
+
+        if let Some(usage) = params.key_usages.iter().find(|usage| {
+            matches!(
+                usage,
+                KeyUsagePurpose::KeyCertSign | KeyUsagePurpose::CrlSign
+            )
+        }) {
+            return Err(CaError::UsageNotAllowed(format!("{:?}", usage)));
+        }
+        if let Some(usage) = params.extended_key_usages.iter().find(|usage| {
+            !matches!(
+                usage,
+                ExtendedKeyUsagePurpose::ServerAuth | ExtendedKeyUsagePurpose::ClientAuth
+            )
+        }) {
+            return Err(CaError::UsageNotAllowed(format!("{:?}", usage)));
+        }
+
+        if policy.validity_days > policy.max_validity_days {
+            return Err(CaError::ValidityTooLong {
+                requested: policy.validity_days,
+                max: policy.max_validity_days,
+            });
+        }
+        if policy.validity_days == 0 {
+            return Err(CaError::Signing(
+                "validity must be at least a day".to_string(),
+            ));
+        }
+
+        let now = Utc::now().trunc_subsecs(0);
+        if self.info.not_after <= now {
+            return Err(CaError::Expired);
+        }
+        let not_after =
+            (now + Duration::days(i64::from(policy.validity_days))).min(self.info.not_after);
+
+        // Fill in what the request left to us
+        if params.key_usages.is_empty() {
+            params.key_usages = vec![
+                KeyUsagePurpose::DigitalSignature,
+                KeyUsagePurpose::KeyEncipherment,
+            ];
+        }
+        if params.extended_key_usages.is_empty() {
+            params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ServerAuth];
+        }
+        params.is_ca = IsCa::NoCa;
+        params.crl_distribution_points = distribution_points(&policy.crl_urls, self.crl_url());
+        params.not_before = to_offset(now)?;
+        params.not_after = to_offset(not_after)?;
+
+        let serial = self.next_serial()?;
+        params.serial_number = Some(serial_number(&serial)?);
+
+        let subject = subject.unwrap_or_else(|| san_text(&params.subject_alt_names[0]));
+        let csr = CertificateSigningRequestParams {
+            params,
+            public_key: csr.public_key,
+        };
+        let cert = sign_with(self.signing_key(), |key| {
+            csr.signed_by(self.certificate(), key)
+        })?;
+        let cert_pem = cert.pem();
+
+        Ok(SignedCert {
+            chain_pem: format!("{}\n{}", cert_pem, self.chain_pem()),
+            cert_pem,
+            info: CertificateInfo {
+                id: Uuid::new_v4(),
+                serial,
+                subject,
+                issuer: self.info.subject.clone(),
+                not_before: now,
+                not_after,
+                cert_type: CertificateType::EndEntity,
+                algorithm,
+                revoked: false,
+                revocation_reason: None,
+            },
+        })
+    }
+}
+
+/// The text of a distinguished name value.
+fn dn_text(value: &DnValue) -> Result<String, CaError> {
+    match value {
+        DnValue::Utf8String(s) => Ok(s.clone()),
+        DnValue::PrintableString(s) => Ok(s.as_str().to_string()),
+        DnValue::Ia5String(s) => Ok(s.as_str().to_string()),
+        DnValue::TeletexString(s) => Ok(s.as_str().to_string()),
+        _ => Err(CaError::InvalidCsr(
+            "common name is not a readable string".to_string(),
+        )),
+    }
+}
+
+/// A subject alternative name as text, for a request without a common name.
+fn san_text(san: &SanType) -> String {
+    match san {
+        SanType::DnsName(name) => name.as_str().to_string(),
+        SanType::IpAddress(ip) => ip.to_string(),
+        other => format!("{:?}", other),
+    }
+}
+
+/// Whether `text` matches `pattern`, where `*` matches any run of characters.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == text,
+        Some((prefix, rest)) => {
+            let Some(text) = text.strip_prefix(prefix) else {
+                return false;
+            };
+            (0..=text.len())
+                .filter(|&i| text.is_char_boundary(i))
+                .any(|i| glob_match(rest, &text[i..]))
+        }
+    }
+}
+
+/// A chrono time as rcgen wants it.
+fn to_offset(at: chrono::DateTime<Utc>) -> Result<time::OffsetDateTime, CaError> {
+    time::OffsetDateTime::from_unix_timestamp(at.timestamp())
+        .map_err(|e| CaError::Signing(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{KeyAlgorithm, RootCa};
+    use rcgen::{CertificateParams, DistinguishedName, KeyPair};
+
+    const OPENSSL_EC: &str = include_str!("../tests/fixtures/openssl-ec.csr");
+    const OPENSSL_RSA: &str = include_str!("../tests/fixtures/openssl-rsa.csr");
+    const OPENSSL_CA: &str = include_str!("../tests/fixtures/openssl-ca.csr");
+
+    fn intermediate() -> IntermediateCa {
+        let root = RootCa::generate("Root", KeyAlgorithm::EcdsaP256).unwrap();
+        IntermediateCa::generate("Intermediate", &root, KeyAlgorithm::EcdsaP256).unwrap()
+    }
+
+    fn policy() -> SigningPolicy {
+        SigningPolicy::default()
+            .allow_domain("*.example.com")
+            .allow_ip("10.0.0.5".parse().unwrap())
+            .validity(30)
+    }
+
+    /// What went into a signed certificate, parsed back out.
+    fn parse(cert_pem: &str) -> CertificateParams {
+        CertificateParams::from_ca_cert_pem(cert_pem).unwrap()
+    }
+
+    #[test]
+    fn test_sign_rcgen_csr() {
+        let ca = intermediate();
+        let key = KeyPair::generate().unwrap();
+        let mut params =
+            CertificateParams::new(vec!["app.example.com".to_string(), "10.0.0.5".to_string()])
+                .unwrap();
+        let mut dn = DistinguishedName::new();
+        dn.push(DnType::CommonName, "app.example.com");
+        params.distinguished_name = dn;
+        params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ClientAuth];
+        let csr = params.serialize_request(&key).unwrap().pem().unwrap();
+
+        let signed = ca.sign_csr(&csr, policy()).unwrap();
+        assert_eq!(signed.info.subject, "app.example.com");
+        assert_eq!(signed.info.algorithm, KeyAlgorithm::EcdsaP256);
+        assert_eq!(signed.info.issuer, "Intermediate");
+        assert_eq!(
+            signed.info.not_after - signed.info.not_before,
+            Duration::days(30)
+        );
+        assert_eq!(signed.chain_pem.matches("BEGIN CERTIFICATE").count(), 3);
+
+        let issued = parse(&signed.cert_pem);
+        assert_eq!(issued.subject_alt_names, params.subject_alt_names);
+        assert_eq!(
+            issued.extended_key_usages,
+            vec![ExtendedKeyUsagePurpose::ClientAuth]
+        );
+        assert_eq!(issued.is_ca, IsCa::NoCa);
+
+        // The certificate carries the requester's key, not a new one
+        let cert = pem::parse(&signed.cert_pem).unwrap();
+        let spki = key.public_key_raw();
+        assert!(cert.contents().windows(spki.len()).any(|w| w == spki));
+    }
+
+    #[test]
+    fn test_sign_openssl_csr() {
+        let ca = intermediate();
+
+        let signed = ca.sign_csr(OPENSSL_EC, policy()).unwrap();
+        assert_eq!(signed.info.subject, "api.example.com");
+        let issued = parse(&signed.cert_pem);
+        assert_eq!(
+            issued.subject_alt_names,
+            vec![
+                SanType::DnsName("api.example.com".try_into().unwrap()),
+                SanType::DnsName("www.example.com".try_into().unwrap()),
+                SanType::IpAddress("10.0.0.5".parse().unwrap()),
+            ]
+        );
+        assert!(issued
+            .distinguished_name
+            .get(&DnType::OrganizationName)
+            .is_some());
+
+        let signed = ca.sign_csr(OPENSSL_RSA, policy()).unwrap();
+        assert_eq!(signed.info.algorithm, KeyAlgorithm::Rsa2048);
+        assert_eq!(
+            parse(&signed.cert_pem).extended_key_usages,
+            vec![
+                ExtendedKeyUsagePurpose::ServerAuth,
+                ExtendedKeyUsagePurpose::ClientAuth,
+            ]
+        );
+
+        // A request for a CA certificate is refused, not signed as a leaf
+        assert!(matches!(
+            ca.sign_csr(OPENSSL_CA, policy()),
+            Err(CaError::InvalidCsr(_))
+        ));
+        assert!(matches!(
+            ca.sign_csr("not a request", policy()),
+            Err(CaError::InvalidCsr(_))
+        ));
+    }
+
+    #[test]
+    fn test_policy_rejection() {
+        let ca = intermediate();
+
+        let narrow = SigningPolicy::default().allow_domain("api.example.com");
+        match ca.sign_csr(OPENSSL_EC, narrow) {
+            Err(CaError::NameNotAllowed(name)) => assert_eq!(name, "DNS name www.example.com"),
+            other => panic!("expected NameNotAllowed, got {:?}", other),
+        }
+
+        let no_ips = SigningPolicy::default().allow_domain("*.example.com");
+        match ca.sign_csr(OPENSSL_EC, no_ips) {
+            Err(CaError::NameNotAllowed(name)) => assert_eq!(name, "IP address 10.0.0.5"),
+            other => panic!("expected NameNotAllowed, got {:?}", other),
+        }
+
+        // `*.example.com` does not cover example.com itself
+        let key = KeyPair::generate().unwrap();
+        let bare = CertificateParams::new(vec!["example.com".to_string()])
+            .unwrap()
+            .serialize_request(&key)
+            .unwrap()
+            .pem()
+            .unwrap();
+        assert!(matches!(
+            ca.sign_csr(&bare, policy()),
+            Err(CaError::NameNotAllowed(_))
+        ));
+
+        match ca.sign_csr(OPENSSL_EC, policy().forbid_cn("API.*")) {
+            Err(CaError::ForbiddenCommonName { name, pattern }) => {
+                assert_eq!(name, "api.example.com");
+                assert_eq!(pattern, "API.*");
+            }
+            other => panic!("expected ForbiddenCommonName, got {:?}", other),
+        }
+
+        assert!(matches!(
+            ca.sign_csr(OPENSSL_EC, policy().validity(400)),
+            Err(CaError::ValidityTooLong {
+                requested: 400,
+                max: 397
+            })
+        ));
+
+        let mut params = CertificateParams::new(vec!["code.example.com".to_string()]).unwrap();
+        params.extended_key_usages = vec![ExtendedKeyUsagePurpose::CodeSigning];
+        let csr = params.serialize_request(&key).unwrap().pem().unwrap();
+        assert!(matches!(
+            ca.sign_csr(&csr, policy()),
+            Err(CaError::UsageNotAllowed(_))
+        ));
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*admin*", "root-admin-01"));
+        assert!(glob_match("*.internal", "db.internal"));
+        assert!(!glob_match("*.internal", "internal"));
+        assert!(glob_match("exact", "exact"));
+        assert!(!glob_match("exact", "exactly"));
+    }
+}
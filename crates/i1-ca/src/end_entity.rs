@@ -7,7 +7,7 @@ use chrono::{Duration, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::{CertificateInfo, CertificateType};
+use crate::{CertificateInfo, CertificateType, KeyAlgorithm};
 
 /// A signed end-entity certificate with its key.
 #[derive(Debug, Clone)]
@@ -25,7 +25,7 @@ pub struct EndEntityCert {
 /// Request for a new certificate.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CertificateRequest {
-    /// Domain name(s) for the certificate
+    /// Domain name(s) or IP addresses for the certificate; the first is the subject
     pub domains: Vec<String>,
     /// Validity in days (default: 1)
     pub validity_days: u32,
@@ -78,23 +78,25 @@ impl CertificateRequest {
 
 impl EndEntityCert {
     /// Create cert info for tracking.
-    #[allow(dead_code)] // Future use when IntermediateCa returns EndEntityCert
     pub(crate) fn create_info(
         domains: &[String],
         issuer: &str,
         validity_days: u32,
+        algorithm: KeyAlgorithm,
+        serial: String,
     ) -> CertificateInfo {
         let now = Utc::now();
         let subject = domains.first().cloned().unwrap_or_default();
 
         CertificateInfo {
             id: Uuid::new_v4(),
-            serial: format!("{:032x}", Uuid::new_v4().as_u128()),
+            serial,
             subject,
             issuer: issuer.to_string(),
             not_before: now,
             not_after: now + Duration::days(validity_days as i64),
             cert_type: CertificateType::EndEntity,
+            algorithm,
             revoked: false,
             revocation_reason: None,
         }
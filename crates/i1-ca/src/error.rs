@@ -33,6 +33,44 @@ pub enum CaError {
     #[error("Certificate not yet valid")]
     NotYetValid,
 
+    /// A certificate signing request could not be parsed or verified.
+    #[error("Invalid certificate signing request: {0}")]
+    InvalidCsr(String),
+
+    /// A requested name is not allowed by the signing policy.
+    #[error("Name not allowed by signing policy: {0}")]
+    NameNotAllowed(String),
+
+    /// The requested common name matches a forbidden pattern.
+    #[error("Common name {name:?} matches forbidden pattern {pattern:?}")]
+    ForbiddenCommonName {
+        /// Common name in the request
+        name: String,
+        /// Pattern it matched
+        pattern: String,
+    },
+
+    /// The requested key usage is not allowed by the signing policy.
+    #[error("Key usage not allowed by signing policy: {0}")]
+    UsageNotAllowed(String),
+
+    /// The validity asked for is longer than the signing policy allows.
+    #[error("Validity of {requested} days exceeds the policy maximum of {max} days")]
+    ValidityTooLong {
+        /// Days asked for
+        requested: u32,
+        /// Most days allowed
+        max: u32,
+    },
+
+    /// A signing key could not be loaded, unlocked or saved.
+    #[error("Key store error: {0}")]
+    KeyStore(String),
+
+    /// The certificate store could not be read, or refused a change.
+    #[error("Certificate store error: {0}")]
+    Store(String),
+
     /// IO error.
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
@@ -9,74 +9,105 @@ use rcgen::{
     ExtendedKeyUsagePurpose, IsCa, KeyPair, KeyUsagePurpose,
 };
 use std::path::Path;
+use std::sync::{Arc, Mutex, PoisonError};
 use uuid::Uuid;
 
-use crate::{CaError, CertificateInfo, CertificateType, IntermediatePurpose, KeyAlgorithm};
+use crate::keystore::{raw_public_key, sign_with};
+use crate::revocation::distribution_points;
+use crate::serial::serial_number;
+use crate::{
+    CaConstraints, CaError, CertificateInfo, CertificateRequest, CertificateType, EndEntityCert,
+    FileKeyStore, IntermediatePurpose, KeyAlgorithm, SerialRegistry, SigningKeyStore,
+};
 
 /// Intermediate Certificate Authority.
 ///
 /// Lives online, signs end-entity certificates.
 /// Can be revoked if compromised without touching root.
 pub struct IntermediateCa {
-    /// Key pair for signing
-    key_pair: KeyPair,
+    /// Key to sign with
+    signing_key: Arc<dyn SigningKeyStore>,
     /// The signed certificate
     certificate: Certificate,
     /// Certificate chain (intermediate + root)
     chain_pem: String,
-    /// Private key
-    key_pem: String,
+    /// Private key, unless it is kept outside, e.g. by an external signer
+    key_pem: Option<String>,
     /// Metadata
     pub info: CertificateInfo,
     /// Purpose of this intermediate (for patient zero tracking)
     pub purpose: IntermediatePurpose,
+    /// What it may sign, as written in its certificate
+    constraints: CaConstraints,
+    /// Serials issued
+    serials: Mutex<SerialRegistry>,
+    /// Where this CA's CRL is published
+    crl_url: Option<String>,
 }
 
 impl IntermediateCa {
-    /// Create a new intermediate CA signed by the root.
+    /// Create a new intermediate CA signed by the root, with a key of
+    /// `algorithm`.
     pub fn generate(
         name: &str,
         root: &crate::RootCa,
-        _algorithm: KeyAlgorithm,
+        algorithm: KeyAlgorithm,
     ) -> Result<Self, CaError> {
-        Self::generate_with_purpose(name, root, IntermediatePurpose::General)
+        Self::build(
+            name,
+            root,
+            IntermediatePurpose::General,
+            algorithm,
+            CaConstraints::new(),
+        )
     }
 
     /// Create a purpose-specific intermediate CA.
     ///
     /// This is key for patient zero tracking - each user/session/region
     /// gets their own intermediate, so when something goes wrong, you
-    /// know exactly where to look.
+    /// know exactly where to look. The key is the same kind as the root's.
     pub fn generate_with_purpose(
         name: &str,
         root: &crate::RootCa,
         purpose: IntermediatePurpose,
     ) -> Result<Self, CaError> {
-        let key_pair = KeyPair::generate()?;
-        let key_pem = key_pair.serialize_pem();
-
-        let mut params = CertificateParams::default();
-
-        // Distinguished Name
-        let mut dn = DistinguishedName::new();
-        dn.push(DnType::CommonName, name);
-        dn.push(DnType::OrganizationName, "i1.is");
-        dn.push(DnType::CountryName, "IS");
-        params.distinguished_name = dn;
+        Self::build(
+            name,
+            root,
+            purpose,
+            root.info.algorithm,
+            CaConstraints::new(),
+        )
+    }
 
-        // Intermediate CA - can sign end-entity only (path length = 0)
-        params.is_ca = IsCa::Ca(BasicConstraints::Constrained(0));
+    /// Create an intermediate CA limited to what `constraints` allow.
+    ///
+    /// The path length and permitted names go into the certificate, for
+    /// clients to enforce, and this CA refuses to sign anything outside
+    /// them. The key is the same kind as the root's.
+    pub fn generate_constrained(
+        name: &str,
+        root: &crate::RootCa,
+        purpose: IntermediatePurpose,
+        constraints: CaConstraints,
+    ) -> Result<Self, CaError> {
+        Self::build(name, root, purpose, root.info.algorithm, constraints)
+    }
 
-        params.key_usages = vec![
-            KeyUsagePurpose::KeyCertSign,
-            KeyUsagePurpose::CrlSign,
-            KeyUsagePurpose::DigitalSignature,
-        ];
+    fn build(
+        name: &str,
+        root: &crate::RootCa,
+        purpose: IntermediatePurpose,
+        algorithm: KeyAlgorithm,
+        constraints: CaConstraints,
+    ) -> Result<Self, CaError> {
+        let key_pair = algorithm.generate_key()?;
+        let key_pem = key_pair.serialize_pem();
+        let signing_key = Arc::new(FileKeyStore::from_key_pair(&key_pair)?);
 
-        params.extended_key_usages = vec![
-            ExtendedKeyUsagePurpose::ServerAuth,
-            ExtendedKeyUsagePurpose::ClientAuth,
-        ];
+        let mut params = Self::params(name, &constraints);
+        params.crl_distribution_points = distribution_points(&[], root.crl_url());
 
         // Validity based on purpose
         let validity = purpose.validity();
@@ -85,35 +116,177 @@ impl IntermediateCa {
         params.not_after = time::OffsetDateTime::now_utc()
             + time::Duration::days(validity.days() as i64);
 
-        let serial = Uuid::new_v4();
-        params.serial_number = Some((serial.as_u128() as u64).into());
+        let serial = root.next_serial()?;
+        params.serial_number = Some(serial_number(&serial)?);
 
-        let certificate = params.signed_by(&key_pair, root.certificate(), root.key_pair())?;
+        let certificate = sign_with(root.signing_key(), |root_key| {
+            params.signed_by(&key_pair, root.certificate(), root_key)
+        })?;
         let cert_pem = certificate.pem();
         let chain_pem = format!("{}\n{}", cert_pem, root.certificate_pem());
 
         let info = CertificateInfo {
             id: Uuid::new_v4(),
-            serial: format!("{:016x}", serial.as_u128() as u64),
+            serial,
             subject: name.to_string(),
             issuer: root.info.subject.clone(),
             not_before: now,
             not_after: now + Duration::days(validity.days() as i64),
             cert_type: CertificateType::Intermediate,
+            algorithm,
             revoked: false,
             revocation_reason: None,
         };
 
         Ok(Self {
-            key_pair,
+            signing_key,
             certificate,
             chain_pem,
-            key_pem,
+            key_pem: Some(key_pem),
             info,
             purpose,
+            constraints,
+            serials: Mutex::new(SerialRegistry::new()),
+            crl_url: None,
+        })
+    }
+
+    /// Load an intermediate CA saved with [`IntermediateCa::save_to_files`].
+    ///
+    /// As with [`crate::RootCa::from_pem`], the subject comes from `info`
+    /// and the chain is kept as it was. Constraints are read from the
+    /// certificate, so a reloaded CA keeps to them.
+    pub fn from_pem(
+        key_pem: &str,
+        chain_pem: &str,
+        info: CertificateInfo,
+        purpose: IntermediatePurpose,
+    ) -> Result<Self, CaError> {
+        let signing_key = FileKeyStore::from_pem(key_pem, None)?;
+        let ca = Self::from_key_store(signing_key, chain_pem, info, purpose)?;
+        Ok(Self {
+            key_pem: Some(key_pem.to_string()),
+            ..ca
         })
     }
 
+    /// Load an intermediate CA whose key is kept by `signing_key`, such as
+    /// a [`crate::FileKeyStore`] opened with its passphrase or an
+    /// [`crate::ExternalSigner`] for a hardware token.
+    ///
+    /// The key must be the one in the first certificate of `chain_pem`.
+    /// Otherwise this is [`IntermediateCa::from_pem`], but with no key PEM
+    /// to hand out or save.
+    pub fn from_key_store(
+        signing_key: impl SigningKeyStore + 'static,
+        chain_pem: &str,
+        info: CertificateInfo,
+        purpose: IntermediatePurpose,
+    ) -> Result<Self, CaError> {
+        if !chain_pem.contains("BEGIN CERTIFICATE") {
+            return Err(CaError::Pem("no certificate found".to_string()));
+        }
+        let der = pem::parse(chain_pem).map_err(|e| CaError::Pem(e.to_string()))?;
+        let (_, cert) = x509_parser::parse_x509_certificate(der.contents())
+            .map_err(|e| CaError::Parsing(e.to_string()))?;
+        if raw_public_key(signing_key.public_key_der())? != cert.public_key().subject_public_key.data.as_ref() {
+            return Err(CaError::KeyStore(format!(
+                "the signing key is not the one certified for {}",
+                info.subject
+            )));
+        }
+        let constraints = CaConstraints::of_certificate(chain_pem)?;
+        // Only the subject and key usages of this stand-in are used when
+        // signing, so any key will do, and an external signer isn't asked
+        let certificate =
+            Self::params(&info.subject, &constraints).self_signed(&KeyPair::generate()?)?;
+
+        Ok(Self {
+            signing_key: Arc::new(signing_key),
+            certificate,
+            chain_pem: chain_pem.to_string(),
+            key_pem: None,
+            info,
+            purpose,
+            constraints,
+            serials: Mutex::new(SerialRegistry::new()),
+            crl_url: None,
+        })
+    }
+
+    /// Restore the serials this CA issued before it was saved, so that
+    /// [`IntermediateCa::from_pem`] does not forget them.
+    pub fn with_serials(self, serials: SerialRegistry) -> Self {
+        Self {
+            serials: Mutex::new(serials),
+            ..self
+        }
+    }
+
+    /// The serials this CA has issued, to save along with it.
+    pub fn serials(&self) -> SerialRegistry {
+        self.serials
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Point the certificates this CA signs at where its CRL is
+    /// published, as in [`crate::RevocationListConfig::distribution_url`].
+    pub fn with_crl_url(self, url: Option<String>) -> Self {
+        Self {
+            crl_url: url,
+            ..self
+        }
+    }
+
+    /// Where this CA's CRL is published, if anywhere.
+    pub fn crl_url(&self) -> Option<&str> {
+        self.crl_url.as_deref()
+    }
+
+    /// A new serial for a certificate this CA signs.
+    pub(crate) fn next_serial(&self) -> Result<String, CaError> {
+        self.serials
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .allocate()
+    }
+
+    /// What this CA may sign.
+    pub fn constraints(&self) -> &CaConstraints {
+        &self.constraints
+    }
+
+    /// Subject and CA constraints, without validity or serial.
+    fn params(name: &str, constraints: &CaConstraints) -> CertificateParams {
+        let mut params = CertificateParams::default();
+
+        // Distinguished Name
+        let mut dn = DistinguishedName::new();
+        dn.push(DnType::CommonName, name);
+        dn.push(DnType::OrganizationName, "i1.is");
+        dn.push(DnType::CountryName, "IS");
+        params.distinguished_name = dn;
+
+        // Intermediate CA - by default can sign end-entity only (path length = 0)
+        params.is_ca = IsCa::Ca(BasicConstraints::Constrained(constraints.path_len));
+        params.name_constraints = constraints.name_constraints();
+
+        params.key_usages = vec![
+            KeyUsagePurpose::KeyCertSign,
+            KeyUsagePurpose::CrlSign,
+            KeyUsagePurpose::DigitalSignature,
+        ];
+
+        params.extended_key_usages = vec![
+            ExtendedKeyUsagePurpose::ServerAuth,
+            ExtendedKeyUsagePurpose::ClientAuth,
+        ];
+
+        params
+    }
+
     /// Create a per-user intermediate CA.
     ///
     /// Each user gets their own CA. If their session is compromised,
@@ -155,32 +328,56 @@ impl IntermediateCa {
         &self.chain_pem
     }
 
-    /// Get the private key PEM.
-    pub fn private_key_pem(&self) -> &str {
-        &self.key_pem
+    /// Get the private key PEM, if this CA holds its key rather than a
+    /// key store doing so.
+    pub fn private_key_pem(&self) -> Option<&str> {
+        self.key_pem.as_deref()
     }
 
-    /// Save to files.
+    /// Save to files. The key is only readable by its owner.
+    ///
+    /// A CA loaded with [`IntermediateCa::from_key_store`] has no key to
+    /// save, and fails.
     pub fn save_to_files(
         &self,
         key_path: impl AsRef<Path>,
         chain_path: impl AsRef<Path>,
     ) -> Result<(), CaError> {
-        std::fs::write(key_path, &self.key_pem)?;
+        let key_pem = self.key_pem.as_deref().ok_or_else(|| {
+            CaError::KeyStore(format!("the key of {} is kept by its key store", self.info.subject))
+        })?;
+        crate::write_key(key_path, key_pem)?;
         std::fs::write(chain_path, &self.chain_pem)?;
         Ok(())
     }
 
     /// Sign an end-entity certificate for a domain.
     pub fn sign_domain(&self, domain: &str, validity_days: u32) -> Result<(String, String), CaError> {
-        // Generate key for end-entity
-        let end_key = KeyPair::generate()?;
+        let cert = self.issue(&CertificateRequest::for_domain(domain).validity(validity_days))?;
+        Ok((cert.cert_pem, cert.key_pem))
+    }
+
+    /// Sign an end-entity certificate for every name in the request.
+    ///
+    /// The first name is the subject; all of them go in the subject
+    /// alternative names, where IP addresses become IP entries.
+    pub fn issue(&self, request: &CertificateRequest) -> Result<EndEntityCert, CaError> {
+        let Some(subject) = request.domains.first() else {
+            return Err(CaError::Signing(
+                "no domain names or addresses requested".to_string(),
+            ));
+        };
+
+        // Generate key for end-entity, the same kind as ours
+        let end_key = self.info.algorithm.generate_key()?;
         let end_key_pem = end_key.serialize_pem();
 
-        let mut params = CertificateParams::new(vec![domain.to_string()])?;
+        let mut params = CertificateParams::new(request.domains.clone())?;
+        self.constraints
+            .check(&self.info.subject, &params.subject_alt_names)?;
 
         let mut dn = DistinguishedName::new();
-        dn.push(DnType::CommonName, domain);
+        dn.push(DnType::CommonName, subject.as_str());
         params.distinguished_name = dn;
 
         params.is_ca = IsCa::NoCa;
@@ -189,19 +386,33 @@ impl IntermediateCa {
             KeyUsagePurpose::KeyEncipherment,
         ];
         params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ServerAuth];
+        params.crl_distribution_points = distribution_points(&[], self.crl_url());
 
         params.not_before = time::OffsetDateTime::now_utc();
-        params.not_after =
-            time::OffsetDateTime::now_utc() + time::Duration::days(validity_days as i64);
+        params.not_after = time::OffsetDateTime::now_utc()
+            + time::Duration::days(request.validity_days as i64);
 
-        let serial = Uuid::new_v4();
-        params.serial_number = Some((serial.as_u128() as u64).into());
+        let serial = self.next_serial()?;
+        params.serial_number = Some(serial_number(&serial)?);
 
         // Sign with intermediate
-        let cert = params.signed_by(&end_key, &self.certificate, &self.key_pair)?;
+        let cert = sign_with(&self.signing_key, |key| {
+            params.signed_by(&end_key, &self.certificate, key)
+        })?;
         let cert_pem = cert.pem();
 
-        Ok((cert_pem, end_key_pem))
+        Ok(EndEntityCert {
+            chain_pem: format!("{}\n{}", cert_pem, self.chain_pem),
+            cert_pem,
+            key_pem: end_key_pem,
+            info: EndEntityCert::create_info(
+                &request.domains,
+                &self.info.subject,
+                request.validity_days,
+                self.info.algorithm,
+                serial,
+            ),
+        })
     }
 
     /// Sign a wildcard certificate.
@@ -215,9 +426,9 @@ impl IntermediateCa {
         &self.certificate
     }
 
-    /// Get the key pair.
-    pub fn key_pair(&self) -> &KeyPair {
-        &self.key_pair
+    /// Get the key to sign with.
+    pub fn signing_key(&self) -> &Arc<dyn SigningKeyStore> {
+        &self.signing_key
     }
 }
 
@@ -248,6 +459,43 @@ mod tests {
         assert!(key_pem.contains("PRIVATE KEY"));
     }
 
+    #[test]
+    fn test_issue() {
+        let root = RootCa::generate("Root", KeyAlgorithm::EcdsaP256).unwrap();
+        let intermediate =
+            IntermediateCa::generate("Intermediate", &root, KeyAlgorithm::EcdsaP256).unwrap();
+
+        let request = CertificateRequest::for_domain("host.example")
+            .add_domain("192.0.2.1")
+            .validity(30);
+        let cert = intermediate.issue(&request).unwrap();
+
+        assert_eq!(cert.info.subject, "host.example");
+        assert_eq!(cert.info.issuer, "Intermediate");
+        assert_eq!(cert.info.serial.len(), 32);
+        assert!(intermediate.serials().contains(&cert.info.serial));
+        assert_eq!(cert.chain_pem.matches("BEGIN CERTIFICATE").count(), 3);
+        assert!(intermediate.issue(&CertificateRequest::default()).is_err());
+    }
+
+    #[test]
+    fn test_intermediate_from_pem() {
+        let root = RootCa::generate("Root", KeyAlgorithm::EcdsaP256).unwrap();
+        let intermediate =
+            IntermediateCa::generate("Intermediate", &root, KeyAlgorithm::EcdsaP256).unwrap();
+
+        let loaded = IntermediateCa::from_pem(
+            intermediate.private_key_pem().unwrap(),
+            intermediate.chain_pem(),
+            intermediate.info.clone(),
+            IntermediatePurpose::General,
+        )
+        .unwrap();
+
+        assert_eq!(loaded.chain_pem(), intermediate.chain_pem());
+        assert!(loaded.sign_domain("example.com", 1).is_ok());
+    }
+
     #[test]
     fn test_patient_zero_tracking() {
         // Simulate: Root CA in vault, user-specific intermediates online
@@ -292,4 +540,114 @@ mod tests {
         assert!(matches!(honeypot.purpose, IntermediatePurpose::Honeypot));
         assert!(honeypot.info.subject.contains("Honeypot"));
     }
+
+    #[test]
+    fn test_constrained() {
+        use crate::{LeafCertificate, LeafParams, SigningPolicy};
+        use x509_parser::extensions::GeneralName;
+
+        let root = RootCa::generate("Root", KeyAlgorithm::EcdsaP256).unwrap();
+        let constraints = CaConstraints::new()
+            .path_len(1)
+            .permit_dns("honeypot.example")
+            .permit_ip("192.0.2.0/24".parse().unwrap());
+        let intermediate = IntermediateCa::generate_constrained(
+            "Honeypot Issuer",
+            &root,
+            IntermediatePurpose::Honeypot,
+            constraints.clone(),
+        )
+        .unwrap();
+
+        // The limits are in the certificate for clients to enforce
+        let der = pem::parse(intermediate.chain_pem()).unwrap();
+        let (_, cert) = x509_parser::parse_x509_certificate(der.contents()).unwrap();
+        let basic = cert.basic_constraints().unwrap().unwrap().value;
+        assert_eq!(basic.path_len_constraint, Some(1));
+        let names = cert.name_constraints().unwrap().unwrap();
+        assert!(names.critical);
+        let permitted: Vec<_> = names
+            .value
+            .permitted_subtrees
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|s| &s.base)
+            .collect();
+        assert!(matches!(
+            permitted[0],
+            GeneralName::DNSName("honeypot.example")
+        ));
+        assert!(matches!(
+            permitted[1],
+            GeneralName::IPAddress(&[192, 0, 2, 0, 255, 255, 255, 0])
+        ));
+        assert!(names.value.excluded_subtrees.is_none());
+
+        // In scope is signed, out of scope refused before signing
+        let leaf = LeafCertificate::issue(
+            &intermediate,
+            &LeafParams::new("web")
+                .dns("web.honeypot.example")
+                .ip("192.0.2.7".parse().unwrap()),
+        )
+        .unwrap();
+        assert_eq!(leaf.info.issuer, "Honeypot Issuer");
+        let issued = intermediate.serials().len();
+
+        let err =
+            LeafCertificate::issue(&intermediate, &LeafParams::new("bank").dns("bank.example"))
+                .unwrap_err();
+        assert!(matches!(&err, CaError::Signing(msg) if msg.contains("bank.example")));
+        let err = LeafCertificate::issue(
+            &intermediate,
+            &LeafParams::new("web")
+                .dns("web.honeypot.example")
+                .ip("198.51.100.1".parse().unwrap()),
+        )
+        .unwrap_err();
+        assert!(matches!(&err, CaError::Signing(msg) if msg.contains("198.51.100.1")));
+        assert!(matches!(
+            intermediate.issue(&CertificateRequest::for_domain("evilhoneypot.example")),
+            Err(CaError::Signing(_))
+        ));
+
+        // A signing policy can't widen what the CA may sign
+        let key = KeyPair::generate().unwrap();
+        let csr = CertificateParams::new(vec!["bank.example".to_string()])
+            .unwrap()
+            .serialize_request(&key)
+            .unwrap()
+            .pem()
+            .unwrap();
+        let policy = SigningPolicy::default().allow_domain("bank.example");
+        assert!(matches!(
+            intermediate.sign_csr(&csr, policy),
+            Err(CaError::Signing(_))
+        ));
+        assert_eq!(intermediate.serials().len(), issued);
+
+        // Reloaded, it still keeps to what its certificate says
+        let loaded = IntermediateCa::from_pem(
+            intermediate.private_key_pem().unwrap(),
+            intermediate.chain_pem(),
+            intermediate.info.clone(),
+            IntermediatePurpose::Honeypot,
+        )
+        .unwrap();
+        assert_eq!(loaded.constraints(), &constraints);
+        assert!(loaded
+            .issue(&CertificateRequest::for_domain("bank.example"))
+            .is_err());
+        assert!(loaded
+            .issue(&CertificateRequest::for_domain("a.honeypot.example"))
+            .is_ok());
+
+        // An unconstrained intermediate carries no name constraints
+        let open = IntermediateCa::generate("Open", &root, KeyAlgorithm::EcdsaP256).unwrap();
+        let der = pem::parse(open.chain_pem()).unwrap();
+        let (_, cert) = x509_parser::parse_x509_certificate(der.contents()).unwrap();
+        assert!(cert.name_constraints().unwrap().is_none());
+        assert_eq!(open.constraints(), &CaConstraints::new());
+    }
 }
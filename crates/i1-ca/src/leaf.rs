@@ -0,0 +1,606 @@
+//! Leaf certificates for servers and clients.
+//!
+//! [`LeafCertificate::issue`] signs a certificate from the root or an
+//! intermediate, for a server, a client or both, with its DNS names and IP
+//! addresses as subject alternative names. A leaf never outlives its issuer.
+
+use std::net::IpAddr;
+
+use chrono::{Duration, SubsecRound, Utc};
+use rcgen::{
+    Certificate, CertificateParams, DistinguishedName, DnType, ExtendedKeyUsagePurpose, IsCa,
+    KeyUsagePurpose, SanType,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::keystore::sign_with;
+use crate::revocation::distribution_points;
+use crate::serial::serial_number;
+use crate::{
+    CaConstraints, CaError, CertificateInfo, CertificateType, IntermediateCa, KeyAlgorithm, RootCa,
+    SigningKeyStore,
+};
+
+/// A CA that can sign leaf certificates: the root or an intermediate.
+pub trait Issuer {
+    /// Certificate to sign with
+    fn certificate(&self) -> &Certificate;
+
+    /// Key to sign with
+    fn signing_key(&self) -> &Arc<dyn SigningKeyStore>;
+
+    /// The issuer's metadata, for its name and expiry
+    fn info(&self) -> &CertificateInfo;
+
+    /// PEM of the CA certificates a peer needs between a leaf and the root
+    fn intermediates_pem(&self) -> String;
+
+    /// A new serial, recorded as issued
+    fn next_serial(&self) -> Result<String, CaError>;
+
+    /// What the issuer may sign, if it is limited
+    fn constraints(&self) -> Option<&CaConstraints>;
+
+    /// Where the issuer's CRL is published, if anywhere
+    fn crl_url(&self) -> Option<&str>;
+}
+
+impl Issuer for RootCa {
+    fn certificate(&self) -> &Certificate {
+        self.certificate()
+    }
+
+    fn signing_key(&self) -> &Arc<dyn SigningKeyStore> {
+        self.signing_key()
+    }
+
+    fn info(&self) -> &CertificateInfo {
+        &self.info
+    }
+
+    fn intermediates_pem(&self) -> String {
+        // The root is the trust anchor, never sent
+        String::new()
+    }
+
+    fn next_serial(&self) -> Result<String, CaError> {
+        self.next_serial()
+    }
+
+    fn constraints(&self) -> Option<&CaConstraints> {
+        None
+    }
+
+    fn crl_url(&self) -> Option<&str> {
+        self.crl_url()
+    }
+}
+
+impl Issuer for IntermediateCa {
+    fn certificate(&self) -> &Certificate {
+        self.certificate()
+    }
+
+    fn signing_key(&self) -> &Arc<dyn SigningKeyStore> {
+        self.signing_key()
+    }
+
+    fn info(&self) -> &CertificateInfo {
+        &self.info
+    }
+
+    fn intermediates_pem(&self) -> String {
+        // The chain is this certificate, then the root
+        const END: &str = "-----END CERTIFICATE-----";
+        let chain = self.chain_pem().trim_start();
+        chain.find(END).map_or_else(String::new, |end| {
+            format!("{}\n", &chain[..end + END.len()])
+        })
+    }
+
+    fn next_serial(&self) -> Result<String, CaError> {
+        self.next_serial()
+    }
+
+    fn constraints(&self) -> Option<&CaConstraints> {
+        Some(self.constraints())
+    }
+
+    fn crl_url(&self) -> Option<&str> {
+        self.crl_url()
+    }
+}
+
+/// What a leaf certificate may be used for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum LeafUsage {
+    /// TLS server authentication
+    #[default]
+    Server,
+    /// TLS client authentication
+    Client,
+    /// Both, e.g. for mutual TLS between services
+    Both,
+}
+
+impl LeafUsage {
+    fn extended_key_usages(self) -> Vec<ExtendedKeyUsagePurpose> {
+        match self {
+            LeafUsage::Server => vec![ExtendedKeyUsagePurpose::ServerAuth],
+            LeafUsage::Client => vec![ExtendedKeyUsagePurpose::ClientAuth],
+            LeafUsage::Both => vec![
+                ExtendedKeyUsagePurpose::ServerAuth,
+                ExtendedKeyUsagePurpose::ClientAuth,
+            ],
+        }
+    }
+}
+
+/// What to put in a leaf certificate.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LeafParams {
+    /// Subject common name
+    pub common_name: String,
+    /// DNS names the certificate is good for
+    pub dns_sans: Vec<String>,
+    /// IP addresses the certificate is good for
+    pub ip_sans: Vec<IpAddr>,
+    /// Validity in days, cut short at the issuer's expiry
+    pub validity_days: u32,
+    /// What the certificate may be used for
+    pub usage: LeafUsage,
+    /// Where the issuer's CRL is published; the issuer's own URL if empty
+    #[serde(default)]
+    pub crl_urls: Vec<String>,
+    /// Key algorithm; the issuer's if unset
+    #[serde(default)]
+    pub algorithm: Option<KeyAlgorithm>,
+}
+
+impl LeafParams {
+    /// A server certificate for `common_name`, valid for a day, with no
+    /// subject alternative names yet.
+    pub fn new(common_name: impl Into<String>) -> Self {
+        Self {
+            common_name: common_name.into(),
+            dns_sans: Vec::new(),
+            ip_sans: Vec::new(),
+            validity_days: 1,
+            usage: LeafUsage::Server,
+            crl_urls: Vec::new(),
+            algorithm: None,
+        }
+    }
+
+    /// A client certificate for `identity`, a user or API token id, which
+    /// becomes its subject. It needs no subject alternative names.
+    pub fn client(identity: impl Into<String>) -> Self {
+        Self {
+            usage: LeafUsage::Client,
+            ..Self::new(identity)
+        }
+    }
+
+    /// Add a DNS name.
+    pub fn dns(mut self, name: impl Into<String>) -> Self {
+        self.dns_sans.push(name.into());
+        self
+    }
+
+    /// Add an IP address.
+    pub fn ip(mut self, ip: IpAddr) -> Self {
+        self.ip_sans.push(ip);
+        self
+    }
+
+    /// Set validity period.
+    pub fn validity(mut self, days: u32) -> Self {
+        self.validity_days = days;
+        self
+    }
+
+    /// Set what the certificate may be used for.
+    pub fn usage(mut self, usage: LeafUsage) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    /// Set the key algorithm.
+    pub fn algorithm(mut self, algorithm: KeyAlgorithm) -> Self {
+        self.algorithm = Some(algorithm);
+        self
+    }
+
+    /// Add a URL the issuer's CRL can be fetched from.
+    pub fn crl_url(mut self, url: impl Into<String>) -> Self {
+        self.crl_urls.push(url.into());
+        self
+    }
+}
+
+/// A signed leaf certificate with its key.
+#[derive(Debug, Clone)]
+pub struct LeafCertificate {
+    /// PEM-encoded certificate
+    pub cert_pem: String,
+    /// PEM-encoded private key
+    pub key_pem: String,
+    /// The certificate and any intermediate above it, as a server presents
+    /// them; the root is left out
+    pub chain_pem: String,
+    /// What the certificate may be used for
+    pub usage: LeafUsage,
+    /// Metadata
+    pub info: CertificateInfo,
+}
+
+impl LeafCertificate {
+    /// Sign a leaf certificate with `issuer`.
+    ///
+    /// Every DNS name and IP address in `params` goes in the subject
+    /// alternative names, and a server certificate needs at least one. The
+    /// certificate expires after `validity_days`, or with the issuer if that
+    /// is sooner.
+    pub fn issue(issuer: &impl Issuer, params: &LeafParams) -> Result<Self, CaError> {
+        let sans = !params.dns_sans.is_empty() || !params.ip_sans.is_empty();
+        if !sans && params.usage != LeafUsage::Client {
+            return Err(CaError::Signing(
+                "no DNS names or IP addresses requested".to_string(),
+            ));
+        }
+        if params.validity_days == 0 {
+            return Err(CaError::Signing(
+                "validity must be at least a day".to_string(),
+            ));
+        }
+
+        let now = Utc::now().trunc_subsecs(0);
+        let issuer_expiry = issuer.info().not_after;
+        if issuer_expiry <= now {
+            return Err(CaError::Expired);
+        }
+        let not_after = (now + Duration::days(i64::from(params.validity_days))).min(issuer_expiry);
+
+        let algorithm = params.algorithm.unwrap_or(issuer.info().algorithm);
+        let key_pair = algorithm.generate_key()?;
+        let key_pem = key_pair.serialize_pem();
+
+        let mut cert_params = CertificateParams::default();
+        let mut dn = DistinguishedName::new();
+        dn.push(DnType::CommonName, params.common_name.as_str());
+        cert_params.distinguished_name = dn;
+
+        for name in &params.dns_sans {
+            let name = name.as_str().try_into()?;
+            cert_params.subject_alt_names.push(SanType::DnsName(name));
+        }
+        for ip in &params.ip_sans {
+            cert_params.subject_alt_names.push(SanType::IpAddress(*ip));
+        }
+        if let Some(constraints) = issuer.constraints() {
+            constraints.check(&issuer.info().subject, &cert_params.subject_alt_names)?;
+        }
+
+        cert_params.is_ca = IsCa::NoCa;
+        cert_params.key_usages = vec![
+            KeyUsagePurpose::DigitalSignature,
+            KeyUsagePurpose::KeyEncipherment,
+        ];
+        cert_params.extended_key_usages = params.usage.extended_key_usages();
+        cert_params.crl_distribution_points =
+            distribution_points(&params.crl_urls, issuer.crl_url());
+
+        cert_params.not_before = to_offset(now)?;
+        cert_params.not_after = to_offset(not_after)?;
+
+        let serial = issuer.next_serial()?;
+        cert_params.serial_number = Some(serial_number(&serial)?);
+
+        let cert = sign_with(issuer.signing_key(), |issuer_key| {
+            cert_params.signed_by(&key_pair, issuer.certificate(), issuer_key)
+        })?;
+        let cert_pem = cert.pem();
+
+        Ok(Self {
+            chain_pem: format!("{}{}", cert_pem, issuer.intermediates_pem()),
+            cert_pem,
+            key_pem,
+            usage: params.usage,
+            info: CertificateInfo {
+                id: Uuid::new_v4(),
+                serial,
+                subject: params.common_name.clone(),
+                issuer: issuer.info().subject.clone(),
+                not_before: now,
+                not_after,
+                cert_type: CertificateType::EndEntity,
+                algorithm,
+                revoked: false,
+                revocation_reason: None,
+            },
+        })
+    }
+}
+
+/// A chrono time as rcgen wants it.
+fn to_offset(at: chrono::DateTime<Utc>) -> Result<time::OffsetDateTime, CaError> {
+    time::OffsetDateTime::from_unix_timestamp(at.timestamp())
+        .map_err(|e| CaError::Signing(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::KeyAlgorithm;
+    use rustls::pki_types::pem::PemObject;
+    use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+    use rustls::server::WebPkiClientVerifier;
+    use rustls::{
+        ClientConfig, ClientConnection, Connection, RootCertStore, ServerConfig, ServerConnection,
+    };
+    use std::sync::Arc;
+
+    /// Complete a TLS handshake in memory, the server presenting `leaf`
+    /// as `server_name` to a client that trusts only `root_pem`.
+    fn handshake(
+        leaf: &LeafCertificate,
+        root_pem: &str,
+        server_name: &str,
+    ) -> Result<(), rustls::Error> {
+        mutual_handshake(leaf, root_pem, server_name, None)
+    }
+
+    /// As [`handshake`], and if `client` is given the server demands a
+    /// client certificate from `root_pem` and gets that one.
+    fn mutual_handshake(
+        leaf: &LeafCertificate,
+        root_pem: &str,
+        server_name: &str,
+        client: Option<&LeafCertificate>,
+    ) -> Result<(), rustls::Error> {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let chain = |cert: &LeafCertificate| {
+            CertificateDer::pem_slice_iter(cert.chain_pem.as_bytes())
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap()
+        };
+        let key = |cert: &LeafCertificate| {
+            PrivateKeyDer::from_pem_slice(cert.key_pem.as_bytes()).unwrap()
+        };
+        let mut roots = RootCertStore::empty();
+        roots.add(CertificateDer::from_pem_slice(root_pem.as_bytes()).unwrap())?;
+
+        let server_config = ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()?;
+        let server_config = match client {
+            Some(_) => {
+                let verifier = WebPkiClientVerifier::builder_with_provider(
+                    Arc::new(roots.clone()),
+                    provider.clone(),
+                )
+                .build()
+                .unwrap();
+                server_config.with_client_cert_verifier(verifier)
+            }
+            None => server_config.with_no_client_auth(),
+        }
+        .with_single_cert(chain(leaf), key(leaf))?;
+
+        let client_config = ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()?
+            .with_root_certificates(roots);
+        let client_config = match client {
+            Some(client) => client_config.with_client_auth_cert(chain(client), key(client))?,
+            None => client_config.with_no_client_auth(),
+        };
+
+        let name = ServerName::try_from(server_name.to_string()).unwrap();
+        let mut client = Connection::Client(ClientConnection::new(Arc::new(client_config), name)?);
+        let mut server = Connection::Server(ServerConnection::new(Arc::new(server_config))?);
+        for _ in 0..10 {
+            if !client.is_handshaking() && !server.is_handshaking() {
+                return Ok(());
+            }
+            transfer(&mut client, &mut server)?;
+            transfer(&mut server, &mut client)?;
+        }
+        panic!("handshake did not finish");
+    }
+
+    /// Move whatever `from` has to send into `to`.
+    fn transfer(from: &mut Connection, to: &mut Connection) -> Result<(), rustls::Error> {
+        let mut bytes = Vec::new();
+        while from.wants_write() {
+            from.write_tls(&mut bytes).unwrap();
+        }
+        let mut pending = &bytes[..];
+        while !pending.is_empty() {
+            to.read_tls(&mut pending).unwrap();
+            to.process_new_packets()?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_server_handshake() {
+        let root = RootCa::generate("Root", KeyAlgorithm::EcdsaP256).unwrap();
+        let intermediate =
+            IntermediateCa::generate("Intermediate", &root, KeyAlgorithm::EcdsaP256).unwrap();
+        let params = LeafParams::new("localhost")
+            .dns("localhost")
+            .ip("127.0.0.1".parse().unwrap())
+            .validity(7);
+        let leaf = LeafCertificate::issue(&intermediate, &params).unwrap();
+
+        assert_eq!(leaf.info.subject, "localhost");
+        assert_eq!(leaf.info.issuer, "Intermediate");
+        assert_eq!(leaf.info.cert_type, CertificateType::EndEntity);
+        assert_eq!(leaf.chain_pem.matches("BEGIN CERTIFICATE").count(), 2);
+        assert!(leaf.chain_pem.starts_with(&leaf.cert_pem));
+
+        handshake(&leaf, root.certificate_pem(), "localhost").unwrap();
+        handshake(&leaf, root.certificate_pem(), "127.0.0.1").unwrap();
+        assert!(handshake(&leaf, root.certificate_pem(), "example.com").is_err());
+
+        // A root nobody issued from is no help
+        let other = RootCa::generate("Other Root", KeyAlgorithm::EcdsaP256).unwrap();
+        assert!(handshake(&leaf, other.certificate_pem(), "localhost").is_err());
+    }
+
+    #[test]
+    fn test_constrained_handshake() {
+        let root = RootCa::generate("Root", KeyAlgorithm::EcdsaP256).unwrap();
+        let constraints = CaConstraints::new()
+            .permit_dns("localhost")
+            .permit_ip("127.0.0.0/8".parse().unwrap());
+        let intermediate = IntermediateCa::generate_constrained(
+            "Local CA",
+            &root,
+            crate::IntermediatePurpose::General,
+            constraints,
+        )
+        .unwrap();
+        let params = LeafParams::new("localhost")
+            .dns("localhost")
+            .ip("127.0.0.1".parse().unwrap());
+        let leaf = LeafCertificate::issue(&intermediate, &params).unwrap();
+
+        // Clients accept the constrained chain for names inside it
+        handshake(&leaf, root.certificate_pem(), "localhost").unwrap();
+        handshake(&leaf, root.certificate_pem(), "127.0.0.1").unwrap();
+    }
+
+    #[test]
+    fn test_usage() {
+        let root = RootCa::generate("Root", KeyAlgorithm::EcdsaP256).unwrap();
+        let params = LeafParams::new("localhost").dns("localhost");
+
+        // Straight from the root, the chain is the leaf alone
+        let both = LeafCertificate::issue(&root, &params.clone().usage(LeafUsage::Both)).unwrap();
+        assert_eq!(both.chain_pem, both.cert_pem);
+        handshake(&both, root.certificate_pem(), "localhost").unwrap();
+
+        // A client certificate is refused as a server's
+        let client = LeafCertificate::issue(&root, &params.usage(LeafUsage::Client)).unwrap();
+        assert_eq!(client.usage, LeafUsage::Client);
+        assert!(handshake(&client, root.certificate_pem(), "localhost").is_err());
+    }
+
+    #[test]
+    fn test_client_identity() {
+        let root = RootCa::generate("Root", KeyAlgorithm::EcdsaP256).unwrap();
+        let intermediate =
+            IntermediateCa::generate("Intermediate", &root, KeyAlgorithm::EcdsaP256).unwrap();
+        let server = LeafCertificate::issue(
+            &intermediate,
+            &LeafParams::new("localhost").dns("localhost"),
+        )
+        .unwrap();
+
+        // No names needed, just who it is
+        let client = LeafCertificate::issue(
+            &intermediate,
+            &LeafParams::client("token-7f3a").validity(30),
+        )
+        .unwrap();
+        assert_eq!(client.usage, LeafUsage::Client);
+        assert_eq!(client.info.subject, "token-7f3a");
+        assert!(LeafCertificate::issue(&intermediate, &LeafParams::new("token-7f3a")).is_err());
+
+        let der = pem::parse(&client.cert_pem).unwrap();
+        let (_, cert) = x509_parser::parse_x509_certificate(der.contents()).unwrap();
+        let cn = cert.subject().iter_common_name().next().unwrap();
+        assert_eq!(cn.as_str().unwrap(), "token-7f3a");
+        let eku = cert.extended_key_usage().unwrap().unwrap().value;
+        assert!(eku.client_auth && !eku.server_auth);
+        assert!(cert.subject_alternative_name().unwrap().is_none());
+
+        // A server that demands client certificates from our root takes it
+        mutual_handshake(&server, root.certificate_pem(), "localhost", Some(&client)).unwrap();
+        assert!(
+            mutual_handshake(&server, root.certificate_pem(), "localhost", Some(&server)).is_err()
+        );
+    }
+
+    #[test]
+    fn test_validity_clamped() {
+        let root = RootCa::generate("Root", KeyAlgorithm::EcdsaP256).unwrap();
+        let session = IntermediateCa::for_session("sess-abc123", &root).unwrap();
+        let params = LeafParams::new("localhost").dns("localhost").validity(30);
+
+        let leaf = LeafCertificate::issue(&session, &params).unwrap();
+        assert!(leaf.info.not_after <= session.info.not_after);
+        assert!(leaf.info.not_after - leaf.info.not_before < Duration::days(2));
+        handshake(&leaf, root.certificate_pem(), "localhost").unwrap();
+
+        let short = LeafCertificate::issue(&root, &params.clone().validity(2)).unwrap();
+        assert_eq!(
+            short.info.not_after - short.info.not_before,
+            Duration::days(2)
+        );
+
+        assert!(LeafCertificate::issue(&root, &LeafParams::new("nothing")).is_err());
+        assert!(LeafCertificate::issue(&root, &params.validity(0)).is_err());
+    }
+
+    #[test]
+    fn test_algorithms() {
+        // Signature and public key algorithm OIDs of each kind
+        let kinds = [
+            (
+                KeyAlgorithm::EcdsaP256,
+                "1.2.840.10045.4.3.2",
+                "1.2.840.10045.2.1",
+            ),
+            (
+                KeyAlgorithm::EcdsaP384,
+                "1.2.840.10045.4.3.3",
+                "1.2.840.10045.2.1",
+            ),
+            (KeyAlgorithm::Ed25519, "1.3.101.112", "1.3.101.112"),
+        ];
+        for (algorithm, signature_oid, key_oid) in kinds {
+            let root = RootCa::generate("Root", algorithm).unwrap();
+            let intermediate = IntermediateCa::generate("Intermediate", &root, algorithm).unwrap();
+            let params = LeafParams::new("localhost").dns("localhost");
+            let leaf = LeafCertificate::issue(&intermediate, &params).unwrap();
+            assert_eq!(root.info.algorithm, algorithm);
+            assert_eq!(intermediate.info.algorithm, algorithm);
+            assert_eq!(leaf.info.algorithm, algorithm);
+
+            let der = pem::parse(&leaf.cert_pem).unwrap();
+            let (_, cert) = x509_parser::parse_x509_certificate(der.contents()).unwrap();
+            assert_eq!(
+                cert.signature_algorithm.algorithm.to_id_string(),
+                signature_oid
+            );
+            assert_eq!(
+                cert.public_key().algorithm.algorithm.to_id_string(),
+                key_oid
+            );
+
+            handshake(&leaf, root.certificate_pem(), "localhost").unwrap();
+        }
+
+        // A leaf can differ from its issuer
+        let root = RootCa::generate("Root", KeyAlgorithm::EcdsaP256).unwrap();
+        let params = LeafParams::new("localhost")
+            .dns("localhost")
+            .algorithm(KeyAlgorithm::Ed25519);
+        let leaf = LeafCertificate::issue(&root, &params).unwrap();
+        assert_eq!(leaf.info.algorithm, KeyAlgorithm::Ed25519);
+        handshake(&leaf, root.certificate_pem(), "localhost").unwrap();
+
+        // ring cannot make RSA keys
+        for rsa in [KeyAlgorithm::Rsa2048, KeyAlgorithm::Rsa4096] {
+            assert!(matches!(
+                RootCa::generate("Root", rsa),
+                Err(CaError::KeyGeneration(_))
+            ));
+            let params = LeafParams::new("localhost").dns("localhost").algorithm(rsa);
+            assert!(LeafCertificate::issue(&root, &params).is_err());
+        }
+    }
+}
@@ -4,10 +4,21 @@
 //! we revoke it here. The root can revoke intermediates,
 //! intermediates can revoke end-entities.
 
+use std::sync::Arc;
+use std::time::Duration;
+
 use chrono::{DateTime, Utc};
+use rcgen::{
+    Certificate, CertificateRevocationList, CertificateRevocationListParams, CrlDistributionPoint,
+    KeyIdMethod, RevokedCertParams,
+};
 use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
 use uuid::Uuid;
 
+use crate::keystore::sign_with;
+use crate::{normalize_serial, CaError, Issuer, SigningKeyStore};
+
 /// Reason for certificate revocation.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum RevocationReason {
@@ -31,6 +42,29 @@ pub enum RevocationReason {
     Unspecified,
 }
 
+impl RevocationReason {
+    /// The CRL reason code. RFC 5280 says to leave out "unspecified".
+    fn code(self) -> Option<rcgen::RevocationReason> {
+        match self {
+            RevocationReason::KeyCompromise => Some(rcgen::RevocationReason::KeyCompromise),
+            RevocationReason::CaCompromise => Some(rcgen::RevocationReason::CaCompromise),
+            RevocationReason::AffiliationChanged => {
+                Some(rcgen::RevocationReason::AffiliationChanged)
+            }
+            RevocationReason::Superseded => Some(rcgen::RevocationReason::Superseded),
+            RevocationReason::CessationOfOperation => {
+                Some(rcgen::RevocationReason::CessationOfOperation)
+            }
+            RevocationReason::CertificateHold => Some(rcgen::RevocationReason::CertificateHold),
+            RevocationReason::PrivilegeWithdrawn => {
+                Some(rcgen::RevocationReason::PrivilegeWithdrawn)
+            }
+            RevocationReason::AaCompromise => Some(rcgen::RevocationReason::AaCompromise),
+            RevocationReason::Unspecified => None,
+        }
+    }
+}
+
 impl std::fmt::Display for RevocationReason {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -60,6 +94,35 @@ pub struct RevocationEntry {
     pub notes: Option<String>,
 }
 
+/// A signed X.509 CRL, in both encodings.
+#[derive(Debug, Clone)]
+pub struct X509Crl {
+    /// PEM-encoded CRL
+    pub pem: String,
+    /// DER-encoded CRL
+    pub der: Vec<u8>,
+    /// CRL number it was signed with
+    pub crl_number: u64,
+}
+
+/// How long a CA's CRLs are good for and where they are published.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RevocationListConfig {
+    /// Time from `this_update` to `next_update`
+    pub validity: Duration,
+    /// Where the CRL is published, for the CA's certificates to point at
+    pub distribution_url: Option<String>,
+}
+
+impl Default for RevocationListConfig {
+    fn default() -> Self {
+        Self {
+            validity: Duration::from_secs(24 * 60 * 60), // Daily updates
+            distribution_url: None,
+        }
+    }
+}
+
 /// Certificate Revocation List.
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct RevocationList {
@@ -73,18 +136,44 @@ pub struct RevocationList {
     pub next_update: DateTime<Utc>,
     /// Revoked certificates
     pub entries: Vec<RevocationEntry>,
+    /// Generation of this CRL, bumped by [`RevocationList::renew`]
+    #[serde(default)]
+    pub crl_number: u64,
+    /// Lifetime and distribution point
+    #[serde(default)]
+    pub config: RevocationListConfig,
 }
 
 impl RevocationList {
-    /// Create a new revocation list.
+    /// Create a new revocation list, due for renewal in a day.
     pub fn new(issuer: impl Into<String>) -> Self {
+        Self::with_config(issuer, RevocationListConfig::default())
+    }
+
+    /// Create a new revocation list, the first of its CA.
+    pub fn with_config(issuer: impl Into<String>, config: RevocationListConfig) -> Self {
         let now = Utc::now();
         Self {
             id: Uuid::new_v4(),
             issuer: issuer.into(),
             this_update: now,
-            next_update: now + chrono::Duration::days(1), // Daily updates
+            next_update: due(now, config.validity),
             entries: Vec::new(),
+            crl_number: 1,
+            config,
+        }
+    }
+
+    /// The next generation of this list: the same entries, updated now,
+    /// due again after the configured validity, with the next CRL number.
+    pub fn renew(&self) -> Self {
+        let now = Utc::now();
+        Self {
+            id: Uuid::new_v4(),
+            this_update: now,
+            next_update: due(now, self.config.validity),
+            crl_number: self.crl_number + 1,
+            ..self.clone()
         }
     }
 
@@ -113,14 +202,17 @@ impl RevocationList {
         });
     }
 
-    /// Check if a serial number is revoked.
+    /// Check if a serial number is revoked, however either is written.
     pub fn is_revoked(&self, serial: &str) -> bool {
-        self.entries.iter().any(|e| e.serial == serial)
+        self.get_revocation(serial).is_some()
     }
 
     /// Get revocation entry if revoked.
     pub fn get_revocation(&self, serial: &str) -> Option<&RevocationEntry> {
-        self.entries.iter().find(|e| e.serial == serial)
+        let serial = normalize_serial(serial);
+        self.entries
+            .iter()
+            .find(|e| normalize_serial(&e.serial) == serial)
     }
 
     /// Export as JSON.
@@ -133,6 +225,79 @@ impl RevocationList {
         serde_json::from_str(json)
     }
 
+    /// Sign as a PEM-encoded X.509 CRL.
+    ///
+    /// `issuer` must be the CA that issued the revoked certificates, and
+    /// `crl_number` should grow with every CRL it publishes.
+    pub fn to_pem(
+        &self,
+        issuer: &Certificate,
+        issuer_key: &Arc<dyn SigningKeyStore>,
+        crl_number: u64,
+    ) -> Result<String, CaError> {
+        Ok(self.signed(issuer, issuer_key, crl_number)?.pem()?)
+    }
+
+    /// Sign as a DER-encoded X.509 CRL.
+    pub fn to_der(
+        &self,
+        issuer: &Certificate,
+        issuer_key: &Arc<dyn SigningKeyStore>,
+        crl_number: u64,
+    ) -> Result<Vec<u8>, CaError> {
+        Ok(self.signed(issuer, issuer_key, crl_number)?.der().to_vec())
+    }
+
+    /// Sign as an X.509 CRL from `issuer`, in PEM and DER, numbered with
+    /// this list's `crl_number`. `issuer` must be the CA this list was
+    /// made for.
+    pub fn sign(&self, issuer: &impl Issuer) -> Result<X509Crl, CaError> {
+        if self.issuer != issuer.info().subject {
+            return Err(CaError::InvalidChain(format!(
+                "revocation list is for '{}', not '{}'",
+                self.issuer,
+                issuer.info().subject
+            )));
+        }
+
+        let crl = self.signed(issuer.certificate(), issuer.signing_key(), self.crl_number)?;
+        Ok(X509Crl {
+            pem: crl.pem()?,
+            der: crl.der().to_vec(),
+            crl_number: self.crl_number,
+        })
+    }
+
+    fn signed(
+        &self,
+        issuer: &Certificate,
+        issuer_key: &Arc<dyn SigningKeyStore>,
+        crl_number: u64,
+    ) -> Result<CertificateRevocationList, CaError> {
+        let revoked_certs = self
+            .entries
+            .iter()
+            .map(|entry| {
+                Ok(RevokedCertParams {
+                    serial_number: serial_bytes(&entry.serial)?.into(),
+                    revocation_time: offset(entry.revoked_at)?,
+                    reason_code: entry.reason.code(),
+                    invalidity_date: None,
+                })
+            })
+            .collect::<Result<Vec<_>, CaError>>()?;
+
+        let params = CertificateRevocationListParams {
+            this_update: offset(self.this_update)?,
+            next_update: offset(self.next_update)?,
+            crl_number: crl_number.into(),
+            issuing_distribution_point: None,
+            revoked_certs,
+            key_identifier_method: KeyIdMethod::Sha256,
+        };
+        sign_with(issuer_key, |key| params.signed_by(issuer, key))
+    }
+
     /// Get count of revoked certificates.
     pub fn len(&self) -> usize {
         self.entries.len()
@@ -144,9 +309,57 @@ impl RevocationList {
     }
 }
 
+/// A CRL distribution point for certificates to carry, naming where
+/// their issuer's CRL is published: `urls` if there are any, or else the
+/// issuer's own. No URLs, no distribution point.
+pub(crate) fn distribution_points(
+    urls: &[String],
+    issuer: Option<&str>,
+) -> Vec<CrlDistributionPoint> {
+    let uris = match issuer {
+        Some(url) if urls.is_empty() => vec![url.to_string()],
+        _ => urls.to_vec(),
+    };
+    if uris.is_empty() {
+        return Vec::new();
+    }
+    vec![CrlDistributionPoint { uris }]
+}
+
+/// When a CRL made at `at` is due, or the end of time if it never is.
+fn due(at: DateTime<Utc>, validity: Duration) -> DateTime<Utc> {
+    chrono::Duration::from_std(validity)
+        .ok()
+        .and_then(|validity| at.checked_add_signed(validity))
+        .unwrap_or(DateTime::<Utc>::MAX_UTC)
+}
+
+/// A hex serial (colons allowed) as big-endian bytes.
+pub(crate) fn serial_bytes(serial: &str) -> Result<Vec<u8>, CaError> {
+    let hex: Vec<u8> = serial.bytes().filter(|b| *b != b':').collect();
+    let invalid = || CaError::Parsing(format!("'{}' is not a hex serial number", serial));
+    if hex.is_empty() || !hex.iter().all(u8::is_ascii_hexdigit) {
+        return Err(invalid());
+    }
+
+    hex.chunks(2)
+        .map(|pair| match pair {
+            [high, low] => u8::from_str_radix(&format!("{}{}", *high as char, *low as char), 16)
+                .map_err(|_| invalid()),
+            _ => Err(invalid()),
+        })
+        .collect()
+}
+
+fn offset(at: DateTime<Utc>) -> Result<OffsetDateTime, CaError> {
+    OffsetDateTime::from_unix_timestamp(at.timestamp()).map_err(|e| CaError::Signing(e.to_string()))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use x509_parser::extensions::{DistributionPointName, GeneralName, ParsedExtension};
+    use x509_parser::x509::ReasonCode;
 
     #[test]
     fn test_revocation_list() {
@@ -159,6 +372,8 @@ mod tests {
         assert!(!crl.is_empty());
         assert!(crl.is_revoked("ABC123"));
         assert!(!crl.is_revoked("XYZ789"));
+        assert!(crl.is_revoked("ab:c1:23"));
+        assert!(crl.is_revoked("00abc123"));
     }
 
     #[test]
@@ -187,4 +402,226 @@ mod tests {
         assert_eq!(loaded.entries.len(), 1);
         assert!(loaded.is_revoked("123"));
     }
+
+    #[test]
+    fn test_sign_crl() {
+        let root = crate::RootCa::generate("Test Root", crate::KeyAlgorithm::EcdsaP256).unwrap();
+        let mut crl = RevocationList::new("Test Root");
+        crl.revoke("00ff10a2b3c4d5e6", RevocationReason::KeyCompromise);
+        crl.revoke("01:02", RevocationReason::Unspecified);
+
+        let pem = crl
+            .to_pem(root.certificate(), root.signing_key(), 1)
+            .unwrap();
+        assert!(pem.contains("BEGIN X509 CRL"));
+        assert!(!crl
+            .to_der(root.certificate(), root.signing_key(), 2)
+            .unwrap()
+            .is_empty());
+
+        crl.revoke("not-hex", RevocationReason::Superseded);
+        assert!(crl
+            .to_pem(root.certificate(), root.signing_key(), 3)
+            .is_err());
+    }
+
+    #[test]
+    fn test_x509_crl() {
+        let root = crate::RootCa::generate("Test Root", crate::KeyAlgorithm::EcdsaP256).unwrap();
+        let ca = crate::IntermediateCa::generate("Test CA", &root, crate::KeyAlgorithm::EcdsaP256)
+            .unwrap();
+        let url = "http://crl.example.com/test-ca.crl";
+        let params = crate::LeafParams::new("localhost")
+            .dns("localhost")
+            .crl_url(url);
+        let leaf = crate::LeafCertificate::issue(&ca, &params).unwrap();
+
+        // The leaf says where to look
+        let leaf_der = pem::parse(&leaf.cert_pem).unwrap();
+        let (_, cert) = x509_parser::parse_x509_certificate(leaf_der.contents()).unwrap();
+        assert!(cert.extensions().iter().any(|ext| matches!(
+            ext.parsed_extension(),
+            ParsedExtension::CRLDistributionPoints(_)
+        )));
+        assert!(leaf_der
+            .contents()
+            .windows(url.len())
+            .any(|w| w == url.as_bytes()));
+
+        let mut crl = RevocationList::new("Test CA");
+        crl.revoke(&leaf.info.serial, RevocationReason::KeyCompromise);
+        crl.revoke("01:02", RevocationReason::Unspecified);
+        let signed = crl.sign(&ca).unwrap();
+        assert_eq!(pem::parse(&signed.pem).unwrap().contents(), &signed.der[..]);
+
+        let (_, parsed) = x509_parser::parse_x509_crl(&signed.der).unwrap();
+        assert_eq!(
+            parsed.last_update().timestamp(),
+            crl.this_update.timestamp()
+        );
+        assert_eq!(
+            parsed.next_update().map(|at| at.timestamp()),
+            Some(crl.next_update.timestamp())
+        );
+        assert_eq!(
+            parsed.crl_number().map(|n| n.to_string()),
+            Some(signed.crl_number.to_string())
+        );
+
+        // DER integers may carry a leading zero
+        let trim = |bytes: &[u8]| {
+            bytes
+                .iter()
+                .copied()
+                .skip_while(|b| *b == 0)
+                .collect::<Vec<_>>()
+        };
+        let revoked: Vec<_> = parsed.iter_revoked_certificates().collect();
+        assert_eq!(revoked.len(), 2);
+        assert_eq!(
+            trim(revoked[0].raw_serial()),
+            trim(&serial_bytes(&leaf.info.serial).unwrap())
+        );
+        assert_eq!(
+            revoked[0].reason_code(),
+            Some((false, ReasonCode::KeyCompromise))
+        );
+        assert_eq!(revoked[1].reason_code(), None);
+
+        // Signed by the intermediate, and only by it
+        let ca_der = ca.certificate().der();
+        let (_, ca_cert) = x509_parser::parse_x509_certificate(ca_der).unwrap();
+        parsed.verify_signature(ca_cert.public_key()).unwrap();
+        let (_, root_cert) = x509_parser::parse_x509_certificate(root.certificate().der()).unwrap();
+        assert!(parsed.verify_signature(root_cert.public_key()).is_err());
+
+        assert!(matches!(crl.sign(&root), Err(CaError::InvalidChain(_))));
+    }
+
+    #[test]
+    fn test_renew() {
+        let root = crate::RootCa::generate("Test Root", crate::KeyAlgorithm::EcdsaP256).unwrap();
+        let config = RevocationListConfig {
+            validity: Duration::from_secs(7 * 24 * 60 * 60),
+            distribution_url: Some("http://crl.example.com/root.crl".to_string()),
+        };
+        let mut crl = RevocationList::with_config("Test Root", config.clone());
+        crl.revoke("0A1B", RevocationReason::CaCompromise);
+        assert_eq!(crl.crl_number, 1);
+        assert_eq!(crl.next_update - crl.this_update, chrono::Duration::days(7));
+
+        let next = crl.renew();
+        assert_eq!(next.crl_number, 2);
+        assert_ne!(next.id, crl.id);
+        assert!(next.this_update >= crl.this_update);
+        assert_eq!(
+            next.next_update - next.this_update,
+            chrono::Duration::days(7)
+        );
+        assert!(next.is_revoked("0A1B"));
+        assert_eq!(next.config, config);
+        assert_eq!(next.renew().crl_number, 3);
+
+        let signed = next.sign(&root).unwrap();
+        assert_eq!(signed.crl_number, 2);
+        let (_, parsed) = x509_parser::parse_x509_crl(&signed.der).unwrap();
+        assert_eq!(
+            parsed.crl_number().map(|n| n.to_string()),
+            Some("2".to_string())
+        );
+
+        // Lists saved before they were numbered still load
+        let mut json = serde_json::to_value(&crl).unwrap();
+        json.as_object_mut().unwrap().remove("crl_number");
+        json.as_object_mut().unwrap().remove("config");
+        let old = RevocationList::from_json(&json.to_string()).unwrap();
+        assert_eq!(old.crl_number, 0);
+        assert_eq!(old.config, RevocationListConfig::default());
+        assert_eq!(old.renew().crl_number, 1);
+    }
+
+    /// URLs in a certificate's CRL distribution points.
+    fn distribution_urls(pem: &str) -> Vec<String> {
+        let der = pem::parse(pem).unwrap();
+        let (_, cert) = x509_parser::parse_x509_certificate(der.contents()).unwrap();
+        let mut urls = Vec::new();
+        for ext in cert.extensions() {
+            if let ParsedExtension::CRLDistributionPoints(points) = ext.parsed_extension() {
+                for point in points.iter() {
+                    if let Some(DistributionPointName::FullName(names)) = &point.distribution_point
+                    {
+                        for name in names {
+                            if let GeneralName::URI(url) = name {
+                                urls.push(url.to_string());
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        urls
+    }
+
+    #[test]
+    fn test_distribution_url() {
+        let root_url = "http://crl.example.com/root.crl";
+        let ca_url = "http://crl.example.com/ca.crl";
+        let root = crate::RootCa::generate("Test Root", crate::KeyAlgorithm::EcdsaP256)
+            .unwrap()
+            .with_crl_url(Some(root_url.to_string()));
+        let ca = crate::IntermediateCa::generate("Test CA", &root, crate::KeyAlgorithm::EcdsaP256)
+            .unwrap()
+            .with_crl_url(Some(ca_url.to_string()));
+        assert_eq!(distribution_urls(ca.chain_pem()), [root_url]);
+
+        // Every way of issuing points at the CA's CRL
+        let leaf = crate::LeafCertificate::issue(
+            &ca,
+            &crate::LeafParams::new("localhost").dns("localhost"),
+        )
+        .unwrap();
+        assert_eq!(distribution_urls(&leaf.cert_pem), [ca_url]);
+        let issued = ca
+            .issue(&crate::CertificateRequest::for_domain("example.com"))
+            .unwrap();
+        assert_eq!(distribution_urls(&issued.cert_pem), [ca_url]);
+
+        let key = rcgen::KeyPair::generate().unwrap();
+        let csr = rcgen::CertificateParams::new(vec!["api.example.com".to_string()])
+            .unwrap()
+            .serialize_request(&key)
+            .unwrap()
+            .pem()
+            .unwrap();
+        let policy = crate::SigningPolicy::default().allow_domain("*.example.com");
+        let signed = ca.sign_csr(&csr, policy).unwrap();
+        assert_eq!(distribution_urls(&signed.cert_pem), [ca_url]);
+
+        // Unless told otherwise
+        let elsewhere = "http://elsewhere.example.com/ca.crl";
+        let leaf = crate::LeafCertificate::issue(
+            &ca,
+            &crate::LeafParams::new("localhost")
+                .dns("localhost")
+                .crl_url(elsewhere),
+        )
+        .unwrap();
+        assert_eq!(distribution_urls(&leaf.cert_pem), [elsewhere]);
+
+        let plain =
+            crate::IntermediateCa::generate("Plain CA", &root, crate::KeyAlgorithm::EcdsaP256)
+                .unwrap();
+        let issued = plain
+            .issue(&crate::CertificateRequest::for_domain("example.com"))
+            .unwrap();
+        assert!(distribution_urls(&issued.cert_pem).is_empty());
+    }
+
+    #[test]
+    fn test_serial_bytes() {
+        assert_eq!(serial_bytes("00ff10").unwrap(), [0x00, 0xff, 0x10]);
+        assert_eq!(serial_bytes("0A:0b").unwrap(), [0x0a, 0x0b]);
+        assert!(serial_bytes("abc").is_err());
+        assert!(serial_bytes("").is_err());
+    }
 }
@@ -0,0 +1,214 @@
+//! Certificate serial numbers.
+//!
+//! Serials are 16 random bytes, well over the 64 bits of entropy CAs are
+//! expected to put in them, and each CA keeps a [`SerialRegistry`] of what
+//! it has issued so it never hands out the same one twice, even across
+//! restarts, as long as the registry is saved with the CA.
+
+use std::collections::BTreeSet;
+
+use rcgen::SerialNumber;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+
+use crate::revocation::serial_bytes;
+use crate::CaError;
+
+/// Random serials to try before giving up on finding an unused one.
+const ATTEMPTS: usize = 8;
+
+/// Serial numbers a CA has issued.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SerialRegistry {
+    /// Every serial issued, normalized
+    issued: BTreeSet<String>,
+}
+
+impl SerialRegistry {
+    /// An empty registry, for a new CA.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A random serial this registry has not seen, recorded as issued.
+    pub fn allocate(&mut self) -> Result<String, CaError> {
+        for _ in 0..ATTEMPTS {
+            let serial = random_serial()?;
+            if self.record(&serial) {
+                return Ok(serial);
+            }
+        }
+        Err(CaError::Signing(
+            "no unused serial number found".to_string(),
+        ))
+    }
+
+    /// Record a serial as issued. False if it already was.
+    pub fn record(&mut self, serial: &str) -> bool {
+        self.issued.insert(normalize_serial(serial))
+    }
+
+    /// Whether a serial has been issued, however it is written.
+    pub fn contains(&self, serial: &str) -> bool {
+        self.issued.contains(&normalize_serial(serial))
+    }
+
+    /// Number of serials issued.
+    pub fn len(&self) -> usize {
+        self.issued.len()
+    }
+
+    /// Check if nothing has been issued.
+    pub fn is_empty(&self) -> bool {
+        self.issued.is_empty()
+    }
+}
+
+/// A serial as uppercase hex, without separators or leading zero bytes,
+/// so that one serial compares equal however it was written.
+pub fn normalize_serial(serial: &str) -> String {
+    let mut hex: String = serial
+        .chars()
+        .filter(|c| *c != ':' && !c.is_whitespace())
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    if hex.len() % 2 == 1 {
+        hex.insert(0, '0');
+    }
+    while hex.len() > 2 && hex.starts_with("00") {
+        hex.drain(..2);
+    }
+    hex
+}
+
+/// Bytes as uppercase hex, the way [`crate::CertificateInfo`] holds
+/// serials.
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+/// A hex serial as rcgen takes it.
+pub(crate) fn serial_number(serial: &str) -> Result<SerialNumber, CaError> {
+    Ok(SerialNumber::from_slice(&serial_bytes(serial)?))
+}
+
+/// 16 random bytes as a serial, in hex.
+fn random_serial() -> Result<String, CaError> {
+    let mut bytes = [0u8; 16];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| CaError::KeyGeneration("no randomness for a serial number".to_string()))?;
+    // Positive and without a leading zero, so it is encoded as these 16
+    // bytes exactly
+    bytes[0] = (bytes[0] & 0x7f).max(1);
+    Ok(to_hex(&bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        CertificateRequest, IntermediateCa, KeyAlgorithm, LeafCertificate, LeafParams, RootCa,
+        SigningPolicy,
+    };
+    use rcgen::{CertificateParams, KeyPair};
+
+    /// The serial in a certificate, as uppercase hex.
+    fn cert_serial(cert_pem: &str) -> String {
+        let der = pem::parse(cert_pem).unwrap();
+        let (_, cert) = x509_parser::parse_x509_certificate(der.contents()).unwrap();
+        to_hex(cert.raw_serial())
+    }
+
+    #[test]
+    fn test_normalize_serial() {
+        assert_eq!(normalize_serial("0a:1b:2c"), "0A1B2C");
+        assert_eq!(normalize_serial("00ff"), "FF");
+        assert_eq!(normalize_serial("00"), "00");
+        assert_eq!(normalize_serial("abc"), "0ABC");
+        assert_eq!(normalize_serial(" 01 02 "), "0102");
+    }
+
+    #[test]
+    fn test_registry() {
+        let mut registry = SerialRegistry::new();
+        let serial = registry.allocate().unwrap();
+        assert_eq!(serial.len(), 32);
+        assert!(u8::from_str_radix(&serial[..2], 16).unwrap() < 0x80);
+        assert_ne!(&serial[..2], "00");
+
+        assert!(registry.contains(&serial.to_lowercase()));
+        assert!(!registry.record(&serial));
+        assert!(registry.record("01:02"));
+        assert!(registry.contains("0102"));
+        assert_eq!(registry.len(), 2);
+
+        let json = serde_json::to_string(&registry).unwrap();
+        let loaded: SerialRegistry = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded, registry);
+
+        let serials: BTreeSet<_> = (0..100).map(|_| registry.allocate().unwrap()).collect();
+        assert_eq!(serials.len(), 100);
+        assert_eq!(registry.len(), 102);
+    }
+
+    #[test]
+    fn test_issued_serials() {
+        let root = RootCa::generate("Root", KeyAlgorithm::EcdsaP256).unwrap();
+        let intermediate =
+            IntermediateCa::generate("Intermediate", &root, KeyAlgorithm::EcdsaP256).unwrap();
+        assert_eq!(cert_serial(root.certificate_pem()), root.info.serial);
+        assert_eq!(
+            cert_serial(intermediate.chain_pem()),
+            intermediate.info.serial
+        );
+        assert!(root.serials().contains(&root.info.serial));
+        assert!(root.serials().contains(&intermediate.info.serial));
+
+        let leaf = LeafCertificate::issue(&intermediate, &LeafParams::new("leaf").dns("leaf.test"))
+            .unwrap();
+        assert_eq!(cert_serial(&leaf.cert_pem), leaf.info.serial);
+
+        let cert = intermediate
+            .issue(&CertificateRequest::for_domain("host.test"))
+            .unwrap();
+        assert_eq!(cert_serial(&cert.cert_pem), cert.info.serial);
+
+        let key = KeyPair::generate().unwrap();
+        let csr = CertificateParams::new(vec!["app.test".to_string()])
+            .unwrap()
+            .serialize_request(&key)
+            .unwrap()
+            .pem()
+            .unwrap();
+        let signed = intermediate
+            .sign_csr(&csr, SigningPolicy::default().allow_domain("*.test"))
+            .unwrap();
+        assert_eq!(cert_serial(&signed.cert_pem), signed.info.serial);
+
+        let serials = intermediate.serials();
+        assert_eq!(serials.len(), 3);
+        for serial in [&leaf.info.serial, &cert.info.serial, &signed.info.serial] {
+            assert!(serials.contains(serial));
+        }
+
+        // A reloaded CA picks up where it left off
+        let reloaded = IntermediateCa::from_pem(
+            intermediate.private_key_pem().unwrap(),
+            intermediate.chain_pem(),
+            intermediate.info.clone(),
+            intermediate.purpose.clone(),
+        )
+        .unwrap()
+        .with_serials(serials.clone());
+        assert_eq!(reloaded.serials(), serials);
+        let root = RootCa::from_pem(
+            root.private_key_pem(),
+            root.certificate_pem(),
+            root.info.clone(),
+        )
+        .unwrap()
+        .with_serials(root.serials());
+        assert_eq!(root.serials().len(), 2);
+    }
+}
@@ -0,0 +1,387 @@
+//! Inventory of issued certificates.
+//!
+//! A [`CertStore`] is a JSON file recording every certificate a CA has
+//! issued, with its PEM, so there is always an answer to "what did we
+//! issue, and what is about to expire". Each call reads and writes the file
+//! under a lock on a `.lock` file next to it, so several processes can
+//! share one store.
+
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Duration, Utc};
+use fs2::FileExt;
+use serde::{Deserialize, Serialize};
+
+use crate::{normalize_serial, CaError, CertificateInfo, RevocationList, RevocationReason};
+
+/// A certificate in the store.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredCert {
+    /// What was issued
+    #[serde(flatten)]
+    pub info: CertificateInfo,
+    /// PEM-encoded certificate
+    pub cert_pem: String,
+    /// When it was recorded
+    pub recorded_at: DateTime<Utc>,
+}
+
+/// The store file's contents.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Inventory {
+    #[serde(default)]
+    certs: Vec<StoredCert>,
+}
+
+/// A file-backed inventory of issued certificates.
+#[derive(Debug, Clone)]
+pub struct CertStore {
+    path: PathBuf,
+}
+
+impl CertStore {
+    /// The store at `path`. The file is created on the first
+    /// [`CertStore::record`]; until then the store is empty.
+    pub fn open(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Path of the store file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Record an issued certificate. Fails if its serial is already in the
+    /// store.
+    pub fn record(&self, info: &CertificateInfo, cert_pem: &str) -> Result<(), CaError> {
+        self.update(|inventory| {
+            let serial = normalize_serial(&info.serial);
+            if inventory
+                .certs
+                .iter()
+                .any(|cert| normalize_serial(&cert.info.serial) == serial)
+            {
+                return Err(CaError::Store(format!(
+                    "serial {} is already recorded",
+                    info.serial
+                )));
+            }
+            inventory.certs.push(StoredCert {
+                info: info.clone(),
+                cert_pem: cert_pem.to_string(),
+                recorded_at: Utc::now(),
+            });
+            Ok(())
+        })
+    }
+
+    /// Every certificate in the store, in the order they were recorded.
+    pub fn all(&self) -> Result<Vec<StoredCert>, CaError> {
+        Ok(self.read()?.certs)
+    }
+
+    /// The certificate with a serial, however it is written.
+    pub fn find_by_serial(&self, serial: &str) -> Result<Option<StoredCert>, CaError> {
+        let serial = normalize_serial(serial);
+        Ok(self
+            .read()?
+            .certs
+            .into_iter()
+            .find(|cert| normalize_serial(&cert.info.serial) == serial))
+    }
+
+    /// Certificates whose subject common name is `subject`, ignoring case.
+    pub fn find_by_subject(&self, subject: &str) -> Result<Vec<StoredCert>, CaError> {
+        Ok(self
+            .read()?
+            .certs
+            .into_iter()
+            .filter(|cert| cert.info.subject.eq_ignore_ascii_case(subject))
+            .collect())
+    }
+
+    /// Unrevoked certificates that are still valid but expire within
+    /// `within` from now, soonest first.
+    pub fn expiring_within(&self, within: Duration) -> Result<Vec<StoredCert>, CaError> {
+        self.expiring(Utc::now(), within)
+    }
+
+    /// Unrevoked certificates valid at `at` that expire by `at + within`.
+    fn expiring(&self, at: DateTime<Utc>, within: Duration) -> Result<Vec<StoredCert>, CaError> {
+        let until = at + within;
+        let mut certs: Vec<_> = self
+            .read()?
+            .certs
+            .into_iter()
+            .filter(|cert| {
+                !cert.info.revoked && cert.info.not_after >= at && cert.info.not_after <= until
+            })
+            .collect();
+        certs.sort_by_key(|cert| cert.info.not_after);
+        Ok(certs)
+    }
+
+    /// Mark a certificate revoked, and add it to `crl` unless it is already
+    /// listed there. `crl` must be the list of the certificate's issuer.
+    pub fn mark_revoked(
+        &self,
+        serial: &str,
+        reason: RevocationReason,
+        crl: &mut RevocationList,
+    ) -> Result<StoredCert, CaError> {
+        let serial = normalize_serial(serial);
+        self.update(|inventory| {
+            let cert = inventory
+                .certs
+                .iter_mut()
+                .find(|cert| normalize_serial(&cert.info.serial) == serial)
+                .ok_or_else(|| CaError::Store(format!("no certificate with serial {serial}")))?;
+            if cert.info.issuer != crl.issuer {
+                return Err(CaError::Store(format!(
+                    "{} was issued by '{}', not '{}'",
+                    cert.info.serial, cert.info.issuer, crl.issuer
+                )));
+            }
+            if cert.info.revoked {
+                return Err(CaError::Revoked(cert.info.serial.clone()));
+            }
+            cert.info.revoked = true;
+            cert.info.revocation_reason = Some(reason);
+            if !crl.is_revoked(&cert.info.serial) {
+                crl.revoke(cert.info.serial.clone(), reason);
+            }
+            Ok(cert.clone())
+        })
+    }
+
+    /// Read the store under a shared lock.
+    fn read(&self) -> Result<Inventory, CaError> {
+        let lock = self.lock()?;
+        FileExt::lock_shared(&lock)?;
+        self.load()
+    }
+
+    /// Read, change and write back the store under an exclusive lock.
+    fn update<T>(
+        &self,
+        change: impl FnOnce(&mut Inventory) -> Result<T, CaError>,
+    ) -> Result<T, CaError> {
+        let lock = self.lock()?;
+        FileExt::lock_exclusive(&lock)?;
+        let mut inventory = self.load()?;
+        let result = change(&mut inventory)?;
+
+        // Readers never see a half-written file
+        let json =
+            serde_json::to_string_pretty(&inventory).map_err(|e| CaError::Store(e.to_string()))?;
+        let temp = self.sibling("tmp");
+        std::fs::write(&temp, json)?;
+        std::fs::rename(&temp, &self.path)?;
+        Ok(result)
+    }
+
+    fn load(&self) -> Result<Inventory, CaError> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(json) => serde_json::from_str(&json)
+                .map_err(|e| CaError::Store(format!("{}: {e}", self.path.display()))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Inventory::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// The lock file, unlocked. The lock is released when it is dropped.
+    fn lock(&self) -> Result<File, CaError> {
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        Ok(OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.sibling("lock"))?)
+    }
+
+    /// A file next to the store, e.g. `certs.json.lock`.
+    fn sibling(&self, extension: &str) -> PathBuf {
+        let mut name = self.path.as_os_str().to_owned();
+        name.push(".");
+        name.push(extension);
+        PathBuf::from(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CertificateType, KeyAlgorithm};
+    use uuid::Uuid;
+
+    fn info(serial: &str, subject: &str, not_after: DateTime<Utc>) -> CertificateInfo {
+        CertificateInfo {
+            id: Uuid::new_v4(),
+            serial: serial.to_string(),
+            subject: subject.to_string(),
+            issuer: "Intermediate".to_string(),
+            not_before: not_after - Duration::days(90),
+            not_after,
+            cert_type: CertificateType::EndEntity,
+            algorithm: KeyAlgorithm::EcdsaP256,
+            revoked: false,
+            revocation_reason: None,
+        }
+    }
+
+    #[test]
+    fn test_persistence() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ca").join("certs.json");
+        let store = CertStore::open(&path);
+        assert!(store.all().unwrap().is_empty());
+
+        let expires = Utc::now() + Duration::days(30);
+        store
+            .record(&info("0A1B", "host.example", expires), "PEM-1")
+            .unwrap();
+        store
+            .record(&info("0C2D", "Host.Example", expires), "PEM-2")
+            .unwrap();
+        assert!(store
+            .record(&info("0a:1b", "other.example", expires), "PEM-3")
+            .is_err());
+
+        let reopened = CertStore::open(&path);
+        assert_eq!(reopened.all().unwrap().len(), 2);
+        let found = reopened.find_by_serial("0a:1b").unwrap().unwrap();
+        assert_eq!(found.cert_pem, "PEM-1");
+        assert_eq!(found.info.subject, "host.example");
+        assert!(reopened.find_by_serial("FFFF").unwrap().is_none());
+        assert_eq!(reopened.find_by_subject("HOST.example").unwrap().len(), 2);
+        assert!(reopened
+            .find_by_subject("other.example")
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_mark_revoked() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = CertStore::open(dir.path().join("certs.json"));
+        let expires = Utc::now() + Duration::days(30);
+        store
+            .record(&info("0A1B", "host.example", expires), "PEM")
+            .unwrap();
+
+        let mut crl = RevocationList::new("Intermediate");
+        let revoked = store
+            .mark_revoked("0a1b", RevocationReason::KeyCompromise, &mut crl)
+            .unwrap();
+        assert!(revoked.info.revoked);
+        assert_eq!(crl.entries.len(), 1);
+        assert_eq!(crl.entries[0].serial, "0A1B");
+        assert_eq!(crl.entries[0].reason, RevocationReason::KeyCompromise);
+
+        let stored = store.find_by_serial("0A1B").unwrap().unwrap();
+        assert!(stored.info.revoked);
+        assert_eq!(
+            stored.info.revocation_reason,
+            Some(RevocationReason::KeyCompromise)
+        );
+
+        assert!(matches!(
+            store.mark_revoked("0A1B", RevocationReason::Superseded, &mut crl),
+            Err(CaError::Revoked(_))
+        ));
+        assert!(store
+            .mark_revoked("FFFF", RevocationReason::Superseded, &mut crl)
+            .is_err());
+        assert_eq!(crl.entries.len(), 1);
+
+        // Already on the CRL: the store catches up without a second entry
+        store
+            .record(&info("0C2D", "other.example", expires), "PEM")
+            .unwrap();
+        crl.revoke("0c2d", RevocationReason::Superseded);
+        store
+            .mark_revoked("0C2D", RevocationReason::Superseded, &mut crl)
+            .unwrap();
+        assert_eq!(crl.entries.len(), 2);
+
+        // Only the issuer's own list
+        store
+            .record(&info("0E3F", "third.example", expires), "PEM")
+            .unwrap();
+        let mut root_crl = RevocationList::new("Root");
+        assert!(store
+            .mark_revoked("0E3F", RevocationReason::Superseded, &mut root_crl)
+            .is_err());
+        assert!(root_crl.is_empty());
+    }
+
+    #[test]
+    fn test_expiring() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = CertStore::open(dir.path().join("certs.json"));
+        let now = Utc::now();
+        let window = Duration::days(30);
+
+        for (serial, not_after) in [
+            ("01", now - Duration::seconds(1)),
+            ("02", now),
+            ("03", now + Duration::days(10)),
+            ("04", now + window),
+            ("05", now + window + Duration::seconds(1)),
+            ("06", now + Duration::days(5)),
+        ] {
+            store
+                .record(&info(serial, "host.example", not_after), "PEM")
+                .unwrap();
+        }
+        let mut crl = RevocationList::new("Intermediate");
+        store
+            .mark_revoked("06", RevocationReason::Superseded, &mut crl)
+            .unwrap();
+
+        // Both ends of the window count; expired and revoked ones don't
+        let serials: Vec<_> = store
+            .expiring(now, window)
+            .unwrap()
+            .into_iter()
+            .map(|cert| cert.info.serial)
+            .collect();
+        assert_eq!(serials, ["02", "03", "04"]);
+        assert!(store.expiring(now, Duration::zero()).unwrap().len() == 1);
+        assert!(store
+            .expiring_within(Duration::days(365))
+            .unwrap()
+            .iter()
+            .all(|cert| cert.info.serial != "01"));
+    }
+
+    #[test]
+    fn test_concurrent_records() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("certs.json");
+        let expires = Utc::now() + Duration::days(30);
+
+        let threads: Vec<_> = (0..8)
+            .map(|i| {
+                let store = CertStore::open(&path);
+                std::thread::spawn(move || {
+                    for j in 0..10 {
+                        let serial = format!("{:02X}{:02X}", i + 1, j);
+                        store
+                            .record(&info(&serial, "host.example", expires), "PEM")
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(CertStore::open(&path).all().unwrap().len(), 80);
+    }
+}
@@ -0,0 +1,484 @@
+//! Certificate chain verification.
+//!
+//! [`verify_chain`] answers "is this certificate good right now, as far as
+//! our CA is concerned": it finds the path from a leaf through the
+//! intermediates to our root, and checks every signature, validity window
+//! and CA constraint on the way, and the CRL.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use x509_parser::certificate::X509Certificate;
+use x509_parser::extensions::{GeneralName, KeyUsage};
+use x509_parser::time::ASN1Time;
+use x509_parser::x509::X509Name;
+
+use crate::serial::to_hex;
+use crate::{CaError, CertificateType, RevocationList};
+
+/// One certificate on a verified path.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChainLink {
+    /// Subject common name
+    pub subject: String,
+    /// Issuer common name
+    pub issuer: String,
+    /// Serial number (hex)
+    pub serial: String,
+    /// Not valid before
+    pub not_before: DateTime<Utc>,
+    /// Not valid after
+    pub not_after: DateTime<Utc>,
+    /// Where it sits in the chain
+    pub cert_type: CertificateType,
+}
+
+/// A chain that verified.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerifiedChain {
+    /// The path, leaf first and root last
+    pub path: Vec<ChainLink>,
+    /// DNS names in the leaf's subject alternative names
+    pub dns_sans: Vec<String>,
+    /// IP addresses in the leaf's subject alternative names
+    pub ip_sans: Vec<IpAddr>,
+    /// Time the chain was verified for
+    pub verified_at: DateTime<Utc>,
+}
+
+impl VerifiedChain {
+    /// The leaf certificate.
+    pub fn leaf(&self) -> &ChainLink {
+        &self.path[0]
+    }
+}
+
+/// Verify a certificate chain as of `at`.
+///
+/// The path runs from the first certificate in `leaf_pem`, through any of
+/// the certificates in `intermediates` (each may hold several, in any
+/// order, and unused ones are ignored), to `root_pem`. Every certificate
+/// on it must be signed by the next and valid at `at`; every one above the
+/// leaf must be a CA allowed to sign that far down; and none issued by the
+/// CA that `crl` belongs to, if given, may be listed in it.
+///
+/// A certificate outside its validity window fails with
+/// [`CaError::Expired`] or [`CaError::NotYetValid`], a revoked one with
+/// [`CaError::Revoked`] and its serial, and anything else wrong with the
+/// chain with [`CaError::InvalidChain`].
+pub fn verify_chain(
+    leaf_pem: &str,
+    intermediates: &[&str],
+    root_pem: &str,
+    crl: Option<&RevocationList>,
+    at: DateTime<Utc>,
+) -> Result<VerifiedChain, CaError> {
+    let leaf_der = first_der(leaf_pem)?;
+    let root_der = first_der(root_pem)?;
+    let mut pool_der = Vec::new();
+    for pem in intermediates {
+        pool_der.extend(ders(pem)?);
+    }
+
+    let leaf = parse(&leaf_der)?;
+    let root = parse(&root_der)?;
+    let pool = pool_der
+        .iter()
+        .map(|der| parse(der))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if root.verify_signature(None).is_err() {
+        return Err(CaError::InvalidChain(format!(
+            "root '{}' is not self-signed",
+            name(root.subject())
+        )));
+    }
+
+    // Walk up from the leaf, each intermediate used at most once
+    let mut path = vec![&leaf];
+    let mut used = vec![false; pool.len()];
+    loop {
+        let current = path[path.len() - 1];
+        if issued_by(current, &root) {
+            path.push(&root);
+            break;
+        }
+        let next = (0..pool.len()).find(|&i| !used[i] && issued_by(current, &pool[i]));
+        let Some(i) = next else {
+            return Err(CaError::InvalidChain(format!(
+                "'{}' does not chain to '{}'",
+                name(current.subject()),
+                name(root.subject())
+            )));
+        };
+        used[i] = true;
+        path.push(&pool[i]);
+    }
+
+    let when = at.timestamp();
+    for cert in &path {
+        if cert.validity().not_before.timestamp() > when {
+            return Err(CaError::NotYetValid);
+        }
+        if cert.validity().not_after.timestamp() < when {
+            return Err(CaError::Expired);
+        }
+    }
+
+    if leaf.is_ca() {
+        return Err(CaError::InvalidChain(format!(
+            "'{}' is a CA, not a leaf",
+            name(leaf.subject())
+        )));
+    }
+    if key_usage(&leaf)?.is_some_and(|usage| !usage.digital_signature()) {
+        return Err(CaError::InvalidChain(format!(
+            "'{}' may not be used for signatures",
+            name(leaf.subject())
+        )));
+    }
+    for (below, ca) in path[1..].iter().enumerate() {
+        let constraints = ca
+            .basic_constraints()
+            .map_err(|e| CaError::Parsing(e.to_string()))?
+            .map(|ext| ext.value);
+        match constraints {
+            Some(constraints) if constraints.ca => {
+                // Path length counts the CAs between this one and the leaf
+                if let Some(max) = constraints.path_len_constraint {
+                    if below > max as usize {
+                        return Err(CaError::InvalidChain(format!(
+                            "'{}' may only have {} CAs below it",
+                            name(ca.subject()),
+                            max
+                        )));
+                    }
+                }
+            }
+            _ => {
+                return Err(CaError::InvalidChain(format!(
+                    "'{}' is not a CA",
+                    name(ca.subject())
+                )));
+            }
+        }
+        if key_usage(ca)?.is_some_and(|usage| !usage.key_cert_sign()) {
+            return Err(CaError::InvalidChain(format!(
+                "'{}' may not sign certificates",
+                name(ca.subject())
+            )));
+        }
+    }
+
+    if let Some(crl) = crl {
+        for cert in &path[..path.len() - 1] {
+            if name(cert.issuer()) != crl.issuer {
+                continue;
+            }
+            let serial = to_hex(cert.raw_serial());
+            if crl.is_revoked(&serial) {
+                return Err(CaError::Revoked(serial));
+            }
+        }
+    }
+
+    let last = path.len() - 1;
+    let links = path
+        .iter()
+        .enumerate()
+        .map(|(i, cert)| {
+            let cert_type = match i {
+                0 => CertificateType::EndEntity,
+                i if i == last => CertificateType::Root,
+                _ => CertificateType::Intermediate,
+            };
+            link(cert, cert_type)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut dns_sans = Vec::new();
+    let mut ip_sans = Vec::new();
+    if let Some(sans) = leaf
+        .subject_alternative_name()
+        .map_err(|e| CaError::Parsing(e.to_string()))?
+    {
+        for san in &sans.value.general_names {
+            match san {
+                GeneralName::DNSName(name) => dns_sans.push(name.to_string()),
+                GeneralName::IPAddress(octets) => ip_sans.extend(ip_addr(octets)),
+                _ => {}
+            }
+        }
+    }
+
+    Ok(VerifiedChain {
+        path: links,
+        dns_sans,
+        ip_sans,
+        verified_at: at,
+    })
+}
+
+/// Whether `ca` issued and signed `cert`.
+fn issued_by(cert: &X509Certificate<'_>, ca: &X509Certificate<'_>) -> bool {
+    cert.issuer().as_raw() == ca.subject().as_raw()
+        && cert.verify_signature(Some(ca.public_key())).is_ok()
+}
+
+fn key_usage<'a>(cert: &'a X509Certificate<'_>) -> Result<Option<&'a KeyUsage>, CaError> {
+    Ok(cert
+        .key_usage()
+        .map_err(|e| CaError::Parsing(e.to_string()))?
+        .map(|ext| ext.value))
+}
+
+fn link(cert: &X509Certificate<'_>, cert_type: CertificateType) -> Result<ChainLink, CaError> {
+    Ok(ChainLink {
+        subject: name(cert.subject()),
+        issuer: name(cert.issuer()),
+        serial: to_hex(cert.raw_serial()),
+        not_before: utc(cert.validity().not_before)?,
+        not_after: utc(cert.validity().not_after)?,
+        cert_type,
+    })
+}
+
+/// The common name, or the whole name if it has none.
+fn name(name: &X509Name<'_>) -> String {
+    name.iter_common_name()
+        .next()
+        .and_then(|cn| cn.as_str().ok())
+        .map_or_else(|| name.to_string(), String::from)
+}
+
+fn ip_addr(octets: &[u8]) -> Option<IpAddr> {
+    match octets.len() {
+        4 => Some(IpAddr::V4(Ipv4Addr::from(
+            <[u8; 4]>::try_from(octets).ok()?,
+        ))),
+        16 => Some(IpAddr::V6(Ipv6Addr::from(
+            <[u8; 16]>::try_from(octets).ok()?,
+        ))),
+        _ => None,
+    }
+}
+
+fn utc(at: ASN1Time) -> Result<DateTime<Utc>, CaError> {
+    Utc.timestamp_opt(at.timestamp(), 0)
+        .single()
+        .ok_or_else(|| CaError::Parsing(format!("time out of range: {}", at)))
+}
+
+fn parse(der: &[u8]) -> Result<X509Certificate<'_>, CaError> {
+    x509_parser::parse_x509_certificate(der)
+        .map(|(_, cert)| cert)
+        .map_err(|e| CaError::Parsing(e.to_string()))
+}
+
+/// Every certificate in a PEM bundle, as DER.
+fn ders(pem: &str) -> Result<Vec<Vec<u8>>, CaError> {
+    Ok(pem::parse_many(pem)
+        .map_err(|e| CaError::Pem(e.to_string()))?
+        .into_iter()
+        .filter(|block| block.tag() == "CERTIFICATE")
+        .map(pem::Pem::into_contents)
+        .collect())
+}
+
+fn first_der(pem: &str) -> Result<Vec<u8>, CaError> {
+    ders(pem)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| CaError::Pem("no certificate found".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        IntermediateCa, KeyAlgorithm, LeafCertificate, LeafParams, RevocationReason, RootCa,
+    };
+    use chrono::Duration;
+
+    struct Chain {
+        root: RootCa,
+        intermediate: IntermediateCa,
+        leaf: LeafCertificate,
+    }
+
+    fn chain() -> Chain {
+        let root = RootCa::generate("Root", KeyAlgorithm::EcdsaP256).unwrap();
+        let intermediate =
+            IntermediateCa::generate("Intermediate", &root, KeyAlgorithm::EcdsaP256).unwrap();
+        let params = LeafParams::new("localhost")
+            .dns("localhost")
+            .ip("127.0.0.1".parse().unwrap());
+        let leaf = LeafCertificate::issue(&intermediate, &params).unwrap();
+        Chain {
+            root,
+            intermediate,
+            leaf,
+        }
+    }
+
+    #[test]
+    fn test_good_chain() {
+        let Chain {
+            root,
+            intermediate,
+            leaf,
+        } = chain();
+        let other = RootCa::generate("Other", KeyAlgorithm::EcdsaP256).unwrap();
+
+        // A CRL that lists someone else changes nothing
+        let mut crl = RevocationList::new("Intermediate");
+        crl.revoke("0102030405060708", RevocationReason::KeyCompromise);
+
+        let now = Utc::now();
+        let verified = verify_chain(
+            &leaf.chain_pem,
+            &[other.certificate_pem(), intermediate.chain_pem()],
+            root.certificate_pem(),
+            Some(&crl),
+            now,
+        )
+        .unwrap();
+
+        let subjects: Vec<_> = verified.path.iter().map(|l| l.subject.as_str()).collect();
+        assert_eq!(subjects, ["localhost", "Intermediate", "Root"]);
+        let types: Vec<_> = verified.path.iter().map(|l| l.cert_type).collect();
+        assert_eq!(
+            types,
+            [
+                CertificateType::EndEntity,
+                CertificateType::Intermediate,
+                CertificateType::Root
+            ]
+        );
+        assert_eq!(verified.leaf().serial, leaf.info.serial);
+        assert_eq!(verified.path[1].serial, intermediate.info.serial);
+        assert_eq!(verified.leaf().issuer, "Intermediate");
+        assert_eq!(verified.dns_sans, ["localhost"]);
+        assert_eq!(verified.ip_sans, ["127.0.0.1".parse::<IpAddr>().unwrap()]);
+        assert_eq!(verified.verified_at, now);
+
+        // Straight from the root
+        let params = LeafParams::new("direct").dns("direct.example.com");
+        let direct = LeafCertificate::issue(&root, &params).unwrap();
+        let verified =
+            verify_chain(&direct.cert_pem, &[], root.certificate_pem(), None, now).unwrap();
+        assert_eq!(verified.path.len(), 2);
+    }
+
+    #[test]
+    fn test_validity() {
+        let Chain {
+            root,
+            intermediate,
+            leaf,
+        } = chain();
+        let verify = |at| {
+            verify_chain(
+                &leaf.cert_pem,
+                &[intermediate.chain_pem()],
+                root.certificate_pem(),
+                None,
+                at,
+            )
+        };
+
+        // The leaf is good for a day
+        assert!(matches!(
+            verify(Utc::now() + Duration::days(2)),
+            Err(CaError::Expired)
+        ));
+        assert!(matches!(
+            verify(Utc::now() - Duration::days(1)),
+            Err(CaError::NotYetValid)
+        ));
+    }
+
+    #[test]
+    fn test_revoked() {
+        let Chain {
+            root,
+            intermediate,
+            leaf,
+        } = chain();
+        let verify = |crl: &RevocationList| {
+            verify_chain(
+                &leaf.cert_pem,
+                &[intermediate.chain_pem()],
+                root.certificate_pem(),
+                Some(crl),
+                Utc::now(),
+            )
+        };
+
+        let mut root_crl = RevocationList::new("Root");
+        root_crl.revoke(&intermediate.info.serial, RevocationReason::CaCompromise);
+        match verify(&root_crl) {
+            Err(CaError::Revoked(serial)) => assert_eq!(serial, intermediate.info.serial),
+            other => panic!("expected Revoked, got {:?}", other),
+        }
+
+        let mut crl = RevocationList::new("Intermediate");
+        crl.revoke(&leaf.info.serial, RevocationReason::KeyCompromise);
+        match verify(&crl) {
+            Err(CaError::Revoked(serial)) => assert_eq!(serial, leaf.info.serial),
+            other => panic!("expected Revoked, got {:?}", other),
+        }
+
+        // Another CA's list says nothing about ours
+        let mut elsewhere = RevocationList::new("Other");
+        elsewhere.revoke(&leaf.info.serial, RevocationReason::KeyCompromise);
+        assert!(verify(&elsewhere).is_ok());
+    }
+
+    #[test]
+    fn test_wrong_root() {
+        let Chain {
+            root,
+            intermediate,
+            leaf,
+        } = chain();
+
+        // Same name, different key
+        let impostor = RootCa::generate("Root", KeyAlgorithm::EcdsaP256).unwrap();
+        assert!(matches!(
+            verify_chain(
+                &leaf.cert_pem,
+                &[intermediate.chain_pem()],
+                impostor.certificate_pem(),
+                None,
+                Utc::now(),
+            ),
+            Err(CaError::InvalidChain(_))
+        ));
+
+        // Missing the intermediate
+        assert!(matches!(
+            verify_chain(
+                &leaf.cert_pem,
+                &[],
+                root.certificate_pem(),
+                None,
+                Utc::now()
+            ),
+            Err(CaError::InvalidChain(_))
+        ));
+
+        // An intermediate is no leaf
+        assert!(matches!(
+            verify_chain(
+                intermediate.chain_pem(),
+                &[],
+                root.certificate_pem(),
+                None,
+                Utc::now()
+            ),
+            Err(CaError::InvalidChain(_))
+        ));
+    }
+}
@@ -20,11 +20,11 @@ use std::sync::Arc;
 use std::time::Instant;
 
 use async_trait::async_trait;
-use governor::{Quota, RateLimiter};
-use i1_core::{GeoLocation, HostInfo, I1Error, Result, Service};
+use governor::Quota;
+use i1_core::{cpe, validate_ip, GeoLocation, HostInfo, I1Error, Result, Service};
 use i1_providers::{
-    AuthConfig, HealthStatus, HostLookup, Provider, ProviderHealth, RateLimitConfig,
-    SearchProvider, SearchResults,
+    schema, AuthConfig, HealthStatus, HostLookup, Provider, ProviderHealth, ProviderHttpConfig,
+    RateLimitConfig, RateLimiter, SearchProvider, SearchResults,
 };
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
@@ -36,18 +36,27 @@ const DEFAULT_BASE_URL: &str = "https://search.censys.io/api/v2";
 /// Censys provider for i1
 pub struct CensysProvider {
     inner: Arc<CensysInner>,
+    /// Keep each host's original JSON in `HostInfo::raw`
+    include_raw: bool,
 }
 
+#[derive(Clone)]
 struct CensysInner {
     http: Client,
+    http_config: ProviderHttpConfig,
     api_id: String,
     api_secret: String,
     base_url: String,
-    rate_limiter: RateLimiter<
-        governor::state::NotKeyed,
-        governor::state::InMemoryState,
-        governor::clock::DefaultClock,
-    >,
+    rate_limiter: Arc<RateLimiter>,
+}
+
+/// How often a provider with `rate_limit` may call the API
+fn quota(rate_limit: &RateLimitConfig) -> Quota {
+    Quota::per_second(
+        NonZeroU32::new((rate_limit.requests_per_second.max(0.1) * 10.0) as u32)
+            .unwrap_or(NonZeroU32::MIN),
+    )
+    .allow_burst(NonZeroU32::new(rate_limit.burst_size).unwrap_or(NonZeroU32::MIN))
 }
 
 impl CensysProvider {
@@ -56,29 +65,66 @@ impl CensysProvider {
         Self::with_config(api_id, api_secret, RateLimitConfig::censys())
     }
 
+    /// Create a provider that shares its rate limiter with every other
+    /// `new_shared` instance for the same API ID. Providers made with
+    /// [`new`](Self::new) keep a limiter of their own.
+    pub fn new_shared(api_id: impl Into<String>, api_secret: impl Into<String>) -> Self {
+        let api_id = api_id.into();
+        let quota = quota(&RateLimitConfig::censys());
+        let rate_limiter = i1_providers::shared_limiter("censys", &api_id, quota);
+        Self::with_limiter(api_id, api_secret.into(), rate_limiter)
+    }
+
     /// Create with custom rate limit config
     pub fn with_config(
         api_id: impl Into<String>,
         api_secret: impl Into<String>,
         rate_limit: RateLimitConfig,
     ) -> Self {
-        let quota = Quota::per_second(
-            NonZeroU32::new((rate_limit.requests_per_second.max(0.1) * 10.0) as u32)
-                .unwrap_or(NonZeroU32::MIN),
-        )
-        .allow_burst(NonZeroU32::new(rate_limit.burst_size).unwrap_or(NonZeroU32::MIN));
+        let rate_limiter = Arc::new(RateLimiter::direct(quota(&rate_limit)));
+        Self::with_limiter(api_id.into(), api_secret.into(), rate_limiter)
+    }
 
+    fn with_limiter(api_id: String, api_secret: String, rate_limiter: Arc<RateLimiter>) -> Self {
         Self {
             inner: Arc::new(CensysInner {
                 http: Client::new(),
-                api_id: api_id.into(),
-                api_secret: api_secret.into(),
+                http_config: ProviderHttpConfig::default(),
+                api_id,
+                api_secret,
                 base_url: DEFAULT_BASE_URL.to_string(),
-                rate_limiter: RateLimiter::direct(quota),
+                rate_limiter,
             }),
+            include_raw: false,
         }
     }
 
+    /// Send requests to another API endpoint, such as a proxy or a mock
+    /// server. Clones made before this call are not affected
+    #[must_use]
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        Arc::make_mut(&mut self.inner).base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Connect with these HTTP settings, e.g. through a proxy. Clones made
+    /// before this call are not affected
+    pub fn with_http_config(mut self, config: &ProviderHttpConfig) -> Result<Self> {
+        let http = config.client()?;
+        let inner = Arc::make_mut(&mut self.inner);
+        inner.http = http;
+        inner.http_config = config.clone();
+        Ok(self)
+    }
+
+    /// Keep Censys's original JSON on hosts it looks up, in
+    /// `HostInfo::raw`. Clones made before this call are not affected
+    #[must_use]
+    pub const fn include_raw(mut self, include: bool) -> Self {
+        self.include_raw = include;
+        self
+    }
+
     /// Get authentication config for this provider
     pub fn auth_config(&self) -> AuthConfig {
         AuthConfig::censys(&self.inner.api_id, &self.inner.api_secret)
@@ -100,7 +146,7 @@ impl CensysProvider {
             .basic_auth(&self.inner.api_id, Some(&self.inner.api_secret))
             .send()
             .await
-            .map_err(|e| I1Error::Http(e.to_string()))?;
+            .map_err(|e| self.inner.http_config.request_error(&e))?;
 
         let status = response.status();
         if !status.is_success() {
@@ -117,10 +163,11 @@ impl CensysProvider {
             };
         }
 
-        response
+        let value = response
             .json()
             .await
-            .map_err(|e| I1Error::Http(e.to_string()))
+            .map_err(|e| I1Error::Http(e.to_string()))?;
+        schema::parse("censys", endpoint, &value)
     }
 
     /// Make a POST request to the Censys API
@@ -143,7 +190,7 @@ impl CensysProvider {
             .json(body)
             .send()
             .await
-            .map_err(|e| I1Error::Http(e.to_string()))?;
+            .map_err(|e| self.inner.http_config.request_error(&e))?;
 
         let status = response.status();
         if !status.is_success() {
@@ -157,10 +204,11 @@ impl CensysProvider {
             };
         }
 
-        response
+        let value = response
             .json()
             .await
-            .map_err(|e| I1Error::Http(e.to_string()))
+            .map_err(|e| I1Error::Http(e.to_string()))?;
+        schema::parse("censys", endpoint, &value)
     }
 
     /// Convert Censys host response to i1 `HostInfo`
@@ -168,31 +216,40 @@ impl CensysProvider {
         let services: Vec<Service> = host
             .services
             .into_iter()
-            .map(|s| Service {
-                port: s.port,
-                transport: i1_core::Transport::from_str(&s.transport_protocol),
-                product: s
-                    .software
-                    .as_ref()
-                    .and_then(|sw| sw.first().map(|s| s.product.clone()))
-                    .flatten(),
-                version: s
-                    .software
-                    .as_ref()
-                    .and_then(|sw| sw.first().map(|s| s.version.clone()))
-                    .flatten(),
-                cpe: vec![],
-                data: s.banner,
-                timestamp: None,
-                shodan_module: None,
-                http: None,
-                ssl: None,
-                ssh: None,
-                vulns: std::collections::HashMap::new(),
-                tags: vec![],
-                devicetype: None,
-                info: None,
-                os: None,
+            .map(|s| {
+                let mut service = Service {
+                    port: s.port,
+                    transport: i1_core::Transport::from_str(&s.transport_protocol),
+                    product: s
+                        .software
+                        .as_ref()
+                        .and_then(|sw| sw.first().map(|s| s.product.clone()))
+                        .flatten(),
+                    version: s
+                        .software
+                        .as_ref()
+                        .and_then(|sw| sw.first().map(|s| s.version.clone()))
+                        .flatten(),
+                    cpe: s
+                        .software
+                        .iter()
+                        .flatten()
+                        .filter_map(|sw| sw.uniform_resource_identifier.clone())
+                        .collect(),
+                    data: s.banner,
+                    timestamp: None,
+                    shodan_module: None,
+                    http: None,
+                    ssl: None,
+                    ssh: None,
+                    vulns: std::collections::HashMap::new(),
+                    tags: vec![],
+                    devicetype: None,
+                    info: None,
+                    os: None,
+                };
+                cpe::fill(&mut service);
+                service
             })
             .collect();
 
@@ -229,6 +286,7 @@ impl CensysProvider {
             },
             data: services,
             last_update: host.last_updated_at,
+            raw: None,
         }
     }
 }
@@ -237,6 +295,7 @@ impl Clone for CensysProvider {
     fn clone(&self) -> Self {
         Self {
             inner: Arc::clone(&self.inner),
+            include_raw: self.include_raw,
         }
     }
 }
@@ -264,17 +323,31 @@ impl Provider for CensysProvider {
 
         match self.get::<serde_json::Value>("/account").await {
             Ok(info) => {
-                let quota = info
-                    .get("quota")
-                    .and_then(|q| q.get("remaining"))
-                    .and_then(serde_json::Value::as_i64);
+                let quota = info.get("quota");
+                let remaining = quota.and_then(|q| {
+                    q.get("remaining")
+                        .and_then(serde_json::Value::as_i64)
+                        .or_else(|| {
+                            let allowance = q.get("allowance")?.as_i64()?;
+                            let used = q.get("used")?.as_i64()?;
+                            Some(allowance - used)
+                        })
+                });
+                let resets_at = quota
+                    .and_then(|q| q.get("resets_at"))
+                    .and_then(serde_json::Value::as_str)
+                    .and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok())
+                    .map(|d| d.with_timezone(&chrono::Utc));
 
                 Ok(ProviderHealth {
                     provider: "censys".to_string(),
                     status: HealthStatus::Healthy,
                     latency_ms: Some(start.elapsed().as_millis() as u64),
-                    credits_remaining: quota,
+                    credits_remaining: remaining,
                     message: None,
+                    plan: None,
+                    scan_credits: None,
+                    resets_at,
                 })
             }
             Err(I1Error::Unauthorized) => Ok(ProviderHealth {
@@ -283,6 +356,9 @@ impl Provider for CensysProvider {
                 latency_ms: Some(start.elapsed().as_millis() as u64),
                 credits_remaining: None,
                 message: Some("Invalid API credentials".to_string()),
+                plan: None,
+                scan_credits: None,
+                resets_at: None,
             }),
             Err(e) => Ok(ProviderHealth {
                 provider: "censys".to_string(),
@@ -290,6 +366,9 @@ impl Provider for CensysProvider {
                 latency_ms: Some(start.elapsed().as_millis() as u64),
                 credits_remaining: None,
                 message: Some(e.to_string()),
+                plan: None,
+                scan_credits: None,
+                resets_at: None,
             }),
         }
     }
@@ -299,8 +378,17 @@ impl Provider for CensysProvider {
 impl HostLookup for CensysProvider {
     #[instrument(skip(self), fields(provider = "censys"))]
     async fn lookup_host(&self, ip: &str) -> Result<HostInfo> {
-        let response: CensysHostResponse = self.get(&format!("/hosts/{ip}")).await?;
-        Ok(Self::convert_host(response.result))
+        let ip = validate_ip(ip)?;
+        let endpoint = format!("/hosts/{ip}");
+        let raw: serde_json::Value = self.get(&endpoint).await?;
+        let mut host =
+            schema::recover_host("censys", &endpoint, &raw, |response: CensysHostResponse| {
+                Self::convert_host(response.result)
+            })?;
+        if self.include_raw {
+            host.raw = Some(Box::new(raw));
+        }
+        Ok(host)
     }
 }
 
@@ -403,6 +491,9 @@ struct CensysService {
 struct CensysSoftware {
     product: Option<String>,
     version: Option<String>,
+    /// CPE 2.3
+    #[serde(default)]
+    uniform_resource_identifier: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -457,3 +548,39 @@ struct CensysAggregateResponse {
 struct CensysAggregateResult {
     total: usize,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{basic_auth, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const IPV6: &str = include_str!("../../i1-core/tests/fixtures/ipv6.txt");
+
+    #[tokio::test]
+    async fn test_lookup_ipv6() {
+        for line in IPV6
+            .lines()
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        {
+            let (input, canonical) = line.split_once('\t').unwrap();
+            let server = MockServer::start().await;
+            Mock::given(method("GET"))
+                .and(path(format!("/hosts/{canonical}")))
+                .and(basic_auth("test-id", "test-secret"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                    "result": { "ip": canonical, "services": [] }
+                })))
+                .expect(1)
+                .mount(&server)
+                .await;
+            let provider =
+                CensysProvider::new("test-id", "test-secret").with_base_url(server.uri());
+
+            let host = provider.lookup_host(input).await.unwrap();
+            assert_eq!(host.ip_str, canonical, "{input}");
+            assert_eq!(host.ip, canonical.parse().ok(), "{input}");
+        }
+    }
+}
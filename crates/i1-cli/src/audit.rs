@@ -0,0 +1,72 @@
+//! The audit log: every provider call and defend change, one JSON line each.
+//!
+//! Off unless `audit_log` is set in the config file or `--audit` is given,
+//! in which case it goes to `audit.jsonl` in the config directory. Provider
+//! calls are recorded by wrapping the providers commands get from the
+//! [`Context`](crate::cli::commands::Context); defend changes are recorded
+//! as they are journaled. `i1 audit tail` and `i1 audit summary` read it.
+
+use std::path::PathBuf;
+use std::sync::{PoisonError, RwLock};
+
+use anyhow::Result;
+use directories::ProjectDirs;
+use i1::AuditLog;
+
+use crate::cli::args::Cli;
+use crate::config::Config;
+
+/// The log this process writes to, if any
+static LOG: RwLock<Option<AuditLog>> = RwLock::new(None);
+
+/// Where the log goes when `audit_log` isn't set.
+pub fn default_path() -> Result<PathBuf> {
+    let dirs = ProjectDirs::from("is", "i1", "i1")
+        .ok_or_else(|| anyhow::anyhow!("Could not determine config directory"))?;
+
+    Ok(dirs.config_dir().join("audit.jsonl"))
+}
+
+/// The log `file` and `cli` ask for, whether or not it is turned on.
+pub fn configured(file: &Config) -> Result<AuditLog> {
+    let path = match &file.audit_log {
+        Some(path) => path.clone(),
+        None => default_path()?,
+    };
+    Ok(AuditLog::new(path).hash_targets(file.audit_hash_targets))
+}
+
+/// Open the log for this run if `file` or `cli` turn it on, or close it.
+pub fn open(cli: &Cli, file: &Config) -> Result<()> {
+    let log = if cli.audit || file.audit_log.is_some() {
+        Some(configured(file)?)
+    } else {
+        None
+    };
+    *LOG.write().unwrap_or_else(PoisonError::into_inner) = log;
+    Ok(())
+}
+
+/// The open log, if auditing is on.
+pub fn log() -> Option<AuditLog> {
+    LOG.read().unwrap_or_else(PoisonError::into_inner).clone()
+}
+
+/// Wait for the calls observed so far to be written. The log stays open
+/// for the life of the process, so nothing else waits for them.
+pub fn flush() {
+    if let Some(log) = log() {
+        log.flush();
+    }
+}
+
+/// Record a change made to `targets`. A log that can't be written is
+/// reported but doesn't undo or fail the change.
+pub fn action(operation: &str, targets: &[String]) {
+    let Some(log) = log() else {
+        return;
+    };
+    if let Err(e) = log.record(&i1::AuditEntry::action(operation, targets.to_vec())) {
+        eprintln!("Warning: {e}");
+    }
+}
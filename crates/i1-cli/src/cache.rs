@@ -0,0 +1,630 @@
+//! On-disk cache of provider answers for `host` and `search`.
+//!
+//! Every answer is a JSON file in the cache directory, at
+//! `<provider>/host-<ip>.json` or `<provider>/search-<hash>.json`, stamped
+//! with when it was fetched. Answers younger than [`TTL`] are served without
+//! a request. `--refresh` fetches again and overwrites the entry, and
+//! `--no-cache` neither reads nor writes. Hits and misses are tallied in
+//! `stats.json` for `i1 cache stats`. `i1 cache grep` searches the banners
+//! of every host it holds, however old.
+//!
+//! Multi-page searches (`--limit`, `--all-pages`) and `--watch` always ask
+//! the provider.
+//!
+//! While an investigation workspace is active, hosts and search pages it
+//! already holds are answered from it before the cache is read, unless
+//! `--refresh`, and every answer is added to it; see [`crate::workspace`].
+//!
+//! Health checks that report remaining credits are kept as
+//! `<provider>/health.json` for [`HEALTH_TTL`], so `--dry-run` can quote a
+//! balance without asking again.
+//!
+//! The cache is best-effort: an entry that can't be read or written is
+//! treated as missing, never as an error.
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use directories::ProjectDirs;
+use i1::{HostInfo, ProviderHealth, SearchResults};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::workspace::{self, Kind, Workspace};
+
+/// How long an answer is served from the cache
+pub const TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How long a provider's remaining credits are quoted from the cache
+pub const HEALTH_TTL: Duration = Duration::from_secs(10 * 60);
+
+const STATS_FILE: &str = "stats.json";
+
+const HEALTH_FILE: &str = "health";
+
+/// How a command uses the cache, from `--no-cache` and `--refresh`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Mode {
+    /// Serve fresh entries, store new answers
+    #[default]
+    Use,
+    /// Always fetch, store the new answer
+    Refresh,
+    /// Neither read nor write
+    Off,
+}
+
+/// One stored answer.
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry<T> {
+    fetched_at: DateTime<Utc>,
+    /// What was asked, e.g. the IP or `query (page 2)`
+    key: String,
+    value: T,
+}
+
+/// An answer and, when it came from the cache, when it was fetched.
+#[derive(Debug)]
+pub struct Cached<T> {
+    pub value: T,
+    pub cached_at: Option<DateTime<Utc>>,
+}
+
+/// Hit and miss counts, kept across runs.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Tally {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// What `i1 cache stats` reports.
+#[derive(Debug, Default, Serialize)]
+pub struct Usage {
+    pub path: PathBuf,
+    pub entries: usize,
+    pub bytes: u64,
+    /// Entries and bytes per provider
+    pub providers: BTreeMap<String, (usize, u64)>,
+    pub hits: u64,
+    pub misses: u64,
+    /// Hits as a percentage of lookups, once there have been any
+    pub hit_rate: Option<f64>,
+}
+
+/// Get the cache directory.
+pub fn dir() -> Result<PathBuf> {
+    let dirs = ProjectDirs::from("is", "i1", "i1")
+        .ok_or_else(|| anyhow::anyhow!("Could not determine cache directory"))?;
+
+    Ok(dirs.cache_dir().to_path_buf())
+}
+
+/// The cache as one command run sees it.
+#[derive(Debug)]
+pub struct Cache {
+    /// `None` with `--no-cache`, or when there's no cache directory
+    dir: Option<PathBuf>,
+    mode: Mode,
+    /// The active workspace, answered from and added to
+    workspace: Option<Workspace>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl Cache {
+    pub fn open(mode: Mode) -> Self {
+        let dir = if mode == Mode::Off { None } else { dir().ok() };
+        Self::at(dir, mode).in_workspace(workspace::active())
+    }
+
+    pub const fn at(dir: Option<PathBuf>, mode: Mode) -> Self {
+        Self {
+            dir,
+            mode,
+            workspace: None,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Answer from `workspace` and keep every answer in it.
+    #[must_use]
+    pub fn in_workspace(mut self, workspace: Option<Workspace>) -> Self {
+        self.workspace = workspace;
+        self
+    }
+
+    /// A host lookup, from the workspace, the cache or `fetch`.
+    pub async fn host<F, Fut>(
+        &self,
+        provider: &str,
+        ip: &str,
+        fetch: F,
+    ) -> i1::Result<Cached<HostInfo>>
+    where
+        F: FnOnce() -> Fut + Send,
+        Fut: Future<Output = i1::Result<HostInfo>> + Send,
+    {
+        if let Some(kept) = self.kept(|ws| ws.host(provider, ip)) {
+            return Ok(kept);
+        }
+        let name = format!("host-{}", ip.replace(':', "_"));
+        let answer = self.get_or_fetch(provider, &name, ip, fetch).await?;
+        self.keep(|ws| ws.record_host(Kind::Host, provider, &answer.value));
+        Ok(answer)
+    }
+
+    /// A page of search results, from the workspace, the cache or `fetch`.
+    pub async fn search<F, Fut>(
+        &self,
+        provider: &str,
+        query: &str,
+        page: u32,
+        fetch: F,
+    ) -> i1::Result<Cached<SearchResults>>
+    where
+        F: FnOnce() -> Fut + Send,
+        Fut: Future<Output = i1::Result<SearchResults>> + Send,
+    {
+        let key = search_key(query, page);
+        if let Some(kept) = self.kept(|ws| ws.search(provider, &key)) {
+            return Ok(kept);
+        }
+        let name = format!("search-{:016x}", fnv1a(key.as_bytes()));
+        let answer = self.get_or_fetch(provider, &name, &key, fetch).await?;
+        self.keep(|ws| ws.record_search(provider, &key, &answer.value));
+        Ok(answer)
+    }
+
+    /// A host from the workspace or fresh in the cache, without fetching.
+    /// Counts a cache hit or miss.
+    pub fn cached_host(&self, provider: &str, ip: &str) -> Option<Cached<HostInfo>> {
+        if let Some(kept) = self.kept(|ws| ws.host(provider, ip)) {
+            return Some(kept);
+        }
+        let name = format!("host-{}", ip.replace(':', "_"));
+        let cached = self.fresh(provider, &name, TTL);
+        self.count(cached.is_some());
+        if let Some(cached) = &cached {
+            self.keep(|ws| ws.record_host(Kind::Host, provider, &cached.value));
+        }
+        cached
+    }
+
+    /// Store a host fetched outside [`Cache::host`].
+    pub fn store_host(&self, provider: &str, host: &HostInfo) {
+        let name = format!("host-{}", host.ip_str.replace(':', "_"));
+        self.store(provider, &name, &host.ip_str, host);
+        self.keep(|ws| ws.record_host(Kind::Host, provider, host));
+    }
+
+    /// An answer the workspace holds, unless `--refresh`.
+    fn kept<T>(&self, find: impl FnOnce(&Workspace) -> Option<Cached<T>>) -> Option<Cached<T>> {
+        if self.mode == Mode::Refresh {
+            return None;
+        }
+        find(self.workspace.as_ref()?)
+    }
+
+    /// Add an answer to the workspace. One that can't be written is
+    /// reported but doesn't fail the lookup.
+    fn keep(&self, record: impl FnOnce(&Workspace) -> Result<()>) {
+        if let Some(workspace) = &self.workspace {
+            if let Err(e) = record(workspace) {
+                eprintln!("Warning: workspace {}: {e}", workspace.name());
+            }
+        }
+    }
+
+    /// A provider's health as checked within [`HEALTH_TTL`], without
+    /// asking it. Not counted as a hit or a miss.
+    pub fn cached_health(&self, provider: &str) -> Option<Cached<ProviderHealth>> {
+        self.fresh(provider, HEALTH_FILE, HEALTH_TTL)
+    }
+
+    /// Remember a health check, if it knew the credits remaining.
+    pub fn store_health(&self, health: &ProviderHealth) {
+        if health.credits_remaining.is_some() {
+            self.store(&health.provider, HEALTH_FILE, &health.provider, health);
+        }
+    }
+
+    async fn get_or_fetch<T, F, Fut>(
+        &self,
+        provider: &str,
+        name: &str,
+        key: &str,
+        fetch: F,
+    ) -> i1::Result<Cached<T>>
+    where
+        T: Serialize + DeserializeOwned,
+        F: FnOnce() -> Fut + Send,
+        Fut: Future<Output = i1::Result<T>> + Send,
+    {
+        if let Some(cached) = self.fresh(provider, name, TTL) {
+            self.count(true);
+            return Ok(cached);
+        }
+        self.count(false);
+
+        let value = fetch().await?;
+        self.store(provider, name, key, &value);
+        Ok(Cached {
+            value,
+            cached_at: None,
+        })
+    }
+
+    fn path(&self, provider: &str, name: &str) -> Option<PathBuf> {
+        Some(
+            self.dir
+                .as_ref()?
+                .join(provider)
+                .join(format!("{name}.json")),
+        )
+    }
+
+    fn fresh<T: DeserializeOwned>(
+        &self,
+        provider: &str,
+        name: &str,
+        ttl: Duration,
+    ) -> Option<Cached<T>> {
+        if self.mode != Mode::Use {
+            return None;
+        }
+        let content = std::fs::read_to_string(self.path(provider, name)?).ok()?;
+        let entry: Entry<T> = serde_json::from_str(&content).ok()?;
+        let age = (Utc::now() - entry.fetched_at).to_std().unwrap_or_default();
+        (age < ttl).then_some(Cached {
+            value: entry.value,
+            cached_at: Some(entry.fetched_at),
+        })
+    }
+
+    fn store<T: Serialize>(&self, provider: &str, name: &str, key: &str, value: &T) {
+        let Some(path) = self.path(provider, name) else {
+            return;
+        };
+        let entry = Entry {
+            fetched_at: Utc::now(),
+            key: key.to_string(),
+            value,
+        };
+        if let (Some(dir), Ok(json)) = (path.parent(), serde_json::to_vec(&entry)) {
+            if std::fs::create_dir_all(dir).is_ok() {
+                std::fs::write(&path, json).ok();
+            }
+        }
+    }
+
+    fn count(&self, hit: bool) {
+        if self.dir.is_none() {
+            return;
+        }
+        let counter = if hit { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl Drop for Cache {
+    /// Add this run's hits and misses to the running tally.
+    fn drop(&mut self) {
+        let (hits, misses) = (*self.hits.get_mut(), *self.misses.get_mut());
+        let Some(dir) = &self.dir else {
+            return;
+        };
+        if hits + misses == 0 {
+            return;
+        }
+        let mut tally = load_tally(dir);
+        tally.hits += hits;
+        tally.misses += misses;
+        if let Ok(json) = serde_json::to_vec(&tally) {
+            if std::fs::create_dir_all(dir).is_ok() {
+                std::fs::write(dir.join(STATS_FILE), json).ok();
+            }
+        }
+    }
+}
+
+fn load_tally(dir: &Path) -> Tally {
+    std::fs::read_to_string(dir.join(STATS_FILE))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// Every entry file, with its provider.
+fn entries(dir: &Path) -> Vec<(String, PathBuf, std::fs::Metadata)> {
+    let Ok(providers) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut found = Vec::new();
+    for provider in providers.flatten() {
+        let Ok(files) = std::fs::read_dir(provider.path()) else {
+            continue;
+        };
+        let name = provider.file_name().to_string_lossy().into_owned();
+        for file in files.flatten() {
+            let path = file.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                if let Ok(meta) = file.metadata() {
+                    found.push((name.clone(), path, meta));
+                }
+            }
+        }
+    }
+    found
+}
+
+/// Entries, size on disk and hit rate of the cache in `dir`.
+pub fn usage(dir: &Path) -> Usage {
+    let mut usage = Usage {
+        path: dir.to_path_buf(),
+        ..Usage::default()
+    };
+
+    for (provider, _, meta) in entries(dir) {
+        let slot = usage.providers.entry(provider).or_default();
+        slot.0 += 1;
+        slot.1 += meta.len();
+        usage.entries += 1;
+        usage.bytes += meta.len();
+    }
+
+    let tally = load_tally(dir);
+    usage.hits = tally.hits;
+    usage.misses = tally.misses;
+    #[allow(clippy::cast_precision_loss)]
+    if tally.hits + tally.misses > 0 {
+        usage.hit_rate = Some(tally.hits as f64 * 100.0 / (tally.hits + tally.misses) as f64);
+    }
+    usage
+}
+
+/// Every host the cache in `dir` holds, fresh or not: those looked up and
+/// those in search pages.
+pub fn hosts(dir: &Path) -> Vec<HostInfo> {
+    let mut hosts = Vec::new();
+    for (_, path, _) in entries(dir) {
+        let Some(name) = path.file_stem().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let Ok(content) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        if name.starts_with("host-") {
+            if let Ok(entry) = serde_json::from_str::<Entry<HostInfo>>(&content) {
+                hosts.push(entry.value);
+            }
+        } else if name.starts_with("search-") {
+            if let Ok(entry) = serde_json::from_str::<Entry<SearchResults>>(&content) {
+                hosts.extend(entry.value.results);
+            }
+        }
+    }
+    hosts
+}
+
+/// Remove entries, optionally only one provider's or only those older than
+/// `older_than`. Returns how many were removed.
+///
+/// Clearing everything also resets the hit rate.
+pub fn clear(dir: &Path, provider: Option<&str>, older_than: Option<Duration>) -> Result<usize> {
+    let now = SystemTime::now();
+    let mut removed = 0;
+
+    for (name, path, meta) in entries(dir) {
+        if provider.is_some_and(|p| p != name) {
+            continue;
+        }
+        if let Some(min_age) = older_than {
+            let age = meta
+                .modified()
+                .ok()
+                .and_then(|at| now.duration_since(at).ok())
+                .unwrap_or_default();
+            if age < min_age {
+                continue;
+            }
+        }
+        std::fs::remove_file(&path)?;
+        removed += 1;
+    }
+
+    if provider.is_none() && older_than.is_none() {
+        let stats = dir.join(STATS_FILE);
+        if stats.exists() {
+            std::fs::remove_file(stats)?;
+        }
+    }
+    Ok(removed)
+}
+
+/// "just now", "5m ago", "2h ago", "3d ago".
+pub fn ago(at: DateTime<Utc>) -> String {
+    let secs = (Utc::now() - at).num_seconds().max(0);
+    match secs {
+        0..=59 => "just now".to_string(),
+        60..=3599 => format!("{}m ago", secs / 60),
+        3600..=86_399 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86_400),
+    }
+}
+
+/// "1 entry, 126 B", "12 entries, 3.4 KiB".
+pub fn describe(entries: usize, bytes: u64) -> String {
+    let noun = if entries == 1 { "entry" } else { "entries" };
+    format!("{entries} {noun}, {}", size(bytes))
+}
+
+/// "512 B", "3.4 KiB", "1.2 MiB".
+#[allow(clippy::cast_precision_loss)]
+pub fn size(bytes: u64) -> String {
+    match bytes {
+        0..=1023 => format!("{bytes} B"),
+        1024..=1_048_575 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MiB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+/// What a search page is stored as: `nginx (page 2)`.
+pub fn search_key(query: &str, page: u32) -> String {
+    format!("{query} (page {page})")
+}
+
+/// 64-bit FNV-1a, stable across runs and Rust versions, for file names.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use i1_core::testing::host;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_host_round_trip() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let dir = tmp.path().to_path_buf();
+
+        let cache = Cache::at(Some(dir.clone()), Mode::Use);
+        let first = cache
+            .host("shodan", "2001:db8::1", || async {
+                Ok(host("2001:db8::1", json!({ "ports": [22] })))
+            })
+            .await
+            .unwrap();
+        assert!(first.cached_at.is_none());
+
+        // Served from disk: fetching again would fail
+        let second = cache
+            .host("shodan", "2001:db8::1", || async {
+                Err(i1::I1Error::Internal("fetched".to_string()))
+            })
+            .await
+            .unwrap();
+        assert!(second.cached_at.is_some());
+        assert_eq!(second.value.ports, [22]);
+
+        // --refresh fetches regardless
+        let refresh = Cache::at(Some(dir.clone()), Mode::Refresh);
+        let fetched = refresh
+            .host("shodan", "2001:db8::1", || async {
+                Err(i1::I1Error::Internal("fetched".to_string()))
+            })
+            .await;
+        assert!(fetched.is_err());
+        drop((cache, refresh));
+
+        let usage = usage(&dir);
+        assert_eq!(usage.entries, 1);
+        assert_eq!((usage.hits, usage.misses), (1, 2));
+        assert!(usage.providers.contains_key("shodan"));
+    }
+
+    #[test]
+    fn test_health() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let cache = Cache::at(Some(tmp.path().to_path_buf()), Mode::Use);
+        let health = |credits| {
+            serde_json::from_value::<ProviderHealth>(json!({
+                "provider": "shodan",
+                "status": "healthy",
+                "credits_remaining": credits,
+            }))
+            .unwrap()
+        };
+
+        // Only worth keeping when it knows the balance
+        cache.store_health(&health(None));
+        assert!(cache.cached_health("shodan").is_none());
+
+        cache.store_health(&health(Some(42)));
+        let cached = cache.cached_health("shodan").unwrap();
+        assert_eq!(cached.value.credits_remaining, Some(42));
+        assert!(cached.cached_at.is_some());
+
+        let refresh = Cache::at(Some(tmp.path().to_path_buf()), Mode::Refresh);
+        assert!(refresh.cached_health("shodan").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_no_cache() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let cache = Cache::at(None, Mode::Off);
+        cache
+            .host("shodan", "192.0.2.1", || async {
+                Ok(host("192.0.2.1", json!({})))
+            })
+            .await
+            .unwrap();
+        drop(cache);
+        assert_eq!(usage(tmp.path()).entries, 0);
+    }
+
+    #[tokio::test]
+    async fn test_hosts() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let cache = Cache::at(Some(tmp.path().to_path_buf()), Mode::Use);
+        cache.store_host("shodan", &host("192.0.2.1", json!({})));
+        cache
+            .search("censys", "ssh", 1, || async {
+                Ok(serde_json::from_value(json!({
+                    "provider": "censys",
+                    "total": 1,
+                    "page": 1,
+                    "results": [{ "ip_str": "192.0.2.2" }],
+                }))?)
+            })
+            .await
+            .unwrap();
+        cache.store_health(
+            &serde_json::from_value(json!({
+                "provider": "shodan",
+                "status": "healthy",
+                "credits_remaining": 3,
+            }))
+            .unwrap(),
+        );
+
+        let mut ips: Vec<String> = hosts(tmp.path()).into_iter().map(|h| h.ip_str).collect();
+        ips.sort();
+        assert_eq!(ips, ["192.0.2.1", "192.0.2.2"]);
+    }
+
+    #[tokio::test]
+    async fn test_clear() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let dir = tmp.path().to_path_buf();
+        let cache = Cache::at(Some(dir.clone()), Mode::Use);
+        cache.store_host("shodan", &host("192.0.2.1", json!({})));
+        cache.store_host("censys", &host("192.0.2.1", json!({})));
+        cache.store_host("censys", &host("192.0.2.2", json!({})));
+
+        // Nothing is a day old yet
+        assert_eq!(clear(&dir, None, Some(TTL)).unwrap(), 0);
+        assert_eq!(clear(&dir, Some("censys"), None).unwrap(), 2);
+        assert_eq!(usage(&dir).entries, 1);
+        assert_eq!(clear(&dir, None, None).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_formatting() {
+        assert_eq!(ago(Utc::now() - chrono::Duration::hours(2)), "2h ago");
+        assert_eq!(ago(Utc::now()), "just now");
+        assert_eq!(size(512), "512 B");
+        assert_eq!(size(3 * 1024 + 512), "3.5 KiB");
+        assert_ne!(fnv1a(b"port:22 (page 1)"), fnv1a(b"port:22 (page 2)"));
+    }
+}
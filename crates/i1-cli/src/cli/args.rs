@@ -1,7 +1,19 @@
 //! Command-line argument definitions using clap.
 
-use crate::output::OutputFormat;
+use crate::cli::commands::ca::Reason;
+use crate::cli::exit;
+use crate::defend::export::ExportFormat;
+use crate::defend::import::ImportFormat;
+use crate::output::fields::Field;
+use crate::output::filter::Filter;
+use crate::output::group::GroupBy;
+use crate::output::severity::Severity;
+use crate::output::template::{self, Template};
+use crate::output::Output;
+use crate::workspace::ReportFormat;
 use clap::{Args, Parser, Subcommand};
+use std::path::PathBuf;
+use std::time::Duration;
 
 /// i1 - Security Operations CLI
 ///
@@ -16,14 +28,26 @@ use clap::{Args, Parser, Subcommand};
 #[command(name = "i1")]
 #[command(author, version, about, long_about = None)]
 #[command(propagate_version = true)]
+#[command(after_help = exit::HELP)]
+#[allow(clippy::struct_excessive_bools)] // each global switch is its own flag
 pub struct Cli {
     /// Primary API key (Shodan by default, or set `I1_SHODAN_KEY`)
-    #[arg(short = 'k', long, env = "SHODAN_API_KEY", global = true)]
+    #[arg(short = 'k', long, global = true)]
     pub api_key: Option<String>,
 
     /// Output format
     #[arg(short, long, global = true, value_enum)]
-    pub output: Option<OutputFormat>,
+    pub output: Option<Output>,
+
+    /// Write results to this file instead of stdout, without colors. The
+    /// format follows the extension (.json, .ndjson, .csv, .yaml, .md)
+    /// unless --output is given
+    #[arg(long, global = true, value_name = "PATH")]
+    pub output_file: Option<PathBuf>,
+
+    /// With --output-file, add to the end of the file instead of replacing it
+    #[arg(long, global = true, requires = "output_file")]
+    pub append: bool,
 
     /// Explain what this command does
     #[arg(long, global = true)]
@@ -37,9 +61,67 @@ pub struct Cli {
     #[arg(long, global = true)]
     pub no_color: bool,
 
-    /// Which provider to use (shodan, censys, criminalip, native, all, auto)
-    #[arg(short, long, global = true, default_value = "auto")]
-    pub provider: String,
+    /// Show every table cell in full instead of fitting the table to the
+    /// terminal
+    #[arg(long, global = true)]
+    pub wide: bool,
+
+    /// Show each table row as its own block of `column │ value` lines,
+    /// with every value in full
+    #[arg(long, global = true)]
+    pub expand: bool,
+
+    /// Print straight to the terminal instead of through `$PAGER` when
+    /// the output is taller than the screen
+    #[arg(long, global = true)]
+    pub no_pager: bool,
+
+    /// Print only results: no headers, tips, progress or summaries.
+    /// Check the exit code to tell found from not found.
+    #[arg(long, global = true)]
+    pub quiet: bool,
+
+    /// Which provider to use (shodan, censys, criminalip, native, all, auto) [default: auto]
+    #[arg(short, long, global = true)]
+    pub provider: Option<String>,
+
+    /// Config profile to use (or set `I1_PROFILE`)
+    #[arg(long, global = true)]
+    pub profile: Option<String>,
+
+    /// Don't read or write cached host and search answers
+    #[arg(long, global = true, conflicts_with = "refresh")]
+    pub no_cache: bool,
+
+    /// Fetch host and search answers again and update the cache
+    #[arg(long, global = true)]
+    pub refresh: bool,
+
+    /// Send provider requests through this proxy: http://, https://,
+    /// socks5:// or socks5h://, with user:pass@ if it needs a login (or set
+    /// `I1_PROXY`). Without it, `HTTPS_PROXY` and `ALL_PROXY` are honored
+    #[arg(long, global = true, value_name = "URL", conflicts_with = "no_proxy")]
+    pub proxy: Option<String>,
+
+    /// Connect directly, ignoring the configured proxy and proxy
+    /// environment variables
+    #[arg(long, global = true)]
+    pub no_proxy: bool,
+
+    /// Record provider calls and defend changes in the audit log, as if
+    /// `audit_log` were set
+    #[arg(long, global = true)]
+    pub audit: bool,
+
+    /// Save provider responses that don't match what i1 expects under
+    /// this directory, to attach to a bug report
+    #[arg(long, global = true, value_name = "DIR")]
+    pub dump_responses: Option<PathBuf>,
+
+    /// Add host, search and scan results to this investigation workspace,
+    /// and answer repeat lookups from it (see `i1 workspace`)
+    #[arg(long, global = true, value_name = "NAME", env = "I1_WORKSPACE")]
+    pub workspace: Option<String>,
 
     #[command(subcommand)]
     pub command: Option<Commands>,
@@ -59,18 +141,56 @@ pub enum Commands {
     /// DNS lookups and domain information
     Dns(DnsArgs),
 
+    /// Subdomains, DNS records and registration details for a domain
+    Domain(DomainArgs),
+
+    /// Network monitoring alerts (Shodan)
+    Alert(AlertArgs),
+
+    /// Remaining API credits and health for each provider
+    Credits(CreditsArgs),
+
     /// Show your public IP address
-    Myip,
+    Myip(MyipArgs),
 
     /// Defensive tools: geo-blocking, IP bans, firewall rules
     Defend(DefendArgs),
 
+    /// Certificate authority: root, intermediate and server certificates
+    Ca(CaArgs),
+
     /// Manage CLI configuration
     Config(ConfigArgs),
 
+    /// Inspect or clear cached host and search answers
+    Cache(CacheArgs),
+
+    /// Read the audit log of provider calls and defend changes
+    Audit(AuditArgs),
+
+    /// Analyze tripwire events from deployed honeypot kits
+    Honeypot(HoneypotArgs),
+
+    /// Keep an investigation's lookups together and report on them
+    Workspace(WorkspaceArgs),
+
     /// Quick threat response: lookup + optional ban in one command
     #[command(alias = "t")]
     Threat(ThreatArgs),
+
+    /// Port scan a host or network with the local scanner
+    #[cfg(feature = "scanner")]
+    Scan(ScanArgs),
+
+    /// List the external endpoints in a packet capture or Zeek conn.log
+    #[cfg(feature = "ingest")]
+    Ingest(IngestArgs),
+
+    /// CVE lookups: vulnerabilities on a host, or hosts with a CVE
+    Vuln(VulnArgs),
+
+    /// Interactive shell: run commands one after another, pipe results onward
+    Shell,
 }
 
 // ============================================================================
@@ -78,13 +198,183 @@ pub enum Commands {
 // ============================================================================
 
 #[derive(Args, Debug)]
+#[allow(clippy::struct_excessive_bools)] // --all, --compare, --force, --raw and --dry-run are separate flags
 pub struct HostArgs {
-    /// IP address to look up
-    pub ip: String,
+    /// IP address, CIDR block, domain or URL to look up, or `-` to read targets from stdin
+    #[arg(required_unless_present = "file")]
+    pub ip: Option<String>,
+
+    /// Read IPs/hostnames from a file, one per line
+    #[arg(short, long, conflicts_with_all = ["ip", "all", "watch"])]
+    pub file: Option<PathBuf>,
+
+    /// Query all configured providers and merge the results
+    #[arg(long, conflicts_with = "watch")]
+    pub all: bool,
+
+    /// With --all, compare providers side by side instead of merging
+    #[arg(long, requires = "all")]
+    pub compare: bool,
+
+    /// Look up CIDR blocks with more than 1024 addresses
+    #[arg(long)]
+    pub force: bool,
+
+    /// Only show vulnerabilities at or above this severity
+    #[arg(long, value_enum, value_name = "LEVEL", conflicts_with_all = ["watch", "all"])]
+    pub min_severity: Option<Severity>,
+
+    /// Print the provider's own JSON instead of the normalized host (as
+    /// YAML with -o yaml). With --all, every provider's, keyed by name.
+    /// Always asks the provider: raw answers aren't cached
+    #[arg(long, conflicts_with_all = ["file", "watch", "compare", "min_severity", "format_template"])]
+    pub raw: bool,
+
+    /// With --file or `-`, report the lookups and credits the batch would
+    /// spend, without looking anything up
+    #[arg(long, conflicts_with_all = ["all", "watch", "raw"])]
+    pub dry_run: bool,
+
+    #[command(flatten)]
+    pub columns: ColumnArgs,
+
+    #[command(flatten)]
+    pub template: TemplateArgs,
+
+    #[command(flatten)]
+    pub watch: WatchArgs,
+}
+
+/// Column selection and sorting shared by commands that print hosts.
+#[derive(Args, Debug, Clone, Default)]
+pub struct ColumnArgs {
+    /// Columns to show, comma-separated (e.g. ip,ports,org,country,vulns).
+    /// Applies to tables and CSV; JSON/YAML objects are projected to these keys.
+    #[arg(long, value_delimiter = ',')]
+    pub fields: Vec<Field>,
 
-    /// Query all configured providers
+    /// Sort hosts by a field (list fields sort by item count)
+    #[arg(long)]
+    pub sort: Option<Field>,
+
+    /// Sort in descending order
+    #[arg(long, requires = "sort")]
+    pub desc: bool,
+
+    /// Only show hosts this expression is true for, e.g.
+    /// 'ports.len >= 3 && org !~ "Amazon|Google" && country == "DE"'.
+    /// Names are --fields names, with .len for a list's length; operators
+    /// are == != < <= > >= ~ !~ contains in, joined with && || !
+    #[arg(long, value_name = "EXPR")]
+    pub filter: Option<Filter>,
+}
+
+/// `--format-template`: one line of custom text per host.
+#[derive(Args, Debug, Clone, Default)]
+pub struct TemplateArgs {
+    /// Print each host as this template instead of the output format,
+    /// e.g. `{ip}\t{org}\t{country_code}`. Placeholders are `--fields` names;
+    /// `\t` and `\n` are expanded and `{{ }}` are literal braces
+    #[arg(long, value_name = "TEMPLATE")]
+    pub format_template: Option<Template>,
+
+    /// Separator for list placeholders such as `{ports}` and `{vulns}`
+    #[arg(long, value_name = "SEP", requires = "format_template")]
+    pub list_sep: Option<String>,
+}
+
+impl TemplateArgs {
+    /// The separator for list placeholders, escapes expanded
+    pub fn list_sep(&self) -> String {
+        template::unescape(self.list_sep.as_deref().unwrap_or(template::DEFAULT_SEP))
+    }
+}
+
+/// Re-running a lookup or search and reporting what changed.
+#[derive(Args, Debug, Clone, Default)]
+#[command(group(clap::ArgGroup::new("webhook").arg("notify_url").requires("watch")))]
+pub struct WatchArgs {
+    /// Re-run every N seconds and print only what changed, until Ctrl-C
+    #[arg(long, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub watch: Option<u64>,
+
+    /// Run a command on each change, with the change as JSON on stdin
+    #[arg(long, value_name = "CMD", requires = "watch")]
+    pub notify_cmd: Option<String>,
+
+    #[command(flatten)]
+    pub notify: NotifyArgs,
+}
+
+/// Webhook alerts for hosts that turn out to be high risk.
+#[derive(Args, Debug, Clone)]
+pub struct NotifyArgs {
+    /// POST hosts reaching --notify-level to this webhook; Slack and
+    /// Discord webhooks get a chat message, anything else JSON
+    #[arg(long, value_name = "URL")]
+    pub notify_url: Option<String>,
+
+    /// Threat level that triggers --notify-url
+    #[arg(long, value_enum, value_name = "LEVEL", default_value = "high")]
+    pub notify_level: Severity,
+
+    /// Sign webhook bodies with HMAC-SHA256 in an X-I1-Signature header
+    #[arg(
+        long,
+        value_name = "SECRET",
+        env = "I1_NOTIFY_SECRET",
+        hide_env_values = true
+    )]
+    pub notify_secret: Option<String>,
+}
+
+impl Default for NotifyArgs {
+    fn default() -> Self {
+        Self {
+            notify_url: None,
+            notify_level: Severity::High,
+            notify_secret: None,
+        }
+    }
+}
+
+impl NotifyArgs {
+    /// A notifier posting to --notify-url, or notifying no one without it
+    pub fn notifier(&self) -> i1::notify::Notifier {
+        let notifier = i1::notify::Notifier::new();
+        if let Some(url) = &self.notify_url {
+            let mut webhook = i1::notify::Webhook::new(url);
+            if let Some(secret) = &self.notify_secret {
+                webhook = webhook.secret(secret);
+            }
+            notifier.notify_on(self.notify_level.level(), webhook);
+        }
+        notifier
+    }
+}
+
+// ============================================================================
+// Myip command
+// ============================================================================
+
+#[derive(Args, Debug)]
+#[allow(clippy::struct_excessive_bools)] // the family and --enrich flags
+pub struct MyipArgs {
+    /// Only report the IPv4 address
+    #[arg(short = '4', long = "ipv4", conflicts_with_all = ["ipv6", "all"])]
+    pub ipv4: bool,
+
+    /// Only report the IPv6 address
+    #[arg(short = '6', long = "ipv6", conflicts_with = "all")]
+    pub ipv6: bool,
+
+    /// Report both the IPv4 and IPv6 address
     #[arg(long)]
     pub all: bool,
+
+    /// Look up ISP, ASN, hostnames and tags for the address
+    #[arg(long)]
+    pub enrich: bool,
 }
 
 // ============================================================================
@@ -92,6 +382,7 @@ pub struct HostArgs {
 // ============================================================================
 
 #[derive(Args, Debug)]
+#[allow(clippy::struct_excessive_bools)] // the --ban, --ban-asn, --yes and --execute flags
 pub struct ThreatArgs {
     /// IP address to investigate
     pub ip: String,
@@ -113,18 +404,152 @@ pub struct ThreatArgs {
     pub execute: bool,
 }
 
+// ============================================================================
+// Scan command - local port scanner
+// ============================================================================
+
+#[cfg(feature = "scanner")]
+#[derive(Args, Debug)]
+#[command(group(clap::ArgGroup::new("webhook").arg("notify_url").requires("enrich")))]
+#[allow(clippy::struct_excessive_bools)] // one bool per scan flag
+pub struct ScanArgs {
+    /// Target to scan: IP address, hostname, or CIDR range
+    pub target: String,
+
+    /// Ports: top100, top1000, all, a range (1-1024) or a list (22,80,443)
+    #[arg(long, default_value = "top100")]
+    pub ports: String,
+
+    /// Timing preset: polite, normal, aggressive
+    #[arg(long, default_value = "normal")]
+    pub timing: String,
+
+    /// Scan UDP instead of TCP
+    #[arg(long)]
+    pub udp: bool,
+
+    /// Grab service banners from open ports
+    #[arg(long)]
+    pub banner: bool,
+
+    /// Enrich hosts with open ports using the configured provider
+    #[arg(long)]
+    pub enrich: bool,
+
+    /// Report the lookups and credits --enrich could spend, without scanning
+    #[arg(long, requires = "enrich")]
+    pub dry_run: bool,
+
+    /// Save results as JSON to this path
+    #[arg(long)]
+    pub save: Option<String>,
+
+    /// Confirm scanning targets outside private address space
+    #[arg(long, short = 'y')]
+    pub yes: bool,
+
+    /// Only report hosts this expression is true for, as for search; the
+    /// ports are the ones found open, the rest needs --enrich
+    #[arg(long, value_name = "EXPR")]
+    pub filter: Option<Filter>,
+
+    #[command(flatten)]
+    pub template: TemplateArgs,
+
+    #[command(flatten)]
+    pub notify: NotifyArgs,
+}
+
+// ============================================================================
+// Ingest command - targets from captures and logs
+// ============================================================================
+
+#[cfg(feature = "ingest")]
+#[derive(Args, Debug)]
+pub struct IngestArgs {
+    /// A pcap/pcapng capture or a Zeek conn.log (TSV or JSON)
+    pub file: PathBuf,
+
+    /// Look each endpoint up with the configured provider
+    #[arg(long)]
+    pub enrich: bool,
+
+    /// Only list endpoints seen in at least this many connections
+    #[arg(long, default_value_t = 1, value_name = "N")]
+    pub min_connections: u64,
+}
+
 // ============================================================================
 // Search command
 // ============================================================================
 
 #[derive(Args, Debug)]
+#[allow(clippy::struct_excessive_bools)] // one bool per search flag
 pub struct SearchArgs {
     /// Search query (e.g., "apache country:US port:80")
+    #[arg(
+        required_unless_present_any = ["history", "saved"],
+        default_value = "",
+        hide_default_value = true
+    )]
     pub query: String,
 
+    /// List recent searches and counts, and the saved queries
+    #[arg(long, conflicts_with_all = ["query", "save", "saved"])]
+    pub history: bool,
+
+    /// Save the query under this name instead of searching; it may hold
+    /// `{placeholder}`s to fill in when it's run
+    #[arg(long, value_name = "NAME", conflicts_with = "saved")]
+    pub save: Option<String>,
+
+    /// Run the query saved under NAME, filling its placeholders from
+    /// KEY=VALUE pairs (`--saved rdp-by-country country=DE`)
+    #[arg(long, num_args = 1.., value_name = "NAME [KEY=VALUE]...", conflicts_with = "query")]
+    pub saved: Vec<String>,
+
     /// Page number (1-indexed)
-    #[arg(short, long, default_value = "1")]
+    #[arg(long, default_value = "1")]
     pub page: u32,
+
+    /// Fetch pages until this many hosts have been collected
+    #[arg(short, long, conflicts_with = "watch")]
+    pub limit: Option<usize>,
+
+    /// Keep fetching pages until the results run out
+    #[arg(long, conflicts_with = "watch")]
+    pub all_pages: bool,
+
+    /// Stop paging (or watching) before spending more than this many query credits
+    #[arg(long, default_value = "10")]
+    pub max_credits: u32,
+
+    /// Only show hosts with a vulnerability at or above this severity
+    #[arg(long, value_enum, value_name = "LEVEL", conflicts_with = "watch")]
+    pub min_severity: Option<Severity>,
+
+    /// Count hosts per country, org, ASN or port instead of listing them
+    #[arg(long, value_enum, value_name = "FIELD", conflicts_with_all = ["watch", "format_template"])]
+    pub group_by: Option<GroupBy>,
+
+    /// Check the query and report the pages and credits the search would
+    /// spend, without searching
+    #[arg(long, conflicts_with = "watch")]
+    pub dry_run: bool,
+
+    /// Show each provider's share of the hosts and how many more than one
+    /// provider returned (on stderr, as JSON, with machine-readable output)
+    #[arg(long, conflicts_with = "watch")]
+    pub stats: bool,
+
+    #[command(flatten)]
+    pub columns: ColumnArgs,
+
+    #[command(flatten)]
+    pub template: TemplateArgs,
+
+    #[command(flatten)]
+    pub watch: WatchArgs,
 }
 
 // ============================================================================
@@ -149,19 +574,195 @@ pub struct DnsArgs {
 
 #[derive(Subcommand, Debug)]
 pub enum DnsCommands {
-    /// Resolve hostname to IP addresses
+    /// Resolve hostnames to IP addresses
     Resolve {
-        /// Hostname to resolve
-        hostname: String,
+        /// Hostname to resolve, or `-` to read hostnames from stdin
+        #[arg(required_unless_present = "file")]
+        hostname: Option<String>,
+
+        /// Read hostnames from a file, one per line, and resolve them locally
+        #[arg(short, long, conflicts_with = "hostname")]
+        file: Option<PathBuf>,
+
+        /// Only keep addresses of these record types (A, AAAA)
+        #[arg(short = 't', long = "type", value_delimiter = ',', value_parser = parse_address_type)]
+        types: Vec<String>,
+
+        /// Print just the deduplicated IPs, one per line
+        #[arg(long)]
+        unique_ips: bool,
     },
 
     /// Reverse DNS lookup
     Reverse {
         /// IP address
+        #[arg(value_parser = parse_ip)]
         ip: String,
     },
 }
 
+// ============================================================================
+// Domain command
+// ============================================================================
+
+#[derive(Args, Debug)]
+#[allow(clippy::struct_excessive_bools)] // the record, subdomain, audit and bruteforce switches
+pub struct DomainArgs {
+    /// Domain name (e.g. example.com)
+    pub domain: String,
+
+    /// Only show DNS records
+    #[arg(long, conflicts_with = "subdomains_only")]
+    pub records_only: bool,
+
+    /// Only show subdomains
+    #[arg(long, conflicts_with = "types")]
+    pub subdomains_only: bool,
+
+    /// Only show records of these types (e.g. --type MX,TXT)
+    #[arg(short = 't', long = "type", value_delimiter = ',')]
+    pub types: Vec<String>,
+
+    /// Show at most this many subdomains, and records per type (0 for no limit)
+    #[arg(short, long)]
+    pub limit: Option<usize>,
+
+    /// Audit the domain's nameservers instead: zone transfers, wildcards,
+    /// CNAMEs into unclaimed cloud resources, and SPF/DMARC
+    #[cfg(feature = "dns")]
+    #[arg(long, conflicts_with_all = ["records_only", "subdomains_only", "types", "limit"])]
+    pub audit: bool,
+
+    /// Also try names from a wordlist against DNS, skipping wildcard
+    /// answers, and show which source found each subdomain
+    #[cfg(feature = "dns")]
+    #[arg(long, conflicts_with_all = ["audit", "records_only"])]
+    pub bruteforce: bool,
+
+    /// Wordlist for --bruteforce, one label per line (default: a built-in
+    /// list of common names)
+    #[cfg(feature = "dns")]
+    #[arg(long, value_name = "PATH", requires = "bruteforce")]
+    pub wordlist: Option<PathBuf>,
+}
+
+// ============================================================================
+// Alert command
+// ============================================================================
+
+#[derive(Args, Debug)]
+pub struct AlertArgs {
+    #[command(subcommand)]
+    pub command: AlertCommands,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum AlertCommands {
+    /// List your network alerts
+    List,
+
+    /// Create an alert for one or more networks
+    Create {
+        /// Alert name
+        #[arg(required_unless_present = "from_file")]
+        name: Option<String>,
+
+        /// Network to monitor, as an IP or CIDR (repeatable)
+        #[arg(long = "cidr", value_parser = parse_network, required_unless_present = "from_file")]
+        cidrs: Vec<String>,
+
+        /// Trigger to enable, e.g. `new_service` or `malware` (repeatable)
+        #[arg(long = "trigger")]
+        triggers: Vec<String>,
+
+        /// Expire the alert after this many days
+        #[arg(long)]
+        expires: Option<u32>,
+
+        /// Recreate alerts from `i1 alert list -o json` output
+        #[arg(long, conflicts_with_all = ["name", "cidrs", "triggers", "expires"])]
+        from_file: Option<PathBuf>,
+    },
+
+    /// Delete an alert
+    Delete {
+        /// Alert ID
+        id: String,
+
+        /// Skip the confirmation prompt
+        #[arg(long, short = 'y')]
+        yes: bool,
+    },
+
+    /// Hosts currently seen in an alert's networks
+    Matches {
+        /// Alert ID
+        id: String,
+
+        #[command(flatten)]
+        columns: ColumnArgs,
+    },
+
+    /// List the triggers alerts can use
+    Triggers,
+}
+
+/// Accept a bare IP as a single-address network
+fn parse_network(s: &str) -> Result<String, String> {
+    if let Ok(net) = s.parse::<ipnet::IpNet>() {
+        return Ok(net.to_string());
+    }
+    i1::validate_ip(s)
+        .map(|ip| ipnet::IpNet::from(ip).to_string())
+        .map_err(|_| format!("'{s}' is not an IP address or CIDR range"))
+}
+
+/// An IP address in canonical form, bracketed IPv6 or not
+fn parse_ip(s: &str) -> Result<String, String> {
+    i1::validate_ip(s)
+        .map(|ip| ip.to_string())
+        .map_err(|_| format!("'{s}' is not an IP address"))
+}
+
+// ============================================================================
+// Credits command
+// ============================================================================
+
+#[derive(Args, Debug)]
+pub struct CreditsArgs {
+    /// Refresh every N seconds until interrupted
+    #[arg(long, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub watch: Option<u64>,
+}
+
+// ============================================================================
+// Vuln command
+// ============================================================================
+
+#[derive(Args, Debug)]
+pub struct VulnArgs {
+    #[command(subcommand)]
+    pub command: VulnCommands,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum VulnCommands {
+    /// List known CVEs for a host, most severe first
+    Host {
+        /// IP address
+        ip: String,
+    },
+
+    /// Find hosts affected by a CVE
+    Search {
+        /// CVE id (e.g., CVE-2024-3400)
+        cve: String,
+
+        #[command(flatten)]
+        columns: ColumnArgs,
+    },
+}
+
 // ============================================================================
 // Defend command
 // ============================================================================
@@ -179,6 +780,10 @@ pub enum DefendCommands {
         /// Quick one-line summary
         #[arg(long, short)]
         quick: bool,
+
+        /// Exit nonzero if the live firewall has drifted from the saved state
+        #[arg(long)]
+        check: bool,
     },
 
     /// Manage country-level geo-blocking
@@ -189,10 +794,18 @@ pub enum DefendCommands {
         /// IP address or CIDR to block
         target: String,
 
-        /// Treat target as AS number
+        /// Treat target as AS number and ban every prefix it announces
         #[arg(long, short = 'a')]
         as_number: bool,
 
+        /// Why it's banned (kept in the journal)
+        #[arg(long, short)]
+        reason: Option<String>,
+
+        /// Don't ask before banning a large AS
+        #[arg(long, short)]
+        yes: bool,
+
         /// Show what would happen without making changes
         #[arg(long)]
         dry_run: bool,
@@ -202,34 +815,107 @@ pub enum DefendCommands {
     Unban {
         /// IP address, CIDR, or AS number to unblock
         target: String,
+
+        /// Show what would happen without making changes
+        #[arg(long)]
+        dry_run: bool,
     },
 
     /// Manage whitelist (IPs that are never blocked)
     Whitelist(WhitelistArgs),
 
+    /// Subscribe to blocklist feeds (Spamhaus DROP, CINS, any CIDR list)
+    Feeds(FeedsArgs),
+
     /// Export firewall rules
     Export {
-        /// Output format: nftables, iptables, pf
-        #[arg(long, default_value = "nftables")]
-        format: String,
+        /// Firewall to export for
+        #[arg(long, value_enum, default_value = "nftables")]
+        format: ExportFormat,
     },
 
-    /// Import IPs from file or stdin
+    /// Ban IPs from a list, search results (JSON/NDJSON) or CSV
+    ///
+    /// Input is streamed, so lists of millions of lines are fine, and
+    /// contiguous addresses are banned as the CIDR ranges they add up to.
     Import {
         /// Read from stdin
-        #[arg(long)]
+        #[arg(long, conflicts_with = "file")]
         stdin: bool,
 
         /// Read from file
         #[arg(long)]
-        file: Option<String>,
+        file: Option<PathBuf>,
+
+        /// Input format
+        #[arg(long, value_enum, default_value = "auto")]
+        format: ImportFormat,
+
+        /// Why these are banned, kept in the journal
+        #[arg(short, long)]
+        reason: Option<String>,
+
+        /// Don't ask before banning more than 100 addresses
+        #[arg(short, long)]
+        yes: bool,
+
+        /// Show what would happen without making changes
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Group IPs by WHOIS netblock and offer to ban the worst blocks whole
+    ///
+    /// Shows who owns each allocation the addresses fall in, with its
+    /// country and abuse contact. Blocks holding a whitelist entry or your
+    /// SSH session are never offered.
+    #[cfg(feature = "whois")]
+    Suggest {
+        /// IPs to group: a list, search results (JSON/NDJSON) or CSV
+        #[arg(long)]
+        from_file: PathBuf,
+
+        /// Offer blocks holding at least this many of the IPs
+        #[arg(long, default_value_t = 3, value_parser = clap::value_parser!(u64).range(1..))]
+        min_ips: u64,
+
+        /// Why these are banned, kept in the journal
+        #[arg(short, long)]
+        reason: Option<String>,
+
+        /// Ban the offered blocks without asking
+        #[arg(short, long)]
+        yes: bool,
+
+        /// Show what would happen without making changes
+        #[arg(long)]
+        dry_run: bool,
     },
 
     /// Undo the last change
-    Undo,
+    Undo {
+        /// How many changes to undo, newest first
+        #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+        steps: u32,
+
+        /// Show what would happen without making changes
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// List recent defend changes
+    History {
+        /// How many changes to show
+        #[arg(short = 'n', long, default_value_t = 20)]
+        limit: usize,
+    },
 
     /// Emergency disable all blocking
-    Disable,
+    Disable {
+        /// Show what would happen without making changes
+        #[arg(long)]
+        dry_run: bool,
+    },
 
     /// Push blocks to remote servers via SSH
     Push(PushArgs),
@@ -395,9 +1081,9 @@ pub enum GeoblockCommands {
     /// List currently blocked countries
     List,
 
-    /// Block countries by code (e.g., cn ru)
+    /// Block countries by code or name (e.g., cn ru "South Korea")
     Add {
-        /// Country codes to block
+        /// Country codes or names to block
         countries: Vec<String>,
 
         /// Show what would happen without making changes
@@ -407,17 +1093,74 @@ pub enum GeoblockCommands {
 
     /// Unblock a country
     Remove {
-        /// Country code to unblock
+        /// Country code or name to unblock
         country: String,
+
+        /// Show what would happen without making changes
+        #[arg(long)]
+        dry_run: bool,
     },
 
-    /// Update IP ranges from upstream
-    Update,
+    /// Download country ranges from the RIR delegation files
+    Update {
+        /// Download everything again, even if unchanged
+        #[arg(long)]
+        force: bool,
+    },
 
     /// Show country code reference
     Codes,
 }
 
+#[derive(Args, Debug)]
+pub struct FeedsArgs {
+    #[command(subcommand)]
+    pub command: FeedsCommands,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum FeedsCommands {
+    /// List subscribed feeds
+    List,
+
+    /// Subscribe to a feed and load it
+    Add {
+        /// Feed name (spamhaus-drop, spamhaus-edrop and cins need no URL)
+        name: String,
+
+        /// URL of a list with one IP or CIDR per line
+        url: Option<String>,
+
+        /// Download and check the feed without subscribing
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Drop a feed and unload its ranges
+    Remove {
+        /// Feed name
+        name: String,
+
+        /// Show what would happen without making changes
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Download feeds that changed and apply the differences
+    Update {
+        /// Only this feed
+        name: Option<String>,
+
+        /// Re-download even if unchanged, and accept a feed that shrank a lot
+        #[arg(long)]
+        force: bool,
+
+        /// Show what would change without applying it
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
 #[derive(Args, Debug)]
 pub struct WhitelistArgs {
     #[command(subcommand)]
@@ -429,19 +1172,185 @@ pub enum WhitelistCommands {
     /// Show whitelisted IPs
     Show,
 
-    /// Add IP to whitelist
+    /// Add an IP or CIDR range to the whitelist
     Add {
-        /// IP address to whitelist
+        /// IP address or CIDR range to whitelist
         ip: String,
+
+        /// Remove bans the new entry covers without asking
+        #[arg(short, long)]
+        yes: bool,
+
+        /// Show what would happen without making changes
+        #[arg(long)]
+        dry_run: bool,
     },
 
-    /// Remove IP from whitelist
+    /// Remove an IP or CIDR range from the whitelist
     Remove {
-        /// IP address to remove
+        /// IP address or CIDR range to remove
+        ip: String,
+
+        /// Show what would happen without making changes
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Explain whether an address is protected or blocked, and why
+    Check {
+        /// IP address or CIDR range to check
         ip: String,
     },
 }
 
+// ============================================================================
+// CA command
+// ============================================================================
+
+#[derive(Args, Debug)]
+pub struct CaArgs {
+    #[command(subcommand)]
+    pub command: CaCommands,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CaCommands {
+    /// Create a root CA (best done on an offline machine)
+    InitRoot {
+        /// Common name of the root certificate
+        #[arg(long)]
+        cn: String,
+
+        /// Directory for the CA's keys, certificates and index
+        #[arg(long)]
+        out: PathBuf,
+
+        /// Where the root's CRL will be published, for intermediates to
+        /// point at
+        #[arg(long)]
+        crl_url: Option<String>,
+
+        /// Replace an existing root key
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Sign a new intermediate CA with the root
+    IssueIntermediate {
+        /// Common name of the intermediate certificate
+        #[arg(long, default_value = "i1.is General CA")]
+        cn: String,
+
+        /// CA directory (from init-root)
+        #[arg(long, default_value = ".")]
+        ca: PathBuf,
+
+        /// Where the intermediate's CRL will be published, for its
+        /// certificates to point at
+        #[arg(long)]
+        crl_url: Option<String>,
+
+        /// Replace the current intermediate
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Issue a server certificate from the intermediate
+    IssueCert {
+        /// Common name, also added as a subject alternative name
+        #[arg(long)]
+        cn: String,
+
+        /// More names: dns:<name> or ip:<address> (repeatable)
+        #[arg(long = "san")]
+        sans: Vec<String>,
+
+        /// Days the certificate is valid
+        #[arg(long, default_value_t = 30)]
+        days: u32,
+
+        /// CA directory (from init-root)
+        #[arg(long, default_value = ".")]
+        ca: PathBuf,
+
+        /// Where to write the key and certificates [default: <ca>/certs]
+        #[arg(long)]
+        out: Option<PathBuf>,
+
+        /// Replace existing files for this name
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Revoke an intermediate or server certificate
+    Revoke {
+        /// Serial number in hex, as shown when it was issued
+        serial: String,
+
+        /// Why it is revoked
+        #[arg(long, value_enum, default_value = "unspecified")]
+        reason: Reason,
+
+        /// CA directory (from init-root)
+        #[arg(long, default_value = ".")]
+        ca: PathBuf,
+    },
+
+    /// List every certificate the CA has issued
+    List {
+        /// CA directory (from init-root)
+        #[arg(long, default_value = ".")]
+        ca: PathBuf,
+    },
+
+    /// List valid certificates that expire soon
+    Expiring {
+        /// How many days ahead to look
+        #[arg(long, default_value_t = 30)]
+        days: u32,
+
+        /// CA directory (from init-root)
+        #[arg(long, default_value = ".")]
+        ca: PathBuf,
+    },
+
+    /// Certificate revocation lists
+    Crl(CrlArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct CrlArgs {
+    #[command(subcommand)]
+    pub command: CrlCommands,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CrlCommands {
+    /// Sign and export a CRL (PEM to stdout by default)
+    Export {
+        /// The root's CRL of revoked intermediates, instead of the
+        /// intermediate's CRL of revoked server certificates
+        #[arg(long)]
+        root: bool,
+
+        /// Write to a file
+        #[arg(long)]
+        out: Option<PathBuf>,
+
+        /// Write DER instead of PEM
+        #[arg(long, requires = "out")]
+        der: bool,
+
+        /// Days until the next CRL is due
+        #[arg(long, default_value_t = 7)]
+        days: u32,
+
+        /// CA directory (from init-root)
+        #[arg(long, default_value = ".")]
+        ca: PathBuf,
+    },
+}
+
 // ============================================================================
 // Config command
 // ============================================================================
@@ -468,4 +1377,206 @@ pub enum ConfigCommands {
 
     /// Show config file path
     Path,
+
+    /// List config profiles
+    Profiles,
+
+    /// Check config, keys, network and provider health
+    Doctor,
+}
+
+// ============================================================================
+// Cache command
+// ============================================================================
+
+#[derive(Args, Debug)]
+pub struct CacheArgs {
+    #[command(subcommand)]
+    pub command: CacheCommands,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CacheCommands {
+    /// Show entries, hit rate and size on disk
+    Stats,
+
+    /// Remove cached answers
+    Clear {
+        /// Only this provider's answers (e.g. shodan)
+        #[arg(long)]
+        provider: Option<String>,
+
+        /// Only answers older than this, e.g. 30m, 12h or 7d
+        #[arg(long, value_name = "AGE", value_parser = parse_age)]
+        older_than: Option<Duration>,
+    },
+
+    /// Find services in cached and workspace hosts by banner or product
+    Grep {
+        /// Regular expression to find in banners (case-insensitive), or
+        /// with --like, a banner to find others like
+        #[arg(required_unless_present = "product")]
+        pattern: Option<String>,
+
+        /// Find banners like PATTERN once timestamps, hostnames and
+        /// addresses are set aside, most alike first
+        #[arg(long)]
+        like: bool,
+
+        /// How alike banners must be for --like, from 0 to 1
+        #[arg(long, default_value_t = 0.6, requires = "like", value_parser = parse_threshold)]
+        threshold: f64,
+
+        /// Services running this product, optionally this version of it,
+        /// e.g. OpenSSH or OpenSSH/8.9p1
+        #[arg(long, value_name = "PRODUCT[/VERSION]", conflicts_with_all = ["pattern", "like"])]
+        product: Option<String>,
+    },
+}
+
+// ============================================================================
+// Audit command
+// ============================================================================
+
+#[derive(Args, Debug)]
+pub struct AuditArgs {
+    #[command(subcommand)]
+    pub command: AuditCommands,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum AuditCommands {
+    /// Show the newest entries
+    Tail {
+        /// How many entries
+        #[arg(short = 'n', long, default_value_t = 20)]
+        lines: usize,
+    },
+
+    /// Calls, failures, credits and changes per day
+    Summary {
+        /// Only the last N days
+        #[arg(long)]
+        days: Option<u32>,
+    },
+}
+
+// ============================================================================
+// Honeypot command
+// ============================================================================
+
+#[derive(Args, Debug)]
+pub struct HoneypotArgs {
+    #[command(subcommand)]
+    pub command: HoneypotCommands,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum HoneypotCommands {
+    /// Time to first access, top sources, bait taken and a timeline, from
+    /// an archive of tripwire events
+    Stats {
+        /// Tripwire events, one JSON object per line
+        events: PathBuf,
+
+        /// A kit's JSON, to attribute its events to it (repeatable)
+        #[arg(long = "kit", value_name = "FILE")]
+        kits: Vec<PathBuf>,
+
+        /// A directory a kit was deployed to, for its kit and deployment time (repeatable)
+        #[arg(long = "deploy", value_name = "DIR")]
+        deploys: Vec<PathBuf>,
+
+        /// How many source addresses to list
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+
+        /// Timeline bucket, e.g. 1h or 1d
+        #[arg(long, value_name = "SIZE", default_value = "1d", value_parser = parse_age)]
+        bucket: Duration,
+    },
+}
+
+// ============================================================================
+// Workspace command
+// ============================================================================
+
+#[derive(Args, Debug)]
+pub struct WorkspaceArgs {
+    #[command(subcommand)]
+    pub command: WorkspaceCommands,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum WorkspaceCommands {
+    /// Start a workspace. Activate it with --workspace or `I1_WORKSPACE`
+    New {
+        /// Letters, digits, '.', '_' and '-', e.g. incident-2024-05
+        name: String,
+    },
+
+    /// List workspaces
+    List,
+
+    /// Targets investigated, providers used, credits spent and top findings
+    Show {
+        /// Workspace to show [default: the active one]
+        name: Option<String>,
+
+        /// How many findings to list
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+    },
+
+    /// Print a report skeleton with what the workspace found
+    Export {
+        /// Workspace to export [default: the active one]
+        name: Option<String>,
+
+        /// Report format
+        #[arg(long, value_enum, default_value_t = ReportFormat::Markdown)]
+        format: ReportFormat,
+
+        /// How many findings to list
+        #[arg(long, default_value_t = 25)]
+        top: usize,
+    },
+}
+
+/// Accept the record types `dns resolve` can answer, as upper case.
+fn parse_address_type(s: &str) -> Result<String, String> {
+    let upper = s.trim().to_uppercase();
+    match upper.as_str() {
+        "A" | "AAAA" => Ok(upper),
+        _ => Err(format!(
+            "dns resolve only looks up addresses (A, AAAA); \
+             use `i1 domain <domain> --type {upper}` for other records"
+        )),
+    }
+}
+
+/// Parse a similarity threshold between 0 and 1.
+fn parse_threshold(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(threshold) if (0.0..=1.0).contains(&threshold) => Ok(threshold),
+        _ => Err(format!("'{s}' is not a threshold from 0 to 1")),
+    }
+}
+
+/// Parse an age like `90s`, `30m`, `12h`, `7d` or `2w`.
+fn parse_age(s: &str) -> Result<Duration, String> {
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("'{s}' is not an age like 30m, 12h or 7d"))?;
+    let unit = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(format!("'{s}' is not an age like 30m, 12h or 7d")),
+    };
+    Ok(Duration::from_secs(number.saturating_mul(unit)))
 }
@@ -1,195 +1,350 @@
-//! `showdi1 alert` - Network monitoring alerts.
+//! `i1 alert` - Manage network monitoring alerts.
+
+use std::path::Path;
 
 use anyhow::Result;
 use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use tabled::Tabled;
 
-use super::Context;
+use super::{confirm, search, Context};
 use crate::cli::args::{AlertArgs, AlertCommands};
-use crate::education::Explain;
-use crate::output::OutputFormat;
+use crate::output::{terminal, OutputFormat};
+use i1::{Alert, AlertFilters, CreateAlertRequest, I1Error};
+use i1_providers::AlertProvider;
+
+/// An alert as `list` prints it and `create --from-file` reads it.
+///
+/// `name`, `cidrs` and `triggers` mirror the `create` options; the rest is
+/// informational and ignored when creating.
+#[derive(Debug, Serialize, Deserialize)]
+struct AlertSpec {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    name: String,
+    cidrs: Vec<String>,
+    #[serde(default)]
+    triggers: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    size: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    created: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    expired: bool,
+}
 
-pub async fn execute(ctx: Context, args: AlertArgs) -> Result<()> {
-    match args.command {
-        AlertCommands::List => list_alerts(ctx).await,
-        AlertCommands::Create { name, ips, expires } => create_alert(ctx, &name, &ips, expires).await,
-        AlertCommands::Get { id } => get_alert(ctx, &id).await,
-        AlertCommands::Delete { id } => delete_alert(ctx, &id).await,
-        AlertCommands::Triggers => list_triggers(ctx).await,
+impl From<Alert> for AlertSpec {
+    fn from(alert: Alert) -> Self {
+        let mut triggers: Vec<String> = alert
+            .triggers
+            .into_iter()
+            .filter_map(|(name, enabled)| enabled.then_some(name))
+            .collect();
+        triggers.sort();
+
+        Self {
+            id: Some(alert.id),
+            name: alert.name,
+            cidrs: alert.filters.ip,
+            triggers,
+            size: Some(alert.size),
+            created: alert.created,
+            expired: alert.expired,
+        }
     }
 }
 
-async fn list_alerts(ctx: Context) -> Result<()> {
-    if ctx.explain {
-        Explain::alert_list().print();
+#[derive(Tabled)]
+struct AlertRow {
+    #[tabled(rename = "ID")]
+    id: String,
+    #[tabled(rename = "Name")]
+    name: String,
+    #[tabled(rename = "Networks")]
+    cidrs: String,
+    #[tabled(rename = "Triggers")]
+    triggers: String,
+    #[tabled(rename = "IPs")]
+    size: String,
+    #[tabled(rename = "Status")]
+    status: String,
+}
+
+#[derive(Tabled)]
+struct TriggerRow {
+    #[tabled(rename = "Trigger")]
+    name: String,
+    #[tabled(rename = "Description")]
+    description: String,
+}
+
+pub async fn execute(ctx: Context, args: AlertArgs) -> Result<()> {
+    let provider = ctx.alert_provider()?;
+
+    match args.command {
+        AlertCommands::List => list(&ctx, provider.as_ref()).await,
+        AlertCommands::Create {
+            name,
+            cidrs,
+            triggers,
+            expires,
+            from_file,
+        } => {
+            let specs = match (from_file, name) {
+                (Some(path), _) => read_specs(&path)?,
+                (None, Some(name)) => vec![AlertSpec {
+                    id: None,
+                    name,
+                    cidrs,
+                    triggers,
+                    size: None,
+                    created: None,
+                    expired: false,
+                }],
+                (None, None) => anyhow::bail!("An alert name or --from-file is required"),
+            };
+            create(&ctx, provider.as_ref(), specs, expires).await
+        }
+        AlertCommands::Delete { id, yes } => {
+            if !yes && !confirm(&format!("Delete alert {id}?"))? {
+                println!("{}", "Cancelled.".dimmed());
+                return Ok(());
+            }
+            provider.delete_alert(&id).await.map_err(explain)?;
+            if ctx.output_format == OutputFormat::Pretty {
+                println!("{} Deleted alert {id}", "Success:".green().bold());
+            }
+            Ok(())
+        }
+        AlertCommands::Matches { id, columns } => {
+            let mut results = provider.alert_matches(&id).await.map_err(explain)?;
+            search::print_results(
+                &ctx,
+                &mut results,
+                &format!("alert {id}"),
+                &columns,
+                search::PRETTY_ROWS,
+            )
+        }
+        AlertCommands::Triggers => triggers(&ctx, provider.as_ref()).await,
     }
+}
 
-    let client = ctx.client()?;
-    let alerts = client.alerts().list().await?;
+async fn list(ctx: &Context, provider: &(dyn AlertProvider + Send + Sync)) -> Result<()> {
+    let mut alerts: Vec<AlertSpec> = provider
+        .list_alerts()
+        .await
+        .map_err(explain)?
+        .into_iter()
+        .map(AlertSpec::from)
+        .collect();
+    alerts.sort_by(|a, b| a.name.cmp(&b.name));
 
     match ctx.output_format {
         OutputFormat::Json => {
             println!("{}", serde_json::to_string_pretty(&alerts)?);
         }
-        _ => {
-            println!("{}", "Your Alerts:".bold());
-            println!();
-
+        OutputFormat::Yaml => {
+            println!("{}", serde_yaml::to_string(&alerts)?);
+        }
+        OutputFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(std::io::stdout().lock());
+            writer.write_record(["id", "name", "cidrs", "triggers", "size", "expired"])?;
+            for alert in &alerts {
+                writer.write_record([
+                    alert.id.as_deref().unwrap_or(""),
+                    &alert.name,
+                    &alert.cidrs.join(";"),
+                    &alert.triggers.join(";"),
+                    &alert.size.unwrap_or(0).to_string(),
+                    &alert.expired.to_string(),
+                ])?;
+            }
+            writer.flush()?;
+        }
+        OutputFormat::Pretty => {
             if alerts.is_empty() {
-                println!("  No alerts configured.");
-                println!();
-                println!("  Create one with: {} alert create <NAME> --ips <IP>", "showdi1".cyan());
-            } else {
-                for alert in &alerts {
-                    println!("  {} {}", alert.id.cyan(), alert.name);
-                    if !alert.filters.ip.is_empty() {
-                        println!("    IPs: {}", alert.filters.ip.join(", "));
-                    }
-                    if let Some(exp) = &alert.expires {
-                        println!("    Expires: {}", exp);
-                    }
-                    println!();
-                }
+                println!("No alerts. Create one with: i1 alert create <name> --cidr <network>");
+                return Ok(());
             }
+
+            let rows: Vec<AlertRow> = alerts
+                .iter()
+                .map(|a| AlertRow {
+                    id: a.id.clone().unwrap_or_default(),
+                    name: a.name.clone(),
+                    cidrs: a.cidrs.join(", "),
+                    triggers: a.triggers.join(", "),
+                    size: a.size.unwrap_or(0).to_string(),
+                    status: status(a.expired, ctx.no_color),
+                })
+                .collect();
+
+            let table = terminal::table(&rows);
+            println!("{table}");
         }
     }
 
     Ok(())
 }
 
-async fn create_alert(ctx: Context, name: &str, ips: &[String], expires: u32) -> Result<()> {
-    if ctx.explain {
-        Explain::alert_create().print();
+async fn create(
+    ctx: &Context,
+    provider: &(dyn AlertProvider + Send + Sync),
+    specs: Vec<AlertSpec>,
+    expires: Option<u32>,
+) -> Result<()> {
+    // Check trigger names up front so a typo doesn't leave a half-made alert
+    if specs.iter().any(|s| !s.triggers.is_empty()) {
+        let available: Vec<String> = provider
+            .triggers()
+            .await
+            .map_err(explain)?
+            .into_iter()
+            .map(|t| t.name)
+            .collect();
+        for trigger in specs.iter().flat_map(|s| &s.triggers) {
+            if !available.contains(trigger) {
+                anyhow::bail!(
+                    "Unknown trigger '{trigger}'. Available: {}",
+                    available.join(", ")
+                );
+            }
+        }
     }
 
-    let client = ctx.client()?;
+    let mut created = Vec::new();
+    for spec in specs {
+        if spec.cidrs.is_empty() {
+            anyhow::bail!("Alert '{}' has no networks to monitor", spec.name);
+        }
 
-    let mut builder = client.alerts().create(name);
+        let request = CreateAlertRequest {
+            name: spec.name.clone(),
+            filters: AlertFilters::new(spec.cidrs.clone()),
+            expires,
+        };
+        let alert = provider.create_alert(&request).await.map_err(explain)?;
+
+        for trigger in &spec.triggers {
+            provider
+                .enable_trigger(&alert.id, trigger)
+                .await
+                .map_err(explain)?;
+        }
 
-    for ip in ips {
-        builder = builder.ip(ip);
-    }
+        if ctx.output_format == OutputFormat::Pretty {
+            println!(
+                "{} Created alert {} ({})",
+                "Success:".green().bold(),
+                spec.name.cyan(),
+                alert.id
+            );
+            if !spec.triggers.is_empty() {
+                println!("  {} {}", "Triggers:".bold(), spec.triggers.join(", "));
+            }
+        }
 
-    if expires > 0 {
-        builder = builder.expires_in_days(expires);
+        created.push(AlertSpec {
+            id: Some(alert.id),
+            size: Some(alert.size),
+            created: alert.created,
+            ..spec
+        });
     }
 
-    let alert = builder.send().await?;
-
     match ctx.output_format {
         OutputFormat::Json => {
-            println!("{}", serde_json::to_string_pretty(&alert)?);
-        }
-        _ => {
-            println!("{}", "Alert created!".green().bold());
-            println!();
-            println!("  {} {}", "ID:".bold(), alert.id.cyan());
-            println!("  {} {}", "Name:".bold(), alert.name);
-            println!("  {} {}", "IPs:".bold(), alert.filters.ip.join(", "));
-
-            if expires > 0 {
-                println!("  {} {} days", "Expires in:".bold(), expires);
-            } else {
-                println!("  {} Never", "Expires:".bold());
+            println!("{}", serde_json::to_string_pretty(&created)?);
+        }
+        OutputFormat::Yaml => println!("{}", serde_yaml::to_string(&created)?),
+        OutputFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(std::io::stdout().lock());
+            writer.write_record(["id", "name"])?;
+            for alert in &created {
+                writer.write_record([alert.id.as_deref().unwrap_or(""), &alert.name])?;
             }
-
-            println!();
-            println!("View details: {} alert get {}", "showdi1".cyan(), alert.id);
+            writer.flush()?;
         }
+        OutputFormat::Pretty => {}
     }
 
     Ok(())
 }
 
-async fn get_alert(ctx: Context, id: &str) -> Result<()> {
-    if ctx.explain {
-        Explain::alert_get().print();
-    }
-
-    let client = ctx.client()?;
-    let alert = client.alerts().get(id).await?;
+async fn triggers(ctx: &Context, provider: &(dyn AlertProvider + Send + Sync)) -> Result<()> {
+    let triggers = provider.triggers().await.map_err(explain)?;
 
     match ctx.output_format {
         OutputFormat::Json => {
-            println!("{}", serde_json::to_string_pretty(&alert)?);
+            println!("{}", serde_json::to_string_pretty(&triggers)?);
         }
-        _ => {
-            println!("{} {}", "Alert:".bold(), alert.name.cyan());
-            println!();
-            println!("  {} {}", "ID:".bold(), alert.id);
-            println!("  {} {}", "Created:".bold(), alert.created.as_deref().unwrap_or("?"));
-
-            if let Some(exp) = &alert.expires {
-                println!("  {} {}", "Expires:".bold(), exp);
-            }
-
-            println!();
-            println!("{}", "Monitored IPs:".bold().underline());
-            for ip in &alert.filters.ip {
-                println!("  {}", ip);
-            }
-
-            if !alert.triggers.is_empty() {
-                println!();
-                println!("{}", "Triggers:".bold().underline());
-                for (name, enabled) in &alert.triggers {
-                    let status = if *enabled { "enabled".green() } else { "disabled".dimmed() };
-                    println!("  {} [{}]", name, status);
-                }
+        OutputFormat::Yaml => println!("{}", serde_yaml::to_string(&triggers)?),
+        OutputFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(std::io::stdout().lock());
+            writer.write_record(["name", "description"])?;
+            for trigger in &triggers {
+                writer
+                    .write_record([&trigger.name, trigger.description.as_deref().unwrap_or("")])?;
             }
+            writer.flush()?;
+        }
+        OutputFormat::Pretty => {
+            let rows: Vec<TriggerRow> = triggers
+                .into_iter()
+                .map(|t| TriggerRow {
+                    name: t.name,
+                    description: t.description.unwrap_or_default(),
+                })
+                .collect();
+            let table = terminal::table(&rows);
+            println!("{table}");
         }
     }
 
     Ok(())
 }
 
-async fn delete_alert(ctx: Context, id: &str) -> Result<()> {
-    if ctx.explain {
-        Explain::alert_delete().print();
-    }
+/// Read alerts saved from `i1 alert list -o json` (or YAML): a list or a single alert.
+fn read_specs(path: &Path) -> Result<Vec<AlertSpec>> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Could not read {}: {e}", path.display()))?;
 
-    let client = ctx.client()?;
-    client.alerts().delete(id).await?;
+    // YAML is a superset of JSON, so this takes either
+    let value: serde_yaml::Value = serde_yaml::from_str(&text)?;
+    let specs = if value.is_sequence() {
+        serde_yaml::from_value(value)?
+    } else {
+        vec![serde_yaml::from_value(value)?]
+    };
 
-    match ctx.output_format {
-        OutputFormat::Json => {
-            println!("{}", serde_json::json!({ "deleted": id }));
-        }
-        _ => {
-            println!("{} Alert {} deleted.", "Success:".green().bold(), id.cyan());
-        }
-    }
-
-    Ok(())
+    Ok(specs)
 }
 
-async fn list_triggers(ctx: Context) -> Result<()> {
-    if ctx.explain {
-        Explain::alert_triggers().print();
+fn status(expired: bool, no_color: bool) -> String {
+    match (expired, no_color) {
+        (true, true) => "expired".to_string(),
+        (true, false) => "expired".red().to_string(),
+        (false, true) => "active".to_string(),
+        (false, false) => "active".green().to_string(),
     }
+}
 
-    let client = ctx.client()?;
-    let triggers = client.alerts().triggers().await?;
-
-    match ctx.output_format {
-        OutputFormat::Json => {
-            println!("{}", serde_json::to_string_pretty(&triggers)?);
+/// Turn auth and plan failures into something actionable.
+///
+/// The provider error stays underneath so the exit code still reflects it.
+fn explain(e: I1Error) -> anyhow::Error {
+    let help = match &e {
+        I1Error::Unauthorized => {
+            "Shodan rejected the request. Check your API key, and that your plan \
+             includes network monitoring (Membership or higher)."
         }
-        _ => {
-            println!("{}", "Available Alert Triggers:".bold());
-            println!();
-
-            for trigger in &triggers {
-                println!("  {}", trigger.name.cyan().bold());
-                if let Some(desc) = &trigger.description {
-                    println!("    {}", desc);
-                }
-                if let Some(rule) = &trigger.rule {
-                    println!("    Rule: {}", rule.dimmed());
-                }
-                println!();
-            }
+        I1Error::Provider { code: 403, .. } => {
+            "Network monitoring is not included in your Shodan plan.\n\
+             See https://account.shodan.io/billing to upgrade."
         }
-    }
-
-    Ok(())
+        I1Error::NotFound { .. } => "Alert not found",
+        _ => return e.into(),
+    };
+    anyhow::Error::new(e).context(help)
 }
@@ -0,0 +1,247 @@
+//! `i1 audit` - Read the audit log of provider calls and defend changes.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+
+use anyhow::Result;
+use chrono::{Duration, NaiveDate, Utc};
+use colored::Colorize;
+use i1::audit::{AuditEntry, AuditKind};
+use serde::Serialize;
+
+use super::Context;
+use crate::cli::args::{AuditArgs, AuditCommands};
+use crate::config::Config;
+use crate::output::OutputFormat;
+
+pub fn execute(ctx: &Context, args: &AuditArgs) -> Result<()> {
+    let log = crate::audit::configured(&Config::load()?)?;
+    let entries = log.entries()?;
+
+    if entries.is_empty() && ctx.output_format == OutputFormat::Pretty && !ctx.quiet {
+        println!("No audit entries in {}", log.path().display());
+        if crate::audit::log().is_none() {
+            println!(
+                "{}",
+                "Turn it on with --audit, or `i1 config set audit-log <path>`.".dimmed()
+            );
+        }
+        return Ok(());
+    }
+
+    match args.command {
+        AuditCommands::Tail { lines } => tail(ctx, &entries[entries.len().saturating_sub(lines)..]),
+        AuditCommands::Summary { days } => summary(ctx, &entries, days),
+    }
+}
+
+fn tail(ctx: &Context, entries: &[AuditEntry]) -> Result<()> {
+    match ctx.output_format {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(entries)?);
+        }
+        OutputFormat::Yaml => println!("{}", serde_yaml::to_string(entries)?),
+        OutputFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(std::io::stdout());
+            writer.write_record([
+                "at",
+                "kind",
+                "provider",
+                "operation",
+                "targets",
+                "ok",
+                "error",
+                "latency_ms",
+                "credits",
+            ])?;
+            for entry in entries {
+                writer.write_record([
+                    entry.at.to_rfc3339(),
+                    kind(entry.kind).to_string(),
+                    entry.provider.clone().unwrap_or_default(),
+                    entry.operation.clone(),
+                    entry.targets.join(" "),
+                    entry.ok.to_string(),
+                    entry.error.clone().unwrap_or_default(),
+                    entry
+                        .latency_ms
+                        .map(|ms| ms.to_string())
+                        .unwrap_or_default(),
+                    entry.credits.map(|c| c.to_string()).unwrap_or_default(),
+                ])?;
+            }
+            writer.flush()?;
+        }
+        OutputFormat::Pretty => {
+            for entry in entries {
+                let status = if entry.ok { "✓".green() } else { "✗".red() };
+                let who = entry
+                    .provider
+                    .as_deref()
+                    .unwrap_or_else(|| kind(entry.kind));
+                let mut line = format!(
+                    "{} {status} {:<8} {} {}",
+                    entry.at.format("%Y-%m-%d %H:%M:%S").to_string().dimmed(),
+                    who.cyan(),
+                    entry.operation.bold(),
+                    entry.targets.join(", ")
+                );
+                if let Some(ms) = entry.latency_ms {
+                    let _ = write!(line, " {}", format!("{ms}ms").dimmed());
+                }
+                if let Some(credits @ 1..) = entry.credits {
+                    let _ = write!(line, " {}", format!("{credits} credits").yellow());
+                }
+                if let Some(error) = &entry.error {
+                    let _ = write!(line, " {}", error.red());
+                }
+                println!("{line}");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+const fn kind(kind: AuditKind) -> &'static str {
+    match kind {
+        AuditKind::Call => "call",
+        AuditKind::Action => "action",
+    }
+}
+
+/// One day of `audit summary`.
+#[derive(Debug, Default, Serialize, PartialEq, Eq)]
+struct Day {
+    calls: u64,
+    failed: u64,
+    credits: u64,
+    changes: u64,
+    /// Calls per provider
+    providers: BTreeMap<String, u64>,
+}
+
+/// Count `entries` per UTC day, newer than `days` ago if given.
+fn by_day(entries: &[AuditEntry], days: Option<u32>) -> BTreeMap<NaiveDate, Day> {
+    let since = days.map(|days| (Utc::now() - Duration::days(i64::from(days) - 1)).date_naive());
+    let mut summary: BTreeMap<NaiveDate, Day> = BTreeMap::new();
+
+    for entry in entries {
+        let date = entry.at.date_naive();
+        if since.is_some_and(|since| date < since) {
+            continue;
+        }
+        let day = summary.entry(date).or_default();
+        match entry.kind {
+            AuditKind::Call => {
+                day.calls += 1;
+                day.failed += u64::from(!entry.ok);
+                day.credits += u64::from(entry.credits.unwrap_or(0));
+                if let Some(provider) = &entry.provider {
+                    *day.providers.entry(provider.clone()).or_default() += 1;
+                }
+            }
+            AuditKind::Action => day.changes += 1,
+        }
+    }
+    summary
+}
+
+fn summary(ctx: &Context, entries: &[AuditEntry], days: Option<u32>) -> Result<()> {
+    let summary = by_day(entries, days);
+
+    match ctx.output_format {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&summary)?);
+        }
+        OutputFormat::Yaml => println!("{}", serde_yaml::to_string(&summary)?),
+        OutputFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(std::io::stdout());
+            writer.write_record(["date", "calls", "failed", "credits", "changes"])?;
+            for (date, day) in &summary {
+                writer.write_record([
+                    date.to_string(),
+                    day.calls.to_string(),
+                    day.failed.to_string(),
+                    day.credits.to_string(),
+                    day.changes.to_string(),
+                ])?;
+            }
+            writer.flush()?;
+        }
+        OutputFormat::Pretty => {
+            println!(
+                "{}",
+                format!(
+                    "{:<12}{:>7}{:>8}{:>9}{:>9}  {}",
+                    "Date", "Calls", "Failed", "Credits", "Changes", "Providers"
+                )
+                .bold()
+            );
+            for (date, day) in &summary {
+                let providers = day
+                    .providers
+                    .iter()
+                    .map(|(name, calls)| format!("{name} {calls}"))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let failed = if day.failed > 0 {
+                    format!("{:>8}", day.failed).red().to_string()
+                } else {
+                    format!("{:>8}", day.failed)
+                };
+                println!(
+                    "{:<12}{:>7}{failed}{:>9}{:>9}  {}",
+                    date.to_string(),
+                    day.calls,
+                    day.credits,
+                    day.changes,
+                    providers.dimmed()
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(at: &str, kind: AuditKind, ok: bool, credits: Option<u32>) -> AuditEntry {
+        AuditEntry {
+            at: at.parse().unwrap(),
+            kind,
+            provider: (kind == AuditKind::Call).then(|| "shodan".to_string()),
+            operation: "lookup".to_string(),
+            targets: vec!["192.0.2.1".to_string()],
+            ok,
+            error: None,
+            latency_ms: None,
+            credits,
+        }
+    }
+
+    #[test]
+    fn test_by_day() {
+        let entries = [
+            entry("2026-03-01T10:00:00Z", AuditKind::Call, true, Some(1)),
+            entry("2026-03-01T23:59:59Z", AuditKind::Call, false, Some(0)),
+            entry("2026-03-01T12:00:00Z", AuditKind::Action, true, None),
+            entry("2026-03-02T00:00:00Z", AuditKind::Call, true, Some(2)),
+        ];
+
+        let summary = by_day(&entries, None);
+        let first = &summary[&"2026-03-01".parse().unwrap()];
+        assert_eq!(
+            (first.calls, first.failed, first.credits, first.changes),
+            (2, 1, 1, 1)
+        );
+        assert_eq!(first.providers["shodan"], 2);
+        assert_eq!(summary[&"2026-03-02".parse().unwrap()].credits, 2);
+
+        // Far in the past, so nothing is recent enough
+        assert!(by_day(&entries, Some(1)).is_empty());
+    }
+}
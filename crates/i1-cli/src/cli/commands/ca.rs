@@ -0,0 +1,1038 @@
+//! `i1 ca` - Run a small certificate authority with i1-ca.
+//!
+//! A CA directory holds the root (`root.key`, `root.crt`), the current
+//! intermediate (`intermediate.key`, `intermediate.crt` and `chain.pem`,
+//! the intermediate followed by the root), `ca.json`, which records
+//! everything issued and revoked, and `inventory.json`, every certificate
+//! issued with its PEM, for `list` and `expiring`. Issued certificates go
+//! to `certs/` unless `--out` says otherwise.
+//!
+//! Keys are written readable only by their owner and never replaced
+//! without `--force`. The root key is only read by `issue-intermediate` and
+//! `crl export --root`, so day-to-day issuing can run from a copy of the
+//! directory without it.
+
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context as _, Result};
+use chrono::{DateTime, Duration, Utc};
+use clap::ValueEnum;
+use colored::Colorize;
+use i1_ca::{
+    CertStore, CertificateInfo, CertificateRequest, CertificateType, IntermediateCa,
+    IntermediatePurpose, KeyAlgorithm, RevocationList, RevocationListConfig, RevocationReason,
+    RootCa, SerialRegistry, StoredCert,
+};
+use serde::{Deserialize, Serialize};
+use tabled::Tabled;
+
+use super::Context;
+use crate::cli::args::{CaArgs, CaCommands, CrlCommands};
+use crate::output::{terminal, OutputFormat};
+
+const ROOT_KEY: &str = "root.key";
+const ROOT_CERT: &str = "root.crt";
+const INTERMEDIATE_KEY: &str = "intermediate.key";
+const INTERMEDIATE_CERT: &str = "intermediate.crt";
+/// The intermediate followed by the root
+const CHAIN: &str = "chain.pem";
+const INDEX: &str = "ca.json";
+/// Every certificate issued, with its PEM
+const INVENTORY: &str = "inventory.json";
+/// Where issued certificates go inside the CA directory
+const CERTS: &str = "certs";
+
+/// Why a certificate is revoked, as RFC 5280 names them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Reason {
+    Unspecified,
+    KeyCompromise,
+    CaCompromise,
+    AffiliationChanged,
+    Superseded,
+    CessationOfOperation,
+    CertificateHold,
+    PrivilegeWithdrawn,
+}
+
+impl From<Reason> for RevocationReason {
+    fn from(reason: Reason) -> Self {
+        match reason {
+            Reason::Unspecified => Self::Unspecified,
+            Reason::KeyCompromise => Self::KeyCompromise,
+            Reason::CaCompromise => Self::CaCompromise,
+            Reason::AffiliationChanged => Self::AffiliationChanged,
+            Reason::Superseded => Self::Superseded,
+            Reason::CessationOfOperation => Self::CessationOfOperation,
+            Reason::CertificateHold => Self::CertificateHold,
+            Reason::PrivilegeWithdrawn => Self::PrivilegeWithdrawn,
+        }
+    }
+}
+
+/// Everything one CA directory has issued and revoked, kept in `ca.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Index {
+    root: CertificateInfo,
+    /// Every intermediate the root signed; the last one is current
+    #[serde(default)]
+    intermediates: Vec<CertificateInfo>,
+    #[serde(default)]
+    issued: Vec<Issued>,
+    /// Revoked intermediates, signed by the root
+    root_crl: RevocationList,
+    /// Revoked certificates of the current intermediate, signed by it
+    #[serde(default)]
+    crl: RevocationList,
+    /// Number of the last CRL exported, from either list
+    #[serde(default)]
+    crl_number: u64,
+    /// Serials the root has issued
+    #[serde(default)]
+    root_serials: SerialRegistry,
+    /// Serials the current intermediate has issued
+    #[serde(default)]
+    serials: SerialRegistry,
+}
+
+/// An end-entity certificate and the intermediate that signed it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Issued {
+    #[serde(flatten)]
+    info: CertificateInfo,
+    /// Serial of the signing intermediate
+    intermediate: String,
+}
+
+impl Index {
+    fn new(root: CertificateInfo) -> Self {
+        Self {
+            root_crl: RevocationList::new(root.subject.clone()),
+            root,
+            intermediates: Vec::new(),
+            issued: Vec::new(),
+            crl: RevocationList::default(),
+            crl_number: 0,
+            root_serials: SerialRegistry::new(),
+            serials: SerialRegistry::new(),
+        }
+    }
+
+    fn load(dir: &Path) -> Result<Self> {
+        let path = dir.join(INDEX);
+        if !path.exists() {
+            anyhow::bail!(
+                "No CA in {} (create one with: i1 ca init-root --cn <name> --out {})",
+                dir.display(),
+                dir.display()
+            );
+        }
+
+        let content = std::fs::read_to_string(&path)?;
+        let mut index: Self = serde_json::from_str(&content)
+            .with_context(|| format!("{} is unreadable", path.display()))?;
+        index.backfill_serials();
+        Ok(index)
+    }
+
+    /// Record what was issued before `ca.json` kept serial registries.
+    fn backfill_serials(&mut self) {
+        self.root_serials.record(&self.root.serial);
+        for info in &self.intermediates {
+            self.root_serials.record(&info.serial);
+        }
+        if let Some(current) = self.intermediates.last() {
+            for issued in &self.issued {
+                if issued.intermediate == current.serial {
+                    self.serials.record(&issued.info.serial);
+                }
+            }
+        }
+    }
+
+    fn save(&self, dir: &Path) -> Result<()> {
+        std::fs::write(dir.join(INDEX), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    fn intermediate(&self) -> Result<&CertificateInfo> {
+        self.intermediates.last().ok_or_else(|| {
+            anyhow::anyhow!("No intermediate CA yet (create one with: i1 ca issue-intermediate)")
+        })
+    }
+}
+
+/// Files a command wrote, and the certificate in them.
+#[derive(Debug, Serialize)]
+struct Written {
+    #[serde(flatten)]
+    info: CertificateInfo,
+    key: PathBuf,
+    cert: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    chain: Option<PathBuf>,
+    /// Subject alternative names
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    sans: Vec<String>,
+}
+
+/// A revocation, for `-o json`.
+#[derive(Debug, Serialize)]
+struct Revoked {
+    serial: String,
+    subject: String,
+    cert_type: CertificateType,
+    reason: RevocationReason,
+    at: DateTime<Utc>,
+    /// The CRL that lists it: `root` or `intermediate`
+    crl: &'static str,
+}
+
+/// A signed CRL, for `-o json`.
+#[derive(Debug, Serialize)]
+struct Crl {
+    issuer: String,
+    number: u64,
+    this_update: DateTime<Utc>,
+    next_update: DateTime<Utc>,
+    revoked: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pem: Option<String>,
+}
+
+#[derive(Tabled)]
+struct CertRow {
+    #[tabled(rename = "Serial")]
+    serial: String,
+    #[tabled(rename = "Subject")]
+    subject: String,
+    #[tabled(rename = "Type")]
+    cert_type: String,
+    #[tabled(rename = "Issuer")]
+    issuer: String,
+    #[tabled(rename = "Expires")]
+    expires: String,
+    #[tabled(rename = "Status")]
+    status: String,
+}
+
+/// Execute the ca command.
+pub fn execute(ctx: &Context, args: CaArgs) -> Result<()> {
+    match args.command {
+        CaCommands::InitRoot {
+            cn,
+            out,
+            crl_url,
+            force,
+        } => init_root(ctx, &cn, &out, crl_url, force),
+        CaCommands::IssueIntermediate {
+            cn,
+            ca,
+            crl_url,
+            force,
+        } => issue_intermediate(ctx, &cn, &ca, crl_url, force),
+        CaCommands::IssueCert {
+            cn,
+            sans,
+            days,
+            ca,
+            out,
+            force,
+        } => issue_cert(ctx, &cn, &sans, days, &ca, out, force),
+        CaCommands::Revoke { serial, reason, ca } => revoke(ctx, &serial, reason, &ca),
+        CaCommands::List { ca } => list(ctx, &ca),
+        CaCommands::Expiring { days, ca } => expiring(ctx, days, &ca),
+        CaCommands::Crl(args) => match args.command {
+            CrlCommands::Export {
+                root,
+                out,
+                der,
+                days,
+                ca,
+            } => crl_export(ctx, root, out.as_deref(), der, days, &ca),
+        },
+    }
+}
+
+fn init_root(
+    ctx: &Context,
+    cn: &str,
+    out: &Path,
+    crl_url: Option<String>,
+    force: bool,
+) -> Result<()> {
+    let (key, cert) = (out.join(ROOT_KEY), out.join(ROOT_CERT));
+    refuse_overwrite(&[&key, &cert, &out.join(INDEX)], force)?;
+
+    std::fs::create_dir_all(out)?;
+    let root = RootCa::generate(cn, KeyAlgorithm::default())?;
+    root.save_to_files(&key, &cert)?;
+    let mut index = Index::new(root.info.clone());
+    index.root_crl.config.distribution_url = crl_url;
+    index.root_serials = root.serials();
+    index.save(out)?;
+    inventory(out).record(&root.info, root.certificate_pem())?;
+
+    let written = Written {
+        info: root.info,
+        key,
+        cert,
+        chain: None,
+        sans: Vec::new(),
+    };
+    report(
+        ctx,
+        "Root CA created",
+        &written,
+        Some("Keep root.key offline: it's only needed to sign intermediates and the root CRL."),
+    )
+}
+
+fn issue_intermediate(
+    ctx: &Context,
+    cn: &str,
+    dir: &Path,
+    crl_url: Option<String>,
+    force: bool,
+) -> Result<()> {
+    let mut index = Index::load(dir)?;
+    let (key, cert, chain) = (
+        dir.join(INTERMEDIATE_KEY),
+        dir.join(INTERMEDIATE_CERT),
+        dir.join(CHAIN),
+    );
+    refuse_overwrite(&[&key, &cert, &chain], force)?;
+
+    let root = load_root(dir, &index)?;
+    let intermediate = IntermediateCa::generate(cn, &root, KeyAlgorithm::default())?;
+    intermediate.save_to_files(&key, &chain)?;
+    std::fs::write(&cert, intermediate.certificate().pem())?;
+
+    let replaced = index.intermediates.last().map(|old| old.serial.clone());
+    index.intermediates.push(intermediate.info.clone());
+    index.crl = RevocationList::with_config(
+        cn,
+        RevocationListConfig {
+            distribution_url: crl_url,
+            ..RevocationListConfig::default()
+        },
+    );
+    index.root_serials = root.serials();
+    index.serials = intermediate.serials();
+    index.save(dir)?;
+    inventory(dir).record(&intermediate.info, &intermediate.certificate().pem())?;
+
+    let note = replaced.map(|serial| {
+        format!(
+            "Certificates from the previous intermediate stay valid until it is revoked: \
+             i1 ca revoke {serial}"
+        )
+    });
+    let written = Written {
+        info: intermediate.info,
+        key,
+        cert,
+        chain: Some(chain),
+        sans: Vec::new(),
+    };
+    report(ctx, "Intermediate CA issued", &written, note.as_deref())
+}
+
+fn issue_cert(
+    ctx: &Context,
+    cn: &str,
+    sans: &[String],
+    days: u32,
+    dir: &Path,
+    out: Option<PathBuf>,
+    force: bool,
+) -> Result<()> {
+    let names = subject_names(cn, sans)?;
+    if days == 0 {
+        anyhow::bail!("--days must be at least 1");
+    }
+
+    let mut index = Index::load(dir)?;
+    let current = index.intermediate()?;
+    if current.revoked {
+        anyhow::bail!(
+            "The intermediate CA is revoked; issue a new one with: i1 ca issue-intermediate --force"
+        );
+    }
+    if Utc::now() + Duration::days(days.into()) > current.not_after {
+        anyhow::bail!(
+            "A {days}-day certificate would outlive the intermediate CA (expires {})",
+            current.not_after.format("%Y-%m-%d")
+        );
+    }
+
+    let out = out.unwrap_or_else(|| dir.join(CERTS));
+    let stem = file_stem(cn);
+    let (key, cert, chain) = (
+        out.join(format!("{stem}.key")),
+        out.join(format!("{stem}.crt")),
+        out.join(format!("{stem}.chain.pem")),
+    );
+    refuse_overwrite(&[&key, &cert, &chain], force)?;
+
+    let intermediate = load_intermediate(dir, &index)?;
+    let issued = intermediate.issue(&CertificateRequest {
+        domains: names.clone(),
+        validity_days: days,
+        include_wildcard: cn.starts_with("*."),
+    })?;
+
+    std::fs::create_dir_all(&out)?;
+    i1_ca::write_key(&key, &issued.key_pem)?;
+    std::fs::write(&cert, &issued.cert_pem)?;
+    std::fs::write(&chain, &issued.chain_pem)?;
+
+    index.issued.push(Issued {
+        info: issued.info.clone(),
+        intermediate: intermediate.info.serial.clone(),
+    });
+    index.serials = intermediate.serials();
+    index.save(dir)?;
+    inventory(dir).record(&issued.info, &issued.cert_pem)?;
+
+    let written = Written {
+        info: issued.info,
+        key,
+        cert,
+        chain: Some(chain),
+        sans: names,
+    };
+    report(ctx, "Certificate issued", &written, None)
+}
+
+fn revoke(ctx: &Context, serial: &str, reason: Reason, dir: &Path) -> Result<()> {
+    let serial = i1_ca::normalize_serial(serial);
+    let reason = RevocationReason::from(reason);
+    let mut index = Index::load(dir)?;
+
+    let matches = |other: &str| i1_ca::normalize_serial(other) == serial;
+    if matches(&index.root.serial) {
+        anyhow::bail!("The root CA can't revoke itself; create a new one with: i1 ca init-root");
+    }
+
+    let current = index.intermediates.last().map(|i| i.serial.clone());
+    let (info, crl, name) =
+        if let Some(info) = index.intermediates.iter_mut().find(|i| matches(&i.serial)) {
+            (info, &mut index.root_crl, "root")
+        } else if let Some(issued) = index.issued.iter_mut().find(|i| matches(&i.info.serial)) {
+            if current.as_ref() != Some(&issued.intermediate) {
+                anyhow::bail!(
+                    "{serial} was signed by intermediate {}, which has been replaced; \
+                 revoke that intermediate instead",
+                    issued.intermediate
+                );
+            }
+            (&mut issued.info, &mut index.crl, "intermediate")
+        } else {
+            anyhow::bail!("No certificate with serial {serial} in {}", dir.display());
+        };
+
+    if info.revoked {
+        anyhow::bail!("{serial} ({}) is already revoked", info.subject);
+    }
+    info.revoked = true;
+    info.revocation_reason = Some(reason);
+    // CAs from before the inventory aren't in it
+    let store = inventory(dir);
+    if store.find_by_serial(&serial)?.is_some() {
+        store.mark_revoked(&serial, reason, crl)?;
+    } else {
+        crl.revoke(serial.clone(), reason);
+    }
+
+    let revoked = Revoked {
+        serial,
+        subject: info.subject.clone(),
+        cert_type: info.cert_type,
+        reason,
+        at: Utc::now(),
+        crl: name,
+    };
+    index.save(dir)?;
+
+    match ctx.output_format {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&revoked)?);
+        }
+        OutputFormat::Yaml => println!("{}", serde_yaml::to_string(&revoked)?),
+        _ => {
+            println!(
+                "{} Revoked {} ({}): {}",
+                "Success:".green().bold(),
+                revoked.subject.bold(),
+                revoked.serial,
+                revoked.reason
+            );
+            let flag = if name == "root" { " --root" } else { "" };
+            println!(
+                "{} Clients only see this once the CRL is published: i1 ca crl export{flag}",
+                "Note:".yellow()
+            );
+        }
+    }
+    Ok(())
+}
+
+fn list(ctx: &Context, dir: &Path) -> Result<()> {
+    Index::load(dir)?;
+    let certs = inventory(dir).all()?;
+    show_certs(ctx, &certs, "No certificates yet.")
+}
+
+fn expiring(ctx: &Context, days: u32, dir: &Path) -> Result<()> {
+    Index::load(dir)?;
+    let certs = inventory(dir).expiring_within(Duration::days(days.into()))?;
+    let empty = format!("Nothing expires in the next {days} days.");
+    show_certs(ctx, &certs, &empty)
+}
+
+fn show_certs(ctx: &Context, certs: &[StoredCert], empty: &str) -> Result<()> {
+    let infos: Vec<&CertificateInfo> = certs.iter().map(|cert| &cert.info).collect();
+    match ctx.output_format {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&infos)?);
+        }
+        OutputFormat::Yaml => println!("{}", serde_yaml::to_string(&infos)?),
+        OutputFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(std::io::stdout().lock());
+            writer.write_record([
+                "serial",
+                "subject",
+                "type",
+                "issuer",
+                "not_before",
+                "not_after",
+                "revoked",
+            ])?;
+            for info in &infos {
+                writer.write_record([
+                    info.serial.as_str(),
+                    &info.subject,
+                    cert_type(info.cert_type),
+                    &info.issuer,
+                    &info.not_before.to_rfc3339(),
+                    &info.not_after.to_rfc3339(),
+                    &info.revoked.to_string(),
+                ])?;
+            }
+            writer.flush()?;
+        }
+        OutputFormat::Pretty => {
+            if infos.is_empty() {
+                println!("{empty}");
+                return Ok(());
+            }
+            let now = Utc::now();
+            let rows: Vec<CertRow> = infos
+                .iter()
+                .map(|info| CertRow {
+                    serial: info.serial.clone(),
+                    subject: info.subject.clone(),
+                    cert_type: cert_type(info.cert_type).to_string(),
+                    issuer: info.issuer.clone(),
+                    expires: info.not_after.format("%Y-%m-%d").to_string(),
+                    status: status(info, now, ctx.no_color),
+                })
+                .collect();
+            println!("{}", terminal::table(&rows));
+        }
+    }
+    Ok(())
+}
+
+const fn cert_type(cert_type: CertificateType) -> &'static str {
+    match cert_type {
+        CertificateType::Root => "root",
+        CertificateType::Intermediate => "intermediate",
+        CertificateType::EndEntity => "server",
+    }
+}
+
+fn status(info: &CertificateInfo, now: DateTime<Utc>, no_color: bool) -> String {
+    let (text, color) = if info.revoked {
+        ("revoked", colored::Color::Red)
+    } else if info.not_after < now {
+        ("expired", colored::Color::Yellow)
+    } else {
+        ("valid", colored::Color::Green)
+    };
+    if no_color {
+        text.to_string()
+    } else {
+        text.color(color).to_string()
+    }
+}
+
+fn crl_export(
+    ctx: &Context,
+    root: bool,
+    out: Option<&Path>,
+    der: bool,
+    days: u32,
+    dir: &Path,
+) -> Result<()> {
+    if days == 0 {
+        anyhow::bail!("--days must be at least 1");
+    }
+    let mut index = Index::load(dir)?;
+
+    // Both lists share one sequence of CRL numbers
+    let renew = |list: &RevocationList, last: u64| {
+        let mut list = list.clone();
+        list.crl_number = last;
+        list.config.validity = std::time::Duration::from_secs(u64::from(days) * 24 * 60 * 60);
+        list.renew()
+    };
+    let (list, signed) = if root {
+        let ca = load_root(dir, &index)?;
+        index.root_crl = renew(&index.root_crl, index.crl_number);
+        (index.root_crl.clone(), index.root_crl.sign(&ca)?)
+    } else {
+        let ca = load_intermediate(dir, &index)?;
+        index.crl = renew(&index.crl, index.crl_number);
+        (index.crl.clone(), index.crl.sign(&ca)?)
+    };
+    let number = signed.crl_number;
+
+    let pem = if der { None } else { Some(signed.pem) };
+    if let Some(path) = out {
+        match &pem {
+            Some(pem) => std::fs::write(path, pem)?,
+            None => std::fs::write(path, &signed.der)?,
+        }
+    }
+    index.crl_number = number;
+    index.save(dir)?;
+
+    let crl = Crl {
+        issuer: list.issuer.clone(),
+        number,
+        this_update: list.this_update,
+        next_update: list.next_update,
+        revoked: list.len(),
+        path: out.map(Path::to_path_buf),
+        pem: pem.filter(|_| out.is_none()),
+    };
+    match ctx.output_format {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&crl)?);
+        }
+        OutputFormat::Yaml => println!("{}", serde_yaml::to_string(&crl)?),
+        _ => match &crl.path {
+            Some(path) => println!(
+                "{} CRL #{} from {} written to {} ({} revoked, next update due {})",
+                "Success:".green().bold(),
+                crl.number,
+                crl.issuer.bold(),
+                path.display(),
+                crl.revoked,
+                crl.next_update.format("%Y-%m-%d %H:%M UTC")
+            ),
+            None => print!("{}", crl.pem.as_deref().unwrap_or_default()),
+        },
+    }
+    Ok(())
+}
+
+fn inventory(dir: &Path) -> CertStore {
+    CertStore::open(dir.join(INVENTORY))
+}
+
+fn load_root(dir: &Path, index: &Index) -> Result<RootCa> {
+    let key_path = dir.join(ROOT_KEY);
+    let key = std::fs::read_to_string(&key_path).with_context(|| {
+        format!(
+            "Could not read the root key {} (it's needed to sign intermediates and the root CRL)",
+            key_path.display()
+        )
+    })?;
+    let cert = std::fs::read_to_string(dir.join(ROOT_CERT))?;
+    Ok(RootCa::from_pem(&key, &cert, index.root.clone())?
+        .with_serials(index.root_serials.clone())
+        .with_crl_url(index.root_crl.config.distribution_url.clone()))
+}
+
+fn load_intermediate(dir: &Path, index: &Index) -> Result<IntermediateCa> {
+    let info = index.intermediate()?;
+    let key_path = dir.join(INTERMEDIATE_KEY);
+    let key = std::fs::read_to_string(&key_path)
+        .with_context(|| format!("Could not read the intermediate key {}", key_path.display()))?;
+    let chain = std::fs::read_to_string(dir.join(CHAIN))?;
+    Ok(
+        IntermediateCa::from_pem(&key, &chain, info.clone(), IntermediatePurpose::General)?
+            .with_serials(index.serials.clone())
+            .with_crl_url(index.crl.config.distribution_url.clone()),
+    )
+}
+
+/// Fail if any of `paths` exists, unless `force`.
+fn refuse_overwrite(paths: &[&Path], force: bool) -> Result<()> {
+    if force {
+        return Ok(());
+    }
+    if let Some(path) = paths.iter().find(|p| p.exists()) {
+        anyhow::bail!(
+            "{} already exists; pass --force to replace it",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+/// The names a certificate covers: the common name, then each `--san`.
+///
+/// SANs are `dns:<name>` or `ip:<address>`; duplicates are dropped.
+fn subject_names(cn: &str, sans: &[String]) -> Result<Vec<String>> {
+    let cn = cn.trim();
+    if cn.is_empty() {
+        anyhow::bail!("--cn can't be empty");
+    }
+
+    let mut names = vec![cn.to_string()];
+    for san in sans {
+        let name = match san.split_once(':') {
+            Some(("dns", name)) if !name.is_empty() && name.parse::<IpAddr>().is_err() => {
+                name.to_string()
+            }
+            Some(("ip", addr)) => addr
+                .parse::<IpAddr>()
+                .map_err(|_| anyhow::anyhow!("'{addr}' in --san {san} is not an IP address"))?
+                .to_string(),
+            _ => anyhow::bail!("SANs are dns:<name> or ip:<address> (got '{san}')"),
+        };
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    Ok(names)
+}
+
+/// A file name for a certificate's common name, e.g. `wildcard.example.com`.
+fn file_stem(cn: &str) -> String {
+    cn.trim()
+        .replace('*', "wildcard")
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+fn report(ctx: &Context, title: &str, written: &Written, note: Option<&str>) -> Result<()> {
+    match ctx.output_format {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(written)?);
+        }
+        OutputFormat::Yaml => println!("{}", serde_yaml::to_string(written)?),
+        _ => {
+            let info = &written.info;
+            println!("{} {title}", "Success:".green().bold());
+            println!("  Subject: {}", info.subject.bold());
+            println!("  Issuer:  {}", info.issuer);
+            println!("  Serial:  {}", info.serial);
+            println!(
+                "  Valid:   {} to {}",
+                info.not_before.format("%Y-%m-%d"),
+                info.not_after.format("%Y-%m-%d")
+            );
+            if !written.sans.is_empty() {
+                println!("  SANs:    {}", written.sans.join(", "));
+            }
+            println!("  Key:     {} (owner only)", written.key.display());
+            println!("  Cert:    {}", written.cert.display());
+            if let Some(chain) = &written.chain {
+                println!("  Chain:   {}", chain.display());
+            }
+            if let Some(note) = note {
+                println!("{} {note}", "Note:".yellow());
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use rustls_pki_types::pem::PemObject;
+    use rustls_pki_types::{CertificateDer, ServerName, UnixTime};
+    use webpki::{
+        BorrowedCertRevocationList, CertRevocationList, EndEntityCert, KeyUsage,
+        RevocationCheckDepth, RevocationOptionsBuilder,
+    };
+
+    use super::*;
+
+    fn ctx() -> Context {
+        Context {
+            shodan_key: None,
+            censys_id: None,
+            censys_secret: None,
+            criminalip_key: None,
+            token: None,
+            shodan_url: None,
+            censys_url: None,
+            criminalip_url: None,
+            provider: "auto".to_string(),
+            output_format: OutputFormat::Json,
+            stix: false,
+            profile: None,
+            explain: false,
+            verbose: false,
+            no_color: true,
+            quiet: false,
+            cache: crate::cache::Mode::Off,
+            raw: false,
+            http: i1::ProviderHttpConfig::default(),
+            policy: i1::QueryPolicy::new(),
+            sources: BTreeMap::new(),
+            clients: crate::cli::commands::Clients::default(),
+        }
+    }
+
+    fn certs(path: &Path) -> Vec<CertificateDer<'static>> {
+        CertificateDer::pem_file_iter(path)
+            .unwrap()
+            .map(Result::unwrap)
+            .collect()
+    }
+
+    #[test]
+    fn test_subject_names() {
+        let sans = ["dns:www.host.example", "ip:192.0.2.10", "dns:host.example"].map(String::from);
+        assert_eq!(
+            subject_names("host.example", &sans).unwrap(),
+            ["host.example", "www.host.example", "192.0.2.10"]
+        );
+
+        for bad in [
+            "www.host.example",
+            "dns:192.0.2.10",
+            "ip:host",
+            "uri:x",
+            "dns:",
+        ] {
+            assert!(
+                subject_names("host.example", &[bad.to_string()]).is_err(),
+                "{bad}"
+            );
+        }
+        assert!(subject_names(" ", &[]).is_err());
+        assert_eq!(file_stem("*.example.com"), "wildcard.example.com");
+        assert_eq!(file_stem("2001:db8::1"), "2001_db8__1");
+    }
+
+    /// Build a chain into a tempdir and check it the way a TLS client would.
+    #[test]
+    fn test_inventory() {
+        let ctx = ctx();
+        let dir = tempfile::tempdir().unwrap();
+        let ca = dir.path().join("ca");
+        assert!(list(&ctx, &ca).is_err());
+
+        init_root(&ctx, "Test Root CA", &ca, None, false).unwrap();
+        issue_intermediate(&ctx, "Test Intermediate CA", &ca, None, false).unwrap();
+        issue_cert(&ctx, "host.example", &[], 30, &ca, None, false).unwrap();
+        issue_cert(&ctx, "later.example", &[], 90, &ca, None, false).unwrap();
+        list(&ctx, &ca).unwrap();
+        expiring(&ctx, 30, &ca).unwrap();
+
+        let store = inventory(&ca);
+        let all = store.all().unwrap();
+        let types: Vec<_> = all.iter().map(|cert| cert.info.cert_type).collect();
+        assert_eq!(
+            types,
+            [
+                CertificateType::Root,
+                CertificateType::Intermediate,
+                CertificateType::EndEntity,
+                CertificateType::EndEntity
+            ]
+        );
+        assert_eq!(
+            all[3].cert_pem,
+            std::fs::read_to_string(ca.join(CERTS).join("later.example.crt")).unwrap()
+        );
+        let soon = store.expiring_within(Duration::days(30)).unwrap();
+        assert_eq!(soon.len(), 1);
+        assert_eq!(soon[0].info.subject, "host.example");
+
+        // Revoking updates the inventory and puts one entry on the CRL
+        revoke(&ctx, &soon[0].info.serial, Reason::Superseded, &ca).unwrap();
+        let revoked = store.find_by_serial(&soon[0].info.serial).unwrap().unwrap();
+        assert!(revoked.info.revoked);
+        assert!(store
+            .expiring_within(Duration::days(30))
+            .unwrap()
+            .is_empty());
+        assert_eq!(Index::load(&ca).unwrap().crl.len(), 1);
+
+        // A CA from before the inventory can still revoke
+        std::fs::remove_file(ca.join(INVENTORY)).unwrap();
+        revoke(&ctx, &all[3].info.serial, Reason::Superseded, &ca).unwrap();
+        assert_eq!(Index::load(&ca).unwrap().crl.len(), 2);
+    }
+
+    #[test]
+    fn test_crl_url() {
+        let ctx = ctx();
+        let dir = tempfile::tempdir().unwrap();
+        let ca = dir.path().join("ca");
+        let root_url = "http://crl.host.example/root.crl";
+        let url = "http://crl.host.example/intermediate.crl";
+
+        init_root(&ctx, "Test Root CA", &ca, Some(root_url.to_string()), false).unwrap();
+        issue_intermediate(
+            &ctx,
+            "Test Intermediate CA",
+            &ca,
+            Some(url.to_string()),
+            false,
+        )
+        .unwrap();
+        issue_cert(&ctx, "host.example", &[], 30, &ca, None, false).unwrap();
+
+        let chain = certs(&ca.join(CERTS).join("host.example.chain.pem"));
+        let names =
+            |cert: &CertificateDer, url: &str| cert.windows(url.len()).any(|w| w == url.as_bytes());
+        assert!(names(&chain[0], url));
+        assert!(names(&chain[1], root_url));
+        assert!(!names(&chain[0], root_url));
+
+        // Each export is the next generation, due after --days
+        crl_export(&ctx, false, None, false, 3, &ca).unwrap();
+        crl_export(&ctx, false, None, false, 3, &ca).unwrap();
+        let index = Index::load(&ca).unwrap();
+        assert_eq!(index.crl_number, 2);
+        assert_eq!(index.crl.crl_number, 2);
+        assert_eq!(
+            index.crl.next_update - index.crl.this_update,
+            Duration::days(3)
+        );
+        assert_eq!(index.crl.config.distribution_url.as_deref(), Some(url));
+    }
+
+    #[test]
+    fn test_chain_end_to_end() {
+        let ctx = ctx();
+        let dir = tempfile::tempdir().unwrap();
+        let ca = dir.path().join("ca");
+
+        init_root(&ctx, "Test Root CA", &ca, None, false).unwrap();
+        issue_intermediate(&ctx, "Test Intermediate CA", &ca, None, false).unwrap();
+        let sans = ["dns:www.host.example", "ip:192.0.2.10"].map(String::from);
+        issue_cert(&ctx, "host.example", &sans, 30, &ca, None, false).unwrap();
+
+        let root = certs(&ca.join(ROOT_CERT));
+        let chain = certs(&ca.join(CERTS).join("host.example.chain.pem"));
+        assert_eq!(chain.len(), 3);
+        assert_eq!(certs(&ca.join(CERTS).join("host.example.crt"))[0], chain[0]);
+
+        let anchors = [webpki::anchor_from_trusted_cert(&root[0]).unwrap()];
+        let leaf = EndEntityCert::try_from(&chain[0]).unwrap();
+        let verify = |revocation| {
+            leaf.verify_for_usage(
+                webpki::ALL_VERIFICATION_ALGS,
+                &anchors,
+                &chain[1..2],
+                UnixTime::now(),
+                KeyUsage::server_auth(),
+                revocation,
+                None,
+            )
+            .map(|_| ())
+        };
+        verify(None).unwrap();
+        for name in ["host.example", "www.host.example", "192.0.2.10"] {
+            let name = ServerName::try_from(name).unwrap();
+            leaf.verify_is_valid_for_subject_name(&name).unwrap();
+        }
+        let other = ServerName::try_from("other.example").unwrap();
+        assert!(leaf.verify_is_valid_for_subject_name(&other).is_err());
+
+        #[cfg(unix)]
+        for key in [
+            ca.join(ROOT_KEY),
+            ca.join(INTERMEDIATE_KEY),
+            ca.join(CERTS).join("host.example.key"),
+        ] {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&key).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600, "{}", key.display());
+        }
+
+        // Existing key material is only replaced with --force
+        let key = std::fs::read(ca.join(ROOT_KEY)).unwrap();
+        assert!(init_root(&ctx, "Other Root", &ca, None, false).is_err());
+        assert!(issue_intermediate(&ctx, "Other", &ca, None, false).is_err());
+        assert!(issue_cert(&ctx, "host.example", &[], 30, &ca, None, false).is_err());
+        assert_eq!(std::fs::read(ca.join(ROOT_KEY)).unwrap(), key);
+
+        // Revoke the leaf and publish a CRL the client checks against
+        let serial = Index::load(&ca).unwrap().issued[0].info.serial.clone();
+        let colons = serial
+            .as_bytes()
+            .chunks(2)
+            .map(|pair| std::str::from_utf8(pair).unwrap().to_lowercase())
+            .collect::<Vec<_>>()
+            .join(":");
+        revoke(&ctx, &colons, Reason::KeyCompromise, &ca).unwrap();
+        assert!(revoke(&ctx, &serial, Reason::KeyCompromise, &ca).is_err());
+        assert!(revoke(&ctx, "00", Reason::Unspecified, &ca).is_err());
+
+        let crl_path = dir.path().join("intermediate.crl");
+        crl_export(&ctx, false, Some(&crl_path), true, 7, &ca).unwrap();
+        let der = std::fs::read(&crl_path).unwrap();
+        let crl = CertRevocationList::from(BorrowedCertRevocationList::from_der(&der).unwrap());
+        let crls = [&crl];
+        // Only the leaf: the intermediate is on the root's CRL, exported below
+        let options = RevocationOptionsBuilder::new(&crls)
+            .unwrap()
+            .with_depth(RevocationCheckDepth::EndEntity)
+            .build();
+        assert!(matches!(
+            verify(Some(options)),
+            Err(webpki::Error::CertRevoked)
+        ));
+
+        let index = Index::load(&ca).unwrap();
+        assert_eq!(index.crl_number, 1);
+        assert!(index.serials.contains(&index.issued[0].info.serial));
+        assert!(index.root_serials.contains(&index.intermediates[0].serial));
+        assert!(index.issued[0].info.revoked);
+        assert_eq!(
+            index.issued[0].info.revocation_reason,
+            Some(RevocationReason::KeyCompromise)
+        );
+
+        // Revoking the intermediate goes on the root's CRL
+        let serial = index.intermediates[0].serial.clone();
+        revoke(&ctx, &serial, Reason::CaCompromise, &ca).unwrap();
+        let crl_path = dir.path().join("root.crl");
+        crl_export(&ctx, true, Some(&crl_path), true, 7, &ca).unwrap();
+        let der = std::fs::read(&crl_path).unwrap();
+        let root_crl =
+            CertRevocationList::from(BorrowedCertRevocationList::from_der(&der).unwrap());
+        let crls = [&crl, &root_crl];
+        let options = RevocationOptionsBuilder::new(&crls).unwrap().build();
+        assert!(matches!(
+            verify(Some(options)),
+            Err(webpki::Error::CertRevoked)
+        ));
+        assert_eq!(Index::load(&ca).unwrap().crl_number, 2);
+        assert!(issue_cert(&ctx, "new.example", &[], 30, &ca, None, false).is_err());
+    }
+}
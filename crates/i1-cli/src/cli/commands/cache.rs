@@ -0,0 +1,292 @@
+//! `i1 cache` - Inspect and clear cached host and search answers, and find
+//! services in them by banner.
+
+use std::time::Duration;
+
+use anyhow::Result;
+use colored::Colorize;
+use i1::index::{BannerIndex, IndexedService};
+use regex::RegexBuilder;
+use serde::Serialize;
+use tabled::Tabled;
+
+use super::Context;
+use crate::cache;
+use crate::cli::args::{CacheArgs, CacheCommands};
+use crate::cli::exit::{self, Exit};
+use crate::output::{terminal, OutputFormat};
+use crate::workspace;
+
+/// A service `cache grep` found.
+#[derive(Serialize)]
+struct Found<'a> {
+    ip: &'a str,
+    port: u16,
+    /// How alike its banner is, for `--like`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    score: Option<f64>,
+    product: Option<&'a str>,
+    version: Option<&'a str>,
+    banner: Option<&'a str>,
+}
+
+impl<'a> Found<'a> {
+    fn new(service: &'a IndexedService, score: Option<f64>) -> Self {
+        Self {
+            ip: &service.ip,
+            port: service.port,
+            score,
+            product: service.product.as_deref(),
+            version: service.version.as_deref(),
+            banner: service.banner.as_deref(),
+        }
+    }
+
+    /// `OpenSSH 8.9p1`
+    fn product_name(&self) -> String {
+        match (self.product, self.version) {
+            (Some(product), Some(version)) => format!("{product} {version}"),
+            (Some(product), None) => product.to_string(),
+            _ => String::new(),
+        }
+    }
+}
+
+#[derive(Tabled)]
+struct FoundRow {
+    #[tabled(rename = "Host")]
+    ip: String,
+    #[tabled(rename = "Port")]
+    port: u16,
+    #[tabled(rename = "Product")]
+    product: String,
+    #[tabled(rename = "Banner")]
+    banner: String,
+}
+
+#[derive(Tabled)]
+struct LikeRow {
+    #[tabled(rename = "Score")]
+    score: String,
+    #[tabled(rename = "Host")]
+    ip: String,
+    #[tabled(rename = "Port")]
+    port: u16,
+    #[tabled(rename = "Product")]
+    product: String,
+    #[tabled(rename = "Banner")]
+    banner: String,
+}
+
+pub fn execute(ctx: &Context, args: CacheArgs) -> Result<()> {
+    match args.command {
+        CacheCommands::Stats => stats(ctx),
+        CacheCommands::Clear {
+            provider,
+            older_than,
+        } => clear(ctx, provider.as_deref(), older_than),
+        CacheCommands::Grep {
+            pattern,
+            like,
+            threshold,
+            product,
+        } => grep(
+            ctx,
+            pattern.as_deref(),
+            like.then_some(threshold),
+            product.as_deref(),
+        ),
+    }
+}
+
+fn stats(ctx: &Context) -> Result<()> {
+    let usage = cache::usage(&cache::dir()?);
+
+    match ctx.output_format {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&usage)?);
+        }
+        OutputFormat::Yaml => println!("{}", serde_yaml::to_string(&usage)?),
+        OutputFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(std::io::stdout());
+            writer.write_record(["provider", "entries", "bytes"])?;
+            for (provider, (entries, bytes)) in &usage.providers {
+                writer.write_record([provider, &entries.to_string(), &bytes.to_string()])?;
+            }
+            writer.flush()?;
+        }
+        OutputFormat::Pretty => {
+            println!("{}", "Cache:".bold());
+            println!("  {:<12}{}", "Path:".bold(), usage.path.display());
+            println!("  {:<12}{}", "Entries:".bold(), usage.entries);
+            println!("  {:<12}{}", "Size:".bold(), cache::size(usage.bytes));
+            let rate = usage.hit_rate.map_or_else(
+                || "no lookups yet".dimmed().to_string(),
+                |rate| {
+                    format!(
+                        "{rate:.0}% ({} of {})",
+                        usage.hits,
+                        usage.hits + usage.misses
+                    )
+                },
+            );
+            println!("  {:<12}{rate}", "Hit rate:".bold());
+
+            if !usage.providers.is_empty() {
+                println!();
+                for (provider, (entries, bytes)) in &usage.providers {
+                    println!("  {provider:<12}{}", cache::describe(*entries, *bytes));
+                }
+            }
+            if !ctx.quiet {
+                println!();
+                println!(
+                    "{}",
+                    format!(
+                        "Answers are reused for {}h. Use --refresh or --no-cache to skip them.",
+                        cache::TTL.as_secs() / 3600
+                    )
+                    .dimmed()
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn clear(ctx: &Context, provider: Option<&str>, older_than: Option<Duration>) -> Result<()> {
+    let removed = cache::clear(&cache::dir()?, provider, older_than)?;
+
+    match ctx.output_format {
+        OutputFormat::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(&serde_json::json!({ "removed": removed }))?
+        ),
+        OutputFormat::Yaml => println!(
+            "{}",
+            serde_yaml::to_string(&serde_json::json!({ "removed": removed }))?
+        ),
+        OutputFormat::Csv | OutputFormat::Pretty => {
+            if !ctx.quiet {
+                let entries = if removed == 1 { "entry" } else { "entries" };
+                println!("{} Removed {removed} cached {entries}", "✓".green());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// `i1 cache grep`: services of every cached host, and of the active
+/// workspace's, whose banner matches `pattern`, is like it when
+/// `threshold` is given, or that run `product`.
+fn grep(
+    ctx: &Context,
+    pattern: Option<&str>,
+    threshold: Option<f64>,
+    product: Option<&str>,
+) -> Result<()> {
+    let mut index = BannerIndex::new();
+    for host in cache::hosts(&cache::dir()?) {
+        index.add_host(&host);
+    }
+    if let Some(workspace) = workspace::active() {
+        for host in workspace.hosts()? {
+            index.add_host(&host);
+        }
+    }
+
+    let found: Vec<Found> = match (product, threshold, pattern) {
+        (Some(product), ..) => {
+            let (product, version) = match product.rsplit_once('/') {
+                Some((product, version)) => (product, Some(version)),
+                None => (product, None),
+            };
+            index
+                .find_product(product, version)
+                .iter()
+                .filter_map(|(ip, port)| index.get(ip, *port))
+                .map(|service| Found::new(service, None))
+                .collect()
+        }
+        (None, Some(threshold), Some(like)) => index
+            .find_similar(like, threshold)
+            .iter()
+            .filter_map(|(ip, port, score)| Some(Found::new(index.get(ip, *port)?, Some(*score))))
+            .collect(),
+        (None, None, Some(pattern)) => {
+            let regex = RegexBuilder::new(pattern)
+                .case_insensitive(true)
+                .build()
+                .map_err(|e| anyhow::anyhow!("Invalid pattern: {e}"))?;
+            index
+                .iter()
+                .filter(|service| service.banner.as_deref().is_some_and(|b| regex.is_match(b)))
+                .map(|service| Found::new(service, None))
+                .collect()
+        }
+        (None, _, None) => Vec::new(),
+    };
+
+    match ctx.output_format {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&found)?);
+        }
+        OutputFormat::Yaml => println!("{}", serde_yaml::to_string(&found)?),
+        OutputFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(std::io::stdout());
+            writer.write_record(["ip", "port", "score", "product", "version", "banner"])?;
+            for service in &found {
+                writer.write_record([
+                    service.ip.to_string(),
+                    service.port.to_string(),
+                    service.score.map(|s| format!("{s:.2}")).unwrap_or_default(),
+                    service.product.unwrap_or_default().to_string(),
+                    service.version.unwrap_or_default().to_string(),
+                    service.banner.unwrap_or_default().to_string(),
+                ])?;
+            }
+            writer.flush()?;
+        }
+        OutputFormat::Pretty => {
+            if found.is_empty() {
+                if !ctx.quiet {
+                    println!(
+                        "No service matches among {} indexed. Look hosts up, or scan \
+                         with --banner, to collect more.",
+                        index.len()
+                    );
+                }
+            } else if threshold.is_some() && product.is_none() {
+                let rows: Vec<LikeRow> = found
+                    .iter()
+                    .map(|service| LikeRow {
+                        score: format!("{:.2}", service.score.unwrap_or_default()),
+                        ip: service.ip.to_string(),
+                        port: service.port,
+                        product: service.product_name(),
+                        banner: service.banner.unwrap_or_default().to_string(),
+                    })
+                    .collect();
+                println!("{}", terminal::table(&rows));
+            } else {
+                let rows: Vec<FoundRow> = found
+                    .iter()
+                    .map(|service| FoundRow {
+                        ip: service.ip.to_string(),
+                        port: service.port,
+                        product: service.product_name(),
+                        banner: service.banner.unwrap_or_default().to_string(),
+                    })
+                    .collect();
+                println!("{}", terminal::table(&rows));
+            }
+        }
+    }
+
+    if found.is_empty() {
+        return Err(Exit(exit::NOT_FOUND).into());
+    }
+    Ok(())
+}
@@ -1,113 +1,313 @@
 //! `i1 config` - CLI configuration management.
 
+use std::time::Duration;
+
 use anyhow::Result;
+use clap::ValueEnum;
 use colored::Colorize;
+use serde::Serialize;
 
 use super::Context;
+use crate::cache;
 use crate::cli::args::{ConfigArgs, ConfigCommands};
-use crate::config::Config;
+use crate::config::{Config, Policy, Profile, Source};
+use crate::defend::asn;
 use crate::output::OutputFormat;
+use i1_providers::{HealthStatus, Provider};
+
+/// How long `doctor` waits on any one network check
+const DOCTOR_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Keys `config set` accepts, as completed by `i1 shell`
+pub const KEYS: &[&str] = &[
+    "shodan-key",
+    "censys-id",
+    "censys-secret",
+    "criminalip-key",
+    "token",
+    "provider",
+    "output",
+    "show_tips",
+    "geo-mirror",
+    "asn-source",
+    "proxy",
+    "no-proxy",
+    "audit-log",
+    "audit-hash-targets",
+    "explain",
+    "policy.deny-external",
+    "policy.allow-private",
+];
+
+/// Outcome of one `config doctor` check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Verdict {
+    Pass,
+    Warn,
+    Fail,
+}
+
+/// One line of the `config doctor` checklist.
+#[derive(Debug, Serialize)]
+struct Check {
+    name: String,
+    verdict: Verdict,
+    detail: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hint: Option<String>,
+}
+
+impl Check {
+    fn new(name: impl Into<String>, verdict: Verdict, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            verdict,
+            detail: detail.into(),
+            hint: None,
+        }
+    }
+
+    fn hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+}
 
 pub async fn execute(ctx: Context, args: ConfigArgs) -> Result<()> {
     match args.command {
         ConfigCommands::Show => show_config(ctx).await,
         ConfigCommands::Set { key, value } => set_config(ctx, &key, &value).await,
         ConfigCommands::Path => show_path(ctx).await,
+        ConfigCommands::Profiles => list_profiles(&ctx),
+        ConfigCommands::Doctor => doctor(&ctx).await,
     }
 }
 
 /// Mask an API key for display (show first 4 and last 4 chars).
-fn mask_key(key: &Option<String>) -> String {
-    key.as_ref()
-        .map(|k| {
-            if k.len() > 8 {
-                format!("{}...{}", &k[..4], &k[k.len() - 4..])
-            } else {
-                "****".to_string()
-            }
-        })
-        .unwrap_or_else(|| "(not set)".dimmed().to_string())
+fn mask_key(key: &str) -> String {
+    if key.len() > 8 {
+        format!("{}...{}", &key[..4], &key[key.len() - 4..])
+    } else {
+        "****".to_string()
+    }
 }
 
 async fn show_config(ctx: Context) -> Result<()> {
     let config = Config::load()?;
 
+    let output_format = ctx.output_format.to_string();
+    let settings: [(&str, Option<String>); 12] = [
+        ("shodan_key", ctx.shodan_key.as_deref().map(mask_key)),
+        ("censys_id", ctx.censys_id.as_deref().map(mask_key)),
+        ("censys_secret", ctx.censys_secret.as_deref().map(mask_key)),
+        (
+            "criminalip_key",
+            ctx.criminalip_key.as_deref().map(mask_key),
+        ),
+        ("token", ctx.token.as_deref().map(mask_key)),
+        ("output_format", Some(output_format)),
+        ("provider", Some(ctx.provider.clone())),
+        ("profile", ctx.profile.clone()),
+        // Password hidden
+        (
+            "proxy",
+            ctx.http.proxy.as_ref().and_then(|_| ctx.http.proxy_url()),
+        ),
+        ("shodan_url", ctx.shodan_url.clone()),
+        ("censys_url", ctx.censys_url.clone()),
+        ("criminalip_url", ctx.criminalip_url.clone()),
+    ];
+    let source = |key: &str| ctx.sources.get(key).copied().unwrap_or(Source::Default);
+
     match ctx.output_format {
-        OutputFormat::Json => {
-            println!("{}", serde_json::to_string_pretty(&config)?);
-        }
-        OutputFormat::Yaml => {
-            println!("{}", serde_yaml::to_string(&config)?);
+        OutputFormat::Json | OutputFormat::Yaml => {
+            let mut value = serde_json::Map::new();
+            for (key, setting) in &settings {
+                value.insert(
+                    (*key).to_string(),
+                    serde_json::json!({ "value": setting, "source": source(key) }),
+                );
+            }
+            value.insert("show_tips".to_string(), config.show_tips.into());
+            value.insert(
+                "explain_by_default".to_string(),
+                config.explain_by_default.into(),
+            );
+            value.insert("geo_mirror".to_string(), config.geo_mirror.into());
+            value.insert(
+                "asn_source".to_string(),
+                serde_json::to_value(config.asn_source)?,
+            );
+            value.insert("no_proxy".to_string(), config.no_proxy.into());
+            value.insert(
+                "audit_log".to_string(),
+                serde_json::to_value(&config.audit_log)?,
+            );
+            value.insert(
+                "audit_hash_targets".to_string(),
+                config.audit_hash_targets.into(),
+            );
+            value.insert("policy".to_string(), serde_json::to_value(&config.policy)?);
+            if let Ok(dir) = cache::dir() {
+                let usage = cache::usage(&dir);
+                value.insert(
+                    "cache".to_string(),
+                    serde_json::json!({
+                        "path": dir,
+                        "entries": usage.entries,
+                        "bytes": usage.bytes,
+                    }),
+                );
+            }
+
+            if ctx.output_format == OutputFormat::Yaml {
+                println!("{}", serde_yaml::to_string(&value)?);
+            } else {
+                println!("{}", serde_json::to_string_pretty(&value)?);
+            }
         }
         _ => {
             println!("{}", "Current Configuration:".bold());
             println!();
 
-            // Provider API keys (masked)
+            let (keys, other) = settings.split_at(5);
             println!("{}", "Provider Keys:".bold().underline());
-            println!(
-                "  {} {}",
-                "shodan_key:".bold(),
-                mask_key(&config.shodan_key)
-            );
-            println!("  {} {}", "censys_id:".bold(), mask_key(&config.censys_id));
-            println!(
-                "  {} {}",
-                "censys_secret:".bold(),
-                mask_key(&config.censys_secret)
-            );
-            println!(
-                "  {} {}",
-                "criminalip_key:".bold(),
-                mask_key(&config.criminalip_key)
-            );
+            for (key, setting) in keys {
+                print_setting(key, setting.as_deref(), source(key));
+            }
             println!();
 
-            // Output format
             println!("{}", "Settings:".bold().underline());
-            println!(
-                "  {} {:?}",
-                "output_format:".bold(),
-                config.output_format.unwrap_or(OutputFormat::Pretty)
-            );
-
-            // Other settings
+            for (key, setting) in other {
+                print_setting(key, setting.as_deref(), source(key));
+            }
             println!("  {} {}", "show_tips:".bold(), config.show_tips);
             println!(
                 "  {} {}",
                 "explain_by_default:".bold(),
                 config.explain_by_default
             );
+            if let Some(mirror) = &config.geo_mirror {
+                println!("  {} {mirror}", "geo_mirror:".bold());
+            }
+            if let Some(source) = config.asn_source {
+                println!("  {} {source}", "asn_source:".bold());
+            }
+            if let Some(hosts) = &config.no_proxy {
+                println!("  {} {hosts}", "no_proxy:".bold());
+            }
+            if let Some(path) = &config.audit_log {
+                let hashed = if config.audit_hash_targets {
+                    " (targets hashed)".dimmed().to_string()
+                } else {
+                    String::new()
+                };
+                println!("  {} {}{hashed}", "audit_log:".bold(), path.display());
+            }
+            print_policy(&config.policy);
+            if let Ok(dir) = cache::dir() {
+                let usage = cache::usage(&dir);
+                println!(
+                    "  {} {} {}",
+                    "cache:".bold(),
+                    dir.display(),
+                    format!("({})", cache::describe(usage.entries, usage.bytes)).dimmed()
+                );
+            }
         }
     }
 
     Ok(())
 }
 
-async fn set_config(_ctx: Context, key: &str, value: &str) -> Result<()> {
+/// The `[policy]` lines of `config show`, if anything is set.
+fn print_policy(policy: &Policy) {
+    if !policy.deny_external.is_empty() {
+        println!(
+            "  {} {}",
+            "policy.deny_external:".bold(),
+            policy.deny_external.join(", ")
+        );
+    }
+    for (provider, rules) in &policy.deny {
+        println!(
+            "  {} {}",
+            format!("policy.deny.{provider}:").bold(),
+            rules.join(", ")
+        );
+    }
+    if policy.allow_private {
+        println!("  {} true", "policy.allow_private:".bold());
+    }
+}
+
+/// A provider endpoint for `config set`, without its trailing slash.
+fn endpoint(provider: &str, value: &str) -> Result<String> {
+    let url = reqwest::Url::parse(value)
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+        .ok_or_else(|| {
+            anyhow::anyhow!("{provider} endpoint must be an http(s) URL (got '{value}')")
+        })?;
+    println!(
+        "{} {provider} requests will go to {}.",
+        "Success:".green().bold(),
+        url.as_str().cyan()
+    );
+    Ok(value.trim_end_matches('/').to_string())
+}
+
+/// One `key: value (source)` line of `config show`.
+fn print_setting(key: &str, value: Option<&str>, source: Source) {
+    let value = value.map_or_else(|| "(not set)".dimmed().to_string(), String::from);
+    let source = if source == Source::Default {
+        String::new()
+    } else {
+        format!("({source})").dimmed().to_string()
+    };
+    println!("  {:<20}{value} {source}", format!("{key}:").bold());
+}
+
+async fn set_config(ctx: Context, key: &str, value: &str) -> Result<()> {
     let mut config = Config::load()?;
+    let mut update = Profile::default();
 
     match key {
         // Provider keys
         "shodan-key" | "shodan_key" | "api_key" => {
-            config.shodan_key = Some(value.to_string());
+            update.shodan_key = Some(value.to_string());
             println!("{} Shodan API key set.", "Success:".green().bold());
         }
         "censys-id" | "censys_id" => {
-            config.censys_id = Some(value.to_string());
+            update.censys_id = Some(value.to_string());
             println!("{} Censys API ID set.", "Success:".green().bold());
         }
         "censys-secret" | "censys_secret" => {
-            config.censys_secret = Some(value.to_string());
+            update.censys_secret = Some(value.to_string());
             println!("{} Censys API secret set.", "Success:".green().bold());
         }
         "criminalip-key" | "criminalip_key" => {
-            config.criminalip_key = Some(value.to_string());
+            update.criminalip_key = Some(value.to_string());
             println!("{} Criminal IP API key set.", "Success:".green().bold());
         }
+        "token" | "i1-token" => {
+            update.token = Some(value.to_string());
+            println!("{} i1.is token set.", "Success:".green().bold());
+        }
         // Settings
+        "provider" => {
+            let name =
+                super::provider_name(value).map_err(|why| super::invalid_provider(value, why))?;
+            update.provider = Some(name.to_string());
+            println!(
+                "{} Default provider set to {}.",
+                "Success:".green().bold(),
+                name.cyan()
+            );
+        }
         "output_format" | "output" => {
-            config.output_format = Some(value.parse()?);
+            update.output_format = Some(value.parse()?);
             println!(
                 "{} Output format set to {}.",
                 "Success:".green().bold(),
@@ -118,6 +318,94 @@ async fn set_config(_ctx: Context, key: &str, value: &str) -> Result<()> {
             config.show_tips = value.parse()?;
             println!("{} show_tips set to {}.", "Success:".green().bold(), value);
         }
+        "geo_mirror" | "geo-mirror" => {
+            config.geo_mirror = Some(value.trim_end_matches('/').to_string());
+            println!(
+                "{} Geoblock mirror set to {}.",
+                "Success:".green().bold(),
+                value.cyan()
+            );
+        }
+        "asn_source" | "asn-source" => {
+            let source = asn::Source::from_str(value, true).map_err(|_| {
+                anyhow::anyhow!("asn-source is 'ripestat' or 'whois' (got '{value}')")
+            })?;
+            config.asn_source = Some(source);
+            println!(
+                "{} ASN prefixes will be looked up with {}.",
+                "Success:".green().bold(),
+                source.to_string().cyan()
+            );
+        }
+        "proxy" => {
+            let proxy = i1::ProviderHttpConfig::new().proxy(value);
+            proxy.client()?;
+            config.proxy = Some(value.to_string());
+            println!(
+                "{} Provider requests will go through {}.",
+                "Success:".green().bold(),
+                proxy.proxy_url().unwrap_or_default().cyan()
+            );
+        }
+        "no_proxy" | "no-proxy" => {
+            config.no_proxy = Some(value.to_string());
+            println!(
+                "{} {} will be reached without the proxy.",
+                "Success:".green().bold(),
+                value.cyan()
+            );
+        }
+        "shodan_url" | "shodan-url" => {
+            config.shodan_url = Some(endpoint("Shodan", value)?);
+        }
+        "censys_url" | "censys-url" => {
+            config.censys_url = Some(endpoint("Censys", value)?);
+        }
+        "criminalip_url" | "criminalip-url" => {
+            config.criminalip_url = Some(endpoint("Criminal IP", value)?);
+        }
+        "audit_log" | "audit-log" => {
+            config.audit_log = Some(value.into());
+            println!(
+                "{} Provider calls and defend changes will be logged to {}.",
+                "Success:".green().bold(),
+                value.cyan()
+            );
+        }
+        "audit_hash_targets" | "audit-hash-targets" => {
+            config.audit_hash_targets = value.parse()?;
+            println!(
+                "{} audit_hash_targets set to {}.",
+                "Success:".green().bold(),
+                value
+            );
+        }
+        "policy.deny-external" | "policy.deny_external" => {
+            add_rule(&mut config.policy.deny_external, value)?;
+            print_rules("third-party providers", &config.policy.deny_external);
+        }
+        key if key.starts_with("policy.deny.") => {
+            let name = &key["policy.deny.".len()..];
+            let name =
+                super::provider_name(name).map_err(|why| super::invalid_provider(name, why))?;
+            if matches!(name, "auto" | "all") {
+                anyhow::bail!("policy.deny.<provider> names one provider, not '{name}'");
+            }
+            let rules = config.policy.deny.entry(name.to_string()).or_default();
+            add_rule(rules, value)?;
+            print_rules(name, rules);
+            if rules.is_empty() {
+                config.policy.deny.remove(name);
+            }
+        }
+        "policy.allow-private" | "policy.allow_private" => {
+            config.policy.allow_private = value.parse()?;
+            println!(
+                "{} policy.allow_private set to {}.",
+                "Success:".green().bold(),
+                value
+            );
+        }
         "explain_by_default" | "explain" => {
             config.explain_by_default = value.parse()?;
             println!(
@@ -134,20 +422,413 @@ async fn set_config(_ctx: Context, key: &str, value: &str) -> Result<()> {
                  censys-id        - Censys API ID\n  \
                  censys-secret    - Censys API secret\n  \
                  criminalip-key   - Criminal IP API key\n  \
+                 token            - i1.is API token\n  \
+                 provider         - Default provider (auto/shodan/censys/...)\n  \
                  output_format    - Default output format (pretty/json/csv/yaml)\n  \
                  show_tips        - Show helpful tips (true/false)\n  \
-                 explain_by_default - Always explain commands (true/false)"
+                 explain_by_default - Always explain commands (true/false)\n  \
+                 geo-mirror       - Mirror for RIR delegation files (geoblock)\n  \
+                 asn-source       - ASN prefix lookups (ripestat/whois)\n  \
+                 proxy            - Proxy for provider requests (http/https/socks5 URL)\n  \
+                 no-proxy         - Hosts reached directly (api.i1.is,.internal)\n  \
+                 shodan-url       - Shodan API endpoint (a proxy or mock server)\n  \
+                 censys-url       - Censys API endpoint\n  \
+                 criminalip-url   - Criminal IP API endpoint\n  \
+                 audit-log        - File to log provider calls and defend changes to\n  \
+                 audit-hash-targets - Log targets as SHA-256 hashes (true/false)\n  \
+                 policy.deny-external - CIDR/IP/domain never sent to third-party providers\n  \
+                 policy.deny.<provider> - CIDR/IP/domain never sent to one provider\n  \
+                 policy.allow-private - Send private addresses to providers (true/false)"
             );
         }
     }
 
+    if let Some(profile) = &ctx.profile {
+        if update.keys().is_empty() {
+            println!("{}", format!("({key} applies to every profile)").dimmed());
+        } else {
+            println!("{}", format!("(saved to profile {profile})").dimmed());
+        }
+    }
+    config.update(ctx.profile.as_deref(), update);
     config.save()?;
 
     Ok(())
 }
 
+/// Add `value` to a policy deny list, or empty the list if `value` is
+/// empty.
+fn add_rule(rules: &mut Vec<String>, value: &str) -> Result<()> {
+    if value.trim().is_empty() {
+        rules.clear();
+        return Ok(());
+    }
+    let rule: i1::PolicyRule = value.parse()?;
+    let rule = rule.to_string();
+    if !rules.contains(&rule) {
+        rules.push(rule);
+    }
+    Ok(())
+}
+
+fn print_rules(from: &str, rules: &[String]) {
+    if rules.is_empty() {
+        println!(
+            "{} Nothing is withheld from {from}.",
+            "Success:".green().bold()
+        );
+    } else {
+        println!(
+            "{} Withheld from {from}: {}.",
+            "Success:".green().bold(),
+            rules.join(", ").cyan()
+        );
+    }
+}
+
 async fn show_path(_ctx: Context) -> Result<()> {
     let path = Config::path()?;
     println!("{}", path.display());
     Ok(())
 }
+
+fn list_profiles(ctx: &Context) -> Result<()> {
+    let config = Config::load()?;
+    let active = ctx.profile.as_deref();
+
+    match ctx.output_format {
+        OutputFormat::Json | OutputFormat::Yaml => {
+            let profiles: Vec<serde_json::Value> = config
+                .profiles
+                .iter()
+                .map(|(name, profile)| {
+                    serde_json::json!({
+                        "name": name,
+                        "active": active == Some(name.as_str()),
+                        "overrides": profile.keys(),
+                    })
+                })
+                .collect();
+
+            if ctx.output_format == OutputFormat::Yaml {
+                println!("{}", serde_yaml::to_string(&profiles)?);
+            } else {
+                println!("{}", serde_json::to_string_pretty(&profiles)?);
+            }
+        }
+        OutputFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(std::io::stdout().lock());
+            writer.write_record(["name", "active", "overrides"])?;
+            for (name, profile) in &config.profiles {
+                writer.write_record([
+                    name.as_str(),
+                    &(active == Some(name.as_str())).to_string(),
+                    &profile.keys().join(";"),
+                ])?;
+            }
+            writer.flush()?;
+        }
+        OutputFormat::Pretty => {
+            if config.profiles.is_empty() {
+                println!(
+                    "No profiles. Create one with: i1 config set --profile <name> <key> <value>"
+                );
+                return Ok(());
+            }
+
+            for (name, profile) in &config.profiles {
+                let marker = if active == Some(name.as_str()) {
+                    "*"
+                } else {
+                    " "
+                };
+                let overrides = profile.keys().join(", ");
+                if ctx.no_color {
+                    println!("{marker} {name:<16}{overrides}");
+                } else {
+                    println!(
+                        "{} {:<16}{}",
+                        marker.green(),
+                        name.bold(),
+                        overrides.dimmed()
+                    );
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Check the config file, key shapes, the network and every provider.
+///
+/// Fails (nonzero exit) if any check fails, so it can gate CI scripts.
+async fn doctor(ctx: &Context) -> Result<()> {
+    let mut checks = vec![check_file()];
+    checks.extend(check_keys(ctx));
+
+    let providers = ctx.providers()?;
+    let mut hosts: Vec<String> = providers
+        .iter()
+        .filter_map(|p| reqwest::Url::parse(p.base_url()).ok())
+        .filter_map(|url| url.host_str().map(String::from))
+        .collect();
+    hosts.sort();
+    hosts.dedup();
+
+    let network = futures_util::future::join_all(hosts.iter().map(|h| check_host(h)));
+    let health =
+        futures_util::future::join_all(providers.iter().map(|p| check_provider(p.as_ref())));
+    let (network, health) = futures_util::join!(network, health);
+    checks.extend(network.into_iter().flatten());
+    checks.extend(health);
+
+    match ctx.output_format {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&checks)?);
+        }
+        OutputFormat::Yaml => println!("{}", serde_yaml::to_string(&checks)?),
+        OutputFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(std::io::stdout().lock());
+            writer.write_record(["check", "verdict", "detail", "hint"])?;
+            for check in &checks {
+                writer.write_record([
+                    check.name.as_str(),
+                    verdict_name(check.verdict),
+                    &check.detail,
+                    check.hint.as_deref().unwrap_or(""),
+                ])?;
+            }
+            writer.flush()?;
+        }
+        OutputFormat::Pretty => {
+            let width = checks.iter().map(|c| c.name.len()).max().unwrap_or(0);
+            for check in &checks {
+                println!(
+                    "{} {:<width$}  {}",
+                    verdict_mark(check.verdict, ctx.no_color),
+                    check.name,
+                    check.detail
+                );
+                if let Some(hint) = &check.hint {
+                    println!("    {}", format!("-> {hint}").dimmed());
+                }
+            }
+        }
+    }
+
+    let failed = checks.iter().filter(|c| c.verdict == Verdict::Fail).count();
+    if failed > 0 {
+        anyhow::bail!("{failed} check(s) failed");
+    }
+    Ok(())
+}
+
+fn check_file() -> Check {
+    let path = match Config::path() {
+        Ok(path) => path,
+        Err(e) => return Check::new("config file", Verdict::Warn, e.to_string()),
+    };
+
+    if !path.exists() {
+        return Check::new(
+            "config file",
+            Verdict::Warn,
+            "not found (using env and flags only)",
+        )
+        .hint("Create it with: i1 config set shodan-key <KEY>");
+    }
+
+    match Config::load() {
+        Ok(_) => Check::new("config file", Verdict::Pass, path.display().to_string()),
+        Err(e) => Check::new(
+            "config file",
+            Verdict::Fail,
+            format!("{}: {e}", path.display()),
+        )
+        .hint("Fix the syntax, or move the file aside and run i1 config set again"),
+    }
+}
+
+/// Catch keys that were pasted wrong before they cost a round trip.
+fn check_keys(ctx: &Context) -> Vec<Check> {
+    let mut checks = Vec::new();
+
+    if let Some(key) = &ctx.shodan_key {
+        let check = if key.len() == 32 && key.chars().all(|c| c.is_ascii_alphanumeric()) {
+            Check::new("shodan key", Verdict::Pass, "looks like a Shodan key")
+        } else {
+            Check::new(
+                "shodan key",
+                Verdict::Warn,
+                format!(
+                    "expected 32 letters and digits, got {} characters",
+                    key.len()
+                ),
+            )
+            .hint(format!("Copy it again from {}", key_page("shodan")))
+        };
+        checks.push(check);
+    }
+
+    match (&ctx.censys_id, &ctx.censys_secret) {
+        (Some(_), Some(_)) => {
+            checks.push(Check::new("censys key", Verdict::Pass, "id and secret set"));
+        }
+        (Some(_), None) => checks.push(
+            Check::new("censys key", Verdict::Fail, "API ID set but no secret")
+                .hint("i1 config set censys-secret <SECRET>"),
+        ),
+        (None, Some(_)) => checks.push(
+            Check::new("censys key", Verdict::Fail, "API secret set but no ID")
+                .hint("i1 config set censys-id <ID>"),
+        ),
+        (None, None) => {}
+    }
+
+    let keys = [
+        ("shodan", &ctx.shodan_key),
+        ("censys", &ctx.censys_id),
+        ("censys", &ctx.censys_secret),
+        ("criminalip", &ctx.criminalip_key),
+    ];
+    for (provider, key) in keys {
+        if key
+            .as_deref()
+            .is_some_and(|k| k.chars().any(char::is_whitespace))
+        {
+            checks.push(
+                Check::new(
+                    format!("{provider} key"),
+                    Verdict::Warn,
+                    "contains whitespace",
+                )
+                .hint("Check for a stray space or newline from copy-paste"),
+            );
+        }
+    }
+
+    if !ctx.has_any_provider() {
+        checks.push(
+            Check::new("api keys", Verdict::Warn, "none configured")
+                .hint("Only local lookups will work. Run: i1 config set shodan-key <KEY>"),
+        );
+    }
+
+    checks
+}
+
+/// DNS, then a TCP connection to port 443.
+async fn check_host(host: &str) -> Vec<Check> {
+    let lookup = tokio::time::timeout(DOCTOR_TIMEOUT, tokio::net::lookup_host((host, 443))).await;
+    let addr = match lookup {
+        Ok(Ok(mut addrs)) => addrs.next(),
+        Ok(Err(e)) => {
+            return vec![
+                Check::new(format!("dns {host}"), Verdict::Fail, e.to_string())
+                    .hint("Check your resolver (/etc/resolv.conf) or network connection"),
+            ];
+        }
+        Err(_) => {
+            return vec![
+                Check::new(format!("dns {host}"), Verdict::Fail, "timed out")
+                    .hint("Check your resolver (/etc/resolv.conf) or network connection"),
+            ];
+        }
+    };
+    let Some(addr) = addr else {
+        return vec![Check::new(
+            format!("dns {host}"),
+            Verdict::Fail,
+            "no addresses",
+        )];
+    };
+
+    let dns = Check::new(format!("dns {host}"), Verdict::Pass, addr.ip().to_string());
+    let connect =
+        match tokio::time::timeout(DOCTOR_TIMEOUT, tokio::net::TcpStream::connect(addr)).await {
+            Ok(Ok(_)) => Check::new(format!("reach {host}"), Verdict::Pass, format!("{addr}")),
+            Ok(Err(e)) => Check::new(format!("reach {host}"), Verdict::Fail, e.to_string())
+                .hint("Outbound HTTPS looks blocked. Check firewall or proxy settings"),
+            Err(_) => Check::new(format!("reach {host}"), Verdict::Fail, "timed out")
+                .hint("Outbound HTTPS looks blocked. Check firewall or proxy settings"),
+        };
+
+    vec![dns, connect]
+}
+
+async fn check_provider(provider: &dyn Provider) -> Check {
+    let name = provider.display_name();
+    let health = match tokio::time::timeout(DOCTOR_TIMEOUT, provider.health_check()).await {
+        Ok(Ok(health)) => health,
+        Ok(Err(e)) => return provider_failure(provider, &e.to_string()),
+        Err(_) => {
+            return Check::new(name, Verdict::Fail, "no answer within 5s")
+                .hint(format!("{name} may be down, or the network is slow"));
+        }
+    };
+
+    let message = health.message.unwrap_or_default();
+    match health.status {
+        HealthStatus::Healthy => {
+            let latency = health
+                .latency_ms
+                .map_or_else(String::new, |ms| format!(" ({ms}ms)"));
+            let credits = health
+                .credits_remaining
+                .map_or_else(String::new, |n| format!(", {n} credits"));
+            Check::new(name, Verdict::Pass, format!("ok{latency}{credits}"))
+        }
+        HealthStatus::Degraded => Check::new(name, Verdict::Warn, message),
+        HealthStatus::Unhealthy => provider_failure(provider, &message),
+        HealthStatus::Unconfigured => Check::new(name, Verdict::Warn, "not configured"),
+    }
+}
+
+/// A failed health check, with a hint when the key is the likely culprit.
+fn provider_failure(provider: &dyn Provider, message: &str) -> Check {
+    let name = provider.display_name();
+    let lower = message.to_lowercase();
+    let rejected = ["401", "403", "unauthorized", "forbidden", "invalid api key"]
+        .iter()
+        .any(|m| lower.contains(m));
+
+    let check = Check::new(name, Verdict::Fail, message);
+    if rejected {
+        check.hint(format!(
+            "Your {name} key was rejected. Regenerate it at {}",
+            key_page(provider.name())
+        ))
+    } else {
+        check
+    }
+}
+
+/// Where each provider hands out API keys.
+fn key_page(provider: &str) -> &'static str {
+    match provider {
+        "shodan" => "https://account.shodan.io",
+        "censys" => "https://search.censys.io/account/api",
+        "criminalip" => "https://www.criminalip.io/mypage/information",
+        _ => "the provider's account page",
+    }
+}
+
+const fn verdict_name(verdict: Verdict) -> &'static str {
+    match verdict {
+        Verdict::Pass => "pass",
+        Verdict::Warn => "warn",
+        Verdict::Fail => "fail",
+    }
+}
+
+fn verdict_mark(verdict: Verdict, no_color: bool) -> String {
+    if no_color {
+        return format!("[{}]", verdict_name(verdict));
+    }
+    match verdict {
+        Verdict::Pass => "✓".green().to_string(),
+        Verdict::Warn => "!".yellow().bold().to_string(),
+        Verdict::Fail => "✗".red().bold().to_string(),
+    }
+}
@@ -5,12 +5,18 @@ use colored::Colorize;
 
 use super::Context;
 use crate::cli::args::CountArgs;
+use crate::history;
 use crate::output::OutputFormat;
 
 pub async fn execute(ctx: Context, args: CountArgs) -> Result<()> {
+    if ctx.provider == "all" {
+        return count_all(&ctx, &args).await;
+    }
+
     let provider = ctx.search_provider()?;
 
     let count = provider.count(&args.query).await?;
+    history::record(&ctx, provider.name(), &args.query, Some(count), 0);
 
     match ctx.output_format {
         OutputFormat::Json => {
@@ -42,3 +48,74 @@ pub async fn execute(ctx: Context, args: CountArgs) -> Result<()> {
 
     Ok(())
 }
+
+/// `--provider all`: a count from each provider. Providers index different
+/// hosts, so the counts are shown side by side rather than added up.
+async fn count_all(ctx: &Context, args: &CountArgs) -> Result<()> {
+    let client = ctx.client()?;
+    let mut names = client.providers();
+    names.sort_unstable();
+
+    let counts = names
+        .iter()
+        .map(|name| client.count_with(&args.query, name));
+    let counts = futures_util::future::join_all(counts).await;
+
+    let mut answered = Vec::new();
+    let mut first_error = None;
+    for (name, count) in names.into_iter().zip(counts) {
+        match count {
+            Ok(count) => answered.push((name, count)),
+            Err(e) => {
+                if ctx.output_format == OutputFormat::Pretty {
+                    eprintln!("{} {name} count failed: {e}", "Warning:".yellow());
+                }
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+    if let (true, Some(e)) = (answered.is_empty(), first_error) {
+        return Err(e.into());
+    }
+    for (name, count) in &answered {
+        history::record(ctx, name, &args.query, Some(*count), 0);
+    }
+
+    match ctx.output_format {
+        OutputFormat::Json | OutputFormat::Yaml => {
+            let counts: serde_json::Map<String, serde_json::Value> = answered
+                .iter()
+                .map(|(name, count)| ((*name).to_string(), (*count).into()))
+                .collect();
+            let value = serde_json::json!({ "counts": counts, "query": args.query });
+            if ctx.output_format == OutputFormat::Yaml {
+                println!("{}", serde_yaml::to_string(&value)?);
+            } else {
+                println!("{value}");
+            }
+        }
+        OutputFormat::Csv => {
+            println!("provider,total");
+            for (name, count) in &answered {
+                println!("{name},{count}");
+            }
+        }
+        OutputFormat::Pretty => {
+            for (name, count) in &answered {
+                if ctx.no_color {
+                    println!("{name:<12}{count}");
+                } else {
+                    println!("{:<12}{}", name.bold(), count.to_string().cyan().bold());
+                }
+            }
+            println!("{} {}", "Query:".bold(), args.query.dimmed());
+            println!();
+            println!(
+                "{}",
+                "Providers index different hosts; counts overlap.".dimmed()
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,262 @@
+//! `i1 credits` - Remaining API credits across providers.
+
+use std::time::Duration;
+
+use anyhow::Result;
+use colored::Colorize;
+use serde::Serialize;
+use tabled::Tabled;
+
+use super::{not_configured, Context};
+use crate::cache::Cache;
+use crate::cli::args::CreditsArgs;
+use crate::output::{terminal, OutputFormat};
+use i1_providers::{HealthStatus, Provider, ProviderHealth, ProviderId};
+
+/// Providers that answer from local tools and have no quota to run out of
+const UNMETERED: &[&str] = &["native"];
+
+/// One provider's health, as printed for dashboards.
+#[derive(Debug, Serialize)]
+struct CreditReport {
+    #[serde(flatten)]
+    health: ProviderHealth,
+    unmetered: bool,
+}
+
+#[derive(Tabled)]
+struct CreditRow {
+    #[tabled(rename = "")]
+    dot: String,
+    #[tabled(rename = "Provider")]
+    provider: String,
+    #[tabled(rename = "Plan")]
+    plan: String,
+    #[tabled(rename = "Query Credits")]
+    query: String,
+    #[tabled(rename = "Scan Credits")]
+    scan: String,
+    #[tabled(rename = "Resets")]
+    resets: String,
+    #[tabled(rename = "Latency")]
+    latency: String,
+}
+
+pub async fn execute(ctx: Context, args: CreditsArgs) -> Result<()> {
+    loop {
+        let reports = check(&ctx).await?;
+
+        match ctx.output_format {
+            OutputFormat::Json if args.watch.is_some() => {
+                // One line per refresh, easy to tail into a dashboard
+                println!("{}", serde_json::to_string(&reports)?);
+            }
+            OutputFormat::Json => {
+                println!("{}", serde_json::to_string_pretty(&reports)?);
+            }
+            OutputFormat::Yaml => {
+                println!("{}", serde_yaml::to_string(&reports)?);
+            }
+            OutputFormat::Csv => print_csv(&reports)?,
+            OutputFormat::Pretty => {
+                if args.watch.is_some() {
+                    // Clear the screen so the table redraws in place
+                    print!("\x1B[2J\x1B[H");
+                }
+                print_pretty(&ctx, &reports);
+            }
+        }
+
+        let Some(secs) = args.watch else {
+            return Ok(());
+        };
+        tokio::time::sleep(Duration::from_secs(secs)).await;
+    }
+}
+
+/// Check every selected provider at once.
+async fn check(ctx: &Context) -> Result<Vec<CreditReport>> {
+    let mut providers = ctx.providers()?;
+
+    if !matches!(ctx.provider.as_str(), "auto" | "all") {
+        let wanted: ProviderId = ctx.provider.parse()?;
+        providers.retain(|p| p.name() == wanted.as_str());
+        if providers.is_empty() {
+            return Err(not_configured(
+                Some(wanted.as_str()),
+                format!(
+                    "{} is not configured. Run `i1 config` to add credentials.",
+                    wanted.display_name()
+                ),
+            ));
+        }
+    }
+
+    if providers.is_empty() {
+        return Err(not_configured(
+            None,
+            "No API key configured.\n\n\
+             Set one with:\n  \
+             1. i1 config set shodan-key <KEY>\n  \
+             2. i1 config set censys-id <ID> + censys-secret <SECRET>\n  \
+             3. i1 config set criminalip-key <KEY>",
+        ));
+    }
+
+    let checks = providers.iter().map(|p| health(p.as_ref()));
+    let cache = Cache::open(ctx.cache);
+
+    Ok(futures_util::future::join_all(checks)
+        .await
+        .into_iter()
+        .map(|health| {
+            // Kept for --dry-run to quote
+            cache.store_health(&health);
+            CreditReport {
+                unmetered: UNMETERED.contains(&health.provider.as_str()),
+                health,
+            }
+        })
+        .collect())
+}
+
+/// Check one provider. A failed check is an unhealthy provider, not an error.
+pub(super) async fn health<P: Provider + ?Sized>(provider: &P) -> ProviderHealth {
+    provider
+        .health_check()
+        .await
+        .unwrap_or_else(|e| ProviderHealth {
+            provider: provider.name().to_string(),
+            status: HealthStatus::Unhealthy,
+            latency_ms: None,
+            credits_remaining: None,
+            message: Some(e.to_string()),
+            plan: None,
+            scan_credits: None,
+            resets_at: None,
+        })
+}
+
+fn print_csv(reports: &[CreditReport]) -> Result<()> {
+    let mut writer = csv::Writer::from_writer(std::io::stdout().lock());
+    writer.write_record([
+        "provider",
+        "status",
+        "plan",
+        "query_credits",
+        "scan_credits",
+        "resets_at",
+        "latency_ms",
+        "message",
+    ])?;
+
+    for report in reports {
+        let health = &report.health;
+        let number = |n: Option<i64>| {
+            if report.unmetered {
+                "unlimited".to_string()
+            } else {
+                n.map(|n| n.to_string()).unwrap_or_default()
+            }
+        };
+        writer.write_record([
+            health.provider.clone(),
+            status_name(health.status).to_string(),
+            health.plan.clone().unwrap_or_default(),
+            number(health.credits_remaining),
+            number(health.scan_credits),
+            health.resets_at.map(|t| t.to_rfc3339()).unwrap_or_default(),
+            health
+                .latency_ms
+                .map(|ms| ms.to_string())
+                .unwrap_or_default(),
+            health.message.clone().unwrap_or_default(),
+        ])?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+fn print_pretty(ctx: &Context, reports: &[CreditReport]) {
+    let rows: Vec<CreditRow> = reports
+        .iter()
+        .map(|report| {
+            let health = &report.health;
+            let credits = |n: Option<i64>| {
+                if report.unmetered {
+                    "unlimited".to_string()
+                } else {
+                    n.map_or_else(|| "-".to_string(), |n| n.to_string())
+                }
+            };
+
+            CreditRow {
+                dot: status_dot(health.status, ctx.no_color),
+                provider: health.provider.parse::<ProviderId>().map_or_else(
+                    |_| health.provider.clone(),
+                    |id| id.display_name().to_string(),
+                ),
+                plan: health
+                    .plan
+                    .clone()
+                    .unwrap_or_else(|| if report.unmetered { "local" } else { "-" }.to_string()),
+                query: credits(health.credits_remaining),
+                scan: credits(health.scan_credits),
+                resets: health.resets_at.map_or_else(
+                    || "-".to_string(),
+                    |t| {
+                        let days = (t - chrono::Utc::now()).num_days();
+                        format!("{} ({days}d)", t.format("%Y-%m-%d"))
+                    },
+                ),
+                latency: health
+                    .latency_ms
+                    .map_or_else(|| "-".to_string(), |ms| format!("{ms}ms")),
+            }
+        })
+        .collect();
+
+    let table = terminal::table(&rows);
+    println!("{table}");
+
+    for report in reports {
+        if let Some(message) = &report.health.message {
+            let line = format!("{}: {message}", report.health.provider);
+            if ctx.no_color {
+                println!("  {line}");
+            } else {
+                println!("  {}", line.yellow());
+            }
+        }
+    }
+
+    let missing = ctx.unconfigured_providers();
+    if matches!(ctx.provider.as_str(), "auto" | "all") && !missing.is_empty() {
+        println!(
+            "{}",
+            format!("Not configured: {}", missing.join(", ")).dimmed()
+        );
+    }
+}
+
+const fn status_name(status: HealthStatus) -> &'static str {
+    match status {
+        HealthStatus::Healthy => "healthy",
+        HealthStatus::Degraded => "degraded",
+        HealthStatus::Unhealthy => "unhealthy",
+        HealthStatus::Unconfigured => "unconfigured",
+    }
+}
+
+fn status_dot(status: HealthStatus, no_color: bool) -> String {
+    if no_color {
+        return status_name(status).to_string();
+    }
+    match status {
+        HealthStatus::Healthy => "●".green().to_string(),
+        HealthStatus::Degraded => "●".yellow().to_string(),
+        HealthStatus::Unhealthy => "●".red().to_string(),
+        HealthStatus::Unconfigured => "●".dimmed().to_string(),
+    }
+}
@@ -1,31 +1,171 @@
 //! `i1 defend` - Defensive tools: geo-blocking, IP bans, firewall rules.
 
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{BufRead, BufReader, IsTerminal};
+
 use anyhow::Result;
+use chrono::{DateTime, Utc};
 use colored::Colorize;
+use ipnet::IpNet;
+use serde::Serialize;
 
-use super::Context;
+use super::{confirm, myip, Context};
 use crate::cli::args::{
-    CommunityArgs, CommunityCommands, DefendArgs, DefendCommands, GeoblockArgs, GeoblockCommands,
-    PatrolArgs, PatrolCommands, PullArgs, PushArgs, WhitelistArgs, WhitelistCommands,
+    CommunityArgs, CommunityCommands, DefendArgs, DefendCommands, FeedsArgs, FeedsCommands,
+    GeoblockArgs, GeoblockCommands, PatrolArgs, PatrolCommands, PullArgs, PushArgs, WhitelistArgs,
+    WhitelistCommands,
 };
-use crate::defend;
+use crate::defend::export::{self, Blocklist, ExportFormat};
+use crate::defend::feeds::{self, Feed};
+use crate::defend::import::{self, ImportFormat};
+use crate::defend::journal::{self, Action, Entry};
+use crate::defend::plan::{self, Backend, Plan, Rules};
+use crate::defend::{self, asn, geo, nft};
 use crate::output::OutputFormat;
 
+/// What `whitelist check` found for one address.
+#[derive(Debug, Serialize)]
+struct ProtectionReport {
+    target: String,
+    /// Fully covered by the whitelist
+    protected: bool,
+    whitelisted_by: Vec<String>,
+    /// Covers the address of the current SSH session
+    ssh_session: bool,
+    banned_by: Vec<String>,
+    geoblocked: Vec<String>,
+}
+
+/// Imports larger than this ask before banning
+const IMPORT_CONFIRM: usize = 100;
+
+/// Lines between progress updates while importing
+const IMPORT_PROGRESS_EVERY: usize = 100_000;
+
+/// AS numbers covering more IPv4 addresses than this (a /16) ask before banning
+const ASN_CONFIRM: u64 = 1 << 16;
+
+/// Journal entries shown by `status`
+const RECENT_CHANGES: usize = 5;
+
+/// Entries shown under "Recently Added" by `status`
+const RECENTLY_ADDED: usize = 10;
+
+/// `status` output: the saved state plus what the firewall really has.
+#[derive(Debug, Serialize)]
+struct StatusReport {
+    #[serde(flatten)]
+    state: defend::State,
+    /// Blocked ranges per source: `manual`, `import:<file>`,
+    /// `suggest:<file>`, `asn:<AS>`, `feed:<name>` and `geo:<country>`
+    sources: BTreeMap<String, usize>,
+    /// `nftables`, or `export` where rules are only exported
+    backend: &'static str,
+    /// Subscribed blocklist feeds
+    feeds: BTreeMap<String, Feed>,
+    /// Elements per set in the live table, if it's loaded and readable
+    firewall: Option<BTreeMap<String, usize>>,
+    /// Sets where the live table differs from the saved state; `None` if
+    /// it couldn't be checked
+    drift: Option<Vec<nft::Drift>>,
+    /// Why the live table couldn't be read
+    #[serde(skip_serializing_if = "Option::is_none")]
+    firewall_error: Option<String>,
+    updated: Updated,
+    /// Newest bans still in effect
+    recently_added: Vec<Added>,
+    recent: Vec<Entry>,
+}
+
+/// When things last changed.
+#[derive(Debug, Serialize)]
+struct Updated {
+    /// Newest journal entry
+    last_change: Option<DateTime<Utc>>,
+    /// Newest change loaded into the firewall
+    last_applied: Option<DateTime<Utc>>,
+    /// Country ranges
+    geo: Option<DateTime<Utc>>,
+    /// Newest feed download
+    feeds: Option<DateTime<Utc>>,
+}
+
+/// A ban still in effect, for "Recently Added".
+#[derive(Debug, Serialize)]
+struct Added {
+    target: String,
+    action: Action,
+    at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source: Option<String>,
+}
+
+/// A set element to read back after applying.
+struct Expect {
+    set: &'static str,
+    net: IpNet,
+    present: bool,
+}
+
 pub async fn execute(ctx: Context, args: DefendArgs) -> Result<()> {
     match args.command {
-        DefendCommands::Status { quick } => status(ctx, quick).await,
+        DefendCommands::Status { quick, check } => status(ctx, quick, check).await,
         DefendCommands::Geoblock(gb) => geoblock(ctx, gb).await,
         DefendCommands::Ban {
             target,
             as_number,
+            reason,
+            yes,
             dry_run,
-        } => ban(ctx, &target, as_number, dry_run).await,
-        DefendCommands::Unban { target } => unban(ctx, &target).await,
+        } => ban(ctx, &target, as_number, reason, yes, dry_run).await,
+        DefendCommands::Unban { target, dry_run } => unban(ctx, &target, dry_run).await,
         DefendCommands::Whitelist(wl) => whitelist(ctx, wl).await,
-        DefendCommands::Export { format } => export(ctx, &format).await,
-        DefendCommands::Import { stdin, file } => import(ctx, stdin, file.as_deref()).await,
-        DefendCommands::Undo => undo(ctx).await,
-        DefendCommands::Disable => disable(ctx).await,
+        DefendCommands::Feeds(args) => feed(ctx, args).await,
+        DefendCommands::Export { format } => export(format).await,
+        DefendCommands::Import {
+            stdin,
+            file,
+            format,
+            reason,
+            yes,
+            dry_run,
+        } => {
+            let (input, source): (Box<dyn BufRead + Send>, String) = match (stdin, file) {
+                (true, _) => (
+                    Box::new(BufReader::new(std::io::stdin())),
+                    "stdin".to_string(),
+                ),
+                (false, Some(path)) => (
+                    Box::new(BufReader::new(std::fs::File::open(&path).map_err(|e| {
+                        anyhow::anyhow!("Could not read {}: {e}", path.display())
+                    })?)),
+                    path.file_name().map_or_else(
+                        || path.display().to_string(),
+                        |n| n.to_string_lossy().into(),
+                    ),
+                ),
+                (false, None) => anyhow::bail!(
+                    "Nothing to import. Give --file <path>, or pipe into --stdin:\n  \
+                     i1 search 'product:\"MikroTik\"' -o json | i1 defend import --stdin"
+                ),
+            };
+            let source = format!("import:{source}");
+            import(ctx, input, &source, format, reason, yes, dry_run).await
+        }
+        #[cfg(feature = "whois")]
+        DefendCommands::Suggest {
+            from_file,
+            min_ips,
+            reason,
+            yes,
+            dry_run,
+        } => {
+            let min_ips = usize::try_from(min_ips).unwrap_or(usize::MAX);
+            suggest(ctx, &from_file, min_ips, reason, yes, dry_run).await
+        }
+        DefendCommands::Undo { steps, dry_run } => undo(ctx, steps as usize, dry_run).await,
+        DefendCommands::History { limit } => history(&ctx, limit),
+        DefendCommands::Disable { dry_run } => disable(ctx, dry_run).await,
         DefendCommands::Push(args) => push(ctx, args).await,
         DefendCommands::Pull(args) => pull(ctx, args).await,
         DefendCommands::Community(args) => community(ctx, args).await,
@@ -33,18 +173,12 @@ pub async fn execute(ctx: Context, args: DefendArgs) -> Result<()> {
     }
 }
 
-async fn status(ctx: Context, quick: bool) -> Result<()> {
-    let state = defend::State::load()?;
-
-    if quick {
-        let countries: Vec<&str> = state
-            .blocked_countries
-            .iter()
-            .map(std::string::String::as_str)
-            .collect();
+async fn status(ctx: Context, quick: bool, check: bool) -> Result<()> {
+    if quick && !check {
+        let state = defend::State::load()?;
         println!(
             "Blocking {} countries, {} IPs, {} ASNs | Whitelist: {} IPs",
-            countries.len(),
+            state.blocked_countries.len(),
             state.blocked_ips.len(),
             state.blocked_asns.len(),
             state.whitelisted_ips.len()
@@ -52,73 +186,311 @@ async fn status(ctx: Context, quick: bool) -> Result<()> {
         return Ok(());
     }
 
+    let report = status_report().await?;
     match ctx.output_format {
         OutputFormat::Json => {
-            println!("{}", serde_json::to_string_pretty(&state)?);
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+        OutputFormat::Yaml => println!("{}", serde_yaml::to_string(&report)?),
+        _ if quick => {
+            let state = &report.state;
+            let firewall = match &report.drift {
+                Some(drift) if drift.is_empty() => "in sync",
+                Some(_) => "DRIFTED",
+                None => "unchecked",
+            };
+            println!(
+                "Blocking {} countries, {} IPs, {} ASNs | Whitelist: {} IPs | Firewall: {firewall}",
+                state.blocked_countries.len(),
+                state.blocked_ips.len(),
+                state.blocked_asns.len(),
+                state.whitelisted_ips.len()
+            );
         }
-        OutputFormat::Yaml => {
-            println!("{}", serde_yaml::to_string(&state)?);
+        _ => print_status(&report),
+    }
+
+    // Monitoring runs `status --check` and alerts on the exit code
+    if check {
+        match &report.drift {
+            Some(drift) if drift.is_empty() => {}
+            Some(drift) => anyhow::bail!(
+                "Firewall has drifted from the saved state in {} set(s)",
+                drift.len()
+            ),
+            None => anyhow::bail!(
+                "Could not check the firewall: {}",
+                report
+                    .firewall_error
+                    .as_deref()
+                    .unwrap_or("rules are only exported on this system")
+            ),
         }
-        _ => {
-            println!("{}", "Defense Status".bold().underline());
-            println!();
+    }
 
-            // Countries
-            if state.blocked_countries.is_empty() {
-                println!("{} None", "Blocked Countries:".bold());
-            } else {
-                println!("{}", "Blocked Countries:".bold());
-                for code in &state.blocked_countries {
-                    let name = defend::country_name(code);
-                    println!("  {} - {}", code.to_uppercase().red(), name);
-                }
+    Ok(())
+}
+
+async fn status_report() -> Result<StatusReport> {
+    let state = defend::State::load()?;
+    let entries = journal::load()?;
+    let feeds = feeds::Index::load()?.feeds;
+    let geo_index = geo::Index::load()?;
+
+    // Who added each ban, newest entry winning
+    let mut origin: BTreeMap<&str, &str> = BTreeMap::new();
+    for entry in entries.iter().filter(|e| e.action == Action::Ban) {
+        for target in &entry.targets {
+            origin.insert(target, entry.source.as_deref().unwrap_or("manual"));
+        }
+    }
+    let mut sources: BTreeMap<String, usize> = BTreeMap::new();
+    for target in &state.blocked_ips {
+        let source = origin.get(target.as_str()).copied().unwrap_or("manual");
+        *sources.entry(source.to_string()).or_default() += 1;
+    }
+    for (asn, prefixes) in &state.asn_prefixes {
+        sources.insert(format!("asn:{asn}"), prefixes.len());
+    }
+    for (name, feed) in &feeds {
+        sources.insert(format!("feed:{name}"), feed.entries);
+    }
+    for code in &state.blocked_countries {
+        let ranges = geo_index.countries.get(code).map_or(0, |c| c.v4 + c.v6);
+        sources.insert(format!("geo:{code}"), ranges);
+    }
+
+    let mut seen = BTreeSet::new();
+    let recently_added = entries
+        .iter()
+        .rev()
+        .filter(|e| matches!(e.action, Action::Ban | Action::BanAsn | Action::Geoblock))
+        .flat_map(|e| e.targets.iter().map(move |target| (e, target)))
+        .filter(|(e, target)| {
+            let list = match e.action {
+                Action::Ban => &state.blocked_ips,
+                Action::BanAsn => &state.blocked_asns,
+                _ => &state.blocked_countries,
+            };
+            list.contains(target) && seen.insert(target.as_str())
+        })
+        .take(RECENTLY_ADDED)
+        .map(|(e, target)| Added {
+            target: target.clone(),
+            action: e.action,
+            at: e.at,
+            source: e.source.clone(),
+        })
+        .collect();
+
+    let linux = cfg!(target_os = "linux") && nft::available();
+    let (live, firewall_error) = if linux {
+        match nft::live_sets() {
+            Ok(live) => (Some(live), None),
+            Err(e) => (None, Some(e.to_string())),
+        }
+    } else {
+        (None, None)
+    };
+    let drift = match &live {
+        Some(live) => {
+            let ranges = geo::country_ranges(&state.blocked_countries).await?;
+            let expected = nft::sets(&state, &ranges, &feeds::load_all()?);
+            // Without a table only a change that was loaded and not disabled since is drift
+            let loaded = entries
+                .iter()
+                .rev()
+                .find(|e| e.applied)
+                .is_some_and(|e| e.action != Action::Disable);
+            match live {
+                Some(live) => Some(nft::drift(&expected, live)),
+                None if loaded => Some(nft::drift(&expected, &BTreeMap::new())),
+                None => Some(Vec::new()),
             }
-            println!();
+        }
+        None => None,
+    };
+
+    Ok(StatusReport {
+        sources,
+        backend: if linux { "nftables" } else { "export" },
+        firewall: live.flatten().map(|sets| {
+            sets.into_iter()
+                .map(|(name, elements)| (name, elements.len()))
+                .collect()
+        }),
+        drift,
+        firewall_error,
+        updated: Updated {
+            last_change: entries.last().map(|e| e.at),
+            last_applied: entries.iter().rev().find(|e| e.applied).map(|e| e.at),
+            geo: geo_index.updated(),
+            feeds: feeds.values().filter_map(|f| f.updated).max(),
+        },
+        recently_added,
+        recent: entries.iter().rev().take(RECENT_CHANGES).cloned().collect(),
+        feeds,
+        state,
+    })
+}
+
+fn print_status(report: &StatusReport) {
+    let state = &report.state;
+
+    println!("{}", "Defense Status".bold().underline());
+    println!();
+
+    // Countries
+    if state.blocked_countries.is_empty() {
+        println!("{} None", "Blocked Countries:".bold());
+    } else {
+        println!("{}", "Blocked Countries:".bold());
+        for code in &state.blocked_countries {
+            let name = defend::country_name(code);
+            println!("  {} - {}", code.to_uppercase().red(), name);
+        }
+    }
+    println!();
 
-            // IPs
+    // Where the blocked ranges come from
+    println!(
+        "{} {} IPs/ranges, {} ASNs",
+        "Blocked:".bold(),
+        state.blocked_ips.len(),
+        state.blocked_asns.len()
+    );
+    for (source, count) in &report.sources {
+        println!("  {:<24} {:>8}", source, thousands(count).red());
+    }
+    println!();
+
+    if !report.recently_added.is_empty() {
+        println!("{}", "Recently Added:".bold());
+        for added in &report.recently_added {
+            let source = added
+                .source
+                .as_deref()
+                .map(|s| format!(" ({s})"))
+                .unwrap_or_default();
             println!(
-                "{} {}",
-                "Blocked IPs/Ranges:".bold(),
-                state.blocked_ips.len()
+                "  {} {:<10} {}{}",
+                added.at.format("%Y-%m-%d %H:%M").to_string().dimmed(),
+                added.action.to_string(),
+                added.target.red(),
+                source.dimmed()
             );
-            for ip in state.blocked_ips.iter().take(10) {
-                println!("  {}", ip.red());
-            }
-            if state.blocked_ips.len() > 10 {
-                println!("  ... and {} more", state.blocked_ips.len() - 10);
-            }
-            println!();
+        }
+        println!();
+    }
 
-            // ASNs
-            println!("{} {}", "Blocked ASNs:".bold(), state.blocked_asns.len());
-            for asn in state.blocked_asns.iter().take(5) {
-                println!("  {}", asn.red());
-            }
-            println!();
+    // Whitelist
+    println!(
+        "{} {}",
+        "Whitelisted IPs:".bold(),
+        state.whitelisted_ips.len()
+    );
+    for ip in &state.whitelisted_ips {
+        println!("  {}", ip.green());
+    }
+    println!();
+
+    // Feeds
+    if !report.feeds.is_empty() {
+        println!("{}", "Feeds:".bold());
+        for (name, feed) in &report.feeds {
+            print_feed(name, feed);
+        }
+        println!();
+    }
+
+    // What's actually loaded, and whether it matches
+    println!("{} {}", "Backend:".bold(), report.backend);
+    if report.backend == "nftables" {
+        print_firewall(report.firewall.as_ref());
+        print_drift(report);
+    }
+    println!();
 
-            // Whitelist
+    let time = |at: Option<DateTime<Utc>>| {
+        at.map_or_else(
+            || "never".to_string(),
+            |at| at.format("%Y-%m-%d %H:%M").to_string(),
+        )
+    };
+    println!("{}", "Updated:".bold());
+    println!(
+        "  {:<16} {}",
+        "last change",
+        time(report.updated.last_change)
+    );
+    println!(
+        "  {:<16} {}",
+        "last applied",
+        time(report.updated.last_applied)
+    );
+    if !state.blocked_countries.is_empty() {
+        println!("  {:<16} {}", "country ranges", time(report.updated.geo));
+    }
+    if !report.feeds.is_empty() {
+        println!("  {:<16} {}", "feeds", time(report.updated.feeds));
+    }
+    println!();
+
+    if !report.recent.is_empty() {
+        println!("{}", "Recent Changes:".bold());
+        for entry in &report.recent {
+            print_entry(entry);
+        }
+        println!();
+    }
+
+    // Tip
+    println!(
+        "{}",
+        "Use 'defend export' to generate firewall rules.".dimmed()
+    );
+}
+
+fn print_drift(report: &StatusReport) {
+    match &report.drift {
+        Some(drift) if drift.is_empty() => {
             println!(
-                "{} {}",
-                "Whitelisted IPs:".bold(),
-                state.whitelisted_ips.len()
+                "{} live rules match the saved state",
+                "In sync:".green().bold()
             );
-            for ip in &state.whitelisted_ips {
-                println!("  {}", ip.green());
+        }
+        Some(drift) => {
+            println!(
+                "{} live rules differ from the saved state",
+                "Drift:".red().bold()
+            );
+            for set in drift {
+                println!(
+                    "  {:<24} {} missing, {} extra",
+                    set.set,
+                    set.missing.to_string().red(),
+                    set.extra.to_string().yellow()
+                );
             }
-            println!();
-
-            // Tip
             println!(
                 "{}",
-                "Use 'defend export' to generate firewall rules.".dimmed()
+                "Something changed the table outside i1; the next defend change reloads it."
+                    .dimmed()
             );
         }
+        None => {
+            if let Some(error) = &report.firewall_error {
+                println!(
+                    "{} {}",
+                    "Drift:".bold(),
+                    format!("not checked ({error})").dimmed()
+                );
+            }
+        }
     }
-
-    Ok(())
 }
 
-async fn geoblock(_ctx: Context, args: GeoblockArgs) -> Result<()> {
+async fn geoblock(ctx: Context, args: GeoblockArgs) -> Result<()> {
     match args.command {
         GeoblockCommands::List => {
             let state = defend::State::load()?;
@@ -130,82 +502,121 @@ async fn geoblock(_ctx: Context, args: GeoblockArgs) -> Result<()> {
                     "i1".cyan()
                 );
             } else {
+                let index = geo::Index::load()?;
                 println!("{}", "Blocked Countries:".bold());
                 for code in &state.blocked_countries {
                     let name = defend::country_name(code);
-                    println!("  {} - {}", code.to_uppercase().red(), name);
+                    let prefixes = index.countries.get(code).map_or_else(
+                        || "not downloaded".dimmed().to_string(),
+                        |c| format!("{} IPv4, {} IPv6 prefixes", c.v4, c.v6),
+                    );
+                    println!("  {} - {name} ({prefixes})", code.to_uppercase().red());
+                }
+                println!();
+                match index.updated() {
+                    Some(at) => println!(
+                        "Ranges updated {}",
+                        at.format("%Y-%m-%d %H:%M UTC").to_string().cyan()
+                    ),
+                    None => println!(
+                        "Ranges not downloaded yet. Run: {} defend geoblock update",
+                        "i1".cyan()
+                    ),
                 }
             }
             Ok(())
         }
         GeoblockCommands::Add { countries, dry_run } => {
-            let mut state = defend::State::load()?;
-            let mut added = Vec::new();
-
-            for code in &countries {
-                let normalized = code.to_lowercase();
-                if !state.blocked_countries.contains(&normalized) {
-                    state.blocked_countries.push(normalized.clone());
-                    added.push(normalized);
+            let state = defend::State::load()?;
+            let mut codes: Vec<String> = Vec::new();
+            for country in &countries {
+                let code = defend::country_code(country)?;
+                if !codes.contains(&code) {
+                    codes.push(code);
                 }
             }
-
-            if added.is_empty() {
+            let plan = plan_change(&state, Action::Geoblock, &codes).await?;
+            if dry_run {
+                return show_plan(&ctx, plan);
+            }
+            if plan.is_empty() {
                 println!("All specified countries are already blocked.");
                 return Ok(());
             }
 
-            if dry_run {
-                println!("{}", "[DRY RUN]".yellow().bold());
-                println!("Would block: {}", added.join(", ").red());
-                println!();
-                println!("Run without --dry-run to apply.");
-            } else {
-                state.save()?;
-                println!(
-                    "{} Now blocking: {}",
-                    "Success:".green().bold(),
-                    added.join(", ").red()
-                );
-                println!();
-                println!("Generate rules with: {} defend export", "i1".cyan());
+            let added = plan.changed.clone();
+            let entries = vec![Entry::batch(Action::Geoblock, added.clone())];
+            let applied = carry_out(&ctx, plan.rules, entries, None).await?;
+            println!(
+                "{} Now blocking: {}",
+                "Success:".green().bold(),
+                added.join(", ").red()
+            );
+            for code in &added {
+                let ranges = geo::ranges(code)?.unwrap_or_default();
+                print_whitelisted_inside(&state.whitelisted_ips, &ranges, &code.to_uppercase());
             }
+            print_export_hint(applied);
 
             Ok(())
         }
-        GeoblockCommands::Remove { country } => {
-            let mut state = defend::State::load()?;
-            let normalized = country.to_lowercase();
-
-            if let Some(pos) = state
-                .blocked_countries
-                .iter()
-                .position(|c| c == &normalized)
-            {
-                state.blocked_countries.remove(pos);
-                state.save()?;
-                println!(
-                    "{} Removed {} from blocked countries.",
-                    "Success:".green().bold(),
-                    country.to_uppercase().cyan()
-                );
-            } else {
+        GeoblockCommands::Remove { country, dry_run } => {
+            let state = defend::State::load()?;
+            // Whatever was blocked before names were checked can still go
+            let normalized = match country.to_lowercase() {
+                code if state.blocked_countries.contains(&code) => code,
+                _ => defend::country_code(&country)?,
+            };
+            let plan = plan_change(
+                &state,
+                Action::Ungeoblock,
+                std::slice::from_ref(&normalized),
+            )
+            .await?;
+            if dry_run {
+                return show_plan(&ctx, plan);
+            }
+            if plan.is_empty() {
                 println!(
                     "Country {} is not currently blocked.",
-                    country.to_uppercase()
+                    normalized.to_uppercase()
                 );
+                return Ok(());
             }
 
+            let entry = Entry::new(Action::Ungeoblock, &normalized);
+            carry_out(&ctx, plan.rules, vec![entry], None).await?;
+            println!(
+                "{} Removed {} from blocked countries.",
+                "Success:".green().bold(),
+                normalized.to_uppercase().cyan()
+            );
+
             Ok(())
         }
-        GeoblockCommands::Update => {
-            println!("Updating IP ranges from ipdeny.com...");
-            println!();
+        GeoblockCommands::Update { force } => {
+            println!("Updating country ranges from the RIR delegation files...");
+            let report = geo::update(geo::mirror()?.as_deref(), force).await?;
+            for rir in &report.downloaded {
+                println!("  {} downloaded", rir.cyan());
+            }
+            for rir in &report.unchanged {
+                println!("  {} {}", rir.cyan(), "unchanged".dimmed());
+            }
+            let Some(countries) = report.rebuilt else {
+                println!("{} Ranges already up to date.", "Success:".green().bold());
+                return Ok(());
+            };
             println!(
-                "{}",
-                "This feature will download fresh IP ranges for blocked countries.".dimmed()
+                "{} Rebuilt ranges for {countries} countries.",
+                "Success:".green().bold()
             );
-            println!("{}", "Coming soon!".yellow());
+
+            let state = defend::State::load()?;
+            if !state.blocked_countries.is_empty() {
+                let applied = commit(&ctx, &state, Vec::new(), false, None).await?;
+                print_export_hint(applied);
+            }
             Ok(())
         }
         GeoblockCommands::Codes => {
@@ -251,199 +662,1540 @@ async fn geoblock(_ctx: Context, args: GeoblockArgs) -> Result<()> {
     }
 }
 
-async fn ban(_ctx: Context, target: &str, as_number: bool, dry_run: bool) -> Result<()> {
-    // Safety check: refuse to block your own SSH session
-    if let Some(ssh_ip) = get_ssh_client_ip() {
-        if target == ssh_ip || target.starts_with(&format!("{}/", ssh_ip)) {
-            println!(
-                "{} Refusing to block {} - that's your current SSH session!",
-                "🛡️ PROTECTED:".yellow().bold(),
-                target.cyan()
-            );
-            println!();
-            println!(
-                "{}",
-                "This prevents you from locking yourself out.".dimmed()
+async fn ban(
+    ctx: Context,
+    target: &str,
+    as_number: bool,
+    reason: Option<String>,
+    yes: bool,
+    dry_run: bool,
+) -> Result<()> {
+    // Safety check: refuse to block your own SSH session
+    if let Some(ssh_ip) = get_ssh_client_ip() {
+        let covers_ssh = match (nft::parse_net(target), ssh_ip.parse::<std::net::IpAddr>()) {
+            (Ok(net), Ok(ip)) => net.contains(&ip),
+            _ => target == ssh_ip,
+        };
+        if covers_ssh {
+            println!(
+                "{} Refusing to block {} - that's your current SSH session!",
+                "🛡️ PROTECTED:".yellow().bold(),
+                target.cyan()
+            );
+            println!();
+            println!(
+                "{}",
+                "This prevents you from locking yourself out.".dimmed()
+            );
+            return Ok(());
+        }
+    }
+
+    let state = defend::State::load()?;
+
+    if as_number {
+        return ban_asn(&ctx, state, target, reason, yes, dry_run).await;
+    }
+
+    let net = nft::parse_net(target)?;
+    let target = nft::element(&net);
+    let plan = plan_change(&state, Action::Ban, std::slice::from_ref(&target)).await?;
+    if dry_run {
+        return show_plan(&ctx, plan);
+    }
+    if let Some(skipped) = plan.skipped.first() {
+        println!(
+            "{} {target} is {}; not banned.",
+            "Skipped:".yellow().bold(),
+            skipped.reason
+        );
+        println!(
+            "Explain with: {} defend whitelist check {target}",
+            "i1".cyan()
+        );
+        return Ok(());
+    }
+    if plan.is_empty() {
+        println!("{target} is already blocked.");
+        return Ok(());
+    }
+    print_whitelisted_inside(&state.whitelisted_ips, &[net], &target);
+
+    let entry = Entry::new(Action::Ban, &target).reason(reason);
+    let expect = Expect {
+        set: nft::blocked_set(&net),
+        net,
+        present: true,
+    };
+    let applied = carry_out(&ctx, plan.rules, vec![entry], Some(expect)).await?;
+    println!("{} Blocked {}", "Success:".green().bold(), target.red());
+    print_export_hint(applied);
+
+    Ok(())
+}
+
+/// Ban every prefix an AS announces, as one group under the ASN.
+async fn ban_asn(
+    ctx: &Context,
+    mut state: defend::State,
+    target: &str,
+    reason: Option<String>,
+    yes: bool,
+    dry_run: bool,
+) -> Result<()> {
+    let asn = asn::normalize(target)?;
+    if state
+        .blocked_asns
+        .iter()
+        .any(|a| a.eq_ignore_ascii_case(&asn))
+    {
+        println!("{asn} is already blocked.");
+        return Ok(());
+    }
+
+    let prefixes = asn::resolve(&asn, asn::source()?, false).await?;
+    println!(
+        "{} announces {} IPv4 prefixes ({} addresses) and {} IPv6 prefixes ({} /48s)",
+        asn.cyan(),
+        thousands(prefixes.v4()),
+        thousands(prefixes.v4_addresses()),
+        thousands(prefixes.v6()),
+        thousands(prefixes.v6_48s())
+    );
+    println!(
+        "{}",
+        format!(
+            "(from {}, looked up {})",
+            prefixes.source,
+            prefixes.fetched_at.format("%Y-%m-%d %H:%M")
+        )
+        .dimmed()
+    );
+
+    if let Some(ssh_ip) = get_ssh_client_ip().and_then(|ip| ip.parse::<std::net::IpAddr>().ok()) {
+        if let Some(net) = prefixes.nets.iter().find(|n| n.contains(&ssh_ip)) {
+            println!(
+                "{} Refusing to block {} - {} covers your current SSH session!",
+                "🛡️ PROTECTED:".yellow().bold(),
+                asn.cyan(),
+                net
+            );
+            return Ok(());
+        }
+    }
+    print_whitelisted_inside(&state.whitelisted_ips, &prefixes.nets, &asn);
+
+    if prefixes.v4_addresses() > ASN_CONFIRM && !yes && !dry_run {
+        if !std::io::stdin().is_terminal() {
+            anyhow::bail!(
+                "Banning {} addresses needs confirmation. Re-run with --yes.",
+                thousands(prefixes.v4_addresses())
+            );
+        }
+        if !confirm(&format!(
+            "Ban {} IPv4 addresses of {asn}?",
+            thousands(prefixes.v4_addresses())
+        ))? {
+            println!("{}", "Cancelled.".dimmed());
+            return Ok(());
+        }
+    }
+
+    let nets: Vec<String> = prefixes.nets.iter().map(nft::element).collect();
+    state.ban_asn(&asn, nets.clone());
+    if dry_run {
+        println!(
+            "{} Would block {} ({} prefixes)",
+            "[DRY RUN]".yellow().bold(),
+            asn,
+            thousands(nets.len())
+        );
+    }
+    let entry = Entry::new(Action::BanAsn, &asn)
+        .prefixes(nets)
+        .reason(reason);
+    let applied = commit(ctx, &state, vec![entry], dry_run, None).await?;
+    if !dry_run {
+        println!("{} Blocked {}", "Success:".green().bold(), asn.red());
+        print_export_hint(applied);
+    }
+
+    Ok(())
+}
+
+async fn unban(ctx: Context, target: &str, dry_run: bool) -> Result<()> {
+    let mut state = defend::State::load()?;
+
+    // Check if it's an ASN; its whole group of prefixes goes with it
+    if target.to_uppercase().starts_with("AS") {
+        let asn = asn::normalize(target)?;
+        let prefixes = state.asn_prefixes.get(&asn).cloned().unwrap_or_default();
+        if !state.apply(Action::UnbanAsn, &asn) {
+            println!("{} {} is not currently blocked.", "Note:".yellow(), asn);
+            return Ok(());
+        }
+        if dry_run {
+            println!(
+                "{} Would unblock {} ({} prefixes)",
+                "[DRY RUN]".yellow().bold(),
+                asn,
+                thousands(prefixes.len())
+            );
+        }
+        let entry = Entry::new(Action::UnbanAsn, &asn).prefixes(prefixes.clone());
+        // ASNs banned before prefixes were resolved never reached the firewall
+        if prefixes.is_empty() {
+            if !dry_run {
+                record(&state, vec![entry])?;
+            }
+        } else {
+            commit(&ctx, &state, vec![entry], dry_run, None).await?;
+        }
+        if !dry_run {
+            println!("{} Unblocked {}", "Success:".green().bold(), asn.cyan());
+        }
+        return Ok(());
+    }
+
+    // Check IPs, matching however the entry was written
+    let Ok(net) = nft::parse_net(target) else {
+        println!("{} {} is not currently blocked.", "Note:".yellow(), target);
+        return Ok(());
+    };
+    let Some(existing) = find_net(&state.blocked_ips, &net) else {
+        println!("{} {} is not currently blocked.", "Note:".yellow(), target);
+        return Ok(());
+    };
+    state.apply(Action::Unban, &existing);
+
+    if dry_run {
+        println!("{} Would unblock {}", "[DRY RUN]".yellow().bold(), existing);
+    }
+    // A wider ban may still cover it, in which case it stays in the set
+    let expect = covering(&state.blocked_ips, &net)
+        .is_none()
+        .then(|| Expect {
+            set: nft::blocked_set(&net),
+            net,
+            present: false,
+        });
+    let entry = Entry::new(Action::Unban, &existing);
+    commit(&ctx, &state, vec![entry], dry_run, expect).await?;
+
+    if !dry_run {
+        println!(
+            "{} Unblocked {}",
+            "Success:".green().bold(),
+            existing.cyan()
+        );
+        if let Some(wider) = covering(&state.blocked_ips, &net) {
+            println!(
+                "{}",
+                format!("Still blocked by {wider}. Unban that to let it through.").yellow()
+            );
+        }
+    }
+    Ok(())
+}
+
+async fn whitelist(ctx: Context, args: WhitelistArgs) -> Result<()> {
+    match args.command {
+        WhitelistCommands::Show => {
+            let state = defend::State::load()?;
+            if state.whitelisted_ips.is_empty() {
+                println!("No IPs whitelisted.");
+            } else {
+                println!("{}", "Whitelisted IPs:".bold());
+                for ip in &state.whitelisted_ips {
+                    println!("  {}", ip.green());
+                }
+            }
+            Ok(())
+        }
+        WhitelistCommands::Add { ip, yes, dry_run } => whitelist_add(ctx, &ip, yes, dry_run).await,
+        WhitelistCommands::Remove { ip, dry_run } => {
+            let mut state = defend::State::load()?;
+            let existing = nft::parse_net(&ip)
+                .ok()
+                .and_then(|net| find_net(&state.whitelisted_ips, &net).map(|e| (net, e)));
+            let Some((net, existing)) = existing else {
+                println!("{ip} is not in the whitelist.");
+                return Ok(());
+            };
+            state.apply(Action::Disallow, &existing);
+
+            if dry_run {
+                println!(
+                    "{} Would remove {} from whitelist",
+                    "[DRY RUN]".yellow().bold(),
+                    existing
+                );
+            }
+            let expect = covering(&state.whitelisted_ips, &net)
+                .is_none()
+                .then(|| Expect {
+                    set: nft::allowed_set(&net),
+                    net,
+                    present: false,
+                });
+            let entry = Entry::new(Action::Disallow, &existing);
+            commit(&ctx, &state, vec![entry], dry_run, expect).await?;
+            if !dry_run {
+                println!(
+                    "{} Removed {} from whitelist.",
+                    "Success:".green().bold(),
+                    existing
+                );
+            }
+            Ok(())
+        }
+        WhitelistCommands::Check { ip } => check_protection(&ctx, &ip),
+    }
+}
+
+async fn whitelist_add(ctx: Context, ip: &str, yes: bool, dry_run: bool) -> Result<()> {
+    let mut state = defend::State::load()?;
+    let net = nft::parse_net(ip)?;
+    let ip = nft::element(&net);
+    if !state.apply(Action::Allow, &ip) {
+        println!("{ip} is already whitelisted.");
+        return Ok(());
+    }
+
+    if dry_run {
+        println!("{} Would whitelist {}", "[DRY RUN]".yellow().bold(), ip);
+    }
+    let mut entry = Entry::new(Action::Allow, &ip);
+
+    // Bans inside the new entry can never match again
+    let covered: Vec<String> = state
+        .blocked_ips
+        .iter()
+        .filter(|b| nft::parse_net(b).is_ok_and(|b| net.contains(&b)))
+        .cloned()
+        .collect();
+    if !covered.is_empty() {
+        println!(
+            "{} {ip} covers {} existing ban(s): {}",
+            "Conflict:".yellow().bold(),
+            covered.len(),
+            covered.join(", ").red()
+        );
+        let remove = if dry_run {
+            println!("{} Would offer to remove them", "[DRY RUN]".yellow().bold());
+            false
+        } else {
+            yes || (std::io::stdin().is_terminal()
+                && confirm(&format!("Remove {} ban(s)?", covered.len()))?)
+        };
+        if remove {
+            for target in &covered {
+                state.apply(Action::Unban, target);
+            }
+            entry = entry.unbanned(covered.clone());
+        }
+    }
+    if let Some(wider) = covering(&state.blocked_ips, &net) {
+        println!(
+            "{} ban {} still covers {ip}; the whitelist is checked first.",
+            "Note:".yellow(),
+            wider.red()
+        );
+    }
+    for code in &state.blocked_countries {
+        let ranges = geo::ranges(code)?.unwrap_or_default();
+        if ranges.iter().any(|r| overlaps(r, &net)) {
+            println!(
+                "{} {ip} is inside geoblocked {}; the whitelist is checked first.",
+                "Note:".yellow(),
+                code.to_uppercase().red()
+            );
+        }
+    }
+
+    let expect = Expect {
+        set: nft::allowed_set(&net),
+        net,
+        present: true,
+    };
+    let removed = !entry.unbanned.is_empty();
+    commit(&ctx, &state, vec![entry], dry_run, Some(expect)).await?;
+    if !dry_run {
+        println!(
+            "{} Added {} to whitelist.",
+            "Success:".green().bold(),
+            ip.green()
+        );
+        if removed {
+            println!(
+                "{} Removed {} ban(s) it covered.",
+                "Success:".green().bold(),
+                covered.len()
+            );
+        }
+    }
+    Ok(())
+}
+
+fn check_protection(ctx: &Context, target: &str) -> Result<()> {
+    let state = defend::State::load()?;
+    let net = nft::parse_net(target)?;
+    let target = nft::element(&net);
+
+    let overlapping = |list: &[String]| -> Vec<String> {
+        list.iter()
+            .filter(|entry| nft::parse_net(entry).is_ok_and(|n| overlaps(&n, &net)))
+            .cloned()
+            .collect()
+    };
+    let mut geoblocked = Vec::new();
+    for code in &state.blocked_countries {
+        let ranges = geo::ranges(code)?.unwrap_or_default();
+        if ranges.iter().any(|r| overlaps(r, &net)) {
+            geoblocked.push(code.to_uppercase());
+        }
+    }
+    let mut banned_by = overlapping(&state.blocked_ips);
+    for (asn, prefixes) in &state.asn_prefixes {
+        banned_by.extend(overlapping(prefixes).iter().map(|p| format!("{p} ({asn})")));
+    }
+    let report = ProtectionReport {
+        whitelisted_by: overlapping(&state.whitelisted_ips),
+        ssh_session: get_ssh_client_ip()
+            .and_then(|ip| ip.parse::<std::net::IpAddr>().ok())
+            .is_some_and(|ip| net.contains(&ip)),
+        banned_by,
+        geoblocked,
+        protected: covering(&state.whitelisted_ips, &net).is_some(),
+        target,
+    };
+
+    match ctx.output_format {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+        OutputFormat::Yaml => println!("{}", serde_yaml::to_string(&report)?),
+        _ => print_protection(&report),
+    }
+
+    Ok(())
+}
+
+fn print_protection(report: &ProtectionReport) {
+    let target = &report.target;
+    let blocked = !report.banned_by.is_empty() || !report.geoblocked.is_empty();
+
+    if report.protected {
+        println!(
+            "{} {target} is whitelisted by {}",
+            "Protected:".green().bold(),
+            report.whitelisted_by.join(", ").green()
+        );
+    } else if !report.whitelisted_by.is_empty() {
+        println!(
+            "{} only part of {target} is whitelisted ({})",
+            "Partly protected:".yellow().bold(),
+            report.whitelisted_by.join(", ")
+        );
+    } else {
+        println!(
+            "{} {target} is not whitelisted",
+            "Not protected:".yellow().bold()
+        );
+    }
+
+    if report.ssh_session {
+        println!("  It covers your current SSH session, so bans covering it are refused.");
+    }
+    if !report.banned_by.is_empty() {
+        println!("  Matching bans: {}", report.banned_by.join(", ").red());
+    }
+    if !report.geoblocked.is_empty() {
+        println!(
+            "  Inside geoblocked: {}",
+            report.geoblocked.join(", ").red()
+        );
+    }
+    match (blocked, report.protected) {
+        (true, true) => println!("  The whitelist is checked first, so it stays reachable."),
+        (true, false) => println!(
+            "  Keep it reachable with: {} defend whitelist add {target}",
+            "i1".cyan()
+        ),
+        (false, _) => println!("  Nothing in the block list matches it."),
+    }
+}
+
+async fn feed(ctx: Context, args: FeedsArgs) -> Result<()> {
+    let mut index = feeds::Index::load()?;
+
+    match args.command {
+        FeedsCommands::List => {
+            match ctx.output_format {
+                OutputFormat::Json => {
+                    println!("{}", serde_json::to_string_pretty(&index.feeds)?);
+                }
+                OutputFormat::Yaml => println!("{}", serde_yaml::to_string(&index.feeds)?),
+                _ if index.feeds.is_empty() => {
+                    println!(
+                        "No feeds. Subscribe with: {} defend feeds add spamhaus-drop",
+                        "i1".cyan()
+                    );
+                    println!();
+                    println!("{}", "Well-known feeds:".bold());
+                    for (name, url) in feeds::KNOWN {
+                        println!("  {:<16} {}", name.cyan(), url.dimmed());
+                    }
+                }
+                _ => {
+                    println!("{}", "Feeds:".bold());
+                    for (name, feed) in &index.feeds {
+                        print_feed(name, feed);
+                    }
+                }
+            }
+            Ok(())
+        }
+        FeedsCommands::Add { name, url, dry_run } => {
+            feeds::validate_name(&name)?;
+            if index.feeds.contains_key(&name) {
+                anyhow::bail!(
+                    "Already subscribed to {name}. Refresh it with: i1 defend feeds update {name}"
+                );
+            }
+            let Some(url) = url.or_else(|| feeds::known_url(&name).map(String::from)) else {
+                let known: Vec<&str> = feeds::KNOWN.iter().map(|(n, _)| *n).collect();
+                anyhow::bail!(
+                    "'{name}' is not a well-known feed ({}). Give its URL too.",
+                    known.join(", ")
+                );
+            };
+
+            let mut feed = Feed::new(url.clone());
+            let update = feeds::update(&name, &mut feed, false, dry_run).await?;
+            if dry_run {
+                println!(
+                    "{} {name} has {} ranges{}; run without --dry-run to subscribe.",
+                    "[DRY RUN]".yellow().bold(),
+                    thousands(update.total),
+                    invalid_note(update.invalid)
+                );
+                return Ok(());
+            }
+
+            index.feeds.insert(name.clone(), feed);
+            index.save()?;
+            let state = defend::State::load()?;
+            let entry = Entry::new(Action::Subscribe, &name).reason(Some(url));
+            let applied = commit(&ctx, &state, vec![entry], false, None).await?;
+            println!(
+                "{} Subscribed to {} ({} ranges{})",
+                "Success:".green().bold(),
+                name.cyan(),
+                thousands(update.total),
+                invalid_note(update.invalid)
+            );
+            print_export_hint(applied);
+            Ok(())
+        }
+        FeedsCommands::Remove { name, dry_run } => {
+            let Some(feed) = index.feeds.remove(&name) else {
+                println!("Not subscribed to {name}.");
+                return Ok(());
+            };
+            if dry_run {
+                println!(
+                    "{} Would remove {name} and unload its {} ranges",
+                    "[DRY RUN]".yellow().bold(),
+                    thousands(feed.entries)
+                );
+                return Ok(());
+            }
+
+            index.save()?;
+            feeds::forget(&name)?;
+            let state = defend::State::load()?;
+            let entry = Entry::new(Action::Unsubscribe, &name).reason(Some(feed.url));
+            commit(&ctx, &state, vec![entry], false, None).await?;
+            println!("{} Removed feed {}", "Success:".green().bold(), name.cyan());
+            Ok(())
+        }
+        FeedsCommands::Update {
+            name,
+            force,
+            dry_run,
+        } => update_feeds(&ctx, index, name, force, dry_run).await,
+    }
+}
+
+async fn update_feeds(
+    ctx: &Context,
+    mut index: feeds::Index,
+    only: Option<String>,
+    force: bool,
+    dry_run: bool,
+) -> Result<()> {
+    if let Some(name) = &only {
+        if !index.feeds.contains_key(name) {
+            anyhow::bail!("Not subscribed to {name}. See: i1 defend feeds list");
+        }
+    }
+    if index.feeds.is_empty() {
+        println!(
+            "No feeds to update. Subscribe with: {} defend feeds add spamhaus-drop",
+            "i1".cyan()
+        );
+        return Ok(());
+    }
+
+    let mut changed = Vec::new();
+    let mut failed = 0;
+    for (name, feed) in &mut index.feeds {
+        if only.as_ref().is_some_and(|only| only != name) {
+            continue;
+        }
+        let update = match feeds::update(name, feed, force, dry_run).await {
+            Ok(update) => update,
+            Err(e) => {
+                println!("  {} {} {e}", name.cyan(), "failed:".red());
+                failed += 1;
+                continue;
+            }
+        };
+
+        if update.unchanged {
+            println!("  {} {}", name.cyan(), "unchanged".dimmed());
+        } else if update.held {
+            println!(
+                "  {} {} shrank from {} to {} ranges; kept the old list. \
+                 Re-run with --force if that's expected.",
+                name.cyan(),
+                "Warning:".yellow().bold(),
+                thousands(feed.entries),
+                thousands(update.total)
+            );
+        } else {
+            println!(
+                "  {} +{} -{} ({} ranges{})",
+                name.cyan(),
+                thousands(update.added).red(),
+                thousands(update.removed).green(),
+                thousands(update.total),
+                invalid_note(update.invalid)
+            );
+            if update.added + update.removed > 0 {
+                changed.push(name.clone());
+            }
+        }
+    }
+
+    if dry_run {
+        println!();
+        println!("Run without --dry-run to apply.");
+    } else {
+        index.save()?;
+        if !changed.is_empty() {
+            let state = defend::State::load()?;
+            let applied = commit(
+                ctx,
+                &state,
+                vec![Entry::batch(Action::FeedUpdate, changed)],
+                false,
+                None,
+            )
+            .await?;
+            print_export_hint(applied);
+        }
+    }
+
+    if failed > 0 {
+        anyhow::bail!("{failed} feed(s) failed to update");
+    }
+    Ok(())
+}
+
+fn invalid_note(invalid: usize) -> String {
+    if invalid == 0 {
+        String::new()
+    } else {
+        format!(", {} invalid lines skipped", thousands(invalid))
+    }
+}
+
+fn print_feed(name: &str, feed: &Feed) {
+    let updated = feed.updated.map_or_else(
+        || "never updated".to_string(),
+        |at| format!("updated {}", at.format("%Y-%m-%d %H:%M")),
+    );
+    println!(
+        "  {:<16} {:>8} ranges  {}  {}",
+        name.cyan(),
+        thousands(feed.entries),
+        updated,
+        feed.url.dimmed()
+    );
+}
+
+async fn export(format: ExportFormat) -> Result<()> {
+    let state = defend::State::load()?;
+    let ranges = geo::country_ranges(&state.blocked_countries).await?;
+    let feed_ranges: Vec<IpNet> = feeds::load_all()?.into_values().flatten().collect();
+    let list = Blocklist::new(&state, &ranges, &feed_ranges);
+
+    for target in &list.skipped {
+        eprintln!(
+            "{} skipped {target}: not an IP address or CIDR range",
+            "Warning:".yellow().bold()
+        );
+    }
+    let unresolved = state
+        .blocked_asns
+        .iter()
+        .filter(|asn| !state.asn_prefixes.contains_key(*asn))
+        .count();
+    if unresolved > 0 {
+        eprintln!(
+            "{} {unresolved} blocked ASN(s) have no prefixes to export; unban and ban them again to resolve",
+            "Warning:".yellow().bold(),
+        );
+    }
+
+    print!("{}", export::render(format, &list));
+
+    Ok(())
+}
+
+async fn import(
+    ctx: Context,
+    input: Box<dyn BufRead + Send>,
+    source: &str,
+    format: ImportFormat,
+    reason: Option<String>,
+    yes: bool,
+    dry_run: bool,
+) -> Result<()> {
+    let mut state = defend::State::load()?;
+    let Sorted {
+        new,
+        taken,
+        banned,
+        allowed,
+        protected,
+        rejected,
+    } = sort_import(&ctx, input, format, &state).await?;
+
+    print_rejected(&rejected);
+
+    let mut skipped = vec![
+        format!("{} already banned", thousands(banned)),
+        format!("{} whitelisted", thousands(allowed)),
+    ];
+    if protected > 0 {
+        skipped.push(format!("{protected} covering your SSH session"));
+    }
+    if rejected.count > 0 {
+        skipped.push(format!("{} malformed", thousands(rejected.count)));
+    }
+    let merged = if taken > new.len() {
+        format!(" (merged from {})", thousands(taken))
+    } else {
+        String::new()
+    };
+    println!(
+        "{} new{merged}, {} \u{2014} skipped",
+        thousands(new.len()),
+        skipped.join(", ")
+    );
+
+    if new.is_empty() {
+        println!("Nothing new to ban.");
+        return Ok(());
+    }
+    if new.len() > IMPORT_CONFIRM && !yes && !dry_run {
+        // Piped input leaves no terminal to answer the prompt on
+        if !std::io::stdin().is_terminal() {
+            anyhow::bail!(
+                "Banning {} addresses needs confirmation. Re-run with --yes.",
+                thousands(new.len())
+            );
+        }
+        if !confirm(&format!("Ban {} addresses?", thousands(new.len())))? {
+            println!("{}", "Cancelled.".dimmed());
+            return Ok(());
+        }
+    }
+
+    // None of them are banned yet, so skip `apply`'s search per target
+    state.blocked_ips.extend(new.iter().cloned());
+    if dry_run {
+        println!(
+            "{} Would ban {} addresses",
+            "[DRY RUN]".yellow().bold(),
+            thousands(new.len())
+        );
+    }
+    let entries = vec![Entry::batch(Action::Ban, new.clone())
+        .reason(reason)
+        .source(source)];
+    let applied = commit(&ctx, &state, entries, dry_run, None).await?;
+
+    if dry_run {
+        println!();
+        println!("Run without --dry-run to apply.");
+    } else {
+        println!(
+            "{} Banned {} addresses",
+            "Success:".green().bold(),
+            thousands(new.len())
+        );
+        print_export_hint(applied);
+    }
+
+    Ok(())
+}
+
+/// Show the first malformed lines and how many more there were.
+fn print_rejected(rejected: &import::Rejected) {
+    for malformed in &rejected.samples {
+        eprintln!(
+            "{} line {}: {}",
+            "Skipped".yellow(),
+            malformed.line,
+            malformed.reason
+        );
+    }
+    if rejected.count > rejected.samples.len() {
+        eprintln!(
+            "{} {} more malformed lines",
+            "Skipped".yellow(),
+            thousands(rejected.count - rejected.samples.len())
+        );
+    }
+}
+
+/// Group the IPs in `path` by WHOIS netblock and offer to ban the blocks
+/// holding at least `min_ips` of them.
+#[cfg(feature = "whois")]
+async fn suggest(
+    ctx: Context,
+    path: &std::path::Path,
+    min_ips: usize,
+    reason: Option<String>,
+    yes: bool,
+    dry_run: bool,
+) -> Result<()> {
+    use crate::defend::suggest::{self, Verdict};
+
+    let file = std::fs::File::open(path)
+        .map_err(|e| anyhow::anyhow!("Could not read {}: {e}", path.display()))?;
+    let mut ips = Vec::new();
+    let mut rejected = import::Rejected::default();
+    import::read(
+        BufReader::new(file),
+        ImportFormat::Auto,
+        &mut |line, target| {
+            match target {
+                Ok(net) if net.prefix_len() == net.max_prefix_len() => ips.push(net.addr()),
+                Ok(net) => rejected.push(line, &anyhow::anyhow!("{net} is a range, not an IP")),
+                Err(e) => rejected.push(line, &e),
+            }
+            std::ops::ControlFlow::Continue(())
+        },
+    )?;
+    print_rejected(&rejected);
+    if ips.is_empty() {
+        anyhow::bail!("No IP addresses in {}", path.display());
+    }
+
+    let pretty = ctx.output_format == OutputFormat::Pretty;
+    let progress = (pretty && !ctx.quiet).then(|| {
+        let pb = indicatif::ProgressBar::new_spinner();
+        pb.set_message(format!("Looking up {} addresses...", thousands(ips.len())));
+        pb.enable_steady_tick(std::time::Duration::from_millis(100));
+        pb
+    });
+    let summaries = i1::recon::whois::summarize_owners(&ips)
+        .await
+        .map_err(i1::I1Error::from);
+    if let Some(pb) = progress {
+        pb.finish_and_clear();
+    }
+
+    let mut state = defend::State::load()?;
+    let ssh = get_ssh_client_ip().and_then(|ip| ip.parse::<std::net::IpAddr>().ok());
+    let suggestions = suggest::suggest(summaries?, min_ips, &state, ssh);
+    print_suggestions(&ctx, &suggestions)?;
+
+    let nets: Vec<String> = suggestions
+        .iter()
+        .filter(|s| s.verdict == Verdict::Ban)
+        .map(|s| nft::element(&s.block.cidr))
+        .collect();
+    if nets.is_empty() {
+        if pretty {
+            println!("Nothing to ban: no other netblock holds {min_ips} or more of the addresses.");
+        }
+        return Ok(());
+    }
+    if !yes && !dry_run {
+        // The prompt would land in the middle of JSON or piped output
+        if !pretty || !std::io::stdin().is_terminal() {
+            eprintln!(
+                "Ban the {} suggested netblocks with: {} defend suggest --from-file {} --min-ips {min_ips} --yes",
+                nets.len(),
+                "i1".cyan(),
+                path.display()
             );
             return Ok(());
         }
+        println!();
+        if !confirm(&format!("Ban {} netblocks?", nets.len()))? {
+            println!("{}", "Cancelled.".dimmed());
+            return Ok(());
+        }
     }
 
-    let mut state = defend::State::load()?;
+    state.blocked_ips.extend(nets.iter().cloned());
+    if dry_run {
+        println!(
+            "{} Would ban {} netblocks",
+            "[DRY RUN]".yellow().bold(),
+            nets.len()
+        );
+    }
+    let source = path.file_name().map_or_else(
+        || path.display().to_string(),
+        |n| n.to_string_lossy().into(),
+    );
+    let entries = vec![Entry::batch(Action::Ban, nets.clone())
+        .reason(reason)
+        .source(format!("suggest:{source}"))];
+    let applied = commit(&ctx, &state, entries, dry_run, None).await?;
 
-    if as_number {
-        // Ban AS number
-        let asn = target.trim_start_matches("AS").trim_start_matches("as");
-        if dry_run {
-            println!("{} Would block AS{}", "[DRY RUN]".yellow().bold(), asn);
-        } else {
-            state.blocked_asns.push(format!("AS{asn}"));
-            state.save()?;
-            println!("{} Blocked AS{}", "Success:".green().bold(), asn.red());
+    if dry_run {
+        println!();
+        println!("Run without --dry-run to apply.");
+    } else if pretty {
+        println!(
+            "{} Banned {} netblocks",
+            "Success:".green().bold(),
+            nets.len()
+        );
+        print_export_hint(applied);
+    }
+    Ok(())
+}
+
+/// The netblocks `defend suggest` found, with what it makes of each.
+#[cfg(feature = "whois")]
+fn print_suggestions(
+    ctx: &Context,
+    suggestions: &[crate::defend::suggest::Suggestion],
+) -> Result<()> {
+    use crate::defend::suggest::Verdict;
+
+    match ctx.output_format {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(suggestions)?);
+        }
+        OutputFormat::Yaml => println!("{}", serde_yaml::to_string(suggestions)?),
+        OutputFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(std::io::stdout().lock());
+            writer.write_record([
+                "cidr",
+                "org",
+                "country",
+                "abuse_email",
+                "ip_count",
+                "sample_ips",
+                "verdict",
+            ])?;
+            for s in suggestions {
+                writer.write_record([
+                    s.block.cidr.to_string(),
+                    s.block.org.clone().unwrap_or_default(),
+                    s.block.country.clone().unwrap_or_default(),
+                    s.block.abuse_email.clone().unwrap_or_default(),
+                    s.block.ip_count.to_string(),
+                    join_ips(&s.block.sample_ips, ";"),
+                    s.verdict.name().to_string(),
+                ])?;
+            }
+            writer.flush()?;
+        }
+        OutputFormat::Pretty => {
+            let header = [
+                "Netblock",
+                "Owner",
+                "Country",
+                "Abuse contact",
+                "IPs",
+                "Examples",
+                "",
+            ]
+            .map(String::from)
+            .to_vec();
+            let rows = suggestions
+                .iter()
+                .map(|s| {
+                    let verdict = match &s.verdict {
+                        Verdict::Ban => "ban".red().bold().to_string(),
+                        Verdict::TooFew => "too few".dimmed().to_string(),
+                        Verdict::Whitelisted(entry) => {
+                            format!("whitelisted ({entry})").green().to_string()
+                        }
+                        Verdict::SshSession => "your SSH session".yellow().to_string(),
+                        Verdict::Banned => "already banned".dimmed().to_string(),
+                        Verdict::Unknown => "owner unknown".dimmed().to_string(),
+                    };
+                    vec![
+                        s.block.cidr.to_string(),
+                        s.block.org.clone().unwrap_or_else(|| "-".to_string()),
+                        s.block.country.clone().unwrap_or_else(|| "-".to_string()),
+                        s.block
+                            .abuse_email
+                            .clone()
+                            .unwrap_or_else(|| "-".to_string()),
+                        thousands(s.block.ip_count),
+                        join_ips(&s.block.sample_ips, ", "),
+                        verdict,
+                    ]
+                })
+                .collect();
+            println!("{}", crate::output::terminal::records(header, rows));
         }
-    } else {
-        // Ban IP or CIDR
-        if dry_run {
-            println!("{} Would block {}", "[DRY RUN]".yellow().bold(), target);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "whois")]
+fn join_ips(ips: &[std::net::IpAddr], sep: &str) -> String {
+    ips.iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(sep)
+}
+
+/// How `defend import` sorted its input.
+struct Sorted {
+    /// Ranges to ban
+    new: Vec<String>,
+    /// Targets merged into `new`
+    taken: usize,
+    banned: usize,
+    allowed: usize,
+    protected: usize,
+    rejected: import::Rejected,
+}
+
+/// Stream `input` against the whitelist and bans in `state`, keeping what's
+/// new to ban.
+async fn sort_import(
+    ctx: &Context,
+    input: Box<dyn BufRead + Send>,
+    format: ImportFormat,
+    state: &defend::State,
+) -> Result<Sorted> {
+    let ssh = get_ssh_client_ip().and_then(|ip| ip.parse::<std::net::IpAddr>().ok());
+    let whitelist = import::Cover::new(&state.whitelisted_ips);
+    let blocked = import::Cover::new(&state.blocked_ips);
+
+    let progress = (ctx.output_format == OutputFormat::Pretty && !ctx.quiet).then(|| {
+        let pb = indicatif::ProgressBar::new_spinner();
+        pb.set_message("Reading...");
+        pb
+    });
+
+    let (mut lines, reader) = import::spawn(input, format);
+    let mut ranges = import::Ranges::default();
+    let mut rejected = import::Rejected::default();
+    let (mut read, mut taken, mut banned, mut allowed, mut protected) = (0, 0, 0, 0, 0);
+
+    while let Some((line, target)) = lines.recv().await {
+        read += 1;
+        if read % IMPORT_PROGRESS_EVERY == 0 {
+            if let Some(pb) = &progress {
+                pb.set_message(format!("Read {} entries...", thousands(read)));
+            }
+        }
+        let net = match target {
+            Ok(net) => net,
+            Err(e) => {
+                rejected.push(line, &e);
+                continue;
+            }
+        };
+        if ssh.is_some_and(|ip| net.contains(&ip)) {
+            protected += 1;
+        } else if whitelist.covers(&net) {
+            allowed += 1;
+        } else if blocked.covers(&net) {
+            banned += 1;
         } else {
-            state.blocked_ips.push(target.to_string());
-            state.save()?;
-            println!("{} Blocked {}", "Success:".green().bold(), target.red());
+            ranges.push(net)?;
+            taken += 1;
         }
     }
+    reader.await??;
+    if let Some(pb) = progress {
+        pb.finish_and_clear();
+    }
 
-    println!();
-    println!("Generate rules with: {} defend export", "i1".cyan());
+    Ok(Sorted {
+        // Contiguous and repeated targets collapse into CIDR ranges
+        new: ranges.finish().iter().map(nft::element).collect(),
+        taken,
+        banned,
+        allowed,
+        protected,
+        rejected,
+    })
+}
 
-    Ok(())
+/// `1204` as `1,204`.
+fn thousands(n: impl std::fmt::Display) -> String {
+    let digits = n.to_string();
+    let mut out = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
 }
 
-async fn unban(_ctx: Context, target: &str) -> Result<()> {
-    let mut state = defend::State::load()?;
+async fn undo(ctx: Context, steps: usize, dry_run: bool) -> Result<()> {
+    let entries = journal::load()?;
+    let undoable = journal::undoable(&entries, steps);
+    if undoable.is_empty() {
+        println!("Nothing to undo.");
+        return Ok(());
+    }
+    if undoable.len() < steps {
+        println!("Only {} change(s) to undo.", undoable.len());
+    }
 
-    // Check if it's an ASN
-    if target.to_uppercase().starts_with("AS") {
-        if let Some(pos) = state
-            .blocked_asns
-            .iter()
-            .position(|a| a.eq_ignore_ascii_case(target))
-        {
-            state.blocked_asns.remove(pos);
-            state.save()?;
-            println!("{} Unblocked {}", "Success:".green().bold(), target.cyan());
-            return Ok(());
+    let mut state = defend::State::load()?;
+    let mut undos = Vec::new();
+    let mut firewall = false;
+    for entry in &undoable {
+        state.revert(entry);
+        firewall |= !matches!(entry.action, Action::BanAsn | Action::UnbanAsn)
+            || !entry.prefixes.is_empty();
+
+        let what = entry.summary();
+        if dry_run {
+            println!("{} Would undo: {}", "[DRY RUN]".yellow().bold(), what);
         }
+        undos.push(
+            Entry::batch(Action::Undo, entry.targets.clone()).reason(Some(format!("undo {what}"))),
+        );
     }
 
-    // Check IPs
-    if let Some(pos) = state.blocked_ips.iter().position(|i| i == target) {
-        state.blocked_ips.remove(pos);
-        state.save()?;
-        println!("{} Unblocked {}", "Success:".green().bold(), target.cyan());
-        return Ok(());
+    // ASNs banned before prefixes were resolved never reached the firewall
+    if firewall {
+        commit(&ctx, &state, undos, dry_run, None).await?;
+    } else if !dry_run {
+        record(&state, undos)?;
     }
 
-    println!("{} {} is not currently blocked.", "Note:".yellow(), target);
+    if !dry_run {
+        for entry in &undoable {
+            println!(
+                "{} Undid: {}",
+                "Success:".green().bold(),
+                entry.summary().cyan()
+            );
+        }
+    }
     Ok(())
 }
 
-async fn whitelist(_ctx: Context, args: WhitelistArgs) -> Result<()> {
-    match args.command {
-        WhitelistCommands::Show => {
-            let state = defend::State::load()?;
-            if state.whitelisted_ips.is_empty() {
-                println!("No IPs whitelisted.");
-            } else {
-                println!("{}", "Whitelisted IPs:".bold());
-                for ip in &state.whitelisted_ips {
-                    println!("  {}", ip.green());
-                }
+fn history(ctx: &Context, limit: usize) -> Result<()> {
+    let entries = journal::load()?;
+    let recent: Vec<&Entry> = entries.iter().rev().take(limit).collect();
+
+    match ctx.output_format {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&recent)?);
+        }
+        OutputFormat::Yaml => println!("{}", serde_yaml::to_string(&recent)?),
+        OutputFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(std::io::stdout().lock());
+            writer.write_record(["at", "action", "targets", "reason", "actor", "applied"])?;
+            for entry in &recent {
+                writer.write_record([
+                    entry.at.to_rfc3339(),
+                    entry.action.to_string(),
+                    entry.targets.join(";"),
+                    entry.reason.clone().unwrap_or_default(),
+                    entry.actor.clone().unwrap_or_default(),
+                    entry.applied.to_string(),
+                ])?;
             }
-            Ok(())
+            writer.flush()?;
         }
-        WhitelistCommands::Add { ip } => {
-            let mut state = defend::State::load()?;
-            if state.whitelisted_ips.contains(&ip) {
-                println!("{ip} is already whitelisted.");
-            } else {
-                state.whitelisted_ips.push(ip.clone());
-                state.save()?;
-                println!(
-                    "{} Added {} to whitelist.",
-                    "Success:".green().bold(),
-                    ip.green()
-                );
+        OutputFormat::Pretty => {
+            if recent.is_empty() {
+                println!("No defend changes yet.");
+                return Ok(());
             }
-            Ok(())
-        }
-        WhitelistCommands::Remove { ip } => {
-            let mut state = defend::State::load()?;
-            if let Some(pos) = state.whitelisted_ips.iter().position(|i| i == &ip) {
-                state.whitelisted_ips.remove(pos);
-                state.save()?;
+            println!("{}", "Recent Changes:".bold());
+            for entry in &recent {
+                print_entry(entry);
+            }
+            if entries.len() > recent.len() {
+                println!();
                 println!(
-                    "{} Removed {} from whitelist.",
-                    "Success:".green().bold(),
-                    ip
+                    "{}",
+                    format!(
+                        "{} older changes; show more with -n",
+                        entries.len() - recent.len()
+                    )
+                    .dimmed()
                 );
-            } else {
-                println!("{ip} is not in the whitelist.");
             }
-            Ok(())
         }
     }
+
+    Ok(())
 }
 
-async fn export(_ctx: Context, format: &str) -> Result<()> {
-    let state = defend::State::load()?;
+async fn disable(ctx: Context, dry_run: bool) -> Result<()> {
+    println!("{}", "EMERGENCY DISABLE".red().bold());
+    println!();
+
+    if !cfg!(target_os = "linux") {
+        println!("Only nftables on Linux is managed directly.");
+        println!("Remove the rules you loaded from 'i1 defend export' by hand.");
+        return Ok(());
+    }
+
+    if dry_run {
+        println!(
+            "{} Would delete table inet {}",
+            "[DRY RUN]".yellow().bold(),
+            nft::TABLE
+        );
+        check_batch(&ctx, &nft::teardown())?;
+        return Ok(());
+    }
+
+    nft::apply(&nft::teardown(), false)?;
+    let mut entry = Entry::new(Action::Disable, "");
+    entry.applied = true;
+    journal::append(&entry)?;
+
+    println!(
+        "{} All i1 blocking removed (table inet {} deleted).",
+        "Success:".green().bold(),
+        nft::TABLE
+    );
+    println!(
+        "{}",
+        "Your block list is kept; the next defend change loads it again.".dimmed()
+    );
+    Ok(())
+}
+
+/// The rules for `state`, with the nft batch on Linux.
+async fn rules(state: &defend::State) -> Result<Rules> {
+    let batch = if cfg!(target_os = "linux") {
+        let ranges = geo::country_ranges(&state.blocked_countries).await?;
+        Some(nft::ruleset(state, &ranges, &feeds::load_all()?))
+    } else {
+        None
+    };
+    Ok(Rules::new(state.clone(), batch))
+}
 
-    match format.to_lowercase().as_str() {
-        "nftables" | "nft" => {
-            let rules = defend::generate_nftables(&state)?;
-            println!("{rules}");
+/// Load `state` into the firewall, then save it and journal `entries`.
+///
+/// With `dry_run` the rules are only checked and nothing is saved. Off
+/// Linux only the state is saved. Returns whether the firewall was changed.
+async fn commit(
+    ctx: &Context,
+    state: &defend::State,
+    entries: Vec<Entry>,
+    dry_run: bool,
+    expect: Option<Expect>,
+) -> Result<bool> {
+    let rules = rules(state).await?;
+    if dry_run {
+        if let Some(batch) = &rules.batch {
+            check_batch(ctx, batch)?;
         }
-        "iptables" | "ipt" => {
-            let rules = defend::generate_iptables(&state)?;
-            println!("{rules}");
+        return Ok(false);
+    }
+    carry_out(ctx, rules, entries, expect).await
+}
+
+/// Load `rules`, then save their state and journal `entries`.
+async fn carry_out(
+    ctx: &Context,
+    mut rules: Rules,
+    entries: Vec<Entry>,
+    expect: Option<Expect>,
+) -> Result<bool> {
+    let first = rules.batch.is_some() && !journal::load()?.iter().any(|e| e.applied);
+    let applied = rules.execute(&plan::Nft, false)?;
+    if let Some(expect) = expect.filter(|_| applied) {
+        nft::verify(expect.set, &expect.net, expect.present)?;
+    }
+
+    rules.state.save()?;
+    for mut entry in entries {
+        entry.applied = applied;
+        journal::append(&entry)?;
+    }
+
+    if first {
+        suggest_whitelist(ctx, &rules.state).await;
+    }
+
+    Ok(applied)
+}
+
+/// Work out `action` on `targets` and the firewall changes it makes.
+async fn plan_change(state: &defend::State, action: Action, targets: &[String]) -> Result<Plan> {
+    let mut plan = Plan::new(state, action, targets);
+    let feeds = feeds::load_all()?;
+
+    let ranges = geo::country_ranges(&state.blocked_countries).await?;
+    let before = nft::sets(state, &ranges, &feeds);
+    let next = &plan.rules.state;
+    let ranges = geo::country_ranges(&next.blocked_countries).await?;
+    let after = nft::sets(next, &ranges, &feeds);
+    let batch = cfg!(target_os = "linux").then(|| nft::ruleset(next, &ranges, &feeds));
+
+    plan.render(&before, &after, batch);
+    Ok(plan)
+}
+
+/// A dry run: check the plan's batch and show the plan.
+fn show_plan(ctx: &Context, mut plan: Plan) -> Result<()> {
+    plan.rules.execute(&plan::Nft, true)?;
+
+    match ctx.output_format {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&plan)?);
         }
-        "pf" => {
-            let rules = defend::generate_pf(&state)?;
-            println!("{rules}");
+        OutputFormat::Yaml => println!("{}", serde_yaml::to_string(&plan)?),
+        _ => print_plan(ctx, &plan),
+    }
+    Ok(())
+}
+
+fn print_plan(ctx: &Context, plan: &Plan) {
+    let adds = matches!(
+        plan.action,
+        Action::Ban | Action::BanAsn | Action::Geoblock | Action::Allow
+    );
+    println!("{} {}", "[DRY RUN]".yellow().bold(), plan.action);
+    for target in &plan.changed {
+        if adds {
+            println!("  {} {}", "+".red(), target.red());
+        } else {
+            println!("  {} {}", "-".green(), target.green());
         }
-        _ => {
-            anyhow::bail!(
-                "Unknown format: {format}\n\n\
-                 Supported formats:\n  \
-                 nftables  - Linux nftables (recommended)\n  \
-                 iptables  - Legacy iptables\n  \
-                 pf        - BSD/macOS pf"
+    }
+    for target in &plan.unchanged {
+        println!("  = {target} {}", "(already in effect)".dimmed());
+    }
+    for skipped in &plan.skipped {
+        println!(
+            "  ! {} {}",
+            skipped.target,
+            format!("skipped: {}", skipped.reason).yellow()
+        );
+    }
+    println!();
+
+    if !plan.sets.is_empty() {
+        println!("{}", "Firewall changes:".bold());
+        for set in &plan.sets {
+            println!(
+                "  {:<24} {} {}",
+                set.set,
+                format!("+{}", thousands(set.added.len())).red(),
+                format!("-{}", thousands(set.removed.len())).green()
             );
         }
+        println!();
     }
 
-    Ok(())
+    match &plan.rules.batch {
+        Some(batch) if ctx.verbose => {
+            println!("{}", "Batch for nft -f -:".bold());
+            println!("{batch}");
+        }
+        Some(_) => println!("{}", "Show the full nft batch with --verbose.".dimmed()),
+        None => println!("Rules aren't loaded on this system; see 'i1 defend export'."),
+    }
+    match plan.rules.checked {
+        Some(true) => println!("{} Rules pass nft --check.", "[DRY RUN]".yellow().bold()),
+        Some(false) => println!(
+            "{}",
+            "nft is not installed; rules were not checked.".dimmed()
+        ),
+        None => {}
+    }
+    println!();
+    println!("Run without --dry-run to apply.");
 }
 
-async fn import(_ctx: Context, stdin: bool, file: Option<&str>) -> Result<()> {
-    println!("{}", "Import feature coming soon!".yellow());
-    println!();
-    println!("This will allow importing IPs from:");
-    if stdin {
-        println!("  - Standard input (pipe from other commands)");
+/// The first time rules go live, suggest whitelisting our own address.
+async fn suggest_whitelist(ctx: &Context, state: &defend::State) {
+    let Ok(ip) = myip::public_ip(ctx).await else {
+        return;
+    };
+    let covered =
+        nft::parse_net(&ip).is_ok_and(|net| covering(&state.whitelisted_ips, &net).is_some());
+    if !covered {
+        println!();
+        println!(
+            "{} Your public IP is {}. Make sure you can always get back in with:",
+            "Tip:".cyan().bold(),
+            ip.green()
+        );
+        println!("  {} defend whitelist add {ip}", "i1".cyan());
     }
-    if let Some(f) = file {
-        println!("  - File: {f}");
+}
+
+/// Save `state` and journal `entries` without touching the firewall.
+fn record(state: &defend::State, entries: Vec<Entry>) -> Result<()> {
+    state.save()?;
+    for entry in entries {
+        journal::append(&entry)?;
     }
     Ok(())
 }
 
-async fn undo(_ctx: Context) -> Result<()> {
-    println!("{}", "Undo feature coming soon!".yellow());
-    println!();
-    println!("This will revert the last change to defense settings.");
+/// Run a batch through `nft --check`, if nft is installed.
+fn check_batch(ctx: &Context, batch: &str) -> Result<()> {
+    if ctx.verbose {
+        println!("{batch}");
+    }
+    if plan::Nft.check(batch)? {
+        println!("{} Rules pass nft --check.", "[DRY RUN]".yellow().bold());
+    } else {
+        println!(
+            "{}",
+            "nft is not installed; rules were not checked.".dimmed()
+        );
+    }
     Ok(())
 }
 
-async fn disable(_ctx: Context) -> Result<()> {
-    println!("{}", "EMERGENCY DISABLE".red().bold());
-    println!();
-    println!("This would remove all blocking rules immediately.");
-    println!();
-    println!("On Linux, run:");
-    println!("  {}", "nft delete table inet geoblock".cyan());
-    println!();
-    println!("This is a safety feature - not applying automatically.");
-    Ok(())
+/// The entry in `list` that parses to exactly `net`.
+fn find_net(list: &[String], net: &IpNet) -> Option<String> {
+    list.iter()
+        .find(|entry| nft::parse_net(entry).is_ok_and(|n| n == *net))
+        .cloned()
+}
+
+/// An entry in `list` that still covers `net`.
+fn covering(list: &[String], net: &IpNet) -> Option<String> {
+    list.iter()
+        .find(|entry| nft::parse_net(entry).is_ok_and(|n| n.contains(net)))
+        .cloned()
+}
+
+/// Whether two ranges share any address. CIDRs either nest or are disjoint.
+fn overlaps(a: &IpNet, b: &IpNet) -> bool {
+    a.contains(b) || b.contains(a)
+}
+
+/// Point out whitelisted entries inside `ranges`, which stay reachable.
+fn print_whitelisted_inside(whitelist: &[String], ranges: &[IpNet], what: &str) {
+    let inside: Vec<&String> = whitelist
+        .iter()
+        .filter(|entry| nft::parse_net(entry).is_ok_and(|n| ranges.iter().any(|r| r.contains(&n))))
+        .collect();
+    if !inside.is_empty() {
+        println!(
+            "{} {} whitelisted entr{} inside {what} stay reachable",
+            "Note:".yellow(),
+            inside.len(),
+            if inside.len() == 1 { "y" } else { "ies" }
+        );
+    }
 }
 
-async fn push(_ctx: Context, args: PushArgs) -> Result<()> {
+fn print_export_hint(applied: bool) {
+    if !applied {
+        println!();
+        println!("Generate rules with: {} defend export", "i1".cyan());
+    }
+}
+
+fn print_firewall(counts: Option<&BTreeMap<String, usize>>) {
+    let Some(counts) = counts else {
+        println!(
+            "{} {}",
+            "Firewall:".bold(),
+            format!(
+                "table inet {} not loaded (or not readable without root)",
+                nft::TABLE
+            )
+            .dimmed()
+        );
+        return;
+    };
+
+    let count = |sets: [&str; 2]| -> usize { sets.iter().filter_map(|s| counts.get(*s)).sum() };
+    let feed_count: usize = counts
+        .iter()
+        .filter(|(set, _)| set.starts_with("feed_"))
+        .map(|(_, n)| n)
+        .sum();
+    println!(
+        "{} table inet {} loaded: {} blocked, {} ASN ranges, {} geo ranges, {} feed ranges, {} whitelisted",
+        "Firewall:".bold(),
+        nft::TABLE,
+        count([nft::BLOCKED_V4, nft::BLOCKED_V6]).to_string().red(),
+        count([nft::ASN_V4, nft::ASN_V6]).to_string().red(),
+        count([nft::GEO_V4, nft::GEO_V6]).to_string().red(),
+        feed_count.to_string().red(),
+        count([nft::ALLOWED_V4, nft::ALLOWED_V6])
+            .to_string()
+            .green()
+    );
+}
+
+fn print_entry(entry: &Entry) {
+    let reason = entry
+        .reason
+        .as_ref()
+        .map(|r| format!(" ({r})"))
+        .unwrap_or_default();
+    let actor = entry
+        .actor
+        .as_ref()
+        .map(|a| format!(" by {a}"))
+        .unwrap_or_default();
+    let line = format!(
+        "  {}  {}{reason}{}",
+        entry.at.format("%Y-%m-%d %H:%M"),
+        entry.summary(),
+        actor.dimmed()
+    );
+    if entry.applied || entry.action == Action::Disable {
+        println!("{line}");
+    } else {
+        println!("{line} {}", "[state only]".dimmed());
+    }
+}
+
+async fn push(ctx: Context, args: PushArgs) -> Result<()> {
     use std::io::Write;
     use std::process::{Command, Stdio};
 
     let state = defend::State::load()?;
 
     // Get current user's public IP to auto-whitelist
-    let my_ip = get_my_public_ip().await.ok();
+    let my_ip = myip::public_ip(&ctx).await.ok();
 
     // Parse SSH config
     let ssh_config_path = shellexpand::tilde("~/.ssh/config").to_string();
@@ -655,17 +2407,6 @@ fn parse_ssh_config(path: &str) -> Result<Vec<String>> {
 }
 
 /// Get current public IP address
-async fn get_my_public_ip() -> Result<String> {
-    let client = reqwest::Client::new();
-    let ip = client
-        .get("https://api.ipify.org")
-        .send()
-        .await?
-        .text()
-        .await?;
-    Ok(ip.trim().to_string())
-}
-
 /// Get the IP of the current SSH session (if any)
 fn get_ssh_client_ip() -> Option<String> {
     // SSH_CLIENT format: "client_ip client_port server_port"
@@ -1106,8 +2847,8 @@ async fn community_fetch(min_reports: u32, replace: bool, dry_run: bool) -> Resu
             );
             println!();
             println!("In the meantime, you can manually share blocklists:");
-            println!("  {} defend export --format json > blocklist.json", "i1".cyan());
-            println!("  # Share blocklist.json with others");
+            println!("  {} defend export --format list --output-file blocklist.txt", "i1".cyan());
+            println!("  # Share blocklist.txt with others");
         }
     }
 
@@ -1327,7 +3068,7 @@ async fn community_stats() -> Result<()> {
     Ok(())
 }
 
-async fn patrol(_ctx: Context, args: PatrolArgs) -> Result<()> {
+async fn patrol(ctx: Context, args: PatrolArgs) -> Result<()> {
     match args.command {
         PatrolCommands::Run {
             threshold,
@@ -1335,7 +3076,7 @@ async fn patrol(_ctx: Context, args: PatrolArgs) -> Result<()> {
             dry_run,
             compose_dir,
             execute,
-        } => patrol_run(threshold, window, dry_run, compose_dir, execute).await,
+        } => patrol_run(&ctx, threshold, window, dry_run, compose_dir, execute).await,
         PatrolCommands::Cron {
             interval,
             remove,
@@ -1405,6 +3146,7 @@ struct PatrolHit {
 }
 
 async fn patrol_run(
+    ctx: &Context,
     threshold: u32,
     window: u32,
     dry_run: bool,
@@ -1737,7 +3479,7 @@ async fn patrol_run(
     }
 
     // Get our own IP to never ban ourselves
-    let my_ip = get_my_public_ip().await.ok();
+    let my_ip = myip::public_ip(ctx).await.ok();
     let ssh_ip = get_ssh_client_ip();
 
     // Filter to attackers that exceed threshold
@@ -2078,3 +3820,56 @@ fn extract_source_ip(line: &str) -> Option<String> {
 
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn net(s: &str) -> IpNet {
+        nft::parse_net(s).unwrap()
+    }
+
+    #[test]
+    fn test_overlaps() {
+        let office = net("203.0.113.0/24");
+        // Contained, containing, the same range: either order
+        for other in [
+            "203.0.113.7",
+            "203.0.113.64/26",
+            "203.0.0.0/16",
+            "203.0.113.0/24",
+        ] {
+            assert!(overlaps(&office, &net(other)), "{other}");
+            assert!(overlaps(&net(other), &office), "{other}");
+        }
+        // Disjoint, including the neighbours and the other family
+        for other in [
+            "203.0.112.255",
+            "203.0.114.0/24",
+            "198.51.100.0/24",
+            "2001:db8::/32",
+        ] {
+            assert!(!overlaps(&office, &net(other)), "{other}");
+            assert!(!overlaps(&net(other), &office), "{other}");
+        }
+    }
+
+    #[test]
+    fn test_covering() {
+        let whitelist = ["198.51.100.9".to_string(), "203.0.113.0/24".to_string()];
+        // Contained: the entry that covers it
+        assert_eq!(
+            covering(&whitelist, &net("203.0.113.7")).as_deref(),
+            Some("203.0.113.0/24")
+        );
+        assert_eq!(
+            covering(&whitelist, &net("198.51.100.9")).as_deref(),
+            Some("198.51.100.9")
+        );
+        // Containing a whitelisted entry isn't covered by it
+        assert_eq!(covering(&whitelist, &net("203.0.0.0/16")), None);
+        assert_eq!(covering(&whitelist, &net("198.51.100.0/24")), None);
+        // Disjoint
+        assert_eq!(covering(&whitelist, &net("192.0.2.1")), None);
+    }
+}
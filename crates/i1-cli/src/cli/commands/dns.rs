@@ -1,19 +1,123 @@
 //! `i1 dns` - DNS lookups.
+//!
+//! A single `dns resolve` asks the provider, or every DNS provider with
+//! `--provider all`. With `--file` or `-` the hostnames are resolved many
+//! at once, and every hostname gets a result even when it fails: with
+//! `--provider shodan` in Shodan's batched requests, otherwise locally with
+//! the system resolver.
+
+use std::collections::{BTreeSet, HashMap};
+use std::future::Future;
+use std::net::IpAddr;
 
 use anyhow::Result;
 use colored::Colorize;
+use futures_util::future::join_all;
+use futures_util::StreamExt;
+use serde::Serialize;
 
+use super::host::parse_targets;
 use super::Context;
 use crate::cli::args::{DnsArgs, DnsCommands};
+use crate::cli::exit::{self, Exit};
 use crate::output::OutputFormat;
 use i1_providers::DnsProvider;
 
-pub async fn execute(ctx: Context, args: DnsArgs) -> Result<()> {
-    let provider = ctx.shodan_provider()?;
+/// Hostnames resolved at once in bulk mode
+const CONCURRENCY: usize = 32;
+
+/// Which address families `--type` keeps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Families {
+    v4: bool,
+    v6: bool,
+}
+
+impl Families {
+    /// From `--type`, already checked to be A or AAAA; both when none are given.
+    fn new(types: &[String]) -> Self {
+        if types.is_empty() {
+            return Self { v4: true, v6: true };
+        }
+        Self {
+            v4: types.iter().any(|t| t == "A"),
+            v6: types.iter().any(|t| t == "AAAA"),
+        }
+    }
+
+    const fn keeps(self, ip: &IpAddr) -> bool {
+        match ip {
+            IpAddr::V4(_) => self.v4,
+            IpAddr::V6(_) => self.v6,
+        }
+    }
+}
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Status {
+    Resolved,
+    /// The name exists but has no address of the requested types
+    NoAddress,
+    Nxdomain,
+    Error,
+}
+
+/// One output row: a hostname with one of its addresses, or why it has none.
+#[derive(Debug, Serialize)]
+struct Row {
+    hostname: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ip: Option<IpAddr>,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    record_type: Option<&'static str>,
+    status: Status,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl Row {
+    const fn failed(hostname: String, status: Status, error: Option<String>) -> Self {
+        Self {
+            hostname,
+            ip: None,
+            record_type: None,
+            status,
+            error,
+        }
+    }
+}
+
+pub async fn execute(ctx: Context, args: DnsArgs) -> Result<()> {
     match args.command {
-        DnsCommands::Resolve { hostname } => {
-            let ips = provider.resolve(&hostname).await?;
+        DnsCommands::Resolve {
+            hostname,
+            file,
+            types,
+            unique_ips,
+        } => {
+            let families = Families::new(&types);
+
+            let input = match (&file, hostname.as_deref()) {
+                (Some(path), _) => Some(
+                    std::fs::read_to_string(path)
+                        .map_err(|e| anyhow::anyhow!("Could not read {}: {e}", path.display()))?,
+                ),
+                (None, Some("-")) => Some(std::io::read_to_string(std::io::stdin())?),
+                _ => None,
+            };
+            if let Some(input) = input {
+                return resolve_bulk(&ctx, parse_targets(&input), families, unique_ips).await;
+            }
+
+            let hostname = hostname.unwrap_or_default();
+            let providers = ctx.dns_providers()?;
+            let mut ips = ask(&ctx, &providers, |p| p.resolve(&hostname)).await?;
+            ips.retain(|ip| families.keeps(ip));
+
+            if unique_ips {
+                return print_unique(&ctx, &ips.into_iter().collect());
+            }
 
             match ctx.output_format {
                 OutputFormat::Json => {
@@ -41,7 +145,8 @@ pub async fn execute(ctx: Context, args: DnsArgs) -> Result<()> {
             }
         }
         DnsCommands::Reverse { ip } => {
-            let hostnames = provider.reverse(&ip).await?;
+            let providers = ctx.dns_providers()?;
+            let hostnames = ask(&ctx, &providers, |p| p.reverse(&ip)).await?;
 
             match ctx.output_format {
                 OutputFormat::Json => {
@@ -76,3 +181,307 @@ pub async fn execute(ctx: Context, args: DnsArgs) -> Result<()> {
 
     Ok(())
 }
+
+/// `i1 dns resolve --file` / `-`: resolve every hostname, one row per address.
+/// Put the same question to every provider `--provider` picked and keep
+/// every distinct answer. Fails only when no provider answers.
+async fn ask<'a, T, F, Fut>(
+    ctx: &Context,
+    providers: &'a [Box<dyn DnsProvider + Send + Sync>],
+    question: F,
+) -> Result<Vec<T>>
+where
+    T: PartialEq,
+    F: Fn(&'a (dyn DnsProvider + Send + Sync)) -> Fut,
+    Fut: Future<Output = i1::Result<Vec<T>>>,
+{
+    let answers = join_all(providers.iter().map(|p| question(p.as_ref()))).await;
+
+    let mut merged: Option<Vec<T>> = None;
+    let mut first_error = None;
+    for (provider, answer) in providers.iter().zip(answers) {
+        match answer {
+            Ok(values) => {
+                let merged = merged.get_or_insert_with(Vec::new);
+                for value in values {
+                    if !merged.contains(&value) {
+                        merged.push(value);
+                    }
+                }
+            }
+            Err(e) => {
+                if providers.len() > 1 && ctx.output_format == OutputFormat::Pretty {
+                    eprintln!(
+                        "{} {} lookup failed: {e}",
+                        "Warning:".yellow(),
+                        provider.display_name()
+                    );
+                }
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+
+    match (merged, first_error) {
+        (Some(values), _) => Ok(values),
+        (None, Some(e)) => Err(e.into()),
+        (None, None) => anyhow::bail!("No provider available for DNS lookups"),
+    }
+}
+
+async fn resolve_bulk(
+    ctx: &Context,
+    hostnames: Vec<String>,
+    families: Families,
+    unique_ips: bool,
+) -> Result<()> {
+    if hostnames.is_empty() {
+        anyhow::bail!("No hostnames given. Provide one hostname per line.");
+    }
+
+    let resolved = if ctx.provider == "shodan" {
+        resolve_with_shodan(ctx, hostnames).await?
+    } else {
+        resolve_locally(hostnames).await
+    };
+    let rows = rows(resolved, families);
+
+    let count = |status| {
+        rows.iter()
+            .filter(|r| r.status == status)
+            .map(|r| &r.hostname)
+            .collect::<BTreeSet<_>>()
+            .len()
+    };
+    let (ok, errors) = (count(Status::Resolved), count(Status::Error));
+
+    if unique_ips {
+        print_unique(ctx, &rows.iter().filter_map(|r| r.ip).collect())?;
+    } else {
+        print_rows(ctx, &rows)?;
+    }
+
+    if !ctx.quiet {
+        let empty = count(Status::NoAddress);
+        let empty = if empty > 0 {
+            format!(", {empty} without addresses")
+        } else {
+            String::new()
+        };
+        eprintln!(
+            "{ok} resolved, {} NXDOMAIN, {errors} errors{empty}",
+            count(Status::Nxdomain)
+        );
+    }
+
+    if ok == 0 && errors == 0 {
+        return Err(Exit(exit::NOT_FOUND).into());
+    }
+    Ok(())
+}
+
+/// Look the hostnames up with the system resolver, [`CONCURRENCY`] at a time.
+async fn resolve_locally(hostnames: Vec<String>) -> Vec<(String, std::io::Result<Vec<IpAddr>>)> {
+    futures_util::stream::iter(hostnames)
+        .map(|hostname| async move {
+            let ips = tokio::net::lookup_host((hostname.as_str(), 0))
+                .await
+                .map(|addrs| addrs.map(|addr| addr.ip()).collect());
+            (hostname, ips)
+        })
+        .buffered(CONCURRENCY)
+        .collect()
+        .await
+}
+
+/// Look the hostnames up in as few Shodan requests as will do. Shodan
+/// doesn't say why a name has no address, so none is NXDOMAIN.
+async fn resolve_with_shodan(
+    ctx: &Context,
+    hostnames: Vec<String>,
+) -> Result<Vec<(String, std::io::Result<Vec<IpAddr>>)>> {
+    let names: Vec<&str> = hostnames.iter().map(String::as_str).collect();
+    let mut resolved = HashMap::new();
+    for provider in ctx.dns_providers()? {
+        resolved.extend(provider.resolve_many(&names).await?);
+    }
+    Ok(hostnames
+        .into_iter()
+        .map(|hostname| {
+            let ips = resolved.get(&hostname).cloned().unwrap_or_default();
+            (hostname, Ok(ips))
+        })
+        .collect())
+}
+
+/// Turn lookups into rows, keeping input order and addresses in resolver order.
+fn rows(resolved: Vec<(String, std::io::Result<Vec<IpAddr>>)>, families: Families) -> Vec<Row> {
+    let mut rows = Vec::new();
+    for (hostname, result) in resolved {
+        match result {
+            Ok(ips) => {
+                let mut seen = BTreeSet::new();
+                let ips: Vec<IpAddr> = ips
+                    .into_iter()
+                    .filter(|ip| families.keeps(ip) && seen.insert(*ip))
+                    .collect();
+                if ips.is_empty() {
+                    rows.push(Row::failed(hostname, Status::NoAddress, None));
+                    continue;
+                }
+                for ip in ips {
+                    rows.push(Row {
+                        hostname: hostname.clone(),
+                        ip: Some(ip),
+                        record_type: Some(if ip.is_ipv4() { "A" } else { "AAAA" }),
+                        status: Status::Resolved,
+                        error: None,
+                    });
+                }
+            }
+            Err(e) if is_nxdomain(&e) => rows.push(Row::failed(hostname, Status::Nxdomain, None)),
+            Err(e) => rows.push(Row::failed(hostname, Status::Error, Some(e.to_string()))),
+        }
+    }
+    rows
+}
+
+/// Whether a system resolver error means the name doesn't exist.
+///
+/// getaddrinfo failures only come through as messages, which differ by
+/// platform.
+fn is_nxdomain(e: &std::io::Error) -> bool {
+    let message = e.to_string();
+    [
+        "Name or service not known",
+        "No address associated with hostname",
+        "nodename nor servname provided",
+        "No such host is known",
+    ]
+    .iter()
+    .any(|m| message.contains(m))
+}
+
+/// `--unique-ips`: the address set alone, ready for `i1 host -`.
+fn print_unique(ctx: &Context, ips: &BTreeSet<IpAddr>) -> Result<()> {
+    match ctx.output_format {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(ips)?);
+        }
+        OutputFormat::Yaml => println!("{}", serde_yaml::to_string(ips)?),
+        OutputFormat::Csv | OutputFormat::Pretty => {
+            for ip in ips {
+                println!("{ip}");
+            }
+        }
+    }
+    Ok(())
+}
+
+fn print_rows(ctx: &Context, rows: &[Row]) -> Result<()> {
+    match ctx.output_format {
+        // One record per line, like `i1 host --file`
+        OutputFormat::Json => {
+            for row in rows {
+                println!("{}", serde_json::to_string(row)?);
+            }
+        }
+        OutputFormat::Yaml => println!("{}", serde_yaml::to_string(rows)?),
+        OutputFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(std::io::stdout());
+            writer.write_record(["hostname", "ip", "type", "status", "error"])?;
+            for row in rows {
+                let status = serde_json::to_value(row.status)?;
+                writer.write_record([
+                    row.hostname.as_str(),
+                    &row.ip.map(|ip| ip.to_string()).unwrap_or_default(),
+                    row.record_type.unwrap_or_default(),
+                    status.as_str().unwrap_or_default(),
+                    row.error.as_deref().unwrap_or_default(),
+                ])?;
+            }
+            writer.flush()?;
+        }
+        OutputFormat::Pretty => {
+            let mut last: Option<&str> = None;
+            for row in rows {
+                if last != Some(row.hostname.as_str()) {
+                    if ctx.no_color {
+                        println!("{}", row.hostname);
+                    } else {
+                        println!("{}", row.hostname.green());
+                    }
+                    last = Some(&row.hostname);
+                }
+                match (row.status, row.ip) {
+                    (Status::Resolved, Some(ip)) => println!("  -> {ip}"),
+                    (Status::NoAddress, _) => println!("  {}", "no matching addresses".dimmed()),
+                    (Status::Nxdomain, _) => println!("  {}", "NXDOMAIN".yellow()),
+                    (_, _) => println!(
+                        "  {} {}",
+                        "Error:".red(),
+                        row.error.as_deref().unwrap_or_default()
+                    ),
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nxdomain() -> std::io::Error {
+        std::io::Error::other("failed to lookup address information: Name or service not known")
+    }
+
+    #[test]
+    fn test_families() {
+        let both = Families::new(&[]);
+        assert!(both.v4 && both.v6);
+
+        let v6 = Families::new(&["AAAA".to_string()]);
+        assert!(!v6.keeps(&"192.0.2.1".parse().unwrap()));
+        assert!(v6.keeps(&"2001:db8::1".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_rows() {
+        let v4: IpAddr = "192.0.2.1".parse().unwrap();
+        let v6: IpAddr = "2001:db8::1".parse().unwrap();
+        let resolved = vec![
+            ("a.example".to_string(), Ok(vec![v4, v6, v4])),
+            ("gone.example".to_string(), Err(nxdomain())),
+            (
+                "slow.example".to_string(),
+                Err(std::io::Error::other("timed out")),
+            ),
+            ("v6only.example".to_string(), Ok(vec![v6])),
+        ];
+        let rows = rows(
+            resolved,
+            Families {
+                v4: true,
+                v6: false,
+            },
+        );
+
+        let summary: Vec<(&str, Option<IpAddr>, Status)> = rows
+            .iter()
+            .map(|r| (r.hostname.as_str(), r.ip, r.status))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("a.example", Some(v4), Status::Resolved),
+                ("gone.example", None, Status::Nxdomain),
+                ("slow.example", None, Status::Error),
+                ("v6only.example", None, Status::NoAddress),
+            ]
+        );
+        assert_eq!(rows[0].record_type, Some("A"));
+        assert_eq!(rows[2].error.as_deref(), Some("timed out"));
+    }
+}
@@ -0,0 +1,497 @@
+//! `i1 domain` - Subdomain and DNS record enumeration.
+
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use colored::Colorize;
+use serde::Serialize;
+
+use super::Context;
+use crate::cli::args::DomainArgs;
+use crate::output::OutputFormat;
+use i1_providers::{DnsProvider, DnsRecord, DomainInfo};
+
+/// Subdomains (and records per type) shown in pretty mode without `--limit`
+const PRETTY_LIMIT: usize = 50;
+
+/// Record types in the order people usually read them; anything else follows
+const TYPE_ORDER: &[&str] = &["A", "AAAA", "CNAME", "MX", "NS", "TXT", "SOA", "SRV", "CAA"];
+
+/// Who found each subdomain, with `--bruteforce`
+type Sources = BTreeMap<String, Vec<String>>;
+
+/// The merged domain, and with `--bruteforce` who found each subdomain and
+/// which providers failed
+#[derive(Serialize)]
+struct Output<'a> {
+    #[serde(flatten)]
+    info: &'a DomainInfo,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    sources: &'a Sources,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    errors: &'a BTreeMap<String, String>,
+}
+
+/// What `--limit` cut from the output.
+#[derive(Debug, Default)]
+struct Hidden {
+    subdomains: usize,
+    records: BTreeMap<String, usize>,
+}
+
+pub async fn execute(ctx: Context, args: DomainArgs) -> Result<()> {
+    let domain = args.domain.trim().trim_end_matches('.').to_lowercase();
+    #[cfg(feature = "dns")]
+    if args.audit {
+        return audit(&ctx, &domain).await;
+    }
+    let (mut info, mut sources, errors) = discover(&ctx, &args, &domain).await?;
+    filter(&mut info, &args);
+
+    let limit = match args.limit {
+        Some(0) => None,
+        Some(n) => Some(n),
+        None => (ctx.output_format == OutputFormat::Pretty).then_some(PRETTY_LIMIT),
+    };
+    let hidden = limit.map(|n| truncate(&mut info, n)).unwrap_or_default();
+    sources.retain(|name, _| info.subdomains.contains(name));
+
+    let output = Output {
+        info: &info,
+        sources: &sources,
+        errors: &errors,
+    };
+    match ctx.output_format {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&output)?);
+        }
+        OutputFormat::Yaml => {
+            println!("{}", serde_yaml::to_string(&output)?);
+        }
+        OutputFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(std::io::stdout().lock());
+            if args.subdomains_only && !sources.is_empty() {
+                writer.write_record(["domain", "subdomain", "sources"])?;
+                for sub in &info.subdomains {
+                    let found_by = sources.get(sub).map(|s| s.join(";")).unwrap_or_default();
+                    writer.write_record([&info.domain, sub, &found_by])?;
+                }
+            } else if args.subdomains_only {
+                writer.write_record(["domain", "subdomain"])?;
+                for sub in &info.subdomains {
+                    writer.write_record([&info.domain, sub])?;
+                }
+            } else {
+                writer.write_record(["domain", "type", "name", "value", "ttl"])?;
+                for record in &info.records {
+                    writer.write_record([
+                        &info.domain,
+                        &record.record_type,
+                        &record.name,
+                        &record.value,
+                        &record.ttl.map(|t| t.to_string()).unwrap_or_default(),
+                    ])?;
+                }
+            }
+            writer.flush()?;
+        }
+        OutputFormat::Pretty => print_domain_pretty(&info, &sources, &hidden, &args, &ctx),
+    }
+
+    Ok(())
+}
+
+/// The providers' view of the domain, or with `--bruteforce` that unioned
+/// with the names a wordlist turned up.
+async fn discover(
+    ctx: &Context,
+    args: &DomainArgs,
+    domain: &str,
+) -> Result<(DomainInfo, Sources, BTreeMap<String, String>)> {
+    #[cfg(feature = "dns")]
+    if args.bruteforce {
+        let correlated = bruteforce(ctx, args, domain).await?;
+        if ctx.output_format == OutputFormat::Pretty {
+            for (provider, error) in &correlated.errors {
+                eprintln!("{} {provider} lookup failed: {error}", "Warning:".yellow());
+            }
+        }
+        return Ok((correlated.info, correlated.sources, correlated.errors));
+    }
+    #[cfg(not(feature = "dns"))]
+    let _ = args;
+
+    let providers = ctx.dns_providers()?;
+    let info = lookup(ctx, &providers, domain).await?;
+    Ok((info, Sources::new(), BTreeMap::new()))
+}
+
+/// Ask every provider at once and merge what comes back.
+async fn lookup(
+    ctx: &Context,
+    providers: &[Box<dyn DnsProvider + Send + Sync>],
+    domain: &str,
+) -> Result<DomainInfo> {
+    let lookups = providers.iter().map(|p| p.domain_info(domain));
+    let results = futures_util::future::join_all(lookups).await;
+
+    let mut merged: Option<DomainInfo> = None;
+    let mut first_error = None;
+
+    for (provider, result) in providers.iter().zip(results) {
+        match result {
+            Ok(info) => {
+                if let Some(merged) = &mut merged {
+                    merged.merge(info);
+                } else {
+                    let mut info = info;
+                    info.qualify();
+                    merged = Some(info);
+                }
+            }
+            Err(e) => {
+                if providers.len() > 1 && ctx.output_format == OutputFormat::Pretty {
+                    eprintln!(
+                        "{} {} lookup failed: {e}",
+                        "Warning:".yellow(),
+                        provider.display_name()
+                    );
+                }
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+
+    match (merged, first_error) {
+        (Some(info), _) => Ok(info),
+        (None, Some(e)) => Err(e.into()),
+        (None, None) => anyhow::bail!("No provider available for domain lookups"),
+    }
+}
+
+/// Apply `--records-only`, `--subdomains-only` and `--type`.
+fn filter(info: &mut DomainInfo, args: &DomainArgs) {
+    if args.records_only || !args.types.is_empty() {
+        info.subdomains.clear();
+    }
+    if args.subdomains_only {
+        info.records.clear();
+    }
+    if !args.types.is_empty() {
+        let types: Vec<String> = args.types.iter().map(|t| t.trim().to_uppercase()).collect();
+        info.records.retain(|r| types.contains(&r.record_type));
+    }
+
+    info.records.sort_by(|a, b| {
+        type_rank(&a.record_type)
+            .cmp(&type_rank(&b.record_type))
+            .then_with(|| a.record_type.cmp(&b.record_type))
+            .then_with(|| a.name.cmp(&b.name))
+            .then_with(|| a.value.cmp(&b.value))
+    });
+}
+
+/// Keep `limit` subdomains and `limit` records of each type.
+fn truncate(info: &mut DomainInfo, limit: usize) -> Hidden {
+    let mut hidden = Hidden {
+        subdomains: info.subdomains.len().saturating_sub(limit),
+        records: BTreeMap::new(),
+    };
+    info.subdomains.truncate(limit);
+
+    let mut kept: BTreeMap<String, usize> = BTreeMap::new();
+    info.records.retain(|r| {
+        let count = kept.entry(r.record_type.clone()).or_default();
+        *count += 1;
+        if *count > limit {
+            *hidden.records.entry(r.record_type.clone()).or_default() += 1;
+            false
+        } else {
+            true
+        }
+    });
+
+    hidden
+}
+
+fn type_rank(record_type: &str) -> usize {
+    TYPE_ORDER
+        .iter()
+        .position(|t| *t == record_type)
+        .unwrap_or(TYPE_ORDER.len())
+}
+
+fn print_domain_pretty(
+    info: &DomainInfo,
+    sources: &Sources,
+    hidden: &Hidden,
+    args: &DomainArgs,
+    ctx: &Context,
+) {
+    if ctx.no_color {
+        println!("Domain: {}", info.domain);
+    } else {
+        println!("{} {}", "Domain:".bold(), info.domain.cyan().bold());
+    }
+    if let Some(registrar) = &info.registrar {
+        println!("{} {}", "Registrar:".bold(), registrar);
+    }
+    if let Some(created) = &info.created {
+        println!("{} {}", "Created:".bold(), created.format("%Y-%m-%d"));
+    }
+    if let Some(expires) = &info.expires {
+        let days = (*expires - chrono::Utc::now()).num_days();
+        let line = format!("{} ({days} days)", expires.format("%Y-%m-%d"));
+        if days < 30 && !ctx.no_color {
+            println!("{} {}", "Expires:".bold(), line.red());
+        } else {
+            println!("{} {}", "Expires:".bold(), line);
+        }
+    }
+
+    if !args.records_only && args.types.is_empty() {
+        println!();
+        let total = info.subdomains.len() + hidden.subdomains;
+        println!("{}", format!("Subdomains ({total}):").bold().underline());
+        if info.subdomains.is_empty() {
+            println!("  None found");
+        }
+        let width = info.subdomains.iter().map(String::len).max().unwrap_or(0);
+        for sub in &info.subdomains {
+            match sources.get(sub) {
+                Some(found_by) => {
+                    let found_by = format!("[{}]", found_by.join(", "));
+                    if ctx.no_color {
+                        println!("  {sub:<width$}  {found_by}");
+                    } else {
+                        println!("  {sub:<width$}  {}", found_by.dimmed());
+                    }
+                }
+                None => println!("  {sub}"),
+            }
+        }
+        print_hidden(hidden.subdomains, "subdomains");
+    }
+
+    if !args.subdomains_only {
+        println!();
+        println!("{}", "Records:".bold().underline());
+        if info.records.is_empty() {
+            println!("  None found");
+        }
+        // Records are sorted by type, so each type is one contiguous run
+        let mut rest = info.records.as_slice();
+        while let Some(first) = rest.first() {
+            let len = rest
+                .iter()
+                .position(|r| r.record_type != first.record_type)
+                .unwrap_or(rest.len());
+            let (group, tail) = rest.split_at(len);
+            print_group(group, hidden, ctx);
+            rest = tail;
+        }
+    }
+}
+
+/// One record type: a header, then names and values in aligned columns.
+fn print_group(group: &[DnsRecord], hidden: &Hidden, ctx: &Context) {
+    let record_type = &group[0].record_type;
+    let more = hidden.records.get(record_type).copied().unwrap_or(0);
+    let header = format!("{record_type} ({})", group.len() + more);
+    if ctx.no_color {
+        println!("  {header}");
+    } else {
+        println!("  {}", header.cyan().bold());
+    }
+
+    let width = group.iter().map(|r| r.name.len()).max().unwrap_or(0);
+    for record in group {
+        println!("    {:<width$}  {}", record.name, record.value);
+    }
+    print_hidden(more, "records");
+}
+
+fn print_hidden(count: usize, what: &str) {
+    if count > 0 {
+        println!(
+            "{}",
+            format!("  ... and {count} more {what} (use --limit 0 to show all)").dimmed()
+        );
+    }
+}
+
+/// `--bruteforce`: try every word under the domain, then union what turned
+/// up with the subdomains configured providers know of.
+#[cfg(feature = "dns")]
+async fn bruteforce(
+    ctx: &Context,
+    args: &DomainArgs,
+    domain: &str,
+) -> Result<i1::recon::dns::CorrelatedDomain> {
+    use anyhow::Context as _;
+    use futures_util::StreamExt;
+    use i1::recon::dns::{self, SubdomainBruteforcer};
+    use i1_core::I1Error;
+
+    let words = match &args.wordlist {
+        Some(path) => {
+            let text = std::fs::read_to_string(path)
+                .with_context(|| format!("Could not read the wordlist {}", path.display()))?;
+            dns::parse_wordlist(&text)
+        }
+        None => dns::default_wordlist(),
+    };
+    if words.is_empty() {
+        anyhow::bail!("The wordlist has no words");
+    }
+
+    let progress = (ctx.output_format == OutputFormat::Pretty && !ctx.quiet).then(|| {
+        let pb = indicatif::ProgressBar::new(words.len() as u64);
+        if let Ok(style) = indicatif::ProgressStyle::with_template(
+            "{spinner} [{bar:40.cyan/blue}] {pos}/{len} names {msg}",
+        ) {
+            pb.set_style(style.progress_chars("=> "));
+        }
+        pb
+    });
+
+    let mut probes = SubdomainBruteforcer::new()
+        .stream(domain, words)
+        .await
+        .map_err(I1Error::from)?;
+    let mut found = Vec::new();
+    while let Some(probe) = probes.next().await {
+        if let Some(pb) = &progress {
+            pb.inc(1);
+        }
+        if probe.found() {
+            found.push(probe);
+            if let Some(pb) = &progress {
+                pb.set_message(format!("({} found)", found.len()));
+            }
+        }
+    }
+    if let Some(pb) = progress {
+        pb.finish_and_clear();
+    }
+
+    let providers = ctx.dns_providers().unwrap_or_else(|_| {
+        if ctx.output_format == OutputFormat::Pretty {
+            eprintln!(
+                "{} no provider configured, so only bruteforced names are shown",
+                "Note:".yellow()
+            );
+        }
+        Vec::new()
+    });
+    Ok(dns::correlate(dns::domain_info(domain, found), &providers).await)
+}
+
+/// `--audit`: ask the domain's own nameservers. Subdomains the providers
+/// know of are checked for dangling CNAMEs too, when one is configured.
+#[cfg(feature = "dns")]
+async fn audit(ctx: &Context, domain: &str) -> Result<()> {
+    use i1::recon::dns::{DnsAuditor, DnsResolver};
+    use i1_core::I1Error;
+
+    let nameservers = DnsResolver::new()
+        .nameservers(domain)
+        .await
+        .map_err(I1Error::from)?;
+    if nameservers.is_empty() {
+        return Err(I1Error::Dns(format!("No nameservers found for {domain}")).into());
+    }
+
+    let names = if let Ok(providers) = ctx.dns_providers() {
+        lookup(ctx, &providers, domain)
+            .await
+            .map(|info| info.subdomains)
+            .unwrap_or_default()
+    } else {
+        if ctx.output_format == OutputFormat::Pretty {
+            eprintln!(
+                "{} no provider configured, so only names from a zone transfer are checked for dangling CNAMEs",
+                "Note:".yellow()
+            );
+        }
+        Vec::new()
+    };
+
+    let report = DnsAuditor::new()
+        .names(names)
+        .audit(domain, &nameservers)
+        .await
+        .map_err(I1Error::from)?;
+
+    match ctx.output_format {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+        OutputFormat::Yaml => println!("{}", serde_yaml::to_string(&report)?),
+        OutputFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(std::io::stdout().lock());
+            writer.write_record(["domain", "severity", "check", "name", "detail"])?;
+            for finding in &report.findings {
+                writer.serialize((
+                    &report.domain,
+                    finding.severity,
+                    finding.check,
+                    &finding.name,
+                    &finding.detail,
+                ))?;
+            }
+            writer.flush()?;
+        }
+        OutputFormat::Pretty => print_audit_pretty(&report, ctx),
+    }
+    Ok(())
+}
+
+#[cfg(feature = "dns")]
+fn print_audit_pretty(report: &i1::recon::dns::DnsAuditReport, ctx: &Context) {
+    use i1::recon::dns::Severity;
+
+    if ctx.no_color {
+        println!("DNS audit: {}", report.domain);
+    } else {
+        println!("{} {}", "DNS audit:".bold(), report.domain.cyan().bold());
+    }
+
+    println!();
+    println!("{}", "Zone transfers:".bold().underline());
+    for transfer in &report.zone_transfers {
+        let outcome = match (&transfer.error, transfer.allowed) {
+            (Some(error), _) => format!("unreachable ({error})"),
+            (None, true) => format!("ALLOWED, {} records", transfer.records.len()),
+            (None, false) => "refused".to_string(),
+        };
+        let outcome = match (&transfer.error, transfer.allowed) {
+            _ if ctx.no_color => outcome,
+            (Some(_), _) => outcome.dimmed().to_string(),
+            (None, true) => outcome.red().bold().to_string(),
+            (None, false) => outcome.green().to_string(),
+        };
+        println!("  {:<24} {outcome}", transfer.nameserver);
+    }
+
+    println!();
+    println!(
+        "{}",
+        format!("Findings ({}):", report.findings.len())
+            .bold()
+            .underline()
+    );
+    if report.findings.is_empty() {
+        println!("  None");
+    }
+    for finding in &report.findings {
+        let label = format!("{:<6}", format!("{:?}", finding.severity).to_uppercase());
+        let label = match finding.severity {
+            _ if ctx.no_color => label,
+            Severity::High => label.red().bold().to_string(),
+            Severity::Medium => label.yellow().to_string(),
+            Severity::Low | Severity::Info => label.dimmed().to_string(),
+        };
+        println!("  {label} {}  {}", finding.name, finding.detail);
+    }
+}
@@ -0,0 +1,272 @@
+//! `i1 honeypot` - Make sense of the tripwire events honeypot kits send.
+//!
+//! `stats` reads an archive of delivered events (one JSON object per line,
+//! or whole `{"events": [...]}` deliveries) and reports what the kits
+//! caught. Kits given with `--kit`, or found through the manifest in a
+//! `--deploy` directory, let events be attributed to them; without either,
+//! only the log-wide figures are meaningful.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context as _, Result};
+use colored::Colorize;
+use i1_honeypot::analytics::{
+    ComponentHits, EventLog, EventSummary, FirstAccess, SourceIp, TimelineBucket,
+};
+use i1_honeypot::HoneypotKit;
+use serde::Serialize;
+use tabled::Tabled;
+
+use super::Context;
+use crate::cli::args::{HoneypotArgs, HoneypotCommands};
+use crate::output::{terminal, OutputFormat};
+
+/// Longest timeline drawn in pretty mode; CSV has all of it
+const MAX_PRETTY_BUCKETS: usize = 60;
+
+/// Widest timeline bar
+const BAR_WIDTH: usize = 40;
+
+/// Everything `honeypot stats` reports, as JSON and YAML print it.
+#[derive(Debug, Serialize)]
+struct Stats {
+    summary: EventSummary,
+    first_access: Vec<FirstAccess>,
+    top_sources: Vec<SourceIp>,
+    components: Vec<ComponentHits>,
+    timeline: Vec<TimelineBucket>,
+}
+
+#[derive(Tabled)]
+struct AccessRow {
+    #[tabled(rename = "Kit")]
+    kit: String,
+    #[tabled(rename = "Deployed")]
+    deployed: String,
+    #[tabled(rename = "First access")]
+    first: String,
+    #[tabled(rename = "After")]
+    delay: String,
+    #[tabled(rename = "Before deploy")]
+    early: usize,
+}
+
+#[derive(Tabled)]
+struct SourceRow {
+    #[tabled(rename = "Source")]
+    ip: String,
+    #[tabled(rename = "Events")]
+    events: usize,
+    #[tabled(rename = "Honeypots")]
+    honeypots: usize,
+    #[tabled(rename = "First seen")]
+    first: String,
+    #[tabled(rename = "Last seen")]
+    last: String,
+}
+
+#[derive(Tabled)]
+struct ComponentRow {
+    #[tabled(rename = "Bait")]
+    component: String,
+    #[tabled(rename = "Events")]
+    events: usize,
+    #[tabled(rename = "Honeypots")]
+    honeypots: usize,
+}
+
+pub fn execute(ctx: &Context, args: HoneypotArgs) -> Result<()> {
+    match args.command {
+        HoneypotCommands::Stats {
+            events,
+            kits,
+            deploys,
+            top,
+            bucket,
+        } => stats(ctx, &events, &kits, &deploys, top, bucket),
+    }
+}
+
+fn stats(
+    ctx: &Context,
+    events: &Path,
+    kits: &[PathBuf],
+    deploys: &[PathBuf],
+    top: usize,
+    bucket: Duration,
+) -> Result<()> {
+    let mut log = EventLog::load(events)
+        .with_context(|| format!("Can't read events from {}", events.display()))?;
+
+    let kits = kits
+        .iter()
+        .map(|path| {
+            let json = std::fs::read_to_string(path)
+                .with_context(|| format!("Can't read {}", path.display()))?;
+            serde_json::from_str::<HoneypotKit>(&json)
+                .with_context(|| format!("{} is not a honeypot kit", path.display()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    log = log.with_kits(&kits);
+    for dir in deploys {
+        let manifest = HoneypotKit::load_manifest(dir)
+            .with_context(|| format!("No kit manifest in {}", dir.display()))?;
+        log = log.with_manifest(&manifest);
+    }
+
+    let stats = Stats {
+        summary: log.summary(),
+        first_access: log.first_access_delay(),
+        top_sources: log.top_source_ips(top),
+        components: log.component_hit_counts(),
+        timeline: log.timeline(bucket),
+    };
+
+    match ctx.output_format {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&stats)?);
+        }
+        OutputFormat::Yaml => println!("{}", serde_yaml::to_string(&stats)?),
+        // The timeline, for plotting
+        OutputFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(std::io::stdout().lock());
+            writer.write_record(["start", "events"])?;
+            for bucket in &stats.timeline {
+                writer.write_record([bucket.start.to_rfc3339(), bucket.events.to_string()])?;
+            }
+            writer.flush()?;
+        }
+        OutputFormat::Pretty => print_pretty(ctx, &stats),
+    }
+    Ok(())
+}
+
+fn print_pretty(ctx: &Context, stats: &Stats) {
+    let summary = &stats.summary;
+    let range = match (summary.first, summary.last) {
+        (Some(first), Some(last)) => format!(
+            ", {} to {}",
+            first.format("%Y-%m-%d %H:%M"),
+            last.format("%Y-%m-%d %H:%M")
+        ),
+        _ => String::new(),
+    };
+    println!(
+        "{} events from {} sources across {} kits{range}",
+        summary.events.to_string().bold(),
+        summary.sources,
+        summary.kits
+    );
+
+    let skipped = [
+        (summary.duplicates, "duplicate deliveries"),
+        (summary.malformed, "malformed lines"),
+        (summary.unattributed, "from honeypots in no known kit"),
+        (
+            summary.before_deployment,
+            "stamped before their kit was deployed",
+        ),
+    ];
+    for (count, what) in skipped.into_iter().filter(|&(count, _)| count > 0) {
+        let line = format!("  {count} {what}");
+        if ctx.no_color {
+            println!("{line}");
+        } else {
+            println!("{}", line.yellow());
+        }
+    }
+
+    if !stats.first_access.is_empty() {
+        println!();
+        println!("{}", "First access".bold());
+        let rows: Vec<AccessRow> = stats
+            .first_access
+            .iter()
+            .map(|access| AccessRow {
+                kit: access.kit_id.to_string(),
+                deployed: access.deployed_at.format("%Y-%m-%d %H:%M").to_string(),
+                first: access.first_triggered_at.map_or_else(
+                    || "never".to_string(),
+                    |at| at.format("%Y-%m-%d %H:%M").to_string(),
+                ),
+                delay: access.delay_secs.map_or_else(String::new, delay),
+                early: access.before_deployment,
+            })
+            .collect();
+        println!("{}", terminal::table(&rows));
+    }
+
+    if !stats.top_sources.is_empty() {
+        println!();
+        println!("{}", "Top sources".bold());
+        let rows: Vec<SourceRow> = stats
+            .top_sources
+            .iter()
+            .map(|source| SourceRow {
+                ip: source.ip.clone(),
+                events: source.events,
+                honeypots: source.honeypots,
+                first: source.first_seen.format("%Y-%m-%d %H:%M").to_string(),
+                last: source.last_seen.format("%Y-%m-%d %H:%M").to_string(),
+            })
+            .collect();
+        println!("{}", terminal::table(&rows));
+    }
+
+    if !stats.components.is_empty() {
+        println!();
+        println!("{}", "Bait taken".bold());
+        let rows: Vec<ComponentRow> = stats
+            .components
+            .iter()
+            .map(|hits| ComponentRow {
+                component: hits.component.clone(),
+                events: hits.events,
+                honeypots: hits.honeypots,
+            })
+            .collect();
+        println!("{}", terminal::table(&rows));
+    }
+
+    if !stats.timeline.is_empty() {
+        println!();
+        println!("{}", "Timeline".bold());
+        print_timeline(&stats.timeline);
+    }
+}
+
+fn print_timeline(timeline: &[TimelineBucket]) {
+    if timeline.len() > MAX_PRETTY_BUCKETS {
+        println!(
+            "{}",
+            format!(
+                "  {} buckets; use a larger --bucket, or -o csv to plot them",
+                timeline.len()
+            )
+            .dimmed()
+        );
+        return;
+    }
+    let most = timeline.iter().map(|b| b.events).max().unwrap_or(0).max(1);
+    for bucket in timeline {
+        let line = format!(
+            "  {} {:>5} {}",
+            bucket.start.format("%Y-%m-%d %H:%M"),
+            bucket.events,
+            "#".repeat((bucket.events * BAR_WIDTH).div_ceil(most))
+        );
+        println!("{}", line.trim_end());
+    }
+}
+
+/// `3d 4h`, `5h 12m` or `42s`
+fn delay(secs: i64) -> String {
+    let (days, hours, minutes) = (secs / 86_400, secs % 86_400 / 3600, secs % 3600 / 60);
+    match (days, hours, minutes) {
+        (0, 0, 0) => format!("{secs}s"),
+        (0, 0, m) => format!("{m}m"),
+        (0, h, m) => format!("{h}h {m}m"),
+        (d, h, _) => format!("{d}d {h}h"),
+    }
+}
@@ -1,13 +1,35 @@
 //! `i1 host` - Look up information about an IP address.
 
+use std::collections::{BTreeMap, HashMap};
+
 use anyhow::Result;
+use chrono::{DateTime, Utc};
 use colored::Colorize;
-use tabled::{settings::Style, Table, Tabled};
+use futures_util::StreamExt;
+use serde::Serialize;
+use tabled::Tabled;
+
+use super::preflight::{self, Preflight};
+use super::watch::Watch;
+use super::{Context, HOST_PROVIDERS};
+use crate::cache::{self, Cache};
+use crate::cli::args::{ColumnArgs, HostArgs, WatchArgs};
+use crate::cli::exit::{self, Exit};
+use crate::output::fields::{self, Field};
+use crate::output::filter::Filter;
+use crate::output::severity::{self, Severity};
+use crate::output::{no_stix, print_stix, template, terminal, OutputFormat};
+use crate::workspace::{self, Kind};
+use i1::{HostInfo, I1Error, Investigation, IpNet, MergedHostInfo, ProviderId, Target};
 
-use super::Context;
-use crate::cli::args::HostArgs;
-use crate::output::OutputFormat;
-use i1::HostInfo;
+/// CSV columns when `--fields` is not given
+const DEFAULT_CSV_FIELDS: &[Field] = &[
+    Field::Ip,
+    Field::Org,
+    Field::Asn,
+    Field::Country,
+    Field::Ports,
+];
 
 #[derive(Tabled)]
 struct PortRow {
@@ -21,51 +43,569 @@ struct PortRow {
     version: String,
 }
 
-pub async fn execute(ctx: Context, args: HostArgs) -> Result<()> {
+/// What one provider returned for `--all`.
+#[derive(Serialize)]
+#[serde(untagged)]
+enum ProviderResult {
+    Host(Box<HostInfo>),
+    Error { error: String },
+}
+
+/// JSON/YAML shape of `--all`: the merged view plus every provider's answer.
+#[derive(Serialize)]
+struct AllReport<'a> {
+    merged: Option<&'a MergedHostInfo>,
+    /// Whether every provider contributed to `merged`
+    complete: bool,
+    providers: BTreeMap<String, ProviderResult>,
+}
+
+pub async fn execute(mut ctx: Context, args: HostArgs) -> Result<()> {
+    ctx.raw = args.raw;
+
+    if let Some(path) = &args.file {
+        let input = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Could not read {}: {e}", path.display()))?;
+        if args.dry_run {
+            return dry_run(&ctx, &parse_targets(&input), path.display().to_string()).await;
+        }
+        return execute_batch(&ctx, parse_targets(&input), &args).await;
+    }
+
+    let ip = args.ip.clone().unwrap_or_default();
+
+    // --provider all is --all for a single IP
+    let all = args.all || ctx.provider == "all";
+
+    if args.template.format_template.is_some() && (all || args.watch.watch.is_some()) {
+        anyhow::bail!("--format-template cannot be combined with --all, --provider all or --watch");
+    }
+    if args.columns.filter.is_some() && (all || args.watch.watch.is_some() || args.raw) {
+        anyhow::bail!("--filter cannot be combined with --all, --provider all, --watch or --raw");
+    }
+    if ctx.stix && args.watch.watch.is_some() {
+        return Err(no_stix("host --watch").into());
+    }
+
+    if ip == "-" {
+        if args.all || args.watch.watch.is_some() || args.raw {
+            anyhow::bail!(
+                "--all, --watch and --raw look up a single IP and cannot read targets from stdin"
+            );
+        }
+        let input = std::io::read_to_string(std::io::stdin())?;
+        if args.dry_run {
+            return dry_run(&ctx, &parse_targets(&input), "stdin".to_string()).await;
+        }
+        return execute_batch(&ctx, parse_targets(&input), &args).await;
+    }
+
+    if args.dry_run {
+        anyhow::bail!("--dry-run estimates batch lookups; give it targets with --file or `-`");
+    }
+
+    // URLs are looked up by their host, and anything that is no kind of
+    // target fails here rather than at the provider
+    let ip = match Target::parse(&ip)?.host() {
+        Target::Ip(ip) => ip.to_string(),
+        Target::Cidr(net) => {
+            if args.all || args.watch.watch.is_some() || args.raw {
+                anyhow::bail!("--all, --watch and --raw look up a single IP, not a CIDR block");
+            }
+            return execute_cidr(&ctx, net, &args).await;
+        }
+        Target::Domain(domain) | Target::Url(domain) => {
+            if args.all || args.watch.watch.is_some() || args.raw {
+                anyhow::bail!(
+                    "--all, --watch and --raw look up a single IP, not a domain.\n\
+                     Resolve it first with: i1 dns resolve {domain}"
+                );
+            }
+            return execute_domain(&ctx, domain, &args).await;
+        }
+    };
+
+    if all {
+        if args.watch.watch.is_some() {
+            return Err(ctx.one_provider("Host watches", &HOST_PROVIDERS));
+        }
+        return execute_all(ctx, &ip, args.compare).await;
+    }
+
     let provider = ctx.host_provider()?;
+    ctx.policy.check(provider.name(), &ip)?;
 
-    let host = provider.lookup_host(&args.ip).await?;
+    if let Some(secs) = args.watch.watch {
+        return execute_watch(&ctx, provider.as_ref(), &ip, secs, &args.watch).await;
+    }
+    if args.raw {
+        return execute_raw(&ctx, provider.as_ref(), &ip).await;
+    }
+    execute_one(&ctx, provider.as_ref(), &ip, &args).await
+}
 
-    match ctx.output_format {
-        OutputFormat::Json => {
-            println!("{}", serde_json::to_string_pretty(&host)?);
+/// `i1 host <ip>`: one provider's answer, from the cache when it has one.
+async fn execute_one(
+    ctx: &Context,
+    provider: &(dyn i1_providers::HostLookup + Send + Sync),
+    ip: &str,
+    args: &HostArgs,
+) -> Result<()> {
+    let cached = Cache::open(ctx.cache)
+        .host(provider.name(), ip, || provider.lookup_host(ip))
+        .await?;
+    let mut host = cached.value;
+    let risk = host.risk_score();
+    let hidden = severity::retain(&mut host, args.min_severity);
+
+    if let Some(filter) = &args.columns.filter {
+        if !filter.matches(&host) {
+            if ctx.output_format == OutputFormat::Pretty && !ctx.quiet {
+                println!("{}", format!("{ip} does not match --filter").dimmed());
+            }
+            return Err(Exit(exit::NOT_FOUND).into());
         }
-        OutputFormat::Yaml => {
-            println!("{}", serde_yaml::to_string(&host)?);
+    }
+
+    if let Some(template) = &args.template.format_template {
+        template::print([&host], template, &args.template.list_sep());
+        return Ok(());
+    }
+
+    let selected = if args.columns.fields.is_empty() {
+        DEFAULT_CSV_FIELDS
+    } else {
+        &args.columns.fields
+    };
+
+    match ctx.output_format {
+        _ if ctx.stix => print_stix(std::slice::from_ref(&host))?,
+        OutputFormat::Json | OutputFormat::Yaml => {
+            let value = if args.columns.fields.is_empty() {
+                serde_json::to_value(&host)?
+            } else {
+                fields::project(&host, selected)
+            };
+
+            if ctx.output_format == OutputFormat::Json {
+                println!("{}", serde_json::to_string_pretty(&value)?);
+            } else {
+                println!("{}", serde_yaml::to_string(&value)?);
+            }
         }
         OutputFormat::Csv => {
-            println!("ip,org,asn,country,ports");
-            let ports: Vec<String> = host
-                .ports
+            fields::write_csv([&host], selected)?;
+        }
+        OutputFormat::Pretty => {
+            if args.columns.fields.is_empty() {
+                // The whole view is decoration; --quiet leaves just the exit code
+                if !ctx.quiet {
+                    print_host_pretty(&host, risk, hidden, cached.cached_at, ctx);
+                }
+            } else {
+                println!("{}", fields::table([&host], selected));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// `i1 host <ip> --watch`: look the host up every `secs` seconds and print
+/// what changed.
+async fn execute_watch(
+    ctx: &Context,
+    provider: &(dyn i1_providers::HostLookup + Send + Sync),
+    ip: &str,
+    secs: u64,
+    args: &WatchArgs,
+) -> Result<()> {
+    let watch = Watch::new(ctx, ip, secs, args);
+    let notifier = watch.notifier();
+    watch
+        .run(
+            move || async move {
+                let host = provider.lookup_host(ip).await?;
+                notifier.check(&host, "watch");
+                Ok(host)
+            },
+            HostInfo::diff,
+            |host| {
+                format!(
+                    "{} open ports, {} vulnerabilities",
+                    host.open_ports().len(),
+                    host.vulns.len()
+                )
+            },
+        )
+        .await
+}
+
+/// `i1 host <ip> --all`: query every provider at once, then merge or compare.
+async fn execute_all(ctx: Context, ip: &str, compare: bool) -> Result<()> {
+    let client = ctx.client()?;
+    let mut results = client.lookup_host_all(ip).await?;
+
+    // The chosen --provider wins when fields disagree in the merged view
+    results.sort_by_key(|(name, _)| *name != ctx.provider);
+
+    let mut hosts: Vec<(ProviderId, HostInfo)> = Vec::new();
+    let mut failures: Vec<(String, I1Error)> = ctx
+        .unconfigured_providers()
+        .into_iter()
+        .map(|name| {
+            (
+                name.to_string(),
+                I1Error::ProviderNotConfigured(name.to_string()),
+            )
+        })
+        .collect();
+
+    for (name, result) in results {
+        match (result, name.parse::<ProviderId>()) {
+            (Ok(host), Ok(id)) => {
+                workspace::keep_host(Kind::Host, &name, &host);
+                hosts.push((id, host));
+            }
+            (Ok(_), Err(e)) | (Err(e), _) => failures.push((name, e)),
+        }
+    }
+    failures.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let merged = (!hosts.is_empty()).then(|| MergedHostInfo::from_hosts(ip, &hosts));
+
+    if ctx.raw {
+        let raw = merged.as_ref().and_then(|merged| merged.raw.as_ref());
+        let raw =
+            raw.ok_or_else(|| anyhow::anyhow!("No provider returned a raw response for {ip}"))?;
+        return print_raw(&ctx, raw);
+    }
+
+    match ctx.output_format {
+        _ if ctx.stix => {
+            // Each provider's view is its own observation of the address
+            let found: Vec<HostInfo> = hosts.iter().map(|(_, host)| host.clone()).collect();
+            print_stix(&found)?;
+        }
+        OutputFormat::Json | OutputFormat::Yaml => {
+            let mut providers: BTreeMap<String, ProviderResult> = hosts
                 .iter()
-                .map(std::string::ToString::to_string)
+                .map(|(id, host)| {
+                    (
+                        id.as_str().to_string(),
+                        ProviderResult::Host(Box::new(host.clone())),
+                    )
+                })
                 .collect();
-            println!(
-                "{},{},{},{},\"{}\"",
-                host.ip_str,
-                host.org.as_deref().unwrap_or(""),
-                host.asn.as_deref().unwrap_or(""),
-                host.location.country_code.as_deref().unwrap_or(""),
-                ports.join(";")
-            );
+            for (name, e) in &failures {
+                providers.insert(
+                    name.clone(),
+                    ProviderResult::Error {
+                        error: failure_reason(e),
+                    },
+                );
+            }
+
+            let report = AllReport {
+                merged: merged.as_ref(),
+                complete: failures.is_empty(),
+                providers,
+            };
+            if ctx.output_format == OutputFormat::Json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                println!("{}", serde_yaml::to_string(&report)?);
+            }
+        }
+        OutputFormat::Csv => {
+            println!("provider,ip,org,asn,country,ports,error");
+            for (id, host) in &hosts {
+                let ports: Vec<String> = host.ports.iter().map(ToString::to_string).collect();
+                println!(
+                    "{},{},{},{},{},\"{}\",",
+                    id.as_str(),
+                    host.ip_str,
+                    host.org.as_deref().unwrap_or(""),
+                    host.asn.as_deref().unwrap_or(""),
+                    host.location.country_code.as_deref().unwrap_or(""),
+                    ports.join(";")
+                );
+            }
+            for (name, e) in &failures {
+                println!("{name},{ip},,,,,\"{}\"", failure_reason(e));
+            }
         }
         OutputFormat::Pretty => {
-            print_host_pretty(&host, &ctx);
+            match &merged {
+                Some(_) if compare => print_comparison(ip, &hosts, &ctx),
+                Some(merged) => print_merged_pretty(merged, &ctx),
+                // The error says it all
+                None => return Err(no_answer(ip, failures)),
+            }
+            print_partial(&failures, &ctx);
         }
     }
 
+    if merged.is_none() {
+        return Err(no_answer(ip, failures));
+    }
+
     Ok(())
 }
 
-fn print_host_pretty(host: &HostInfo, ctx: &Context) {
-    // Header
+/// `i1 host <ip> --raw`: one provider's answer as it came, never cached.
+async fn execute_raw(
+    ctx: &Context,
+    provider: &(dyn i1_providers::HostLookup + Send + Sync),
+    ip: &str,
+) -> Result<()> {
+    let host = provider.lookup_host(ip).await?;
+    let raw = host.raw.ok_or_else(|| {
+        anyhow::anyhow!(
+            "{} doesn't keep raw responses. Try --provider shodan, censys or criminalip",
+            provider.display_name()
+        )
+    })?;
+    print_raw(ctx, &raw)
+}
+
+/// `--raw`: a provider's JSON as it came, or as YAML.
+fn print_raw(ctx: &Context, raw: &serde_json::Value) -> Result<()> {
+    if ctx.output_format == OutputFormat::Yaml {
+        println!("{}", serde_yaml::to_string(raw)?);
+    } else {
+        println!("{}", serde_json::to_string_pretty(raw)?);
+    }
+    Ok(())
+}
+
+/// One-line explanation of why a provider has no answer.
+fn failure_reason(e: &I1Error) -> String {
+    match e {
+        I1Error::Unauthorized => "invalid API key".to_string(),
+        I1Error::RateLimited { .. } => "rate limited".to_string(),
+        I1Error::InsufficientCredits { .. } => "out of query credits".to_string(),
+        I1Error::NotFound { .. } => "no data for this IP".to_string(),
+        I1Error::Timeout(_) => "timed out".to_string(),
+        I1Error::PolicyDenied { .. } => "skipped by policy".to_string(),
+        I1Error::ProviderNotConfigured(_) => "no API key configured".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// Every provider's failure, as one error.
+fn no_answer(ip: &str, failures: Vec<(String, I1Error)>) -> anyhow::Error {
+    anyhow::Error::new(I1Error::all_failed(failures))
+        .context(format!("No provider returned data for {ip}"))
+}
+
+/// Footer for an answer some providers had no part in:
+/// `⚠ censys: rate limited, criminalip: invalid API key`
+fn print_partial(failures: &[(String, I1Error)], ctx: &Context) {
+    if failures.is_empty() {
+        return;
+    }
+
+    let reasons: Vec<String> = failures
+        .iter()
+        .map(|(name, e)| format!("{name}: {}", failure_reason(e)))
+        .collect();
+    let footer = format!("⚠ {}", reasons.join(", "));
+    println!();
+    if ctx.no_color {
+        println!("{footer}");
+    } else {
+        println!("{}", footer.yellow());
+    }
+}
+
+fn print_merged_pretty(merged: &MergedHostInfo, ctx: &Context) {
+    if ctx.no_color {
+        println!("Host: {}", merged.ip);
+    } else {
+        println!("{} {}", "Host:".bold(), merged.ip.cyan().bold());
+    }
+    let sources: Vec<&str> = merged
+        .sources
+        .iter()
+        .map(ProviderId::display_name)
+        .collect();
+    println!("{}", format!("Sources: {}", sources.join(", ")).dimmed());
+    println!();
+
+    if let Some(org) = &merged.org {
+        println!("  {} {}", "Organization:".bold(), org);
+    }
+    if let Some(asn) = &merged.asn {
+        println!("  {} {}", "ASN:".bold(), asn);
+    }
+    if let Some(os) = &merged.os {
+        println!("  {} {}", "OS:".bold(), os);
+    }
+    if let Some(country) = &merged.country {
+        let location = merged
+            .city
+            .as_ref()
+            .map_or_else(|| country.clone(), |city| format!("{city}, {country}"));
+        println!("  {} {}", "Location:".bold(), location);
+    }
+    if !merged.hostnames.is_empty() {
+        println!("  {} {}", "Hostnames:".bold(), merged.hostnames.join(", "));
+    }
+
+    if !merged.ports.is_empty() {
+        println!();
+        println!("{}", "Open Ports:".bold().underline());
+
+        let rows: Vec<PortRow> = merged
+            .ports
+            .iter()
+            .map(|p| PortRow {
+                port: p.port,
+                transport: p.protocol.clone(),
+                product: p.product.clone().unwrap_or_default(),
+                version: p.version.clone().unwrap_or_default(),
+            })
+            .collect();
+
+        println!("{}", terminal::table(&rows));
+    }
+
+    println!();
+    if merged.vulns.is_empty() {
+        if ctx.no_color {
+            println!("Vulnerabilities: None detected");
+        } else {
+            println!("{} {}", "Vulnerabilities:".bold(), "None detected".green());
+        }
+    } else {
+        if ctx.no_color {
+            println!("Vulnerabilities:");
+        } else {
+            println!("{}", "Vulnerabilities:".bold().red());
+        }
+        for vuln in &merged.vulns {
+            println!("  - {vuln}");
+        }
+    }
+
+    if let Some(seen) = merged.last_seen {
+        println!();
+        println!(
+            "{}",
+            format!("Last seen: {}", seen.format("%Y-%m-%d %H:%M UTC")).dimmed()
+        );
+    }
+}
+
+/// Extracts one comparable field from a provider's answer.
+type FieldFn = fn(&HostInfo) -> String;
+
+/// Side-by-side table of each provider's answer, highlighting disagreements.
+fn print_comparison(ip: &str, hosts: &[(ProviderId, HostInfo)], ctx: &Context) {
     if ctx.no_color {
-        println!("Host: {}", host.ip_str);
+        println!("Host: {ip}");
     } else {
-        println!("{} {}", "Host:".bold(), host.ip_str.cyan().bold());
+        println!("{} {}", "Host:".bold(), ip.cyan().bold());
     }
     println!();
 
+    let fields: [(&str, FieldFn); 10] = [
+        ("Organization", |h| h.org.clone().unwrap_or_default()),
+        ("ASN", |h| h.asn.clone().unwrap_or_default()),
+        ("ISP", |h| h.isp.clone().unwrap_or_default()),
+        ("OS", |h| h.os.clone().unwrap_or_default()),
+        ("Country", |h| {
+            h.location.country_name.clone().unwrap_or_default()
+        }),
+        ("City", |h| h.location.city.clone().unwrap_or_default()),
+        ("Ports", |h| {
+            let mut ports = h.ports.clone();
+            ports.sort_unstable();
+            ports.dedup();
+            ports
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        }),
+        ("Hostnames", |h| sorted_list(&h.hostnames)),
+        ("Vulns", |h| sorted_list(&h.vulns)),
+        ("Last update", |h| h.last_update.clone().unwrap_or_default()),
+    ];
+
+    let header = std::iter::once("Field".to_string())
+        .chain(hosts.iter().map(|(id, _)| id.display_name().to_string()))
+        .collect();
+    let mut rows = Vec::new();
+
+    let mut disagreements = 0;
+    for (label, extract) in fields {
+        let values: Vec<String> = hosts.iter().map(|(_, h)| extract(h)).collect();
+
+        // Blank values are missing data, not a disagreement
+        let mut distinct: Vec<&String> = values.iter().filter(|v| !v.is_empty()).collect();
+        distinct.sort();
+        distinct.dedup();
+        let differs = distinct.len() > 1;
+        if differs {
+            disagreements += 1;
+        }
+
+        let mut record = vec![highlight(label, differs, ctx)];
+        record.extend(values.iter().map(|v| {
+            // Cut before highlighting, so a long value keeps its color
+            let v = terminal::current().cell(v);
+            if v.is_empty() {
+                "-".to_string()
+            } else {
+                highlight(&v, differs, ctx)
+            }
+        }));
+        rows.push(record);
+    }
+
+    println!("{}", terminal::records(header, rows));
+
+    if disagreements > 0 {
+        let note = format!("{disagreements} field(s) differ between providers");
+        if ctx.no_color {
+            println!("{note} (marked *)");
+        } else {
+            println!("{}", note.yellow());
+        }
+    }
+}
+
+fn highlight(value: &str, differs: bool, ctx: &Context) -> String {
+    match (differs, ctx.no_color) {
+        (false, _) => value.to_string(),
+        (true, true) => format!("{value} *"),
+        (true, false) => value.yellow().to_string(),
+    }
+}
+
+fn sorted_list(values: &[String]) -> String {
+    let mut values = values.to_vec();
+    values.sort();
+    values.dedup();
+    values.join(", ")
+}
+
+/// The single-host view. `risk` is scored before `--min-severity` hid
+/// `hidden` of the host's CVEs.
+fn print_host_pretty(
+    host: &HostInfo,
+    risk: f64,
+    hidden: usize,
+    cached_at: Option<DateTime<Utc>>,
+    ctx: &Context,
+) {
+    let color = severity::use_color(ctx.no_color);
+
+    println!("{}", header(host, risk, cached_at, color));
+    println!();
+
     // Basic info
     if let Some(org) = &host.org {
         println!("  {} {}", "Organization:".bold(), org);
@@ -125,14 +665,15 @@ fn print_host_pretty(host: &HostInfo, ctx: &Context) {
             }
         }
 
-        let table = Table::new(&rows).with(Style::rounded()).to_string();
-        println!("{table}");
+        println!("{}", terminal::table(&rows));
     }
 
-    // Vulnerabilities
+    // Vulnerabilities, most severe first
     if host.vulns.is_empty() {
         println!();
-        if ctx.no_color {
+        if hidden > 0 {
+            println!("Vulnerabilities: None at or above --min-severity");
+        } else if ctx.no_color {
             println!("Vulnerabilities: None detected");
         } else {
             println!("{} {}", "Vulnerabilities:".bold(), "None detected".green());
@@ -144,10 +685,16 @@ fn print_host_pretty(host: &HostInfo, ctx: &Context) {
         } else {
             println!("{}", "Vulnerabilities:".bold().red());
         }
-        for vuln in &host.vulns {
-            println!("  - {vuln}");
+        for line in severity::vuln_lines(host, color) {
+            println!("{line}");
         }
     }
+    if hidden > 0 {
+        println!(
+            "{}",
+            format!("  ({hidden} below --min-severity not shown)").dimmed()
+        );
+    }
 
     // Last update
     if let Some(update) = &host.last_update {
@@ -155,3 +702,594 @@ fn print_host_pretty(host: &HostInfo, ctx: &Context) {
         println!("{}", format!("Last updated: {update}").dimmed());
     }
 }
+
+/// `Host: 192.0.2.1 [HIGH 7.5] (cached 2h ago)`, the marker only for an
+/// answer that came from the cache.
+fn header(host: &HostInfo, risk: f64, cached_at: Option<DateTime<Utc>>, color: bool) -> String {
+    let cached = cached_at.map_or_else(String::new, |at| {
+        format!(" {}", format!("(cached {})", cache::ago(at)).dimmed())
+    });
+    if color {
+        format!(
+            "{} {} {}{cached}",
+            "Host:".bold(),
+            host.ip_str.cyan().bold(),
+            severity::badge(risk, true)
+        )
+    } else {
+        format!(
+            "Host: {} {}{cached}",
+            host.ip_str,
+            severity::badge(risk, false)
+        )
+    }
+}
+
+// ============================================================================
+// Batch lookups (stdin / --file)
+// ============================================================================
+
+/// Provider lookups in flight at once in batch mode
+const BATCH_CONCURRENCY: usize = 4;
+
+/// Hostnames resolved at once in batch mode
+const RESOLVE_CONCURRENCY: usize = 16;
+
+/// Largest CIDR block looked up without `--force`
+const CIDR_LIMIT: usize = 1024;
+
+/// Largest CIDR block looked up at all
+const CIDR_HARD_LIMIT: usize = 65_536;
+
+/// Columns shown for batch lookups when `--fields` is not given
+const DEFAULT_BATCH_FIELDS: &[Field] = &[
+    Field::Ip,
+    Field::Org,
+    Field::Asn,
+    Field::Country,
+    Field::Ports,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum LookupStatus {
+    Ok,
+    NotFound,
+    Error,
+}
+
+/// The outcome for one input line.
+#[derive(Debug)]
+struct TargetRecord {
+    target: String,
+    ip: Option<String>,
+    status: LookupStatus,
+    host: Option<HostInfo>,
+    error: Option<String>,
+}
+
+impl TargetRecord {
+    /// A target that never got as far as a lookup
+    const fn unresolved(target: String, error: String) -> Self {
+        Self {
+            target,
+            ip: None,
+            status: LookupStatus::Error,
+            host: None,
+            error: Some(error),
+        }
+    }
+
+    /// A target looked up as `ip`
+    fn looked_up(target: String, ip: String, result: i1::Result<HostInfo>) -> Self {
+        let (status, host, error) = match result {
+            Ok(host) => (LookupStatus::Ok, Some(host), None),
+            Err(I1Error::NotFound { .. }) => (LookupStatus::NotFound, None, None),
+            Err(e) => (LookupStatus::Error, None, Some(failure_reason(&e))),
+        };
+        Self {
+            target,
+            ip: Some(ip),
+            status,
+            host,
+            error,
+        }
+    }
+
+    fn to_json(&self, fields: Option<&[Field]>) -> Result<serde_json::Value> {
+        let mut value = serde_json::json!({
+            "target": self.target,
+            "status": self.status,
+        });
+
+        if let Some(ip) = &self.ip {
+            value["ip"] = ip.clone().into();
+        }
+        if let Some(host) = &self.host {
+            value["host"] = match fields {
+                Some(fields) => fields::project(host, fields),
+                None => serde_json::to_value(host)?,
+            };
+        }
+        if let Some(error) = &self.error {
+            value["error"] = error.clone().into();
+        }
+
+        Ok(value)
+    }
+}
+
+/// Split input into targets, skipping blanks and `#` comments, first occurrence wins.
+pub(super) fn parse_targets(input: &str) -> Vec<String> {
+    let mut seen = std::collections::HashSet::new();
+    input
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|line| !line.is_empty())
+        .filter(|line| seen.insert(line.to_lowercase()))
+        .map(String::from)
+        .collect()
+}
+
+/// Turn a target into an IP, resolving hostnames.
+async fn resolve(target: &str) -> std::result::Result<String, String> {
+    if let Ok(ip) = target.parse::<std::net::IpAddr>() {
+        return Ok(ip.to_string());
+    }
+
+    tokio::net::lookup_host((target, 0))
+        .await
+        .map_err(|e| format!("could not resolve: {e}"))?
+        .next()
+        .map(|addr| addr.ip().to_string())
+        .ok_or_else(|| "did not resolve to any address".to_string())
+}
+
+/// `--dry-run`: the lookups a batch would make and what they'd cost.
+/// Hostnames are resolved locally, and lines that are neither an IP nor a
+/// hostname fail without a lookup.
+async fn dry_run(ctx: &Context, targets: &[String], input: String) -> Result<()> {
+    if ctx.provider == "all" {
+        return Err(ctx.one_provider("Batch and CIDR lookups", &HOST_PROVIDERS));
+    }
+    let provider = ctx.host_provider()?;
+
+    let lookups = targets
+        .iter()
+        .filter(|target| matches!(Target::parse(target), Ok(Target::Ip(_) | Target::Domain(_))))
+        .count() as u64;
+    let mut plan = Preflight::new("host", provider.name(), input);
+    plan.calls = lookups;
+    plan.credits = lookups * u64::from(i1::observe::lookup_credits(provider.name()));
+    let skipped = targets.len() as u64 - lookups;
+    if skipped > 0 {
+        plan.error = Some(format!(
+            "{skipped} targets are not IPs or hostnames and would fail without a lookup"
+        ));
+    }
+
+    preflight::report(ctx, provider.as_ref(), plan).await
+}
+
+/// Look up every target with bounded concurrency, one record per target.
+async fn lookup_batch(ctx: &Context, targets: Vec<String>) -> Result<Vec<TargetRecord>> {
+    if ctx.provider == "all" {
+        return Err(ctx.one_provider("Batch and CIDR lookups", &HOST_PROVIDERS));
+    }
+    let client = ctx.client()?;
+
+    let progress = (ctx.output_format == OutputFormat::Pretty && !ctx.quiet).then(|| {
+        let pb = indicatif::ProgressBar::new(targets.len() as u64);
+        if let Ok(style) = indicatif::ProgressStyle::with_template(
+            "{spinner} [{bar:40.cyan/blue}] {pos}/{len} targets",
+        ) {
+            pb.set_style(style.progress_chars("=> "));
+        }
+        pb
+    });
+
+    let resolved: Vec<(String, std::result::Result<String, String>)> =
+        futures_util::stream::iter(targets)
+            .map(|target| async move {
+                let ip = resolve(&target).await;
+                (target, ip)
+            })
+            .buffered(RESOLVE_CONCURRENCY)
+            .collect()
+            .await;
+
+    // Several targets can resolve to the same address; look each IP up once
+    let mut records = Vec::with_capacity(resolved.len());
+    let mut by_ip: HashMap<String, Vec<usize>> = HashMap::new();
+    let mut ips = Vec::new();
+    for (target, ip) in resolved {
+        match ip {
+            Ok(ip) => {
+                let indices = by_ip.entry(ip.clone()).or_insert_with(|| {
+                    ips.push(ip.clone());
+                    Vec::new()
+                });
+                indices.push(records.len());
+                records.push(TargetRecord {
+                    target,
+                    ip: Some(ip),
+                    status: LookupStatus::Ok,
+                    host: None,
+                    error: None,
+                });
+            }
+            Err(e) => {
+                if let Some(pb) = &progress {
+                    pb.inc(1);
+                }
+                records.push(TargetRecord::unresolved(target, e));
+            }
+        }
+    }
+
+    // Answer what we can from the cache, look up the rest
+    let cache = Cache::open(ctx.cache);
+    let provider = client.default_provider_name().unwrap_or("default");
+    let mut answered: Vec<(String, i1::Result<HostInfo>)> = Vec::new();
+    ips.retain(|ip| match cache.cached_host(provider, ip) {
+        Some(cached) => {
+            answered.push((ip.clone(), Ok(cached.value)));
+            false
+        }
+        None => true,
+    });
+
+    let mut lookups = futures_util::stream::iter(answered).chain(
+        client
+            .lookup_host_batch(ips, BATCH_CONCURRENCY)
+            .map(|(ip, result)| {
+                if let Ok(host) = &result {
+                    cache.store_host(provider, host);
+                }
+                (ip, result)
+            }),
+    );
+    while let Some((ip, result)) = lookups.next().await {
+        for &i in by_ip.get(&ip).into_iter().flatten() {
+            let record = &mut records[i];
+            match &result {
+                Ok(host) => record.host = Some(host.clone()),
+                Err(I1Error::NotFound { .. }) => record.status = LookupStatus::NotFound,
+                Err(e) => {
+                    record.status = LookupStatus::Error;
+                    record.error = Some(failure_reason(e));
+                }
+            }
+            if let Some(pb) = &progress {
+                pb.inc(1);
+            }
+        }
+    }
+
+    if let Some(pb) = &progress {
+        pb.finish_and_clear();
+    }
+
+    Ok(records)
+}
+
+/// `i1 host -` / `i1 host --file`: one record per input line.
+async fn execute_batch(ctx: &Context, targets: Vec<String>, args: &HostArgs) -> Result<()> {
+    if targets.is_empty() {
+        return Err(I1Error::InvalidQuery(
+            "No targets given. Provide one IP or hostname per line.".to_string(),
+        )
+        .into());
+    }
+
+    let records = lookup_batch(ctx, targets).await?;
+    print_batch(ctx, records, args)
+}
+
+/// `i1 host example.com`: look up every address the domain resolves to, as
+/// a batch with one record per address.
+async fn execute_domain(ctx: &Context, domain: String, args: &HostArgs) -> Result<()> {
+    if ctx.provider == "all" {
+        return Err(ctx.one_provider("Domain lookups", &HOST_PROVIDERS));
+    }
+    let client = ctx.client()?;
+    let Investigation::Domain {
+        domain,
+        info,
+        hosts,
+    } = client.investigate(Target::Domain(domain)).await?
+    else {
+        unreachable!("a domain is investigated as a domain");
+    };
+
+    if let Some(Ok(info)) = info {
+        if ctx.output_format == OutputFormat::Pretty && !ctx.quiet {
+            let note = format!(
+                "{domain}: {} subdomains, {} DNS records (see i1 domain {domain})",
+                info.subdomains.len(),
+                info.records.len()
+            );
+            println!("{}", note.dimmed());
+        }
+    }
+
+    let provider = client.default_provider_name().unwrap_or("default");
+    let records = hosts
+        .into_iter()
+        .map(|(ip, result)| {
+            if let Ok(host) = &result {
+                workspace::keep_host(Kind::Host, provider, host);
+            }
+            TargetRecord::looked_up(domain.clone(), ip, result)
+        })
+        .collect();
+    print_batch(ctx, records, args)
+}
+
+/// Print batch records, then the summary, and exit as the batch went.
+fn print_batch(ctx: &Context, mut records: Vec<TargetRecord>, args: &HostArgs) -> Result<()> {
+    let columns = &args.columns;
+    filter_severity(&mut records, args.min_severity);
+    let unmatched = filter_records(&mut records, columns.filter.as_ref());
+    sort_records(&mut records, columns);
+    if !print_template(&records, args) {
+        print_records(&records, ctx, columns)?;
+    }
+
+    let count = |status| records.iter().filter(|r| r.status == status).count();
+    let (ok, errors) = (count(LookupStatus::Ok), count(LookupStatus::Error));
+    if !ctx.quiet {
+        print_summary(ok, count(LookupStatus::NotFound), errors, unmatched);
+    }
+
+    nothing_found(ok, errors)
+}
+
+/// `i1 host 192.0.2.0/28`: look up every address in a block.
+///
+/// Most addresses in a block have no data, so not-found results are only
+/// counted, and JSON/YAML carry the found hosts plus a metadata object.
+async fn execute_cidr(ctx: &Context, net: IpNet, args: &HostArgs) -> Result<()> {
+    let (force, columns) = (args.force, &args.columns);
+    let cap = if force { CIDR_HARD_LIMIT } else { CIDR_LIMIT };
+    let targets: Vec<String> = net.hosts().take(cap + 1).map(|ip| ip.to_string()).collect();
+
+    if targets.len() > cap {
+        let reason = if force {
+            format!(
+                "{net} has more than {CIDR_HARD_LIMIT} addresses. Split it into smaller blocks."
+            )
+        } else {
+            format!(
+                "{net} has more than {CIDR_LIMIT} addresses.\n\
+                 Re-run with --force to look them all up (each address is a separate lookup)."
+            )
+        };
+        return Err(I1Error::InvalidQuery(reason).into());
+    }
+
+    let addresses = targets.len();
+    let mut records = lookup_batch(ctx, targets).await?;
+    filter_severity(&mut records, args.min_severity);
+    let unmatched = filter_records(&mut records, columns.filter.as_ref());
+    let found = records
+        .iter()
+        .filter(|r| r.status == LookupStatus::Ok)
+        .count();
+    let errors = records
+        .iter()
+        .filter(|r| r.status == LookupStatus::Error)
+        .count();
+    let quiet = addresses - found - errors - unmatched;
+
+    records.retain(|r| r.status != LookupStatus::NotFound);
+    sort_records(&mut records, columns);
+
+    match ctx.output_format {
+        _ if print_template(&records, args) => {}
+        _ if ctx.stix => {
+            let found: Vec<HostInfo> = records.iter().filter_map(|r| r.host.clone()).collect();
+            print_stix(&found)?;
+        }
+        OutputFormat::Json | OutputFormat::Yaml => {
+            let projection = (!columns.fields.is_empty()).then_some(columns.fields.as_slice());
+            let hosts = records
+                .iter()
+                .filter(|r| r.status == LookupStatus::Ok)
+                .map(|r| r.to_json(projection))
+                .collect::<Result<Vec<_>>>()?;
+            let failed: BTreeMap<&str, &str> = records
+                .iter()
+                .filter_map(|r| Some((r.target.as_str(), r.error.as_deref()?)))
+                .collect();
+
+            let value = serde_json::json!({
+                "metadata": {
+                    "cidr": net.to_string(),
+                    "addresses": addresses,
+                    "found": found,
+                    "not_found": quiet,
+                    "not_matching_filter": unmatched,
+                    "errors": failed,
+                },
+                "hosts": hosts,
+            });
+
+            if ctx.output_format == OutputFormat::Json {
+                println!("{}", serde_json::to_string_pretty(&value)?);
+            } else {
+                println!("{}", serde_yaml::to_string(&value)?);
+            }
+        }
+        OutputFormat::Csv => print_records(&records, ctx, columns)?,
+        OutputFormat::Pretty => {
+            if !records.is_empty() {
+                print_records(&records, ctx, columns)?;
+            }
+            if !ctx.quiet {
+                println!(
+                    "{}",
+                    format!("{quiet} of {addresses} addresses had no data").dimmed()
+                );
+            }
+        }
+    }
+
+    if !ctx.quiet {
+        print_summary(found, quiet, errors, unmatched);
+    }
+
+    nothing_found(found, errors)
+}
+
+/// Exit with the not-found code when no target had data and none failed.
+fn nothing_found(found: usize, errors: usize) -> Result<()> {
+    if found == 0 && errors == 0 {
+        return Err(Exit(exit::NOT_FOUND).into());
+    }
+    Ok(())
+}
+
+/// `--min-severity` for batch lookups: prune each found host's CVEs.
+fn filter_severity(records: &mut [TargetRecord], min: Option<Severity>) {
+    for host in records.iter_mut().filter_map(|r| r.host.as_mut()) {
+        severity::retain(host, min);
+    }
+}
+
+/// `--filter` for batch lookups: drop the found hosts it is false for, and
+/// say how many.
+fn filter_records(records: &mut Vec<TargetRecord>, filter: Option<&Filter>) -> usize {
+    let before = records.len();
+    if let Some(filter) = filter {
+        records.retain(|r| r.host.as_ref().map_or(true, |host| filter.matches(host)));
+    }
+    before - records.len()
+}
+
+/// `--format-template` for batch lookups: a line per found host, nothing for
+/// the rest. Returns false when no template was given.
+fn print_template(records: &[TargetRecord], args: &HostArgs) -> bool {
+    let Some(template) = &args.template.format_template else {
+        return false;
+    };
+    let hosts = records.iter().filter_map(|r| r.host.as_ref());
+    template::print(hosts, template, &args.template.list_sep());
+    true
+}
+
+/// Hosts with data first, sorted by `--sort`; the rest keep input order
+fn sort_records(records: &mut [TargetRecord], columns: &ColumnArgs) {
+    let Some(sort) = columns.sort else {
+        return;
+    };
+
+    records.sort_by(|a, b| match (&a.host, &b.host) {
+        (Some(x), Some(y)) if columns.desc => sort.compare(y, x),
+        (Some(x), Some(y)) => sort.compare(x, y),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => std::cmp::Ordering::Equal,
+    });
+}
+
+/// Batch summary, on stderr so it never ends up in piped output
+fn print_summary(ok: usize, not_found: usize, errors: usize, unmatched: usize) {
+    if unmatched > 0 {
+        eprintln!(
+            "{ok} ok, {not_found} not found, {errors} errors, {unmatched} not matching --filter"
+        );
+    } else {
+        eprintln!("{ok} ok, {not_found} not found, {errors} errors");
+    }
+}
+
+fn print_records(records: &[TargetRecord], ctx: &Context, columns: &ColumnArgs) -> Result<()> {
+    let projection = (!columns.fields.is_empty()).then_some(columns.fields.as_slice());
+    let selected = projection.unwrap_or(DEFAULT_BATCH_FIELDS);
+
+    match ctx.output_format {
+        _ if ctx.stix => {
+            let found: Vec<HostInfo> = records.iter().filter_map(|r| r.host.clone()).collect();
+            print_stix(&found)?;
+        }
+        // NDJSON: one object per line so the output can be streamed into jq
+        OutputFormat::Json => {
+            for record in records {
+                println!("{}", serde_json::to_string(&record.to_json(projection)?)?);
+            }
+        }
+        OutputFormat::Yaml => {
+            let values = records
+                .iter()
+                .map(|r| r.to_json(projection))
+                .collect::<Result<Vec<_>>>()?;
+            println!("{}", serde_yaml::to_string(&values)?);
+        }
+        OutputFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(std::io::stdout().lock());
+            writer.write_record(
+                ["target", "status"]
+                    .into_iter()
+                    .chain(selected.iter().map(|f| f.name()))
+                    .chain(["error"]),
+            )?;
+            for record in records {
+                let mut row = vec![record.target.clone(), status_text(record.status).into()];
+                row.extend(selected.iter().map(|f| {
+                    record
+                        .host
+                        .as_ref()
+                        .map(|h| f.text(h, ";"))
+                        .unwrap_or_default()
+                }));
+                row.push(record.error.clone().unwrap_or_default());
+                writer.write_record(row)?;
+            }
+            writer.flush()?;
+        }
+        OutputFormat::Pretty => {
+            let header = ["Target", "Status"]
+                .into_iter()
+                .chain(selected.iter().map(|f| f.header()))
+                .map(String::from)
+                .collect();
+            let mut rows = Vec::new();
+            for record in records {
+                let mut row = vec![record.target.clone(), status_cell(record, ctx)];
+                match &record.host {
+                    Some(host) => row.extend(fields::cells(host, selected)),
+                    None => row.extend(selected.iter().map(|_| String::new())),
+                }
+                rows.push(row);
+            }
+            println!("{}", terminal::records(header, rows));
+        }
+    }
+
+    Ok(())
+}
+
+const fn status_text(status: LookupStatus) -> &'static str {
+    match status {
+        LookupStatus::Ok => "ok",
+        LookupStatus::NotFound => "not found",
+        LookupStatus::Error => "error",
+    }
+}
+
+fn status_cell(record: &TargetRecord, ctx: &Context) -> String {
+    let text = record.error.as_ref().map_or_else(
+        || status_text(record.status).to_string(),
+        |error| format!("error: {error}"),
+    );
+
+    if ctx.no_color {
+        return text;
+    }
+    match record.status {
+        LookupStatus::Ok => text.green().to_string(),
+        LookupStatus::NotFound => text.dimmed().to_string(),
+        LookupStatus::Error => text.red().to_string(),
+    }
+}
@@ -0,0 +1,155 @@
+//! `i1 ingest` - Targets from a packet capture or Zeek conn.log.
+//!
+//! Lists the external endpoints in the file with their connection counts
+//! and ports (see [`i1::recon::ingest`]). `--enrich` looks each one up with
+//! the configured provider, so a capture turns into the same table as
+//! `i1 host --file`.
+
+use anyhow::{Context as _, Result};
+use colored::Colorize;
+use futures_util::StreamExt;
+use serde::Serialize;
+
+use super::Context;
+use crate::cli::args::IngestArgs;
+use crate::cli::exit::{self, Exit};
+use crate::output::fields::{self, Field};
+use crate::output::{terminal, OutputFormat};
+use i1::recon::ingest::{self, Endpoint};
+use i1::{HostInfo, I1Error};
+
+/// Provider lookups in flight at once with `--enrich`
+const ENRICH_CONCURRENCY: usize = 4;
+
+/// Host columns added by `--enrich`
+const ENRICH_FIELDS: &[Field] = &[Field::Org, Field::Asn, Field::Country, Field::Vulns];
+
+/// One target, with the provider's data when enriched.
+#[derive(Serialize)]
+struct Target {
+    #[serde(flatten)]
+    endpoint: Endpoint,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    host: Option<HostInfo>,
+}
+
+pub async fn execute(ctx: &Context, args: IngestArgs) -> Result<()> {
+    let ingested = ingest::read_file(&args.file)
+        .map_err(I1Error::from)
+        .with_context(|| format!("Could not read {}", args.file.display()))?;
+    for warning in &ingested.warnings {
+        eprintln!("{} {}: {warning}", "Warning:".yellow(), args.file.display());
+    }
+
+    let endpoints: Vec<Endpoint> = ingested
+        .endpoints
+        .into_iter()
+        .filter(|endpoint| endpoint.connections >= args.min_connections)
+        .collect();
+    if !ctx.quiet && ctx.output_format == OutputFormat::Pretty {
+        println!(
+            "{} external endpoints in {} connections",
+            endpoints.len().to_string().bold(),
+            ingested.connections
+        );
+    }
+    if endpoints.is_empty() {
+        return Err(Exit(exit::NOT_FOUND).into());
+    }
+
+    let targets = if args.enrich {
+        enrich(ctx, endpoints).await?
+    } else {
+        endpoints
+            .into_iter()
+            .map(|endpoint| Target {
+                endpoint,
+                host: None,
+            })
+            .collect()
+    };
+    print_targets(ctx, &targets, args.enrich)
+}
+
+/// Look every endpoint up with the configured provider. A failed lookup
+/// leaves the endpoint without host data rather than failing the command.
+async fn enrich(ctx: &Context, endpoints: Vec<Endpoint>) -> Result<Vec<Target>> {
+    let provider = ctx.host_provider()?;
+    let provider = &provider;
+    Ok(futures_util::stream::iter(endpoints)
+        .map(|endpoint| async move {
+            let host = provider.lookup_host(&endpoint.ip.to_string()).await.ok();
+            Target { endpoint, host }
+        })
+        .buffered(ENRICH_CONCURRENCY)
+        .collect()
+        .await)
+}
+
+fn print_targets(ctx: &Context, targets: &[Target], enriched: bool) -> Result<()> {
+    let host_fields = if enriched { ENRICH_FIELDS } else { &[] };
+
+    match ctx.output_format {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(targets)?);
+        }
+        OutputFormat::Yaml => println!("{}", serde_yaml::to_string(targets)?),
+        OutputFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(std::io::stdout().lock());
+            writer.write_record(
+                ["ip", "connections", "ports"]
+                    .into_iter()
+                    .chain(host_fields.iter().map(|f| f.name())),
+            )?;
+            for target in targets {
+                let mut row = vec![
+                    target.endpoint.ip.to_string(),
+                    target.endpoint.connections.to_string(),
+                    ports(&target.endpoint, ";"),
+                ];
+                row.extend(host_fields.iter().map(|f| {
+                    target
+                        .host
+                        .as_ref()
+                        .map(|h| f.text(h, ";"))
+                        .unwrap_or_default()
+                }));
+                writer.write_record(row)?;
+            }
+            writer.flush()?;
+        }
+        OutputFormat::Pretty => {
+            let header = ["IP", "Connections", "Ports"]
+                .into_iter()
+                .chain(host_fields.iter().map(|f| f.header()))
+                .map(String::from)
+                .collect();
+            let rows = targets
+                .iter()
+                .map(|target| {
+                    let mut row = vec![
+                        target.endpoint.ip.to_string(),
+                        target.endpoint.connections.to_string(),
+                        ports(&target.endpoint, ", "),
+                    ];
+                    match &target.host {
+                        Some(host) => row.extend(fields::cells(host, host_fields)),
+                        None => row.extend(host_fields.iter().map(|_| String::new())),
+                    }
+                    row
+                })
+                .collect();
+            println!("{}", terminal::records(header, rows));
+        }
+    }
+    Ok(())
+}
+
+fn ports(endpoint: &Endpoint, sep: &str) -> String {
+    endpoint
+        .ports
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(sep)
+}
@@ -1,19 +1,56 @@
 //! Command implementations.
 
+pub mod alert;
+pub mod audit;
+pub mod cache;
+pub mod ca;
 pub mod config;
+pub mod credits;
 pub mod count;
 pub mod defend;
 pub mod dns;
+pub mod domain;
+pub mod honeypot;
 pub mod host;
+#[cfg(feature = "ingest")]
+pub mod ingest;
 pub mod myip;
+#[cfg(feature = "scanner")]
+pub mod portscan;
+pub mod preflight;
 pub mod scan;
 pub mod search;
 pub mod threat;
+pub mod vuln;
+pub mod watch;
+pub mod workspace;
 
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use colored::Colorize;
+
+use crate::config::Source;
 use crate::output::OutputFormat;
 
+/// Providers that implement vulnerability lookups
+const VULN_PROVIDERS: &[&str] = &["shodan"];
+
+/// Providers that implement host lookups and search
+const HOST_PROVIDERS: [&str; 4] = ["shodan", "censys", "criminalip", "native"];
+
+/// Every provider i1 knows, and whether this build has it.
+const KNOWN_PROVIDERS: &[(&str, bool)] = &[
+    ("shodan", cfg!(feature = "shodan")),
+    ("censys", cfg!(feature = "censys")),
+    ("criminalip", cfg!(feature = "criminalip")),
+    ("native", cfg!(feature = "native")),
+];
+
 /// Shared context for all commands.
 #[derive(Debug, Clone)]
+#[allow(clippy::struct_excessive_bools)] // the global switches, resolved
 pub struct Context {
     /// Shodan API key
     pub shodan_key: Option<String>,
@@ -27,12 +64,31 @@ pub struct Context {
     /// Criminal IP API key
     pub criminalip_key: Option<String>,
 
-    /// Which provider to use (auto, shodan, censys, criminalip)
+    /// i1.is API token
+    pub token: Option<String>,
+
+    /// Shodan API endpoint, when not Shodan's own
+    pub shodan_url: Option<String>,
+
+    /// Censys API endpoint, when not Censys's own
+    pub censys_url: Option<String>,
+
+    /// Criminal IP API endpoint, when not Criminal IP's own
+    pub criminalip_url: Option<String>,
+
+    /// Which provider to use (auto, all, shodan, censys, criminalip, native)
     pub provider: String,
 
-    /// Output format
+    /// Output format; JSON for `-o stix`
     pub output_format: OutputFormat,
 
+    /// Print the hosts found as a STIX bundle (`-o stix`); only host, search
+    /// and scan run with it
+    pub stix: bool,
+
+    /// Active config profile
+    pub profile: Option<String>,
+
     /// Whether to show educational explanations
     pub explain: bool,
 
@@ -41,13 +97,49 @@ pub struct Context {
 
     /// Disable colors
     pub no_color: bool,
+
+    /// Print only results, no decoration
+    pub quiet: bool,
+
+    /// How host and search use the answer cache
+    pub cache: crate::cache::Mode,
+
+    /// Keep providers' raw responses on the hosts they return (`host --raw`)
+    pub raw: bool,
+
+    /// Proxy settings every provider connects with
+    pub http: i1::ProviderHttpConfig,
+
+    /// Targets withheld from providers
+    pub policy: i1::QueryPolicy,
+
+    /// Where each setting came from (for `config show`)
+    pub sources: BTreeMap<&'static str, Source>,
+
+    /// Provider clients built so far
+    pub clients: Clients,
+}
+
+/// Provider clients shared by every command run in this process, so
+/// `i1 shell` keeps one rate limiter and connection pool per provider.
+#[derive(Clone, Default)]
+pub struct Clients {
+    /// The Shodan client and the key it was built with
+    shodan: Arc<Mutex<Option<(String, i1::ShodanProvider)>>>,
+}
+
+impl std::fmt::Debug for Clients {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Clients").finish_non_exhaustive()
+    }
 }
 
 impl Context {
     /// Get the Shodan API key, returning an error if not set.
     pub fn require_shodan_key(&self) -> anyhow::Result<&str> {
         self.shodan_key.as_deref().ok_or_else(|| {
-            anyhow::anyhow!(
+            not_configured(
+                Some("shodan"),
                 "Shodan API key required.\n\n\
                  Set it with one of:\n  \
                  1. --api-key <KEY>\n  \
@@ -59,54 +151,124 @@ impl Context {
     }
 
     /// Create a Shodan provider with the configured API key.
+    ///
+    /// `shodan_url` points it at another endpoint, such as a proxy or a
+    /// mock server. Every provider made for the key shares one rate limiter,
+    /// so a command using several stays under Shodan's limit.
     pub fn shodan_provider(&self) -> anyhow::Result<i1::ShodanProvider> {
         let key = self.require_shodan_key()?;
-        Ok(i1::ShodanProvider::new(key))
+        let mut shodan = self
+            .clients
+            .shodan
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        match shodan.as_ref() {
+            Some((built_with, provider)) if built_with == key => {
+                Ok(provider.clone().include_raw(self.raw))
+            }
+            _ => {
+                let provider = i1::ShodanProvider::new_shared(key).with_http_config(&self.http)?;
+                let provider = match &self.shodan_url {
+                    Some(url) => provider.with_base_url(url),
+                    None => provider,
+                };
+                *shodan = Some((key.to_string(), provider.clone()));
+                drop(shodan);
+                Ok(provider.include_raw(self.raw))
+            }
+        }
+    }
+
+    /// Create a Censys provider with these credentials.
+    #[cfg(feature = "censys")]
+    fn censys_provider(&self, id: &str, secret: &str) -> anyhow::Result<i1::CensysProvider> {
+        let provider = i1::CensysProvider::new_shared(id, secret).with_http_config(&self.http)?;
+        let provider = match &self.censys_url {
+            Some(url) => provider.with_base_url(url),
+            None => provider,
+        };
+        Ok(provider.include_raw(self.raw))
+    }
+
+    /// Create a Criminal IP provider with this key.
+    #[cfg(feature = "criminalip")]
+    fn criminalip_provider(&self, key: &str) -> anyhow::Result<i1::CriminalIpProvider> {
+        let provider = i1::CriminalIpProvider::new_shared(key).with_http_config(&self.http)?;
+        let provider = match &self.criminalip_url {
+            Some(url) => provider.with_base_url(url),
+            None => provider,
+        };
+        Ok(provider.include_raw(self.raw))
+    }
+
+    /// Create the i1.is provider, authenticated when a token is configured.
+    #[cfg(feature = "native")]
+    pub fn native_provider(&self) -> anyhow::Result<i1::NativeProvider> {
+        let provider = self
+            .token
+            .as_deref()
+            .map_or_else(i1::NativeProvider::anonymous, i1::NativeProvider::new);
+        Ok(provider.with_http_config(&self.http)?)
     }
 
     /// Get the best available provider for host lookups, based on --provider flag
-    /// or auto-detecting from configured keys.
+    /// or auto-detecting from configured keys. Lookups of IPs the policy
+    /// withholds from it fail before anything is sent.
     pub fn host_provider(
         &self,
+    ) -> anyhow::Result<Box<dyn i1_providers::HostLookup + Send + Sync>> {
+        let mut provider = self.pick_host_provider()?;
+        for log in call_logs() {
+            provider = Box::new(i1::Observed::new(provider, log));
+        }
+        Ok(Box::new(i1::Guarded::new(provider, self.policy.clone())))
+    }
+
+    fn pick_host_provider(
+        &self,
     ) -> anyhow::Result<Box<dyn i1_providers::HostLookup + Send + Sync>> {
         match self.provider.as_str() {
             "shodan" => Ok(Box::new(self.shodan_provider()?)),
             #[cfg(feature = "censys")]
             "censys" => {
                 let id = self.censys_id.as_deref().ok_or_else(|| {
-                    anyhow::anyhow!("Censys API ID not configured. Set I1_CENSYS_ID or i1 config set censys-id <ID>")
+                    self.missing("censys", "Censys API ID not configured. Set I1_CENSYS_ID or i1 config set censys-id <ID>")
                 })?;
                 let secret = self.censys_secret.as_deref().ok_or_else(|| {
-                    anyhow::anyhow!("Censys API secret not configured. Set I1_CENSYS_SECRET or i1 config set censys-secret <SECRET>")
+                    self.missing("censys", "Censys API secret not configured. Set I1_CENSYS_SECRET or i1 config set censys-secret <SECRET>")
                 })?;
-                Ok(Box::new(i1::CensysProvider::new(id, secret)))
+                Ok(Box::new(self.censys_provider(id, secret)?))
             }
             #[cfg(feature = "criminalip")]
             "criminalip" => {
                 let key = self.criminalip_key.as_deref().ok_or_else(|| {
-                    anyhow::anyhow!("Criminal IP API key not configured. Set I1_CRIMINALIP_KEY or i1 config set criminalip-key <KEY>")
+                    self.missing("criminalip", "Criminal IP API key not configured. Set I1_CRIMINALIP_KEY or i1 config set criminalip-key <KEY>")
                 })?;
-                Ok(Box::new(i1::CriminalIpProvider::new(key)))
+                Ok(Box::new(self.criminalip_provider(key)?))
             }
-            // "auto" or anything else: pick first configured provider
+            #[cfg(feature = "native")]
+            "native" => Ok(Box::new(self.native_provider()?)),
+            "all" => Err(self.one_provider("These host lookups", &HOST_PROVIDERS)),
+            // "auto": pick first configured provider
             _ => {
                 if self.shodan_key.is_some() {
                     return Ok(Box::new(self.shodan_provider()?));
                 }
                 #[cfg(feature = "censys")]
                 if self.censys_id.is_some() && self.censys_secret.is_some() {
-                    return Ok(Box::new(i1::CensysProvider::new(
+                    return Ok(Box::new(self.censys_provider(
                         self.censys_id.as_deref().unwrap(),
                         self.censys_secret.as_deref().unwrap(),
-                    )));
+                    )?));
                 }
                 #[cfg(feature = "criminalip")]
                 if self.criminalip_key.is_some() {
-                    return Ok(Box::new(i1::CriminalIpProvider::new(
+                    return Ok(Box::new(self.criminalip_provider(
                         self.criminalip_key.as_deref().unwrap(),
-                    )));
+                    )?));
                 }
-                Err(anyhow::anyhow!(
+                Err(not_configured(
+                    None,
                     "No API key configured.\n\n\
                      Set one with:\n  \
                      1. i1 config set shodan-key <KEY>\n  \
@@ -121,44 +283,58 @@ impl Context {
     /// Get the best available search provider.
     pub fn search_provider(
         &self,
+    ) -> anyhow::Result<Box<dyn i1_providers::SearchProvider + Send + Sync>> {
+        let mut provider = self.pick_search_provider()?;
+        for log in call_logs() {
+            provider = Box::new(i1::Observed::new(provider, log));
+        }
+        Ok(provider)
+    }
+
+    fn pick_search_provider(
+        &self,
     ) -> anyhow::Result<Box<dyn i1_providers::SearchProvider + Send + Sync>> {
         match self.provider.as_str() {
             "shodan" => Ok(Box::new(self.shodan_provider()?)),
             #[cfg(feature = "censys")]
             "censys" => {
                 let id = self.censys_id.as_deref().ok_or_else(|| {
-                    anyhow::anyhow!("Censys API ID not configured.")
+                    self.missing("censys", "Censys API ID not configured.")
                 })?;
                 let secret = self.censys_secret.as_deref().ok_or_else(|| {
-                    anyhow::anyhow!("Censys API secret not configured.")
+                    self.missing("censys", "Censys API secret not configured.")
                 })?;
-                Ok(Box::new(i1::CensysProvider::new(id, secret)))
+                Ok(Box::new(self.censys_provider(id, secret)?))
             }
             #[cfg(feature = "criminalip")]
             "criminalip" => {
                 let key = self.criminalip_key.as_deref().ok_or_else(|| {
-                    anyhow::anyhow!("Criminal IP API key not configured.")
+                    self.missing("criminalip", "Criminal IP API key not configured.")
                 })?;
-                Ok(Box::new(i1::CriminalIpProvider::new(key)))
+                Ok(Box::new(self.criminalip_provider(key)?))
             }
+            #[cfg(feature = "native")]
+            "native" => Ok(Box::new(self.native_provider()?)),
+            "all" => Err(self.one_provider("Paged and watched searches", &HOST_PROVIDERS)),
             _ => {
                 if self.shodan_key.is_some() {
                     return Ok(Box::new(self.shodan_provider()?));
                 }
                 #[cfg(feature = "censys")]
                 if self.censys_id.is_some() && self.censys_secret.is_some() {
-                    return Ok(Box::new(i1::CensysProvider::new(
+                    return Ok(Box::new(self.censys_provider(
                         self.censys_id.as_deref().unwrap(),
                         self.censys_secret.as_deref().unwrap(),
-                    )));
+                    )?));
                 }
                 #[cfg(feature = "criminalip")]
                 if self.criminalip_key.is_some() {
-                    return Ok(Box::new(i1::CriminalIpProvider::new(
+                    return Ok(Box::new(self.criminalip_provider(
                         self.criminalip_key.as_deref().unwrap(),
-                    )));
+                    )?));
                 }
-                Err(anyhow::anyhow!(
+                Err(not_configured(
+                    None,
                     "No API key configured.\n\n\
                      Set one with:\n  \
                      1. i1 config set shodan-key <KEY>\n  \
@@ -169,6 +345,292 @@ impl Context {
         }
     }
 
+    /// Get a provider with vulnerability lookups, based on --provider.
+    ///
+    /// Only Shodan implements vulnerability lookups today, so asking for
+    /// another provider explains which configured ones can do it.
+    pub fn vuln_provider(&self) -> anyhow::Result<Box<dyn i1_providers::VulnProvider + Send + Sync>> {
+        let mut provider = self.pick_vuln_provider()?;
+        for log in call_logs() {
+            provider = Box::new(i1::Observed::new(provider, log));
+        }
+        Ok(provider)
+    }
+
+    fn pick_vuln_provider(
+        &self,
+    ) -> anyhow::Result<Box<dyn i1_providers::VulnProvider + Send + Sync>> {
+        match self.provider.as_str() {
+            "auto" | "shodan" => Ok(Box::new(self.shodan_provider()?)),
+            "all" => Err(self.one_provider("Vulnerability lookups", VULN_PROVIDERS)),
+            other => {
+                let configured = self.configured_providers();
+                let capable: Vec<&str> = VULN_PROVIDERS
+                    .iter()
+                    .copied()
+                    .filter(|name| configured.contains(name))
+                    .collect();
+                if capable.is_empty() {
+                    anyhow::bail!(
+                        "Provider '{other}' does not support vulnerability lookups, \
+                         and no provider that does is configured.\n\
+                         Configure Shodan with: i1 config set shodan-key <KEY>"
+                    );
+                }
+                anyhow::bail!(
+                    "Provider '{other}' does not support vulnerability lookups.\n\
+                     Configured providers that do: {}",
+                    capable.join(", ")
+                )
+            }
+        }
+    }
+
+    /// Get a provider with network monitoring, based on --provider.
+    pub fn alert_provider(
+        &self,
+    ) -> anyhow::Result<Box<dyn i1_providers::AlertProvider + Send + Sync>> {
+        let mut provider = self.pick_alert_provider()?;
+        for log in call_logs() {
+            provider = Box::new(i1::Observed::new(provider, log));
+        }
+        Ok(provider)
+    }
+
+    fn pick_alert_provider(
+        &self,
+    ) -> anyhow::Result<Box<dyn i1_providers::AlertProvider + Send + Sync>> {
+        match self.provider.as_str() {
+            "auto" | "shodan" => Ok(Box::new(self.shodan_provider()?)),
+            "all" => Err(self.one_provider("Network alerts", &["shodan"])),
+            other => anyhow::bail!(
+                "Provider '{other}' does not support network alerts. Use --provider shodan."
+            ),
+        }
+    }
+
+    /// Get the providers to ask for domain information, based on --provider.
+    ///
+    /// `all` returns every usable DNS provider; `auto` picks Shodan when a
+    /// key is set and falls back to the native provider.
+    pub fn dns_providers(
+        &self,
+    ) -> anyhow::Result<Vec<Box<dyn i1_providers::DnsProvider + Send + Sync>>> {
+        let mut providers: Vec<Box<dyn i1_providers::DnsProvider + Send + Sync>> = Vec::new();
+
+        match self.provider.as_str() {
+            "shodan" => providers.push(Box::new(self.shodan_provider()?)),
+            #[cfg(feature = "native")]
+            "native" | "i1" => providers.push(Box::new(self.native_provider()?)),
+            "auto" | "all" => {
+                if self.shodan_key.is_some() {
+                    providers.push(Box::new(self.shodan_provider()?));
+                }
+                #[cfg(feature = "native")]
+                if self.provider == "all" || providers.is_empty() {
+                    providers.push(Box::new(self.native_provider()?));
+                }
+                if providers.is_empty() {
+                    self.require_shodan_key()?;
+                }
+            }
+            _ => return Err(self.unsupported("DNS and domain lookups", &["shodan", "native"])),
+        }
+
+        for log in call_logs() {
+            providers = providers
+                .into_iter()
+                .map(|provider| {
+                    Box::new(i1::Observed::new(provider, log.clone()))
+                        as Box<dyn i1_providers::DnsProvider + Send + Sync>
+                })
+                .collect();
+        }
+        Ok(providers)
+    }
+
+    /// Build a multi-provider client from every configured provider.
+    ///
+    /// The `--provider` choice (if not `auto`) becomes the default, which
+    /// takes precedence when results are merged.
+    pub fn client(&self) -> anyhow::Result<i1::I1Client> {
+        if self.configured_providers().is_empty() {
+            return Err(not_configured(
+                None,
+                "No API key configured.\n\n\
+                 Set one with:\n  \
+                 1. i1 config set shodan-key <KEY>\n  \
+                 2. i1 config set censys-id <ID> + censys-secret <SECRET>\n  \
+                 3. i1 config set criminalip-key <KEY>",
+            ));
+        }
+
+        let mut builder = i1::I1Client::builder();
+
+        if self.shodan_key.is_some() {
+            builder = builder.with_provider(self.shodan_provider()?);
+        }
+        #[cfg(feature = "censys")]
+        if let (Some(id), Some(secret)) = (&self.censys_id, &self.censys_secret) {
+            builder = builder.with_provider(self.censys_provider(id, secret)?);
+        }
+        #[cfg(feature = "criminalip")]
+        if let Some(key) = &self.criminalip_key {
+            builder = builder.with_provider(self.criminalip_provider(key)?);
+        }
+        #[cfg(feature = "native")]
+        {
+            builder = builder.with_provider(self.native_provider()?);
+        }
+
+        // Domains are investigated with the DNS provider `i1 domain` would pick
+        if self.shodan_key.is_some() {
+            builder = builder.dns_provider(self.shodan_provider()?);
+        }
+        #[cfg(feature = "native")]
+        if self.shodan_key.is_none() {
+            builder = builder.dns_provider(self.native_provider()?);
+        }
+
+        if !matches!(self.provider.as_str(), "auto" | "all") {
+            if !self.configured_providers().contains(&self.provider.as_str()) {
+                return Err(self.missing(
+                    &self.provider,
+                    format!("Provider '{}' is not configured.", self.provider),
+                ));
+            }
+            builder = builder.default_provider(self.provider.clone());
+        }
+        for log in call_logs() {
+            builder = builder.observer(log);
+        }
+
+        Ok(builder.policy(self.policy.clone()).build())
+    }
+
+    /// Every provider this build can talk to right now: those with
+    /// credentials, plus the keyless native provider when compiled in.
+    pub fn providers(&self) -> anyhow::Result<Vec<Box<dyn i1_providers::Provider>>> {
+        let mut providers: Vec<Box<dyn i1_providers::Provider>> = Vec::new();
+
+        if self.shodan_key.is_some() {
+            providers.push(Box::new(self.shodan_provider()?));
+        }
+        #[cfg(feature = "censys")]
+        if let (Some(id), Some(secret)) = (&self.censys_id, &self.censys_secret) {
+            providers.push(Box::new(self.censys_provider(id, secret)?));
+        }
+        #[cfg(feature = "criminalip")]
+        if let Some(key) = &self.criminalip_key {
+            providers.push(Box::new(self.criminalip_provider(key)?));
+        }
+        #[cfg(feature = "native")]
+        providers.push(Box::new(self.native_provider()?));
+
+        for log in call_logs() {
+            providers = providers
+                .into_iter()
+                .map(|provider| {
+                    Box::new(i1::Observed::new(provider, log.clone()))
+                        as Box<dyn i1_providers::Provider>
+                })
+                .collect();
+        }
+        Ok(providers)
+    }
+
+    /// Providers this build can use right now, in the order `auto` tries
+    /// them. The native provider needs no key.
+    pub fn configured_providers(&self) -> Vec<&'static str> {
+        let unconfigured = self.unconfigured_providers();
+        KNOWN_PROVIDERS
+            .iter()
+            .filter(|(name, built)| *built && !unconfigured.contains(name))
+            .map(|(name, _)| *name)
+            .collect()
+    }
+
+    /// Check `--provider` before a command runs, so a typo fails right away
+    /// with the choices instead of somewhere inside the command.
+    pub fn check_provider(&mut self) -> anyhow::Result<()> {
+        match provider_name(&self.provider) {
+            Ok(name) => {
+                self.provider = name.to_string();
+                Ok(())
+            }
+            Err(why) => Err(invalid_provider(
+                &self.provider,
+                format!(
+                    "{why}\nConfigured providers: {}",
+                    or_none(&self.configured_providers())
+                ),
+            )),
+        }
+    }
+
+    /// The provider named by `--provider` has no credentials.
+    fn missing(&self, provider: &str, help: impl std::fmt::Display) -> anyhow::Error {
+        not_configured(
+            Some(provider),
+            format!(
+                "{help}\nConfigured providers: {}",
+                or_none(&self.configured_providers())
+            ),
+        )
+    }
+
+    /// `--provider all` asked of something that takes one provider.
+    fn one_provider(&self, what: &str, capable: &[&str]) -> anyhow::Error {
+        invalid_provider(
+            "all",
+            format!(
+                "{what} use one provider at a time; --provider all is not supported.\n\
+                 Pick one with --provider: {}",
+                or_none(&self.capable(capable))
+            ),
+        )
+    }
+
+    /// A provider asked for something it cannot do.
+    fn unsupported(&self, what: &str, capable: &[&str]) -> anyhow::Error {
+        invalid_provider(
+            &self.provider,
+            format!(
+                "Provider '{}' does not support {what}.\n\
+                 Configured providers that do: {}",
+                self.provider,
+                or_none(&self.capable(capable))
+            ),
+        )
+    }
+
+    /// The configured providers among `names`.
+    fn capable(&self, names: &[&str]) -> Vec<&'static str> {
+        self.configured_providers()
+            .into_iter()
+            .filter(|name| names.contains(name))
+            .collect()
+    }
+
+    /// Providers compiled into this build that have no credentials configured.
+    pub fn unconfigured_providers(&self) -> Vec<&'static str> {
+        let mut missing = Vec::new();
+
+        if self.shodan_key.is_none() {
+            missing.push("shodan");
+        }
+        #[cfg(feature = "censys")]
+        if self.censys_id.is_none() || self.censys_secret.is_none() {
+            missing.push("censys");
+        }
+        #[cfg(feature = "criminalip")]
+        if self.criminalip_key.is_none() {
+            missing.push("criminalip");
+        }
+
+        missing
+    }
+
     /// Check if any provider is configured.
     pub const fn has_any_provider(&self) -> bool {
         self.shodan_key.is_some()
@@ -176,3 +638,98 @@ impl Context {
             || self.criminalip_key.is_some()
     }
 }
+
+/// The canonical name for a `--provider` value, or why it isn't one.
+///
+/// Takes `auto`, `all` and the providers built into this binary, with the
+/// aliases `i1` and `criminal-ip`.
+pub fn provider_name(name: &str) -> Result<&'static str, String> {
+    let lower = name.to_lowercase();
+    let wanted = match lower.as_str() {
+        "i1" => "native",
+        "criminal-ip" => "criminalip",
+        other => other,
+    };
+    if let Some(special) = ["auto", "all"].into_iter().find(|s| *s == wanted) {
+        return Ok(special);
+    }
+    match KNOWN_PROVIDERS.iter().find(|(known, _)| *known == wanted) {
+        Some((known, true)) => Ok(known),
+        Some((known, false)) => Err(format!(
+            "Provider '{known}' is not built into this i1. \
+             Rebuild with: cargo install i1-cli --features {known}"
+        )),
+        None => Err(format!(
+            "Unknown provider '{name}'. Providers: {}",
+            provider_names().join(", ")
+        )),
+    }
+}
+
+/// Every value `--provider` takes in this build.
+pub fn provider_names() -> Vec<&'static str> {
+    ["auto", "all"]
+        .into_iter()
+        .chain(
+            KNOWN_PROVIDERS
+                .iter()
+                .filter(|(_, built)| *built)
+                .map(|(name, _)| *name),
+        )
+        .collect()
+}
+
+/// A `--provider` that can't be used: `help` is the message, and the run
+/// exits as invalid input.
+pub fn invalid_provider(
+    provider: &str,
+    help: impl std::fmt::Display + Send + Sync + 'static,
+) -> anyhow::Error {
+    anyhow::Error::new(i1::I1Error::Config(format!("invalid provider '{provider}'"))).context(help)
+}
+
+/// The logs provider calls are recorded in: the audit log, and the active
+/// workspace's so it can count what the investigation cost.
+fn call_logs() -> Vec<i1::AuditLog> {
+    crate::audit::log()
+        .into_iter()
+        .chain(crate::workspace::active().map(|workspace| workspace.calls()))
+        .collect()
+}
+
+fn or_none(names: &[&str]) -> String {
+    if names.is_empty() {
+        "none".to_string()
+    } else {
+        names.join(", ")
+    }
+}
+
+/// A missing-credentials error: `help` is the message, and the missing
+/// provider underneath gives the run its exit code.
+pub fn not_configured(
+    provider: Option<&str>,
+    help: impl std::fmt::Display + Send + Sync + 'static,
+) -> anyhow::Error {
+    let cause = provider.map_or(i1::I1Error::NoProviders, |name| {
+        i1::I1Error::ProviderNotConfigured(name.to_string())
+    });
+    anyhow::Error::new(cause).context(help)
+}
+
+/// The first line of an error, for a one-line note about a skipped step.
+pub fn headline(e: &anyhow::Error) -> String {
+    let message = e.to_string();
+    let first = message.lines().next().unwrap_or_default();
+    first.trim_end_matches('.').to_string()
+}
+
+/// Ask a yes/no question on the terminal. Anything but yes is no.
+pub fn confirm(prompt: &str) -> anyhow::Result<bool> {
+    print!("{} ", format!("{prompt} [y/N]").cyan());
+    std::io::stdout().flush()?;
+
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    Ok(matches!(input.trim().to_lowercase().as_str(), "y" | "yes"))
+}
@@ -1,42 +1,325 @@
 //! `i1 myip` - Show your public IP address.
 
+use std::net::IpAddr;
+use std::time::Duration;
+
 use anyhow::Result;
 use colored::Colorize;
+use serde::Serialize;
 
 use super::Context;
+use crate::cli::args::MyipArgs;
 use crate::output::OutputFormat;
 
-pub async fn execute(ctx: Context) -> Result<()> {
-    // Use a simple HTTP request to get public IP (no API key needed)
-    let client = reqwest::Client::new();
-    let ip = client
-        .get("https://api.ipify.org")
-        .send()
-        .await?
-        .text()
-        .await?;
+/// IPv4-only endpoint (also the fast path when no flags are given)
+const IPV4_ENDPOINT: &str = "https://api.ipify.org";
+
+/// IPv6-only endpoint
+const IPV6_ENDPOINT: &str = "https://api6.ipify.org";
+
+/// How long to wait for a family before assuming it has no connectivity
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Tags that are bad news when they show up on your own address.
+const SUSPICIOUS_TAGS: &[&str] = &[
+    "botnet",
+    "c2",
+    "compromised",
+    "malware",
+    "proxy",
+    "scanner",
+    "spam",
+    "tor",
+    "vpn",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+enum Family {
+    #[serde(rename = "ipv4")]
+    V4,
+    #[serde(rename = "ipv6")]
+    V6,
+}
+
+impl Family {
+    /// Where to ask, unless `I1_MYIP_URL` / `I1_MYIP6_URL` point elsewhere
+    fn endpoint(self) -> String {
+        let (var, default) = match self {
+            Self::V4 => ("I1_MYIP_URL", IPV4_ENDPOINT),
+            Self::V6 => ("I1_MYIP6_URL", IPV6_ENDPOINT),
+        };
+        std::env::var(var).unwrap_or_else(|_| default.to_string())
+    }
+
+    const fn label(self) -> &'static str {
+        match self {
+            Self::V4 => "IPv4",
+            Self::V6 => "IPv6",
+        }
+    }
+
+    const fn matches(self, ip: IpAddr) -> bool {
+        matches!(
+            (self, ip),
+            (Self::V4, IpAddr::V4(_)) | (Self::V6, IpAddr::V6(_))
+        )
+    }
+}
+
+/// One public address, optionally enriched.
+#[derive(Debug, Serialize)]
+struct Address {
+    ip: String,
+    family: Family,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    isp: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    asn: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    org: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    hostnames: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    network: Option<String>,
+}
+
+impl Address {
+    const fn new(ip: String, family: Family) -> Self {
+        Self {
+            ip,
+            family,
+            isp: None,
+            asn: None,
+            org: None,
+            hostnames: Vec::new(),
+            tags: Vec::new(),
+            network: None,
+        }
+    }
+}
+
+pub async fn execute(ctx: Context, args: MyipArgs) -> Result<()> {
+    let families: &[Family] = if args.all {
+        &[Family::V4, Family::V6]
+    } else if args.ipv6 {
+        &[Family::V6]
+    } else {
+        &[Family::V4]
+    };
+
+    // Through the proxy, if any, so this shows the address providers see
+    let client = ctx.http.client_builder()?.timeout(TIMEOUT).build()?;
+    let lookups = families
+        .iter()
+        .map(|&family| fetch(&client, &ctx.http, family));
+    let results = futures_util::future::join_all(lookups).await;
+
+    let mut addresses = Vec::new();
+    let mut failures = Vec::new();
+    for (family, result) in families.iter().zip(results) {
+        match result {
+            Ok(ip) => addresses.push(Address::new(ip, *family)),
+            Err(e) => failures.push((*family, e)),
+        }
+    }
+
+    if addresses.is_empty() {
+        let (family, e) = failures.remove(0);
+        anyhow::bail!(
+            "Could not determine your public {} address: {e}",
+            family.label()
+        );
+    }
 
-    let ip = ip.trim();
+    if args.enrich {
+        enrich(&ctx, &mut addresses).await;
+    }
 
     match ctx.output_format {
         OutputFormat::Json => {
-            println!("{{\"ip\":\"{ip}\"}}");
-        }
-        OutputFormat::Csv => {
-            println!("ip");
-            println!("{ip}");
+            if args.all {
+                println!("{}", serde_json::to_string_pretty(&addresses)?);
+            } else {
+                println!("{}", serde_json::to_string_pretty(&addresses[0])?);
+            }
         }
         OutputFormat::Yaml => {
-            println!("ip: {ip}");
+            if args.all {
+                println!("{}", serde_yaml::to_string(&addresses)?);
+            } else {
+                println!("{}", serde_yaml::to_string(&addresses[0])?);
+            }
         }
-        OutputFormat::Pretty => {
-            if ctx.no_color {
-                println!("Your IP: {ip}");
+        OutputFormat::Csv => {
+            if args.enrich {
+                println!("ip,family,isp,asn,org,hostnames,tags");
             } else {
-                println!("Your IP: {}", ip.cyan().bold());
+                println!("ip,family");
+            }
+            for addr in &addresses {
+                let family = addr.family.label().to_lowercase();
+                if args.enrich {
+                    println!(
+                        "{},{family},\"{}\",{},\"{}\",\"{}\",\"{}\"",
+                        addr.ip,
+                        addr.isp.as_deref().unwrap_or(""),
+                        addr.asn.as_deref().unwrap_or(""),
+                        addr.org.as_deref().unwrap_or(""),
+                        addr.hostnames.join(";"),
+                        addr.tags.join(";")
+                    );
+                } else {
+                    println!("{},{family}", addr.ip);
+                }
+            }
+        }
+        OutputFormat::Pretty => {
+            for addr in &addresses {
+                print_address_pretty(addr, &ctx);
+            }
+            for (family, e) in &failures {
+                println!("{}", format!("No {} connectivity", family.label()).dimmed());
+                if ctx.verbose {
+                    eprintln!("  {e}");
+                }
             }
         }
     }
 
     Ok(())
 }
+
+/// Our public IPv4 address, for commands that offer to whitelist it. Asked
+/// through the configured proxy, like `execute`.
+pub async fn public_ip(ctx: &Context) -> Result<String> {
+    let client = ctx.http.client_builder()?.timeout(TIMEOUT).build()?;
+    fetch(&client, &ctx.http, Family::V4).await
+}
+
+/// Ask ipify for our address in one family, with `client` built from `http`.
+async fn fetch(
+    client: &reqwest::Client,
+    http: &i1::ProviderHttpConfig,
+    family: Family,
+) -> Result<String> {
+    let text = client
+        .get(family.endpoint())
+        .send()
+        .await
+        .map_err(|e| http.request_error(&e))?
+        .error_for_status()?
+        .text()
+        .await?;
+
+    let ip: IpAddr = text.trim().parse()?;
+    if !family.matches(ip) {
+        anyhow::bail!("{} endpoint returned {ip}", family.label());
+    }
+
+    Ok(ip.to_string())
+}
+
+/// Fill in provider and WHOIS details. Failures only cost the extra detail.
+async fn enrich(ctx: &Context, addresses: &mut [Address]) {
+    match ctx.host_provider() {
+        Ok(provider) => {
+            for addr in addresses.iter_mut() {
+                match provider.lookup_host(&addr.ip).await {
+                    Ok(host) => {
+                        addr.isp = host.isp;
+                        addr.asn = host.asn;
+                        addr.org = host.org;
+                        addr.hostnames = host.hostnames;
+                        addr.tags = host.tags;
+                    }
+                    Err(e) => {
+                        if ctx.verbose {
+                            eprintln!("{} {} lookup failed: {e}", "Warning:".yellow(), addr.ip);
+                        }
+                    }
+                }
+            }
+        }
+        Err(e) => {
+            if ctx.output_format == OutputFormat::Pretty {
+                eprintln!(
+                    "{} {}, skipping provider lookup",
+                    "Note:".yellow(),
+                    super::headline(&e)
+                );
+            }
+        }
+    }
+
+    #[cfg(feature = "recon")]
+    if let Ok(whois) = i1::recon::whois::WhoisClient::new() {
+        for addr in addresses.iter_mut() {
+            let Ok(ip) = addr.ip.parse() else { continue };
+            if let Ok(info) = whois.lookup_ip(ip).await {
+                addr.network = network_summary(&info.raw);
+            }
+        }
+    }
+}
+
+/// Pull the network name and owner out of a raw IP WHOIS response.
+#[cfg(feature = "recon")]
+fn network_summary(raw: &str) -> Option<String> {
+    let field = |keys: &[&str]| {
+        raw.lines().find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            let value = value.trim();
+            (keys.contains(&key.trim().to_lowercase().as_str()) && !value.is_empty())
+                .then(|| value.to_string())
+        })
+    };
+
+    let name = field(&["netname", "network-name"]);
+    let owner = field(&["orgname", "org-name", "owner", "descr"]);
+
+    match (name, owner) {
+        (Some(name), Some(owner)) => Some(format!("{name} ({owner})")),
+        (name, owner) => name.or(owner),
+    }
+}
+
+fn print_address_pretty(addr: &Address, ctx: &Context) {
+    let label = format!("Your {}:", addr.family.label());
+    if ctx.no_color {
+        println!("{label} {}", addr.ip);
+    } else {
+        println!("{label} {}", addr.ip.cyan().bold());
+    }
+
+    if let Some(isp) = &addr.isp {
+        println!("  {} {}", "ISP:".bold(), isp);
+    }
+    if let Some(asn) = &addr.asn {
+        println!("  {} {}", "ASN:".bold(), asn);
+    }
+    if let Some(org) = &addr.org {
+        println!("  {} {}", "Organization:".bold(), org);
+    }
+    if let Some(network) = &addr.network {
+        println!("  {} {}", "Network:".bold(), network);
+    }
+    if !addr.hostnames.is_empty() {
+        println!("  {} {}", "Reverse DNS:".bold(), addr.hostnames.join(", "));
+    }
+    if !addr.tags.is_empty() {
+        let tags: Vec<String> = addr
+            .tags
+            .iter()
+            .map(|tag| {
+                if !ctx.no_color && SUSPICIOUS_TAGS.contains(&tag.to_lowercase().as_str()) {
+                    tag.red().bold().to_string()
+                } else {
+                    tag.clone()
+                }
+            })
+            .collect();
+        println!("  {} {}", "Tags:".bold(), tags.join(", "));
+    }
+}
@@ -0,0 +1,559 @@
+//! `i1 scan` - Port scan a host or network with the local scanner.
+
+use std::net::IpAddr;
+use std::time::Duration;
+
+use anyhow::Result;
+use colored::Colorize;
+use futures_util::StreamExt;
+use indicatif::{ProgressBar, ProgressStyle};
+use ipnet::IpNet;
+use serde::Serialize;
+use tabled::Tabled;
+
+use super::preflight::{self, Preflight};
+use super::Context;
+use crate::cli::args::{ScanArgs, TemplateArgs};
+use crate::cli::exit::{self, Exit};
+use crate::output::{print_stix, terminal, OutputFormat};
+use crate::workspace::{self, Kind};
+use i1::notify::Notifier;
+use i1::recon::scanner::{PortSpec, ScanResult, ScanType, Scanner, Timing};
+use i1::recon::ScopeGuard;
+use i1::{HostInfo, HostLookup, I1Error};
+
+/// Largest network we sweep in one command.
+const MAX_HOSTS: usize = 65_536;
+
+/// Hosts scanned at the same time during a network sweep.
+const HOST_CONCURRENCY: usize = 8;
+
+/// Scan results for one host.
+#[derive(Debug, Serialize)]
+struct HostReport {
+    ip: String,
+    transport: &'static str,
+    ports: Vec<PortReport>,
+    scan_time_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    host: Option<HostInfo>,
+}
+
+/// A single open port.
+#[derive(Debug, Serialize)]
+struct PortReport {
+    port: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    banner: Option<String>,
+    /// What the banner was identified as
+    #[serde(skip_serializing_if = "Option::is_none")]
+    product: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<String>,
+}
+
+impl HostReport {
+    fn new(result: &ScanResult, transport: &'static str) -> Self {
+        Self {
+            ip: result.target.to_string(),
+            transport,
+            ports: result
+                .open_ports
+                .iter()
+                .map(|p| PortReport {
+                    port: p.port,
+                    banner: p.service.as_ref().and_then(|s| s.banner.clone()),
+                    product: p.service.as_ref().and_then(|s| s.product.clone()),
+                    version: p.service.as_ref().and_then(|s| s.version.clone()),
+                })
+                .collect(),
+            scan_time_ms: u64::try_from(result.scan_time.as_millis()).unwrap_or(u64::MAX),
+            host: None,
+        }
+    }
+
+    /// The host for `--format-template`, `--filter` and the workspace: the
+    /// provider's data when enriched, with the ports this scan found open
+    /// and the banners it grabbed from those the provider had none for.
+    fn to_host(&self) -> Result<HostInfo> {
+        let mut host = match &self.host {
+            Some(host) => host.clone(),
+            None => serde_json::from_value(serde_json::json!({ "ip_str": self.ip }))?,
+        };
+        host.ports = self.ports.iter().map(|p| p.port).collect();
+        for port in &self.ports {
+            if port.banner.is_none() || host.data.iter().any(|s| s.port == port.port) {
+                continue;
+            }
+            host.data.push(serde_json::from_value(serde_json::json!({
+                "port": port.port,
+                "transport": self.transport,
+                "product": port.product,
+                "version": port.version,
+                "data": port.banner,
+            }))?);
+        }
+        host.fill_cpes();
+        Ok(host)
+    }
+}
+
+#[derive(Tabled)]
+struct PortRow {
+    #[tabled(rename = "Port")]
+    port: u16,
+    #[tabled(rename = "Protocol")]
+    transport: String,
+    #[tabled(rename = "Service")]
+    product: String,
+    #[tabled(rename = "Banner")]
+    banner: String,
+}
+
+/// What a scan turned up.
+#[derive(Default)]
+struct Outcome {
+    /// Hosts scanned and not hidden by `--filter`
+    reports: Vec<HostReport>,
+    /// Hosts with something to show that `--filter` hid
+    unmatched: usize,
+    /// Hosts that could not be scanned, and why
+    failures: Vec<(IpAddr, I1Error)>,
+}
+
+pub async fn execute(ctx: Context, args: ScanArgs) -> Result<()> {
+    let ports: PortSpec = args.ports.parse().map_err(I1Error::from)?;
+    let timing: Timing = args.timing.parse().map_err(I1Error::from)?;
+    let targets = resolve_targets(&args.target).await?;
+    let sweep = targets.len() > 1;
+
+    if !args.yes {
+        require_private(&targets)?;
+    }
+
+    let scope = ScopeGuard::new()
+        .exclude_whitelist_file(crate::defend::State::path()?)
+        .map_err(I1Error::from)?;
+    let (targets, skipped): (Vec<IpAddr>, Vec<IpAddr>) =
+        targets.into_iter().partition(|ip| scope.is_in_scope(*ip));
+
+    if targets.is_empty() {
+        anyhow::bail!(
+            "{} is out of scope (government/military range or defend whitelist).",
+            args.target
+        );
+    }
+
+    if args.dry_run {
+        return dry_run(&ctx, &args.target, &targets, skipped.len()).await;
+    }
+
+    let scan_type = if args.udp {
+        ScanType::Udp
+    } else {
+        ScanType::TcpConnect
+    };
+
+    let scanner = Scanner::new()
+        .ports(ports)
+        .timing(timing)
+        .scan_type(scan_type)
+        .grab_banner(args.banner)
+        .scope(scope);
+
+    let provider = args.enrich.then(|| ctx.host_provider()).transpose()?;
+    let notifier = args.notify.notifier();
+
+    if output_format(&ctx, &args) == Some(OutputFormat::Pretty) {
+        for ip in &skipped {
+            println!("{} {} is out of scope, skipping", "Note:".yellow(), ip);
+        }
+    }
+
+    let host_count = targets.len();
+    let mut outcome =
+        scan_hosts(&ctx, &args, &scanner, provider, targets, sweep, &notifier).await?;
+    outcome.reports.sort_by_key(|r| r.ip.parse::<IpAddr>().ok());
+    print_outcome(&ctx, &args, &outcome, host_count, sweep)?;
+
+    notifier.flush().await;
+    scan_failures(&ctx, outcome.failures, host_count)
+}
+
+/// The output format, or `None` when a template replaces it, so nothing
+/// else goes to stdout.
+fn output_format(ctx: &Context, args: &ScanArgs) -> Option<OutputFormat> {
+    args.template
+        .format_template
+        .is_none()
+        .then_some(ctx.output_format)
+}
+
+/// Scan `targets`, enriching each host with `provider` and streaming it as
+/// it completes.
+async fn scan_hosts(
+    ctx: &Context,
+    args: &ScanArgs,
+    scanner: &Scanner,
+    provider: Option<Box<dyn HostLookup + Send + Sync>>,
+    targets: Vec<IpAddr>,
+    sweep: bool,
+    notifier: &Notifier,
+) -> Result<Outcome> {
+    let transport = if args.udp { "udp" } else { "tcp" };
+    let format = output_format(ctx, args);
+
+    let progress =
+        (format == Some(OutputFormat::Pretty)).then(|| progress_bar(&args.target, targets.len()));
+
+    if format == Some(OutputFormat::Csv) {
+        println!("ip,transport,port,banner");
+    }
+
+    let mut outcome = Outcome::default();
+    let mut results = futures_util::stream::iter(targets)
+        .map(|ip| async move { (ip, scanner.scan(ip).await) })
+        .buffer_unordered(HOST_CONCURRENCY);
+
+    while let Some((ip, result)) = results.next().await {
+        if let Some(pb) = &progress {
+            pb.inc(1);
+        }
+
+        let result = match result {
+            Ok(result) => result,
+            Err(e) => {
+                if ctx.verbose {
+                    let warn = || eprintln!("{} {ip}: {e}", "Warning:".yellow());
+                    match &progress {
+                        Some(pb) => pb.suspend(warn),
+                        None => warn(),
+                    }
+                }
+                outcome.failures.push((ip, e.into()));
+                continue;
+            }
+        };
+
+        let mut report = HostReport::new(&result, transport);
+
+        if let Some(provider) = &provider {
+            enrich(&mut report, provider.as_ref(), notifier).await?;
+        }
+
+        // Sweeps don't show hosts without open ports anyway
+        let shown = !sweep || !report.ports.is_empty();
+        let host = report.to_host()?;
+        if shown {
+            workspace::keep_host(Kind::Scan, "scanner", &host);
+        }
+        if args
+            .filter
+            .as_ref()
+            .is_some_and(|filter| !filter.matches(&host))
+        {
+            outcome.unmatched += usize::from(shown);
+            continue;
+        }
+
+        stream_report(&report, ctx, progress.as_ref(), sweep, &args.template)?;
+        outcome.reports.push(report);
+    }
+
+    if let Some(pb) = &progress {
+        pb.finish_and_clear();
+    }
+    Ok(outcome)
+}
+
+/// Print what the streamed output left for the end: the whole JSON, STIX or
+/// YAML document, or the pretty summary. Then save it with `--save`.
+fn print_outcome(
+    ctx: &Context,
+    args: &ScanArgs,
+    outcome: &Outcome,
+    host_count: usize,
+    sweep: bool,
+) -> Result<()> {
+    let reports = &outcome.reports;
+    let format = output_format(ctx, args);
+    match format {
+        Some(_) if ctx.stix => {
+            // Like the streamed output, a sweep leaves out hosts with no open ports
+            let hosts = reports
+                .iter()
+                .filter(|r| !sweep || !r.ports.is_empty())
+                .map(HostReport::to_host)
+                .collect::<Result<Vec<_>>>()?;
+            print_stix(&hosts)?;
+        }
+        Some(OutputFormat::Json) => {
+            println!("{}", serde_json::to_string_pretty(reports)?);
+        }
+        Some(OutputFormat::Yaml) => {
+            println!("{}", serde_yaml::to_string(reports)?);
+        }
+        Some(OutputFormat::Pretty) => {
+            if sweep {
+                let up = reports.iter().filter(|r| !r.ports.is_empty()).count();
+                println!(
+                    "{}",
+                    format!("Scanned {host_count} hosts, {up} with open ports.").dimmed()
+                );
+            }
+            if outcome.unmatched > 0 {
+                println!(
+                    "{}",
+                    format!(
+                        "{} hosts not matching --filter not shown",
+                        outcome.unmatched
+                    )
+                    .dimmed()
+                );
+            }
+        }
+        Some(OutputFormat::Csv) | None => {}
+    }
+
+    if let Some(path) = &args.save {
+        std::fs::write(path, serde_json::to_string_pretty(reports)?)?;
+        if format == Some(OutputFormat::Pretty) {
+            println!("{} Results saved to {}", "Success:".green().bold(), path);
+        }
+    }
+    Ok(())
+}
+
+/// Say how many hosts could not be scanned, and exit as the first of them
+/// failed. A single host's failure is the error itself.
+fn scan_failures(ctx: &Context, mut failures: Vec<(IpAddr, I1Error)>, hosts: usize) -> Result<()> {
+    if failures.is_empty() {
+        return Ok(());
+    }
+    if hosts == 1 {
+        let (ip, e) = failures.remove(0);
+        return Err(anyhow::Error::new(e).context(format!("Could not scan {ip}")));
+    }
+
+    if !ctx.quiet {
+        let why = if ctx.verbose {
+            ""
+        } else {
+            " (--verbose shows why)"
+        };
+        eprintln!(
+            "{} {} of {hosts} hosts could not be scanned{why}",
+            "Warning:".yellow(),
+            failures.len()
+        );
+    }
+    Err(Exit(exit::classify(&failures[0].1)).into())
+}
+
+/// `--dry-run`: every in-scope host could turn up open ports, and each
+/// one that does is looked up once.
+async fn dry_run(ctx: &Context, target: &str, targets: &[IpAddr], skipped: usize) -> Result<()> {
+    let provider = ctx.host_provider()?;
+
+    let mut plan = Preflight::new("scan", provider.name(), target);
+    plan.calls = targets.len() as u64;
+    plan.credits = plan.calls * u64::from(i1::observe::lookup_credits(provider.name()));
+    if skipped > 0 {
+        plan.error = Some(format!(
+            "{skipped} addresses are out of scope and would not be scanned"
+        ));
+    }
+
+    preflight::report(ctx, provider.as_ref(), plan).await
+}
+
+/// Add the provider's data to a host with open ports, and tell
+/// `--notify-url` about it if it scores high enough.
+async fn enrich(
+    report: &mut HostReport,
+    provider: &(dyn HostLookup + Send + Sync),
+    notifier: &Notifier,
+) -> Result<()> {
+    if report.ports.is_empty() {
+        return Ok(());
+    }
+    report.host = provider.lookup_host(&report.ip).await.ok();
+    if report.host.is_some() {
+        notifier.check(&report.to_host()?, "scan");
+    }
+    Ok(())
+}
+
+/// Print a host as soon as it completes (pretty, CSV and template output stream).
+fn stream_report(
+    report: &HostReport,
+    ctx: &Context,
+    progress: Option<&ProgressBar>,
+    sweep: bool,
+    template: &TemplateArgs,
+) -> Result<()> {
+    if let Some(format) = &template.format_template {
+        if !sweep || !report.ports.is_empty() {
+            println!(
+                "{}",
+                format.render(&report.to_host()?, &template.list_sep())
+            );
+        }
+        return Ok(());
+    }
+
+    match ctx.output_format {
+        OutputFormat::Pretty => {
+            // Sweeps only show hosts that have something to show
+            if !sweep || !report.ports.is_empty() {
+                let print = || print_host_pretty(report, ctx);
+                match progress {
+                    Some(pb) => pb.suspend(print),
+                    None => print(),
+                }
+            }
+        }
+        OutputFormat::Csv => {
+            for port in &report.ports {
+                println!(
+                    "{},{},{},\"{}\"",
+                    report.ip,
+                    report.transport,
+                    port.port,
+                    port.banner
+                        .as_deref()
+                        .unwrap_or("")
+                        .replace('"', "\"\"")
+                        .replace('\n', " ")
+                );
+            }
+        }
+        OutputFormat::Json | OutputFormat::Yaml => {}
+    }
+    Ok(())
+}
+
+/// Expand a target into the addresses to scan.
+async fn resolve_targets(target: &str) -> Result<Vec<IpAddr>> {
+    if let Ok(ip) = i1::validate_ip(target) {
+        return Ok(vec![ip]);
+    }
+
+    if let Ok(net) = target.parse::<IpNet>() {
+        let hosts: Vec<IpAddr> = net.hosts().take(MAX_HOSTS + 1).collect();
+        if hosts.len() > MAX_HOSTS {
+            anyhow::bail!(
+                "{target} has more than {MAX_HOSTS} addresses. Split it into smaller ranges."
+            );
+        }
+        return Ok(hosts);
+    }
+
+    let addr = tokio::net::lookup_host((target, 0))
+        .await
+        .map_err(|e| anyhow::anyhow!("Could not resolve {target}: {e}"))?
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("{target} did not resolve to any address"))?;
+
+    Ok(vec![addr.ip()])
+}
+
+/// Safety check: scanning hosts you don't own needs explicit consent.
+fn require_private(targets: &[IpAddr]) -> Result<()> {
+    if let Some(ip) = targets.iter().find(|ip| !is_private(**ip)) {
+        anyhow::bail!(
+            "Refusing to scan {ip}: it is outside private (RFC1918) address space.\n\n\
+             Only scan hosts you own or are authorized to test.\n\
+             Re-run with --yes to confirm."
+        );
+    }
+    Ok(())
+}
+
+/// Private, loopback and link-local addresses are safe to scan without asking.
+const fn is_private(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => v4.is_private() || v4.is_loopback() || v4.is_link_local(),
+        IpAddr::V6(v6) => {
+            let first = v6.segments()[0];
+            v6.is_loopback() || (first & 0xfe00) == 0xfc00 || (first & 0xffc0) == 0xfe80
+        }
+    }
+}
+
+fn progress_bar(target: &str, hosts: usize) -> ProgressBar {
+    if hosts == 1 {
+        let pb = ProgressBar::new_spinner();
+        pb.set_message(format!("Scanning {target}..."));
+        pb.enable_steady_tick(Duration::from_millis(100));
+        return pb;
+    }
+
+    let pb = ProgressBar::new(hosts as u64);
+    if let Ok(style) =
+        ProgressStyle::with_template("{spinner} [{bar:40.cyan/blue}] {pos}/{len} hosts {msg}")
+    {
+        pb.set_style(style.progress_chars("=> "));
+    }
+    pb.set_message(target.to_string());
+    pb
+}
+
+fn print_host_pretty(report: &HostReport, ctx: &Context) {
+    if ctx.no_color {
+        println!("Host: {}", report.ip);
+    } else {
+        println!("{} {}", "Host:".bold(), report.ip.cyan().bold());
+    }
+
+    if let Some(host) = &report.host {
+        if let Some(org) = &host.org {
+            println!("  {} {}", "Organization:".bold(), org);
+        }
+        if let Some(asn) = &host.asn {
+            println!("  {} {}", "ASN:".bold(), asn);
+        }
+        if let Some(country) = &host.location.country_name {
+            println!("  {} {}", "Country:".bold(), country);
+        }
+        if !host.vulns.is_empty() {
+            println!(
+                "  {} {}",
+                "Vulnerabilities:".bold().red(),
+                host.vulns.join(", ")
+            );
+        }
+    }
+
+    if report.ports.is_empty() {
+        println!("  No open {} ports found", report.transport);
+    } else {
+        let rows: Vec<PortRow> = report
+            .ports
+            .iter()
+            .map(|p| PortRow {
+                port: p.port,
+                transport: report.transport.to_string(),
+                product: match (&p.product, &p.version) {
+                    (Some(product), Some(version)) => format!("{product} {version}"),
+                    (Some(product), None) => product.clone(),
+                    (None, _) => String::new(),
+                },
+                banner: p.banner.clone().unwrap_or_default(),
+            })
+            .collect();
+
+        let table = terminal::table(&rows);
+        println!("{table}");
+    }
+
+    println!(
+        "{}",
+        format!(
+            "Scanned in {:.1}s",
+            Duration::from_millis(report.scan_time_ms).as_secs_f64()
+        )
+        .dimmed()
+    );
+    println!();
+}
@@ -0,0 +1,171 @@
+//! `--dry-run` - What a command would cost, without spending anything.
+//!
+//! `search`, `host --file` and `scan --enrich` work out the calls they
+//! would make and what those cost from the estimates in [`i1::observe`],
+//! then report them beside the credits left. The balance comes from a
+//! health check at most [`crate::cache::HEALTH_TTL`] old, and only when
+//! there is none is the provider asked, which costs nothing.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use colored::Colorize;
+use serde::Serialize;
+
+use super::{credits, Context};
+use crate::cache::{self, Cache};
+use crate::cli::exit::{self, Exit};
+use crate::output::{no_stix, OutputFormat};
+use i1::I1Error;
+use i1_providers::Provider;
+
+/// What a command would do, as `--dry-run` reports it.
+#[derive(Debug, Serialize)]
+pub struct Preflight {
+    /// The command, e.g. `search`
+    pub command: &'static str,
+    pub provider: String,
+    /// The query, or what the targets were read from
+    pub input: String,
+    /// Whether the provider would accept the query
+    pub valid: bool,
+    /// Why it wouldn't, or what would be skipped
+    pub error: Option<String>,
+    /// Most API calls the command would make
+    pub calls: u64,
+    /// Most credits those calls would spend
+    pub credits: u64,
+    /// Credits left, when the provider reports them
+    pub credits_remaining: Option<i64>,
+    /// When the balance was checked
+    pub checked_at: Option<DateTime<Utc>>,
+    /// Whether the balance covers the cost, when it is known
+    pub enough_credits: Option<bool>,
+}
+
+impl Preflight {
+    pub fn new(command: &'static str, provider: &str, input: impl Into<String>) -> Self {
+        Self {
+            command,
+            provider: provider.to_string(),
+            input: input.into(),
+            valid: true,
+            error: None,
+            calls: 0,
+            credits: 0,
+            credits_remaining: None,
+            checked_at: None,
+            enough_credits: None,
+        }
+    }
+
+    /// The provider would reject the input with `error`.
+    #[must_use]
+    pub fn invalid(mut self, error: &I1Error) -> Self {
+        self.valid = false;
+        self.error = Some(error.to_string());
+        self
+    }
+}
+
+/// Add `provider`'s balance to `plan`, print it, and fail the way the real
+/// run would: an invalid query is invalid input, and a cost above the
+/// balance is out of credits.
+pub async fn report<P>(ctx: &Context, provider: &P, mut plan: Preflight) -> Result<()>
+where
+    P: Provider + ?Sized,
+{
+    if ctx.stix {
+        return Err(no_stix("--dry-run").into());
+    }
+    let cache = Cache::open(ctx.cache);
+    let (health, checked_at) = if let Some(cached) = cache.cached_health(provider.name()) {
+        (cached.value, cached.cached_at)
+    } else {
+        let health = credits::health(provider).await;
+        cache.store_health(&health);
+        (health, Some(Utc::now()))
+    };
+    if let Some(remaining) = health.credits_remaining {
+        plan.credits_remaining = Some(remaining);
+        plan.checked_at = checked_at;
+        plan.enough_credits = Some(i64::try_from(plan.credits).unwrap_or(i64::MAX) <= remaining);
+    }
+
+    match ctx.output_format {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&plan)?);
+        }
+        OutputFormat::Yaml => println!("{}", serde_yaml::to_string(&plan)?),
+        OutputFormat::Csv => print_csv(&plan)?,
+        OutputFormat::Pretty => print_pretty(ctx, &plan),
+    }
+
+    if !plan.valid {
+        return Err(Exit(exit::INVALID_INPUT).into());
+    }
+    if plan.enough_credits == Some(false) {
+        return Err(Exit(exit::RATE_LIMITED).into());
+    }
+    Ok(())
+}
+
+fn print_csv(plan: &Preflight) -> Result<()> {
+    let mut writer = csv::Writer::from_writer(std::io::stdout().lock());
+    writer.write_record([
+        "command",
+        "provider",
+        "input",
+        "valid",
+        "error",
+        "calls",
+        "credits",
+        "credits_remaining",
+        "checked_at",
+    ])?;
+    writer.write_record([
+        plan.command.to_string(),
+        plan.provider.clone(),
+        plan.input.clone(),
+        plan.valid.to_string(),
+        plan.error.clone().unwrap_or_default(),
+        plan.calls.to_string(),
+        plan.credits.to_string(),
+        plan.credits_remaining
+            .map(|n| n.to_string())
+            .unwrap_or_default(),
+        plan.checked_at.map(|t| t.to_rfc3339()).unwrap_or_default(),
+    ])?;
+    writer.flush()?;
+    Ok(())
+}
+
+fn print_pretty(ctx: &Context, plan: &Preflight) {
+    let label = |text: &str| format!("{text:<10}").bold();
+
+    if !ctx.quiet {
+        println!("{}", "Dry run: no billable calls were made".dimmed());
+        println!();
+    }
+    println!("{} {}", label("Command:"), plan.command);
+    println!("{} {}", label("Provider:"), plan.provider);
+    println!("{} {}", label("Input:"), plan.input);
+    match (&plan.error, plan.valid) {
+        (Some(error), false) => println!("{} {}", label("Invalid:"), error.red()),
+        (Some(note), true) => println!("{} {}", label("Note:"), note.yellow()),
+        (None, _) => {}
+    }
+    println!("{} up to {}", label("Calls:"), plan.calls);
+    println!("{} up to {}", label("Credits:"), plan.credits);
+
+    let balance = match (plan.credits_remaining, plan.checked_at) {
+        (Some(remaining), Some(at)) => format!("{remaining} (checked {})", cache::ago(at)),
+        (Some(remaining), None) => remaining.to_string(),
+        (None, _) => "unknown".to_string(),
+    };
+    println!("{} {balance}", label("Balance:"));
+
+    if plan.enough_credits == Some(false) {
+        println!();
+        println!("{}", "Not enough credits for the whole run.".yellow());
+    }
+}
@@ -1,52 +1,374 @@
 //! `i1 search` - Search threat intelligence database.
 
+use std::collections::BTreeMap;
+use std::time::Duration;
+
 use anyhow::Result;
+use chrono::{DateTime, Utc};
 use colored::Colorize;
-use tabled::{settings::Style, Table, Tabled};
+use futures_util::StreamExt;
+use indicatif::ProgressBar;
+use serde::Serialize;
+use tabled::Tabled;
+
+use super::preflight::{self, Preflight};
+use super::watch::{SearchDiff, Watch};
+use super::{Context, HOST_PROVIDERS};
+use crate::cache::{self, Cache};
+use crate::cli::args::{ColumnArgs, SearchArgs};
+use crate::cli::exit::{self, Exit};
+use crate::config::Config;
+use crate::history::{self, Entry, History};
+use crate::output::fields::{self, Field};
+use crate::output::filter;
+use crate::output::group::{self, GroupBy};
+use crate::output::severity::{self, Severity};
+use crate::output::{no_stix, print_stix, template, terminal, OutputFormat};
+use i1::{HostOrder, I1Error, OverlapStats, ResultSet, SearchResults};
+
+/// Rows shown in pretty mode for a single page
+pub const PRETTY_ROWS: usize = 25;
 
-use super::Context;
-use crate::cli::args::SearchArgs;
-use crate::output::OutputFormat;
+/// Hosts a provider returns per search page
+const PAGE_SIZE: usize = 100;
+
+/// Columns shown when `--fields` is not given
+const DEFAULT_FIELDS: &[Field] = &[Field::Ip, Field::Ports, Field::Org, Field::Country];
+
+/// What a multi-page fetch cost, for the footer.
+#[derive(Debug, Default)]
+struct PageStats {
+    pages: u32,
+    credits: u32,
+    credit_capped: bool,
+}
+
+/// What `--history` lists.
+#[derive(Serialize)]
+struct Listing {
+    history: Vec<Entry>,
+    saved: BTreeMap<String, String>,
+}
 
 #[derive(Tabled)]
-struct SearchRow {
-    #[tabled(rename = "IP")]
-    ip: String,
-    #[tabled(rename = "Ports")]
-    ports: String,
-    #[tabled(rename = "Org")]
-    org: String,
-    #[tabled(rename = "Country")]
-    country: String,
-}
-
-pub async fn execute(ctx: Context, args: SearchArgs) -> Result<()> {
+struct HistoryRow {
+    #[tabled(rename = "When")]
+    at: String,
+    #[tabled(rename = "Provider")]
+    provider: String,
+    #[tabled(rename = "Query")]
+    query: String,
+    #[tabled(rename = "Results")]
+    total: String,
+    #[tabled(rename = "Credits")]
+    credits: u32,
+}
+
+pub async fn execute(ctx: Context, mut args: SearchArgs) -> Result<()> {
+    if ctx.stix {
+        let listing = [
+            (args.history, "--history"),
+            (args.group_by.is_some(), "--group-by"),
+            (args.watch.watch.is_some(), "--watch"),
+        ];
+        if let Some((_, flag)) = listing.iter().find(|(set, _)| *set) {
+            return Err(no_stix(&format!("search {flag}")).into());
+        }
+    }
+    if args.history {
+        return print_history(&ctx);
+    }
+    if let Some(name) = &args.save {
+        return save(&ctx, name, &args.query);
+    }
+    if let Some((name, values)) = args.saved.split_first() {
+        args.query = recall(name, values)?;
+    }
+
+    if args.template.format_template.is_some() && args.watch.watch.is_some() {
+        anyhow::bail!("--format-template cannot be combined with --watch");
+    }
+    if args.columns.filter.is_some() && args.watch.watch.is_some() {
+        anyhow::bail!("--filter cannot be combined with --watch");
+    }
+
+    if args.dry_run {
+        return dry_run(&ctx, &args).await;
+    }
+
+    if let Some(secs) = args.watch.watch {
+        return watch(&ctx, &args, secs).await;
+    }
+
+    let (set, stats, cached_at) = if args.limit.is_some() || args.all_pages {
+        let (set, stats) = fetch_pages(&ctx, &args).await?;
+        (set, Some(stats), None)
+    } else if ctx.provider == "all" {
+        let (set, cached_at) = search_all(&ctx, &args).await?;
+        (set, None, cached_at)
+    } else {
+        let provider = ctx.search_provider()?;
+        let cached = Cache::open(ctx.cache)
+            .search(provider.name(), &args.query, args.page, || {
+                provider.search(&args.query, Some(args.page))
+            })
+            .await?;
+        let mut set = ResultSet::new();
+        set.add(cached.value);
+        (set, None, cached.cached_at)
+    };
+    let overlap = args.stats.then(|| set.stats());
+    let mut results = set.into_results(HostOrder::Seen);
+    remember(&ctx, &args, &results, stats.as_ref(), cached_at);
+    let hidden = retain_severity(&mut results, args.min_severity);
+    let unmatched = filter::retain(&mut results.results, args.columns.filter.as_ref());
+    let shown = if stats.is_some() {
+        results.results.len()
+    } else {
+        PRETTY_ROWS
+    };
+
+    if let Some(by) = args.group_by {
+        let narrowed = args.min_severity.is_some() || args.columns.filter.is_some();
+        print_groups(&ctx, &results, &args.query, by, narrowed)?;
+    } else if let Some(template) = &args.template.format_template {
+        if let Some(sort) = args.columns.sort {
+            fields::sort_hosts(&mut results.results, sort, args.columns.desc);
+        }
+        template::print(&results.results, template, &args.template.list_sep());
+    } else {
+        print_results(&ctx, &mut results, &args.query, &args.columns, shown)?;
+    }
+
+    if ctx.output_format == OutputFormat::Pretty
+        && !ctx.quiet
+        && args.template.format_template.is_none()
+    {
+        println!();
+        if hidden > 0 {
+            println!(
+                "{}",
+                format!("{hidden} hosts below --min-severity not shown").dimmed()
+            );
+        }
+        print_unmatched(unmatched);
+        if let Some(at) = cached_at {
+            println!("{}", format!("(cached {})", cache::ago(at)).dimmed());
+        }
+        if let Some(overlap) = &overlap {
+            print_overlap(overlap);
+        }
+        print_footer(stats.as_ref(), &results, &args);
+    } else if let Some(overlap) = &overlap {
+        eprintln!("{}", serde_json::to_string(overlap)?);
+    }
+
+    if results.results.is_empty() {
+        return Err(Exit(exit::NOT_FOUND).into());
+    }
+    Ok(())
+}
+
+/// `--watch`: search every `secs` seconds and print what changed.
+async fn watch(ctx: &Context, args: &SearchArgs, secs: u64) -> Result<()> {
     let provider = ctx.search_provider()?;
+    let (provider, query, page) = (&provider, args.query.as_str(), args.page);
+    let watch =
+        Watch::new(ctx, query, secs, &args.watch).credits(page_cost(query, page), args.max_credits);
+    let notifier = watch.notifier();
+    watch
+        .run(
+            move || async move {
+                let results = provider.search(query, Some(page)).await?;
+                for host in &results.results {
+                    notifier.check(host, "watch");
+                }
+                Ok(results)
+            },
+            SearchDiff::new,
+            |results| {
+                format!(
+                    "{} results, {} hosts on page {page}",
+                    results.total,
+                    results.results.len()
+                )
+            },
+        )
+        .await
+}
 
-    let results = provider.search(&args.query, Some(args.page)).await?;
+/// Add the search to the history, with what it cost: nothing when it
+/// came from the cache.
+fn remember(
+    ctx: &Context,
+    args: &SearchArgs,
+    results: &SearchResults,
+    stats: Option<&PageStats>,
+    cached_at: Option<DateTime<Utc>>,
+) {
+    let credits = match (stats, cached_at) {
+        (Some(stats), _) => stats.credits,
+        (None, Some(_)) => 0,
+        (None, None) => page_cost(&args.query, args.page),
+    };
+    history::record(
+        ctx,
+        &results.provider,
+        &args.query,
+        Some(results.total),
+        credits,
+    );
+}
+
+/// `--history`: the newest searches and counts, and the saved queries.
+fn print_history(ctx: &Context) -> Result<()> {
+    let listing = Listing {
+        history: History::open()?.recent(PRETTY_ROWS),
+        saved: Config::load()?.queries,
+    };
 
     match ctx.output_format {
         OutputFormat::Json => {
-            println!("{}", serde_json::to_string_pretty(&results)?);
-        }
-        OutputFormat::Yaml => {
-            println!("{}", serde_yaml::to_string(&results)?);
+            println!("{}", serde_json::to_string_pretty(&listing)?);
         }
+        OutputFormat::Yaml => println!("{}", serde_yaml::to_string(&listing)?),
         OutputFormat::Csv => {
-            println!("ip,ports,org,country");
-            for host in &results.results {
-                let ports: Vec<String> = host
-                    .ports
+            let mut writer = csv::Writer::from_writer(std::io::stdout());
+            writer.write_record(["at", "provider", "query", "total", "credits"])?;
+            for entry in &listing.history {
+                writer.write_record([
+                    entry.at.to_rfc3339(),
+                    entry.provider.clone(),
+                    entry.query.clone(),
+                    entry.total.map(|t| t.to_string()).unwrap_or_default(),
+                    entry.credits.to_string(),
+                ])?;
+            }
+            writer.flush()?;
+        }
+        OutputFormat::Pretty => {
+            if listing.history.is_empty() {
+                if !ctx.quiet {
+                    println!("No searches yet.");
+                }
+            } else {
+                let rows: Vec<HistoryRow> = listing
+                    .history
                     .iter()
-                    .map(std::string::ToString::to_string)
+                    .rev()
+                    .map(|entry| HistoryRow {
+                        at: entry.at.format("%Y-%m-%d %H:%M UTC").to_string(),
+                        provider: entry.provider.clone(),
+                        query: entry.query.clone(),
+                        total: entry.total.map(|t| t.to_string()).unwrap_or_default(),
+                        credits: entry.credits,
+                    })
                     .collect();
-                println!(
-                    "{},\"{}\",{},{}",
-                    host.ip_str,
-                    ports.join(";"),
-                    host.org.as_deref().unwrap_or(""),
-                    host.location.country_code.as_deref().unwrap_or("")
-                );
+                println!("{}", terminal::table(&rows));
+            }
+            if !listing.saved.is_empty() {
+                println!();
+                println!("{}", "Saved queries".bold());
+                for (name, query) in &listing.saved {
+                    println!("  {name:<20} {query}");
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `--save`: keep `query` in the config file under `name`.
+fn save(ctx: &Context, name: &str, query: &str) -> Result<()> {
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return Err(I1Error::InvalidQuery(format!(
+            "\"{name}\" can't name a saved query: use one word"
+        ))
+        .into());
+    }
+    let mut config = Config::load()?;
+    let replaced = config.queries.insert(name.to_string(), query.to_string());
+    config.save()?;
+
+    if !ctx.quiet {
+        let verb = if replaced.is_some() {
+            "Replaced"
+        } else {
+            "Saved"
+        };
+        println!("{verb} {name}: {query}");
+        println!(
+            "{}",
+            format!("Run it with: i1 search --saved {name}").dimmed()
+        );
+    }
+    Ok(())
+}
+
+/// `--saved`: the query saved under `name`, with its placeholders filled
+/// from `values`.
+fn recall(name: &str, values: &[String]) -> Result<String> {
+    let mut saved = Config::load()?.queries;
+    let Some(query) = saved.remove(name) else {
+        let names: Vec<String> = saved.into_keys().collect();
+        let names = if names.is_empty() {
+            "none".to_string()
+        } else {
+            names.join(", ")
+        };
+        return Err(I1Error::InvalidQuery(format!(
+            "no saved query named {name}\nSaved queries: {names}"
+        ))
+        .into());
+    };
+    Ok(history::fill(&query, values)?)
+}
+
+/// Render search results in the selected format.
+///
+/// Shared with other commands that produce search results (e.g. `i1 vuln search`).
+pub fn print_results(
+    ctx: &Context,
+    results: &mut SearchResults,
+    query: &str,
+    columns: &ColumnArgs,
+    shown: usize,
+) -> Result<()> {
+    let unmatched = filter::retain(&mut results.results, columns.filter.as_ref());
+    if let Some(sort) = columns.sort {
+        fields::sort_hosts(&mut results.results, sort, columns.desc);
+    }
+
+    let selected = if columns.fields.is_empty() {
+        DEFAULT_FIELDS
+    } else {
+        &columns.fields
+    };
+
+    match ctx.output_format {
+        _ if ctx.stix => print_stix(&results.results)?,
+        OutputFormat::Json | OutputFormat::Yaml => {
+            let mut value = serde_json::to_value(&results)?;
+            if !columns.fields.is_empty() {
+                value["results"] = results
+                    .results
+                    .iter()
+                    .map(|host| fields::project(host, selected))
+                    .collect();
+            }
+
+            if ctx.output_format == OutputFormat::Json {
+                println!("{}", serde_json::to_string_pretty(&value)?);
+            } else {
+                println!("{}", serde_yaml::to_string(&value)?);
+            }
+        }
+        OutputFormat::Csv => {
+            fields::write_csv(&results.results, selected)?;
+        }
+        OutputFormat::Pretty if ctx.quiet => {
+            if !results.results.is_empty() {
+                println!("{}", fields::table(results.results.iter(), selected));
             }
         }
         OutputFormat::Pretty => {
@@ -59,7 +381,7 @@ pub async fn execute(ctx: Context, args: SearchArgs) -> Result<()> {
                     results.total.to_string().cyan()
                 );
             }
-            println!("{} {}", "Query:".bold(), args.query.dimmed());
+            println!("{} {}", "Query:".bold(), query.dimmed());
             println!();
 
             if results.results.is_empty() {
@@ -67,56 +389,352 @@ pub async fn execute(ctx: Context, args: SearchArgs) -> Result<()> {
             } else {
                 println!("{}", "Results:".bold().underline());
 
-                let rows: Vec<SearchRow> = results
-                    .results
-                    .iter()
-                    .take(25)
-                    .map(|host| {
-                        let ports: Vec<String> = host
-                            .ports
-                            .iter()
-                            .map(std::string::ToString::to_string)
-                            .collect();
-                        SearchRow {
-                            ip: host.ip_str.clone(),
-                            ports: ports.join(", "),
-                            org: host
-                                .org
-                                .clone()
-                                .unwrap_or_default()
-                                .chars()
-                                .take(30)
-                                .collect(),
-                            country: host.location.country_code.clone().unwrap_or_default(),
-                        }
-                    })
-                    .collect();
-
-                let table = Table::new(&rows).with(Style::rounded()).to_string();
+                let table = fields::table(results.results.iter().take(shown), selected);
                 println!("{table}");
 
-                if results.results.len() > 25 {
+                if results.results.len() > shown {
                     println!();
                     println!(
                         "{}",
-                        format!("... and {} more results", results.results.len() - 25).dimmed()
+                        format!("... and {} more results", results.results.len() - shown).dimmed()
                     );
                 }
             }
+            if unmatched > 0 {
+                println!();
+                print_unmatched(unmatched);
+            }
+        }
+    }
 
-            println!();
-            if args.page == 1 && results.total > 100 {
+    Ok(())
+}
+
+/// Say how many hosts `--filter` left out, if any.
+fn print_unmatched(count: usize) {
+    if count == 0 {
+        return;
+    }
+    println!(
+        "{}",
+        format!("{count} hosts not matching --filter not shown").dimmed()
+    );
+}
+
+/// Print `results` counted per `by` rather than host by host.
+fn print_groups(
+    ctx: &Context,
+    results: &SearchResults,
+    query: &str,
+    by: GroupBy,
+    filtered: bool,
+) -> Result<()> {
+    // Facets count every match rather than the pages fetched, but know
+    // nothing of --min-severity or --filter
+    let facets = results
+        .facets
+        .as_ref()
+        .filter(|_| !filtered)
+        .and_then(|facets| group::from_facets(facets, by, &results.results));
+    let (groups, total, of) = facets.map_or_else(
+        || {
+            (
+                group::group(&results.results, by),
+                results.results.len() as u64,
+                "hosts fetched",
+            )
+        },
+        |groups| (groups, results.total, "results"),
+    );
+
+    match ctx.output_format {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&groups)?);
+        }
+        OutputFormat::Yaml => println!("{}", serde_yaml::to_string(&groups)?),
+        OutputFormat::Csv => group::write_csv(&groups, by, total)?,
+        OutputFormat::Pretty => {
+            if !ctx.quiet {
+                println!("{} {}", "Query:".bold(), query.dimmed());
                 println!(
-                    "{}",
-                    format!(
-                        "Tip: Use --page 2 to see more results (page 1 of {})",
-                        (results.total / 100) + 1
-                    )
-                    .dimmed()
+                    "{} {} {of}, by {}",
+                    "Grouped:".bold(),
+                    total.to_string().cyan(),
+                    by.name()
                 );
+                println!();
+            }
+            if !groups.is_empty() {
+                println!("{}", group::table(&groups, by, total));
             }
         }
     }
 
     Ok(())
 }
+
+/// Put each host's CVEs in severity order and, with `--min-severity`, drop
+/// the ones below it and the hosts left without any. Returns the hosts dropped.
+fn retain_severity(results: &mut SearchResults, min: Option<Severity>) -> usize {
+    for host in &mut results.results {
+        severity::retain(host, min);
+    }
+    let before = results.results.len();
+    if min.is_some() {
+        results.results.retain(|host| !host.vulns.is_empty());
+    }
+    before - results.results.len()
+}
+
+/// Pull pages until the host limit, the results or the credit budget run out.
+async fn fetch_pages(ctx: &Context, args: &SearchArgs) -> Result<(ResultSet, PageStats)> {
+    if ctx.provider == "all" {
+        return Err(ctx.one_provider("Paged and watched searches", &HOST_PROVIDERS));
+    }
+    let client = ctx.client()?;
+    let limit = args.limit.unwrap_or(usize::MAX);
+
+    let spinner = (ctx.output_format == OutputFormat::Pretty && !ctx.quiet).then(|| {
+        let pb = ProgressBar::new_spinner();
+        pb.set_message(format!("Searching {}...", args.query));
+        pb.enable_steady_tick(Duration::from_millis(100));
+        pb
+    });
+
+    let mut pages = Box::pin(client.search_iter(args.query.clone(), args.page));
+    let mut set = ResultSet::new().limit(limit);
+    let mut stats = PageStats::default();
+
+    loop {
+        let cost = page_cost(&args.query, args.page + stats.pages);
+        if stats.credits + cost > args.max_credits {
+            stats.credit_capped = true;
+            break;
+        }
+
+        let page = match pages.next().await {
+            Some(Ok(page)) => page,
+            Some(Err(e)) if stats.pages > 0 => {
+                // Keep what we already have rather than throwing it away
+                if let Some(pb) = &spinner {
+                    pb.suspend(|| eprintln!("{} {e}, stopping early", "Warning:".yellow()));
+                }
+                break;
+            }
+            Some(Err(e)) => return Err(e.into()),
+            None => break,
+        };
+
+        stats.pages += 1;
+        stats.credits += cost;
+        let key = cache::search_key(&args.query, page.page);
+        crate::workspace::keep_search(&page.provider, &key, &page);
+        set.add(page);
+
+        if let Some(pb) = &spinner {
+            pb.set_message(format!(
+                "Fetched {} page(s), {} hosts...",
+                stats.pages,
+                set.len()
+            ));
+        }
+
+        if set.len() >= limit {
+            break;
+        }
+    }
+
+    if let Some(pb) = &spinner {
+        pb.finish_and_clear();
+    }
+
+    if stats.pages == 0 {
+        anyhow::bail!(
+            "--max-credits {} is not enough to fetch a single page",
+            args.max_credits
+        );
+    }
+
+    Ok((set, stats))
+}
+
+/// `--dry-run`: check the query, and count the pages [`fetch_pages`] would
+/// fetch and the credits they'd cost.
+async fn dry_run(ctx: &Context, args: &SearchArgs) -> Result<()> {
+    if ctx.provider == "all" {
+        return Err(ctx.one_provider("Dry runs", &HOST_PROVIDERS));
+    }
+    let provider = ctx.search_provider()?;
+
+    let checked = if provider.name() == "shodan" {
+        i1::parse_query(&args.query).map(drop)
+    } else {
+        i1::validate_query(&args.query)
+    };
+    let mut plan = Preflight::new("search", provider.name(), &args.query);
+    match checked {
+        Ok(()) => {
+            let (pages, credits) = plan_pages(args);
+            plan.calls = pages.into();
+            plan.credits = credits.into();
+            if pages == 0 {
+                plan.error = Some(format!(
+                    "--max-credits {} is not enough to fetch a single page",
+                    args.max_credits
+                ));
+            }
+        }
+        Err(e) => plan = plan.invalid(&e),
+    }
+
+    preflight::report(ctx, provider.as_ref(), plan).await
+}
+
+/// Pages a search would fetch at most, and what they'd cost: enough for
+/// `--limit`, all of them with `--all-pages`, but never past
+/// `--max-credits`. Every page after the first costs a credit.
+fn plan_pages(args: &SearchArgs) -> (u32, u32) {
+    let first = page_cost(&args.query, args.page);
+    if args.limit.is_none() && !args.all_pages {
+        return (1, first);
+    }
+    if first > args.max_credits {
+        return (0, 0);
+    }
+
+    let wanted = args.limit.map_or(u32::MAX, |limit| {
+        u32::try_from(limit.div_ceil(PAGE_SIZE)).unwrap_or(u32::MAX)
+    });
+    let pages = wanted
+        .min((args.max_credits - first).saturating_add(1))
+        .max(1);
+    (pages, first + pages - 1)
+}
+
+/// `--provider all`: one page from every configured provider, merged by IP.
+///
+/// A provider that fails only costs its share of the results, unless they
+/// all fail.
+async fn search_all(
+    ctx: &Context,
+    args: &SearchArgs,
+) -> Result<(ResultSet, Option<DateTime<Utc>>)> {
+    let client = ctx.client()?;
+    let cache = Cache::open(ctx.cache);
+    let mut names = client.providers();
+    names.sort_unstable();
+
+    let searches = names.iter().map(|name| {
+        cache.search(name, &args.query, args.page, || {
+            client.search_with(&args.query, Some(args.page), name)
+        })
+    });
+    let answers = futures_util::future::join_all(searches).await;
+
+    let mut set = ResultSet::new();
+    let mut cached_at: Option<DateTime<Utc>> = None;
+    let mut first_error = None;
+    let mut answered = 0;
+
+    for (name, answer) in names.iter().zip(answers) {
+        match answer {
+            Ok(cached) => {
+                answered += 1;
+                // The oldest answer is the one worth mentioning
+                if let Some(at) = cached.cached_at {
+                    cached_at = Some(cached_at.map_or(at, |seen| seen.min(at)));
+                }
+                set.add(cached.value);
+            }
+            Err(e) => {
+                if names.len() > 1 && ctx.output_format == OutputFormat::Pretty {
+                    eprintln!("{} {name} search failed: {e}", "Warning:".yellow());
+                }
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+
+    match first_error {
+        Some(e) if answered == 0 => Err(e.into()),
+        _ => Ok((set, cached_at)),
+    }
+}
+
+/// Query credits one search page costs.
+///
+/// Shodan bills every page after the first, and the first page too once the
+/// query uses a filter.
+fn page_cost(query: &str, page: u32) -> u32 {
+    u32::from(page > 1 || query.contains(':'))
+}
+
+/// `--stats`: each provider's share of the hosts, and how many were
+/// returned by more than one.
+fn print_overlap(overlap: &OverlapStats) {
+    println!(
+        "{}",
+        format!(
+            "{} unique hosts, {} returned by 2+ providers",
+            overlap.hosts, overlap.overlapping
+        )
+        .dimmed()
+    );
+    for provider in &overlap.providers {
+        println!(
+            "{}",
+            format!(
+                "  {}: {} hosts ({} only here) of {} results, {} page(s)",
+                provider.provider,
+                provider.hosts,
+                provider.exclusive,
+                provider.total,
+                provider.pages
+            )
+            .dimmed()
+        );
+    }
+}
+
+fn print_footer(stats: Option<&PageStats>, results: &SearchResults, args: &SearchArgs) {
+    let Some(stats) = stats else {
+        if args.page == 1 && results.total > 100 {
+            println!(
+                "{}",
+                format!(
+                    "Tip: Use --page 2 to see more results (page 1 of {})",
+                    (results.total / 100) + 1
+                )
+                .dimmed()
+            );
+        }
+        return;
+    };
+
+    let hosts = results.results.len();
+    let pages = if stats.pages == 1 { "page" } else { "pages" };
+    let credits = if stats.credits == 1 {
+        "credit"
+    } else {
+        "credits"
+    };
+    println!(
+        "{}",
+        format!(
+            "Fetched {} {pages}, {} {credits} used, {hosts} unique hosts",
+            stats.pages, stats.credits
+        )
+        .dimmed()
+    );
+
+    if stats.credit_capped {
+        println!(
+            "{}",
+            format!(
+                "Stopped at --max-credits {}. Raise it to fetch more.",
+                args.max_credits
+            )
+            .yellow()
+        );
+    }
+}
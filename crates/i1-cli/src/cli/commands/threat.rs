@@ -31,8 +31,8 @@ pub async fn execute(ctx: &Context, args: &ThreatArgs) -> Result<()> {
                 None
             }
         },
-        Err(_) => {
-            println!("{} No API key configured, skipping lookup", "⚠".yellow());
+        Err(e) => {
+            println!("{} {}, skipping lookup", "⚠".yellow(), super::headline(&e));
             println!();
             None
         }
@@ -165,8 +165,8 @@ pub async fn lookup_only(ctx: &Context, ip: &str) -> Result<()> {
                 None
             }
         },
-        Err(_) => {
-            println!("{} No API key configured, skipping Shodan lookup", "⚠".yellow());
+        Err(e) => {
+            println!("{} {}, skipping lookup", "⚠".yellow(), super::headline(&e));
             println!();
             None
         }
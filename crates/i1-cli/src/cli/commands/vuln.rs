@@ -0,0 +1,158 @@
+//! `i1 vuln` - CVE lookups for hosts and hosts for CVEs.
+
+use anyhow::Result;
+use colored::{ColoredString, Colorize};
+use tabled::Tabled;
+
+use super::{search, Context};
+use crate::cli::args::{VulnArgs, VulnCommands};
+use crate::output::{terminal, OutputFormat};
+use i1::VulnInfo;
+use i1_providers::{parse_cve_id, ThreatLevel};
+
+#[derive(Tabled)]
+struct VulnRow {
+    #[tabled(rename = "CVE")]
+    cve: String,
+    #[tabled(rename = "CVSS")]
+    cvss: String,
+    #[tabled(rename = "Severity")]
+    severity: String,
+    #[tabled(rename = "Verified")]
+    verified: String,
+    #[tabled(rename = "Summary")]
+    summary: String,
+}
+
+pub async fn execute(ctx: Context, args: VulnArgs) -> Result<()> {
+    match args.command {
+        VulnCommands::Host { ip } => host(&ctx, &ip).await,
+        VulnCommands::Search { cve, columns } => {
+            // Check the id before the provider spends a query credit on it
+            let cve = parse_cve_id(&cve)?;
+            let provider = ctx.vuln_provider()?;
+            let mut results = provider.hosts_with_cve(&cve).await?;
+            search::print_results(
+                &ctx,
+                &mut results,
+                &format!("vuln:{cve}"),
+                &columns,
+                search::PRETTY_ROWS,
+            )
+        }
+    }
+}
+
+async fn host(ctx: &Context, ip: &str) -> Result<()> {
+    let provider = ctx.vuln_provider()?;
+    let mut vulns = provider.vulns_for_ip(ip).await?;
+
+    // Most severe first; unscored CVEs last, by id
+    vulns.sort_by(|a, b| {
+        b.cvss
+            .unwrap_or(-1.0)
+            .total_cmp(&a.cvss.unwrap_or(-1.0))
+            .then_with(|| a.cve.cmp(&b.cve))
+    });
+
+    match ctx.output_format {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&vulns)?);
+        }
+        OutputFormat::Yaml => {
+            println!("{}", serde_yaml::to_string(&vulns)?);
+        }
+        OutputFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(std::io::stdout().lock());
+            writer.write_record(["ip", "cve", "cvss", "severity", "verified", "summary"])?;
+            for vuln in &vulns {
+                writer.write_record([
+                    ip,
+                    &vuln.cve,
+                    &vuln.cvss.map(|c| format!("{c:.1}")).unwrap_or_default(),
+                    severity_name(vuln),
+                    &vuln.verified.to_string(),
+                    vuln.summary.as_deref().unwrap_or(""),
+                ])?;
+            }
+            writer.flush()?;
+        }
+        OutputFormat::Pretty => print_vulns_pretty(ip, &vulns, ctx),
+    }
+
+    Ok(())
+}
+
+fn print_vulns_pretty(ip: &str, vulns: &[VulnInfo], ctx: &Context) {
+    if ctx.no_color {
+        println!("Host: {ip}");
+    } else {
+        println!("{} {}", "Host:".bold(), ip.cyan().bold());
+    }
+    println!();
+
+    if vulns.is_empty() {
+        if ctx.no_color {
+            println!("No known vulnerabilities");
+        } else {
+            println!("{}", "No known vulnerabilities".green());
+        }
+        return;
+    }
+
+    let rows: Vec<VulnRow> = vulns
+        .iter()
+        .map(|v| VulnRow {
+            cve: v.cve.clone(),
+            cvss: v.cvss.map(|c| format!("{c:.1}")).unwrap_or_default(),
+            severity: if ctx.no_color {
+                severity_name(v).to_string()
+            } else {
+                severity_colored(v).to_string()
+            },
+            verified: if v.verified { "yes" } else { "" }.to_string(),
+            summary: v.summary.clone().unwrap_or_default(),
+        })
+        .collect();
+
+    let table = terminal::table(&rows);
+    println!("{table}");
+
+    let critical = vulns
+        .iter()
+        .filter(|v| level(v) == ThreatLevel::Critical)
+        .count();
+    println!();
+    println!(
+        "{}",
+        format!("{} CVEs, {critical} critical", vulns.len()).dimmed()
+    );
+}
+
+fn level(vuln: &VulnInfo) -> ThreatLevel {
+    vuln.cvss.map_or(ThreatLevel::None, ThreatLevel::from_cvss)
+}
+
+fn severity_name(vuln: &VulnInfo) -> &'static str {
+    if vuln.cvss.is_none() {
+        return "unknown";
+    }
+    match level(vuln) {
+        ThreatLevel::Critical => "critical",
+        ThreatLevel::High => "high",
+        ThreatLevel::Medium => "medium",
+        ThreatLevel::Low => "low",
+        ThreatLevel::None => "none",
+    }
+}
+
+fn severity_colored(vuln: &VulnInfo) -> ColoredString {
+    let name = severity_name(vuln);
+    match level(vuln) {
+        ThreatLevel::Critical => name.red().bold(),
+        ThreatLevel::High => name.red(),
+        ThreatLevel::Medium => name.yellow(),
+        ThreatLevel::Low => name.blue(),
+        ThreatLevel::None => name.dimmed(),
+    }
+}
@@ -0,0 +1,567 @@
+//! `--watch` for `i1 host` and `i1 search`: re-run on an interval and
+//! report only what changed since the last check.
+
+use std::collections::BTreeSet;
+use std::future::Future;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use colored::Colorize;
+use serde::Serialize;
+
+use super::Context;
+use crate::cli::args::WatchArgs;
+use crate::output::OutputFormat;
+use i1::notify::Notifier;
+use i1::{HostDiff, I1Error, SearchResults};
+
+/// IPs listed per line before the rest are summed up
+const LISTED_HOSTS: usize = 10;
+
+/// A difference between two checks that can be reported.
+pub trait Change: Serialize {
+    /// Nothing changed.
+    fn is_empty(&self) -> bool;
+
+    /// One line per change, e.g. "new port 3389 on 203.0.113.5".
+    fn lines(&self, target: &str) -> Vec<String>;
+}
+
+impl Change for HostDiff {
+    fn is_empty(&self) -> bool {
+        Self::is_empty(self)
+    }
+
+    fn lines(&self, target: &str) -> Vec<String> {
+        let mut lines = Vec::new();
+        lines.extend(
+            self.opened_ports
+                .iter()
+                .map(|port| format!("new port {port} on {target}")),
+        );
+        lines.extend(
+            self.closed_ports
+                .iter()
+                .map(|port| format!("port {port} closed on {target}")),
+        );
+        lines.extend(
+            self.new_vulns
+                .iter()
+                .map(|cve| format!("new vulnerability {cve} on {target}")),
+        );
+        lines.extend(
+            self.resolved_vulns
+                .iter()
+                .map(|cve| format!("{cve} no longer reported on {target}")),
+        );
+        lines.extend(self.changed_services.iter().map(|s| {
+            let label = |l: &Option<String>| l.clone().unwrap_or_else(|| "unknown".to_string());
+            format!(
+                "port {}/{} on {target} changed: {} -> {}",
+                s.port,
+                s.transport,
+                label(&s.before),
+                label(&s.after)
+            )
+        }));
+        lines.extend(
+            self.added_hostnames
+                .iter()
+                .map(|name| format!("new hostname {name} on {target}")),
+        );
+        lines.extend(
+            self.removed_hostnames
+                .iter()
+                .map(|name| format!("hostname {name} gone from {target}")),
+        );
+        lines.extend(
+            self.added_tags
+                .iter()
+                .map(|tag| format!("tag '{tag}' added to {target}")),
+        );
+        lines.extend(
+            self.removed_tags
+                .iter()
+                .map(|tag| format!("tag '{tag}' removed from {target}")),
+        );
+        lines
+    }
+}
+
+/// Hosts that started or stopped matching a search.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SearchDiff {
+    pub total_before: u64,
+    pub total_after: u64,
+    /// IPs in this page of results that weren't in the last one
+    pub new_hosts: Vec<String>,
+    /// IPs in the last page of results that aren't in this one
+    pub gone_hosts: Vec<String>,
+}
+
+impl SearchDiff {
+    pub fn new(old: &SearchResults, new: &SearchResults) -> Self {
+        let ips = |results: &SearchResults| -> BTreeSet<String> {
+            results.results.iter().map(|h| h.ip_str.clone()).collect()
+        };
+        let (old_ips, new_ips) = (ips(old), ips(new));
+
+        Self {
+            total_before: old.total,
+            total_after: new.total,
+            new_hosts: new_ips.difference(&old_ips).cloned().collect(),
+            gone_hosts: old_ips.difference(&new_ips).cloned().collect(),
+        }
+    }
+}
+
+impl Change for SearchDiff {
+    fn is_empty(&self) -> bool {
+        // The total alone drifts all the time; only report hosts coming and going
+        self.new_hosts.is_empty() && self.gone_hosts.is_empty()
+    }
+
+    fn lines(&self, target: &str) -> Vec<String> {
+        let mut lines = Vec::new();
+        if !self.new_hosts.is_empty() {
+            lines.push(format!(
+                "{} query '{target}': {}",
+                counted(self.new_hosts.len(), "new host matches", "new hosts match"),
+                listed(&self.new_hosts)
+            ));
+        }
+        if !self.gone_hosts.is_empty() {
+            lines.push(format!(
+                "{} query '{target}': {}",
+                counted(
+                    self.gone_hosts.len(),
+                    "host no longer matches",
+                    "hosts no longer match"
+                ),
+                listed(&self.gone_hosts)
+            ));
+        }
+        lines
+    }
+}
+
+/// "1 new host matches" / "12 new hosts match".
+fn counted(n: usize, one: &str, many: &str) -> String {
+    format!("{n} {}", if n == 1 { one } else { many })
+}
+
+/// The first few IPs, then how many more there are.
+fn listed(ips: &[String]) -> String {
+    let shown = ips
+        .iter()
+        .take(LISTED_HOSTS)
+        .cloned()
+        .collect::<Vec<_>>()
+        .join(", ");
+    if ips.len() > LISTED_HOSTS {
+        format!("{shown} and {} more", ips.len() - LISTED_HOSTS)
+    } else {
+        shown
+    }
+}
+
+/// One check that found changes, as printed in JSON mode and piped to `--notify-cmd`.
+#[derive(Debug, Serialize)]
+struct Event<'a, D> {
+    at: DateTime<Utc>,
+    target: &'a str,
+    #[serde(flatten)]
+    diff: &'a D,
+}
+
+/// What a watch session did, printed when it ends.
+#[derive(Debug, Default, Serialize)]
+struct Summary {
+    checks: u32,
+    /// Checks that found something
+    changes: u32,
+    errors: u32,
+    credits: u32,
+    seconds: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stopped: Option<String>,
+}
+
+/// A watch session over one host or query.
+pub struct Watch<'a> {
+    ctx: &'a Context,
+    target: &'a str,
+    interval: Duration,
+    notify_cmd: Option<&'a str>,
+    /// Where `--notify-url` alerts go
+    notifier: Notifier,
+    /// Query credits each check spends
+    cost: u32,
+    max_credits: u32,
+}
+
+impl<'a> Watch<'a> {
+    pub fn new(ctx: &'a Context, target: &'a str, secs: u64, args: &'a WatchArgs) -> Self {
+        Self {
+            ctx,
+            target,
+            interval: Duration::from_secs(secs),
+            notify_cmd: args.notify_cmd.as_deref(),
+            notifier: args.notify.notifier(),
+            cost: 0,
+            max_credits: u32::MAX,
+        }
+    }
+
+    /// Each check spends `cost` query credits; stop before spending more than `max`.
+    #[must_use]
+    pub const fn credits(mut self, cost: u32, max: u32) -> Self {
+        self.cost = cost;
+        self.max_credits = max;
+        self
+    }
+
+    /// Fetches hand the hosts they find here, so `--notify-url` hears
+    /// about those that become high risk.
+    pub const fn notifier(&self) -> &Notifier {
+        &self.notifier
+    }
+
+    /// Check with `fetch` until Ctrl-C, reporting `diff` of each result
+    /// against the last. `describe` sums up the first result.
+    pub async fn run<T, D, F, Fut>(
+        &self,
+        mut fetch: F,
+        diff: impl Fn(&T, &T) -> D,
+        describe: impl Fn(&T) -> String,
+    ) -> Result<()>
+    where
+        T: Send + Sync,
+        F: FnMut() -> Fut + Send,
+        Fut: Future<Output = Result<T>> + Send,
+        D: Change + Sync,
+    {
+        if self.cost > 0 {
+            self.warn_cost();
+        }
+
+        let started = Instant::now();
+        let mut summary = Summary::default();
+        let mut last: Option<T> = None;
+
+        loop {
+            if summary.credits + self.cost > self.max_credits {
+                summary.stopped = Some(format!(
+                    "Stopped at --max-credits {}. Raise it to keep watching.",
+                    self.max_credits
+                ));
+                break;
+            }
+
+            let Some(result) = interruptible(fetch()).await else {
+                break;
+            };
+            summary.checks += 1;
+            summary.credits += self.cost;
+
+            let mut wait = self.interval;
+            match result {
+                Ok(current) => {
+                    match &last {
+                        None => self.print_start(&describe(&current)),
+                        Some(previous) => {
+                            let changes = diff(previous, &current);
+                            if !changes.is_empty() {
+                                summary.changes += 1;
+                                self.report(&changes).await?;
+                            }
+                        }
+                    }
+                    last = Some(current);
+                }
+                // Nothing to compare against yet, so there's nothing to watch
+                Err(e) if last.is_none() => return Err(e),
+                Err(e) => {
+                    summary.errors += 1;
+                    match e.downcast_ref::<I1Error>() {
+                        Some(I1Error::RateLimited { retry_after }) => {
+                            wait = retry_after
+                                .map_or(self.interval * 2, Duration::from_secs)
+                                .max(self.interval);
+                        }
+                        Some(I1Error::InsufficientCredits { .. }) => {
+                            summary.stopped = Some(format!("Stopped: {e}"));
+                            break;
+                        }
+                        Some(I1Error::Unauthorized) => return Err(e),
+                        _ => {}
+                    }
+                    eprintln!(
+                        "{} {e}, checking again in {}",
+                        "Warning:".yellow().bold(),
+                        elapsed(wait.as_secs())
+                    );
+                }
+            }
+
+            if interruptible(tokio::time::sleep(wait)).await.is_none() {
+                break;
+            }
+        }
+
+        self.notifier.flush().await;
+        summary.seconds = started.elapsed().as_secs();
+        self.print_summary(&summary);
+        Ok(())
+    }
+
+    fn warn_cost(&self) {
+        let secs = self.interval.as_secs();
+        let per_day = u64::from(self.cost) * (86_400 / secs).max(1);
+        let credits = if self.cost == 1 { "credit" } else { "credits" };
+        eprintln!(
+            "{} each check spends {} query {credits} (~{per_day} a day every {}); \
+             watching stops at --max-credits {}",
+            "Warning:".yellow().bold(),
+            self.cost,
+            elapsed(secs),
+            self.max_credits
+        );
+    }
+
+    fn print_start(&self, description: &str) {
+        if self.ctx.output_format == OutputFormat::Json || self.ctx.quiet {
+            return;
+        }
+        println!(
+            "{} Watching {} every {}: {description}. Press Ctrl-C to stop.",
+            self.timestamp(Utc::now()),
+            self.target.bold(),
+            elapsed(self.interval.as_secs())
+        );
+    }
+
+    /// Print the changes and hand them to `--notify-cmd`.
+    async fn report<D: Change + Sync>(&self, changes: &D) -> Result<()> {
+        let at = Utc::now();
+        let event = serde_json::to_string(&Event {
+            at,
+            target: self.target,
+            diff: changes,
+        })?;
+
+        if self.ctx.output_format == OutputFormat::Json {
+            println!("{event}");
+        } else {
+            for line in changes.lines(self.target) {
+                println!("{} {line}", self.timestamp(at));
+            }
+        }
+
+        if let Some(cmd) = self.notify_cmd {
+            let cmd = cmd.to_string();
+            if let Err(e) = tokio::task::spawn_blocking(move || notify(&cmd, &event)).await? {
+                eprintln!("{} --notify-cmd failed: {e}", "Warning:".yellow().bold());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn print_summary(&self, summary: &Summary) {
+        if self.ctx.output_format == OutputFormat::Json {
+            println!("{}", serde_json::json!({ "summary": summary }));
+            return;
+        }
+        if self.ctx.quiet {
+            return;
+        }
+
+        println!();
+        if let Some(stopped) = &summary.stopped {
+            println!("{}", stopped.yellow());
+        }
+        let checks = if summary.checks == 1 {
+            "check"
+        } else {
+            "checks"
+        };
+        let credits = if summary.credits == 1 {
+            "credit"
+        } else {
+            "credits"
+        };
+        println!(
+            "{}",
+            format!(
+                "Watched {} for {}: {} {checks}, {} with changes, {} failed, {} {credits} used",
+                self.target,
+                elapsed(summary.seconds),
+                summary.checks,
+                summary.changes,
+                summary.errors,
+                summary.credits
+            )
+            .dimmed()
+        );
+    }
+
+    fn timestamp(&self, at: DateTime<Utc>) -> String {
+        let stamp = format!("[{}]", at.format("%Y-%m-%d %H:%M:%S UTC"));
+        if self.ctx.no_color {
+            stamp
+        } else {
+            stamp.dimmed().to_string()
+        }
+    }
+}
+
+/// Run `future` unless Ctrl-C comes first.
+async fn interruptible<T>(future: impl Future<Output = T>) -> Option<T> {
+    tokio::select! {
+        out = future => Some(out),
+        Ok(()) = tokio::signal::ctrl_c() => None,
+    }
+}
+
+/// Run `cmd` through the shell with `json` on stdin.
+fn notify(cmd: &str, json: &str) -> Result<()> {
+    let mut command = if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.args(["/C", cmd]);
+        command
+    } else {
+        let mut command = Command::new("sh");
+        command.args(["-c", cmd]);
+        command
+    };
+
+    let mut child = command.stdin(Stdio::piped()).spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(json.as_bytes())?;
+        stdin.write_all(b"\n")?;
+    }
+
+    let status = child.wait()?;
+    if !status.success() {
+        anyhow::bail!("`{cmd}` exited with {status}");
+    }
+    Ok(())
+}
+
+/// "42s", "5m 10s", "2h 05m".
+fn elapsed(secs: u64) -> String {
+    match secs {
+        0..=59 => format!("{secs}s"),
+        60..=3599 if secs % 60 == 0 => format!("{}m", secs / 60),
+        60..=3599 => format!("{}m {}s", secs / 60, secs % 60),
+        _ => format!("{}h {:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use i1_core::testing::host;
+    use serde_json::json;
+
+    #[test]
+    fn test_host_diff() {
+        let before = host(
+            "203.0.113.5",
+            json!({
+                "ports": [22, 80],
+                "vulns": ["CVE-2021-44228"],
+                "tags": ["cloud"],
+                "data": [
+                    {"port": 22, "product": "OpenSSH", "version": "8.2"},
+                    {"port": 80, "product": "nginx"}
+                ]
+            }),
+        );
+        let after = host(
+            "203.0.113.5",
+            json!({
+                "ports": [22, 3389],
+                "vulns": ["CVE-2024-6387"],
+                "tags": ["cloud"],
+                "data": [
+                    {"port": 22, "product": "OpenSSH", "version": "9.6"},
+                    {"port": 3389}
+                ]
+            }),
+        );
+
+        let diff = before.diff(&after);
+        assert_eq!(diff.opened_ports, [3389]);
+        assert_eq!(diff.closed_ports, [80]);
+        assert_eq!(diff.new_vulns, ["CVE-2024-6387"]);
+        assert_eq!(diff.resolved_vulns, ["CVE-2021-44228"]);
+        assert!(diff.added_tags.is_empty() && diff.removed_tags.is_empty());
+        assert_eq!(diff.changed_services.len(), 1);
+
+        let lines = diff.lines("203.0.113.5");
+        assert_eq!(lines[0], "new port 3389 on 203.0.113.5");
+        assert_eq!(lines[1], "port 80 closed on 203.0.113.5");
+        assert!(lines.contains(
+            &"port 22/tcp on 203.0.113.5 changed: OpenSSH 8.2 -> OpenSSH 9.6".to_string()
+        ));
+
+        assert!(Change::is_empty(&after.diff(&after)));
+    }
+
+    #[test]
+    fn test_search_diff() {
+        let results = |total: u64, ips: &[&str]| SearchResults {
+            provider: "shodan".to_string(),
+            total,
+            page: 1,
+            results: ips.iter().map(|ip| host(ip, json!({}))).collect(),
+            facets: None,
+        };
+
+        let old = results(2, &["192.0.2.1", "192.0.2.2"]);
+        let new = results(3, &["192.0.2.2", "192.0.2.3", "192.0.2.4"]);
+        let diff = SearchDiff::new(&old, &new);
+        assert_eq!(diff.new_hosts, ["192.0.2.3", "192.0.2.4"]);
+        assert_eq!(diff.gone_hosts, ["192.0.2.1"]);
+        assert_eq!(
+            diff.lines("port:3389"),
+            [
+                "2 new hosts match query 'port:3389': 192.0.2.3, 192.0.2.4",
+                "1 host no longer matches query 'port:3389': 192.0.2.1",
+            ]
+        );
+
+        // A moving total on its own isn't a change
+        assert!(Change::is_empty(&SearchDiff::new(
+            &old,
+            &results(5, &["192.0.2.2", "192.0.2.1"])
+        )));
+
+        let many: Vec<String> = (1..=12).map(|i| format!("192.0.2.{i}")).collect();
+        assert!(listed(&many).ends_with("192.0.2.10 and 2 more"));
+    }
+
+    #[test]
+    fn test_elapsed() {
+        assert_eq!(elapsed(42), "42s");
+        assert_eq!(elapsed(300), "5m");
+        assert_eq!(elapsed(310), "5m 10s");
+        assert_eq!(elapsed(7500), "2h 05m");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_notify() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("event.json");
+        notify(&format!("cat > '{}'", out.display()), r#"{"ok":true}"#).unwrap();
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "{\"ok\":true}\n");
+
+        assert!(notify("exit 3", "{}").is_err());
+    }
+}
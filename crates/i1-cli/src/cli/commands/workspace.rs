@@ -0,0 +1,397 @@
+//! `i1 workspace` - Keep an investigation's lookups together.
+//!
+//! `new` makes a workspace; with it active, `host`, `search` and `scan`
+//! add to it (see [`crate::workspace`]). `show` summarizes what it holds,
+//! and `export` turns that into a report to finish by hand.
+
+use std::fmt::Write as _;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use colored::Colorize;
+use serde::Serialize;
+use tabled::Tabled;
+
+use super::Context;
+use crate::cli::args::{WorkspaceArgs, WorkspaceCommands};
+use crate::output::{terminal, OutputFormat};
+use crate::workspace::{self, Finding, ReportFormat, Summary, Target, Workspace};
+
+/// Everything `workspace export --format json` writes.
+#[derive(Serialize)]
+struct Report {
+    summary: Summary,
+    targets: Vec<Target>,
+    hosts: Vec<i1::HostInfo>,
+}
+
+#[derive(Tabled)]
+struct WorkspaceRow {
+    #[tabled(rename = "Workspace")]
+    name: String,
+    #[tabled(rename = "Targets")]
+    targets: usize,
+    #[tabled(rename = "Hosts")]
+    hosts: usize,
+    #[tabled(rename = "Credits")]
+    credits: u64,
+    #[tabled(rename = "Last activity")]
+    last: String,
+}
+
+#[derive(Tabled)]
+struct ProviderRow {
+    #[tabled(rename = "Provider")]
+    provider: String,
+    #[tabled(rename = "Calls")]
+    calls: usize,
+    #[tabled(rename = "Failed")]
+    failed: usize,
+    #[tabled(rename = "Credits")]
+    credits: u64,
+    #[tabled(rename = "Answers")]
+    answers: usize,
+}
+
+#[derive(Tabled)]
+struct FindingRow {
+    #[tabled(rename = "Host")]
+    ip: String,
+    #[tabled(rename = "Risk")]
+    risk: String,
+    #[tabled(rename = "CVEs")]
+    vulns: String,
+    #[tabled(rename = "Ports")]
+    ports: String,
+    #[tabled(rename = "Organization")]
+    org: String,
+    #[tabled(rename = "Seen in")]
+    seen_in: String,
+}
+
+impl From<&Finding> for FindingRow {
+    fn from(finding: &Finding) -> Self {
+        Self {
+            ip: finding.ip.clone(),
+            risk: format!("{:.1}", finding.risk),
+            vulns: finding.vulns.join(", "),
+            ports: join(&finding.ports),
+            org: finding.org.clone().unwrap_or_default(),
+            seen_in: seen_in(finding),
+        }
+    }
+}
+
+pub fn execute(ctx: &Context, args: WorkspaceArgs) -> Result<()> {
+    match args.command {
+        WorkspaceCommands::New { name } => new(ctx, &name),
+        WorkspaceCommands::List => list(ctx),
+        WorkspaceCommands::Show { name, top } => {
+            show(ctx, &resolve(name.as_deref())?.summary(top)?)
+        }
+        WorkspaceCommands::Export { name, format, top } => {
+            export(&resolve(name.as_deref())?, format, top)
+        }
+    }
+}
+
+/// The workspace `name`, or the active one.
+fn resolve(name: Option<&str>) -> Result<Workspace> {
+    match name {
+        Some(name) => Workspace::open(&workspace::root()?, name),
+        None => workspace::active().ok_or_else(|| {
+            anyhow::anyhow!(
+                "No workspace is active. Name one, or activate one with \
+                 --workspace <NAME> or I1_WORKSPACE"
+            )
+        }),
+    }
+}
+
+fn new(ctx: &Context, name: &str) -> Result<()> {
+    let workspace = Workspace::create(&workspace::root()?, name)?;
+    if ctx.quiet {
+        return Ok(());
+    }
+    println!(
+        "Created workspace {} at {}",
+        name.bold(),
+        workspace.path().display()
+    );
+    println!(
+        "{}",
+        format!("Add to it with --workspace {name}, or: export I1_WORKSPACE={name}").dimmed()
+    );
+    Ok(())
+}
+
+fn list(ctx: &Context) -> Result<()> {
+    let summaries = workspace::list(&workspace::root()?)
+        .iter()
+        .map(|workspace| workspace.summary(0))
+        .collect::<Result<Vec<_>>>()?;
+    let active = workspace::active();
+    let active = active.as_ref().map(Workspace::name);
+
+    match ctx.output_format {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&summaries)?);
+        }
+        OutputFormat::Yaml => println!("{}", serde_yaml::to_string(&summaries)?),
+        OutputFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(std::io::stdout());
+            writer.write_record(["name", "targets", "hosts", "credits", "last_activity"])?;
+            for summary in &summaries {
+                writer.write_record([
+                    summary.name.clone(),
+                    targets(summary).to_string(),
+                    summary.hosts.to_string(),
+                    summary.credits.to_string(),
+                    summary
+                        .last_activity
+                        .map(|at| at.to_rfc3339())
+                        .unwrap_or_default(),
+                ])?;
+            }
+            writer.flush()?;
+        }
+        OutputFormat::Pretty => {
+            if summaries.is_empty() {
+                if !ctx.quiet {
+                    println!("No workspaces yet. Start one with: i1 workspace new <name>");
+                }
+                return Ok(());
+            }
+            let rows: Vec<WorkspaceRow> = summaries
+                .iter()
+                .map(|summary| WorkspaceRow {
+                    name: if active == Some(summary.name.as_str()) {
+                        format!("{} (active)", summary.name)
+                    } else {
+                        summary.name.clone()
+                    },
+                    targets: targets(summary),
+                    hosts: summary.hosts,
+                    credits: summary.credits,
+                    last: summary.last_activity.map_or_else(String::new, when),
+                })
+                .collect();
+            println!("{}", terminal::table(&rows));
+        }
+    }
+    Ok(())
+}
+
+fn show(ctx: &Context, summary: &Summary) -> Result<()> {
+    match ctx.output_format {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(summary)?);
+        }
+        OutputFormat::Yaml => println!("{}", serde_yaml::to_string(summary)?),
+        // The findings, for a spreadsheet
+        OutputFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(std::io::stdout());
+            writer.write_record(["ip", "risk", "vulns", "ports", "org", "seen_in"])?;
+            for finding in &summary.findings {
+                let row = FindingRow::from(finding);
+                writer.write_record([
+                    row.ip,
+                    row.risk,
+                    finding.vulns.join(";"),
+                    row.ports.replace(", ", ";"),
+                    row.org,
+                    row.seen_in,
+                ])?;
+            }
+            writer.flush()?;
+        }
+        OutputFormat::Pretty => print_pretty(ctx, summary),
+    }
+    Ok(())
+}
+
+fn print_pretty(ctx: &Context, summary: &Summary) {
+    if !ctx.quiet {
+        let last = summary
+            .last_activity
+            .map_or_else(String::new, |at| format!(", last activity {}", when(at)));
+        println!(
+            "{} {} (opened {}{last})",
+            "Workspace".bold(),
+            summary.name.bold(),
+            when(summary.created_at)
+        );
+        println!("{}", format!("  {}", summary.path.display()).dimmed());
+        println!(
+            "Investigated {}; {} seen",
+            investigated(summary),
+            plural(summary.hosts, "unique host", "unique hosts")
+        );
+        println!("Credits spent: {}", summary.credits);
+    }
+
+    if !summary.providers.is_empty() {
+        println!();
+        println!("{}", "Providers".bold());
+        let rows: Vec<ProviderRow> = summary
+            .providers
+            .iter()
+            .map(|(provider, used)| ProviderRow {
+                provider: provider.clone(),
+                calls: used.calls,
+                failed: used.failed,
+                credits: used.credits,
+                answers: used.answers,
+            })
+            .collect();
+        println!("{}", terminal::table(&rows));
+    }
+
+    if !summary.findings.is_empty() {
+        println!();
+        println!("{}", "Top findings".bold());
+        let rows: Vec<FindingRow> = summary.findings.iter().map(FindingRow::from).collect();
+        println!("{}", terminal::table(&rows));
+    }
+}
+
+fn export(workspace: &Workspace, format: ReportFormat, top: usize) -> Result<()> {
+    let summary = workspace.summary(top)?;
+    let mut targets: Vec<Target> = workspace.index().into_values().collect();
+    targets.sort_by_key(|target| target.at);
+
+    match format {
+        ReportFormat::Json => {
+            let report = Report {
+                summary,
+                targets,
+                hosts: workspace.hosts()?,
+            };
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+        ReportFormat::Markdown => print!("{}", markdown(&summary, &targets)),
+    }
+    Ok(())
+}
+
+/// The report skeleton: the facts filled in, headings for the rest.
+fn markdown(summary: &Summary, targets: &[Target]) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "# Investigation: {}\n", summary.name);
+    out.push_str("| | |\n|---|---|\n");
+    let _ = writeln!(out, "| Opened | {} |", when(summary.created_at));
+    if let Some(at) = summary.last_activity {
+        let _ = writeln!(out, "| Last activity | {} |", when(at));
+    }
+    let _ = writeln!(out, "| Investigated | {} |", investigated(summary));
+    let _ = writeln!(out, "| Unique hosts | {} |", summary.hosts);
+    let _ = writeln!(out, "| Credits spent | {} |", summary.credits);
+
+    out.push_str("\n## Summary\n\n_What happened, what it affected, and what was done._\n");
+
+    out.push_str("\n## Providers\n\n");
+    if summary.providers.is_empty() {
+        out.push_str("_No provider was asked._\n");
+    } else {
+        out.push_str("| Provider | Calls | Failed | Credits | Answers |\n|---|---|---|---|---|\n");
+        for (provider, used) in &summary.providers {
+            let _ = writeln!(
+                out,
+                "| {} | {} | {} | {} | {} |",
+                cell(provider),
+                used.calls,
+                used.failed,
+                used.credits,
+                used.answers
+            );
+        }
+    }
+
+    out.push_str("\n## Targets investigated\n\n");
+    if targets.is_empty() {
+        out.push_str("_Nothing yet._\n");
+    } else {
+        out.push_str("| When | Kind | Target | Provider | Hosts |\n|---|---|---|---|---|\n");
+        for target in targets {
+            let _ = writeln!(
+                out,
+                "| {} | {} | `{}` | {} | {} |",
+                when(target.at),
+                target.kind.name(),
+                cell(&target.target).replace('`', "'"),
+                cell(&target.provider),
+                target.hosts
+            );
+        }
+    }
+
+    out.push_str("\n## Findings\n\n");
+    if summary.findings.is_empty() {
+        out.push_str("_No host with known vulnerabilities was found._\n");
+    } else {
+        out.push_str(
+            "| Host | Risk | CVEs | Ports | Organization | Seen in |\n|---|---|---|---|---|---|\n",
+        );
+        for finding in &summary.findings {
+            let row = FindingRow::from(finding);
+            let _ = writeln!(
+                out,
+                "| {} | {} | {} | {} | {} | {} |",
+                row.ip,
+                row.risk,
+                cell(&row.vulns),
+                row.ports,
+                cell(&row.org),
+                row.seen_in
+            );
+        }
+    }
+
+    out.push_str("\n## Timeline\n\n_Key events, oldest first._\n");
+    out.push_str("\n## Recommendations\n\n_What to do next, and who owns it._\n");
+    out
+}
+
+/// `3 hosts, 1 search and 2 scans`
+fn investigated(summary: &Summary) -> String {
+    let parts = [
+        plural(summary.targets.hosts, "host", "hosts"),
+        plural(summary.targets.searches, "search", "searches"),
+        plural(summary.targets.scans, "scan", "scans"),
+    ];
+    format!("{}, {} and {}", parts[0], parts[1], parts[2])
+}
+
+const fn targets(summary: &Summary) -> usize {
+    summary.targets.hosts + summary.targets.searches + summary.targets.scans
+}
+
+fn plural(count: usize, one: &str, many: &str) -> String {
+    format!("{count} {}", if count == 1 { one } else { many })
+}
+
+fn when(at: DateTime<Utc>) -> String {
+    at.format("%Y-%m-%d %H:%M UTC").to_string()
+}
+
+fn join(ports: &[u16]) -> String {
+    ports
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn seen_in(finding: &Finding) -> String {
+    finding
+        .seen_in
+        .iter()
+        .map(|kind| kind.name())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// `text` safe inside a Markdown table cell.
+fn cell(text: &str) -> String {
+    text.replace('|', "\\|").replace(['\n', '\r'], " ")
+}
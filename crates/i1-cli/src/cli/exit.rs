@@ -0,0 +1,237 @@
+//! Exit codes scripts can rely on.
+//!
+//! | Code | Meaning                                      |
+//! |------|----------------------------------------------|
+//! | 0    | Success                                      |
+//! | 1    | Any other error                              |
+//! | 2    | Not found, or a search with no results       |
+//! | 3    | Missing or rejected API key                  |
+//! | 4    | Rate limited or out of credits               |
+//! | 5    | Invalid input (arguments, IPs, queries)      |
+//! | 6    | Provider or network error                    |
+
+use std::fmt;
+use std::process::ExitCode;
+
+use i1::{I1Error, ReconKind};
+
+pub const SUCCESS: u8 = 0;
+pub const FAILURE: u8 = 1;
+pub const NOT_FOUND: u8 = 2;
+pub const AUTH: u8 = 3;
+pub const RATE_LIMITED: u8 = 4;
+pub const INVALID_INPUT: u8 = 5;
+pub const PROVIDER: u8 = 6;
+
+/// The contract as shown at the end of `--help`.
+pub const HELP: &str = "\
+Exit codes:
+  0  success
+  1  any other error
+  2  not found, or a search with no results
+  3  missing or rejected API key
+  4  rate limited or out of credits
+  5  invalid input
+  6  provider or network error";
+
+/// Ends the run with a code and no error message, e.g. a search that
+/// found nothing after saying so.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Exit(pub u8);
+
+impl fmt::Display for Exit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "exit code {}", self.0)
+    }
+}
+
+impl std::error::Error for Exit {}
+
+/// The exit code for an error, from the first [`I1Error`] or [`Exit`] in its chain.
+pub fn code(err: &anyhow::Error) -> u8 {
+    err.chain()
+        .find_map(|cause| {
+            cause
+                .downcast_ref::<Exit>()
+                .map(|exit| exit.0)
+                .or_else(|| cause.downcast_ref::<I1Error>().map(classify))
+        })
+        .unwrap_or(FAILURE)
+}
+
+/// Which part of the contract a provider error falls under. When every
+/// provider failed, that's the part they all fall under, if they agree.
+pub fn classify(err: &I1Error) -> u8 {
+    match err {
+        I1Error::NotFound { .. } => NOT_FOUND,
+        I1Error::Unauthorized | I1Error::ProviderNotConfigured(_) | I1Error::NoProviders => AUTH,
+        I1Error::RateLimited { .. } | I1Error::InsufficientCredits { .. } => RATE_LIMITED,
+        I1Error::InvalidIp(_)
+        | I1Error::InvalidQuery(_)
+        | I1Error::InvalidUrl(_)
+        | I1Error::Config(_)
+        | I1Error::PolicyDenied { .. } => INVALID_INPUT,
+        I1Error::Provider { code, .. } => match *code {
+            401 | 403 => AUTH,
+            404 => NOT_FOUND,
+            402 | 429 => RATE_LIMITED,
+            400 | 422 => INVALID_INPUT,
+            _ => PROVIDER,
+        },
+        I1Error::Http(_)
+        | I1Error::Timeout(_)
+        | I1Error::Connection(_)
+        | I1Error::Json(_)
+        | I1Error::Schema { .. }
+        | I1Error::Scan(_)
+        | I1Error::Whois(_)
+        | I1Error::Dns(_)
+        | I1Error::Trace(_) => PROVIDER,
+        I1Error::Recon { kind, .. } => match kind {
+            ReconKind::OutOfScope | ReconKind::Parse | ReconKind::MissingDatabase => INVALID_INPUT,
+            ReconKind::Timeout
+            | ReconKind::Network
+            | ReconKind::Scan
+            | ReconKind::Whois
+            | ReconKind::Dns
+            | ReconKind::Trace => PROVIDER,
+            ReconKind::Privileges | ReconKind::Io => FAILURE,
+        },
+        I1Error::AllProvidersFailed { failures } => {
+            let mut codes = failures.iter().map(|(_, e)| classify(e));
+            let first = codes.next().unwrap_or(PROVIDER);
+            if codes.all(|code| code == first) {
+                first
+            } else {
+                PROVIDER
+            }
+        }
+        I1Error::Internal(_) => FAILURE,
+    }
+}
+
+/// Print the error, if there is one to print, and turn the result into an exit code.
+pub fn report(result: anyhow::Result<()>) -> ExitCode {
+    match result {
+        Ok(()) => ExitCode::from(SUCCESS),
+        Err(e) => {
+            if e.downcast_ref::<Exit>().is_none() {
+                eprintln!("Error: {e:?}");
+            }
+            ExitCode::from(code(&e))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code() {
+        let not_found = anyhow::Error::new(I1Error::NotFound {
+            resource: "/shodan/host/192.0.2.1".to_string(),
+        });
+        assert_eq!(code(&not_found), NOT_FOUND);
+
+        // Context added on top doesn't hide the cause
+        let auth = anyhow::Error::new(I1Error::Unauthorized).context("Check your API key");
+        assert_eq!(code(&auth), AUTH);
+
+        assert_eq!(
+            code(&I1Error::provider("censys", 429, "slow down").into()),
+            RATE_LIMITED
+        );
+        assert_eq!(
+            code(&I1Error::provider("shodan", 503, "down").into()),
+            PROVIDER
+        );
+        assert_eq!(
+            code(&I1Error::InvalidIp("nope".to_string()).into()),
+            INVALID_INPUT
+        );
+        assert_eq!(
+            code(
+                &I1Error::PolicyDenied {
+                    provider: "shodan".to_string(),
+                    target: "10.0.0.1".to_string(),
+                }
+                .into()
+            ),
+            INVALID_INPUT
+        );
+
+        let all_failed = |errors: Vec<I1Error>| {
+            let failures = ["censys", "criminalip"].map(String::from).into_iter();
+            code(&I1Error::all_failed(failures.zip(errors).collect()).into())
+        };
+        assert_eq!(
+            all_failed(vec![
+                I1Error::Unauthorized,
+                I1Error::provider("criminalip", 403, "no")
+            ]),
+            AUTH
+        );
+        assert_eq!(
+            all_failed(vec![
+                I1Error::RateLimited { retry_after: None },
+                I1Error::Unauthorized
+            ]),
+            PROVIDER
+        );
+
+        assert_eq!(code(&Exit(NOT_FOUND).into()), NOT_FOUND);
+        assert_eq!(code(&anyhow::anyhow!("something else")), FAILURE);
+    }
+
+    #[cfg(feature = "scanner")]
+    #[test]
+    fn test_recon_code() {
+        use anyhow::Context as _;
+        use i1::recon::ReconError;
+
+        let recon = |err: ReconError| code(&anyhow::Error::new(I1Error::from(err)));
+        assert_eq!(
+            recon(ReconError::OutOfScope([192, 168, 1, 1].into())),
+            INVALID_INPUT
+        );
+        assert_eq!(
+            recon(ReconError::Parse {
+                what: "port specification",
+                input: "99999".to_string(),
+            }),
+            INVALID_INPUT
+        );
+        assert_eq!(
+            recon(ReconError::MissingDatabase("GeoLite2-ASN.mmdb".into())),
+            INVALID_INPUT
+        );
+        assert_eq!(
+            recon(ReconError::Timeout {
+                target: "192.0.2.1:22".to_string(),
+                what: "connecting to",
+            }),
+            PROVIDER
+        );
+        assert_eq!(
+            recon(std::io::Error::from(std::io::ErrorKind::ConnectionReset).into()),
+            PROVIDER
+        );
+        assert_eq!(recon(ReconError::Whois("no referral".into())), PROVIDER);
+        assert_eq!(
+            recon(ReconError::InsufficientPrivileges {
+                needed: "CAP_NET_RAW"
+            }),
+            FAILURE
+        );
+
+        // Context added on the way up doesn't hide it
+        let wrapped = Err::<(), _>(I1Error::from(ReconError::Parse {
+            what: "capture or Zeek log",
+            input: "line 3".to_string(),
+        }))
+        .context("Could not read conn.log")
+        .unwrap_err();
+        assert_eq!(code(&wrapped), INVALID_INPUT);
+    }
+}
@@ -2,60 +2,219 @@
 
 pub mod args;
 pub mod commands;
+pub mod exit;
+
+use std::io::IsTerminal;
 
 use anyhow::Result;
-use args::{Cli, Commands};
+use args::{Cli, Commands, ConfigCommands, DefendArgs, DefendCommands};
 use clap::Parser;
 
+use crate::cache;
 use crate::config::Config;
-use crate::output::OutputFormat;
+use crate::output::{self, file, terminal, Output, OutputFormat};
 
 /// Run the CLI application.
+///
+/// Errors carry their exit code; see [`exit`].
 pub async fn run() -> Result<()> {
-    let cli = Cli::parse();
-
-    // Load configuration
-    let config = Config::load()?;
-
-    // Determine output format
-    let output_format = cli.output.unwrap_or(OutputFormat::Pretty);
-
-    // Get API keys from CLI, env, or config
-    let shodan_key = cli
-        .api_key
-        .or_else(|| std::env::var("SHODAN_API_KEY").ok())
-        .or_else(|| std::env::var("I1_SHODAN_KEY").ok())
-        .or_else(|| config.shodan_key.clone());
-
-    // Create context for commands
-    let ctx = commands::Context {
-        shodan_key,
-        censys_id: std::env::var("I1_CENSYS_ID")
-            .ok()
-            .or_else(|| config.censys_id.clone()),
-        censys_secret: std::env::var("I1_CENSYS_SECRET")
-            .ok()
-            .or_else(|| config.censys_secret.clone()),
-        criminalip_key: std::env::var("I1_CRIMINALIP_KEY")
-            .ok()
-            .or_else(|| config.criminalip_key.clone()),
-        provider: cli.provider,
-        output_format,
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        Err(e) => {
+            e.print()?;
+            // --help and --version land here too
+            return if e.use_stderr() {
+                Err(exit::Exit(exit::INVALID_INPUT).into())
+            } else {
+                Ok(())
+            };
+        }
+    };
+
+    // Run again with stdout going to the file; the child gets here with
+    // CHILD_ENV set and carries on below
+    if let Some(path) = &cli.output_file {
+        if std::env::var_os(file::CHILD_ENV).is_none() {
+            if matches!(cli.command, None | Some(Commands::Shell)) {
+                anyhow::bail!("--output-file needs a command; interactive mode writes to the terminal");
+            }
+            return file::run(path, cli.append, cli.output, cli.quiet).await;
+        }
+    }
+
+    if matches!(cli.command, Some(Commands::Shell)) {
+        let result = crate::interactive::run(cli).await;
+        crate::audit::flush();
+        return result;
+    }
+
+    // `config doctor` reports a broken config file rather than dying on it
+    let doctor = matches!(
+        &cli.command,
+        Some(Commands::Config(args)) if matches!(args.command, ConfigCommands::Doctor)
+    );
+    let file = match Config::load() {
+        Ok(file) => file,
+        Err(_) if doctor => Config::default(),
+        Err(e) => return Err(e),
+    };
+    let ctx = context(&cli, &file, commands::Clients::default())?;
+
+    // Covers output that doesn't check ctx.no_color itself
+    if ctx.no_color {
+        colored::control::set_override(false);
+    }
+
+    // Run again with stdout going through the pager; the child gets here
+    // with PAGER_CHILD_ENV set and colors as if it were on the terminal
+    if std::env::var_os(terminal::PAGER_CHILD_ENV).is_some() {
+        if !ctx.no_color {
+            colored::control::set_override(true);
+        }
+    } else if ctx.output_format == OutputFormat::Pretty
+        && !cli.no_pager
+        && std::io::stdout().is_terminal()
+        && terminal::current().height.is_some()
+        && cli.command.as_ref().is_some_and(pageable)
+    {
+        return terminal::page(terminal::current()).await;
+    }
+
+    let result = dispatch(ctx, cli.command).await;
+    crate::audit::flush();
+    result
+}
+
+/// Commands whose pretty output can run long enough to page. Anything
+/// that prompts, streams until Ctrl-C or opens an editor stays direct.
+const fn pageable(command: &Commands) -> bool {
+    match command {
+        Commands::Host(args) => args.watch.watch.is_none(),
+        Commands::Search(args) => args.watch.watch.is_none(),
+        #[cfg(feature = "scanner")]
+        Commands::Scan(_) => true,
+        #[cfg(feature = "ingest")]
+        Commands::Ingest(_) => true,
+        Commands::Domain(_)
+        | Commands::Dns(_)
+        | Commands::Vuln(_)
+        | Commands::Honeypot(_)
+        | Commands::Workspace(_)
+        | Commands::Audit(_) => true,
+        Commands::Defend(DefendArgs {
+            command: DefendCommands::Status { .. },
+        }) => true,
+        _ => false,
+    }
+}
+
+/// Commands whose output is hosts, which `-o stix` bundles.
+const fn bundles_hosts(command: &Commands) -> bool {
+    match command {
+        Commands::Host(_) | Commands::Search(_) => true,
+        #[cfg(feature = "scanner")]
+        Commands::Scan(_) => true,
+        _ => false,
+    }
+}
+
+/// The context commands run with: defaults, config file, environment and
+/// flags layered, sharing `clients` with other commands in this process.
+pub fn context(cli: &Cli, file: &Config, clients: commands::Clients) -> Result<commands::Context> {
+    let config = file.resolve(cli)?;
+    crate::audit::open(cli, file)?;
+    crate::workspace::open(cli)?;
+    terminal::configure(cli);
+    i1_providers::schema::dump_responses(cli.dump_responses.clone());
+
+    let mut http = if cli.no_proxy {
+        i1::ProviderHttpConfig::direct()
+    } else {
+        i1::ProviderHttpConfig::new()
+    };
+    if let Some(proxy) = config.proxy.filter(|_| !cli.no_proxy) {
+        http = http.proxy(proxy);
+    }
+    if let Some(hosts) = &file.no_proxy {
+        http = http.no_proxy(hosts);
+    }
+
+    // `config` still runs with a broken policy saved, so it can be fixed
+    let policy = match file.policy.query_policy() {
+        Ok(policy) => policy,
+        Err(_) if matches!(cli.command, Some(Commands::Config(_))) => i1::QueryPolicy::new(),
+        Err(e) => return Err(e),
+    };
+
+    let mut ctx = commands::Context {
+        shodan_key: config.shodan_key,
+        censys_id: config.censys_id,
+        censys_secret: config.censys_secret,
+        criminalip_key: config.criminalip_key,
+        token: config.token,
+        shodan_url: config.shodan_url,
+        censys_url: config.censys_url,
+        criminalip_url: config.criminalip_url,
+        provider: config.provider,
+        output_format: config.output_format.format(),
+        stix: config.output_format == Output::Stix,
+        profile: config.profile,
         explain: cli.explain,
         verbose: cli.verbose,
-        no_color: cli.no_color,
+        // Files never get colors
+        no_color: cli.no_color || cli.output_file.is_some(),
+        quiet: cli.quiet,
+        cache: if cli.no_cache {
+            cache::Mode::Off
+        } else if cli.refresh {
+            cache::Mode::Refresh
+        } else {
+            cache::Mode::Use
+        },
+        raw: false,
+        http,
+        policy,
+        sources: config.sources,
+        clients,
     };
 
-    // Dispatch to appropriate command, or run interactive scan if none given
-    match cli.command {
+    // `config` still runs with a bad provider or proxy saved, so it can be fixed
+    if !matches!(cli.command, Some(Commands::Config(_))) {
+        ctx.check_provider()?;
+        ctx.http.client()?;
+    }
+    Ok(ctx)
+}
+
+/// Run a command, or the interactive scan if none was given.
+pub async fn dispatch(ctx: commands::Context, command: Option<Commands>) -> Result<()> {
+    if ctx.stix && !command.as_ref().is_some_and(bundles_hosts) {
+        return Err(output::no_stix("This command").into());
+    }
+
+    match command {
         Some(Commands::Host(args)) => commands::host::execute(ctx, args).await,
         Some(Commands::Search(args)) => commands::search::execute(ctx, args).await,
         Some(Commands::Count(args)) => commands::count::execute(ctx, args).await,
         Some(Commands::Dns(args)) => commands::dns::execute(ctx, args).await,
-        Some(Commands::Myip) => commands::myip::execute(ctx).await,
+        Some(Commands::Domain(args)) => commands::domain::execute(ctx, args).await,
+        Some(Commands::Alert(args)) => commands::alert::execute(ctx, args).await,
+        Some(Commands::Credits(args)) => commands::credits::execute(ctx, args).await,
+        Some(Commands::Myip(args)) => commands::myip::execute(ctx, args).await,
         Some(Commands::Defend(args)) => commands::defend::execute(ctx, args).await,
+        Some(Commands::Ca(args)) => commands::ca::execute(&ctx, args),
         Some(Commands::Config(args)) => commands::config::execute(ctx, args).await,
+        Some(Commands::Cache(args)) => commands::cache::execute(&ctx, args),
+        Some(Commands::Audit(args)) => commands::audit::execute(&ctx, &args),
+        Some(Commands::Honeypot(args)) => commands::honeypot::execute(&ctx, args),
+        Some(Commands::Workspace(args)) => commands::workspace::execute(&ctx, args),
         Some(Commands::Threat(args)) => commands::threat::execute(&ctx, &args).await,
+        #[cfg(feature = "scanner")]
+        Some(Commands::Scan(args)) => commands::portscan::execute(ctx, args).await,
+        #[cfg(feature = "ingest")]
+        Some(Commands::Ingest(args)) => commands::ingest::execute(&ctx, args).await,
+        Some(Commands::Vuln(args)) => commands::vuln::execute(ctx, args).await,
+        Some(Commands::Shell) => anyhow::bail!("Already in the shell"),
         None => commands::scan::execute(ctx).await,
     }
 }
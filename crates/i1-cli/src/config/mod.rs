@@ -3,9 +3,12 @@
 use anyhow::Result;
 use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
-use crate::output::OutputFormat;
+use crate::cli::args::{Cli, Commands, ConfigCommands};
+use crate::defend::asn;
+use crate::output::Output;
 
 /// CLI configuration.
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -23,8 +26,14 @@ pub struct Config {
     /// Criminal IP API key.
     pub criminalip_key: Option<String>,
 
+    /// i1.is API token.
+    pub token: Option<String>,
+
     /// Default output format.
-    pub output_format: Option<OutputFormat>,
+    pub output_format: Option<Output>,
+
+    /// Default provider (auto, shodan, censys, criminalip, native, all).
+    pub provider: Option<String>,
 
     /// Show helpful tips after commands.
     #[serde(default = "default_true")]
@@ -33,12 +42,265 @@ pub struct Config {
     /// Always show explanations (as if --explain was passed).
     #[serde(default)]
     pub explain_by_default: bool,
+
+    /// Mirror for the RIR delegation files used by `defend geoblock`.
+    pub geo_mirror: Option<String>,
+
+    /// Where `defend ban --as-number` looks up prefixes (ripestat or whois).
+    pub asn_source: Option<asn::Source>,
+
+    /// Proxy for provider requests (http://, https://, socks5://, socks5h://).
+    pub proxy: Option<String>,
+
+    /// Hosts reached without the proxy, in `NO_PROXY` form
+    /// (`api.i1.is,.internal,10.0.0.0/8`).
+    pub no_proxy: Option<String>,
+
+    /// Shodan API endpoint, for a proxy or a mock server.
+    pub shodan_url: Option<String>,
+
+    /// Censys API endpoint.
+    pub censys_url: Option<String>,
+
+    /// Criminal IP API endpoint.
+    pub criminalip_url: Option<String>,
+
+    /// Append every provider call and defend change to this file.
+    pub audit_log: Option<PathBuf>,
+
+    /// Write audit log targets as SHA-256 hashes.
+    #[serde(default)]
+    pub audit_hash_targets: bool,
+
+    /// Targets never sent to providers.
+    #[serde(default, skip_serializing_if = "Policy::is_empty")]
+    pub policy: Policy,
+
+    /// Saved queries for `search --saved`, by name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub queries: BTreeMap<String, String>,
+
+    /// Named profiles, selected with `--profile` or `I1_PROFILE`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, Profile>,
 }
 
 const fn default_true() -> bool {
     true
 }
 
+/// The `[policy]` table: CIDR blocks, addresses and domains withheld from
+/// providers.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Policy {
+    /// Never sent to a third-party provider.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deny_external: Vec<String>,
+
+    /// Never sent to one provider, by provider name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub deny: BTreeMap<String, Vec<String>>,
+
+    /// Send private, loopback and link-local addresses to third-party
+    /// providers too.
+    #[serde(default)]
+    pub allow_private: bool,
+}
+
+impl Policy {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The policy the client enforces.
+    pub fn query_policy(&self) -> Result<i1::QueryPolicy> {
+        let parse = |rule: &String| {
+            rule.parse::<i1::PolicyRule>()
+                .map_err(|e| anyhow::anyhow!("policy: {e}"))
+        };
+        let mut policy = i1::QueryPolicy::new().allow_private(self.allow_private);
+        for rule in &self.deny_external {
+            policy = policy.deny_external(parse(rule)?);
+        }
+        for (provider, rules) in &self.deny {
+            for rule in rules {
+                policy = policy.deny(provider.clone(), parse(rule)?);
+            }
+        }
+        Ok(policy)
+    }
+}
+
+/// One `[profiles.<name>]` table. Anything left unset falls back to the
+/// top-level value.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Profile {
+    /// Shodan API key.
+    #[serde(alias = "api_key")]
+    pub shodan_key: Option<String>,
+
+    /// Censys API ID.
+    pub censys_id: Option<String>,
+
+    /// Censys API secret.
+    pub censys_secret: Option<String>,
+
+    /// Criminal IP API key.
+    pub criminalip_key: Option<String>,
+
+    /// i1.is API token.
+    pub token: Option<String>,
+
+    /// Default output format.
+    pub output_format: Option<Output>,
+
+    /// Default provider.
+    pub provider: Option<String>,
+}
+
+impl Profile {
+    /// Copy every value set in `other` over this one.
+    pub fn update(&mut self, other: Self) {
+        if other.shodan_key.is_some() {
+            self.shodan_key = other.shodan_key;
+        }
+        if other.censys_id.is_some() {
+            self.censys_id = other.censys_id;
+        }
+        if other.censys_secret.is_some() {
+            self.censys_secret = other.censys_secret;
+        }
+        if other.criminalip_key.is_some() {
+            self.criminalip_key = other.criminalip_key;
+        }
+        if other.token.is_some() {
+            self.token = other.token;
+        }
+        if other.output_format.is_some() {
+            self.output_format = other.output_format;
+        }
+        if other.provider.is_some() {
+            self.provider = other.provider;
+        }
+    }
+
+    /// Names of the settings this profile overrides.
+    pub fn keys(&self) -> Vec<&'static str> {
+        [
+            ("shodan_key", self.shodan_key.is_some()),
+            ("censys_id", self.censys_id.is_some()),
+            ("censys_secret", self.censys_secret.is_some()),
+            ("criminalip_key", self.criminalip_key.is_some()),
+            ("token", self.token.is_some()),
+            ("output_format", self.output_format.is_some()),
+            ("provider", self.provider.is_some()),
+        ]
+        .into_iter()
+        .filter_map(|(key, set)| set.then_some(key))
+        .collect()
+    }
+}
+
+/// Where a resolved setting came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    /// Built-in default
+    Default,
+    /// The config file
+    File,
+    /// The active profile in the config file
+    Profile,
+    /// An environment variable
+    Env(&'static str),
+    /// A command-line flag
+    Flag,
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Default => write!(f, "default"),
+            Self::File => write!(f, "file"),
+            Self::Profile => write!(f, "profile"),
+            Self::Env(var) => write!(f, "env {var}"),
+            Self::Flag => write!(f, "flag"),
+        }
+    }
+}
+
+impl Serialize for Source {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Settings after every layer has been applied.
+#[derive(Debug, Clone, Default)]
+pub struct Resolved {
+    pub shodan_key: Option<String>,
+    pub censys_id: Option<String>,
+    pub censys_secret: Option<String>,
+    pub criminalip_key: Option<String>,
+    pub token: Option<String>,
+    pub output_format: Output,
+    pub provider: String,
+    pub proxy: Option<String>,
+    pub shodan_url: Option<String>,
+    pub censys_url: Option<String>,
+    pub criminalip_url: Option<String>,
+    /// Active profile, if any
+    pub profile: Option<String>,
+    /// Where each setting came from, keyed by its config file name
+    pub sources: BTreeMap<&'static str, Source>,
+}
+
+/// Walks the layers for one setting at a time, recording the winner.
+struct Layers<E> {
+    env: E,
+    sources: BTreeMap<&'static str, Source>,
+}
+
+impl<E: Fn(&str) -> Option<String>> Layers<E> {
+    /// Highest layer that has a value wins: flag, then the first set
+    /// variable in `vars`, then the active profile, then the file. Empty
+    /// variables count as unset.
+    fn pick<T, P: std::fmt::Display>(
+        &mut self,
+        key: &'static str,
+        flag: Option<T>,
+        vars: &[&'static str],
+        parse: impl Fn(&str) -> Result<T, P>,
+        profile: Option<T>,
+        file: Option<T>,
+    ) -> Result<Option<T>> {
+        if let Some(value) = flag {
+            self.sources.insert(key, Source::Flag);
+            return Ok(Some(value));
+        }
+
+        for var in vars {
+            if let Some(raw) = (self.env)(var).filter(|v| !v.is_empty()) {
+                let value = parse(&raw).map_err(|e| anyhow::anyhow!("{var}: {e}"))?;
+                self.sources.insert(key, Source::Env(var));
+                return Ok(Some(value));
+            }
+        }
+
+        if let Some(value) = profile {
+            self.sources.insert(key, Source::Profile);
+            return Ok(Some(value));
+        }
+
+        let source = if file.is_some() {
+            Source::File
+        } else {
+            Source::Default
+        };
+        self.sources.insert(key, source);
+        Ok(file)
+    }
+}
+
 impl Config {
     /// Get the config file path.
     pub fn path() -> Result<PathBuf> {
@@ -62,6 +324,181 @@ impl Config {
         Ok(config)
     }
 
+    /// Resolve the settings a command runs with.
+    ///
+    /// Precedence, lowest to highest:
+    ///
+    /// 1. built-in defaults
+    /// 2. the config file (`self`)
+    /// 3. the profile picked by `--profile` or `I1_PROFILE`
+    /// 4. environment: `I1_SHODAN_KEY` (or `SHODAN_API_KEY`), `I1_CENSYS_ID`,
+    ///    `I1_CENSYS_SECRET`, `I1_CRIMINALIP_KEY`, `I1_TOKEN`, `I1_OUTPUT`,
+    ///    `I1_PROVIDER`, `I1_PROXY`, `I1_SHODAN_URL`, `I1_CENSYS_URL`,
+    ///    `I1_CRIMINALIP_URL`
+    /// 5. command-line flags (`--api-key`, `--output`, `--provider`, `--proxy`)
+    ///
+    /// Naming a profile that doesn't exist is an error, except for
+    /// `config set`, which creates it.
+    pub fn resolve(&self, cli: &Cli) -> Result<Resolved> {
+        self.resolve_with_env(cli, |var| std::env::var(var).ok())
+    }
+
+    /// [`Config::resolve`] with a custom environment lookup.
+    pub fn resolve_with_env(
+        &self,
+        cli: &Cli,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Resolved> {
+        let mut layers = Layers {
+            env,
+            sources: BTreeMap::new(),
+        };
+
+        let profile = layers.pick(
+            "profile",
+            cli.profile.clone(),
+            &["I1_PROFILE"],
+            str::parse,
+            None,
+            None,
+        )?;
+        let mut overrides = match &profile {
+            Some(name) => match self.profiles.get(name) {
+                Some(overrides) => overrides.clone(),
+                None if creates_profile(cli) => Profile::default(),
+                None => anyhow::bail!(
+                    "Unknown profile '{name}'. Create it with: \
+                     i1 config set --profile {name} <key> <value>"
+                ),
+            },
+            None => Profile::default(),
+        };
+
+        let mut resolved = Resolved {
+            profile,
+            ..Resolved::default()
+        };
+        self.resolve_flagged(cli, &mut overrides, &mut layers, &mut resolved)?;
+        self.resolve_unflagged(&mut overrides, &mut layers, &mut resolved)?;
+        resolved.sources = layers.sources;
+        Ok(resolved)
+    }
+
+    /// The settings a flag can set: `--api-key`, `--output`, `--provider`
+    /// and `--proxy`, over their variables, the profile and the file.
+    fn resolve_flagged<E: Fn(&str) -> Option<String>>(
+        &self,
+        cli: &Cli,
+        overrides: &mut Profile,
+        layers: &mut Layers<E>,
+        resolved: &mut Resolved,
+    ) -> Result<()> {
+        resolved.shodan_key = layers.pick(
+            "shodan_key",
+            cli.api_key.clone(),
+            &["I1_SHODAN_KEY", "SHODAN_API_KEY"],
+            str::parse,
+            overrides.shodan_key.take(),
+            self.shodan_key.clone(),
+        )?;
+        resolved.output_format = layers
+            .pick(
+                "output_format",
+                cli.output,
+                &["I1_OUTPUT"],
+                str::parse,
+                overrides.output_format,
+                self.output_format,
+            )?
+            .unwrap_or_default();
+        resolved.provider = layers
+            .pick(
+                "provider",
+                cli.provider.clone(),
+                &["I1_PROVIDER"],
+                str::parse,
+                overrides.provider.take(),
+                self.provider.clone(),
+            )?
+            .unwrap_or_else(|| "auto".to_string());
+        resolved.proxy = layers.pick(
+            "proxy",
+            cli.proxy.clone(),
+            &["I1_PROXY"],
+            str::parse,
+            None,
+            self.proxy.clone(),
+        )?;
+        Ok(())
+    }
+
+    /// The settings no flag sets: credentials besides the Shodan key, and
+    /// provider endpoints, from their variables, the profile or the file.
+    fn resolve_unflagged<E: Fn(&str) -> Option<String>>(
+        &self,
+        overrides: &mut Profile,
+        layers: &mut Layers<E>,
+        resolved: &mut Resolved,
+    ) -> Result<()> {
+        resolved.censys_id = layers.pick(
+            "censys_id",
+            None,
+            &["I1_CENSYS_ID"],
+            str::parse,
+            overrides.censys_id.take(),
+            self.censys_id.clone(),
+        )?;
+        resolved.censys_secret = layers.pick(
+            "censys_secret",
+            None,
+            &["I1_CENSYS_SECRET"],
+            str::parse,
+            overrides.censys_secret.take(),
+            self.censys_secret.clone(),
+        )?;
+        resolved.criminalip_key = layers.pick(
+            "criminalip_key",
+            None,
+            &["I1_CRIMINALIP_KEY"],
+            str::parse,
+            overrides.criminalip_key.take(),
+            self.criminalip_key.clone(),
+        )?;
+        resolved.token = layers.pick(
+            "token",
+            None,
+            &["I1_TOKEN"],
+            str::parse,
+            overrides.token.take(),
+            self.token.clone(),
+        )?;
+        resolved.shodan_url = layers.pick(
+            "shodan_url",
+            None,
+            &["I1_SHODAN_URL"],
+            str::parse,
+            None,
+            self.shodan_url.clone(),
+        )?;
+        resolved.censys_url = layers.pick(
+            "censys_url",
+            None,
+            &["I1_CENSYS_URL"],
+            str::parse,
+            None,
+            self.censys_url.clone(),
+        )?;
+        resolved.criminalip_url = layers.pick(
+            "criminalip_url",
+            None,
+            &["I1_CRIMINALIP_URL"],
+            str::parse,
+            None,
+            self.criminalip_url.clone(),
+        )?;
+        Ok(())
+    }
+
     /// Save configuration to file.
     pub fn save(&self) -> Result<()> {
         let path = Self::path()?;
@@ -76,4 +513,205 @@ impl Config {
 
         Ok(())
     }
+
+    /// Apply `update` to the named profile (creating it) or to the top level.
+    pub fn update(&mut self, profile: Option<&str>, update: Profile) {
+        if let Some(name) = profile {
+            self.profiles
+                .entry(name.to_string())
+                .or_default()
+                .update(update);
+            return;
+        }
+
+        let mut base = Profile {
+            shodan_key: self.shodan_key.take(),
+            censys_id: self.censys_id.take(),
+            censys_secret: self.censys_secret.take(),
+            criminalip_key: self.criminalip_key.take(),
+            token: self.token.take(),
+            output_format: self.output_format.take(),
+            provider: self.provider.take(),
+        };
+        base.update(update);
+
+        self.shodan_key = base.shodan_key;
+        self.censys_id = base.censys_id;
+        self.censys_secret = base.censys_secret;
+        self.criminalip_key = base.criminalip_key;
+        self.token = base.token;
+        self.output_format = base.output_format;
+        self.provider = base.provider;
+    }
+}
+
+/// Whether this invocation is `config set`, which may name a new profile.
+const fn creates_profile(cli: &Cli) -> bool {
+    matches!(
+        &cli.command,
+        Some(Commands::Config(args)) if matches!(args.command, ConfigCommands::Set { .. })
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::collections::HashMap;
+
+    fn resolve(config: &Config, args: &[&str], env: &[(&str, &str)]) -> Resolved {
+        let cli = Cli::parse_from(std::iter::once("i1").chain(args.iter().copied()));
+        let env: HashMap<String, String> = env
+            .iter()
+            .map(|(k, v)| ((*k).to_string(), (*v).to_string()))
+            .collect();
+        config
+            .resolve_with_env(&cli, |var| env.get(var).cloned())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_resolve_precedence() {
+        let config = Config {
+            shodan_key: Some("file-key".to_string()),
+            censys_id: Some("file-id".to_string()),
+            output_format: Some(Output::Yaml),
+            proxy: Some("socks5://file-proxy:1080".to_string()),
+            criminalip_url: Some("https://file.example/v1".to_string()),
+            ..Config::default()
+        };
+
+        // Nothing set beyond the file
+        let resolved = resolve(&config, &["myip"], &[]);
+        assert_eq!(resolved.shodan_key.as_deref(), Some("file-key"));
+        assert_eq!(resolved.output_format, Output::Yaml);
+        assert_eq!(resolved.provider, "auto");
+        assert_eq!(resolved.sources["shodan_key"], Source::File);
+        assert_eq!(resolved.sources["provider"], Source::Default);
+        assert_eq!(resolved.criminalip_key, None);
+        assert_eq!(resolved.proxy.as_deref(), Some("socks5://file-proxy:1080"));
+        assert_eq!(resolved.shodan_url, None);
+
+        // Environment beats the file
+        let env = [
+            ("I1_SHODAN_KEY", "env-key"),
+            ("I1_OUTPUT", "csv"),
+            ("I1_PROXY", "http://env-proxy:3128"),
+            ("I1_SHODAN_URL", "http://127.0.0.1:8080"),
+            ("I1_CRIMINALIP_URL", "http://127.0.0.1:8081"),
+        ];
+        let resolved = resolve(&config, &["myip"], &env);
+        assert_eq!(resolved.shodan_key.as_deref(), Some("env-key"));
+        assert_eq!(resolved.output_format, Output::Csv);
+        assert_eq!(resolved.sources["shodan_key"], Source::Env("I1_SHODAN_KEY"));
+        assert_eq!(resolved.censys_id.as_deref(), Some("file-id"));
+        assert_eq!(resolved.proxy.as_deref(), Some("http://env-proxy:3128"));
+        assert_eq!(
+            resolved.shodan_url.as_deref(),
+            Some("http://127.0.0.1:8080")
+        );
+        assert_eq!(
+            resolved.sources["criminalip_url"],
+            Source::Env("I1_CRIMINALIP_URL")
+        );
+
+        // Flags beat the environment
+        let args = [
+            "-k",
+            "flag-key",
+            "-o",
+            "json",
+            "--proxy",
+            "socks5h://flag:1080",
+            "myip",
+        ];
+        let resolved = resolve(&config, &args, &env);
+        assert_eq!(resolved.shodan_key.as_deref(), Some("flag-key"));
+        assert_eq!(resolved.output_format, Output::Json);
+        assert_eq!(resolved.sources["output_format"], Source::Flag);
+        assert_eq!(resolved.proxy.as_deref(), Some("socks5h://flag:1080"));
+    }
+
+    #[test]
+    fn test_resolve_env_details() {
+        let config = Config::default();
+
+        // The legacy variable still works, but the namespaced one wins
+        let resolved = resolve(&config, &["myip"], &[("SHODAN_API_KEY", "legacy")]);
+        assert_eq!(
+            resolved.sources["shodan_key"],
+            Source::Env("SHODAN_API_KEY")
+        );
+        let env = [("SHODAN_API_KEY", "legacy"), ("I1_SHODAN_KEY", "new")];
+        assert_eq!(
+            resolve(&config, &["myip"], &env).shodan_key.as_deref(),
+            Some("new")
+        );
+
+        // Empty variables are ignored
+        let resolved = resolve(&config, &["myip"], &[("I1_PROVIDER", "")]);
+        assert_eq!(resolved.provider, "auto");
+
+        let cli = Cli::parse_from(["i1", "myip"]);
+        let err = config
+            .resolve_with_env(&cli, |var| (var == "I1_OUTPUT").then(|| "xml".to_string()))
+            .unwrap_err();
+        assert!(err.to_string().starts_with("I1_OUTPUT:"));
+    }
+
+    #[test]
+    fn test_resolve_profile() {
+        let mut config = Config {
+            shodan_key: Some("top-key".to_string()),
+            censys_id: Some("top-id".to_string()),
+            ..Config::default()
+        };
+        let work = Profile {
+            shodan_key: Some("work-key".to_string()),
+            provider: Some("shodan".to_string()),
+            ..Profile::default()
+        };
+        config.update(Some("work"), work);
+
+        // Unset profile keys fall back to the top level
+        let resolved = resolve(&config, &["--profile", "work", "myip"], &[]);
+        assert_eq!(resolved.profile.as_deref(), Some("work"));
+        assert_eq!(resolved.shodan_key.as_deref(), Some("work-key"));
+        assert_eq!(resolved.censys_id.as_deref(), Some("top-id"));
+        assert_eq!(resolved.provider, "shodan");
+        assert_eq!(resolved.sources["shodan_key"], Source::Profile);
+        assert_eq!(resolved.sources["censys_id"], Source::File);
+
+        // I1_PROFILE selects too, but the flag wins
+        let env = [("I1_PROFILE", "personal")];
+        let resolved = resolve(&config, &["--profile", "work", "myip"], &env);
+        assert_eq!(resolved.sources["profile"], Source::Flag);
+        let resolved = resolve(&config, &["myip"], &[("I1_PROFILE", "work")]);
+        assert_eq!(resolved.shodan_key.as_deref(), Some("work-key"));
+
+        // Environment and flags still beat the profile
+        let env = [("I1_PROFILE", "work"), ("I1_SHODAN_KEY", "env-key")];
+        let resolved = resolve(&config, &["-p", "censys", "myip"], &env);
+        assert_eq!(resolved.shodan_key.as_deref(), Some("env-key"));
+        assert_eq!(resolved.provider, "censys");
+
+        // Without a profile the top level is untouched
+        let resolved = resolve(&config, &["myip"], &[]);
+        assert_eq!(resolved.shodan_key.as_deref(), Some("top-key"));
+        assert_eq!(resolved.profile, None);
+    }
+
+    #[test]
+    fn test_unknown_profile() {
+        let config = Config::default();
+
+        let cli = Cli::parse_from(["i1", "--profile", "nope", "myip"]);
+        let err = config.resolve_with_env(&cli, |_| None).unwrap_err();
+        assert!(err.to_string().contains("Unknown profile 'nope'"));
+
+        // `config set` is how a profile gets created
+        let cli = Cli::parse_from(["i1", "config", "set", "--profile", "new", "token", "t"]);
+        let resolved = config.resolve_with_env(&cli, |_| None).unwrap();
+        assert_eq!(resolved.profile.as_deref(), Some("new"));
+    }
 }
@@ -0,0 +1,336 @@
+//! AS number to announced prefixes, for `defend ban --as-number`.
+//!
+//! Prefixes come from `RIPEstat`'s announced-prefixes API, falling back to
+//! the route objects in the `RADb` routing registry over whois. Team Cymru's
+//! DNS interface only maps addresses to ASNs, so it can't list prefixes.
+//! Results are cached per ASN for a day under the config directory.
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use directories::ProjectDirs;
+use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
+
+use super::{geo, nft};
+use crate::config::Config;
+
+/// `RIPEstat`'s announced-prefixes endpoint; the ASN goes on the end
+const RIPESTAT_URL: &str = "https://stat.ripe.net/data/announced-prefixes/data.json?resource=";
+
+/// Whois server holding the route objects
+const WHOIS_SERVER: &str = "whois.radb.net:43";
+
+/// How long a lookup is reused before asking again
+pub const CACHE_HOURS: i64 = 24;
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Where prefixes are looked up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    /// `RIPEstat` announced-prefixes (what's actually routed)
+    Ripestat,
+    /// `RADb` route objects over whois (what's registered)
+    Whois,
+}
+
+impl Source {
+    /// The other source, tried when this one fails.
+    #[must_use]
+    pub const fn fallback(self) -> Self {
+        match self {
+            Self::Ripestat => Self::Whois,
+            Self::Whois => Self::Ripestat,
+        }
+    }
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Ripestat => "RIPEstat",
+            Self::Whois => "RADb whois",
+        })
+    }
+}
+
+/// The prefixes one AS announces.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Prefixes {
+    /// e.g. `AS9009`
+    pub asn: String,
+    /// Aggregated, IPv4 first
+    pub nets: Vec<IpNet>,
+    pub source: Source,
+    pub fetched_at: DateTime<Utc>,
+}
+
+impl Prefixes {
+    pub fn v4(&self) -> usize {
+        self.nets
+            .iter()
+            .filter(|n| matches!(n, IpNet::V4(_)))
+            .count()
+    }
+
+    pub fn v6(&self) -> usize {
+        self.nets.len() - self.v4()
+    }
+
+    /// IPv4 addresses covered.
+    pub fn v4_addresses(&self) -> u64 {
+        self.nets
+            .iter()
+            .filter_map(|n| match n {
+                IpNet::V4(n) => Some(1u64 << (32 - n.prefix_len())),
+                IpNet::V6(_) => None,
+            })
+            .sum()
+    }
+
+    /// IPv6 space covered, in /48s (the usual end-site assignment).
+    pub fn v6_48s(&self) -> u128 {
+        self.nets
+            .iter()
+            .filter_map(|n| match n {
+                IpNet::V6(n) if n.prefix_len() <= 48 => Some(1u128 << (48 - n.prefix_len())),
+                IpNet::V6(_) => Some(1),
+                IpNet::V4(_) => None,
+            })
+            .fold(0, u128::saturating_add)
+    }
+
+    fn fresh(&self) -> bool {
+        Utc::now() - self.fetched_at < chrono::Duration::hours(CACHE_HOURS)
+    }
+}
+
+/// Normalize `AS9009`, `as9009` or `9009` to `AS9009`.
+pub fn normalize(asn: &str) -> Result<String> {
+    let digits = asn.trim();
+    let digits = digits
+        .strip_prefix("AS")
+        .or_else(|| digits.strip_prefix("as"))
+        .or_else(|| digits.strip_prefix("As"))
+        .unwrap_or(digits);
+
+    match digits.parse::<u32>() {
+        Ok(n) if n > 0 => Ok(format!("AS{n}")),
+        _ => anyhow::bail!("'{asn}' is not an AS number (e.g. AS9009)"),
+    }
+}
+
+/// The configured source, `RIPEstat` unless `asn-source` says otherwise.
+pub fn source() -> Result<Source> {
+    Ok(Config::load()?.asn_source.unwrap_or(Source::Ripestat))
+}
+
+/// Get the ASN cache directory.
+pub fn dir() -> Result<PathBuf> {
+    let dirs = ProjectDirs::from("is", "i1", "i1")
+        .ok_or_else(|| anyhow::anyhow!("Could not determine config directory"))?;
+
+    Ok(dirs.config_dir().join("asn"))
+}
+
+/// The prefixes `asn` announces, from the cache if it's less than a day old.
+///
+/// `source` is asked first and the other one if that fails; `refresh`
+/// ignores the cache.
+pub async fn resolve(asn: &str, source: Source, refresh: bool) -> Result<Prefixes> {
+    let path = dir()?.join(format!("{asn}.json"));
+
+    if !refresh {
+        if let Ok(cached) = std::fs::read_to_string(&path) {
+            if let Ok(prefixes) = serde_json::from_str::<Prefixes>(&cached) {
+                if prefixes.fresh() {
+                    return Ok(prefixes);
+                }
+            }
+        }
+    }
+
+    let (nets, source) = match fetch(asn, source).await {
+        Ok(nets) => (nets, source),
+        Err(first) => match fetch(asn, source.fallback()).await {
+            Ok(nets) => (nets, source.fallback()),
+            Err(second) => anyhow::bail!(
+                "Could not look up {asn}: {source}: {first}; {}: {second}",
+                source.fallback()
+            ),
+        },
+    };
+    if nets.is_empty() {
+        anyhow::bail!("{asn} announces no prefixes (according to {source})");
+    }
+
+    let prefixes = Prefixes {
+        asn: asn.to_string(),
+        nets,
+        source,
+        fetched_at: Utc::now(),
+    };
+    std::fs::create_dir_all(dir()?)?;
+    std::fs::write(&path, serde_json::to_string_pretty(&prefixes)?)?;
+
+    Ok(prefixes)
+}
+
+async fn fetch(asn: &str, source: Source) -> Result<Vec<IpNet>> {
+    match source {
+        Source::Ripestat => {
+            let client = reqwest::Client::builder().timeout(TIMEOUT).build()?;
+            let body = client
+                .get(format!("{RIPESTAT_URL}{asn}"))
+                .send()
+                .await?
+                .error_for_status()?
+                .text()
+                .await?;
+            parse_ripestat(&body)
+        }
+        Source::Whois => {
+            let query = format!("-i origin {asn}\r\n");
+            let text = tokio::task::spawn_blocking(move || whois(&query)).await??;
+            Ok(parse_whois(&text))
+        }
+    }
+}
+
+/// Ask the whois server and read the whole answer.
+fn whois(query: &str) -> Result<String> {
+    let mut stream = TcpStream::connect(WHOIS_SERVER)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    stream.write_all(query.as_bytes())?;
+
+    let mut text = String::new();
+    stream.read_to_string(&mut text)?;
+    Ok(text)
+}
+
+/// Read the prefixes out of a `RIPEstat` announced-prefixes response.
+pub fn parse_ripestat(body: &str) -> Result<Vec<IpNet>> {
+    #[derive(Deserialize)]
+    struct Response {
+        status: String,
+        data: Data,
+    }
+    #[derive(Deserialize)]
+    struct Data {
+        #[serde(default)]
+        prefixes: Vec<Prefix>,
+    }
+    #[derive(Deserialize)]
+    struct Prefix {
+        prefix: String,
+    }
+
+    let response: Response = serde_json::from_str(body)
+        .map_err(|e| anyhow::anyhow!("unexpected RIPEstat response: {e}"))?;
+    if response.status != "ok" {
+        anyhow::bail!("RIPEstat answered '{}'", response.status);
+    }
+
+    let nets: Vec<IpNet> = response
+        .data
+        .prefixes
+        .iter()
+        .filter_map(|p| nft::parse_net(&p.prefix).ok())
+        .collect();
+    Ok(geo::aggregate(&nets))
+}
+
+/// Read the `route:` and `route6:` objects out of a whois answer.
+pub fn parse_whois(text: &str) -> Vec<IpNet> {
+    let nets: Vec<IpNet> = text
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            matches!(key.trim(), "route" | "route6")
+                .then(|| nft::parse_net(value).ok())
+                .flatten()
+        })
+        .collect();
+    geo::aggregate(&nets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RIPESTAT: &str = r#"{
+        "messages": [],
+        "data": {
+            "prefixes": [
+                {"prefix": "5.181.234.0/24", "timelines": [{"starttime": "2026-10-01T00:00:00", "endtime": "2026-10-15T00:00:00"}]},
+                {"prefix": "5.181.235.0/24", "timelines": []},
+                {"prefix": "2a0d:5600::/29", "timelines": []},
+                {"prefix": "not-a-prefix", "timelines": []}
+            ],
+            "query_starttime": "2026-10-01T00:00:00",
+            "query_endtime": "2026-10-15T00:00:00",
+            "resource": "9009"
+        },
+        "status": "ok",
+        "status_code": 200
+    }"#;
+
+    #[test]
+    fn test_parse_ripestat() {
+        let nets: Vec<String> = parse_ripestat(RIPESTAT)
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(nets, ["5.181.234.0/23", "2a0d:5600::/29"]);
+
+        assert!(parse_ripestat(r#"{"status": "error", "data": {}}"#).is_err());
+        assert!(parse_ripestat("<html>").is_err());
+    }
+
+    #[test]
+    fn test_parse_whois() {
+        let text = "\
+route:      5.181.234.0/24
+descr:      M247
+origin:     AS9009
+
+route6:     2a0d:5600::/29
+origin:     AS9009
+";
+        let nets: Vec<String> = parse_whois(text).iter().map(ToString::to_string).collect();
+        assert_eq!(nets, ["5.181.234.0/24", "2a0d:5600::/29"]);
+    }
+
+    #[test]
+    fn test_sizes() {
+        let prefixes = Prefixes {
+            asn: "AS9009".to_string(),
+            nets: parse_ripestat(RIPESTAT).unwrap(),
+            source: Source::Ripestat,
+            fetched_at: Utc::now(),
+        };
+        assert_eq!((prefixes.v4(), prefixes.v6()), (1, 1));
+        assert_eq!(prefixes.v4_addresses(), 512);
+        assert_eq!(prefixes.v6_48s(), 1 << 19);
+        assert!(prefixes.fresh());
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("AS9009").unwrap(), "AS9009");
+        assert_eq!(normalize("as9009").unwrap(), "AS9009");
+        assert_eq!(normalize(" 9009 ").unwrap(), "AS9009");
+        assert!(normalize("AS").is_err());
+        assert!(normalize("AS0").is_err());
+        assert!(normalize("1.2.3.4").is_err());
+    }
+}
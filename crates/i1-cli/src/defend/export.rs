@@ -0,0 +1,334 @@
+//! Firewall exports: the block list rendered for other firewalls.
+//!
+//! Every format is built from the same [`Blocklist`], so an nftables table
+//! and a plain CIDR list exported from one state always block the same
+//! addresses.
+
+use std::fmt::Write as _;
+
+use clap::ValueEnum;
+use ipnet::IpNet;
+
+use super::{geo, nft, State};
+
+/// Name prefix for the sets, tables and chains we export
+const NAME: &str = "i1";
+
+/// Export formats for `defend export`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// nftables table with sets and an input chain (`nft -f`)
+    #[value(alias = "nft")]
+    Nftables,
+    /// IPv4 rules for `iptables-restore`
+    #[value(alias = "iptables-restore", alias = "ipt")]
+    Iptables,
+    /// IPv6 rules for `ip6tables-restore`
+    #[value(alias = "ip6tables-restore")]
+    Ip6tables,
+    /// pf.conf tables and block rules (BSD/macOS)
+    Pf,
+    /// Sets for `ipset restore`
+    Ipset,
+    /// One CIDR per line, for fail2ban or cloud firewalls
+    #[value(alias = "cidr", alias = "fail2ban")]
+    List,
+}
+
+/// What gets blocked, with whitelisted ranges already cut out.
+#[derive(Debug, Clone, Default)]
+pub struct Blocklist {
+    /// Banned IPs and ranges, prefixes of banned ASNs and subscribed feeds
+    pub blocked: Vec<IpNet>,
+    /// Ranges of geo-blocked countries
+    pub geo: Vec<IpNet>,
+    /// Entries that aren't IPs or CIDRs and can't be exported
+    pub skipped: Vec<String>,
+}
+
+impl Blocklist {
+    /// Build the block list from `state`, the ranges of its countries and
+    /// the ranges of its subscribed feeds.
+    pub fn new(state: &State, geo: &[IpNet], feeds: &[IpNet]) -> Self {
+        let mut skipped = Vec::new();
+        let mut parse = |list: &[String]| -> Vec<IpNet> {
+            list.iter()
+                .filter_map(|target| {
+                    nft::parse_net(target)
+                        .map_err(|_| skipped.push(target.clone()))
+                        .ok()
+                })
+                .collect()
+        };
+        let mut blocked = parse(&state.blocked_ips);
+        let allowed = parse(&state.whitelisted_ips);
+        blocked.extend(state.asn_nets());
+        blocked.extend_from_slice(feeds);
+
+        Self {
+            blocked: exclude(&blocked, &allowed),
+            geo: exclude(geo, &allowed),
+            skipped,
+        }
+    }
+
+    /// Number of ranges across both lists.
+    pub fn len(&self) -> usize {
+        self.blocked.len() + self.geo.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// `nets` minus every range in `holes`, aggregated.
+///
+/// A range with a hole inside is split in half until the hole falls out on
+/// its own, so `10.0.0.0/8` minus `10.0.0.1` leaves 24 prefixes.
+pub fn exclude(nets: &[IpNet], holes: &[IpNet]) -> Vec<IpNet> {
+    let mut out = Vec::new();
+    let mut queue = nets.to_vec();
+
+    while let Some(net) = queue.pop() {
+        if holes.iter().any(|hole| hole.contains(&net)) {
+            continue;
+        }
+        if holes.iter().any(|hole| net.contains(hole)) {
+            if let Ok(halves) = net.subnets(net.prefix_len() + 1) {
+                queue.extend(halves);
+            }
+        } else {
+            out.push(net);
+        }
+    }
+
+    geo::aggregate(&out)
+}
+
+/// Render the block list in `format`.
+pub fn render(format: ExportFormat, list: &Blocklist) -> String {
+    match format {
+        ExportFormat::Nftables => nftables(list),
+        ExportFormat::Iptables => iptables(list, false),
+        ExportFormat::Ip6tables => iptables(list, true),
+        ExportFormat::Pf => pf(list),
+        ExportFormat::Ipset => ipset(list),
+        ExportFormat::List => cidr_list(list),
+    }
+}
+
+fn v4(nets: &[IpNet]) -> impl Iterator<Item = &IpNet> {
+    nets.iter().filter(|n| matches!(n, IpNet::V4(_)))
+}
+
+fn v6(nets: &[IpNet]) -> impl Iterator<Item = &IpNet> {
+    nets.iter().filter(|n| matches!(n, IpNet::V6(_)))
+}
+
+fn elements<'a>(nets: impl Iterator<Item = &'a IpNet>) -> Vec<String> {
+    nets.map(nft::element).collect()
+}
+
+fn nftables(list: &Blocklist) -> String {
+    let mut out = String::new();
+    let sets = [
+        (nft::BLOCKED_V4, "ipv4_addr", elements(v4(&list.blocked))),
+        (nft::BLOCKED_V6, "ipv6_addr", elements(v6(&list.blocked))),
+        (nft::GEO_V4, "ipv4_addr", elements(v4(&list.geo))),
+        (nft::GEO_V6, "ipv6_addr", elements(v6(&list.geo))),
+    ];
+
+    let _ = writeln!(out, "#!/usr/sbin/nft -f");
+    let _ = writeln!(out, "# Generated by i1 defend export");
+    let _ = writeln!(out, "# Apply with: nft -f <file>");
+    let _ = writeln!(out);
+    let _ = writeln!(out, "add table inet {NAME}");
+    let _ = writeln!(out, "delete table inet {NAME}");
+    let _ = writeln!(out, "table inet {NAME} {{");
+
+    for (name, kind, elements) in &sets {
+        let _ = writeln!(out, "    set {name} {{");
+        let _ = writeln!(out, "        type {kind}");
+        let _ = writeln!(out, "        flags interval");
+        if !elements.is_empty() {
+            let _ = writeln!(out, "        elements = {{ {} }}", elements.join(", "));
+        }
+        let _ = writeln!(out, "    }}");
+    }
+
+    let _ = writeln!(out, "    chain input {{");
+    let _ = writeln!(
+        out,
+        "        type filter hook input priority filter - 10; policy accept;"
+    );
+    for (name, kind, _) in &sets {
+        let family = if *kind == "ipv4_addr" { "ip" } else { "ip6" };
+        let _ = writeln!(out, "        {family} saddr @{name} drop");
+    }
+    let _ = writeln!(out, "    }}");
+    let _ = writeln!(out, "}}");
+
+    out
+}
+
+fn iptables(list: &Blocklist, ipv6: bool) -> String {
+    let mut out = String::new();
+    let tool = if ipv6 { "ip6tables" } else { "iptables" };
+    let chain = NAME.to_uppercase();
+
+    let _ = writeln!(out, "# Generated by i1 defend export");
+    let _ = writeln!(out, "# Load with: {tool}-restore --noflush < <file>");
+    let _ = writeln!(
+        out,
+        "# Then hook the chain in once: {tool} -I INPUT -j {chain}"
+    );
+    let _ = writeln!(out, "*filter");
+    let _ = writeln!(out, ":{chain} - [0:0]");
+    for net in list.blocked.iter().chain(&list.geo) {
+        if matches!(net, IpNet::V6(_)) == ipv6 {
+            let _ = writeln!(out, "-A {chain} -s {net} -j DROP");
+        }
+    }
+    let _ = writeln!(out, "COMMIT");
+
+    out
+}
+
+fn pf(list: &Blocklist) -> String {
+    let mut out = String::new();
+    let tables = [("blocked", &list.blocked), ("geo", &list.geo)];
+
+    let _ = writeln!(out, "# Generated by i1 defend export");
+    let _ = writeln!(
+        out,
+        "# Add to /etc/pf.conf and reload with: pfctl -f /etc/pf.conf"
+    );
+    for (name, nets) in tables {
+        // pf rejects an empty `{ }`, but a persistent table may start empty
+        if nets.is_empty() {
+            let _ = writeln!(out, "table <{NAME}_{name}> persist");
+        } else {
+            let _ = writeln!(
+                out,
+                "table <{NAME}_{name}> persist {{ {} }}",
+                elements(nets.iter()).join(", ")
+            );
+        }
+    }
+    for (name, _) in tables {
+        let _ = writeln!(out, "block drop in quick from <{NAME}_{name}>");
+    }
+
+    out
+}
+
+fn ipset(list: &Blocklist) -> String {
+    let mut out = String::new();
+    let sets = [
+        (nft::BLOCKED_V4, "inet", elements(v4(&list.blocked))),
+        (nft::BLOCKED_V6, "inet6", elements(v6(&list.blocked))),
+        (nft::GEO_V4, "inet", elements(v4(&list.geo))),
+        (nft::GEO_V6, "inet6", elements(v6(&list.geo))),
+    ];
+
+    let _ = writeln!(out, "# Generated by i1 defend export");
+    let _ = writeln!(out, "# Load with: ipset restore < <file>");
+    for (name, family, elements) in &sets {
+        // The default maxelem of 65536 is too small for the larger countries
+        let max = elements.len().max(65536);
+        let _ = writeln!(
+            out,
+            "create {NAME}_{name} hash:net family {family} maxelem {max} -exist"
+        );
+        let _ = writeln!(out, "flush {NAME}_{name}");
+        for element in elements {
+            let _ = writeln!(out, "add {NAME}_{name} {element} -exist");
+        }
+    }
+
+    out
+}
+
+fn cidr_list(list: &Blocklist) -> String {
+    let mut out = String::new();
+    for net in geo::aggregate(&[list.blocked.as_slice(), list.geo.as_slice()].concat()) {
+        let _ = writeln!(out, "{net}");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Blocklist {
+        let state = State {
+            blocked_ips: vec![
+                "1.2.3.4".to_string(),
+                "10.0.0.0/8".to_string(),
+                "2001:db8::/32".to_string(),
+                "junk".to_string(),
+            ],
+            whitelisted_ips: vec!["10.0.0.0/9".to_string(), "5.6.7.0/24".to_string()],
+            ..State::default()
+        };
+        let geo = ["5.6.0.0/16", "2a00::/16"].map(|n| n.parse().unwrap());
+        Blocklist::new(&state, &geo, &[])
+    }
+
+    #[test]
+    fn test_exclude() {
+        let nets = ["10.0.0.0/30".parse().unwrap()];
+        let holes = ["10.0.0.1".parse::<std::net::IpAddr>().unwrap().into()];
+        let left: Vec<String> = exclude(&nets, &holes)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(left, ["10.0.0.0/32", "10.0.0.2/31"]);
+
+        // Whitelisting the whole range leaves nothing
+        assert!(exclude(&nets, &nets).is_empty());
+
+        let list = sample();
+        assert_eq!(list.skipped, ["junk"]);
+        assert_eq!(list.blocked.len(), 3);
+        // 5.6.0.0/16 minus a /24 is one prefix per bit
+        assert_eq!(list.geo.len(), 9);
+    }
+
+    #[test]
+    fn test_golden_files() {
+        let list = sample();
+        let golden = [
+            (
+                ExportFormat::Nftables,
+                include_str!("../../tests/fixtures/export/nftables.nft"),
+            ),
+            (
+                ExportFormat::Iptables,
+                include_str!("../../tests/fixtures/export/iptables.rules"),
+            ),
+            (
+                ExportFormat::Ip6tables,
+                include_str!("../../tests/fixtures/export/ip6tables.rules"),
+            ),
+            (
+                ExportFormat::Pf,
+                include_str!("../../tests/fixtures/export/pf.conf"),
+            ),
+            (
+                ExportFormat::Ipset,
+                include_str!("../../tests/fixtures/export/ipset.restore"),
+            ),
+            (
+                ExportFormat::List,
+                include_str!("../../tests/fixtures/export/list.txt"),
+            ),
+        ];
+
+        for (format, expected) in golden {
+            assert_eq!(render(format, &list), expected, "{format:?}");
+        }
+    }
+}
@@ -0,0 +1,372 @@
+//! Blocklist feeds: third-party lists of bad ranges, kept up to date.
+//!
+//! Subscriptions live in `feeds/index.json` under the config directory,
+//! with each feed's last good download next to it as `<name>.list`. An
+//! update is validated in full before it replaces the cached list, so a
+//! broken download never reaches the firewall.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use directories::ProjectDirs;
+use ipnet::IpNet;
+use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use super::{geo, nft};
+
+/// Well-known feeds that can be added by name alone.
+pub const KNOWN: &[(&str, &str)] = &[
+    ("spamhaus-drop", "https://www.spamhaus.org/drop/drop.txt"),
+    ("spamhaus-edrop", "https://www.spamhaus.org/drop/edrop.txt"),
+    ("cins", "https://cinsscore.com/list/ci-badguys.txt"),
+];
+
+/// Refuse downloads bigger than this
+pub const MAX_BYTES: usize = 16 * 1024 * 1024;
+
+/// A feed losing more than this percentage of its entries needs --force
+pub const SHRINK_PERCENT: usize = 50;
+
+/// Reject a download where more than this percentage of lines don't parse
+const INVALID_PERCENT: usize = 10;
+
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// One subscription.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Feed {
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<String>,
+    /// When the list last changed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated: Option<DateTime<Utc>>,
+    /// Ranges in the cached list
+    #[serde(default)]
+    pub entries: usize,
+}
+
+impl Feed {
+    pub const fn new(url: String) -> Self {
+        Self {
+            url,
+            etag: None,
+            last_modified: None,
+            updated: None,
+            entries: 0,
+        }
+    }
+}
+
+/// Every subscription, by name.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Index {
+    #[serde(default)]
+    pub feeds: BTreeMap<String, Feed>,
+}
+
+/// What one feed's update found.
+#[derive(Debug, Default, Serialize)]
+pub struct Update {
+    /// The server answered 304 Not Modified
+    pub unchanged: bool,
+    pub added: usize,
+    pub removed: usize,
+    pub total: usize,
+    /// Lines that didn't parse and were left out
+    pub invalid: usize,
+    /// Held back because the feed shrank past [`SHRINK_PERCENT`]
+    pub held: bool,
+}
+
+/// Get the feeds directory.
+pub fn dir() -> Result<PathBuf> {
+    let dirs = ProjectDirs::from("is", "i1", "i1")
+        .ok_or_else(|| anyhow::anyhow!("Could not determine config directory"))?;
+
+    Ok(dirs.config_dir().join("feeds"))
+}
+
+impl Index {
+    /// Load the subscriptions, or none before the first `feeds add`.
+    pub fn load() -> Result<Self> {
+        let path = dir()?.join("index.json");
+
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = std::fs::read_to_string(&path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let dir = dir()?;
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(dir.join("index.json"), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// Check a feed name: it ends up in nft set names.
+pub fn validate_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name.len() <= 32
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+    if !valid {
+        anyhow::bail!("Feed names are up to 32 characters of a-z, 0-9, '-' and '_' (got '{name}')");
+    }
+    Ok(())
+}
+
+/// The URL of a well-known feed.
+pub fn known_url(name: &str) -> Option<&'static str> {
+    KNOWN.iter().find(|(n, _)| *n == name).map(|(_, url)| *url)
+}
+
+/// The nft set holding a feed's ranges for one family.
+pub fn set_name(name: &str, v6: bool) -> String {
+    format!(
+        "feed_{}_{}",
+        name.replace('-', "_"),
+        if v6 { "v6" } else { "v4" }
+    )
+}
+
+/// Parse a feed body: one IP or CIDR per line.
+///
+/// `#` and `;` start comments (Spamhaus writes `1.2.3.0/24 ; SBL123`), and
+/// anything after the first word is ignored. Returns the aggregated ranges
+/// and the number of lines that didn't parse.
+pub fn parse(text: &str) -> (Vec<IpNet>, usize) {
+    let mut nets = Vec::new();
+    let mut invalid = 0;
+
+    for line in text.lines() {
+        let line = line.split(['#', ';']).next().unwrap_or_default();
+        let Some(word) = line.split_whitespace().next() else {
+            continue;
+        };
+        match nft::parse_net(word) {
+            Ok(net) => nets.push(net),
+            Err(_) => invalid += 1,
+        }
+    }
+
+    (geo::aggregate(&nets), invalid)
+}
+
+/// Check a parsed download before it replaces the cached list.
+pub fn validate(nets: &[IpNet], invalid: usize) -> Result<()> {
+    if nets.is_empty() {
+        anyhow::bail!("no IPs or CIDR ranges found (is the URL right?)");
+    }
+    if invalid * 100 > (nets.len() + invalid) * INVALID_PERCENT {
+        anyhow::bail!(
+            "{invalid} of {} lines aren't IPs or CIDR ranges; not a blocklist?",
+            nets.len() + invalid
+        );
+    }
+    Ok(())
+}
+
+/// Whether going from `old` to `new` entries loses more than [`SHRINK_PERCENT`].
+pub const fn shrinks_too_much(old: usize, new: usize) -> bool {
+    old.saturating_sub(new) * 100 > old * SHRINK_PERCENT
+}
+
+/// The cached ranges of one feed.
+pub fn cached(name: &str) -> Result<Vec<IpNet>> {
+    let path = dir()?.join(format!("{name}.list"));
+
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = std::fs::read_to_string(&path)?;
+    Ok(content
+        .lines()
+        .filter_map(|line| line.trim().parse().ok())
+        .collect())
+}
+
+/// The cached ranges of every subscribed feed.
+pub fn load_all() -> Result<BTreeMap<String, Vec<IpNet>>> {
+    Index::load()?
+        .feeds
+        .into_keys()
+        .map(|name| {
+            let nets = cached(&name)?;
+            Ok((name, nets))
+        })
+        .collect()
+}
+
+/// Remove a feed's cached list.
+pub fn forget(name: &str) -> Result<()> {
+    let path = dir()?.join(format!("{name}.list"));
+    if path.exists() {
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// Download one feed and, unless `dry_run`, replace its cached list.
+///
+/// A feed that shrinks past [`SHRINK_PERCENT`] keeps its old list unless
+/// `force` is set; `force` also skips the conditional request.
+pub async fn update(name: &str, feed: &mut Feed, force: bool, dry_run: bool) -> Result<Update> {
+    let client = reqwest::Client::builder().timeout(TIMEOUT).build()?;
+
+    let mut request = client.get(&feed.url);
+    if !force && feed.updated.is_some() {
+        if let Some(etag) = &feed.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(modified) = &feed.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, modified);
+        }
+    }
+
+    let mut response = request
+        .send()
+        .await
+        .map_err(|e| anyhow::anyhow!("{}: {e}", feed.url))?;
+    if response.status() == StatusCode::NOT_MODIFIED {
+        return Ok(Update {
+            unchanged: true,
+            total: feed.entries,
+            ..Update::default()
+        });
+    }
+    response = response
+        .error_for_status()
+        .map_err(|e| anyhow::anyhow!("{}: {e}", feed.url))?;
+
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(String::from)
+    };
+    let (etag, last_modified) = (header(ETAG), header(LAST_MODIFIED));
+
+    if response
+        .content_length()
+        .is_some_and(|len| len > MAX_BYTES as u64)
+    {
+        anyhow::bail!("{}: larger than {} MB", feed.url, MAX_BYTES / 1024 / 1024);
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        body.extend_from_slice(&chunk);
+        if body.len() > MAX_BYTES {
+            anyhow::bail!("{}: larger than {} MB", feed.url, MAX_BYTES / 1024 / 1024);
+        }
+    }
+
+    let (nets, invalid) = parse(&String::from_utf8_lossy(&body));
+    validate(&nets, invalid).map_err(|e| anyhow::anyhow!("{}: {e}", feed.url))?;
+
+    let old: BTreeSet<IpNet> = cached(name)?.into_iter().collect();
+    let new: BTreeSet<IpNet> = nets.iter().copied().collect();
+    let update = Update {
+        unchanged: false,
+        added: new.difference(&old).count(),
+        removed: old.difference(&new).count(),
+        total: new.len(),
+        invalid,
+        held: !force && shrinks_too_much(old.len(), new.len()),
+    };
+
+    if dry_run || update.held {
+        return Ok(update);
+    }
+
+    let dir = dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let mut list = nets
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("\n");
+    list.push('\n');
+    std::fs::write(dir.join(format!("{name}.list")), list)?;
+
+    feed.etag = etag;
+    feed.last_modified = last_modified;
+    feed.updated = Some(Utc::now());
+    feed.entries = update.total;
+
+    Ok(update)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let text = "\
+; Spamhaus DROP List
+1.10.16.0/20 ; SBL256894
+1.19.0.0/16 ; SBL434604
+# CINS style
+5.6.7.8
+5.6.7.9
+<html>
+
+2001:db8::/32
+";
+        let (nets, invalid) = parse(text);
+        let nets: Vec<String> = nets.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            nets,
+            ["1.10.16.0/20", "1.19.0.0/16", "5.6.7.8/31", "2001:db8::/32"]
+        );
+        assert_eq!(invalid, 1);
+    }
+
+    #[test]
+    fn test_validate() {
+        let (nets, invalid) = parse("1.2.3.4\n5.6.7.8\n");
+        assert!(validate(&nets, invalid).is_ok());
+
+        // An HTML error page is not a blocklist
+        let (nets, invalid) = parse("<html>\n<body>Not found</body>\n</html>\n");
+        assert!(validate(&nets, invalid).is_err());
+
+        let (nets, invalid) = parse("1.2.3.4\n<p>\n<p>\n");
+        assert!(validate(&nets, invalid).is_err());
+    }
+
+    #[test]
+    fn test_shrinks_too_much() {
+        assert!(!shrinks_too_much(0, 0));
+        assert!(!shrinks_too_much(100, 60));
+        assert!(!shrinks_too_much(100, 150));
+        assert!(shrinks_too_much(100, 40));
+    }
+
+    #[test]
+    fn test_names() {
+        assert!(validate_name("spamhaus-drop").is_ok());
+        assert!(validate_name("Bad Name").is_err());
+        assert!(validate_name("").is_err());
+        assert_eq!(set_name("spamhaus-drop", false), "feed_spamhaus_drop_v4");
+        assert_eq!(
+            known_url("cins"),
+            Some("https://cinsscore.com/list/ci-badguys.txt")
+        );
+    }
+}
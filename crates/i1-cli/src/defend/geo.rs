@@ -0,0 +1,401 @@
+//! Country IP ranges from the RIR delegation files.
+//!
+//! Each regional internet registry publishes a `delegated-<rir>-extended-latest`
+//! file listing every allocation with the country it went to. `update`
+//! downloads the ones that changed (by `ETag` / `Last-Modified`), aggregates
+//! each country's allocations into as few prefixes as possible, and caches one
+//! zone file per country under the config directory.
+
+use std::collections::BTreeMap;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use directories::ProjectDirs;
+use ipnet::{IpNet, Ipv4Net, Ipv4Subnets, Ipv6Net};
+use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+
+/// The five registries and where they publish.
+pub const RIRS: &[(&str, &str)] = &[
+    (
+        "afrinic",
+        "https://ftp.afrinic.net/pub/stats/afrinic/delegated-afrinic-extended-latest",
+    ),
+    (
+        "apnic",
+        "https://ftp.apnic.net/stats/apnic/delegated-apnic-extended-latest",
+    ),
+    (
+        "arin",
+        "https://ftp.arin.net/pub/stats/arin/delegated-arin-extended-latest",
+    ),
+    (
+        "lacnic",
+        "https://ftp.lacnic.net/pub/stats/lacnic/delegated-lacnic-extended-latest",
+    ),
+    (
+        "ripencc",
+        "https://ftp.ripe.net/pub/stats/ripencc/delegated-ripencc-extended-latest",
+    ),
+];
+
+/// The delegation files are a few MB each
+const TIMEOUT: Duration = Duration::from_secs(120);
+
+/// What's cached, kept in `index.json` next to the zone files.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Index {
+    /// Per registry: validators for the next conditional download
+    #[serde(default)]
+    pub sources: BTreeMap<String, SourceMeta>,
+
+    /// Per country (lowercase code): prefix counts after aggregation
+    #[serde(default)]
+    pub countries: BTreeMap<String, CountryMeta>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SourceMeta {
+    pub url: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub fetched_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct CountryMeta {
+    pub v4: usize,
+    pub v6: usize,
+    pub updated: DateTime<Utc>,
+}
+
+/// What an `update` did.
+#[derive(Debug, Default)]
+pub struct UpdateReport {
+    /// Registries whose file changed and was downloaded
+    pub downloaded: Vec<String>,
+    /// Registries that answered 304 Not Modified
+    pub unchanged: Vec<String>,
+    /// Countries written, if the zones were rebuilt
+    pub rebuilt: Option<usize>,
+}
+
+/// Get the cache directory.
+pub fn dir() -> Result<PathBuf> {
+    let dirs = ProjectDirs::from("is", "i1", "i1")
+        .ok_or_else(|| anyhow::anyhow!("Could not determine config directory"))?;
+
+    Ok(dirs.config_dir().join("geo"))
+}
+
+impl Index {
+    /// Load the index, or an empty one before the first update.
+    pub fn load() -> Result<Self> {
+        let path = dir()?.join("index.json");
+
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = std::fs::read_to_string(&path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    fn save(&self) -> Result<()> {
+        let dir = dir()?;
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(dir.join("index.json"), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// When the zones were last rebuilt.
+    pub fn updated(&self) -> Option<DateTime<Utc>> {
+        self.countries.values().map(|c| c.updated).max()
+    }
+}
+
+/// Parse a delegated-extended file into allocations per country.
+///
+/// Lines are `registry|cc|type|start|value|date|status[|opaque-id]`. For
+/// `ipv4` the value is an address count (not always a power of two); for
+/// `ipv6` it is a prefix length. Only `allocated` and `assigned` records
+/// count; the version header, summaries and comments are skipped.
+pub fn parse_delegated(text: &str) -> BTreeMap<String, Vec<IpNet>> {
+    let mut countries: BTreeMap<String, Vec<IpNet>> = BTreeMap::new();
+
+    for line in text.lines() {
+        let fields: Vec<&str> = line.trim().split('|').collect();
+        if line.starts_with('#') || fields.len() < 7 {
+            continue;
+        }
+        let (cc, kind, start, value, status) =
+            (fields[1], fields[2], fields[3], fields[4], fields[6]);
+        if cc.len() != 2 || cc == "ZZ" || !matches!(status, "allocated" | "assigned") {
+            continue;
+        }
+
+        let nets = countries.entry(cc.to_lowercase()).or_default();
+        match kind {
+            "ipv4" => {
+                let (Ok(start), Ok(count)) = (start.parse::<Ipv4Addr>(), value.parse::<u32>())
+                else {
+                    continue;
+                };
+                let Some(end) = count
+                    .checked_sub(1)
+                    .and_then(|n| u32::from(start).checked_add(n))
+                else {
+                    continue;
+                };
+                nets.extend(Ipv4Subnets::new(start, Ipv4Addr::from(end), 0).map(IpNet::V4));
+            }
+            "ipv6" => {
+                let (Ok(start), Ok(len)) = (start.parse::<Ipv6Addr>(), value.parse::<u8>()) else {
+                    continue;
+                };
+                if let Ok(net) = Ipv6Net::new(start, len) {
+                    nets.push(IpNet::V6(net.trunc()));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    countries.retain(|_, nets| !nets.is_empty());
+    countries
+}
+
+/// Merge adjacent and overlapping prefixes, IPv4 first.
+pub fn aggregate(nets: &[IpNet]) -> Vec<IpNet> {
+    let v4: Vec<Ipv4Net> = nets
+        .iter()
+        .filter_map(|n| match n {
+            IpNet::V4(n) => Some(*n),
+            IpNet::V6(_) => None,
+        })
+        .collect();
+    let v6: Vec<Ipv6Net> = nets
+        .iter()
+        .filter_map(|n| match n {
+            IpNet::V6(n) => Some(*n),
+            IpNet::V4(_) => None,
+        })
+        .collect();
+
+    Ipv4Net::aggregate(&v4)
+        .into_iter()
+        .map(IpNet::V4)
+        .chain(Ipv6Net::aggregate(&v6).into_iter().map(IpNet::V6))
+        .collect()
+}
+
+/// Download the registry files that changed and rebuild the country zones.
+///
+/// `mirror` replaces the registries' own servers: files are fetched from
+/// `<mirror>/delegated-<rir>-extended-latest`. `force` skips the
+/// conditional request and rebuilds everything.
+pub async fn update(mirror: Option<&str>, force: bool) -> Result<UpdateReport> {
+    let dir = dir()?;
+    std::fs::create_dir_all(&dir)?;
+
+    let client = reqwest::Client::builder().timeout(TIMEOUT).build()?;
+    let mut index = Index::load()?;
+    let mut report = UpdateReport::default();
+
+    for (rir, default_url) in RIRS {
+        let url = mirror.map_or_else(
+            || (*default_url).to_string(),
+            |m| {
+                format!(
+                    "{}/delegated-{rir}-extended-latest",
+                    m.trim_end_matches('/')
+                )
+            },
+        );
+        let raw_path = dir.join(format!("delegated-{rir}"));
+
+        let mut request = client.get(&url);
+        let cached = index
+            .sources
+            .get(*rir)
+            .filter(|meta| !force && meta.url == url && raw_path.exists());
+        if let Some(meta) = cached {
+            if let Some(etag) = &meta.etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(modified) = &meta.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, modified);
+            }
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| anyhow::anyhow!("{url}: {e}"))?;
+        if response.status() == StatusCode::NOT_MODIFIED {
+            report.unchanged.push((*rir).to_string());
+            continue;
+        }
+        let response = response
+            .error_for_status()
+            .map_err(|e| anyhow::anyhow!("{url}: {e}"))?;
+
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(String::from)
+        };
+        let meta = SourceMeta {
+            url: url.clone(),
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+            fetched_at: Utc::now(),
+        };
+
+        std::fs::write(&raw_path, response.bytes().await?)?;
+        index.sources.insert((*rir).to_string(), meta);
+        report.downloaded.push((*rir).to_string());
+    }
+
+    if force || !report.downloaded.is_empty() || index.countries.is_empty() {
+        report.rebuilt = Some(rebuild(&mut index)?);
+    }
+    index.save()?;
+
+    Ok(report)
+}
+
+/// Re-parse every cached registry file and rewrite the zone files.
+fn rebuild(index: &mut Index) -> Result<usize> {
+    let dir = dir()?;
+    let mut countries: BTreeMap<String, Vec<IpNet>> = BTreeMap::new();
+
+    for (rir, _) in RIRS {
+        let raw_path = dir.join(format!("delegated-{rir}"));
+        if !raw_path.exists() {
+            continue;
+        }
+        let text = String::from_utf8_lossy(&std::fs::read(&raw_path)?).into_owned();
+        for (cc, nets) in parse_delegated(&text) {
+            countries.entry(cc).or_default().extend(nets);
+        }
+    }
+
+    let now = Utc::now();
+    index.countries.clear();
+    for (cc, nets) in &countries {
+        let nets = aggregate(nets);
+        let mut zone = nets
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("\n");
+        zone.push('\n');
+        std::fs::write(dir.join(format!("{cc}.zone")), zone)?;
+
+        let v4 = nets.iter().filter(|n| matches!(n, IpNet::V4(_))).count();
+        index.countries.insert(
+            cc.clone(),
+            CountryMeta {
+                v4,
+                v6: nets.len() - v4,
+                updated: now,
+            },
+        );
+    }
+
+    Ok(countries.len())
+}
+
+/// Cached ranges for one country, if the zones have been built.
+pub fn ranges(code: &str) -> Result<Option<Vec<IpNet>>> {
+    let path = dir()?.join(format!("{}.zone", code.to_lowercase()));
+
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let zone = std::fs::read_to_string(&path)?;
+    Ok(Some(
+        zone.lines()
+            .filter_map(|line| line.trim().parse().ok())
+            .collect(),
+    ))
+}
+
+/// Ranges for every country, running a first `update` if nothing is cached.
+pub async fn country_ranges(countries: &[String]) -> Result<Vec<IpNet>> {
+    if countries.is_empty() {
+        return Ok(Vec::new());
+    }
+    if Index::load()?.countries.is_empty() {
+        update(mirror()?.as_deref(), false).await?;
+    }
+
+    let mut all = Vec::new();
+    for code in countries {
+        let Some(nets) = ranges(code)? else {
+            anyhow::bail!(
+                "No allocations found for country '{code}'. \
+                 Check the code with: i1 defend geoblock codes"
+            );
+        };
+        all.extend(nets);
+    }
+
+    Ok(all)
+}
+
+/// The mirror from the config file, if one is set.
+pub fn mirror() -> Result<Option<String>> {
+    Ok(Config::load()?.geo_mirror)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = include_str!("../../tests/fixtures/delegated-extended-sample.txt");
+
+    fn nets(list: &[&str]) -> Vec<IpNet> {
+        list.iter().map(|n| n.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_parse_delegated() {
+        let countries = parse_delegated(SAMPLE);
+
+        // Only allocated/assigned records with a real country code
+        assert_eq!(countries.keys().collect::<Vec<_>>(), ["de", "nl", "us"]);
+
+        // 768 addresses is a /23 plus a /24
+        assert_eq!(
+            countries["de"][..2],
+            nets(&["2.160.0.0/23", "2.160.2.0/24"])
+        );
+        assert_eq!(countries["nl"].len(), 3);
+        assert_eq!(countries["us"], nets(&["2600::/12"]));
+    }
+
+    #[test]
+    fn test_aggregate() {
+        let countries = parse_delegated(SAMPLE);
+
+        // A /21 and two /22s line up into one /20
+        assert_eq!(aggregate(&countries["nl"]), nets(&["193.0.0.0/20"]));
+
+        // Adjacent /32s merge into a /31; IPv4 comes first
+        assert_eq!(
+            aggregate(&countries["de"]),
+            nets(&["2.160.0.0/23", "2.160.2.0/24", "2001:db8::/31"])
+        );
+    }
+}
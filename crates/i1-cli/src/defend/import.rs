@@ -0,0 +1,553 @@
+//! Reading ban lists for `defend import`.
+//!
+//! Takes plain IP/CIDR lists, search results as JSON or NDJSON (one
+//! `HostInfo` per line), and CSV with an `ip` column. Bad lines are
+//! counted, with the first few kept as examples, rather than failing the
+//! whole import.
+//!
+//! Input is read a line at a time on a blocking thread and handed over a
+//! bounded channel ([`spawn`]), so a list is never held in memory whole.
+//! The exception is a JSON document, which can only be parsed whole; NDJSON
+//! is the format for big exports. New targets are merged into CIDR ranges
+//! as they arrive ([`Ranges`]), so memory follows the number of distinct
+//! ranges rather than the number of lines, up to [`MAX_RANGES`].
+
+use std::io::{BufRead, Cursor, Read};
+use std::net::Ipv4Addr;
+use std::ops::ControlFlow;
+
+use anyhow::Result;
+use clap::ValueEnum;
+use ipnet::IpNet;
+use serde_json::Value;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use super::nft;
+
+/// Targets the reader may get ahead of whoever consumes them
+pub const CHANNEL_CAPACITY: usize = 4096;
+
+/// New ranges taken in between merges
+const MERGE_EVERY: usize = 1 << 16;
+
+/// Most distinct ranges one import may hold. With merging that caps memory
+/// at `MAX_RANGES + MERGE_EVERY` ranges of ~20 bytes, a few tens of
+/// megabytes, however long the input is.
+pub const MAX_RANGES: usize = 1_000_000;
+
+/// Malformed lines kept as examples; the rest are only counted
+pub const MALFORMED_SAMPLES: usize = 5;
+
+/// Input formats for `defend import`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ImportFormat {
+    /// Guess from the first line
+    Auto,
+    /// One IP or CIDR per line, `#` starts a comment
+    List,
+    /// One JSON host per line (`ip_str` or `ip`)
+    #[value(alias = "jsonl")]
+    Ndjson,
+    /// A JSON host, a list of hosts, or `i1 search -o json` output
+    Json,
+    /// CSV with an `ip` or `ip_str` column
+    Csv,
+}
+
+/// A line that couldn't be read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Malformed {
+    /// Line number, or entry number for JSON
+    pub line: usize,
+    pub reason: String,
+}
+
+/// A target read from the input, or why its line isn't one, by line number.
+pub type Line = (usize, Result<IpNet>);
+
+/// How many lines were malformed, and the first [`MALFORMED_SAMPLES`].
+#[derive(Debug, Default)]
+pub struct Rejected {
+    pub count: usize,
+    pub samples: Vec<Malformed>,
+}
+
+impl Rejected {
+    pub fn push(&mut self, line: usize, reason: &anyhow::Error) {
+        self.count += 1;
+        if self.samples.len() < MALFORMED_SAMPLES {
+            self.samples.push(Malformed {
+                line,
+                reason: reason.to_string(),
+            });
+        }
+    }
+}
+
+/// Read targets from `input` on a blocking thread.
+///
+/// They come through a channel of [`CHANNEL_CAPACITY`], so a slow consumer
+/// holds the reading back instead of lines piling up. Dropping the receiver
+/// stops the reading. The task fails only if the input can't be read, or
+/// is a CSV without an IP column.
+pub fn spawn(
+    input: impl BufRead + Send + 'static,
+    format: ImportFormat,
+) -> (mpsc::Receiver<Line>, JoinHandle<Result<()>>) {
+    let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+    let reader = tokio::task::spawn_blocking(move || {
+        read(
+            input,
+            format,
+            &mut |line, target| match tx.blocking_send((line, target)) {
+                Ok(()) => ControlFlow::Continue(()),
+                Err(_) => ControlFlow::Break(()),
+            },
+        )
+    });
+    (rx, reader)
+}
+
+/// Read targets from `input`, handing each to `each` in order until it
+/// breaks.
+///
+/// Only an unreadable input or a CSV without an IP column fails outright;
+/// anything else wrong is handed over per line.
+pub fn read(
+    mut input: impl BufRead,
+    format: ImportFormat,
+    each: &mut dyn FnMut(usize, Result<IpNet>) -> ControlFlow<()>,
+) -> Result<()> {
+    // Guessing needs the first entry; the lines up to it are read again
+    let mut head = String::new();
+    let format = match format {
+        ImportFormat::Auto => loop {
+            let start = head.len();
+            let eof = input.read_line(&mut head)? == 0;
+            let line = head[start..].trim();
+            if eof || (!line.is_empty() && !line.starts_with('#')) {
+                break detect(&head);
+            }
+        },
+        format => format,
+    };
+    let input = Cursor::new(head).chain(input);
+
+    match format {
+        ImportFormat::Auto | ImportFormat::List => read_list(input, each),
+        ImportFormat::Ndjson => read_ndjson(input, each),
+        ImportFormat::Json => read_json(input, each),
+        ImportFormat::Csv => read_csv(input, each),
+    }
+}
+
+/// Guess the format from the first line that isn't blank or a comment.
+pub fn detect(text: &str) -> ImportFormat {
+    let Some(first) = text
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+    else {
+        return ImportFormat::List;
+    };
+
+    if first.starts_with('[') {
+        ImportFormat::Json
+    } else if first.starts_with('{') {
+        // A pretty-printed document spans lines; NDJSON is whole per line
+        if serde_json::from_str::<Value>(first).is_ok() {
+            ImportFormat::Ndjson
+        } else {
+            ImportFormat::Json
+        }
+    } else if first.contains(',') {
+        ImportFormat::Csv
+    } else {
+        ImportFormat::List
+    }
+}
+
+fn read_list(
+    input: impl BufRead,
+    each: &mut dyn FnMut(usize, Result<IpNet>) -> ControlFlow<()>,
+) -> Result<()> {
+    for (i, line) in input.lines().enumerate() {
+        let line = line?;
+        let target = line.split('#').next().unwrap_or_default().trim();
+        if !target.is_empty() && each(i + 1, nft::parse_net(target)).is_break() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn read_ndjson(
+    input: impl BufRead,
+    each: &mut dyn FnMut(usize, Result<IpNet>) -> ControlFlow<()>,
+) -> Result<()> {
+    for (i, line) in input.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let target = serde_json::from_str::<Value>(line)
+            .map_err(|e| anyhow::anyhow!("invalid JSON: {e}"))
+            .and_then(|host| host_ip(&host));
+        if each(i + 1, target).is_break() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn read_json(
+    mut input: impl BufRead,
+    each: &mut dyn FnMut(usize, Result<IpNet>) -> ControlFlow<()>,
+) -> Result<()> {
+    let mut text = String::new();
+    input.read_to_string(&mut text)?;
+
+    let value: Value = match serde_json::from_str(&text) {
+        Ok(value) => value,
+        Err(e) => {
+            let _ = each(e.line(), Err(anyhow::anyhow!("invalid JSON: {e}")));
+            return Ok(());
+        }
+    };
+
+    // `i1 search -o json` wraps the hosts in a `results` list
+    let hosts = match value {
+        Value::Object(mut object) if object.contains_key("results") => {
+            object.remove("results").unwrap_or_default()
+        }
+        value => value,
+    };
+    let hosts = match hosts {
+        Value::Array(hosts) => hosts,
+        host => vec![host],
+    };
+
+    for (i, host) in hosts.iter().enumerate() {
+        if each(i + 1, host_ip(host)).is_break() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn read_csv(
+    input: impl BufRead,
+    each: &mut dyn FnMut(usize, Result<IpNet>) -> ControlFlow<()>,
+) -> Result<()> {
+    let mut reader = csv::Reader::from_reader(input);
+    let column = reader
+        .headers()?
+        .iter()
+        .position(|h| matches!(h.trim().to_lowercase().as_str(), "ip" | "ip_str"))
+        .ok_or_else(|| anyhow::anyhow!("CSV has no 'ip' or 'ip_str' column"))?;
+
+    for record in reader.records() {
+        let (line, target) = match record {
+            Ok(record) => {
+                let line = record.position().map_or(0, csv::Position::line);
+                let target = record
+                    .get(column)
+                    .ok_or_else(|| anyhow::anyhow!("missing ip column"))
+                    .and_then(nft::parse_net);
+                (line, target)
+            }
+            Err(e) if e.is_io_error() => return Err(e.into()),
+            Err(e) => (e.position().map_or(0, csv::Position::line), Err(e.into())),
+        };
+        if each(usize::try_from(line).unwrap_or(usize::MAX), target).is_break() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// The address of a host: `ip_str`, or `ip` as text or Shodan's integer.
+fn host_ip(host: &Value) -> Result<IpNet> {
+    match (&host["ip_str"], &host["ip"]) {
+        (Value::String(ip), _) | (_, Value::String(ip)) => nft::parse_net(ip),
+        (_, Value::Number(n)) => n
+            .as_u64()
+            .and_then(|n| u32::try_from(n).ok())
+            .map(|n| IpNet::from(std::net::IpAddr::from(Ipv4Addr::from(n))))
+            .ok_or_else(|| anyhow::anyhow!("'{n}' is not an IPv4 address")),
+        _ => anyhow::bail!("no 'ip_str' or 'ip' field"),
+    }
+}
+
+/// New targets, merged into the fewest CIDR ranges covering exactly the
+/// same addresses: `10.0.0.0` through `10.0.0.255` become `10.0.0.0/24`,
+/// and duplicates disappear.
+///
+/// Targets are merged every [`MERGE_EVERY`] pushes, so only distinct ranges
+/// take up memory; past [`MAX_RANGES`] of them, [`Ranges::push`] fails.
+#[derive(Debug, Default)]
+pub struct Ranges {
+    nets: Vec<IpNet>,
+    /// How many of `nets` were there after the last merge
+    merged: usize,
+}
+
+impl Ranges {
+    pub fn push(&mut self, net: IpNet) -> Result<()> {
+        self.nets.push(net);
+        if self.nets.len() >= self.merged + MERGE_EVERY {
+            self.merge();
+            if self.nets.len() > MAX_RANGES {
+                anyhow::bail!(
+                    "More than {MAX_RANGES} distinct ranges to ban. Split the input \
+                     and import it in parts."
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Ranges held right now, merged or not.
+    pub fn len(&self) -> usize {
+        self.nets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nets.is_empty()
+    }
+
+    /// The merged ranges, IPv4 first, each in address order.
+    pub fn finish(mut self) -> Vec<IpNet> {
+        self.merge();
+        self.nets
+    }
+
+    fn merge(&mut self) {
+        self.nets = IpNet::aggregate(&self.nets);
+        self.merged = self.nets.len();
+    }
+}
+
+/// Ranges that can be asked quickly whether they cover a target, for
+/// checking a big import against the whitelist and existing bans.
+#[derive(Debug, Default)]
+pub struct Cover(Vec<IpNet>);
+
+impl Cover {
+    /// The IPs and CIDRs in `entries`; anything else is left out.
+    pub fn new(entries: &[String]) -> Self {
+        let nets: Vec<IpNet> = entries
+            .iter()
+            .filter_map(|entry| nft::parse_net(entry).ok())
+            .collect();
+        let mut nets = IpNet::aggregate(&nets);
+        nets.sort_unstable_by_key(IpNet::network);
+        Self(nets)
+    }
+
+    /// Whether one range covers all of `net`.
+    pub fn covers(&self, net: &IpNet) -> bool {
+        // Merged ranges don't overlap, so only the last one starting at or
+        // before `net` can hold it
+        let after = self.0.partition_point(|r| r.network() <= net.network());
+        after > 0 && self.0[after - 1].contains(net)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufReader, Write};
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    /// Everything read from the input, in order.
+    #[derive(Debug, Default)]
+    struct Parsed {
+        targets: Vec<IpNet>,
+        malformed: Vec<Malformed>,
+    }
+
+    fn parse(text: &str, format: ImportFormat) -> Result<Parsed> {
+        let mut parsed = Parsed::default();
+        read(text.as_bytes(), format, &mut |line, target| {
+            match target {
+                Ok(net) => parsed.targets.push(net),
+                Err(e) => parsed.malformed.push(Malformed {
+                    line,
+                    reason: e.to_string(),
+                }),
+            }
+            ControlFlow::Continue(())
+        })?;
+        Ok(parsed)
+    }
+
+    fn targets(parsed: &Parsed) -> Vec<String> {
+        parsed.targets.iter().map(nft::element).collect()
+    }
+
+    fn nets(nets: &[&str]) -> Vec<IpNet> {
+        nets.iter().map(|n| nft::parse_net(n).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(detect("# bans\n1.2.3.4\n"), ImportFormat::List);
+        assert_eq!(detect("{\"ip_str\": \"1.2.3.4\"}\n"), ImportFormat::Ndjson);
+        assert_eq!(detect("{\n  \"total\": 1\n}"), ImportFormat::Json);
+        assert_eq!(detect("[]"), ImportFormat::Json);
+        assert_eq!(detect("ip,port\n1.2.3.4,22\n"), ImportFormat::Csv);
+        assert_eq!(detect(""), ImportFormat::List);
+    }
+
+    #[test]
+    fn test_parse_list() {
+        let parsed = parse(
+            "# from fail2ban\n1.2.3.4\n\n10.0.0.0/8  # office\nnot-an-ip\n",
+            ImportFormat::Auto,
+        )
+        .unwrap();
+        assert_eq!(targets(&parsed), ["1.2.3.4", "10.0.0.0/8"]);
+        assert_eq!(parsed.malformed.len(), 1);
+        assert_eq!(parsed.malformed[0].line, 5);
+    }
+
+    #[test]
+    fn test_parse_ndjson() {
+        let text = concat!(
+            "{\"ip_str\": \"1.2.3.4\", \"port\": 22}\n",
+            "{\"ip\": 16909060}\n",
+            "{\"port\": 80}\n",
+            "{oops\n",
+            "{\"ip\": \"2001:db8::1\"}\n",
+        );
+        let parsed = parse(text, ImportFormat::Auto).unwrap();
+        assert_eq!(targets(&parsed), ["1.2.3.4", "1.2.3.4", "2001:db8::1"]);
+        let lines: Vec<usize> = parsed.malformed.iter().map(|m| m.line).collect();
+        assert_eq!(lines, [3, 4]);
+    }
+
+    #[test]
+    fn test_parse_json() {
+        let search = r#"{
+            "total": 2,
+            "results": [{"ip_str": "1.2.3.4"}, {"ip_str": "5.6.7.8"}]
+        }"#;
+        let parsed = parse(search, ImportFormat::Auto).unwrap();
+        assert_eq!(targets(&parsed), ["1.2.3.4", "5.6.7.8"]);
+
+        let parsed = parse(r#"[{"ip": "9.9.9.9"}, {}]"#, ImportFormat::Json).unwrap();
+        assert_eq!(targets(&parsed), ["9.9.9.9"]);
+        assert_eq!(parsed.malformed[0].line, 2);
+    }
+
+    #[test]
+    fn test_parse_csv() {
+        let text = "port,IP,org\n22,1.2.3.4,Acme\n80,bogus,Acme\n443,5.6.7.8,Acme\n";
+        let parsed = parse(text, ImportFormat::Auto).unwrap();
+        assert_eq!(targets(&parsed), ["1.2.3.4", "5.6.7.8"]);
+        assert_eq!(parsed.malformed[0].line, 3);
+
+        assert!(parse("port,org\n22,Acme\n", ImportFormat::Csv).is_err());
+    }
+
+    #[test]
+    fn test_rejected() {
+        let mut rejected = Rejected::default();
+        for line in 1..=20 {
+            rejected.push(line, &anyhow::anyhow!("bad"));
+        }
+        assert_eq!(rejected.count, 20);
+        assert_eq!(rejected.samples.len(), MALFORMED_SAMPLES);
+        assert_eq!(rejected.samples[0].line, 1);
+    }
+
+    #[test]
+    fn test_ranges() {
+        let mut ranges = Ranges::default();
+        for net in nets(&["10.0.0.1", "10.0.0.0", "10.0.0.2", "10.0.0.3", "10.0.0.1"]) {
+            ranges.push(net).unwrap();
+        }
+        ranges
+            .push(nft::parse_net("2001:db8::/64").unwrap())
+            .unwrap();
+        ranges.push(nft::parse_net("10.9.0.0/16").unwrap()).unwrap();
+        let merged: Vec<String> = ranges.finish().iter().map(nft::element).collect();
+        assert_eq!(merged, ["10.0.0.0/30", "10.9.0.0/16", "2001:db8::/64"]);
+    }
+
+    #[test]
+    fn test_cover() {
+        let cover = Cover::new(&[
+            "10.0.0.0/8".to_string(),
+            "192.0.2.7".to_string(),
+            "AS13335".to_string(),
+            "2001:db8::/32".to_string(),
+        ]);
+        for net in nets(&["10.1.2.3", "10.9.0.0/16", "192.0.2.7", "2001:db8:1::1"]) {
+            assert!(cover.covers(&net), "{net}");
+        }
+        for net in nets(&["9.255.255.255", "0.0.0.0/0", "192.0.2.8", "11.0.0.0", "::1"]) {
+            assert!(!cover.covers(&net), "{net}");
+        }
+        assert!(!Cover::default().covers(&nets(&["10.0.0.1"])[0]));
+    }
+
+    /// A million-line list goes through in bounded memory and sane time.
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_import_million_lines() {
+        const LINES: u32 = 1_000_000;
+
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        {
+            let mut out = std::io::BufWriter::new(&mut file);
+            writeln!(out, "# generated").unwrap();
+            for i in 0..LINES {
+                if i % 100_000 == 0 {
+                    writeln!(out, "bogus-{i}").unwrap();
+                }
+                // 10.0.0.0 upwards, then each address again
+                writeln!(out, "{}", Ipv4Addr::from(0x0a00_0000 + i % (LINES / 2))).unwrap();
+            }
+        }
+
+        let started = Instant::now();
+        let input = BufReader::new(std::fs::File::open(file.path()).unwrap());
+        let (mut lines, reader) = spawn(input, ImportFormat::Auto);
+        let mut ranges = Ranges::default();
+        let mut rejected = Rejected::default();
+        let mut peak = 0;
+        while let Some((line, target)) = lines.recv().await {
+            match target {
+                Ok(net) => ranges.push(net).unwrap(),
+                Err(e) => rejected.push(line, &e),
+            }
+            peak = peak.max(ranges.len());
+        }
+        reader.await.unwrap().unwrap();
+
+        assert_eq!(rejected.count, 10);
+        assert_eq!(rejected.samples.len(), MALFORMED_SAMPLES);
+        assert_eq!(rejected.samples[0].line, 2);
+        assert!(peak <= 2 * MERGE_EVERY, "held {peak} ranges");
+
+        // 500,000 addresses from 10.0.0.0: a /14, a /15, a /16 and so on
+        let merged = ranges.finish();
+        let covered: u64 = merged
+            .iter()
+            .map(|net| 1u64 << (32 - net.prefix_len()))
+            .sum();
+        assert_eq!(covered, u64::from(LINES / 2));
+        assert_eq!(nft::element(&merged[0]), "10.0.0.0/14");
+        assert!(merged.len() < 20);
+
+        assert!(
+            started.elapsed() < Duration::from_secs(60),
+            "took {:?}",
+            started.elapsed()
+        );
+    }
+}
@@ -0,0 +1,438 @@
+//! Journal of defend changes: what was blocked, when and why.
+//!
+//! One JSON object per line in the config directory, only ever appended
+//! to. `status` and `history` show the recent entries, and `undo` walks it
+//! backwards. Lines that can't be read are skipped with a warning, so a
+//! torn write doesn't lose the rest of the history.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use directories::ProjectDirs;
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// Format version written to every line
+pub const VERSION: u32 = 1;
+
+/// Targets listed by [`Entry::summary`] before the rest are counted
+const SUMMARY_TARGETS: usize = 3;
+
+/// A change to the block list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Ban,
+    Unban,
+    BanAsn,
+    UnbanAsn,
+    Geoblock,
+    Ungeoblock,
+    Allow,
+    Disallow,
+    /// A blocklist feed was subscribed to
+    Subscribe,
+    /// A blocklist feed was dropped
+    Unsubscribe,
+    /// A feed's list was refreshed
+    FeedUpdate,
+    /// The firewall table was removed
+    Disable,
+    /// The newest change still in effect was reverted
+    Undo,
+}
+
+impl Action {
+    /// The change that reverts this one.
+    pub const fn inverse(self) -> Option<Self> {
+        match self {
+            Self::Ban => Some(Self::Unban),
+            Self::Unban => Some(Self::Ban),
+            Self::BanAsn => Some(Self::UnbanAsn),
+            Self::UnbanAsn => Some(Self::BanAsn),
+            Self::Geoblock => Some(Self::Ungeoblock),
+            Self::Ungeoblock => Some(Self::Geoblock),
+            Self::Allow => Some(Self::Disallow),
+            Self::Disallow => Some(Self::Allow),
+            Self::Subscribe | Self::Unsubscribe | Self::FeedUpdate | Self::Disable | Self::Undo => {
+                None
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Ban => "ban",
+            Self::Unban => "unban",
+            Self::BanAsn => "ban asn",
+            Self::UnbanAsn => "unban asn",
+            Self::Geoblock => "geoblock",
+            Self::Ungeoblock => "remove geoblock",
+            Self::Allow => "whitelist",
+            Self::Disallow => "remove whitelist",
+            Self::Subscribe => "add feed",
+            Self::Unsubscribe => "remove feed",
+            Self::FeedUpdate => "update feed",
+            Self::Disable => "disable",
+            Self::Undo => "undo",
+        };
+        f.write_str(name)
+    }
+}
+
+/// One journal line: a single command, however many targets it touched.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    /// Format version; lines from before versioning read as 0
+    #[serde(default)]
+    pub v: u32,
+    pub at: DateTime<Utc>,
+    pub action: Action,
+    /// IPs, ranges, ASNs or country codes the change touched
+    #[serde(
+        default,
+        alias = "target",
+        deserialize_with = "one_or_many",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub targets: Vec<String>,
+    /// Prefixes an ASN target resolved to, so undo restores the same group
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub prefixes: Vec<String>,
+    /// Bans lifted along with the change, so undo puts them back with it
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unbanned: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Where the targets came from when they weren't typed in, e.g. `import:bans.txt`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// Who ran the command (the user behind sudo, if any)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actor: Option<String>,
+    /// Whether the firewall was changed, or only the saved state
+    #[serde(default)]
+    pub applied: bool,
+}
+
+impl Entry {
+    pub fn new(action: Action, target: impl Into<String>) -> Self {
+        Self::batch(action, vec![target.into()])
+    }
+
+    /// One entry for a change to several targets at once.
+    pub fn batch(action: Action, mut targets: Vec<String>) -> Self {
+        targets.retain(|t| !t.is_empty());
+        Self {
+            v: VERSION,
+            at: Utc::now(),
+            action,
+            targets,
+            prefixes: Vec::new(),
+            unbanned: Vec::new(),
+            reason: None,
+            source: None,
+            actor: actor(),
+            applied: false,
+        }
+    }
+
+    #[must_use]
+    pub fn reason(mut self, reason: Option<String>) -> Self {
+        self.reason = reason;
+        self
+    }
+
+    #[must_use]
+    pub fn source(mut self, source: impl Into<String>) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+
+    #[must_use]
+    pub fn prefixes(mut self, prefixes: Vec<String>) -> Self {
+        self.prefixes = prefixes;
+        self
+    }
+
+    #[must_use]
+    pub fn unbanned(mut self, unbanned: Vec<String>) -> Self {
+        self.unbanned = unbanned;
+        self
+    }
+
+    /// The action and its first few targets, e.g. `ban 1.2.3.4, 5.6.7.8 and 10 more`,
+    /// and how many bans it lifted.
+    pub fn summary(&self) -> String {
+        let shown = self.targets.len().min(SUMMARY_TARGETS);
+        let mut summary = format!("{} {}", self.action, self.targets[..shown].join(", "));
+        if self.targets.len() > shown {
+            summary = format!("{summary} and {} more", self.targets.len() - shown);
+        }
+        if !self.unbanned.is_empty() {
+            summary = format!("{summary}, lifting {} ban(s)", self.unbanned.len());
+        }
+        summary.trim_end().to_string()
+    }
+}
+
+/// Accept the single `target` of older lines as well as a `targets` list.
+fn one_or_many<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(target) if target.is_empty() => Vec::new(),
+        OneOrMany::One(target) => vec![target],
+        OneOrMany::Many(targets) => targets,
+    })
+}
+
+/// The user running i1, looking past sudo.
+fn actor() -> Option<String> {
+    ["SUDO_USER", "USER", "USERNAME"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok().filter(|v| !v.is_empty()))
+}
+
+/// Get the journal file path.
+pub fn path() -> Result<PathBuf> {
+    let dirs = ProjectDirs::from("is", "i1", "i1")
+        .ok_or_else(|| anyhow::anyhow!("Could not determine config directory"))?;
+
+    Ok(dirs.config_dir().join("defend_journal.jsonl"))
+}
+
+/// Load every entry, oldest first.
+pub fn load() -> Result<Vec<Entry>> {
+    read(&path()?)
+}
+
+/// Read a journal file, skipping lines that can't be used.
+pub fn read(path: &Path) -> Result<Vec<Entry>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = std::fs::read_to_string(path)?;
+    let mut entries = Vec::new();
+
+    for (i, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<Entry>(line) {
+            Ok(entry) if entry.v > VERSION => eprintln!(
+                "Warning: {}:{} was written by a newer i1 (format {}), skipped",
+                path.display(),
+                i + 1,
+                entry.v
+            ),
+            Ok(entry) => entries.push(entry),
+            Err(e) => eprintln!(
+                "Warning: {}:{} is unreadable ({e}), skipped",
+                path.display(),
+                i + 1
+            ),
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Add an entry to the end of the journal, and to the audit log.
+pub fn append(entry: &Entry) -> Result<()> {
+    append_to(&path()?, entry)?;
+    crate::audit::action(&format!("defend {}", entry.action), &entry.targets);
+    Ok(())
+}
+
+/// Add an entry to the end of a journal file.
+pub fn append_to(path: &Path, entry: &Entry) -> Result<()> {
+    // Ensure parent directory exists
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(file, "{}", serde_json::to_string(entry)?)?;
+
+    Ok(())
+}
+
+/// The newest `steps` changes that haven't been undone yet, newest first.
+pub fn undoable(entries: &[Entry], steps: usize) -> Vec<&Entry> {
+    let mut stack = Vec::new();
+
+    for entry in entries {
+        match entry.action {
+            Action::Undo => {
+                stack.pop();
+            }
+            // Feed changes and disable aren't undoable
+            _ if entry.action.inverse().is_none() => {}
+            _ => stack.push(entry),
+        }
+    }
+
+    stack.into_iter().rev().take(steps).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::super::{nft, State};
+    use super::*;
+
+    #[test]
+    fn test_undoable() {
+        let entries = vec![
+            Entry::new(Action::Ban, "1.2.3.4"),
+            Entry::batch(Action::Ban, vec!["5.6.7.8".into(), "9.9.9.9".into()]),
+            Entry::new(Action::Geoblock, "cn"),
+            Entry::new(Action::Disable, ""),
+            Entry::new(Action::Undo, "cn"),
+        ];
+
+        // The geoblock was undone and disable isn't undoable
+        let next = undoable(&entries, 1);
+        assert_eq!(next.len(), 1);
+        assert_eq!(next[0].targets, ["5.6.7.8", "9.9.9.9"]);
+
+        let all = undoable(&entries, 5);
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[1].targets, ["1.2.3.4"]);
+
+        assert!(undoable(&entries[..0], 1).is_empty());
+        assert!(undoable(
+            &[
+                Entry::new(Action::Ban, "1.2.3.4"),
+                Entry::new(Action::Undo, "")
+            ],
+            1
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn test_summary() {
+        let targets = (1..=5).map(|i| format!("10.0.0.{i}")).collect();
+        assert_eq!(
+            Entry::batch(Action::Ban, targets).summary(),
+            "ban 10.0.0.1, 10.0.0.2, 10.0.0.3 and 2 more"
+        );
+        assert_eq!(Entry::new(Action::Disable, "").summary(), "disable");
+        assert_eq!(
+            Entry::new(Action::Allow, "10.0.0.0/8")
+                .unbanned(vec!["10.1.2.3".into()])
+                .summary(),
+            "whitelist 10.0.0.0/8, lifting 1 ban(s)"
+        );
+    }
+
+    #[test]
+    fn test_read_skips_bad_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.jsonl");
+        let lines = [
+            // Written before the format was versioned
+            r#"{"at":"2026-01-01T00:00:00Z","action":"ban","target":"1.2.3.4","applied":true}"#,
+            r#"{"at":"2026-01-01T00:00:01Z","action":"ban","targ"#,
+            r#"{"v":99,"at":"2026-01-01T00:00:02Z","action":"ban","targets":["5.6.7.8"]}"#,
+            "",
+            r#"{"v":1,"at":"2026-01-01T00:00:03Z","action":"geoblock","targets":["cn","ru"]}"#,
+        ];
+        std::fs::write(&path, lines.join("\n")).unwrap();
+
+        let entries = read(&path).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].targets, ["1.2.3.4"]);
+        assert_eq!(entries[1].targets, ["cn", "ru"]);
+
+        assert!(read(&dir.path().join("missing.jsonl")).unwrap().is_empty());
+    }
+
+    /// ban, undo, then check the state and the rules it would load.
+    #[test]
+    fn test_ban_undo_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.jsonl");
+        let mut state = State::default();
+
+        assert!(state.apply(Action::Ban, "1.2.3.4"));
+        assert!(state.apply(Action::Ban, "10.0.0.0/8"));
+        append_to(&path, &Entry::new(Action::Ban, "1.2.3.4")).unwrap();
+        append_to(&path, &Entry::new(Action::Ban, "10.0.0.0/8")).unwrap();
+        assert!(nft::ruleset(&state, &[], &BTreeMap::new()).contains("1.2.3.4, 10.0.0.0/8"));
+
+        // Undo the newest ban only
+        let entries = read(&path).unwrap();
+        let last = undoable(&entries, 1)[0];
+        assert!(state.revert(last));
+        append_to(&path, &Entry::batch(Action::Undo, last.targets.clone())).unwrap();
+
+        assert_eq!(state.blocked_ips, ["1.2.3.4"]);
+        let batch = nft::ruleset(&state, &[], &BTreeMap::new());
+        assert!(batch.contains("elements = { 1.2.3.4 }"));
+        assert!(!batch.contains("10.0.0.0/8"));
+        if nft::available() {
+            nft::apply(&batch, true).unwrap();
+        }
+
+        // Then the older one, leaving nothing to undo
+        let entries = read(&path).unwrap();
+        let remaining = undoable(&entries, 3);
+        assert_eq!(remaining.len(), 1);
+        assert!(state.revert(remaining[0]));
+        append_to(&path, &Entry::new(Action::Undo, "1.2.3.4")).unwrap();
+
+        assert!(state.blocked_ips.is_empty());
+        assert!(undoable(&read(&path).unwrap(), 1).is_empty());
+    }
+
+    /// whitelist a range covering bans, undo, and get the same state back.
+    #[test]
+    fn test_whitelist_conflicts_undo_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.jsonl");
+        let mut state = State::default();
+        state.apply(Action::Ban, "198.51.100.1");
+        state.apply(Action::Ban, "203.0.113.7");
+        state.apply(Action::Ban, "203.0.113.128/25");
+        let before = serde_json::to_value(&state).unwrap();
+
+        // The whitelist lifts the two bans inside it, in one journal line
+        let covered = vec!["203.0.113.7".to_string(), "203.0.113.128/25".to_string()];
+        assert!(state.apply(Action::Allow, "203.0.113.0/24"));
+        for target in &covered {
+            state.apply(Action::Unban, target);
+        }
+        append_to(
+            &path,
+            &Entry::new(Action::Allow, "203.0.113.0/24").unbanned(covered),
+        )
+        .unwrap();
+        assert_eq!(state.blocked_ips, ["198.51.100.1"]);
+        let entries = read(&path).unwrap();
+        assert_eq!(entries.len(), 1);
+
+        // One undo takes the whitelist entry out and puts the bans back
+        let last = undoable(&entries, 1)[0];
+        assert!(state.revert(last));
+        append_to(&path, &Entry::batch(Action::Undo, last.targets.clone())).unwrap();
+
+        assert_eq!(serde_json::to_value(&state).unwrap(), before);
+        assert!(undoable(&read(&path).unwrap(), 1).is_empty());
+    }
+}
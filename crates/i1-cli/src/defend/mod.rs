@@ -1,10 +1,25 @@
 //! Defense module: geo-blocking, IP banning, firewall rule generation.
 
+pub mod asn;
+pub mod export;
+pub mod feeds;
+pub mod geo;
+pub mod import;
+pub mod journal;
+pub mod nft;
+pub mod plan;
+#[cfg(feature = "whois")]
+pub mod suggest;
+
 use anyhow::Result;
 use directories::ProjectDirs;
+use ipnet::IpNet;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
+use journal::{Action, Entry};
+
 /// Defense state - what's currently blocked.
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct State {
@@ -17,6 +32,10 @@ pub struct State {
     /// Blocked AS numbers (e.g., "AS12345").
     pub blocked_asns: Vec<String>,
 
+    /// Prefixes banned on behalf of each blocked AS number.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub asn_prefixes: BTreeMap<String, Vec<String>>,
+
     /// Whitelisted IPs (never blocked).
     pub whitelisted_ips: Vec<String>,
 }
@@ -58,6 +77,79 @@ impl State {
 
         Ok(())
     }
+
+    /// Apply one journaled change. Returns false if it was already in effect.
+    pub fn apply(&mut self, action: Action, target: &str) -> bool {
+        let (list, add) = match action {
+            Action::Ban => (&mut self.blocked_ips, true),
+            Action::Unban => (&mut self.blocked_ips, false),
+            Action::BanAsn => (&mut self.blocked_asns, true),
+            Action::UnbanAsn => (&mut self.blocked_asns, false),
+            Action::Geoblock => (&mut self.blocked_countries, true),
+            Action::Ungeoblock => (&mut self.blocked_countries, false),
+            Action::Allow => (&mut self.whitelisted_ips, true),
+            Action::Disallow => (&mut self.whitelisted_ips, false),
+            Action::Subscribe
+            | Action::Unsubscribe
+            | Action::FeedUpdate
+            | Action::Disable
+            | Action::Undo => return false,
+        };
+
+        let pos = list.iter().position(|t| t.eq_ignore_ascii_case(target));
+        let changed = match (pos, add) {
+            (None, true) => {
+                list.push(target.to_string());
+                true
+            }
+            (Some(pos), false) => {
+                list.remove(pos);
+                true
+            }
+            _ => false,
+        };
+        if action == Action::UnbanAsn {
+            self.asn_prefixes.remove(target);
+        }
+        changed
+    }
+
+    /// Ban an AS number along with the prefixes it resolved to.
+    pub fn ban_asn(&mut self, asn: &str, prefixes: Vec<String>) -> bool {
+        let added = self.apply(Action::BanAsn, asn);
+        if added && !prefixes.is_empty() {
+            self.asn_prefixes.insert(asn.to_string(), prefixes);
+        }
+        added
+    }
+
+    /// Every prefix banned on behalf of an AS number.
+    pub fn asn_nets(&self) -> Vec<IpNet> {
+        self.asn_prefixes
+            .values()
+            .flatten()
+            .filter_map(|p| nft::parse_net(p).ok())
+            .collect()
+    }
+
+    /// Revert a journaled change. Returns whether anything changed.
+    pub fn revert(&mut self, entry: &Entry) -> bool {
+        let Some(inverse) = entry.action.inverse() else {
+            return false;
+        };
+        let mut changed = false;
+        for target in &entry.targets {
+            changed |= if inverse == Action::BanAsn {
+                self.ban_asn(target, entry.prefixes.clone())
+            } else {
+                self.apply(inverse, target)
+            };
+        }
+        for target in &entry.unbanned {
+            changed |= self.apply(Action::Ban, target);
+        }
+        changed
+    }
 }
 
 /// Geo-blocking operations.
@@ -68,205 +160,25 @@ pub struct IpSet;
 
 /// Get country name from code.
 pub fn country_name(code: &str) -> &'static str {
-    match code.to_lowercase().as_str() {
-        "cn" => "China",
-        "ru" => "Russia",
-        "us" => "United States",
-        "ro" => "Romania",
-        "pl" => "Poland",
-        "kz" => "Kazakhstan",
-        "ua" => "Ukraine",
-        "vn" => "Vietnam",
-        "br" => "Brazil",
-        "in" => "India",
-        "kr" => "South Korea",
-        "de" => "Germany",
-        "fr" => "France",
-        "gb" | "uk" => "United Kingdom",
-        "jp" => "Japan",
-        "nl" => "Netherlands",
-        "th" => "Thailand",
-        "id" => "Indonesia",
-        "ca" => "Canada",
-        "au" => "Australia",
-        "mx" => "Mexico",
-        "it" => "Italy",
-        "es" => "Spain",
-        "ar" => "Argentina",
-        "eg" => "Egypt",
-        "za" => "South Africa",
-        "ng" => "Nigeria",
-        "pk" => "Pakistan",
-        "bd" => "Bangladesh",
-        "ph" => "Philippines",
-        "my" => "Malaysia",
-        "sg" => "Singapore",
-        "hk" => "Hong Kong",
-        "tw" => "Taiwan",
-        "ir" => "Iran",
-        "kp" => "North Korea",
-        _ => "Unknown",
-    }
-}
-
-/// Generate nftables rules from state.
-pub fn generate_nftables(state: &State) -> Result<String> {
-    let mut rules = String::new();
-
-    rules.push_str("#!/usr/sbin/nft -f\n");
-    rules.push_str("# Generated by showdi1 defend export\n");
-    rules.push_str("# Apply with: nft -f <filename>\n\n");
-
-    rules.push_str("table inet geoblock {\n");
-
-    // Whitelist set
-    if !state.whitelisted_ips.is_empty() {
-        rules.push_str("    set whitelist {\n");
-        rules.push_str("        type ipv4_addr\n");
-        rules.push_str("        flags interval\n");
-        rules.push_str("        elements = { ");
-        rules.push_str(&state.whitelisted_ips.join(", "));
-        rules.push_str(" }\n");
-        rules.push_str("    }\n\n");
-    }
-
-    // Blocked IPs set
-    if !state.blocked_ips.is_empty() {
-        rules.push_str("    set blocked_ips {\n");
-        rules.push_str("        type ipv4_addr\n");
-        rules.push_str("        flags interval\n");
-        rules.push_str("        elements = { ");
-        rules.push_str(&state.blocked_ips.join(", "));
-        rules.push_str(" }\n");
-        rules.push_str("    }\n\n");
-    }
-
-    // Country sets (placeholder - would need IP ranges)
-    for country in &state.blocked_countries {
-        rules.push_str(&format!(
-            "    # Country: {} ({})\n",
-            country.to_uppercase(),
-            country_name(country)
-        ));
-        rules.push_str(&format!("    # Download ranges from: https://www.ipdeny.com/ipblocks/data/aggregated/{country}-aggregated.zone\n"));
-        rules.push_str(&format!("    set country_{country} {{\n"));
-        rules.push_str("        type ipv4_addr\n");
-        rules.push_str("        flags interval\n");
-        rules.push_str("        # elements = { ... load from zone file ... }\n");
-        rules.push_str("    }\n\n");
-    }
-
-    // Chain
-    rules.push_str("    chain input {\n");
-    rules.push_str("        type filter hook input priority 0; policy accept;\n\n");
-
-    // Whitelist rule
-    if !state.whitelisted_ips.is_empty() {
-        rules.push_str("        # Allow whitelisted IPs\n");
-        rules.push_str("        ip saddr @whitelist accept\n\n");
-    }
-
-    // Block rules
-    if !state.blocked_ips.is_empty() {
-        rules.push_str("        # Block specific IPs\n");
-        rules.push_str("        ip saddr @blocked_ips drop\n\n");
-    }
-
-    for country in &state.blocked_countries {
-        rules.push_str(&format!("        # Block {}\n", country_name(country)));
-        rules.push_str(&format!("        ip saddr @country_{country} drop\n"));
-    }
-
-    rules.push_str("    }\n");
-    rules.push_str("}\n");
-
-    Ok(rules)
-}
-
-/// Generate iptables rules from state.
-pub fn generate_iptables(state: &State) -> Result<String> {
-    let mut rules = String::new();
-
-    rules.push_str("#!/bin/bash\n");
-    rules.push_str("# Generated by showdi1 defend export\n");
-    rules.push_str("# Run as root to apply\n\n");
-
-    rules.push_str("# Create chain\n");
-    rules.push_str("iptables -N GEOBLOCK 2>/dev/null || iptables -F GEOBLOCK\n\n");
-
-    // Whitelist
-    for ip in &state.whitelisted_ips {
-        rules.push_str(&format!(
-            "# Whitelist\niptables -A GEOBLOCK -s {ip} -j ACCEPT\n"
-        ));
-    }
-
-    if !state.whitelisted_ips.is_empty() {
-        rules.push('\n');
-    }
-
-    // Block IPs
-    for ip in &state.blocked_ips {
-        rules.push_str(&format!("iptables -A GEOBLOCK -s {ip} -j DROP\n"));
-    }
-
-    // Country blocks (placeholder)
-    for country in &state.blocked_countries {
-        rules.push_str(&format!(
-            "\n# Block {} ({})\n",
-            country_name(country),
-            country.to_uppercase()
-        ));
-        rules.push_str(&format!("# Download: curl -s https://www.ipdeny.com/ipblocks/data/aggregated/{country}-aggregated.zone | while read ip; do\n"));
-        rules.push_str("#   iptables -A GEOBLOCK -s $ip -j DROP\n");
-        rules.push_str("# done\n");
-    }
-
-    rules.push_str("\n# Insert chain into INPUT\n");
-    rules.push_str("iptables -I INPUT -j GEOBLOCK\n");
-
-    Ok(rules)
+    i1::country::country_name(code).unwrap_or("Unknown")
 }
 
-/// Generate pf rules for BSD/macOS.
-pub fn generate_pf(state: &State) -> Result<String> {
-    let mut rules = String::new();
-
-    rules.push_str("# Generated by showdi1 defend export\n");
-    rules.push_str("# Add to /etc/pf.conf and run: pfctl -f /etc/pf.conf\n\n");
-
-    // Tables
-    if !state.whitelisted_ips.is_empty() {
-        rules.push_str("table <whitelist> { ");
-        rules.push_str(&state.whitelisted_ips.join(", "));
-        rules.push_str(" }\n");
-    }
-
-    if !state.blocked_ips.is_empty() {
-        rules.push_str("table <blocked> { ");
-        rules.push_str(&state.blocked_ips.join(", "));
-        rules.push_str(" }\n");
-    }
-
-    for country in &state.blocked_countries {
-        rules.push_str(&format!(
-            "# table <{country}> {{ ... load from {country}-aggregated.zone ... }}\n"
-        ));
-    }
-
-    rules.push_str("\n# Rules\n");
-
-    if !state.whitelisted_ips.is_empty() {
-        rules.push_str("pass in quick from <whitelist>\n");
-    }
-
-    if !state.blocked_ips.is_empty() {
-        rules.push_str("block in quick from <blocked>\n");
-    }
-
-    for country in &state.blocked_countries {
-        rules.push_str(&format!("# block in quick from <{country}>\n"));
-    }
-
-    Ok(rules)
+/// The lowercase code of the country `input` names, by code or by name
+/// (`kr`, `South Korea`, `Korea, Republic of`). Unknown countries are an
+/// error suggesting the closest ones.
+pub fn country_code(input: &str) -> Result<String> {
+    if let Some(code) = i1::country::country_code(input) {
+        return Ok(code.to_lowercase());
+    }
+    let suggestions: Vec<String> = i1::country::suggestions(input)
+        .into_iter()
+        .map(|code| format!("{} ({})", code.to_lowercase(), country_name(code)))
+        .collect();
+    if suggestions.is_empty() {
+        anyhow::bail!("Unknown country '{input}'. See: i1 defend geoblock codes");
+    }
+    anyhow::bail!(
+        "Unknown country '{input}'. Did you mean {}?",
+        suggestions.join(", ")
+    )
 }
@@ -0,0 +1,545 @@
+//! nftables backend: keeps `table inet i1` in step with [`State`].
+//!
+//! Every change rebuilds the whole table in a single `nft -f -` batch, so the
+//! firewall never sees a half-applied block list.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::process::{Command, Stdio};
+
+use anyhow::Result;
+use ipnet::IpNet;
+use serde::Serialize;
+
+use super::{feeds, State};
+
+/// Table holding everything i1 manages
+pub const TABLE: &str = "i1";
+
+/// Banned IPs and ranges
+pub const BLOCKED_V4: &str = "blocked_v4";
+pub const BLOCKED_V6: &str = "blocked_v6";
+
+/// Prefixes of banned AS numbers
+pub const ASN_V4: &str = "asn_v4";
+pub const ASN_V6: &str = "asn_v6";
+
+/// Ranges of geo-blocked countries
+pub const GEO_V4: &str = "geo_v4";
+pub const GEO_V6: &str = "geo_v6";
+
+/// Whitelisted IPs, accepted before anything is dropped
+pub const ALLOWED_V4: &str = "allowed_v4";
+pub const ALLOWED_V6: &str = "allowed_v6";
+
+/// Parse an IP or CIDR the way nft stores it (host bits cleared).
+pub fn parse_net(target: &str) -> Result<IpNet> {
+    let target = target.trim();
+    let net = target
+        .parse::<IpNet>()
+        .ok()
+        .or_else(|| i1::validate_ip(target).ok().map(IpNet::from))
+        .ok_or_else(|| anyhow::anyhow!("'{target}' is not an IP address or CIDR range"))?;
+
+    Ok(net.trunc())
+}
+
+/// An element as nft prints it: single hosts without a prefix.
+pub fn element(net: &IpNet) -> String {
+    if net.prefix_len() == net.max_prefix_len() {
+        net.addr().to_string()
+    } else {
+        net.to_string()
+    }
+}
+
+/// The blocked set a target belongs in.
+pub const fn blocked_set(net: &IpNet) -> &'static str {
+    match net {
+        IpNet::V4(_) => BLOCKED_V4,
+        IpNet::V6(_) => BLOCKED_V6,
+    }
+}
+
+/// The whitelist set a target belongs in.
+pub const fn allowed_set(net: &IpNet) -> &'static str {
+    match net {
+        IpNet::V4(_) => ALLOWED_V4,
+        IpNet::V6(_) => ALLOWED_V6,
+    }
+}
+
+/// What every set should hold for `state`.
+///
+/// Banned AS numbers get the prefixes they resolved to. `geo` holds the
+/// ranges of every blocked country, and `feeds` each subscribed feed's,
+/// which get a pair of sets per feed. Entries that aren't IPs or CIDRs are
+/// left out.
+pub fn sets(
+    state: &State,
+    geo: &[IpNet],
+    feeds: &BTreeMap<String, Vec<IpNet>>,
+) -> BTreeMap<String, Vec<IpNet>> {
+    let mut sets: BTreeMap<String, Vec<IpNet>> = BTreeMap::new();
+
+    for name in [
+        ALLOWED_V4, ALLOWED_V6, BLOCKED_V4, BLOCKED_V6, ASN_V4, ASN_V6, GEO_V4, GEO_V6,
+    ] {
+        sets.insert(name.to_string(), Vec::new());
+    }
+
+    for (list, set_for) in [
+        (
+            &state.whitelisted_ips,
+            allowed_set as fn(&IpNet) -> &'static str,
+        ),
+        (&state.blocked_ips, blocked_set),
+    ] {
+        for net in list.iter().filter_map(|target| parse_net(target).ok()) {
+            sets.entry(set_for(&net).to_string()).or_default().push(net);
+        }
+    }
+    for (nets, v4, v6) in [
+        (state.asn_nets().as_slice(), ASN_V4, ASN_V6),
+        (geo, GEO_V4, GEO_V6),
+    ] {
+        for net in nets {
+            let set = match net {
+                IpNet::V4(_) => v4,
+                IpNet::V6(_) => v6,
+            };
+            sets.entry(set.to_string()).or_default().push(*net);
+        }
+    }
+    for (name, nets) in feeds {
+        for v6 in [false, true] {
+            let elements = nets
+                .iter()
+                .filter(|n| matches!(n, IpNet::V6(_)) == v6)
+                .copied()
+                .collect();
+            sets.insert(feeds::set_name(name, v6), elements);
+        }
+    }
+
+    sets
+}
+
+/// Build the batch that replaces the table with `state`.
+///
+/// The sets are those of [`sets`]. Entries in `state` that aren't IPs or
+/// CIDRs can't be expressed in a set and are left as comments.
+pub fn ruleset(state: &State, geo: &[IpNet], feeds: &BTreeMap<String, Vec<IpNet>>) -> String {
+    let mut out = String::new();
+
+    for target in state.whitelisted_ips.iter().chain(&state.blocked_ips) {
+        if parse_net(target).is_err() {
+            let _ = writeln!(out, "# skipped {target}: not an IP address or CIDR range");
+        }
+    }
+    let sets = sets(state, geo, feeds);
+
+    // `add` first so the `delete` can't fail on a fresh machine
+    let _ = writeln!(out, "add table inet {TABLE}");
+    let _ = writeln!(out, "delete table inet {TABLE}");
+    let _ = writeln!(out, "table inet {TABLE} {{");
+
+    for (name, nets) in &sets {
+        let elements: Vec<String> = nets.iter().map(element).collect();
+        let kind = if name.ends_with("v4") {
+            "ipv4_addr"
+        } else {
+            "ipv6_addr"
+        };
+        let _ = writeln!(out, "    set {name} {{");
+        let _ = writeln!(out, "        type {kind}");
+        let _ = writeln!(out, "        flags interval");
+        let _ = writeln!(out, "        auto-merge");
+        if !elements.is_empty() {
+            let _ = writeln!(out, "        elements = {{ {} }}", elements.join(", "));
+        }
+        let _ = writeln!(out, "    }}");
+    }
+
+    let _ = writeln!(out, "    chain input {{");
+    let _ = writeln!(
+        out,
+        "        type filter hook input priority filter - 10; policy accept;"
+    );
+    let _ = writeln!(out, "        ip saddr @{ALLOWED_V4} accept");
+    let _ = writeln!(out, "        ip6 saddr @{ALLOWED_V6} accept");
+    let _ = writeln!(out, "        ip saddr @{BLOCKED_V4} drop");
+    let _ = writeln!(out, "        ip6 saddr @{BLOCKED_V6} drop");
+    let _ = writeln!(out, "        ip saddr @{ASN_V4} drop");
+    let _ = writeln!(out, "        ip6 saddr @{ASN_V6} drop");
+    let _ = writeln!(out, "        ip saddr @{GEO_V4} drop");
+    let _ = writeln!(out, "        ip6 saddr @{GEO_V6} drop");
+    for set in sets.keys().filter(|set| set.starts_with("feed_")) {
+        let family = if set.ends_with("v4") { "ip" } else { "ip6" };
+        let _ = writeln!(out, "        {family} saddr @{set} drop");
+    }
+    let _ = writeln!(out, "    }}");
+    let _ = writeln!(out, "}}");
+
+    out
+}
+
+/// The batch that removes the table and every rule in it.
+pub fn teardown() -> String {
+    format!("add table inet {TABLE}\ndelete table inet {TABLE}\n")
+}
+
+/// Whether the `nft` binary is installed.
+pub fn available() -> bool {
+    Command::new("nft")
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|s| s.success())
+}
+
+/// Whether we're running as root.
+pub fn is_root() -> bool {
+    Command::new("id")
+        .arg("-u")
+        .output()
+        .is_ok_and(|o| String::from_utf8_lossy(&o.stdout).trim() == "0")
+}
+
+/// Run a batch through `nft -f -`, or only check it with `dry_run`.
+///
+/// Applying needs root; checking doesn't.
+pub fn apply(batch: &str, dry_run: bool) -> Result<()> {
+    if !dry_run && !is_root() {
+        anyhow::bail!(
+            "Changing the firewall needs root. Re-run with sudo, \
+             or add --dry-run to check the rules without applying them."
+        );
+    }
+
+    let mut command = Command::new("nft");
+    if dry_run {
+        command.arg("--check");
+    }
+    let mut child = command
+        .args(["-f", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                anyhow::anyhow!(
+                    "nft not found. Install nftables, or use 'i1 defend export' \
+                     to generate rules for another firewall."
+                )
+            } else {
+                e.into()
+            }
+        })?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(batch.as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "nft rejected the rules: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(())
+}
+
+/// Whether the live set covers `net`.
+pub fn contains(set: &str, net: &IpNet) -> bool {
+    Command::new("nft")
+        .args(["get", "element", "inet", TABLE, set])
+        .arg(format!("{{ {} }}", element(net)))
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|s| s.success())
+}
+
+/// Read the table back and check the change landed.
+pub fn verify(set: &str, net: &IpNet, present: bool) -> Result<()> {
+    if contains(set, net) != present {
+        let state = if present { "missing from" } else { "still in" };
+        anyhow::bail!(
+            "nft accepted the rules, but {} is {state} {set}. \
+             Check 'nft list table inet {TABLE}'.",
+            element(net)
+        );
+    }
+    Ok(())
+}
+
+/// An inclusive range of addresses; IPv4 is widened to `u128`.
+pub type Interval = (u128, u128);
+
+/// One set whose live contents differ from the saved state.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Drift {
+    pub set: String,
+    /// Ranges that should be blocked (or allowed) but aren't loaded
+    pub missing: usize,
+    /// Loaded ranges the saved state doesn't have
+    pub extra: usize,
+}
+
+/// The elements of every set in the live table, as address intervals.
+///
+/// `Ok(None)` if the table isn't loaded; an error if nft isn't installed or
+/// the table can't be read (usually because we're not root).
+pub fn live_sets() -> Result<Option<BTreeMap<String, Vec<Interval>>>> {
+    let output = Command::new("nft")
+        .args(["-j", "list", "table", "inet", TABLE])
+        .output()
+        .map_err(|e| anyhow::anyhow!("Could not run nft: {e}"))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.contains("No such file or directory") {
+            return Ok(None);
+        }
+        anyhow::bail!("Could not read table inet {TABLE}: {}", stderr.trim());
+    }
+
+    parse_live(&String::from_utf8_lossy(&output.stdout)).map(Some)
+}
+
+/// Read the sets out of `nft -j list table` output.
+pub fn parse_live(json: &str) -> Result<BTreeMap<String, Vec<Interval>>> {
+    let json: serde_json::Value = serde_json::from_str(json)?;
+    let items = json["nftables"]
+        .as_array()
+        .ok_or_else(|| anyhow::anyhow!("unexpected nft -j output"))?;
+
+    Ok(items
+        .iter()
+        .filter_map(|item| item.get("set"))
+        .filter_map(|set| {
+            let name = set["name"].as_str()?.to_string();
+            let elements = set["elem"]
+                .as_array()
+                .map(|elements| elements.iter().filter_map(live_interval).collect())
+                .unwrap_or_default();
+            Some((name, elements))
+        })
+        .collect())
+}
+
+/// One element as nft prints it: an address, a prefix or a range.
+fn live_interval(element: &serde_json::Value) -> Option<Interval> {
+    if let Some(addr) = element.as_str() {
+        return Some(bounds(&parse_net(addr).ok()?));
+    }
+    if let Some(prefix) = element.get("prefix") {
+        let net = format!("{}/{}", prefix["addr"].as_str()?, prefix["len"].as_u64()?);
+        return Some(bounds(&parse_net(&net).ok()?));
+    }
+    if let Some([start, end]) = element
+        .get("range")
+        .and_then(|r| r.as_array())
+        .map(Vec::as_slice)
+    {
+        let start = bounds(&parse_net(start.as_str()?).ok()?).0;
+        let end = bounds(&parse_net(end.as_str()?).ok()?).1;
+        return Some((start, end));
+    }
+    // Elements with a timeout or comment wrap the value
+    live_interval(element.get("elem")?.get("val")?)
+}
+
+/// The first and last address of `net`.
+pub fn bounds(net: &IpNet) -> Interval {
+    match net {
+        IpNet::V4(net) => (
+            u32::from(net.network()).into(),
+            u32::from(net.broadcast()).into(),
+        ),
+        IpNet::V6(net) => (net.network().into(), net.broadcast().into()),
+    }
+}
+
+/// Sort and merge overlapping or adjacent intervals.
+fn merge(mut intervals: Vec<Interval>) -> Vec<Interval> {
+    intervals.sort_unstable();
+    let mut merged: Vec<Interval> = Vec::new();
+    for (start, end) in intervals {
+        match merged.last_mut() {
+            Some(last) if start <= last.1.saturating_add(1) => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+/// How many of `intervals` aren't wholly inside the merged `cover`.
+fn uncovered(intervals: &[Interval], cover: &[Interval]) -> usize {
+    intervals
+        .iter()
+        .filter(|(start, end)| !cover.iter().any(|c| c.0 <= *start && *end <= c.1))
+        .count()
+}
+
+/// Compare what [`sets`] says should be loaded with what [`live_sets`] read.
+///
+/// nft merges adjacent elements, so both sides are compared as merged
+/// address intervals rather than element by element.
+pub fn drift(
+    expected: &BTreeMap<String, Vec<IpNet>>,
+    live: &BTreeMap<String, Vec<Interval>>,
+) -> Vec<Drift> {
+    let names: std::collections::BTreeSet<&String> = expected.keys().chain(live.keys()).collect();
+
+    names
+        .into_iter()
+        .filter_map(|set| {
+            let want = merge(
+                expected
+                    .get(set)
+                    .map_or_else(Vec::new, |nets| nets.iter().map(bounds).collect()),
+            );
+            let have = merge(live.get(set).cloned().unwrap_or_default());
+            let drift = Drift {
+                set: set.clone(),
+                missing: uncovered(&want, &have),
+                extra: uncovered(&have, &want),
+            };
+            (drift.missing + drift.extra > 0).then_some(drift)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IPV6: &str = include_str!("../../../i1-core/tests/fixtures/ipv6.txt");
+
+    #[test]
+    fn test_parse_net() {
+        assert_eq!(element(&parse_net("1.2.3.4").unwrap()), "1.2.3.4");
+        assert_eq!(element(&parse_net("10.1.2.3/8").unwrap()), "10.0.0.0/8");
+        assert_eq!(
+            element(&parse_net("2001:db8::1/32").unwrap()),
+            "2001:db8::/32"
+        );
+        assert!(parse_net("example.com").is_err());
+
+        // Every IPv6 form lands in the v6 set, canonical
+        for line in IPV6
+            .lines()
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        {
+            let (input, canonical) = line.split_once('\t').unwrap();
+            let net = parse_net(input).unwrap();
+            assert_eq!(element(&net), canonical, "{input}");
+            assert_eq!(blocked_set(&net), BLOCKED_V6, "{input}");
+            assert_eq!(allowed_set(&net), ALLOWED_V6, "{input}");
+        }
+    }
+
+    #[test]
+    fn test_ruleset() {
+        let mut state = State {
+            blocked_ips: vec![
+                "1.2.3.4".to_string(),
+                "2001:db8::/32".to_string(),
+                "junk".to_string(),
+            ],
+            whitelisted_ips: vec!["192.168.1.10".to_string()],
+            ..State::default()
+        };
+        state.ban_asn("AS9009", vec!["5.181.234.0/23".to_string()]);
+        let geo = [parse_net("5.6.0.0/16").unwrap()];
+        let feeds = BTreeMap::from([("cins".to_string(), vec![parse_net("9.9.9.0/24").unwrap()])]);
+        let batch = ruleset(&state, &geo, &feeds);
+
+        assert!(batch.starts_with("# skipped junk"));
+        assert!(batch.contains("delete table inet i1\ntable inet i1 {"));
+        assert!(batch.contains("elements = { 1.2.3.4 }"));
+        assert!(batch.contains("elements = { 2001:db8::/32 }"));
+        assert!(batch.contains("elements = { 5.6.0.0/16 }"));
+        assert!(batch.contains("elements = { 192.168.1.10 }"));
+        assert!(batch.contains("set feed_cins_v4 {"));
+        assert!(batch.contains("elements = { 9.9.9.0/24 }"));
+        assert!(batch.contains("ip saddr @feed_cins_v4 drop"));
+        assert!(batch.contains("set asn_v4 {"));
+        assert!(batch.contains("elements = { 5.181.234.0/23 }"));
+
+        // Whitelist is accepted before anything is dropped
+        let accept = batch.find("@allowed_v4 accept").unwrap();
+        let drop = batch.find("@blocked_v4 drop").unwrap();
+        assert!(accept < drop);
+    }
+
+    #[test]
+    fn test_drift() {
+        let state = State {
+            blocked_ips: vec!["1.2.3.4".to_string(), "10.0.0.0/8".to_string()],
+            ..State::default()
+        };
+        let expected = sets(
+            &state,
+            &[parse_net("5.6.0.0/16").unwrap()],
+            &BTreeMap::new(),
+        );
+
+        // nft merged the geo ranges into one and prints hosts bare
+        let json = r#"{"nftables": [
+            {"metainfo": {"json_schema_version": 1}},
+            {"table": {"family": "inet", "name": "i1"}},
+            {"set": {"family": "inet", "name": "blocked_v4", "table": "i1", "type": "ipv4_addr",
+                     "elem": ["1.2.3.4", {"prefix": {"addr": "10.0.0.0", "len": 8}}]}},
+            {"set": {"family": "inet", "name": "geo_v4", "table": "i1", "type": "ipv4_addr",
+                     "elem": [{"range": ["5.6.0.0", "5.6.255.255"]}]}},
+            {"set": {"family": "inet", "name": "allowed_v4", "table": "i1", "type": "ipv4_addr"}}
+        ]}"#;
+        let live = parse_live(json).unwrap();
+        // Sets that are empty on both sides don't count, missing or not
+        assert!(drift(&expected, &live).is_empty());
+
+        // Someone deleted a ban and added one by hand
+        let json = json.replace(
+            r#""1.2.3.4", "#,
+            r#"{"elem": {"val": "9.9.9.9", "comment": "manual"}}, "#,
+        );
+        let live = parse_live(&json).unwrap();
+        assert_eq!(
+            drift(&expected, &live),
+            [Drift {
+                set: BLOCKED_V4.to_string(),
+                missing: 1,
+                extra: 1,
+            }]
+        );
+    }
+
+    /// `nft --check` doesn't need root, so this runs wherever nft is installed.
+    #[test]
+    fn test_ruleset_passes_nft_check() {
+        if !available() {
+            return;
+        }
+
+        let state = State {
+            blocked_ips: vec![
+                "1.2.3.4".to_string(),
+                "10.0.0.0/8".to_string(),
+                "2001:db8::/32".to_string(),
+            ],
+            whitelisted_ips: vec!["10.1.1.1".to_string()],
+            ..State::default()
+        };
+        apply(&ruleset(&state, &[], &BTreeMap::new()), true).unwrap();
+        apply(&ruleset(&State::default(), &[], &BTreeMap::new()), true).unwrap();
+        apply(&teardown(), true).unwrap();
+    }
+}
@@ -0,0 +1,320 @@
+//! Plans: a change worked out against the current state before anything
+//! is applied.
+//!
+//! `--dry-run` shows the plan and stops; a real run carries out the same
+//! plan, so the batch that was shown is the batch that gets loaded.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::Result;
+use ipnet::IpNet;
+use serde::Serialize;
+
+use super::journal::Action;
+use super::{nft, State};
+
+/// Where a batch goes. [`Nft`] in real use; tests record the calls.
+pub trait Backend {
+    /// Validate a batch without changing anything. Returns false if it
+    /// couldn't be checked here.
+    fn check(&self, batch: &str) -> Result<bool>;
+
+    /// Load a batch.
+    fn apply(&self, batch: &str) -> Result<()>;
+}
+
+/// The nftables backend.
+pub struct Nft;
+
+impl Backend for Nft {
+    fn check(&self, batch: &str) -> Result<bool> {
+        if !nft::available() {
+            return Ok(false);
+        }
+        nft::apply(batch, true)?;
+        Ok(true)
+    }
+
+    fn apply(&self, batch: &str) -> Result<()> {
+        nft::apply(batch, false)
+    }
+}
+
+/// What the firewall should hold after a change.
+#[derive(Debug, Clone, Serialize)]
+pub struct Rules {
+    #[serde(skip)]
+    pub state: State,
+    /// The batch for `nft -f -`, where nft manages the firewall
+    pub batch: Option<String>,
+    /// Whether `nft --check` accepted the batch; `None` if it wasn't checked
+    pub checked: Option<bool>,
+}
+
+impl Rules {
+    pub const fn new(state: State, batch: Option<String>) -> Self {
+        Self {
+            state,
+            batch,
+            checked: None,
+        }
+    }
+
+    /// Hand the batch to `backend`: only checked with `dry_run`, loaded
+    /// otherwise. Returns whether the firewall was changed.
+    pub fn execute(&mut self, backend: &dyn Backend, dry_run: bool) -> Result<bool> {
+        let Some(batch) = &self.batch else {
+            return Ok(false);
+        };
+        if dry_run {
+            self.checked = Some(backend.check(batch)?);
+            return Ok(false);
+        }
+        backend.apply(batch)?;
+        Ok(true)
+    }
+}
+
+/// A target left out of the change, and why.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Skipped {
+    pub target: String,
+    pub reason: String,
+}
+
+/// Elements one set gains and loses.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SetDiff {
+    pub set: String,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+/// A change to the block list, diffed against the current state.
+#[derive(Debug, Clone, Serialize)]
+pub struct Plan {
+    pub action: Action,
+    /// Targets the change adds (or removes, for the removing actions)
+    pub changed: Vec<String>,
+    /// Targets already in effect
+    pub unchanged: Vec<String>,
+    /// Targets left out, such as bans the whitelist covers
+    pub skipped: Vec<Skipped>,
+    /// Element changes per nft set
+    pub sets: Vec<SetDiff>,
+    #[serde(flatten)]
+    pub rules: Rules,
+}
+
+impl Plan {
+    /// Work out `action` on each of `targets` against `state`.
+    ///
+    /// The firewall side is left empty until [`Plan::render`].
+    pub fn new(state: &State, action: Action, targets: &[String]) -> Self {
+        let mut next = state.clone();
+        let mut plan = Self {
+            action,
+            changed: Vec::new(),
+            unchanged: Vec::new(),
+            skipped: Vec::new(),
+            sets: Vec::new(),
+            rules: Rules::new(State::default(), None),
+        };
+
+        for target in targets {
+            if action == Action::Ban {
+                if let Some(allowed) = whitelisted(state, target) {
+                    plan.skipped.push(Skipped {
+                        target: target.clone(),
+                        reason: format!("covered by whitelist entry {allowed}"),
+                    });
+                    continue;
+                }
+            }
+            if next.apply(action, target) {
+                plan.changed.push(target.clone());
+            } else {
+                plan.unchanged.push(target.clone());
+            }
+        }
+
+        plan.rules.state = next;
+        plan
+    }
+
+    /// Nothing would change.
+    pub fn is_empty(&self) -> bool {
+        self.changed.is_empty()
+    }
+
+    /// Fill in the firewall side from the sets before and after the change.
+    pub fn render(
+        &mut self,
+        before: &BTreeMap<String, Vec<IpNet>>,
+        after: &BTreeMap<String, Vec<IpNet>>,
+        batch: Option<String>,
+    ) {
+        self.sets = diff(before, after);
+        self.rules.batch = batch;
+    }
+}
+
+/// The whitelist entry that covers `target`, if any.
+fn whitelisted(state: &State, target: &str) -> Option<String> {
+    let net = nft::parse_net(target).ok()?;
+    state
+        .whitelisted_ips
+        .iter()
+        .find(|entry| nft::parse_net(entry).is_ok_and(|n| n.contains(&net)))
+        .cloned()
+}
+
+/// Element changes between two sets of sets, skipping sets that don't change.
+pub fn diff(
+    before: &BTreeMap<String, Vec<IpNet>>,
+    after: &BTreeMap<String, Vec<IpNet>>,
+) -> Vec<SetDiff> {
+    let names: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    let elements = |sets: &BTreeMap<String, Vec<IpNet>>, name: &String| -> BTreeSet<String> {
+        sets.get(name)
+            .map(|nets| nets.iter().map(nft::element).collect())
+            .unwrap_or_default()
+    };
+
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let (old, new) = (elements(before, name), elements(after, name));
+            let set = SetDiff {
+                set: name.clone(),
+                added: new.difference(&old).cloned().collect(),
+                removed: old.difference(&new).cloned().collect(),
+            };
+            (!set.added.is_empty() || !set.removed.is_empty()).then_some(set)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+
+    /// Records what it was asked to do instead of touching a firewall.
+    #[derive(Default)]
+    struct Recorder {
+        calls: RefCell<Vec<&'static str>>,
+    }
+
+    impl Backend for Recorder {
+        fn check(&self, _batch: &str) -> Result<bool> {
+            self.calls.borrow_mut().push("check");
+            Ok(true)
+        }
+
+        fn apply(&self, _batch: &str) -> Result<()> {
+            self.calls.borrow_mut().push("apply");
+            Ok(())
+        }
+    }
+
+    fn planned(state: &State, action: Action, targets: &[&str]) -> Plan {
+        let targets: Vec<String> = targets.iter().map(ToString::to_string).collect();
+        let mut plan = Plan::new(state, action, &targets);
+        let before = nft::sets(state, &[], &BTreeMap::new());
+        let after = nft::sets(&plan.rules.state, &[], &BTreeMap::new());
+        let batch = nft::ruleset(&plan.rules.state, &[], &BTreeMap::new());
+        plan.render(&before, &after, Some(batch));
+        plan
+    }
+
+    #[test]
+    fn test_plan() {
+        let state = State {
+            blocked_ips: vec!["5.6.7.8".to_string()],
+            whitelisted_ips: vec!["10.0.0.0/8".to_string()],
+            ..State::default()
+        };
+        let plan = planned(&state, Action::Ban, &["1.2.3.4", "5.6.7.8", "10.1.1.1"]);
+
+        assert_eq!(plan.changed, ["1.2.3.4"]);
+        assert_eq!(plan.unchanged, ["5.6.7.8"]);
+        assert_eq!(plan.skipped[0].target, "10.1.1.1");
+        assert_eq!(
+            plan.sets,
+            [SetDiff {
+                set: nft::BLOCKED_V4.to_string(),
+                added: vec!["1.2.3.4".to_string()],
+                removed: Vec::new(),
+            }]
+        );
+        assert!(plan
+            .rules
+            .batch
+            .as_deref()
+            .unwrap()
+            .contains("elements = { 5.6.7.8, 1.2.3.4 }"));
+        // The current state is left alone
+        assert_eq!(state.blocked_ips, ["5.6.7.8"]);
+
+        let plan = planned(&state, Action::Unban, &["5.6.7.8"]);
+        assert_eq!(plan.sets[0].removed, ["5.6.7.8"]);
+        assert!(planned(&state, Action::Ban, &["5.6.7.8"]).is_empty());
+    }
+
+    #[test]
+    fn test_whitelist_overlap() {
+        let state = State {
+            whitelisted_ips: vec!["192.0.2.0/24".to_string(), "2001:db8::/48".to_string()],
+            ..State::default()
+        };
+        let skipped = |targets: &[&str]| -> Vec<String> {
+            let plan = Plan::new(
+                &state,
+                Action::Ban,
+                &targets.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            );
+            plan.skipped.into_iter().map(|s| s.target).collect()
+        };
+
+        // Contained in a whitelisted range, or the range itself: not banned
+        let contained = ["192.0.2.7", "192.0.2.128/25", "192.0.2.0/24", "2001:db8::1"];
+        assert_eq!(skipped(&contained), contained);
+
+        // Containing a whitelisted range: banned, the whitelist still wins
+        // for the addresses inside it
+        assert!(skipped(&["192.0.0.0/16", "2001:db8::/32"]).is_empty());
+
+        // Disjoint, or only next door
+        assert!(skipped(&["192.0.3.1", "198.51.100.0/24", "2001:db9::1"]).is_empty());
+
+        let plan = Plan::new(&state, Action::Ban, &["192.0.2.7".to_string()]);
+        assert!(plan.is_empty());
+        assert_eq!(
+            plan.skipped[0].reason,
+            "covered by whitelist entry 192.0.2.0/24"
+        );
+        // Unbanning is never held back
+        let plan = Plan::new(&state, Action::Unban, &["192.0.2.7".to_string()]);
+        assert!(plan.skipped.is_empty());
+    }
+
+    #[test]
+    fn test_dry_run_never_applies() {
+        let mut plan = planned(&State::default(), Action::Geoblock, &["cn"]);
+        let backend = Recorder::default();
+
+        assert!(!plan.rules.execute(&backend, true).unwrap());
+        assert_eq!(*backend.calls.borrow(), ["check"]);
+        assert_eq!(plan.rules.checked, Some(true));
+
+        assert!(plan.rules.execute(&backend, false).unwrap());
+        assert_eq!(*backend.calls.borrow(), ["check", "apply"]);
+
+        // Without a batch (rules only exported) there's nothing to run
+        let mut rules = Rules::new(State::default(), None);
+        assert!(!rules.execute(&backend, false).unwrap());
+        assert_eq!(backend.calls.borrow().len(), 2);
+    }
+}
@@ -0,0 +1,172 @@
+//! Netblock bans for `defend suggest`.
+//!
+//! The offending addresses are grouped by the WHOIS allocation holding
+//! them ([`i1::recon::whois::summarize_owners`]), and an allocation with
+//! enough of them is worth banning whole. [`suggest`] decides which: never
+//! one that holds a whitelist entry or the current SSH session, and never
+//! a lone address whose allocation wasn't found.
+
+use std::net::IpAddr;
+
+use i1::recon::whois::NetblockSummary;
+use ipnet::IpNet;
+use serde::Serialize;
+
+use super::import::Cover;
+use super::{nft, State};
+
+/// What to do about one netblock.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "verdict", content = "by")]
+pub enum Verdict {
+    /// Ban it whole
+    Ban,
+    /// Fewer of the addresses than the threshold
+    TooFew,
+    /// Overlaps this whitelist entry
+    Whitelisted(String),
+    /// Holds the address of the current SSH session
+    SshSession,
+    /// An existing ban covers it already
+    Banned,
+    /// Its allocation wasn't found, so there's no block to ban
+    Unknown,
+}
+
+impl Verdict {
+    /// The name it's serialized under.
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Ban => "ban",
+            Self::TooFew => "too_few",
+            Self::Whitelisted(_) => "whitelisted",
+            Self::SshSession => "ssh_session",
+            Self::Banned => "banned",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+/// A netblock and the verdict on it.
+#[derive(Debug, Clone, Serialize)]
+pub struct Suggestion {
+    #[serde(flatten)]
+    pub block: NetblockSummary,
+    #[serde(flatten)]
+    pub verdict: Verdict,
+}
+
+/// Judge each block in `summaries`: ban those holding at least `min_ips`
+/// of the addresses, unless `state` whitelists or already bans any of it,
+/// or it holds `ssh`.
+pub fn suggest(
+    summaries: Vec<NetblockSummary>,
+    min_ips: usize,
+    state: &State,
+    ssh: Option<IpAddr>,
+) -> Vec<Suggestion> {
+    let whitelist: Vec<(&String, IpNet)> = state
+        .whitelisted_ips
+        .iter()
+        .filter_map(|entry| Some((entry, nft::parse_net(entry).ok()?)))
+        .collect();
+    let banned = Cover::new(&state.blocked_ips);
+
+    summaries
+        .into_iter()
+        .map(|block| {
+            let cidr = block.cidr;
+            let verdict = if cidr.prefix_len() == cidr.max_prefix_len() && block.org.is_none() {
+                Verdict::Unknown
+            } else if let Some((entry, _)) = whitelist
+                .iter()
+                .find(|(_, net)| cidr.contains(net) || net.contains(&cidr))
+            {
+                Verdict::Whitelisted((*entry).clone())
+            } else if ssh.is_some_and(|ip| cidr.contains(&ip)) {
+                Verdict::SshSession
+            } else if banned.covers(&cidr) {
+                Verdict::Banned
+            } else if block.ip_count < min_ips {
+                Verdict::TooFew
+            } else {
+                Verdict::Ban
+            };
+            Suggestion { block, verdict }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(cidr: &str, ip_count: usize) -> NetblockSummary {
+        NetblockSummary {
+            cidr: cidr.parse().unwrap(),
+            org: Some("Example Hosting".to_string()),
+            country: Some("NL".to_string()),
+            abuse_email: None,
+            ip_count,
+            sample_ips: Vec::new(),
+        }
+    }
+
+    fn verdicts(suggestions: &[Suggestion]) -> Vec<(String, Verdict)> {
+        suggestions
+            .iter()
+            .map(|s| (s.block.cidr.to_string(), s.verdict.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_suggest() {
+        let state = State {
+            whitelisted_ips: vec!["203.0.113.7".to_string(), "10.0.0.0/8".to_string()],
+            blocked_ips: vec!["192.0.2.0/23".to_string()],
+            ..State::default()
+        };
+        let mut unknown = block("198.18.0.1/32", 1);
+        unknown.org = None;
+        let summaries = vec![
+            block("198.51.100.0/24", 12),
+            block("203.0.113.0/24", 9),
+            block("10.20.0.0/16", 6),
+            block("100.64.0.0/10", 5),
+            block("192.0.2.0/24", 4),
+            block("2001:db8::/32", 3),
+            block("233.252.0.0/24", 2),
+            unknown,
+        ];
+        let ssh = Some("100.64.1.1".parse().unwrap());
+
+        assert_eq!(
+            verdicts(&suggest(summaries.clone(), 3, &state, ssh)),
+            [
+                ("198.51.100.0/24".to_string(), Verdict::Ban),
+                // Holds a whitelisted address, or sits inside a whitelisted range
+                (
+                    "203.0.113.0/24".to_string(),
+                    Verdict::Whitelisted("203.0.113.7".to_string())
+                ),
+                (
+                    "10.20.0.0/16".to_string(),
+                    Verdict::Whitelisted("10.0.0.0/8".to_string())
+                ),
+                ("100.64.0.0/10".to_string(), Verdict::SshSession),
+                ("192.0.2.0/24".to_string(), Verdict::Banned),
+                ("2001:db8::/32".to_string(), Verdict::Ban),
+                ("233.252.0.0/24".to_string(), Verdict::TooFew),
+                ("198.18.0.1/32".to_string(), Verdict::Unknown),
+            ]
+        );
+
+        // A higher threshold leaves fewer to ban
+        let bans: Vec<String> = suggest(summaries, 10, &state, None)
+            .into_iter()
+            .filter(|s| s.verdict == Verdict::Ban)
+            .map(|s| s.block.cidr.to_string())
+            .collect();
+        assert_eq!(bans, ["198.51.100.0/24"]);
+    }
+}
@@ -330,25 +330,5 @@ impl Explain {
 
 /// Get country name from code.
 fn country_name(code: &str) -> String {
-    match code.to_lowercase().as_str() {
-        "cn" => "China".to_string(),
-        "ru" => "Russia".to_string(),
-        "us" => "United States".to_string(),
-        "ro" => "Romania".to_string(),
-        "pl" => "Poland".to_string(),
-        "kz" => "Kazakhstan".to_string(),
-        "ua" => "Ukraine".to_string(),
-        "vn" => "Vietnam".to_string(),
-        "br" => "Brazil".to_string(),
-        "in" => "India".to_string(),
-        "kr" => "South Korea".to_string(),
-        "de" => "Germany".to_string(),
-        "fr" => "France".to_string(),
-        "gb" | "uk" => "United Kingdom".to_string(),
-        "jp" => "Japan".to_string(),
-        "nl" => "Netherlands".to_string(),
-        "th" => "Thailand".to_string(),
-        "id" => "Indonesia".to_string(),
-        _ => code.to_uppercase(),
-    }
+    i1::country::country_name(code).map_or_else(|| code.to_uppercase(), String::from)
 }
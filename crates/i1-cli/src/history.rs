@@ -0,0 +1,271 @@
+//! Query history and saved queries for `search` and `count`.
+//!
+//! Every search and count is appended to `history.jsonl` in the data
+//! directory: when, which provider, the query, how many results it matched
+//! and the credits it cost. Only the newest [`MAX_ENTRIES`] are kept.
+//! Configured API keys are masked in a query before it's written. Like the
+//! cache, history is best-effort: a file that can't be read or written
+//! never fails the command. `i1 search --history` lists it.
+//!
+//! Saved queries live in the `[queries]` table of the config file, stored
+//! with `search --save <name>` and run with `search --saved <name>`. They may
+//! hold `{placeholder}`s, filled from `key=value` arguments by [`fill`].
+
+use std::collections::BTreeMap;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use directories::ProjectDirs;
+use i1::I1Error;
+use serde::{Deserialize, Serialize};
+
+use crate::cli::commands::Context;
+
+/// Entries kept; older ones are dropped as new ones are added
+pub const MAX_ENTRIES: usize = 1000;
+
+/// What a key is replaced with in a recorded query
+const MASK: &str = "<redacted>";
+
+/// One search or count.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    pub at: DateTime<Utc>,
+    pub provider: String,
+    pub query: String,
+    /// Results the query matched, if the provider said
+    pub total: Option<u64>,
+    pub credits: u32,
+}
+
+/// The history file.
+#[derive(Debug, Clone)]
+pub struct History {
+    path: PathBuf,
+    cap: usize,
+}
+
+impl History {
+    /// The history at `path`, keeping [`MAX_ENTRIES`].
+    pub const fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            cap: MAX_ENTRIES,
+        }
+    }
+
+    /// The history in the data directory.
+    pub fn open() -> Result<Self> {
+        let dirs = ProjectDirs::from("is", "i1", "i1")
+            .ok_or_else(|| anyhow::anyhow!("Could not determine data directory"))?;
+
+        Ok(Self::new(dirs.data_dir().join("history.jsonl")))
+    }
+
+    /// Keep `cap` entries instead.
+    #[must_use]
+    pub const fn cap(mut self, cap: usize) -> Self {
+        self.cap = cap;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append `entry`, dropping the oldest entries past the cap.
+    pub fn record(&self, entry: &Entry) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(&line)?;
+
+        let content = std::fs::read_to_string(&self.path)?;
+        let lines: Vec<&str> = content.lines().collect();
+        if lines.len() > self.cap {
+            let mut kept = lines[lines.len() - self.cap..].join("\n");
+            kept.push('\n');
+            let tmp = self.path.with_extension("jsonl.tmp");
+            std::fs::write(&tmp, kept)?;
+            std::fs::rename(&tmp, &self.path)?;
+        }
+        Ok(())
+    }
+
+    /// The newest `limit` entries, oldest first. Lines that don't parse
+    /// are skipped.
+    pub fn recent(&self, limit: usize) -> Vec<Entry> {
+        let Ok(content) = std::fs::read_to_string(&self.path) else {
+            return Vec::new();
+        };
+        let entries: Vec<Entry> = content
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect();
+        let skip = entries.len().saturating_sub(limit);
+        entries.into_iter().skip(skip).collect()
+    }
+}
+
+/// Record a search or count run with `ctx`, with its keys masked.
+pub fn record(ctx: &Context, provider: &str, query: &str, total: Option<u64>, credits: u32) {
+    let secrets = [
+        &ctx.shodan_key,
+        &ctx.censys_id,
+        &ctx.censys_secret,
+        &ctx.criminalip_key,
+        &ctx.token,
+    ];
+    let entry = Entry {
+        at: Utc::now(),
+        provider: provider.to_string(),
+        query: redact(query, secrets.iter().filter_map(|s| s.as_deref())),
+        total,
+        credits,
+    };
+    if let Ok(history) = History::open() {
+        let _ = history.record(&entry);
+    }
+}
+
+/// `query` with every one of `secrets` in it masked.
+pub fn redact<'a>(query: &str, secrets: impl IntoIterator<Item = &'a str>) -> String {
+    secrets
+        .into_iter()
+        .filter(|secret| !secret.is_empty())
+        .fold(query.to_string(), |query, secret| {
+            query.replace(secret, MASK)
+        })
+}
+
+/// Fill the `{name}` placeholders in a saved query from `key=value`
+/// arguments. Every placeholder needs a value and every value a
+/// placeholder; other braces are left as they are.
+pub fn fill(query: &str, args: &[String]) -> Result<String, I1Error> {
+    let mut values = BTreeMap::new();
+    for arg in args {
+        let (key, value) = arg
+            .split_once('=')
+            .ok_or_else(|| I1Error::InvalidQuery(format!("expected key=value, got \"{arg}\"")))?;
+        values.insert(key.trim(), (value, false));
+    }
+
+    let mut filled = String::with_capacity(query.len());
+    let mut rest = query;
+    while let Some(start) = rest.find('{') {
+        filled.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let name = after
+            .find('}')
+            .map(|end| &after[..end])
+            .filter(|name| is_placeholder(name));
+        let Some(name) = name else {
+            filled.push('{');
+            rest = after;
+            continue;
+        };
+        let (value, used) = values.get_mut(name).ok_or_else(|| {
+            I1Error::InvalidQuery(format!("no value for {{{name}}}: add {name}=..."))
+        })?;
+        filled.push_str(value);
+        *used = true;
+        rest = &after[name.len() + 1..];
+    }
+    filled.push_str(rest);
+
+    if let Some((key, _)) = values.iter().find(|(_, (_, used))| !used) {
+        return Err(I1Error::InvalidQuery(format!(
+            "the saved query has no {{{key}}} placeholder"
+        )));
+    }
+    Ok(filled)
+}
+
+fn is_placeholder(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(query: &str) -> Entry {
+        Entry {
+            at: Utc::now(),
+            provider: "shodan".to_string(),
+            query: query.to_string(),
+            total: Some(42),
+            credits: 1,
+        }
+    }
+
+    #[test]
+    fn test_history_cap() {
+        let dir = tempfile::tempdir().unwrap();
+        let history = History::new(dir.path().join("i1/history.jsonl")).cap(3);
+        assert!(history.recent(10).is_empty());
+
+        for i in 0..5 {
+            history.record(&entry(&format!("port:{i}"))).unwrap();
+        }
+        let queries: Vec<String> = history.recent(10).into_iter().map(|e| e.query).collect();
+        assert_eq!(queries, ["port:2", "port:3", "port:4"]);
+        assert_eq!(history.recent(1)[0].query, "port:4");
+
+        let lines = std::fs::read_to_string(history.path()).unwrap();
+        assert_eq!(lines.lines().count(), 3);
+    }
+
+    #[test]
+    fn test_redact() {
+        assert_eq!(
+            redact("apache key:sk-123 sk-123", ["sk-123", ""]),
+            "apache key:<redacted> <redacted>"
+        );
+        assert_eq!(redact("port:22", std::iter::empty()), "port:22");
+    }
+
+    #[test]
+    fn test_fill() {
+        let args =
+            |pairs: &[&str]| -> Vec<String> { pairs.iter().copied().map(String::from).collect() };
+
+        assert_eq!(
+            fill("port:3389 country:{country}", &args(&["country=DE"])).unwrap(),
+            "port:3389 country:DE"
+        );
+        assert_eq!(
+            fill(
+                "{product} port:{port} org:\"{org}\" {port}",
+                &args(&["port=443", "product=nginx", "org=Example Hosting"])
+            )
+            .unwrap(),
+            "nginx port:443 org:\"Example Hosting\" 443"
+        );
+        // Braces that aren't placeholders stay
+        assert_eq!(
+            fill("http.html:\"{ }\" {", &args(&[])).unwrap(),
+            "http.html:\"{ }\" {"
+        );
+
+        let missing = fill("country:{country}", &args(&[])).unwrap_err();
+        assert!(missing.to_string().contains("no value for {country}"));
+        let unused = fill("port:22", &args(&["country=DE"])).unwrap_err();
+        assert!(unused.to_string().contains("no {country} placeholder"));
+        assert!(matches!(
+            fill("country:{country}", &args(&["DE"])),
+            Err(I1Error::InvalidQuery(_))
+        ));
+    }
+}
@@ -0,0 +1,147 @@
+//! Tab completion for `i1 shell`.
+
+use clap::CommandFactory;
+use rustyline::completion::{Completer, Pair};
+use rustyline::{Helper, Highlighter, Hinter, Validator};
+
+use crate::cli::args::Cli;
+use crate::cli::commands::{self, config};
+
+/// Words the shell handles itself
+pub const BUILTINS: &[&str] = &["help", "exit", "quit", "set", "use", "last"];
+
+/// Session settings `set` changes
+pub const SETTINGS: &[&str] = &["output", "provider", "quiet", "verbose", "explain"];
+
+const FORMATS: &[&str] = &["pretty", "json", "csv", "yaml"];
+const SWITCHES: &[&str] = &["on", "off"];
+
+#[derive(Helper, Hinter, Highlighter, Validator)]
+pub struct ShellHelper {
+    command: clap::Command,
+}
+
+impl ShellHelper {
+    pub fn new() -> Self {
+        Self {
+            command: Cli::command(),
+        }
+    }
+}
+
+impl Completer for ShellHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &rustyline::Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let (start, words) = candidates(&self.command, &line[..pos]);
+        let pairs = words
+            .into_iter()
+            .map(|word| Pair {
+                display: word.clone(),
+                replacement: word,
+            })
+            .collect();
+        Ok((start, pairs))
+    }
+}
+
+/// Where the word before the cursor starts, and the words it could become.
+pub fn candidates(command: &clap::Command, line: &str) -> (usize, Vec<String>) {
+    // Only the current pipeline stage matters
+    let stage = line.rfind('|').map_or(line, |i| &line[i + 1..]);
+    let mut words: Vec<&str> = stage.split_whitespace().collect();
+    let partial = if stage.ends_with(char::is_whitespace) {
+        ""
+    } else {
+        words.pop().unwrap_or_default()
+    };
+    let start = line.len() - partial.len();
+
+    let listed = |names: &[&str]| names.iter().map(ToString::to_string).collect();
+    let options: Vec<String> = match words.as_slice() {
+        [] => BUILTINS
+            .iter()
+            .map(ToString::to_string)
+            .chain(subcommands(command))
+            .collect(),
+        ["set"] => listed(SETTINGS),
+        ["set", "output"] | [.., "-o" | "--output"] => listed(FORMATS),
+        ["use"] => std::iter::once("provider".to_string())
+            .chain(listed(&commands::provider_names()))
+            .collect(),
+        ["set" | "use", "provider"] | [.., "-p" | "--provider"] => {
+            listed(&commands::provider_names())
+        }
+        ["set", "quiet" | "verbose" | "explain"] => listed(SWITCHES),
+        ["config", "set"] => listed(config::KEYS),
+        [first, rest @ ..] => {
+            // Follow subcommands as far as the line names them
+            let mut current = command;
+            for word in std::iter::once(first).chain(rest) {
+                if let Some(sub) = current.find_subcommand(word) {
+                    current = sub;
+                }
+            }
+            if partial.starts_with('-') {
+                flags(current).chain(flags(command)).collect()
+            } else {
+                subcommands(current).collect()
+            }
+        }
+    };
+
+    let mut matches: Vec<String> = options
+        .into_iter()
+        .filter(|option| option.starts_with(partial))
+        .collect();
+    matches.sort();
+    matches.dedup();
+    (start, matches)
+}
+
+fn subcommands(command: &clap::Command) -> impl Iterator<Item = String> + '_ {
+    command
+        .get_subcommands()
+        .map(|sub| sub.get_name().to_string())
+        .filter(|name| name != "help")
+}
+
+fn flags(command: &clap::Command) -> impl Iterator<Item = String> + '_ {
+    command
+        .get_arguments()
+        .filter_map(|arg| arg.get_long())
+        .map(|long| format!("--{long}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn complete(line: &str) -> (usize, Vec<String>) {
+        candidates(&Cli::command(), line)
+    }
+
+    #[test]
+    fn test_candidates() {
+        let (start, words) = complete("se");
+        assert_eq!((start, words), (0, vec!["search".into(), "set".into()]));
+
+        assert_eq!(complete("defend ba").1, ["ban"]);
+        assert_eq!(complete("set output j"), (11, vec!["json".into()]));
+        assert_eq!(complete("use provider sh").1, ["shodan"]);
+        assert!(complete("config set sho").1.contains(&"shodan-key".into()));
+
+        // Flags of the subcommand and the global ones
+        let (_, words) = complete("host 8.8.8.8 --mi");
+        assert_eq!(words, ["--min-severity"]);
+        assert!(complete("search x --qu").1.contains(&"--quiet".into()));
+
+        // After a pipe, completion starts over
+        assert_eq!(complete("last | defend imp").1, ["import"]);
+    }
+}
@@ -1,4 +1,615 @@
-//! Interactive shell mode (coming soon).
+//! `i1 shell` - run commands one after another without restarting.
+//!
+//! Lines use the command-line grammar without the leading `i1`. Commands
+//! run in this process and share provider clients, so each provider keeps
+//! its rate limiter and connections between commands. Pipelines
+//! (`search nginx | defend import`) and `--output-file` run as child
+//! processes, since they need a stdin and stdout of their own.
 
-// This module will implement the REPL using rustyline.
-// For now, it's a placeholder.
+mod complete;
+
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+
+use anyhow::Result;
+use clap::Parser;
+use colored::Colorize;
+use rustyline::error::ReadlineError;
+use rustyline::history::DefaultHistory;
+use rustyline::Editor;
+
+use crate::cli::args::Cli;
+use crate::cli::commands::{self, Clients};
+use crate::cli::exit::{self, Exit};
+use crate::config::Config;
+use crate::output::Output;
+use complete::{ShellHelper, SETTINGS};
+
+const HELP: &str = "\
+Commands are the same as on the command line, without the i1:
+  host 8.8.8.8
+  search port:445 country:DE        (no quotes needed for the query)
+  defend status
+
+Shell commands:
+  use provider <name>               provider for the rest of the session
+  set <setting> <value>             output, provider, quiet, verbose, explain
+  set                               show the session settings
+  last                              run the previous command again
+  last | defend import              pipe the previous results into a command
+  help                              this help
+  exit, quit, Ctrl-D                leave the shell
+
+Pipes run each side as its own i1 process. The left side prints JSON
+unless it has an -o of its own; host and defend import read the piped input
+without being told. Ctrl-C stops the running command. `<command> --help`
+shows a command's options.";
+
+/// What to do after a line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Flow {
+    Continue,
+    Exit,
+}
+
+/// A global switch a session can keep on for every command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Switch {
+    Quiet,
+    Verbose,
+    Explain,
+    // Only set by the flags `i1 shell` was started with
+    NoColor,
+    NoCache,
+    Refresh,
+}
+
+impl Switch {
+    const ALL: [Self; 6] = [
+        Self::Quiet,
+        Self::Verbose,
+        Self::Explain,
+        Self::NoColor,
+        Self::NoCache,
+        Self::Refresh,
+    ];
+
+    /// The flag's long name, and its short one if it has one
+    const fn flag(self) -> (&'static str, Option<char>) {
+        match self {
+            Self::Quiet => ("quiet", None),
+            Self::Verbose => ("verbose", Some('v')),
+            Self::Explain => ("explain", None),
+            Self::NoColor => ("no-color", None),
+            Self::NoCache => ("no-cache", None),
+            Self::Refresh => ("refresh", None),
+        }
+    }
+
+    const fn is_on(self, cli: &Cli) -> bool {
+        match self {
+            Self::Quiet => cli.quiet,
+            Self::Verbose => cli.verbose,
+            Self::Explain => cli.explain,
+            Self::NoColor => cli.no_color,
+            Self::NoCache => cli.no_cache,
+            Self::Refresh => cli.refresh,
+        }
+    }
+
+    fn turn_on(self, cli: &mut Cli) {
+        match self {
+            Self::Quiet => cli.quiet = true,
+            Self::Verbose => cli.verbose = true,
+            Self::Explain => cli.explain = true,
+            Self::NoColor => cli.no_color = true,
+            // A line's --refresh and --no-cache rule each other out
+            Self::NoCache => cli.no_cache |= !cli.refresh,
+            Self::Refresh => cli.refresh |= !cli.no_cache,
+        }
+    }
+}
+
+/// Settings that carry over from one command to the next.
+///
+/// Flags given on a line win over these for that line.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Session {
+    output: Option<Output>,
+    provider: Option<String>,
+    switches: BTreeSet<Switch>,
+    // Fixed by the flags `i1 shell` was started with
+    api_key: Option<String>,
+    profile: Option<String>,
+}
+
+impl Session {
+    fn new(cli: &Cli) -> Self {
+        Self {
+            output: cli.output,
+            provider: cli.provider.clone(),
+            switches: Switch::ALL.into_iter().filter(|s| s.is_on(cli)).collect(),
+            api_key: cli.api_key.clone(),
+            profile: cli.profile.clone(),
+        }
+    }
+
+    fn is_on(&self, switch: Switch) -> bool {
+        self.switches.contains(&switch)
+    }
+
+    /// Fill in what a line's own flags leave open.
+    fn apply(&self, cli: &mut Cli) {
+        cli.output = cli.output.or(self.output);
+        cli.provider = cli.provider.take().or_else(|| self.provider.clone());
+        cli.api_key = cli.api_key.take().or_else(|| self.api_key.clone());
+        cli.profile = cli.profile.take().or_else(|| self.profile.clone());
+        for switch in &self.switches {
+            switch.turn_on(cli);
+        }
+    }
+
+    /// The same settings as flags for a child process running `stage`.
+    ///
+    /// `json` asks for JSON output, for a stage whose output is piped on.
+    fn args(&self, stage: &[String], json: bool) -> Vec<String> {
+        let mut args = Vec::new();
+        let output = if json {
+            Some(Output::Json)
+        } else {
+            self.output
+        };
+        if let (Some(output), false) = (output, has_flag(stage, "output", Some('o'))) {
+            args.extend(["--output".to_string(), output.to_string()]);
+        }
+        if let (Some(provider), false) = (&self.provider, has_flag(stage, "provider", Some('p'))) {
+            args.extend(["--provider".to_string(), provider.clone()]);
+        }
+        if let (Some(profile), false) = (&self.profile, has_flag(stage, "profile", None)) {
+            args.extend(["--profile".to_string(), profile.clone()]);
+        }
+        for switch in &self.switches {
+            let (name, short) = switch.flag();
+            if !has_flag(stage, name, short) {
+                args.push(format!("--{name}"));
+            }
+        }
+        args
+    }
+
+    /// `set <setting> <value>`
+    fn set(&mut self, setting: &str, value: &str) -> Result<()> {
+        let switch = match setting {
+            "output" => {
+                self.output = Some(Output::from_str(value)?);
+                return Ok(());
+            }
+            "provider" => {
+                let name = commands::provider_name(value).map_err(anyhow::Error::msg)?;
+                self.provider = Some(name.to_string());
+                return Ok(());
+            }
+            "quiet" => Switch::Quiet,
+            "verbose" => Switch::Verbose,
+            "explain" => Switch::Explain,
+            _ => anyhow::bail!(
+                "Unknown setting '{setting}'. Settings: {}\n\
+                 Saved settings and keys are changed with: config set <key> <value>",
+                SETTINGS.join(", ")
+            ),
+        };
+        match value {
+            "on" | "true" | "yes" => self.switches.insert(switch),
+            "off" | "false" | "no" => self.switches.remove(&switch),
+            _ => anyhow::bail!("Use on or off for {setting}"),
+        };
+        Ok(())
+    }
+
+    fn prompt(&self) -> String {
+        match &self.provider {
+            Some(provider) if provider != "auto" => format!("i1 ({provider})> "),
+            _ => "i1> ".to_string(),
+        }
+    }
+
+    fn print(&self) {
+        let on = |on: bool| if on { "on" } else { "off" };
+        let output = self
+            .output
+            .map_or_else(|| "from config".to_string(), |output| output.to_string());
+        println!("  {:<10}{output}", "output".bold());
+        println!(
+            "  {:<10}{}",
+            "provider".bold(),
+            self.provider.as_deref().unwrap_or("from config")
+        );
+        for switch in [Switch::Quiet, Switch::Verbose, Switch::Explain] {
+            println!("  {:<10}{}", switch.flag().0.bold(), on(self.is_on(switch)));
+        }
+    }
+}
+
+/// Run the shell until `exit` or Ctrl-D.
+pub async fn run(cli: Cli) -> Result<()> {
+    let mut session = Session::new(&cli);
+    let clients = Clients::default();
+    let mut last: Option<Vec<Vec<String>>> = None;
+
+    let mut editor = Editor::<ShellHelper, DefaultHistory>::new()?;
+    editor.set_helper(Some(ShellHelper::new()));
+    let history = history_path();
+    if let Some(path) = &history {
+        // No history yet on the first run
+        let _ = editor.load_history(path);
+    }
+
+    // Ctrl-C stops the running command, never the shell
+    tokio::spawn(async { while tokio::signal::ctrl_c().await.is_ok() {} });
+
+    if !session.is_on(Switch::Quiet) {
+        println!(
+            "{} shell. Type {} for commands, {} or Ctrl-D to leave.",
+            "i1".cyan().bold(),
+            "help".green(),
+            "exit".green()
+        );
+    }
+
+    loop {
+        let line = match editor.readline(&session.prompt()) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let _ = editor.add_history_entry(line);
+        if let Some(path) = &history {
+            if let Some(dir) = path.parent() {
+                let _ = std::fs::create_dir_all(dir);
+            }
+            let _ = editor.save_history(path);
+        }
+
+        match execute(line, &mut session, &clients, &mut last).await {
+            Ok(Flow::Continue) => {}
+            Ok(Flow::Exit) => break,
+            // Codes alone have nothing more to say
+            Err(e) if e.downcast_ref::<Exit>().is_some() => {}
+            Err(e) => eprintln!("{} {e:#}", "Error:".red().bold()),
+        }
+    }
+
+    Ok(())
+}
+
+/// History lives next to the config file.
+fn history_path() -> Option<PathBuf> {
+    Config::path()
+        .ok()
+        .map(|path| path.with_file_name("history"))
+}
+
+/// Run one line: a shell command, an i1 command, or a pipeline.
+async fn execute(
+    line: &str,
+    session: &mut Session,
+    clients: &Clients,
+    last: &mut Option<Vec<Vec<String>>>,
+) -> Result<Flow> {
+    let mut stages = split(line)?;
+    if stages[0].first().map(String::as_str) == Some("last") {
+        if stages[0].len() > 1 {
+            anyhow::bail!("`last` takes no arguments; pipe it instead: last | <command>");
+        }
+        let previous = last
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("No previous command"))?;
+        stages.splice(0..1, previous);
+    }
+    if stages[1..]
+        .iter()
+        .any(|stage| stage.first().map(String::as_str) == Some("last"))
+    {
+        anyhow::bail!("`last` only works at the start of a line");
+    }
+
+    if let [words] = stages.as_slice() {
+        if let Some(flow) = builtin(words, session)? {
+            return Ok(flow);
+        }
+    }
+
+    if stages.len() > 1 {
+        pipeline(&stages, session).await?;
+    } else {
+        command(&stages[0], session, clients).await?;
+    }
+    *last = Some(stages);
+    Ok(Flow::Continue)
+}
+
+/// Shell commands; `None` when the line is an i1 command.
+fn builtin(words: &[String], session: &mut Session) -> Result<Option<Flow>> {
+    let words: Vec<&str> = words.iter().map(String::as_str).collect();
+    match words.as_slice() {
+        ["help" | "?"] => println!("{HELP}"),
+        ["exit" | "quit"] => return Ok(Some(Flow::Exit)),
+        ["set"] => session.print(),
+        ["set", setting, value] => session.set(setting, value)?,
+        ["use", "provider", provider] | ["use", provider] => session.set("provider", provider)?,
+        ["set" | "use", ..] => anyhow::bail!("Usage: set <setting> <value>, use provider <name>"),
+        _ => return Ok(None),
+    }
+    Ok(Some(Flow::Continue))
+}
+
+/// Run an i1 command in this process.
+async fn command(words: &[String], session: &Session, clients: &Clients) -> Result<()> {
+    let mut cli =
+        match Cli::try_parse_from(std::iter::once("i1").chain(words.iter().map(String::as_str))) {
+            Ok(cli) => cli,
+            Err(e) => {
+                e.print()?;
+                return if e.use_stderr() {
+                    Err(Exit(exit::INVALID_INPUT).into())
+                } else {
+                    Ok(())
+                };
+            }
+        };
+    session.apply(&mut cli);
+
+    if cli.output_file.is_some() {
+        return pipeline(&[words.to_vec()], session).await;
+    }
+    if cli.command.is_none() {
+        anyhow::bail!("Give a command, e.g. host 8.8.8.8. Type help for more.");
+    }
+
+    let ctx = crate::cli::context(&cli, &Config::load()?, clients.clone())?;
+    if ctx.no_color {
+        colored::control::set_override(false);
+    } else {
+        colored::control::unset_override();
+    }
+
+    tokio::select! {
+        result = Box::pin(crate::cli::dispatch(ctx, cli.command)) => result,
+        _ = tokio::signal::ctrl_c() => {
+            eprintln!("{}", "Cancelled".yellow());
+            Err(Exit(exit::FAILURE).into())
+        }
+    }
+}
+
+/// Run each stage as its own `i1`, each stdout feeding the next stdin.
+async fn pipeline(stages: &[Vec<String>], session: &Session) -> Result<()> {
+    let exe = std::env::current_exe()?;
+    let mut children = Vec::new();
+    let mut input = None;
+
+    for (i, stage) in stages.iter().enumerate() {
+        let piped_on = i + 1 < stages.len();
+        let mut args = session.args(stage, piped_on);
+        args.extend(stage.iter().cloned());
+        if input.is_some() {
+            args.extend(reads_stdin(stage).map(str::to_string));
+        }
+
+        let mut command = Command::new(&exe);
+        command
+            .args(&args)
+            .stdin(input.take().map_or_else(Stdio::inherit, Stdio::from))
+            .stdout(if piped_on {
+                Stdio::piped()
+            } else {
+                Stdio::inherit()
+            });
+        if let Some(key) = &session.api_key {
+            command.env("I1_SHODAN_KEY", key);
+        }
+        let mut child = command.spawn()?;
+        input = child.stdout.take();
+        children.push(child);
+    }
+
+    let statuses = tokio::task::spawn_blocking(move || {
+        children
+            .into_iter()
+            .map(|mut child| child.wait())
+            .collect::<std::io::Result<Vec<_>>>()
+    })
+    .await??;
+
+    // Like a shell, the last stage decides
+    match statuses.last().map(std::process::ExitStatus::code) {
+        Some(Some(0)) | None => Ok(()),
+        Some(code) => {
+            let code = code.and_then(|code| u8::try_from(code).ok());
+            Err(Exit(code.unwrap_or(exit::FAILURE)).into())
+        }
+    }
+}
+
+/// The argument that makes `stage` read piped input, when it needs one.
+fn reads_stdin(stage: &[String]) -> Option<&'static str> {
+    let words: Vec<&str> = stage.iter().map(String::as_str).collect();
+    match words.as_slice() {
+        ["host"] | ["dns", "resolve"] => Some("-"),
+        ["defend", "import", ..]
+            if !has_flag(stage, "stdin", None) && !has_flag(stage, "file", None) =>
+        {
+            Some("--stdin")
+        }
+        _ => None,
+    }
+}
+
+/// Whether `words` already give `--long` (or `-short`).
+fn has_flag(words: &[String], long: &str, short: Option<char>) -> bool {
+    let long = format!("--{long}");
+    let short = short.map(|c| format!("-{c}"));
+    words.iter().any(|word| {
+        word == &long
+            || word.starts_with(&format!("{long}="))
+            || short
+                .as_ref()
+                .is_some_and(|short| word.starts_with(short.as_str()))
+    })
+}
+
+/// Split a line into pipeline stages of words.
+///
+/// Quotes group words and `\` escapes the next character. A leading `i1` is
+/// dropped, so pasted command lines work. Unquoted `search` and `count`
+/// queries are joined into one, up to the first flag.
+fn split(line: &str) -> Result<Vec<Vec<String>>> {
+    let mut stages: Vec<Vec<String>> = vec![Vec::new()];
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"') | None, '\\') => {
+                if let Some(next) = chars.next() {
+                    word.push(next);
+                }
+                in_word = true;
+            }
+            (Some(_), c) => word.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, '|') => {
+                if in_word {
+                    stages.last_mut().unwrap().push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+                stages.push(Vec::new());
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    stages.last_mut().unwrap().push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if quote.is_some() {
+        anyhow::bail!("Unclosed quote");
+    }
+    if in_word {
+        stages.last_mut().unwrap().push(word);
+    }
+
+    for stage in &mut stages {
+        if stage.first().map(String::as_str) == Some("i1") {
+            stage.remove(0);
+        }
+        if stage.is_empty() {
+            anyhow::bail!("Empty command in pipeline");
+        }
+        join_query(stage);
+    }
+    Ok(stages)
+}
+
+/// `search port:445 country:DE` means one query, not two arguments.
+fn join_query(stage: &mut Vec<String>) {
+    if !matches!(stage[0].as_str(), "search" | "count") {
+        return;
+    }
+    let end = stage[1..]
+        .iter()
+        .position(|word| word.starts_with('-'))
+        .map_or(stage.len(), |i| i + 1);
+    if end > 2 {
+        let query = stage.drain(1..end).collect::<Vec<_>>().join(" ");
+        stage.insert(1, query);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(line: &str) -> Vec<Vec<String>> {
+        split(line).unwrap()
+    }
+
+    #[test]
+    fn test_split() {
+        assert_eq!(words("host 8.8.8.8"), [["host", "8.8.8.8"]]);
+        assert_eq!(
+            words("search port:445 country:DE --limit 50"),
+            [["search", "port:445 country:DE", "--limit", "50"]]
+        );
+        assert_eq!(
+            words(r#"i1 search 'product:"nginx" | x' | defend import -y"#),
+            [
+                vec!["search", "product:\"nginx\" | x"],
+                vec!["defend", "import", "-y"]
+            ]
+        );
+        assert_eq!(
+            words(r"config set token a\ b"),
+            [["config", "set", "token", "a b"]]
+        );
+
+        assert!(split("search 'open").is_err());
+        assert!(split("host 8.8.8.8 |").is_err());
+    }
+
+    #[test]
+    fn test_session() {
+        let mut session = Session::default();
+        session.set("output", "json").unwrap();
+        session.set("provider", "all").unwrap();
+        session.set("quiet", "on").unwrap();
+        assert!(session.set("provider", "nope").is_err());
+        assert!(session.set("colour", "on").is_err());
+        assert_eq!(session.prompt(), "i1 (all)> ");
+
+        // Flags on the line win
+        let mut cli = Cli::parse_from(["i1", "host", "8.8.8.8", "-o", "csv"]);
+        session.apply(&mut cli);
+        assert_eq!(cli.output, Some(Output::Csv));
+        assert_eq!(cli.provider.as_deref(), Some("all"));
+        assert!(cli.quiet);
+
+        // Piped stages print JSON, and flags aren't given twice
+        let stage: Vec<String> = vec!["search".into(), "nginx".into(), "--quiet".into()];
+        assert_eq!(
+            session.args(&stage, true),
+            ["--output", "json", "--provider", "all"]
+        );
+    }
+
+    #[test]
+    fn test_reads_stdin() {
+        let stage = |words: &[&str]| words.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(reads_stdin(&stage(&["host"])), Some("-"));
+        assert_eq!(
+            reads_stdin(&stage(&["defend", "import", "-y"])),
+            Some("--stdin")
+        );
+        assert_eq!(reads_stdin(&stage(&["defend", "import", "--stdin"])), None);
+        assert_eq!(reads_stdin(&stage(&["host", "8.8.8.8"])), None);
+    }
+}
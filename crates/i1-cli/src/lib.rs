@@ -10,9 +10,14 @@
 //! - **Defend module**: Geo-blocking, IP banning, firewall rules
 //! - **Multiple output formats**: Pretty tables, JSON, CSV
 
+pub mod audit;
+pub mod cache;
 pub mod cli;
 pub mod config;
 pub mod defend;
+pub mod history;
+pub mod interactive;
 pub mod output;
+pub mod workspace;
 
 pub use cli::run;
@@ -2,9 +2,9 @@
 //!
 //! Multi-provider threat intelligence at your fingertips.
 
-use anyhow::Result;
+use std::process::ExitCode;
 
 #[tokio::main]
-async fn main() -> Result<()> {
-    i1_cli::run().await
+async fn main() -> ExitCode {
+    i1_cli::cli::exit::report(i1_cli::run().await)
 }
@@ -0,0 +1,363 @@
+//! Column selection and sorting for host tables.
+//!
+//! Every command that prints a list of hosts goes through [`Field`], so
+//! `--fields`, `--sort` and `--desc` behave the same everywhere. Pretty
+//! tables and CSV show exactly the selected columns; JSON and YAML project
+//! each host down to an object with just those keys.
+
+use std::cmp::Ordering;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use super::terminal;
+use i1::HostInfo;
+use serde_json::{json, Value};
+
+/// A column of the flattened host row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Ip,
+    Hostnames,
+    Domains,
+    Org,
+    Asn,
+    Isp,
+    Os,
+    Ports,
+    Vulns,
+    /// CPE 2.3 identifiers of the host's services
+    Cpe,
+    Tags,
+    /// Two-letter country code
+    Country,
+    CountryName,
+    City,
+    Region,
+    Latitude,
+    Longitude,
+    LastUpdate,
+}
+
+impl Field {
+    /// Every field, in the order they are listed in help and errors
+    pub const ALL: &'static [Self] = &[
+        Self::Ip,
+        Self::Hostnames,
+        Self::Domains,
+        Self::Org,
+        Self::Asn,
+        Self::Isp,
+        Self::Os,
+        Self::Ports,
+        Self::Vulns,
+        Self::Cpe,
+        Self::Tags,
+        Self::Country,
+        Self::CountryName,
+        Self::City,
+        Self::Region,
+        Self::Latitude,
+        Self::Longitude,
+        Self::LastUpdate,
+    ];
+
+    /// Field name as accepted by `--fields` and used for CSV/JSON keys
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Ip => "ip",
+            Self::Hostnames => "hostnames",
+            Self::Domains => "domains",
+            Self::Org => "org",
+            Self::Asn => "asn",
+            Self::Isp => "isp",
+            Self::Os => "os",
+            Self::Ports => "ports",
+            Self::Vulns => "vulns",
+            Self::Cpe => "cpe",
+            Self::Tags => "tags",
+            Self::Country => "country",
+            Self::CountryName => "country_name",
+            Self::City => "city",
+            Self::Region => "region",
+            Self::Latitude => "latitude",
+            Self::Longitude => "longitude",
+            Self::LastUpdate => "last_update",
+        }
+    }
+
+    /// Column header for pretty tables
+    pub const fn header(self) -> &'static str {
+        match self {
+            Self::Ip => "IP",
+            Self::Hostnames => "Hostnames",
+            Self::Domains => "Domains",
+            Self::Org => "Org",
+            Self::Asn => "ASN",
+            Self::Isp => "ISP",
+            Self::Os => "OS",
+            Self::Ports => "Ports",
+            Self::Vulns => "Vulns",
+            Self::Cpe => "CPE",
+            Self::Tags => "Tags",
+            Self::Country => "Country",
+            Self::CountryName => "Country Name",
+            Self::City => "City",
+            Self::Region => "Region",
+            Self::Latitude => "Latitude",
+            Self::Longitude => "Longitude",
+            Self::LastUpdate => "Last Update",
+        }
+    }
+
+    /// Cell text. Lists are joined with `sep`.
+    pub fn text(self, host: &HostInfo, sep: &str) -> String {
+        let opt = |v: &Option<String>| v.clone().unwrap_or_default();
+        let num = |v: Option<f64>| v.map(|v| v.to_string()).unwrap_or_default();
+
+        match self {
+            Self::Ip => host.ip_str.clone(),
+            Self::Hostnames => host.hostnames.join(sep),
+            Self::Domains => host.domains.join(sep),
+            Self::Org => opt(&host.org),
+            Self::Asn => opt(&host.asn),
+            Self::Isp => opt(&host.isp),
+            Self::Os => opt(&host.os),
+            Self::Ports => host
+                .ports
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(sep),
+            Self::Vulns => host.vulns.join(sep),
+            Self::Cpe => host.cpes().join(sep),
+            Self::Tags => host.tags.join(sep),
+            Self::Country => opt(&host.location.country_code),
+            Self::CountryName => opt(&host.location.country_name),
+            Self::City => opt(&host.location.city),
+            Self::Region => opt(&host.location.region_code),
+            Self::Latitude => num(host.location.latitude),
+            Self::Longitude => num(host.location.longitude),
+            Self::LastUpdate => opt(&host.last_update),
+        }
+    }
+
+    /// Typed JSON value for projected output
+    pub fn json(self, host: &HostInfo) -> Value {
+        match self {
+            Self::Hostnames => json!(host.hostnames),
+            Self::Domains => json!(host.domains),
+            Self::Ports => json!(host.ports),
+            Self::Vulns => json!(host.vulns),
+            Self::Cpe => json!(host.cpes()),
+            Self::Tags => json!(host.tags),
+            Self::Latitude => json!(host.location.latitude),
+            Self::Longitude => json!(host.location.longitude),
+            _ => {
+                let text = self.text(host, ",");
+                if text.is_empty() {
+                    Value::Null
+                } else {
+                    Value::String(text)
+                }
+            }
+        }
+    }
+
+    /// Compare two hosts on this field. Lists sort by length, so
+    /// `--sort vulns --desc` puts the most vulnerable hosts first.
+    pub fn compare(self, a: &HostInfo, b: &HostInfo) -> Ordering {
+        match self {
+            Self::Ip => a
+                .ip_str
+                .parse::<IpAddr>()
+                .ok()
+                .cmp(&b.ip_str.parse::<IpAddr>().ok()),
+            Self::Hostnames => a.hostnames.len().cmp(&b.hostnames.len()),
+            Self::Domains => a.domains.len().cmp(&b.domains.len()),
+            Self::Ports => a.ports.len().cmp(&b.ports.len()),
+            Self::Vulns => a.vulns.len().cmp(&b.vulns.len()),
+            Self::Cpe => a.cpes().len().cmp(&b.cpes().len()),
+            Self::Tags => a.tags.len().cmp(&b.tags.len()),
+            Self::Asn => asn_number(a).cmp(&asn_number(b)),
+            Self::Latitude => a
+                .location
+                .latitude
+                .partial_cmp(&b.location.latitude)
+                .unwrap_or(Ordering::Equal),
+            Self::Longitude => a
+                .location
+                .longitude
+                .partial_cmp(&b.location.longitude)
+                .unwrap_or(Ordering::Equal),
+            _ => self.text(a, ",").cmp(&self.text(b, ",")),
+        }
+    }
+}
+
+impl FromStr for Field {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase().replace('-', "_");
+        let field = match s.as_str() {
+            "ip" | "ip_str" => Self::Ip,
+            "hostname" | "hostnames" => Self::Hostnames,
+            "domain" | "domains" => Self::Domains,
+            "org" | "organization" => Self::Org,
+            "asn" => Self::Asn,
+            "isp" => Self::Isp,
+            "os" => Self::Os,
+            "port" | "ports" => Self::Ports,
+            "vuln" | "vulns" | "cves" => Self::Vulns,
+            "cpe" | "cpes" => Self::Cpe,
+            "tag" | "tags" => Self::Tags,
+            "country" | "country_code" | "cc" => Self::Country,
+            "country_name" => Self::CountryName,
+            "city" => Self::City,
+            "region" | "region_code" => Self::Region,
+            "lat" | "latitude" => Self::Latitude,
+            "lon" | "lng" | "longitude" => Self::Longitude,
+            "last_update" | "updated" => Self::LastUpdate,
+            _ => {
+                let valid: Vec<&str> = Self::ALL.iter().map(|f| f.name()).collect();
+                anyhow::bail!("unknown field '{s}'. Valid fields: {}", valid.join(", "))
+            }
+        };
+        Ok(field)
+    }
+}
+
+/// Numeric part of an ASN like "AS15169"
+fn asn_number(host: &HostInfo) -> Option<u64> {
+    host.asn
+        .as_deref()
+        .map(|asn| asn.trim_start_matches("AS").trim_start_matches("as"))
+        .and_then(|n| n.parse().ok())
+}
+
+/// Sort hosts by a field. Ties keep their original order.
+pub fn sort_hosts(hosts: &mut [HostInfo], field: Field, desc: bool) {
+    hosts.sort_by(|a, b| {
+        let ord = field.compare(a, b);
+        if desc {
+            ord.reverse()
+        } else {
+            ord
+        }
+    });
+}
+
+/// Render hosts as a pretty table with the given columns, fitted to the
+/// terminal
+pub fn table<'a>(hosts: impl IntoIterator<Item = &'a HostInfo>, fields: &[Field]) -> String {
+    terminal::records(
+        fields.iter().map(|f| f.header().to_string()).collect(),
+        hosts.into_iter().map(|host| cells(host, fields)).collect(),
+    )
+}
+
+/// Pretty table cells for one host, before they are fitted
+pub fn cells(host: &HostInfo, fields: &[Field]) -> Vec<String> {
+    fields.iter().map(|f| f.text(host, ", ")).collect()
+}
+
+/// Write hosts as CSV with the given columns. Lists are `;`-separated.
+pub fn write_csv<'a>(
+    hosts: impl IntoIterator<Item = &'a HostInfo>,
+    fields: &[Field],
+) -> anyhow::Result<()> {
+    let mut writer = csv::Writer::from_writer(std::io::stdout().lock());
+    writer.write_record(fields.iter().map(|f| f.name()))?;
+
+    for host in hosts {
+        writer.write_record(fields.iter().map(|f| f.text(host, ";")))?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Project a host down to an object holding only the given fields
+pub fn project(host: &HostInfo, fields: &[Field]) -> Value {
+    Value::Object(
+        fields
+            .iter()
+            .map(|f| (f.name().to_string(), f.json(host)))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use i1_core::testing::host;
+
+    #[test]
+    fn test_parse_fields() {
+        assert_eq!("port".parse::<Field>().unwrap(), Field::Ports);
+        assert_eq!("Country-Name".parse::<Field>().unwrap(), Field::CountryName);
+
+        let err = "bogus".parse::<Field>().unwrap_err().to_string();
+        assert!(err.contains("Valid fields: ip, hostnames"));
+    }
+
+    #[test]
+    fn test_sort_hosts() {
+        let mut hosts = vec![
+            host("10.0.0.10", json!({ "asn": "AS200", "vulns": ["CVE-1"] })),
+            host(
+                "10.0.0.9",
+                json!({ "asn": "AS1000", "vulns": ["CVE-1", "CVE-2"] }),
+            ),
+            host("10.0.0.100", json!({ "asn": "AS30" })),
+        ];
+
+        sort_hosts(&mut hosts, Field::Ip, false);
+        let ips: Vec<&str> = hosts.iter().map(|h| h.ip_str.as_str()).collect();
+        assert_eq!(ips, ["10.0.0.9", "10.0.0.10", "10.0.0.100"]);
+
+        sort_hosts(&mut hosts, Field::Asn, true);
+        assert_eq!(hosts[0].asn.as_deref(), Some("AS1000"));
+
+        sort_hosts(&mut hosts, Field::Vulns, true);
+        assert_eq!(hosts[0].vulns.len(), 2);
+    }
+
+    #[test]
+    fn test_project() {
+        let value = project(
+            &host("192.0.2.1", json!({ "asn": "AS1", "vulns": ["CVE-1"] })),
+            &[Field::Ip, Field::Vulns],
+        );
+        assert_eq!(value, json!({ "ip": "192.0.2.1", "vulns": ["CVE-1"] }));
+    }
+
+    #[test]
+    fn test_cpe_field() {
+        let mut host = host(
+            "192.0.2.1",
+            json!({
+                "data": [
+                    { "port": 22, "product": "OpenSSH", "version": "8.9" },
+                    { "port": 2222, "product": "OpenSSH", "version": "8.9" },
+                    { "port": 8080, "product": "Acme Appliance" },
+                    { "port": 9999 },
+                ]
+            }),
+        );
+        host.fill_cpes();
+
+        assert_eq!(
+            Field::Cpe.text(&host, ";"),
+            "cpe:2.3:a:*:acme_appliance:*:*:*:*:*:*:*:*;cpe:2.3:a:openbsd:openssh:8.9:*:*:*:*:*:*:*"
+        );
+        assert_eq!(
+            project(&host, &[Field::Cpe])["cpe"]
+                .as_array()
+                .unwrap()
+                .len(),
+            2
+        );
+        assert_eq!("cpes".parse::<Field>().unwrap(), Field::Cpe);
+    }
+}
@@ -0,0 +1,294 @@
+//! `--output-file`: results go to a file, chatter stays on the terminal.
+//!
+//! The command runs again as a child process with its stdout piped through
+//! here, so every command's results land in the file without it knowing.
+//! Progress bars, summaries and errors are on stderr and still reach the
+//! terminal. Colors are off in the child and any escape codes left are
+//! stripped on the way through.
+//!
+//! Without `--append` the file is written next to its destination and
+//! renamed into place once the command succeeds, so a failed run never
+//! leaves a half-written file behind.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use anyhow::{Context as _, Result};
+use colored::Colorize;
+
+use super::{Output, OutputFormat};
+use crate::cli::exit::{self, Exit};
+
+/// Set in the child so it prints to stdout instead of starting another child.
+pub const CHILD_ENV: &str = "I1_OUTPUT_FILE_CHILD";
+
+/// How the child's output is laid out in the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    /// As printed
+    Plain,
+    /// Each JSON document on its own line
+    Ndjson,
+    /// A fenced code block with this language
+    Markdown(&'static str),
+}
+
+/// The format a file's extension implies, if it implies one.
+pub fn infer(path: &Path) -> Option<OutputFormat> {
+    let ext = path.extension()?.to_str()?.to_lowercase();
+    match ext.as_str() {
+        "json" | "ndjson" | "jsonl" => Some(OutputFormat::Json),
+        "csv" => Some(OutputFormat::Csv),
+        "yaml" | "yml" => Some(OutputFormat::Yaml),
+        "md" | "markdown" | "txt" => Some(OutputFormat::Pretty),
+        _ => None,
+    }
+}
+
+/// How output in `format` is laid out in a file named `path`.
+pub fn layout(path: &Path, format: OutputFormat) -> Layout {
+    let ext = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_lowercase);
+    match (ext.as_deref(), format) {
+        (Some("ndjson" | "jsonl"), OutputFormat::Json) => Layout::Ndjson,
+        (Some("md" | "markdown"), format) => Layout::Markdown(match format {
+            OutputFormat::Json => "json",
+            OutputFormat::Yaml => "yaml",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Pretty => "text",
+        }),
+        _ => Layout::Plain,
+    }
+}
+
+/// Run this command again with its results written to `path`.
+///
+/// `format` is the `--output` given on the command line; without it the
+/// extension decides, and then the config.
+pub async fn run(path: &Path, append: bool, format: Option<Output>, quiet: bool) -> Result<()> {
+    let (file, temp) = if append {
+        let file = OpenOptions::new().create(true).append(true).open(path);
+        (file, None)
+    } else {
+        let temp = temp_path(path);
+        (File::create(&temp), Some(temp))
+    };
+    let file = file.with_context(|| format!("Could not write {}", path.display()))?;
+    let discard = || {
+        if let Some(temp) = &temp {
+            let _ = std::fs::remove_file(temp);
+        }
+    };
+
+    let mut command = Command::new(std::env::current_exe()?);
+    let inferred = format.is_none().then(|| infer(path)).flatten();
+    if let Some(format) = inferred {
+        command.arg("--output").arg(format.to_string());
+    }
+    let format = format.map(Output::format).or(inferred);
+    let spawned = command
+        .args(std::env::args_os().skip(1))
+        .env(CHILD_ENV, "1")
+        .stdout(Stdio::piped())
+        .spawn();
+    let (mut child, stdout) = match spawned {
+        Ok(mut child) => {
+            let stdout = child.stdout.take().context("Child has no stdout")?;
+            (child, stdout)
+        }
+        Err(e) => {
+            discard();
+            return Err(e).context("Could not start the command for --output-file");
+        }
+    };
+
+    // The child stops on Ctrl-C by itself; stay alive to finish the file
+    tokio::spawn(async { while tokio::signal::ctrl_c().await.is_ok() {} });
+
+    let layout = layout(path, format.unwrap_or_default());
+    let (copied, status) = tokio::task::spawn_blocking(move || {
+        let copied = copy(stdout, file, layout);
+        if copied.is_err() {
+            let _ = child.kill();
+        }
+        (copied, child.wait())
+    })
+    .await?;
+
+    let failure = match (copied, status) {
+        (Ok(()), Ok(status)) if status.success() => None,
+        (Ok(()), Ok(status)) => {
+            let code = status.code().and_then(|code| u8::try_from(code).ok());
+            Some(Exit(code.unwrap_or(exit::FAILURE)).into())
+        }
+        (Err(e), _) => {
+            Some(anyhow::Error::new(e).context(format!("Could not write {}", path.display())))
+        }
+        (Ok(()), Err(e)) => Some(e.into()),
+    };
+    if let Some(e) = failure {
+        discard();
+        return Err(e);
+    }
+
+    if let Some(temp) = &temp {
+        std::fs::rename(temp, path)
+            .with_context(|| format!("Could not write {}", path.display()))?;
+    }
+    if !quiet {
+        eprintln!("{}", format!("Wrote {}", path.display()).dimmed());
+    }
+    Ok(())
+}
+
+/// A temporary name next to `path`, so the rename stays on one filesystem.
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{name}.{}.tmp", std::process::id()))
+}
+
+/// Copy the child's output into the file, a line (or document) at a time
+/// so `tail -f` follows long-running commands.
+pub fn copy(reader: impl Read, writer: impl Write, layout: Layout) -> io::Result<()> {
+    let mut reader = BufReader::new(reader);
+    let mut writer = BufWriter::new(writer);
+
+    if layout == Layout::Ndjson {
+        for value in serde_json::Deserializer::from_reader(reader).into_iter::<serde_json::Value>()
+        {
+            let value = value.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            serde_json::to_writer(&mut writer, &value)?;
+            writer.write_all(b"\n")?;
+            writer.flush()?;
+        }
+        return Ok(());
+    }
+
+    if let Layout::Markdown(lang) = layout {
+        writeln!(writer, "```{lang}")?;
+    }
+    let mut line = Vec::new();
+    let mut ended = true;
+    while reader.read_until(b'\n', &mut line)? > 0 {
+        writer.write_all(strip_ansi(&String::from_utf8_lossy(&line)).as_bytes())?;
+        writer.flush()?;
+        ended = line.ends_with(b"\n");
+        line.clear();
+    }
+    if layout != Layout::Plain {
+        if !ended {
+            writeln!(writer)?;
+        }
+        writeln!(writer, "```")?;
+    }
+    writer.flush()
+}
+
+/// Remove terminal escape sequences (colors, cursor movement).
+pub fn strip_ansi(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            // CSI: parameters, then a final byte in @..~
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // OSC: up to BEL or ESC \
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' || (c == '\x1b' && chars.next() == Some('\\')) {
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn copied(input: &str, layout: Layout) -> String {
+        let mut out = Vec::new();
+        copy(input.as_bytes(), &mut out, layout).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_infer() {
+        let format = |name: &str| infer(Path::new(name));
+        assert_eq!(format("hosts.json"), Some(OutputFormat::Json));
+        assert_eq!(format("hosts.NDJSON"), Some(OutputFormat::Json));
+        assert_eq!(format("out/hosts.csv"), Some(OutputFormat::Csv));
+        assert_eq!(format("hosts.yml"), Some(OutputFormat::Yaml));
+        assert_eq!(format("report.md"), Some(OutputFormat::Pretty));
+        assert_eq!(format("rules.nft"), None);
+        assert_eq!(format("hosts"), None);
+
+        assert_eq!(
+            layout(Path::new("hosts.ndjson"), OutputFormat::Json),
+            Layout::Ndjson
+        );
+        assert_eq!(
+            layout(Path::new("hosts.ndjson"), OutputFormat::Csv),
+            Layout::Plain
+        );
+        assert_eq!(
+            layout(Path::new("report.md"), OutputFormat::Pretty),
+            Layout::Markdown("text")
+        );
+        assert_eq!(
+            layout(Path::new("hosts.json"), OutputFormat::Json),
+            Layout::Plain
+        );
+    }
+
+    #[test]
+    fn test_strip_ansi() {
+        assert_eq!(
+            strip_ansi("\x1b[1mHost:\x1b[0m \x1b[1;36m192.0.2.1\x1b[0m"),
+            "Host: 192.0.2.1"
+        );
+        assert_eq!(
+            strip_ansi("\x1b]8;;https://example.com\x07link\x1b]8;;\x07"),
+            "link"
+        );
+        assert_eq!(strip_ansi("plain ✓"), "plain ✓");
+    }
+
+    #[test]
+    fn test_copy() {
+        assert_eq!(copied("\x1b[32mok\x1b[0m\nlast", Layout::Plain), "ok\nlast");
+        assert_eq!(
+            copied(
+                "{\n  \"ip\": \"192.0.2.1\"\n}\n{\"ip\": \"192.0.2.2\"}\n",
+                Layout::Ndjson
+            ),
+            "{\"ip\":\"192.0.2.1\"}\n{\"ip\":\"192.0.2.2\"}\n"
+        );
+        assert_eq!(
+            copied("a | b\n", Layout::Markdown("text")),
+            "```text\na | b\n```\n"
+        );
+        assert!(copy(&b"not json"[..], Vec::new(), Layout::Ndjson).is_err());
+    }
+}
@@ -0,0 +1,821 @@
+//! `--filter`: keep the hosts an expression is true for.
+//!
+//! Providers' query languages can't say everything, so results can be
+//! narrowed down here before they are printed. Names are [`Field`] names,
+//! with `.len` for the length of a list or string:
+//!
+//! ```text
+//! ports.len >= 3 && org !~ "Amazon|Google|Microsoft" && country == "DE"
+//! vulns && not (tags contains "cdn")
+//! country in ["DE", "AT", "CH"] || ports == 3389
+//! ```
+//!
+//! Comparisons are `==`, `!=`, `<`, `<=`, `>`, `>=`, `~` and `!~` (regular
+//! expression), `contains`, `in` and `not in`, combined with `&&`/`and`,
+//! `||`/`or` and `!`/`not`. String comparisons ignore case. A list field
+//! compares true when any of its items does, so `ports == 22` is "has port
+//! 22", and the negated operators are true when none does. A name on its
+//! own is true when the field has a value.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+use i1::HostInfo;
+use regex::{Regex, RegexBuilder};
+
+use super::fields::Field;
+
+/// A parsed `--filter` expression.
+#[derive(Debug, Clone)]
+pub struct Filter {
+    expr: Expr,
+}
+
+impl Filter {
+    /// Whether `host` passes the filter.
+    pub fn matches(&self, host: &HostInfo) -> bool {
+        self.expr.eval(host)
+    }
+}
+
+impl FromStr for Filter {
+    type Err = FilterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens = lex(s)?;
+        let mut parser = Parser {
+            source: s,
+            tokens,
+            next: 0,
+        };
+        let expr = parser.or()?;
+        if let Some(token) = parser.peek() {
+            return Err(parser.error(
+                token.span,
+                format!("unexpected {}: expected '&&', '||' or the end", token.kind),
+            ));
+        }
+        Ok(Self { expr })
+    }
+}
+
+/// Keep the hosts that pass `filter`, if there is one, and say how many
+/// didn't.
+pub fn retain(hosts: &mut Vec<HostInfo>, filter: Option<&Filter>) -> usize {
+    let before = hosts.len();
+    if let Some(filter) = filter {
+        hosts.retain(|host| filter.matches(host));
+    }
+    before - hosts.len()
+}
+
+/// Why an expression doesn't parse, and where.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilterError {
+    /// What is wrong
+    pub message: String,
+    /// The expression
+    pub source: String,
+    /// Byte range of the offending token
+    pub span: (usize, usize),
+}
+
+impl fmt::Display for FilterError {
+    /// The message, then the expression with the token underlined
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (start, end) = self.span;
+        let indent = self.source[..start].chars().count();
+        let width = self.source[start..end].chars().count().max(1);
+        write!(
+            f,
+            "{}\n  {}\n  {}{}",
+            self.message,
+            self.source,
+            " ".repeat(indent),
+            "^".repeat(width)
+        )
+    }
+}
+
+impl std::error::Error for FilterError {}
+
+// ============================================================================
+// Values
+// ============================================================================
+
+/// A field's value for one host, or a literal.
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    Text(String),
+    List(Vec<Self>),
+}
+
+impl Value {
+    fn of(field: Field, host: &HostInfo) -> Self {
+        let list = |items: &[String]| Self::List(items.iter().cloned().map(Self::Text).collect());
+        let number = |n: Option<f64>| n.map_or(Self::Null, Self::Number);
+        match field {
+            Field::Hostnames => list(&host.hostnames),
+            Field::Domains => list(&host.domains),
+            Field::Vulns => list(&host.vulns),
+            Field::Cpe => list(&host.cpes()),
+            Field::Tags => list(&host.tags),
+            Field::Ports => Self::List(
+                host.ports
+                    .iter()
+                    .map(|&port| Self::Number(f64::from(port)))
+                    .collect(),
+            ),
+            Field::Latitude => number(host.location.latitude),
+            Field::Longitude => number(host.location.longitude),
+            _ => {
+                let text = field.text(host, ",");
+                if text.is_empty() {
+                    Self::Null
+                } else {
+                    Self::Text(text)
+                }
+            }
+        }
+    }
+
+    /// Items of a list, or length of a string
+    fn len(&self) -> usize {
+        match self {
+            Self::List(items) => items.len(),
+            Self::Text(text) => text.chars().count(),
+            Self::Null | Self::Bool(_) | Self::Number(_) => 0,
+        }
+    }
+
+    fn truthy(&self) -> bool {
+        match self {
+            Self::Null => false,
+            Self::Bool(b) => *b,
+            Self::Number(n) => *n != 0.0,
+            Self::Text(text) => !text.is_empty(),
+            Self::List(items) => !items.is_empty(),
+        }
+    }
+
+    /// The value, or any of its items, passes `test`
+    fn any(&self, test: &impl Fn(&Self) -> bool) -> bool {
+        match self {
+            Self::List(items) => items.iter().any(|item| item.any(test)),
+            scalar => test(scalar),
+        }
+    }
+
+    fn text(&self) -> Option<String> {
+        match self {
+            Self::Text(text) => Some(text.clone()),
+            Self::Number(n) => Some(n.to_string()),
+            Self::Bool(b) => Some(b.to_string()),
+            Self::Null | Self::List(_) => None,
+        }
+    }
+
+    fn number(&self) -> Option<f64> {
+        match self {
+            Self::Number(n) => Some(*n),
+            Self::Text(text) => text.trim().parse().ok(),
+            _ => None,
+        }
+    }
+
+    /// Order two scalars: numbers as numbers, strings without case
+    fn compare(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Self::Null, Self::Null) => Some(Ordering::Equal),
+            (Self::Bool(a), Self::Bool(b)) => Some(a.cmp(b)),
+            (Self::Text(a), Self::Text(b)) => Some(a.to_lowercase().cmp(&b.to_lowercase())),
+            (Self::Number(_), Self::Text(_)) | (Self::Text(_), Self::Number(_)) => {
+                self.number()?.partial_cmp(&other.number()?)
+            }
+            (Self::Number(a), Self::Number(b)) => a.partial_cmp(b),
+            _ => None,
+        }
+    }
+
+    fn equals(&self, other: &Self) -> bool {
+        self.compare(other) == Some(Ordering::Equal)
+    }
+}
+
+// ============================================================================
+// Expressions
+// ============================================================================
+
+#[derive(Debug, Clone)]
+enum Operand {
+    Field(Field),
+    /// `field.len`
+    Len(Field),
+    Literal(Value),
+}
+
+impl Operand {
+    fn eval(&self, host: &HostInfo) -> Value {
+        match self {
+            Self::Field(field) => Value::of(*field, host),
+            // Lengths are small enough to be exact as f64
+            #[allow(clippy::cast_precision_loss)]
+            Self::Len(field) => Value::Number(Value::of(*field, host).len() as f64),
+            Self::Literal(value) => value.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+    In,
+    NotIn,
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Or(Box<Self>, Box<Self>),
+    And(Box<Self>, Box<Self>),
+    Not(Box<Self>),
+    Truthy(Operand),
+    Compare(Operand, Op, Operand),
+    Matches(Operand, Regex),
+}
+
+impl Expr {
+    fn eval(&self, host: &HostInfo) -> bool {
+        match self {
+            Self::Or(a, b) => a.eval(host) || b.eval(host),
+            Self::And(a, b) => a.eval(host) && b.eval(host),
+            Self::Not(expr) => !expr.eval(host),
+            Self::Truthy(operand) => operand.eval(host).truthy(),
+            Self::Matches(operand, regex) => operand
+                .eval(host)
+                .any(&|v| v.text().is_some_and(|text| regex.is_match(&text))),
+            Self::Compare(left, op, right) => compare(&left.eval(host), *op, &right.eval(host)),
+        }
+    }
+}
+
+fn compare(left: &Value, op: Op, right: &Value) -> bool {
+    let ordered = |wanted: fn(Ordering) -> bool| {
+        left.any(&|l| right.any(&|r| l.compare(r).is_some_and(wanted)))
+    };
+    match op {
+        Op::Eq => ordered(Ordering::is_eq),
+        Op::Ne => !ordered(Ordering::is_eq),
+        Op::Lt => ordered(Ordering::is_lt),
+        Op::Le => ordered(Ordering::is_le),
+        Op::Gt => ordered(Ordering::is_gt),
+        Op::Ge => ordered(Ordering::is_ge),
+        Op::Contains => contains(left, right),
+        Op::In => contains(right, left),
+        Op::NotIn => !contains(right, left),
+    }
+}
+
+/// A list holding `needle` (or any of its items), or a string holding it
+fn contains(haystack: &Value, needle: &Value) -> bool {
+    match haystack {
+        Value::List(items) => needle.any(&|n| items.iter().any(|item| item.equals(n))),
+        Value::Text(text) => {
+            let text = text.to_lowercase();
+            needle.any(&|n| n.text().is_some_and(|n| text.contains(&n.to_lowercase())))
+        }
+        _ => false,
+    }
+}
+
+// ============================================================================
+// Lexer
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq)]
+enum Kind {
+    Name(String),
+    Text(String),
+    Number(f64),
+    Op(&'static str),
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Name(name) => write!(f, "'{name}'"),
+            Self::Text(text) => write!(f, "{text:?}"),
+            Self::Number(n) => write!(f, "{n}"),
+            Self::Op(op) => write!(f, "'{op}'"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: Kind,
+    span: (usize, usize),
+}
+
+/// Operators, longest first so `<=` isn't read as `<`
+const OPERATORS: &[&str] = &[
+    "==", "!=", "<=", ">=", "!~", "&&", "||", "<", ">", "~", "!", "(", ")", "[", "]", ",",
+];
+
+fn lex(source: &str) -> Result<Vec<Token>, FilterError> {
+    let error = |span, message: String| FilterError {
+        message,
+        source: source.to_string(),
+        span,
+    };
+    let mut tokens = Vec::new();
+    let mut rest = source.char_indices().peekable();
+
+    while let Some(&(start, c)) = rest.peek() {
+        if c.is_whitespace() {
+            rest.next();
+            continue;
+        }
+
+        if c == '"' || c == '\'' {
+            rest.next();
+            let mut text = String::new();
+            let mut end = None;
+            while let Some((at, d)) = rest.next() {
+                match d {
+                    // `\"` is a quote; other escapes are kept for the regex
+                    '\\' => match rest.next() {
+                        Some((_, escaped)) if escaped == c => text.push(escaped),
+                        Some((_, escaped)) => {
+                            text.push('\\');
+                            text.push(escaped);
+                        }
+                        None => break,
+                    },
+                    d if d == c => {
+                        end = Some(at + 1);
+                        break;
+                    }
+                    d => text.push(d),
+                }
+            }
+            let Some(end) = end else {
+                return Err(error(
+                    (start, source.len()),
+                    "unterminated string".to_string(),
+                ));
+            };
+            tokens.push(Token {
+                kind: Kind::Text(text),
+                span: (start, end),
+            });
+            continue;
+        }
+
+        let after = &source[start..];
+        let negative = c == '-' && after[1..].starts_with(|d: char| d.is_ascii_digit());
+        if c.is_ascii_digit() || negative {
+            let len = after
+                .char_indices()
+                .skip(1)
+                .find(|&(_, d)| !(d.is_ascii_digit() || d == '.'))
+                .map_or(after.len(), |(i, _)| i);
+            let span = (start, start + len);
+            let number = after[..len]
+                .parse()
+                .map_err(|_| error(span, format!("'{}' is not a number", &after[..len])))?;
+            tokens.push(Token {
+                kind: Kind::Number(number),
+                span,
+            });
+            advance(&mut rest, start + len);
+            continue;
+        }
+
+        if c.is_alphabetic() || c == '_' {
+            let len = after
+                .char_indices()
+                .find(|&(_, d)| !(d.is_alphanumeric() || matches!(d, '_' | '.' | '-')))
+                .map_or(after.len(), |(i, _)| i);
+            tokens.push(Token {
+                kind: Kind::Name(after[..len].to_string()),
+                span: (start, start + len),
+            });
+            advance(&mut rest, start + len);
+            continue;
+        }
+
+        let Some(op) = OPERATORS.iter().find(|op| after.starts_with(**op)) else {
+            let span = (start, start + c.len_utf8());
+            let hint = if c == '=' { " (compare with '==')" } else { "" };
+            return Err(error(span, format!("unexpected '{c}'{hint}")));
+        };
+        tokens.push(Token {
+            kind: Kind::Op(op),
+            span: (start, start + op.len()),
+        });
+        advance(&mut rest, start + op.len());
+    }
+    Ok(tokens)
+}
+
+fn advance(chars: &mut std::iter::Peekable<std::str::CharIndices<'_>>, to: usize) {
+    while chars.next_if(|&(at, _)| at < to).is_some() {}
+}
+
+// ============================================================================
+// Parser
+// ============================================================================
+
+/// Recursive descent, loosest first: `||`, `&&`, `!`, comparisons.
+struct Parser<'a> {
+    source: &'a str,
+    tokens: Vec<Token>,
+    next: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next)
+    }
+
+    /// Whether the next token is the operator or keyword `word`
+    fn at(&self, words: &[&str]) -> bool {
+        self.peek().is_some_and(|token| match &token.kind {
+            Kind::Op(op) => words.contains(op),
+            Kind::Name(name) => words.iter().any(|w| name.eq_ignore_ascii_case(w)),
+            _ => false,
+        })
+    }
+
+    fn eat(&mut self, words: &[&str]) -> bool {
+        let found = self.at(words);
+        if found {
+            self.next += 1;
+        }
+        found
+    }
+
+    fn error(&self, span: (usize, usize), message: String) -> FilterError {
+        FilterError {
+            message,
+            source: self.source.to_string(),
+            span,
+        }
+    }
+
+    /// An error at the next token, or just past the end
+    fn expected(&self, what: &str) -> FilterError {
+        let end = self.source.len();
+        self.peek().map_or_else(
+            || self.error((end, end), format!("expected {what} at the end")),
+            |token| self.error(token.span, format!("expected {what}, found {}", token.kind)),
+        )
+    }
+
+    fn or(&mut self) -> Result<Expr, FilterError> {
+        let mut expr = self.and()?;
+        while self.eat(&["||", "or"]) {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, FilterError> {
+        let mut expr = self.not()?;
+        while self.eat(&["&&", "and"]) {
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Expr, FilterError> {
+        if self.eat(&["!", "not"]) {
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        if self.eat(&["("]) {
+            let expr = self.or()?;
+            if !self.eat(&[")"]) {
+                return Err(self.expected("')'"));
+            }
+            return Ok(expr);
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expr, FilterError> {
+        let left = self.operand()?;
+
+        if self.at(&["~", "!~"]) {
+            let negate = self.at(&["!~"]);
+            self.next += 1;
+            let expr = Expr::Matches(left, self.regex()?);
+            return Ok(if negate {
+                Expr::Not(Box::new(expr))
+            } else {
+                expr
+            });
+        }
+
+        let op = match self.peek().map(|token| &token.kind) {
+            Some(Kind::Op("==")) => Op::Eq,
+            Some(Kind::Op("!=")) => Op::Ne,
+            Some(Kind::Op("<")) => Op::Lt,
+            Some(Kind::Op("<=")) => Op::Le,
+            Some(Kind::Op(">")) => Op::Gt,
+            Some(Kind::Op(">=")) => Op::Ge,
+            _ if self.at(&["contains"]) => Op::Contains,
+            _ if self.at(&["in"]) => Op::In,
+            _ if self.at(&["not"]) => {
+                self.next += 1;
+                if !self.at(&["in"]) {
+                    return Err(self.expected("'in' after 'not'"));
+                }
+                Op::NotIn
+            }
+            _ => return Ok(Expr::Truthy(left)),
+        };
+        self.next += 1;
+        let right = self.operand()?;
+        Ok(Expr::Compare(left, op, right))
+    }
+
+    /// A field, `field.len`, a string, a number, `true`, `false`, `null`
+    /// or a list of literals
+    fn operand(&mut self) -> Result<Operand, FilterError> {
+        let Some(token) = self.peek().cloned() else {
+            return Err(self.expected("a field or a value"));
+        };
+        let operand = match token.kind {
+            Kind::Text(text) => Operand::Literal(Value::Text(text)),
+            Kind::Number(n) => Operand::Literal(Value::Number(n)),
+            Kind::Op("[") => {
+                self.next += 1;
+                return self.list();
+            }
+            Kind::Name(name) => self.name(&name, token.span)?,
+            Kind::Op(_) => return Err(self.expected("a field or a value")),
+        };
+        self.next += 1;
+        Ok(operand)
+    }
+
+    fn name(&self, name: &str, span: (usize, usize)) -> Result<Operand, FilterError> {
+        match name.to_ascii_lowercase().as_str() {
+            "true" => return Ok(Operand::Literal(Value::Bool(true))),
+            "false" => return Ok(Operand::Literal(Value::Bool(false))),
+            "null" => return Ok(Operand::Literal(Value::Null)),
+            _ => {}
+        }
+        let (field, len) = match name.rsplit_once('.') {
+            Some((field, "len" | "count")) => (field, true),
+            Some((_, suffix)) => {
+                return Err(self.error(
+                    span,
+                    format!("unknown '.{suffix}' on '{name}' (only '.len' is supported)"),
+                ))
+            }
+            None => (name, false),
+        };
+        let field: Field = field.parse().map_err(|e: anyhow::Error| {
+            self.error(span, format!("{e}. Quote strings: \"{name}\""))
+        })?;
+        Ok(if len {
+            Operand::Len(field)
+        } else {
+            Operand::Field(field)
+        })
+    }
+
+    /// The rest of a `[a, b, c]` list, after its `[`
+    fn list(&mut self) -> Result<Operand, FilterError> {
+        let mut items = Vec::new();
+        if self.eat(&["]"]) {
+            return Ok(Operand::Literal(Value::List(items)));
+        }
+        loop {
+            match self.peek().map(|token| token.kind.clone()) {
+                Some(Kind::Text(text)) => items.push(Value::Text(text)),
+                Some(Kind::Number(n)) => items.push(Value::Number(n)),
+                _ => return Err(self.expected("a string or a number in the list")),
+            }
+            self.next += 1;
+            if self.eat(&["]"]) {
+                return Ok(Operand::Literal(Value::List(items)));
+            }
+            if !self.eat(&[","]) {
+                return Err(self.expected("',' or ']'"));
+            }
+        }
+    }
+
+    /// The quoted pattern after `~` or `!~`
+    fn regex(&mut self) -> Result<Regex, FilterError> {
+        let Some(Token {
+            kind: Kind::Text(pattern),
+            span,
+        }) = self.peek().cloned()
+        else {
+            return Err(self.expected("a quoted regular expression"));
+        };
+        self.next += 1;
+        RegexBuilder::new(&pattern)
+            .case_insensitive(true)
+            .build()
+            .map_err(|e| {
+                let reason = e.to_string();
+                let reason = reason.lines().last().unwrap_or_default().trim();
+                self.error(span, format!("invalid regular expression: {reason}"))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use i1_core::testing::host;
+    use serde_json::json;
+
+    fn hosts() -> Vec<HostInfo> {
+        vec![
+            host(
+                "192.0.2.1",
+                json!({
+                    "ports": [22, 80, 443],
+                    "org": "Hetzner Online GmbH",
+                    "asn": "AS24940",
+                    "vulns": ["CVE-2023-38408"],
+                    "tags": ["self-signed"],
+                    "hostnames": ["mail.example.de"],
+                    "country_code": "DE",
+                    "city": "Falkenstein",
+                    "latitude": 50.47,
+                    "last_update": "2024-05-01T10:00:00",
+                }),
+            ),
+            host(
+                "192.0.2.2",
+                json!({
+                    "ports": [80, 443, 8080, 8443],
+                    "org": "Amazon.com, Inc.",
+                    "tags": ["cloud", "cdn"],
+                    "country_code": "DE",
+                }),
+            ),
+            host(
+                "192.0.2.3",
+                json!({
+                    "ports": [3389],
+                    "org": "Example Telecom",
+                    "country_code": "AT",
+                    "last_update": "2023-11-20T08:00:00",
+                }),
+            ),
+        ]
+    }
+
+    /// IPs of the sample hosts `expr` keeps
+    fn kept(expr: &str) -> Vec<String> {
+        let filter: Filter = expr.parse().unwrap_or_else(|e| panic!("{e}"));
+        hosts()
+            .into_iter()
+            .filter(|host| filter.matches(host))
+            .map(|host| host.ip_str)
+            .collect()
+    }
+
+    #[test]
+    fn test_issue_example() {
+        assert_eq!(
+            kept(r#"ports.len >= 3 && org !~ "Amazon|Google|Microsoft" && country == "DE""#),
+            ["192.0.2.1"]
+        );
+    }
+
+    #[test]
+    fn test_comparisons() {
+        assert_eq!(kept("ports.len > 3"), ["192.0.2.2"]);
+        assert_eq!(kept("ports.len <= 1"), ["192.0.2.3"]);
+        assert_eq!(kept("country != 'de'"), ["192.0.2.3"]);
+        assert_eq!(kept("latitude > 50"), ["192.0.2.1"]);
+        assert_eq!(kept("latitude == null"), ["192.0.2.2", "192.0.2.3"]);
+        assert_eq!(kept("ip == '192.0.2.3'"), ["192.0.2.3"]);
+        assert_eq!(kept(r#"last_update >= "2024-01-01""#), ["192.0.2.1"]);
+        assert_eq!(kept("org.len < 16"), ["192.0.2.3"]);
+        assert_eq!(
+            kept("-1 < ports.len"),
+            ["192.0.2.1", "192.0.2.2", "192.0.2.3"]
+        );
+    }
+
+    #[test]
+    fn test_lists_match_any_item() {
+        assert_eq!(kept("ports == 22"), ["192.0.2.1"]);
+        assert_eq!(kept("ports != 80"), ["192.0.2.3"]);
+        assert_eq!(kept("ports > 8000"), ["192.0.2.2"]);
+        assert_eq!(kept("tags ~ '^c'"), ["192.0.2.2"]);
+        assert_eq!(kept(r"hostnames ~ '\.de$'"), ["192.0.2.1"]);
+        assert_eq!(kept(r"hostnames ~ 'mail\.example'"), ["192.0.2.1"]);
+        assert_eq!(kept(r"org ~ 'amazon\.com'"), ["192.0.2.2"]);
+        assert_eq!(
+            kept(r"org == 'Amazon.com, Inc.' && org !~ 'x\'s'"),
+            ["192.0.2.2"]
+        );
+    }
+
+    #[test]
+    fn test_contains_and_membership() {
+        assert_eq!(kept("tags contains 'CDN'"), ["192.0.2.2"]);
+        assert_eq!(kept("org contains 'online'"), ["192.0.2.1"]);
+        assert_eq!(kept("vulns contains 'CVE-2023-38408'"), ["192.0.2.1"]);
+        assert_eq!(kept("country in ['AT', 'CH']"), ["192.0.2.3"]);
+        assert_eq!(
+            kept("country not in ['AT', 'CH']"),
+            ["192.0.2.1", "192.0.2.2"]
+        );
+        assert_eq!(kept("ports in [3389, 5900]"), ["192.0.2.3"]);
+        assert_eq!(kept("443 in ports && 22 not in ports"), ["192.0.2.2"]);
+        assert_eq!(kept("asn in []"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_logic() {
+        assert_eq!(kept("vulns"), ["192.0.2.1"]);
+        assert_eq!(kept("!vulns && !tags"), ["192.0.2.3"]);
+        assert_eq!(kept("not (country == 'DE')"), ["192.0.2.3"]);
+        assert_eq!(
+            kept("country == 'AT' or ports == 22 and tags"),
+            ["192.0.2.1", "192.0.2.3"]
+        );
+        assert_eq!(
+            kept("(country == 'AT' || ports == 22) && tags"),
+            ["192.0.2.1"]
+        );
+        assert_eq!(kept("true"), ["192.0.2.1", "192.0.2.2", "192.0.2.3"]);
+        assert_eq!(kept("NOT true"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_retain() {
+        let mut all = hosts();
+        assert_eq!(retain(&mut all, None), 0);
+        let filter: Filter = "ports == 443".parse().unwrap();
+        assert_eq!(retain(&mut all, Some(&filter)), 1);
+        assert_eq!(all.len(), 2);
+    }
+
+    /// The underlined part of the error for `expr`
+    fn underlined(expr: &str) -> (String, String) {
+        let error = expr.parse::<Filter>().unwrap_err();
+        let (start, end) = error.span;
+        (expr[start..end].to_string(), error.message)
+    }
+
+    #[test]
+    fn test_errors_point_at_token() {
+        let (token, message) = underlined("ports.len >= 3 && && org");
+        assert_eq!(token, "&&");
+        assert_eq!(message, "expected a field or a value, found '&&'");
+
+        let (token, message) = underlined("country == DE");
+        assert_eq!(token, "DE");
+        assert!(message.starts_with("unknown field 'de'"), "{message}");
+        assert!(message.ends_with("Quote strings: \"DE\""), "{message}");
+
+        assert_eq!(underlined("ports.size > 1").0, "ports.size");
+        assert_eq!(underlined("country = 'DE'").0, "=");
+        assert_eq!(underlined("org ~ 'a(b'").0, "'a(b'");
+        assert_eq!(underlined("org ~ Amazon").0, "Amazon");
+        assert_eq!(underlined("(ports == 22").1, "expected ')' at the end");
+        assert_eq!(underlined("ports == 22 country").0, "country");
+        assert_eq!(underlined("org == \"open").0, "\"open");
+        assert_eq!(underlined("country in ['DE' 'AT']").0, "'AT'");
+        assert_eq!(underlined("tags not 'cdn'").0, "'cdn'");
+    }
+
+    #[test]
+    fn test_error_display() {
+        let error = "ports.len >= 3 && && org".parse::<Filter>().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "expected a field or a value, found '&&'\n  \
+             ports.len >= 3 && && org\n  \
+             \x20                 ^^"
+        );
+
+        // Columns count characters, not bytes
+        let error = "city == 'Reykjavík' &&".parse::<Filter>().unwrap_err();
+        assert!(error
+            .to_string()
+            .ends_with(&format!("\n  {}^", " ".repeat(22))));
+    }
+}
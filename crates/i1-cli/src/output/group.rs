@@ -0,0 +1,308 @@
+//! `--group-by`: hosts counted per country, org, ASN or port instead of
+//! listed one by one.
+//!
+//! Works on any list of hosts, so search and scan results summarize the
+//! same way. When a provider already returned facets for the field, those
+//! count the whole result set and are used instead of the fetched page.
+//! Countries are grouped by ISO code however the provider spelled them.
+
+use std::collections::HashMap;
+
+use clap::ValueEnum;
+use i1::{FacetValue, GeoLocation, HostInfo};
+use serde::Serialize;
+use serde_json::Value;
+use tabled::{builder::Builder, settings::Style};
+
+/// IPs kept per group as examples
+pub const SAMPLE_IPS: usize = 3;
+
+/// Key for hosts without a value for the field
+const UNKNOWN: &str = "(unknown)";
+
+/// What to group hosts by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum GroupBy {
+    /// Two-letter country code
+    Country,
+    Org,
+    Asn,
+    /// Each open port; a host counts once per port
+    Port,
+}
+
+impl GroupBy {
+    /// Name of the field, and of the matching Shodan facet
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Country => "country",
+            Self::Org => "org",
+            Self::Asn => "asn",
+            Self::Port => "port",
+        }
+    }
+
+    /// Column header for pretty tables
+    pub const fn header(self) -> &'static str {
+        match self {
+            Self::Country => "Country",
+            Self::Org => "Org",
+            Self::Asn => "ASN",
+            Self::Port => "Port",
+        }
+    }
+
+    fn keys(self, host: &HostInfo) -> Vec<String> {
+        let one = |value: &Option<String>| {
+            vec![value
+                .clone()
+                .filter(|v| !v.is_empty())
+                .unwrap_or_else(|| UNKNOWN.to_string())]
+        };
+        match self {
+            Self::Country => one(&country(&host.location)),
+            Self::Org => one(&host.org),
+            Self::Asn => one(&host.asn),
+            Self::Port if host.ports.is_empty() => vec![UNKNOWN.to_string()],
+            Self::Port => host.ports.iter().map(ToString::to_string).collect(),
+        }
+    }
+}
+
+/// The location's country code, worked out from its name if need be.
+fn country(location: &GeoLocation) -> Option<String> {
+    let mut location = location.clone();
+    location.normalize();
+    location.country_code
+}
+
+/// Hosts sharing one value.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Group {
+    pub key: String,
+    pub count: u64,
+    /// The first few of them
+    pub sample_ips: Vec<String>,
+}
+
+/// Count `hosts` per value of `by`, most common first.
+pub fn group(hosts: &[HostInfo], by: GroupBy) -> Vec<Group> {
+    let mut groups: HashMap<String, Group> = HashMap::new();
+    for host in hosts {
+        for key in by.keys(host) {
+            let group = groups.entry(key.clone()).or_insert_with(|| Group {
+                key,
+                count: 0,
+                sample_ips: Vec::new(),
+            });
+            group.count += 1;
+            if group.sample_ips.len() < SAMPLE_IPS {
+                group.sample_ips.push(host.ip_str.clone());
+            }
+        }
+    }
+    sorted(groups.into_values().collect())
+}
+
+/// Groups from a provider's `facets` for `by`, if it returned any, with
+/// sample IPs taken from `hosts`.
+pub fn from_facets(facets: &Value, by: GroupBy, hosts: &[HostInfo]) -> Option<Vec<Group>> {
+    let values: Vec<FacetValue> = serde_json::from_value(facets.get(by.name())?.clone()).ok()?;
+    let samples: HashMap<String, Vec<String>> = group(hosts, by)
+        .into_iter()
+        .map(|group| (group.key, group.sample_ips))
+        .collect();
+
+    let mut groups: HashMap<String, Group> = HashMap::new();
+    for facet in values {
+        let mut key = match facet.value {
+            Value::String(key) => key,
+            value => value.to_string(),
+        };
+        if by == GroupBy::Country {
+            key = i1::country::country_code(&key).map_or(key, String::from);
+        }
+        groups
+            .entry(key.clone())
+            .or_insert_with(|| Group {
+                sample_ips: samples.get(&key).cloned().unwrap_or_default(),
+                key,
+                count: 0,
+            })
+            .count += facet.count;
+    }
+    Some(sorted(groups.into_values().collect()))
+}
+
+/// Most common first, ties by key.
+fn sorted(mut groups: Vec<Group>) -> Vec<Group> {
+    groups.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.key.cmp(&b.key)));
+    groups
+}
+
+/// `count` as a percentage of `total`, e.g. `12.5%`.
+#[allow(clippy::cast_precision_loss)]
+pub fn percent(count: u64, total: u64) -> String {
+    if total == 0 {
+        return "-".to_string();
+    }
+    format!("{:.1}%", count as f64 * 100.0 / total as f64)
+}
+
+/// Render groups as a pretty table, with percentages of `total`.
+pub fn table(groups: &[Group], by: GroupBy, total: u64) -> String {
+    let mut builder = Builder::default();
+    builder.push_record([by.header(), "Hosts", "%", "Sample IPs"]);
+
+    for group in groups {
+        builder.push_record([
+            group.key.clone(),
+            group.count.to_string(),
+            percent(group.count, total),
+            group.sample_ips.join(", "),
+        ]);
+    }
+
+    builder.build().with(Style::rounded()).to_string()
+}
+
+/// Write groups as CSV. Sample IPs are `;`-separated.
+pub fn write_csv(groups: &[Group], by: GroupBy, total: u64) -> anyhow::Result<()> {
+    let mut writer = csv::Writer::from_writer(std::io::stdout().lock());
+    writer.write_record([by.name(), "count", "percent", "sample_ips"])?;
+
+    for group in groups {
+        writer.write_record([
+            group.key.clone(),
+            group.count.to_string(),
+            percent(group.count, total)
+                .trim_end_matches('%')
+                .to_string(),
+            group.sample_ips.join(";"),
+        ])?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use i1_core::testing::host;
+    use serde_json::json;
+
+    use super::*;
+
+    fn hosts() -> Vec<HostInfo> {
+        vec![
+            host(
+                "192.0.2.1",
+                json!({ "country_code": "US", "asn": "AS1", "ports": [22, 80] }),
+            ),
+            host(
+                "192.0.2.2",
+                json!({ "country_code": "DE", "asn": "AS2", "ports": [80] }),
+            ),
+            host(
+                "192.0.2.3",
+                json!({ "country_code": "US", "asn": "AS1", "ports": [443] }),
+            ),
+            host("192.0.2.4", json!({ "asn": "AS3" })),
+            host(
+                "192.0.2.5",
+                json!({ "country_code": "US", "asn": "AS2", "ports": [80] }),
+            ),
+            host(
+                "192.0.2.6",
+                json!({ "country_code": "US", "asn": "AS1", "ports": [80] }),
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_group() {
+        let groups = group(&hosts(), GroupBy::Country);
+        let counts: Vec<(&str, u64)> = groups.iter().map(|g| (g.key.as_str(), g.count)).collect();
+        assert_eq!(counts, [("US", 4), ("(unknown)", 1), ("DE", 1)]);
+        assert_eq!(
+            groups[0].sample_ips,
+            ["192.0.2.1", "192.0.2.3", "192.0.2.5"]
+        );
+
+        // A host counts once for each of its ports
+        let ports = group(&hosts(), GroupBy::Port);
+        assert_eq!(ports[0].key, "80");
+        assert_eq!(ports[0].count, 4);
+        assert_eq!(ports.iter().map(|g| g.count).sum::<u64>(), 7);
+
+        assert!(group(&[], GroupBy::Asn).is_empty());
+    }
+
+    #[test]
+    fn test_from_facets() {
+        let facets = json!({
+            "country": [
+                { "value": "DE", "count": 20 },
+                { "value": "US", "count": 300 },
+            ],
+            "port": [{ "value": 80, "count": 12 }],
+        });
+
+        let groups = from_facets(&facets, GroupBy::Country, &hosts()).unwrap();
+        assert_eq!(groups[0].key, "US");
+        assert_eq!(groups[0].count, 300);
+        assert_eq!(groups[0].sample_ips.len(), SAMPLE_IPS);
+        assert_eq!(groups[1].sample_ips, ["192.0.2.2"]);
+
+        let ports = from_facets(&facets, GroupBy::Port, &hosts()).unwrap();
+        assert_eq!(ports[0].key, "80");
+
+        assert!(from_facets(&facets, GroupBy::Org, &hosts()).is_none());
+    }
+
+    #[test]
+    fn test_group_country_spellings() {
+        let named = |ip: &str, code: Option<&str>, name: &str| -> HostInfo {
+            host(ip, json!({ "country_code": code, "country_name": name }))
+        };
+        let hosts = [
+            named("192.0.2.1", Some("KR"), "Korea, Republic of"),
+            named("192.0.2.2", None, "South Korea"),
+            named("192.0.2.3", Some("kr"), ""),
+            named("192.0.2.4", None, "Republic of Korea"),
+        ];
+        let groups = group(&hosts, GroupBy::Country);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].key, "KR");
+        assert_eq!(groups[0].count, 4);
+
+        let facets = json!({
+            "country": [
+                { "value": "KR", "count": 5 },
+                { "value": "Korea, Republic of", "count": 2 },
+            ],
+        });
+        let groups = from_facets(&facets, GroupBy::Country, &hosts).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].count, 7);
+    }
+
+    #[test]
+    fn test_percent() {
+        assert_eq!(percent(1, 8), "12.5%");
+        assert_eq!(percent(3, 3), "100.0%");
+        assert_eq!(percent(0, 0), "-");
+    }
+
+    #[test]
+    fn test_serialize() {
+        let groups = group(&hosts()[..2], GroupBy::Asn);
+        assert_eq!(
+            serde_json::to_value(&groups).unwrap(),
+            json!([
+                { "key": "AS1", "count": 1, "sample_ips": ["192.0.2.1"] },
+                { "key": "AS2", "count": 1, "sample_ips": ["192.0.2.2"] },
+            ])
+        );
+    }
+}
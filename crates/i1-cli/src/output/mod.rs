@@ -1,13 +1,48 @@
 //! Output formatting for different formats.
 
+pub mod fields;
+pub mod file;
+pub mod filter;
+pub mod group;
+pub mod severity;
+pub mod template;
+pub mod terminal;
+
 use clap::ValueEnum;
+use i1::export::stix;
+use i1::{HostInfo, I1Error};
 use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 
 /// Available output formats.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Pretty-printed tables with colors
+    #[default]
+    Pretty,
+    /// JSON output
+    Json,
+    /// CSV output
+    Csv,
+    /// YAML output
+    Yaml,
+}
+
+impl std::fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Output::from(*self).fmt(f)
+    }
+}
+
+/// What `--output`, `I1_OUTPUT` and the config's `output_format` take: an
+/// [`OutputFormat`], or a STIX bundle.
+///
+/// Only host, search and scan print STIX, which is JSON, so everything else
+/// is refused it once when the command is dispatched and sees
+/// [`Output::format`] from then on.
 #[derive(Debug, Clone, Copy, Default, ValueEnum, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
-pub enum OutputFormat {
+pub enum Output {
     /// Pretty-printed tables with colors
     #[default]
     Pretty,
@@ -17,9 +52,34 @@ pub enum OutputFormat {
     Csv,
     /// YAML output
     Yaml,
+    /// STIX 2.1 bundle of the hosts found by host, search and scan
+    Stix,
+}
+
+impl Output {
+    /// The format to print in; a STIX bundle is JSON.
+    pub const fn format(self) -> OutputFormat {
+        match self {
+            Self::Pretty => OutputFormat::Pretty,
+            Self::Json | Self::Stix => OutputFormat::Json,
+            Self::Csv => OutputFormat::Csv,
+            Self::Yaml => OutputFormat::Yaml,
+        }
+    }
+}
+
+impl From<OutputFormat> for Output {
+    fn from(format: OutputFormat) -> Self {
+        match format {
+            OutputFormat::Pretty => Self::Pretty,
+            OutputFormat::Json => Self::Json,
+            OutputFormat::Csv => Self::Csv,
+            OutputFormat::Yaml => Self::Yaml,
+        }
+    }
 }
 
-impl FromStr for OutputFormat {
+impl FromStr for Output {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
@@ -28,21 +88,38 @@ impl FromStr for OutputFormat {
             "json" => Ok(Self::Json),
             "csv" => Ok(Self::Csv),
             "yaml" | "yml" => Ok(Self::Yaml),
+            "stix" => Ok(Self::Stix),
             _ => anyhow::bail!(
                 "Unknown output format: {s}\n\
-                 Valid formats: pretty, json, csv, yaml"
+                 Valid formats: pretty, json, csv, yaml, stix"
             ),
         }
     }
 }
 
-impl std::fmt::Display for OutputFormat {
+impl std::fmt::Display for Output {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Pretty => write!(f, "pretty"),
             Self::Json => write!(f, "json"),
             Self::Csv => write!(f, "csv"),
             Self::Yaml => write!(f, "yaml"),
+            Self::Stix => write!(f, "stix"),
         }
     }
 }
+
+/// The error for `-o stix` on output with no hosts to bundle, e.g. `count`.
+pub fn no_stix(what: &str) -> I1Error {
+    I1Error::InvalidQuery(format!(
+        "{what} has no STIX output; -o stix bundles the hosts from host, search and scan. \
+         Use -o json instead."
+    ))
+}
+
+/// Print hosts and their CVEs as a STIX 2.1 bundle.
+pub fn print_stix(hosts: &[HostInfo]) -> anyhow::Result<()> {
+    let bundle = stix::to_stix_bundle(hosts, &[]);
+    println!("{}", serde_json::to_string_pretty(&bundle)?);
+    Ok(())
+}
@@ -0,0 +1,245 @@
+//! Vulnerability severity: CVSS scores to levels, colored CVE ids, the
+//! per-host risk badge and the `--min-severity` filter.
+//!
+//! Scores come from the banners a provider returns with the host, so no
+//! extra lookups are spent. Rendering only colors when asked to; callers
+//! decide with [`use_color`].
+
+use clap::ValueEnum;
+use colored::Colorize;
+use i1::HostInfo;
+use i1_providers::ThreatLevel;
+
+/// Lowest severity to show, for `--min-severity`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum Severity {
+    /// CVSS 0.1 and up
+    Low,
+    /// CVSS 4.0 and up
+    Medium,
+    /// CVSS 7.0 and up
+    High,
+    /// CVSS 9.0 and up
+    Critical,
+}
+
+impl Severity {
+    pub const fn level(self) -> ThreatLevel {
+        match self {
+            Self::Low => ThreatLevel::Low,
+            Self::Medium => ThreatLevel::Medium,
+            Self::High => ThreatLevel::High,
+            Self::Critical => ThreatLevel::Critical,
+        }
+    }
+}
+
+/// A CVE with its best known score.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScoredCve {
+    pub id: String,
+    pub cvss: Option<f64>,
+}
+
+impl ScoredCve {
+    /// `None` when no banner scores it.
+    pub fn level(&self) -> Option<ThreatLevel> {
+        self.cvss.map(level)
+    }
+}
+
+/// Whether to color: not with `--no-color`, and not when stdout is piped.
+pub fn use_color(no_color: bool) -> bool {
+    !no_color && super::terminal::is_terminal()
+}
+
+#[allow(clippy::cast_possible_truncation)]
+pub fn level(cvss: f64) -> ThreatLevel {
+    ThreatLevel::from_cvss(cvss as f32)
+}
+
+pub const fn name(level: ThreatLevel) -> &'static str {
+    match level {
+        ThreatLevel::Critical => "critical",
+        ThreatLevel::High => "high",
+        ThreatLevel::Medium => "medium",
+        ThreatLevel::Low => "low",
+        ThreatLevel::None => "none",
+    }
+}
+
+/// The host's CVEs, most severe first; unscored ones last, by id.
+pub fn ranked(host: &HostInfo) -> Vec<ScoredCve> {
+    let mut cves: Vec<ScoredCve> = host
+        .vuln_scores()
+        .into_iter()
+        .map(|(id, cvss)| ScoredCve { id, cvss })
+        .collect();
+    cves.sort_by(|a, b| {
+        b.cvss
+            .unwrap_or(-1.0)
+            .total_cmp(&a.cvss.unwrap_or(-1.0))
+            .then_with(|| a.id.cmp(&b.id))
+    });
+    cves
+}
+
+/// Drop the host's CVEs below `min`, from the list and from the banners,
+/// and put the rest in severity order.
+///
+/// CVEs without a score are kept, since there's no telling how bad they
+/// are. Returns how many were dropped.
+pub fn retain(host: &mut HostInfo, min: Option<Severity>) -> usize {
+    let (kept, dropped): (Vec<ScoredCve>, Vec<ScoredCve>) =
+        ranked(host).into_iter().partition(|cve| {
+            min.map_or(true, |min| {
+                cve.level().map_or(true, |level| level >= min.level())
+            })
+        });
+    for service in &mut host.data {
+        service
+            .vulns
+            .retain(|id, _| !dropped.iter().any(|cve| &cve.id == id));
+    }
+    host.vulns = kept.into_iter().map(|cve| cve.id).collect();
+    dropped.len()
+}
+
+/// A CVE id, colored by severity.
+pub fn cve(cve: &ScoredCve, color: bool) -> String {
+    let score = cve
+        .cvss
+        .map_or_else(|| "unscored".to_string(), |cvss| format!("{cvss:.1}"));
+    let text = format!("{} ({score})", cve.id);
+    if !color {
+        return text;
+    }
+    match cve.level() {
+        Some(ThreatLevel::Critical) => text.red().bold().to_string(),
+        Some(ThreatLevel::High) => text.red().to_string(),
+        Some(ThreatLevel::Medium) => text.yellow().to_string(),
+        Some(ThreatLevel::Low) => text.blue().to_string(),
+        Some(ThreatLevel::None) | None => text.dimmed().to_string(),
+    }
+}
+
+/// The per-host risk badge, e.g. `[HIGH 7.5]`, for a [`HostInfo::risk_score`].
+pub fn badge(score: f64, color: bool) -> String {
+    let level = level(score);
+    let text = format!("[{} {score:.1}]", name(level).to_uppercase());
+    if !color {
+        return text;
+    }
+    match level {
+        ThreatLevel::Critical => text.white().on_red().bold().to_string(),
+        ThreatLevel::High => text.red().bold().to_string(),
+        ThreatLevel::Medium => text.yellow().bold().to_string(),
+        ThreatLevel::Low => text.blue().to_string(),
+        ThreatLevel::None => text.green().to_string(),
+    }
+}
+
+/// The vulnerability list of the pretty host view, one CVE per line.
+pub fn vuln_lines(host: &HostInfo, color: bool) -> Vec<String> {
+    ranked(host)
+        .iter()
+        .map(|scored| format!("  - {}", cve(scored, color)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use i1_core::testing;
+
+    fn host() -> HostInfo {
+        testing::host(
+            "192.0.2.1",
+            serde_json::json!({
+                "vulns": ["CVE-2021-0001", "CVE-2024-3400", "CVE-2023-0002", "CVE-2020-0003"],
+                "data": [
+                    {"port": 443, "vulns": {
+                        "CVE-2024-3400": {"cvss": 10.0, "verified": true},
+                        "CVE-2021-0001": {"cvss": 3.1, "verified": false}
+                    }},
+                    {"port": 80, "vulns": {
+                        "CVE-2023-0002": {"cvss": 7.5, "verified": false},
+                        "CVE-2021-0001": {"cvss": 2.0, "verified": false}
+                    }}
+                ]
+            }),
+        )
+    }
+
+    #[test]
+    fn test_ranked() {
+        let ids: Vec<(String, Option<f64>)> = ranked(&host())
+            .into_iter()
+            .map(|c| (c.id, c.cvss))
+            .collect();
+        assert_eq!(
+            ids,
+            [
+                ("CVE-2024-3400".to_string(), Some(10.0)),
+                ("CVE-2023-0002".to_string(), Some(7.5)),
+                // The higher of the two banner scores
+                ("CVE-2021-0001".to_string(), Some(3.1)),
+                ("CVE-2020-0003".to_string(), None),
+            ]
+        );
+        assert!((host().risk_score() - 10.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_retain() {
+        let mut filtered = host();
+        assert_eq!(retain(&mut filtered, Some(Severity::High)), 1);
+        assert_eq!(
+            filtered.vulns,
+            ["CVE-2024-3400", "CVE-2023-0002", "CVE-2020-0003"]
+        );
+
+        let mut all = host();
+        assert_eq!(retain(&mut all, None), 0);
+        assert_eq!(all.vulns[0], "CVE-2024-3400");
+        // Banners lose the dropped CVE too, so it doesn't come back
+        assert!(filtered
+            .data
+            .iter()
+            .all(|s| !s.vulns.contains_key("CVE-2021-0001")));
+        assert_eq!(ranked(&filtered).len(), 3);
+    }
+
+    #[test]
+    fn test_plain_snapshot() {
+        assert_eq!(badge(host().risk_score(), false), "[CRITICAL 10.0]");
+        assert_eq!(
+            vuln_lines(&host(), false).join("\n"),
+            "  - CVE-2024-3400 (10.0)\n  \
+             - CVE-2023-0002 (7.5)\n  \
+             - CVE-2021-0001 (3.1)\n  \
+             - CVE-2020-0003 (unscored)"
+        );
+
+        let clean = testing::host("192.0.2.2", serde_json::json!({}));
+        assert_eq!(badge(clean.risk_score(), false), "[NONE 0.0]");
+    }
+
+    #[test]
+    fn test_colored_snapshot() {
+        colored::control::set_override(true);
+        assert_eq!(
+            badge(host().risk_score(), true),
+            "\u{1b}[1;41;37m[CRITICAL 10.0]\u{1b}[0m"
+        );
+        assert_eq!(
+            vuln_lines(&host(), true),
+            [
+                "  - \u{1b}[1;31mCVE-2024-3400 (10.0)\u{1b}[0m",
+                "  - \u{1b}[31mCVE-2023-0002 (7.5)\u{1b}[0m",
+                "  - \u{1b}[34mCVE-2021-0001 (3.1)\u{1b}[0m",
+                "  - \u{1b}[2mCVE-2020-0003 (unscored)\u{1b}[0m",
+            ]
+        );
+    }
+}
@@ -0,0 +1,182 @@
+//! `--format-template`: one line of text per host, shaped for another tool.
+//!
+//! Placeholders are [`Field`] names in braces, e.g. `{ip}\t{org}\t{country}`.
+//! `\t`, `\n` and `\\` are expanded, and `{{` and `}}` are literal braces.
+//! List fields such as `{ports}` and `{vulns}` are joined with `--list-sep`.
+//! A template replaces the output format: nothing but the rendered lines is
+//! printed.
+
+use std::str::FromStr;
+
+use i1::HostInfo;
+
+use super::fields::Field;
+
+/// Separator for list fields when `--list-sep` is not given
+pub const DEFAULT_SEP: &str = ",";
+
+/// A parsed `--format-template`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Field(Field),
+}
+
+impl Template {
+    /// The template filled in for one host. Missing values are empty.
+    pub fn render(&self, host: &HostInfo, sep: &str) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Text(text) => text.clone(),
+                Part::Field(field) => field.text(host, sep),
+            })
+            .collect()
+    }
+}
+
+impl FromStr for Template {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = s.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some('t') => text.push('\t'),
+                    Some('n') => text.push('\n'),
+                    Some('\\') | None => text.push('\\'),
+                    Some(other) => {
+                        text.push('\\');
+                        text.push(other);
+                    }
+                },
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    let mut closed = false;
+                    for c in chars.by_ref() {
+                        if c == '}' {
+                            closed = true;
+                            break;
+                        }
+                        name.push(c);
+                    }
+                    if !closed || name.contains('{') {
+                        anyhow::bail!("unclosed '{{' in template; use '{{{{' for a literal brace");
+                    }
+                    let field = placeholder(&name)?;
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(Part::Field(field));
+                }
+                '}' => anyhow::bail!("unmatched '}}' in template; use '}}}}' for a literal brace"),
+                _ => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+
+        Ok(Self { parts })
+    }
+}
+
+/// The field a placeholder names, or an error listing the valid names.
+fn placeholder(name: &str) -> anyhow::Result<Field> {
+    name.parse::<Field>().map_err(|_| {
+        let valid: Vec<String> = Field::ALL
+            .iter()
+            .map(|f| format!("{{{}}}", f.name()))
+            .collect();
+        anyhow::anyhow!(
+            "unknown placeholder '{{{name}}}' in template. Valid placeholders: {}",
+            valid.join(", ")
+        )
+    })
+}
+
+/// Expand `\t`, `\n` and `\\`, for `--list-sep`.
+pub fn unescape(s: &str) -> String {
+    s.replace("\\t", "\t")
+        .replace("\\n", "\n")
+        .replace("\\\\", "\\")
+}
+
+/// Print one rendered line per host.
+pub fn print<'a>(hosts: impl IntoIterator<Item = &'a HostInfo>, template: &Template, sep: &str) {
+    for host in hosts {
+        println!("{}", template.render(host, sep));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use i1_core::testing;
+
+    fn host() -> HostInfo {
+        testing::host(
+            "192.0.2.1",
+            serde_json::json!({
+                "org": "Example Org",
+                "country_code": "NL",
+                "ports": [22, 443],
+                "vulns": ["CVE-2024-3400", "CVE-2023-0002"]
+            }),
+        )
+    }
+
+    fn render(template: &str, sep: &str) -> String {
+        template.parse::<Template>().unwrap().render(&host(), sep)
+    }
+
+    #[test]
+    fn test_render() {
+        assert_eq!(
+            render(r"{ip}\t{org}\t{country_code}", ","),
+            "192.0.2.1\tExample Org\tNL"
+        );
+        assert_eq!(render("{ip}:{ports}", ","), "192.0.2.1:22,443");
+        assert_eq!(render("{vulns}", ";"), "CVE-2024-3400;CVE-2023-0002");
+        // Missing values render empty, literal braces survive
+        assert_eq!(render("{{{os}}} {ip}\\n", ","), "{} 192.0.2.1\n");
+        assert_eq!(render("no fields", ","), "no fields");
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = "{ip} {nope}".parse::<Template>().unwrap_err().to_string();
+        assert!(err.starts_with("unknown placeholder '{nope}'"), "{err}");
+        assert!(
+            err.contains("Valid placeholders: {ip}, {hostnames}"),
+            "{err}"
+        );
+
+        assert!("{ip".parse::<Template>().is_err());
+        assert!("{ip} {org".parse::<Template>().is_err());
+        assert!("ip}".parse::<Template>().is_err());
+    }
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(unescape(r"\t"), "\t");
+        assert_eq!(unescape(" | "), " | ");
+    }
+}
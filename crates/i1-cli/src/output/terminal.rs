@@ -0,0 +1,451 @@
+//! Fitting pretty output to the terminal it lands on.
+//!
+//! Tables are cut down to the terminal's width, widest columns first, with
+//! `…` where text was dropped; `--wide` keeps every cell whole and
+//! `--expand` prints each row as its own block of `column │ value` lines.
+//! Cells are sanitized on the way in: banners are whatever the scanned host
+//! sent, so line breaks become spaces and other control characters are
+//! shown escaped rather than handed to the terminal.
+//!
+//! Output taller than the terminal goes through `$PAGER` (`less` by
+//! default). Like `--output-file`, the command runs again as a child with
+//! its stdout piped through here, so no command has to know about paging.
+
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, IsTerminal, Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::{PoisonError, RwLock};
+
+use anyhow::{Context as _, Result};
+use tabled::builder::Builder;
+use tabled::settings::peaker::PriorityMax;
+use tabled::settings::{Style, Width};
+use tabled::Tabled;
+
+use super::file::strip_ansi;
+use crate::cli::args::Cli;
+use crate::cli::exit::{self, Exit};
+
+/// Set in the child so it prints to the pipe instead of starting another
+/// pager, and still colors as if it were on the terminal.
+pub const PAGER_CHILD_ENV: &str = "I1_PAGER_CHILD";
+
+/// Widest cell in a table that doesn't know the terminal's width
+pub const MAX_CELL_WIDTH: usize = 40;
+
+const ELLIPSIS: &str = "…";
+
+static TERMINAL: RwLock<Terminal> = RwLock::new(Terminal::UNKNOWN);
+
+/// The terminal pretty output is laid out for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Terminal {
+    /// Columns, when stdout is a terminal or `COLUMNS` is set
+    pub width: Option<usize>,
+    /// Rows, when stdout is a terminal or `LINES` is set
+    pub height: Option<usize>,
+    /// Keep every cell whole, however wide the table gets
+    pub wide: bool,
+    /// One block per row instead of a table
+    pub expand: bool,
+}
+
+impl Terminal {
+    const UNKNOWN: Self = Self {
+        width: None,
+        height: None,
+        wide: false,
+        expand: false,
+    };
+
+    /// The size of the terminal on stdout. `COLUMNS` and `LINES` win over
+    /// what the terminal reports.
+    pub fn detect(wide: bool, expand: bool) -> Self {
+        let size = console::Term::stdout().size_checked();
+        let env = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .and_then(|value| value.trim().parse::<usize>().ok())
+                .filter(|&n| n > 0)
+        };
+        Self {
+            width: env("COLUMNS").or_else(|| size.map(|(_, cols)| usize::from(cols))),
+            height: env("LINES").or_else(|| size.map(|(rows, _)| usize::from(rows))),
+            wide,
+            expand,
+        }
+    }
+
+    /// One cell: sanitized, and cut to [`MAX_CELL_WIDTH`] unless whole
+    /// values were asked for.
+    pub fn cell(&self, text: &str) -> String {
+        let text = sanitize(text);
+        if self.wide || self.expand {
+            text
+        } else {
+            truncate(&text, MAX_CELL_WIDTH)
+        }
+    }
+
+    /// Render `header` over `rows`.
+    pub fn render(&self, header: Vec<String>, rows: Vec<Vec<String>>) -> String {
+        if self.expand {
+            return self.expanded(&header, &rows);
+        }
+
+        let mut builder = Builder::default();
+        builder.push_record(header);
+        for row in rows {
+            builder.push_record(row.iter().map(|cell| self.cell(cell)));
+        }
+        let mut table = builder.build();
+        table.with(Style::rounded());
+        if let (Some(width), false) = (self.width, self.wide) {
+            table.with(
+                Width::truncate(width)
+                    .priority(PriorityMax)
+                    .suffix(ELLIPSIS),
+            );
+        }
+        table.to_string()
+    }
+
+    /// Each row as a `-[ n ]-` rule over `column │ value` lines
+    fn expanded(&self, header: &[String], rows: &[Vec<String>]) -> String {
+        let rows: Vec<Vec<String>> = rows
+            .iter()
+            .map(|row| row.iter().map(|cell| self.cell(cell)).collect())
+            .collect();
+        let label = header.iter().map(|h| h.chars().count()).max().unwrap_or(0);
+        let longest = rows
+            .iter()
+            .flatten()
+            .map(|value| strip_ansi(value).chars().count())
+            .max()
+            .unwrap_or(0);
+        let rule = (label + 3 + longest).min(self.width.unwrap_or(usize::MAX));
+
+        let mut out = String::new();
+        for (n, row) in rows.iter().enumerate() {
+            let title = format!("-[ {} ]", n + 1);
+            out.push_str(&title);
+            out.push_str(&"-".repeat(rule.saturating_sub(title.len())));
+            out.push('\n');
+            for (name, value) in header.iter().zip(row) {
+                let line = format!("{name:<label$} │ {value}");
+                out.push_str(line.trim_end());
+                out.push('\n');
+            }
+        }
+        out.pop();
+        out
+    }
+}
+
+/// Lay out for the terminal this command's output goes to.
+pub fn configure(cli: &Cli) {
+    *TERMINAL.write().unwrap_or_else(PoisonError::into_inner) =
+        Terminal::detect(cli.wide, cli.expand);
+}
+
+/// The terminal set by [`configure`]; nothing is known before it runs.
+pub fn current() -> Terminal {
+    *TERMINAL.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Rows as a table fitted to the current terminal
+pub fn table<T: Tabled>(rows: &[T]) -> String {
+    records(
+        T::headers().into_iter().map(String::from).collect(),
+        rows.iter()
+            .map(|row| row.fields().into_iter().map(String::from).collect())
+            .collect(),
+    )
+}
+
+/// Records under `header` as a table fitted to the current terminal
+pub fn records(header: Vec<String>, rows: Vec<Vec<String>>) -> String {
+    current().render(header, rows)
+}
+
+/// Whether stdout ends up on a terminal, through the pager or not.
+pub fn is_terminal() -> bool {
+    std::env::var_os(PAGER_CHILD_ENV).is_some() || io::stdout().is_terminal()
+}
+
+/// Make `text` safe to print in one table cell: line breaks and tabs
+/// become a space, other control characters and bidi overrides are
+/// escaped, and color codes are kept.
+pub fn sanitize(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    let mut gap = false;
+    while let Some(c) = chars.next() {
+        if matches!(c, '\n' | '\r' | '\t') {
+            gap = true;
+            continue;
+        }
+        if gap && !out.is_empty() && !out.ends_with(' ') && c != ' ' {
+            out.push(' ');
+        }
+        gap = false;
+
+        if c == '\x1b' {
+            let rest = chars.as_str();
+            if let Some(len) = sgr_len(rest) {
+                out.push(c);
+                out.push_str(&rest[..len]);
+                chars = rest[len..].chars();
+                continue;
+            }
+        }
+        if c.is_control() {
+            let _ = write!(out, "\\x{:02x}", u32::from(c));
+        } else if matches!(c, '\u{202a}'..='\u{202e}' | '\u{2066}'..='\u{2069}') {
+            let _ = write!(out, "\\u{{{:x}}}", u32::from(c));
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Length of the color sequence `rest` starts with, after its ESC
+fn sgr_len(rest: &str) -> Option<usize> {
+    let params = rest.strip_prefix('[')?;
+    let end = params.find(|c: char| !(c.is_ascii_digit() || c == ';'))?;
+    params[end..].starts_with('m').then_some(end + 2)
+}
+
+/// `text` cut to `width` columns, ending in `…` when cut. Colors are
+/// dropped from cut text rather than left open.
+fn truncate(text: &str, width: usize) -> String {
+    let plain = strip_ansi(text);
+    if plain.chars().count() <= width {
+        return text.to_string();
+    }
+    let mut short: String = plain.chars().take(width.saturating_sub(1)).collect();
+    short.push_str(ELLIPSIS);
+    short
+}
+
+/// Run this command again with its output going through `$PAGER` once it
+/// is taller than `terminal`.
+pub async fn page(terminal: Terminal) -> Result<()> {
+    let Some(height) = terminal.height else {
+        anyhow::bail!("Can't page without knowing the terminal's height");
+    };
+
+    let mut command = Command::new(std::env::current_exe()?);
+    command
+        .args(std::env::args_os().skip(1))
+        .env(PAGER_CHILD_ENV, "1")
+        .env("LINES", height.to_string())
+        .stdout(Stdio::piped());
+    if let Some(width) = terminal.width {
+        command.env("COLUMNS", width.to_string());
+    }
+    let mut child = command
+        .spawn()
+        .context("Could not start the command to page it")?;
+    let mut stdout = child.stdout.take().context("Child has no stdout")?;
+
+    // The child and the pager deal with Ctrl-C; stay alive to clean up
+    tokio::spawn(async { while tokio::signal::ctrl_c().await.is_ok() {} });
+
+    let (paged, status) = tokio::task::spawn_blocking(move || {
+        let paged = show(&mut stdout, height);
+        // Quitting the pager early leaves nobody to read the rest; stop
+        // the child before closing the pipe, so it doesn't die writing
+        if !matches!(paged, Ok(true)) {
+            let _ = child.kill();
+        }
+        let status = child.wait();
+        drop(stdout);
+        (paged, status)
+    })
+    .await?;
+
+    match (paged?, status?) {
+        (false, _) => Ok(()),
+        (true, status) if status.success() => Ok(()),
+        (true, status) => {
+            let code = status.code().and_then(|code| u8::try_from(code).ok());
+            Err(Exit(code.unwrap_or(exit::FAILURE)).into())
+        }
+    }
+}
+
+/// Print `reader`, through the pager if it runs past `height` lines.
+/// `Ok(false)` means the pager was quit before the end.
+fn show(reader: impl Read, height: usize) -> io::Result<bool> {
+    let mut reader = BufReader::new(reader);
+    let mut head = Vec::new();
+    let mut lines = 0;
+    // The last row is the pager's, or the shell prompt's
+    while lines < height.saturating_sub(1) {
+        if reader.read_until(b'\n', &mut head)? == 0 {
+            let mut stdout = io::stdout().lock();
+            stdout.write_all(&head)?;
+            stdout.flush()?;
+            return Ok(true);
+        }
+        lines += 1;
+    }
+
+    let Some(mut pager) = pager() else {
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(&head)?;
+        io::copy(&mut reader, &mut stdout)?;
+        stdout.flush()?;
+        return Ok(true);
+    };
+    let mut input = pager.stdin.take().expect("pager stdin is piped");
+    let written = input
+        .write_all(&head)
+        .and_then(|()| io::copy(&mut reader, &mut input).map(drop));
+    drop(input);
+    pager.wait()?;
+    match written {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// `$PAGER`, or `less`, reading from a pipe. `None` when it won't start.
+fn pager() -> Option<std::process::Child> {
+    let pager = std::env::var("PAGER").unwrap_or_else(|_| "less".to_string());
+    let mut words = pager.split_whitespace();
+    let mut command = Command::new(words.next()?);
+    command.args(words).stdin(Stdio::piped());
+    // Keep colors, and don't clear the screen on the way out
+    if std::env::var_os("LESS").is_none() {
+        command.env("LESS", "FRX");
+    }
+    command.spawn().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Tabled)]
+    struct Row {
+        #[tabled(rename = "Port")]
+        port: u16,
+        #[tabled(rename = "Service")]
+        service: &'static str,
+        #[tabled(rename = "Banner")]
+        banner: &'static str,
+    }
+
+    const ROWS: &[Row] = &[
+        Row {
+            port: 22,
+            service: "ssh",
+            banner: "SSH-2.0-OpenSSH_9.6p1 Ubuntu-3ubuntu13.5",
+        },
+        Row {
+            port: 80,
+            service: "http",
+            banner:
+                "HTTP/1.1 200 OK\r\nServer: nginx/1.24.0 (Ubuntu)\r\nContent-Type: text/html\r\n",
+        },
+    ];
+
+    fn rendered(terminal: Terminal) -> String {
+        terminal.render(
+            Row::headers().into_iter().map(String::from).collect(),
+            ROWS.iter()
+                .map(|row| row.fields().into_iter().map(String::from).collect())
+                .collect(),
+        )
+    }
+
+    fn width(width: usize) -> Terminal {
+        Terminal {
+            width: Some(width),
+            ..Terminal::default()
+        }
+    }
+
+    #[test]
+    fn test_table_at_60_columns() {
+        assert_eq!(
+            rendered(width(60)),
+            "\
+╭──────┬─────────┬─────────────────────────────────────────╮
+│ Port │ Service │ Banner                                  │
+├──────┼─────────┼─────────────────────────────────────────┤
+│ 22   │ ssh     │ SSH-2.0-OpenSSH_9.6p1 Ubuntu-3ubuntu13… │
+│ 80   │ http    │ HTTP/1.1 200 OK Server: nginx/1.24.0 (… │
+╰──────┴─────────┴─────────────────────────────────────────╯"
+        );
+    }
+
+    #[test]
+    fn test_table_at_40_columns() {
+        assert_eq!(
+            rendered(width(40)),
+            "\
+╭──────┬─────────┬─────────────────────╮
+│ Port │ Service │ Banner              │
+├──────┼─────────┼─────────────────────┤
+│ 22   │ ssh     │ SSH-2.0-OpenSSH_9.… │
+│ 80   │ http    │ HTTP/1.1 200 OK Se… │
+╰──────┴─────────┴─────────────────────╯"
+        );
+    }
+
+    #[test]
+    fn test_wide_table() {
+        let table = rendered(Terminal {
+            wide: true,
+            ..width(40)
+        });
+        assert!(table
+            .contains("│ HTTP/1.1 200 OK Server: nginx/1.24.0 (Ubuntu) Content-Type: text/html │"));
+    }
+
+    #[test]
+    fn test_expanded_rows() {
+        assert_eq!(
+            rendered(Terminal {
+                expand: true,
+                ..width(60)
+            }),
+            "\
+-[ 1 ]------------------------------------------------------
+Port    │ 22
+Service │ ssh
+Banner  │ SSH-2.0-OpenSSH_9.6p1 Ubuntu-3ubuntu13.5
+-[ 2 ]------------------------------------------------------
+Port    │ 80
+Service │ http
+Banner  │ HTTP/1.1 200 OK Server: nginx/1.24.0 (Ubuntu) Content-Type: text/html"
+        );
+    }
+
+    #[test]
+    fn test_sanitize() {
+        assert_eq!(sanitize("line one\r\nline two\n"), "line one line two");
+        assert_eq!(sanitize("a\tb"), "a b");
+        assert_eq!(
+            sanitize("\x1b[2J\x1b]0;pwned\x07ok"),
+            "\\x1b[2J\\x1b]0;pwned\\x07ok"
+        );
+        assert_eq!(sanitize("\x1b[1;31mred\x1b[0m"), "\x1b[1;31mred\x1b[0m");
+        assert_eq!(sanitize("admin\u{202e}txt.exe"), "admin\\u{202e}txt.exe");
+        assert_eq!(sanitize("bell\x07 del\x7f"), "bell\\x07 del\\x7f");
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("short", 10), "short");
+        assert_eq!(truncate("exactly ten", 11), "exactly ten");
+        assert_eq!(truncate("a little too long", 10), "a little …");
+        assert_eq!(truncate("\x1b[31mcolored text\x1b[0m", 8), "colored…");
+        assert_eq!(truncate("\x1b[31mred\x1b[0m", 8), "\x1b[31mred\x1b[0m");
+    }
+}
@@ -0,0 +1,615 @@
+//! Investigation workspaces: the lookups made during one incident, kept
+//! together.
+//!
+//! `i1 workspace new <name>` makes a directory under the data directory.
+//! While it is active (`--workspace <name>` or `I1_WORKSPACE`), every host,
+//! search page and scan result is appended to its `results.ndjson`, and
+//! `index.json` remembers where the latest answer for each target is.
+//! Provider calls go to its own `calls.jsonl`, written like the audit log,
+//! so `i1 workspace show` can say which providers were asked and what they
+//! cost.
+//!
+//! A host or search page the workspace already holds is answered from it,
+//! through the [`Cache`](crate::cache::Cache), rather than asked again;
+//! `--refresh` asks anyway and keeps the new answer. Scans always run.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::{BufRead, BufReader, Seek, SeekFrom, Write as _};
+use std::path::{Path, PathBuf};
+use std::sync::{PoisonError, RwLock};
+
+use anyhow::{Context as _, Result};
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use directories::ProjectDirs;
+use i1::{AuditLog, HostInfo, SearchResults};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::cache::Cached;
+use crate::cli::args::{Cli, Commands};
+
+const META: &str = "workspace.json";
+const RESULTS: &str = "results.ndjson";
+const INDEX: &str = "index.json";
+const CALLS: &str = "calls.jsonl";
+
+/// The workspace this process adds to, if any
+static ACTIVE: RwLock<Option<Workspace>> = RwLock::new(None);
+
+/// What produced a stored answer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    /// `i1 host`: one host from a provider
+    Host,
+    /// `i1 search`: one page of results from a provider
+    Search,
+    /// `i1 scan`: one host from the local scanner
+    Scan,
+}
+
+impl Kind {
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Host => "host",
+            Self::Search => "search",
+            Self::Scan => "scan",
+        }
+    }
+}
+
+/// Formats for `workspace export`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    /// The summary, every target and every host, as JSON
+    Json,
+    /// A report skeleton with the facts filled in and the prose left to write
+    #[value(alias = "md")]
+    Markdown,
+}
+
+/// `workspace.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Meta {
+    name: String,
+    created_at: DateTime<Utc>,
+}
+
+/// One line of `results.ndjson`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Record {
+    pub at: DateTime<Utc>,
+    pub kind: Kind,
+    pub provider: String,
+    /// The IP, or the query and page
+    pub target: String,
+    /// A [`HostInfo`], or [`SearchResults`] for a search
+    pub value: serde_json::Value,
+}
+
+impl Record {
+    /// The hosts in the answer.
+    pub fn hosts(&self) -> Vec<HostInfo> {
+        match self.kind {
+            Kind::Host | Kind::Scan => serde_json::from_value(self.value.clone())
+                .map(|host| vec![host])
+                .unwrap_or_default(),
+            Kind::Search => serde_json::from_value::<SearchResults>(self.value.clone())
+                .map(|page| page.results)
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Where the latest answer for one target is.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Target {
+    pub kind: Kind,
+    pub provider: String,
+    pub target: String,
+    pub at: DateTime<Utc>,
+    /// Hosts in the answer
+    pub hosts: usize,
+    /// Byte offset of its line in `results.ndjson`
+    offset: u64,
+}
+
+/// Calls made to one provider, and what they cost.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ProviderUse {
+    pub calls: usize,
+    pub failed: usize,
+    pub credits: u64,
+    /// Answers kept from it, cache hits included
+    pub answers: usize,
+}
+
+/// Targets investigated, by kind.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Targets {
+    pub hosts: usize,
+    pub searches: usize,
+    pub scans: usize,
+}
+
+/// A host worth a look: the riskiest the workspace has seen.
+#[derive(Debug, Clone, Serialize)]
+pub struct Finding {
+    pub ip: String,
+    pub risk: f64,
+    pub vulns: Vec<String>,
+    pub ports: Vec<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub org: Option<String>,
+    /// How it was found: `host`, `search`, `scan`
+    pub seen_in: BTreeSet<Kind>,
+}
+
+/// What `i1 workspace show` reports.
+#[derive(Debug, Clone, Serialize)]
+pub struct Summary {
+    pub name: String,
+    pub path: PathBuf,
+    pub created_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_activity: Option<DateTime<Utc>>,
+    pub targets: Targets,
+    /// Unique hosts across every answer
+    pub hosts: usize,
+    pub providers: BTreeMap<String, ProviderUse>,
+    pub credits: u64,
+    pub findings: Vec<Finding>,
+}
+
+/// One investigation's directory.
+#[derive(Debug, Clone)]
+pub struct Workspace {
+    name: String,
+    dir: PathBuf,
+}
+
+/// The directory workspaces are made in.
+pub fn root() -> Result<PathBuf> {
+    let dirs = ProjectDirs::from("is", "i1", "i1")
+        .ok_or_else(|| anyhow::anyhow!("Could not determine data directory"))?;
+
+    Ok(dirs.data_dir().join("workspaces"))
+}
+
+/// Every workspace in `root`, by name.
+pub fn list(root: &Path) -> Vec<Workspace> {
+    let Ok(dirs) = std::fs::read_dir(root) else {
+        return Vec::new();
+    };
+    let mut found: Vec<Workspace> = dirs
+        .flatten()
+        .filter(|dir| dir.path().join(META).is_file())
+        .map(|dir| Workspace {
+            name: dir.file_name().to_string_lossy().into_owned(),
+            dir: dir.path(),
+        })
+        .collect();
+    found.sort_by(|a, b| a.name.cmp(&b.name));
+    found
+}
+
+/// Names are directory names: letters, digits, `.`, `_` and `-`, starting
+/// with a letter or digit.
+fn check_name(name: &str) -> Result<()> {
+    let valid = name.len() <= 64
+        && name.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+    if !valid {
+        anyhow::bail!(
+            "'{name}' is not a workspace name. Use letters, digits, '.', '_' and '-', \
+             starting with a letter or digit"
+        );
+    }
+    Ok(())
+}
+
+impl Workspace {
+    /// Make a new workspace `name` under `root`.
+    pub fn create(root: &Path, name: &str) -> Result<Self> {
+        check_name(name)?;
+        let dir = root.join(name);
+        if dir.join(META).exists() {
+            anyhow::bail!("Workspace '{name}' already exists at {}", dir.display());
+        }
+        std::fs::create_dir_all(&dir).with_context(|| format!("Can't create {}", dir.display()))?;
+        let meta = Meta {
+            name: name.to_string(),
+            created_at: Utc::now(),
+        };
+        std::fs::write(dir.join(META), serde_json::to_vec_pretty(&meta)?)?;
+        Ok(Self {
+            name: name.to_string(),
+            dir,
+        })
+    }
+
+    /// The existing workspace `name` under `root`.
+    pub fn open(root: &Path, name: &str) -> Result<Self> {
+        check_name(name)?;
+        let dir = root.join(name);
+        if !dir.join(META).is_file() {
+            anyhow::bail!("No workspace '{name}'. Create it with: i1 workspace new {name}");
+        }
+        Ok(Self {
+            name: name.to_string(),
+            dir,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The log provider calls made in the workspace go to.
+    pub fn calls(&self) -> AuditLog {
+        AuditLog::new(self.dir.join(CALLS))
+    }
+
+    fn created_at(&self) -> Result<DateTime<Utc>> {
+        let content = std::fs::read_to_string(self.dir.join(META))?;
+        Ok(serde_json::from_str::<Meta>(&content)?.created_at)
+    }
+
+    /// Where the latest answer for each target is, by [`key`].
+    pub fn index(&self) -> BTreeMap<String, Target> {
+        std::fs::read_to_string(self.dir.join(INDEX))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Every answer kept, oldest first. Lines that can't be read are skipped.
+    pub fn records(&self) -> Result<Vec<Record>> {
+        let content = match std::fs::read_to_string(self.dir.join(RESULTS)) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(content
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+
+    /// Keep a host from `provider`.
+    pub fn record_host(&self, kind: Kind, provider: &str, host: &HostInfo) -> Result<()> {
+        self.record(kind, provider, &host.ip_str, host, 1)
+    }
+
+    /// Keep a search page from `provider`; `target` is the query and page.
+    pub fn record_search(&self, provider: &str, target: &str, page: &SearchResults) -> Result<()> {
+        self.record(Kind::Search, provider, target, page, page.results.len())
+    }
+
+    /// The host `provider` gave for `ip`, if the workspace has it.
+    pub fn host(&self, provider: &str, ip: &str) -> Option<Cached<HostInfo>> {
+        self.find(Kind::Host, provider, ip)
+    }
+
+    /// The search page `provider` gave for `target`, if the workspace has it.
+    pub fn search(&self, provider: &str, target: &str) -> Option<Cached<SearchResults>> {
+        self.find(Kind::Search, provider, target)
+    }
+
+    fn record<T: Serialize>(
+        &self,
+        kind: Kind,
+        provider: &str,
+        target: &str,
+        value: &T,
+        hosts: usize,
+    ) -> Result<()> {
+        let record = Record {
+            at: Utc::now(),
+            kind,
+            provider: provider.to_string(),
+            target: target.to_string(),
+            value: serde_json::to_value(value)?,
+        };
+        let mut line = serde_json::to_vec(&record)?;
+        line.push(b'\n');
+
+        let path = self.dir.join(RESULTS);
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("Can't write {}", path.display()))?;
+        let offset = file.metadata()?.len();
+        file.write_all(&line)?;
+
+        let mut index = self.index();
+        index.insert(
+            key(kind, provider, target),
+            Target {
+                kind,
+                provider: record.provider,
+                target: record.target,
+                at: record.at,
+                hosts,
+                offset,
+            },
+        );
+        std::fs::write(self.dir.join(INDEX), serde_json::to_vec_pretty(&index)?)?;
+        Ok(())
+    }
+
+    fn find<T: DeserializeOwned>(
+        &self,
+        kind: Kind,
+        provider: &str,
+        target: &str,
+    ) -> Option<Cached<T>> {
+        let entry = self.index().remove(&key(kind, provider, target))?;
+        let mut file = std::fs::File::open(self.dir.join(RESULTS)).ok()?;
+        file.seek(SeekFrom::Start(entry.offset)).ok()?;
+        let mut line = String::new();
+        BufReader::new(file).read_line(&mut line).ok()?;
+        let record: Record = serde_json::from_str(&line).ok()?;
+        if record.kind != kind || record.target != target {
+            return None;
+        }
+        Some(Cached {
+            value: serde_json::from_value(record.value).ok()?,
+            cached_at: Some(record.at),
+        })
+    }
+
+    /// Every host the workspace has seen, in address order.
+    pub fn hosts(&self) -> Result<Vec<HostInfo>> {
+        let mut hosts: Vec<HostInfo> = merge_hosts(&self.records()?)
+            .into_values()
+            .map(|(host, _)| host)
+            .collect();
+        hosts.sort_by(|a, b| (a.ip_addr(), &a.ip_str).cmp(&(b.ip_addr(), &b.ip_str)));
+        Ok(hosts)
+    }
+
+    /// Targets, providers, credits and the `top` riskiest hosts.
+    pub fn summary(&self, top: usize) -> Result<Summary> {
+        let index = self.index();
+        let records = self.records()?;
+        let calls = self.calls().entries()?;
+
+        let mut targets = Targets::default();
+        for target in index.values() {
+            match target.kind {
+                Kind::Host => targets.hosts += 1,
+                Kind::Search => targets.searches += 1,
+                Kind::Scan => targets.scans += 1,
+            }
+        }
+
+        let mut providers: BTreeMap<String, ProviderUse> = BTreeMap::new();
+        for call in &calls {
+            let Some(provider) = &call.provider else {
+                continue;
+            };
+            let used = providers.entry(provider.clone()).or_default();
+            used.calls += 1;
+            used.failed += usize::from(!call.ok);
+            used.credits += u64::from(call.credits.unwrap_or(0));
+        }
+        for record in &records {
+            providers
+                .entry(record.provider.clone())
+                .or_default()
+                .answers += 1;
+        }
+
+        let hosts = merge_hosts(&records);
+        let mut findings: Vec<Finding> = hosts
+            .values()
+            .map(|(host, kinds)| Finding {
+                ip: host.ip_str.clone(),
+                risk: host.risk_score(),
+                vulns: host.vulns.clone(),
+                ports: host.open_ports().into_iter().collect(),
+                org: host.org.clone(),
+                seen_in: kinds.clone(),
+            })
+            .filter(|finding| finding.risk > 0.0)
+            .collect();
+        findings.sort_by(|a, b| {
+            b.risk
+                .total_cmp(&a.risk)
+                .then(b.vulns.len().cmp(&a.vulns.len()))
+                .then_with(|| a.ip.cmp(&b.ip))
+        });
+        findings.truncate(top);
+
+        let last_activity = records
+            .iter()
+            .map(|r| r.at)
+            .chain(calls.iter().map(|c| c.at))
+            .max();
+
+        Ok(Summary {
+            name: self.name.clone(),
+            path: self.dir.clone(),
+            created_at: self.created_at()?,
+            last_activity,
+            targets,
+            hosts: hosts.len(),
+            credits: providers.values().map(|used| used.credits).sum(),
+            providers,
+            findings,
+        })
+    }
+}
+
+/// Every host in `records`, and what found it. The newest answer for a
+/// host wins, with what older ones add.
+fn merge_hosts(records: &[Record]) -> HashMap<String, (HostInfo, BTreeSet<Kind>)> {
+    let mut hosts: HashMap<String, (HostInfo, BTreeSet<Kind>)> = HashMap::new();
+    for record in records {
+        for mut host in record.hosts() {
+            let mut kinds = match hosts.remove(&host.ip_str) {
+                Some((seen, kinds)) => {
+                    host.merge(seen);
+                    kinds
+                }
+                None => BTreeSet::new(),
+            };
+            kinds.insert(record.kind);
+            hosts.insert(host.ip_str.clone(), (host, kinds));
+        }
+    }
+    hosts
+}
+
+/// The index key for a target: `host shodan 192.0.2.1`.
+fn key(kind: Kind, provider: &str, target: &str) -> String {
+    format!("{} {provider} {target}", kind.name())
+}
+
+/// Make the workspace `cli` names active for this run, or none.
+///
+/// `i1 workspace` itself runs without one if it doesn't exist yet, so
+/// `workspace new` can make it.
+pub fn open(cli: &Cli) -> Result<()> {
+    let workspace = match &cli.workspace {
+        Some(name) => match Workspace::open(&root()?, name) {
+            Ok(workspace) => Some(workspace),
+            Err(_) if matches!(cli.command, Some(Commands::Workspace(_))) => None,
+            Err(e) => return Err(e),
+        },
+        None => None,
+    };
+    *ACTIVE.write().unwrap_or_else(PoisonError::into_inner) = workspace;
+    Ok(())
+}
+
+/// The active workspace, if there is one.
+pub fn active() -> Option<Workspace> {
+    ACTIVE
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+}
+
+/// Keep a host in the active workspace. A workspace that can't be written
+/// is reported but doesn't fail the lookup.
+pub fn keep_host(kind: Kind, provider: &str, host: &HostInfo) {
+    if let Some(workspace) = active() {
+        if let Err(e) = workspace.record_host(kind, provider, host) {
+            eprintln!("Warning: workspace {}: {e}", workspace.name);
+        }
+    }
+}
+
+/// Keep a search page in the active workspace, like [`keep_host`].
+pub fn keep_search(provider: &str, target: &str, page: &SearchResults) {
+    if let Some(workspace) = active() {
+        if let Err(e) = workspace.record_search(provider, target, page) {
+            eprintln!("Warning: workspace {}: {e}", workspace.name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use i1_core::testing::host;
+    use serde_json::json;
+
+    #[test]
+    fn test_record_and_find() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let workspace = Workspace::create(tmp.path(), "incident-7").unwrap();
+        assert!(Workspace::create(tmp.path(), "incident-7").is_err());
+        assert!(Workspace::create(tmp.path(), "../escape").is_err());
+        assert!(Workspace::open(tmp.path(), "other").is_err());
+
+        assert!(workspace.host("shodan", "192.0.2.1").is_none());
+        workspace
+            .record_host(Kind::Host, "shodan", &host("192.0.2.1", json!({})))
+            .unwrap();
+        workspace
+            .record_host(
+                Kind::Host,
+                "shodan",
+                &host("192.0.2.1", json!({ "vulns": ["CVE-2024-6387"] })),
+            )
+            .unwrap();
+
+        // The newest answer, found through the index
+        let found = workspace.host("shodan", "192.0.2.1").unwrap();
+        assert_eq!(found.value.vulns, ["CVE-2024-6387"]);
+        assert!(found.cached_at.is_some());
+        assert!(workspace.host("censys", "192.0.2.1").is_none());
+
+        let page = SearchResults {
+            provider: "shodan".to_string(),
+            total: 2,
+            page: 1,
+            results: vec![host("192.0.2.2", json!({})), host("192.0.2.3", json!({}))],
+            facets: None,
+        };
+        workspace
+            .record_search("shodan", "nginx (page 1)", &page)
+            .unwrap();
+        let found = workspace.search("shodan", "nginx (page 1)").unwrap();
+        assert_eq!(found.value.results.len(), 2);
+        assert!(workspace.search("shodan", "nginx (page 2)").is_none());
+
+        assert_eq!(workspace.records().unwrap().len(), 3);
+        assert_eq!(workspace.index().len(), 2);
+        assert_eq!(list(tmp.path()).len(), 1);
+    }
+
+    #[test]
+    fn test_summary() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let workspace = Workspace::create(tmp.path(), "case").unwrap();
+        workspace
+            .record_host(
+                Kind::Host,
+                "shodan",
+                &host("192.0.2.1", json!({ "vulns": ["CVE-2024-6387"] })),
+            )
+            .unwrap();
+        // A scan of the same host adds to it rather than replacing it
+        let mut scanned = host("192.0.2.1", json!({}));
+        scanned.ports = vec![8080];
+        workspace
+            .record_host(Kind::Scan, "scanner", &scanned)
+            .unwrap();
+        workspace
+            .record_host(Kind::Host, "shodan", &host("192.0.2.9", json!({})))
+            .unwrap();
+
+        let summary = workspace.summary(10).unwrap();
+        assert_eq!(
+            summary.targets,
+            Targets {
+                hosts: 2,
+                searches: 0,
+                scans: 1
+            }
+        );
+        assert_eq!(summary.hosts, 2);
+        assert_eq!(summary.providers["shodan"].answers, 2);
+        assert_eq!(summary.credits, 0);
+
+        let [finding] = summary.findings.as_slice() else {
+            panic!("expected one finding, got {:?}", summary.findings);
+        };
+        assert_eq!(finding.ip, "192.0.2.1");
+        assert_eq!(finding.vulns, ["CVE-2024-6387"]);
+        assert!(finding.ports.contains(&8080));
+        assert_eq!(finding.seen_in, BTreeSet::from([Kind::Host, Kind::Scan]));
+    }
+}
@@ -0,0 +1,205 @@
+//! Harness for scenario tests: `i1` in an empty home, talking to mock
+//! providers that answer from the canned responses in `tests/fixtures`.
+//!
+//! A new command or flag lands with a scenario: add the responses it needs
+//! to [`SHODAN`] or [`CRIMINALIP`] (or mount them in the test with
+//! [`answer`]), run it with [`Harness::run`], and snapshot the result.
+//!
+//! ```ignore
+//! let harness = Harness::start().await;
+//! insta::assert_snapshot!(harness.run(&["host", "192.0.2.1"]).snapshot());
+//! ```
+//!
+//! Review new or changed snapshots with `cargo insta review`, or accept
+//! them with `INSTA_UPDATE=always cargo test`.
+
+// Each test binary uses its own part of the harness
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+
+use assert_cmd::Command;
+use tempfile::TempDir;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// Shodan routes, and the fixture each answers with.
+pub const SHODAN: &[(&str, &str)] = &[
+    ("/shodan/host/192.0.2.1", "shodan/host.json"),
+    ("/shodan/host/search", "shodan/search.json"),
+    ("/shodan/host/count", "shodan/count.json"),
+    ("/dns/resolve", "shodan/dns-resolve.json"),
+    ("/dns/reverse", "shodan/dns-reverse.json"),
+];
+
+/// Criminal IP routes, and the fixture each answers with.
+pub const CRIMINALIP: &[(&str, &str)] = &[
+    ("/asset/ip/report", "criminalip/ip-report.json"),
+    ("/banner/search", "criminalip/banner-search.json"),
+];
+
+/// Variables that would leak the developer's own setup into a run.
+const ISOLATED: &[&str] = &[
+    "I1_SHODAN_KEY",
+    "SHODAN_API_KEY",
+    "I1_CENSYS_ID",
+    "I1_CENSYS_SECRET",
+    "I1_CRIMINALIP_KEY",
+    "I1_TOKEN",
+    "I1_OUTPUT",
+    "I1_PROVIDER",
+    "I1_PROFILE",
+    "I1_PROXY",
+    "I1_WORKSPACE",
+    "I1_SHODAN_URL",
+    "I1_CENSYS_URL",
+    "I1_CRIMINALIP_URL",
+    "I1_MYIP_URL",
+    "I1_MYIP6_URL",
+    "RUST_BACKTRACE",
+    "CLICOLOR_FORCE",
+    "SSH_CLIENT",
+    "SSH_CONNECTION",
+];
+
+/// A fixture's contents, by its path under `tests/fixtures`.
+pub fn fixture(name: &str) -> String {
+    let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "tests", "fixtures", name]
+        .iter()
+        .collect();
+    std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {e}", path.display()))
+}
+
+/// Answer GETs for `route` on `server` with `response`.
+pub async fn answer(server: &MockServer, route: &str, response: ResponseTemplate) {
+    Mock::given(method("GET"))
+        .and(path(route))
+        .respond_with(response)
+        .mount(server)
+        .await;
+}
+
+/// A mock answering GETs for `route` with `response`, and nothing else.
+pub async fn answering(route: &str, response: ResponseTemplate) -> MockServer {
+    let server = MockServer::start().await;
+    answer(&server, route, response).await;
+    server
+}
+
+/// A mock provider answering each route with its fixture.
+pub async fn mock(routes: &[(&str, &str)]) -> MockServer {
+    let server = MockServer::start().await;
+    for (route, name) in routes {
+        let response = ResponseTemplate::new(200)
+            .insert_header("content-type", "application/json")
+            .set_body_string(fixture(name));
+        answer(&server, route, response).await;
+    }
+    server
+}
+
+/// `i1` with nothing configured: no config file, cache, credentials or
+/// colors from whoever runs the tests.
+pub fn isolated(home: &TempDir) -> Command {
+    let mut cmd = Command::cargo_bin("i1").unwrap();
+    cmd.env("HOME", home.path())
+        .env("XDG_CONFIG_HOME", home.path())
+        .env("XDG_CACHE_HOME", home.path())
+        .env("XDG_DATA_HOME", home.path())
+        .env("NO_COLOR", "1")
+        .env("COLUMNS", "120")
+        .env("TZ", "UTC");
+    for var in ISOLATED {
+        cmd.env_remove(var);
+    }
+    cmd
+}
+
+/// [`isolated`] `i1` with a Shodan key, talking to `server`.
+pub fn against(home: &TempDir, server: &MockServer) -> Command {
+    let mut cmd = isolated(home);
+    cmd.env("I1_SHODAN_KEY", "test-key")
+        .env("I1_SHODAN_URL", server.uri());
+    cmd
+}
+
+/// An empty home and mock providers, with keys for both.
+pub struct Harness {
+    pub home: TempDir,
+    pub shodan: MockServer,
+    pub criminalip: MockServer,
+}
+
+impl Harness {
+    /// Mock Shodan and Criminal IP serving [`SHODAN`] and [`CRIMINALIP`].
+    pub async fn start() -> Self {
+        Self {
+            home: TempDir::new().unwrap(),
+            shodan: mock(SHODAN).await,
+            criminalip: mock(CRIMINALIP).await,
+        }
+    }
+
+    /// `i1` pointed at the mock providers. Shodan is picked by default,
+    /// as it is whenever its key is set.
+    pub fn i1(&self) -> Command {
+        let mut cmd = isolated(&self.home);
+        cmd.env("I1_SHODAN_KEY", "shodan-test-key")
+            .env("I1_SHODAN_URL", self.shodan.uri())
+            .env("I1_CRIMINALIP_KEY", "criminalip-test-key")
+            .env("I1_CRIMINALIP_URL", self.criminalip.uri());
+        cmd
+    }
+
+    /// Run `i1 args` against the mocks, answers never cached.
+    pub fn run(&self, args: &[&str]) -> Run {
+        self.run_with(self.i1().arg("--no-cache").args(args))
+    }
+
+    /// Run a command built from [`Harness::i1`], e.g. with stdin.
+    pub fn run_with(&self, cmd: &mut Command) -> Run {
+        let output = cmd.output().unwrap();
+        let redact = |bytes: &[u8]| {
+            String::from_utf8_lossy(bytes)
+                .replace(&self.home.path().display().to_string(), "[HOME]")
+                .replace(&self.shodan.uri(), "[SHODAN]")
+                .replace(&self.criminalip.uri(), "[CRIMINALIP]")
+        };
+        Run {
+            code: output.status.code(),
+            stdout: redact(&output.stdout),
+            stderr: redact(&output.stderr),
+        }
+    }
+}
+
+/// What a run printed and how it ended, with paths and mock addresses
+/// replaced so it reads the same on every machine.
+#[derive(Debug)]
+pub struct Run {
+    pub code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl Run {
+    /// Replace what `pattern` matches in stdout and stderr, for output
+    /// that changes from run to run.
+    pub fn redact(mut self, pattern: &str, with: &str) -> Self {
+        let pattern = regex::Regex::new(pattern).unwrap();
+        self.stdout = pattern.replace_all(&self.stdout, with).into_owned();
+        self.stderr = pattern.replace_all(&self.stderr, with).into_owned();
+        self
+    }
+
+    /// Exit code, stdout and stderr, as one snapshot.
+    pub fn snapshot(&self) -> String {
+        let code = self
+            .code
+            .map_or_else(|| "signal".to_string(), |code| code.to_string());
+        format!(
+            "exit: {code}\n--- stdout\n{}--- stderr\n{}",
+            self.stdout, self.stderr
+        )
+    }
+}
@@ -0,0 +1,58 @@
+//! `defend` against a state file in an empty home. Nothing here gets as far
+//! as loading rules into the firewall.
+
+mod common;
+
+use std::path::PathBuf;
+
+use predicates::prelude::*;
+use tempfile::TempDir;
+
+/// Where `defend` keeps its state under `home`.
+fn state_file(home: &TempDir) -> PathBuf {
+    home.path().join("showdi1").join("defend_state.json")
+}
+
+/// Start from a state with `whitelisted` and nothing blocked.
+fn whitelisting(home: &TempDir, whitelisted: &[&str]) -> String {
+    let state = serde_json::json!({
+        "blocked_countries": [],
+        "blocked_ips": [],
+        "blocked_asns": [],
+        "whitelisted_ips": whitelisted,
+    })
+    .to_string();
+    let path = state_file(home);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, &state).unwrap();
+    state
+}
+
+#[test]
+fn test_ban_overlapping_whitelist() {
+    let home = TempDir::new().unwrap();
+    let state = whitelisting(&home, &["203.0.113.0/24"]);
+
+    for target in ["203.0.113.7", "203.0.113.128/25", "203.0.113.0/24"] {
+        common::isolated(&home)
+            .args(["defend", "ban", target])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains(format!(
+                "{target} is covered by whitelist entry 203.0.113.0/24; not banned."
+            )))
+            .stdout(predicate::str::contains("Blocked").not());
+    }
+    // Nothing was banned
+    assert_eq!(std::fs::read_to_string(state_file(&home)).unwrap(), state);
+
+    // The dry run's plan says the same
+    let output = common::isolated(&home)
+        .args(["-o", "json", "defend", "ban", "203.0.113.7", "--dry-run"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let plan: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(plan["changed"], serde_json::json!([]));
+    assert_eq!(plan["skipped"][0]["target"], "203.0.113.7");
+}
@@ -0,0 +1,932 @@
+//! The exit-code contract, pinned against a mock Shodan API.
+
+mod common;
+
+use clap::CommandFactory;
+use common::{against, answering, isolated};
+use i1_cli::cli::args::Cli;
+use i1_cli::cli::exit;
+use predicates::prelude::*;
+use tempfile::TempDir;
+use wiremock::matchers::{method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+#[test]
+fn test_cli_definition() {
+    Cli::command().debug_assert();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_host_found() {
+    let home = TempDir::new().unwrap();
+    let server = answering(
+        "/shodan/host/192.0.2.1",
+        ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "ip_str": "192.0.2.1",
+            "ports": [22],
+            "data": [{"port": 22, "product": "OpenSSH"}]
+        })),
+    )
+    .await;
+
+    // --quiet leaves only the exit code
+    against(&home, &server)
+        .args(["host", "192.0.2.1", "--quiet"])
+        .assert()
+        .code(i32::from(exit::SUCCESS))
+        .stdout(predicate::str::is_empty());
+
+    against(&home, &server)
+        .args(["host", "192.0.2.1", "-o", "json"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"ip_str\": \"192.0.2.1\""));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_host_ipv6() {
+    const IPV6: &str = include_str!("../../i1-core/tests/fixtures/ipv6.txt");
+    let home = TempDir::new().unwrap();
+
+    for line in IPV6
+        .lines()
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+    {
+        let (input, canonical) = line.split_once('\t').unwrap();
+        let ip: std::net::Ipv6Addr = canonical.parse().unwrap();
+        if ip.is_loopback() || (ip.segments()[0] & 0xffc0) == 0xfe80 {
+            // Withheld from Shodan by the default policy
+            continue;
+        }
+        let server = answering(
+            &format!("/shodan/host/{canonical}"),
+            ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "ip_str": canonical,
+                "ports": [443]
+            })),
+        )
+        .await;
+
+        against(&home, &server)
+            .args(["host", input, "-o", "json"])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains(format!(
+                "\"ip_str\": \"{canonical}\""
+            )));
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_host_raw() {
+    let home = TempDir::new().unwrap();
+    let server = answering(
+        "/shodan/host/192.0.2.1",
+        ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "ip_str": "192.0.2.1",
+            "region_name": "Capital Region",
+            "data": [{"port": 22, "_shodan": {"module": "ssh"}}]
+        })),
+    )
+    .await;
+
+    // Fields the normalized host drops are still there
+    against(&home, &server)
+        .args(["host", "192.0.2.1", "--raw"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "\"region_name\": \"Capital Region\"",
+        ));
+    against(&home, &server)
+        .args(["host", "192.0.2.1", "-o", "json"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("region_name").not());
+
+    against(&home, &server)
+        .args(["host", "192.0.2.0/30", "--raw"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("not a CIDR block"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_host_domain() {
+    let home = TempDir::new().unwrap();
+    let server = answering(
+        "/shodan/host/192.0.2.1",
+        ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "ip_str": "192.0.2.1",
+            "ports": [443]
+        })),
+    )
+    .await;
+    Mock::given(method("GET"))
+        .and(path("/dns/resolve"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(serde_json::json!({ "example.com": "192.0.2.1" })),
+        )
+        .mount(&server)
+        .await;
+
+    // Domain info failing doesn't stop the addresses being looked up
+    against(&home, &server)
+        .args(["host", "https://Example.com/login", "-o", "json"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(r#""target":"example.com""#))
+        .stdout(predicate::str::contains(r#""ip":"192.0.2.1""#))
+        .stdout(predicate::str::contains(r#""ports":[443]"#));
+
+    against(&home, &server)
+        .args(["host", "example.com", "--raw"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("i1 dns resolve example.com"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_host_errors() {
+    let home = TempDir::new().unwrap();
+    let cases = [
+        (404, exit::NOT_FOUND),
+        (401, exit::AUTH),
+        (429, exit::RATE_LIMITED),
+        (402, exit::RATE_LIMITED),
+        (503, exit::PROVIDER),
+    ];
+
+    for (status, code) in cases {
+        let server = answering("/shodan/host/192.0.2.1", ResponseTemplate::new(status)).await;
+        against(&home, &server)
+            .args(["host", "192.0.2.1", "--quiet"])
+            .assert()
+            .code(i32::from(code))
+            .stdout(predicate::str::is_empty());
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_host_schema_drift() {
+    let home = TempDir::new().unwrap();
+    let dumps = home.path().join("dumps");
+
+    // A banner whose port turned into a string is dropped, the host kept
+    let server = answering(
+        "/shodan/host/192.0.2.1",
+        ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "ip_str": "192.0.2.1",
+            "ports": [22, 80],
+            "data": [{"port": 22, "product": "OpenSSH"}, {"port": "80"}]
+        })),
+    )
+    .await;
+    against(&home, &server)
+        .args(["host", "192.0.2.1", "-o", "json"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"partial-parse\""))
+        .stdout(predicate::str::contains("OpenSSH"));
+
+    // A renamed address leaves nothing to salvage
+    let server = answering(
+        "/shodan/host/192.0.2.2",
+        ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "ip_address": "192.0.2.2",
+            "ports": [22]
+        })),
+    )
+    .await;
+    against(&home, &server)
+        .args(["host", "192.0.2.2", "--dump-responses"])
+        .arg(&dumps)
+        .assert()
+        .code(i32::from(exit::PROVIDER))
+        .stderr(predicate::str::contains("$: missing field `ip_str`"))
+        .stderr(predicate::str::contains("response saved to"));
+    let saved: Vec<_> = std::fs::read_dir(&dumps).unwrap().collect();
+    assert_eq!(saved.len(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_policy() {
+    let home = TempDir::new().unwrap();
+    let server = answering(
+        "/shodan/host/198.51.100.1",
+        ResponseTemplate::new(200).set_body_json(serde_json::json!({ "ip_str": "198.51.100.1" })),
+    )
+    .await;
+
+    // Private space is withheld from Shodan by default
+    against(&home, &server)
+        .args(["host", "10.1.2.3"])
+        .assert()
+        .code(i32::from(exit::INVALID_INPUT))
+        .stderr(predicate::str::contains("policy"));
+
+    isolated(&home)
+        .args(["config", "set", "policy.deny-external", "198.51.100.0/24"])
+        .assert()
+        .success();
+    against(&home, &server)
+        .args(["host", "198.51.100.1"])
+        .assert()
+        .code(i32::from(exit::INVALID_INPUT));
+    against(&home, &server)
+        .args(["host", "198.51.100.1", "--all", "--output", "json"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains(r#""error": "skipped by policy""#));
+
+    isolated(&home)
+        .args(["config", "set", "policy.deny-external", "not a target"])
+        .assert()
+        .code(i32::from(exit::INVALID_INPUT));
+
+    // Nothing was sent
+    assert!(server.received_requests().await.unwrap().is_empty());
+
+    // Emptying the list lets it through again
+    isolated(&home)
+        .args(["config", "set", "policy.deny-external", ""])
+        .assert()
+        .success();
+    against(&home, &server)
+        .args(["host", "198.51.100.1", "--quiet"])
+        .assert()
+        .success();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_search_without_results() {
+    let home = TempDir::new().unwrap();
+    let server = answering(
+        "/shodan/host/search",
+        ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "matches": [],
+            "total": 0
+        })),
+    )
+    .await;
+
+    against(&home, &server)
+        .args(["search", "port:1", "--quiet"])
+        .assert()
+        .code(i32::from(exit::NOT_FOUND))
+        .stderr(predicate::str::is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_search_group_by() {
+    let home = TempDir::new().unwrap();
+    let server = answering(
+        "/shodan/host/search",
+        ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "matches": [
+                { "ip_str": "192.0.2.1", "port": 22, "location": { "country_code": "US" } },
+                { "ip_str": "192.0.2.1", "port": 80, "location": { "country_code": "US" } },
+                { "ip_str": "192.0.2.2", "port": 80, "location": { "country_code": "DE" } },
+            ],
+            "total": 3
+        })),
+    )
+    .await;
+
+    against(&home, &server)
+        .args(["search", "ssh", "--group-by", "port", "-o", "json"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(r#""key": "80","#))
+        .stdout(predicate::str::contains(r#""count": 2,"#));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_search_stats() {
+    let home = TempDir::new().unwrap();
+    let server = answering(
+        "/shodan/host/search",
+        ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "matches": [
+                { "ip_str": "192.0.2.1", "port": 22, "hostnames": ["WWW.Example.com."], "tags": ["cloud"] },
+                { "ip_str": "192.0.2.1", "port": 80, "hostnames": ["www.example.com"], "tags": ["cloud"] },
+                { "ip_str": "192.0.2.2", "port": 80 },
+            ],
+            "total": 3
+        })),
+    )
+    .await;
+
+    let output = against(&home, &server)
+        .args(["search", "ssh", "--stats", "-o", "json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let results: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let hosts = results["results"].as_array().unwrap();
+    assert_eq!(hosts.len(), 2);
+    let host = hosts.iter().find(|h| h["ip_str"] == "192.0.2.1").unwrap();
+    assert_eq!(host["hostnames"], serde_json::json!(["www.example.com"]));
+    assert_eq!(host["tags"], serde_json::json!(["cloud"]));
+    assert_eq!(host["ports"], serde_json::json!([22, 80]));
+
+    let stats: serde_json::Value = serde_json::from_slice(&output.stderr).unwrap();
+    assert_eq!(stats["hosts"], 2);
+    assert_eq!(stats["overlapping"], 0);
+    assert_eq!(stats["providers"][0]["provider"], "shodan");
+    assert_eq!(stats["providers"][0]["exclusive"], 2);
+
+    against(&home, &server)
+        .args(["search", "ssh", "--stats"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "2 unique hosts, 0 returned by 2+ providers",
+        ));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_search_filter() {
+    let home = TempDir::new().unwrap();
+    let server = answering(
+        "/shodan/host/search",
+        ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "matches": [
+                { "ip_str": "192.0.2.1", "port": 22, "org": "Hetzner", "location": { "country_code": "DE" } },
+                { "ip_str": "192.0.2.1", "port": 80, "org": "Hetzner", "location": { "country_code": "DE" } },
+                { "ip_str": "192.0.2.1", "port": 443, "org": "Hetzner", "location": { "country_code": "DE" } },
+                { "ip_str": "192.0.2.2", "port": 80, "org": "Amazon.com", "location": { "country_code": "DE" } },
+                { "ip_str": "192.0.2.2", "port": 443, "org": "Amazon.com", "location": { "country_code": "DE" } },
+                { "ip_str": "192.0.2.2", "port": 8080, "org": "Amazon.com", "location": { "country_code": "DE" } },
+            ],
+            "total": 6
+        })),
+    )
+    .await;
+
+    let filter = r#"ports.len >= 3 && org !~ "Amazon|Google" && country == "DE""#;
+    against(&home, &server)
+        .args([
+            "search", "port:22", "--filter", filter, "-o", "csv", "--fields", "ip",
+        ])
+        .assert()
+        .success()
+        .stdout("ip\n192.0.2.1\n");
+
+    against(&home, &server)
+        .args(["search", "port:22", "--filter", "ports == 3389", "--quiet"])
+        .assert()
+        .code(i32::from(exit::NOT_FOUND));
+
+    against(&home, &server)
+        .args(["search", "port:22", "--filter", "ports.len >= && org"])
+        .assert()
+        .code(i32::from(exit::INVALID_INPUT))
+        .stderr(predicate::str::contains(
+            "  ports.len >= && org\n               ^^",
+        ));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_saved_queries() {
+    let home = TempDir::new().unwrap();
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/shodan/host/search"))
+        .and(query_param("query", "port:3389 country:DE"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "matches": [{ "ip_str": "192.0.2.1", "port": 3389 }],
+            "total": 17
+        })))
+        .mount(&server)
+        .await;
+
+    isolated(&home)
+        .args([
+            "search",
+            "port:3389 country:{country}",
+            "--save",
+            "rdp-by-country",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Saved rdp-by-country"));
+    against(&home, &server)
+        .args([
+            "search",
+            "--saved",
+            "rdp-by-country",
+            "country=DE",
+            "-o",
+            "json",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("192.0.2.1"));
+
+    // A placeholder left empty, or a name never saved, is the caller's mistake
+    against(&home, &server)
+        .args(["search", "--saved", "rdp-by-country"])
+        .assert()
+        .code(i32::from(exit::INVALID_INPUT))
+        .stderr(predicate::str::contains("country=..."));
+    against(&home, &server)
+        .args(["search", "--saved", "nope"])
+        .assert()
+        .code(i32::from(exit::INVALID_INPUT))
+        .stderr(predicate::str::contains("Saved queries: rdp-by-country"));
+
+    // The search is in the history, the key isn't
+    Mock::given(method("GET"))
+        .and(path("/shodan/host/count"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(serde_json::json!({ "matches": [], "total": 5 })),
+        )
+        .mount(&server)
+        .await;
+    against(&home, &server)
+        .args(["count", "ssl:test-key"])
+        .assert()
+        .success();
+    isolated(&home)
+        .args(["search", "--history", "-o", "json"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            r#""query": "port:3389 country:DE""#,
+        ))
+        .stdout(predicate::str::contains(r#""total": 17"#))
+        .stdout(predicate::str::contains(
+            r#""rdp-by-country": "port:3389 country:{country}""#,
+        ))
+        .stdout(predicate::str::contains("ssl:<redacted>"))
+        .stdout(predicate::str::contains("test-key").not());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_dry_run() {
+    let home = TempDir::new().unwrap();
+    let server = answering(
+        "/api-info",
+        ResponseTemplate::new(200)
+            .set_body_json(serde_json::json!({ "query_credits": 3, "plan": "dev" })),
+    )
+    .await;
+    let targets = home.path().join("targets.txt");
+    std::fs::write(&targets, "192.0.2.1\nexample.com\n10.0.0.0/8\n# done\n").unwrap();
+
+    // Five pages of a filtered search cost more than the three credits left
+    against(&home, &server)
+        .args([
+            "search",
+            "apache port:22",
+            "--limit",
+            "500",
+            "--dry-run",
+            "-o",
+            "json",
+        ])
+        .assert()
+        .code(i32::from(exit::RATE_LIMITED))
+        .stdout(predicate::str::contains(r#""calls": 5,"#))
+        .stdout(predicate::str::contains(r#""credits": 5,"#))
+        .stdout(predicate::str::contains(r#""credits_remaining": 3,"#));
+
+    against(&home, &server)
+        .args(["search", "apache", "--dry-run", "-o", "json"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(r#""credits": 0,"#));
+
+    against(&home, &server)
+        .args(["search", r#"org:"Acme"#, "--dry-run"])
+        .assert()
+        .code(i32::from(exit::INVALID_INPUT))
+        .stdout(predicate::str::contains("unclosed quote"));
+
+    // The block is neither an IP nor a hostname, so it costs no lookup
+    against(&home, &server)
+        .args(["host", "--file"])
+        .arg(&targets)
+        .args(["--dry-run", "-o", "csv"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(",true,1 targets are not IPs"))
+        .stdout(predicate::str::contains(",2,0,3,"));
+
+    // Only the first run checked the balance; nothing billable was asked
+    let requests = server.received_requests().await.unwrap();
+    let paths: Vec<&str> = requests.iter().map(|r| r.url.path()).collect();
+    assert_eq!(paths, ["/api-info"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_audit_log() {
+    let home = TempDir::new().unwrap();
+    let server = answering("/shodan/host/192.0.2.1", ResponseTemplate::new(503)).await;
+
+    // Off unless asked for
+    against(&home, &server)
+        .args(["host", "192.0.2.1", "--quiet"])
+        .assert()
+        .code(i32::from(exit::PROVIDER));
+    isolated(&home)
+        .args(["audit", "tail", "-o", "json"])
+        .assert()
+        .success()
+        .stdout("[]\n");
+
+    against(&home, &server)
+        .args(["host", "192.0.2.1", "--quiet", "--audit"])
+        .assert()
+        .code(i32::from(exit::PROVIDER));
+    isolated(&home)
+        .args(["audit", "tail", "-o", "json"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"operation\": \"lookup\""))
+        .stdout(predicate::str::contains(
+            "\"targets\": [\n      \"192.0.2.1\"",
+        ))
+        .stdout(predicate::str::contains("\"ok\": false"))
+        .stdout(predicate::str::contains("test-key").not());
+    isolated(&home)
+        .args(["audit", "summary", "-o", "csv"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(",1,1,0,0\n"));
+}
+
+#[test]
+fn test_honeypot_stats() {
+    let home = TempDir::new().unwrap();
+    let event = |ip: &str, at: &str| {
+        format!(
+            r#"{{"honeypot_id":"6f1c1a8e-2b7d-4c55-9d5e-0c1f3a9b7e21","honeypot_type":"card","triggered_at":"{at}","source_ip":"{ip}","context":{{}}}}"#
+        )
+    };
+    let events = home.path().join("events.jsonl");
+    std::fs::write(
+        &events,
+        [
+            event("198.51.100.7", "2024-05-01T10:00:00Z"),
+            event("198.51.100.7", "2024-05-01T10:00:00Z"),
+            event("203.0.113.9", "2024-05-02T08:30:00Z"),
+            "{\"honeypot_id\": ".to_string(),
+        ]
+        .join("\n"),
+    )
+    .unwrap();
+
+    isolated(&home)
+        .args(["honeypot", "stats"])
+        .arg(&events)
+        .args(["-o", "json"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"events\": 2,"))
+        .stdout(predicate::str::contains("\"duplicates\": 1,"))
+        .stdout(predicate::str::contains("\"malformed\": 1,"))
+        .stdout(predicate::str::contains("\"unattributed\": 2,"));
+    isolated(&home)
+        .args(["honeypot", "stats"])
+        .arg(&events)
+        .args(["-o", "csv"])
+        .assert()
+        .success()
+        .stdout("start,events\n2024-05-01T00:00:00+00:00,1\n2024-05-02T00:00:00+00:00,1\n");
+    isolated(&home)
+        .args(["honeypot", "stats", "missing.jsonl"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("missing.jsonl"));
+
+    // Pretty tables fit COLUMNS unless --wide; other lines aren't cut
+    let widest = |args: &[&str]| {
+        let output = isolated(&home)
+            .env("COLUMNS", "50")
+            .args(["honeypot", "stats", "--no-color"])
+            .arg(&events)
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .filter(|line| line.starts_with(['╭', '│', '├', '╰']))
+            .map(|line| line.chars().count())
+            .max()
+            .unwrap()
+    };
+    assert!(widest(&[]) <= 50);
+    assert!(widest(&["--wide"]) > 50);
+}
+
+#[test]
+fn test_unreachable_provider() {
+    let home = TempDir::new().unwrap();
+    // Nothing listens on the port once the listener is dropped
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+
+    isolated(&home)
+        .env("I1_SHODAN_KEY", "test-key")
+        .env("I1_SHODAN_URL", format!("http://127.0.0.1:{port}"))
+        .args(["host", "192.0.2.1"])
+        .assert()
+        .code(i32::from(exit::PROVIDER));
+
+    // A dead proxy is named, rather than blamed on the provider
+    let proxy = format!("socks5://127.0.0.1:{port}");
+    isolated(&home)
+        .env("I1_SHODAN_KEY", "test-key")
+        .args(["host", "192.0.2.1", "--proxy", &proxy])
+        .assert()
+        .code(i32::from(exit::PROVIDER))
+        .stderr(predicate::str::contains(format!("through proxy {proxy}")));
+    isolated(&home)
+        .env("I1_SHODAN_KEY", "test-key")
+        .args(["host", "192.0.2.1", "--proxy", "ftp://proxy.example:21"])
+        .assert()
+        .code(i32::from(exit::PROVIDER))
+        .stderr(predicate::str::contains("proxy ftp://proxy.example:21"));
+}
+
+#[test]
+fn test_missing_key() {
+    let home = TempDir::new().unwrap();
+    isolated(&home)
+        .args(["host", "192.0.2.1"])
+        .assert()
+        .code(i32::from(exit::AUTH))
+        .stderr(predicate::str::contains("No API key configured"));
+}
+
+#[test]
+fn test_invalid_input() {
+    let home = TempDir::new().unwrap();
+    isolated(&home)
+        .args(["host", "192.0.2.1", "--watch", "0"])
+        .assert()
+        .code(i32::from(exit::INVALID_INPUT));
+    isolated(&home)
+        .args([
+            "host",
+            "192.0.2.1",
+            "--notify-url",
+            "http://127.0.0.1:9/hook",
+        ])
+        .assert()
+        .code(i32::from(exit::INVALID_INPUT))
+        .stderr(predicate::str::contains("--watch"));
+    isolated(&home)
+        .args(["host", "192.0.2"])
+        .assert()
+        .code(i32::from(exit::INVALID_INPUT))
+        .stderr(predicate::str::contains("invalid IP address"));
+    isolated(&home)
+        .args(["dns", "resolve", "-", "--type", "MX"])
+        .assert()
+        .code(i32::from(exit::INVALID_INPUT));
+    isolated(&home)
+        .args(["scan", "192.0.2.1", "--ports", "99999"])
+        .assert()
+        .code(i32::from(exit::INVALID_INPUT))
+        .stderr(predicate::str::contains("invalid port specification"));
+    isolated(&home)
+        .args(["search", "nginx", "--format-template", "{ip} {nope}"])
+        .assert()
+        .code(i32::from(exit::INVALID_INPUT))
+        .stderr(predicate::str::contains("Valid placeholders: {ip}"));
+    isolated(&home)
+        .args(["no-such-command"])
+        .assert()
+        .code(i32::from(exit::INVALID_INPUT));
+
+    // Help is not an error
+    isolated(&home)
+        .arg("--help")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Exit codes:"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_workspace() {
+    let home = TempDir::new().unwrap();
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/shodan/host/192.0.2.1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "ip_str": "192.0.2.1",
+            "org": "Example Hosting",
+            "ports": [22],
+            "vulns": ["CVE-2024-6387"],
+            "data": [{"port": 22, "product": "OpenSSH"}]
+        })))
+        .expect(2)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/shodan/host/search"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "matches": [
+                { "ip_str": "192.0.2.1", "port": 22 },
+                { "ip_str": "192.0.2.2", "port": 443 },
+            ],
+            "total": 2
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    // Only an existing workspace can be added to
+    against(&home, &server)
+        .args(["host", "192.0.2.1", "--workspace", "case-1"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("i1 workspace new case-1"));
+    isolated(&home)
+        .args(["workspace", "new", "case-1"])
+        .assert()
+        .success();
+
+    // Without the answer cache, only the workspace can spare the repeats
+    let run = |args: &[&str]| {
+        against(&home, &server)
+            .args(args)
+            .args(["-o", "json"])
+            .env("I1_WORKSPACE", "case-1")
+            .assert()
+            .success();
+    };
+    run(&["host", "192.0.2.1", "--no-cache"]);
+    run(&["host", "192.0.2.1", "--no-cache"]);
+    run(&["host", "192.0.2.1", "--refresh"]);
+    run(&["search", "port:22", "--no-cache"]);
+    run(&["search", "port:22", "--no-cache"]);
+
+    let output = isolated(&home)
+        .args(["workspace", "show", "case-1", "-o", "json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let summary: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(
+        summary["targets"],
+        serde_json::json!({ "hosts": 1, "searches": 1, "scans": 0 })
+    );
+    assert_eq!(summary["hosts"], 2);
+    // Two lookups and one filtered search page, which costs a credit
+    assert_eq!(summary["providers"]["shodan"]["calls"], 3);
+    assert_eq!(summary["providers"]["shodan"]["answers"], 3);
+    assert_eq!(summary["credits"], 1);
+    assert_eq!(summary["findings"][0]["ip"], "192.0.2.1");
+    assert_eq!(summary["findings"][0]["vulns"][0], "CVE-2024-6387");
+    assert_eq!(summary["findings"].as_array().unwrap().len(), 1);
+
+    isolated(&home)
+        .args(["workspace", "export", "--format", "markdown"])
+        .env("I1_WORKSPACE", "case-1")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("# Investigation: case-1"))
+        .stdout(predicate::str::contains("| Credits spent | 1 |"))
+        .stdout(predicate::str::contains("| 192.0.2.1 |"))
+        .stdout(predicate::str::contains("`port:22 (page 1)`"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_cache_grep() {
+    let home = TempDir::new().unwrap();
+    let server = MockServer::start().await;
+    for (ip, banner) in [
+        ("192.0.2.1", "SSH-2.0-OpenSSH_7.4p1 WeirdCorp-hardened-2"),
+        ("192.0.2.2", "SSH-2.0-OpenSSH_7.4p1 WeirdCorp-hardened-3"),
+        ("192.0.2.3", "SSH-2.0-OpenSSH_8.9p1 Ubuntu-3ubuntu0.6"),
+    ] {
+        Mock::given(method("GET"))
+            .and(path(format!("/shodan/host/{ip}")))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "ip_str": ip,
+                "ports": [22],
+                "data": [{ "port": 22, "data": banner }]
+            })))
+            .mount(&server)
+            .await;
+        against(&home, &server)
+            .args(["host", ip, "--quiet"])
+            .assert()
+            .success();
+    }
+
+    let grep = |args: &[&str]| -> Vec<serde_json::Value> {
+        let output = isolated(&home)
+            .args(["cache", "grep"])
+            .args(args)
+            .args(["-o", "json"])
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{args:?}");
+        serde_json::from_slice(&output.stdout).unwrap()
+    };
+    let ips = |found: &[serde_json::Value]| -> Vec<String> {
+        let mut ips: Vec<String> = found.iter().map(|f| f["ip"].to_string()).collect();
+        ips.sort();
+        ips
+    };
+
+    assert_eq!(
+        ips(&grep(&["weirdcorp"])),
+        [r#""192.0.2.1""#, r#""192.0.2.2""#]
+    );
+    let like = grep(&["--like", "SSH-2.0-OpenSSH_7.4p1 WeirdCorp-hardened-9"]);
+    assert_eq!(ips(&like), [r#""192.0.2.1""#, r#""192.0.2.2""#]);
+    assert!(like[0]["score"].as_f64().unwrap() > 0.6);
+    let openssh = grep(&["--product", "OpenSSH/8.9p1"]);
+    assert_eq!(ips(&openssh), [r#""192.0.2.3""#]);
+    assert_eq!(openssh[0]["port"], 22);
+
+    isolated(&home)
+        .args(["cache", "grep", "dropbear", "--quiet"])
+        .assert()
+        .code(i32::from(exit::NOT_FOUND));
+    isolated(&home)
+        .args(["cache", "grep", "--like", "ssh", "--threshold", "2"])
+        .assert()
+        .code(i32::from(exit::INVALID_INPUT))
+        .stderr(predicate::str::contains("threshold"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_dns_bulk_shodan() {
+    let home = TempDir::new().unwrap();
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/dns/resolve"))
+        .and(query_param("hostnames", "a.example,nx.example"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "a.example": "192.0.2.1",
+            "nx.example": null
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    // One request for the whole list, every hostname answered
+    against(&home, &server)
+        .args(["dns", "resolve", "-", "-p", "shodan", "-o", "csv"])
+        .write_stdin("a.example\nnx.example\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("a.example,192.0.2.1,A,resolved"))
+        .stdout(predicate::str::contains("nx.example,,,no_address"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_ingest() {
+    let home = TempDir::new().unwrap();
+    let fixture = |name: &str| {
+        format!(
+            "{}/../i1-recon/tests/fixtures/{name}",
+            env!("CARGO_MANIFEST_DIR")
+        )
+    };
+
+    // The malformed line is skipped with a warning, the rest still listed
+    isolated(&home)
+        .args(["ingest", &fixture("conn.log"), "-o", "csv"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("192.0.2.10,2,443"))
+        .stdout(predicate::str::contains("198.51.100.7,2,22;3389"))
+        .stderr(predicate::str::contains("first at line 15"));
+
+    isolated(&home)
+        .args(["ingest", &fixture("conn.log"), "--min-connections", "3"])
+        .assert()
+        .code(i32::from(exit::NOT_FOUND));
+
+    // Only the endpoint the provider knows gets host data
+    let server = answering(
+        "/shodan/host/192.0.2.10",
+        ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "ip_str": "192.0.2.10",
+            "org": "Example Hosting"
+        })),
+    )
+    .await;
+    against(&home, &server)
+        .args(["ingest", &fixture("capture.pcap"), "--enrich"])
+        .args(["--min-connections", "2", "-o", "csv"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("192.0.2.10,2,443,Example Hosting"))
+        .stdout(predicate::str::contains("8.8.8.8").not());
+}
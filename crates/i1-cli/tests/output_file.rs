@@ -0,0 +1,109 @@
+//! `--output-file`: format inference, appending, and files free of colors.
+
+use assert_cmd::Command;
+use tempfile::TempDir;
+
+/// `i1` with an empty home, forced to color its output.
+fn i1(home: &TempDir) -> Command {
+    let mut cmd = Command::cargo_bin("i1").unwrap();
+    cmd.env("HOME", home.path())
+        .env("XDG_CONFIG_HOME", home.path())
+        .env("XDG_CACHE_HOME", home.path())
+        .env("CLICOLOR_FORCE", "1")
+        .env_remove("I1_OUTPUT")
+        .env_remove("RUST_BACKTRACE");
+    cmd
+}
+
+fn read(home: &TempDir, name: &str) -> String {
+    std::fs::read_to_string(home.path().join(name)).unwrap()
+}
+
+#[test]
+fn test_format_from_extension() {
+    let home = TempDir::new().unwrap();
+    for name in ["stats.json", "stats.yaml", "stats.md"] {
+        i1(&home)
+            .args(["cache", "stats", "--output-file"])
+            .arg(home.path().join(name))
+            .assert()
+            .success()
+            .stdout("");
+    }
+
+    let json: serde_json::Value = serde_json::from_str(&read(&home, "stats.json")).unwrap();
+    assert_eq!(json["entries"], 0);
+    assert!(read(&home, "stats.yaml").contains("entries: 0"));
+
+    let md = read(&home, "stats.md");
+    assert!(md.starts_with("```text\nCache:\n"), "{md}");
+    assert!(md.ends_with("```\n"), "{md}");
+
+    // An explicit --output wins over the extension
+    i1(&home)
+        .args(["cache", "stats", "-o", "yaml", "--output-file"])
+        .arg(home.path().join("explicit.json"))
+        .assert()
+        .success();
+    assert!(read(&home, "explicit.json").contains("entries: 0"));
+}
+
+#[test]
+fn test_no_ansi_in_file() {
+    let home = TempDir::new().unwrap();
+    // Colors are forced, and still never reach the file
+    let colored = i1(&home).args(["cache", "stats"]).output().unwrap();
+    assert!(String::from_utf8_lossy(&colored.stdout).contains('\x1b'));
+
+    i1(&home)
+        .args(["cache", "stats", "--output-file"])
+        .arg(home.path().join("stats.txt"))
+        .assert()
+        .success();
+    let text = read(&home, "stats.txt");
+    assert!(text.contains("Cache:"), "{text}");
+    assert!(!text.contains('\x1b'), "{text:?}");
+}
+
+#[test]
+fn test_append() {
+    let home = TempDir::new().unwrap();
+    let path = home.path().join("cleared.ndjson");
+    for _ in 0..2 {
+        i1(&home)
+            .args(["cache", "clear", "--append", "--output-file"])
+            .arg(&path)
+            .assert()
+            .success();
+    }
+    assert_eq!(
+        read(&home, "cleared.ndjson"),
+        "{\"removed\":0}\n{\"removed\":0}\n"
+    );
+
+    // Without --append the file is replaced
+    i1(&home)
+        .args(["cache", "clear", "--output-file"])
+        .arg(&path)
+        .assert()
+        .success();
+    assert_eq!(read(&home, "cleared.ndjson"), "{\"removed\":0}\n");
+}
+
+#[test]
+fn test_failure_keeps_old_file() {
+    let home = TempDir::new().unwrap();
+    let path = home.path().join("hosts.json");
+    std::fs::write(&path, "previous").unwrap();
+
+    // No API key: the lookup fails and the old file stays as it was
+    i1(&home)
+        .env_remove("I1_SHODAN_KEY")
+        .env_remove("SHODAN_API_KEY")
+        .args(["host", "192.0.2.1", "--output-file"])
+        .arg(&path)
+        .assert()
+        .failure();
+    assert_eq!(read(&home, "hosts.json"), "previous");
+    assert_eq!(std::fs::read_dir(home.path()).unwrap().count(), 1);
+}
@@ -0,0 +1,248 @@
+//! `--provider` across commands, against a mock Shodan API.
+//!
+//! Shodan is the only provider in the default build, so `all` means "every
+//! configured provider" with one of them, and the others are not built in.
+
+use assert_cmd::Command;
+use i1_cli::cli::exit;
+use predicates::prelude::*;
+use tempfile::TempDir;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// Commands that talk to a provider, with arguments.
+const COMMANDS: &[&[&str]] = &[
+    &["host", "192.0.2.1"],
+    &["search", "nginx"],
+    &["count", "nginx"],
+    &["dns", "resolve", "example.com"],
+    &["dns", "reverse", "192.0.2.1"],
+    &["domain", "example.com"],
+    &["vuln", "host", "192.0.2.1"],
+    &["alert", "list"],
+];
+
+/// Commands that take one provider and refuse `--provider all`.
+const SINGLE: &[&str] = &["vuln", "alert"];
+
+/// `i1` with a Shodan key for `server` and nothing else configured.
+fn i1(home: &TempDir, server: &MockServer) -> Command {
+    let mut cmd = Command::cargo_bin("i1").unwrap();
+    cmd.env("HOME", home.path())
+        .env("XDG_CONFIG_HOME", home.path())
+        .env("XDG_CACHE_HOME", home.path())
+        .env("I1_SHODAN_KEY", "test-key")
+        .env("I1_SHODAN_URL", server.uri())
+        .env_remove("RUST_BACKTRACE");
+    for var in [
+        "SHODAN_API_KEY",
+        "I1_CENSYS_ID",
+        "I1_CENSYS_SECRET",
+        "I1_CRIMINALIP_KEY",
+        "I1_TOKEN",
+        "I1_OUTPUT",
+        "I1_PROVIDER",
+        "I1_PROFILE",
+    ] {
+        cmd.env_remove(var);
+    }
+    cmd
+}
+
+/// A Shodan that knows one host, on every endpoint the commands use.
+async fn shodan() -> MockServer {
+    let server = MockServer::start().await;
+    let routes = [
+        (
+            "/shodan/host/192.0.2.1",
+            serde_json::json!({
+                "ip_str": "192.0.2.1",
+                "ports": [22],
+                "data": [{"port": 22, "product": "OpenSSH"}]
+            }),
+        ),
+        (
+            "/shodan/host/search",
+            serde_json::json!({
+                "total": 1,
+                "matches": [{"ip_str": "192.0.2.1", "port": 80}]
+            }),
+        ),
+        ("/shodan/host/count", serde_json::json!({"total": 42})),
+        (
+            "/dns/resolve",
+            serde_json::json!({"example.com": "192.0.2.1"}),
+        ),
+        (
+            "/dns/reverse",
+            serde_json::json!({"192.0.2.1": ["host.example.com"]}),
+        ),
+        (
+            "/dns/domain/example.com",
+            serde_json::json!({
+                "domain": "example.com",
+                "subdomains": ["www"],
+                "data": [{"type": "A", "subdomain": "www", "value": "192.0.2.1"}]
+            }),
+        ),
+        ("/shodan/alert/info", serde_json::json!([])),
+    ];
+    for (route, body) in routes {
+        Mock::given(method("GET"))
+            .and(path(route))
+            .respond_with(ResponseTemplate::new(200).set_body_json(body))
+            .mount(&server)
+            .await;
+    }
+    server
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_named_providers() {
+    let home = TempDir::new().unwrap();
+    let server = shodan().await;
+    for command in COMMANDS {
+        for provider in ["auto", "shodan", "Shodan"] {
+            i1(&home, &server)
+                .args(["-o", "json", "--no-cache", "--provider", provider])
+                .args(*command)
+                .assert()
+                .success();
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_provider_all() {
+    let home = TempDir::new().unwrap();
+    let server = shodan().await;
+    for command in COMMANDS {
+        let assert = i1(&home, &server)
+            .args(["-o", "json", "--no-cache", "--provider", "all"])
+            .args(*command)
+            .assert();
+        if SINGLE.contains(&command[0]) {
+            assert
+                .code(i32::from(exit::INVALID_INPUT))
+                .stderr(predicate::str::contains("--provider all is not supported"))
+                .stderr(predicate::str::contains("Pick one with --provider: shodan"));
+        } else {
+            assert.success();
+        }
+    }
+
+    // Merged host, every provider's search and count
+    i1(&home, &server)
+        .args([
+            "-o",
+            "json",
+            "--no-cache",
+            "--provider",
+            "all",
+            "host",
+            "192.0.2.1",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"merged\""));
+    i1(&home, &server)
+        .args(["-o", "json", "--provider", "all", "count", "nginx"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"counts\":{\"shodan\":42}"));
+
+    // Batches and paging stay with one provider, and say so
+    let batch = home.path().join("ips.txt");
+    std::fs::write(&batch, "192.0.2.1\n").unwrap();
+    i1(&home, &server)
+        .args(["--provider", "all", "host", "--file"])
+        .arg(&batch)
+        .assert()
+        .code(i32::from(exit::INVALID_INPUT))
+        .stderr(predicate::str::contains("Batch and CIDR lookups"));
+    i1(&home, &server)
+        .args(["--provider", "all", "search", "nginx", "--limit", "10"])
+        .assert()
+        .code(i32::from(exit::INVALID_INPUT))
+        .stderr(predicate::str::contains("Paged and watched searches"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_unknown_provider() {
+    let home = TempDir::new().unwrap();
+    let server = shodan().await;
+    for command in COMMANDS {
+        i1(&home, &server)
+            .args(["--provider", "shodam"])
+            .args(*command)
+            .assert()
+            .code(i32::from(exit::INVALID_INPUT))
+            .stderr(predicate::str::contains("Unknown provider 'shodam'"))
+            .stderr(predicate::str::contains("Configured providers: shodan"));
+    }
+    // Rejected before anything is asked
+    assert!(server.received_requests().await.unwrap().is_empty());
+
+    // From the environment too, and `config` still runs to fix it
+    i1(&home, &server)
+        .env("I1_PROVIDER", "shodam")
+        .args(["count", "nginx"])
+        .assert()
+        .code(i32::from(exit::INVALID_INPUT));
+    i1(&home, &server)
+        .env("I1_PROVIDER", "shodam")
+        .args(["config", "show"])
+        .assert()
+        .success();
+    i1(&home, &server)
+        .args(["config", "set", "provider", "shodam"])
+        .assert()
+        .code(i32::from(exit::INVALID_INPUT));
+}
+
+#[cfg(not(feature = "censys"))]
+#[tokio::test(flavor = "multi_thread")]
+async fn test_provider_not_built() {
+    let home = TempDir::new().unwrap();
+    let server = shodan().await;
+    i1(&home, &server)
+        .args(["--provider", "censys", "host", "192.0.2.1"])
+        .assert()
+        .code(i32::from(exit::INVALID_INPUT))
+        .stderr(predicate::str::contains("not built into this i1"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_stix_output() {
+    let home = TempDir::new().unwrap();
+    let server = shodan().await;
+    for command in [&["host", "192.0.2.1"][..], &["search", "nginx"]] {
+        let output = i1(&home, &server)
+            .args(["-o", "stix", "--no-cache"])
+            .args(command)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let bundle: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(bundle["type"], "bundle");
+        let objects = bundle["objects"].as_array().unwrap();
+        assert!(objects
+            .iter()
+            .any(|o| o["type"] == "ipv4-addr" && o["value"] == "192.0.2.1"));
+        assert!(objects.iter().any(|o| o["type"] == "observed-data"));
+    }
+
+    // Output with no hosts to bundle is refused rather than printed as JSON
+    for command in [
+        &["count", "nginx"][..],
+        &["search", "nginx", "--group-by", "port"],
+    ] {
+        i1(&home, &server)
+            .args(["-o", "stix"])
+            .args(command)
+            .assert()
+            .code(i32::from(exit::INVALID_INPUT))
+            .stdout(predicate::str::is_empty())
+            .stderr(predicate::str::contains("has no STIX output"));
+    }
+}
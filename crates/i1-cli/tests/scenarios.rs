@@ -0,0 +1,596 @@
+//! Whole-command scenarios against mock providers, output pinned by
+//! snapshots in `tests/snapshots`. See `tests/common` for adding one.
+
+mod common;
+
+use common::{answer, answering, Harness, Run};
+use insta::assert_snapshot;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// The formats every provider command prints.
+const FORMATS: &[&str] = &["pretty", "json", "csv", "yaml"];
+
+/// Runs joined into one snapshot, each under a `## name` heading.
+#[derive(Default)]
+struct Cases(Vec<String>);
+
+impl Cases {
+    fn add(&mut self, name: &str, run: &Run) {
+        self.0.push(format!("## {name}\n{}", run.snapshot()));
+    }
+
+    fn snapshot(&self) -> String {
+        self.0.join("\n")
+    }
+}
+
+/// Paths `server` was asked for, sorted.
+async fn paths(server: &MockServer) -> Vec<String> {
+    let requests = server.received_requests().await.unwrap();
+    let mut paths: Vec<String> = requests.iter().map(|r| r.url.path().to_string()).collect();
+    paths.sort_unstable();
+    paths
+}
+
+/// Snapshot `args` once per output format, as `<name>_<format>`.
+fn each_format(harness: &Harness, name: &str, args: &[&str]) {
+    for format in FORMATS {
+        let run = harness.run(&[args, &["-o", format]].concat());
+        assert_snapshot!(format!("{name}_{format}"), run.snapshot());
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_host() {
+    let harness = Harness::start().await;
+    each_format(&harness, "host", &["host", "192.0.2.1"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_host_batch() {
+    let harness = Harness::start().await;
+    // 198.51.100.9 is unknown to the mock, so it comes back not found
+    for format in ["json", "csv"] {
+        let run = harness.run_with(
+            harness
+                .i1()
+                .args(["--no-cache", "host", "-", "-o", format])
+                .write_stdin("192.0.2.1\n198.51.100.9\n"),
+        );
+        let name = if format == "json" { "ndjson" } else { format };
+        assert_snapshot!(format!("host_batch_{name}"), run.snapshot());
+    }
+}
+
+/// Blanks and comments in a target list are skipped and repeats looked up
+/// once, from a file or from stdin.
+#[tokio::test(flavor = "multi_thread")]
+async fn test_host_batch_input() {
+    let harness = Harness::start().await;
+    let targets = harness.home.path().join("targets.txt");
+    let input =
+        "# seen this week\n\n192.0.2.1   # first\n  \t\n  198.51.100.9\n192.0.2.1\n#192.0.2.7\n";
+    std::fs::write(&targets, input).unwrap();
+    let mut cases = Cases::default();
+
+    let run = harness.run_with(
+        harness
+            .i1()
+            .args(["--no-cache", "host", "-o", "csv", "--file"])
+            .arg(&targets),
+    );
+    cases.add("file", &run);
+    let run = harness.run_with(
+        harness
+            .i1()
+            .args(["--no-cache", "host", "-", "-o", "csv"])
+            .write_stdin(input),
+    );
+    cases.add("stdin", &run);
+    assert_eq!(
+        paths(&harness.shodan).await,
+        [
+            "/shodan/host/192.0.2.1",
+            "/shodan/host/192.0.2.1",
+            "/shodan/host/198.51.100.9",
+            "/shodan/host/198.51.100.9",
+        ]
+    );
+
+    // Nothing but comments is no targets at all
+    let run = harness.run_with(
+        harness
+            .i1()
+            .args(["host", "-"])
+            .write_stdin("# nothing yet\n\n"),
+    );
+    cases.add("only comments", &run);
+
+    assert_snapshot!(cases.snapshot());
+}
+
+/// Blocks past the cap are refused before a single lookup; small ones are
+/// looked up address by address.
+#[tokio::test(flavor = "multi_thread")]
+async fn test_host_cidr() {
+    let harness = Harness::start().await;
+    let mut cases = Cases::default();
+
+    for net in ["0.0.0.0/0", "::/0", "10.0.0.0/16"] {
+        cases.add(net, &harness.run(&["host", net]));
+    }
+    // --force raises the cap, but not without limit
+    for net in ["0.0.0.0/0", "10.0.0.0/8"] {
+        let run = harness.run(&["host", net, "--force"]);
+        cases.add(&format!("{net} --force"), &run);
+    }
+    assert!(harness.shodan.received_requests().await.unwrap().is_empty());
+
+    // A /30 is its two usable addresses; the one the mock doesn't know is
+    // counted as having no data
+    let run = harness.run(&["host", "192.0.2.0/30", "-o", "csv"]);
+    cases.add("192.0.2.0/30", &run);
+    assert_eq!(
+        paths(&harness.shodan).await,
+        ["/shodan/host/192.0.2.1", "/shodan/host/192.0.2.2"]
+    );
+
+    assert_snapshot!(cases.snapshot());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_search() {
+    let harness = Harness::start().await;
+    each_format(&harness, "search", &["search", "nginx"]);
+}
+
+/// `--max-credits` ends paging once the next page would go over it.
+#[tokio::test(flavor = "multi_thread")]
+async fn test_search_credit_guard() {
+    let harness = Harness::start().await;
+    let shodan = MockServer::start().await;
+    // Every page has a host, so only the credit guard ends the paging
+    Mock::given(method("GET"))
+        .and(path("/shodan/host/search"))
+        .respond_with(|request: &wiremock::Request| {
+            let page = request
+                .url
+                .query_pairs()
+                .find(|(key, _)| key == "page")
+                .map_or(1, |(_, page)| page.parse::<u8>().unwrap());
+            ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "matches": [{ "ip_str": format!("10.0.{page}.1"), "port": 80 }],
+                "total": 100_000
+            }))
+        })
+        .mount(&shodan)
+        .await;
+    let search = |args: &[&str]| {
+        harness.run_with(
+            harness
+                .i1()
+                .env("I1_SHODAN_URL", shodan.uri())
+                .args(["--no-cache", "search"])
+                .args(args),
+        )
+    };
+    // The pages asked for so far, in order
+    let pages = || async {
+        let requests = shodan.received_requests().await.unwrap();
+        requests
+            .iter()
+            .filter_map(|r| r.url.query_pairs().find(|(key, _)| key == "page"))
+            .map(|(_, page)| page.into_owned())
+            .collect::<Vec<_>>()
+    };
+    let mut cases = Cases::default();
+
+    // The first page of an unfiltered search is free, so two credits buy three
+    let run = search(&["nginx", "--all-pages", "--max-credits", "2"]);
+    cases.add("unfiltered", &run);
+    assert_eq!(pages().await, ["1", "2", "3"]);
+
+    // With a filter every page costs a credit, and --limit alone still
+    // stops at the budget
+    let run = search(&[
+        "port:80",
+        "--limit",
+        "1000",
+        "--max-credits",
+        "2",
+        "-o",
+        "csv",
+    ]);
+    cases.add("filtered", &run);
+    assert_eq!(pages().await, ["1", "2", "3", "1", "2"]);
+
+    // Not even one page fits the budget: nothing is fetched
+    let run = search(&["port:80", "--all-pages", "--max-credits", "0"]);
+    cases.add("no budget", &run);
+    assert_eq!(pages().await.len(), 5);
+
+    assert_snapshot!(cases.snapshot());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_count() {
+    let harness = Harness::start().await;
+    each_format(&harness, "count", &["count", "nginx"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_dns() {
+    let harness = Harness::start().await;
+    each_format(&harness, "dns_resolve", &["dns", "resolve", "example.com"]);
+    each_format(&harness, "dns_reverse", &["dns", "reverse", "192.0.2.1"]);
+
+    // In bulk every hostname gets a row, one JSON object per line
+    for format in ["json", "csv"] {
+        let run = harness.run_with(
+            harness
+                .i1()
+                .args(["dns", "resolve", "-", "-p", "shodan", "-o", format])
+                .write_stdin("example.com\nwww.example.com\nnx.example.com\n"),
+        );
+        let name = if format == "json" { "ndjson" } else { format };
+        assert_snapshot!(format!("dns_bulk_{name}"), run.snapshot());
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_config_show() {
+    let harness = Harness::start().await;
+    for format in ["pretty", "json"] {
+        let run = harness.run(&["config", "show", "-o", format]);
+        assert_snapshot!(format!("config_show_{format}"), run.snapshot());
+    }
+
+    // Saved endpoints show up, and the environment still wins
+    harness
+        .i1()
+        .args(["config", "set", "criminalip-url", "https://cip.example/v1/"])
+        .assert()
+        .success();
+    harness
+        .i1()
+        .env_remove("I1_CRIMINALIP_URL")
+        .env("I1_SHODAN_URL", "http://127.0.0.1:9")
+        .args(["config", "show"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(
+            "criminalip_url:     https://cip.example/v1 (file)",
+        ))
+        .stdout(predicates::str::contains(
+            "shodan_url:         http://127.0.0.1:9 (env I1_SHODAN_URL)",
+        ));
+    harness
+        .i1()
+        .args(["config", "set", "shodan-url", "ftp://example.com"])
+        .assert()
+        .code(1)
+        .stderr(predicates::str::contains("must be an http(s) URL"));
+}
+
+/// `config doctor` offline: no keys means no network checks. The native
+/// provider needs no key, so it would check the network.
+#[cfg(not(feature = "native"))]
+#[tokio::test(flavor = "multi_thread")]
+async fn test_config_doctor() {
+    let harness = Harness::start().await;
+    let doctor = |format: &str, env: &[(&str, &str)]| {
+        let mut cmd = common::isolated(&harness.home);
+        cmd.envs(env.iter().copied())
+            .args(["config", "doctor", "-o", format]);
+        harness.run_with(&mut cmd)
+    };
+    let mut cases = Vec::new();
+
+    // Warnings alone pass
+    let run = doctor("pretty", &[]);
+    assert_eq!(run.code, Some(0));
+    cases.push(format!("## nothing configured\n{}", run.snapshot()));
+
+    let run = doctor("json", &[("I1_CENSYS_ID", "censys-test-id")]);
+    assert_eq!(run.code, Some(1));
+    cases.push(format!("## censys id without secret\n{}", run.snapshot()));
+
+    let config = harness.home.path().join("i1").join("config.toml");
+    std::fs::create_dir_all(config.parent().unwrap()).unwrap();
+    std::fs::write(&config, "shodan_key = [unclosed\n").unwrap();
+    let run = doctor("pretty", &[]);
+    assert_eq!(run.code, Some(1));
+    cases.push(format!("## unreadable config file\n{}", run.snapshot()));
+
+    assert_snapshot!(cases.join("\n"));
+}
+
+/// `alert create` checks its triggers before making the alert, and `alert
+/// list` explains a plan without network monitoring.
+#[tokio::test(flavor = "multi_thread")]
+async fn test_alert() {
+    let harness = Harness::start().await;
+    let shodan = &harness.shodan;
+    answer(
+        shodan,
+        "/shodan/alert/triggers",
+        ResponseTemplate::new(200).set_body_json(serde_json::json!([
+            { "name": "new_service", "description": "New service discovered" },
+        ])),
+    )
+    .await;
+    answer(shodan, "/shodan/alert/info", ResponseTemplate::new(403)).await;
+    Mock::given(method("POST"))
+        .and(path("/shodan/alert"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "id": "ABC123",
+            "name": "office",
+            "filters": { "ip": ["192.0.2.0/24"] },
+            "size": 256
+        })))
+        .mount(shodan)
+        .await;
+    Mock::given(method("PUT"))
+        .and(path("/shodan/alert/ABC123/trigger/new_service"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(serde_json::json!({ "success": true })),
+        )
+        .mount(shodan)
+        .await;
+    let create = |trigger: &str| {
+        harness.run(&[
+            "alert",
+            "create",
+            "office",
+            "--cidr",
+            "192.0.2.0/24",
+            "--cidr",
+            "198.51.100.7",
+            "--trigger",
+            trigger,
+            "-o",
+            "csv",
+        ])
+    };
+    let mut cases = Cases::default();
+
+    // A mistyped trigger is caught before the alert is made
+    cases.add("unknown trigger", &create("new_servce"));
+    cases.add("create", &create("new_service"));
+
+    let requests = shodan.received_requests().await.unwrap();
+    let calls: Vec<String> = requests
+        .iter()
+        .map(|r| format!("{} {}", r.method, r.url.path()))
+        .collect();
+    assert_eq!(
+        calls,
+        [
+            "GET /shodan/alert/triggers",
+            "GET /shodan/alert/triggers",
+            "POST /shodan/alert",
+            "PUT /shodan/alert/ABC123/trigger/new_service",
+        ]
+    );
+    // A bare address is monitored as a single-address network
+    let body: serde_json::Value = requests[2].body_json().unwrap();
+    assert_eq!(
+        body["filters"]["ip"],
+        serde_json::json!(["192.0.2.0/24", "198.51.100.7/32"])
+    );
+
+    cases.add("no monitoring", &harness.run(&["alert", "list"]));
+    assert_snapshot!(cases.snapshot());
+}
+
+/// `credits` reports each provider's quota, and a failed check without
+/// failing.
+#[tokio::test(flavor = "multi_thread")]
+async fn test_credits() {
+    let harness = Harness::start().await;
+    answer(
+        &harness.shodan,
+        "/api-info",
+        ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "plan": "dev",
+            "query_credits": 97,
+            "scan_credits": 12
+        })),
+    )
+    .await;
+    let rejected = answering("/api-info", ResponseTemplate::new(401)).await;
+    // The reset date follows the calendar and the latency the machine
+    let redact = |run: Run| {
+        run.redact(r"\d{4}-\d{2}-\d{2}T[0-9:+]+", "[RESETS]")
+            .redact(r"(?m),\d+,([^,\n]*)$", ",[MS],$1")
+    };
+    let mut cases = Cases::default();
+
+    let run = harness.run(&["credits", "-p", "shodan", "-o", "csv"]);
+    cases.add("quota", &redact(run));
+    let run = harness.run_with(harness.i1().env("I1_SHODAN_URL", rejected.uri()).args([
+        "--no-cache",
+        "credits",
+        "-p",
+        "shodan",
+        "-o",
+        "csv",
+    ]));
+    cases.add("rejected key", &redact(run));
+
+    assert_snapshot!(cases.snapshot());
+}
+
+/// `myip` against mock address endpoints: this machine has IPv4 only.
+#[tokio::test(flavor = "multi_thread")]
+async fn test_myip() {
+    let harness = Harness::start().await;
+    let ipv4 = answering(
+        "/",
+        ResponseTemplate::new(200).set_body_string("192.0.2.1\n"),
+    )
+    .await;
+    let ipv6 = answering("/", ResponseTemplate::new(503)).await;
+    let myip = |ipv6: &MockServer, args: &[&str]| {
+        harness.run_with(
+            harness
+                .i1()
+                .env("I1_MYIP_URL", ipv4.uri())
+                .env("I1_MYIP6_URL", ipv6.uri())
+                .arg("myip")
+                .args(args),
+        )
+    };
+    let mut cases = Cases::default();
+
+    cases.add("csv", &myip(&ipv6, &["-o", "csv"]));
+    // A family without connectivity is noted, unless it was the only one asked for
+    cases.add("all", &myip(&ipv6, &["--all"]));
+    let run = myip(&ipv6, &["--ipv6"]).redact(&regex::escape(&ipv6.uri()), "[MYIP6]");
+    cases.add("ipv6 only", &run);
+    // An answer in the wrong family is not taken for the address
+    cases.add("ipv6 answered with ipv4", &myip(&ipv4, &["--ipv6"]));
+    assert_snapshot!(cases.snapshot());
+
+    // Enrichment fills in what the provider knows; WHOIS would go to the
+    // real registries, so only without it
+    #[cfg(not(feature = "recon"))]
+    assert_snapshot!(
+        "myip_enrich",
+        myip(&ipv6, &["--enrich", "-o", "json"]).snapshot()
+    );
+}
+
+/// Enrichment asks providers only about what the policy lets them see:
+/// private space by default, and nothing under `policy.deny-external`.
+#[tokio::test(flavor = "multi_thread")]
+async fn test_enrich_policy() {
+    let harness = Harness::start().await;
+    let mut cases = Cases::default();
+
+    // An open port on loopback, which is withheld just like 10.0.0.0/8
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port().to_string();
+    let ports = format!("--ports={port}");
+    let run = harness
+        .run(&["scan", "127.0.0.1", &ports, "--enrich", "-o", "csv"])
+        .redact(&format!(r"\b{port}\b"), "[PORT]");
+    cases.add("scan --enrich", &run);
+    assert!(harness.shodan.received_requests().await.unwrap().is_empty());
+
+    // The log's endpoints in the denied block are listed without data
+    harness.run(&["config", "set", "policy.deny-external", "198.51.100.0/24"]);
+    let log = format!(
+        "{}/../i1-recon/tests/fixtures/conn.log",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let run = harness
+        .run(&["ingest", &log, "--enrich", "-o", "csv"])
+        .redact(&regex::escape(&log), "[LOG]");
+    cases.add("ingest --enrich", &run);
+    assert_eq!(
+        paths(&harness.shodan).await,
+        ["/shodan/host/192.0.2.10", "/shodan/host/8.8.8.8"]
+    );
+
+    assert_snapshot!(cases.snapshot());
+}
+
+#[cfg(feature = "criminalip")]
+#[tokio::test(flavor = "multi_thread")]
+async fn test_criminalip() {
+    let harness = Harness::start().await;
+    let cip = ["-p", "criminalip"];
+    each_format(
+        &harness,
+        "criminalip_host",
+        &[&cip[..], &["host", "192.0.2.1"]].concat(),
+    );
+    each_format(
+        &harness,
+        "criminalip_search",
+        &[&cip[..], &["search", "nginx"]].concat(),
+    );
+    each_format(
+        &harness,
+        "criminalip_count",
+        &[&cip[..], &["count", "nginx"]].concat(),
+    );
+
+    // Nothing went to Shodan
+    assert!(harness.shodan.received_requests().await.unwrap().is_empty());
+}
+
+/// The documented exit codes, with what each failure says.
+#[tokio::test(flavor = "multi_thread")]
+async fn test_exit_codes() {
+    let harness = Harness::start().await;
+    let mut cases = Vec::new();
+    let mut case = |name: &str, run: common::Run| {
+        cases.push(format!("## {name}\n{}", run.snapshot()));
+    };
+
+    case("found", harness.run(&["host", "192.0.2.1", "--quiet"]));
+    case("host not found", harness.run(&["host", "198.51.100.9"]));
+    case("invalid ip", harness.run(&["host", "192.0.2.300"]));
+    case(
+        "unknown format",
+        harness.run(&["-o", "xml", "count", "nginx"]),
+    );
+
+    let run = harness.run_with(
+        harness
+            .i1()
+            .env_remove("I1_SHODAN_KEY")
+            .env_remove("I1_CRIMINALIP_KEY")
+            .args(["host", "192.0.2.1"]),
+    );
+    case("no key", run);
+
+    // What Shodan answers decides the code
+    for (status, name) in [
+        (401, "rejected key"),
+        (429, "rate limited"),
+        (402, "out of credits"),
+        (503, "provider down"),
+    ] {
+        let shodan = MockServer::start().await;
+        answer(
+            &shodan,
+            "/shodan/host/192.0.2.1",
+            ResponseTemplate::new(status),
+        )
+        .await;
+        let run = harness.run_with(harness.i1().env("I1_SHODAN_URL", shodan.uri()).args([
+            "--no-cache",
+            "host",
+            "192.0.2.1",
+        ]));
+        case(name, run);
+    }
+
+    let empty = MockServer::start().await;
+    answer(
+        &empty,
+        "/shodan/host/search",
+        ResponseTemplate::new(200).set_body_json(serde_json::json!({"total": 0, "matches": []})),
+    )
+    .await;
+    let run = harness.run_with(harness.i1().env("I1_SHODAN_URL", empty.uri()).args([
+        "--no-cache",
+        "search",
+        "nothing",
+    ]));
+    case("no results", run);
+
+    assert_snapshot!(cases.join("\n"));
+
+    // Which providers it lists depends on the build
+    harness
+        .i1()
+        .args(["-p", "shodam", "count", "nginx"])
+        .assert()
+        .code(5)
+        .stderr(predicates::str::contains("Unknown provider 'shodam'"));
+}
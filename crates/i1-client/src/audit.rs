@@ -0,0 +1,541 @@
+//! An audit trail of what was queried and changed, and when.
+//!
+//! An [`AuditLog`] appends one JSON object per line: a `call` entry for
+//! each provider call it observes (see [`CallObserver`]) and an `action`
+//! entry for each change the caller records itself, such as a firewall
+//! ban. Each line is written with a single append, so concurrent writers
+//! never interleave, and the file is rotated to `<file>.1`, `<file>.2`, ...
+//! once it passes a size limit.
+//!
+//! Observed calls are written by a background thread, in the order they
+//! were made, so a provider call never waits on the disk. [`AuditLog::flush`]
+//! waits for them to be written; dropping the last clone of a log does too.
+//!
+//! Entries never carry credentials: API keys in error messages are masked,
+//! and targets can be stored as SHA-256 hashes instead of in the clear.
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! use i1_client::{AuditLog, I1Client};
+//!
+//! let audit = AuditLog::new("/var/log/i1/audit.jsonl").hash_targets(true);
+//! let client = I1Client::builder()
+//!     .with_provider(shodan)
+//!     .observer(audit.clone())
+//!     .build();
+//! ```
+
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex, OnceLock, PoisonError};
+use std::thread::JoinHandle;
+
+use chrono::{DateTime, Utc};
+use i1_core::{I1Error, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::observe::{Call, CallObserver};
+
+/// Size a log file may reach before it is rotated
+pub const DEFAULT_MAX_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Rotated files kept besides the current one
+pub const DEFAULT_KEEP: usize = 5;
+
+/// Query parameters whose values are masked in error messages
+const SECRET_PARAMS: &[&str] = &["key=", "api_key=", "apikey=", "token=", "secret="];
+
+/// What an [`AuditEntry`] records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditKind {
+    /// A provider API call
+    Call,
+    /// A change made by the caller, such as a ban
+    Action,
+}
+
+/// One line of the audit log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub at: DateTime<Utc>,
+    pub kind: AuditKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    /// `lookup`, `search`, ... for calls; `ban`, `geoblock`, ... for actions
+    pub operation: String,
+    /// What was queried or changed, or its `sha256:` hash
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub targets: Vec<String>,
+    /// Whether it succeeded
+    pub ok: bool,
+    /// Why it failed, e.g. `rate_limited: rate limit exceeded, ...`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+    /// Query credits it is estimated to have spent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credits: Option<u32>,
+}
+
+impl AuditEntry {
+    /// An entry for a provider call.
+    pub fn call(call: &Call<'_>) -> Self {
+        Self {
+            at: Utc::now(),
+            kind: AuditKind::Call,
+            provider: Some(call.provider.to_string()),
+            operation: call.operation.to_string(),
+            targets: if call.target.is_empty() {
+                Vec::new()
+            } else {
+                vec![call.target.to_string()]
+            },
+            ok: call.error.is_none(),
+            error: call.error.map(|e| format!("{}: {e}", e.kind())),
+            latency_ms: Some(u64::try_from(call.elapsed.as_millis()).unwrap_or(u64::MAX)),
+            credits: Some(call.credits),
+        }
+    }
+
+    /// An entry for a successful change to `targets`.
+    pub fn action(operation: impl Into<String>, targets: Vec<String>) -> Self {
+        Self {
+            at: Utc::now(),
+            kind: AuditKind::Action,
+            provider: None,
+            operation: operation.into(),
+            targets,
+            ok: true,
+            error: None,
+            latency_ms: None,
+            credits: None,
+        }
+    }
+}
+
+/// An append-only JSON Lines audit log. Clones share the file.
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+    path: PathBuf,
+    max_bytes: u64,
+    keep: usize,
+    hash_targets: bool,
+    /// Held while rotating and writing, so lines in this process stay whole
+    lock: Arc<Mutex<()>>,
+    /// Writes observed calls; started by the first one
+    writer: Arc<OnceLock<Writer>>,
+}
+
+impl AuditLog {
+    /// A log at `path`, created with its directory on the first write.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            max_bytes: DEFAULT_MAX_BYTES,
+            keep: DEFAULT_KEEP,
+            hash_targets: false,
+            lock: Arc::new(Mutex::new(())),
+            writer: Arc::default(),
+        }
+    }
+
+    /// Rotate the file once it would grow past `bytes`.
+    #[must_use]
+    pub const fn max_bytes(mut self, bytes: u64) -> Self {
+        self.max_bytes = bytes;
+        self
+    }
+
+    /// Keep this many rotated files; older ones are deleted.
+    #[must_use]
+    pub const fn keep(mut self, files: usize) -> Self {
+        self.keep = files;
+        self
+    }
+
+    /// Store targets as `sha256:<hex>` rather than as given.
+    #[must_use]
+    pub const fn hash_targets(mut self, hash: bool) -> Self {
+        self.hash_targets = hash;
+        self
+    }
+
+    /// The current log file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append `entry`, masking secrets and hashing targets as configured.
+    ///
+    /// Written before returning, after any calls observed before it.
+    pub fn record(&self, entry: &AuditEntry) -> Result<()> {
+        let line = self.line(entry)?;
+        self.flush();
+        self.append(&line)
+    }
+
+    /// Wait until every call observed so far has been written.
+    pub fn flush(&self) {
+        let Some(writer) = self.writer.get() else {
+            return;
+        };
+        let (done, written) = mpsc::sync_channel(1);
+        if writer.send(Job::Flush(done)).is_ok() {
+            let _ = written.recv();
+        }
+    }
+
+    /// `entry` as a line of the log.
+    fn line(&self, entry: &AuditEntry) -> Result<Vec<u8>> {
+        let mut entry = entry.clone();
+        if self.hash_targets {
+            entry.targets = entry.targets.iter().map(|t| hash(t)).collect();
+        }
+        entry.error = entry.error.as_deref().map(scrub);
+
+        let mut line = serde_json::to_vec(&entry)?;
+        line.push(b'\n');
+        Ok(line)
+    }
+
+    fn append(&self, line: &[u8]) -> Result<()> {
+        let _guard = self.lock.lock().unwrap_or_else(PoisonError::into_inner);
+        self.write(line)
+            .map_err(|e| I1Error::Config(format!("audit log {}: {e}", self.path.display())))
+    }
+
+    /// Rotate if `line` would take the file past the limit, then append it
+    /// in one write.
+    fn write(&self, line: &[u8]) -> std::io::Result<()> {
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let len = std::fs::metadata(&self.path).map_or(0, |m| m.len());
+        if len > 0 && len + line.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(line)
+    }
+
+    /// Shift `<file>.N` up by one, dropping the oldest, and move the
+    /// current file to `<file>.1`.
+    fn rotate(&self) -> std::io::Result<()> {
+        if self.keep == 0 {
+            return std::fs::remove_file(&self.path);
+        }
+        for n in (1..self.keep).rev() {
+            let from = self.rotated(n);
+            if from.exists() {
+                std::fs::rename(&from, self.rotated(n + 1))?;
+            }
+        }
+        std::fs::rename(&self.path, self.rotated(1))
+    }
+
+    /// The `n`th rotated file, `<file>.n`.
+    fn rotated(&self, n: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{n}"));
+        name.into()
+    }
+
+    /// Every entry still on disk, oldest first, rotated files included.
+    /// Lines that can't be read are skipped.
+    pub fn entries(&self) -> Result<Vec<AuditEntry>> {
+        let mut files: Vec<PathBuf> = (1..=self.keep).rev().map(|n| self.rotated(n)).collect();
+        files.push(self.path.clone());
+
+        let mut entries = Vec::new();
+        for file in files {
+            let content = match std::fs::read_to_string(&file) {
+                Ok(content) => content,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => {
+                    return Err(I1Error::Config(format!(
+                        "audit log {}: {e}",
+                        file.display()
+                    )))
+                }
+            };
+            entries.extend(
+                content
+                    .lines()
+                    .filter_map(|line| serde_json::from_str::<AuditEntry>(line).ok()),
+            );
+        }
+        Ok(entries)
+    }
+}
+
+impl CallObserver for AuditLog {
+    fn on_call(&self, call: &Call<'_>) {
+        let line = match self.line(&AuditEntry::call(call)) {
+            Ok(line) => line,
+            Err(e) => {
+                warn!(error = %e, "Could not write the audit log");
+                return;
+            }
+        };
+
+        // Written here only if the writer thread can't be started
+        let writer = self.writer.get_or_init(|| {
+            Writer::start(Self {
+                writer: Arc::default(),
+                ..self.clone()
+            })
+        });
+        if let Err(Job::Line(line)) = writer.send(Job::Line(line)) {
+            if let Err(e) = self.append(&line) {
+                warn!(error = %e, "Could not write the audit log");
+            }
+        }
+    }
+}
+
+/// Work for the writer thread.
+enum Job {
+    Line(Vec<u8>),
+    /// Answered once everything queued before it is written
+    Flush(mpsc::SyncSender<()>),
+}
+
+/// A thread appending queued lines to a log, in order. Dropping it waits
+/// for the queue to drain.
+#[derive(Debug)]
+struct Writer {
+    jobs: Option<mpsc::Sender<Job>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Writer {
+    /// Start writing to `log`, which must not share this writer.
+    fn start(log: AuditLog) -> Self {
+        let (jobs, queue) = mpsc::channel();
+        let thread = std::thread::Builder::new()
+            .name("i1-audit".to_string())
+            .spawn(move || {
+                for job in queue {
+                    match job {
+                        Job::Line(line) => {
+                            if let Err(e) = log.append(&line) {
+                                warn!(error = %e, "Could not write the audit log");
+                            }
+                        }
+                        Job::Flush(done) => {
+                            let _ = done.send(());
+                        }
+                    }
+                }
+            });
+
+        match thread {
+            Ok(thread) => Self {
+                jobs: Some(jobs),
+                thread: Some(thread),
+            },
+            Err(e) => {
+                warn!(error = %e, "Could not start the audit log writer");
+                Self {
+                    jobs: None,
+                    thread: None,
+                }
+            }
+        }
+    }
+
+    /// Queue `job`, or hand it back if there is no thread to take it.
+    fn send(&self, job: Job) -> std::result::Result<(), Job> {
+        match &self.jobs {
+            Some(jobs) => jobs.send(job).map_err(|e| e.0),
+            None => Err(job),
+        }
+    }
+}
+
+impl Drop for Writer {
+    fn drop(&mut self) {
+        // Closing the queue ends the thread once it is drained
+        self.jobs.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// `sha256:` and the hex SHA-256 of `target`.
+fn hash(target: &str) -> String {
+    Sha256::digest(target.as_bytes())
+        .iter()
+        .fold(String::from("sha256:"), |mut out, byte| {
+            let _ = write!(out, "{byte:02x}");
+            out
+        })
+}
+
+/// `message` with the values of `key=`, `token=` and similar parameters
+/// replaced by `***`.
+fn scrub(message: &str) -> String {
+    let mut out = String::with_capacity(message.len());
+    let mut rest = message;
+    while let Some((at, param)) = SECRET_PARAMS
+        .iter()
+        .filter_map(|p| Some((find_param(rest, p)?, *p)))
+        .min()
+    {
+        let value = at + param.len();
+        out.push_str(&rest[..value]);
+        out.push_str("***");
+        let end = rest[value..]
+            .find(['&', ')', ' ', '"', '\'', ',', '>'])
+            .map_or(rest.len(), |i| value + i);
+        rest = &rest[end..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Where `param` starts in `text` as a whole parameter name, so `key=`
+/// doesn't match inside `monkey=`.
+fn find_param(text: &str, param: &str) -> Option<usize> {
+    text.match_indices(param)
+        .map(|(i, _)| i)
+        .find(|&i| i == 0 || matches!(text.as_bytes()[i - 1], b'?' | b'&' | b' ' | b'('))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn call<'a>(target: &'a str, error: Option<&'a I1Error>) -> Call<'a> {
+        Call {
+            provider: "shodan",
+            operation: "lookup",
+            target,
+            elapsed: Duration::from_millis(42),
+            error,
+            credits: 0,
+        }
+    }
+
+    #[test]
+    fn test_record_and_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = AuditLog::new(dir.path().join("logs/audit.jsonl"));
+
+        log.on_call(&call("192.0.2.1", None));
+        let error = I1Error::Http(
+            "error sending request for url (https://api.shodan.io/shodan/host/192.0.2.2?key=SECRETKEY&minify=true)"
+                .to_string(),
+        );
+        log.on_call(&call("192.0.2.2", Some(&error)));
+        log.record(&AuditEntry::action("ban", vec!["198.51.100.0/24".into()]))
+            .unwrap();
+
+        let raw = std::fs::read_to_string(log.path()).unwrap();
+        assert_eq!(raw.lines().count(), 3);
+        assert!(!raw.contains("SECRETKEY"), "{raw}");
+
+        let entries = log.entries().unwrap();
+        assert_eq!(entries[0].targets, ["192.0.2.1"]);
+        assert_eq!(entries[0].latency_ms, Some(42));
+        assert!(entries[0].ok);
+        assert_eq!(
+            entries[1].error.as_deref(),
+            Some("http: HTTP request failed: error sending request for url (https://api.shodan.io/shodan/host/192.0.2.2?key=***&minify=true)")
+        );
+        assert_eq!(entries[2].kind, AuditKind::Action);
+        assert_eq!(entries[2].provider, None);
+    }
+
+    #[test]
+    fn test_hash_targets() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = AuditLog::new(dir.path().join("audit.jsonl")).hash_targets(true);
+
+        log.on_call(&call("192.0.2.1", None));
+        log.flush();
+        let raw = std::fs::read_to_string(log.path()).unwrap();
+        assert!(!raw.contains("192.0.2.1"));
+
+        // The same target always hashes the same, so it can still be matched
+        let target = &log.entries().unwrap()[0].targets[0];
+        assert_eq!(*target, hash("192.0.2.1"));
+        assert!(target.starts_with("sha256:"));
+        assert_eq!(target.len(), "sha256:".len() + 64);
+    }
+
+    #[test]
+    fn test_writer() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let log = AuditLog::new(&path);
+        let observer = log.clone();
+
+        let targets: Vec<String> = (0..50).map(|i| format!("192.0.2.{i}")).collect();
+        for target in &targets {
+            observer.on_call(&call(target, None));
+        }
+        log.flush();
+        let written: Vec<String> = log
+            .entries()
+            .unwrap()
+            .into_iter()
+            .flat_map(|e| e.targets)
+            .collect();
+        assert_eq!(written, targets);
+
+        // The last clone going away finishes what is queued
+        observer.on_call(&call("198.51.100.1", None));
+        drop((log, observer));
+        let entries = AuditLog::new(&path).entries().unwrap();
+        assert_eq!(entries.len(), 51);
+        assert_eq!(entries[50].targets, ["198.51.100.1"]);
+    }
+
+    #[test]
+    fn test_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let log = AuditLog::new(&path).max_bytes(400).keep(2);
+
+        for i in 0..20 {
+            log.record(&AuditEntry::action("ban", vec![format!("192.0.2.{i}")]))
+                .unwrap();
+        }
+
+        assert!(std::fs::metadata(&path).unwrap().len() <= 400);
+        assert!(dir.path().join("audit.jsonl.1").exists());
+        assert!(dir.path().join("audit.jsonl.2").exists());
+        assert!(!dir.path().join("audit.jsonl.3").exists());
+
+        // What's left is in order and ends with the newest
+        let entries = log.entries().unwrap();
+        assert!(entries.len() < 20);
+        assert_eq!(entries.last().unwrap().targets, ["192.0.2.19"]);
+        assert!(entries.windows(2).all(|w| w[0].at <= w[1].at));
+    }
+
+    #[test]
+    fn test_scrub() {
+        assert_eq!(scrub("GET /host?key=abc123"), "GET /host?key=***");
+        assert_eq!(
+            scrub("(https://x/y?token=t0k&api_key=k3y)"),
+            "(https://x/y?token=***&api_key=***)"
+        );
+        assert_eq!(scrub("monkey=business"), "monkey=business");
+    }
+}
@@ -1,14 +1,24 @@
 //! Unified i1 client that aggregates multiple providers.
 
 use std::collections::HashMap;
+use std::future::Future;
 use std::sync::Arc;
 
-use i1_core::{HostInfo, I1Error, Result};
+use futures_util::future::join_all;
+use futures_util::stream::{self, Stream, StreamExt};
+use i1_core::{validate_ip, validate_query, HostInfo, I1Error, Result};
 use i1_providers::{
-    HealthStatus, HostLookup, Provider, ProviderHealth, SearchProvider, SearchResults,
+    DnsProvider, HealthStatus, HostLookup, MergedHostInfo, Provider, ProviderHealth, ProviderId,
+    ResultSet, SearchProvider, SearchResults, ThreatLevel,
 };
 use tracing::{debug, info, instrument};
 
+#[cfg(feature = "metrics")]
+use crate::metrics::Metrics;
+use crate::notify::{NotificationSink, Notifier};
+use crate::observe::{self, CallObserver};
+use crate::policy::QueryPolicy;
+
 /// Unified i1 client that can aggregate multiple providers
 pub struct I1Client {
     inner: Arc<I1ClientInner>,
@@ -17,6 +27,28 @@ pub struct I1Client {
 struct I1ClientInner {
     providers: HashMap<String, Arc<dyn ProviderBox>>,
     default_provider: Option<String>,
+    dns: Option<Arc<dyn DnsProvider + Send + Sync>>,
+    notifier: Notifier,
+    observers: Vec<Arc<dyn CallObserver>>,
+    policy: QueryPolicy,
+    #[cfg(feature = "metrics")]
+    metrics: Metrics,
+}
+
+/// What [`I1Client::lookup_host_merged`] could put together.
+///
+/// An outcome that isn't [`complete`](Self::complete) is a partial answer:
+/// some providers failed, and `errors` says why.
+#[derive(Debug)]
+pub struct MergeOutcome {
+    /// The host as every contributing provider sees it
+    pub merged: MergedHostInfo,
+    /// Providers that returned data, in order of precedence
+    pub contributors: Vec<String>,
+    /// Providers that didn't, each with its error
+    pub errors: Vec<(String, I1Error)>,
+    /// Whether every provider contributed
+    pub complete: bool,
 }
 
 /// Trait object wrapper for providers
@@ -42,6 +74,110 @@ impl I1Client {
         self.inner.providers.keys().map(String::as_str).collect()
     }
 
+    /// Name of the provider used when none is given
+    pub fn default_provider_name(&self) -> Option<&str> {
+        self.inner.default_provider.as_deref()
+    }
+
+    /// Targets this client withholds from its providers
+    pub fn policy(&self) -> &QueryPolicy {
+        &self.inner.policy
+    }
+
+    /// The provider [`investigate`](Self::investigate) asks about domains,
+    /// if one was set
+    pub(crate) fn dns(&self) -> Option<&(dyn DnsProvider + Send + Sync)> {
+        self.inner.dns.as_deref()
+    }
+
+    /// Notify `sink` whenever a looked-up host reaches `level` or above.
+    ///
+    /// Delivery happens in the background; see [`Notifier`].
+    pub fn notify_on(&self, level: ThreatLevel, sink: impl NotificationSink + 'static) {
+        self.inner.notifier.notify_on(level, sink);
+    }
+
+    /// The notifier lookups report to, for hosts found some other way and
+    /// for waiting on deliveries before exiting.
+    pub fn notifier(&self) -> &Notifier {
+        &self.inner.notifier
+    }
+
+    /// What this client's providers have done, as Prometheus metrics.
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&self) -> &Metrics {
+        &self.inner.metrics
+    }
+
+    /// The Prometheus registry behind [`metrics`](Self::metrics), for
+    /// encoding into an exporter you already run.
+    #[cfg(feature = "metrics")]
+    pub fn metrics_registry(&self) -> &prometheus_client::registry::Registry {
+        self.inner.metrics.registry()
+    }
+
+    /// Answer Prometheus scrapes of `http://<addr>/metrics` until the task
+    /// is dropped. Fails only if `addr` can't be bound.
+    #[cfg(feature = "metrics")]
+    pub async fn serve_metrics(&self, addr: impl tokio::net::ToSocketAddrs) -> Result<()> {
+        let listener = tokio::net::TcpListener::bind(addr)
+            .await
+            .map_err(|e| I1Error::Config(format!("cannot serve metrics: {e}")))?;
+        info!(addr = ?listener.local_addr().ok(), "Serving metrics");
+
+        loop {
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    debug!(error = %e, "Metrics connection failed");
+                    continue;
+                }
+            };
+            let client = self.clone();
+            tokio::spawn(async move {
+                if let Err(e) = crate::metrics::answer(stream, &client.inner.metrics).await {
+                    debug!(error = %e, "Metrics scrape failed");
+                }
+            });
+        }
+    }
+
+    /// Make one provider call about `target`, counting it in the metrics
+    /// and telling the observers.
+    pub(crate) async fn call<T>(
+        &self,
+        provider: &str,
+        operation: &'static str,
+        target: &str,
+        credits: u32,
+        call: impl Future<Output = Result<T>>,
+    ) -> Result<T> {
+        let call = observe::observe(
+            &self.inner.observers,
+            provider,
+            operation,
+            target,
+            credits,
+            call,
+        );
+        #[cfg(feature = "metrics")]
+        {
+            let started = std::time::Instant::now();
+            let result = call.await;
+            self.inner.metrics.record_call(
+                provider,
+                operation,
+                started.elapsed(),
+                result.as_ref().err(),
+            );
+            result
+        }
+        #[cfg(not(feature = "metrics"))]
+        {
+            call.await
+        }
+    }
+
     /// Check health of all providers
     #[instrument(skip(self))]
     pub async fn health_check_all(&self) -> Vec<ProviderHealth> {
@@ -57,6 +193,9 @@ impl I1Client {
                     latency_ms: None,
                     credits_remaining: None,
                     message: Some(e.to_string()),
+                    plan: None,
+                    scan_credits: None,
+                    resets_at: None,
                 }),
             }
         }
@@ -76,32 +215,154 @@ impl I1Client {
         self.lookup_host_with(ip, provider_name).await
     }
 
-    /// Look up host using a specific provider
+    /// Look up host using a specific provider.
+    ///
+    /// Anything but an IP address fails with [`I1Error::InvalidIp`] before
+    /// the provider is called; see [`investigate`](Self::investigate) for
+    /// domains, blocks and URLs. An IP the [`QueryPolicy`] withholds from
+    /// the provider fails with [`I1Error::PolicyDenied`].
     #[instrument(skip(self))]
     pub async fn lookup_host_with(&self, ip: &str, provider: &str) -> Result<HostInfo> {
+        // Providers get the canonical form, never `[2001:db8::1]`
+        let ip = validate_ip(ip)?.to_string();
+        let ip = ip.as_str();
+        let name = provider;
         let provider = self
             .inner
             .providers
             .get(provider)
             .ok_or_else(|| I1Error::ProviderNotConfigured(provider.to_string()))?;
+        self.inner.policy.check(name, ip)?;
+
+        let host = self
+            .call(
+                name,
+                "lookup",
+                ip,
+                observe::lookup_credits(name),
+                provider.lookup_host(ip),
+            )
+            .await?;
+        self.inner.notifier.check(&host, "lookup");
+        Ok(host)
+    }
 
-        provider.lookup_host(ip).await
+    /// Look up many hosts with the default provider.
+    ///
+    /// At most `concurrency` lookups are in flight at once, on top of the
+    /// provider's own rate limiting. Results are yielded in input order,
+    /// each paired with the IP it belongs to.
+    pub fn lookup_host_batch<I>(
+        &self,
+        ips: I,
+        concurrency: usize,
+    ) -> impl Stream<Item = (String, Result<HostInfo>)>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let client = self.clone();
+
+        stream::iter(ips)
+            .map(move |ip| {
+                let client = client.clone();
+                async move {
+                    let result = client.lookup_host(&ip).await;
+                    (ip, result)
+                }
+            })
+            .buffered(concurrency.max(1))
     }
 
-    /// Look up host from all configured providers and merge results
+    /// Look up host from all configured providers concurrently.
+    ///
+    /// Every provider gets its own result, so one failing provider does not
+    /// hide the others, and providers the policy withholds `ip` from get
+    /// [`I1Error::PolicyDenied`]. Results are sorted by provider name.
     #[instrument(skip(self))]
     pub async fn lookup_host_all(&self, ip: &str) -> Result<Vec<(String, Result<HostInfo>)>> {
-        let mut results = Vec::new();
-
-        for (name, provider) in &self.inner.providers {
-            info!(provider = %name, ip = %ip, "Looking up host");
-            let result = provider.lookup_host(ip).await;
-            results.push((name.clone(), result));
+        let ip = validate_ip(ip)?.to_string();
+        let ip = ip.as_str();
+        let lookups = self
+            .inner
+            .providers
+            .iter()
+            .map(|(name, provider)| async move {
+                if let Err(e) = self.inner.policy.check(name, ip) {
+                    debug!(provider = %name, ip = %ip, "Withheld by policy");
+                    return (name.clone(), Err(e));
+                }
+                info!(provider = %name, ip = %ip, "Looking up host");
+                let result = self
+                    .call(
+                        name,
+                        "lookup",
+                        ip,
+                        observe::lookup_credits(name),
+                        provider.lookup_host(ip),
+                    )
+                    .await;
+                (name.clone(), result)
+            });
+
+        let mut results = join_all(lookups).await;
+        results.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let worst = results
+            .iter()
+            .filter_map(|(_, result)| result.as_ref().ok())
+            .max_by(|a, b| a.risk_score().total_cmp(&b.risk_score()));
+        if let Some(host) = worst {
+            self.inner.notifier.check(host, "lookup");
         }
 
         Ok(results)
     }
 
+    /// Look up host from all configured providers and merge the results.
+    ///
+    /// The default provider takes precedence for single-valued fields.
+    /// Providers that fail don't stop the others: the outcome says which
+    /// ones contributed and why the rest didn't. Fails only if no provider
+    /// returned data, with an error that names every failure.
+    #[instrument(skip(self))]
+    pub async fn lookup_host_merged(&self, ip: &str) -> Result<MergeOutcome> {
+        let mut results = self.lookup_host_all(ip).await?;
+        results
+            .sort_by_key(|(name, _)| Some(name.as_str()) != self.inner.default_provider.as_deref());
+
+        let mut hosts = Vec::new();
+        let mut contributors = Vec::new();
+        let mut errors = Vec::new();
+
+        for (name, result) in results {
+            match (result, name.parse::<ProviderId>()) {
+                (Ok(host), Ok(id)) => {
+                    hosts.push((id, host));
+                    contributors.push(name);
+                }
+                (Ok(_), Err(e)) => {
+                    debug!(provider = %name, "Skipping unknown provider");
+                    errors.push((name, e));
+                }
+                (Err(e), _) => {
+                    debug!(provider = %name, error = %e, "Provider lookup failed");
+                    errors.push((name, e));
+                }
+            }
+        }
+
+        if hosts.is_empty() {
+            return Err(I1Error::all_failed(errors));
+        }
+
+        Ok(MergeOutcome {
+            merged: MergedHostInfo::from_hosts(ip, &hosts),
+            contributors,
+            complete: errors.is_empty(),
+            errors,
+        })
+    }
+
     /// Search using default provider
     #[instrument(skip(self))]
     pub async fn search(&self, query: &str, page: Option<u32>) -> Result<SearchResults> {
@@ -114,7 +375,8 @@ impl I1Client {
         self.search_with(query, page, provider_name).await
     }
 
-    /// Search using a specific provider
+    /// Search using a specific provider. An empty query fails with
+    /// [`I1Error::InvalidQuery`] without spending a credit.
     #[instrument(skip(self))]
     pub async fn search_with(
         &self,
@@ -122,13 +384,84 @@ impl I1Client {
         page: Option<u32>,
         provider: &str,
     ) -> Result<SearchResults> {
+        validate_query(query)?;
+        let name = provider;
         let provider = self
             .inner
             .providers
             .get(provider)
             .ok_or_else(|| I1Error::ProviderNotConfigured(provider.to_string()))?;
 
-        provider.search(query, page).await
+        let credits = observe::search_credits(name, query, page);
+        let results = self
+            .call(name, "search", query, credits, provider.search(query, page))
+            .await?;
+        #[cfg(feature = "metrics")]
+        self.inner.metrics.record_search(name, query, page);
+        Ok(results)
+    }
+
+    /// Search every configured provider concurrently, merged by IP.
+    ///
+    /// The [`ResultSet`] also says which providers returned each host. Fails
+    /// only if no provider answered, with the first error seen.
+    #[instrument(skip(self))]
+    pub async fn search_all(&self, query: &str, page: Option<u32>) -> Result<ResultSet> {
+        validate_query(query)?;
+        let mut names: Vec<&str> = self.providers();
+        names.sort_unstable();
+
+        let searches = names.iter().map(|name| self.search_with(query, page, name));
+        let answers = join_all(searches).await;
+
+        let mut set = ResultSet::new();
+        let mut first_error = None;
+        for (name, answer) in names.iter().zip(answers) {
+            match answer {
+                Ok(results) => set.add(results),
+                Err(e) => {
+                    debug!(provider = %name, error = %e, "Provider search failed");
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+
+        match first_error {
+            Some(e) if set.providers().is_empty() => Err(e),
+            None if names.is_empty() => Err(I1Error::NoProviders),
+            _ => Ok(set),
+        }
+    }
+
+    /// Page through search results using the default provider.
+    ///
+    /// Yields one [`SearchResults`] per page starting at `start_page`, and
+    /// ends after an empty page or the first error. Pages are only fetched
+    /// as the stream is polled, so callers stop spending credits by simply
+    /// dropping it.
+    pub fn search_iter(
+        &self,
+        query: impl Into<String>,
+        start_page: u32,
+    ) -> impl Stream<Item = Result<SearchResults>> {
+        let client = self.clone();
+        let query = query.into();
+
+        stream::unfold(Some(start_page.max(1)), move |page| {
+            let client = client.clone();
+            let query = query.clone();
+            async move {
+                let page = page?;
+                debug!(page, "Fetching search page");
+                match client.search(&query, Some(page)).await {
+                    Ok(results) => {
+                        let next = (!results.results.is_empty()).then_some(page + 1);
+                        Some((Ok(results), next))
+                    }
+                    Err(e) => Some((Err(e), None)),
+                }
+            }
+        })
     }
 
     /// Count results using default provider
@@ -146,13 +479,16 @@ impl I1Client {
     /// Count results using a specific provider
     #[instrument(skip(self))]
     pub async fn count_with(&self, query: &str, provider: &str) -> Result<u64> {
+        validate_query(query)?;
+        let name = provider;
         let provider = self
             .inner
             .providers
             .get(provider)
             .ok_or_else(|| I1Error::ProviderNotConfigured(provider.to_string()))?;
 
-        provider.count(query).await
+        self.call(name, "count", query, 0, provider.count(query))
+            .await
     }
 }
 
@@ -168,6 +504,9 @@ impl Clone for I1Client {
 pub struct I1ClientBuilder {
     providers: HashMap<String, Arc<dyn ProviderBox>>,
     default_provider: Option<String>,
+    dns: Option<Arc<dyn DnsProvider + Send + Sync>>,
+    observers: Vec<Arc<dyn CallObserver>>,
+    policy: QueryPolicy,
 }
 
 impl I1ClientBuilder {
@@ -176,10 +515,14 @@ impl I1ClientBuilder {
         Self {
             providers: HashMap::new(),
             default_provider: None,
+            dns: None,
+            observers: Vec::new(),
+            policy: QueryPolicy::new(),
         }
     }
 
     /// Add a provider to the client
+    #[must_use]
     pub fn with_provider<P>(mut self, provider: P) -> Self
     where
         P: Provider + HostLookup + SearchProvider + Send + Sync + 'static,
@@ -193,17 +536,50 @@ impl I1ClientBuilder {
     }
 
     /// Set the default provider (must be added first)
+    #[must_use]
     pub fn default_provider(mut self, name: impl Into<String>) -> Self {
         self.default_provider = Some(name.into());
         self
     }
 
+    /// Ask `provider` about domains when investigating them. Without one,
+    /// domains are only resolved, with the system resolver.
+    #[must_use]
+    pub fn dns_provider<P>(mut self, provider: P) -> Self
+    where
+        P: DnsProvider + Send + Sync + 'static,
+    {
+        self.dns = Some(Arc::new(provider));
+        self
+    }
+
+    /// Tell `observer` about every provider call the client makes
+    #[must_use]
+    pub fn observer(mut self, observer: impl CallObserver + 'static) -> Self {
+        self.observers.push(Arc::new(observer));
+        self
+    }
+
+    /// Withhold targets from providers as `policy` says, instead of the
+    /// default of withholding only private space from external providers
+    #[must_use]
+    pub fn policy(mut self, policy: QueryPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
     /// Build the client
     pub fn build(self) -> I1Client {
         I1Client {
             inner: Arc::new(I1ClientInner {
                 providers: self.providers,
                 default_provider: self.default_provider,
+                dns: self.dns,
+                notifier: Notifier::new(),
+                observers: self.observers,
+                policy: self.policy,
+                #[cfg(feature = "metrics")]
+                metrics: Metrics::new(),
             }),
         }
     }
@@ -214,3 +590,143 @@ impl Default for I1ClientBuilder {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockProvider;
+
+    /// A provider knowing `ips`, as hosts of its own `org` and hostname.
+    fn mock(name: &'static str, ips: &[&str]) -> MockProvider {
+        let fields = serde_json::json!({
+            "org": name,
+            "hostnames": [format!("{name}.example")],
+        });
+        ips.iter().fold(MockProvider::new(name), |mock, ip| {
+            mock.host(ip, fields.clone())
+        })
+    }
+
+    #[tokio::test]
+    async fn test_search_all() {
+        let client = I1Client::builder()
+            .with_provider(mock("shodan", &["192.0.2.1", "192.0.2.2"]))
+            .with_provider(mock("censys", &["192.0.2.2", "192.0.2.3"]))
+            .with_provider(MockProvider::new("criminalip").failing(|| I1Error::Unauthorized))
+            .build();
+
+        let set = client.search_all("nginx", None).await.unwrap();
+        let stats = set.stats();
+        assert_eq!((stats.hosts, stats.overlapping), (3, 1));
+        assert_eq!(set.providers(), ["censys", "shodan"]);
+
+        let results = set.into_results(i1_providers::HostOrder::Ip);
+        assert_eq!(results.total, 4);
+        assert_eq!(
+            results
+                .results
+                .iter()
+                .map(|host| host.ip_str.as_str())
+                .collect::<Vec<_>>(),
+            ["192.0.2.1", "192.0.2.2", "192.0.2.3"]
+        );
+
+        // Only an error when nobody answers
+        let client = I1Client::builder()
+            .with_provider(MockProvider::new("shodan").failing(|| I1Error::Unauthorized))
+            .build();
+        assert!(matches!(
+            client.search_all("nginx", None).await,
+            Err(I1Error::Unauthorized)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_lookup_host_merged() {
+        let rate_limited = || I1Error::RateLimited { retry_after: None };
+
+        let client = I1Client::builder()
+            .with_provider(mock("shodan", &["192.0.2.1"]))
+            .with_provider(mock("censys", &["192.0.2.1"]))
+            .default_provider("censys")
+            .build();
+        let outcome = client.lookup_host_merged("192.0.2.1").await.unwrap();
+        assert!(outcome.complete);
+        assert!(outcome.errors.is_empty());
+        assert_eq!(outcome.contributors, ["censys", "shodan"]);
+        assert_eq!(outcome.merged.org.as_deref(), Some("censys"));
+        assert_eq!(
+            outcome.merged.hostnames,
+            ["censys.example", "shodan.example"]
+        );
+
+        // Two of three failing still leaves an answer, marked partial
+        let client = I1Client::builder()
+            .with_provider(mock("shodan", &["192.0.2.1"]))
+            .with_provider(MockProvider::new("censys").failing(rate_limited))
+            .with_provider(MockProvider::new("criminalip").failing(|| I1Error::Unauthorized))
+            .build();
+        let outcome = client.lookup_host_merged("192.0.2.1").await.unwrap();
+        assert!(!outcome.complete);
+        assert_eq!(outcome.contributors, ["shodan"]);
+        assert_eq!(outcome.merged.sources, [ProviderId::Shodan]);
+        let errors: Vec<(&str, &str)> = outcome
+            .errors
+            .iter()
+            .map(|(name, e)| (name.as_str(), e.kind()))
+            .collect();
+        assert_eq!(
+            errors,
+            [("censys", "rate_limited"), ("criminalip", "unauthorized")]
+        );
+
+        // Nobody answering is an error that names every failure
+        let client = I1Client::builder()
+            .with_provider(mock("shodan", &[]))
+            .with_provider(MockProvider::new("censys").failing(rate_limited))
+            .with_provider(MockProvider::new("criminalip").failing(|| I1Error::Unauthorized))
+            .build();
+        let err = client.lookup_host_merged("192.0.2.1").await.unwrap_err();
+        let I1Error::AllProvidersFailed { failures } = &err else {
+            panic!("expected every provider to fail, got {err:?}");
+        };
+        assert_eq!(failures.len(), 3);
+        let message = err.to_string();
+        for provider in ["censys:", "criminalip:", "shodan:"] {
+            assert!(message.contains(provider), "{message}");
+        }
+
+        // With one provider, its own error is the summary
+        let client = I1Client::builder()
+            .with_provider(MockProvider::new("censys").failing(rate_limited))
+            .build();
+        assert!(matches!(
+            client.lookup_host_merged("192.0.2.1").await,
+            Err(I1Error::RateLimited { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_lookup_ipv6() {
+        const IPV6: &str = include_str!("../../i1-core/tests/fixtures/ipv6.txt");
+
+        for line in IPV6
+            .lines()
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        {
+            let (input, canonical) = line.split_once('\t').unwrap();
+            // The mock only knows the canonical form
+            let ips = [canonical];
+            let client = I1Client::builder()
+                .with_provider(mock("shodan", &ips))
+                .with_provider(mock("censys", &ips))
+                .policy(QueryPolicy::new().allow_private(true))
+                .build();
+
+            let host = client.lookup_host_with(input, "shodan").await.unwrap();
+            assert_eq!(host.ip_str, canonical, "{input}");
+            let outcome = client.lookup_host_merged(input).await.unwrap();
+            assert!(outcome.complete, "{input}");
+        }
+    }
+}
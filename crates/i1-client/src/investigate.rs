@@ -0,0 +1,308 @@
+//! One call for any [`Target`]: an IP, domain, CIDR block or URL.
+
+use std::net::IpAddr;
+
+use futures_util::StreamExt;
+use i1_core::{HostInfo, I1Error, IpNet, Result, Target};
+use i1_providers::DomainInfo;
+use tracing::{debug, instrument};
+
+use crate::I1Client;
+
+/// Most addresses of a block, or of a domain's records, looked up by one
+/// investigation
+pub const INVESTIGATE_LIMIT: usize = 256;
+
+/// Lookups in flight at once while investigating a block or domain
+const INVESTIGATE_CONCURRENCY: usize = 8;
+
+/// What [`I1Client::investigate`] found, by kind of target.
+///
+/// Hosts of a domain or block come with the address they were looked up
+/// by, in address order, and fail one by one.
+#[derive(Debug)]
+pub enum Investigation {
+    /// An IP address, or the one a URL points at
+    Host(Box<HostInfo>),
+    /// A domain, or the one a URL points at
+    Domain {
+        domain: String,
+        /// What the DNS provider knows about it; `None` without one
+        info: Option<Result<DomainInfo>>,
+        /// The addresses it resolves to
+        hosts: Vec<(String, Result<HostInfo>)>,
+    },
+    /// A CIDR block
+    Range {
+        net: IpNet,
+        hosts: Vec<(String, Result<HostInfo>)>,
+        /// Whether the block had more than [`INVESTIGATE_LIMIT`] addresses,
+        /// and only the first ones were looked up
+        truncated: bool,
+    },
+}
+
+impl I1Client {
+    /// Find out what the default provider knows about `target`.
+    ///
+    /// IPs are looked up directly. Domains are resolved and each address is
+    /// looked up, alongside the DNS provider's domain info if the client has
+    /// one. Blocks are looked up address by address, up to
+    /// [`INVESTIGATE_LIMIT`]. URLs are investigated through their host.
+    ///
+    /// A domain that doesn't resolve fails with [`I1Error::Dns`]. A domain
+    /// the [`QueryPolicy`](crate::QueryPolicy) withholds from the DNS
+    /// provider is resolved with the system resolver instead.
+    #[instrument(skip(self), fields(target = %target))]
+    pub async fn investigate(&self, target: Target) -> Result<Investigation> {
+        match target.host() {
+            Target::Ip(ip) => {
+                let host = self.lookup_host(&ip.to_string()).await?;
+                Ok(Investigation::Host(Box::new(host)))
+            }
+            Target::Domain(domain) => self.investigate_domain(domain).await,
+            Target::Cidr(net) => {
+                let mut ips = net.hosts();
+                let hosts = self
+                    .lookup_all(ips.by_ref().take(INVESTIGATE_LIMIT).collect())
+                    .await;
+                Ok(Investigation::Range {
+                    net,
+                    hosts,
+                    truncated: ips.next().is_some(),
+                })
+            }
+            Target::Url(url) => Err(I1Error::InvalidUrl(format!("{url}: no host"))),
+        }
+    }
+
+    async fn investigate_domain(&self, domain: String) -> Result<Investigation> {
+        let info = async {
+            let dns = self.dns()?;
+            if let Err(e) = self.policy().check(dns.name(), &domain) {
+                return Some(Err(e));
+            }
+            let info = self
+                .call(dns.name(), "domain", &domain, 0, dns.domain_info(&domain))
+                .await;
+            Some(info)
+        };
+        let (info, ips) = tokio::join!(info, self.resolve(&domain));
+
+        let mut ips = ips?;
+        ips.sort_unstable();
+        ips.dedup();
+        if ips.len() > INVESTIGATE_LIMIT {
+            debug!(domain = %domain, addresses = ips.len(), "Looking up the first addresses only");
+            ips.truncate(INVESTIGATE_LIMIT);
+        }
+
+        let hosts = self.lookup_all(ips).await;
+        Ok(Investigation::Domain {
+            domain,
+            info,
+            hosts,
+        })
+    }
+
+    /// Addresses of `domain`, from the DNS provider or else the system
+    /// resolver.
+    async fn resolve(&self, domain: &str) -> Result<Vec<IpAddr>> {
+        let dns = self
+            .dns()
+            .filter(|dns| self.policy().check(dns.name(), domain).is_ok());
+        let ips = match dns {
+            Some(dns) => {
+                self.call(dns.name(), "resolve", domain, 0, dns.resolve(domain))
+                    .await?
+            }
+            None => tokio::net::lookup_host((domain, 0))
+                .await
+                .map_err(|e| I1Error::Dns(format!("{domain}: {e}")))?
+                .map(|addr| addr.ip())
+                .collect(),
+        };
+        if ips.is_empty() {
+            return Err(I1Error::Dns(format!("{domain} has no addresses")));
+        }
+        Ok(ips)
+    }
+
+    async fn lookup_all(&self, ips: Vec<IpAddr>) -> Vec<(String, Result<HostInfo>)> {
+        self.lookup_host_batch(ips.iter().map(ToString::to_string), INVESTIGATE_CONCURRENCY)
+            .collect()
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::observe::{Call, CallObserver};
+    use crate::testing::MockProvider;
+
+    /// Knows 192.0.2.1 to 192.0.2.3, and resolves `example.com` to two of
+    /// them.
+    fn mock() -> MockProvider {
+        MockProvider::new("shodan")
+            .hosts(&["192.0.2.1", "192.0.2.2", "192.0.2.3"])
+            .resolving("example.com", &["192.0.2.2", "192.0.2.1", "192.0.2.2"])
+            .resolving("www.example.com", &["192.0.2.1"])
+    }
+
+    /// Remembers each call as `operation target`.
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl CallObserver for Recorder {
+        fn on_call(&self, call: &Call<'_>) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("{} {}", call.operation, call.target));
+        }
+    }
+
+    fn client(recorder: &Recorder) -> I1Client {
+        I1Client::builder()
+            .with_provider(mock())
+            .dns_provider(mock())
+            .observer(recorder.clone())
+            .build()
+    }
+
+    fn ips(hosts: &[(String, Result<HostInfo>)]) -> Vec<(&str, bool)> {
+        hosts
+            .iter()
+            .map(|(ip, result)| (ip.as_str(), result.is_ok()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_investigate() {
+        let recorder = Recorder::default();
+        let client = client(&recorder);
+
+        let Investigation::Host(host) = client
+            .investigate("http://192.0.2.1:8080/admin".parse().unwrap())
+            .await
+            .unwrap()
+        else {
+            panic!("not a host");
+        };
+        assert_eq!(host.ip_str, "192.0.2.1");
+
+        let Investigation::Domain {
+            domain,
+            info,
+            hosts,
+        } = client
+            .investigate("Example.com".parse().unwrap())
+            .await
+            .unwrap()
+        else {
+            panic!("not a domain");
+        };
+        assert_eq!(domain, "example.com");
+        assert_eq!(info.unwrap().unwrap().subdomains, ["www"]);
+        assert_eq!(ips(&hosts), [("192.0.2.1", true), ("192.0.2.2", true)]);
+
+        let Investigation::Range {
+            net,
+            hosts,
+            truncated,
+        } = client
+            .investigate("192.0.2.0/29".parse().unwrap())
+            .await
+            .unwrap()
+        else {
+            panic!("not a range");
+        };
+        assert_eq!(net.to_string(), "192.0.2.0/29");
+        assert!(!truncated);
+        assert_eq!(hosts.len(), 6);
+        assert_eq!(hosts.iter().filter(|(_, r)| r.is_ok()).count(), 3);
+
+        let err = client
+            .investigate("nowhere.example".parse().unwrap())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, I1Error::Dns(_)), "{err}");
+
+        let calls = recorder.0.lock().unwrap().clone();
+        assert!(calls.contains(&"resolve example.com".to_string()));
+        assert!(calls.contains(&"domain example.com".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_investigate_truncated() {
+        let client = client(&Recorder::default());
+        let Investigation::Range {
+            hosts, truncated, ..
+        } = client
+            .investigate("10.0.0.0/16".parse().unwrap())
+            .await
+            .unwrap()
+        else {
+            panic!("not a range");
+        };
+        assert!(truncated);
+        assert_eq!(hosts.len(), INVESTIGATE_LIMIT);
+        assert_eq!(hosts[0].0, "10.0.0.1");
+    }
+
+    #[tokio::test]
+    async fn test_validation() {
+        let recorder = Recorder::default();
+        let client = client(&recorder);
+
+        let err = client.lookup_host("example.com").await.unwrap_err();
+        assert!(matches!(err, I1Error::InvalidIp(_)), "{err}");
+        let err = client.lookup_host_all("1.2.3").await.unwrap_err();
+        assert!(matches!(err, I1Error::InvalidIp(_)), "{err}");
+        let err = client.search(" ", None).await.unwrap_err();
+        assert!(matches!(err, I1Error::InvalidQuery(_)), "{err}");
+        let err = client.count("").await.unwrap_err();
+        assert!(matches!(err, I1Error::InvalidQuery(_)), "{err}");
+
+        // Nothing reached the provider
+        assert!(recorder.0.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_policy() {
+        let recorder = Recorder::default();
+        let client = I1Client::builder()
+            .with_provider(mock())
+            .dns_provider(mock())
+            .observer(recorder.clone())
+            .policy(
+                crate::QueryPolicy::new()
+                    .deny_external("192.0.2.2".parse().unwrap())
+                    .deny("shodan", "example".parse().unwrap()),
+            )
+            .build();
+
+        for ip in ["10.0.0.1", "192.0.2.2"] {
+            let err = client.lookup_host(ip).await.unwrap_err();
+            assert!(matches!(err, I1Error::PolicyDenied { .. }), "{err}");
+            let all = client.lookup_host_all(ip).await.unwrap();
+            assert!(matches!(all[0].1, Err(I1Error::PolicyDenied { .. })));
+        }
+        assert!(recorder.0.lock().unwrap().is_empty());
+
+        // The DNS provider isn't told about the domain, and nothing resolves
+        // it locally
+        let err = client
+            .investigate("intranet.example".parse().unwrap())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, I1Error::Dns(_)), "{err}");
+        assert!(recorder.0.lock().unwrap().is_empty());
+
+        client.lookup_host("192.0.2.1").await.unwrap();
+        assert_eq!(*recorder.0.lock().unwrap(), ["lookup 192.0.2.1"]);
+    }
+}
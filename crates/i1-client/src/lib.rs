@@ -1,13 +1,35 @@
 //! HTTP client infrastructure for i1 security reconnaissance.
 //!
 //! This crate provides the unified [`I1Client`] that can work with multiple
-//! threat intelligence providers simultaneously.
+//! threat intelligence providers simultaneously and [investigate] any kind
+//! of target, and the [`notify`] sinks it can alert when a lookup turns up a
+//! high-risk host. Provider calls can be [`observe`]d, for example by an
+//! [`audit`] log. A [`policy`] keeps chosen targets from ever reaching
+//! third-party providers. With the `metrics` feature it also keeps Prometheus
+//! [`metrics`] of its providers. The `testing` feature adds a
+//! [`testing`] provider to build clients from in tests.
+//!
+//! [investigate]: I1Client::investigate
 
 #![doc(html_root_url = "https://docs.rs/i1-client/0.1.0")]
 
+pub mod audit;
 mod client;
 mod config;
+mod investigate;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod notify;
+pub mod observe;
+pub mod policy;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 
-pub use client::{I1Client, I1ClientBuilder};
+pub use audit::{AuditEntry, AuditKind, AuditLog};
+pub use client::{I1Client, I1ClientBuilder, MergeOutcome};
 pub use config::*;
 pub use i1_core::{I1Error, Result};
+pub use investigate::{Investigation, INVESTIGATE_LIMIT};
+pub use notify::{Finding, NotificationSink, Notifier, PayloadFormat, Webhook};
+pub use observe::{Call, CallObserver, Observed};
+pub use policy::{Guarded, PolicyRule, QueryPolicy};
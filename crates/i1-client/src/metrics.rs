@@ -0,0 +1,370 @@
+//! Prometheus metrics for provider calls, behind the `metrics` feature.
+//!
+//! Every [`I1Client`](crate::I1Client) keeps a [`Metrics`] registry of what
+//! its providers did. Encode it into an exporter you already run with
+//! [`Metrics::encode`] or [`I1Client::metrics_registry`](crate::I1Client::metrics_registry),
+//! or let [`I1Client::serve_metrics`](crate::I1Client::serve_metrics) answer
+//! scrapes itself.
+//!
+//! # Metric families
+//!
+//! These names and labels are stable; new families may be added.
+//!
+//! | Name | Type | Labels | Meaning |
+//! |------|------|--------|---------|
+//! | `i1_requests_total` | counter | `provider`, `operation` | Provider calls made |
+//! | `i1_errors_total` | counter | `provider`, `kind` | Failed calls, by [`I1Error::kind`] |
+//! | `i1_request_duration_seconds` | histogram | `provider`, `operation` | Time each call took |
+//! | `i1_rate_limit_wait_seconds` | histogram | `provider` | Waits providers asked for when rate limiting |
+//! | `i1_cache_lookups_total` | counter | `provider`, `result` | Cache `hit`s and `miss`es reported with [`Metrics::record_cache`] |
+//! | `i1_credits_consumed_total` | counter | `provider` | Query credits searches are estimated to have spent |
+//!
+//! `operation` is one of `lookup`, `search` or `count`. Searches are
+//! counted as one credit each, except a first Shodan page without filters,
+//! which Shodan gives away.
+
+use std::time::Duration;
+
+use i1_core::I1Error;
+use prometheus_client::encoding::EncodeLabelSet;
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::histogram::{exponential_buckets, Histogram};
+use prometheus_client::registry::Registry;
+
+/// Content type of [`Metrics::encode`]'s output
+pub const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct CallLabels {
+    provider: String,
+    operation: &'static str,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct ErrorLabels {
+    provider: String,
+    kind: &'static str,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct ProviderLabels {
+    provider: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct CacheLabels {
+    provider: String,
+    result: &'static str,
+}
+
+/// What a client's providers have done, as Prometheus metrics.
+#[derive(Debug)]
+pub struct Metrics {
+    registry: Registry,
+    requests: Family<CallLabels, Counter>,
+    errors: Family<ErrorLabels, Counter>,
+    duration: Family<CallLabels, Histogram>,
+    rate_limit_wait: Family<ProviderLabels, Histogram>,
+    cache: Family<CacheLabels, Counter>,
+    credits: Family<ProviderLabels, Counter>,
+}
+
+impl Metrics {
+    /// An empty registry with every family registered.
+    #[must_use]
+    pub fn new() -> Self {
+        let requests = Family::default();
+        let errors = Family::default();
+        let duration: Family<_, Histogram> = Family::new_with_constructor(duration_buckets);
+        let rate_limit_wait: Family<_, Histogram> = Family::new_with_constructor(wait_buckets);
+        let cache = Family::default();
+        let credits = Family::default();
+
+        let mut registry = Registry::with_prefix("i1");
+        registry.register("requests", "Provider calls made", requests.clone());
+        registry.register("errors", "Failed provider calls, by kind", errors.clone());
+        registry.register_with_unit(
+            "request_duration",
+            "Time each provider call took",
+            prometheus_client::registry::Unit::Seconds,
+            duration.clone(),
+        );
+        registry.register_with_unit(
+            "rate_limit_wait",
+            "Waits providers asked for when rate limiting",
+            prometheus_client::registry::Unit::Seconds,
+            rate_limit_wait.clone(),
+        );
+        registry.register("cache_lookups", "Cache hits and misses", cache.clone());
+        registry.register(
+            "credits_consumed",
+            "Query credits searches are estimated to have spent",
+            credits.clone(),
+        );
+
+        Self {
+            registry,
+            requests,
+            errors,
+            duration,
+            rate_limit_wait,
+            cache,
+            credits,
+        }
+    }
+
+    /// The registry, to encode alongside your own.
+    #[must_use]
+    pub const fn registry(&self) -> &Registry {
+        &self.registry
+    }
+
+    /// The metrics in the text exposition format, served as [`CONTENT_TYPE`].
+    #[must_use]
+    pub fn encode(&self) -> String {
+        let mut out = String::new();
+        // Writing to a String can't fail
+        let _ = prometheus_client::encoding::text::encode(&mut out, &self.registry);
+        out
+    }
+
+    /// Count a cache hit or miss for answers from `provider`, for callers
+    /// that cache what the client returns.
+    pub fn record_cache(&self, provider: &str, hit: bool) {
+        self.cache
+            .get_or_create(&CacheLabels {
+                provider: provider.to_string(),
+                result: if hit { "hit" } else { "miss" },
+            })
+            .inc();
+    }
+
+    /// Count one call to `provider` that took `elapsed`, and how it failed.
+    pub(crate) fn record_call(
+        &self,
+        provider: &str,
+        operation: &'static str,
+        elapsed: Duration,
+        error: Option<&I1Error>,
+    ) {
+        let labels = CallLabels {
+            provider: provider.to_string(),
+            operation,
+        };
+        self.requests.get_or_create(&labels).inc();
+        self.duration
+            .get_or_create(&labels)
+            .observe(elapsed.as_secs_f64());
+
+        let Some(error) = error else {
+            return;
+        };
+        self.errors
+            .get_or_create(&ErrorLabels {
+                provider: provider.to_string(),
+                kind: error.kind(),
+            })
+            .inc();
+        if let I1Error::RateLimited {
+            retry_after: Some(secs),
+        } = error
+        {
+            #[allow(clippy::cast_precision_loss)]
+            self.rate_limit_wait
+                .get_or_create(&ProviderLabels {
+                    provider: provider.to_string(),
+                })
+                .observe(*secs as f64);
+        }
+    }
+
+    /// Count the credits a search is estimated to have spent.
+    pub(crate) fn record_search(&self, provider: &str, query: &str, page: Option<u32>) {
+        let credits = crate::observe::search_credits(provider, query, page);
+        if credits > 0 {
+            self.credits
+                .get_or_create(&ProviderLabels {
+                    provider: provider.to_string(),
+                })
+                .inc_by(u64::from(credits));
+        }
+    }
+}
+
+/// 50ms to ~25s
+fn duration_buckets() -> Histogram {
+    Histogram::new(exponential_buckets(0.05, 2.0, 10))
+}
+
+/// 1s to ~17 minutes
+fn wait_buckets() -> Histogram {
+    Histogram::new(exponential_buckets(1.0, 2.0, 11))
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Answer one scrape on `stream`: the metrics for `GET /metrics`, 404 for
+/// anything else.
+pub(crate) async fn answer(
+    mut stream: tokio::net::TcpStream,
+    metrics: &Metrics,
+) -> std::io::Result<()> {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    // The request line is all that matters, and it fits in the first read
+    let mut buf = [0u8; 1024];
+    let read = stream.read(&mut buf).await?;
+    let request = String::from_utf8_lossy(&buf[..read]);
+    let path = request.split_whitespace().nth(1).unwrap_or_default();
+
+    let (status, content_type, body) =
+        if request.starts_with("GET ") && (path == "/metrics" || path.starts_with("/metrics?")) {
+            ("200 OK", CONTENT_TYPE, metrics.encode())
+        } else {
+            ("404 Not Found", "text/plain", "Not found\n".to_string())
+        };
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockProvider;
+    use crate::I1Client;
+
+    /// Answers 192.0.2.1, is rate limited for 192.0.2.2 and refuses to count
+    /// `apache`.
+    fn mock() -> MockProvider {
+        MockProvider::new("shodan")
+            .hosts(&["192.0.2.1"])
+            .refusing("192.0.2.2", || I1Error::RateLimited {
+                retry_after: Some(30),
+            })
+            .refusing("apache", || I1Error::Unauthorized)
+    }
+
+    /// The value of the sample `line` starts with.
+    fn sample<'a>(exposition: &'a str, line: &str) -> &'a str {
+        exposition
+            .lines()
+            .find_map(|l| Some(l.strip_prefix(line)?.trim()))
+            .unwrap_or_else(|| panic!("no sample {line} in\n{exposition}"))
+    }
+
+    #[tokio::test]
+    async fn test_exposition() {
+        let client = I1Client::builder().with_provider(mock()).build();
+        client.lookup_host("192.0.2.1").await.unwrap();
+        client.lookup_host("192.0.2.2").await.unwrap_err();
+        client.search("nginx", None).await.unwrap();
+        client.search("port:22", None).await.unwrap();
+        client.search("nginx", Some(2)).await.unwrap();
+        client.count("apache").await.unwrap_err();
+        client.metrics().record_cache("shodan", true);
+        client.metrics().record_cache("shodan", false);
+        client.metrics().record_cache("shodan", true);
+
+        let text = client.metrics().encode();
+        for family in [
+            "# TYPE i1_requests counter",
+            "# TYPE i1_errors counter",
+            "# TYPE i1_request_duration_seconds histogram",
+            "# TYPE i1_rate_limit_wait_seconds histogram",
+            "# TYPE i1_cache_lookups counter",
+            "# TYPE i1_credits_consumed counter",
+        ] {
+            assert!(text.contains(family), "no {family} in\n{text}");
+        }
+
+        let requests = |op| format!("i1_requests_total{{provider=\"shodan\",operation=\"{op}\"}}");
+        assert_eq!(sample(&text, &requests("lookup")), "2");
+        assert_eq!(sample(&text, &requests("search")), "3");
+        assert_eq!(sample(&text, &requests("count")), "1");
+        assert_eq!(
+            sample(
+                &text,
+                "i1_errors_total{provider=\"shodan\",kind=\"rate_limited\"}"
+            ),
+            "1"
+        );
+        assert_eq!(
+            sample(
+                &text,
+                "i1_errors_total{provider=\"shodan\",kind=\"unauthorized\"}"
+            ),
+            "1"
+        );
+        assert_eq!(
+            sample(
+                &text,
+                "i1_request_duration_seconds_count{provider=\"shodan\",operation=\"lookup\"}"
+            ),
+            "2"
+        );
+        assert_eq!(
+            sample(&text, "i1_rate_limit_wait_seconds_sum{provider=\"shodan\"}"),
+            "30.0"
+        );
+        assert_eq!(
+            sample(
+                &text,
+                "i1_cache_lookups_total{provider=\"shodan\",result=\"hit\"}"
+            ),
+            "2"
+        );
+        // The first unfiltered page is free
+        assert_eq!(
+            sample(&text, "i1_credits_consumed_total{provider=\"shodan\"}"),
+            "2"
+        );
+        assert!(text.ends_with("# EOF\n"));
+    }
+
+    #[tokio::test]
+    async fn test_serve_metrics() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let client = I1Client::builder().with_provider(mock()).build();
+        client.lookup_host("192.0.2.1").await.unwrap();
+
+        // Find a free port, then serve on it
+        let addr = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let server = client.clone();
+        tokio::spawn(async move { server.serve_metrics(addr).await });
+
+        let scrape = |path: &'static str| async move {
+            let mut stream = loop {
+                match tokio::net::TcpStream::connect(addr).await {
+                    Ok(stream) => break stream,
+                    Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+                }
+            };
+            let request = format!("GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n");
+            stream.write_all(request.as_bytes()).await.unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            response
+        };
+
+        let response = scrape("/metrics").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains(CONTENT_TYPE));
+        assert!(response.contains("i1_requests_total{provider=\"shodan\",operation=\"lookup\"} 1"));
+
+        assert!(scrape("/").await.starts_with("HTTP/1.1 404"));
+    }
+}
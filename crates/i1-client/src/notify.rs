@@ -0,0 +1,605 @@
+//! Notifications for high-risk findings.
+//!
+//! A [`Notifier`] looks at each host a lookup, watch or scan turns up and
+//! hands a [`Finding`] to every [`NotificationSink`] whose threshold the
+//! host has just crossed: a host already reported stays quiet until its
+//! threat level rises, or drops below the threshold and comes back.
+//!
+//! Delivery runs in the background and its failures are only logged, so a
+//! slow or broken webhook never holds up or fails the lookup itself.
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! use i1_client::{PayloadFormat, Webhook};
+//! use i1_providers::ThreatLevel;
+//!
+//! let slack = Webhook::new("https://hooks.slack.com/services/T000/B000/XXXX")
+//!     .format(PayloadFormat::Slack);
+//! client.notify_on(ThreatLevel::High, slack);
+//! ```
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use i1_core::{HostInfo, I1Error, Result};
+use i1_providers::ThreatLevel;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE, RETRY_AFTER};
+use reqwest::{Client, StatusCode};
+use serde::Serialize;
+use sha2::Sha256;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+use crate::RetryConfig;
+
+/// Header carrying the HMAC-SHA256 of the body, as `sha256=<hex>`
+pub const SIGNATURE_HEADER: &str = "x-i1-signature";
+
+/// How long one delivery attempt may take
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A host at or above a threat level, as sent to sinks.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Finding {
+    pub ip: String,
+    pub level: ThreatLevel,
+    /// The host's 0-10 risk score
+    pub risk_score: f64,
+    pub vulns: Vec<String>,
+    pub ports: Vec<u16>,
+    pub hostnames: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub org: Option<String>,
+    /// What turned the host up, such as `lookup`, `watch` or `scan`
+    pub source: String,
+    pub at: DateTime<Utc>,
+}
+
+impl Finding {
+    /// The finding for `host`, turned up by `source`.
+    #[must_use]
+    pub fn new(host: &HostInfo, source: &str) -> Self {
+        let risk_score = host.risk_score();
+        #[allow(clippy::cast_possible_truncation)]
+        let level = ThreatLevel::from_cvss(risk_score as f32);
+        Self {
+            ip: host.ip_str.clone(),
+            level,
+            risk_score,
+            vulns: host.vuln_scores().into_keys().collect(),
+            ports: host.ports.clone(),
+            hostnames: host.hostnames.clone(),
+            org: host.org.clone(),
+            source: source.to_string(),
+            at: Utc::now(),
+        }
+    }
+
+    /// One line for chat messages.
+    #[must_use]
+    pub fn summary(&self) -> String {
+        let mut line = format!(
+            "{} risk on {} ({:.1}/10, found by {})",
+            level_name(self.level),
+            self.ip,
+            self.risk_score,
+            self.source
+        );
+        if let Some(org) = &self.org {
+            let _ = write!(line, ", {org}");
+        }
+        if !self.vulns.is_empty() {
+            let _ = write!(line, ": {}", self.vulns.join(", "));
+        }
+        line
+    }
+}
+
+/// "High", "Critical", ...
+const fn level_name(level: ThreatLevel) -> &'static str {
+    match level {
+        ThreatLevel::None => "No",
+        ThreatLevel::Low => "Low",
+        ThreatLevel::Medium => "Medium",
+        ThreatLevel::High => "High",
+        ThreatLevel::Critical => "Critical",
+    }
+}
+
+/// Somewhere findings are delivered.
+#[async_trait]
+pub trait NotificationSink: Send + Sync {
+    /// Deliver one finding, retrying as the sink sees fit.
+    async fn deliver(&self, finding: &Finding) -> Result<()>;
+}
+
+/// The JSON a [`Webhook`] posts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PayloadFormat {
+    /// The [`Finding`] as it is
+    #[default]
+    Json,
+    /// A Slack incoming-webhook message
+    Slack,
+    /// A Discord webhook message
+    Discord,
+}
+
+impl PayloadFormat {
+    /// The format a webhook URL expects: Slack and Discord hooks are
+    /// recognised by host, anything else gets plain JSON.
+    #[must_use]
+    pub fn for_url(url: &str) -> Self {
+        let host = url::Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_ascii_lowercase));
+        match host.as_deref() {
+            Some("hooks.slack.com") => Self::Slack,
+            Some("discord.com" | "discordapp.com") => Self::Discord,
+            _ => Self::Json,
+        }
+    }
+
+    fn body(self, finding: &Finding) -> Result<Vec<u8>> {
+        let value = match self {
+            Self::Json => serde_json::to_value(finding)?,
+            Self::Slack => serde_json::json!({ "text": finding.summary() }),
+            Self::Discord => serde_json::json!({ "username": "i1", "content": finding.summary() }),
+        };
+        Ok(serde_json::to_vec(&value)?)
+    }
+}
+
+/// Posts findings to a URL.
+#[derive(Debug, Clone)]
+pub struct Webhook {
+    http: Client,
+    url: String,
+    headers: HeaderMap,
+    secret: Option<String>,
+    format: PayloadFormat,
+    retry: RetryConfig,
+}
+
+impl Webhook {
+    /// A webhook posting to `url`, in the format its host expects.
+    pub fn new(url: impl Into<String>) -> Self {
+        let url = url.into();
+        Self {
+            http: Client::builder()
+                .timeout(DELIVERY_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+            format: PayloadFormat::for_url(&url),
+            url,
+            headers: HeaderMap::new(),
+            secret: None,
+            retry: RetryConfig::default(),
+        }
+    }
+
+    /// Send this header with every delivery, such as an `Authorization`.
+    pub fn header(mut self, name: &str, value: &str) -> Result<Self> {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|e| I1Error::Config(format!("invalid header name '{name}': {e}")))?;
+        let value = HeaderValue::from_str(value)
+            .map_err(|e| I1Error::Config(format!("invalid value for header {name}: {e}")))?;
+        self.headers.insert(name, value);
+        Ok(self)
+    }
+
+    /// Sign each body with HMAC-SHA256 under `secret`, in the
+    /// [`SIGNATURE_HEADER`] header.
+    #[must_use]
+    pub fn secret(mut self, secret: impl Into<String>) -> Self {
+        self.secret = Some(secret.into());
+        self
+    }
+
+    /// Post this format instead of the one the URL suggests.
+    #[must_use]
+    pub const fn format(mut self, format: PayloadFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Retry failed deliveries this way.
+    #[must_use]
+    pub const fn retry(mut self, retry: RetryConfig) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// One delivery attempt.
+    async fn attempt(&self, body: &[u8]) -> Result<Attempt> {
+        let mut request = self
+            .http
+            .post(&self.url)
+            .headers(self.headers.clone())
+            .header(CONTENT_TYPE, "application/json")
+            .body(body.to_vec());
+        if let Some(secret) = &self.secret {
+            request = request.header(SIGNATURE_HEADER, signature(secret, body));
+        }
+
+        let response = match request.send().await {
+            Ok(response) => response,
+            Err(e) if e.is_timeout() || e.is_connect() => {
+                debug!(url = %self.url, error = %e, "Webhook unreachable");
+                return Ok(Attempt::Retry(None));
+            }
+            Err(e) => return Err(I1Error::Http(e.to_string())),
+        };
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(Attempt::Delivered);
+        }
+        if status == StatusCode::TOO_MANY_REQUESTS && self.retry.retry_on_rate_limit {
+            let wait = response
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse().ok())
+                .map(|secs| Duration::from_secs(secs).min(self.retry.max_backoff));
+            return Ok(Attempt::Retry(wait));
+        }
+        if status.is_server_error() {
+            return Ok(Attempt::Retry(None));
+        }
+        let message = response.text().await.unwrap_or_default();
+        Err(I1Error::provider("webhook", status.as_u16(), message))
+    }
+}
+
+/// How a delivery attempt went, short of a failure not worth retrying
+enum Attempt {
+    Delivered,
+    /// Try again, after this long if the server said
+    Retry(Option<Duration>),
+}
+
+#[async_trait]
+impl NotificationSink for Webhook {
+    async fn deliver(&self, finding: &Finding) -> Result<()> {
+        let body = self.format.body(finding)?;
+        let mut attempt = 0;
+        loop {
+            let wait = match self.attempt(&body).await? {
+                Attempt::Delivered => return Ok(()),
+                Attempt::Retry(wait) => wait,
+            };
+            if attempt >= self.retry.max_retries {
+                return Err(I1Error::Http(format!(
+                    "webhook {} failed after {} attempts",
+                    self.url,
+                    attempt + 1
+                )));
+            }
+            tokio::time::sleep(wait.unwrap_or_else(|| self.retry.backoff_for(attempt))).await;
+            attempt += 1;
+        }
+    }
+}
+
+/// `sha256=` and the hex HMAC-SHA256 of `body` under `secret`.
+#[must_use]
+pub fn signature(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any length");
+    mac.update(body);
+    let digest = mac.finalize().into_bytes();
+
+    let mut hex = String::with_capacity(7 + digest.len() * 2);
+    hex.push_str("sha256=");
+    for byte in digest {
+        let _ = write!(hex, "{byte:02x}");
+    }
+    hex
+}
+
+/// Sinks and the threat level each is notified from.
+#[derive(Clone, Default)]
+pub struct Notifier {
+    inner: Arc<Mutex<NotifierState>>,
+}
+
+#[derive(Default)]
+struct NotifierState {
+    sinks: Vec<(ThreatLevel, Arc<dyn NotificationSink>)>,
+    /// Last level seen for each IP
+    levels: HashMap<String, ThreatLevel>,
+    /// Deliveries not yet known to be done
+    pending: Vec<JoinHandle<()>>,
+}
+
+impl Notifier {
+    /// A notifier with no sinks, which notifies no one.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Notify `sink` of hosts that reach `level` or above.
+    pub fn notify_on(&self, level: ThreatLevel, sink: impl NotificationSink + 'static) {
+        self.lock().sinks.push((level, Arc::new(sink)));
+    }
+
+    /// Check if any sink is set up.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.lock().sinks.is_empty()
+    }
+
+    /// Look at `host`, turned up by `source`, and start delivering it to
+    /// each sink whose threshold it has just crossed. Returns at once.
+    pub fn check(&self, host: &HostInfo, source: &str) {
+        let mut state = self.lock();
+        if state.sinks.is_empty() {
+            return;
+        }
+
+        let finding = Finding::new(host, source);
+        let before = state
+            .levels
+            .insert(finding.ip.clone(), finding.level)
+            .unwrap_or(ThreatLevel::None);
+        let crossed: Vec<Arc<dyn NotificationSink>> = state
+            .sinks
+            .iter()
+            .filter(|(level, _)| finding.level >= *level && before < *level)
+            .map(|(_, sink)| Arc::clone(sink))
+            .collect();
+        if crossed.is_empty() {
+            return;
+        }
+
+        state.pending.retain(|task| !task.is_finished());
+        let finding = Arc::new(finding);
+        for sink in crossed {
+            let finding = Arc::clone(&finding);
+            state.pending.push(tokio::spawn(async move {
+                if let Err(e) = sink.deliver(&finding).await {
+                    warn!(ip = %finding.ip, error = %e, "Notification not delivered");
+                }
+            }));
+        }
+    }
+
+    /// Wait for deliveries already started, such as before exiting.
+    pub async fn flush(&self) {
+        let pending = std::mem::take(&mut self.lock().pending);
+        for task in pending {
+            let _ = task.await;
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, NotifierState> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl std::fmt::Debug for Notifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = self.lock();
+        f.debug_struct("Notifier")
+            .field("sinks", &state.sinks.len())
+            .field("pending", &state.pending.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use i1_core::testing::host;
+    use wiremock::matchers::{body_partial_json, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    /// A host with `Log4Shell` on 443, scored `cvss`.
+    fn log4shell(cvss: f64) -> HostInfo {
+        host(
+            "192.0.2.1",
+            serde_json::json!({
+                "ports": [443],
+                "org": "Example Corp",
+                "vulns": ["CVE-2021-44228"],
+                "data": [{
+                    "port": 443,
+                    "transport": "tcp",
+                    "vulns": {"CVE-2021-44228": {"cvss": cvss}}
+                }]
+            }),
+        )
+    }
+
+    fn quick() -> RetryConfig {
+        RetryConfig::new()
+            .max_retries(2)
+            .initial_backoff(Duration::from_millis(10))
+    }
+
+    async fn hook(server: &MockServer, status: u16) {
+        Mock::given(method("POST"))
+            .and(path("/hook"))
+            .respond_with(ResponseTemplate::new(status))
+            .mount(server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_payloads() {
+        let server = MockServer::start().await;
+        hook(&server, 200).await;
+        let finding = Finding::new(&log4shell(10.0), "scan");
+        let url = format!("{}/hook", server.uri());
+
+        for format in [
+            PayloadFormat::Json,
+            PayloadFormat::Slack,
+            PayloadFormat::Discord,
+        ] {
+            let webhook = Webhook::new(&url).format(format);
+            webhook.deliver(&finding).await.unwrap();
+        }
+
+        let bodies: Vec<serde_json::Value> = server
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .map(|request| request.body_json().unwrap())
+            .collect();
+        assert_eq!(bodies[0]["ip"], "192.0.2.1");
+        assert_eq!(bodies[0]["level"], "critical");
+        assert_eq!(bodies[0]["risk_score"], 10.0);
+        assert_eq!(bodies[0]["vulns"], serde_json::json!(["CVE-2021-44228"]));
+        assert_eq!(bodies[0]["source"], "scan");
+        assert!(bodies[0]["at"].is_string());
+
+        let summary =
+            "Critical risk on 192.0.2.1 (10.0/10, found by scan), Example Corp: CVE-2021-44228";
+        assert_eq!(bodies[1], serde_json::json!({ "text": summary }));
+        assert_eq!(
+            bodies[2],
+            serde_json::json!({ "username": "i1", "content": summary })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_signature() {
+        let server = MockServer::start().await;
+        hook(&server, 204).await;
+        let webhook = Webhook::new(format!("{}/hook", server.uri()))
+            .secret("s3cret")
+            .header("Authorization", "Bearer token")
+            .unwrap();
+        webhook
+            .deliver(&Finding::new(&log4shell(9.8), "lookup"))
+            .await
+            .unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        let request = &requests[0];
+        assert_eq!(request.headers["authorization"], "Bearer token");
+        let sent = request.headers[SIGNATURE_HEADER].to_str().unwrap();
+        assert_eq!(sent, signature("s3cret", &request.body));
+
+        // Anyone holding the secret can check it
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"s3cret").unwrap();
+        mac.update(&request.body);
+        let hex = sent.strip_prefix("sha256=").unwrap();
+        let digest: Vec<u8> = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect();
+        mac.verify_slice(&digest).unwrap();
+
+        // Known vector: RFC 4231 test case 2
+        assert_eq!(
+            signature("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        assert!(Webhook::new("http://x").header("bad header", "v").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_retry() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/hook"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(2)
+            .mount(&server)
+            .await;
+        hook(&server, 200).await;
+        let finding = Finding::new(&log4shell(9.8), "watch");
+
+        let webhook = Webhook::new(format!("{}/hook", server.uri())).retry(quick());
+        webhook.deliver(&finding).await.unwrap();
+        assert_eq!(server.received_requests().await.unwrap().len(), 3);
+
+        // Client errors are not retried
+        let server = MockServer::start().await;
+        hook(&server, 400).await;
+        let webhook = Webhook::new(format!("{}/hook", server.uri())).retry(quick());
+        assert!(webhook.deliver(&finding).await.is_err());
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+
+        // Nor are they forever
+        let server = MockServer::start().await;
+        hook(&server, 500).await;
+        let webhook = Webhook::new(format!("{}/hook", server.uri())).retry(quick());
+        assert!(webhook.deliver(&finding).await.is_err());
+        assert_eq!(server.received_requests().await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_crossing_threshold() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/hook"))
+            .and(header("content-type", "application/json"))
+            .and(body_partial_json(serde_json::json!({"ip": "192.0.2.1"})))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        let notifier = Notifier::new();
+        notifier.check(&log4shell(9.8), "watch");
+        notifier.notify_on(
+            ThreatLevel::High,
+            Webhook::new(format!("{}/hook", server.uri())),
+        );
+
+        let sent = || async { server.received_requests().await.unwrap().len() };
+        for (cvss, total) in [
+            (5.0, 0), // Below the threshold
+            (7.5, 1), // Crossed it
+            (8.0, 1), // Still above
+            (9.8, 1), // Rose, but the sink is only told once per crossing
+            (3.0, 1), // Dropped below
+            (7.0, 2), // And crossed again
+        ] {
+            notifier.check(&log4shell(cvss), "watch");
+            notifier.flush().await;
+            assert_eq!(sent().await, total, "after CVSS {cvss}");
+        }
+
+        // A broken sink doesn't stop the others, or the caller
+        let notifier = Notifier::new();
+        notifier.notify_on(
+            ThreatLevel::Low,
+            Webhook::new("http://127.0.0.1:9/hook").retry(quick()),
+        );
+        notifier.notify_on(
+            ThreatLevel::Low,
+            Webhook::new(format!("{}/hook", server.uri())),
+        );
+        notifier.check(&log4shell(9.8), "watch");
+        notifier.flush().await;
+        assert_eq!(sent().await, 3);
+    }
+
+    #[test]
+    fn test_format_for_url() {
+        assert_eq!(
+            PayloadFormat::for_url("https://hooks.slack.com/services/T/B/X"),
+            PayloadFormat::Slack
+        );
+        assert_eq!(
+            PayloadFormat::for_url("https://discord.com/api/webhooks/1/abc"),
+            PayloadFormat::Discord
+        );
+        assert_eq!(
+            PayloadFormat::for_url("https://example.com/hook"),
+            PayloadFormat::Json
+        );
+        assert_eq!(PayloadFormat::for_url("not a url"), PayloadFormat::Json);
+    }
+}
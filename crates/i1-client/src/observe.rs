@@ -0,0 +1,328 @@
+//! Hooks that see every provider call.
+//!
+//! A [`CallObserver`] is told about each lookup, search or other call once
+//! it finishes: which provider, what it was asked, how long it took, how it
+//! failed and roughly what it cost. Add one to an [`I1Client`] with
+//! [`I1ClientBuilder::observer`], or wrap a single provider in [`Observed`]
+//! to watch calls made on it directly. Providers themselves don't change.
+//!
+//! [`I1Client`]: crate::I1Client
+//! [`I1ClientBuilder::observer`]: crate::I1ClientBuilder::observer
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use i1_core::{Alert, CreateAlertRequest, HostInfo, I1Error, Result, Trigger};
+use i1_providers::{
+    AlertProvider, DnsProvider, DomainInfo, HostLookup, Provider, ProviderHealth, SearchProvider,
+    SearchResults, VulnInfo, VulnProvider,
+};
+
+/// One finished provider call.
+#[derive(Debug, Clone, Copy)]
+pub struct Call<'a> {
+    /// Provider name, e.g. `shodan`
+    pub provider: &'a str,
+    /// What was asked: `lookup`, `search`, `count`, `vulns`, ...
+    pub operation: &'static str,
+    /// The IP, query, domain or ID it was asked about; empty if none
+    pub target: &'a str,
+    pub elapsed: Duration,
+    /// Why it failed, if it did
+    pub error: Option<&'a I1Error>,
+    /// Query credits it is estimated to have spent
+    pub credits: u32,
+}
+
+/// Told about every call made through a client or an [`Observed`] provider.
+///
+/// Runs on the caller's task once the call returns, so it should be quick.
+pub trait CallObserver: Send + Sync {
+    fn on_call(&self, call: &Call<'_>);
+}
+
+/// Credits a search page is estimated to cost: one, except the first page
+/// of a Shodan search without filters, which is free.
+pub fn search_credits(provider: &str, query: &str, page: Option<u32>) -> u32 {
+    let free = provider == "shodan" && page.unwrap_or(1) <= 1 && !query.contains(':');
+    u32::from(!free)
+}
+
+/// Credits a host lookup is estimated to cost: one, except with Shodan and
+/// the native provider, which don't bill lookups.
+pub fn lookup_credits(provider: &str) -> u32 {
+    u32::from(!matches!(provider, "shodan" | "native"))
+}
+
+/// Run `call` and tell `observers` how it went. `credits` only count when
+/// it succeeds.
+pub(crate) async fn observe<T>(
+    observers: &[Arc<dyn CallObserver>],
+    provider: &str,
+    operation: &'static str,
+    target: &str,
+    credits: u32,
+    call: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    if observers.is_empty() {
+        return call.await;
+    }
+
+    let started = Instant::now();
+    let result = call.await;
+    let event = Call {
+        provider,
+        operation,
+        target,
+        elapsed: started.elapsed(),
+        error: result.as_ref().err(),
+        credits: if result.is_ok() { credits } else { 0 },
+    };
+    for observer in observers {
+        observer.on_call(&event);
+    }
+    result
+}
+
+/// A provider whose calls are reported to an observer.
+///
+/// It implements whichever provider traits the wrapped one does, so a
+/// `Box<dyn HostLookup>` wraps into something that still looks up hosts:
+///
+/// ```rust,ignore
+/// let provider: Box<dyn HostLookup + Send + Sync> = Box::new(shodan);
+/// let provider = Observed::new(provider, audit_log);
+/// provider.lookup_host("8.8.8.8").await?;
+/// ```
+pub struct Observed<P: ?Sized> {
+    observer: Arc<dyn CallObserver>,
+    inner: Box<P>,
+}
+
+impl<P: Provider + ?Sized> Observed<P> {
+    /// Report calls on `inner` to `observer`.
+    pub fn new(inner: Box<P>, observer: impl CallObserver + 'static) -> Self {
+        Self {
+            observer: Arc::new(observer),
+            inner,
+        }
+    }
+
+    /// The wrapped provider.
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+
+    async fn observe<T>(
+        &self,
+        operation: &'static str,
+        target: &str,
+        credits: u32,
+        call: impl Future<Output = Result<T>>,
+    ) -> Result<T> {
+        observe(
+            std::slice::from_ref(&self.observer),
+            self.inner.name(),
+            operation,
+            target,
+            credits,
+            call,
+        )
+        .await
+    }
+}
+
+#[async_trait]
+impl<P: Provider + ?Sized> Provider for Observed<P> {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn display_name(&self) -> &'static str {
+        self.inner.display_name()
+    }
+
+    fn base_url(&self) -> &str {
+        self.inner.base_url()
+    }
+
+    fn is_configured(&self) -> bool {
+        self.inner.is_configured()
+    }
+
+    async fn health_check(&self) -> Result<ProviderHealth> {
+        self.observe("health", "", 0, self.inner.health_check())
+            .await
+    }
+}
+
+#[async_trait]
+impl<P: HostLookup + ?Sized> HostLookup for Observed<P> {
+    async fn lookup_host(&self, ip: &str) -> Result<HostInfo> {
+        let credits = lookup_credits(self.inner.name());
+        self.observe("lookup", ip, credits, self.inner.lookup_host(ip))
+            .await
+    }
+}
+
+#[async_trait]
+impl<P: SearchProvider + ?Sized> SearchProvider for Observed<P> {
+    async fn search(&self, query: &str, page: Option<u32>) -> Result<SearchResults> {
+        let credits = search_credits(self.inner.name(), query, page);
+        self.observe("search", query, credits, self.inner.search(query, page))
+            .await
+    }
+
+    async fn count(&self, query: &str) -> Result<u64> {
+        self.observe("count", query, 0, self.inner.count(query))
+            .await
+    }
+
+    async fn filters(&self) -> Result<Vec<String>> {
+        self.inner.filters().await
+    }
+}
+
+#[async_trait]
+impl<P: VulnProvider + ?Sized> VulnProvider for Observed<P> {
+    async fn vulns_for_ip(&self, ip: &str) -> Result<Vec<VulnInfo>> {
+        self.observe("vulns", ip, 0, self.inner.vulns_for_ip(ip))
+            .await
+    }
+
+    async fn hosts_with_cve(&self, cve: &str) -> Result<SearchResults> {
+        self.observe("search", cve, 1, self.inner.hosts_with_cve(cve))
+            .await
+    }
+}
+
+#[async_trait]
+impl<P: DnsProvider + ?Sized> DnsProvider for Observed<P> {
+    async fn resolve(&self, hostname: &str) -> Result<Vec<IpAddr>> {
+        self.observe("resolve", hostname, 0, self.inner.resolve(hostname))
+            .await
+    }
+
+    async fn reverse(&self, ip: &str) -> Result<Vec<String>> {
+        self.observe("reverse", ip, 0, self.inner.reverse(ip)).await
+    }
+
+    async fn resolve_many(&self, hostnames: &[&str]) -> Result<HashMap<String, Vec<IpAddr>>> {
+        let targets = hostnames.join(",");
+        self.observe("resolve", &targets, 0, self.inner.resolve_many(hostnames))
+            .await
+    }
+
+    async fn reverse_many(&self, ips: &[&str]) -> Result<HashMap<String, Vec<String>>> {
+        let targets = ips.join(",");
+        self.observe("reverse", &targets, 0, self.inner.reverse_many(ips))
+            .await
+    }
+
+    async fn domain_info(&self, domain: &str) -> Result<DomainInfo> {
+        self.observe("domain", domain, 0, self.inner.domain_info(domain))
+            .await
+    }
+}
+
+#[async_trait]
+impl<P: AlertProvider + ?Sized> AlertProvider for Observed<P> {
+    async fn list_alerts(&self) -> Result<Vec<Alert>> {
+        self.observe("list_alerts", "", 0, self.inner.list_alerts())
+            .await
+    }
+
+    async fn create_alert(&self, request: &CreateAlertRequest) -> Result<Alert> {
+        self.observe(
+            "create_alert",
+            &request.name,
+            0,
+            self.inner.create_alert(request),
+        )
+        .await
+    }
+
+    async fn delete_alert(&self, id: &str) -> Result<()> {
+        self.observe("delete_alert", id, 0, self.inner.delete_alert(id))
+            .await
+    }
+
+    async fn enable_trigger(&self, id: &str, trigger: &str) -> Result<()> {
+        self.observe(
+            "enable_trigger",
+            id,
+            0,
+            self.inner.enable_trigger(id, trigger),
+        )
+        .await
+    }
+
+    async fn triggers(&self) -> Result<Vec<Trigger>> {
+        self.observe("triggers", "", 0, self.inner.triggers()).await
+    }
+
+    async fn alert_matches(&self, id: &str) -> Result<SearchResults> {
+        self.observe("alert_matches", id, 0, self.inner.alert_matches(id))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::testing::MockProvider;
+
+    /// A provider that knows one host.
+    fn mock() -> MockProvider {
+        MockProvider::new("shodan").hosts(&["192.0.2.1"])
+    }
+
+    /// Remembers each call as `operation target error credits`.
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl CallObserver for Recorder {
+        fn on_call(&self, call: &Call<'_>) {
+            let error = call.error.map_or("ok", I1Error::kind);
+            self.0.lock().unwrap().push(format!(
+                "{} {} {} {error} {}",
+                call.provider, call.operation, call.target, call.credits
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_observed() {
+        let recorder = Recorder::default();
+        let provider: Box<dyn HostLookup> = Box::new(mock());
+        let provider = Observed::new(provider, recorder.clone());
+
+        assert_eq!(provider.name(), "shodan");
+        provider.lookup_host("192.0.2.1").await.unwrap();
+        provider.lookup_host("192.0.2.2").await.unwrap_err();
+        provider.health_check().await.unwrap();
+
+        let search = Observed::new(Box::new(mock()), recorder.clone());
+        search.search("nginx", None).await.unwrap();
+        search.search("port:22", Some(1)).await.unwrap();
+        search.search("nginx", Some(2)).await.unwrap();
+
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            [
+                "shodan lookup 192.0.2.1 ok 0",
+                "shodan lookup 192.0.2.2 not_found 0",
+                "shodan health  ok 0",
+                "shodan search nginx ok 0",
+                "shodan search port:22 ok 1",
+                "shodan search nginx ok 1",
+            ]
+        );
+    }
+}
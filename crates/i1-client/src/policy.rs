@@ -0,0 +1,370 @@
+//! Which targets may be sent to which providers.
+//!
+//! A [`QueryPolicy`] is checked before the client makes any provider call
+//! about an IP address or domain. Denied targets fail with
+//! [`I1Error::PolicyDenied`] and never leave the machine. Rules apply to one
+//! provider, or to every external provider at once. Local providers
+//! ([`LOCAL_PROVIDERS`]) are never restricted by the external rules.
+//!
+//! Private, loopback and link-local addresses are withheld from external
+//! providers by default, since they can't know anything about them; see
+//! [`QueryPolicy::allow_private`].
+//!
+//! Code that calls a provider directly, without a client, can wrap it in
+//! [`Guarded`] to have the same checks made.
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! use i1_client::{I1Client, QueryPolicy};
+//!
+//! let policy = QueryPolicy::new()
+//!     .deny_external("198.51.100.0/24".parse()?)
+//!     .deny("censys", "corp.example".parse()?);
+//! let client = I1Client::builder()
+//!     .with_provider(shodan)
+//!     .policy(policy)
+//!     .build();
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use i1_core::{validate_ip, HostInfo, I1Error, IpNet, Result};
+use i1_providers::{HostLookup, Provider, ProviderHealth};
+
+/// Providers that answer without sending the target to a third party
+pub const LOCAL_PROVIDERS: &[&str] = &["native"];
+
+/// Addresses external providers never see unless private ones are allowed:
+/// RFC 1918, loopback and link-local space, and their IPv6 counterparts.
+const PRIVATE_NETS: &[&str] = &[
+    "10.0.0.0/8",
+    "172.16.0.0/12",
+    "192.168.0.0/16",
+    "127.0.0.0/8",
+    "169.254.0.0/16",
+    "::1/128",
+    "fe80::/10",
+    "fc00::/7",
+];
+
+/// A target a policy withholds: a block of addresses, or a domain and its
+/// subdomains.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PolicyRule {
+    Net(IpNet),
+    Domain(String),
+}
+
+impl PolicyRule {
+    fn matches(&self, target: &Target) -> bool {
+        match (self, target) {
+            (Self::Net(net), Target::Ip(ip)) => net.contains(ip),
+            (Self::Domain(rule), Target::Domain(domain)) => {
+                domain == rule
+                    || domain
+                        .strip_suffix(rule.as_str())
+                        .is_some_and(|sub| sub.ends_with('.'))
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for PolicyRule {
+    type Err = I1Error;
+
+    /// A CIDR block, a single address, or a domain (`*.` or `.` in front
+    /// is allowed; subdomains are always covered).
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if let Ok(net) = s.parse::<IpNet>() {
+            return Ok(Self::Net(net.trunc()));
+        }
+        if let Ok(ip) = s.parse::<IpAddr>() {
+            return Ok(Self::Net(IpNet::from(ip)));
+        }
+
+        let domain = normalize(s.trim_start_matches("*.").trim_start_matches('.'));
+        let valid = !domain.is_empty()
+            && domain.split('.').all(|label| {
+                !label.is_empty()
+                    && label
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'))
+            });
+        if !valid {
+            return Err(I1Error::Config(format!(
+                "'{s}' is not a CIDR block, IP address or domain"
+            )));
+        }
+        Ok(Self::Domain(domain))
+    }
+}
+
+impl fmt::Display for PolicyRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Net(net) => write!(f, "{net}"),
+            Self::Domain(domain) => write!(f, "{domain}"),
+        }
+    }
+}
+
+/// What a check is about.
+enum Target {
+    Ip(IpAddr),
+    Domain(String),
+}
+
+fn normalize(domain: &str) -> String {
+    domain.trim_end_matches('.').to_ascii_lowercase()
+}
+
+/// Targets withheld from providers.
+#[derive(Debug, Clone, Default)]
+pub struct QueryPolicy {
+    /// Withheld from every external provider
+    external: Vec<PolicyRule>,
+    /// Withheld from one provider, by name
+    providers: HashMap<String, Vec<PolicyRule>>,
+    /// Let external providers see private, loopback and link-local space
+    allow_private: bool,
+}
+
+impl QueryPolicy {
+    /// The default policy: only private space is withheld, and only from
+    /// external providers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Withhold `rule` from every external provider
+    #[must_use]
+    pub fn deny_external(mut self, rule: PolicyRule) -> Self {
+        self.external.push(rule);
+        self
+    }
+
+    /// Withhold `rule` from `provider`, local or not
+    #[must_use]
+    pub fn deny(mut self, provider: impl Into<String>, rule: PolicyRule) -> Self {
+        self.providers
+            .entry(provider.into())
+            .or_default()
+            .push(rule);
+        self
+    }
+
+    /// Let external providers see private, loopback and link-local
+    /// addresses, e.g. for a provider that indexes an internal network
+    #[must_use]
+    pub const fn allow_private(mut self, allow: bool) -> Self {
+        self.allow_private = allow;
+        self
+    }
+
+    /// Whether `provider` sends what it is asked to a third party
+    pub fn is_external(provider: &str) -> bool {
+        !LOCAL_PROVIDERS.contains(&provider)
+    }
+
+    /// Fail with [`I1Error::PolicyDenied`] if `target`, an IP address or a
+    /// domain, may not be sent to `provider`.
+    pub fn check(&self, provider: &str, target: &str) -> Result<()> {
+        let parsed =
+            validate_ip(target).map_or_else(|_| Target::Domain(normalize(target)), Target::Ip);
+
+        let external = Self::is_external(provider);
+        let private =
+            external && !self.allow_private && matches!(&parsed, Target::Ip(ip) if is_private(*ip));
+        let denied = private
+            || (external && self.external.iter().any(|rule| rule.matches(&parsed)))
+            || self
+                .providers
+                .get(provider)
+                .is_some_and(|rules| rules.iter().any(|rule| rule.matches(&parsed)));
+
+        if denied {
+            return Err(I1Error::PolicyDenied {
+                provider: provider.to_string(),
+                target: target.to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// A provider that checks a [`QueryPolicy`] before every host lookup.
+///
+/// A withheld IP fails with [`I1Error::PolicyDenied`] and the wrapped
+/// provider is never called:
+///
+/// ```rust,ignore
+/// let provider: Box<dyn HostLookup + Send + Sync> = Box::new(shodan);
+/// let provider = Guarded::new(provider, QueryPolicy::new());
+/// provider.lookup_host("10.0.0.1").await?; // PolicyDenied
+/// ```
+pub struct Guarded<P: ?Sized> {
+    policy: QueryPolicy,
+    inner: Box<P>,
+}
+
+impl<P: Provider + ?Sized> Guarded<P> {
+    /// Check calls on `inner` against `policy`.
+    pub const fn new(inner: Box<P>, policy: QueryPolicy) -> Self {
+        Self { policy, inner }
+    }
+
+    /// The wrapped provider.
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+}
+
+#[async_trait]
+impl<P: Provider + ?Sized> Provider for Guarded<P> {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn display_name(&self) -> &'static str {
+        self.inner.display_name()
+    }
+
+    fn base_url(&self) -> &str {
+        self.inner.base_url()
+    }
+
+    fn is_configured(&self) -> bool {
+        self.inner.is_configured()
+    }
+
+    async fn health_check(&self) -> Result<ProviderHealth> {
+        self.inner.health_check().await
+    }
+}
+
+#[async_trait]
+impl<P: HostLookup + ?Sized> HostLookup for Guarded<P> {
+    async fn lookup_host(&self, ip: &str) -> Result<HostInfo> {
+        self.policy.check(self.inner.name(), ip)?;
+        self.inner.lookup_host(ip).await
+    }
+}
+
+fn is_private(ip: IpAddr) -> bool {
+    let ip = match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+        IpAddr::V4(_) => ip,
+    };
+    PRIVATE_NETS
+        .iter()
+        .filter_map(|net| net.parse::<IpNet>().ok())
+        .any(|net| net.contains(&ip))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockProvider;
+
+    fn denied(policy: &QueryPolicy, provider: &str, target: &str) -> bool {
+        match policy.check(provider, target) {
+            Ok(()) => false,
+            Err(I1Error::PolicyDenied {
+                provider: p,
+                target: t,
+            }) => {
+                assert_eq!((p.as_str(), t.as_str()), (provider, target));
+                true
+            }
+            Err(e) => panic!("{e}"),
+        }
+    }
+
+    #[test]
+    fn test_default_policy() {
+        let policy = QueryPolicy::new();
+        for ip in [
+            "10.1.2.3",
+            "172.31.0.1",
+            "192.168.1.1",
+            "127.0.0.1",
+            "169.254.1.1",
+        ] {
+            assert!(denied(&policy, "shodan", ip), "{ip}");
+            assert!(!denied(&policy, "native", ip), "{ip}");
+        }
+        for ip in ["::1", "[::1]", "fe80::1", "fd00::1", "::ffff:10.0.0.1"] {
+            assert!(denied(&policy, "censys", ip), "{ip}");
+        }
+        for target in ["8.8.8.8", "172.32.0.1", "2001:4860::8888", "example.com"] {
+            assert!(!denied(&policy, "shodan", target), "{target}");
+        }
+
+        let policy = QueryPolicy::new().allow_private(true);
+        assert!(!denied(&policy, "shodan", "10.1.2.3"));
+    }
+
+    #[tokio::test]
+    async fn test_guarded_lookup() {
+        let shodan = MockProvider::new("shodan").hosts(&["10.0.0.1", "198.51.100.1", "8.8.8.8"]);
+        let policy = QueryPolicy::new().deny_external("198.51.100.0/24".parse().unwrap());
+        let provider = Guarded::new(Box::new(shodan), policy);
+
+        for ip in ["10.0.0.1", "198.51.100.1"] {
+            assert!(matches!(
+                provider.lookup_host(ip).await,
+                Err(I1Error::PolicyDenied { .. })
+            ));
+        }
+        assert_eq!(
+            provider.lookup_host("8.8.8.8").await.unwrap().ip_str,
+            "8.8.8.8"
+        );
+    }
+
+    #[test]
+    fn test_rules() {
+        let policy = QueryPolicy::new()
+            .deny_external("198.51.100.0/24".parse().unwrap())
+            .deny_external("*.Corp.Example".parse().unwrap())
+            .deny("censys", "203.0.113.7".parse().unwrap())
+            .deny("native", "secret.example".parse().unwrap());
+
+        assert!(denied(&policy, "shodan", "198.51.100.20"));
+        assert!(!denied(&policy, "native", "198.51.100.20"));
+        assert!(denied(&policy, "shodan", "corp.example"));
+        assert!(denied(&policy, "criminalip", "vpn.CORP.example."));
+        assert!(!denied(&policy, "shodan", "notcorp.example"));
+
+        assert!(denied(&policy, "censys", "203.0.113.7"));
+        assert!(!denied(&policy, "shodan", "203.0.113.7"));
+        assert!(!denied(&policy, "censys", "203.0.113.8"));
+        assert!(denied(&policy, "native", "www.secret.example"));
+    }
+
+    #[test]
+    fn test_parse_rule() {
+        assert_eq!(
+            "10.1.2.3/8".parse::<PolicyRule>().unwrap().to_string(),
+            "10.0.0.0/8"
+        );
+        assert_eq!(
+            "2001:db8::1".parse::<PolicyRule>().unwrap().to_string(),
+            "2001:db8::1/128"
+        );
+        assert_eq!(
+            ".Example.COM.".parse::<PolicyRule>().unwrap(),
+            PolicyRule::Domain("example.com".to_string())
+        );
+        for bad in ["", "*.", "exa mple.com", "10.0.0.0/33", "a..b"] {
+            assert!(bad.parse::<PolicyRule>().is_err(), "{bad}");
+        }
+    }
+}
@@ -0,0 +1,229 @@
+//! A provider for tests that answers from what it is told, without a network.
+//!
+//! Enabled by the `testing` feature, for crates testing code that takes
+//! an [`I1Client`](crate::I1Client).
+//!
+//! ```rust,ignore
+//! use i1_client::testing::MockProvider;
+//! use i1_client::{I1Client, I1Error};
+//!
+//! let shodan = MockProvider::new("shodan").host("192.0.2.1", json!({ "org": "Example" }));
+//! let client = I1Client::builder()
+//!     .with_provider(shodan)
+//!     .with_provider(MockProvider::new("censys").failing(|| I1Error::Unauthorized))
+//!     .build();
+//! ```
+
+use std::net::IpAddr;
+
+use async_trait::async_trait;
+use i1_core::{HostInfo, I1Error, Result};
+use i1_providers::{
+    DnsProvider, DomainInfo, HealthStatus, HostLookup, Provider, ProviderHealth, SearchProvider,
+    SearchResults,
+};
+use serde_json::{Map, Value};
+
+/// A provider that knows a few hosts and names.
+///
+/// Lookups find the hosts it was given and nothing else; a search finds all
+/// of them, whatever the query. Names resolve to the addresses they were
+/// given. A failing provider fails every call, health checks included, and a
+/// refused target fails just the calls for it.
+#[derive(Debug, Clone)]
+pub struct MockProvider {
+    name: &'static str,
+    hosts: Vec<(String, Value)>,
+    names: Vec<(String, Vec<IpAddr>)>,
+    refused: Vec<(String, fn() -> I1Error)>,
+    failure: Option<fn() -> I1Error>,
+}
+
+impl MockProvider {
+    /// A provider calling itself `name`, knowing nothing yet.
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            hosts: Vec::new(),
+            names: Vec::new(),
+            refused: Vec::new(),
+            failure: None,
+        }
+    }
+
+    /// Know `ip`, answering lookups for it with `fields` and its address.
+    #[must_use]
+    pub fn host(mut self, ip: &str, fields: Value) -> Self {
+        self.hosts.push((ip.to_string(), fields));
+        self
+    }
+
+    /// Know each of `ips`, with nothing but their addresses.
+    #[must_use]
+    pub fn hosts(self, ips: &[&str]) -> Self {
+        ips.iter()
+            .fold(self, |mock, ip| mock.host(ip, Value::Object(Map::new())))
+    }
+
+    /// Resolve `hostname` to `ips`, in that order.
+    ///
+    /// # Panics
+    ///
+    /// If one of `ips` is not an IP address.
+    #[must_use]
+    pub fn resolving(mut self, hostname: &str, ips: &[&str]) -> Self {
+        let ips = ips.iter().map(|ip| ip.parse().expect("an IP address"));
+        self.names.push((hostname.to_string(), ips.collect()));
+        self
+    }
+
+    /// Fail lookups of `target`, or searches and counts for it, with `error`.
+    #[must_use]
+    pub fn refusing(mut self, target: &str, error: fn() -> I1Error) -> Self {
+        self.refused.push((target.to_string(), error));
+        self
+    }
+
+    /// Fail every call with `error`.
+    #[must_use]
+    pub fn failing(mut self, error: fn() -> I1Error) -> Self {
+        self.failure = Some(error);
+        self
+    }
+
+    /// Fail if the whole provider or `target` is meant to.
+    fn check(&self, target: &str) -> Result<()> {
+        let refused = self
+            .refused
+            .iter()
+            .find(|(refused, _)| refused == target)
+            .map(|(_, error)| error);
+        self.failure
+            .as_ref()
+            .or(refused)
+            .map_or(Ok(()), |error| Err(error()))
+    }
+}
+
+/// A known host: its `fields`, at `ip`.
+fn answer(ip: &str, fields: &Value) -> Result<HostInfo> {
+    let mut host = fields.clone();
+    host["ip_str"] = ip.into();
+    Ok(serde_json::from_value(host)?)
+}
+
+#[async_trait]
+impl Provider for MockProvider {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn display_name(&self) -> &'static str {
+        "Mock"
+    }
+
+    fn base_url(&self) -> &'static str {
+        "http://mock.invalid"
+    }
+
+    fn is_configured(&self) -> bool {
+        true
+    }
+
+    async fn health_check(&self) -> Result<ProviderHealth> {
+        self.check("")?;
+        Ok(ProviderHealth {
+            provider: self.name.to_string(),
+            status: HealthStatus::Healthy,
+            latency_ms: Some(0),
+            credits_remaining: None,
+            message: None,
+            plan: None,
+            scan_credits: None,
+            resets_at: None,
+        })
+    }
+}
+
+#[async_trait]
+impl HostLookup for MockProvider {
+    async fn lookup_host(&self, ip: &str) -> Result<HostInfo> {
+        self.check(ip)?;
+        let (ip, fields) = self
+            .hosts
+            .iter()
+            .find(|(known, _)| known == ip)
+            .ok_or_else(|| I1Error::NotFound {
+                resource: ip.to_string(),
+            })?;
+        answer(ip, fields)
+    }
+}
+
+#[async_trait]
+impl SearchProvider for MockProvider {
+    async fn search(&self, query: &str, page: Option<u32>) -> Result<SearchResults> {
+        self.check(query)?;
+        let results = self
+            .hosts
+            .iter()
+            .map(|(ip, fields)| answer(ip, fields))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(SearchResults {
+            provider: self.name.to_string(),
+            total: results.len() as u64,
+            page: page.unwrap_or(1),
+            results,
+            facets: None,
+        })
+    }
+
+    async fn count(&self, query: &str) -> Result<u64> {
+        self.check(query)?;
+        Ok(self.hosts.len() as u64)
+    }
+}
+
+#[async_trait]
+impl DnsProvider for MockProvider {
+    async fn resolve(&self, hostname: &str) -> Result<Vec<IpAddr>> {
+        self.check(hostname)?;
+        Ok(self
+            .names
+            .iter()
+            .find(|(name, _)| name == hostname)
+            .map(|(_, ips)| ips.clone())
+            .unwrap_or_default())
+    }
+
+    async fn reverse(&self, ip: &str) -> Result<Vec<String>> {
+        self.check(ip)?;
+        let ip: IpAddr = ip.parse().map_err(|_| I1Error::InvalidIp(ip.to_string()))?;
+        Ok(self
+            .names
+            .iter()
+            .filter(|(_, ips)| ips.contains(&ip))
+            .map(|(name, _)| name.clone())
+            .collect())
+    }
+
+    /// The domain, with the names it was given under it as subdomains.
+    async fn domain_info(&self, domain: &str) -> Result<DomainInfo> {
+        self.check(domain)?;
+        let suffix = format!(".{domain}");
+        let subdomains = self
+            .names
+            .iter()
+            .filter_map(|(name, _)| name.strip_suffix(&suffix))
+            .map(String::from)
+            .collect();
+        Ok(DomainInfo {
+            domain: domain.to_string(),
+            subdomains,
+            records: Vec::new(),
+            registrar: None,
+            created: None,
+            expires: None,
+        })
+    }
+}
@@ -0,0 +1,634 @@
+//! Country codes and names.
+//!
+//! Providers disagree on how they name a country: one sends `KR`, another
+//! `Korea, Republic of`, a third `South Korea`, and some send a code and a
+//! name that don't match. [`country_code`] turns any of those into the
+//! ISO 3166-1 alpha-2 code, and [`country_name`] gives the one name i1
+//! uses for it, so hosts group and geoblock the same whichever provider
+//! they came from.
+//!
+//! Names are matched without regard to case, accents or punctuation, with
+//! a leading "the" dropped and "Korea, Republic of" read as "Republic of
+//! Korea". Official ISO names and the other spellings providers use are
+//! kept as aliases.
+//!
+//! # Example
+//!
+//! ```rust
+//! use i1_core::country;
+//!
+//! assert_eq!(country::country_code_from_name("Korea, Republic of"), Some("KR"));
+//! assert_eq!(country::country_code("south korea"), Some("KR"));
+//! assert_eq!(country::country_name("kr"), Some("South Korea"));
+//! ```
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// Every ISO 3166-1 country, by alpha-2 code, with its common English
+/// name. `XK` (Kosovo) isn't assigned by ISO but every registry uses it.
+const COUNTRIES: &[(&str, &str)] = &[
+    ("AD", "Andorra"),
+    ("AE", "United Arab Emirates"),
+    ("AF", "Afghanistan"),
+    ("AG", "Antigua and Barbuda"),
+    ("AI", "Anguilla"),
+    ("AL", "Albania"),
+    ("AM", "Armenia"),
+    ("AO", "Angola"),
+    ("AQ", "Antarctica"),
+    ("AR", "Argentina"),
+    ("AS", "American Samoa"),
+    ("AT", "Austria"),
+    ("AU", "Australia"),
+    ("AW", "Aruba"),
+    ("AX", "Åland Islands"),
+    ("AZ", "Azerbaijan"),
+    ("BA", "Bosnia and Herzegovina"),
+    ("BB", "Barbados"),
+    ("BD", "Bangladesh"),
+    ("BE", "Belgium"),
+    ("BF", "Burkina Faso"),
+    ("BG", "Bulgaria"),
+    ("BH", "Bahrain"),
+    ("BI", "Burundi"),
+    ("BJ", "Benin"),
+    ("BL", "Saint Barthélemy"),
+    ("BM", "Bermuda"),
+    ("BN", "Brunei"),
+    ("BO", "Bolivia"),
+    ("BQ", "Caribbean Netherlands"),
+    ("BR", "Brazil"),
+    ("BS", "Bahamas"),
+    ("BT", "Bhutan"),
+    ("BV", "Bouvet Island"),
+    ("BW", "Botswana"),
+    ("BY", "Belarus"),
+    ("BZ", "Belize"),
+    ("CA", "Canada"),
+    ("CC", "Cocos (Keeling) Islands"),
+    ("CD", "DR Congo"),
+    ("CF", "Central African Republic"),
+    ("CG", "Congo"),
+    ("CH", "Switzerland"),
+    ("CI", "Côte d'Ivoire"),
+    ("CK", "Cook Islands"),
+    ("CL", "Chile"),
+    ("CM", "Cameroon"),
+    ("CN", "China"),
+    ("CO", "Colombia"),
+    ("CR", "Costa Rica"),
+    ("CU", "Cuba"),
+    ("CV", "Cape Verde"),
+    ("CW", "Curaçao"),
+    ("CX", "Christmas Island"),
+    ("CY", "Cyprus"),
+    ("CZ", "Czechia"),
+    ("DE", "Germany"),
+    ("DJ", "Djibouti"),
+    ("DK", "Denmark"),
+    ("DM", "Dominica"),
+    ("DO", "Dominican Republic"),
+    ("DZ", "Algeria"),
+    ("EC", "Ecuador"),
+    ("EE", "Estonia"),
+    ("EG", "Egypt"),
+    ("EH", "Western Sahara"),
+    ("ER", "Eritrea"),
+    ("ES", "Spain"),
+    ("ET", "Ethiopia"),
+    ("FI", "Finland"),
+    ("FJ", "Fiji"),
+    ("FK", "Falkland Islands"),
+    ("FM", "Micronesia"),
+    ("FO", "Faroe Islands"),
+    ("FR", "France"),
+    ("GA", "Gabon"),
+    ("GB", "United Kingdom"),
+    ("GD", "Grenada"),
+    ("GE", "Georgia"),
+    ("GF", "French Guiana"),
+    ("GG", "Guernsey"),
+    ("GH", "Ghana"),
+    ("GI", "Gibraltar"),
+    ("GL", "Greenland"),
+    ("GM", "Gambia"),
+    ("GN", "Guinea"),
+    ("GP", "Guadeloupe"),
+    ("GQ", "Equatorial Guinea"),
+    ("GR", "Greece"),
+    ("GS", "South Georgia and the South Sandwich Islands"),
+    ("GT", "Guatemala"),
+    ("GU", "Guam"),
+    ("GW", "Guinea-Bissau"),
+    ("GY", "Guyana"),
+    ("HK", "Hong Kong"),
+    ("HM", "Heard Island and McDonald Islands"),
+    ("HN", "Honduras"),
+    ("HR", "Croatia"),
+    ("HT", "Haiti"),
+    ("HU", "Hungary"),
+    ("ID", "Indonesia"),
+    ("IE", "Ireland"),
+    ("IL", "Israel"),
+    ("IM", "Isle of Man"),
+    ("IN", "India"),
+    ("IO", "British Indian Ocean Territory"),
+    ("IQ", "Iraq"),
+    ("IR", "Iran"),
+    ("IS", "Iceland"),
+    ("IT", "Italy"),
+    ("JE", "Jersey"),
+    ("JM", "Jamaica"),
+    ("JO", "Jordan"),
+    ("JP", "Japan"),
+    ("KE", "Kenya"),
+    ("KG", "Kyrgyzstan"),
+    ("KH", "Cambodia"),
+    ("KI", "Kiribati"),
+    ("KM", "Comoros"),
+    ("KN", "Saint Kitts and Nevis"),
+    ("KP", "North Korea"),
+    ("KR", "South Korea"),
+    ("KW", "Kuwait"),
+    ("KY", "Cayman Islands"),
+    ("KZ", "Kazakhstan"),
+    ("LA", "Laos"),
+    ("LB", "Lebanon"),
+    ("LC", "Saint Lucia"),
+    ("LI", "Liechtenstein"),
+    ("LK", "Sri Lanka"),
+    ("LR", "Liberia"),
+    ("LS", "Lesotho"),
+    ("LT", "Lithuania"),
+    ("LU", "Luxembourg"),
+    ("LV", "Latvia"),
+    ("LY", "Libya"),
+    ("MA", "Morocco"),
+    ("MC", "Monaco"),
+    ("MD", "Moldova"),
+    ("ME", "Montenegro"),
+    ("MF", "Saint Martin"),
+    ("MG", "Madagascar"),
+    ("MH", "Marshall Islands"),
+    ("MK", "North Macedonia"),
+    ("ML", "Mali"),
+    ("MM", "Myanmar"),
+    ("MN", "Mongolia"),
+    ("MO", "Macao"),
+    ("MP", "Northern Mariana Islands"),
+    ("MQ", "Martinique"),
+    ("MR", "Mauritania"),
+    ("MS", "Montserrat"),
+    ("MT", "Malta"),
+    ("MU", "Mauritius"),
+    ("MV", "Maldives"),
+    ("MW", "Malawi"),
+    ("MX", "Mexico"),
+    ("MY", "Malaysia"),
+    ("MZ", "Mozambique"),
+    ("NA", "Namibia"),
+    ("NC", "New Caledonia"),
+    ("NE", "Niger"),
+    ("NF", "Norfolk Island"),
+    ("NG", "Nigeria"),
+    ("NI", "Nicaragua"),
+    ("NL", "Netherlands"),
+    ("NO", "Norway"),
+    ("NP", "Nepal"),
+    ("NR", "Nauru"),
+    ("NU", "Niue"),
+    ("NZ", "New Zealand"),
+    ("OM", "Oman"),
+    ("PA", "Panama"),
+    ("PE", "Peru"),
+    ("PF", "French Polynesia"),
+    ("PG", "Papua New Guinea"),
+    ("PH", "Philippines"),
+    ("PK", "Pakistan"),
+    ("PL", "Poland"),
+    ("PM", "Saint Pierre and Miquelon"),
+    ("PN", "Pitcairn Islands"),
+    ("PR", "Puerto Rico"),
+    ("PS", "Palestine"),
+    ("PT", "Portugal"),
+    ("PW", "Palau"),
+    ("PY", "Paraguay"),
+    ("QA", "Qatar"),
+    ("RE", "Réunion"),
+    ("RO", "Romania"),
+    ("RS", "Serbia"),
+    ("RU", "Russia"),
+    ("RW", "Rwanda"),
+    ("SA", "Saudi Arabia"),
+    ("SB", "Solomon Islands"),
+    ("SC", "Seychelles"),
+    ("SD", "Sudan"),
+    ("SE", "Sweden"),
+    ("SG", "Singapore"),
+    ("SH", "Saint Helena"),
+    ("SI", "Slovenia"),
+    ("SJ", "Svalbard and Jan Mayen"),
+    ("SK", "Slovakia"),
+    ("SL", "Sierra Leone"),
+    ("SM", "San Marino"),
+    ("SN", "Senegal"),
+    ("SO", "Somalia"),
+    ("SR", "Suriname"),
+    ("SS", "South Sudan"),
+    ("ST", "São Tomé and Príncipe"),
+    ("SV", "El Salvador"),
+    ("SX", "Sint Maarten"),
+    ("SY", "Syria"),
+    ("SZ", "Eswatini"),
+    ("TC", "Turks and Caicos Islands"),
+    ("TD", "Chad"),
+    ("TF", "French Southern Territories"),
+    ("TG", "Togo"),
+    ("TH", "Thailand"),
+    ("TJ", "Tajikistan"),
+    ("TK", "Tokelau"),
+    ("TL", "Timor-Leste"),
+    ("TM", "Turkmenistan"),
+    ("TN", "Tunisia"),
+    ("TO", "Tonga"),
+    ("TR", "Turkey"),
+    ("TT", "Trinidad and Tobago"),
+    ("TV", "Tuvalu"),
+    ("TW", "Taiwan"),
+    ("TZ", "Tanzania"),
+    ("UA", "Ukraine"),
+    ("UG", "Uganda"),
+    ("UM", "United States Minor Outlying Islands"),
+    ("US", "United States"),
+    ("UY", "Uruguay"),
+    ("UZ", "Uzbekistan"),
+    ("VA", "Vatican City"),
+    ("VC", "Saint Vincent and the Grenadines"),
+    ("VE", "Venezuela"),
+    ("VG", "British Virgin Islands"),
+    ("VI", "U.S. Virgin Islands"),
+    ("VN", "Vietnam"),
+    ("VU", "Vanuatu"),
+    ("WF", "Wallis and Futuna"),
+    ("WS", "Samoa"),
+    ("XK", "Kosovo"),
+    ("YE", "Yemen"),
+    ("YT", "Mayotte"),
+    ("ZA", "South Africa"),
+    ("ZM", "Zambia"),
+    ("ZW", "Zimbabwe"),
+];
+
+/// Other names a country goes by: ISO's official short names, older names
+/// and the spellings providers send. Matched the way names are (see
+/// [`country_code_from_name`]), so `Korea, Republic of` needs no entry of
+/// its own next to `Republic of Korea`.
+const ALIASES: &[(&str, &str)] = &[
+    ("UK", "GB"),
+    ("Great Britain", "GB"),
+    ("Britain", "GB"),
+    ("England", "GB"),
+    ("United Kingdom of Great Britain and Northern Ireland", "GB"),
+    ("USA", "US"),
+    ("United States of America", "US"),
+    ("America", "US"),
+    ("Republic of Korea", "KR"),
+    ("Korea South", "KR"),
+    ("Democratic People's Republic of Korea", "KP"),
+    ("Korea North", "KP"),
+    ("DPRK", "KP"),
+    ("Russian Federation", "RU"),
+    ("Islamic Republic of Iran", "IR"),
+    ("Viet Nam", "VN"),
+    ("Province of China Taiwan", "TW"),
+    ("Republic of China", "TW"),
+    ("Hong Kong SAR", "HK"),
+    ("China Hong Kong", "HK"),
+    ("Macau", "MO"),
+    ("Macao SAR", "MO"),
+    ("Czech Republic", "CZ"),
+    ("Türkiye", "TR"),
+    ("Republic of Moldova", "MD"),
+    ("United Republic of Tanzania", "TZ"),
+    ("Bolivarian Republic of Venezuela", "VE"),
+    ("Plurinational State of Bolivia", "BO"),
+    ("Syrian Arab Republic", "SY"),
+    ("Lao People's Democratic Republic", "LA"),
+    ("Macedonia", "MK"),
+    ("Republic of North Macedonia", "MK"),
+    ("The former Yugoslav Republic of Macedonia", "MK"),
+    ("Democratic Republic of the Congo", "CD"),
+    ("The Democratic Republic of the Congo", "CD"),
+    ("Congo Kinshasa", "CD"),
+    ("DRC", "CD"),
+    ("Republic of the Congo", "CG"),
+    ("Congo Brazzaville", "CG"),
+    ("Ivory Coast", "CI"),
+    ("State of Palestine", "PS"),
+    ("Palestinian Territory", "PS"),
+    ("Palestinian Territories", "PS"),
+    ("Federated States of Micronesia", "FM"),
+    ("Brunei Darussalam", "BN"),
+    ("Cabo Verde", "CV"),
+    ("Swaziland", "SZ"),
+    ("Burma", "MM"),
+    ("Holland", "NL"),
+    ("Kingdom of the Netherlands", "NL"),
+    ("Holy See", "VA"),
+    ("Holy See Vatican City State", "VA"),
+    ("Vatican", "VA"),
+    ("Libyan Arab Jamahiriya", "LY"),
+    ("East Timor", "TL"),
+    ("Virgin Islands British", "VG"),
+    ("Virgin Islands U.S.", "VI"),
+    ("US Virgin Islands", "VI"),
+    ("Saint Martin French part", "MF"),
+    ("Sint Maarten Dutch part", "SX"),
+    ("Falkland Islands Malvinas", "FK"),
+    ("Bonaire, Sint Eustatius and Saba", "BQ"),
+    ("Saint Helena, Ascension and Tristan da Cunha", "SH"),
+    ("Pitcairn", "PN"),
+    ("Cocos Islands", "CC"),
+    ("Keeling Islands", "CC"),
+    ("Reunion Island", "RE"),
+    ("St Kitts and Nevis", "KN"),
+    ("St Lucia", "LC"),
+    ("St Vincent and the Grenadines", "VC"),
+    ("St Barts", "BL"),
+    ("UAE", "AE"),
+    ("Republic of Kosovo", "XK"),
+];
+
+/// The name i1 uses for the country with this two-letter `code`, in any
+/// case. `UK` is taken for `GB`.
+pub fn country_name(code: &str) -> Option<&'static str> {
+    let code = known_code(code)?;
+    COUNTRIES
+        .iter()
+        .find(|(c, _)| *c == code)
+        .map(|&(_, name)| name)
+}
+
+/// The ISO 3166-1 alpha-2 code of the country called `name`, by its
+/// common name, its official ISO name or an alias.
+///
+/// `Korea, Republic of`, `Republic of Korea`, `South Korea` and `KOREA
+/// (SOUTH)` are all `KR`.
+pub fn country_code_from_name(name: &str) -> Option<&'static str> {
+    let names = names();
+    if let Some(code) = names.get(&key(name)) {
+        return Some(code);
+    }
+    // `Korea, Republic of` is `Republic of Korea`
+    let (head, tail) = name.rsplit_once(',')?;
+    names.get(&key(&format!("{tail} {head}"))).copied()
+}
+
+/// The code of the country `code_or_name` stands for: a two-letter code
+/// in any case, or a name as [`country_code_from_name`] takes it.
+pub fn country_code(code_or_name: &str) -> Option<&'static str> {
+    known_code(code_or_name).or_else(|| country_code_from_name(code_or_name))
+}
+
+/// Whether `code` is a country code, in any case.
+pub fn is_country_code(code: &str) -> bool {
+    known_code(code).is_some()
+}
+
+/// Codes of the countries whose names or codes are closest to what was
+/// given, best first: for "did you mean" when [`country_code`] finds none.
+pub fn suggestions(input: &str) -> Vec<&'static str> {
+    let wanted = key(input);
+    if wanted.is_empty() {
+        return Vec::new();
+    }
+    let mut scored: Vec<(usize, &'static str)> = Vec::new();
+    for (name, &code) in names() {
+        // The whole name or any word of it, so `Korae` finds `South Korea`
+        let distance = std::iter::once(name.as_str())
+            .chain(name.split(' '))
+            .map(|candidate| {
+                if wanted.len() >= 3 && candidate.starts_with(&wanted) {
+                    0
+                } else {
+                    edit_distance(&wanted, candidate)
+                }
+            })
+            .min()
+            .unwrap_or(usize::MAX);
+        // About one typo in four letters
+        if distance <= (wanted.len() / 4).max(1) {
+            match scored.iter_mut().find(|(_, c)| *c == code) {
+                Some(best) => best.0 = best.0.min(distance),
+                None => scored.push((distance, code)),
+            }
+        }
+    }
+    scored.sort_unstable();
+    scored.into_iter().map(|(_, code)| code).take(3).collect()
+}
+
+/// `code` as it appears in the table, if it's there.
+fn known_code(code: &str) -> Option<&'static str> {
+    let code = code.trim();
+    if code.len() != 2 {
+        return None;
+    }
+    if code.eq_ignore_ascii_case("uk") {
+        return Some("GB");
+    }
+    COUNTRIES
+        .iter()
+        .find(|(c, _)| c.eq_ignore_ascii_case(code))
+        .map(|&(c, _)| c)
+}
+
+/// Every name and alias, by [`key`].
+fn names() -> &'static HashMap<String, &'static str> {
+    static NAMES: OnceLock<HashMap<String, &'static str>> = OnceLock::new();
+    NAMES.get_or_init(|| {
+        COUNTRIES
+            .iter()
+            .map(|&(code, name)| (name, code))
+            .chain(ALIASES.iter().copied())
+            .map(|(name, code)| (key(name), code))
+            .collect()
+    })
+}
+
+/// `name` the way names are compared: lowercase letters and digits
+/// without accents, words separated by single spaces, `&` as `and`, `St`
+/// as `saint`, and no leading `the`.
+fn key(name: &str) -> String {
+    let mut folded = String::with_capacity(name.len());
+    for c in name.chars() {
+        match c {
+            '&' => folded.push_str(" and "),
+            '\'' | '’' | '.' => {}
+            c => folded.push(fold(c)),
+        }
+    }
+    let words: Vec<&str> = folded
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect();
+    let words = match words.first() {
+        Some(&"the") => &words[1..],
+        _ => &words[..],
+    };
+    words
+        .iter()
+        .map(|&word| if word == "st" { "saint" } else { word })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// `c` lowercased and without its accent, for the letters country names
+/// use.
+fn fold(c: char) -> char {
+    match c.to_lowercase().next().unwrap_or(c) {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' => 'a',
+        'ç' => 'c',
+        'è' | 'é' | 'ê' | 'ë' => 'e',
+        'ì' | 'í' | 'î' | 'ï' => 'i',
+        'ñ' => 'n',
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' => 'o',
+        'ù' | 'ú' | 'û' | 'ü' => 'u',
+        'ý' | 'ÿ' => 'y',
+        c => c,
+    }
+}
+
+/// Edits between two ASCII keys: letters added, removed or changed, and
+/// neighbours swapped.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let mut before: Vec<usize> = Vec::new();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let mut row = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            row[j] = (previous[j] + 1)
+                .min(row[j - 1] + 1)
+                .min(previous[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(before[j - 2] + 1);
+            }
+        }
+        before = std::mem::replace(&mut previous, row);
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GeoLocation;
+
+    #[test]
+    fn test_aliases_converge() {
+        for alias in [
+            "KR",
+            "kr",
+            "South Korea",
+            "Republic of Korea",
+            "Korea, Republic of",
+            "KOREA, REPUBLIC OF",
+            "Korea (South)",
+            "Korea, South",
+        ] {
+            assert_eq!(country_code(alias), Some("KR"), "{alias}");
+        }
+        assert_eq!(
+            country_code("Korea, Democratic People's Republic of"),
+            Some("KP")
+        );
+        // Every alias points at a country in the table
+        for &(alias, code) in ALIASES {
+            assert!(country_name(code).is_some(), "{alias} -> {code}");
+            assert_eq!(country_code(alias), Some(code), "{alias}");
+        }
+        for &(code, name) in COUNTRIES {
+            assert_eq!(country_code_from_name(name), Some(code), "{name}");
+        }
+    }
+
+    #[test]
+    fn test_provider_spellings() {
+        let spellings = [
+            ("Iran, Islamic Republic of", "IR"),
+            ("Russian Federation", "RU"),
+            ("Viet Nam", "VN"),
+            ("Taiwan, Province of China", "TW"),
+            ("Congo, The Democratic Republic of the", "CD"),
+            ("Cote D'Ivoire", "CI"),
+            ("Curacao", "CW"),
+            ("Reunion", "RE"),
+            ("Netherlands, The", "NL"),
+            ("the Bahamas", "BS"),
+            ("Bosnia & Herzegovina", "BA"),
+            ("St. Vincent and the Grenadines", "VC"),
+            ("  united states  ", "US"),
+            ("Moldova, Republic of", "MD"),
+            ("Micronesia, Federated States of", "FM"),
+            ("Virgin Islands, U.S.", "VI"),
+        ];
+        for (spelling, code) in spellings {
+            assert_eq!(country_code(spelling), Some(code), "{spelling}");
+        }
+        assert_eq!(country_code("Atlantis"), None);
+        assert_eq!(country_code("ZZ"), None);
+        assert_eq!(country_code(""), None);
+    }
+
+    #[test]
+    fn test_country_name() {
+        assert_eq!(country_name("kr"), Some("South Korea"));
+        assert_eq!(country_name("UK"), Some("United Kingdom"));
+        assert_eq!(country_name("ZZ"), None);
+        assert!(is_country_code("Is"));
+        assert!(!is_country_code("Iceland"));
+    }
+
+    #[test]
+    fn test_suggestions() {
+        assert_eq!(suggestions("Korae"), ["KP", "KR"]);
+        assert_eq!(suggestions("Germny"), ["DE"]);
+        assert_eq!(suggestions("Icel"), ["IS"]);
+        assert!(suggestions("qqqqqqqq").is_empty());
+    }
+
+    #[test]
+    fn test_normalize_location() {
+        let mut location = GeoLocation {
+            country_name: Some("Korea, Republic of".to_string()),
+            ..GeoLocation::default()
+        };
+        location.normalize();
+        assert_eq!(location.country_code.as_deref(), Some("KR"));
+        assert_eq!(location.country_name.as_deref(), Some("South Korea"));
+
+        // A valid code wins over a name that disagrees
+        let mut location = GeoLocation {
+            country_code: Some("de".to_string()),
+            country_name: Some("France".to_string()),
+            city: Some("  ".to_string()),
+            ..GeoLocation::default()
+        };
+        location.normalize();
+        assert_eq!(location.country_code.as_deref(), Some("DE"));
+        assert_eq!(location.country_name.as_deref(), Some("Germany"));
+        assert_eq!(location.city, None);
+
+        // Nothing to go on is left alone
+        let mut location = GeoLocation {
+            country_code: Some("EU".to_string()),
+            ..GeoLocation::default()
+        };
+        location.normalize();
+        assert_eq!(location.country_code.as_deref(), Some("EU"));
+        assert_eq!(location.country_name, None);
+    }
+}
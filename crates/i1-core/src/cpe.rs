@@ -0,0 +1,373 @@
+//! CPE 2.3 identifiers for services.
+//!
+//! Vulnerability feeds key products by CPE, so every [`Service`] should
+//! carry one. Providers that send CPEs (Shodan's banners have CPE 2.2 URIs)
+//! are normalized to the 2.3 formatted string with [`parse`]; for the rest
+//! [`from_product`] derives one from the product name and version, using a
+//! small table of the vendor and product names NVD files them under. A
+//! product the table doesn't know gets a wildcard vendor and its own name,
+//! sanitized, which is still enough to match on.
+//!
+//! # Example
+//!
+//! ```rust
+//! use i1_core::cpe;
+//!
+//! assert_eq!(
+//!     cpe::from_product("OpenSSH", "8.9"),
+//!     "cpe:2.3:a:openbsd:openssh:8.9:*:*:*:*:*:*:*"
+//! );
+//! assert_eq!(
+//!     cpe::parse("cpe:/a:apache:http_server:2.4.41").as_deref(),
+//!     Some("cpe:2.3:a:apache:http_server:2.4.41:*:*:*:*:*:*:*")
+//! );
+//! ```
+
+use crate::Service;
+
+/// Components after `cpe:2.3:`: part, vendor, product, version, update,
+/// edition, language, `sw_edition`, `target_sw`, `target_hw` and other
+const COMPONENTS: usize = 11;
+
+/// Product names as providers and fingerprints report them, lowercased, and
+/// the part, vendor and product NVD uses. A name matches its entry exactly
+/// or followed by more words, e.g. `redis key-value store`.
+const PRODUCTS: &[(&str, &str, &str, &str)] = &[
+    ("openssh", "a", "openbsd", "openssh"),
+    ("dropbear", "a", "dropbear_ssh_project", "dropbear_ssh"),
+    ("dropbear sshd", "a", "dropbear_ssh_project", "dropbear_ssh"),
+    ("libssh", "a", "libssh", "libssh"),
+    ("vsftpd", "a", "beasts", "vsftpd"),
+    ("proftpd", "a", "proftpd", "proftpd"),
+    ("pure-ftpd", "a", "pureftpd", "pure-ftpd"),
+    (
+        "filezilla server",
+        "a",
+        "filezilla-project",
+        "filezilla_server",
+    ),
+    ("samba", "a", "samba", "samba"),
+    ("exim", "a", "exim", "exim"),
+    ("postfix", "a", "postfix", "postfix"),
+    ("sendmail", "a", "sendmail", "sendmail"),
+    ("microsoft exchange", "a", "microsoft", "exchange_server"),
+    ("opensmtpd", "a", "openbsd", "opensmtpd"),
+    ("dovecot", "a", "dovecot", "dovecot"),
+    ("openresty", "a", "openresty", "openresty"),
+    ("nginx", "a", "f5", "nginx"),
+    ("apache httpd", "a", "apache", "http_server"),
+    ("apache tomcat", "a", "apache", "tomcat"),
+    (
+        "microsoft iis",
+        "a",
+        "microsoft",
+        "internet_information_services",
+    ),
+    (
+        "microsoft iis httpd",
+        "a",
+        "microsoft",
+        "internet_information_services",
+    ),
+    ("lighttpd", "a", "lighttpd", "lighttpd"),
+    ("litespeed", "a", "litespeedtech", "litespeed_web_server"),
+    ("caddy", "a", "caddyserver", "caddy"),
+    ("jetty", "a", "eclipse", "jetty"),
+    ("gunicorn", "a", "gunicorn", "gunicorn"),
+    ("werkzeug", "a", "palletsprojects", "werkzeug"),
+    ("envoy", "a", "envoyproxy", "envoy"),
+    ("squid", "a", "squid-cache", "squid"),
+    ("webmin", "a", "webmin", "webmin"),
+    ("jenkins", "a", "jenkins", "jenkins"),
+    ("mariadb", "a", "mariadb", "mariadb"),
+    ("mysql", "a", "oracle", "mysql"),
+    ("postgresql", "a", "postgresql", "postgresql"),
+    ("mongodb", "a", "mongodb", "mongodb"),
+    ("redis", "a", "redis", "redis"),
+    ("memcached", "a", "memcached", "memcached"),
+    ("elasticsearch", "a", "elastic", "elasticsearch"),
+    ("couchdb", "a", "apache", "couchdb"),
+    ("zookeeper", "a", "apache", "zookeeper"),
+    ("mosquitto", "a", "eclipse", "mosquitto"),
+    ("rabbitmq", "a", "vmware", "rabbitmq"),
+    ("asterisk", "a", "digium", "asterisk"),
+    ("dnsmasq", "a", "thekelleys", "dnsmasq"),
+    ("isc bind", "a", "isc", "bind"),
+    ("openssl", "a", "openssl", "openssl"),
+    ("mikrotik routeros", "o", "mikrotik", "routeros"),
+];
+
+/// The CPE 2.3 identifier for `product` at `version`, or at any version
+/// when `version` is empty. Products missing from the table get a wildcard
+/// vendor.
+#[must_use]
+pub fn from_product(product: &str, version: &str) -> String {
+    let name = product.trim().to_lowercase();
+    let version = version
+        .split_whitespace()
+        .next()
+        .map_or_else(|| "*".to_string(), escape);
+    match lookup(&name) {
+        Some((part, vendor, product)) => format_cpe(&[part, vendor, product, &version]),
+        None => format_cpe(&["a", "*", &escape(&name), &version]),
+    }
+}
+
+/// The longest table entry `name` starts with, on a word boundary.
+fn lookup(name: &str) -> Option<(&'static str, &'static str, &'static str)> {
+    PRODUCTS
+        .iter()
+        .filter(|(key, ..)| {
+            name.strip_prefix(key)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with(' '))
+        })
+        .max_by_key(|(key, ..)| key.len())
+        .map(|&(_, part, vendor, product)| (part, vendor, product))
+}
+
+/// `s` as a CPE 2.3 formatted string: a 2.3 string is checked and kept,
+/// a 2.2 URI (`cpe:/a:vendor:product:version`) is converted. Anything else
+/// is `None`.
+#[must_use]
+pub fn parse(s: &str) -> Option<String> {
+    let s = s.trim();
+    if let Some(rest) = s.strip_prefix("cpe:2.3:") {
+        let components = split(rest);
+        let part = components.first()?;
+        if components.len() != COMPONENTS || !matches!(part.as_str(), "a" | "o" | "h") {
+            return None;
+        }
+        return Some(s.to_string());
+    }
+
+    let rest = s.strip_prefix("cpe:/")?;
+    let components: Vec<String> = rest
+        .split(':')
+        .map(|c| {
+            if c.is_empty() {
+                "*".to_string()
+            } else {
+                escape(&percent_decode(c))
+            }
+        })
+        .collect();
+    let part = components.first()?;
+    if components.len() > 7 || !matches!(part.as_str(), "a" | "o" | "h") {
+        return None;
+    }
+    let components: Vec<&str> = components.iter().map(String::as_str).collect();
+    Some(format_cpe(&components))
+}
+
+/// Normalize `service`'s CPEs to CPE 2.3, dropping any that don't parse,
+/// and derive one from its product and version if it has none. Returns
+/// whether it has any.
+pub fn fill(service: &mut Service) -> bool {
+    let mut cpes: Vec<String> = service.cpe.iter().filter_map(|c| parse(c)).collect();
+    if cpes.is_empty() {
+        if let Some(product) = service.product.as_deref().filter(|p| !p.trim().is_empty()) {
+            cpes.push(from_product(
+                product,
+                service.version.as_deref().unwrap_or(""),
+            ));
+        }
+    }
+    cpes.sort();
+    cpes.dedup();
+    service.cpe = cpes;
+    !service.cpe.is_empty()
+}
+
+/// `cpe:2.3:` and the given components, padded with `*`.
+fn format_cpe(components: &[&str]) -> String {
+    let mut cpe = "cpe:2.3".to_string();
+    for i in 0..COMPONENTS {
+        cpe.push(':');
+        cpe.push_str(components.get(i).copied().unwrap_or("*"));
+    }
+    cpe
+}
+
+/// A component's value in a formatted string: lowercased, spaces as `_`,
+/// and punctuation other than `_`, `-` and `.` backslash-escaped.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.trim().to_lowercase().chars() {
+        match c {
+            c if c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.') => escaped.push(c),
+            c if c.is_whitespace() => escaped.push('_'),
+            c if c.is_ascii_punctuation() => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            // Not representable
+            _ => {}
+        }
+    }
+    if escaped.is_empty() {
+        "*".to_string()
+    } else {
+        escaped
+    }
+}
+
+/// Decode the `%xx` escapes of a 2.2 URI component.
+fn percent_decode(component: &str) -> String {
+    let mut decoded = String::with_capacity(component.len());
+    let mut chars = component.chars();
+    while let Some(c) = chars.next() {
+        if c == '%' {
+            let hex: String = chars.clone().take(2).collect();
+            if let Ok(byte) = u8::from_str_radix(&hex, 16) {
+                decoded.push(char::from(byte));
+                chars.nth(1);
+                continue;
+            }
+        }
+        decoded.push(c);
+    }
+    decoded
+}
+
+/// The components of a formatted string, split on colons that aren't
+/// escaped.
+fn split(rest: &str) -> Vec<String> {
+    let mut components = vec![String::new()];
+    let mut chars = rest.chars();
+    while let Some(c) = chars.next() {
+        let current = components.last_mut().expect("starts with one");
+        match c {
+            '\\' => {
+                current.push(c);
+                if let Some(next) = chars.next() {
+                    current.push(next);
+                }
+            }
+            ':' => components.push(String::new()),
+            c => current.push(c),
+        }
+    }
+    components
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_product() {
+        let cases = [
+            (
+                "OpenSSH",
+                "8.9",
+                "cpe:2.3:a:openbsd:openssh:8.9:*:*:*:*:*:*:*",
+            ),
+            (
+                "Apache httpd",
+                "2.4.41 (Ubuntu)",
+                "cpe:2.3:a:apache:http_server:2.4.41:*:*:*:*:*:*:*",
+            ),
+            ("nginx", "", "cpe:2.3:a:f5:nginx:*:*:*:*:*:*:*:*"),
+            (
+                "Microsoft IIS httpd",
+                "10.0",
+                "cpe:2.3:a:microsoft:internet_information_services:10.0:*:*:*:*:*:*:*",
+            ),
+            // The longest entry wins, and more words after it still match
+            (
+                "Apache Tomcat",
+                "9.0.31",
+                "cpe:2.3:a:apache:tomcat:9.0.31:*:*:*:*:*:*:*",
+            ),
+            (
+                "Redis key-value store",
+                "6.0.9",
+                "cpe:2.3:a:redis:redis:6.0.9:*:*:*:*:*:*:*",
+            ),
+            (
+                "MikroTik RouterOS",
+                "6.48.3",
+                "cpe:2.3:o:mikrotik:routeros:6.48.3:*:*:*:*:*:*:*",
+            ),
+        ];
+        for (product, version, expected) in cases {
+            assert_eq!(from_product(product, version), expected, "{product}");
+        }
+    }
+
+    #[test]
+    fn test_from_unknown_product() {
+        // Not in the table: wildcard vendor, sanitized product
+        assert_eq!(
+            from_product("Acme Web/Appliance", "1.2-beta"),
+            "cpe:2.3:a:*:acme_web\\/appliance:1.2-beta:*:*:*:*:*:*:*"
+        );
+        assert_eq!(
+            from_product("OpenSSHish", "1.0"),
+            "cpe:2.3:a:*:opensshish:1.0:*:*:*:*:*:*:*"
+        );
+        // Characters a CPE can't hold are dropped
+        assert_eq!(
+            from_product("Caddy™", "2"),
+            "cpe:2.3:a:*:caddy:2:*:*:*:*:*:*:*"
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            parse("cpe:/a:openbsd:openssh:7.4").as_deref(),
+            Some("cpe:2.3:a:openbsd:openssh:7.4:*:*:*:*:*:*:*")
+        );
+        assert_eq!(
+            parse("cpe:/o:linux:linux_kernel").as_deref(),
+            Some("cpe:2.3:o:linux:linux_kernel:*:*:*:*:*:*:*:*")
+        );
+        assert_eq!(
+            parse("cpe:/a:microsoft:asp.net%21").as_deref(),
+            Some("cpe:2.3:a:microsoft:asp.net\\!:*:*:*:*:*:*:*:*")
+        );
+
+        let v23 = "cpe:2.3:a:vendor:prod\\:uct:1.0:*:*:*:*:*:*:*";
+        assert_eq!(parse(v23).as_deref(), Some(v23));
+
+        for bad in [
+            "",
+            "openssh 7.4",
+            "cpe:/x:openbsd:openssh",
+            "cpe:2.3:a:openbsd:openssh:7.4",
+            "cpe:/a:1:2:3:4:5:6:7",
+        ] {
+            assert_eq!(parse(bad), None, "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_fill() {
+        let mut service: Service = serde_json::from_value(serde_json::json!({
+            "port": 22,
+            "product": "OpenSSH",
+            "version": "7.4",
+            "cpe": ["cpe:/a:openbsd:openssh:7.4", "not a cpe"],
+        }))
+        .unwrap();
+        assert!(fill(&mut service));
+        assert_eq!(service.cpe, ["cpe:2.3:a:openbsd:openssh:7.4:*:*:*:*:*:*:*"]);
+
+        // Derived when the provider sent none
+        service.cpe.clear();
+        service.product = Some("Dropbear sshd".to_string());
+        service.version = Some("2020.81".to_string());
+        assert!(fill(&mut service));
+        assert_eq!(
+            service.cpe,
+            ["cpe:2.3:a:dropbear_ssh_project:dropbear_ssh:2020.81:*:*:*:*:*:*:*"]
+        );
+
+        // Nothing to go on
+        service.cpe.clear();
+        service.product = None;
+        assert!(!fill(&mut service));
+        assert!(service.cpe.is_empty());
+    }
+}
@@ -60,6 +60,17 @@ pub enum I1Error {
     #[error("JSON error: {0}")]
     Json(#[from] serde_json::Error),
 
+    /// A provider answered with JSON that no longer matches its schema
+    #[error("unexpected {provider} response from {endpoint}: {detail}")]
+    Schema {
+        /// Provider name
+        provider: String,
+        /// The endpoint that answered
+        endpoint: String,
+        /// Where the response stopped matching, and how
+        detail: String,
+    },
+
     /// Invalid IP address format
     #[error("invalid IP address: {0}")]
     InvalidIp(String),
@@ -100,6 +111,31 @@ pub enum I1Error {
     #[error("no providers available for this operation")]
     NoProviders,
 
+    /// A query policy forbids sending the target to the provider
+    #[error("policy forbids sending {target} to {provider}")]
+    PolicyDenied {
+        /// Provider name
+        provider: String,
+        /// The IP address or domain that was withheld
+        target: String,
+    },
+
+    /// A local reconnaissance tool failed: a scan, lookup, trace or capture
+    #[error("{error}")]
+    Recon {
+        /// What went wrong, for telling failures apart without the recon crate
+        kind: ReconKind,
+        /// The recon crate's own error, which can be downcast back to it
+        error: Box<dyn std::error::Error + Send + Sync>,
+    },
+
+    /// Every provider asked failed, each for its own reason
+    #[error("every provider failed: {}", summarize(.failures))]
+    AllProvidersFailed {
+        /// Each provider's name and error, in the order they were asked
+        failures: Vec<(String, Self)>,
+    },
+
     /// Generic internal error
     #[error("internal error: {0}")]
     Internal(String),
@@ -111,7 +147,13 @@ impl I1Error {
     pub const fn is_retryable(&self) -> bool {
         matches!(
             self,
-            Self::RateLimited { .. } | Self::Timeout(_) | Self::Connection(_)
+            Self::RateLimited { .. }
+                | Self::Timeout(_)
+                | Self::Connection(_)
+                | Self::Recon {
+                    kind: ReconKind::Timeout | ReconKind::Network,
+                    ..
+                }
         )
     }
 
@@ -133,6 +175,34 @@ impl I1Error {
         }
     }
 
+    /// A short, stable name for the kind of error, e.g. `rate_limited`
+    #[must_use]
+    pub const fn kind(&self) -> &'static str {
+        match self {
+            Self::Unauthorized => "unauthorized",
+            Self::RateLimited { .. } => "rate_limited",
+            Self::InsufficientCredits { .. } => "insufficient_credits",
+            Self::NotFound { .. } => "not_found",
+            Self::Provider { .. } => "provider",
+            Self::Http(_) => "http",
+            Self::Timeout(_) => "timeout",
+            Self::Connection(_) => "connection",
+            Self::Json(_) => "json",
+            Self::Schema { .. } => "schema",
+            Self::InvalidIp(_) | Self::InvalidQuery(_) | Self::InvalidUrl(_) => "invalid_input",
+            Self::Config(_) => "config",
+            Self::Scan(_) => "scan",
+            Self::Whois(_) => "whois",
+            Self::Dns(_) => "dns",
+            Self::Trace(_) => "trace",
+            Self::ProviderNotConfigured(_) | Self::NoProviders => "no_provider",
+            Self::PolicyDenied { .. } => "policy_denied",
+            Self::Recon { kind, .. } => kind.name(),
+            Self::AllProvidersFailed { .. } => "all_failed",
+            Self::Internal(_) => "internal",
+        }
+    }
+
     /// Create a provider error
     pub fn provider(provider: impl Into<String>, code: u16, message: impl Into<String>) -> Self {
         Self::Provider {
@@ -141,4 +211,72 @@ impl I1Error {
             message: message.into(),
         }
     }
+
+    /// The error for a call every provider in `failures` failed: the error
+    /// itself if there was only one provider, [`Self::NoProviders`] if there
+    /// were none.
+    #[must_use]
+    pub fn all_failed(mut failures: Vec<(String, Self)>) -> Self {
+        match failures.len() {
+            0 => Self::NoProviders,
+            1 => failures.remove(0).1,
+            _ => Self::AllProvidersFailed { failures },
+        }
+    }
+}
+
+/// What a reconnaissance error was about; see [`I1Error::Recon`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReconKind {
+    /// Needs root or a capability the process doesn't have
+    Privileges,
+    /// Target outside the scan scope
+    OutOfScope,
+    /// No answer in time
+    Timeout,
+    /// A database file that isn't there
+    MissingDatabase,
+    /// A connection that failed or was cut off
+    Network,
+    /// Any other I/O error, such as a file that can't be read
+    Io,
+    /// Input that doesn't parse
+    Parse,
+    /// A port scan failed
+    Scan,
+    /// A WHOIS lookup failed
+    Whois,
+    /// A DNS lookup failed
+    Dns,
+    /// A traceroute failed
+    Trace,
+}
+
+impl ReconKind {
+    /// A short, stable name, as [`I1Error::kind`] gives it
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Privileges => "insufficient_privileges",
+            Self::OutOfScope => "out_of_scope",
+            Self::Timeout => "timeout",
+            Self::MissingDatabase => "missing_database",
+            Self::Network => "connection",
+            Self::Io => "io",
+            Self::Parse => "invalid_input",
+            Self::Scan => "scan",
+            Self::Whois => "whois",
+            Self::Dns => "dns",
+            Self::Trace => "trace",
+        }
+    }
+}
+
+/// `censys: ...; shodan: ...`
+fn summarize(failures: &[(String, I1Error)]) -> String {
+    failures
+        .iter()
+        .map(|(provider, error)| format!("{provider}: {error}"))
+        .collect::<Vec<_>>()
+        .join("; ")
 }
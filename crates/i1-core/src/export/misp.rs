@@ -0,0 +1,454 @@
+//! MISP events from search results.
+//!
+//! Each host's address becomes an `ip-dst` attribute, commented with its
+//! open ports and tagged with the host's tags. Hostnames and domains become
+//! `hostname` and `domain` attributes, and CVEs `vulnerability` attributes,
+//! each commented with the addresses it was seen on. MISP refuses the same
+//! value twice in one event, so names and CVEs shared by several hosts are
+//! one attribute.
+//!
+//! [`MispEvent::body`] is the JSON `POST /events` takes. With the `misp`
+//! feature, [`MispClient`] sends it.
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! use i1_core::export::misp::{self, Distribution};
+//!
+//! let event = misp::to_event("nginx on example.com", &results.results)
+//!     .orgc("Example CERT")
+//!     .distribution(Distribution::Community)
+//!     .tag("tlp:amber");
+//! println!("{}", event.body());
+//! ```
+
+use std::collections::HashMap;
+
+use chrono::{NaiveDate, Utc};
+use serde::{Serialize, Serializer};
+
+use crate::HostInfo;
+
+/// Who a MISP event is shared with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Distribution {
+    /// This organisation only
+    #[default]
+    Organisation,
+    /// Organisations on this MISP instance
+    Community,
+    /// This community and instances it syncs with
+    Connected,
+    /// Everyone
+    All,
+}
+
+impl Serialize for Distribution {
+    /// MISP's number for it, as a string like MISP writes it.
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let level = match self {
+            Self::Organisation => "0",
+            Self::Community => "1",
+            Self::Connected => "2",
+            Self::All => "3",
+        };
+        serializer.serialize_str(level)
+    }
+}
+
+/// How serious a MISP event is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThreatLevel {
+    /// CVEs with a CVSS of 7 or more
+    High,
+    /// CVEs with a CVSS of 4 or more
+    Medium,
+    /// Only minor CVEs
+    Low,
+    /// No CVEs
+    Undefined,
+}
+
+impl ThreatLevel {
+    /// The level for the worst risk score among `hosts`.
+    #[must_use]
+    pub fn of(hosts: &[HostInfo]) -> Self {
+        let worst = hosts.iter().map(HostInfo::risk_score).fold(0.0, f64::max);
+        if worst >= 7.0 {
+            Self::High
+        } else if worst >= 4.0 {
+            Self::Medium
+        } else if worst > 0.0 {
+            Self::Low
+        } else {
+            Self::Undefined
+        }
+    }
+}
+
+impl Serialize for ThreatLevel {
+    /// MISP's number for it, as a string like MISP writes it.
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let level = match self {
+            Self::High => "1",
+            Self::Medium => "2",
+            Self::Low => "3",
+            Self::Undefined => "4",
+        };
+        serializer.serialize_str(level)
+    }
+}
+
+/// A MISP event.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MispEvent {
+    /// The event's title
+    pub info: String,
+    pub date: NaiveDate,
+    pub distribution: Distribution,
+    #[serde(rename = "threat_level_id")]
+    pub threat_level: ThreatLevel,
+    /// Organisation that created the event, if not the one pushing it
+    #[serde(rename = "Orgc", skip_serializing_if = "Option::is_none")]
+    pub orgc: Option<MispOrg>,
+    #[serde(rename = "Attribute")]
+    pub attributes: Vec<MispAttribute>,
+    #[serde(rename = "Tag", skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<MispTag>,
+}
+
+/// A MISP organisation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MispOrg {
+    pub name: String,
+}
+
+/// A MISP tag, such as `tlp:amber`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MispTag {
+    pub name: String,
+}
+
+/// One value in a MISP event.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MispAttribute {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub category: String,
+    pub value: String,
+    /// Whether IDSes should alert on it. Hosts found by a search are
+    /// exposed, not hostile, so never.
+    pub to_ids: bool,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub comment: String,
+    #[serde(rename = "Tag", skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<MispTag>,
+}
+
+impl MispEvent {
+    /// Share the event with more than this organisation.
+    #[must_use]
+    pub const fn distribution(mut self, distribution: Distribution) -> Self {
+        self.distribution = distribution;
+        self
+    }
+
+    /// Set the threat level instead of taking it from the hosts' risk.
+    #[must_use]
+    pub const fn threat_level(mut self, threat_level: ThreatLevel) -> Self {
+        self.threat_level = threat_level;
+        self
+    }
+
+    /// Name the organisation that created the event.
+    #[must_use]
+    pub fn orgc(mut self, name: impl Into<String>) -> Self {
+        self.orgc = Some(MispOrg { name: name.into() });
+        self
+    }
+
+    /// Tag the whole event.
+    #[must_use]
+    pub fn tag(mut self, name: impl Into<String>) -> Self {
+        self.tags.push(MispTag { name: name.into() });
+        self
+    }
+
+    /// Date the event; it is today otherwise.
+    #[must_use]
+    pub const fn date(mut self, date: NaiveDate) -> Self {
+        self.date = date;
+        self
+    }
+
+    /// The JSON body for `POST /events`.
+    #[must_use]
+    pub fn body(&self) -> serde_json::Value {
+        serde_json::json!({ "Event": self })
+    }
+}
+
+/// An event titled `title` with the attributes of `hosts`, shared with this
+/// organisation only and as threatening as the riskiest host. Hosts without
+/// a valid IP address are left out.
+#[must_use]
+pub fn to_event(title: impl Into<String>, hosts: &[HostInfo]) -> MispEvent {
+    let mut attributes = Attributes::default();
+    for host in hosts {
+        let Some(ip) = host.ip_addr() else {
+            continue;
+        };
+        let ip = ip.to_string();
+
+        let mut ports = host.ports.clone();
+        ports.sort_unstable();
+        ports.dedup();
+        let ports: Vec<String> = ports.iter().map(ToString::to_string).collect();
+        let ip_dst = attributes.add("ip-dst", "Network activity", &ip);
+        if !ports.is_empty() {
+            ip_dst.comment = format!("ports {}", ports.join(", "));
+        }
+        ip_dst
+            .tags
+            .extend(host.tags.iter().map(|name| MispTag { name: name.clone() }));
+
+        for name in &host.hostnames {
+            attributes.seen_on("hostname", "Network activity", name, &ip);
+        }
+        for name in &host.domains {
+            attributes.seen_on("domain", "Network activity", name, &ip);
+        }
+        for cve in host.vuln_scores().into_keys() {
+            attributes.seen_on("vulnerability", "External analysis", &cve, &ip);
+        }
+    }
+
+    MispEvent {
+        info: title.into(),
+        date: Utc::now().date_naive(),
+        distribution: Distribution::default(),
+        threat_level: ThreatLevel::of(hosts),
+        orgc: None,
+        attributes: attributes.list,
+        tags: Vec::new(),
+    }
+}
+
+/// Attributes for an event, each type and value added once.
+#[derive(Default)]
+struct Attributes {
+    list: Vec<MispAttribute>,
+    index: HashMap<(&'static str, String), usize>,
+}
+
+impl Attributes {
+    /// The attribute for this type and value, added if it is new.
+    fn add(&mut self, kind: &'static str, category: &str, value: &str) -> &mut MispAttribute {
+        let list = &mut self.list;
+        let i = *self
+            .index
+            .entry((kind, value.to_string()))
+            .or_insert_with(|| {
+                list.push(MispAttribute {
+                    kind: kind.to_string(),
+                    category: category.to_string(),
+                    value: value.to_string(),
+                    to_ids: false,
+                    comment: String::new(),
+                    tags: Vec::new(),
+                });
+                list.len() - 1
+            });
+        &mut self.list[i]
+    }
+
+    /// Add an attribute seen on `ip`, or add `ip` to where it was seen.
+    fn seen_on(&mut self, kind: &'static str, category: &str, value: &str, ip: &str) {
+        let attribute = self.add(kind, category, value);
+        if attribute.comment.is_empty() {
+            attribute.comment = format!("seen on {ip}");
+        } else if !attribute.comment.split([' ', ',']).any(|seen| seen == ip) {
+            attribute.comment.push_str(", ");
+            attribute.comment.push_str(ip);
+        }
+    }
+}
+
+/// Pushes events to a MISP instance.
+#[cfg(feature = "misp")]
+#[derive(Debug, Clone)]
+pub struct MispClient {
+    http: reqwest::Client,
+    base_url: String,
+    api_key: String,
+}
+
+#[cfg(feature = "misp")]
+impl MispClient {
+    /// A client for the MISP at `base_url`, such as `https://misp.example`,
+    /// authenticating with an automation key.
+    pub fn new(base_url: impl Into<String>, api_key: impl Into<String>) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            api_key: api_key.into(),
+        }
+    }
+
+    /// Create `event` and return the ID MISP gave it.
+    pub async fn push(&self, event: &MispEvent) -> crate::Result<String> {
+        use crate::I1Error;
+
+        let response = self
+            .http
+            .post(format!("{}/events", self.base_url))
+            .header(reqwest::header::AUTHORIZATION, &self.api_key)
+            .header(reqwest::header::ACCEPT, "application/json")
+            .json(&event.body())
+            .send()
+            .await
+            .map_err(|e| I1Error::Http(e.to_string()))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let message = response.text().await.unwrap_or_default();
+            return match status.as_u16() {
+                401 | 403 => Err(I1Error::Unauthorized),
+                code => Err(I1Error::provider("misp", code, message)),
+            };
+        }
+
+        let created: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| I1Error::Http(e.to_string()))?;
+        created["Event"]["id"]
+            .as_str()
+            .map(ToString::to_string)
+            .ok_or_else(|| I1Error::provider("misp", status.as_u16(), "no event ID in response"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXPECTED: &str = include_str!("../../tests/fixtures/misp-event.json");
+
+    fn hosts() -> Vec<HostInfo> {
+        let hosts = serde_json::json!([
+            {
+                "ip_str": "198.51.100.3",
+                "hostnames": ["www.example.com"],
+                "domains": ["example.com"],
+                "ports": [443, 22],
+                "tags": ["cloud"],
+                "vulns": ["CVE-2021-44228"],
+                "data": [{
+                    "port": 443,
+                    "transport": "tcp",
+                    "vulns": {"CVE-2021-44228": {"verified": true, "cvss": 10.0}}
+                }]
+            },
+            {
+                "ip_str": "198.51.100.4",
+                "hostnames": ["www.example.com"],
+                "ports": [80],
+                "vulns": ["CVE-2021-44228", "CVE-2014-0160"]
+            },
+            { "ip_str": "not an address", "ports": [8080] }
+        ]);
+        serde_json::from_value(hosts).unwrap()
+    }
+
+    #[test]
+    fn test_fixture() {
+        let event = to_event("nginx exposed on example.com", &hosts())
+            .orgc("Example CERT")
+            .distribution(Distribution::Community)
+            .date(NaiveDate::from_ymd_opt(2024, 1, 15).unwrap())
+            .tag("tlp:amber");
+        let expected: serde_json::Value = serde_json::from_str(EXPECTED).unwrap();
+        assert_eq!(event.body(), expected);
+    }
+
+    #[test]
+    fn test_defaults() {
+        let event = to_event("quiet", &[]);
+        assert_eq!(event.distribution, Distribution::Organisation);
+        assert_eq!(event.threat_level, ThreatLevel::Undefined);
+        assert_eq!(event.date, Utc::now().date_naive());
+
+        let body = event.body();
+        assert_eq!(body["Event"]["distribution"], "0");
+        assert_eq!(body["Event"]["threat_level_id"], "4");
+        assert_eq!(body["Event"]["Attribute"], serde_json::json!([]));
+        assert!(body["Event"].get("Orgc").is_none());
+        assert!(body["Event"].get("Tag").is_none());
+
+        let event = to_event("set", &hosts()).threat_level(ThreatLevel::Low);
+        assert_eq!(event.body()["Event"]["threat_level_id"], "3");
+    }
+
+    #[test]
+    fn test_threat_level_of() {
+        let host = |vulns: serde_json::Value| {
+            crate::testing::host(
+                "192.0.2.1",
+                serde_json::json!({
+                    "vulns": vulns.as_object().unwrap().keys().collect::<Vec<_>>(),
+                    "data": [{"port": 80, "transport": "tcp", "vulns": vulns}]
+                }),
+            )
+        };
+        let scored = |cvss: f64| host(serde_json::json!({ "CVE-1": { "cvss": cvss } }));
+
+        assert_eq!(ThreatLevel::of(&[]), ThreatLevel::Undefined);
+        assert_eq!(
+            ThreatLevel::of(&[host(serde_json::json!({}))]),
+            ThreatLevel::Undefined
+        );
+        assert_eq!(ThreatLevel::of(&[scored(2.0)]), ThreatLevel::Low);
+        assert_eq!(ThreatLevel::of(&[scored(5.0)]), ThreatLevel::Medium);
+        assert_eq!(
+            ThreatLevel::of(&[scored(2.0), scored(9.8)]),
+            ThreatLevel::High
+        );
+    }
+
+    #[cfg(feature = "misp")]
+    #[tokio::test]
+    async fn test_push() {
+        use wiremock::matchers::{body_partial_json, header, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/events"))
+            .and(header("authorization", "test-key"))
+            .and(body_partial_json(
+                serde_json::json!({"Event": {"info": "pushed"}}),
+            ))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({"Event": {"id": "42", "info": "pushed"}})),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/events"))
+            .respond_with(ResponseTemplate::new(403))
+            .mount(&server)
+            .await;
+
+        let event = to_event("pushed", &hosts());
+        let client = MispClient::new(format!("{}/", server.uri()), "test-key");
+        assert_eq!(client.push(&event).await.unwrap(), "42");
+
+        let client = MispClient::new(server.uri(), "wrong-key");
+        assert!(matches!(
+            client.push(&event).await,
+            Err(crate::I1Error::Unauthorized)
+        ));
+    }
+}
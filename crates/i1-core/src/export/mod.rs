@@ -0,0 +1,4 @@
+//! Host and vulnerability data in formats other tools import.
+
+pub mod misp;
+pub mod stix;
@@ -0,0 +1,582 @@
+//! STIX 2.1 bundles of hosts and their vulnerabilities.
+//!
+//! Each host's address becomes an `ipv4-addr` or `ipv6-addr` object that
+//! `belongs-to` its `autonomous-system`, with a `domain-name` for each of
+//! its names that `resolves-to` it. The three are gathered in an
+//! `observed-data` object dated from the host's last scan. Every CVE on the
+//! host becomes a `vulnerability` the address is `related-to`.
+//!
+//! IDs are version 5 UUIDs. Observables are named the way STIX 2.1 names
+//! them, from their value or AS number, and everything else from what it
+//! links, so the same data always gives the same IDs. Importing a bundle
+//! twice then updates objects instead of duplicating them.
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! use i1_core::export::stix;
+//!
+//! let bundle = stix::to_stix_bundle(&hosts, &[]);
+//! println!("{}", serde_json::to_string_pretty(&bundle)?);
+//! ```
+
+use std::collections::{BTreeMap, HashSet};
+use std::net::IpAddr;
+
+use chrono::{DateTime, NaiveDateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{HostInfo, VulnInfo};
+
+/// Version of STIX every object is written in
+pub const SPEC_VERSION: &str = "2.1";
+
+/// Namespace STIX 2.1 gives for the IDs of cyber-observable objects
+const NAMESPACE: Uuid = Uuid::from_u128(0x00ab_edb4_aa42_572c_9f71_61d2_cd8b_0a3a);
+
+/// A STIX bundle: a collection of objects to share in one document.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename = "bundle")]
+pub struct StixBundle {
+    /// `bundle--` and a UUID
+    pub id: String,
+    /// The objects, observables before the objects that refer to them
+    pub objects: Vec<StixObject>,
+}
+
+impl StixBundle {
+    /// The object with this ID.
+    #[must_use]
+    pub fn get(&self, id: &str) -> Option<&StixObject> {
+        self.objects.iter().find(|object| object.id() == id)
+    }
+}
+
+/// The STIX objects a bundle of hosts is made of.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum StixObject {
+    /// An IPv4 address
+    Ipv4Addr(Observable),
+    /// An IPv6 address
+    Ipv6Addr(Observable),
+    /// A hostname or domain
+    DomainName(Observable),
+    /// The network an address is routed from
+    AutonomousSystem(AutonomousSystem),
+    /// A CVE
+    Vulnerability(Vulnerability),
+    /// When a host was seen, and what was seen
+    ObservedData(ObservedData),
+    /// A link between two other objects
+    Relationship(Relationship),
+}
+
+impl StixObject {
+    /// The object's ID, its type followed by `--` and a UUID.
+    #[must_use]
+    pub fn id(&self) -> &str {
+        match self {
+            Self::Ipv4Addr(o) | Self::Ipv6Addr(o) | Self::DomainName(o) => &o.id,
+            Self::AutonomousSystem(o) => &o.id,
+            Self::Vulnerability(o) => &o.id,
+            Self::ObservedData(o) => &o.id,
+            Self::Relationship(o) => &o.id,
+        }
+    }
+}
+
+/// An observable identified by its value: an address or domain name.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Observable {
+    pub spec_version: String,
+    pub id: String,
+    pub value: String,
+}
+
+/// An autonomous system.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AutonomousSystem {
+    pub spec_version: String,
+    pub id: String,
+    pub number: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+/// A vulnerability, named by its CVE ID.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Vulnerability {
+    pub spec_version: String,
+    pub id: String,
+    pub created: String,
+    pub modified: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub external_references: Vec<ExternalReference>,
+    /// CVSS score, which STIX has no property for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub x_i1_cvss: Option<f64>,
+}
+
+/// Where more about an object can be found.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExternalReference {
+    pub source_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub external_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+/// Observables seen together at one time.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ObservedData {
+    pub spec_version: String,
+    pub id: String,
+    pub created: String,
+    pub modified: String,
+    pub first_observed: String,
+    pub last_observed: String,
+    pub number_observed: u32,
+    pub object_refs: Vec<String>,
+}
+
+/// `source_ref` is `relationship_type` to `target_ref`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Relationship {
+    pub spec_version: String,
+    pub id: String,
+    pub created: String,
+    pub modified: String,
+    pub relationship_type: String,
+    pub source_ref: String,
+    pub target_ref: String,
+}
+
+/// A bundle of `hosts` and the CVEs on them.
+///
+/// `vulns` add descriptions, scores and references to the CVEs; ones on no
+/// host are added on their own. Where both give details of a CVE, `vulns`
+/// win over the host's banners. Hosts without a valid IP address are left
+/// out.
+#[must_use]
+pub fn to_stix_bundle(hosts: &[HostInfo], vulns: &[VulnInfo]) -> StixBundle {
+    let mut details: BTreeMap<String, &VulnInfo> = vulns
+        .iter()
+        .filter_map(|vuln| Some((vuln.cve.clone()?, vuln)))
+        .collect();
+    for host in hosts {
+        for service in &host.data {
+            for (cve, vuln) in &service.vulns {
+                details.entry(cve.clone()).or_insert(vuln);
+            }
+        }
+    }
+
+    let now = Utc::now();
+    let mut bundle = Bundler::default();
+    for host in hosts {
+        let Some(ip) = host.ip_addr() else {
+            continue;
+        };
+        let seen = host
+            .last_update
+            .as_deref()
+            .and_then(parse_time)
+            .unwrap_or(now);
+        let seen = timestamp(seen);
+
+        let address = match ip {
+            IpAddr::V4(_) => StixObject::Ipv4Addr(observable("ipv4-addr", ip.to_string())),
+            IpAddr::V6(_) => StixObject::Ipv6Addr(observable("ipv6-addr", ip.to_string())),
+        };
+        let address = bundle.add(address);
+        let mut refs = vec![address.clone()];
+
+        if let Some(number) = host.asn.as_deref().and_then(as_number) {
+            let asn = bundle.add(StixObject::AutonomousSystem(AutonomousSystem {
+                spec_version: SPEC_VERSION.to_string(),
+                id: sco_id("autonomous-system", &json!({ "number": number })),
+                number,
+                name: host.isp.clone().or_else(|| host.org.clone()),
+            }));
+            bundle.relate(&address, "belongs-to", &asn, &seen);
+            refs.push(asn);
+        }
+
+        let names: Vec<String> = host
+            .hostnames
+            .iter()
+            .chain(&host.domains)
+            .map(|name| name.trim_end_matches('.').to_lowercase())
+            .filter(|name| !name.is_empty())
+            .collect();
+        for name in names {
+            let domain = bundle.add(StixObject::DomainName(observable("domain-name", name)));
+            bundle.relate(&domain, "resolves-to", &address, &seen);
+            if !refs.contains(&domain) {
+                refs.push(domain);
+            }
+        }
+
+        bundle.add(StixObject::ObservedData(ObservedData {
+            spec_version: SPEC_VERSION.to_string(),
+            id: sdo_id("observed-data", &json!([refs, seen])),
+            created: seen.clone(),
+            modified: seen.clone(),
+            first_observed: seen.clone(),
+            last_observed: seen.clone(),
+            number_observed: 1,
+            object_refs: refs,
+        }));
+
+        for cve in host.vuln_scores().into_keys() {
+            let vuln = bundle.add(vulnerability(&cve, details.get(&cve).copied(), &seen));
+            bundle.relate(&address, "related-to", &vuln, &seen);
+        }
+    }
+
+    let now = timestamp(now);
+    for (cve, vuln) in details {
+        bundle.add(vulnerability(&cve, Some(vuln), &now));
+    }
+
+    bundle.finish()
+}
+
+/// Objects for a bundle, each added once.
+#[derive(Default)]
+struct Bundler {
+    objects: Vec<StixObject>,
+    ids: HashSet<String>,
+}
+
+impl Bundler {
+    /// Add an object unless one with its ID is already in, and return the ID.
+    fn add(&mut self, object: StixObject) -> String {
+        let id = object.id().to_string();
+        if self.ids.insert(id.clone()) {
+            self.objects.push(object);
+        }
+        id
+    }
+
+    /// Add a relationship from `source` to `target`.
+    fn relate(&mut self, source: &str, kind: &str, target: &str, created: &str) {
+        self.add(StixObject::Relationship(Relationship {
+            spec_version: SPEC_VERSION.to_string(),
+            id: sdo_id("relationship", &json!([source, kind, target])),
+            created: created.to_string(),
+            modified: created.to_string(),
+            relationship_type: kind.to_string(),
+            source_ref: source.to_string(),
+            target_ref: target.to_string(),
+        }));
+    }
+
+    fn finish(self) -> StixBundle {
+        let ids: Vec<&str> = self.objects.iter().map(StixObject::id).collect();
+        StixBundle {
+            id: sdo_id("bundle", &json!(ids)),
+            objects: self.objects,
+        }
+    }
+}
+
+/// An observable identified by `value`.
+fn observable(kind: &str, value: String) -> Observable {
+    Observable {
+        spec_version: SPEC_VERSION.to_string(),
+        id: sco_id(kind, &json!({ "value": value })),
+        value,
+    }
+}
+
+/// A vulnerability for `cve`, with whatever `details` say about it.
+fn vulnerability(cve: &str, details: Option<&VulnInfo>, created: &str) -> StixObject {
+    let mut external_references = vec![ExternalReference {
+        source_name: "cve".to_string(),
+        external_id: Some(cve.to_string()),
+        url: None,
+    }];
+    external_references.extend(details.into_iter().flat_map(|vuln| {
+        vuln.references.iter().map(|url| ExternalReference {
+            source_name: "url".to_string(),
+            external_id: None,
+            url: Some(url.clone()),
+        })
+    }));
+
+    StixObject::Vulnerability(Vulnerability {
+        spec_version: SPEC_VERSION.to_string(),
+        id: sdo_id("vulnerability", &json!(cve)),
+        created: created.to_string(),
+        modified: created.to_string(),
+        name: cve.to_string(),
+        description: details.and_then(|vuln| vuln.summary.clone()),
+        external_references,
+        x_i1_cvss: details.and_then(|vuln| vuln.cvss),
+    })
+}
+
+/// The ID STIX 2.1 gives an observable: a version 5 UUID of its identifying
+/// properties as canonical JSON. One-property objects serialize canonically
+/// as they are.
+fn sco_id(kind: &str, properties: &serde_json::Value) -> String {
+    let uuid = Uuid::new_v5(&NAMESPACE, properties.to_string().as_bytes());
+    format!("{kind}--{uuid}")
+}
+
+/// A version 5 UUID for any other object, from its type and what it links.
+fn sdo_id(kind: &str, key: &serde_json::Value) -> String {
+    let uuid = Uuid::new_v5(&NAMESPACE, format!("{kind}:{key}").as_bytes());
+    format!("{kind}--{uuid}")
+}
+
+/// `AS15169` or `15169` as a number.
+fn as_number(asn: &str) -> Option<u32> {
+    let asn = asn.trim();
+    let digits = match asn.get(..2) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("as") => &asn[2..],
+        _ => asn,
+    };
+    digits.parse().ok()
+}
+
+/// A provider's timestamp: RFC 3339, or Shodan's without a timezone, in UTC.
+fn parse_time(s: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(s)
+        .map(|t| t.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S%.f")
+                .ok()
+                .map(|t| t.and_utc())
+        })
+}
+
+/// A STIX timestamp: RFC 3339 in UTC, to the millisecond.
+fn timestamp(t: DateTime<Utc>) -> String {
+    t.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::host;
+
+    fn hosts() -> Vec<HostInfo> {
+        vec![
+            host(
+                "198.51.100.3",
+                json!({
+                    "hostnames": ["www.example.com"],
+                    "domains": ["example.com"],
+                    "asn": "AS64496",
+                    "isp": "Example Transit",
+                    "org": "Example Corp",
+                    "vulns": ["CVE-2021-44228"],
+                    "last_update": "2024-01-15T10:30:00.123456",
+                    "data": [{
+                        "port": 443,
+                        "transport": "tcp",
+                        "vulns": {
+                            "CVE-2021-44228": {
+                                "verified": true,
+                                "cvss": 10.0,
+                                "summary": "Log4Shell",
+                                "references": ["https://nvd.nist.gov/vuln/detail/CVE-2021-44228"]
+                            }
+                        }
+                    }]
+                }),
+            ),
+            host(
+                "2001:db8::1",
+                json!({ "asn": "64496", "last_update": "2024-02-01T00:00:00+00:00" }),
+            ),
+            host("not an address", json!({})),
+        ]
+    }
+
+    fn ids(bundle: &StixBundle, kind: &str) -> Vec<String> {
+        bundle
+            .objects
+            .iter()
+            .map(StixObject::id)
+            .filter(|id| id.starts_with(&format!("{kind}--")))
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn test_required_fields() {
+        let bundle = to_stix_bundle(&hosts(), &[]);
+        let value = serde_json::to_value(&bundle).unwrap();
+        assert_eq!(value["type"], "bundle");
+        assert!(value["id"].as_str().unwrap().starts_with("bundle--"));
+
+        for object in value["objects"].as_array().unwrap() {
+            let kind = object["type"].as_str().unwrap();
+            let id = object["id"].as_str().unwrap();
+            let (prefix, uuid) = id.split_once("--").unwrap();
+            assert_eq!(prefix, kind);
+            assert_eq!(Uuid::parse_str(uuid).unwrap().get_version_num(), 5);
+            assert_eq!(object["spec_version"], SPEC_VERSION);
+
+            let required: &[&str] = match kind {
+                "ipv4-addr" | "ipv6-addr" | "domain-name" => &["value"],
+                "autonomous-system" => &["number"],
+                "vulnerability" => &["created", "modified", "name"],
+                "observed-data" => &[
+                    "created",
+                    "modified",
+                    "first_observed",
+                    "last_observed",
+                    "number_observed",
+                    "object_refs",
+                ],
+                "relationship" => &[
+                    "created",
+                    "modified",
+                    "relationship_type",
+                    "source_ref",
+                    "target_ref",
+                ],
+                other => panic!("unexpected type {other}"),
+            };
+            for field in required {
+                assert!(!object[field].is_null(), "{kind} without {field}");
+            }
+            for field in ["created", "modified", "first_observed", "last_observed"] {
+                if let Some(t) = object[field].as_str() {
+                    assert!(DateTime::parse_from_rfc3339(t).is_ok(), "{field}: {t}");
+                    assert!(t.ends_with('Z'));
+                }
+            }
+        }
+
+        // Every reference points into the bundle
+        for object in &bundle.objects {
+            let refs = match object {
+                StixObject::ObservedData(o) => o.object_refs.clone(),
+                StixObject::Relationship(r) => vec![r.source_ref.clone(), r.target_ref.clone()],
+                _ => continue,
+            };
+            for id in refs {
+                assert!(bundle.get(&id).is_some(), "dangling {id}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_objects() {
+        let bundle = to_stix_bundle(&hosts(), &[]);
+        assert_eq!(ids(&bundle, "ipv4-addr").len(), 1);
+        assert_eq!(ids(&bundle, "ipv6-addr").len(), 1);
+        assert_eq!(ids(&bundle, "domain-name").len(), 2);
+        // Both hosts are in AS64496
+        assert_eq!(ids(&bundle, "autonomous-system").len(), 1);
+        assert_eq!(ids(&bundle, "observed-data").len(), 2);
+        assert_eq!(ids(&bundle, "vulnerability").len(), 1);
+
+        let v4 = &ids(&bundle, "ipv4-addr")[0];
+        let related = |kind: &str| -> Vec<(String, String)> {
+            bundle
+                .objects
+                .iter()
+                .filter_map(|object| match object {
+                    StixObject::Relationship(r) if r.relationship_type == kind => {
+                        Some((r.source_ref.clone(), r.target_ref.clone()))
+                    }
+                    _ => None,
+                })
+                .collect()
+        };
+        let asn = &ids(&bundle, "autonomous-system")[0];
+        assert!(related("belongs-to").contains(&(v4.clone(), asn.clone())));
+        assert_eq!(related("belongs-to").len(), 2);
+        assert_eq!(related("resolves-to").len(), 2);
+        assert!(related("resolves-to").iter().all(|(_, to)| to == v4));
+        let cve = &ids(&bundle, "vulnerability")[0];
+        assert_eq!(related("related-to"), vec![(v4.clone(), cve.clone())]);
+
+        let Some(StixObject::Vulnerability(vuln)) = bundle.get(cve) else {
+            panic!("no vulnerability");
+        };
+        assert_eq!(vuln.name, "CVE-2021-44228");
+        assert_eq!(vuln.description.as_deref(), Some("Log4Shell"));
+        assert_eq!(vuln.x_i1_cvss, Some(10.0));
+        assert_eq!(vuln.external_references.len(), 2);
+        assert_eq!(
+            vuln.external_references[0].external_id.as_deref(),
+            Some("CVE-2021-44228")
+        );
+        assert_eq!(vuln.created, "2024-01-15T10:30:00.123Z");
+
+        let Some(StixObject::AutonomousSystem(asn)) = bundle.get(asn) else {
+            panic!("no autonomous system");
+        };
+        assert_eq!(asn.number, 64496);
+        assert_eq!(asn.name.as_deref(), Some("Example Transit"));
+    }
+
+    #[test]
+    fn test_deterministic_ids() {
+        let first = to_stix_bundle(&hosts(), &[]);
+        let second = to_stix_bundle(&hosts(), &[]);
+        assert_eq!(first, second);
+
+        // Observable IDs are the ones STIX 2.1 derives from the value
+        let expected = Uuid::new_v5(&NAMESPACE, br#"{"value":"198.51.100.3"}"#);
+        assert_eq!(ids(&first, "ipv4-addr"), [format!("ipv4-addr--{expected}")]);
+
+        let json = serde_json::to_string(&first).unwrap();
+        assert_eq!(serde_json::from_str::<StixBundle>(&json).unwrap(), first);
+    }
+
+    #[test]
+    fn test_vulns_without_hosts() {
+        let vulns = [VulnInfo {
+            cve: Some("CVE-2014-0160".to_string()),
+            verified: false,
+            cvss: Some(7.5),
+            summary: Some("Heartbleed".to_string()),
+            references: vec![],
+        }];
+        let bundle = to_stix_bundle(&[], &vulns);
+        assert_eq!(bundle.objects.len(), 1);
+        let StixObject::Vulnerability(vuln) = &bundle.objects[0] else {
+            panic!("not a vulnerability");
+        };
+        assert_eq!(vuln.description.as_deref(), Some("Heartbleed"));
+        assert!(DateTime::parse_from_rfc3339(&vuln.created).is_ok());
+
+        // Details given directly win over the banners'
+        let vulns = [VulnInfo {
+            cve: Some("CVE-2021-44228".to_string()),
+            summary: Some("Apache Log4j2 JNDI".to_string()),
+            ..vulns[0].clone()
+        }];
+        let bundle = to_stix_bundle(&hosts(), &vulns);
+        let cve = &ids(&bundle, "vulnerability")[0];
+        let Some(StixObject::Vulnerability(vuln)) = bundle.get(cve) else {
+            panic!("no vulnerability");
+        };
+        assert_eq!(vuln.description.as_deref(), Some("Apache Log4j2 JNDI"));
+    }
+
+    #[test]
+    fn test_as_number() {
+        assert_eq!(as_number("AS15169"), Some(15169));
+        assert_eq!(as_number("as15169"), Some(15169));
+        assert_eq!(as_number(" 15169 "), Some(15169));
+        assert_eq!(as_number("ASN"), None);
+        assert_eq!(as_number(""), None);
+    }
+}
@@ -0,0 +1,371 @@
+//! What product a banner comes from.
+//!
+//! A [`FingerprintDb`] is a list of banner patterns, each naming the
+//! service, product and usual transport it identifies, with a `version`
+//! capture group where the banner gives one away. The built-in table covers
+//! common SSH, FTP, SMB, mail, HTTP, database and remote access services; TOML
+//! files in the same format extend it:
+//!
+//! ```toml
+//! [[fingerprint]]
+//! service = "http"
+//! product = "Acme Appliance"
+//! pattern = '(?i)^server:[ \t]*acme-httpd/(?P<version>[\d.]+)'
+//! ports = [8080]
+//! tags = ["embedded"]
+//! ```
+//!
+//! In patterns, `^` and `$` match at the start and end of each line of the
+//! banner.
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! use i1_core::fingerprints::{self, FingerprintDb};
+//!
+//! let found = fingerprints::match_banner(22, "SSH-2.0-OpenSSH_8.9p1").unwrap();
+//! assert_eq!(found.product, "OpenSSH");
+//!
+//! let mut db = FingerprintDb::builtin().clone();
+//! db.extend(FingerprintDb::load("my-fingerprints.toml")?);
+//! ```
+
+use std::path::Path;
+use std::sync::OnceLock;
+
+use regex::{Regex, RegexBuilder};
+use serde::Deserialize;
+
+use crate::{I1Error, Result, Service, Transport};
+
+/// The built-in table, compiled in.
+const BUILTIN: &str = include_str!("fingerprints.toml");
+
+/// A banner pattern and what it identifies, as written in a fingerprint file.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Fingerprint {
+    /// Protocol spoken, e.g. `ssh` or `http`
+    pub service: String,
+    /// Product name, e.g. "OpenSSH"
+    pub product: String,
+    /// Regular expression matched against the banner
+    pub pattern: String,
+    /// Ports the service usually listens on, tried first there
+    #[serde(default)]
+    pub ports: Vec<u16>,
+    /// Transport the service usually runs over
+    #[serde(default)]
+    pub transport: Transport,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// What a banner was identified as.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FingerprintMatch {
+    pub service: String,
+    pub product: String,
+    /// Version, when the banner gives it away
+    pub version: Option<String>,
+    pub transport: Transport,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct FingerprintFile {
+    #[serde(default)]
+    fingerprint: Vec<Fingerprint>,
+}
+
+#[derive(Debug, Clone)]
+struct Compiled {
+    fingerprint: Fingerprint,
+    regex: Regex,
+}
+
+impl Compiled {
+    fn matches(&self, banner: &str) -> Option<FingerprintMatch> {
+        let captures = self.regex.captures(banner)?;
+        let fingerprint = &self.fingerprint;
+        Some(FingerprintMatch {
+            service: fingerprint.service.clone(),
+            product: fingerprint.product.clone(),
+            version: captures.name("version").map(|v| v.as_str().to_string()),
+            transport: fingerprint.transport,
+            tags: fingerprint.tags.clone(),
+        })
+    }
+}
+
+/// Fingerprints, tried in order.
+#[derive(Debug, Clone, Default)]
+pub struct FingerprintDb {
+    entries: Vec<Compiled>,
+}
+
+impl FingerprintDb {
+    /// The compiled-in table.
+    pub fn builtin() -> &'static Self {
+        static BUILTIN_DB: OnceLock<FingerprintDb> = OnceLock::new();
+        BUILTIN_DB
+            .get_or_init(|| Self::from_toml(BUILTIN).expect("built-in fingerprints are valid"))
+    }
+
+    /// Fingerprints from the text of a fingerprint file.
+    pub fn from_toml(text: &str) -> Result<Self> {
+        let file: FingerprintFile = toml::from_str(text)
+            .map_err(|e| I1Error::Config(format!("invalid fingerprint file: {e}")))?;
+        file.fingerprint
+            .into_iter()
+            .map(compile)
+            .collect::<Result<_>>()
+            .map(|entries| Self { entries })
+    }
+
+    /// Fingerprints from the file at `path`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| I1Error::Config(format!("{}: {e}", path.display())))?;
+        Self::from_toml(&text).map_err(|e| I1Error::Config(format!("{}: {e}", path.display())))
+    }
+
+    /// Add `other`'s fingerprints, trying them before these so they can
+    /// override built-in matches for the same ports.
+    pub fn extend(&mut self, other: Self) {
+        let mut entries = other.entries;
+        entries.append(&mut self.entries);
+        self.entries = entries;
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Identify the service that sent `banner` on `port`. Fingerprints for
+    /// the port are tried first, then the rest.
+    pub fn match_banner(&self, port: u16, banner: &str) -> Option<FingerprintMatch> {
+        let (usual, other): (Vec<_>, Vec<_>) = self
+            .entries
+            .iter()
+            .partition(|e| e.fingerprint.ports.contains(&port));
+        usual
+            .into_iter()
+            .chain(other)
+            .find_map(|entry| entry.matches(banner))
+    }
+
+    /// Fill in `service`'s product and version from its banner, if it has
+    /// a banner but no product. Returns whether anything was identified.
+    pub fn identify(&self, service: &mut Service) -> bool {
+        if service.product.is_some() {
+            return false;
+        }
+        let Some(found) = service
+            .data
+            .as_deref()
+            .and_then(|banner| self.match_banner(service.port, banner))
+        else {
+            return false;
+        };
+        service.product = Some(found.product);
+        if service.version.is_none() {
+            service.version = found.version;
+        }
+        true
+    }
+}
+
+fn compile(fingerprint: Fingerprint) -> Result<Compiled> {
+    if fingerprint.product.is_empty() {
+        return Err(I1Error::Config(format!(
+            "fingerprint '{}' has no product",
+            fingerprint.pattern
+        )));
+    }
+    let regex = RegexBuilder::new(&fingerprint.pattern)
+        .multi_line(true)
+        .crlf(true)
+        .build()
+        .map_err(|e| {
+            I1Error::Config(format!(
+                "fingerprint '{}' has an invalid pattern: {e}",
+                fingerprint.product
+            ))
+        })?;
+    Ok(Compiled { fingerprint, regex })
+}
+
+/// Identify `banner` on `port` with the built-in fingerprints.
+pub fn match_banner(port: u16, banner: &str) -> Option<FingerprintMatch> {
+    FingerprintDb::builtin().match_banner(port, banner)
+}
+
+/// Fill in `service`'s product and version from its banner with the
+/// built-in fingerprints. See [`FingerprintDb::identify`].
+pub fn identify(service: &mut Service) -> bool {
+    FingerprintDb::builtin().identify(service)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_banners() {
+        let cases: &[(u16, &str, &str, Option<&str>)] = &[
+            (22, "SSH-2.0-OpenSSH_8.9p1 Ubuntu-3ubuntu0.6", "OpenSSH", Some("8.9p1")),
+            (22, "SSH-2.0-dropbear_2020.81", "Dropbear", Some("2020.81")),
+            (22, "SSH-2.0-Cisco-1.25", "Cisco SSH", Some("1.25")),
+            (2222, "SSH-2.0-ROSSSH", "MikroTik RouterOS SSH", None),
+            (22, "SSH-1.99-Unknown", "SSH", Some("1.99")),
+            (21, "220 (vsFTPd 3.0.3)", "vsftpd", Some("3.0.3")),
+            (21, "220 ProFTPD 1.3.5e Server (Debian) [::ffff:192.0.2.1]", "ProFTPD", Some("1.3.5e")),
+            (21, "220 MikroTik FTP server (MikroTik 6.48.3) ready", "MikroTik FTP", Some("6.48.3")),
+            (
+                445,
+                "SMB Status:\n  Authentication: enabled\n  SMB Version: 2\n  Software: Samba 4.15.13\n",
+                "Samba",
+                Some("4.15.13"),
+            ),
+            (
+                445,
+                "SMB Status:\n  Authentication: enabled\n  SMB Version: 2\n  OS: Windows 10 Pro 19045\n",
+                "Microsoft Windows SMB",
+                None,
+            ),
+            (139, "SMB Status:\n  Authentication: disabled\n", "SMB", None),
+            (25, "220 mail.example.com ESMTP Exim 4.94.2 Tue, 01 Jun 2021", "Exim", Some("4.94.2")),
+            (25, "220 mx.example.com ESMTP Postfix (Ubuntu)", "Postfix", None),
+            (587, "220 EX01.corp.example Microsoft ESMTP MAIL Service ready", "Microsoft Exchange", None),
+            (143, "* OK [CAPABILITY IMAP4rev1 SASL-IR] Dovecot (Ubuntu) ready.", "Dovecot", None),
+            (
+                80,
+                "HTTP/1.1 200 OK\r\nDate: Tue, 01 Jun 2021\r\nServer: nginx/1.18.0 (Ubuntu)\r\n\r\n",
+                "nginx",
+                Some("1.18.0"),
+            ),
+            (443, "HTTP/1.1 301 Moved\r\nserver: openresty/1.19.9.1\r\n", "OpenResty", Some("1.19.9.1")),
+            (80, "HTTP/1.1 200 OK\r\nServer: Apache/2.4.41 (Ubuntu)\r\n", "Apache httpd", Some("2.4.41")),
+            (80, "HTTP/1.1 403 Forbidden\r\nServer: Apache\r\n", "Apache httpd", None),
+            (8080, "HTTP/1.1 200 OK\r\nServer: Apache-Coyote/1.1\r\n", "Apache Tomcat", Some("1.1")),
+            (80, "HTTP/1.1 200 OK\r\nServer: Microsoft-IIS/10.0\r\n", "Microsoft IIS", Some("10.0")),
+            (10000, "HTTP/1.0 200 Document follows\r\nServer: MiniServ/1.973\r\n", "Webmin", Some("1.973")),
+            (8080, "HTTP/1.1 200 OK\r\nX-Jenkins: 2.289.1\r\n", "Jenkins", Some("2.289.1")),
+            (3306, "J\0\0\0\n5.7.33-0ubuntu0.18.04.1\0\x08\0\0\0", "MySQL", Some("5.7.33")),
+            (3306, "n\0\0\0\n5.5.5-10.5.12-MariaDB-1:10.5.12+maria~focal\0", "MariaDB", Some("10.5.12")),
+            (6379, "# Server\r\nredis_version:6.0.9\r\nredis_mode:standalone", "Redis", Some("6.0.9")),
+            (6379, "-NOAUTH Authentication required.", "Redis", None),
+            (11211, "STAT pid 1\r\nSTAT version 1.6.9\r\n", "Memcached", Some("1.6.9")),
+            (
+                9200,
+                r#"{"name":"node-1","version":{"number":"7.10.2"},"tagline":"You Know, for Search"}"#,
+                "Elasticsearch",
+                Some("7.10.2"),
+            ),
+            (
+                27017,
+                "MongoDB Server Information\n{\n  \"version\": \"4.4.6\",\n}",
+                "MongoDB",
+                Some("4.4.6"),
+            ),
+            (3389, "\x03\0\0\x13\x0e\u{fffd}\0\0\x124\0\x02", "Remote Desktop Protocol", None),
+            (5900, "RFB 003.008", "VNC", Some("003.008")),
+            (
+                1883,
+                "MQTT Connection Code: 0\n\nTopics:\n  $SYS/broker/version: mosquitto version 2.0.11",
+                "Mosquitto",
+                Some("2.0.11"),
+            ),
+            (1883, "MQTT Connection Code: 5", "MQTT", None),
+            (5060, "SIP/2.0 200 OK\r\nUser-Agent: Asterisk PBX 16.2.1\r\n", "Asterisk", Some("16.2.1")),
+        ];
+
+        for &(port, banner, product, version) in cases {
+            let found = match_banner(port, banner).unwrap_or_else(|| panic!("{banner:?}"));
+            assert_eq!(found.product, product, "{banner:?}");
+            assert_eq!(found.version.as_deref(), version, "{banner:?}");
+        }
+    }
+
+    #[test]
+    fn test_unknown_banners() {
+        for banner in ["", "hello", "HTTP/1.1 200 OK\r\nServer: apachefoo\r\n"] {
+            assert_eq!(match_banner(80, banner), None, "{banner:?}");
+        }
+    }
+
+    #[test]
+    fn test_match_details() {
+        let found = match_banner(5060, "SIP/2.0 200 OK\r\nServer: Asterisk\r\n").unwrap();
+        assert_eq!(found.service, "sip");
+        assert_eq!(found.transport, Transport::Udp);
+        assert_eq!(found.tags, ["voip"]);
+    }
+
+    #[test]
+    fn test_user_fingerprints() {
+        let user = FingerprintDb::from_toml(
+            r#"
+            [[fingerprint]]
+            service = "http"
+            product = "Acme Appliance"
+            pattern = '(?i)^server:[ \t]*nginx/(?P<version>1\.18\.0)'
+            ports = [8080]
+            tags = ["embedded"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(user.len(), 1);
+
+        let mut db = FingerprintDb::builtin().clone();
+        db.extend(user);
+        assert_eq!(db.len(), FingerprintDb::builtin().len() + 1);
+
+        // User fingerprints win on their ports, and the built-ins still apply
+        let banner = "HTTP/1.1 200 OK\r\nServer: nginx/1.18.0\r\n";
+        assert_eq!(
+            db.match_banner(8080, banner).unwrap().product,
+            "Acme Appliance"
+        );
+        assert_eq!(db.match_banner(80, banner).unwrap().product, "nginx");
+        assert_eq!(
+            db.match_banner(22, "SSH-2.0-OpenSSH_9.6").unwrap().product,
+            "OpenSSH"
+        );
+    }
+
+    #[test]
+    fn test_invalid_fingerprints() {
+        for text in [
+            "[[fingerprint]]\nservice = \"x\"\nproduct = \"X\"\npattern = \"(\"",
+            "[[fingerprint]]\nservice = \"x\"\nproduct = \"\"\npattern = \"x\"",
+            "[[fingerprint]]\nproduct = \"X\"",
+            "fingerprint = 1",
+        ] {
+            let err = FingerprintDb::from_toml(text).unwrap_err();
+            assert!(matches!(err, I1Error::Config(_)), "{text}: {err}");
+        }
+    }
+
+    #[test]
+    fn test_identify() {
+        let mut service: Service = serde_json::from_value(serde_json::json!({
+            "port": 22,
+            "data": "SSH-2.0-OpenSSH_7.4",
+        }))
+        .unwrap();
+        assert!(identify(&mut service));
+        assert_eq!(service.product.as_deref(), Some("OpenSSH"));
+        assert_eq!(service.version.as_deref(), Some("7.4"));
+
+        // A product the provider named is kept
+        service.product = Some("sshd".to_string());
+        assert!(!identify(&mut service));
+        assert_eq!(service.product.as_deref(), Some("sshd"));
+    }
+}
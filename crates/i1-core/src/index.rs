@@ -0,0 +1,528 @@
+//! Finding services by their banners.
+//!
+//! A [`BannerIndex`] holds the service banners of hosts already collected,
+//! so that a distinctive one (an odd SSH version string, a custom HTTP
+//! header) leads to the other hosts that send it.
+//!
+//! Two banners from the same build rarely match byte for byte: they carry
+//! the time they were sent, the host's name and address, session ids and
+//! lengths. [`normalize`] replaces those with placeholders and breaks what
+//! is left into lowercase words, and banners are compared on the words and
+//! pairs of adjacent words they share. [`BannerIndex::find_product`] looks
+//! services up by product and version instead.
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! use i1_core::index::BannerIndex;
+//!
+//! let mut index = BannerIndex::new();
+//! for host in &hosts {
+//!     index.add_host(host);
+//! }
+//! for (ip, port, score) in index.find_similar("SSH-2.0-OpenSSH_8.9p1 Ubuntu-3ubuntu0.6", 0.6) {
+//!     println!("{ip}:{port} ({score:.2})");
+//! }
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+use crate::{fingerprints, HostInfo, Service};
+
+/// Stands in for a date or time of day
+const TIME: &str = "<time>";
+/// Stands in for an IP address, with or without a port
+const IP: &str = "<ip>";
+/// Stands in for a hostname or email address
+const HOST: &str = "<host>";
+/// Stands in for a hex id: session, etag, request or key fingerprint
+const HEX: &str = "<hex>";
+/// Stands in for a number on its own, like a length or a counter
+const NUMBER: &str = "<n>";
+
+/// Top-level domains taken as the end of a two-label hostname. Longer
+/// names pass on their shape alone, so `index.html` isn't a host.
+const TLDS: &[&str] = &[
+    "com", "net", "org", "edu", "gov", "mil", "int", "io", "co", "info", "biz", "local", "lan",
+    "home", "internal", "corp", "arpa", "is", "uk", "de", "fr", "nl", "ru", "cn", "jp", "br", "us",
+    "eu",
+];
+
+/// Dates and times in the forms servers print them: ISO 8601, HTTP and
+/// mail dates, ctime and syslog stamps, and bare times of day.
+fn timestamps() -> &'static Regex {
+    static TIMESTAMPS: OnceLock<Regex> = OnceLock::new();
+    TIMESTAMPS.get_or_init(|| {
+        let month = "(?:jan|feb|mar|apr|may|jun|jul|aug|sep|oct|nov|dec)[a-z]*";
+        let day = "(?:mon|tue|wed|thu|fri|sat|sun)[a-z]*";
+        let zone = r"(?:\s*(?:z|gmt|utc|[+-]\d{2}:?\d{2}))?";
+        let time = r"\d{1,2}:\d{2}(?::\d{2}(?:[.,]\d+)?)?";
+        let pattern = [
+            format!(r"\b\d{{4}}-\d{{2}}-\d{{2}}(?:[t ]{time})?{zone}"),
+            format!(r"\b(?:{day},?\s+)?\d{{1,2}}[ -]{month}[ -]\d{{2,4}}(?:\s+{time})?{zone}"),
+            format!(r"\b(?:{day}\s+)?{month}\s+\d{{1,2}}\s+{time}(?:\s+\d{{4}})?{zone}"),
+            format!(r"\b{time}{zone}"),
+        ]
+        .join("|");
+        Regex::new(&pattern).expect("timestamp pattern compiles")
+    })
+}
+
+/// `banner` with what varies between hosts running the same software
+/// replaced, as lowercase words separated by single spaces.
+///
+/// Dates and times become `<time>`, IP addresses `<ip>`, hostnames and
+/// email addresses `<host>`, long hex ids `<hex>` and numbers standing on
+/// their own `<n>`. Everything else is split into its letters and digits:
+/// `SSH-2.0-OpenSSH_8.9p1` is `ssh 2 0 openssh 8 9p1`, so a version
+/// still tells builds apart.
+pub fn normalize(banner: &str) -> String {
+    words(banner).join(" ")
+}
+
+fn words(banner: &str) -> Vec<String> {
+    let lower = banner.to_lowercase();
+    // Addresses go first, or `192.0.2.1:22` would read as a time of day
+    let addressed: Vec<&str> = lower
+        .split(|c: char| c.is_whitespace() || matches!(c, '=' | ',' | ';'))
+        .map(|word| if is_address(word) { IP } else { word })
+        .collect();
+    let addressed = addressed.join(" ");
+    let text = timestamps().replace_all(&addressed, format!(" {TIME} ").as_str());
+
+    let mut words = Vec::new();
+    for word in text.split_whitespace() {
+        if let Some(placeholder) = placeholder(word) {
+            words.push(placeholder.to_string());
+            continue;
+        }
+        words.extend(
+            word.split(|c: char| !c.is_alphanumeric())
+                .filter(|part| !part.is_empty())
+                .map(str::to_string),
+        );
+    }
+    words
+}
+
+/// `word` without the brackets, quotes and punctuation around it.
+fn trim(word: &str) -> &str {
+    word.trim_matches(|c: char| !(c.is_alphanumeric() || c == ':'))
+        .trim_end_matches(':')
+}
+
+/// Whether `word` is an IP address, with or without a port.
+fn is_address(word: &str) -> bool {
+    let core = word.trim_matches(|c: char| !(c.is_alphanumeric() || matches!(c, ':' | '[' | ']')));
+    core.parse::<SocketAddr>().is_ok() || trim(word).parse::<IpAddr>().is_ok()
+}
+
+/// The placeholder for a word that varies from host to host, if it is one.
+fn placeholder(word: &str) -> Option<&'static str> {
+    match word {
+        TIME => return Some(TIME),
+        IP => return Some(IP),
+        _ => {}
+    }
+    let bare = trim(word);
+    if bare.is_empty() {
+        return None;
+    }
+    if let Some((user, domain)) = bare.split_once('@') {
+        if !user.is_empty() && is_hostname(domain) {
+            return Some(HOST);
+        }
+    }
+    if is_hostname(bare) {
+        return Some(HOST);
+    }
+    if bare.chars().all(|c| c.is_ascii_digit()) {
+        return Some(NUMBER);
+    }
+    let hex_digits = bare.chars().filter(char::is_ascii_hexdigit).count();
+    if hex_digits >= 8
+        && bare.chars().all(|c| c.is_ascii_hexdigit() || c == '-')
+        && bare.chars().any(|c| c.is_ascii_digit())
+    {
+        return Some(HEX);
+    }
+    None
+}
+
+/// Whether `word` looks like a DNS name: dotted labels of letters, digits
+/// and hyphens, ending in letters. Two labels are a name only with a
+/// known top-level domain.
+fn is_hostname(word: &str) -> bool {
+    let labels: Vec<&str> = word.split('.').collect();
+    let Some(tld) = labels.last() else {
+        return false;
+    };
+    labels.len() >= 2
+        && labels.iter().all(|label| {
+            !label.is_empty() && label.chars().all(|c| c.is_alphanumeric() || c == '-')
+        })
+        && tld.len() >= 2
+        && tld.chars().all(char::is_alphabetic)
+        && (labels.len() >= 3 || TLDS.contains(tld))
+}
+
+/// The words of `banner` and each pair of neighbouring words, hashed.
+fn shingles(banner: &str) -> BTreeSet<u64> {
+    let words = words(banner);
+    let pairs = words.windows(2).map(|pair| pair.join(" "));
+    words
+        .iter()
+        .cloned()
+        .chain(pairs)
+        .map(|shingle| fnv1a(shingle.as_bytes()))
+        .collect()
+}
+
+/// Shingles in both sets over shingles in either.
+#[allow(clippy::cast_precision_loss)]
+fn jaccard(a: &BTreeSet<u64>, b: &BTreeSet<u64>) -> f64 {
+    let shared = a.intersection(b).count();
+    let either = a.len() + b.len() - shared;
+    if either == 0 {
+        return 0.0;
+    }
+    shared as f64 / either as f64
+}
+
+/// 64-bit FNV-1a, so shingles hash the same in every run.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// A service in a [`BannerIndex`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexedService {
+    /// Address of the host
+    pub ip: String,
+    /// Port the service listens on
+    pub port: u16,
+    /// Product, as the provider or a fingerprint named it
+    pub product: Option<String>,
+    /// Product version
+    pub version: Option<String>,
+    /// The banner as it was collected
+    pub banner: Option<String>,
+}
+
+/// Services of collected hosts, searchable by banner and by product.
+#[derive(Debug, Clone, Default)]
+pub struct BannerIndex {
+    services: Vec<IndexedService>,
+    /// Each service's banner shingles, by position in `services`
+    shingles: Vec<BTreeSet<u64>>,
+    /// Lowercase product name to positions in `services`
+    products: BTreeMap<String, Vec<usize>>,
+    /// Address, port and normalized banner of every service, to skip
+    /// the same one seen again
+    seen: HashSet<(String, u16, String)>,
+}
+
+impl BannerIndex {
+    /// An empty index.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of services indexed
+    pub fn len(&self) -> usize {
+        self.services.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.services.is_empty()
+    }
+
+    /// Every service indexed, in the order they were added.
+    pub fn iter(&self) -> impl Iterator<Item = &IndexedService> {
+        self.services.iter()
+    }
+
+    /// Index every service of `host`.
+    pub fn add_host(&mut self, host: &HostInfo) {
+        for service in &host.data {
+            self.add_service(&host.ip_str, service);
+        }
+    }
+
+    /// Index `service` of the host at `ip`. A service without a product
+    /// gets one from its banner's fingerprint, when it matches one.
+    pub fn add_service(&mut self, ip: &str, service: &Service) {
+        let banner = service.data.as_deref().filter(|b| !b.trim().is_empty());
+        let found = match (&service.product, banner) {
+            (None, Some(banner)) => fingerprints::match_banner(service.port, banner),
+            _ => None,
+        };
+        let (product, version) = match found {
+            Some(found) => (Some(found.product), found.version),
+            None => (service.product.clone(), service.version.clone()),
+        };
+        self.add(IndexedService {
+            ip: ip.to_string(),
+            port: service.port,
+            product,
+            version,
+            banner: banner.map(str::to_string),
+        });
+    }
+
+    /// Index a banner seen on `ip`:`port`, with no product known.
+    pub fn add_banner(&mut self, ip: &str, port: u16, banner: &str) {
+        self.add(IndexedService {
+            ip: ip.to_string(),
+            port,
+            product: None,
+            version: None,
+            banner: Some(banner.to_string()),
+        });
+    }
+
+    fn add(&mut self, service: IndexedService) {
+        let normalized = service.banner.as_deref().map(normalize).unwrap_or_default();
+        let key = match (&service.banner, &service.product) {
+            (None, Some(product)) => format!("{product} {:?}", service.version),
+            _ => normalized,
+        };
+        if !self.seen.insert((service.ip.clone(), service.port, key)) {
+            return;
+        }
+
+        let at = self.services.len();
+        if let Some(product) = &service.product {
+            self.products
+                .entry(product.to_lowercase())
+                .or_default()
+                .push(at);
+        }
+        self.shingles
+            .push(service.banner.as_deref().map(shingles).unwrap_or_default());
+        self.services.push(service);
+    }
+
+    /// Services whose banners are at least `threshold` alike with `banner`,
+    /// from 0 (nothing shared) to 1 (the same once normalized), most alike
+    /// first. Each address and port is listed once, with its best score.
+    pub fn find_similar(&self, banner: &str, threshold: f64) -> Vec<(String, u16, f64)> {
+        let wanted = shingles(banner);
+        if wanted.is_empty() {
+            return Vec::new();
+        }
+
+        let mut best: BTreeMap<(&str, u16), f64> = BTreeMap::new();
+        for (service, shingles) in self.services.iter().zip(&self.shingles) {
+            let score = jaccard(&wanted, shingles);
+            if score < threshold || score == 0.0 {
+                continue;
+            }
+            let slot = best.entry((&service.ip, service.port)).or_insert(score);
+            *slot = slot.max(score);
+        }
+
+        let mut found: Vec<(String, u16, f64)> = best
+            .into_iter()
+            .map(|((ip, port), score)| (ip.to_string(), port, score))
+            .collect();
+        found.sort_by(|a, b| {
+            b.2.total_cmp(&a.2)
+                .then_with(|| addr(&a.0).cmp(&addr(&b.0)))
+                .then(a.1.cmp(&b.1))
+        });
+        found
+    }
+
+    /// Services running `product` (case aside), and when given, exactly
+    /// `version` of it, by address and port.
+    pub fn find_product(&self, product: &str, version: Option<&str>) -> Vec<(String, u16)> {
+        let Some(at) = self.products.get(&product.to_lowercase()) else {
+            return Vec::new();
+        };
+        let found: BTreeSet<(Option<IpAddr>, &str, u16)> = at
+            .iter()
+            .map(|&i| &self.services[i])
+            .filter(|service| {
+                version.map_or(true, |v| {
+                    service
+                        .version
+                        .as_deref()
+                        .is_some_and(|have| have.eq_ignore_ascii_case(v))
+                })
+            })
+            .map(|service| (addr(&service.ip), service.ip.as_str(), service.port))
+            .collect();
+        found
+            .into_iter()
+            .map(|(_, ip, port)| (ip.to_string(), port))
+            .collect()
+    }
+
+    /// The first service indexed at `ip`:`port`.
+    pub fn get(&self, ip: &str, port: u16) -> Option<&IndexedService> {
+        self.services
+            .iter()
+            .find(|service| service.ip == ip && service.port == port)
+    }
+}
+
+/// Addresses sort numerically, anything unparsable after them.
+fn addr(ip: &str) -> Option<IpAddr> {
+    ip.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_strips_what_varies() {
+        // The same Exim build greeting from two hosts at different times
+        let a = "220 mail.example.com ESMTP Exim 4.94.2 Tue, 01 Jun 2021 10:15:42 +0000";
+        let b = "220 mx2.other.org ESMTP Exim 4.94.2 Wed, 17 Nov 2021 23:01:07 +0100";
+        assert_eq!(normalize(a), "<n> <host> esmtp exim 4 94 2 <time>");
+        assert_eq!(normalize(a), normalize(b));
+
+        // Addresses, with and without ports and brackets
+        assert_eq!(
+            normalize("220 ProFTPD 1.3.5e Server (Debian) [::ffff:192.0.2.1]"),
+            normalize("220 ProFTPD 1.3.5e Server (Debian) [::ffff:198.51.100.23]")
+        );
+        assert_eq!(normalize("from 192.0.2.1:51234"), "from <ip>");
+        assert_eq!(normalize("peer=[2001:db8::1]:443;"), "peer <ip>");
+
+        // HTTP dates, lengths and ids
+        let response = |date: &str, length: u32, etag: &str| {
+            format!(
+                "HTTP/1.1 200 OK\r\nDate: {date}\r\nServer: nginx/1.18.0 (Ubuntu)\r\n\
+                 Content-Length: {length}\r\nETag: \"{etag}\"\r\n"
+            )
+        };
+        let first = response("Tue, 01 Jun 2021 10:15:42 GMT", 612, "5f3a1b2c-264");
+        let second = response("Sat, 13 Jan 2024 08:00:00 GMT", 1043, "64a2ff01-413");
+        assert_eq!(normalize(&first), normalize(&second));
+        assert_eq!(
+            normalize(&first),
+            "http 1 1 <n> ok date <time> server nginx 1 18 0 ubuntu \
+             content length <n> etag <hex>"
+        );
+
+        // ISO, ctime and syslog stamps
+        for stamp in [
+            "2024-01-13T08:00:00Z",
+            "2024-01-13 08:00:00.123+02:00",
+            "Sat Jan 13 08:00:00 2024",
+            "Jan 13 08:00:00",
+            "08:00:00",
+        ] {
+            assert_eq!(
+                normalize(&format!("started {stamp} ok")),
+                "started <time> ok"
+            );
+        }
+
+        // Emails are hosts; versions and file names are not
+        assert_eq!(normalize("admin@corp.example.com"), HOST);
+        assert_eq!(normalize("Server: Apache/2.4.41"), "server apache 2 4 41");
+        assert_eq!(normalize("GET /index.html"), "get index html");
+    }
+
+    #[test]
+    fn test_near_duplicates_rank_first() {
+        let mut index = BannerIndex::new();
+        index.add_banner(
+            "192.0.2.1",
+            22,
+            "SSH-2.0-OpenSSH_7.4p1 WeirdCorp-hardened-2",
+        );
+        index.add_banner(
+            "192.0.2.2",
+            2222,
+            "SSH-2.0-OpenSSH_7.4p1 WeirdCorp-hardened-3",
+        );
+        index.add_banner("192.0.2.3", 22, "SSH-2.0-OpenSSH_8.9p1 Ubuntu-3ubuntu0.6");
+        index.add_banner("192.0.2.4", 21, "220 (vsFTPd 3.0.3)");
+
+        let found = index.find_similar("SSH-2.0-OpenSSH_7.4p1 WeirdCorp-hardened-2", 0.5);
+        let hits: Vec<(&str, u16)> = found
+            .iter()
+            .map(|(ip, port, _)| (ip.as_str(), *port))
+            .collect();
+        assert_eq!(hits, [("192.0.2.1", 22), ("192.0.2.2", 2222)]);
+        assert!((found[0].2 - 1.0).abs() < f64::EPSILON);
+        assert!(found[1].2 < 1.0);
+
+        // A stock OpenSSH shares something, but not enough
+        let loose = index.find_similar("SSH-2.0-OpenSSH_7.4p1 WeirdCorp-hardened-2", 0.1);
+        assert!(loose.iter().any(|(ip, ..)| ip == "192.0.2.3"));
+        assert!(loose.iter().all(|(ip, ..)| ip != "192.0.2.4"));
+
+        // The same greeting from hosts with different names and clocks
+        let mut index = BannerIndex::new();
+        index.add_banner(
+            "198.51.100.1",
+            25,
+            "220 mx1.victim.example ESMTP Postfix (custom-build-7) Tue, 01 Jun 2021 10:15:42 +0000",
+        );
+        index.add_banner(
+            "198.51.100.2",
+            25,
+            "220 relay.other.example ESMTP Postfix (custom-build-7) Sat, 13 Jan 2024 08:00:00 +0100",
+        );
+        let found = index.find_similar(
+            "220 smtp.third.example ESMTP Postfix (custom-build-7) Mon, 04 Mar 2024 12:00:00 +0000",
+            0.99,
+        );
+        assert_eq!(found.len(), 2);
+        assert!(index.find_similar("", 0.0).is_empty());
+    }
+
+    #[test]
+    fn test_find_product() {
+        let service = |port: u16, banner: &str| -> Service {
+            serde_json::from_value(serde_json::json!({ "port": port, "data": banner })).unwrap()
+        };
+        let host = crate::testing::host(
+            "192.0.2.10",
+            serde_json::json!({
+                "data": [
+                    { "port": 80, "product": "nginx", "version": "1.18.0" },
+                    { "port": 22, "data": "SSH-2.0-OpenSSH_8.9p1 Ubuntu-3ubuntu0.6" },
+                ],
+            }),
+        );
+
+        let mut index = BannerIndex::new();
+        index.add_host(&host);
+        index.add_service("192.0.2.2", &service(2222, "SSH-2.0-OpenSSH_8.9p1"));
+        index.add_service("192.0.2.3", &service(22, "SSH-2.0-OpenSSH_7.4"));
+        // Seen again, from another provider
+        index.add_service("192.0.2.3", &service(22, "SSH-2.0-OpenSSH_7.4"));
+        assert_eq!(index.len(), 4);
+
+        assert_eq!(
+            index.find_product("openssh", Some("8.9P1")),
+            [
+                ("192.0.2.2".to_string(), 2222),
+                ("192.0.2.10".to_string(), 22)
+            ]
+        );
+        assert_eq!(index.find_product("OpenSSH", None).len(), 3);
+        assert_eq!(
+            index.find_product("nginx", Some("1.18.0")),
+            [("192.0.2.10".to_string(), 80)]
+        );
+        assert!(index.find_product("nginx", Some("1.19.0")).is_empty());
+        assert!(index.find_product("Apache httpd", None).is_empty());
+
+        let ssh = index.get("192.0.2.10", 22).unwrap();
+        assert_eq!(ssh.version.as_deref(), Some("8.9p1"));
+    }
+}
@@ -3,7 +3,14 @@
 //! This crate provides the foundational types used across the i1 library:
 //!
 //! - **Types**: Strongly-typed representations of threat intelligence data
+//! - **Countries**: ISO 3166-1 codes and names, whatever a provider sent
+//! - **CPE**: CPE 2.3 identifiers for services, parsed or derived
 //! - **Errors**: Comprehensive error handling with [`I1Error`]
+//! - **Export**: Hosts and vulnerabilities as STIX 2.1 bundles
+//! - **Fingerprints**: The product and version behind a service banner
+//! - **Index**: Collected services found again by banner or product
+//!
+//! The `testing` feature adds a [`testing`] host builder for tests.
 //!
 //! # Example
 //!
@@ -19,8 +26,16 @@
 
 #![doc(html_root_url = "https://docs.rs/i1-core/0.1.0")]
 
+pub mod country;
+pub mod cpe;
 mod error;
+pub mod export;
+pub mod fingerprints;
+pub mod index;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 pub mod types;
 
-pub use error::{I1Error, Result};
+pub use error::{I1Error, ReconKind, Result};
+pub use ipnet::{IpNet, Ipv4Net, Ipv6Net};
 pub use types::*;
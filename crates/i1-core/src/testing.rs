@@ -0,0 +1,23 @@
+//! Hosts for tests, built from the JSON a provider would send.
+//!
+//! Enabled by the `testing` feature, for crates testing code that takes a
+//! [`HostInfo`].
+//!
+//! ```rust,ignore
+//! use i1_core::testing::host;
+//!
+//! let host = host("192.0.2.1", json!({ "ports": [22, 443], "org": "Example" }));
+//! ```
+
+use serde_json::Value;
+
+use crate::HostInfo;
+
+/// A host at `ip` with `fields` as a provider would send them; `json!({})`
+/// for nothing but the address.
+///
+/// Panics if `fields` isn't an object or doesn't fit a [`HostInfo`].
+pub fn host(ip: &str, mut fields: Value) -> HostInfo {
+    fields["ip_str"] = ip.into();
+    serde_json::from_value(fields).unwrap_or_else(|e| panic!("not a host: {e}"))
+}
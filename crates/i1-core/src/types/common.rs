@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use std::net::IpAddr;
 
+use crate::country;
+
 /// Geographic location information
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct GeoLocation {
@@ -56,10 +58,45 @@ impl GeoLocation {
             _ => None,
         }
     }
+
+    /// Put the country in one form: the ISO 3166-1 alpha-2 code, uppercase,
+    /// and the name [`country::country_name`] gives for it. The code is
+    /// worked out from the name when it's missing or not a country code
+    /// (some providers put the name there), and a valid code wins over a
+    /// name that disagrees. Blank text fields
+    /// become `None`.
+    pub fn normalize(&mut self) {
+        for field in [
+            &mut self.country_code,
+            &mut self.country_name,
+            &mut self.city,
+            &mut self.region_code,
+            &mut self.postal_code,
+        ] {
+            *field = field
+                .take()
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty());
+        }
+
+        let code = self
+            .country_code
+            .as_deref()
+            .and_then(country::country_code)
+            .or_else(|| {
+                self.country_name
+                    .as_deref()
+                    .and_then(country::country_code_from_name)
+            });
+        if let Some(code) = code {
+            self.country_code = Some(code.to_string());
+            self.country_name = country::country_name(code).map(String::from);
+        }
+    }
 }
 
 /// Transport protocol for a service
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 #[derive(Default)]
 pub enum Transport {
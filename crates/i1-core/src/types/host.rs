@@ -1,8 +1,11 @@
 use super::{GeoLocation, Transport};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::net::IpAddr;
 
+/// CVSS assumed for a CVE that no banner scores
+const UNSCORED_CVSS: f64 = 5.0;
+
 /// Complete host information from Shodan
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HostInfo {
@@ -60,13 +63,19 @@ pub struct HostInfo {
     /// Last time the host was scanned
     #[serde(default)]
     pub last_update: Option<String>,
+
+    /// The provider's response before it was normalized, kept only when the
+    /// provider was built with `include_raw`. Never serialized
+    #[serde(skip)]
+    pub raw: Option<Box<serde_json::Value>>,
 }
 
 impl HostInfo {
-    /// Returns the IP address, parsing from string if needed
+    /// Returns the IP address, parsing from string if needed. A bracketed
+    /// IPv6 address parses too
     #[must_use]
     pub fn ip_addr(&self) -> Option<IpAddr> {
-        self.ip.or_else(|| self.ip_str.parse().ok())
+        self.ip.or_else(|| crate::validate_ip(&self.ip_str).ok())
     }
 
     /// Returns true if the host has known vulnerabilities
@@ -80,6 +89,298 @@ impl HostInfo {
     pub fn service_count(&self) -> usize {
         self.data.len()
     }
+
+    /// Returns the CPEs of every service on the host, sorted and without
+    /// duplicates
+    #[must_use]
+    pub fn cpes(&self) -> Vec<String> {
+        self.data
+            .iter()
+            .flat_map(|service| service.cpe.iter().cloned())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    /// Normalizes or derives each service's CPEs; see [`crate::cpe::fill`]
+    pub fn fill_cpes(&mut self) {
+        for service in &mut self.data {
+            crate::cpe::fill(service);
+        }
+    }
+
+    /// Returns every CVE on the host with the highest CVSS score its
+    /// banners carry, or `None` where no banner scores it
+    #[must_use]
+    pub fn vuln_scores(&self) -> BTreeMap<String, Option<f64>> {
+        let mut scores: BTreeMap<String, Option<f64>> =
+            self.vulns.iter().map(|cve| (cve.clone(), None)).collect();
+
+        for service in &self.data {
+            for (cve, info) in &service.vulns {
+                let score = scores.entry(cve.clone()).or_insert(None);
+                if let Some(cvss) = info.cvss {
+                    *score = Some(score.map_or(cvss, |s| s.max(cvss)));
+                }
+            }
+        }
+        scores
+    }
+
+    /// Returns a 0-10 risk score: the worst CVSS on the host, raised by 0.5
+    /// for each further high or critical CVE. Unscored CVEs count as medium.
+    #[must_use]
+    pub fn risk_score(&self) -> f64 {
+        let scores: Vec<f64> = self
+            .vuln_scores()
+            .into_values()
+            .map(|score| score.unwrap_or(UNSCORED_CVSS))
+            .collect();
+        let Some(worst) = scores.iter().copied().reduce(f64::max) else {
+            return 0.0;
+        };
+
+        let severe = scores.iter().filter(|&&score| score >= 7.0).count();
+        let extra = u32::try_from(severe.saturating_sub(1)).unwrap_or(u32::MAX);
+        0.5f64.mul_add(f64::from(extra), worst).min(10.0)
+    }
+
+    /// Returns the open ports, from the port list and the service banners
+    #[must_use]
+    pub fn open_ports(&self) -> BTreeSet<u16> {
+        self.ports
+            .iter()
+            .copied()
+            .chain(self.data.iter().map(|s| s.port))
+            .collect()
+    }
+
+    /// Returns what changed between this lookup and a `newer` one of the same host
+    #[must_use]
+    pub fn diff(&self, newer: &Self) -> HostDiff {
+        let (old_ports, new_ports) = (self.open_ports(), newer.open_ports());
+        let (old_services, new_services) = (service_labels(self), service_labels(newer));
+
+        HostDiff {
+            opened_ports: new_ports.difference(&old_ports).copied().collect(),
+            closed_ports: old_ports.difference(&new_ports).copied().collect(),
+            new_vulns: added(&self.vulns, &newer.vulns),
+            resolved_vulns: added(&newer.vulns, &self.vulns),
+            added_tags: added(&self.tags, &newer.tags),
+            removed_tags: added(&newer.tags, &self.tags),
+            added_hostnames: added(&self.hostnames, &newer.hostnames),
+            removed_hostnames: added(&newer.hostnames, &self.hostnames),
+            changed_services: old_services
+                .iter()
+                .filter_map(|(&(port, transport), before)| {
+                    let after = new_services.get(&(port, transport))?;
+                    (before != after).then(|| ServiceChange {
+                        port,
+                        transport,
+                        before: before.clone(),
+                        after: after.clone(),
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    /// Put the record in one canonical form, whichever provider it came
+    /// from: `AS15169` ASNs, ISO country codes and names, lowercase
+    /// hostnames and domains without the trailing dot, uppercase CVE ids,
+    /// and every list sorted without duplicates. Blank single-valued fields
+    /// become `None`.
+    pub fn normalize(&mut self) {
+        if let Some(ip) = self.ip_addr() {
+            self.ip = Some(ip);
+            self.ip_str = ip.to_string();
+        }
+
+        for field in [&mut self.org, &mut self.isp, &mut self.os] {
+            *field = field
+                .take()
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty());
+        }
+        self.asn = self.asn.as_deref().and_then(normalize_asn);
+        self.location.normalize();
+
+        for names in [&mut self.hostnames, &mut self.domains] {
+            for name in names.iter_mut() {
+                *name = name.trim().trim_end_matches('.').to_lowercase();
+            }
+        }
+        for tag in &mut self.tags {
+            *tag = tag.trim().to_lowercase();
+        }
+        for cve in &mut self.vulns {
+            *cve = cve.trim().to_uppercase();
+        }
+        for list in [
+            &mut self.hostnames,
+            &mut self.domains,
+            &mut self.tags,
+            &mut self.vulns,
+        ] {
+            list.retain(|value| !value.is_empty());
+            list.sort();
+            list.dedup();
+        }
+
+        self.ports.sort_unstable();
+        self.ports.dedup();
+    }
+
+    /// Fold another record of the same host into this one, e.g. from a
+    /// later page or another provider.
+    ///
+    /// Single-valued fields already set here win, and the location is only
+    /// taken when this record has none. Lists are unioned, services are
+    /// keyed by port and transport, and `last_update` is the later of the
+    /// two. Raw responses from both sides are kept, gathered into one array.
+    /// The result is normalized.
+    pub fn merge(&mut self, other: Self) {
+        self.ip = self.ip.or(other.ip);
+        self.org = self.org.take().or(other.org);
+        self.asn = self.asn.take().or(other.asn);
+        self.isp = self.isp.take().or(other.isp);
+        self.os = self.os.take().or(other.os);
+        self.raw = match (self.raw.take(), other.raw) {
+            (Some(raw), Some(other)) => Some(Box::new(merge_raw(*raw, *other))),
+            (raw, other) => raw.or(other),
+        };
+        if self.location.country_code.is_none() && self.location.country_name.is_none() {
+            self.location = other.location;
+        }
+        // Both are ISO 8601, so the later one sorts last
+        self.last_update = self.last_update.take().max(other.last_update);
+
+        self.hostnames.extend(other.hostnames);
+        self.domains.extend(other.domains);
+        self.ports.extend(other.ports);
+        self.vulns.extend(other.vulns);
+        self.tags.extend(other.tags);
+        for service in other.data {
+            let known = self
+                .data
+                .iter()
+                .any(|s| s.port == service.port && s.transport == service.transport);
+            if !known {
+                self.data.push(service);
+            }
+        }
+
+        self.normalize();
+    }
+}
+
+/// Both raw responses as one array. A side that is already an array, such as
+/// a Shodan search host built from several banners, adds its items
+fn merge_raw(raw: serde_json::Value, other: serde_json::Value) -> serde_json::Value {
+    let mut raws = match raw {
+        serde_json::Value::Array(raws) => raws,
+        raw => vec![raw],
+    };
+    match other {
+        serde_json::Value::Array(more) => raws.extend(more),
+        other => raws.push(other),
+    }
+    serde_json::Value::Array(raws)
+}
+
+/// `AS15169` for `15169`, `as15169` or `AS 15169`; anything else that
+/// isn't blank is kept as it is
+fn normalize_asn(asn: &str) -> Option<String> {
+    let asn = asn.trim();
+    let digits = asn
+        .get(..2)
+        .filter(|prefix| prefix.eq_ignore_ascii_case("as"))
+        .map_or(asn, |_| asn[2..].trim_start());
+    digits.parse::<u32>().map_or_else(
+        |_| (!asn.is_empty()).then(|| asn.to_string()),
+        |number| Some(format!("AS{number}")),
+    )
+}
+
+/// Changes between two lookups of the same host, from [`HostInfo::diff`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HostDiff {
+    /// Ports open now that weren't before
+    pub opened_ports: Vec<u16>,
+
+    /// Ports no longer open
+    pub closed_ports: Vec<u16>,
+
+    /// Vulnerabilities that appeared
+    pub new_vulns: Vec<String>,
+
+    /// Vulnerabilities no longer reported
+    pub resolved_vulns: Vec<String>,
+
+    /// Tags that appeared
+    pub added_tags: Vec<String>,
+
+    /// Tags no longer reported
+    pub removed_tags: Vec<String>,
+
+    /// Hostnames that appeared
+    pub added_hostnames: Vec<String>,
+
+    /// Hostnames no longer reported
+    pub removed_hostnames: Vec<String>,
+
+    /// Services still open whose product or version changed
+    pub changed_services: Vec<ServiceChange>,
+}
+
+impl HostDiff {
+    /// Returns true if nothing changed
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// A service whose product or version changed between lookups
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServiceChange {
+    /// Port number
+    pub port: u16,
+
+    /// Transport protocol
+    pub transport: Transport,
+
+    /// Product and version before (e.g. "nginx 1.18.0")
+    pub before: Option<String>,
+
+    /// Product and version after
+    pub after: Option<String>,
+}
+
+/// Values in `new` that aren't in `old`, sorted
+fn added(old: &[String], new: &[String]) -> Vec<String> {
+    let old: BTreeSet<&String> = old.iter().collect();
+    new.iter()
+        .filter(|v| !old.contains(v))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .cloned()
+        .collect()
+}
+
+/// "product version" per port and transport
+fn service_labels(host: &HostInfo) -> BTreeMap<(u16, Transport), Option<String>> {
+    host.data
+        .iter()
+        .map(|s| {
+            let label = match (&s.product, &s.version) {
+                (Some(product), Some(version)) => Some(format!("{product} {version}")),
+                (Some(product), None) => Some(product.clone()),
+                (None, _) => None,
+            };
+            ((s.port, s.transport), label)
+        })
+        .collect()
 }
 
 /// Individual service/banner information
@@ -480,3 +781,41 @@ pub struct VulnInfo {
     #[serde(default)]
     pub references: Vec<String>,
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::host;
+    use serde_json::json;
+
+    #[test]
+    fn test_merge_keeps_both_raws() {
+        let mut shodan = host("192.0.2.1", json!({ "ports": [22] }));
+        shodan.raw = Some(Box::new(json!({"_shodan": {"module": "ssh"}})));
+        let mut censys = host("192.0.2.1", json!({ "ports": [80] }));
+        censys.raw = Some(Box::new(json!({"ip": "192.0.2.1"})));
+        let mut native = host("192.0.2.1", json!({ "ports": [443] }));
+        native.raw = Some(Box::new(json!([{"port": 443}, {"port": 8443}])));
+
+        shodan.merge(censys);
+        shodan.merge(native);
+        assert_eq!(
+            shodan.raw.as_deref(),
+            Some(&json!([
+                {"_shodan": {"module": "ssh"}},
+                {"ip": "192.0.2.1"},
+                {"port": 443},
+                {"port": 8443},
+            ]))
+        );
+
+        let mut bare = host("192.0.2.1", json!({}));
+        bare.merge(shodan);
+        assert_eq!(
+            bare.raw
+                .as_deref()
+                .and_then(|raw| raw.as_array())
+                .map(Vec::len),
+            Some(4)
+        );
+    }
+}
@@ -6,6 +6,7 @@ mod host;
 mod notifier;
 mod scan;
 mod search;
+mod target;
 
 pub use account::*;
 pub use alert::*;
@@ -15,3 +16,4 @@ pub use host::*;
 pub use notifier::*;
 pub use scan::*;
 pub use search::*;
+pub use target::*;
@@ -0,0 +1,297 @@
+use crate::{I1Error, Result};
+use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// Longest domain name DNS allows
+const MAX_DOMAIN_LEN: usize = 253;
+
+/// Something to investigate, recognized from what a user typed.
+///
+/// Parsing normalizes as it goes: domains are lowercased without a trailing
+/// dot, CIDR blocks are truncated to their network, a block of one address
+/// is an [`Target::Ip`], and `[::1]` is an IPv6 address.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "lowercase")]
+pub enum Target {
+    /// A single IP address
+    Ip(IpAddr),
+    /// A hostname or domain
+    Domain(String),
+    /// A block of addresses
+    Cidr(IpNet),
+    /// A URL, investigated through its host
+    Url(String),
+}
+
+impl Target {
+    /// Recognize `input`, or say why it is none of the target kinds.
+    pub fn parse(input: &str) -> Result<Self> {
+        let input = input.trim();
+        if input.is_empty() {
+            return Err(I1Error::InvalidQuery("empty target".to_string()));
+        }
+
+        if input.contains("://") {
+            let url =
+                url::Url::parse(input).map_err(|e| I1Error::InvalidUrl(format!("{input}: {e}")))?;
+            if url.host().is_none() {
+                return Err(I1Error::InvalidUrl(format!("{input}: no host")));
+            }
+            return Ok(Self::Url(url.to_string()));
+        }
+
+        if let Ok(ip) = unbracket(input).parse::<IpAddr>() {
+            return Ok(Self::Ip(ip));
+        }
+        if input.contains('/') {
+            let net = input
+                .parse::<IpNet>()
+                .map_err(|_| I1Error::InvalidQuery(format!("'{input}' is not a CIDR block")))?;
+            return Ok(if net.prefix_len() == net.max_prefix_len() {
+                Self::Ip(net.addr())
+            } else {
+                Self::Cidr(net.trunc())
+            });
+        }
+
+        // All digits and dots is a mistyped IPv4 address, not a domain
+        if input.chars().all(|c| c.is_ascii_digit() || c == '.') {
+            return Err(I1Error::InvalidIp(input.to_string()));
+        }
+
+        let domain = input.trim_end_matches('.').to_ascii_lowercase();
+        if is_domain(&domain) {
+            Ok(Self::Domain(domain))
+        } else {
+            Err(I1Error::InvalidQuery(format!(
+                "'{input}' is not an IP address, CIDR block, domain or URL"
+            )))
+        }
+    }
+
+    /// What a URL points at: its IP or domain. Other targets are their own
+    /// host.
+    #[must_use]
+    pub fn host(&self) -> Self {
+        let Self::Url(url) = self else {
+            return self.clone();
+        };
+        match url::Url::parse(url)
+            .ok()
+            .and_then(|url| url.host().map(|h| h.to_owned()))
+        {
+            Some(url::Host::Ipv4(ip)) => Self::Ip(ip.into()),
+            Some(url::Host::Ipv6(ip)) => Self::Ip(ip.into()),
+            Some(url::Host::Domain(domain)) => Self::Domain(domain.to_ascii_lowercase()),
+            // Only URLs with a host parse, so this is unreachable in practice
+            None => self.clone(),
+        }
+    }
+
+    /// Kind of target, as used in serialized output
+    #[must_use]
+    pub const fn kind(&self) -> &'static str {
+        match self {
+            Self::Ip(_) => "ip",
+            Self::Domain(_) => "domain",
+            Self::Cidr(_) => "cidr",
+            Self::Url(_) => "url",
+        }
+    }
+}
+
+impl FromStr for Target {
+    type Err = I1Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ip(ip) => write!(f, "{ip}"),
+            Self::Domain(domain) | Self::Url(domain) => f.write_str(domain),
+            Self::Cidr(net) => write!(f, "{net}"),
+        }
+    }
+}
+
+impl From<IpAddr> for Target {
+    fn from(ip: IpAddr) -> Self {
+        Self::Ip(ip)
+    }
+}
+
+/// `ip` as an address, or [`I1Error::InvalidIp`] before anything is sent
+/// to a provider. An IPv6 address may be bracketed, as in a URL; its
+/// `Display` is the canonical form to send on.
+pub fn validate_ip(ip: &str) -> Result<IpAddr> {
+    unbracket(ip.trim())
+        .parse()
+        .map_err(|_| I1Error::InvalidIp(ip.to_string()))
+}
+
+/// `::1` for `[::1]`; anything else as it is.
+fn unbracket(input: &str) -> &str {
+    input
+        .strip_prefix('[')
+        .and_then(|rest| rest.strip_suffix(']'))
+        .unwrap_or(input)
+}
+
+/// [`I1Error::InvalidQuery`] for a query with nothing in it.
+pub fn validate_query(query: &str) -> Result<()> {
+    if query.trim().is_empty() {
+        return Err(I1Error::InvalidQuery("empty query".to_string()));
+    }
+    Ok(())
+}
+
+/// Dot-separated labels of letters, digits, `-` and `_`, none empty or
+/// longer than 63 characters, and none starting or ending with `-`.
+fn is_domain(name: &str) -> bool {
+    name.len() <= MAX_DOMAIN_LEN
+        && name.split('.').all(|label| {
+            (1..=63).contains(&label.len())
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> Target {
+        Target::parse(input).unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse(" 8.8.8.8 "), Target::Ip("8.8.8.8".parse().unwrap()));
+        assert_eq!(
+            parse("[2001:db8::1]"),
+            Target::Ip("2001:db8::1".parse().unwrap())
+        );
+        assert_eq!(
+            parse("192.0.2.7/32"),
+            Target::Ip("192.0.2.7".parse().unwrap())
+        );
+        assert_eq!(
+            parse("192.0.2.7/24"),
+            Target::Cidr("192.0.2.0/24".parse().unwrap())
+        );
+        assert_eq!(
+            parse("WWW.Example.com."),
+            Target::Domain("www.example.com".into())
+        );
+        assert_eq!(
+            parse("https://example.com/login?next=/"),
+            Target::Url("https://example.com/login?next=/".into())
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(matches!(Target::parse(""), Err(I1Error::InvalidQuery(_))));
+        assert!(matches!(Target::parse("1.2.3"), Err(I1Error::InvalidIp(_))));
+        assert!(matches!(
+            Target::parse("999.1.1.1"),
+            Err(I1Error::InvalidIp(_))
+        ));
+        assert!(matches!(
+            Target::parse("10.0.0.0/33"),
+            Err(I1Error::InvalidQuery(_))
+        ));
+        assert!(matches!(
+            Target::parse("not a host"),
+            Err(I1Error::InvalidQuery(_))
+        ));
+        assert!(matches!(
+            Target::parse("-bad.com"),
+            Err(I1Error::InvalidQuery(_))
+        ));
+        assert!(matches!(
+            Target::parse("http://"),
+            Err(I1Error::InvalidUrl(_))
+        ));
+    }
+
+    #[test]
+    fn test_host() {
+        assert_eq!(
+            parse("https://user@Example.COM:8443/x").host(),
+            Target::Domain("example.com".into())
+        );
+        assert_eq!(
+            parse("http://[2001:db8::1]:8080/").host(),
+            Target::Ip("2001:db8::1".parse().unwrap())
+        );
+        assert_eq!(parse("example.com").host(), parse("example.com"));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(validate_ip("192.0.2.1").is_ok());
+        assert!(matches!(
+            validate_ip("example.com"),
+            Err(I1Error::InvalidIp(_))
+        ));
+        assert!(validate_query("port:22").is_ok());
+        assert!(matches!(
+            validate_query("  "),
+            Err(I1Error::InvalidQuery(_))
+        ));
+    }
+
+    /// IPv6 addresses as users and providers write them, with the form i1
+    /// keeps. Shared with the providers, scanner and CLI tests.
+    const IPV6: &str = include_str!("../../tests/fixtures/ipv6.txt");
+
+    #[test]
+    fn test_ipv6_matrix() {
+        for line in IPV6
+            .lines()
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        {
+            let (input, canonical) = line.split_once('\t').unwrap();
+            let ip: IpAddr = canonical.parse().unwrap();
+            assert!(ip.is_ipv6(), "{line}");
+
+            assert_eq!(validate_ip(input).unwrap(), ip, "{input}");
+            assert_eq!(validate_ip(input).unwrap().to_string(), canonical);
+            assert_eq!(parse(input), Target::Ip(ip), "{input}");
+            assert_eq!(parse(&format!("{canonical}/128")), Target::Ip(ip));
+
+            let mut host = crate::testing::host(input, serde_json::json!({}));
+            assert_eq!(host.ip_addr(), Some(ip), "{input}");
+            host.normalize();
+            assert_eq!(host.ip_str, canonical);
+        }
+        assert!(matches!(
+            validate_ip("[192.0.2.1"),
+            Err(I1Error::InvalidIp(_))
+        ));
+        assert!(matches!(
+            validate_ip("2001:db8::1::2"),
+            Err(I1Error::InvalidIp(_))
+        ));
+    }
+
+    #[test]
+    fn test_serialize() {
+        let json = serde_json::to_value(parse("192.0.2.0/24")).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({ "type": "cidr", "value": "192.0.2.0/24" })
+        );
+    }
+}
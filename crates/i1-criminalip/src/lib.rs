@@ -20,11 +20,11 @@ use std::sync::Arc;
 use std::time::Instant;
 
 use async_trait::async_trait;
-use governor::{Quota, RateLimiter};
-use i1_core::{GeoLocation, HostInfo, I1Error, Result, Service};
+use governor::Quota;
+use i1_core::{cpe, fingerprints, validate_ip, GeoLocation, HostInfo, I1Error, Result, Service};
 use i1_providers::{
-    AuthConfig, HealthStatus, HostLookup, Provider, ProviderHealth, RateLimitConfig,
-    SearchProvider, SearchResults,
+    schema, AuthConfig, HealthStatus, HostLookup, Provider, ProviderHealth, ProviderHttpConfig,
+    RateLimitConfig, RateLimiter, SearchProvider, SearchResults,
 };
 use reqwest::Client;
 use serde::Deserialize;
@@ -36,17 +36,25 @@ const DEFAULT_BASE_URL: &str = "https://api.criminalip.io/v1";
 /// Criminal IP provider for i1
 pub struct CriminalIpProvider {
     inner: Arc<CriminalIpInner>,
+    /// Keep each host's original JSON in `HostInfo::raw`
+    include_raw: bool,
 }
 
+#[derive(Clone)]
 struct CriminalIpInner {
     http: Client,
+    http_config: ProviderHttpConfig,
     api_key: String,
     base_url: String,
-    rate_limiter: RateLimiter<
-        governor::state::NotKeyed,
-        governor::state::InMemoryState,
-        governor::clock::DefaultClock,
-    >,
+    rate_limiter: Arc<RateLimiter>,
+}
+
+/// How often a provider with `rate_limit` may call the API
+fn quota(rate_limit: &RateLimitConfig) -> Quota {
+    Quota::per_second(
+        NonZeroU32::new(rate_limit.requests_per_second.max(1.0) as u32).unwrap_or(NonZeroU32::MIN),
+    )
+    .allow_burst(NonZeroU32::new(rate_limit.burst_size).unwrap_or(NonZeroU32::MIN))
 }
 
 impl CriminalIpProvider {
@@ -55,32 +63,79 @@ impl CriminalIpProvider {
         Self::with_config(api_key, RateLimitConfig::criminalip())
     }
 
+    /// Create a provider that shares its rate limiter with every other
+    /// `new_shared` instance for the same key. Providers made with
+    /// [`new`](Self::new) keep a limiter of their own.
+    pub fn new_shared(api_key: impl Into<String>) -> Self {
+        let api_key = api_key.into();
+        let quota = quota(&RateLimitConfig::criminalip());
+        let rate_limiter = i1_providers::shared_limiter("criminalip", &api_key, quota);
+        Self::with_limiter(api_key, rate_limiter)
+    }
+
     /// Create with custom rate limit config
     pub fn with_config(api_key: impl Into<String>, rate_limit: RateLimitConfig) -> Self {
-        let quota = Quota::per_second(
-            NonZeroU32::new(rate_limit.requests_per_second.max(1.0) as u32)
-                .unwrap_or(NonZeroU32::MIN),
-        )
-        .allow_burst(NonZeroU32::new(rate_limit.burst_size).unwrap_or(NonZeroU32::MIN));
+        let rate_limiter = Arc::new(RateLimiter::direct(quota(&rate_limit)));
+        Self::with_limiter(api_key.into(), rate_limiter)
+    }
 
+    fn with_limiter(api_key: String, rate_limiter: Arc<RateLimiter>) -> Self {
         Self {
             inner: Arc::new(CriminalIpInner {
                 http: Client::new(),
-                api_key: api_key.into(),
+                http_config: ProviderHttpConfig::default(),
+                api_key,
                 base_url: DEFAULT_BASE_URL.to_string(),
-                rate_limiter: RateLimiter::direct(quota),
+                rate_limiter,
             }),
+            include_raw: false,
         }
     }
 
+    /// Send requests to another API endpoint, such as a proxy or a mock
+    /// server. Clones made before this call are not affected
+    #[must_use]
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        Arc::make_mut(&mut self.inner).base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Connect with these HTTP settings, e.g. through a proxy. Clones made
+    /// before this call are not affected
+    pub fn with_http_config(mut self, config: &ProviderHttpConfig) -> Result<Self> {
+        let http = config.client()?;
+        let inner = Arc::make_mut(&mut self.inner);
+        inner.http = http;
+        inner.http_config = config.clone();
+        Ok(self)
+    }
+
+    /// Keep Criminal IP's original JSON on hosts it looks up, in
+    /// `HostInfo::raw`. Clones made before this call are not affected
+    #[must_use]
+    pub const fn include_raw(mut self, include: bool) -> Self {
+        self.include_raw = include;
+        self
+    }
+
     /// Get authentication config for this provider
     pub fn auth_config(&self) -> AuthConfig {
         AuthConfig::criminalip(&self.inner.api_key)
     }
 
     /// Make a GET request to the Criminal IP API
-    #[instrument(skip(self), fields(provider = "criminalip"))]
     async fn get<T: serde::de::DeserializeOwned>(&self, endpoint: &str) -> Result<T> {
+        self.get_with_query(endpoint, &[]).await
+    }
+
+    /// Make a GET request with query parameters, encoded so that an IPv6
+    /// address or a search with `&` in it arrives as it was given
+    #[instrument(skip(self), fields(provider = "criminalip"))]
+    async fn get_with_query<T: serde::de::DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        query: &[(&str, &str)],
+    ) -> Result<T> {
         self.inner.rate_limiter.until_ready().await;
 
         let url = format!("{}{}", self.inner.base_url, endpoint);
@@ -90,10 +145,11 @@ impl CriminalIpProvider {
             .inner
             .http
             .get(&url)
+            .query(query)
             .header("x-api-key", &self.inner.api_key)
             .send()
             .await
-            .map_err(|e| I1Error::Http(e.to_string()))?;
+            .map_err(|e| self.inner.http_config.request_error(&e))?;
 
         let status = response.status();
         if !status.is_success() {
@@ -110,10 +166,11 @@ impl CriminalIpProvider {
             };
         }
 
-        response
+        let value = response
             .json()
             .await
-            .map_err(|e| I1Error::Http(e.to_string()))
+            .map_err(|e| I1Error::Http(e.to_string()))?;
+        schema::parse("criminalip", endpoint, &value)
     }
 
     /// Convert Criminal IP response to i1 `HostInfo`
@@ -121,23 +178,29 @@ impl CriminalIpProvider {
         let services: Vec<Service> = host
             .port
             .into_iter()
-            .map(|p| Service {
-                port: p.open_port_no as u16,
-                transport: i1_core::Transport::Tcp,
-                product: p.app_name,
-                version: p.app_version,
-                cpe: vec![],
-                data: p.banner,
-                timestamp: None,
-                shodan_module: None,
-                http: None,
-                ssl: None,
-                ssh: None,
-                vulns: std::collections::HashMap::new(),
-                tags: vec![],
-                devicetype: None,
-                info: None,
-                os: None,
+            .map(|p| {
+                let mut service = Service {
+                    port: p.open_port_no as u16,
+                    transport: i1_core::Transport::Tcp,
+                    product: p.app_name,
+                    version: p.app_version,
+                    cpe: vec![],
+                    data: p.banner,
+                    timestamp: None,
+                    shodan_module: None,
+                    http: None,
+                    ssl: None,
+                    ssh: None,
+                    vulns: std::collections::HashMap::new(),
+                    tags: vec![],
+                    devicetype: None,
+                    info: None,
+                    os: None,
+                };
+                // Criminal IP often has the banner but not the product
+                fingerprints::identify(&mut service);
+                cpe::fill(&mut service);
+                service
             })
             .collect();
 
@@ -191,6 +254,7 @@ impl CriminalIpProvider {
             },
             data: services,
             last_update: None,
+            raw: None,
         }
     }
 }
@@ -199,6 +263,7 @@ impl Clone for CriminalIpProvider {
     fn clone(&self) -> Self {
         Self {
             inner: Arc::clone(&self.inner),
+            include_raw: self.include_raw,
         }
     }
 }
@@ -238,6 +303,9 @@ impl Provider for CriminalIpProvider {
                     latency_ms: Some(start.elapsed().as_millis() as u64),
                     credits_remaining: credits,
                     message: None,
+                    plan: None,
+                    scan_credits: None,
+                    resets_at: None,
                 })
             }
             Err(I1Error::Unauthorized) => Ok(ProviderHealth {
@@ -246,6 +314,9 @@ impl Provider for CriminalIpProvider {
                 latency_ms: Some(start.elapsed().as_millis() as u64),
                 credits_remaining: None,
                 message: Some("Invalid API key".to_string()),
+                plan: None,
+                scan_credits: None,
+                resets_at: None,
             }),
             Err(e) => Ok(ProviderHealth {
                 provider: "criminalip".to_string(),
@@ -253,6 +324,9 @@ impl Provider for CriminalIpProvider {
                 latency_ms: Some(start.elapsed().as_millis() as u64),
                 credits_remaining: None,
                 message: Some(e.to_string()),
+                plan: None,
+                scan_credits: None,
+                resets_at: None,
             }),
         }
     }
@@ -262,17 +336,29 @@ impl Provider for CriminalIpProvider {
 impl HostLookup for CriminalIpProvider {
     #[instrument(skip(self), fields(provider = "criminalip"))]
     async fn lookup_host(&self, ip: &str) -> Result<HostInfo> {
-        let response: CriminalIpResponse = self.get(&format!("/asset/ip/report?ip={ip}")).await?;
+        let ip = validate_ip(ip)?.to_string();
+        let endpoint = "/asset/ip/report";
+        let raw: serde_json::Value = self.get_with_query(endpoint, &[("ip", &ip)]).await?;
 
-        if response.status != 200 {
+        // Failures come back as a 200 with their own status and no data
+        if let Some(status) = raw["status"].as_u64().filter(|&status| status != 200) {
             return Err(I1Error::provider(
                 "criminalip",
-                response.status as u16,
-                response.message.unwrap_or_default(),
+                u16::try_from(status).unwrap_or(500),
+                raw["message"].as_str().unwrap_or_default(),
             ));
         }
 
-        Ok(Self::convert_host(response.data))
+        let mut host = schema::recover_host(
+            "criminalip",
+            endpoint,
+            &raw,
+            |response: CriminalIpResponse| Self::convert_host(response.data),
+        )?;
+        if self.include_raw {
+            host.raw = Some(Box::new(raw));
+        }
+        Ok(host)
     }
 }
 
@@ -281,8 +367,9 @@ impl SearchProvider for CriminalIpProvider {
     #[instrument(skip(self), fields(provider = "criminalip"))]
     async fn search(&self, query: &str, page: Option<u32>) -> Result<SearchResults> {
         let offset = page.map_or(0, |p| (p - 1) * 10);
+        let offset = offset.to_string();
         let response: CriminalIpSearchResponse = self
-            .get(&format!("/banner/search?query={query}&offset={offset}"))
+            .get_with_query("/banner/search", &[("query", query), ("offset", &offset)])
             .await?;
 
         if response.status != 200 {
@@ -317,6 +404,7 @@ impl SearchProvider for CriminalIpProvider {
                 },
                 data: vec![],
                 last_update: None,
+                raw: None,
             })
             .collect();
 
@@ -332,7 +420,7 @@ impl SearchProvider for CriminalIpProvider {
     #[instrument(skip(self), fields(provider = "criminalip"))]
     async fn count(&self, query: &str) -> Result<u64> {
         let response: CriminalIpSearchResponse = self
-            .get(&format!("/banner/search?query={query}&offset=0"))
+            .get_with_query("/banner/search", &[("query", query), ("offset", "0")])
             .await?;
 
         if response.status != 200 {
@@ -350,8 +438,6 @@ impl SearchProvider for CriminalIpProvider {
 // Criminal IP specific types
 #[derive(Debug, Deserialize)]
 struct CriminalIpResponse {
-    status: i32,
-    message: Option<String>,
     data: CriminalIpHost,
 }
 
@@ -443,3 +529,70 @@ struct CriminalIpSearchResult {
     #[serde(default)]
     as_no: Option<u32>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{header, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const IPV6: &str = include_str!("../../i1-core/tests/fixtures/ipv6.txt");
+
+    #[tokio::test]
+    async fn test_lookup_ipv6() {
+        for line in IPV6
+            .lines()
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        {
+            let (input, canonical) = line.split_once('\t').unwrap();
+            let server = MockServer::start().await;
+            Mock::given(method("GET"))
+                .and(path("/asset/ip/report"))
+                .and(query_param("ip", canonical))
+                .and(header("x-api-key", "test-key"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                    "status": 200,
+                    "data": { "ip": canonical, "port": [] }
+                })))
+                .expect(1)
+                .mount(&server)
+                .await;
+            let provider = CriminalIpProvider::new("test-key").with_base_url(server.uri());
+
+            let host = provider.lookup_host(input).await.unwrap();
+            assert_eq!(host.ip_str, canonical, "{input}");
+            assert_eq!(host.ip, canonical.parse().ok(), "{input}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_search_query_encoded() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/banner/search"))
+            .and(query_param("query", "ip:2001:db8::/32 & port:443"))
+            .and(query_param("offset", "10"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "status": 200,
+                "data": {
+                    "count": 11,
+                    "result": [{ "ip_address": "2001:db8::443", "open_port_no": 443 }]
+                }
+            })))
+            .mount(&server)
+            .await;
+        let provider = CriminalIpProvider::new("test-key").with_base_url(server.uri());
+
+        let results = provider
+            .search("ip:2001:db8::/32 & port:443", Some(2))
+            .await
+            .unwrap();
+        assert_eq!(results.total, 11);
+        assert_eq!(results.results[0].ip, "2001:db8::443".parse().ok());
+        assert!(matches!(
+            provider.lookup_host("2001:db8::zz").await,
+            Err(I1Error::InvalidIp(_))
+        ));
+    }
+}
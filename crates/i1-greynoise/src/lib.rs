@@ -0,0 +1,899 @@
+//! # i1-greynoise
+//!
+//! `GreyNoise` provider implementation for i1 threat intelligence.
+//!
+//! This crate provides access to the [GreyNoise](https://www.greynoise.io) API,
+//! implementing the i1 provider traits. `GreyNoise` knows which addresses scan
+//! the internet, whether that traffic is benign or malicious, and which
+//! addresses belong to common business services (RIOT).
+//!
+//! # Keys
+//!
+//! Enterprise keys ([`GreyNoiseProvider::new`]) get the full IP context and
+//! GNQL search. Community keys ([`GreyNoiseProvider::community`]) get the
+//! community summary of an IP and nothing else:
+//!
+//! - GNQL searches and counts fail with [`I1Error::Unauthorized`] without
+//!   being sent, as does any paid endpoint `GreyNoise` refuses the key for.
+//! - Running out of the daily community allowance fails with
+//!   [`I1Error::InsufficientCredits`] rather than [`I1Error::RateLimited`],
+//!   since retrying won't help until the next day.
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! use i1_greynoise::GreyNoiseProvider;
+//! use i1_providers::{Provider, ReputationProvider};
+//!
+//! let provider = GreyNoiseProvider::community("your-api-key");
+//! let reputation = provider.reputation("8.8.8.8").await?;
+//! println!("{:?} ({:?})", reputation.classification, reputation.actor);
+//! ```
+
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use governor::{Quota, RateLimiter};
+use i1_core::{validate_ip, GeoLocation, HostInfo, I1Error, Result};
+use i1_providers::{
+    schema, AuthConfig, HealthStatus, HostLookup, IpClassification, IpReputation, Provider,
+    ProviderHealth, ProviderHttpConfig, RateLimitConfig, ReputationProvider, SearchProvider,
+    SearchResults,
+};
+use reqwest::header::RETRY_AFTER;
+use reqwest::Client;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::num::NonZeroU32;
+use tracing::{debug, instrument};
+
+const DEFAULT_BASE_URL: &str = "https://api.greynoise.io";
+
+/// Results per page of a GNQL search
+const PAGE_SIZE: u32 = 100;
+
+/// Which `GreyNoise` API a key opens
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tier {
+    /// Free community API: a summary per IP, a limited number a day
+    Community,
+    /// Paid API: full IP context and GNQL search
+    Enterprise,
+}
+
+/// `GreyNoise` provider for i1
+pub struct GreyNoiseProvider {
+    inner: Arc<GreyNoiseInner>,
+}
+
+#[derive(Clone)]
+struct GreyNoiseInner {
+    http: Client,
+    http_config: ProviderHttpConfig,
+    api_key: String,
+    base_url: String,
+    tier: Tier,
+    /// Shared with the copies [`GreyNoiseProvider::with_base_url`] and
+    /// [`GreyNoiseProvider::with_http_config`] make of a shared provider
+    rate_limiter: Arc<
+        RateLimiter<
+            governor::state::NotKeyed,
+            governor::state::InMemoryState,
+            governor::clock::DefaultClock,
+        >,
+    >,
+}
+
+impl GreyNoiseProvider {
+    /// Create a new `GreyNoise` provider with an enterprise API key
+    pub fn new(api_key: impl Into<String>) -> Self {
+        Self::with_config(api_key, Tier::Enterprise, RateLimitConfig::greynoise())
+    }
+
+    /// Create a new `GreyNoise` provider with a community API key
+    pub fn community(api_key: impl Into<String>) -> Self {
+        Self::with_config(
+            api_key,
+            Tier::Community,
+            RateLimitConfig::greynoise_community(),
+        )
+    }
+
+    /// Create with custom rate limit config
+    pub fn with_config(
+        api_key: impl Into<String>,
+        tier: Tier,
+        rate_limit: RateLimitConfig,
+    ) -> Self {
+        // A request every 1/rps seconds, and at least one a second
+        let period = Duration::from_secs_f64(1.0 / rate_limit.requests_per_second.max(1.0));
+        let quota = Quota::with_period(period)
+            .unwrap_or_else(|| Quota::per_second(NonZeroU32::MAX))
+            .allow_burst(NonZeroU32::new(rate_limit.burst_size).unwrap_or(NonZeroU32::MIN));
+
+        Self {
+            inner: Arc::new(GreyNoiseInner {
+                http: Client::new(),
+                http_config: ProviderHttpConfig::default(),
+                api_key: api_key.into(),
+                base_url: DEFAULT_BASE_URL.to_string(),
+                tier,
+                rate_limiter: Arc::new(RateLimiter::direct(quota)),
+            }),
+        }
+    }
+
+    /// Send requests to another API endpoint, such as a proxy or a mock
+    /// server. Clones made before this call are not affected
+    #[must_use]
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        Arc::make_mut(&mut self.inner).base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Connect with these HTTP settings, e.g. through a proxy. Clones made
+    /// before this call are not affected
+    pub fn with_http_config(mut self, config: &ProviderHttpConfig) -> Result<Self> {
+        let http = config.client()?;
+        let inner = Arc::make_mut(&mut self.inner);
+        inner.http = http;
+        inner.http_config = config.clone();
+        Ok(self)
+    }
+
+    /// Which API the key opens
+    pub fn tier(&self) -> Tier {
+        self.inner.tier
+    }
+
+    /// Get authentication config for this provider
+    pub fn auth_config(&self) -> AuthConfig {
+        AuthConfig::greynoise(&self.inner.api_key)
+    }
+
+    /// Make a GET request to the `GreyNoise` API
+    #[instrument(skip(self), fields(provider = "greynoise"))]
+    async fn get<T: DeserializeOwned>(&self, endpoint: &str, query: &[(&str, &str)]) -> Result<T> {
+        self.inner.rate_limiter.until_ready().await;
+
+        let url = format!("{}{}", self.inner.base_url, endpoint);
+        debug!(url = %url, "GreyNoise API request");
+
+        let mut request = self.inner.http.get(&url).header("key", &self.inner.api_key);
+        if !query.is_empty() {
+            request = request.query(query);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| self.inner.http_config.request_error(&e))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let code = status.as_u16();
+            let retry_after = response
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse().ok());
+            let message = response.text().await.unwrap_or_default();
+
+            return match code {
+                401 | 403 => Err(I1Error::Unauthorized),
+                404 => Err(I1Error::NotFound {
+                    resource: endpoint.to_string(),
+                }),
+                // Community keys are limited per day, not per second
+                429 if self.inner.tier == Tier::Community => Err(I1Error::InsufficientCredits {
+                    required: 1,
+                    available: 0,
+                }),
+                429 => Err(I1Error::RateLimited { retry_after }),
+                _ => Err(I1Error::provider("greynoise", code, message)),
+            };
+        }
+
+        let value = response
+            .json()
+            .await
+            .map_err(|e| I1Error::Http(e.to_string()))?;
+        schema::parse("greynoise", endpoint, &value)
+    }
+
+    /// Refuse paid endpoints up front for community keys
+    fn require_enterprise(&self, what: &str) -> Result<()> {
+        if self.inner.tier == Tier::Community {
+            debug!("{what} needs an enterprise GreyNoise key");
+            return Err(I1Error::Unauthorized);
+        }
+        Ok(())
+    }
+
+    /// Full context of an IP (enterprise)
+    async fn context(&self, ip: IpAddr) -> Result<NoiseContext> {
+        self.get(&format!("/v2/noise/context/{ip}"), &[]).await
+    }
+
+    /// Community summary of an IP; addresses `GreyNoise` hasn't seen are a 404
+    async fn community_summary(&self, ip: IpAddr) -> Result<CommunityResponse> {
+        match self.get(&format!("/v3/community/{ip}"), &[]).await {
+            Err(I1Error::NotFound { .. }) => Ok(CommunityResponse {
+                ip: ip.to_string(),
+                ..CommunityResponse::default()
+            }),
+            result => result,
+        }
+    }
+
+    /// One page of GNQL results. GNQL pages with scroll tokens, so page `n`
+    /// follows `n - 1` of them first.
+    async fn gnql(&self, query: &str, page: u32, size: u32) -> Result<GnqlResponse> {
+        self.require_enterprise("GNQL")?;
+
+        let size = size.to_string();
+        let mut scroll: Option<String> = None;
+        for _ in 1..page.max(1) {
+            let skipped: GnqlResponse = self.gnql_request(query, &size, scroll.as_deref()).await?;
+            match skipped.scroll {
+                Some(next) if !skipped.complete => scroll = Some(next),
+                // Past the last page
+                _ => {
+                    return Ok(GnqlResponse {
+                        data: None,
+                        ..skipped
+                    })
+                }
+            }
+        }
+        self.gnql_request(query, &size, scroll.as_deref()).await
+    }
+
+    async fn gnql_request(
+        &self,
+        query: &str,
+        size: &str,
+        scroll: Option<&str>,
+    ) -> Result<GnqlResponse> {
+        let mut params = vec![("query", query), ("size", size)];
+        if let Some(scroll) = scroll {
+            params.push(("scroll", scroll));
+        }
+        self.get("/v2/experimental/gnql", &params).await
+    }
+}
+
+impl Clone for GreyNoiseProvider {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+#[async_trait]
+impl Provider for GreyNoiseProvider {
+    fn name(&self) -> &'static str {
+        "greynoise"
+    }
+
+    fn display_name(&self) -> &'static str {
+        "GreyNoise"
+    }
+
+    fn base_url(&self) -> &str {
+        &self.inner.base_url
+    }
+
+    fn is_configured(&self) -> bool {
+        !self.inner.api_key.is_empty()
+    }
+
+    async fn health_check(&self) -> Result<ProviderHealth> {
+        let start = Instant::now();
+
+        match self.get::<PingResponse>("/ping", &[]).await {
+            Ok(ping) => Ok(ProviderHealth {
+                provider: "greynoise".to_string(),
+                status: HealthStatus::Healthy,
+                latency_ms: Some(u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX)),
+                credits_remaining: None,
+                message: ping.expiration.map(|at| format!("Key expires {at}")),
+                plan: ping.offer,
+                scan_credits: None,
+                resets_at: None,
+            }),
+            Err(I1Error::Unauthorized) => Ok(ProviderHealth {
+                provider: "greynoise".to_string(),
+                status: HealthStatus::Unhealthy,
+                latency_ms: Some(u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX)),
+                credits_remaining: None,
+                message: Some("Invalid API key".to_string()),
+                plan: None,
+                scan_credits: None,
+                resets_at: None,
+            }),
+            Err(e) => Ok(ProviderHealth {
+                provider: "greynoise".to_string(),
+                status: HealthStatus::Unhealthy,
+                latency_ms: Some(u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX)),
+                credits_remaining: None,
+                message: Some(e.to_string()),
+                plan: None,
+                scan_credits: None,
+                resets_at: None,
+            }),
+        }
+    }
+}
+
+#[async_trait]
+impl ReputationProvider for GreyNoiseProvider {
+    #[instrument(skip(self), fields(provider = "greynoise"))]
+    async fn reputation(&self, ip: &str) -> Result<IpReputation> {
+        let ip = validate_ip(ip)?;
+        match self.inner.tier {
+            Tier::Community => Ok(self.community_summary(ip).await?.reputation()),
+            Tier::Enterprise => Ok(self.context(ip).await?.reputation()),
+        }
+    }
+}
+
+#[async_trait]
+impl HostLookup for GreyNoiseProvider {
+    #[instrument(skip(self), fields(provider = "greynoise"))]
+    async fn lookup_host(&self, ip: &str) -> Result<HostInfo> {
+        let ip = validate_ip(ip)?;
+        match self.inner.tier {
+            Tier::Community => {
+                let reputation = self.community_summary(ip).await?.reputation();
+                Ok(HostInfo {
+                    ip: Some(ip),
+                    ip_str: reputation.ip.clone(),
+                    hostnames: vec![],
+                    domains: vec![],
+                    org: None,
+                    asn: None,
+                    isp: None,
+                    os: None,
+                    ports: vec![],
+                    vulns: vec![],
+                    tags: host_tags(&reputation),
+                    location: GeoLocation::default(),
+                    data: vec![],
+                    last_update: reputation.last_seen.map(|at| at.to_rfc3339()),
+                    raw: None,
+                })
+            }
+            Tier::Enterprise => Ok(self.context(ip).await?.into_host_info()),
+        }
+    }
+}
+
+#[async_trait]
+impl SearchProvider for GreyNoiseProvider {
+    #[instrument(skip(self), fields(provider = "greynoise"))]
+    async fn search(&self, query: &str, page: Option<u32>) -> Result<SearchResults> {
+        let page = page.unwrap_or(1).max(1);
+        let response = self.gnql(query, page, PAGE_SIZE).await?;
+
+        Ok(SearchResults {
+            provider: "greynoise".to_string(),
+            total: response.count,
+            page,
+            results: response
+                .data
+                .unwrap_or_default()
+                .into_iter()
+                .map(NoiseContext::into_host_info)
+                .collect(),
+            facets: None,
+        })
+    }
+
+    #[instrument(skip(self), fields(provider = "greynoise"))]
+    async fn count(&self, query: &str) -> Result<u64> {
+        Ok(self.gnql(query, 1, 1).await?.count)
+    }
+}
+
+fn classification(value: Option<&str>) -> IpClassification {
+    match value {
+        Some("benign") => IpClassification::Benign,
+        Some("malicious") => IpClassification::Malicious,
+        _ => IpClassification::Unknown,
+    }
+}
+
+/// `GreyNoise` says "unknown" where it means it doesn't know
+fn known(value: Option<String>) -> Option<String> {
+    value.filter(|v| !v.is_empty() && !v.eq_ignore_ascii_case("unknown"))
+}
+
+/// `GreyNoise` dates are days (`2024-01-31`); full timestamps are accepted too
+fn parse_date(value: Option<&str>) -> Option<DateTime<Utc>> {
+    let value = value?;
+    DateTime::parse_from_rfc3339(value)
+        .map(|at| at.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .ok()
+                .and_then(|day| day.and_hms_opt(0, 0, 0))
+                .map(|at| at.and_utc())
+        })
+}
+
+/// Classification and actor as host tags, followed by `GreyNoise`'s own
+fn host_tags(reputation: &IpReputation) -> Vec<String> {
+    let mut tags = vec![];
+    if reputation.seen {
+        let classification = match reputation.classification {
+            IpClassification::Benign => "benign",
+            IpClassification::Malicious => "malicious",
+            IpClassification::Unknown => "unknown",
+            IpClassification::Riot => "riot",
+        };
+        tags.push(format!("classification:{classification}"));
+    }
+    if let Some(actor) = &reputation.actor {
+        tags.push(format!("actor:{actor}"));
+    }
+    tags.extend(reputation.tags.iter().cloned());
+    tags
+}
+
+// GreyNoise specific types
+#[derive(Debug, Deserialize)]
+struct PingResponse {
+    #[serde(default)]
+    offer: Option<String>,
+    #[serde(default)]
+    expiration: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct CommunityResponse {
+    ip: String,
+    #[serde(default)]
+    noise: bool,
+    #[serde(default)]
+    riot: bool,
+    #[serde(default)]
+    classification: Option<String>,
+    /// Actor for noise, the service for RIOT
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    link: Option<String>,
+    #[serde(default)]
+    last_seen: Option<String>,
+}
+
+impl CommunityResponse {
+    fn reputation(self) -> IpReputation {
+        IpReputation {
+            ip: self.ip,
+            provider: "greynoise".to_string(),
+            seen: self.noise || self.riot,
+            classification: if self.riot {
+                IpClassification::Riot
+            } else {
+                classification(self.classification.as_deref())
+            },
+            actor: known(self.name),
+            tags: vec![],
+            last_seen: parse_date(self.last_seen.as_deref()),
+            link: self.link,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct NoiseContext {
+    ip: String,
+    #[serde(default)]
+    seen: bool,
+    #[serde(default)]
+    classification: Option<String>,
+    #[serde(default)]
+    actor: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    last_seen: Option<String>,
+    #[serde(default)]
+    cve: Vec<String>,
+    #[serde(default)]
+    metadata: NoiseMetadata,
+    #[serde(default)]
+    raw_data: NoiseRawData,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct NoiseMetadata {
+    #[serde(default)]
+    asn: Option<String>,
+    #[serde(default)]
+    city: Option<String>,
+    #[serde(default)]
+    country: Option<String>,
+    #[serde(default)]
+    country_code: Option<String>,
+    #[serde(default)]
+    organization: Option<String>,
+    #[serde(default)]
+    os: Option<String>,
+    #[serde(default)]
+    rdns: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct NoiseRawData {
+    #[serde(default)]
+    scan: Vec<NoiseScan>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NoiseScan {
+    port: u16,
+}
+
+impl NoiseContext {
+    fn reputation(&self) -> IpReputation {
+        IpReputation {
+            ip: self.ip.clone(),
+            provider: "greynoise".to_string(),
+            seen: self.seen,
+            classification: classification(self.classification.as_deref()),
+            actor: known(self.actor.clone()),
+            tags: self.tags.clone(),
+            last_seen: parse_date(self.last_seen.as_deref()),
+            link: Some(format!("https://viz.greynoise.io/ip/{}", self.ip)),
+        }
+    }
+
+    /// Convert to i1 `HostInfo`; the ports are those the address scanned
+    fn into_host_info(self) -> HostInfo {
+        let reputation = self.reputation();
+        let mut ports: Vec<u16> = self.raw_data.scan.iter().map(|s| s.port).collect();
+        ports.sort_unstable();
+        ports.dedup();
+
+        HostInfo {
+            ip: self.ip.parse().ok(),
+            ip_str: self.ip,
+            hostnames: known(self.metadata.rdns).into_iter().collect(),
+            domains: vec![],
+            org: known(self.metadata.organization),
+            asn: known(self.metadata.asn),
+            isp: None,
+            os: known(self.metadata.os),
+            ports,
+            vulns: self.cve,
+            tags: host_tags(&reputation),
+            location: GeoLocation {
+                country_code: known(self.metadata.country_code),
+                country_name: known(self.metadata.country),
+                city: known(self.metadata.city),
+                ..Default::default()
+            },
+            data: vec![],
+            last_update: reputation.last_seen.map(|at| at.to_rfc3339()),
+            raw: None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct GnqlResponse {
+    #[serde(default)]
+    complete: bool,
+    #[serde(default)]
+    count: u64,
+    #[serde(default)]
+    data: Option<Vec<NoiseContext>>,
+    #[serde(default)]
+    scroll: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{header, method, path, query_param, query_param_is_missing};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    async fn mock(server: &MockServer, route: &str, status: u16, body: serde_json::Value) {
+        Mock::given(method("GET"))
+            .and(path(route))
+            .and(header("key", "test-key"))
+            .respond_with(ResponseTemplate::new(status).set_body_json(body))
+            .mount(server)
+            .await;
+    }
+
+    fn context() -> serde_json::Value {
+        json!({
+            "ip": "192.0.2.1",
+            "seen": true,
+            "classification": "malicious",
+            "actor": "unknown",
+            "tags": ["Mirai", "SSH Bruteforcer"],
+            "first_seen": "2023-11-02",
+            "last_seen": "2024-01-31",
+            "cve": ["CVE-2021-44228"],
+            "metadata": {
+                "asn": "AS64496",
+                "organization": "Example Hosting",
+                "country": "Iceland",
+                "country_code": "IS",
+                "city": "Reykjavik",
+                "os": "Linux 2.2-3.x",
+                "rdns": ""
+            },
+            "raw_data": {"scan": [{"port": 23, "protocol": "TCP"}, {"port": 22, "protocol": "TCP"}, {"port": 23, "protocol": "TCP"}]}
+        })
+    }
+
+    #[tokio::test]
+    async fn test_health_check() {
+        let server = MockServer::start().await;
+        mock(
+            &server,
+            "/ping",
+            200,
+            json!({"message": "pong", "offer": "community", "expiration": "2025-01-01"}),
+        )
+        .await;
+
+        let provider = GreyNoiseProvider::community("test-key").with_base_url(server.uri());
+        let health = provider.health_check().await.unwrap();
+        assert_eq!(health.status, HealthStatus::Healthy);
+        assert_eq!(health.plan.as_deref(), Some("community"));
+
+        let server = MockServer::start().await;
+        mock(&server, "/ping", 401, json!({"message": "unauthorized"})).await;
+        let provider = GreyNoiseProvider::community("test-key").with_base_url(server.uri());
+        let health = provider.health_check().await.unwrap();
+        assert_eq!(health.status, HealthStatus::Unhealthy);
+        assert_eq!(health.message.as_deref(), Some("Invalid API key"));
+    }
+
+    #[tokio::test]
+    async fn test_community() {
+        let server = MockServer::start().await;
+        mock(
+            &server,
+            "/v3/community/192.0.2.1",
+            200,
+            json!({
+                "ip": "192.0.2.1",
+                "noise": true,
+                "riot": false,
+                "classification": "malicious",
+                "name": "unknown",
+                "link": "https://viz.greynoise.io/ip/192.0.2.1",
+                "last_seen": "2024-01-31",
+                "message": "Success"
+            }),
+        )
+        .await;
+        mock(
+            &server,
+            "/v3/community/192.0.2.53",
+            200,
+            json!({
+                "ip": "192.0.2.53",
+                "noise": false,
+                "riot": true,
+                "classification": "benign",
+                "name": "Example DNS",
+                "last_seen": "2024-02-01"
+            }),
+        )
+        .await;
+        mock(
+            &server,
+            "/v3/community/192.0.2.2",
+            404,
+            json!({"ip": "192.0.2.2", "noise": false, "riot": false, "message": "IP not observed scanning the internet or contained in RIOT data set."}),
+        )
+        .await;
+        let provider = GreyNoiseProvider::community("test-key").with_base_url(server.uri());
+
+        let reputation = provider.reputation("192.0.2.1").await.unwrap();
+        assert!(reputation.seen);
+        assert_eq!(reputation.classification, IpClassification::Malicious);
+        assert_eq!(reputation.actor, None);
+        assert_eq!(
+            reputation.last_seen.map(|at| at.to_rfc3339()),
+            Some("2024-01-31T00:00:00+00:00".to_string())
+        );
+        assert_eq!(reputation.threat_level(), i1_providers::ThreatLevel::High);
+
+        let riot = provider.reputation("192.0.2.53").await.unwrap();
+        assert_eq!(riot.classification, IpClassification::Riot);
+        assert_eq!(riot.actor.as_deref(), Some("Example DNS"));
+        let host = provider.lookup_host("192.0.2.53").await.unwrap();
+        assert_eq!(host.tags, ["classification:riot", "actor:Example DNS"]);
+        assert_eq!(
+            host.last_update.as_deref(),
+            Some("2024-02-01T00:00:00+00:00")
+        );
+
+        let unseen = provider.reputation("192.0.2.2").await.unwrap();
+        assert!(!unseen.seen);
+        assert_eq!(unseen.classification, IpClassification::Unknown);
+        assert_eq!(unseen.threat_level(), i1_providers::ThreatLevel::None);
+        assert!(provider
+            .lookup_host("192.0.2.2")
+            .await
+            .unwrap()
+            .tags
+            .is_empty());
+
+        assert!(matches!(
+            provider.reputation("not-an-ip").await,
+            Err(I1Error::InvalidIp(_))
+        ));
+
+        // GNQL is enterprise only, and never sent
+        let before = server.received_requests().await.unwrap().len();
+        assert!(matches!(
+            provider.search("tags:Mirai", None).await,
+            Err(I1Error::Unauthorized)
+        ));
+        assert!(matches!(
+            provider.count("tags:Mirai").await,
+            Err(I1Error::Unauthorized)
+        ));
+        assert_eq!(server.received_requests().await.unwrap().len(), before);
+    }
+
+    #[tokio::test]
+    async fn test_community_ipv6() {
+        const IPV6: &str = include_str!("../../i1-core/tests/fixtures/ipv6.txt");
+
+        for line in IPV6
+            .lines()
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        {
+            let (input, canonical) = line.split_once('\t').unwrap();
+            let server = MockServer::start().await;
+            mock(
+                &server,
+                &format!("/v3/community/{canonical}"),
+                200,
+                json!({
+                    "ip": canonical,
+                    "noise": true,
+                    "riot": false,
+                    "classification": "malicious",
+                    "last_seen": "2024-01-31"
+                }),
+            )
+            .await;
+            let provider = GreyNoiseProvider::community("test-key").with_base_url(server.uri());
+
+            let host = provider.lookup_host(input).await.unwrap();
+            assert_eq!(host.ip_str, canonical, "{input}");
+            assert_eq!(host.ip, canonical.parse().ok(), "{input}");
+            let reputation = provider.reputation(input).await.unwrap();
+            assert_eq!(reputation.ip, canonical, "{input}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_community_limits() {
+        let server = MockServer::start().await;
+        let limited =
+            json!({"plan": "community", "message": "You have hit your daily rate limit."});
+        mock(&server, "/v3/community/192.0.2.1", 429, limited.clone()).await;
+        mock(&server, "/v2/noise/context/192.0.2.1", 429, limited).await;
+
+        let community = GreyNoiseProvider::community("test-key").with_base_url(server.uri());
+        assert!(matches!(
+            community.reputation("192.0.2.1").await,
+            Err(I1Error::InsufficientCredits { .. })
+        ));
+
+        let enterprise = GreyNoiseProvider::new("test-key").with_base_url(server.uri());
+        assert!(matches!(
+            enterprise.reputation("192.0.2.1").await,
+            Err(I1Error::RateLimited { .. })
+        ));
+
+        // A community key on a paid endpoint
+        let server = MockServer::start().await;
+        mock(
+            &server,
+            "/v2/noise/context/192.0.2.1",
+            401,
+            json!({"message": "unauthorized"}),
+        )
+        .await;
+        let provider = GreyNoiseProvider::new("test-key").with_base_url(server.uri());
+        assert!(matches!(
+            provider.lookup_host("192.0.2.1").await,
+            Err(I1Error::Unauthorized)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_context() {
+        let server = MockServer::start().await;
+        mock(&server, "/v2/noise/context/192.0.2.1", 200, context()).await;
+        let provider = GreyNoiseProvider::new("test-key").with_base_url(server.uri());
+
+        let host = provider.lookup_host("192.0.2.1").await.unwrap();
+        assert_eq!(host.ip_str, "192.0.2.1");
+        assert_eq!(host.ports, [22, 23]);
+        assert_eq!(host.org.as_deref(), Some("Example Hosting"));
+        assert_eq!(host.asn.as_deref(), Some("AS64496"));
+        assert_eq!(host.location.country_code.as_deref(), Some("IS"));
+        assert!(host.hostnames.is_empty());
+        assert_eq!(host.vulns, ["CVE-2021-44228"]);
+        assert_eq!(
+            host.tags,
+            ["classification:malicious", "Mirai", "SSH Bruteforcer"]
+        );
+        assert_eq!(
+            host.last_update.as_deref(),
+            Some("2024-01-31T00:00:00+00:00")
+        );
+
+        let reputation = provider.reputation("192.0.2.1").await.unwrap();
+        assert_eq!(reputation.classification, IpClassification::Malicious);
+        assert_eq!(reputation.tags, ["Mirai", "SSH Bruteforcer"]);
+        assert_eq!(reputation.actor, None);
+    }
+
+    #[tokio::test]
+    async fn test_gnql() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v2/experimental/gnql"))
+            .and(query_param("query", "tags:Mirai"))
+            .and(query_param_is_missing("scroll"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "complete": false,
+                "count": 101,
+                "data": [context()],
+                "scroll": "next-page",
+                "query": "tags:Mirai"
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v2/experimental/gnql"))
+            .and(query_param("scroll", "next-page"))
+            .and(query_param("size", "100"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "complete": true,
+                "count": 101,
+                "data": [context()],
+                "query": "tags:Mirai"
+            })))
+            .mount(&server)
+            .await;
+        let provider = GreyNoiseProvider::new("test-key").with_base_url(server.uri());
+
+        let results = provider.search("tags:Mirai", None).await.unwrap();
+        assert_eq!(results.provider, "greynoise");
+        assert_eq!(results.total, 101);
+        assert_eq!(results.results.len(), 1);
+        assert_eq!(results.results[0].ports, [22, 23]);
+
+        let second = provider.search("tags:Mirai", Some(2)).await.unwrap();
+        assert_eq!(second.page, 2);
+        assert_eq!(second.results.len(), 1);
+        let past = provider.search("tags:Mirai", Some(3)).await.unwrap();
+        assert!(past.results.is_empty());
+
+        assert_eq!(provider.count("tags:Mirai").await.unwrap(), 101);
+    }
+}
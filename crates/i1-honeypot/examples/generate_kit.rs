@@ -4,7 +4,7 @@ use i1_honeypot::HoneypotKit;
 
 fn main() {
     // Generate a kit for a user
-    let kit = HoneypotKit::generate_default_kit("grandma-protection-123");
+    let kit = HoneypotKit::generate_default_kit("grandma-protection-123", None);
 
     println!("╔══════════════════════════════════════════════════════════════╗");
     println!("║              i1.is HONEYPOT KIT - FOR GRANDMA                ║");
@@ -0,0 +1,539 @@
+//! What tripwire events say about the kits that fired them.
+//!
+//! An [`EventLog`] reads an archive of delivered [`TripwireEvent`]s, one
+//! JSON object per line, and answers the questions worth asking of it: how
+//! long a kit sat before someone took the bait, who keeps coming back, which
+//! bait gets taken, and when. Every result is `Serialize`.
+//!
+//! Archives are messy, and the mess is counted rather than trusted:
+//!
+//! - The same event delivered twice (a retried batch, say) counts once, and
+//!   shows up in [`EventSummary::duplicates`].
+//! - Lines that aren't events are skipped and counted as `malformed`.
+//! - Events from honeypots no known kit holds still count everywhere except
+//!   per kit, as `unattributed`.
+//! - Events stamped before their kit was deployed (clock skew between the
+//!   bait and the deployer) are left out of [`EventLog::first_access_delay`]
+//!   and counted as `before_deployment`.
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! use i1_honeypot::analytics::EventLog;
+//!
+//! let log = EventLog::load(Path::new("events.jsonl"))?
+//!     .with_kits(&kits)
+//!     .with_manifest(&HoneypotKit::load_manifest(Path::new("/srv/bait"))?);
+//! for delay in log.first_access_delay() {
+//!     println!("{}: {:?}s", delay.kit_id, delay.delay_secs);
+//! }
+//! ```
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::time::Duration;
+
+use chrono::{DateTime, TimeZone, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{HoneypotError, HoneypotKit, KitManifest, TripwireEvent};
+
+/// Tripwire events read from an archive, with what's known of the kits
+/// behind them.
+#[derive(Debug, Clone, Default)]
+pub struct EventLog {
+    /// Unique events, oldest first
+    events: Vec<TripwireEvent>,
+    duplicates: usize,
+    malformed: usize,
+    /// The kit each honeypot belongs to, kits themselves included
+    owners: HashMap<Uuid, Uuid>,
+    /// When each kit was deployed
+    deployed: HashMap<Uuid, DateTime<Utc>>,
+}
+
+/// The first time a kit's bait was taken.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FirstAccess {
+    pub kit_id: Uuid,
+    pub deployed_at: DateTime<Utc>,
+    /// The first event at or after deployment, if any
+    pub first_triggered_at: Option<DateTime<Utc>>,
+    /// Seconds from deployment to that event
+    pub delay_secs: Option<i64>,
+    /// Events stamped before the deployment, and left out
+    pub before_deployment: usize,
+}
+
+/// A source address and what it triggered.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SourceIp {
+    pub ip: String,
+    pub events: usize,
+    /// Distinct honeypots it triggered
+    pub honeypots: usize,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+}
+
+/// How often one type of bait was taken.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ComponentHits {
+    /// The events' `honeypot_type`, e.g. `card` or `wallet`
+    pub component: String,
+    pub events: usize,
+    /// Distinct honeypots of this type triggered
+    pub honeypots: usize,
+}
+
+/// Events in one slice of a [`EventLog::timeline`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TimelineBucket {
+    pub start: DateTime<Utc>,
+    pub events: usize,
+}
+
+/// The log as a whole, and what didn't add up in it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct EventSummary {
+    /// Unique events
+    pub events: usize,
+    /// Repeated deliveries of an event already counted
+    pub duplicates: usize,
+    /// Lines that weren't events
+    pub malformed: usize,
+    /// Events from honeypots in no known kit
+    pub unattributed: usize,
+    /// Events stamped before their kit was deployed
+    pub before_deployment: usize,
+    /// Kits with at least one event
+    pub kits: usize,
+    /// Distinct source addresses
+    pub sources: usize,
+    pub first: Option<DateTime<Utc>>,
+    pub last: Option<DateTime<Utc>>,
+}
+
+impl EventLog {
+    /// Read a JSONL archive of events.
+    pub fn load(path: &Path) -> Result<Self, HoneypotError> {
+        Self::from_reader(BufReader::new(File::open(path)?))
+    }
+
+    /// Read events, one JSON object per line. A line may also be a whole
+    /// delivery, `{"events": [...]}`, as the reporter sends them.
+    pub fn from_reader(reader: impl BufRead) -> Result<Self, HoneypotError> {
+        let mut log = Self::default();
+        let mut seen = HashSet::new();
+
+        for line in reader.lines() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let Ok(value) = serde_json::from_str::<serde_json::Value>(line) else {
+                log.malformed += 1;
+                continue;
+            };
+            let values = match value {
+                serde_json::Value::Object(mut batch) if batch.contains_key("events") => {
+                    match batch.remove("events") {
+                        Some(serde_json::Value::Array(events)) => events,
+                        _ => {
+                            log.malformed += 1;
+                            continue;
+                        }
+                    }
+                }
+                value => vec![value],
+            };
+
+            for value in values {
+                // The serialized event is its identity: a redelivery is
+                // byte-for-byte the same
+                let key = value.to_string();
+                match serde_json::from_value::<TripwireEvent>(value) {
+                    Ok(_) if seen.contains(&key) => log.duplicates += 1,
+                    Ok(event) => {
+                        seen.insert(key);
+                        log.events.push(event);
+                    }
+                    Err(_) => log.malformed += 1,
+                }
+            }
+        }
+
+        log.events.sort_by_key(|event| event.triggered_at);
+        Ok(log)
+    }
+
+    /// Attribute events to `kits`, and take each kit's creation as its
+    /// deployment unless a manifest says otherwise.
+    #[must_use]
+    pub fn with_kits(mut self, kits: &[HoneypotKit]) -> Self {
+        for kit in kits {
+            let components = kit
+                .cards
+                .iter()
+                .map(|c| c.id)
+                .chain(kit.credentials.iter().map(|c| c.id))
+                .chain(kit.wallets.iter().map(|w| w.id))
+                .chain(kit.documents.iter().map(|d| d.id))
+                .chain(kit.honeytokens.iter().map(|t| t.id))
+                .chain(kit.mail.iter().map(|m| m.id));
+            for id in std::iter::once(kit.id).chain(components) {
+                self.owners.insert(id, kit.id);
+            }
+            self.deployed.entry(kit.id).or_insert(kit.created_at);
+        }
+        self
+    }
+
+    /// Attribute events to a deployed kit, and the kits it retired, and
+    /// take the manifest's deployment time.
+    #[must_use]
+    pub fn with_manifest(mut self, manifest: &KitManifest) -> Self {
+        self.owners.insert(manifest.kit_id, manifest.kit_id);
+        for entry in &manifest.entries {
+            for &id in &entry.honeypot_ids {
+                self.owners.insert(id, manifest.kit_id);
+            }
+        }
+        for retired in &manifest.retired {
+            self.owners.insert(retired.kit_id, retired.kit_id);
+            for entry in &retired.entries {
+                for &id in &entry.honeypot_ids {
+                    self.owners.entry(id).or_insert(retired.kit_id);
+                }
+            }
+        }
+        self.deployed.insert(manifest.kit_id, manifest.deployed_at);
+        self
+    }
+
+    /// Unique events, oldest first.
+    pub fn events(&self) -> &[TripwireEvent] {
+        &self.events
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// The kit an event's honeypot belongs to, if it's known.
+    pub fn kit_of(&self, event: &TripwireEvent) -> Option<Uuid> {
+        self.owners.get(&event.honeypot_id).copied()
+    }
+
+    /// Events from one kit's honeypots, oldest first.
+    pub fn events_for_kit(&self, kit_id: Uuid) -> Vec<&TripwireEvent> {
+        self.events
+            .iter()
+            .filter(|event| self.kit_of(event) == Some(kit_id))
+            .collect()
+    }
+
+    /// For every kit with a known deployment time, how long it was out
+    /// before its bait was first taken. Kits never triggered have no delay.
+    /// Oldest deployment first.
+    pub fn first_access_delay(&self) -> Vec<FirstAccess> {
+        let mut delays: Vec<FirstAccess> = self
+            .deployed
+            .iter()
+            .map(|(&kit_id, &deployed_at)| {
+                let events = self.events_for_kit(kit_id);
+                let before_deployment = events
+                    .iter()
+                    .filter(|event| event.triggered_at < deployed_at)
+                    .count();
+                let first = events
+                    .iter()
+                    .map(|event| event.triggered_at)
+                    .find(|&at| at >= deployed_at);
+                FirstAccess {
+                    kit_id,
+                    deployed_at,
+                    first_triggered_at: first,
+                    delay_secs: first.map(|at| (at - deployed_at).num_seconds()),
+                    before_deployment,
+                }
+            })
+            .collect();
+        delays.sort_by_key(|delay| (delay.deployed_at, delay.kit_id));
+        delays
+    }
+
+    /// The `n` addresses behind the most events, most first.
+    pub fn top_source_ips(&self, n: usize) -> Vec<SourceIp> {
+        let mut sources: BTreeMap<&str, (SourceIp, BTreeSet<Uuid>)> = BTreeMap::new();
+        for event in &self.events {
+            let Some(ip) = event.source_ip.as_deref() else {
+                continue;
+            };
+            let (source, honeypots) = sources.entry(ip).or_insert_with(|| {
+                let source = SourceIp {
+                    ip: ip.to_string(),
+                    events: 0,
+                    honeypots: 0,
+                    first_seen: event.triggered_at,
+                    last_seen: event.triggered_at,
+                };
+                (source, BTreeSet::new())
+            });
+            source.events += 1;
+            source.last_seen = event.triggered_at;
+            honeypots.insert(event.honeypot_id);
+        }
+
+        let mut sources: Vec<SourceIp> = sources
+            .into_values()
+            .map(|(source, honeypots)| SourceIp {
+                honeypots: honeypots.len(),
+                ..source
+            })
+            .collect();
+        // Stable, so ties stay in address order
+        sources.sort_by_key(|source| Reverse(source.events));
+        sources.truncate(n);
+        sources
+    }
+
+    /// Events per type of bait, most taken first.
+    pub fn component_hit_counts(&self) -> Vec<ComponentHits> {
+        let mut hits: BTreeMap<&str, (usize, BTreeSet<Uuid>)> = BTreeMap::new();
+        for event in &self.events {
+            let (events, honeypots) = hits.entry(event.honeypot_type.as_str()).or_default();
+            *events += 1;
+            honeypots.insert(event.honeypot_id);
+        }
+
+        let mut hits: Vec<ComponentHits> = hits
+            .into_iter()
+            .map(|(component, (events, honeypots))| ComponentHits {
+                component: component.to_string(),
+                events,
+                honeypots: honeypots.len(),
+            })
+            .collect();
+        hits.sort_by_key(|hits| Reverse(hits.events));
+        hits
+    }
+
+    /// Events per `bucket`, from the first event's bucket to the last's
+    /// with empty ones included, ready to plot. Buckets are aligned to the
+    /// Unix epoch (so daily buckets start at midnight UTC) and are at
+    /// least a second long.
+    pub fn timeline(&self, bucket: Duration) -> Vec<TimelineBucket> {
+        let (Some(first), Some(last)) = (self.events.first(), self.events.last()) else {
+            return Vec::new();
+        };
+        let size = i64::try_from(bucket.as_secs()).unwrap_or(i64::MAX).max(1);
+        let slot = |at: DateTime<Utc>| at.timestamp().div_euclid(size);
+        let (first, last) = (slot(first.triggered_at), slot(last.triggered_at));
+
+        let mut counts = vec![0; usize::try_from(last - first).unwrap_or(0) + 1];
+        for event in &self.events {
+            counts[usize::try_from(slot(event.triggered_at) - first).unwrap_or(0)] += 1;
+        }
+        counts
+            .into_iter()
+            .zip(first..)
+            .filter_map(|(events, slot)| {
+                let start = Utc.timestamp_opt(slot.checked_mul(size)?, 0).single()?;
+                Some(TimelineBucket { start, events })
+            })
+            .collect()
+    }
+
+    /// Counts over the whole log.
+    pub fn summary(&self) -> EventSummary {
+        let mut kits = BTreeSet::new();
+        let mut unattributed = 0;
+        let mut before_deployment = 0;
+        for event in &self.events {
+            match self.kit_of(event) {
+                Some(kit) => {
+                    kits.insert(kit);
+                    let deployed = self.deployed.get(&kit);
+                    if deployed.is_some_and(|&at| event.triggered_at < at) {
+                        before_deployment += 1;
+                    }
+                }
+                None => unattributed += 1,
+            }
+        }
+
+        EventSummary {
+            events: self.events.len(),
+            duplicates: self.duplicates,
+            malformed: self.malformed,
+            unattributed,
+            before_deployment,
+            kits: kits.len(),
+            sources: self
+                .events
+                .iter()
+                .filter_map(|event| event.source_ip.as_deref())
+                .collect::<BTreeSet<_>>()
+                .len(),
+            first: self.events.first().map(|event| event.triggered_at),
+            last: self.events.last().map(|event| event.triggered_at),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Write as _;
+
+    use super::*;
+    use crate::DeployOptions;
+
+    const HOUR: i64 = 60 * 60;
+
+    fn at(hours: i64) -> DateTime<Utc> {
+        Utc.timestamp_opt(1_700_000_000 - 1_700_000_000 % (24 * HOUR), 0)
+            .unwrap()
+            + chrono::Duration::seconds(hours * HOUR)
+    }
+
+    fn event(id: Uuid, kind: &str, hours: i64, ip: Option<&str>) -> String {
+        serde_json::to_string(&TripwireEvent {
+            honeypot_id: id,
+            honeypot_type: kind.to_string(),
+            triggered_at: at(hours),
+            source_ip: ip.map(str::to_string),
+            context: serde_json::json!({ "path": "/t" }),
+        })
+        .unwrap()
+    }
+
+    /// A kit deployed at hour 0, with its card taken three times from two
+    /// addresses, its wallet once, a decoy no kit knows about, one event
+    /// from before the deployment, one retried delivery and one bad line.
+    fn log() -> (HoneypotKit, EventLog) {
+        let mut kit = HoneypotKit::generate_with_seed("analyst", 7);
+        kit.created_at = at(0);
+        let card = kit.cards[0].id;
+        let wallet = kit.wallets[0].id;
+        let decoy = Uuid::new_v4();
+
+        let mut archive = String::new();
+        for line in [
+            event(card, "card", 5, Some("198.51.100.7")),
+            event(card, "card", 6, Some("198.51.100.7")),
+            event(card, "card", 30, Some("203.0.113.9")),
+            event(wallet, "wallet", 29, Some("198.51.100.7")),
+            event(decoy, "decoy", 2, Some("192.0.2.44")),
+            event(wallet, "wallet", -3, None),
+        ] {
+            writeln!(archive, "{line}").unwrap();
+        }
+        // A retried batch, as the reporter sends it, and a torn write
+        let batch = serde_json::json!({ "events": [
+            serde_json::from_str::<serde_json::Value>(&event(card, "card", 6, Some("198.51.100.7"))).unwrap(),
+        ]});
+        writeln!(archive, "{batch}\n\n{{\"honeypot_id\": ").unwrap();
+
+        let log = EventLog::from_reader(archive.as_bytes())
+            .unwrap()
+            .with_kits(std::slice::from_ref(&kit));
+        (kit, log)
+    }
+
+    #[test]
+    fn test_summary() {
+        let (_, log) = log();
+        assert_eq!(
+            log.summary(),
+            EventSummary {
+                events: 6,
+                duplicates: 1,
+                malformed: 1,
+                unattributed: 1,
+                before_deployment: 1,
+                kits: 1,
+                sources: 3,
+                first: Some(at(-3)),
+                last: Some(at(30)),
+            }
+        );
+    }
+
+    #[test]
+    fn test_first_access_delay() {
+        let (kit, log) = log();
+        assert_eq!(log.events_for_kit(kit.id).len(), 5);
+        assert_eq!(
+            log.first_access_delay(),
+            [FirstAccess {
+                kit_id: kit.id,
+                deployed_at: at(0),
+                first_triggered_at: Some(at(5)),
+                delay_secs: Some(5 * HOUR),
+                before_deployment: 1,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_sources_and_components() {
+        let (_, log) = log();
+        let top = log.top_source_ips(2);
+        assert_eq!(top.len(), 2);
+        assert_eq!(top[0].ip, "198.51.100.7");
+        assert_eq!((top[0].events, top[0].honeypots), (3, 2));
+        assert_eq!((top[0].first_seen, top[0].last_seen), (at(5), at(29)));
+        assert_eq!(top[1].ip, "192.0.2.44");
+
+        let hits = log.component_hit_counts();
+        assert_eq!(hits.len(), 3);
+        assert_eq!(
+            hits[0],
+            ComponentHits {
+                component: "card".to_string(),
+                events: 3,
+                honeypots: 1,
+            }
+        );
+        assert_eq!(hits[1].component, "wallet");
+    }
+
+    #[test]
+    fn test_timeline() {
+        let (_, log) = log();
+        let days = log.timeline(Duration::from_secs(24 * 60 * 60));
+        assert_eq!(days.iter().map(|d| d.events).collect::<Vec<_>>(), [1, 3, 2]);
+        assert_eq!(days[1].start, at(0));
+
+        let hours = log.timeline(Duration::from_secs(60 * 60));
+        assert_eq!(hours.len(), 34);
+        assert_eq!(hours.iter().map(|h| h.events).sum::<usize>(), 6);
+        assert!(EventLog::default().timeline(Duration::ZERO).is_empty());
+    }
+
+    #[test]
+    fn test_manifest_deployment() {
+        let (kit, _) = log();
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = kit.deploy_to(dir.path(), DeployOptions::default()).unwrap();
+        let card = kit.cards[0].id;
+
+        let archive = event(card, "card", 1, None);
+        let log = EventLog::from_reader(archive.as_bytes())
+            .unwrap()
+            .with_manifest(&manifest);
+        assert_eq!(log.kit_of(&log.events()[0]), Some(kit.id));
+        // Deployed now, so the hour-1 event predates it
+        assert_eq!(log.first_access_delay()[0].before_deployment, 1);
+    }
+}
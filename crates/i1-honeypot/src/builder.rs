@@ -0,0 +1,698 @@
+//! Building kits to order.
+//!
+//! [`KitBuilder`] picks what goes in a kit: how many of each component and
+//! of which kinds, whose they are, the seed, where tracking URLs point and
+//! how long the kit lasts.
+
+use chrono::{DateTime, Duration, Utc};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::{
+    CardNetwork, CredentialType, CryptoNetwork, DocumentType, HoneypotCard, HoneypotCredential,
+    HoneypotError, HoneypotKit, HoneypotWallet, Honeytoken, HoneytokenKind, Locale, PaperTrail,
+    Persona, SiteCatalog, SiteEntry, TrapDocument, DEFAULT_TRACKING_BASE,
+};
+
+/// Most of any one component a kit may have. More looks planted, not lived in.
+pub const MAX_PER_COMPONENT: usize = 50;
+
+/// How long a kit lasts before it should be rotated, unless told otherwise.
+pub const DEFAULT_LIFETIME_DAYS: i64 = 90;
+
+/// Builder for [`HoneypotKit`].
+///
+/// Each component has a count and the kinds to make: a count larger than
+/// the list of kinds goes round the list again, so three cards of
+/// `[Visa, Amex]` are Visa, Amex, Visa.
+#[derive(Debug, Clone)]
+pub struct KitBuilder {
+    user_id: String,
+    card_count: usize,
+    card_networks: Vec<CardNetwork>,
+    credential_count: usize,
+    credential_types: Vec<CredentialType>,
+    site_catalogs: Vec<SiteCatalog>,
+    wallet_count: usize,
+    wallet_networks: Vec<CryptoNetwork>,
+    document_count: usize,
+    document_types: Vec<DocumentType>,
+    honeytokens: Vec<HoneytokenKind>,
+    mail: bool,
+    locale: Locale,
+    persona: Option<Persona>,
+    seed: Option<u64>,
+    created_at: Option<DateTime<Utc>>,
+    tracking_base: String,
+    lifetime: Option<Duration>,
+}
+
+impl KitBuilder {
+    /// Create a builder for the default kit: three cards, three logins, two
+    /// wallets, two documents, every honeytoken and an inbox, expiring after
+    /// [`DEFAULT_LIFETIME_DAYS`].
+    pub fn new(user_id: impl Into<String>) -> Self {
+        Self {
+            user_id: user_id.into(),
+            card_count: 3,
+            card_networks: vec![
+                CardNetwork::Visa,
+                CardNetwork::Mastercard,
+                CardNetwork::Amex,
+            ],
+            credential_count: 3,
+            credential_types: vec![
+                CredentialType::BankLogin,
+                CredentialType::EmailLogin,
+                CredentialType::SocialMedia,
+            ],
+            site_catalogs: Vec::new(),
+            wallet_count: 2,
+            wallet_networks: vec![CryptoNetwork::Bitcoin, CryptoNetwork::Ethereum],
+            document_count: 2,
+            document_types: vec![DocumentType::TaxReturn, DocumentType::BankStatement],
+            honeytokens: vec![
+                HoneytokenKind::SshKey,
+                HoneytokenKind::AwsCredentials,
+                HoneytokenKind::Npmrc,
+                HoneytokenKind::DotEnv,
+            ],
+            mail: true,
+            locale: Locale::default(),
+            persona: None,
+            seed: None,
+            created_at: None,
+            tracking_base: DEFAULT_TRACKING_BASE.to_string(),
+            lifetime: Some(Duration::days(DEFAULT_LIFETIME_DAYS)),
+        }
+    }
+
+    /// A builder for a kit like `kit`: the same user, persona, sites,
+    /// tracking base and lifetime, and as many of each component of the
+    /// same kinds.
+    pub(crate) fn like(kit: &HoneypotKit) -> Self {
+        let mut builder = Self::new(kit.user_id.clone());
+        builder.card_networks = kit.cards.iter().map(|c| c.network).collect();
+        builder.card_count = kit.cards.len();
+        builder.credential_types = kit.credentials.iter().map(|c| c.credential_type).collect();
+        builder.credential_count = kit.credentials.len();
+        builder.site_catalogs = kit.site_catalogs.clone();
+        builder.wallet_networks = kit.wallets.iter().map(|w| w.network).collect();
+        builder.wallet_count = kit.wallets.len();
+        builder.document_types = kit.documents.iter().map(|d| d.document_type).collect();
+        builder.document_count = kit.documents.len();
+        builder.honeytokens = kit.honeytokens.iter().map(|t| t.kind).collect();
+        builder.mail = !kit.mail.is_empty();
+        builder.persona = kit.persona.clone();
+        builder.tracking_base = kit.tracking_base.clone();
+        builder.lifetime = kit.expires_at.map(|expires| expires - kit.created_at);
+        builder
+    }
+
+    /// Preset for someone known to hold crypto: wallets on every network,
+    /// exchange logins and a single card.
+    pub fn crypto_heavy(self) -> Self {
+        self.cards(1)
+            .card_networks(&[CardNetwork::Visa])
+            .credentials(4)
+            .credential_types(&[
+                CredentialType::CryptoExchange,
+                CredentialType::EmailLogin,
+                CredentialType::CryptoExchange,
+            ])
+            .wallets(6)
+            .wallet_networks(&[
+                CryptoNetwork::Bitcoin,
+                CryptoNetwork::Ethereum,
+                CryptoNetwork::Litecoin,
+                CryptoNetwork::Dogecoin,
+            ])
+            .documents(1)
+            .document_types(&[DocumentType::TaxReturn])
+    }
+
+    /// Preset for the classic bank fraud target: cards on every network,
+    /// bank logins and the paperwork to go with them, no crypto.
+    pub fn banking_focus(self) -> Self {
+        self.cards(4)
+            .card_networks(&[
+                CardNetwork::Visa,
+                CardNetwork::Mastercard,
+                CardNetwork::Amex,
+                CardNetwork::Discover,
+            ])
+            .credentials(4)
+            .credential_types(&[
+                CredentialType::BankLogin,
+                CredentialType::EmailLogin,
+                CredentialType::BankLogin,
+                CredentialType::Shopping,
+            ])
+            .wallets(0)
+            .documents(4)
+            .document_types(&[
+                DocumentType::BankStatement,
+                DocumentType::TaxReturn,
+                DocumentType::PayStub,
+                DocumentType::BankStatement,
+            ])
+    }
+
+    /// Number of credit cards
+    pub fn cards(mut self, count: usize) -> Self {
+        self.card_count = count;
+        self
+    }
+
+    /// Networks the cards are on
+    pub fn card_networks(mut self, networks: &[CardNetwork]) -> Self {
+        self.card_networks = networks.to_vec();
+        self
+    }
+
+    /// Number of logins
+    pub fn credentials(mut self, count: usize) -> Self {
+        self.credential_count = count;
+        self
+    }
+
+    /// Kinds of site the logins are for
+    pub fn credential_types(mut self, types: &[CredentialType]) -> Self {
+        self.credential_types = types.to_vec();
+        self
+    }
+
+    /// Pick logins of `credential_type` from `sites` instead of the built-in
+    /// catalog. Sites for the persona's locale, or for every locale, are
+    /// preferred; the catalog is saved in the kit.
+    pub fn with_site_catalog(
+        mut self,
+        credential_type: CredentialType,
+        sites: Vec<SiteEntry>,
+    ) -> Self {
+        self.site_catalogs
+            .retain(|catalog| catalog.credential_type != credential_type);
+        self.site_catalogs.push(SiteCatalog {
+            credential_type,
+            sites,
+        });
+        self
+    }
+
+    /// Number of crypto wallets
+    pub fn wallets(mut self, count: usize) -> Self {
+        self.wallet_count = count;
+        self
+    }
+
+    /// Networks the wallets are on
+    pub fn wallet_networks(mut self, networks: &[CryptoNetwork]) -> Self {
+        self.wallet_networks = networks.to_vec();
+        self
+    }
+
+    /// Number of trap documents
+    pub fn documents(mut self, count: usize) -> Self {
+        self.document_count = count;
+        self
+    }
+
+    /// Kinds of trap document
+    pub fn document_types(mut self, types: &[DocumentType]) -> Self {
+        self.document_types = types.to_vec();
+        self
+    }
+
+    /// Honeytokens to plant, one of each kind given
+    pub fn honeytokens(mut self, kinds: &[HoneytokenKind]) -> Self {
+        self.honeytokens = kinds.to_vec();
+        self
+    }
+
+    /// Whether to fill an inbox with mail about the rest of the kit
+    pub fn mail(mut self, enabled: bool) -> Self {
+        self.mail = enabled;
+        self
+    }
+
+    /// Locale of the generated persona
+    pub fn locale(mut self, locale: Locale) -> Self {
+        self.locale = locale;
+        self
+    }
+
+    /// Use this persona instead of generating one
+    pub fn persona(mut self, persona: Persona) -> Self {
+        self.persona = Some(persona);
+        self
+    }
+
+    /// Generate from a seed: the same seed and [`created_at`](Self::created_at)
+    /// give the same kit, identifiers included
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// When the kit is made, which dates its cards and documents. Defaults
+    /// to now, or to the start of today (UTC) for a seeded kit.
+    pub fn created_at(mut self, at: DateTime<Utc>) -> Self {
+        self.created_at = Some(at);
+        self
+    }
+
+    /// Base of the tracking URLs, e.g. `https://canary.example.org/t` for a
+    /// self-hosted collector
+    pub fn tracking_base(mut self, url: impl Into<String>) -> Self {
+        self.tracking_base = url.into();
+        self
+    }
+
+    /// How long the kit lasts before [`HoneypotKit::is_expired`] says to
+    /// rotate it
+    pub fn lifetime(mut self, lifetime: Duration) -> Self {
+        self.lifetime = Some(lifetime);
+        self
+    }
+
+    /// Make a kit that never expires
+    pub fn never_expires(mut self) -> Self {
+        self.lifetime = None;
+        self
+    }
+
+    /// Build the kit, or say why it can't be built.
+    pub fn build(self) -> Result<HoneypotKit, HoneypotError> {
+        self.validate()?;
+        Ok(self.generate())
+    }
+
+    /// Build the kit as asked, without checking the request is sensible.
+    pub(crate) fn generate(self) -> HoneypotKit {
+        match self.seed {
+            Some(seed) => {
+                let mut rng = StdRng::seed_from_u64(seed);
+                let today = Utc::now().date_naive().and_hms_opt(0, 0, 0).unwrap();
+                let kit = HoneypotKit {
+                    id: crate::random_id(&mut rng),
+                    created_at: self.created_at.unwrap_or_else(|| today.and_utc()),
+                    ..HoneypotKit::new(self.user_id.clone())
+                };
+                self.fill(kit, &mut rng)
+            }
+            None => {
+                let mut kit = HoneypotKit::new(self.user_id.clone());
+                if let Some(at) = self.created_at {
+                    kit.created_at = at;
+                }
+                self.fill(kit, &mut rand::thread_rng())
+            }
+        }
+    }
+
+    fn validate(&self) -> Result<(), HoneypotError> {
+        let invalid = |message: String| Err(HoneypotError::InvalidConfig(message));
+
+        let components = [
+            ("cards", self.card_count, self.card_networks.is_empty()),
+            (
+                "credentials",
+                self.credential_count,
+                self.credential_types.is_empty(),
+            ),
+            (
+                "wallets",
+                self.wallet_count,
+                self.wallet_networks.is_empty(),
+            ),
+            (
+                "documents",
+                self.document_count,
+                self.document_types.is_empty(),
+            ),
+            ("honeytokens", self.honeytokens.len(), false),
+        ];
+        for (name, count, no_kinds) in components {
+            if count > MAX_PER_COMPONENT {
+                return invalid(format!(
+                    "{} {} is more than the {} a kit may have",
+                    count, name, MAX_PER_COMPONENT
+                ));
+            }
+            if count > 0 && no_kinds {
+                return invalid(format!(
+                    "{} {} asked for, but no kinds to make",
+                    count, name
+                ));
+            }
+        }
+        for catalog in &self.site_catalogs {
+            if catalog.sites.is_empty() {
+                return invalid(format!(
+                    "the {} site catalog has no sites",
+                    catalog.credential_type
+                ));
+            }
+            if let Some(site) = catalog
+                .sites
+                .iter()
+                .find(|site| site.domain.is_empty() || site.domain.contains(char::is_whitespace))
+            {
+                return invalid(format!(
+                    "'{}' in the {} site catalog is not a domain",
+                    site.domain, catalog.credential_type
+                ));
+            }
+        }
+        if components.iter().all(|(_, count, _)| *count == 0) {
+            return invalid("a kit needs at least one component".to_string());
+        }
+        if let Some(lifetime) = self.lifetime.filter(|l| *l <= Duration::zero()) {
+            return invalid(format!(
+                "a lifetime of {} seconds has already run out",
+                lifetime.num_seconds()
+            ));
+        }
+
+        let base = &self.tracking_base;
+        let host = base
+            .strip_prefix("https://")
+            .or_else(|| base.strip_prefix("http://"))
+            .and_then(|rest| rest.split('/').next())
+            .unwrap_or_default();
+        if host.is_empty() || base.ends_with('/') || base.contains(char::is_whitespace) {
+            return invalid(format!(
+                "tracking base '{}' is not an http(s) URL without a trailing slash",
+                base
+            ));
+        }
+        Ok(())
+    }
+
+    fn fill(self, mut kit: HoneypotKit, rng: &mut impl Rng) -> HoneypotKit {
+        let persona = match self.persona {
+            Some(persona) => persona,
+            None => Persona::generate_with_rng(self.locale, rng),
+        };
+        kit.tracking_base = self.tracking_base;
+        kit.expires_at = self.lifetime.map(|lifetime| kit.created_at + lifetime);
+        let today = kit.created_at.date_naive();
+
+        for &network in self.card_networks.iter().cycle().take(self.card_count) {
+            let card = HoneypotCard::generate_for_persona_on(network, &persona, today, rng);
+            kit.cards.push(card);
+        }
+
+        for &kind in self
+            .credential_types
+            .iter()
+            .cycle()
+            .take(self.credential_count)
+        {
+            let catalog = self
+                .site_catalogs
+                .iter()
+                .find(|catalog| catalog.credential_type == kind);
+            let credential = match catalog {
+                Some(catalog) => {
+                    let sites = catalog.sites_for(persona.locale);
+                    let site = &sites[rng.gen_range(0..sites.len())];
+                    HoneypotCredential::generate_for_site(kind, site, &persona, rng)
+                }
+                None => HoneypotCredential::generate_for_persona(kind, &persona, rng),
+            };
+            kit.credentials.push(credential);
+        }
+
+        for &network in self.wallet_networks.iter().cycle().take(self.wallet_count) {
+            let wallet = HoneypotWallet::generate_with_rng(network, rng);
+            kit.wallets.push(wallet);
+        }
+
+        for &kind in &self.honeytokens {
+            let token = Honeytoken::generate_for_persona(kind, &persona, rng);
+            kit.honeytokens.push(token);
+        }
+
+        // Documents last, so they can name the cards made above
+        let mut trail = PaperTrail::new(&persona, &kit.cards, rng);
+        trail.date = today;
+        let documents: Vec<_> = self
+            .document_types
+            .iter()
+            .cycle()
+            .take(self.document_count)
+            .map(|&kind| {
+                let mut document = TrapDocument::generate_with_rng(kind, &trail, rng);
+                document.tracking_url = format!("{}/{}", kit.tracking_base, document.id);
+                document
+            })
+            .collect();
+        kit.documents = documents;
+
+        // Mail about all of the above
+        if self.mail {
+            kit.mail = kit.generate_mail(&persona, rng);
+        }
+
+        kit.persona = Some(persona);
+        kit.site_catalogs = self.site_catalogs;
+        kit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_and_kinds() {
+        let kit = HoneypotKit::builder("test-user")
+            .cards(5)
+            .card_networks(&[CardNetwork::Discover, CardNetwork::Amex])
+            .credentials(1)
+            .credential_types(&[CredentialType::Streaming])
+            .wallets(0)
+            .documents(3)
+            .document_types(&[DocumentType::Will])
+            .honeytokens(&[HoneytokenKind::Npmrc])
+            .mail(false)
+            .build()
+            .unwrap();
+
+        let networks: Vec<_> = kit.cards.iter().map(|c| c.network).collect();
+        assert_eq!(
+            networks,
+            [
+                CardNetwork::Discover,
+                CardNetwork::Amex,
+                CardNetwork::Discover,
+                CardNetwork::Amex,
+                CardNetwork::Discover
+            ]
+        );
+        assert_eq!(
+            kit.credentials[0].credential_type,
+            CredentialType::Streaming
+        );
+        assert!(kit.wallets.is_empty());
+        assert_eq!(kit.documents.len(), 3);
+        assert_eq!(kit.honeytokens.len(), 1);
+        assert!(kit.mail.is_empty());
+    }
+
+    #[test]
+    fn test_persona_seed_and_tracking_base() {
+        let persona = Persona::generate(Locale::IsIs);
+        let build = || {
+            HoneypotKit::builder("test-user")
+                .persona(persona.clone())
+                .seed(9)
+                .tracking_base("https://canary.example.org/t")
+                .build()
+                .unwrap()
+        };
+        let kit = build();
+        assert_eq!(kit.persona.as_ref(), Some(&persona));
+        assert_eq!(kit.cards[0].holder_name, persona.card_name());
+        assert_eq!(kit.to_json().unwrap(), build().to_json().unwrap());
+
+        for document in &kit.documents {
+            assert!(document
+                .tracking_url
+                .starts_with("https://canary.example.org/t/"));
+        }
+        for message in &kit.mail {
+            assert!(message
+                .tracking_pixel
+                .starts_with("https://canary.example.org/t/"));
+        }
+
+        let icelandic = HoneypotKit::builder("test-user")
+            .locale(Locale::IsIs)
+            .build()
+            .unwrap();
+        assert_eq!(icelandic.persona.unwrap().locale, Locale::IsIs);
+    }
+
+    #[test]
+    fn test_locale_picks_sites_and_names() {
+        let build = |locale| {
+            HoneypotKit::builder("test-user")
+                .credentials(6)
+                .credential_types(&[CredentialType::BankLogin, CredentialType::Shopping])
+                .locale(locale)
+                .seed(5)
+                .build()
+                .unwrap()
+        };
+        let american = build(Locale::EnUs);
+        let icelandic = build(Locale::IsIs);
+        assert_eq!(
+            american.to_json().unwrap(),
+            build(Locale::EnUs).to_json().unwrap()
+        );
+        assert_eq!(
+            icelandic.to_json().unwrap(),
+            build(Locale::IsIs).to_json().unwrap()
+        );
+
+        for (kit, locale) in [(&american, Locale::EnUs), (&icelandic, Locale::IsIs)] {
+            let handle = kit.persona.as_ref().unwrap().handle();
+            let first = handle.split('.').next().unwrap();
+            for credential in &kit.credentials {
+                let sites = credential.credential_type.sites(locale);
+                assert!(sites.iter().any(|site| site.domain == credential.site));
+                assert!(credential.username.starts_with(first));
+            }
+        }
+        let banks = |kit: &HoneypotKit| -> Vec<String> {
+            kit.credentials
+                .iter()
+                .filter(|c| c.credential_type == CredentialType::BankLogin)
+                .map(|c| c.site.clone())
+                .collect()
+        };
+        assert!(banks(&american).iter().all(|site| site.ends_with(".com")));
+        assert!(banks(&icelandic).iter().all(|site| site.ends_with(".is")));
+        assert_ne!(
+            american.persona.unwrap().full_name(),
+            icelandic.persona.unwrap().full_name()
+        );
+    }
+
+    #[test]
+    fn test_site_catalog() {
+        let sites = vec![
+            SiteEntry::new("sparisjodur.is", "Sparisjóðurinn", Some(Locale::IsIs)),
+            SiteEntry::new("bank.example.com", "Example Bank", Some(Locale::EnUs)),
+        ];
+        let kit = HoneypotKit::builder("test-user")
+            .credentials(4)
+            .credential_types(&[CredentialType::BankLogin, CredentialType::Streaming])
+            .with_site_catalog(CredentialType::BankLogin, vec![])
+            .with_site_catalog(CredentialType::BankLogin, sites.clone())
+            .locale(Locale::IsIs)
+            .seed(3)
+            .build()
+            .unwrap();
+        for credential in &kit.credentials {
+            if credential.credential_type == CredentialType::BankLogin {
+                assert_eq!(credential.site, "sparisjodur.is");
+                assert_eq!(credential.site_name.as_deref(), Some("Sparisjóðurinn"));
+            } else {
+                assert_ne!(credential.site, "sparisjodur.is");
+            }
+        }
+        assert!(kit
+            .mail
+            .iter()
+            .any(|message| message.from.contains("alerts@sparisjodur.is")));
+
+        // The catalog travels with the kit, and into its successor
+        let json = kit.to_json().unwrap();
+        let loaded: HoneypotKit = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.site_catalogs.len(), 1);
+        assert_eq!(loaded.site_catalogs[0].sites, sites);
+        let successor = KitBuilder::like(&loaded).generate();
+        assert_eq!(successor.site_catalogs, loaded.site_catalogs);
+        assert!(successor
+            .credentials
+            .iter()
+            .filter(|c| c.credential_type == CredentialType::BankLogin)
+            .all(|c| c.site == "sparisjodur.is"));
+
+        for sites in [vec![], vec![SiteEntry::new("", "Nowhere", None)]] {
+            let rejected =
+                HoneypotKit::builder("u").with_site_catalog(CredentialType::Shopping, sites);
+            assert!(matches!(
+                rejected.build(),
+                Err(HoneypotError::InvalidConfig(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_presets() {
+        let crypto = HoneypotKit::builder("test-user")
+            .crypto_heavy()
+            .build()
+            .unwrap();
+        assert_eq!(crypto.wallets.len(), 6);
+        assert!(crypto
+            .wallets
+            .iter()
+            .any(|w| w.network == CryptoNetwork::Dogecoin));
+        assert!(crypto
+            .credentials
+            .iter()
+            .any(|c| c.credential_type == CredentialType::CryptoExchange));
+
+        let banking = HoneypotKit::builder("test-user")
+            .banking_focus()
+            .build()
+            .unwrap();
+        assert!(banking.wallets.is_empty());
+        assert_eq!(banking.cards.len(), 4);
+        assert!(banking
+            .cards
+            .iter()
+            .any(|c| c.network == CardNetwork::Discover));
+    }
+
+    #[test]
+    fn test_validation() {
+        let empty = HoneypotKit::builder("test-user")
+            .cards(0)
+            .credentials(0)
+            .wallets(0)
+            .documents(0)
+            .honeytokens(&[]);
+        assert!(empty.clone().build().is_err());
+        assert!(empty.cards(1).build().is_ok());
+
+        let rejected = [
+            HoneypotKit::builder("u").cards(MAX_PER_COMPONENT + 1),
+            HoneypotKit::builder("u").wallets(10_000),
+            HoneypotKit::builder("u").card_networks(&[]),
+            HoneypotKit::builder("u").tracking_base("i1.is/t"),
+            HoneypotKit::builder("u").tracking_base("https://i1.is/t/"),
+            HoneypotKit::builder("u").tracking_base("https:///t"),
+            HoneypotKit::builder("u").lifetime(Duration::zero()),
+        ];
+        for builder in rejected {
+            assert!(matches!(
+                builder.build(),
+                Err(HoneypotError::InvalidConfig(_))
+            ),);
+        }
+
+        // No kinds is fine when none are wanted
+        let no_wallets = HoneypotKit::builder("u").wallets(0).wallet_networks(&[]);
+        assert!(no_wallets.build().is_ok());
+        assert!(HoneypotKit::builder("u")
+            .cards(MAX_PER_COMPONENT)
+            .build()
+            .is_ok());
+    }
+}
@@ -1,10 +1,12 @@
 //! LUHN-valid credit card generation for honeypots.
 
-use chrono::{Datelike, Utc};
+use chrono::{Datelike, NaiveDate, Utc};
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::{Locale, Persona};
+
 /// Credit card network prefixes.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum CardNetwork {
@@ -15,18 +17,21 @@ pub enum CardNetwork {
 }
 
 impl CardNetwork {
-    /// Get the IIN/BIN prefix for this network.
-    fn prefix(&self) -> &str {
+    /// Six-digit BINs of the kind real issuers use on this network, none
+    /// of them the well-known test ranges a scammer would recognize.
+    pub fn bins(&self) -> &'static [&'static str] {
         match self {
-            CardNetwork::Visa => "4",
-            CardNetwork::Mastercard => "51",
-            CardNetwork::Amex => "34",
-            CardNetwork::Discover => "6011",
+            CardNetwork::Visa => &["414720", "426684", "440393", "453275", "471604", "492913"],
+            CardNetwork::Mastercard => {
+                &["515599", "531106", "542418", "552433", "222345", "262915"]
+            }
+            CardNetwork::Amex => &["341134", "373953", "374622", "377481", "379764"],
+            CardNetwork::Discover => &["601156", "601198", "644521", "650052"],
         }
     }
 
     /// Get the expected card number length.
-    fn length(&self) -> usize {
+    pub fn length(&self) -> usize {
         match self {
             CardNetwork::Amex => 15,
             _ => 16,
@@ -67,16 +72,44 @@ pub struct HoneypotCard {
 impl HoneypotCard {
     /// Generate a new honeypot card for the given network.
     pub fn generate(network: CardNetwork) -> Self {
-        let number = generate_luhn_valid(network.prefix(), network.length());
+        Self::generate_with_rng(network, &mut rand::thread_rng())
+    }
+
+    /// Generate a card from `rng`, so a seeded RNG gives the same card.
+    pub fn generate_with_rng(network: CardNetwork, rng: &mut impl Rng) -> Self {
+        let persona = Persona::generate_with_rng(Locale::default(), rng);
+        Self::generate_for_persona(network, &persona, rng)
+    }
+
+    /// Generate a card in a persona's name.
+    pub fn generate_for_persona(
+        network: CardNetwork,
+        persona: &Persona,
+        rng: &mut impl Rng,
+    ) -> Self {
+        Self::generate_for_persona_on(network, persona, Utc::now().date_naive(), rng)
+    }
+
+    /// Generate a card in a persona's name, issued as of `today`: it
+    /// expires one to four years after.
+    pub fn generate_for_persona_on(
+        network: CardNetwork,
+        persona: &Persona,
+        today: NaiveDate,
+        rng: &mut impl Rng,
+    ) -> Self {
+        let bins = network.bins();
+        let bin = bins[rng.gen_range(0..bins.len())];
+        let number = luhn_valid_with_rng(bin, network.length(), rng);
         let display_number = format_card_number(&number);
 
         Self {
-            id: Uuid::new_v4(),
+            id: crate::random_id(rng),
             network,
             number: number.clone(),
-            expiry: generate_expiry(),
-            cvv: generate_cvv(network),
-            holder_name: generate_holder_name(),
+            expiry: generate_expiry(today, rng),
+            cvv: generate_cvv(network, rng),
+            holder_name: persona.card_name(),
             display_number,
         }
     }
@@ -89,8 +122,10 @@ impl HoneypotCard {
 
 /// Generate a LUHN-valid card number with the given prefix.
 pub fn generate_luhn_valid(prefix: &str, length: usize) -> String {
-    let mut rng = rand::thread_rng();
+    luhn_valid_with_rng(prefix, length, &mut rand::thread_rng())
+}
 
+fn luhn_valid_with_rng(prefix: &str, length: usize, rng: &mut impl Rng) -> String {
     // Start with prefix
     let mut digits: Vec<u8> = prefix.chars().map(|c| c.to_digit(10).unwrap() as u8).collect();
 
@@ -177,18 +212,15 @@ fn format_card_number(number: &str) -> String {
     }
 }
 
-/// Generate a realistic expiration date (1-4 years from now).
-fn generate_expiry() -> String {
-    let mut rng = rand::thread_rng();
-    let now = Utc::now();
-    let year = now.year() + rng.gen_range(1..=4);
+/// Generate a realistic expiration date (1-4 years from `today`).
+fn generate_expiry(today: NaiveDate, rng: &mut impl Rng) -> String {
+    let year = today.year() + rng.gen_range(1..=4);
     let month = rng.gen_range(1..=12);
     format!("{:02}/{}", month, year % 100)
 }
 
 /// Generate a CVV/CVC code.
-fn generate_cvv(network: CardNetwork) -> String {
-    let mut rng = rand::thread_rng();
+fn generate_cvv(network: CardNetwork, rng: &mut impl Rng) -> String {
     let length = match network {
         CardNetwork::Amex => 4,
         _ => 3,
@@ -198,27 +230,6 @@ fn generate_cvv(network: CardNetwork) -> String {
         .collect()
 }
 
-/// Generate a realistic cardholder name.
-fn generate_holder_name() -> String {
-    let mut rng = rand::thread_rng();
-
-    let first_names = [
-        "JAMES", "MARY", "JOHN", "PATRICIA", "ROBERT", "JENNIFER", "MICHAEL", "LINDA",
-        "WILLIAM", "ELIZABETH", "DAVID", "BARBARA", "RICHARD", "SUSAN", "JOSEPH", "JESSICA",
-    ];
-
-    let last_names = [
-        "SMITH", "JOHNSON", "WILLIAMS", "BROWN", "JONES", "GARCIA", "MILLER", "DAVIS",
-        "RODRIGUEZ", "MARTINEZ", "HERNANDEZ", "LOPEZ", "GONZALEZ", "WILSON", "ANDERSON", "THOMAS",
-    ];
-
-    format!(
-        "{} {}",
-        first_names[rng.gen_range(0..first_names.len())],
-        last_names[rng.gen_range(0..last_names.len())]
-    )
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -269,6 +280,30 @@ mod tests {
         assert!(card.cvv.len() == 3);
     }
 
+    #[test]
+    fn test_bins_and_lengths() {
+        for network in [
+            CardNetwork::Visa,
+            CardNetwork::Mastercard,
+            CardNetwork::Amex,
+            CardNetwork::Discover,
+        ] {
+            for _ in 0..20 {
+                let card = HoneypotCard::generate(network);
+                assert!(card.is_valid(), "{}", card.number);
+                assert_eq!(card.number.len(), network.length());
+                assert!(network.bins().contains(&&card.number[..6]), "{}", card.number);
+            }
+        }
+        assert_eq!(CardNetwork::Amex.length(), 15);
+        assert_eq!(CardNetwork::Discover.length(), 16);
+        assert!(CardNetwork::Mastercard
+            .bins()
+            .iter()
+            .all(|bin| (51..=55).contains(&bin[..2].parse::<u32>().unwrap())
+                || (2221..=2720).contains(&bin[..4].parse::<u32>().unwrap())));
+    }
+
     #[test]
     fn test_amex_has_4_digit_cvv() {
         let card = HoneypotCard::generate(CardNetwork::Amex);
@@ -4,7 +4,10 @@ use rand::Rng;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-/// Types of credentials to generate.
+use crate::{Locale, Persona, SiteEntry};
+
+/// Types of credentials to generate. The sites each is for are in its
+/// [`SiteCatalog`](crate::SiteCatalog).
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum CredentialType {
     BankLogin,
@@ -15,49 +18,6 @@ pub enum CredentialType {
     Streaming,
 }
 
-impl CredentialType {
-    fn sites(&self) -> &[&str] {
-        match self {
-            CredentialType::BankLogin => &[
-                "chase.com",
-                "bankofamerica.com",
-                "wellsfargo.com",
-                "citibank.com",
-            ],
-            CredentialType::EmailLogin => &[
-                "gmail.com",
-                "outlook.com",
-                "yahoo.com",
-                "protonmail.com",
-            ],
-            CredentialType::SocialMedia => &[
-                "facebook.com",
-                "instagram.com",
-                "twitter.com",
-                "linkedin.com",
-            ],
-            CredentialType::CryptoExchange => &[
-                "coinbase.com",
-                "binance.com",
-                "kraken.com",
-                "gemini.com",
-            ],
-            CredentialType::Shopping => &[
-                "amazon.com",
-                "ebay.com",
-                "walmart.com",
-                "target.com",
-            ],
-            CredentialType::Streaming => &[
-                "netflix.com",
-                "hulu.com",
-                "disneyplus.com",
-                "hbomax.com",
-            ],
-        }
-    }
-}
-
 impl std::fmt::Display for CredentialType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -80,6 +40,9 @@ pub struct HoneypotCredential {
     pub credential_type: CredentialType,
     /// Website/service
     pub site: String,
+    /// The company behind the site, as it signs its mail
+    #[serde(default)]
+    pub site_name: Option<String>,
     /// Username/email
     pub username: String,
     /// Password
@@ -91,37 +54,69 @@ pub struct HoneypotCredential {
 impl HoneypotCredential {
     /// Generate a new honeypot credential.
     pub fn generate(credential_type: CredentialType) -> Self {
-        let mut rng = rand::thread_rng();
-        let sites = credential_type.sites();
-        let site = sites[rng.gen_range(0..sites.len())].to_string();
+        Self::generate_with_rng(credential_type, &mut rand::thread_rng())
+    }
+
+    /// Generate a credential from `rng`, so a seeded RNG gives the same one.
+    pub fn generate_with_rng(credential_type: CredentialType, rng: &mut impl Rng) -> Self {
+        let persona = Persona::generate_with_rng(Locale::default(), rng);
+        Self::generate_for_persona(credential_type, &persona, rng)
+    }
+
+    /// Generate a credential for a persona's account, on one of the
+    /// built-in sites for the persona's locale.
+    pub fn generate_for_persona(
+        credential_type: CredentialType,
+        persona: &Persona,
+        rng: &mut impl Rng,
+    ) -> Self {
+        let sites = credential_type.sites(persona.locale);
+        let site = &sites[rng.gen_range(0..sites.len())];
+        Self::generate_for_site(credential_type, site, persona, rng)
+    }
 
-        let (username, password) = generate_username_password(&site);
+    /// Generate a credential for a persona's account on `site`.
+    pub fn generate_for_site(
+        credential_type: CredentialType,
+        site: &SiteEntry,
+        persona: &Persona,
+        rng: &mut impl Rng,
+    ) -> Self {
+        let id = crate::random_id(rng);
+        let (username, password) =
+            generate_username_password(credential_type, &site.domain, persona, rng);
 
         Self {
-            id: Uuid::new_v4(),
+            id,
             credential_type,
-            site,
+            site: site.domain.clone(),
+            site_name: Some(site.display_name.clone()),
             username,
             password,
-            security_questions: generate_security_questions(),
+            security_questions: generate_security_questions(persona.locale, rng),
         }
     }
 }
 
 /// Generate a realistic username and password pair.
-fn generate_username_password(site: &str) -> (String, String) {
-    let mut rng = rand::thread_rng();
-
-    let first_names = ["james", "mary", "john", "patricia", "robert", "jennifer"];
-    let last_names = ["smith", "johnson", "williams", "brown", "jones"];
-
-    let first = first_names[rng.gen_range(0..first_names.len())];
-    let last = last_names[rng.gen_range(0..last_names.len())];
+fn generate_username_password(
+    credential_type: CredentialType,
+    site: &str,
+    persona: &Persona,
+    rng: &mut impl Rng,
+) -> (String, String) {
+    let handle = persona.handle();
+    let (first, last) = handle.split_once('.').unwrap_or((&handle, ""));
     let num = rng.gen_range(1..999);
 
-    // Email-style username for most sites
-    let username = if site.contains("gmail") || site.contains("outlook") || site.contains("yahoo") {
+    // A webmail login is the address itself; elsewhere people sign up
+    // with a made-up name or with the address from their local provider
+    let username = if credential_type == CredentialType::EmailLogin {
         format!("{}.{}{}@{}", first, last, num, site)
+    } else if rng.gen_bool(0.3) {
+        let providers = CredentialType::EmailLogin.sites(persona.locale);
+        let provider = &providers[rng.gen_range(0..providers.len())];
+        format!("{}.{}{}@{}", first, last, num, provider.domain)
     } else {
         format!("{}{}{}", first, last, num)
     };
@@ -140,28 +135,24 @@ fn generate_username_password(site: &str) -> (String, String) {
     (username, password)
 }
 
-/// Generate security questions and answers.
-fn generate_security_questions() -> Vec<(String, String)> {
-    let mut rng = rand::thread_rng();
-
-    let qa_pairs = [
-        ("What is your mother's maiden name?", &["Smith", "Johnson", "Williams", "Davis"][..]),
-        ("What was the name of your first pet?", &["Max", "Buddy", "Charlie", "Lucy"]),
-        ("What city were you born in?", &["New York", "Los Angeles", "Chicago", "Houston"]),
-        ("What is your favorite movie?", &["Star Wars", "Titanic", "The Godfather", "Forrest Gump"]),
-        ("What was the make of your first car?", &["Toyota", "Honda", "Ford", "Chevrolet"]),
-    ];
-
-    qa_pairs
-        .iter()
-        .take(3)
-        .map(|(q, answers)| {
-            (
-                q.to_string(),
-                answers[rng.gen_range(0..answers.len())].to_string(),
-            )
-        })
-        .collect()
+/// Generate security questions and answers, from the same name and town
+/// pools as personas from `locale`.
+fn generate_security_questions(locale: Locale, rng: &mut impl Rng) -> Vec<(String, String)> {
+    let pets = ["Max", "Buddy", "Charlie", "Lucy", "Bella", "Moli"];
+    vec![
+        (
+            "What is your mother's maiden name?".to_string(),
+            locale.family_name(rng).to_string(),
+        ),
+        (
+            "What was the name of your first pet?".to_string(),
+            pets[rng.gen_range(0..pets.len())].to_string(),
+        ),
+        (
+            "What city were you born in?".to_string(),
+            locale.town(rng).to_string(),
+        ),
+    ]
 }
 
 #[cfg(test)]
@@ -179,8 +170,22 @@ mod tests {
     #[test]
     fn test_email_format() {
         let cred = HoneypotCredential::generate(CredentialType::EmailLogin);
-        if cred.site.contains("gmail") {
-            assert!(cred.username.contains('@'));
-        }
+        assert!(cred.username.ends_with(&format!("@{}", cred.site)));
+    }
+
+    #[test]
+    fn test_local_security_answers() {
+        let persona = Persona::generate(Locale::IsIs);
+        let cred = HoneypotCredential::generate_for_persona(
+            CredentialType::Shopping,
+            &persona,
+            &mut rand::thread_rng(),
+        );
+        assert!(cred.security_questions[0].1.ends_with("dóttir"));
+        assert!(CredentialType::Shopping
+            .sites(Locale::IsIs)
+            .iter()
+            .any(|site| site.domain == cred.site));
+        assert!(cred.site_name.is_some());
     }
 }
@@ -1,7 +1,13 @@
-//! Fake cryptocurrency wallet generation for honeypots.
+//! Honeypot cryptocurrency wallets: empty, watchable, and valid down to the seed phrase.
 
+use std::sync::OnceLock;
+
+use bip32::XPrv;
 use rand::Rng;
+use ripemd::Ripemd160;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256, Sha512};
+use sha3::Keccak256;
 use uuid::Uuid;
 
 /// Supported cryptocurrency networks.
@@ -14,31 +20,15 @@ pub enum CryptoNetwork {
 }
 
 impl CryptoNetwork {
-    /// Address prefix for this network.
-    fn prefix(&self) -> &str {
-        match self {
-            CryptoNetwork::Bitcoin => "1",      // Legacy P2PKH
-            CryptoNetwork::Ethereum => "0x",
-            CryptoNetwork::Litecoin => "L",
-            CryptoNetwork::Dogecoin => "D",
-        }
-    }
-
-    /// Address length (excluding prefix).
-    fn address_length(&self) -> usize {
-        match self {
-            CryptoNetwork::Bitcoin => 33,   // 34 total with prefix
-            CryptoNetwork::Ethereum => 40,  // 42 total with 0x
-            CryptoNetwork::Litecoin => 33,
-            CryptoNetwork::Dogecoin => 33,
-        }
-    }
-
-    /// Valid characters for address generation.
-    fn charset(&self) -> &str {
+    /// Base58Check version byte of a legacy P2PKH address, `None` for
+    /// Ethereum. It makes Bitcoin addresses start with `1`, Litecoin with
+    /// `L` and Dogecoin with `D`.
+    fn version_byte(&self) -> Option<u8> {
         match self {
-            CryptoNetwork::Ethereum => "0123456789abcdef",
-            _ => "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz", // Base58
+            CryptoNetwork::Bitcoin => Some(0x00),
+            CryptoNetwork::Ethereum => None,
+            CryptoNetwork::Litecoin => Some(0x30),
+            CryptoNetwork::Dogecoin => Some(0x1e),
         }
     }
 }
@@ -61,11 +51,12 @@ pub struct HoneypotWallet {
     pub id: Uuid,
     /// Cryptocurrency network
     pub network: CryptoNetwork,
-    /// Wallet address (looks valid but we control it)
+    /// Wallet address, the first receive address of `seed_phrase`. It is
+    /// real and empty by design, so any movement is worth watching.
     pub address: String,
-    /// Fake private key (DO NOT USE - for honeypot display only)
+    /// Private key of `address` (DO NOT FUND - for honeypot display only)
     pub private_key: String,
-    /// BIP-39 style seed phrase (fake)
+    /// BIP-39 seed phrase with a valid checksum
     pub seed_phrase: String,
     /// Fake balance to make it enticing
     pub fake_balance: String,
@@ -74,91 +65,193 @@ pub struct HoneypotWallet {
 impl HoneypotWallet {
     /// Generate a new honeypot wallet.
     pub fn generate(network: CryptoNetwork) -> Self {
+        Self::generate_with_rng(network, &mut rand::thread_rng())
+    }
+
+    /// Generate a wallet from `rng`, so a seeded RNG gives the same seed
+    /// phrase, keys and address.
+    pub fn generate_with_rng(network: CryptoNetwork, rng: &mut impl Rng) -> Self {
+        let seed_phrase = generate_seed_phrase(rng);
+        Self::from_seed_phrase(network, false, seed_phrase, rng)
+    }
+
+    /// Generate a Bitcoin wallet with a native SegWit (`bc1q...`) address.
+    pub fn generate_segwit() -> Self {
+        let mut rng = rand::thread_rng();
+        let seed_phrase = generate_seed_phrase(&mut rng);
+        Self::from_seed_phrase(CryptoNetwork::Bitcoin, true, seed_phrase, &mut rng)
+    }
+
+    /// The wallet a wallet app would restore from `seed_phrase`: the key and
+    /// address are the first receive address of its standard account.
+    fn from_seed_phrase(
+        network: CryptoNetwork,
+        segwit: bool,
+        seed_phrase: String,
+        rng: &mut impl Rng,
+    ) -> Self {
+        let key = derive_key(&seed_phrase, derivation_path(network, segwit));
+
         Self {
-            id: Uuid::new_v4(),
+            id: crate::random_id(rng),
             network,
-            address: generate_address(network),
-            private_key: generate_private_key(network),
-            seed_phrase: generate_seed_phrase(),
-            fake_balance: generate_fake_balance(network),
+            address: key_address(network, segwit, &key),
+            private_key: key_export(network, &key),
+            seed_phrase,
+            fake_balance: generate_fake_balance(network, rng),
         }
     }
+
+    /// Check that the address would pass a wallet's checks: Base58Check
+    /// with this network's version byte (or bech32 P2WPKH on Bitcoin), or
+    /// EIP-55 capitalization on Ethereum.
+    pub fn validate_address(&self) -> bool {
+        let address = self.address.as_str();
+        match self.network.version_byte() {
+            Some(version) => {
+                let segwit = self.network == CryptoNetwork::Bitcoin
+                    && bech32_decode("bc", address)
+                        .is_some_and(|(v, program)| v == 0 && program.len() == 20);
+                segwit
+                    || base58check_decode(address)
+                        .is_some_and(|payload| payload.len() == 21 && payload[0] == version)
+            }
+            None => address
+                .strip_prefix("0x")
+                .and_then(hex_decode)
+                .is_some_and(|bytes| bytes.len() == 20 && eip55(&bytes) == address),
+        }
+    }
+
+    /// Check that the seed phrase is a BIP-39 mnemonic wallet software
+    /// accepts: 12 to 24 words from the English list, checksum included.
+    pub fn mnemonic_is_valid(&self) -> bool {
+        mnemonic_entropy(&self.seed_phrase).is_some()
+    }
 }
 
-/// Generate a realistic-looking address.
-fn generate_address(network: CryptoNetwork) -> String {
-    let mut rng = rand::thread_rng();
-    let charset: Vec<char> = network.charset().chars().collect();
-    let length = network.address_length();
+/// BIP-44 path of the first receive address (BIP-84 for native SegWit).
+fn derivation_path(network: CryptoNetwork, segwit: bool) -> &'static str {
+    match (network, segwit) {
+        (CryptoNetwork::Bitcoin, true) => "m/84'/0'/0'/0/0",
+        (CryptoNetwork::Bitcoin, false) => "m/44'/0'/0'/0/0",
+        (CryptoNetwork::Litecoin, _) => "m/44'/2'/0'/0/0",
+        (CryptoNetwork::Dogecoin, _) => "m/44'/3'/0'/0/0",
+        (CryptoNetwork::Ethereum, _) => "m/44'/60'/0'/0/0",
+    }
+}
 
-    let random_part: String = (0..length)
-        .map(|_| charset[rng.gen_range(0..charset.len())])
-        .collect();
+/// The key at `path` under a mnemonic's BIP-39 seed (no passphrase).
+fn derive_key(mnemonic: &str, path: &str) -> XPrv {
+    let mut seed = [0u8; 64];
+    pbkdf2::pbkdf2_hmac::<Sha512>(mnemonic.as_bytes(), b"mnemonic", 2048, &mut seed);
 
-    format!("{}{}", network.prefix(), random_part)
+    let path = path.parse().expect("derivation paths are well formed");
+    XPrv::derive_from_path(seed, &path).expect("a random seed derives a valid key")
 }
 
-/// Generate a fake private key.
-fn generate_private_key(network: CryptoNetwork) -> String {
-    let mut rng = rand::thread_rng();
+/// The address of a key: P2PKH or P2WPKH over the compressed public key, or
+/// the last 20 bytes of the Keccak-256 hash of the public key on Ethereum.
+fn key_address(network: CryptoNetwork, segwit: bool, key: &XPrv) -> String {
+    match network.version_byte() {
+        Some(_) if segwit => bech32_encode("bc", 0, &hash160(&key.public_key().to_bytes())),
+        Some(version) => {
+            let mut payload = vec![version];
+            payload.extend_from_slice(&hash160(&key.public_key().to_bytes()));
+            base58check_encode(&payload)
+        }
+        None => {
+            let point = key.private_key().verifying_key().to_encoded_point(false);
+            eip55(&Keccak256::digest(&point.as_bytes()[1..])[12..])
+        }
+    }
+}
 
-    match network {
+/// The private key as wallets import it: compressed WIF, or hex on Ethereum.
+fn key_export(network: CryptoNetwork, key: &XPrv) -> String {
+    let prefix = match network {
+        CryptoNetwork::Bitcoin => 0x80,
+        CryptoNetwork::Litecoin => 0xb0,
+        CryptoNetwork::Dogecoin => 0x9e,
         CryptoNetwork::Ethereum => {
-            let hex: String = (0..64)
-                .map(|_| format!("{:x}", rng.gen_range(0..16)))
-                .collect();
-            format!("0x{}", hex)
-        }
-        _ => {
-            // WIF format for Bitcoin-like
-            let charset: Vec<char> = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz"
-                .chars()
-                .collect();
-            let key: String = (0..51)
-                .map(|_| charset[rng.gen_range(0..charset.len())])
-                .collect();
-            format!("5{}", key)
-        }
-    }
-}
-
-/// Generate a BIP-39 style seed phrase (fake but looks real).
-fn generate_seed_phrase() -> String {
-    let mut rng = rand::thread_rng();
-
-    // Common BIP-39 words (subset for generation)
-    let words = [
-        "abandon", "ability", "able", "about", "above", "absent", "absorb", "abstract",
-        "absurd", "abuse", "access", "accident", "account", "accuse", "achieve", "acid",
-        "acoustic", "acquire", "across", "act", "action", "actor", "actress", "actual",
-        "adapt", "add", "addict", "address", "adjust", "admit", "adult", "advance",
-        "advice", "aerobic", "affair", "afford", "afraid", "again", "age", "agent",
-        "agree", "ahead", "aim", "air", "airport", "aisle", "alarm", "album",
-        "alert", "alien", "almost", "alpha", "already", "also", "alter", "always",
-        "amazing", "among", "amount", "anchor", "ancient", "anger", "angry", "animal",
-        "ankle", "announce", "annual", "another", "answer", "antenna", "antique", "anxiety",
-        "any", "apart", "apology", "appear", "apple", "approve", "april", "arch",
-        "arctic", "area", "arena", "argue", "arm", "armed", "armor", "army",
-        "around", "arrange", "arrest", "arrive", "arrow", "art", "artefact", "artist",
-        "artwork", "ask", "aspect", "assault", "asset", "assist", "assume", "asthma",
-        "atom", "attack", "attend", "auction", "audit", "august", "aunt", "author",
-        "auto", "autumn", "average", "avocado", "avoid", "awake", "aware", "away",
-        "awesome", "awful", "awkward", "axis", "baby", "bachelor", "bacon", "badge",
-    ];
-
-    // Generate 12 or 24 word phrase
-    let count = if rng.gen_bool(0.5) { 12 } else { 24 };
-
-    (0..count)
-        .map(|_| words[rng.gen_range(0..words.len())])
+            return format!("0x{}", hex_encode(&key.to_bytes()));
+        }
+    };
+
+    let mut payload = vec![prefix];
+    payload.extend_from_slice(&key.to_bytes());
+    payload.push(0x01);
+    base58check_encode(&payload)
+}
+
+/// The BIP-39 English wordlist, one word per line.
+const WORDLIST: &str = include_str!("bip39_english.txt");
+
+fn wordlist() -> &'static [&'static str] {
+    static WORDS: OnceLock<Vec<&str>> = OnceLock::new();
+    WORDS.get_or_init(|| WORDLIST.lines().collect())
+}
+
+/// Generate a BIP-39 seed phrase from fresh entropy.
+fn generate_seed_phrase(rng: &mut impl Rng) -> String {
+    // 12 or 24 words, from 128 or 256 bits
+    let mut entropy = vec![0u8; if rng.gen_bool(0.5) { 16 } else { 32 }];
+    rng.fill(&mut entropy[..]);
+    entropy_to_mnemonic(&entropy)
+}
+
+/// Entropy and its checksum, the first `bits / 32` bits of its SHA-256 hash,
+/// written out 11 bits to a word.
+fn entropy_to_mnemonic(entropy: &[u8]) -> String {
+    let hash = Sha256::digest(entropy);
+    let bits = entropy.len() * 8 + entropy.len() / 4;
+    let bit = |i: usize| {
+        let byte = match entropy.get(i / 8) {
+            Some(byte) => *byte,
+            None => hash[i / 8 - entropy.len()],
+        };
+        (byte >> (7 - i % 8)) & 1
+    };
+
+    let words = wordlist();
+    (0..bits / 11)
+        .map(|w| {
+            let index = (0..11).fold(0, |acc, i| (acc << 1) | usize::from(bit(w * 11 + i)));
+            words[index]
+        })
         .collect::<Vec<_>>()
         .join(" ")
 }
 
-/// Generate an enticing fake balance.
-fn generate_fake_balance(network: CryptoNetwork) -> String {
-    let mut rng = rand::thread_rng();
+/// The entropy behind a mnemonic, if every word is on the list and the
+/// checksum matches.
+fn mnemonic_entropy(mnemonic: &str) -> Option<Vec<u8>> {
+    let words = wordlist();
+    let indices: Vec<usize> = mnemonic
+        .split_whitespace()
+        .map(|word| words.binary_search(&word).ok())
+        .collect::<Option<_>>()?;
+    if !matches!(indices.len(), 12 | 15 | 18 | 21 | 24) {
+        return None;
+    }
 
+    let bits: Vec<u8> = indices
+        .iter()
+        .flat_map(|&index| (0..11).rev().map(move |i| ((index >> i) & 1) as u8))
+        .collect();
+    let (data, check) = bits.split_at(bits.len() * 32 / 33);
+    let entropy: Vec<u8> = data
+        .chunks(8)
+        .map(|byte| byte.iter().fold(0, |acc, bit| (acc << 1) | bit))
+        .collect();
+
+    let hash = Sha256::digest(&entropy);
+    let expected = (0..check.len()).map(|i| (hash[i / 8] >> (7 - i % 8)) & 1);
+    expected.eq(check.iter().copied()).then_some(entropy)
+}
+
+/// Generate an enticing fake balance.
+fn generate_fake_balance(network: CryptoNetwork, rng: &mut impl Rng) -> String {
     let (amount, symbol) = match network {
         CryptoNetwork::Bitcoin => (rng.gen_range(0.5..5.0), "BTC"),
         CryptoNetwork::Ethereum => (rng.gen_range(2.0..20.0), "ETH"),
@@ -169,6 +262,215 @@ fn generate_fake_balance(network: CryptoNetwork) -> String {
     format!("{:.4} {}", amount, symbol)
 }
 
+const BASE58: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+const BECH32: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+/// First four bytes of SHA-256(SHA-256(data)).
+fn checksum(data: &[u8]) -> [u8; 4] {
+    let hash = Sha256::digest(Sha256::digest(data));
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+/// RIPEMD-160 of SHA-256, the hash behind P2PKH and P2WPKH addresses.
+fn hash160(data: &[u8]) -> [u8; 20] {
+    Ripemd160::digest(Sha256::digest(data)).into()
+}
+
+/// Base58 with a checksum appended, as used by legacy addresses.
+fn base58check_encode(payload: &[u8]) -> String {
+    let mut data = payload.to_vec();
+    data.extend_from_slice(&checksum(payload));
+
+    // Base 256 to base 58, least significant digit first
+    let mut digits: Vec<u8> = Vec::new();
+    for &byte in &data {
+        let mut carry = u32::from(byte);
+        for digit in &mut digits {
+            carry += u32::from(*digit) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+
+    // Each leading zero byte is written as a '1'
+    let zeros = data.iter().take_while(|&&b| b == 0).count();
+    let mut encoded = "1".repeat(zeros);
+    encoded.extend(digits.iter().rev().map(|&d| BASE58[d as usize] as char));
+    encoded
+}
+
+/// The payload of a Base58Check string, if the checksum matches.
+fn base58check_decode(s: &str) -> Option<Vec<u8>> {
+    // Base 58 to base 256, least significant byte first
+    let mut bytes: Vec<u8> = Vec::new();
+    for c in s.bytes() {
+        let mut carry = BASE58.iter().position(|&b| b == c)? as u32;
+        for byte in &mut bytes {
+            carry += u32::from(*byte) * 58;
+            *byte = (carry & 0xff) as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.push((carry & 0xff) as u8);
+            carry >>= 8;
+        }
+    }
+    let zeros = s.bytes().take_while(|&c| c == b'1').count();
+    let mut data = vec![0; zeros];
+    data.extend(bytes.into_iter().rev());
+
+    if data.len() < 4 {
+        return None;
+    }
+    let (payload, check) = data.split_at(data.len() - 4);
+    (checksum(payload) == check).then(|| payload.to_vec())
+}
+
+/// BIP-173 checksum over 5-bit values.
+fn bech32_polymod(values: &[u8]) -> u32 {
+    const GENERATOR: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+
+    let mut chk: u32 = 1;
+    for &value in values {
+        let top = chk >> 25;
+        chk = ((chk & 0x01ff_ffff) << 5) ^ u32::from(value);
+        for (i, g) in GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                chk ^= g;
+            }
+        }
+    }
+    chk
+}
+
+/// The human-readable part as the checksum sees it.
+fn bech32_hrp_expand(hrp: &str) -> Vec<u8> {
+    let bytes = hrp.as_bytes();
+    bytes
+        .iter()
+        .map(|b| b >> 5)
+        .chain(std::iter::once(0))
+        .chain(bytes.iter().map(|b| b & 0x1f))
+        .collect()
+}
+
+/// Regroup bits, e.g. bytes into the 5-bit values bech32 writes and back.
+fn convert_bits(data: &[u8], from: u32, to: u32, pad: bool) -> Option<Vec<u8>> {
+    let mut acc: u32 = 0;
+    let mut bits: u32 = 0;
+    let max = (1 << to) - 1;
+    let mut out = Vec::new();
+
+    for &value in data {
+        if u32::from(value) >> from != 0 {
+            return None;
+        }
+        acc = (acc << from) | u32::from(value);
+        bits += from;
+        while bits >= to {
+            bits -= to;
+            out.push(((acc >> bits) & max) as u8);
+        }
+    }
+
+    if pad {
+        if bits > 0 {
+            out.push(((acc << (to - bits)) & max) as u8);
+        }
+    } else if bits >= from || ((acc << (to - bits)) & max) != 0 {
+        return None;
+    }
+    Some(out)
+}
+
+/// A SegWit address (BIP-173) for a witness version and program.
+fn bech32_encode(hrp: &str, version: u8, program: &[u8]) -> String {
+    let mut data = vec![version];
+    data.extend(convert_bits(program, 8, 5, true).unwrap_or_default());
+
+    let mut values = bech32_hrp_expand(hrp);
+    values.extend_from_slice(&data);
+    values.extend_from_slice(&[0; 6]);
+    let polymod = bech32_polymod(&values) ^ 1;
+    let check = (0..6).map(|i| ((polymod >> (5 * (5 - i))) & 0x1f) as u8);
+
+    let mut address = format!("{}1", hrp);
+    address.extend(
+        data.into_iter()
+            .chain(check)
+            .map(|v| BECH32[v as usize] as char),
+    );
+    address
+}
+
+/// The witness version and program of a SegWit address, if it is valid.
+fn bech32_decode(hrp: &str, address: &str) -> Option<(u8, Vec<u8>)> {
+    // Either case is fine, but not both
+    if address.chars().any(char::is_uppercase) && address.chars().any(char::is_lowercase) {
+        return None;
+    }
+    let address = address.to_lowercase();
+    let rest = address.strip_prefix(hrp)?.strip_prefix('1')?;
+    let values: Vec<u8> = rest
+        .bytes()
+        .map(|c| BECH32.iter().position(|&b| b == c).map(|v| v as u8))
+        .collect::<Option<_>>()?;
+    if values.len() < 7 {
+        return None;
+    }
+
+    let mut check = bech32_hrp_expand(hrp);
+    check.extend_from_slice(&values);
+    if bech32_polymod(&check) != 1 {
+        return None;
+    }
+    let data = &values[..values.len() - 6];
+    let program = convert_bits(&data[1..], 5, 8, false)?;
+    Some((data[0], program))
+}
+
+/// An Ethereum address with the EIP-55 checksum: a hex letter is upper case
+/// when the matching nibble of the Keccak-256 hash of the lower-case
+/// address is 8 or more.
+fn eip55(address: &[u8]) -> String {
+    let lower = hex_encode(address);
+    let hash = Keccak256::digest(lower.as_bytes());
+
+    let checksummed: String = lower
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            let nibble = if i % 2 == 0 {
+                hash[i / 2] >> 4
+            } else {
+                hash[i / 2] & 0x0f
+            };
+            if nibble >= 8 {
+                c.to_ascii_uppercase()
+            } else {
+                c
+            }
+        })
+        .collect();
+    format!("0x{}", checksummed)
+}
+
+pub(crate) fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Bytes from hex; `None` for anything else, including an odd length.
+fn hex_decode(hex: &str) -> Option<Vec<u8>> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -177,7 +479,7 @@ mod tests {
     fn test_bitcoin_wallet() {
         let wallet = HoneypotWallet::generate(CryptoNetwork::Bitcoin);
         assert!(wallet.address.starts_with('1'));
-        assert!(wallet.private_key.starts_with('5'));
+        assert!(wallet.private_key.starts_with(['K', 'L']));
         assert!(wallet.seed_phrase.split_whitespace().count() >= 12);
     }
 
@@ -186,13 +488,213 @@ mod tests {
         let wallet = HoneypotWallet::generate(CryptoNetwork::Ethereum);
         assert!(wallet.address.starts_with("0x"));
         assert!(wallet.private_key.starts_with("0x"));
+        assert_eq!(wallet.private_key.len(), 66);
         assert_eq!(wallet.address.len(), 42);
     }
 
+    fn wallet(network: CryptoNetwork, address: &str) -> HoneypotWallet {
+        HoneypotWallet {
+            address: address.to_string(),
+            ..HoneypotWallet::generate(network)
+        }
+    }
+
+    #[test]
+    fn test_known_addresses() {
+        // The genesis block's address, from its public key hash
+        let genesis = hex_decode("0062e907b15cbf27d5425399ebf6f0fb50ebb88f18").unwrap();
+        assert_eq!(
+            base58check_encode(&genesis),
+            "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa"
+        );
+        assert!(
+            wallet(CryptoNetwork::Bitcoin, "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa").validate_address()
+        );
+        assert!(
+            !wallet(CryptoNetwork::Bitcoin, "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNb")
+                .validate_address()
+        );
+        assert!(!wallet(
+            CryptoNetwork::Litecoin,
+            "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa"
+        )
+        .validate_address());
+
+        // BIP-173 test vector
+        let program = hex_decode("751e76e8199196d454941c45d1b3a323f1433bd6").unwrap();
+        assert_eq!(
+            bech32_encode("bc", 0, &program),
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"
+        );
+        assert!(wallet(
+            CryptoNetwork::Bitcoin,
+            "BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4"
+        )
+        .validate_address());
+        assert!(!wallet(
+            CryptoNetwork::Bitcoin,
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t5"
+        )
+        .validate_address());
+
+        // EIP-55 test vectors
+        for address in [
+            "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+            "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359",
+            "0xdbF03B407c01E7cD3CBea99509d93f8DDDC8C6FB",
+            "0xD1220A0cf47c7B9Be7A2E6BA89F429762e7b9aDb",
+        ] {
+            let bytes = hex_decode(&address[2..].to_lowercase()).unwrap();
+            assert_eq!(eip55(&bytes), address);
+            assert!(wallet(CryptoNetwork::Ethereum, address).validate_address());
+            assert!(!wallet(CryptoNetwork::Ethereum, &address.to_lowercase()).validate_address());
+        }
+    }
+
+    #[test]
+    fn test_generated_addresses_validate() {
+        for (network, prefix) in [
+            (CryptoNetwork::Bitcoin, "1"),
+            (CryptoNetwork::Litecoin, "L"),
+            (CryptoNetwork::Dogecoin, "D"),
+            (CryptoNetwork::Ethereum, "0x"),
+        ] {
+            for _ in 0..10 {
+                let wallet = HoneypotWallet::generate(network);
+                assert!(wallet.address.starts_with(prefix), "{}", wallet.address);
+                assert!(wallet.validate_address(), "{}", wallet.address);
+            }
+        }
+
+        for _ in 0..10 {
+            let wallet = HoneypotWallet::generate_segwit();
+            assert!(wallet.address.starts_with("bc1q"), "{}", wallet.address);
+            assert_eq!(wallet.address.len(), 42);
+            assert!(wallet.validate_address(), "{}", wallet.address);
+        }
+    }
+
+    #[test]
+    fn test_altered_addresses_fail() {
+        let mut btc = HoneypotWallet::generate(CryptoNetwork::Dogecoin);
+        let last = if btc.address.ends_with('z') { "y" } else { "z" };
+        btc.address.replace_range(btc.address.len() - 1.., last);
+        assert!(!btc.validate_address());
+
+        let mut eth = HoneypotWallet::generate(CryptoNetwork::Ethereum);
+        let i = eth.address[2..]
+            .find(|c: char| c.is_ascii_alphabetic())
+            .unwrap()
+            + 2;
+        let flipped = if eth.address.as_bytes()[i].is_ascii_uppercase() {
+            eth.address[i..=i].to_lowercase()
+        } else {
+            eth.address[i..=i].to_uppercase()
+        };
+        eth.address.replace_range(i..=i, &flipped);
+        assert!(!eth.validate_address());
+    }
+
     #[test]
     fn test_seed_phrase_length() {
         let wallet = HoneypotWallet::generate(CryptoNetwork::Bitcoin);
         let word_count = wallet.seed_phrase.split_whitespace().count();
         assert!(word_count == 12 || word_count == 24);
     }
+
+    #[test]
+    fn test_mnemonic_vectors() {
+        // BIP-39 test vectors
+        for (entropy, mnemonic) in [
+            (
+                "00000000000000000000000000000000",
+                "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+            ),
+            (
+                "7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f",
+                "legal winner thank year wave sausage worth useful legal winner thank yellow",
+            ),
+            (
+                "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+                "zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo vote",
+            ),
+        ] {
+            let entropy = hex_decode(entropy).unwrap();
+            assert_eq!(entropy_to_mnemonic(&entropy), mnemonic);
+            assert_eq!(mnemonic_entropy(mnemonic), Some(entropy));
+        }
+
+        assert_eq!(wordlist().len(), 2048);
+        assert!(mnemonic_entropy("abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon").is_none());
+        assert!(mnemonic_entropy("abandon abandon abandon").is_none());
+        assert!(mnemonic_entropy("abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon bitcoin").is_none());
+    }
+
+    #[test]
+    fn test_mnemonic_matches_reference() {
+        for _ in 0..5 {
+            let phrase = generate_seed_phrase(&mut rand::thread_rng());
+            let reference = bip39::Mnemonic::parse_in(bip39::Language::English, &phrase)
+                .unwrap_or_else(|e| panic!("{phrase}: {e}"));
+            assert_eq!(reference.to_entropy(), mnemonic_entropy(&phrase).unwrap());
+
+            let mut seed = [0u8; 64];
+            pbkdf2::pbkdf2_hmac::<Sha512>(phrase.as_bytes(), b"mnemonic", 2048, &mut seed);
+            assert_eq!(reference.to_seed(""), seed);
+        }
+
+        for bytes in [16, 32] {
+            let entropy: Vec<u8> = (0..bytes).map(|_| rand::random()).collect();
+            let reference = bip39::Mnemonic::from_entropy(&entropy).unwrap();
+            let wallet = HoneypotWallet {
+                seed_phrase: reference.to_string(),
+                ..HoneypotWallet::generate(CryptoNetwork::Bitcoin)
+            };
+            assert!(wallet.mnemonic_is_valid());
+        }
+    }
+
+    #[test]
+    fn test_address_matches_seed() {
+        let mut rng = rand::thread_rng();
+        // Well-known first addresses of the all-"abandon" test mnemonic
+        let phrase = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        for (network, segwit, address) in [
+            (
+                CryptoNetwork::Bitcoin,
+                false,
+                "1LqBGSKuX5yYUonjxT5qGfpUsXKYYWeabA",
+            ),
+            (
+                CryptoNetwork::Bitcoin,
+                true,
+                "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu",
+            ),
+            (
+                CryptoNetwork::Ethereum,
+                false,
+                "0x9858EfFD232B4033E47d90003D41EC34EcaEda94",
+            ),
+        ] {
+            let wallet =
+                HoneypotWallet::from_seed_phrase(network, segwit, phrase.to_string(), &mut rng);
+            assert_eq!(wallet.address, address);
+            assert!(wallet.mnemonic_is_valid());
+            assert!(wallet.validate_address());
+        }
+
+        for network in [
+            CryptoNetwork::Bitcoin,
+            CryptoNetwork::Litecoin,
+            CryptoNetwork::Dogecoin,
+            CryptoNetwork::Ethereum,
+        ] {
+            let wallet = HoneypotWallet::generate(network);
+            assert!(wallet.mnemonic_is_valid(), "{}", wallet.seed_phrase);
+            let phrase = wallet.seed_phrase.clone();
+            let restored = HoneypotWallet::from_seed_phrase(network, false, phrase, &mut rng);
+            assert_eq!(restored.address, wallet.address);
+            assert_eq!(restored.private_key, wallet.private_key);
+        }
+    }
 }
@@ -0,0 +1,585 @@
+//! TLS decoy services posing as a device's admin login.
+//!
+//! [`decoy_service`] listens with a certificate freshly issued by an i1-ca
+//! intermediate for the decoy's hostname, and serves the login page of a
+//! router, a NAS or a Remote Desktop web portal. Nothing is behind it:
+//! every connection becomes a [`TripwireEvent`] with the peer's address,
+//! the name it asked for, a fingerprint of its TLS hello and whatever
+//! credentials it submitted.
+
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use i1_ca::{IntermediateCa, LeafCertificate, LeafParams};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::{Acceptor, ClientHello};
+use rustls::ServerConfig;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, watch, Semaphore};
+use tokio::task::{JoinHandle, JoinSet};
+use tokio_rustls::LazyConfigAcceptor;
+use uuid::Uuid;
+
+use crate::{HoneypotError, TripwireEvent};
+
+/// Connections a decoy serves at once unless configured otherwise.
+pub const DEFAULT_MAX_CONNECTIONS: usize = 64;
+
+/// Days a decoy's certificate is valid unless configured otherwise.
+pub const DEFAULT_VALIDITY_DAYS: u32 = 7;
+
+/// How long a connection may take, handshake and request included.
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Most of a request read, head and body together.
+const MAX_REQUEST: usize = 16 * 1024;
+
+/// Events held for the caller before connections wait for it.
+const EVENT_BUFFER: usize = 256;
+
+/// Connections the kernel queues before the decoy accepts them
+const LISTEN_BACKLOG: i32 = 1024;
+
+/// What a decoy pretends to be.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DecoyKind {
+    /// A home router's web admin
+    #[default]
+    RouterAdmin,
+    /// A NAS's storage manager
+    Nas,
+    /// A Remote Desktop web access portal
+    RdpWeb,
+}
+
+impl DecoyKind {
+    /// Name used in tripwire events
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::RouterAdmin => "router_admin",
+            Self::Nas => "nas",
+            Self::RdpWeb => "rdp_web",
+        }
+    }
+
+    const fn title(self) -> &'static str {
+        match self {
+            Self::RouterAdmin => "Router Administration",
+            Self::Nas => "Storage Manager",
+            Self::RdpWeb => "RD Web Access",
+        }
+    }
+
+    /// `Server` header the real thing tends to send
+    pub(crate) const fn server(self) -> &'static str {
+        match self {
+            Self::RouterAdmin => "mini_httpd/1.30 26Oct2018",
+            Self::Nas => "nginx",
+            Self::RdpWeb => "Microsoft-IIS/10.0",
+        }
+    }
+
+    /// Names of the login form's username and password fields
+    const fn fields(self) -> (&'static str, &'static str) {
+        match self {
+            Self::RouterAdmin => ("username", "password"),
+            Self::Nas => ("account", "passwd"),
+            Self::RdpWeb => ("DomainUserName", "UserPass"),
+        }
+    }
+
+    /// The login page, saying the last attempt failed if `failed`.
+    fn page(self, hostname: &str, failed: bool) -> String {
+        let (user, pass) = self.fields();
+        let title = self.title();
+        let error = if failed {
+            r#"<p class="error">Invalid username or password.</p>"#
+        } else {
+            ""
+        };
+        format!(
+            r#"<!DOCTYPE html>
+<html><head><meta charset="utf-8"><title>{title} - {hostname}</title></head>
+<body><form method="post" action="/login">
+<h1>{title}</h1>
+{error}
+<label>Username <input name="{user}" autocomplete="username"></label>
+<label>Password <input name="{pass}" type="password" autocomplete="current-password"></label>
+<button type="submit">Sign in</button>
+</form></body></html>
+"#
+        )
+    }
+}
+
+/// Where and as what a decoy listens.
+#[derive(Debug, Clone)]
+pub struct DecoyConfig {
+    /// Name the certificate is issued for, a DNS name or an IP address
+    pub hostname: String,
+    pub kind: DecoyKind,
+    /// Address to listen on; port 0 picks a free one
+    pub bind: SocketAddr,
+    /// Connections served at once; more are dropped unanswered
+    pub max_connections: usize,
+    /// Days the certificate is valid
+    pub validity_days: u32,
+}
+
+impl DecoyConfig {
+    /// A router admin for `hostname` on port 443 of every interface, IPv4
+    /// and IPv6.
+    pub fn new(hostname: impl Into<String>) -> Self {
+        Self {
+            hostname: hostname.into(),
+            kind: DecoyKind::default(),
+            bind: SocketAddr::from((Ipv6Addr::UNSPECIFIED, 443)),
+            max_connections: DEFAULT_MAX_CONNECTIONS,
+            validity_days: DEFAULT_VALIDITY_DAYS,
+        }
+    }
+
+    /// Pose as `kind`.
+    pub fn kind(mut self, kind: DecoyKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    /// Listen on `addr`.
+    pub fn bind(mut self, addr: SocketAddr) -> Self {
+        self.bind = addr;
+        self
+    }
+
+    /// Serve at most `max` connections at once.
+    pub fn max_connections(mut self, max: usize) -> Self {
+        self.max_connections = max;
+        self
+    }
+
+    /// Issue the certificate for `days`.
+    pub fn validity(mut self, days: u32) -> Self {
+        self.validity_days = days;
+        self
+    }
+}
+
+/// A running decoy. Dropping it stops the listener.
+pub struct DecoyService {
+    id: Uuid,
+    local_addr: SocketAddr,
+    certificate: LeafCertificate,
+    events: mpsc::Receiver<TripwireEvent>,
+    shutdown: watch::Sender<bool>,
+    task: Option<JoinHandle<()>>,
+}
+
+/// Start a decoy as `config` describes, presenting a certificate `issuer`
+/// signs for its hostname.
+///
+/// The decoy runs on the current Tokio runtime until
+/// [`DecoyService::shutdown`] or until it is dropped.
+pub async fn decoy_service(
+    issuer: &IntermediateCa,
+    config: DecoyConfig,
+) -> Result<DecoyService, HoneypotError> {
+    if config.hostname.is_empty() {
+        return Err(HoneypotError::InvalidConfig(
+            "a decoy needs a hostname".to_string(),
+        ));
+    }
+    if config.max_connections == 0 {
+        return Err(HoneypotError::InvalidConfig(
+            "a decoy must accept at least one connection".to_string(),
+        ));
+    }
+
+    let params = LeafParams::new(&config.hostname).validity(config.validity_days);
+    let params = match config.hostname.parse::<IpAddr>() {
+        Ok(ip) => params.ip(ip),
+        Err(_) => params.dns(&config.hostname),
+    };
+    let certificate = LeafCertificate::issue(issuer, &params)?;
+    let tls = tls_config(&certificate)?;
+
+    let listener = listen(config.bind)?;
+    let local_addr = listener.local_addr()?;
+    let (events_tx, events) = mpsc::channel(EVENT_BUFFER);
+    let (shutdown, shutdown_rx) = watch::channel(false);
+
+    let decoy = Arc::new(Decoy {
+        id: Uuid::new_v4(),
+        kind: config.kind,
+        hostname: config.hostname,
+        tls,
+        events: events_tx,
+    });
+    let id = decoy.id;
+    let task = tokio::spawn(serve(
+        listener,
+        decoy,
+        Arc::new(Semaphore::new(config.max_connections)),
+        shutdown_rx,
+    ));
+
+    Ok(DecoyService {
+        id,
+        local_addr,
+        certificate,
+        events,
+        shutdown,
+        task: Some(task),
+    })
+}
+
+/// Listen on `addr`. The IPv6 wildcard takes IPv4 connections as well,
+/// and where IPv6 is switched off the IPv4 wildcard stands in for it.
+fn listen(addr: SocketAddr) -> io::Result<TcpListener> {
+    let socket = match Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP)) {
+        Err(_) if addr.ip() == Ipv6Addr::UNSPECIFIED => {
+            return listen(SocketAddr::from((Ipv4Addr::UNSPECIFIED, addr.port())));
+        }
+        socket => socket?,
+    };
+    if addr.is_ipv6() {
+        socket.set_only_v6(false)?;
+    }
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(LISTEN_BACKLOG)?;
+    TcpListener::from_std(socket.into())
+}
+
+impl DecoyService {
+    /// Identifies this decoy's events, as their `honeypot_id`
+    pub const fn id(&self) -> Uuid {
+        self.id
+    }
+
+    /// Address the decoy is listening on
+    pub const fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// The certificate the decoy presents
+    pub const fn certificate(&self) -> &LeafCertificate {
+        &self.certificate
+    }
+
+    /// The next connection's event, once it is over.
+    pub async fn next_event(&mut self) -> Option<TripwireEvent> {
+        self.events.recv().await
+    }
+
+    /// Stop accepting, let connections in progress finish, and return the
+    /// events not taken yet.
+    pub async fn shutdown(mut self) -> Vec<TripwireEvent> {
+        let _ = self.shutdown.send(true);
+        if let Some(task) = self.task.take() {
+            let _ = task.await;
+        }
+
+        let mut events = Vec::new();
+        while let Ok(event) = self.events.try_recv() {
+            events.push(event);
+        }
+        events
+    }
+}
+
+impl Drop for DecoyService {
+    fn drop(&mut self) {
+        let _ = self.shutdown.send(true);
+    }
+}
+
+/// What every connection to one decoy shares.
+struct Decoy {
+    id: Uuid,
+    kind: DecoyKind,
+    hostname: String,
+    tls: Arc<ServerConfig>,
+    events: mpsc::Sender<TripwireEvent>,
+}
+
+/// What a connection showed of itself.
+#[derive(Debug, Default, Serialize)]
+struct Visit {
+    sni: Option<String>,
+    /// Ciphers, groups, signature schemes and ALPN protocols offered, in
+    /// the client's order
+    tls_hello: Option<String>,
+    /// SHA-256 of `tls_hello`, shortened: alike for clients built alike
+    tls_fingerprint: Option<String>,
+    /// Method and path, e.g. `POST /login`
+    request: Option<String>,
+    user_agent: Option<String>,
+    credentials: Option<Credentials>,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct Credentials {
+    username: String,
+    password: String,
+}
+
+/// Accept connections until told to stop, then wait for those in progress.
+async fn serve(
+    listener: TcpListener,
+    decoy: Arc<Decoy>,
+    limit: Arc<Semaphore>,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let mut connections = JoinSet::new();
+    loop {
+        tokio::select! {
+            _ = shutdown.changed() => break,
+            accepted = listener.accept() => {
+                let Ok((stream, peer)) = accepted else { continue };
+                // Over the cap, connections are dropped unanswered
+                let Ok(permit) = Arc::clone(&limit).try_acquire_owned() else { continue };
+                let decoy = Arc::clone(&decoy);
+                connections.spawn(async move {
+                    let mut visit = Visit::default();
+                    let _ = tokio::time::timeout(
+                        CONNECTION_TIMEOUT,
+                        serve_one(stream, &decoy, &mut visit),
+                    )
+                    .await;
+                    drop(permit);
+                    let _ = decoy.events.send(decoy.event(peer, visit)).await;
+                });
+            }
+            Some(_) = connections.join_next(), if !connections.is_empty() => {}
+        }
+    }
+    while connections.join_next().await.is_some() {}
+}
+
+/// Shake hands, read a request and answer with the login page, noting in
+/// `visit` what the client gave away on the way. Fails as soon as the
+/// client stops playing along, keeping what was noted.
+async fn serve_one(stream: TcpStream, decoy: &Decoy, visit: &mut Visit) -> io::Result<()> {
+    let start = LazyConfigAcceptor::new(Acceptor::default(), stream).await?;
+    let hello = start.client_hello();
+    visit.sni = hello.server_name().map(str::to_string);
+    let summary = hello_summary(&hello);
+    let digest = Sha256::digest(summary.as_bytes());
+    visit.tls_fingerprint = Some(hex(&digest[..16]));
+    visit.tls_hello = Some(summary);
+
+    let mut stream = start.into_stream(Arc::clone(&decoy.tls)).await?;
+    let request = read_request(&mut stream).await?;
+    visit.request = Some(format!("{} {}", request.method, request.path));
+    visit.user_agent = request.header("user-agent").map(str::to_string);
+
+    let failed = request.method == "POST";
+    if failed {
+        visit.credentials = credentials(&request.body);
+    }
+    let page = decoy.kind.page(&decoy.hostname, failed);
+    let response = format!(
+        "HTTP/1.1 200 OK\r\n\
+         Server: {}\r\n\
+         Content-Type: text/html; charset=utf-8\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\r\n{page}",
+        decoy.kind.server(),
+        page.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+impl Decoy {
+    fn event(&self, peer: SocketAddr, visit: Visit) -> TripwireEvent {
+        let mut context = serde_json::to_value(visit).unwrap_or_default();
+        context["kind"] = self.kind.name().into();
+        context["hostname"] = self.hostname.as_str().into();
+        TripwireEvent {
+            honeypot_id: self.id,
+            honeypot_type: "decoy".to_string(),
+            triggered_at: Utc::now(),
+            // IPv4 visitors of a dual-stack listener come as ::ffff:a.b.c.d
+            source_ip: Some(peer.ip().to_canonical().to_string()),
+            context,
+        }
+    }
+}
+
+/// `ciphers,groups,signature schemes,alpn`: numbers joined by `-` as in
+/// JA3, protocols by `-` as named. Groups and ALPN are empty if not sent.
+fn hello_summary(hello: &ClientHello<'_>) -> String {
+    fn join<T: Copy>(values: &[T], code: impl Fn(T) -> u16) -> String {
+        values
+            .iter()
+            .map(|&v| code(v).to_string())
+            .collect::<Vec<_>>()
+            .join("-")
+    }
+
+    let alpn = hello
+        .alpn()
+        .map(|protocols| {
+            protocols
+                .map(String::from_utf8_lossy)
+                .collect::<Vec<_>>()
+                .join("-")
+        })
+        .unwrap_or_default();
+    format!(
+        "{},{},{},{alpn}",
+        join(hello.cipher_suites(), u16::from),
+        join(hello.named_groups().unwrap_or_default(), u16::from),
+        join(hello.signature_schemes(), u16::from),
+    )
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// One HTTP request, as much of it as [`MAX_REQUEST`] allows.
+struct Request {
+    method: String,
+    path: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+async fn read_request(stream: &mut (impl AsyncRead + Unpin)) -> io::Result<Request> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let head_end = loop {
+        if let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break end;
+        }
+        if buf.len() >= MAX_REQUEST {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "request too large",
+            ));
+        }
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    };
+
+    let head = String::from_utf8_lossy(&buf[..head_end]).into_owned();
+    let mut lines = head.lines();
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    let method = request_line.next().unwrap_or_default().to_string();
+    let path = request_line.next().unwrap_or_default().to_string();
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect();
+
+    let mut request = Request {
+        method,
+        path,
+        headers,
+        body: buf[head_end + 4..].to_vec(),
+    };
+    let length = request
+        .header("content-length")
+        .and_then(|n| n.parse::<usize>().ok())
+        .unwrap_or(0)
+        .min(MAX_REQUEST - head_end);
+    while request.body.len() < length {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            break;
+        }
+        request.body.extend_from_slice(&chunk[..n]);
+    }
+    request.body.truncate(length);
+    Ok(request)
+}
+
+/// The username and password in a submitted form, by any of the names
+/// login forms give them.
+fn credentials(body: &[u8]) -> Option<Credentials> {
+    let mut found = Credentials::default();
+    for (key, value) in url::form_urlencoded::parse(body) {
+        let key = key.to_ascii_lowercase();
+        if key.contains("pass") || key == "pwd" {
+            found.password = value.into_owned();
+        } else if key.contains("user") || key == "account" || key == "login" || key == "email" {
+            found.username = value.into_owned();
+        }
+    }
+    (!found.username.is_empty() || !found.password.is_empty()).then_some(found)
+}
+
+/// Server settings presenting `certificate` and its chain.
+fn tls_config(certificate: &LeafCertificate) -> Result<Arc<ServerConfig>, HoneypotError> {
+    let tls_error = |e: &dyn std::fmt::Display| HoneypotError::Tls(e.to_string());
+    let chain = CertificateDer::pem_slice_iter(certificate.chain_pem.as_bytes())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| tls_error(&e))?;
+    let key =
+        PrivateKeyDer::from_pem_slice(certificate.key_pem.as_bytes()).map_err(|e| tls_error(&e))?;
+
+    let mut config =
+        ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .map_err(|e| tls_error(&e))?
+            .with_no_client_auth()
+            .with_single_cert(chain, key)
+            .map_err(|e| tls_error(&e))?;
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(Arc::new(config))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_credentials() {
+        let found = credentials(b"DomainUserName=CORP%5Cadmin&UserPass=hunter2&x=1").unwrap();
+        assert_eq!(found.username, "CORP\\admin");
+        assert_eq!(found.password, "hunter2");
+
+        let found = credentials(b"account=root&passwd=").unwrap();
+        assert_eq!(found.username, "root");
+        assert!(found.password.is_empty());
+
+        assert!(credentials(b"q=search").is_none());
+    }
+
+    #[test]
+    fn test_pages() {
+        for kind in [DecoyKind::RouterAdmin, DecoyKind::Nas, DecoyKind::RdpWeb] {
+            let (user, pass) = kind.fields();
+            let page = kind.page("decoy.example", false);
+            assert!(page.contains(&format!(r#"name="{user}""#)));
+            assert!(page.contains(&format!(r#"name="{pass}""#)));
+            assert!(page.contains("decoy.example"));
+            assert!(!page.contains("Invalid"));
+            assert!(kind.page("decoy.example", true).contains("Invalid"));
+        }
+    }
+}
@@ -0,0 +1,547 @@
+//! Deploying a kit into a sandbox directory.
+//!
+//! Every file written is recorded in a hidden manifest, so the honeypots in
+//! a file can be looked up when it is touched, and the kit removed again.
+//! A kit's successor deploys over it, and the manifest remembers the old
+//! kit for a while so its bait can still be traced.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{HoneypotError, HoneypotKit, RotationPlan};
+
+/// Name of the manifest in the deploy directory.
+const MANIFEST: &str = ".kit-manifest.json";
+
+/// How a kit is laid out on disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeployOptions {
+    /// Youngest a file may look, in days
+    pub min_age_days: u32,
+    /// Oldest a file may look, in days
+    pub max_age_days: u32,
+    /// How long the manifest remembers a kit after rotating it out, in days
+    pub overlap_days: u32,
+}
+
+impl Default for DeployOptions {
+    fn default() -> Self {
+        Self {
+            min_age_days: 7,
+            max_age_days: 365,
+            overlap_days: 30,
+        }
+    }
+}
+
+/// What a deploy wrote, saved as `.kit-manifest.json` next to it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KitManifest {
+    /// Kit the files came from
+    pub kit_id: Uuid,
+    /// User the kit belongs to
+    pub user_id: String,
+    /// When the kit was deployed
+    pub deployed_at: DateTime<Utc>,
+    /// Every file written, relative to the deploy directory
+    pub entries: Vec<ManifestEntry>,
+    /// Kits this one replaced, newest last
+    #[serde(default)]
+    pub retired: Vec<RetiredKit>,
+    #[serde(skip)]
+    root: PathBuf,
+}
+
+/// A kit rotated out of a deploy directory, kept in the manifest through the
+/// overlap so tripwires from its bait still lead back to it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetiredKit {
+    /// Kit the files came from
+    pub kit_id: Uuid,
+    /// When its successor replaced it
+    pub retired_at: DateTime<Utc>,
+    /// The files it had deployed
+    pub entries: Vec<ManifestEntry>,
+}
+
+/// One deployed file and the honeypots in it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// Path relative to the deploy directory
+    pub path: String,
+    /// Honeypots whose data the file holds
+    pub honeypot_ids: Vec<Uuid>,
+    /// Modification time the file was given
+    pub modified: DateTime<Utc>,
+}
+
+impl HoneypotKit {
+    /// Write the kit under `dir`: the filesystem artifacts, a browser
+    /// password export, the trap documents, the wallets' files and the inbox
+    /// under `Mail/`, each backdated by a random age within `options`. Files
+    /// already there are left alone and fail the deploy, so undeploy a kit
+    /// before deploying over it.
+    ///
+    /// The exception is the kit this one [`supersedes`](HoneypotKit::supersedes):
+    /// if that is what is deployed in `dir`, its files are rotated out as
+    /// [`RotationPlan`] says and it moves to the manifest's `retired` list,
+    /// where it stays for `overlap_days`.
+    pub fn deploy_to(
+        &self,
+        dir: &Path,
+        options: DeployOptions,
+    ) -> Result<KitManifest, HoneypotError> {
+        if options.min_age_days > options.max_age_days {
+            return Err(HoneypotError::InvalidConfig(format!(
+                "min_age_days ({}) is more than max_age_days ({})",
+                options.min_age_days, options.max_age_days
+            )));
+        }
+
+        // The kit this one replaces, if it is the one deployed here
+        let previous = match self.supersedes {
+            Some(id) if dir.join(MANIFEST).exists() => {
+                Some(Self::load_manifest(dir)?).filter(|manifest| manifest.kit_id == id)
+            }
+            _ => None,
+        };
+        let files = self.deploy_files();
+        let plan = previous.as_ref().map(|manifest| {
+            let old: Vec<String> = manifest.entries.iter().map(|e| e.path.clone()).collect();
+            let new: Vec<String> = files.iter().map(|(path, _, _)| path.clone()).collect();
+            RotationPlan::from_paths(manifest.kit_id, self.id, &old, &new)
+        });
+
+        // Check every path before anything is written or removed
+        let replaced = plan.as_ref().map_or(&[][..], |plan| &plan.replace[..]);
+        for (path, _, _) in &files {
+            if safe_path(dir, path)?.exists() && !replaced.contains(path) {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::AlreadyExists,
+                    format!("{} already exists", path),
+                )
+                .into());
+            }
+        }
+        if let Some(plan) = &plan {
+            for path in &plan.remove {
+                safe_path(dir, path)?;
+            }
+            remove_files(dir, plan.remove.iter().chain(&plan.replace))?;
+        }
+
+        let mut rng = rand::thread_rng();
+        let now = SystemTime::now();
+        let mut entries = Vec::new();
+        for (path, content, honeypot_ids) in files {
+            let target = safe_path(dir, &path)?;
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            let days = u64::from(options.min_age_days)..=u64::from(options.max_age_days);
+            let age = rng.gen_range(days.start() * 86_400..=days.end() * 86_400);
+            let modified = now - Duration::from_secs(age);
+            let mut file = open_options(&path).open(&target)?;
+            file.write_all(&content)?;
+            file.set_modified(modified)?;
+
+            entries.push(ManifestEntry {
+                path,
+                honeypot_ids,
+                modified: modified.into(),
+            });
+        }
+
+        let deployed_at: DateTime<Utc> = now.into();
+        let mut retired = Vec::new();
+        if let Some(previous) = previous {
+            retired = previous.retired;
+            retired.push(RetiredKit {
+                kit_id: previous.kit_id,
+                retired_at: deployed_at,
+                entries: previous.entries,
+            });
+        }
+        let overlap = chrono::Duration::days(i64::from(options.overlap_days));
+        retired.retain(|kit| deployed_at - kit.retired_at < overlap);
+
+        let manifest = KitManifest {
+            kit_id: self.id,
+            user_id: self.user_id.clone(),
+            deployed_at,
+            entries,
+            retired,
+            root: dir.to_path_buf(),
+        };
+        fs::write(dir.join(MANIFEST), serde_json::to_string_pretty(&manifest)?)?;
+        Ok(manifest)
+    }
+
+    /// Read the manifest a deploy left in `dir`.
+    pub fn load_manifest(dir: &Path) -> Result<KitManifest, HoneypotError> {
+        let json = fs::read_to_string(dir.join(MANIFEST))?;
+        let mut manifest: KitManifest = serde_json::from_str(&json)?;
+        manifest.root = dir.to_path_buf();
+        Ok(manifest)
+    }
+
+    /// Every file a deploy writes, with the honeypots in it.
+    pub(crate) fn deploy_files(&self) -> Vec<(String, Vec<u8>, Vec<Uuid>)> {
+        let mut files: Vec<_> = self
+            .tagged_artifacts()
+            .into_iter()
+            .map(|(path, content, ids)| (path, content.into_bytes(), ids))
+            .collect();
+
+        // Chrome's password export
+        let mut csv = "name,url,username,password\n".to_string();
+        for credential in &self.credentials {
+            csv.push_str(&format!(
+                "{0},https://{0}/,{1},{2}\n",
+                credential.site, credential.username, credential.password
+            ));
+        }
+        files.push((
+            "Downloads/Chrome Passwords.csv".to_string(),
+            csv.into_bytes(),
+            self.credentials.iter().map(|c| c.id).collect(),
+        ));
+
+        for document in &self.documents {
+            files.push((
+                document.full_path.clone(),
+                document.generate_content(),
+                vec![document.id],
+            ));
+        }
+
+        for wallet in &self.wallets {
+            for (path, content) in wallet.artifact_files_on(self.created_at.date_naive()) {
+                files.push((path, content, vec![wallet.id]));
+            }
+        }
+
+        // The inbox, as a mail client's export and as single messages
+        if !self.mail.is_empty() {
+            let mut ids: Vec<Uuid> = self
+                .mail
+                .iter()
+                .flat_map(|m| m.honeypot_ids.iter().copied())
+                .collect();
+            ids.sort();
+            ids.dedup();
+            files.push((
+                "Mail/Inbox.mbox".to_string(),
+                self.to_mbox().into_bytes(),
+                ids,
+            ));
+        }
+        for message in &self.mail {
+            files.push((
+                format!("Mail/Inbox/{}", message.filename()),
+                message.to_eml().into_bytes(),
+                message.honeypot_ids.clone(),
+            ));
+        }
+        files
+    }
+}
+
+impl KitManifest {
+    /// The directory the kit was deployed to.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The honeypots in a deployed file, by its path relative to the root.
+    pub fn honeypots_in(&self, path: &str) -> Option<&[Uuid]> {
+        self.entries
+            .iter()
+            .find(|entry| entry.path == path)
+            .map(|entry| entry.honeypot_ids.as_slice())
+    }
+
+    /// The kit a honeypot was deployed from: this one, or one it replaced
+    /// that is still in the overlap.
+    pub fn kit_for(&self, honeypot_id: Uuid) -> Option<Uuid> {
+        let holds = |entries: &[ManifestEntry]| {
+            entries
+                .iter()
+                .any(|entry| entry.honeypot_ids.contains(&honeypot_id))
+        };
+        if holds(&self.entries) {
+            return Some(self.kit_id);
+        }
+        self.retired
+            .iter()
+            .rev()
+            .find(|kit| holds(&kit.entries))
+            .map(|kit| kit.kit_id)
+    }
+
+    /// Remove the deployed files, the directories left empty by that, and
+    /// the manifest. Anything else in the directory stays.
+    pub fn undeploy(self) -> Result<(), HoneypotError> {
+        remove_files(&self.root, self.entries.iter().map(|entry| &entry.path))?;
+        match fs::remove_file(self.root.join(MANIFEST)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Remove `paths` under `root` and the directories left empty by that.
+fn remove_files<'a>(
+    root: &Path,
+    paths: impl IntoIterator<Item = &'a String>,
+) -> Result<(), HoneypotError> {
+    let mut dirs = Vec::new();
+    for path in paths {
+        let target = safe_path(root, path)?;
+        match fs::remove_file(&target) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+        dirs.extend(
+            target
+                .ancestors()
+                .skip(1)
+                .take_while(|dir| *dir != root)
+                .map(Path::to_path_buf),
+        );
+    }
+
+    // Deepest first, and only the ones now empty
+    dirs.sort();
+    dirs.dedup();
+    dirs.sort_by_key(|dir| std::cmp::Reverse(dir.components().count()));
+    for dir in dirs {
+        let _ = fs::remove_dir(dir);
+    }
+    Ok(())
+}
+
+/// Options for a new file. Keys and credentials are readable by their owner
+/// only, as `ssh` insists and as a real home directory would have them.
+fn open_options(path: &str) -> fs::OpenOptions {
+    let mut options = fs::File::options();
+    options.write(true).create_new(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        let secret =
+            path == ".aws/credentials" || (path.starts_with(".ssh/") && !path.ends_with(".pub"));
+        if secret {
+            options.mode(0o600);
+        }
+    }
+    options
+}
+
+/// `path` under `root`, if it is a plain relative path that stays there.
+fn safe_path(root: &Path, path: &str) -> Result<PathBuf, HoneypotError> {
+    let relative = Path::new(path);
+    let plain = relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)));
+    if !plain || path.is_empty() || path == MANIFEST {
+        return Err(HoneypotError::UnsafePath(path.to_string()));
+    }
+    Ok(root.join(relative))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DocumentType, PaperTrail, TrapDocument};
+
+    #[test]
+    fn test_deploy_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let kit = HoneypotKit::generate_with_seed("test-user", 7);
+        let manifest = kit.deploy_to(dir.path(), DeployOptions::default()).unwrap();
+
+        assert!(dir.path().join(MANIFEST).exists());
+        assert!(dir.path().join("Documents/passwords.txt").exists());
+        assert!(dir.path().join("Mail/Inbox.mbox").exists());
+        for message in &kit.mail {
+            let path = format!("Mail/Inbox/{}", message.filename());
+            assert!(dir.path().join(&path).exists());
+            assert_eq!(
+                manifest.honeypots_in(&path),
+                Some(&message.honeypot_ids[..])
+            );
+        }
+        for document in &kit.documents {
+            assert!(dir.path().join(&document.full_path).exists());
+            assert_eq!(
+                manifest.honeypots_in(&document.full_path),
+                Some(&[document.id][..])
+            );
+        }
+        for wallet in &kit.wallets {
+            let (path, _) = &wallet.artifact_files_on(kit.created_at.date_naive())[0];
+            assert!(dir.path().join(path).exists());
+            assert_eq!(manifest.honeypots_in(path), Some(&[wallet.id][..]));
+        }
+        let wallets: Vec<Uuid> = kit.wallets.iter().map(|w| w.id).collect();
+        assert_eq!(
+            manifest.honeypots_in("Documents/crypto_backup.txt"),
+            Some(wallets.as_slice())
+        );
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = |path: &str| {
+                let metadata = std::fs::metadata(dir.path().join(path)).unwrap();
+                metadata.permissions().mode() & 0o777
+            };
+            assert_eq!(mode(".ssh/id_ed25519"), 0o600);
+            assert_eq!(mode(".aws/credentials"), 0o600);
+            assert_ne!(mode(".ssh/id_ed25519.pub"), 0o600);
+        }
+
+        let loaded = HoneypotKit::load_manifest(dir.path()).unwrap();
+        assert_eq!(loaded, manifest);
+
+        // Deploying again over the same files is refused
+        assert!(kit.deploy_to(dir.path(), DeployOptions::default()).is_err());
+
+        std::fs::write(dir.path().join("Documents/mine.txt"), "keep").unwrap();
+        loaded.undeploy().unwrap();
+        let left: Vec<_> = walk(dir.path());
+        assert_eq!(left, [dir.path().join("Documents/mine.txt")]);
+    }
+
+    #[test]
+    fn test_mtimes_backdated() {
+        let dir = tempfile::tempdir().unwrap();
+        let kit = HoneypotKit::generate_default_kit("test-user", None);
+        let options = DeployOptions {
+            min_age_days: 30,
+            max_age_days: 60,
+            ..DeployOptions::default()
+        };
+        let manifest = kit.deploy_to(dir.path(), options).unwrap();
+
+        let now = SystemTime::now();
+        for entry in &manifest.entries {
+            let modified = std::fs::metadata(dir.path().join(&entry.path))
+                .unwrap()
+                .modified()
+                .unwrap();
+            let days = now.duration_since(modified).unwrap().as_secs() / 86_400;
+            assert!((30..=60).contains(&days), "{}: {} days", entry.path, days);
+            assert_eq!(DateTime::<Utc>::from(modified), entry.modified);
+        }
+
+        let backwards = DeployOptions {
+            min_age_days: 60,
+            max_age_days: 30,
+            ..DeployOptions::default()
+        };
+        assert!(kit.deploy_to(dir.path(), backwards).is_err());
+    }
+
+    #[test]
+    fn test_unsafe_paths() {
+        for path in [
+            "../escape.txt",
+            "/etc/passwd",
+            "Documents/../../x",
+            "",
+            MANIFEST,
+        ] {
+            assert!(safe_path(Path::new("/sandbox"), path).is_err(), "{path}");
+        }
+        assert!(safe_path(Path::new("/sandbox"), "Documents/a b.pdf").is_ok());
+
+        // A kit whose document escapes writes nothing at all
+        let dir = tempfile::tempdir().unwrap();
+        let mut kit = HoneypotKit::generate_default_kit("test-user", None);
+        let trail = PaperTrail::new(kit.persona.as_ref().unwrap(), &[], &mut rand::thread_rng());
+        let mut document = TrapDocument::generate(DocumentType::Will, &trail);
+        document.full_path = "../Last_Will.pdf".to_string();
+        kit.documents.push(document);
+        assert!(matches!(
+            kit.deploy_to(dir.path(), DeployOptions::default()),
+            Err(HoneypotError::UnsafePath(_))
+        ));
+        assert!(walk(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn test_rotate_in_place() {
+        let dir = tempfile::tempdir().unwrap();
+        let old = HoneypotKit::generate_with_seed("test-user", 8);
+        old.deploy_to(dir.path(), DeployOptions::default()).unwrap();
+        std::fs::write(dir.path().join("Documents/mine.txt"), "keep").unwrap();
+
+        // Only a successor may deploy over a kit
+        let stranger = HoneypotKit::generate_with_seed("test-user", 9);
+        assert!(stranger
+            .deploy_to(dir.path(), DeployOptions::default())
+            .is_err());
+
+        let new = old.rotate();
+        let plan = RotationPlan::diff(&old, &new);
+        let manifest = new.deploy_to(dir.path(), DeployOptions::default()).unwrap();
+        for path in &plan.remove {
+            assert!(!dir.path().join(path).exists(), "{path}");
+        }
+        for path in plan.add.iter().chain(&plan.replace) {
+            assert!(dir.path().join(path).exists(), "{path}");
+        }
+        let passwords = std::fs::read_to_string(dir.path().join("Documents/passwords.txt"));
+        assert!(passwords.unwrap().contains(&new.credentials[0].password));
+        assert!(dir.path().join("Documents/mine.txt").exists());
+
+        // Both generations can be traced through the overlap
+        assert_eq!(manifest.kit_id, new.id);
+        assert_eq!(manifest.retired.len(), 1);
+        assert_eq!(manifest.retired[0].kit_id, old.id);
+        assert_eq!(manifest.kit_for(new.cards[0].id), Some(new.id));
+        assert_eq!(manifest.kit_for(old.cards[0].id), Some(old.id));
+        assert_eq!(manifest.kit_for(old.documents[0].id), Some(old.id));
+        assert_eq!(manifest.kit_for(Uuid::new_v4()), None);
+        let loaded = HoneypotKit::load_manifest(dir.path()).unwrap();
+        assert_eq!(loaded, manifest);
+
+        // A rotation with no overlap forgets the kit it replaced
+        let newer = new.rotate();
+        let options = DeployOptions {
+            overlap_days: 0,
+            ..DeployOptions::default()
+        };
+        let manifest = newer.deploy_to(dir.path(), options).unwrap();
+        assert!(manifest.retired.is_empty());
+        assert_eq!(manifest.kit_for(old.cards[0].id), None);
+
+        manifest.undeploy().unwrap();
+        assert_eq!(walk(dir.path()), [dir.path().join("Documents/mine.txt")]);
+    }
+
+    /// Every file under `dir`.
+    fn walk(dir: &Path) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                files.extend(walk(&path));
+            } else {
+                files.push(path);
+            }
+        }
+        files
+    }
+}
@@ -1,12 +1,17 @@
 //! Trap document generation for honeypots.
 //!
 //! These documents look like sensitive files but contain tracking mechanisms.
+//! A kit's documents share a [`PaperTrail`]: one owner, one SSN, one bank
+//! account and employer, and the kit's cards on the statements.
 
-use chrono::{Datelike, Utc};
-use rand::Rng;
+use chrono::{Datelike, Duration, NaiveDate, Utc};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::{HoneypotCard, Persona};
+
 /// Types of trap documents.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum DocumentType {
@@ -21,20 +26,35 @@ pub enum DocumentType {
 }
 
 impl DocumentType {
-    /// Suggested filename for this document type.
-    fn filename(&self) -> String {
-        let mut rng = rand::thread_rng();
-        let year = Utc::now().year() - rng.gen_range(0..3);
+    /// The format this kind of document is usually kept in: spreadsheets
+    /// for figures, Word for letters and forms, PDF for scans.
+    pub fn default_format(&self) -> DocumentFormat {
+        match self {
+            DocumentType::BankStatement | DocumentType::PayStub => DocumentFormat::Xlsx,
+            DocumentType::TaxReturn
+            | DocumentType::MedicalRecord
+            | DocumentType::InsurancePolicy
+            | DocumentType::Will => DocumentFormat::Docx,
+            DocumentType::Passport | DocumentType::DriversLicense => DocumentFormat::Pdf,
+        }
+    }
+
+    /// Suggested filename for this document type, without an extension,
+    /// from up to two years before `today`.
+    fn filename(&self, today: NaiveDate, rng: &mut impl Rng) -> String {
+        let year = today.year() - rng.gen_range(0..3);
 
         match self {
-            DocumentType::TaxReturn => format!("Tax_Return_{}.pdf", year),
-            DocumentType::BankStatement => format!("Bank_Statement_{:02}_{}.pdf", rng.gen_range(1..=12), year),
-            DocumentType::PayStub => format!("PayStub_{:02}_{}.pdf", rng.gen_range(1..=12), year),
-            DocumentType::MedicalRecord => format!("Medical_Records_{}.pdf", year),
-            DocumentType::InsurancePolicy => format!("Insurance_Policy_{}.pdf", year),
-            DocumentType::Will => "Last_Will_and_Testament.pdf".to_string(),
-            DocumentType::Passport => "Passport_Scan.pdf".to_string(),
-            DocumentType::DriversLicense => "Drivers_License_Copy.pdf".to_string(),
+            DocumentType::TaxReturn => format!("Tax_Return_{}", year),
+            DocumentType::BankStatement => {
+                format!("Bank_Statement_{:02}_{}", rng.gen_range(1..=12), year)
+            }
+            DocumentType::PayStub => format!("PayStub_{:02}_{}", rng.gen_range(1..=12), year),
+            DocumentType::MedicalRecord => format!("Medical_Records_{}", year),
+            DocumentType::InsurancePolicy => format!("Insurance_Policy_{}", year),
+            DocumentType::Will => "Last_Will_and_Testament".to_string(),
+            DocumentType::Passport => "Passport_Scan".to_string(),
+            DocumentType::DriversLicense => "Drivers_License_Copy".to_string(),
         }
     }
 
@@ -68,6 +88,66 @@ impl std::fmt::Display for DocumentType {
     }
 }
 
+/// File format of a trap document.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DocumentFormat {
+    #[default]
+    Pdf,
+    /// Word, with the tracking URL as its attached template
+    Docx,
+    /// Excel, with a picture linked from the tracking URL
+    Xlsx,
+}
+
+impl DocumentFormat {
+    /// File extension, without the dot.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            DocumentFormat::Pdf => "pdf",
+            DocumentFormat::Docx => "docx",
+            DocumentFormat::Xlsx => "xlsx",
+        }
+    }
+}
+
+/// What a persona's documents have in common, so they agree with each
+/// other and with the rest of the kit.
+#[derive(Debug, Clone)]
+pub struct PaperTrail<'a> {
+    /// Whose documents they are
+    pub persona: &'a Persona,
+    /// Cards the bank statements show payments to
+    pub cards: &'a [HoneypotCard],
+    /// SSN on every document that asks for one
+    pub ssn: String,
+    /// Bank account, as `Routing: ... Account: ...`
+    pub account: String,
+    /// Employer on the pay stubs and the tax return
+    pub employer: String,
+    /// The day the documents are current to
+    pub date: NaiveDate,
+}
+
+impl<'a> PaperTrail<'a> {
+    /// A trail for `persona` and their cards, with a new SSN and bank
+    /// account, current to today. The employer is the persona's, or one
+    /// from their locale.
+    pub fn new(persona: &'a Persona, cards: &'a [HoneypotCard], rng: &mut impl Rng) -> Self {
+        let employer = match &persona.employer {
+            Some(employer) => employer.clone(),
+            None => persona.locale.employer(rng).to_string(),
+        };
+        Self {
+            persona,
+            cards,
+            ssn: generate_fake_ssn(rng),
+            account: generate_fake_account(rng),
+            employer,
+            date: Utc::now().date_naive(),
+        }
+    }
+}
+
 /// A trap document that reports back when opened.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TrapDocument {
@@ -75,6 +155,9 @@ pub struct TrapDocument {
     pub id: Uuid,
     /// Document type
     pub document_type: DocumentType,
+    /// File format
+    #[serde(default)]
+    pub format: DocumentFormat,
     /// Filename
     pub filename: String,
     /// Full path suggestion
@@ -85,32 +168,244 @@ pub struct TrapDocument {
     pub fake_ssn: Option<String>,
     /// Fake account number (for tracking if used)
     pub fake_account: Option<String>,
+    /// Whose document it claims to be
+    #[serde(default)]
+    pub owner: Option<String>,
+    /// Employer named on pay stubs and tax returns
+    #[serde(default)]
+    pub employer: Option<String>,
+    /// Cards the document mentions, as network and last four digits
+    #[serde(default)]
+    pub cards: Vec<String>,
+    /// The day the document is current to; statements end on it
+    #[serde(default)]
+    pub dated: Option<NaiveDate>,
 }
 
 impl TrapDocument {
-    /// Generate a new trap document.
-    pub fn generate(document_type: DocumentType) -> Self {
-        let id = Uuid::new_v4();
-        let filename = document_type.filename();
+    /// Generate a new trap document from a paper trail.
+    pub fn generate(document_type: DocumentType, trail: &PaperTrail<'_>) -> Self {
+        Self::generate_with_rng(document_type, trail, &mut rand::thread_rng())
+    }
+
+    /// Generate a document from `rng`, so a seeded RNG gives the same one,
+    /// tracking URL included.
+    pub fn generate_with_rng(
+        document_type: DocumentType,
+        trail: &PaperTrail<'_>,
+        rng: &mut impl Rng,
+    ) -> Self {
+        let id = crate::random_id(rng);
+        let format = document_type.default_format();
+        let filename = format!(
+            "{}.{}",
+            document_type.filename(trail.date, rng),
+            format.extension()
+        );
         let folder = document_type.folder();
 
         // Tracking URL that will phone home when document is opened
         // (PDF can contain JavaScript or external resource requests)
-        let tracking_url = format!("https://i1.is/t/{}", id);
+        let tracking_url = format!("{}/{}", crate::DEFAULT_TRACKING_BASE, id);
+
+        // Only statements list card payments
+        let cards = match document_type {
+            DocumentType::BankStatement => trail
+                .cards
+                .iter()
+                .map(|card| format!("{} {}", card.network, last_four(&card.number)))
+                .collect(),
+            _ => Vec::new(),
+        };
 
         Self {
             id,
             document_type,
+            format,
             filename: filename.clone(),
             full_path: format!("{}/{}", folder, filename),
             tracking_url,
-            fake_ssn: Some(generate_fake_ssn()),
-            fake_account: Some(generate_fake_account()),
+            fake_ssn: Some(trail.ssn.clone()),
+            fake_account: Some(trail.account.clone()),
+            owner: Some(trail.persona.full_name()),
+            employer: Some(trail.employer.clone()),
+            cards,
+            dated: Some(trail.date),
+        }
+    }
+
+    /// The same document in another format, its filename and path changed
+    /// to match.
+    pub fn with_format(mut self, format: DocumentFormat) -> Self {
+        let old = format!(".{}", self.format.extension());
+        let new = format!(".{}", format.extension());
+        for name in [&mut self.filename, &mut self.full_path] {
+            if let Some(stem) = name.strip_suffix(&old) {
+                *name = format!("{}{}", stem, new);
+            }
         }
+        self.format = format;
+        self
     }
 
-    /// Generate document content (placeholder - would be PDF generation).
+    /// Generate document content in the document's format.
+    ///
+    /// Word and Excel files are filled with the owner and figures that fit
+    /// the document type, and call the tracking URL when opened. The
+    /// figures come from the document's id, so the same document always
+    /// has the same content.
     pub fn generate_content(&self) -> Vec<u8> {
+        let title = self.document_type.to_string();
+        let author = self.owner.as_deref().unwrap_or("Owner");
+        match self.format {
+            DocumentFormat::Pdf => self.placeholder_content(),
+            DocumentFormat::Docx => crate::ooxml::docx(
+                &title,
+                author,
+                &self.rows(),
+                &format!("{}/template.dotx", self.tracking_url),
+            ),
+            DocumentFormat::Xlsx => crate::ooxml::xlsx(
+                &title,
+                author,
+                &self.rows(),
+                &format!("{}/logo.png", self.tracking_url),
+            ),
+        }
+    }
+
+    /// What the document says, as labels and values.
+    fn rows(&self) -> Vec<(String, String)> {
+        let mut rng = StdRng::seed_from_u64(self.id.as_u64_pair().0);
+        let mut rows = Vec::new();
+        let mut row = |label: &str, value: String| rows.push((label.to_string(), value));
+
+        if let Some(owner) = &self.owner {
+            row("Name", owner.clone());
+        }
+        let ssn = self.fake_ssn.clone().unwrap_or_default();
+        let account = self.fake_account.clone().unwrap_or_default();
+        match self.document_type {
+            DocumentType::BankStatement => {
+                row("Account", account);
+                let mut balance: i64 = rng.gen_range(150_000..2_500_000);
+                row("Opening balance", money(balance));
+                // Documents made before they were dated end today
+                let end = self.dated.unwrap_or_else(|| Utc::now().date_naive());
+                let start = end - Duration::days(30);
+                for day in 0..8 {
+                    let (merchant, cents) = [
+                        ("PAYROLL DIRECT DEP", rng.gen_range(180_000..420_000)),
+                        ("AMAZON MKTPLACE", -rng.gen_range(1_500..25_000)),
+                        ("WHOLE FOODS", -rng.gen_range(2_000..18_000)),
+                        ("SHELL OIL", -rng.gen_range(3_000..9_000)),
+                        ("ZELLE TRANSFER", -rng.gen_range(5_000..60_000)),
+                        ("NETFLIX.COM", -1_549),
+                    ][rng.gen_range(0..6)];
+                    balance += cents;
+                    let date = start + Duration::days(day * 4 + rng.gen_range(0..4));
+                    row(
+                        &format!("{} {}", date.format("%m/%d"), merchant),
+                        money(cents),
+                    );
+                }
+                // The cards are paid off from this account
+                for card in &self.cards {
+                    let cents = -rng.gen_range(5_000..150_000);
+                    balance += cents;
+                    let date = start + Duration::days(rng.gen_range(0..30));
+                    row(
+                        &format!("{} CARD PAYMENT {}", date.format("%m/%d"), card),
+                        money(cents),
+                    );
+                }
+                row("Closing balance", money(balance));
+            }
+            DocumentType::PayStub => {
+                if let Some(employer) = &self.employer {
+                    row("Employer", employer.clone());
+                }
+                row("Employee SSN", ssn);
+                let gross: i64 = rng.gen_range(250_000..900_000);
+                let federal = gross * rng.gen_range(10..22) / 100;
+                let state = gross * rng.gen_range(3..7) / 100;
+                let social_security = gross * 62 / 1000;
+                let medicare = gross * 145 / 10_000;
+                row("Gross pay", money(gross));
+                row("Federal income tax", money(-federal));
+                row("State income tax", money(-state));
+                row("Social Security", money(-social_security));
+                row("Medicare", money(-medicare));
+                row(
+                    "Net pay",
+                    money(gross - federal - state - social_security - medicare),
+                );
+                row("Direct deposit", account);
+            }
+            DocumentType::TaxReturn => {
+                row("SSN", ssn);
+                let wages: i64 = rng.gen_range(4_500_000..18_000_000);
+                let interest: i64 = rng.gen_range(1_000..250_000);
+                let deduction = 1_460_000;
+                let taxable = wages + interest - deduction;
+                let tax = taxable * rng.gen_range(12..22) / 100;
+                let withheld = tax + rng.gen_range(-150_000..300_000);
+                if let Some(employer) = &self.employer {
+                    row("Employer", employer.clone());
+                }
+                row("Wages, salaries, tips", money(wages));
+                row("Taxable interest", money(interest));
+                row("Adjusted gross income", money(wages + interest));
+                row("Standard deduction", money(deduction));
+                row("Taxable income", money(taxable));
+                row("Total tax", money(tax));
+                row("Federal income tax withheld", money(withheld));
+                row("Refund", money(withheld - tax));
+                row("Direct deposit", account);
+            }
+            DocumentType::MedicalRecord => {
+                row("SSN", ssn);
+                row(
+                    "Member ID",
+                    format!("W{:09}", rng.gen_range(0..1_000_000_000)),
+                );
+                row("Amount billed", money(rng.gen_range(20_000..1_500_000)));
+                row(
+                    "Patient responsibility",
+                    money(rng.gen_range(2_000..150_000)),
+                );
+            }
+            DocumentType::InsurancePolicy => {
+                row("SSN", ssn);
+                row(
+                    "Policy number",
+                    format!("POL-{:08}", rng.gen_range(0..100_000_000)),
+                );
+                row("Coverage", money(rng.gen_range(25_000_000..200_000_000)));
+                row("Annual premium", money(rng.gen_range(40_000..400_000)));
+                row("Premium account", account);
+            }
+            DocumentType::Will => {
+                row("SSN", ssn);
+                row(
+                    "Estimated estate",
+                    money(rng.gen_range(30_000_000..250_000_000)),
+                );
+                row("Estate account", account);
+            }
+            DocumentType::Passport | DocumentType::DriversLicense => {
+                row("SSN", ssn);
+                row(
+                    "Document number",
+                    format!("{:09}", rng.gen_range(0..1_000_000_000)),
+                );
+            }
+        }
+        rows
+    }
+
+    /// Plain-text stand-in for a PDF.
+    fn placeholder_content(&self) -> Vec<u8> {
         // In a real implementation, this would generate a PDF with:
         // - Embedded tracking pixel/JavaScript
         // - The fake SSN/account numbers
@@ -120,11 +415,13 @@ impl TrapDocument {
         let content = format!(
             "TRAP DOCUMENT\n\
             Type: {}\n\
+            Name: {}\n\
             Tracking ID: {}\n\
             Tracking URL: {}\n\
             Fake SSN: {}\n\
             Fake Account: {}\n",
             self.document_type,
+            self.owner.as_deref().unwrap_or("N/A"),
             self.id,
             self.tracking_url,
             self.fake_ssn.as_deref().unwrap_or("N/A"),
@@ -135,10 +432,19 @@ impl TrapDocument {
     }
 }
 
-/// Generate a fake but valid-format SSN.
-fn generate_fake_ssn() -> String {
-    let mut rng = rand::thread_rng();
+/// Cents as dollars, the way a spreadsheet reads them.
+fn money(cents: i64) -> String {
+    let sign = if cents < 0 { "-" } else { "" };
+    format!("{}{}.{:02}", sign, cents.abs() / 100, cents.abs() % 100)
+}
 
+/// The last four digits of a card number, as statements show them.
+fn last_four(number: &str) -> &str {
+    &number[number.len().saturating_sub(4)..]
+}
+
+/// Generate a fake but valid-format SSN.
+fn generate_fake_ssn(rng: &mut impl Rng) -> String {
     // Generate area number (001-899, excluding 666)
     let area = loop {
         let n = rng.gen_range(1..900);
@@ -157,9 +463,7 @@ fn generate_fake_ssn() -> String {
 }
 
 /// Generate a fake bank account number.
-fn generate_fake_account() -> String {
-    let mut rng = rand::thread_rng();
-
+fn generate_fake_account(rng: &mut impl Rng) -> String {
     // Routing number (9 digits, valid format)
     let routing: String = (0..9).map(|_| rng.gen_range(0..10).to_string()).collect();
 
@@ -176,7 +480,9 @@ mod tests {
 
     #[test]
     fn test_generate_document() {
-        let doc = TrapDocument::generate(DocumentType::TaxReturn);
+        let persona = Persona::generate(crate::Locale::EnUs);
+        let trail = PaperTrail::new(&persona, &[], &mut rand::thread_rng());
+        let doc = TrapDocument::generate(DocumentType::TaxReturn, &trail);
         assert!(doc.filename.contains("Tax_Return"));
         assert!(doc.tracking_url.contains("i1.is"));
         assert!(doc.fake_ssn.is_some());
@@ -184,14 +490,152 @@ mod tests {
 
     #[test]
     fn test_ssn_format() {
-        let ssn = generate_fake_ssn();
+        let ssn = generate_fake_ssn(&mut rand::thread_rng());
         assert_eq!(ssn.len(), 11); // XXX-XX-XXXX
         assert_eq!(ssn.chars().filter(|c| *c == '-').count(), 2);
     }
 
     #[test]
     fn test_document_paths() {
-        let doc = TrapDocument::generate(DocumentType::BankStatement);
+        let persona = Persona::generate(crate::Locale::EnUs);
+        let trail = PaperTrail::new(&persona, &[], &mut rand::thread_rng());
+        let doc = TrapDocument::generate(DocumentType::BankStatement, &trail);
         assert!(doc.full_path.contains("Financial"));
+        assert!(doc.full_path.ends_with(".xlsx"));
+
+        let doc = doc.with_format(DocumentFormat::Pdf);
+        assert!(doc.filename.ends_with(".pdf"));
+        assert!(doc.full_path.ends_with(&doc.filename));
+    }
+
+    /// The parts of an Office file, by name.
+    fn unzip(content: &[u8]) -> std::collections::BTreeMap<String, String> {
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(content)).unwrap();
+        let mut parts = std::collections::BTreeMap::new();
+        for i in 0..archive.len() {
+            let mut file = archive.by_index(i).unwrap();
+            let mut text = String::new();
+            std::io::Read::read_to_string(&mut file, &mut text).unwrap();
+            parts.insert(file.name().to_string(), text);
+        }
+        parts
+    }
+
+    #[test]
+    fn test_docx() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let persona = Persona::generate_with_rng(crate::Locale::EnUs, &mut rng);
+        let trail = PaperTrail::new(&persona, &[], &mut rng);
+        let doc = TrapDocument::generate_with_rng(DocumentType::TaxReturn, &trail, &mut rng);
+        assert_eq!(doc.format, DocumentFormat::Docx);
+        assert!(doc.filename.ends_with(".docx"));
+
+        let content = doc.generate_content();
+        assert_eq!(content, doc.generate_content());
+        let parts = unzip(&content);
+
+        let types = &parts["[Content_Types].xml"];
+        assert!(types.contains(
+            "PartName=\"/word/document.xml\" ContentType=\"application/vnd.openxmlformats-\
+             officedocument.wordprocessingml.document.main+xml\""
+        ));
+        assert!(parts["_rels/.rels"].contains("Target=\"word/document.xml\""));
+        assert!(parts["word/settings.xml"].contains("<w:attachedTemplate r:id=\"rId1\"/>"));
+        let rels = &parts["word/_rels/settings.xml.rels"];
+        assert!(rels.contains("/relationships/attachedTemplate\""));
+        assert!(rels.contains(&format!(
+            "Target=\"{}/{}/template.dotx\" TargetMode=\"External\"",
+            crate::DEFAULT_TRACKING_BASE,
+            doc.id
+        )));
+
+        let body = &parts["word/document.xml"];
+        assert!(body.contains(&persona.full_name()));
+        assert!(body.contains(doc.fake_ssn.as_deref().unwrap()));
+        assert!(body.contains("Adjusted gross income"));
+        assert!(parts["docProps/core.xml"].contains(&persona.full_name()));
+    }
+
+    #[test]
+    fn test_xlsx() {
+        let persona = Persona::generate(crate::Locale::EnUs);
+        let trail = PaperTrail::new(&persona, &[], &mut rand::thread_rng());
+        let doc = TrapDocument::generate(DocumentType::BankStatement, &trail);
+        assert_eq!(doc.format, DocumentFormat::Xlsx);
+        let parts = unzip(&doc.generate_content());
+
+        let types = &parts["[Content_Types].xml"];
+        for (part, content_type) in [
+            ("/xl/workbook.xml", "spreadsheetml.sheet.main+xml"),
+            ("/xl/worksheets/sheet1.xml", "spreadsheetml.worksheet+xml"),
+            ("/xl/drawings/drawing1.xml", "drawing+xml"),
+        ] {
+            assert!(types.contains(&format!(
+                "PartName=\"{}\" ContentType=\"application/vnd.openxmlformats-officedocument.{}\"",
+                part, content_type
+            )));
+        }
+        assert!(parts["xl/worksheets/_rels/sheet1.xml.rels"]
+            .contains("Target=\"../drawings/drawing1.xml\""));
+        assert!(parts["xl/drawings/drawing1.xml"].contains("<a:blip r:link=\"rId1\"/>"));
+        let rels = &parts["xl/drawings/_rels/drawing1.xml.rels"];
+        assert!(rels.contains("/relationships/image\""));
+        assert!(rels.contains(&format!(
+            "Target=\"{}/logo.png\" TargetMode=\"External\"",
+            doc.tracking_url
+        )));
+
+        // The figures are numbers, and the account is there to be found
+        let sheet = &parts["xl/worksheets/sheet1.xml"];
+        assert!(sheet.contains("Closing balance"));
+        assert!(sheet.contains("<v>"));
+        assert!(sheet.contains(doc.fake_account.as_deref().unwrap()));
+    }
+
+    #[test]
+    fn test_paper_trail() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let persona = Persona::generate_with_rng(crate::Locale::IsIs, &mut rng);
+        let cards = [
+            HoneypotCard::generate_for_persona(crate::CardNetwork::Visa, &persona, &mut rng),
+            HoneypotCard::generate_for_persona(crate::CardNetwork::Amex, &persona, &mut rng),
+        ];
+        let trail = PaperTrail::new(&persona, &cards, &mut rng);
+        assert_eq!(Some(&trail.employer), persona.employer.as_ref());
+
+        let [statement, stub, tax_return] = [
+            DocumentType::BankStatement,
+            DocumentType::PayStub,
+            DocumentType::TaxReturn,
+        ]
+        .map(|kind| TrapDocument::generate_with_rng(kind, &trail, &mut rng));
+        let contains = |doc: &TrapDocument, text: &str| {
+            let content = doc.generate_content();
+            let parts = unzip(&content);
+            parts.values().any(|part| part.contains(text))
+        };
+
+        // Payments to both cards, from the one account
+        for card in &cards {
+            let last_four = &card.number[card.number.len() - 4..];
+            assert!(
+                contains(
+                    &statement,
+                    &format!("CARD PAYMENT {} {}", card.network, last_four)
+                ),
+                "{last_four}"
+            );
+        }
+        assert!(contains(&statement, &trail.account));
+        assert!(contains(&stub, &trail.account));
+
+        // One employer and one SSN throughout
+        let employer = persona.employer.as_deref().unwrap();
+        assert!(contains(&stub, employer));
+        assert!(contains(&tax_return, employer));
+        assert!(contains(&stub, &trail.ssn));
+        assert!(contains(&tax_return, &trail.ssn));
+        assert_eq!(stub.fake_ssn, tax_return.fake_ssn);
+        assert!(stub.cards.is_empty());
     }
 }
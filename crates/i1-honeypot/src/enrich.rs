@@ -0,0 +1,252 @@
+//! Context for a tripwire's source IP.
+//!
+//! A bare IP in an alert means someone has to go and look it up. Enrichment
+//! asks every provider the client has about the address before the alert
+//! goes out, so it arrives saying who owns the address, where it is, and
+//! whether it is a Tor exit, a VPN or a proxy.
+
+use i1_client::I1Client;
+use i1_providers::{MergedHostInfo, ProviderId};
+use serde::{Deserialize, Serialize};
+
+use crate::TripwireEvent;
+
+/// Criminal IP tags that say the address hides someone else.
+const ANONYMIZER_TAGS: [&str; 4] = ["tor", "vpn", "proxy", "hosting"];
+
+/// A tripwire event with what the providers know about its source IP.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnrichedTripwire {
+    /// The event as it was reported
+    pub event: TripwireEvent,
+    /// Providers that answered for the source IP
+    pub sources: Vec<ProviderId>,
+    /// Organization the address is registered to
+    pub org: Option<String>,
+    /// Autonomous system announcing the address
+    pub asn: Option<String>,
+    /// Internet service provider
+    pub isp: Option<String>,
+    /// Country the address is registered in
+    pub country: Option<String>,
+    /// City the address geolocates to
+    pub city: Option<String>,
+    /// Worst risk score any provider gave the host, 0 to 10
+    pub risk_score: Option<f64>,
+    /// Criminal IP's classification: `tor`, `vpn`, `proxy` or `hosting`
+    pub classification: Vec<String>,
+    /// Tags from every provider, merged
+    pub tags: Vec<String>,
+    /// One line for a human, such as "Comcast in Lagos, Nigeria via known
+    /// VPN exit"
+    pub summary: String,
+    /// Providers that failed, with why
+    pub errors: Vec<String>,
+}
+
+impl EnrichedTripwire {
+    /// True if the source IP is a Tor exit, a VPN or a proxy.
+    pub fn is_anonymized(&self) -> bool {
+        self.classification
+            .iter()
+            .any(|tag| matches!(tag.as_str(), "tor" | "vpn" | "proxy"))
+    }
+}
+
+impl TripwireEvent {
+    /// Look up the event's source IP with every provider `client` has.
+    ///
+    /// A provider that fails only leaves its part out, and its error is
+    /// kept in `errors`; an event without a source IP comes back with
+    /// nothing added. Either way the event itself is never lost.
+    pub async fn enrich(&self, client: &I1Client) -> EnrichedTripwire {
+        let mut enriched = EnrichedTripwire {
+            event: self.clone(),
+            sources: Vec::new(),
+            org: None,
+            asn: None,
+            isp: None,
+            country: None,
+            city: None,
+            risk_score: None,
+            classification: Vec::new(),
+            tags: Vec::new(),
+            summary: String::new(),
+            errors: Vec::new(),
+        };
+        let Some(ip) = self.source_ip.as_deref() else {
+            enriched.summary = "no source IP".to_string();
+            return enriched;
+        };
+
+        let results = match client.lookup_host_all(ip).await {
+            Ok(results) => results,
+            Err(e) => {
+                enriched.errors.push(e.to_string());
+                Vec::new()
+            }
+        };
+
+        // The default provider first, as the client merges
+        let default = client.default_provider_name();
+        let mut hosts = Vec::new();
+        for (name, result) in results {
+            match (result, name.parse::<ProviderId>()) {
+                (Ok(host), Ok(id)) if Some(name.as_str()) == default => hosts.insert(0, (id, host)),
+                (Ok(host), Ok(id)) => hosts.push((id, host)),
+                (Ok(_), Err(_)) => {}
+                (Err(e), _) => enriched.errors.push(format!("{}: {}", name, e)),
+            }
+        }
+
+        if !hosts.is_empty() {
+            let merged = MergedHostInfo::from_hosts(ip, &hosts);
+            enriched.isp = hosts.iter().find_map(|(_, h)| h.isp.clone());
+            enriched.risk_score = hosts.iter().map(|(_, h)| h.risk_score()).reduce(f64::max);
+            enriched.classification = hosts
+                .iter()
+                .filter(|(id, _)| *id == ProviderId::CriminalIp)
+                .flat_map(|(_, h)| &h.tags)
+                .filter(|tag| ANONYMIZER_TAGS.contains(&tag.as_str()))
+                .cloned()
+                .collect();
+            enriched.sources = merged.sources;
+            enriched.org = merged.org;
+            enriched.asn = merged.asn;
+            enriched.country = merged.country;
+            enriched.city = merged.city;
+            enriched.tags = merged.tags;
+        }
+
+        enriched.summary = summary(ip, &enriched);
+        enriched
+    }
+}
+
+/// Who has the address, where, and what it hides behind.
+fn summary(ip: &str, enriched: &EnrichedTripwire) -> String {
+    let mut summary = enriched
+        .isp
+        .clone()
+        .or_else(|| enriched.org.clone())
+        .unwrap_or_else(|| ip.to_string());
+
+    let place: Vec<&str> = [&enriched.city, &enriched.country]
+        .into_iter()
+        .filter_map(Option::as_deref)
+        .collect();
+    if !place.is_empty() {
+        summary.push_str(&format!(" in {}", place.join(", ")));
+    }
+
+    let via: Vec<&str> = enriched
+        .classification
+        .iter()
+        .map(|tag| match tag.as_str() {
+            "tor" => "Tor exit",
+            "vpn" => "known VPN exit",
+            "proxy" => "open proxy",
+            _ => "hosting provider",
+        })
+        .collect();
+    if !via.is_empty() {
+        summary.push_str(&format!(" via {}", via.join(" and ")));
+    }
+
+    if let Some(score) = enriched.risk_score.filter(|&score| score > 0.0) {
+        summary.push_str(&format!(", risk {:.1}/10", score));
+    }
+    if enriched.sources.is_empty() {
+        summary.push_str(" (no provider data)");
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use i1_client::testing::MockProvider;
+    use i1_core::I1Error;
+    use uuid::Uuid;
+
+    /// A provider that fails every lookup.
+    fn unavailable(name: &'static str) -> MockProvider {
+        MockProvider::new(name).failing(|| I1Error::provider("mock", 503, "unavailable"))
+    }
+
+    fn event(source_ip: Option<&str>) -> TripwireEvent {
+        TripwireEvent {
+            honeypot_id: Uuid::new_v4(),
+            honeypot_type: "card".to_string(),
+            triggered_at: Utc::now(),
+            source_ip: source_ip.map(String::from),
+            context: serde_json::json!({}),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_enrich() {
+        let client = I1Client::builder()
+            .with_provider(MockProvider::new("shodan").host(
+                "102.88.1.1",
+                serde_json::json!({
+                    "org": "MainOne Cable",
+                    "isp": "MainOne",
+                    "asn": "AS37282",
+                    "city": "Lagos",
+                    "country_name": "Nigeria",
+                    "vulns": ["CVE-2021-44228"],
+                }),
+            ))
+            .with_provider(MockProvider::new("criminalip").host(
+                "102.88.1.1",
+                serde_json::json!({
+                    "tags": ["risk:inbound:80", "vpn"],
+                }),
+            ))
+            .with_provider(unavailable("censys"))
+            .build();
+
+        let enriched = event(Some("102.88.1.1")).enrich(&client).await;
+        assert_eq!(
+            enriched.sources,
+            vec![ProviderId::Shodan, ProviderId::CriminalIp]
+        );
+        assert_eq!(enriched.org.as_deref(), Some("MainOne Cable"));
+        assert_eq!(enriched.country.as_deref(), Some("Nigeria"));
+        assert_eq!(enriched.risk_score, Some(5.0));
+        assert_eq!(enriched.classification, vec!["vpn"]);
+        assert!(enriched.is_anonymized());
+        assert!(enriched.tags.contains(&"risk:inbound:80".to_string()));
+        assert_eq!(
+            enriched.summary,
+            "MainOne in Lagos, Nigeria via known VPN exit, risk 5.0/10"
+        );
+        assert_eq!(enriched.errors.len(), 1);
+        assert!(enriched.errors[0].starts_with("censys: "));
+
+        let json = serde_json::to_value(&enriched).unwrap();
+        assert_eq!(json["event"]["source_ip"], "102.88.1.1");
+        assert_eq!(json["classification"][0], "vpn");
+    }
+
+    #[tokio::test]
+    async fn test_enrich_partial() {
+        let client = I1Client::builder()
+            .with_provider(unavailable("shodan"))
+            .build();
+
+        // Every provider down: the event still comes back
+        let enriched = event(Some("203.0.113.9")).enrich(&client).await;
+        assert!(enriched.sources.is_empty());
+        assert!(enriched.risk_score.is_none());
+        assert!(!enriched.is_anonymized());
+        assert_eq!(enriched.errors.len(), 1);
+        assert_eq!(enriched.summary, "203.0.113.9 (no provider data)");
+
+        let enriched = event(None).enrich(&client).await;
+        assert!(enriched.errors.is_empty());
+        assert_eq!(enriched.summary, "no source IP");
+    }
+}
@@ -17,6 +17,26 @@ pub enum HoneypotError {
     #[error("Failed to generate document: {0}")]
     DocumentGeneration(String),
 
+    /// An artifact path that would land outside the deploy directory.
+    #[error("Unsafe artifact path: {0}")]
+    UnsafePath(String),
+
+    /// Tripwire events the endpoint did not accept.
+    #[error("Failed to deliver tripwire events: {0}")]
+    Delivery(String),
+
+    /// A decoy's certificate could not be issued.
+    #[error("Certificate error: {0}")]
+    Certificate(#[from] i1_ca::CaError),
+
+    /// A decoy's TLS settings were rejected.
+    #[error("TLS error: {0}")]
+    Tls(String),
+
+    /// HTTP error.
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+
     /// IO error.
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
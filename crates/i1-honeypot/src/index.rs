@@ -0,0 +1,555 @@
+//! Finding the kit behind a leaked value.
+//!
+//! When a card number, wallet address or token from a kit turns up
+//! somewhere, [`KitIndex`] says which kit it came from and whose it is.
+//! Values are stored normalized, so `4111-1111-1111-1111` finds the card
+//! `4111111111111111` and an Ethereum address matches in any case.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{HoneypotError, HoneypotKit};
+
+/// The part of a kit a match belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ComponentType {
+    Kit,
+    Card,
+    Credential,
+    Wallet,
+    Document,
+    Honeytoken,
+    Mail,
+}
+
+impl std::fmt::Display for ComponentType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ComponentType::Kit => write!(f, "kit"),
+            ComponentType::Card => write!(f, "card"),
+            ComponentType::Credential => write!(f, "credential"),
+            ComponentType::Wallet => write!(f, "wallet"),
+            ComponentType::Document => write!(f, "document"),
+            ComponentType::Honeytoken => write!(f, "honeytoken"),
+            ComponentType::Mail => write!(f, "mail"),
+        }
+    }
+}
+
+/// What kind of value matched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum MatchKind {
+    /// A component's UUID, alone or inside a tracking URL
+    TrackingId,
+    /// A honeytoken value or a wallet's private key
+    Token,
+    WalletAddress,
+    SeedPhrase,
+    CardNumber,
+    /// The last four digits of a card with its expiry, e.g. `1234 08/27`
+    CardLastFour,
+    Ssn,
+    AccountNumber,
+    Username,
+}
+
+impl MatchKind {
+    /// Every kind, in the order [`KitIndex::check`] prefers them: the
+    /// values least likely to match by accident first.
+    const ALL: [MatchKind; 9] = [
+        MatchKind::TrackingId,
+        MatchKind::Token,
+        MatchKind::WalletAddress,
+        MatchKind::SeedPhrase,
+        MatchKind::CardNumber,
+        MatchKind::CardLastFour,
+        MatchKind::Ssn,
+        MatchKind::AccountNumber,
+        MatchKind::Username,
+    ];
+
+    /// `value` as this kind is indexed, or `None` if it can't be one.
+    ///
+    /// - Card, SSN and account numbers: digits only, with spaces, dashes
+    ///   and dots dropped; anything else rules them out.
+    /// - Last four and expiry: the last four digits before an `MM/YY` or
+    ///   `MM/YYYY`, which becomes `MM/YY`.
+    /// - Ethereum and bech32 addresses, usernames and seed phrases: lower
+    ///   case, seed phrases with single spaces. Base58 addresses and tokens
+    ///   are case-sensitive and only trimmed.
+    /// - Tracking IDs: the first UUID anywhere in the value.
+    fn normalize(self, value: &str) -> Option<String> {
+        let value = value.trim();
+        match self {
+            MatchKind::TrackingId => find_uuid(value).map(|id| id.to_string()),
+            MatchKind::Token => (!value.is_empty()).then(|| value.to_string()),
+            MatchKind::WalletAddress => {
+                if value.is_empty() || value.contains(char::is_whitespace) {
+                    return None;
+                }
+                let lower = value.to_ascii_lowercase();
+                let case_insensitive = ["0x", "bc1", "tb1", "ltc1"]
+                    .iter()
+                    .any(|prefix| lower.starts_with(prefix));
+                Some(if case_insensitive {
+                    lower
+                } else {
+                    value.to_string()
+                })
+            }
+            MatchKind::SeedPhrase => {
+                let words: Vec<String> = value.split_whitespace().map(str::to_lowercase).collect();
+                (words.len() >= 12).then(|| words.join(" "))
+            }
+            MatchKind::CardNumber => digits_between(value, 12, 19),
+            MatchKind::CardLastFour => last_four_expiry(value),
+            MatchKind::Ssn => digits_between(value, 9, 9),
+            MatchKind::AccountNumber => digits_between(value, 8, 17),
+            MatchKind::Username => {
+                let lower = value.to_lowercase();
+                (!lower.is_empty() && !lower.contains(char::is_whitespace)).then_some(lower)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for MatchKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MatchKind::TrackingId => write!(f, "tracking ID"),
+            MatchKind::Token => write!(f, "token"),
+            MatchKind::WalletAddress => write!(f, "wallet address"),
+            MatchKind::SeedPhrase => write!(f, "seed phrase"),
+            MatchKind::CardNumber => write!(f, "card number"),
+            MatchKind::CardLastFour => write!(f, "card last four and expiry"),
+            MatchKind::Ssn => write!(f, "SSN"),
+            MatchKind::AccountNumber => write!(f, "account number"),
+            MatchKind::Username => write!(f, "username"),
+        }
+    }
+}
+
+/// Where a value was found.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Match {
+    /// Kit the value belongs to
+    pub kit_id: Uuid,
+    /// User the kit belongs to
+    pub user_id: String,
+    /// Part of the kit that holds the value
+    pub component: ComponentType,
+    /// The component's identifier
+    pub component_id: Uuid,
+    /// What kind of value matched
+    pub kind: MatchKind,
+}
+
+/// Lookup from the values in kits to the kits they came from.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KitIndex {
+    /// Matches by `kind:normalized value`
+    entries: BTreeMap<String, Vec<Match>>,
+}
+
+impl KitIndex {
+    /// Create an empty index.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Index every value in `kits`.
+    pub fn from_kits(kits: &[HoneypotKit]) -> Self {
+        let mut index = Self::new();
+        for kit in kits {
+            index.add_kit(kit);
+        }
+        index
+    }
+
+    /// Index every value in a kit. Adding a kit twice changes nothing.
+    pub fn add_kit(&mut self, kit: &HoneypotKit) {
+        use ComponentType as C;
+        use MatchKind as K;
+
+        let mut values = vec![(C::Kit, kit.id, K::TrackingId, kit.id.to_string())];
+        for card in &kit.cards {
+            let id = card.id;
+            let last_four = &card.number[card.number.len().saturating_sub(4)..];
+            let last_four_expiry = format!("{} {}", last_four, card.expiry);
+            values.extend([
+                (C::Card, id, K::CardNumber, card.number.clone()),
+                (C::Card, id, K::CardLastFour, last_four_expiry),
+            ]);
+        }
+        for credential in &kit.credentials {
+            let id = credential.id;
+            values.push((C::Credential, id, K::Username, credential.username.clone()));
+        }
+        for wallet in &kit.wallets {
+            let id = wallet.id;
+            values.extend([
+                (C::Wallet, id, K::WalletAddress, wallet.address.clone()),
+                (C::Wallet, id, K::SeedPhrase, wallet.seed_phrase.clone()),
+                (C::Wallet, id, K::Token, wallet.private_key.clone()),
+            ]);
+        }
+        for document in &kit.documents {
+            let id = document.id;
+            values.push((C::Document, id, K::TrackingId, id.to_string()));
+            if let Some(ssn) = &document.fake_ssn {
+                values.push((C::Document, id, K::Ssn, ssn.clone()));
+            }
+            if let Some(account) = &document.fake_account {
+                // "Routing: ... Account: ..."
+                let number = account.rsplit(' ').next().unwrap_or(account);
+                values.push((C::Document, id, K::AccountNumber, number.to_string()));
+            }
+        }
+        for token in &kit.honeytokens {
+            for value in &token.tokens {
+                values.push((C::Honeytoken, token.id, K::Token, value.clone()));
+            }
+        }
+        for message in &kit.mail {
+            values.push((C::Mail, message.id, K::TrackingId, message.id.to_string()));
+        }
+
+        for (component, component_id, kind, value) in values {
+            // Every component can be found by its own ID as well
+            for (kind, value) in [(kind, value), (K::TrackingId, component_id.to_string())] {
+                let Some(normalized) = kind.normalize(&value) else {
+                    continue;
+                };
+                let found = Match {
+                    kit_id: kit.id,
+                    user_id: kit.user_id.clone(),
+                    component,
+                    component_id,
+                    kind,
+                };
+                let matches = self.entries.entry(key(kind, &normalized)).or_default();
+                if !matches.contains(&found) {
+                    matches.push(found);
+                }
+            }
+        }
+    }
+
+    /// The kit a value came from. If it matches more than one thing, a
+    /// match on a rarer kind of value wins (a tracking ID over a username,
+    /// say), then the kit indexed first; [`lookup`](Self::lookup) has all
+    /// of them.
+    pub fn check(&self, value: &str) -> Option<Match> {
+        self.lookup(value).into_iter().next()
+    }
+
+    /// Everything a value matches, in the order [`check`](Self::check)
+    /// prefers them.
+    pub fn lookup(&self, value: &str) -> Vec<Match> {
+        MatchKind::ALL
+            .iter()
+            .filter_map(|&kind| {
+                let normalized = kind.normalize(value)?;
+                self.entries.get(&key(kind, &normalized))
+            })
+            .flatten()
+            .cloned()
+            .collect()
+    }
+
+    /// Number of distinct values indexed.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether nothing is indexed.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Save the index as JSON, e.g. next to the kits it covers.
+    pub fn save(&self, path: &Path) -> Result<(), HoneypotError> {
+        fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Load an index saved with [`save`](Self::save).
+    pub fn load(path: &Path) -> Result<Self, HoneypotError> {
+        let json = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&json)?)
+    }
+}
+
+fn key(kind: MatchKind, normalized: &str) -> String {
+    format!("{:?}:{}", kind, normalized)
+}
+
+/// The digits of `value` if it is only digits and separators, and there
+/// are `min..=max` of them.
+fn digits_between(value: &str, min: usize, max: usize) -> Option<String> {
+    if !value
+        .chars()
+        .all(|c| c.is_ascii_digit() || matches!(c, ' ' | '-' | '.'))
+    {
+        return None;
+    }
+    let digits: String = value.chars().filter(char::is_ascii_digit).collect();
+    (min..=max).contains(&digits.len()).then_some(digits)
+}
+
+/// `1234 08/27` from `****1234 exp 08/2027` and the like.
+fn last_four_expiry(value: &str) -> Option<String> {
+    let words: Vec<&str> = value
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|word| !word.is_empty())
+        .collect();
+    let mut expiries = words.iter().filter(|word| word.contains('/'));
+    let expiry = expiries.next()?;
+    if expiries.next().is_some() {
+        return None;
+    }
+
+    let expiry: String = expiry
+        .chars()
+        .filter(|c| c.is_ascii_digit() || *c == '/')
+        .collect();
+    let (month, year) = expiry.split_once('/')?;
+    let month: u32 = month.parse().ok()?;
+    if !(1..=12).contains(&month) || !matches!(year.len(), 2 | 4) {
+        return None;
+    }
+
+    let digits: String = words
+        .iter()
+        .filter(|word| !word.contains('/'))
+        .flat_map(|word| word.chars().filter(char::is_ascii_digit))
+        .collect();
+    let last_four = digits.get(digits.len().checked_sub(4)?..)?;
+    Some(format!(
+        "{} {:02}/{}",
+        last_four,
+        month,
+        &year[year.len() - 2..]
+    ))
+}
+
+/// The first hyphenated UUID in `value`, or `value` itself as a UUID.
+fn find_uuid(value: &str) -> Option<Uuid> {
+    if let Ok(id) = Uuid::parse_str(value) {
+        return Some(id);
+    }
+    (0..value.len().saturating_sub(35))
+        .filter(|&i| value.is_char_boundary(i) && value.is_char_boundary(i + 36))
+        .find_map(|i| Uuid::parse_str(&value[i..i + 36]).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalization() {
+        let normalize = |kind: MatchKind, value: &str| kind.normalize(value);
+
+        for card in [
+            "4111-1111-1111-1111",
+            "4111 1111 1111 1111",
+            " 4111111111111111 ",
+        ] {
+            assert_eq!(
+                normalize(MatchKind::CardNumber, card).as_deref(),
+                Some("4111111111111111")
+            );
+        }
+        assert_eq!(
+            normalize(MatchKind::CardNumber, "4111x1111x1111x1111"),
+            None
+        );
+        assert_eq!(normalize(MatchKind::CardNumber, "4111"), None);
+
+        for value in [
+            "1234 08/27",
+            "**** 1234, exp 08/2027",
+            "4111111111111234 8/27",
+        ] {
+            assert_eq!(
+                normalize(MatchKind::CardLastFour, value).as_deref(),
+                Some("1234 08/27"),
+                "{value}"
+            );
+        }
+        assert_eq!(normalize(MatchKind::CardLastFour, "1234 13/27"), None);
+        assert_eq!(normalize(MatchKind::CardLastFour, "12 08/27"), None);
+
+        assert_eq!(
+            normalize(MatchKind::Ssn, "123-45-6789").as_deref(),
+            Some("123456789")
+        );
+        assert_eq!(
+            normalize(
+                MatchKind::WalletAddress,
+                "0x9858EfFD232B4033E47d90003D41EC34EcaEda94"
+            )
+            .as_deref(),
+            Some("0x9858effd232b4033e47d90003d41ec34ecaeda94")
+        );
+        assert_eq!(
+            normalize(
+                MatchKind::WalletAddress,
+                "1LqBGSKuX5yYUonjxT5qGfpUsXKYYWeabA"
+            )
+            .as_deref(),
+            Some("1LqBGSKuX5yYUonjxT5qGfpUsXKYYWeabA")
+        );
+        let words = ["abandon"; 11].join(" ");
+        let messy = format!(" Abandon  {}\nAbout ", &words["abandon ".len()..]);
+        assert_eq!(
+            normalize(MatchKind::SeedPhrase, &messy),
+            Some(format!("{} about", words))
+        );
+        assert_eq!(normalize(MatchKind::SeedPhrase, "abandon about"), None);
+
+        let id = Uuid::new_v4();
+        for value in [
+            id.to_string(),
+            id.simple().to_string(),
+            format!("https://i1.is/t/{}/open.gif", id),
+        ] {
+            assert_eq!(
+                normalize(MatchKind::TrackingId, &value),
+                Some(id.to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn test_lookup_kits() {
+        let kits = [
+            HoneypotKit::generate_default_kit("alice", None),
+            HoneypotKit::generate_default_kit("bob", None),
+        ];
+        let index = KitIndex::from_kits(&kits);
+
+        for kit in &kits {
+            let card = &kit.cards[0];
+            let spaced = card.display_number.replace(' ', "-");
+            let found = index.check(&spaced).unwrap();
+            assert_eq!(found.kit_id, kit.id);
+            assert_eq!(found.user_id, kit.user_id);
+            assert_eq!(found.component, ComponentType::Card);
+            assert_eq!(found.component_id, card.id);
+            assert_eq!(found.kind, MatchKind::CardNumber);
+
+            let last_four = format!(
+                "ending {} exp {}",
+                &card.number[card.number.len() - 4..],
+                card.expiry
+            );
+            assert_eq!(index.check(&last_four).unwrap().component_id, card.id);
+
+            let wallet = &kit.wallets[1];
+            let found = index
+                .check(&wallet.address.to_uppercase().replace("0X", "0x"))
+                .unwrap();
+            assert_eq!(
+                (found.component_id, found.kind),
+                (wallet.id, MatchKind::WalletAddress)
+            );
+            let found = index.check(&wallet.seed_phrase.to_uppercase()).unwrap();
+            assert_eq!(
+                (found.component_id, found.kind),
+                (wallet.id, MatchKind::SeedPhrase)
+            );
+
+            let document = &kit.documents[0];
+            let found = index.check(document.fake_ssn.as_deref().unwrap()).unwrap();
+            assert_eq!(
+                (found.component_id, found.kind),
+                (document.id, MatchKind::Ssn)
+            );
+            let found = index.check(&document.tracking_url).unwrap();
+            assert_eq!(
+                (found.component_id, found.kind),
+                (document.id, MatchKind::TrackingId)
+            );
+
+            let credential = &kit.credentials[0];
+            let found = index.check(&credential.username.to_uppercase()).unwrap();
+            assert_eq!(found.component_id, credential.id);
+
+            let token = &kit.honeytokens[1];
+            let found = index.check(&token.tokens[0]).unwrap();
+            assert_eq!(
+                (found.component, found.component_id),
+                (ComponentType::Honeytoken, token.id)
+            );
+
+            let message = &kit.mail[0];
+            assert_eq!(
+                index.check(&message.tracking_pixel).unwrap().component_id,
+                message.id
+            );
+            assert_eq!(
+                index.check(&kit.id.to_string()).unwrap().component,
+                ComponentType::Kit
+            );
+        }
+
+        assert!(index.check("not in any kit").is_none());
+        assert!(index.check("0000-0000-0000-0000").is_none());
+    }
+
+    #[test]
+    fn test_collisions() {
+        let first = HoneypotKit::generate_default_kit("alice", None);
+        let mut second = HoneypotKit::generate_default_kit("bob", None);
+        second.credentials[0].username = first.credentials[0].username.to_uppercase();
+
+        // The same number as one kit's account and the other's card
+        let card = first.cards[0].number.clone();
+        second.documents[0].fake_account = Some(format!("Routing: 021000021 Account: {}", card));
+
+        let mut index = KitIndex::from_kits(&[first.clone(), second.clone()]);
+        let size = index.len();
+        index.add_kit(&first);
+        assert_eq!(index.len(), size);
+
+        let shared = index.lookup(&first.credentials[0].username);
+        assert_eq!(shared.len(), 2);
+        assert_eq!(shared[0].user_id, "alice");
+        assert_eq!(shared[1].user_id, "bob");
+        assert_eq!(
+            index.check(&first.credentials[0].username).unwrap().user_id,
+            "alice"
+        );
+
+        // Matches of both kinds, the card first
+        let both = index.lookup(&card);
+        let kinds: Vec<_> = both.iter().map(|m| (m.kind, m.user_id.as_str())).collect();
+        assert_eq!(
+            kinds,
+            [
+                (MatchKind::CardNumber, "alice"),
+                (MatchKind::AccountNumber, "bob")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("kit-index.json");
+        let kit = HoneypotKit::generate_with_seed("alice", 3);
+        let index = KitIndex::from_kits(std::slice::from_ref(&kit));
+
+        index.save(&path).unwrap();
+        let loaded = KitIndex::load(&path).unwrap();
+        assert_eq!(loaded, index);
+        assert_eq!(loaded.check(&kit.cards[1].number).unwrap().kit_id, kit.id);
+        assert!(KitIndex::new().is_empty());
+    }
+}
@@ -4,10 +4,29 @@
 //!
 //! ## Features
 //!
-//! - LUHN-valid credit cards that trigger alerts when used
-//! - Fake cryptocurrency wallets with trackable addresses
-//! - Decoy credentials and password files
-//! - Trap documents that phone home when opened
+//! - LUHN-valid credit cards on realistic BINs that trigger alerts when used
+//! - One persona per kit, so names and addresses agree across the bait
+//! - Empty cryptocurrency wallets whose seed phrases restore in real wallet apps,
+//!   with the keystore and `wallet.dat` files wallet software leaves behind
+//! - Decoy credentials and password files, for sites people in the
+//!   persona's country use
+//! - SSH keys, AWS credentials and registry tokens in the usual dotfiles
+//! - Trap documents that phone home when opened, as Word and Excel files
+//!   with remote templates and linked pictures
+//! - An inbox of statements, exchange confirmations and password resets,
+//!   with a tracking pixel in every message
+//! - A builder for kits made to order, with presets and self-hosted tracking
+//! - Deployment of a kit into a sandbox directory, and clean-up afterwards
+//! - Expiry and rotation of kits in place, with the old kit still traceable
+//! - An index from leaked values back to the kit and user they came from
+//! - Delivery of tripwire events to i1.is, spooled to disk until sent
+//! - Enrichment of a tripwire's source IP from the i1 providers
+//! - TLS decoys posing as a router, NAS or Remote Desktop login, with
+//!   certificates from an i1-ca intermediate
+//! - A plan of the services a home or office network would expose, with
+//!   banners a scanner identifies as the products they claim to be
+//! - Analytics over archived tripwire events: time to first access, top
+//!   sources, the bait that gets taken, and a timeline
 //!
 //! ## Example
 //!
@@ -20,22 +39,57 @@
 //! // Any attempt to charge it = instant notification + scammer tracking
 //! ```
 
+pub mod analytics;
+mod builder;
 mod card;
 mod credentials;
 mod crypto;
+mod decoy;
+mod deploy;
 mod documents;
+mod enrich;
 mod error;
+mod honeytokens;
+mod index;
+mod mailbox;
+mod network;
+mod ooxml;
+mod persona;
+mod reporter;
+mod rotation;
+mod sites;
+mod wallet_files;
 
+pub use analytics::{EventLog, EventSummary};
+pub use builder::{KitBuilder, DEFAULT_LIFETIME_DAYS, MAX_PER_COMPONENT};
 pub use card::{CardNetwork, HoneypotCard, generate_luhn_valid};
 pub use credentials::{CredentialType, HoneypotCredential};
 pub use crypto::{CryptoNetwork, HoneypotWallet};
-pub use documents::{DocumentType, TrapDocument};
+pub use decoy::{
+    decoy_service, DecoyConfig, DecoyKind, DecoyService, DEFAULT_MAX_CONNECTIONS,
+    DEFAULT_VALIDITY_DAYS,
+};
+pub use deploy::{DeployOptions, KitManifest, ManifestEntry, RetiredKit};
+pub use documents::{DocumentFormat, DocumentType, PaperTrail, TrapDocument};
+pub use enrich::EnrichedTripwire;
 pub use error::HoneypotError;
+pub use honeytokens::{aws_access_key_id, Honeytoken, HoneytokenKind};
+pub use index::{ComponentType, KitIndex, Match, MatchKind};
+pub use mailbox::{MailKind, MailMessage};
+pub use network::{DecoyListener, DecoyStyle, NetworkDecoyProfile};
+pub use persona::{Address, Locale, Persona};
+pub use reporter::{TripwireReporter, TripwireReporterBuilder, DEFAULT_ENDPOINT};
+pub use rotation::RotationPlan;
+pub use sites::{SiteCatalog, SiteEntry};
 
 use chrono::{DateTime, Utc};
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+/// Where tracking URLs point unless a kit says otherwise.
+pub const DEFAULT_TRACKING_BASE: &str = "https://i1.is/t";
+
 /// A complete honeypot kit for a user's sandbox environment.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HoneypotKit {
@@ -43,8 +97,17 @@ pub struct HoneypotKit {
     pub id: Uuid,
     /// User this kit belongs to
     pub user_id: String,
+    /// The made-up person the bait belongs to
+    #[serde(default)]
+    pub persona: Option<Persona>,
     /// When this kit was generated
     pub created_at: DateTime<Utc>,
+    /// When the bait goes stale and the kit should be rotated, if ever
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+    /// The kit this one replaced
+    #[serde(default)]
+    pub supersedes: Option<Uuid>,
     /// Generated credit cards
     pub cards: Vec<HoneypotCard>,
     /// Generated credentials
@@ -53,6 +116,18 @@ pub struct HoneypotKit {
     pub wallets: Vec<HoneypotWallet>,
     /// Trap documents
     pub documents: Vec<TrapDocument>,
+    /// SSH keys, cloud credentials and tokens
+    #[serde(default)]
+    pub honeytokens: Vec<Honeytoken>,
+    /// Base of the kit's tracking pixel and link URLs
+    #[serde(default = "default_tracking_base")]
+    pub tracking_base: String,
+    /// Emails in the persona's inbox
+    #[serde(default)]
+    pub mail: Vec<MailMessage>,
+    /// Sites the kit's logins were picked from, where not the built-in ones
+    #[serde(default)]
+    pub site_catalogs: Vec<SiteCatalog>,
 }
 
 impl HoneypotKit {
@@ -61,42 +136,56 @@ impl HoneypotKit {
         Self {
             id: Uuid::new_v4(),
             user_id: user_id.into(),
+            persona: None,
             created_at: Utc::now(),
+            expires_at: None,
+            supersedes: None,
             cards: Vec::new(),
             credentials: Vec::new(),
             wallets: Vec::new(),
             documents: Vec::new(),
+            honeytokens: Vec::new(),
+            tracking_base: default_tracking_base(),
+            mail: Vec::new(),
+            site_catalogs: Vec::new(),
         }
     }
 
-    /// Generate a full kit with default honeypots.
-    pub fn generate_default_kit(user_id: impl Into<String>) -> Self {
-        let mut kit = Self::new(user_id);
-
-        // Generate some credit cards
-        kit.cards.push(HoneypotCard::generate(CardNetwork::Visa));
-        kit.cards.push(HoneypotCard::generate(CardNetwork::Mastercard));
-        kit.cards.push(HoneypotCard::generate(CardNetwork::Amex));
-
-        // Generate credentials
-        kit.credentials.push(HoneypotCredential::generate(CredentialType::BankLogin));
-        kit.credentials.push(HoneypotCredential::generate(CredentialType::EmailLogin));
-        kit.credentials.push(HoneypotCredential::generate(CredentialType::SocialMedia));
-
-        // Generate crypto wallets
-        kit.wallets.push(HoneypotWallet::generate(CryptoNetwork::Bitcoin));
-        kit.wallets.push(HoneypotWallet::generate(CryptoNetwork::Ethereum));
+    /// Create a builder for a kit made to order.
+    pub fn builder(user_id: impl Into<String>) -> KitBuilder {
+        KitBuilder::new(user_id)
+    }
 
-        // Generate trap documents
-        kit.documents.push(TrapDocument::generate(DocumentType::TaxReturn));
-        kit.documents.push(TrapDocument::generate(DocumentType::BankStatement));
+    /// Generate a full kit with default honeypots, all belonging to one
+    /// persona from `locale` (en-US if `None`). The documents come last and
+    /// mention the kit's cards, bank account and SSN.
+    pub fn generate_default_kit(user_id: impl Into<String>, locale: Option<Locale>) -> Self {
+        Self::builder(user_id)
+            .locale(locale.unwrap_or_default())
+            .build()
+            .expect("the default kit is valid")
+    }
 
-        kit
+    /// Generate the default kit from a seed: the same seed gives the same
+    /// kit on the same day, identifiers included. `created_at` is the start
+    /// of the day (UTC); to reproduce a kit on another day, pass its date to
+    /// [`KitBuilder::created_at`] as well as the seed.
+    pub fn generate_with_seed(user_id: impl Into<String>, seed: u64) -> Self {
+        Self::builder(user_id)
+            .seed(seed)
+            .build()
+            .expect("the default kit is valid")
     }
 
-    /// Add a custom credit card to the kit.
+    /// Add a custom credit card to the kit, in the persona's name if the
+    /// kit has one.
     pub fn add_card(&mut self, network: CardNetwork) -> &HoneypotCard {
-        self.cards.push(HoneypotCard::generate(network));
+        let mut rng = rand::thread_rng();
+        let card = match &self.persona {
+            Some(persona) => HoneypotCard::generate_for_persona(network, persona, &mut rng),
+            None => HoneypotCard::generate_with_rng(network, &mut rng),
+        };
+        self.cards.push(card);
         self.cards.last().unwrap()
     }
 
@@ -107,6 +196,14 @@ impl HoneypotKit {
 
     /// Create files that look like real user data.
     pub fn generate_filesystem_artifacts(&self) -> Vec<(String, String)> {
+        self.tagged_artifacts()
+            .into_iter()
+            .map(|(path, content, _)| (path, content))
+            .collect()
+    }
+
+    /// The filesystem artifacts, each with the honeypots it contains.
+    fn tagged_artifacts(&self) -> Vec<(String, String, Vec<Uuid>)> {
         let mut files = Vec::new();
 
         // passwords.txt - classic
@@ -118,6 +215,7 @@ impl HoneypotKit {
         files.push((
             "Documents/passwords.txt".to_string(),
             passwords.join("\n"),
+            self.credentials.iter().map(|c| c.id).collect(),
         ));
 
         // credit_cards.csv
@@ -128,7 +226,11 @@ impl HoneypotKit {
                 card.holder_name, card.number, card.expiry, card.cvv
             ));
         }
-        files.push(("Documents/Financial/cards.csv".to_string(), csv));
+        files.push((
+            "Documents/Financial/cards.csv".to_string(),
+            csv,
+            self.cards.iter().map(|c| c.id).collect(),
+        ));
 
         // crypto seeds
         let seeds: Vec<String> = self
@@ -139,12 +241,29 @@ impl HoneypotKit {
         files.push((
             "Documents/crypto_backup.txt".to_string(),
             seeds.join("\n\n"),
+            self.wallets.iter().map(|w| w.id).collect(),
         ));
 
+        // dotfiles: ~/.ssh, ~/.aws, ~/.npmrc, .env
+        for token in &self.honeytokens {
+            for (path, content) in &token.files {
+                files.push((path.clone(), content.clone(), vec![token.id]));
+            }
+        }
+
         files
     }
 }
 
+fn default_tracking_base() -> String {
+    DEFAULT_TRACKING_BASE.to_string()
+}
+
+/// A version 4 UUID from `rng` rather than the system's randomness.
+pub(crate) fn random_id(rng: &mut impl Rng) -> Uuid {
+    uuid::Builder::from_random_bytes(rng.gen()).into_uuid()
+}
+
 /// Event triggered when a honeypot is accessed/used.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TripwireEvent {
@@ -166,19 +285,146 @@ mod tests {
 
     #[test]
     fn test_generate_kit() {
-        let kit = HoneypotKit::generate_default_kit("test-user");
+        let kit = HoneypotKit::generate_default_kit("test-user", None);
         assert!(!kit.cards.is_empty());
         assert!(!kit.credentials.is_empty());
         assert!(!kit.wallets.is_empty());
     }
 
+    #[test]
+    fn test_seeded_kit() {
+        // Pinned to a day, so it reproduces whenever the test runs
+        let at = chrono::NaiveDate::from_ymd_opt(2020, 6, 15)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+            .and_utc();
+        let seeded = |seed| {
+            HoneypotKit::builder("test-user")
+                .seed(seed)
+                .created_at(at)
+                .build()
+                .unwrap()
+        };
+        let kit = seeded(42);
+        let again = seeded(42);
+        assert_eq!(kit.to_json().unwrap(), again.to_json().unwrap());
+        assert_eq!(kit.id.get_version_num(), 4);
+        assert!(kit.cards.iter().all(HoneypotCard::is_valid));
+        assert!(kit.wallets.iter().all(HoneypotWallet::mnemonic_is_valid));
+
+        let today = HoneypotKit::generate_with_seed("test-user", 42);
+        assert_eq!(today.created_at.time(), chrono::NaiveTime::MIN);
+
+        let other = seeded(43);
+        assert_ne!(kit.id, other.id);
+        assert_ne!(kit.cards[0].number, other.cards[0].number);
+        assert_ne!(kit.wallets[0].seed_phrase, other.wallets[0].seed_phrase);
+    }
+
+    #[test]
+    fn test_seeded_kit_on_a_date() {
+        let day = chrono::NaiveDate::from_ymd_opt(2020, 6, 15).unwrap();
+        let seeded = || {
+            HoneypotKit::builder("test-user")
+                .seed(42)
+                .created_at(day.and_hms_opt(0, 0, 0).unwrap().and_utc())
+                .document_types(&[DocumentType::BankStatement, DocumentType::TaxReturn])
+                .build()
+                .unwrap()
+        };
+        let kit = seeded();
+        assert_eq!(kit.to_json().unwrap(), seeded().to_json().unwrap());
+        for (document, again) in kit.documents.iter().zip(&seeded().documents) {
+            assert_eq!(document.generate_content(), again.generate_content());
+        }
+        for (wallet, again) in kit.wallets.iter().zip(&seeded().wallets) {
+            assert_eq!(wallet.artifact_files_on(day), again.artifact_files_on(day));
+        }
+
+        // Every date follows from the kit's, not from today's
+        assert_eq!(kit.created_at.date_naive(), day);
+        for card in &kit.cards {
+            let year: u32 = card.expiry[3..].parse().unwrap();
+            assert!((21..=24).contains(&year), "{}", card.expiry);
+        }
+        for document in &kit.documents {
+            assert_eq!(document.dated, Some(day));
+            assert!(
+                ["2018", "2019", "2020"]
+                    .iter()
+                    .any(|year| document.filename.contains(year)),
+                "{}",
+                document.filename
+            );
+        }
+    }
+
+    #[test]
+    fn test_kit_shares_persona() {
+        for locale in [Locale::EnUs, Locale::IsIs] {
+            let mut kit = HoneypotKit::generate_default_kit("test-user", Some(locale));
+            let persona = kit.persona.clone().unwrap();
+            assert_eq!(persona.locale, locale);
+            let handle = persona.handle();
+            let (first, last) = handle.split_once('.').unwrap();
+
+            kit.add_card(CardNetwork::Discover);
+            for card in &kit.cards {
+                assert!(card.is_valid());
+                assert_eq!(card.holder_name, persona.card_name());
+            }
+            for credential in &kit.credentials {
+                let username = &credential.username;
+                assert!(username.starts_with(first), "{username}");
+                assert!(username.contains(last), "{username}");
+            }
+            let ssn = kit.documents[0].fake_ssn.clone().unwrap();
+            for document in &kit.documents {
+                assert_eq!(document.owner, Some(persona.full_name()));
+                assert_eq!(document.fake_ssn.as_ref(), Some(&ssn));
+                let content = document.generate_content();
+                let has = |text: &str| content.windows(text.len()).any(|w| w == text.as_bytes());
+                assert!(has(&persona.full_name()));
+                if document.document_type == DocumentType::BankStatement {
+                    // The cards as they were before `add_card`
+                    for card in &kit.cards[..3] {
+                        assert!(has(&card.number[card.number.len() - 4..]));
+                    }
+                } else {
+                    assert!(has(&ssn));
+                }
+            }
+            let ssh = &kit.honeytokens[0].files[1].1;
+            let comment = ssh.split(' ').nth(2).unwrap();
+            assert!(comment.starts_with(&format!("{first}@")), "{ssh}");
+
+            let chrome = kit
+                .deploy_files()
+                .into_iter()
+                .find(|(path, _, _)| path.contains("Chrome"))
+                .unwrap();
+            assert!(String::from_utf8(chrome.1).unwrap().contains(first));
+        }
+    }
+
     #[test]
     fn test_filesystem_artifacts() {
-        let kit = HoneypotKit::generate_default_kit("test-user");
+        let kit = HoneypotKit::generate_default_kit("test-user", None);
         let files = kit.generate_filesystem_artifacts();
         assert!(!files.is_empty());
 
         // Should have passwords.txt
         assert!(files.iter().any(|(path, _)| path.contains("passwords")));
+
+        // And the dotfiles attackers look for first
+        for dotfile in [
+            ".ssh/id_ed25519",
+            ".ssh/id_ed25519.pub",
+            ".aws/credentials",
+            ".npmrc",
+        ] {
+            assert!(files.iter().any(|(path, _)| path == dotfile), "{dotfile}");
+        }
     }
 }
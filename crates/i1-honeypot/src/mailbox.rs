@@ -0,0 +1,502 @@
+//! Fake email for a kit's inbox.
+//!
+//! Bank statements, exchange confirmations and password resets that mention
+//! the kit's cards, accounts, wallets and logins. Every message has its own
+//! tracking pixel, and its links go through the kit's tracking base first,
+//! so opening a message or following a link is a tripwire too.
+
+use base64::Engine;
+use chrono::{DateTime, Duration, Utc};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    CredentialType, CryptoNetwork, DocumentType, HoneypotCredential, HoneypotKit, Persona,
+};
+
+/// What a message is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MailKind {
+    BankStatement,
+    ExchangeConfirmation,
+    PasswordReset,
+}
+
+impl std::fmt::Display for MailKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MailKind::BankStatement => write!(f, "Bank Statement"),
+            MailKind::ExchangeConfirmation => write!(f, "Exchange Confirmation"),
+            MailKind::PasswordReset => write!(f, "Password Reset"),
+        }
+    }
+}
+
+/// One email in the kit's inbox.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MailMessage {
+    /// Unique identifier for tracking
+    pub id: Uuid,
+    /// What the message is about
+    pub kind: MailKind,
+    /// Honeypots the message mentions
+    pub honeypot_ids: Vec<Uuid>,
+    /// Sender, as a `From:` header
+    pub from: String,
+    /// Recipient, as a `To:` header
+    pub to: String,
+    /// Subject line
+    pub subject: String,
+    /// When the message was sent
+    pub date: DateTime<Utc>,
+    /// Image URL that reports the message was opened
+    pub tracking_pixel: String,
+    /// The message's links, each wrapped to pass through the tracking base
+    pub links: Vec<String>,
+    /// Plain-text body
+    pub text: String,
+    /// HTML body, with the tracking pixel
+    pub html: String,
+}
+
+impl MailMessage {
+    /// The message as an RFC 2822 `.eml` file, with a plain-text and an
+    /// HTML part.
+    pub fn to_eml(&self) -> String {
+        let domain = self
+            .from
+            .rsplit_once('@')
+            .map_or("mail.example.com", |(_, rest)| rest.trim_end_matches('>'));
+        let boundary = format!("=_{}", self.id.simple());
+
+        let mut eml = String::new();
+        for (name, value) in [
+            ("Date", self.date.to_rfc2822()),
+            ("From", self.from.clone()),
+            ("To", self.to.clone()),
+            ("Subject", encode_header(&self.subject)),
+            ("Message-ID", format!("<{}@{}>", self.id, domain)),
+            ("MIME-Version", "1.0".to_string()),
+            (
+                "Content-Type",
+                format!("multipart/alternative; boundary=\"{}\"", boundary),
+            ),
+        ] {
+            eml.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        eml.push_str("\r\n");
+
+        for (content_type, body) in [("text/plain", &self.text), ("text/html", &self.html)] {
+            eml.push_str(&format!("--{}\r\n", boundary));
+            eml.push_str(&format!(
+                "Content-Type: {}; charset=utf-8\r\n",
+                content_type
+            ));
+            eml.push_str("Content-Transfer-Encoding: 8bit\r\n\r\n");
+            for line in body.lines() {
+                eml.push_str(line);
+                eml.push_str("\r\n");
+            }
+        }
+        eml.push_str(&format!("--{}--\r\n", boundary));
+        eml
+    }
+
+    /// Name for the message's `.eml` file.
+    pub fn filename(&self) -> String {
+        format!(
+            "{}-{}.eml",
+            self.date.format("%Y%m%d-%H%M%S"),
+            self.kind.to_string().to_lowercase().replace(' ', "-")
+        )
+    }
+}
+
+impl HoneypotKit {
+    /// The inbox as one mbox file, oldest message first.
+    pub fn to_mbox(&self) -> String {
+        let mut mbox = String::new();
+        for message in &self.mail {
+            mbox.push_str(&format!(
+                "From MAILER-DAEMON {}\n",
+                message.date.format("%a %b %e %H:%M:%S %Y")
+            ));
+            for line in message.to_eml().lines() {
+                // mboxrd: quote any line that could be read as a separator
+                if line.trim_start_matches('>').starts_with("From ") {
+                    mbox.push('>');
+                }
+                mbox.push_str(line);
+                mbox.push('\n');
+            }
+            mbox.push('\n');
+        }
+        mbox
+    }
+
+    /// Email about the kit's honeypots, addressed to `persona` and sent
+    /// over the four months before the kit was made.
+    pub(crate) fn generate_mail(&self, persona: &Persona, rng: &mut impl Rng) -> Vec<MailMessage> {
+        let to = address(&persona.full_name(), &self.email_address(persona));
+        let mut drafts = Vec::new();
+
+        // Statements for the bank accounts in the documents and the cards
+        let bank = self
+            .credentials
+            .iter()
+            .find(|c| c.credential_type == CredentialType::BankLogin);
+        let (bank_site, bank_name) = self.first_site(CredentialType::BankLogin, persona);
+        for document in &self.documents {
+            if document.document_type != DocumentType::BankStatement {
+                continue;
+            }
+            let Some(account) = document.fake_account.as_deref() else {
+                continue;
+            };
+            let account = account.rsplit(' ').next().unwrap_or(account);
+            let mut ids = vec![document.id];
+            ids.extend(bank.map(|c| c.id));
+            drafts.push(Draft {
+                kind: MailKind::BankStatement,
+                honeypot_ids: ids,
+                from: address(&bank_name, &format!("alerts@{}", bank_site)),
+                subject: "Your statement is ready".to_string(),
+                paragraphs: vec![
+                    format!("Dear {},", persona.full_name()),
+                    format!(
+                        "Your monthly statement for checking account {} is now available. \
+                         Sign in to view it.",
+                        masked(account)
+                    ),
+                ],
+                links: vec![format!("https://www.{}/statements", bank_site)],
+            });
+        }
+        for card in &self.cards {
+            drafts.push(Draft {
+                kind: MailKind::BankStatement,
+                honeypot_ids: vec![card.id],
+                from: address(&bank_name, &format!("alerts@{}", bank_site)),
+                subject: format!("Your {} statement is available", card.network),
+                paragraphs: vec![
+                    format!("Dear {},", persona.full_name()),
+                    format!(
+                        "The statement for your {} card {} is ready. The minimum payment \
+                         of ${}.{:02} is due in {} days.",
+                        card.network,
+                        masked(&card.number),
+                        rng.gen_range(25..180),
+                        rng.gen_range(0..100),
+                        rng.gen_range(10..25)
+                    ),
+                ],
+                links: vec![format!("https://www.{}/cards/statements", bank_site)],
+            });
+        }
+
+        // Withdrawals to the wallets
+        let (exchange, exchange_name) = self.first_site(CredentialType::CryptoExchange, persona);
+        for wallet in &self.wallets {
+            let amount = match wallet.network {
+                CryptoNetwork::Bitcoin => format!("0.{:04} BTC", rng.gen_range(100..9000)),
+                CryptoNetwork::Ethereum => {
+                    format!("{}.{:03} ETH", rng.gen_range(0..3), rng.gen_range(0..1000))
+                }
+                CryptoNetwork::Litecoin => {
+                    format!("{}.{:02} LTC", rng.gen_range(1..40), rng.gen_range(0..100))
+                }
+                CryptoNetwork::Dogecoin => format!("{} DOGE", rng.gen_range(500..20_000)),
+            };
+            drafts.push(Draft {
+                kind: MailKind::ExchangeConfirmation,
+                honeypot_ids: vec![wallet.id],
+                from: address(&exchange_name, &format!("no-reply@{}", exchange)),
+                subject: format!("Your withdrawal of {} is complete", amount),
+                paragraphs: vec![
+                    format!("Hi {},", persona.first_name),
+                    format!(
+                        "You sent {} to your {} wallet {}. If this wasn't you, \
+                         lock your account right away.",
+                        amount, wallet.network, wallet.address
+                    ),
+                ],
+                links: vec![
+                    format!("https://www.{}/transactions", exchange),
+                    format!("https://www.{}/security/lock", exchange),
+                ],
+            });
+        }
+
+        // Password resets for the logins
+        for credential in &self.credentials {
+            let name = sender(credential);
+            drafts.push(Draft {
+                kind: MailKind::PasswordReset,
+                honeypot_ids: vec![credential.id],
+                from: address(&name, &format!("no-reply@{}", credential.site)),
+                subject: format!("Reset your {} password", name),
+                paragraphs: vec![
+                    format!("Hi {},", persona.first_name),
+                    format!(
+                        "Someone asked to reset the password for {}. If it was you, \
+                         use the link below. It expires in 24 hours.",
+                        credential.username
+                    ),
+                ],
+                links: vec![format!(
+                    "https://www.{}/reset-password?token={}",
+                    credential.site,
+                    crate::random_id(rng).simple()
+                )],
+            });
+        }
+
+        let midnight = self.created_at.date_naive().and_hms_opt(0, 0, 0).unwrap();
+        let mut messages: Vec<MailMessage> = drafts
+            .into_iter()
+            .map(|draft| {
+                // Some day in the last four months, between 7am and 10pm
+                let date = midnight.and_utc() - Duration::days(rng.gen_range(1..=120))
+                    + Duration::seconds(rng.gen_range(7 * 3600..22 * 3600));
+                draft.finish(&self.tracking_base, &to, date, rng)
+            })
+            .collect();
+        messages.sort_by_key(|message| message.date);
+        messages
+    }
+
+    /// Domain and name of the kit's first site of `kind`, or of the first
+    /// built-in one for the persona's locale.
+    fn first_site(&self, kind: CredentialType, persona: &Persona) -> (String, String) {
+        match self.credentials.iter().find(|c| c.credential_type == kind) {
+            Some(credential) => (credential.site.clone(), sender(credential)),
+            None => {
+                let site = kind.sites(persona.locale).swap_remove(0);
+                (site.domain, site.display_name)
+            }
+        }
+    }
+
+    /// The persona's email: the kit's webmail login if it has one.
+    fn email_address(&self, persona: &Persona) -> String {
+        self.credentials
+            .iter()
+            .find(|c| c.credential_type == CredentialType::EmailLogin && c.username.contains('@'))
+            .map_or_else(
+                || format!("{}@gmail.com", persona.handle()),
+                |c| c.username.clone(),
+            )
+    }
+}
+
+/// A message before it has an id, a date and tracking.
+struct Draft {
+    kind: MailKind,
+    honeypot_ids: Vec<Uuid>,
+    from: String,
+    subject: String,
+    paragraphs: Vec<String>,
+    links: Vec<String>,
+}
+
+impl Draft {
+    fn finish(
+        self,
+        tracking_base: &str,
+        to: &str,
+        date: DateTime<Utc>,
+        rng: &mut impl Rng,
+    ) -> MailMessage {
+        let id = crate::random_id(rng);
+        let tracking_pixel = format!("{}/{}/open.gif", tracking_base, id);
+        let links: Vec<String> = self
+            .links
+            .iter()
+            .enumerate()
+            .map(|(n, target)| {
+                let target: String =
+                    url::form_urlencoded::byte_serialize(target.as_bytes()).collect();
+                format!("{}/{}/click/{}?url={}", tracking_base, id, n, target)
+            })
+            .collect();
+
+        let mut text = self.paragraphs.join("\n\n");
+        let mut html = String::new();
+        for paragraph in &self.paragraphs {
+            html.push_str(&format!("<p>{}</p>\n", escape_html(paragraph)));
+        }
+        for link in &links {
+            text.push_str(&format!("\n\n{}", link));
+            html.push_str(&format!(
+                "<p><a href=\"{0}\">{0}</a></p>\n",
+                escape_html(link)
+            ));
+        }
+        html.push_str(&format!(
+            "<img src=\"{}\" width=\"1\" height=\"1\" alt=\"\">\n",
+            tracking_pixel
+        ));
+
+        MailMessage {
+            id,
+            kind: self.kind,
+            honeypot_ids: self.honeypot_ids,
+            from: self.from,
+            to: to.to_string(),
+            subject: self.subject,
+            date,
+            tracking_pixel,
+            links,
+            text,
+            html: format!("<html><body>\n{}</body></html>", html),
+        }
+    }
+}
+
+/// A `Name <address>` mailbox, the name encoded if it isn't ASCII.
+fn address(name: &str, email: &str) -> String {
+    format!("{} <{}>", encode_header(name), email)
+}
+
+/// A header value as RFC 2047 wants it: as is if ASCII, base64 otherwise.
+fn encode_header(value: &str) -> String {
+    if value.is_ascii() {
+        value.to_string()
+    } else {
+        let encoded = base64::engine::general_purpose::STANDARD.encode(value);
+        format!("=?UTF-8?B?{}?=", encoded)
+    }
+}
+
+/// The company behind a login's site, as it signs its mail.
+fn sender(credential: &HoneypotCredential) -> String {
+    credential
+        .site_name
+        .clone()
+        .unwrap_or_else(|| company(&credential.site))
+}
+
+/// The company behind a site: its name in the built-in catalogs, or the
+/// domain's first label, capitalized.
+fn company(site: &str) -> String {
+    if let Some(name) = crate::sites::display_name(site) {
+        return name.to_string();
+    }
+    let name = site.split('.').next().unwrap_or(site);
+    let mut chars = name.chars();
+    chars.next().map_or_else(String::new, |first| {
+        first.to_uppercase().chain(chars).collect()
+    })
+}
+
+/// All but the last four digits hidden, as banks write account numbers.
+fn masked(number: &str) -> String {
+    let digits: String = number.chars().filter(char::is_ascii_digit).collect();
+    format!("ending in {}", &digits[digits.len().saturating_sub(4)..])
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Locale;
+    use mail_parser::MessageParser;
+
+    #[test]
+    fn test_messages_parse() {
+        for locale in [Locale::EnUs, Locale::IsIs] {
+            let kit = HoneypotKit::generate_default_kit("test-user", Some(locale));
+            let persona = kit.persona.as_ref().unwrap();
+            assert_eq!(
+                kit.mail.len(),
+                1 + kit.cards.len() + kit.wallets.len() + kit.credentials.len()
+            );
+
+            for message in &kit.mail {
+                let eml = message.to_eml();
+                assert!(eml.lines().all(|line| line.len() <= 998));
+                let parsed = MessageParser::default().parse(eml.as_bytes()).unwrap();
+
+                assert_eq!(parsed.subject(), Some(message.subject.as_str()));
+                assert_eq!(
+                    parsed.to().unwrap().first().unwrap().name(),
+                    Some(persona.full_name().as_str())
+                );
+                let date = parsed.date().unwrap();
+                assert_eq!(date.to_timestamp(), message.date.timestamp());
+                let message_id = parsed.message_id().unwrap();
+                assert!(message_id.starts_with(&format!("{}@", message.id)));
+
+                let text = parsed.body_text(0).unwrap();
+                let html = parsed.body_html(0).unwrap();
+                assert!(html.contains(&message.tracking_pixel));
+                assert!(message.tracking_pixel.starts_with(&kit.tracking_base));
+                assert!(!message.links.is_empty());
+                for link in &message.links {
+                    assert!(
+                        link.starts_with(&format!("{}/{}/click/", kit.tracking_base, message.id))
+                    );
+                    assert!(text.contains(link.as_str()));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_mail_mentions_kit() {
+        let kit = HoneypotKit::generate_with_seed("test-user", 11);
+        let text: String = kit.mail.iter().map(|m| m.text.clone()).collect();
+
+        for wallet in &kit.wallets {
+            assert!(text.contains(&wallet.address));
+        }
+        for card in &kit.cards {
+            assert!(text.contains(&card.number[card.number.len() - 4..]));
+        }
+        for credential in &kit.credentials {
+            assert!(text.contains(&credential.username));
+        }
+
+        // Unique tracking, dates in order over recent months
+        let mut pixels: Vec<_> = kit.mail.iter().map(|m| &m.tracking_pixel).collect();
+        pixels.dedup();
+        assert_eq!(pixels.len(), kit.mail.len());
+        assert!(kit.mail.windows(2).all(|w| w[0].date <= w[1].date));
+        for message in &kit.mail {
+            let age = kit.created_at - message.date;
+            assert!(age > Duration::zero() && age < Duration::days(121));
+        }
+
+        // The same seed gives the same inbox
+        let again = HoneypotKit::generate_with_seed("test-user", 11);
+        assert_eq!(kit.to_mbox(), again.to_mbox());
+    }
+
+    #[test]
+    fn test_mbox() {
+        let kit = HoneypotKit::generate_default_kit("test-user", None);
+        let mbox = kit.to_mbox();
+        let separators = mbox.lines().filter(|l| l.starts_with("From ")).count();
+        assert_eq!(separators, kit.mail.len());
+        assert!(!mbox.contains('\r'));
+    }
+
+    #[test]
+    fn test_encode_header() {
+        assert_eq!(
+            encode_header("Anna Jónsdóttir"),
+            "=?UTF-8?B?QW5uYSBKw7Nuc2TDs3R0aXI=?="
+        );
+        assert_eq!(encode_header("Plain"), "Plain");
+        assert_eq!(company("bankofamerica.com"), "Bank of America");
+        assert_eq!(company("coinbase.com"), "Coinbase");
+        assert_eq!(masked("Routing 1234567890123"), "ending in 0123");
+    }
+}
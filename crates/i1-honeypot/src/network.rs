@@ -0,0 +1,412 @@
+//! What a sandbox should look like from the network.
+//!
+//! A kit makes a machine look lived-in from the inside; a
+//! [`NetworkDecoyProfile`] does the same from the outside. It plans the
+//! devices a home or small office would have - a router, a NAS, a Windows
+//! machine - and the services each exposes, with the banners they would
+//! send. Every banner is one the built-in fingerprint table identifies as
+//! the product the listener claims to be, so a scanner sees a network
+//! that agrees with itself.
+//!
+//! The plan serializes for whatever runs the listeners. The HTTPS ones can
+//! be served by [`decoy_service`](crate::decoy_service) as they are: see
+//! [`DecoyListener::decoy_config`].
+
+use std::net::{Ipv6Addr, SocketAddr};
+
+use i1_core::fingerprints;
+use i1_core::Transport;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::persona::ascii_fold;
+use crate::{DecoyConfig, DecoyKind, Persona};
+
+/// What sort of network a profile imitates.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DecoyStyle {
+    /// A router, a NAS and a Windows PC with Remote Desktop on
+    #[default]
+    Home,
+    /// A router, a NAS and a Windows server publishing Remote Desktop
+    /// on the web
+    SmallOffice,
+}
+
+/// One service to expose.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DecoyListener {
+    /// Hostname of the device it belongs to
+    pub host: String,
+    pub port: u16,
+    pub protocol: Transport,
+    /// Protocol spoken, as the fingerprint table names it: `ssh`, `http`,
+    /// `smb`
+    pub service: String,
+    /// Product a scanner identifies from the banner
+    pub product: String,
+    pub version: Option<String>,
+    /// What a scanner records on connecting: a greeting, response headers,
+    /// or for binary protocols the summary scanners print
+    pub banner: String,
+    /// Whether the service is behind TLS
+    pub tls: bool,
+    /// The TLS decoy that can serve it, if any
+    pub decoy: Option<DecoyKind>,
+}
+
+impl DecoyListener {
+    /// The banner as a listener sends it: CRLF line endings, and a blank
+    /// line after HTTP headers. RDP, SMB and DNS answer in binary, so for
+    /// them this is the summary, for daemons that take a canned banner.
+    pub fn render_banner(&self) -> String {
+        let mut text = self.banner.replace("\r\n", "\n").replace('\n', "\r\n");
+        if !text.ends_with("\r\n") {
+            text.push_str("\r\n");
+        }
+        if self.service == "http" {
+            text.push_str("\r\n");
+        }
+        text
+    }
+
+    /// Settings for [`decoy_service`](crate::decoy_service) to serve this
+    /// listener on every interface, for the HTTPS logins it can pose as.
+    pub fn decoy_config(&self) -> Option<DecoyConfig> {
+        let kind = self.decoy?;
+        Some(
+            DecoyConfig::new(&self.host)
+                .kind(kind)
+                .bind(SocketAddr::from((Ipv6Addr::UNSPECIFIED, self.port))),
+        )
+    }
+}
+
+/// The devices and services of a made-up network.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NetworkDecoyProfile {
+    pub style: DecoyStyle,
+    /// Services to expose, device by device
+    pub listeners: Vec<DecoyListener>,
+}
+
+impl NetworkDecoyProfile {
+    /// Plan the network `persona` would run.
+    pub fn generate(persona: &Persona, style: DecoyStyle) -> Self {
+        Self::generate_with_rng(persona, style, &mut rand::thread_rng())
+    }
+
+    /// Plan from a seed: the same seed gives the same network.
+    pub fn generate_with_seed(persona: &Persona, style: DecoyStyle, seed: u64) -> Self {
+        Self::generate_with_rng(persona, style, &mut StdRng::seed_from_u64(seed))
+    }
+
+    /// Plan from `rng`, so a seeded RNG gives the same network.
+    pub fn generate_with_rng(persona: &Persona, style: DecoyStyle, rng: &mut impl Rng) -> Self {
+        let first = label(&persona.first_name);
+        let last = label(&persona.last_name);
+
+        let mut plan = router(rng);
+        plan.extend(nas(&format!("{last}-nas"), rng));
+        match style {
+            DecoyStyle::Home => {
+                // Windows names a PC DESKTOP-XXXXXXX until someone renames it
+                let host = if rng.gen_bool(0.5) {
+                    format!("{}-PC", first.to_uppercase())
+                } else {
+                    format!("DESKTOP-{}", random_upper(rng, 7))
+                };
+                plan.extend(windows(&host, WINDOWS_DESKTOPS, None, rng));
+            }
+            DecoyStyle::SmallOffice => {
+                let host = format!("{}-SRV01", last.to_uppercase());
+                plan.extend(windows(
+                    &host,
+                    WINDOWS_SERVERS,
+                    Some(DecoyKind::RdpWeb),
+                    rng,
+                ));
+            }
+        }
+
+        let listeners = plan.into_iter().map(Planned::identify).collect();
+        Self { style, listeners }
+    }
+}
+
+/// A listener before the fingerprint table has named what it runs.
+struct Planned {
+    host: String,
+    port: u16,
+    banner: String,
+    tls: bool,
+    decoy: Option<DecoyKind>,
+}
+
+impl Planned {
+    fn new(host: &str, port: u16, banner: String) -> Self {
+        Self {
+            host: host.to_string(),
+            port,
+            banner,
+            tls: false,
+            decoy: None,
+        }
+    }
+
+    /// The HTTPS login `kind` serves.
+    fn login(host: &str, port: u16, kind: DecoyKind) -> Self {
+        Self {
+            tls: true,
+            decoy: Some(kind),
+            ..Self::new(host, port, http(kind.server()))
+        }
+    }
+
+    fn identify(self) -> DecoyListener {
+        let found = fingerprints::match_banner(self.port, &self.banner)
+            .expect("decoy banners match a built-in fingerprint");
+        DecoyListener {
+            host: self.host,
+            port: self.port,
+            protocol: found.transport,
+            service: found.service,
+            product: found.product,
+            version: found.version,
+            banner: self.banner,
+            tls: self.tls,
+            decoy: self.decoy,
+        }
+    }
+}
+
+/// Versions the devices ship with, a few years' worth each
+const DNSMASQ: &[&str] = &["2.80", "2.85", "2.89"];
+const DROPBEAR: &[&str] = &["2019.78", "2020.81", "2022.83"];
+const OPENSSH: &[&str] = &["7.4", "8.2p1", "8.9p1"];
+const PROFTPD: &[&str] = &["1.3.5e", "1.3.6", "1.3.7a"];
+const SAMBA: &[&str] = &["4.10.18", "4.15.13", "4.17.12"];
+
+/// `OS` lines of Windows SMB banners
+const WINDOWS_DESKTOPS: &[&str] = &["Windows 10 Pro 19045", "Windows 11 Pro 22631"];
+const WINDOWS_SERVERS: &[&str] = &[
+    "Windows Server 2016 Standard 14393",
+    "Windows Server 2019 Standard 17763",
+    "Windows Server 2022 Standard 20348",
+];
+
+/// An X.224 connection confirm, the way scanners print it
+const RDP: &str = "Remote Desktop Protocol\n\
+    \\x03\\x00\\x00\\x13\\x0e\\xd0\\x00\\x00\\x124\\x00\\x02\\x1f\\x08\\x00\\x02\\x00\\x00\\x00";
+
+/// The gateway: its admin page over HTTP and HTTPS, DNS for the LAN, and
+/// sometimes the SSH its firmware ships.
+fn router(rng: &mut impl Rng) -> Vec<Planned> {
+    const HOST: &str = "router.lan";
+    let kind = DecoyKind::RouterAdmin;
+    let mut plan = vec![
+        Planned::new(HOST, 80, http(kind.server())),
+        Planned::login(HOST, 443, kind),
+        Planned::new(
+            HOST,
+            53,
+            format!(
+                "\nRecursion: enabled\nResolver ID: dnsmasq-{}",
+                pick(rng, DNSMASQ)
+            ),
+        ),
+    ];
+    if rng.gen_bool(0.5) {
+        let banner = format!("SSH-2.0-dropbear_{}", pick(rng, DROPBEAR));
+        plan.push(Planned::new(HOST, 22, banner));
+    }
+    plan
+}
+
+/// A consumer NAS: its storage manager, file shares, SSH and sometimes FTP.
+fn nas(host: &str, rng: &mut impl Rng) -> Vec<Planned> {
+    let kind = DecoyKind::Nas;
+    let mut plan = vec![
+        Planned::new(host, 5000, http(kind.server())),
+        Planned::login(host, 5001, kind),
+        Planned::new(
+            host,
+            445,
+            smb(&format!("Software: Samba {}", pick(rng, SAMBA))),
+        ),
+        Planned::new(host, 22, format!("SSH-2.0-OpenSSH_{}", pick(rng, OPENSSH))),
+    ];
+    if rng.gen_bool(0.5) {
+        let banner = format!("220 ProFTPD {} Server ({host})", pick(rng, PROFTPD));
+        plan.push(Planned::new(host, 21, banner));
+    }
+    plan
+}
+
+/// A Windows machine sharing files and open to Remote Desktop, and to RD
+/// Web Access too when `web` is given.
+fn windows(host: &str, os: &[&str], web: Option<DecoyKind>, rng: &mut impl Rng) -> Vec<Planned> {
+    let mut plan = vec![
+        Planned::new(host, 3389, RDP.to_string()),
+        Planned::new(host, 445, smb(&format!("OS: {}", pick(rng, os)))),
+    ];
+    if let Some(kind) = web {
+        plan.push(Planned::new(host, 80, http(kind.server())));
+        plan.push(Planned::login(host, 443, kind));
+    }
+    plan
+}
+
+fn http(server: &str) -> String {
+    format!("HTTP/1.1 200 OK\r\nServer: {server}\r\nContent-Type: text/html\r\n")
+}
+
+/// An SMB summary with `software`, its `Software` or `OS` line
+fn smb(software: &str) -> String {
+    format!("SMB Status:\n  Authentication: enabled\n  SMB Version: 2\n  {software}\n")
+}
+
+/// A name as a hostname label: ASCII letters and digits only
+fn label(name: &str) -> String {
+    ascii_fold(name)
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .collect::<String>()
+        .to_lowercase()
+}
+
+fn random_upper(rng: &mut impl Rng, length: usize) -> String {
+    const CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+    (0..length)
+        .map(|_| char::from(CHARSET[rng.gen_range(0..CHARSET.len())]))
+        .collect()
+}
+
+fn pick<'a>(rng: &mut impl Rng, items: &[&'a str]) -> &'a str {
+    items[rng.gen_range(0..items.len())]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Locale;
+
+    /// The service a scanner expects on each port the profiles use
+    fn usual_service(port: u16) -> &'static str {
+        match port {
+            21 => "ftp",
+            22 => "ssh",
+            53 => "dns",
+            80 | 443 | 5000 | 5001 => "http",
+            445 => "smb",
+            3389 => "rdp",
+            _ => panic!("unexpected port {port}"),
+        }
+    }
+
+    #[test]
+    fn test_banners_fit_ports() {
+        for seed in 0..32 {
+            let persona =
+                Persona::generate_with_rng(Locale::IsIs, &mut StdRng::seed_from_u64(seed));
+            for style in [DecoyStyle::Home, DecoyStyle::SmallOffice] {
+                let profile = NetworkDecoyProfile::generate_with_seed(&persona, style, seed);
+                for listener in &profile.listeners {
+                    let found =
+                        fingerprints::match_banner(listener.port, &listener.banner).unwrap();
+                    assert_eq!(found.product, listener.product, "{listener:?}");
+                    assert_eq!(
+                        listener.service,
+                        usual_service(listener.port),
+                        "{listener:?}"
+                    );
+                    assert!(listener.host.is_ascii(), "{}", listener.host);
+                    if let Some(kind) = listener.decoy {
+                        // The TLS decoy sends the Server header the banner claims
+                        assert!(listener.tls);
+                        assert!(listener.banner.contains(kind.server()), "{listener:?}");
+                    }
+                }
+
+                let mut ports: Vec<_> = profile
+                    .listeners
+                    .iter()
+                    .map(|l| (&l.host, l.port))
+                    .collect();
+                ports.sort_unstable();
+                ports.dedup();
+                assert_eq!(
+                    ports.len(),
+                    profile.listeners.len(),
+                    "one listener per host and port"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_styles() {
+        let persona = Persona::generate(Locale::EnUs);
+        let last = label(&persona.last_name);
+
+        let home = NetworkDecoyProfile::generate(&persona, DecoyStyle::Home);
+        let nas = home.listeners.iter().find(|l| l.port == 5001).unwrap();
+        assert_eq!(nas.host, format!("{last}-nas"));
+        assert!(home.listeners.iter().any(|l| l.product == "Samba"));
+        let rdp = home.listeners.iter().find(|l| l.port == 3389).unwrap();
+        assert!(home
+            .listeners
+            .iter()
+            .any(|l| l.host == rdp.host && l.product == "Microsoft Windows SMB"));
+        assert!(!home.listeners.iter().any(|l| l.product == "Microsoft IIS"));
+
+        let office = NetworkDecoyProfile::generate(&persona, DecoyStyle::SmallOffice);
+        let web = office
+            .listeners
+            .iter()
+            .find(|l| l.decoy == Some(DecoyKind::RdpWeb))
+            .unwrap();
+        assert_eq!(web.host, format!("{}-SRV01", last.to_uppercase()));
+        assert_eq!((web.port, web.product.as_str()), (443, "Microsoft IIS"));
+        let config = web.decoy_config().unwrap();
+        assert_eq!(config.hostname, web.host);
+        assert_eq!(config.bind.port(), 443);
+        assert!(config.bind.is_ipv6() && config.bind.ip().is_unspecified());
+    }
+
+    #[test]
+    fn test_seeded_and_serialized() {
+        let persona = Persona::generate(Locale::EnUs);
+        let profile = NetworkDecoyProfile::generate_with_seed(&persona, DecoyStyle::Home, 7);
+        assert_eq!(
+            profile,
+            NetworkDecoyProfile::generate_with_seed(&persona, DecoyStyle::Home, 7)
+        );
+
+        let json = serde_json::to_string(&profile).unwrap();
+        assert!(json.contains(r#""style":"home""#), "{json}");
+        assert!(json.contains(r#""protocol":"udp""#), "{json}");
+        let parsed: NetworkDecoyProfile = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, profile);
+    }
+
+    #[test]
+    fn test_render_banner() {
+        let persona = Persona::generate(Locale::EnUs);
+        let profile = NetworkDecoyProfile::generate_with_seed(&persona, DecoyStyle::Home, 1);
+        let admin = profile.listeners.iter().find(|l| l.port == 80).unwrap();
+        assert_eq!(
+            admin.render_banner(),
+            "HTTP/1.1 200 OK\r\nServer: mini_httpd/1.30 26Oct2018\r\nContent-Type: text/html\r\n\r\n"
+        );
+        let ssh = profile
+            .listeners
+            .iter()
+            .find(|l| l.port == 22 && l.host.ends_with("-nas"))
+            .unwrap();
+        assert_eq!(ssh.render_banner(), format!("{}\r\n", ssh.banner));
+        assert!(admin.decoy_config().is_none());
+    }
+}
@@ -0,0 +1,346 @@
+//! Word and Excel files for trap documents.
+//!
+//! An Office file is a zip of XML parts tied together by relationships, and
+//! a relationship may point outside the file. Word fetches a document's
+//! attached template when it opens it, and Excel fetches a linked picture,
+//! so pointing either at a tracking URL makes opening the file a tripwire.
+//! The parts here are the fewest Office needs to open the file cleanly.
+
+const CONTENT_TYPES: &str = "http://schemas.openxmlformats.org/package/2006/content-types";
+const PACKAGE_RELS: &str = "http://schemas.openxmlformats.org/package/2006/relationships";
+const OFFICE_RELS: &str = "http://schemas.openxmlformats.org/officeDocument/2006/relationships";
+const WORDPROCESSING: &str = "http://schemas.openxmlformats.org/wordprocessingml/2006/main";
+const SPREADSHEET: &str = "http://schemas.openxmlformats.org/spreadsheetml/2006/main";
+const DRAWING: &str = "http://schemas.openxmlformats.org/drawingml/2006/main";
+const SPREADSHEET_DRAWING: &str =
+    "http://schemas.openxmlformats.org/drawingml/2006/spreadsheetDrawing";
+
+const XML_DECLARATION: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\r\n";
+
+/// A `.docx` with `title` as a heading over a table of `rows`, whose
+/// attached template is `template_url`.
+pub(crate) fn docx(
+    title: &str,
+    author: &str,
+    rows: &[(String, String)],
+    template_url: &str,
+) -> Vec<u8> {
+    let mut table = String::new();
+    for (label, value) in rows {
+        table.push_str("<w:tr>");
+        for cell in [label, value] {
+            table.push_str(&format!(
+                "<w:tc><w:p><w:r><w:t xml:space=\"preserve\">{}</w:t></w:r></w:p></w:tc>",
+                escape_xml(cell)
+            ));
+        }
+        table.push_str("</w:tr>");
+    }
+    let document = format!(
+        "{}<w:document xmlns:w=\"{}\"><w:body>\
+         <w:p><w:r><w:rPr><w:b/><w:sz w:val=\"32\"/></w:rPr><w:t>{}</w:t></w:r></w:p>\
+         <w:tbl><w:tblPr><w:tblW w:w=\"0\" w:type=\"auto\"/></w:tblPr>{}</w:tbl>\
+         <w:p/></w:body></w:document>",
+        XML_DECLARATION,
+        WORDPROCESSING,
+        escape_xml(title),
+        table
+    );
+    let settings = format!(
+        "{}<w:settings xmlns:w=\"{}\" xmlns:r=\"{}\">\
+         <w:attachedTemplate r:id=\"rId1\"/></w:settings>",
+        XML_DECLARATION, WORDPROCESSING, OFFICE_RELS
+    );
+
+    let mut zip = ZipWriter::default();
+    zip.add(
+        "[Content_Types].xml",
+        &content_types(&[
+            ("/word/document.xml", "wordprocessingml.document.main+xml"),
+            ("/word/settings.xml", "wordprocessingml.settings+xml"),
+        ]),
+    );
+    zip.add("_rels/.rels", &package_rels("word/document.xml"));
+    zip.add("docProps/core.xml", &core_properties(title, author));
+    zip.add("word/document.xml", &document);
+    zip.add(
+        "word/_rels/document.xml.rels",
+        &relationships(&[("settings", "settings.xml", false)]),
+    );
+    zip.add("word/settings.xml", &settings);
+    zip.add(
+        "word/_rels/settings.xml.rels",
+        &relationships(&[("attachedTemplate", template_url, true)]),
+    );
+    zip.finish()
+}
+
+/// A `.xlsx` with `title` over two columns of `rows`, and a one-pixel
+/// picture linked from `image_url`. Values that parse as numbers are
+/// written as numbers.
+pub(crate) fn xlsx(
+    title: &str,
+    author: &str,
+    rows: &[(String, String)],
+    image_url: &str,
+) -> Vec<u8> {
+    let mut sheet_data = format!("<row r=\"1\">{}</row>", text_cell("A1", title));
+    for (n, (label, value)) in rows.iter().enumerate() {
+        let row = n + 3;
+        let value_cell = match value.parse::<f64>() {
+            Ok(number) => format!("<c r=\"B{}\"><v>{}</v></c>", row, number),
+            Err(_) => text_cell(&format!("B{}", row), value),
+        };
+        sheet_data.push_str(&format!(
+            "<row r=\"{}\">{}{}</row>",
+            row,
+            text_cell(&format!("A{}", row), label),
+            value_cell
+        ));
+    }
+    let sheet = format!(
+        "{}<worksheet xmlns=\"{}\" xmlns:r=\"{}\"><sheetData>{}</sheetData>\
+         <drawing r:id=\"rId1\"/></worksheet>",
+        XML_DECLARATION, SPREADSHEET, OFFICE_RELS, sheet_data
+    );
+    let workbook = format!(
+        "{}<workbook xmlns=\"{}\" xmlns:r=\"{}\"><sheets>\
+         <sheet name=\"{}\" sheetId=\"1\" r:id=\"rId1\"/></sheets></workbook>",
+        XML_DECLARATION,
+        SPREADSHEET,
+        OFFICE_RELS,
+        escape_xml(&sheet_name(title))
+    );
+    let drawing = format!(
+        "{}<xdr:wsDr xmlns:xdr=\"{}\" xmlns:a=\"{}\" xmlns:r=\"{}\"><xdr:oneCellAnchor>\
+         <xdr:from><xdr:col>3</xdr:col><xdr:colOff>0</xdr:colOff>\
+         <xdr:row>0</xdr:row><xdr:rowOff>0</xdr:rowOff></xdr:from>\
+         <xdr:ext cx=\"9525\" cy=\"9525\"/><xdr:pic><xdr:nvPicPr>\
+         <xdr:cNvPr id=\"2\" name=\"Logo\"/><xdr:cNvPicPr/></xdr:nvPicPr>\
+         <xdr:blipFill><a:blip r:link=\"rId1\"/><a:stretch><a:fillRect/></a:stretch></xdr:blipFill>\
+         <xdr:spPr><a:prstGeom prst=\"rect\"><a:avLst/></a:prstGeom></xdr:spPr>\
+         </xdr:pic><xdr:clientData/></xdr:oneCellAnchor></xdr:wsDr>",
+        XML_DECLARATION, SPREADSHEET_DRAWING, DRAWING, OFFICE_RELS
+    );
+
+    let mut zip = ZipWriter::default();
+    zip.add(
+        "[Content_Types].xml",
+        &content_types(&[
+            ("/xl/workbook.xml", "spreadsheetml.sheet.main+xml"),
+            ("/xl/worksheets/sheet1.xml", "spreadsheetml.worksheet+xml"),
+            ("/xl/drawings/drawing1.xml", "drawing+xml"),
+        ]),
+    );
+    zip.add("_rels/.rels", &package_rels("xl/workbook.xml"));
+    zip.add("docProps/core.xml", &core_properties(title, author));
+    zip.add("xl/workbook.xml", &workbook);
+    zip.add(
+        "xl/_rels/workbook.xml.rels",
+        &relationships(&[("worksheet", "worksheets/sheet1.xml", false)]),
+    );
+    zip.add("xl/worksheets/sheet1.xml", &sheet);
+    zip.add(
+        "xl/worksheets/_rels/sheet1.xml.rels",
+        &relationships(&[("drawing", "../drawings/drawing1.xml", false)]),
+    );
+    zip.add("xl/drawings/drawing1.xml", &drawing);
+    zip.add(
+        "xl/drawings/_rels/drawing1.xml.rels",
+        &relationships(&[("image", image_url, true)]),
+    );
+    zip.finish()
+}
+
+/// `[Content_Types].xml`, with `overrides` as part names and the end of
+/// their Office content types.
+fn content_types(overrides: &[(&str, &str)]) -> String {
+    let mut types = format!(
+        "{}<Types xmlns=\"{}\">\
+         <Default Extension=\"rels\" \
+         ContentType=\"application/vnd.openxmlformats-package.relationships+xml\"/>\
+         <Default Extension=\"xml\" ContentType=\"application/xml\"/>\
+         <Override PartName=\"/docProps/core.xml\" \
+         ContentType=\"application/vnd.openxmlformats-package.core-properties+xml\"/>",
+        XML_DECLARATION, CONTENT_TYPES
+    );
+    for (part, content_type) in overrides {
+        types.push_str(&format!(
+            "<Override PartName=\"{}\" \
+             ContentType=\"application/vnd.openxmlformats-officedocument.{}\"/>",
+            part, content_type
+        ));
+    }
+    types.push_str("</Types>");
+    types
+}
+
+/// The package's own relationships: its main part and its properties.
+fn package_rels(main: &str) -> String {
+    format!(
+        "{}<Relationships xmlns=\"{}\">\
+         <Relationship Id=\"rId1\" Type=\"{}/officeDocument\" Target=\"{}\"/>\
+         <Relationship Id=\"rId2\" \
+         Type=\"{}/metadata/core-properties\" Target=\"docProps/core.xml\"/>\
+         </Relationships>",
+        XML_DECLARATION, PACKAGE_RELS, OFFICE_RELS, main, PACKAGE_RELS
+    )
+}
+
+/// A part's relationships, numbered from `rId1`, as type, target and
+/// whether the target is outside the file.
+fn relationships(targets: &[(&str, &str, bool)]) -> String {
+    let mut rels = format!(
+        "{}<Relationships xmlns=\"{}\">",
+        XML_DECLARATION, PACKAGE_RELS
+    );
+    for (n, (kind, target, external)) in targets.iter().enumerate() {
+        rels.push_str(&format!(
+            "<Relationship Id=\"rId{}\" Type=\"{}/{}\" Target=\"{}\"{}/>",
+            n + 1,
+            OFFICE_RELS,
+            kind,
+            escape_xml(target),
+            if *external {
+                " TargetMode=\"External\""
+            } else {
+                ""
+            }
+        ));
+    }
+    rels.push_str("</Relationships>");
+    rels
+}
+
+/// Title and author, as File > Properties shows them.
+fn core_properties(title: &str, author: &str) -> String {
+    format!(
+        "{}<cp:coreProperties \
+         xmlns:cp=\"http://schemas.openxmlformats.org/package/2006/metadata/core-properties\" \
+         xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\
+         <dc:title>{}</dc:title><dc:creator>{}</dc:creator>\
+         <cp:lastModifiedBy>{}</cp:lastModifiedBy></cp:coreProperties>",
+        XML_DECLARATION,
+        escape_xml(title),
+        escape_xml(author),
+        escape_xml(author)
+    )
+}
+
+fn text_cell(reference: &str, text: &str) -> String {
+    format!(
+        "<c r=\"{}\" t=\"inlineStr\"><is><t xml:space=\"preserve\">{}</t></is></c>",
+        reference,
+        escape_xml(text)
+    )
+}
+
+/// A worksheet name Excel accepts: at most 31 characters, none of `[]:*?/\`.
+fn sheet_name(title: &str) -> String {
+    title
+        .chars()
+        .filter(|c| !"[]:*?/\\".contains(*c))
+        .take(31)
+        .collect()
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Just enough of a zip writer for Office: stored entries, dated
+/// 1980-01-01 as Office dates its own.
+#[derive(Default)]
+struct ZipWriter {
+    data: Vec<u8>,
+    directory: Vec<u8>,
+    entries: u16,
+}
+
+impl ZipWriter {
+    fn add(&mut self, name: &str, content: &str) {
+        let content = content.as_bytes();
+        let crc = crc32(content);
+        let size = content.len() as u32;
+        let offset = self.data.len() as u32;
+        // Version 2.0, no flags, stored, time 00:00, date 1980-01-01
+        let common = [
+            &20u16.to_le_bytes()[..],
+            &0u16.to_le_bytes(),
+            &0u16.to_le_bytes(),
+            &0u16.to_le_bytes(),
+            &0x21u16.to_le_bytes(),
+            &crc.to_le_bytes(),
+            &size.to_le_bytes(),
+            &size.to_le_bytes(),
+            &(name.len() as u16).to_le_bytes(),
+            &0u16.to_le_bytes(),
+        ]
+        .concat();
+
+        self.data.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        self.data.extend_from_slice(&common);
+        self.data.extend_from_slice(name.as_bytes());
+        self.data.extend_from_slice(content);
+
+        self.directory
+            .extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+        self.directory.extend_from_slice(&20u16.to_le_bytes());
+        self.directory.extend_from_slice(&common);
+        // No comment, disk 0, no attributes, then where the entry starts
+        self.directory.extend_from_slice(&[0; 10]);
+        self.directory.extend_from_slice(&offset.to_le_bytes());
+        self.directory.extend_from_slice(name.as_bytes());
+        self.entries += 1;
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        let offset = self.data.len() as u32;
+        let size = self.directory.len() as u32;
+        self.data.append(&mut self.directory);
+        self.data.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+        self.data.extend_from_slice(&[0; 4]);
+        self.data.extend_from_slice(&self.entries.to_le_bytes());
+        self.data.extend_from_slice(&self.entries.to_le_bytes());
+        self.data.extend_from_slice(&size.to_le_bytes());
+        self.data.extend_from_slice(&offset.to_le_bytes());
+        self.data.extend_from_slice(&0u16.to_le_bytes());
+        self.data
+    }
+}
+
+/// CRC-32 as zip uses it (IEEE, reflected).
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn test_sheet_name() {
+        assert_eq!(sheet_name("Q3 [draft]: totals?"), "Q3 draft totals");
+        assert_eq!(sheet_name(&"x".repeat(40)).len(), 31);
+    }
+}
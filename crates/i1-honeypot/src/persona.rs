@@ -0,0 +1,389 @@
+//! The made-up person a kit belongs to.
+//!
+//! One persona per kit, so the name on the cards is the name in the
+//! passwords file, the documents and the SSH key: bait that hangs together.
+
+use std::str::FromStr;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::HoneypotError;
+
+/// Where a persona lives, which shapes names, addresses and phone numbers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Locale {
+    #[default]
+    #[serde(rename = "en-US")]
+    EnUs,
+    #[serde(rename = "is-IS")]
+    IsIs,
+}
+
+impl std::fmt::Display for Locale {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Locale::EnUs => write!(f, "en-US"),
+            Locale::IsIs => write!(f, "is-IS"),
+        }
+    }
+}
+
+impl Locale {
+    /// A family name from the locale's name pool: a woman's, since it
+    /// answers "mother's maiden name".
+    pub(crate) fn family_name(self, rng: &mut impl Rng) -> &'static str {
+        match self {
+            Locale::EnUs => pick(rng, US_LAST_NAMES),
+            // Icelandic women keep their own patronymic when they marry
+            Locale::IsIs => pick(rng, IS_PATRONYMICS).1,
+        }
+    }
+
+    /// A town from the locale's pool.
+    pub(crate) fn town(self, rng: &mut impl Rng) -> &'static str {
+        match self {
+            Locale::EnUs => pick(rng, US_CITIES).0,
+            Locale::IsIs => pick(rng, IS_TOWNS).1,
+        }
+    }
+
+    /// An employer from the locale's pool.
+    pub(crate) fn employer(self, rng: &mut impl Rng) -> &'static str {
+        match self {
+            Locale::EnUs => pick(rng, US_EMPLOYERS),
+            Locale::IsIs => pick(rng, IS_EMPLOYERS),
+        }
+    }
+}
+
+impl FromStr for Locale {
+    type Err = HoneypotError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().replace('_', "-").as_str() {
+            "en-us" => Ok(Locale::EnUs),
+            "is-is" => Ok(Locale::IsIs),
+            _ => Err(HoneypotError::InvalidConfig(format!(
+                "unknown locale '{}' (expected en-US or is-IS)",
+                s
+            ))),
+        }
+    }
+}
+
+/// A postal address.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Address {
+    /// Street and house number, in the locale's order
+    pub street: String,
+    /// City or town
+    pub city: String,
+    /// State, for countries that have them
+    pub region: Option<String>,
+    /// ZIP or postal code
+    pub postal_code: String,
+    /// Country name
+    pub country: String,
+}
+
+/// A consistent fake identity for a kit.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Persona {
+    /// Given name
+    pub first_name: String,
+    /// Family name (a patronymic in Iceland)
+    pub last_name: String,
+    /// Locale the persona was generated for
+    pub locale: Locale,
+    /// Home address
+    pub address: Address,
+    /// Phone number in international format
+    pub phone: String,
+    /// Where the persona works, as pay stubs and tax forms name it
+    #[serde(default)]
+    pub employer: Option<String>,
+}
+
+impl Persona {
+    /// Generate a new persona for a locale.
+    pub fn generate(locale: Locale) -> Self {
+        Self::generate_with_rng(locale, &mut rand::thread_rng())
+    }
+
+    /// Generate a persona from `rng`, so a seeded RNG gives the same one.
+    pub fn generate_with_rng(locale: Locale, rng: &mut impl Rng) -> Self {
+        match locale {
+            Locale::EnUs => american(rng),
+            Locale::IsIs => icelandic(rng),
+        }
+    }
+
+    /// First and last name.
+    pub fn full_name(&self) -> String {
+        format!("{} {}", self.first_name, self.last_name)
+    }
+
+    /// The name as a card carries it: capitals, without accents.
+    pub fn card_name(&self) -> String {
+        ascii_fold(&self.full_name()).to_uppercase()
+    }
+
+    /// `first.last` in lower-case ASCII, the stem of usernames and emails.
+    pub fn handle(&self) -> String {
+        format!(
+            "{}.{}",
+            ascii_fold(&self.first_name).to_lowercase(),
+            ascii_fold(&self.last_name).to_lowercase()
+        )
+    }
+
+    /// The address as it is written on an envelope in the persona's country.
+    pub fn formatted_address(&self) -> String {
+        let address = &self.address;
+        match self.locale {
+            Locale::EnUs => format!(
+                "{}\n{}, {} {}",
+                address.street,
+                address.city,
+                address.region.as_deref().unwrap_or_default(),
+                address.postal_code
+            ),
+            Locale::IsIs => format!(
+                "{}\n{} {}",
+                address.street, address.postal_code, address.city
+            ),
+        }
+    }
+}
+
+const US_FIRST_NAMES: &[&str] = &[
+    "James",
+    "Mary",
+    "John",
+    "Patricia",
+    "Robert",
+    "Jennifer",
+    "Michael",
+    "Linda",
+    "William",
+    "Elizabeth",
+    "David",
+    "Barbara",
+];
+const US_LAST_NAMES: &[&str] = &[
+    "Smith", "Johnson", "Williams", "Brown", "Jones", "Garcia", "Miller", "Davis", "Wilson",
+    "Anderson", "Thomas", "Taylor",
+];
+const US_STREETS: &[&str] = &[
+    "Maple",
+    "Oak",
+    "Cedar",
+    "Elm",
+    "Washington",
+    "Lake",
+    "Hill",
+    "Park",
+];
+/// City, state, first three ZIP digits and area code
+const US_CITIES: &[(&str, &str, &str, &str)] = &[
+    ("Springfield", "IL", "627", "217"),
+    ("Columbus", "OH", "432", "614"),
+    ("Tucson", "AZ", "857", "520"),
+    ("Raleigh", "NC", "276", "919"),
+    ("Boise", "ID", "837", "208"),
+    ("Omaha", "NE", "681", "402"),
+];
+/// Made up, so no real company's payroll turns up in the bait
+const US_EMPLOYERS: &[&str] = &[
+    "Lakeside Regional Medical Center",
+    "Cornerstone Logistics LLC",
+    "Midstate Insurance Group",
+    "Brightpath Elementary School District",
+    "Harbor Point Manufacturing Inc.",
+    "Summit Ridge Credit Union",
+];
+
+const IS_MEN: &[&str] = &[
+    "Jón",
+    "Sigurður",
+    "Guðmundur",
+    "Gunnar",
+    "Ólafur",
+    "Einar",
+    "Kristján",
+    "Stefán",
+];
+const IS_WOMEN: &[&str] = &[
+    "Guðrún",
+    "Anna",
+    "Kristín",
+    "Sigríður",
+    "Margrét",
+    "Helga",
+    "Sigrún",
+    "Ingibjörg",
+];
+/// A father's name as his son's and his daughter's last name
+const IS_PATRONYMICS: &[(&str, &str)] = &[
+    ("Jónsson", "Jónsdóttir"),
+    ("Sigurðsson", "Sigurðardóttir"),
+    ("Guðmundsson", "Guðmundsdóttir"),
+    ("Ólafsson", "Ólafsdóttir"),
+    ("Einarsson", "Einarsdóttir"),
+    ("Magnússon", "Magnúsdóttir"),
+];
+const IS_STREETS: &[&str] = &[
+    "Laugavegur",
+    "Hverfisgata",
+    "Skólavörðustígur",
+    "Bergstaðastræti",
+    "Hringbraut",
+    "Álfheimar",
+];
+const IS_TOWNS: &[(&str, &str)] = &[
+    ("101", "Reykjavík"),
+    ("105", "Reykjavík"),
+    ("200", "Kópavogur"),
+    ("220", "Hafnarfjörður"),
+    ("600", "Akureyri"),
+];
+const IS_EMPLOYERS: &[&str] = &[
+    "Norðurljós ehf.",
+    "Hafnarverk hf.",
+    "Brúarás Verkfræðistofa ehf.",
+    "Hraunberg Bókhald ehf.",
+];
+
+fn american(rng: &mut impl Rng) -> Persona {
+    let (city, state, zip, area) = pick(rng, US_CITIES);
+    let street = format!(
+        "{} {} {}",
+        rng.gen_range(100..9999),
+        pick(rng, US_STREETS),
+        pick(rng, &["St", "Ave", "Dr", "Ln", "Rd"])
+    );
+
+    Persona {
+        first_name: pick(rng, US_FIRST_NAMES).to_string(),
+        last_name: pick(rng, US_LAST_NAMES).to_string(),
+        locale: Locale::EnUs,
+        address: Address {
+            street,
+            city: city.to_string(),
+            region: Some(state.to_string()),
+            postal_code: format!("{}{:02}", zip, rng.gen_range(1..100)),
+            country: "United States".to_string(),
+        },
+        // 555-0100 to 555-0199 is set aside for fiction
+        phone: format!("+1 ({}) 555-01{:02}", area, rng.gen_range(0..100)),
+        employer: Some(Locale::EnUs.employer(rng).to_string()),
+    }
+}
+
+fn icelandic(rng: &mut impl Rng) -> Persona {
+    // Icelanders go by their father's name: his son or his daughter
+    let (son, daughter) = pick(rng, IS_PATRONYMICS);
+    let (first_name, last_name) = if rng.gen_bool(0.5) {
+        (pick(rng, IS_MEN), son)
+    } else {
+        (pick(rng, IS_WOMEN), daughter)
+    };
+    let (postal_code, town) = pick(rng, IS_TOWNS);
+
+    Persona {
+        first_name: first_name.to_string(),
+        last_name: last_name.to_string(),
+        locale: Locale::IsIs,
+        address: Address {
+            street: format!("{} {}", pick(rng, IS_STREETS), rng.gen_range(1..120)),
+            city: town.to_string(),
+            region: None,
+            postal_code: postal_code.to_string(),
+            country: "Iceland".to_string(),
+        },
+        // Mobile numbers start with 6, 7 or 8
+        phone: format!(
+            "+354 {}{:02} {:04}",
+            rng.gen_range(6..=8),
+            rng.gen_range(0..100),
+            rng.gen_range(0..10000)
+        ),
+        employer: Some(Locale::IsIs.employer(rng).to_string()),
+    }
+}
+
+fn pick<T: Copy>(rng: &mut impl Rng, items: &[T]) -> T {
+    items[rng.gen_range(0..items.len())]
+}
+
+/// Spell a name in ASCII, the way it ends up on cards and in email addresses.
+pub(crate) fn ascii_fold(name: &str) -> String {
+    let mut folded = String::with_capacity(name.len());
+    for c in name.chars() {
+        let plain = match c {
+            'á' => "a",
+            'Á' => "A",
+            'é' => "e",
+            'É' => "E",
+            'í' => "i",
+            'Í' => "I",
+            'ó' | 'ö' => "o",
+            'Ó' | 'Ö' => "O",
+            'ú' => "u",
+            'Ú' => "U",
+            'ý' => "y",
+            'Ý' => "Y",
+            'ð' => "d",
+            'Ð' => "D",
+            'þ' => "th",
+            'Þ' => "Th",
+            'æ' => "ae",
+            'Æ' => "Ae",
+            _ => {
+                folded.push(c);
+                continue;
+            }
+        };
+        folded.push_str(plain);
+    }
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locales() {
+        assert_eq!("en-US".parse::<Locale>().unwrap(), Locale::EnUs);
+        assert_eq!("is_is".parse::<Locale>().unwrap(), Locale::IsIs);
+        assert!("fr-FR".parse::<Locale>().is_err());
+        assert_eq!(Locale::IsIs.to_string(), "is-IS");
+
+        let american = Persona::generate(Locale::EnUs);
+        assert!(american.phone.starts_with("+1 ("));
+        assert_eq!(american.address.postal_code.len(), 5);
+        assert!(american.formatted_address().contains(", "));
+
+        let icelander = Persona::generate(Locale::IsIs);
+        assert!(icelander.phone.starts_with("+354 "));
+        assert!(icelander.last_name.ends_with("son") || icelander.last_name.ends_with("dóttir"));
+        let address = icelander.formatted_address();
+        assert!(address.ends_with(&format!(
+            "{} {}",
+            icelander.address.postal_code, icelander.address.city
+        )));
+    }
+
+    #[test]
+    fn test_ascii_names() {
+        let persona = Persona {
+            first_name: "Sigríður".to_string(),
+            last_name: "Þórðardóttir".to_string(),
+            ..Persona::generate(Locale::IsIs)
+        };
+        assert_eq!(persona.card_name(), "SIGRIDUR THORDARDOTTIR");
+        assert_eq!(persona.handle(), "sigridur.thordardottir");
+    }
+}
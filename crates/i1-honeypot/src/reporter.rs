@@ -0,0 +1,450 @@
+//! Delivering tripwire events to the i1.is API.
+//!
+//! Events are queued and sent in batches. With a spool directory each event
+//! is also written to disk until it is delivered, so a crash or a stretch
+//! offline loses nothing: the next reporter on that spool sends them.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use uuid::Uuid;
+
+use crate::{HoneypotError, TripwireEvent};
+
+/// Where events go unless the builder says otherwise.
+pub const DEFAULT_ENDPOINT: &str = "https://api.i1.is/v1/tripwire";
+
+/// Sends [`TripwireEvent`]s to a webhook endpoint.
+pub struct TripwireReporter {
+    client: reqwest::Client,
+    endpoint: String,
+    token: Option<String>,
+    batch_size: usize,
+    max_retries: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    spool_dir: Option<PathBuf>,
+    queue: Mutex<Vec<Queued>>,
+}
+
+/// An event waiting to be sent, and its spool file if it has one.
+struct Queued {
+    event: TripwireEvent,
+    spool_file: Option<PathBuf>,
+}
+
+impl TripwireReporter {
+    /// Create a builder for the reporter.
+    pub fn builder() -> TripwireReporterBuilder {
+        TripwireReporterBuilder::new()
+    }
+
+    /// Queue an event, and send the queue once it holds a full batch.
+    ///
+    /// An event that can't be delivered stays queued (and spooled) for the
+    /// next send, so an error here means "not yet", not "lost".
+    pub async fn report(&self, event: TripwireEvent) -> Result<(), HoneypotError> {
+        let spool_file = match &self.spool_dir {
+            Some(dir) => Some(spool(dir, &event)?),
+            None => None,
+        };
+
+        let full = {
+            let mut queue = self.queue.lock().unwrap();
+            queue.push(Queued { event, spool_file });
+            queue.len() >= self.batch_size
+        };
+        if full {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+
+    /// [`report`](Self::report) for callers without an async runtime.
+    ///
+    /// Panics if called from inside one; use `report` there.
+    pub fn report_blocking(&self, event: TripwireEvent) -> Result<(), HoneypotError> {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?
+            .block_on(self.report(event))
+    }
+
+    /// Send everything queued, a batch at a time, and return how many
+    /// events were delivered. A batch that fails goes back on the queue.
+    pub async fn flush(&self) -> Result<usize, HoneypotError> {
+        let mut delivered = 0;
+        loop {
+            let batch: Vec<Queued> = {
+                let mut queue = self.queue.lock().unwrap();
+                let size = queue.len().min(self.batch_size);
+                queue.drain(..size).collect()
+            };
+            if batch.is_empty() {
+                return Ok(delivered);
+            }
+
+            if let Err(e) = self.send(&batch).await {
+                let mut queue = self.queue.lock().unwrap();
+                let rest = std::mem::take(&mut *queue);
+                queue.extend(batch);
+                queue.extend(rest);
+                return Err(e);
+            }
+
+            delivered += batch.len();
+            for spool_file in batch.into_iter().filter_map(|queued| queued.spool_file) {
+                let _ = fs::remove_file(spool_file);
+            }
+        }
+    }
+
+    /// Number of events not delivered yet.
+    pub fn pending(&self) -> usize {
+        self.queue.lock().unwrap().len()
+    }
+
+    /// POST one batch, retrying server errors, rate limits and failed
+    /// connections with exponential backoff.
+    async fn send(&self, batch: &[Queued]) -> Result<(), HoneypotError> {
+        let events: Vec<&TripwireEvent> = batch.iter().map(|queued| &queued.event).collect();
+        let body = serde_json::json!({ "events": events });
+
+        let mut attempt = 0;
+        loop {
+            let mut request = self.client.post(&self.endpoint).json(&body);
+            if let Some(token) = &self.token {
+                request = request.bearer_auth(token);
+            }
+
+            let error = match request.send().await {
+                Ok(response) if response.status().is_success() => return Ok(()),
+                Ok(response) => {
+                    let status = response.status();
+                    let error =
+                        HoneypotError::Delivery(format!("{} returned {}", self.endpoint, status));
+                    if !(status.is_server_error() || status.as_u16() == 429) {
+                        return Err(error);
+                    }
+                    error
+                }
+                Err(e) => e.into(),
+            };
+
+            if attempt >= self.max_retries {
+                return Err(error);
+            }
+            tokio::time::sleep(self.backoff_for(attempt)).await;
+            attempt += 1;
+        }
+    }
+
+    fn backoff_for(&self, attempt: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_backoff)
+    }
+}
+
+/// Builder for [`TripwireReporter`].
+pub struct TripwireReporterBuilder {
+    endpoint: String,
+    token: Option<String>,
+    batch_size: usize,
+    max_retries: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    spool_dir: Option<PathBuf>,
+    timeout: Duration,
+}
+
+impl TripwireReporterBuilder {
+    /// Create a new builder: the i1.is endpoint, no token, events sent one
+    /// at a time with three retries, and no spool.
+    pub fn new() -> Self {
+        Self {
+            endpoint: DEFAULT_ENDPOINT.to_string(),
+            token: None,
+            batch_size: 1,
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            spool_dir: None,
+            timeout: Duration::from_secs(30),
+        }
+    }
+
+    /// Send events to this URL
+    pub fn endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoint = endpoint.into();
+        self
+    }
+
+    /// Bearer token for the endpoint
+    pub fn token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// Events per request; `report` sends once this many are queued
+    pub fn batch_size(mut self, size: usize) -> Self {
+        self.batch_size = size.max(1);
+        self
+    }
+
+    /// Retries after the first attempt of a batch
+    pub fn max_retries(mut self, max: u32) -> Self {
+        self.max_retries = max;
+        self
+    }
+
+    /// Wait before the first retry; doubled for each one after
+    pub fn initial_backoff(mut self, duration: Duration) -> Self {
+        self.initial_backoff = duration;
+        self
+    }
+
+    /// Longest wait between retries
+    pub fn max_backoff(mut self, duration: Duration) -> Self {
+        self.max_backoff = duration;
+        self
+    }
+
+    /// Keep undelivered events in this directory
+    pub fn spool_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.spool_dir = Some(dir.into());
+        self
+    }
+
+    /// Timeout for each request
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Build the reporter, queueing any events a previous one left in the
+    /// spool.
+    pub fn build(self) -> Result<TripwireReporter, HoneypotError> {
+        let client = reqwest::Client::builder()
+            .timeout(self.timeout)
+            .user_agent(concat!("i1-honeypot/", env!("CARGO_PKG_VERSION")))
+            .build()?;
+
+        let queue = match &self.spool_dir {
+            Some(dir) => {
+                fs::create_dir_all(dir)?;
+                unspool(dir)?
+            }
+            None => Vec::new(),
+        };
+
+        Ok(TripwireReporter {
+            client,
+            endpoint: self.endpoint,
+            token: self.token,
+            batch_size: self.batch_size,
+            max_retries: self.max_retries,
+            initial_backoff: self.initial_backoff,
+            max_backoff: self.max_backoff,
+            spool_dir: self.spool_dir,
+            queue: Mutex::new(queue),
+        })
+    }
+}
+
+impl Default for TripwireReporterBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Write an event to the spool, named so the files sort in report order.
+/// It is written beside its final name and renamed, so a crash never
+/// leaves half an event behind.
+fn spool(dir: &Path, event: &TripwireEvent) -> Result<PathBuf, HoneypotError> {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let name = format!("{:024}-{}.json", nanos, Uuid::new_v4());
+
+    let partial = dir.join(format!(".{}", name));
+    let path = dir.join(name);
+    fs::write(&partial, serde_json::to_vec(event)?)?;
+    fs::rename(&partial, &path)?;
+    Ok(path)
+}
+
+/// The events in the spool, oldest first. Files that don't hold an event
+/// are left where they are.
+fn unspool(dir: &Path) -> Result<Vec<Queued>, HoneypotError> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension().is_some_and(|ext| ext == "json")
+                && !path
+                    .file_name()
+                    .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+        })
+        .collect();
+    files.sort();
+
+    Ok(files
+        .into_iter()
+        .filter_map(|path| {
+            let event = serde_json::from_slice(&fs::read(&path).ok()?).ok()?;
+            Some(Queued {
+                event,
+                spool_file: Some(path),
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn event(source_ip: &str) -> TripwireEvent {
+        TripwireEvent {
+            honeypot_id: Uuid::new_v4(),
+            honeypot_type: "card".to_string(),
+            triggered_at: Utc::now(),
+            source_ip: Some(source_ip.to_string()),
+            context: serde_json::json!({ "merchant": "example" }),
+        }
+    }
+
+    fn builder(server: &MockServer) -> TripwireReporterBuilder {
+        TripwireReporter::builder()
+            .endpoint(format!("{}/v1/tripwire", server.uri()))
+            .token("test-token")
+            .initial_backoff(Duration::from_millis(1))
+    }
+
+    /// The source IPs of every event the server received, in order.
+    async fn received(server: &MockServer) -> Vec<String> {
+        let mut ips = Vec::new();
+        for request in server.received_requests().await.unwrap() {
+            let body: serde_json::Value = request.body_json().unwrap();
+            for event in body["events"].as_array().unwrap() {
+                ips.push(event["source_ip"].as_str().unwrap().to_string());
+            }
+        }
+        ips
+    }
+
+    #[tokio::test]
+    async fn test_delivery_in_batches() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/tripwire"))
+            .and(header("authorization", "Bearer test-token"))
+            .respond_with(ResponseTemplate::new(202))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let reporter = builder(&server).batch_size(2).build().unwrap();
+        for ip in ["192.0.2.1", "192.0.2.2", "192.0.2.3"] {
+            reporter.report(event(ip)).await.unwrap();
+        }
+        // Two sent as a batch, the third waits for a full one
+        assert_eq!(reporter.pending(), 1);
+        assert_eq!(reporter.flush().await.unwrap(), 1);
+        assert_eq!(reporter.pending(), 0);
+
+        assert_eq!(
+            received(&server).await,
+            ["192.0.2.1", "192.0.2.2", "192.0.2.3"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_retry_on_500() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500))
+            .up_to_n_times(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+
+        let reporter = builder(&server).build().unwrap();
+        reporter.report(event("192.0.2.1")).await.unwrap();
+        assert_eq!(reporter.pending(), 0);
+        assert_eq!(server.received_requests().await.unwrap().len(), 3);
+
+        // Client errors are not retried, and the event stays queued
+        let rejecting = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(401))
+            .expect(1)
+            .mount(&rejecting)
+            .await;
+        let reporter = builder(&rejecting).build().unwrap();
+        assert!(reporter.report(event("192.0.2.2")).await.is_err());
+        assert_eq!(reporter.pending(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_spool_replay() {
+        let spool = tempfile::tempdir().unwrap();
+
+        // The API is down: events are spooled, then the process goes away
+        let down = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&down)
+            .await;
+        let reporter = builder(&down)
+            .max_retries(1)
+            .spool_dir(spool.path())
+            .build()
+            .unwrap();
+        for ip in ["192.0.2.1", "192.0.2.2"] {
+            assert!(reporter.report(event(ip)).await.is_err());
+        }
+        drop(reporter);
+        assert_eq!(fs::read_dir(spool.path()).unwrap().count(), 2);
+
+        // A new reporter on the same spool sends them once the API is back
+        let up = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&up)
+            .await;
+        let reporter = builder(&up).spool_dir(spool.path()).build().unwrap();
+        assert_eq!(reporter.pending(), 2);
+        assert_eq!(reporter.flush().await.unwrap(), 2);
+
+        assert_eq!(received(&up).await, ["192.0.2.1", "192.0.2.2"]);
+        assert_eq!(fs::read_dir(spool.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_report_blocking() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let server = runtime.block_on(async {
+            let server = MockServer::start().await;
+            Mock::given(method("POST"))
+                .respond_with(ResponseTemplate::new(200))
+                .mount(&server)
+                .await;
+            server
+        });
+
+        let reporter = builder(&server).build().unwrap();
+        reporter.report_blocking(event("192.0.2.1")).unwrap();
+        assert_eq!(reporter.pending(), 0);
+        assert_eq!(runtime.block_on(received(&server)), ["192.0.2.1"]);
+    }
+}
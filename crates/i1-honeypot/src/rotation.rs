@@ -0,0 +1,166 @@
+//! Expiring and rotating kits.
+//!
+//! Bait that sits unchanged for months gets recognized, and passed around
+//! as known-fake. A kit has a lifetime; when it runs out, [`HoneypotKit::rotate`]
+//! makes a successor for the same person with everything else new, and
+//! deploying the successor where the old kit is replaces it in place.
+
+use std::collections::BTreeSet;
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{HoneypotKit, KitBuilder};
+
+impl HoneypotKit {
+    /// True once the kit's lifetime has run out. A kit without an expiry
+    /// never expires.
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.is_some_and(|expires| expires <= Utc::now())
+    }
+
+    /// A successor to this kit: the same user, persona, tracking base and
+    /// lifetime, as many of each component of the same kinds, but every
+    /// card, login, wallet, document, token and message new, with new ids.
+    ///
+    /// The successor records this kit in `supersedes`, which is how
+    /// [`HoneypotKit::deploy_to`] knows it may replace this kit's files.
+    pub fn rotate(&self) -> HoneypotKit {
+        let mut successor = KitBuilder::like(self).generate();
+        // The builder only knows each document type's default format
+        for (new, old) in successor.documents.iter_mut().zip(&self.documents) {
+            if new.format != old.format {
+                *new = new.clone().with_format(old.format);
+            }
+        }
+        successor.supersedes = Some(self.id);
+        successor
+    }
+}
+
+/// What rotating one kit into another does on disk, by path relative to
+/// the deploy directory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RotationPlan {
+    /// Kit being retired
+    pub from: Uuid,
+    /// Kit taking its place
+    pub to: Uuid,
+    /// Files only the old kit has, to delete
+    pub remove: Vec<String>,
+    /// Files both kits have, to overwrite with the new kit's
+    pub replace: Vec<String>,
+    /// Files only the new kit has, to create
+    pub add: Vec<String>,
+}
+
+impl RotationPlan {
+    /// The files that change when `new` is deployed over `old`. Files
+    /// outside both kits are never in the plan.
+    pub fn diff(old: &HoneypotKit, new: &HoneypotKit) -> Self {
+        let paths = |kit: &HoneypotKit| -> Vec<String> {
+            kit.deploy_files()
+                .into_iter()
+                .map(|(path, _, _)| path)
+                .collect()
+        };
+        Self::from_paths(old.id, new.id, &paths(old), &paths(new))
+    }
+
+    /// The plan between two sets of deployed paths.
+    pub(crate) fn from_paths(from: Uuid, to: Uuid, old: &[String], new: &[String]) -> Self {
+        let old: BTreeSet<&str> = old.iter().map(String::as_str).collect();
+        let new: BTreeSet<&str> = new.iter().map(String::as_str).collect();
+
+        Self {
+            from,
+            to,
+            remove: old.difference(&new).map(|path| path.to_string()).collect(),
+            replace: old
+                .intersection(&new)
+                .map(|path| path.to_string())
+                .collect(),
+            add: new.difference(&old).map(|path| path.to_string()).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DocumentFormat;
+    use chrono::Duration;
+
+    #[test]
+    fn test_rotate() {
+        let old = HoneypotKit::generate_with_seed("test-user", 5);
+        assert!(!old.is_expired());
+        let new = old.rotate();
+
+        assert_eq!(new.supersedes, Some(old.id));
+        assert_eq!(new.user_id, old.user_id);
+        assert_eq!(new.persona, old.persona);
+        assert_eq!(new.tracking_base, old.tracking_base);
+        assert_eq!(
+            new.expires_at.unwrap() - new.created_at,
+            old.expires_at.unwrap() - old.created_at
+        );
+
+        let networks = |kit: &HoneypotKit| kit.cards.iter().map(|c| c.network).collect::<Vec<_>>();
+        assert_eq!(networks(&new), networks(&old));
+        assert_eq!(new.wallets.len(), old.wallets.len());
+        assert_eq!(new.honeytokens.len(), old.honeytokens.len());
+        assert_eq!(new.mail.is_empty(), old.mail.is_empty());
+
+        // Nothing carries over but the person
+        let ids = |kit: &HoneypotKit| -> BTreeSet<Uuid> {
+            let mut ids = BTreeSet::from([kit.id]);
+            ids.extend(kit.cards.iter().map(|c| c.id));
+            ids.extend(kit.credentials.iter().map(|c| c.id));
+            ids.extend(kit.wallets.iter().map(|w| w.id));
+            ids.extend(kit.documents.iter().map(|d| d.id));
+            ids.extend(kit.honeytokens.iter().map(|t| t.id));
+            ids.extend(kit.mail.iter().map(|m| m.id));
+            ids
+        };
+        assert!(ids(&old).is_disjoint(&ids(&new)));
+        for (old, new) in old.cards.iter().zip(&new.cards) {
+            assert_ne!(old.number, new.number);
+        }
+
+        let mut stale = old.clone();
+        stale.expires_at = Some(Utc::now() - Duration::seconds(1));
+        assert!(stale.is_expired());
+        stale.expires_at = None;
+        assert!(!stale.is_expired());
+    }
+
+    #[test]
+    fn test_rotation_diff() {
+        let old = HoneypotKit::generate_with_seed("test-user", 6);
+        let mut new = old.rotate();
+        new.documents[0] = new.documents[0].clone().with_format(DocumentFormat::Pdf);
+        let plan = RotationPlan::diff(&old, &new);
+        assert_eq!((plan.from, plan.to), (old.id, new.id));
+
+        let paths = |kit: &HoneypotKit| -> BTreeSet<String> {
+            kit.deploy_files()
+                .into_iter()
+                .map(|(path, _, _)| path)
+                .collect()
+        };
+        let (old_paths, new_paths) = (paths(&old), paths(&new));
+        let set = |paths: &[String]| paths.iter().cloned().collect::<BTreeSet<_>>();
+
+        // Every old file goes or is replaced, every new one is written
+        assert_eq!(&set(&plan.remove) | &set(&plan.replace), old_paths);
+        assert_eq!(&set(&plan.add) | &set(&plan.replace), new_paths);
+        assert!(set(&plan.remove).is_disjoint(&set(&plan.add)));
+        assert!(plan
+            .replace
+            .contains(&"Documents/passwords.txt".to_string()));
+        assert!(plan.add.iter().any(|path| path.ends_with(".pdf")));
+        assert!(!plan.remove.iter().any(|path| path.ends_with(".pdf")));
+    }
+}
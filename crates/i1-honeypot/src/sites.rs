@@ -0,0 +1,199 @@
+//! The sites a kit's logins are for.
+//!
+//! Each [`CredentialType`] has a catalog of sites, and a login is made for
+//! one of those its persona would plausibly use: an Icelandic grandmother
+//! banks with Landsbankinn, not Chase. The built-in catalogs cover every
+//! [`Locale`]; [`KitBuilder::with_site_catalog`](crate::KitBuilder::with_site_catalog)
+//! replaces one for a kit, and the replacement is saved in the kit so that
+//! rotating it picks from the same sites.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{CredentialType, Locale};
+use CredentialType::{BankLogin, CryptoExchange, EmailLogin, Shopping, SocialMedia, Streaming};
+use Locale::{EnUs, IsIs};
+
+/// A site a login can be for.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SiteEntry {
+    /// Domain the login is for, e.g. `landsbankinn.is`
+    pub domain: String,
+    /// The company behind the site, as it signs its mail
+    pub display_name: String,
+    /// Locale whose people use the site, or `None` if everyone does
+    #[serde(default)]
+    pub locale: Option<Locale>,
+}
+
+impl SiteEntry {
+    /// Create an entry for `domain`.
+    pub fn new(
+        domain: impl Into<String>,
+        display_name: impl Into<String>,
+        locale: Option<Locale>,
+    ) -> Self {
+        Self {
+            domain: domain.into(),
+            display_name: display_name.into(),
+            locale,
+        }
+    }
+
+    /// Whether people in `locale` use the site.
+    pub fn serves(&self, locale: Locale) -> bool {
+        self.locale.is_none() || self.locale == Some(locale)
+    }
+}
+
+/// The sites to use for one kind of login in one kit.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SiteCatalog {
+    /// Kind of login the sites are for
+    pub credential_type: CredentialType,
+    /// Sites to pick from
+    pub sites: Vec<SiteEntry>,
+}
+
+impl SiteCatalog {
+    /// The sites for the persona's `locale`. A catalog with none for it is
+    /// used whole: it was given for this kit, so its sites are wanted.
+    pub fn sites_for(&self, locale: Locale) -> Vec<SiteEntry> {
+        let local: Vec<SiteEntry> = self
+            .sites
+            .iter()
+            .filter(|site| site.serves(locale))
+            .cloned()
+            .collect();
+        if local.is_empty() {
+            self.sites.clone()
+        } else {
+            local
+        }
+    }
+}
+
+/// Kind of login, locale (`None` for everywhere), domain and display name
+const DEFAULT_SITES: &[(CredentialType, Option<Locale>, &str, &str)] = &[
+    (BankLogin, Some(EnUs), "chase.com", "Chase"),
+    (
+        BankLogin,
+        Some(EnUs),
+        "bankofamerica.com",
+        "Bank of America",
+    ),
+    (BankLogin, Some(EnUs), "wellsfargo.com", "Wells Fargo"),
+    (BankLogin, Some(EnUs), "citibank.com", "Citi"),
+    (BankLogin, Some(IsIs), "landsbankinn.is", "Landsbankinn"),
+    (BankLogin, Some(IsIs), "arionbanki.is", "Arion banki"),
+    (BankLogin, Some(IsIs), "islandsbanki.is", "Íslandsbanki"),
+    (BankLogin, Some(IsIs), "indo.is", "indó"),
+    (EmailLogin, None, "gmail.com", "Gmail"),
+    (EmailLogin, None, "outlook.com", "Outlook"),
+    (EmailLogin, Some(EnUs), "yahoo.com", "Yahoo"),
+    (EmailLogin, Some(EnUs), "protonmail.com", "Proton"),
+    (EmailLogin, Some(IsIs), "simnet.is", "Síminn"),
+    (EmailLogin, Some(IsIs), "internet.is", "Vodafone"),
+    (SocialMedia, None, "facebook.com", "Facebook"),
+    (SocialMedia, None, "instagram.com", "Instagram"),
+    (SocialMedia, Some(EnUs), "twitter.com", "Twitter"),
+    (SocialMedia, Some(EnUs), "linkedin.com", "LinkedIn"),
+    (SocialMedia, Some(IsIs), "bland.is", "Bland"),
+    (CryptoExchange, None, "binance.com", "Binance"),
+    (CryptoExchange, None, "kraken.com", "Kraken"),
+    (CryptoExchange, Some(EnUs), "coinbase.com", "Coinbase"),
+    (CryptoExchange, Some(EnUs), "gemini.com", "Gemini"),
+    (CryptoExchange, Some(IsIs), "myntkaup.is", "Myntkaup"),
+    (Shopping, Some(EnUs), "amazon.com", "Amazon"),
+    (Shopping, Some(EnUs), "ebay.com", "eBay"),
+    (Shopping, Some(EnUs), "walmart.com", "Walmart"),
+    (Shopping, Some(EnUs), "target.com", "Target"),
+    (Shopping, Some(IsIs), "elko.is", "ELKO"),
+    (Shopping, Some(IsIs), "heimkaup.is", "Heimkaup"),
+    (Shopping, Some(IsIs), "aha.is", "Aha"),
+    (Shopping, Some(IsIs), "boozt.com", "Boozt"),
+    (Streaming, None, "netflix.com", "Netflix"),
+    (Streaming, None, "disneyplus.com", "Disney+"),
+    (Streaming, Some(EnUs), "hulu.com", "Hulu"),
+    (Streaming, Some(EnUs), "hbomax.com", "HBO Max"),
+    (Streaming, Some(IsIs), "stod2.is", "Stöð 2"),
+    (Streaming, Some(IsIs), "viaplay.is", "Viaplay"),
+];
+
+impl CredentialType {
+    /// The built-in catalog for this kind of login, every locale's sites.
+    pub fn default_catalog(self) -> SiteCatalog {
+        let sites = DEFAULT_SITES
+            .iter()
+            .filter(|(kind, ..)| *kind == self)
+            .map(|&(_, locale, domain, name)| SiteEntry::new(domain, name, locale))
+            .collect();
+        SiteCatalog {
+            credential_type: self,
+            sites,
+        }
+    }
+
+    /// The built-in sites for this kind of login that people in `locale`
+    /// use.
+    pub fn sites(self, locale: Locale) -> Vec<SiteEntry> {
+        self.default_catalog().sites_for(locale)
+    }
+}
+
+/// The name a built-in site goes by.
+pub(crate) fn display_name(domain: &str) -> Option<&'static str> {
+    DEFAULT_SITES
+        .iter()
+        .find(|(_, _, d, _)| *d == domain)
+        .map(|&(_, _, _, name)| name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_sites_by_locale() {
+        let kinds = [
+            BankLogin,
+            EmailLogin,
+            SocialMedia,
+            CryptoExchange,
+            Shopping,
+            Streaming,
+        ];
+        for kind in kinds {
+            for locale in [EnUs, IsIs] {
+                let sites = kind.sites(locale);
+                assert!(!sites.is_empty(), "{kind} {locale}");
+                assert!(sites.iter().all(|site| site.serves(locale)));
+            }
+        }
+
+        let domains = |locale| -> Vec<String> {
+            BankLogin
+                .sites(locale)
+                .into_iter()
+                .map(|site| site.domain)
+                .collect()
+        };
+        assert!(domains(IsIs).iter().all(|d| d.ends_with(".is")));
+        assert!(domains(EnUs).iter().all(|d| d.ends_with(".com")));
+        assert!(EmailLogin
+            .sites(IsIs)
+            .iter()
+            .any(|s| s.domain == "gmail.com"));
+        assert_eq!(display_name("islandsbanki.is"), Some("Íslandsbanki"));
+        assert_eq!(display_name("example.org"), None);
+    }
+
+    #[test]
+    fn test_catalog_without_local_sites() {
+        let catalog = SiteCatalog {
+            credential_type: Shopping,
+            sites: vec![SiteEntry::new("bestbuy.com", "Best Buy", Some(EnUs))],
+        };
+        assert_eq!(catalog.sites_for(EnUs), catalog.sites);
+        assert_eq!(catalog.sites_for(IsIs), catalog.sites);
+    }
+}
@@ -0,0 +1,205 @@
+//! Wallet files for the kit's wallets.
+//!
+//! A seed phrase in a text file is one lure; the files wallet software
+//! leaves behind are another. These have the right shape for the tools that
+//! look for them, but unlock nothing, and carry the wallet's honeypot id
+//! where nobody reading them would notice it.
+
+use chrono::{Duration, NaiveDate, Utc};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use sha2::{Digest, Sha256};
+
+use crate::crypto::hex_encode;
+use crate::{CryptoNetwork, HoneypotWallet};
+
+/// Berkeley DB page size Bitcoin Core uses.
+const PAGE_SIZE: usize = 4096;
+
+/// Magic number of a Berkeley DB btree file.
+const BTREE_MAGIC: u32 = 0x0005_3162;
+
+impl HoneypotWallet {
+    /// The files this wallet's software would leave in a home directory, as
+    /// paths relative to it with their contents: a V3 keystore for an
+    /// Ethereum wallet, a `wallet.dat` for the others.
+    ///
+    /// The keystore's `id` is the wallet's id, and the `wallet.dat` carries
+    /// it as the database's file id. The same wallet gives the same files
+    /// until midnight UTC, when the keystore's date moves on a day; use
+    /// [`artifact_files_on`](Self::artifact_files_on) to pin the day.
+    pub fn artifact_files(&self) -> Vec<(String, Vec<u8>)> {
+        self.artifact_files_on(Utc::now().date_naive())
+    }
+
+    /// The files this wallet's software would leave, as of `today`: the same
+    /// wallet and day always give the same files.
+    pub fn artifact_files_on(&self, today: NaiveDate) -> Vec<(String, Vec<u8>)> {
+        let seed: [u8; 32] = Sha256::digest(self.id.as_bytes()).into();
+        let mut rng = StdRng::from_seed(seed);
+
+        match self.network {
+            CryptoNetwork::Ethereum => vec![self.keystore(today, &mut rng)],
+            CryptoNetwork::Bitcoin => vec![self.wallet_dat(".bitcoin", &mut rng)],
+            CryptoNetwork::Litecoin => vec![self.wallet_dat(".litecoin", &mut rng)],
+            CryptoNetwork::Dogecoin => vec![self.wallet_dat(".dogecoin", &mut rng)],
+        }
+    }
+
+    /// A Web3 Secret Storage (V3) keystore as geth writes it. The
+    /// ciphertext is random, so no password opens it, but every field is
+    /// where a cracker expects it.
+    fn keystore(&self, today: NaiveDate, rng: &mut impl Rng) -> (String, Vec<u8>) {
+        let address = self.address.trim_start_matches("0x").to_ascii_lowercase();
+        let midnight = today.and_hms_opt(0, 0, 0).unwrap();
+        let created = midnight.and_utc() - Duration::days(rng.gen_range(90..1000))
+            + Duration::nanoseconds(rng.gen_range(0..86_400_000_000_000));
+        let salt: [u8; 32] = rng.gen();
+        let iv: [u8; 16] = rng.gen();
+        let ciphertext: [u8; 32] = rng.gen();
+        let mac: [u8; 32] = rng.gen();
+
+        let keystore = serde_json::json!({
+            "address": address,
+            "crypto": {
+                "cipher": "aes-128-ctr",
+                "ciphertext": hex_encode(&ciphertext),
+                "cipherparams": { "iv": hex_encode(&iv) },
+                "kdf": "scrypt",
+                "kdfparams": {
+                    "dklen": 32,
+                    "n": 262_144,
+                    "p": 1,
+                    "r": 8,
+                    "salt": hex_encode(&salt),
+                },
+                "mac": hex_encode(&mac),
+            },
+            "id": self.id,
+            "version": 3,
+        });
+
+        let path = format!(
+            ".ethereum/keystore/UTC--{}Z--{}",
+            created.format("%Y-%m-%dT%H-%M-%S%.9f"),
+            address
+        );
+        (path, keystore.to_string().into_bytes())
+    }
+
+    /// A `wallet.dat` with a Berkeley DB btree meta page up front and the
+    /// record names Bitcoin Core writes further in. It is not a database
+    /// anything can open, only one that looks right to `file` and to a
+    /// grep for wallet files.
+    fn wallet_dat(&self, dir: &str, rng: &mut impl Rng) -> (String, Vec<u8>) {
+        let mut data = vec![0u8; PAGE_SIZE * 8];
+        rng.fill(&mut data[PAGE_SIZE..]);
+
+        // Meta page: magic, version, page size, btree meta type, last page
+        let meta = &mut data[..PAGE_SIZE];
+        meta[8..12].copy_from_slice(&0u32.to_le_bytes());
+        meta[12..16].copy_from_slice(&BTREE_MAGIC.to_le_bytes());
+        meta[16..20].copy_from_slice(&9u32.to_le_bytes());
+        meta[20..24].copy_from_slice(&(PAGE_SIZE as u32).to_le_bytes());
+        meta[25] = 9;
+        meta[32..36].copy_from_slice(&7u32.to_le_bytes());
+        // The file id, which Berkeley DB never shows anyone
+        meta[52..68].copy_from_slice(self.id.as_bytes());
+        rng.fill(&mut meta[68..72]);
+
+        // Records as Bitcoin Core keys them: a length-prefixed type name,
+        // then the data
+        let mut random = |len: usize| {
+            let mut bytes = vec![0u8; len];
+            rng.fill(bytes.as_mut_slice());
+            bytes
+        };
+        let mut records = Vec::new();
+        for (name, value) in [
+            ("version", 169_900u32.to_le_bytes().to_vec()),
+            ("minversion", 169_900u32.to_le_bytes().to_vec()),
+            ("name", self.address.as_bytes().to_vec()),
+            ("bestblock", vec![0; 4]),
+            ("hdchain", random(20)),
+            ("mkey", random(48)),
+            ("ckey", random(33)),
+            ("defaultkey", random(33)),
+        ] {
+            records.push(name.len() as u8);
+            records.extend_from_slice(name.as_bytes());
+            records.push(value.len() as u8);
+            records.extend_from_slice(&value);
+        }
+        let start = PAGE_SIZE + 26;
+        data[start..start + records.len()].copy_from_slice(&records);
+
+        let label = ["savings", "cold", "main", "old"][rng.gen_range(0..4)];
+        let path = format!(
+            "{}/wallets/{}-{}/wallet.dat",
+            dir,
+            label,
+            &self.id.simple().to_string()[..4]
+        );
+        (path, data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keystore() {
+        let wallet = HoneypotWallet::generate(CryptoNetwork::Ethereum);
+        let files = wallet.artifact_files();
+        assert_eq!(files.len(), 1);
+        let (path, content) = &files[0];
+        assert!(path.starts_with(".ethereum/keystore/UTC--"));
+
+        let keystore: serde_json::Value = serde_json::from_slice(content).unwrap();
+        assert_eq!(keystore["version"], 3);
+        assert_eq!(keystore["id"], wallet.id.to_string());
+        let address = keystore["address"].as_str().unwrap();
+        assert_eq!(format!("0x{}", address), wallet.address.to_lowercase());
+        assert!(path.ends_with(&format!("--{}", address)));
+
+        let crypto = &keystore["crypto"];
+        assert_eq!(crypto["cipher"], "aes-128-ctr");
+        assert_eq!(crypto["kdf"], "scrypt");
+        assert_eq!(crypto["kdfparams"]["dklen"], 32);
+        assert_eq!(crypto["kdfparams"]["n"], 262_144);
+        assert_eq!(crypto["kdfparams"]["r"], 8);
+        assert_eq!(crypto["kdfparams"]["p"], 1);
+        for (field, bytes) in [
+            (&crypto["ciphertext"], 32),
+            (&crypto["cipherparams"]["iv"], 16),
+            (&crypto["kdfparams"]["salt"], 32),
+            (&crypto["mac"], 32),
+        ] {
+            let hex = field.as_str().unwrap();
+            assert_eq!(hex.len(), bytes * 2);
+            assert!(hex.chars().all(|c| c.is_ascii_hexdigit()));
+        }
+    }
+
+    #[test]
+    fn test_wallet_dat() {
+        let wallet = HoneypotWallet::generate(CryptoNetwork::Bitcoin);
+        let files = wallet.artifact_files();
+        let (path, data) = &files[0];
+        assert!(path.starts_with(".bitcoin/wallets/"));
+        assert!(path.ends_with("/wallet.dat"));
+
+        assert_eq!(data.len() % PAGE_SIZE, 0);
+        assert_eq!(&data[12..16], &[0x62, 0x31, 0x05, 0x00]);
+        assert_eq!(&data[52..68], wallet.id.as_bytes());
+        let contains = |needle: &[u8]| data.windows(needle.len()).any(|w| w == needle);
+        assert!(contains(b"\x0aminversion"));
+        assert!(contains(wallet.address.as_bytes()));
+
+        // The same wallet, the same file
+        assert_eq!(wallet.artifact_files()[0], files[0]);
+        let dogecoin = HoneypotWallet::generate(CryptoNetwork::Dogecoin);
+        assert!(dogecoin.artifact_files()[0].0.starts_with(".dogecoin/"));
+    }
+}
@@ -0,0 +1,163 @@
+//! A TLS decoy, visited by a real TLS client.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use i1_ca::{IntermediateCa, KeyAlgorithm, RootCa};
+use i1_honeypot::{decoy_service, DecoyConfig, DecoyKind};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, ServerName};
+use rustls::{ClientConfig, RootCertStore};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
+
+const HOSTNAME: &str = "rdweb.corp.example";
+
+/// A client that trusts only `root`, offering HTTP/1.1.
+fn connector(root: &RootCa) -> TlsConnector {
+    let mut roots = RootCertStore::empty();
+    roots
+        .add(CertificateDer::from_pem_slice(root.certificate_pem().as_bytes()).unwrap())
+        .unwrap();
+    let mut config =
+        ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    TlsConnector::from(Arc::new(config))
+}
+
+/// Send `request` over TLS and return the response.
+async fn fetch(
+    connector: &TlsConnector,
+    addr: SocketAddr,
+    request: &str,
+) -> std::io::Result<String> {
+    let stream = TcpStream::connect(addr).await?;
+    let name = ServerName::try_from(HOSTNAME).unwrap();
+    let mut tls = connector.connect(name, stream).await?;
+    tls.write_all(request.as_bytes()).await?;
+    let mut response = String::new();
+    tls.read_to_string(&mut response).await?;
+    Ok(response)
+}
+
+fn localhost() -> SocketAddr {
+    SocketAddr::from(([127, 0, 0, 1], 0))
+}
+
+#[tokio::test]
+async fn test_decoy_login() {
+    let root = RootCa::generate("Root", KeyAlgorithm::EcdsaP256).unwrap();
+    let issuer = IntermediateCa::for_honeypot(&root).unwrap();
+    let config = DecoyConfig::new(HOSTNAME)
+        .kind(DecoyKind::RdpWeb)
+        .bind(localhost());
+    let mut decoy = decoy_service(&issuer, config).await.unwrap();
+    assert_eq!(decoy.certificate().info.subject, HOSTNAME);
+    let connector = connector(&root);
+
+    let body = "DomainUserName=CORP%5Cadministrator&UserPass=Winter2024%21";
+    let request = format!(
+        "POST /RDWeb/Pages/en-US/login.aspx HTTP/1.1\r\n\
+         Host: {HOSTNAME}\r\n\
+         User-Agent: python-requests/2.31\r\n\
+         Content-Type: application/x-www-form-urlencoded\r\n\
+         Content-Length: {}\r\n\r\n{body}",
+        body.len()
+    );
+    let response = fetch(&connector, decoy.local_addr(), &request)
+        .await
+        .unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+    assert!(response.contains("Server: Microsoft-IIS/10.0"));
+    assert!(response.contains("Invalid username or password"));
+
+    let event = decoy.next_event().await.unwrap();
+    assert_eq!(event.honeypot_id, decoy.id());
+    assert_eq!(event.honeypot_type, "decoy");
+    assert_eq!(event.source_ip.as_deref(), Some("127.0.0.1"));
+    let context = &event.context;
+    assert_eq!(context["kind"], "rdp_web");
+    assert_eq!(context["hostname"], HOSTNAME);
+    assert_eq!(context["sni"], HOSTNAME);
+    assert_eq!(context["request"], "POST /RDWeb/Pages/en-US/login.aspx");
+    assert_eq!(context["user_agent"], "python-requests/2.31");
+    assert_eq!(context["credentials"]["username"], "CORP\\administrator");
+    assert_eq!(context["credentials"]["password"], "Winter2024!");
+    assert!(context["tls_hello"]
+        .as_str()
+        .unwrap()
+        .ends_with(",http/1.1"));
+    assert_eq!(context["tls_fingerprint"].as_str().unwrap().len(), 32);
+
+    // The same client looks the same, and a plain GET has no credentials
+    fetch(&connector, decoy.local_addr(), "GET / HTTP/1.1\r\n\r\n")
+        .await
+        .unwrap();
+    let again = decoy.next_event().await.unwrap();
+    assert_eq!(again.context["tls_fingerprint"], context["tls_fingerprint"]);
+    assert!(again.context["credentials"].is_null());
+
+    assert!(decoy.shutdown().await.is_empty());
+}
+
+#[tokio::test]
+async fn test_decoy_connection_cap() {
+    let root = RootCa::generate("Root", KeyAlgorithm::EcdsaP256).unwrap();
+    let issuer = IntermediateCa::for_honeypot(&root).unwrap();
+    let config = DecoyConfig::new(HOSTNAME)
+        .bind(localhost())
+        .max_connections(1);
+    let decoy = decoy_service(&issuer, config).await.unwrap();
+    let addr = decoy.local_addr();
+
+    // A connection that never says hello holds the only slot
+    let idle = TcpStream::connect(addr).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(fetch(&connector(&root), addr, "GET / HTTP/1.1\r\n\r\n")
+        .await
+        .is_err());
+    drop(idle);
+
+    // Shutting down waits for it, and only it was recorded
+    let events = decoy.shutdown().await;
+    assert_eq!(events.len(), 1);
+    assert!(events[0].context["sni"].is_null());
+}
+
+#[tokio::test]
+async fn test_decoy_dual_stack() {
+    let root = RootCa::generate("Root", KeyAlgorithm::EcdsaP256).unwrap();
+    let issuer = IntermediateCa::for_honeypot(&root).unwrap();
+    let config = DecoyConfig::new(HOSTNAME).bind(SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0)));
+    let mut decoy = decoy_service(&issuer, config).await.unwrap();
+    let port = decoy.local_addr().port();
+    let connector = connector(&root);
+
+    // One listener, reached over both families
+    for (ip, source) in [
+        (IpAddr::from(Ipv4Addr::LOCALHOST), "127.0.0.1"),
+        (IpAddr::from(Ipv6Addr::LOCALHOST), "::1"),
+    ] {
+        if decoy.local_addr().is_ipv4() && ip.is_ipv6() {
+            // Fell back to IPv4: no IPv6 here
+            continue;
+        }
+        fetch(
+            &connector,
+            SocketAddr::new(ip, port),
+            "GET / HTTP/1.1\r\n\r\n",
+        )
+        .await
+        .unwrap();
+        let event = decoy.next_event().await.unwrap();
+        assert_eq!(event.source_ip.as_deref(), Some(source));
+    }
+
+    decoy.shutdown().await;
+}
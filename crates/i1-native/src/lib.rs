@@ -16,6 +16,13 @@
 //!
 //! let provider = NativeProvider::new("your-i1-token");
 //!
+//! // Or present a client certificate on every request (mutual TLS)
+//! let provider = NativeProvider::with_mtls("your-i1-token", &cert_pem, &chain_pem, &key_pem)?;
+//!
+//! // Or fetch a new token when the old one expires
+//! let provider = NativeProvider::new("your-i1-token")
+//!     .with_token_refresh(|| async { Ok(fetch_token().await?) });
+//!
 //! // Check cache first, then query i1.is backend
 //! let host = provider.lookup_host("8.8.8.8").await?;
 //!
@@ -23,17 +30,18 @@
 //! let whois = provider.whois("8.8.8.8").await?;
 //! ```
 
+use std::future::Future;
 use std::net::IpAddr;
-use std::sync::Arc;
+use std::sync::{Arc, PoisonError, RwLock};
 use std::time::Instant;
 
 use async_trait::async_trait;
-use i1_core::{GeoLocation, HostInfo, I1Error, Result};
+use i1_core::{validate_ip, GeoLocation, HostInfo, I1Error, Result};
 use i1_providers::{
-    AuthConfig, DnsProvider, DnsRecord, DomainInfo, HealthStatus, HostLookup, Provider,
-    ProviderHealth, SearchProvider, SearchResults, WhoisInfo, WhoisProvider,
+    schema, AuthConfig, DnsProvider, DnsRecord, DomainInfo, HealthStatus, HostLookup, Provider,
+    ProviderHealth, ProviderHttpConfig, SearchProvider, SearchResults, WhoisInfo, WhoisProvider,
 };
-use reqwest::Client;
+use reqwest::{Client, Response, StatusCode};
 use serde::Deserialize;
 use tracing::{debug, instrument};
 
@@ -44,70 +52,271 @@ pub struct NativeProvider {
     inner: Arc<NativeInner>,
 }
 
+/// The token state is behind `Arc`s, so a copy made to change the HTTP
+/// settings still shares it with the provider it was copied from.
+#[derive(Clone)]
 struct NativeInner {
     http: Client,
-    token: Option<String>,
+    http_config: ProviderHttpConfig,
+    mtls: Option<Mtls>,
+    /// The current token, replaced when a refresh gets a new one
+    token: Arc<RwLock<Option<String>>>,
+    /// Where new tokens come from, if they expire
+    token_provider: Arc<RwLock<Option<Arc<dyn TokenProvider>>>>,
+    /// Held while refreshing, so concurrent requests refresh once
+    refreshing: Arc<tokio::sync::Mutex<()>>,
     base_url: String,
 }
 
+impl NativeInner {
+    fn new(http: Client, mtls: Option<Mtls>, token: Option<String>, base_url: String) -> Self {
+        Self {
+            http,
+            http_config: ProviderHttpConfig::default(),
+            mtls,
+            token: Arc::new(RwLock::new(token)),
+            token_provider: Arc::new(RwLock::new(None)),
+            refreshing: Arc::new(tokio::sync::Mutex::new(())),
+            base_url,
+        }
+    }
+}
+
+/// Source of fresh i1.is tokens, for sessions that outlive one.
+///
+/// When a request comes back 401, the provider asks for a new token and
+/// tries the request once more. Any `async` closure returning a token is a
+/// `TokenProvider`.
+#[async_trait]
+pub trait TokenProvider: Send + Sync {
+    /// Get a new token to replace an expired one.
+    async fn refresh(&self) -> Result<String>;
+}
+
+#[async_trait]
+impl<F, Fut> TokenProvider for F
+where
+    F: Fn() -> Fut + Send + Sync,
+    Fut: Future<Output = Result<String>> + Send,
+{
+    async fn refresh(&self) -> Result<String> {
+        self().await
+    }
+}
+
+/// What a mutual TLS client presents and trusts, kept so the client can be
+/// rebuilt with other HTTP settings
+#[derive(Clone)]
+struct Mtls {
+    identity: reqwest::Identity,
+    server_root: Option<reqwest::Certificate>,
+}
+
+impl Mtls {
+    /// A client presenting this identity, with `config` applied
+    fn client(&self, config: &ProviderHttpConfig) -> Result<Client> {
+        let mut builder = config.client_builder()?.identity(self.identity.clone());
+        #[cfg(feature = "rustls")]
+        {
+            builder = builder.use_rustls_tls();
+        }
+        if let Some(root) = &self.server_root {
+            builder = builder.add_root_certificate(root.clone());
+        }
+        builder
+            .build()
+            .map_err(|e| I1Error::Config(format!("mTLS client: {e}")))
+    }
+}
+
 impl NativeProvider {
     /// Create a new native provider (unauthenticated - limited access)
     pub fn anonymous() -> Self {
         Self {
-            inner: Arc::new(NativeInner {
-                http: Client::new(),
-                token: None,
-                base_url: DEFAULT_BASE_URL.to_string(),
-            }),
+            inner: Arc::new(NativeInner::new(
+                Client::new(),
+                None,
+                None,
+                DEFAULT_BASE_URL.to_string(),
+            )),
         }
     }
 
     /// Create a new native provider with i1.is token
     pub fn new(token: impl Into<String>) -> Self {
         Self {
-            inner: Arc::new(NativeInner {
-                http: Client::new(),
-                token: Some(token.into()),
-                base_url: DEFAULT_BASE_URL.to_string(),
-            }),
+            inner: Arc::new(NativeInner::new(
+                Client::new(),
+                None,
+                Some(token.into()),
+                DEFAULT_BASE_URL.to_string(),
+            )),
         }
     }
 
     /// Create with custom base URL (for self-hosted)
     pub fn with_url(token: impl Into<String>, base_url: impl Into<String>) -> Self {
         Self {
-            inner: Arc::new(NativeInner {
-                http: Client::new(),
-                token: Some(token.into()),
-                base_url: base_url.into(),
-            }),
+            inner: Arc::new(NativeInner::new(
+                Client::new(),
+                None,
+                Some(token.into()),
+                base_url.into(),
+            )),
         }
     }
 
+    /// Create with a client certificate for mutual TLS, presented on every
+    /// request.
+    ///
+    /// `cert_pem` is the client certificate, such as one issued with
+    /// `i1_ca::LeafParams::client`, `chain_pem` the intermediates above it
+    /// (a chain that starts with the certificate itself is fine too) and
+    /// `key_pem` its private key.
+    ///
+    /// A certificate that can't be read, isn't valid right now or isn't for
+    /// client authentication, or a key that won't load, fails here with
+    /// [`I1Error::Config`] rather than as a TLS error on the first request.
+    pub fn with_mtls(
+        token: impl Into<String>,
+        cert_pem: &str,
+        chain_pem: &str,
+        key_pem: &str,
+    ) -> Result<Self> {
+        Self::with_mtls_url(token, DEFAULT_BASE_URL, cert_pem, chain_pem, key_pem, None)
+    }
+
+    /// As [`NativeProvider::with_mtls`], for a self-hosted backend at
+    /// `base_url`. If its server certificate is from a private CA, pass that
+    /// CA's root as `server_root_pem`; it is trusted alongside the usual
+    /// roots.
+    pub fn with_mtls_url(
+        token: impl Into<String>,
+        base_url: impl Into<String>,
+        cert_pem: &str,
+        chain_pem: &str,
+        key_pem: &str,
+        server_root_pem: Option<&str>,
+    ) -> Result<Self> {
+        let identity = client_identity(cert_pem, chain_pem, key_pem)?;
+        let server_root = server_root_pem
+            .map(|root| {
+                reqwest::Certificate::from_pem(root.as_bytes())
+                    .map_err(|e| I1Error::Config(format!("mTLS server root certificate: {e}")))
+            })
+            .transpose()?;
+        let mtls = Mtls {
+            identity,
+            server_root,
+        };
+        let http = mtls.client(&ProviderHttpConfig::default())?;
+
+        Ok(Self {
+            inner: Arc::new(NativeInner::new(
+                http,
+                Some(mtls),
+                Some(token.into()),
+                base_url.into(),
+            )),
+        })
+    }
+
+    /// Connect with these HTTP settings, e.g. through a proxy. A client
+    /// certificate from [`NativeProvider::with_mtls`] is kept. Clones made
+    /// before this call keep their settings, but still share the token.
+    pub fn with_http_config(mut self, config: &ProviderHttpConfig) -> Result<Self> {
+        let http = match &self.inner.mtls {
+            Some(mtls) => mtls.client(config)?,
+            None => config.client()?,
+        };
+        let inner = Arc::make_mut(&mut self.inner);
+        inner.http = http;
+        inner.http_config = config.clone();
+        Ok(self)
+    }
+
+    /// Ask `provider` for a new token when the current one is refused,
+    /// then try the request again, once. Clones, made before this call or
+    /// after, share the provider and the new token.
+    #[must_use]
+    pub fn with_token_refresh(self, provider: impl TokenProvider + 'static) -> Self {
+        *self
+            .inner
+            .token_provider
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = Some(Arc::new(provider));
+        self
+    }
+
     /// Get authentication config
     pub fn auth_config(&self) -> AuthConfig {
-        match &self.inner.token {
-            Some(token) => AuthConfig::i1_native(token),
-            None => AuthConfig::None,
+        self.token().map_or(AuthConfig::None, AuthConfig::i1_native)
+    }
+
+    /// The token requests are sent with right now
+    fn token(&self) -> Option<String> {
+        self.inner
+            .token
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Where new tokens come from, if anywhere
+    fn token_provider(&self) -> Option<Arc<dyn TokenProvider>> {
+        self.inner
+            .token_provider
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Replace `stale`, the token a request was refused with, unless
+    /// another request already has. Fails with [`I1Error::Unauthorized`]
+    /// if there is no way to get a new one.
+    async fn refresh_token(&self, stale: Option<&str>) -> Result<()> {
+        let Some(provider) = self.token_provider() else {
+            return Err(I1Error::Unauthorized);
+        };
+        let _refreshing = self.inner.refreshing.lock().await;
+        if self.token().as_deref() != stale {
+            return Ok(());
         }
+
+        debug!("i1.is token refused, refreshing");
+        let fresh = provider.refresh().await?;
+        *self
+            .inner
+            .token
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = Some(fresh);
+        Ok(())
     }
 
     /// Make a GET request to the i1.is API
-    #[instrument(skip(self), fields(provider = "native"))]
     async fn get<T: serde::de::DeserializeOwned>(&self, endpoint: &str) -> Result<T> {
+        self.get_with_query(endpoint, &[]).await
+    }
+
+    /// Make a GET request with query parameters, encoded so that a search
+    /// for an IPv6 address or with `&` in it arrives as it was given
+    #[instrument(skip(self), fields(provider = "native"))]
+    async fn get_with_query<T: serde::de::DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        query: &[(&str, &str)],
+    ) -> Result<T> {
         let url = format!("{}{}", self.inner.base_url, endpoint);
         debug!(url = %url, "i1.is API request");
 
-        let mut request = self.inner.http.get(&url);
-        if let Some(token) = &self.inner.token {
-            request = request.bearer_auth(token);
+        let token = self.token();
+        let mut response = self.send(&url, query, token.as_deref()).await?;
+        if response.status() == StatusCode::UNAUTHORIZED && self.token_provider().is_some() {
+            // Expired, most likely: once more with a new token
+            self.refresh_token(token.as_deref()).await?;
+            response = self.send(&url, query, self.token().as_deref()).await?;
         }
 
-        let response = request
-            .send()
-            .await
-            .map_err(|e| I1Error::Http(e.to_string()))?;
-
         let status = response.status();
         if !status.is_success() {
             let code = status.as_u16();
@@ -123,10 +332,28 @@ impl NativeProvider {
             };
         }
 
-        response
+        let value = response
             .json()
             .await
-            .map_err(|e| I1Error::Http(e.to_string()))
+            .map_err(|e| I1Error::Http(e.to_string()))?;
+        schema::parse("i1.is", endpoint, &value)
+    }
+
+    /// Send one GET request with `token`, if there is one
+    async fn send(
+        &self,
+        url: &str,
+        query: &[(&str, &str)],
+        token: Option<&str>,
+    ) -> Result<Response> {
+        let mut request = self.inner.http.get(url).query(query);
+        if let Some(token) = token {
+            request = request.bearer_auth(token);
+        }
+        request
+            .send()
+            .await
+            .map_err(|e| self.inner.http_config.request_error(&e))
     }
 
     /// Perform a direct WHOIS lookup (local, no API)
@@ -199,6 +426,51 @@ impl NativeProvider {
     }
 }
 
+/// Check a client certificate and load it with its key and chain.
+fn client_identity(cert_pem: &str, chain_pem: &str, key_pem: &str) -> Result<reqwest::Identity> {
+    let invalid = |why: String| I1Error::Config(format!("mTLS client certificate: {why}"));
+
+    let (_, pem) = x509_parser::pem::parse_x509_pem(cert_pem.as_bytes())
+        .map_err(|e| invalid(format!("not a PEM certificate ({e})")))?;
+    let cert = pem
+        .parse_x509()
+        .map_err(|e| invalid(format!("unreadable ({e})")))?;
+
+    let now = x509_parser::time::ASN1Time::now();
+    let validity = cert.validity();
+    if validity.not_after < now {
+        return Err(invalid(format!("expired on {}", validity.not_after)));
+    }
+    if validity.not_before > now {
+        return Err(invalid(format!("not valid until {}", validity.not_before)));
+    }
+    let usage = cert
+        .extended_key_usage()
+        .map_err(|e| invalid(e.to_string()))?;
+    if usage.is_some_and(|usage| !usage.value.client_auth && !usage.value.any) {
+        return Err(invalid(
+            "not for client authentication (it has no clientAuth usage)".to_string(),
+        ));
+    }
+
+    let chain = if chain_pem.contains(cert_pem.trim()) {
+        chain_pem.to_string()
+    } else {
+        format!("{cert_pem}\n{chain_pem}")
+    };
+    #[cfg(feature = "rustls")]
+    let identity = reqwest::Identity::from_pem(format!("{key_pem}\n{chain}").as_bytes());
+    #[cfg(all(feature = "native-tls", not(feature = "rustls")))]
+    let identity = reqwest::Identity::from_pkcs8_pem(chain.as_bytes(), key_pem.as_bytes());
+    #[cfg(not(any(feature = "rustls", feature = "native-tls")))]
+    return Err(I1Error::Config(
+        "mTLS needs the rustls or native-tls feature".to_string(),
+    ));
+
+    #[cfg(any(feature = "rustls", feature = "native-tls"))]
+    identity.map_err(|e| invalid(format!("key or chain won't load ({e})")))
+}
+
 impl Clone for NativeProvider {
     fn clone(&self) -> Self {
         Self {
@@ -237,6 +509,9 @@ impl Provider for NativeProvider {
                 latency_ms: Some(start.elapsed().as_millis() as u64),
                 credits_remaining: None,
                 message: None,
+                plan: None,
+                scan_credits: None,
+                resets_at: None,
             }),
             Err(I1Error::NotFound { .. }) => {
                 // API doesn't have /health, but it responded - that's healthy enough
@@ -246,6 +521,9 @@ impl Provider for NativeProvider {
                     latency_ms: Some(start.elapsed().as_millis() as u64),
                     credits_remaining: None,
                     message: None,
+                    plan: None,
+                    scan_credits: None,
+                    resets_at: None,
                 })
             }
             Err(e) => Ok(ProviderHealth {
@@ -254,6 +532,9 @@ impl Provider for NativeProvider {
                 latency_ms: Some(start.elapsed().as_millis() as u64),
                 credits_remaining: None,
                 message: Some(format!("API unreachable, local lookups available: {e}")),
+                plan: None,
+                scan_credits: None,
+                resets_at: None,
             }),
         }
     }
@@ -263,9 +544,16 @@ impl Provider for NativeProvider {
 impl HostLookup for NativeProvider {
     #[instrument(skip(self), fields(provider = "native"))]
     async fn lookup_host(&self, ip: &str) -> Result<HostInfo> {
+        let ip = validate_ip(ip)?.to_string();
+        let ip = ip.as_str();
         // Try i1.is cache first
-        match self.get::<I1HostResponse>(&format!("/host/{ip}")).await {
-            Ok(response) => Ok(response.data),
+        let endpoint = format!("/host/{ip}");
+        match self.get::<serde_json::Value>(&endpoint).await {
+            Ok(raw) => {
+                schema::recover_host("i1.is", &endpoint, &raw, |response: I1HostResponse| {
+                    response.data
+                })
+            }
             Err(I1Error::NotFound { .. }) => {
                 // Not in cache - return minimal info from local lookups
                 let whois = self.whois_local(ip).await.ok();
@@ -289,6 +577,7 @@ impl HostLookup for NativeProvider {
                     },
                     data: vec![],
                     last_update: None,
+                    raw: None,
                 })
             }
             Err(e) => Err(e),
@@ -301,8 +590,9 @@ impl SearchProvider for NativeProvider {
     #[instrument(skip(self), fields(provider = "native"))]
     async fn search(&self, query: &str, page: Option<u32>) -> Result<SearchResults> {
         let page_num = page.unwrap_or(1);
+        let page = page_num.to_string();
         let response: I1SearchResponse = self
-            .get(&format!("/search?q={query}&page={page_num}"))
+            .get_with_query("/search", &[("q", query), ("page", &page)])
             .await?;
 
         Ok(SearchResults {
@@ -316,7 +606,7 @@ impl SearchProvider for NativeProvider {
 
     #[instrument(skip(self), fields(provider = "native"))]
     async fn count(&self, query: &str) -> Result<u64> {
-        let response: I1CountResponse = self.get(&format!("/count?q={query}")).await?;
+        let response: I1CountResponse = self.get_with_query("/count", &[("q", query)]).await?;
         Ok(response.count)
     }
 }
@@ -393,3 +683,189 @@ struct I1CountResponse {
 struct I1DomainResponse {
     data: DomainInfo,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use wiremock::matchers::{bearer_token, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const IPV6: &str = include_str!("../../i1-core/tests/fixtures/ipv6.txt");
+
+    #[tokio::test]
+    async fn test_lookup_ipv6() {
+        for line in IPV6
+            .lines()
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        {
+            let (input, canonical) = line.split_once('\t').unwrap();
+            let server = MockServer::start().await;
+            Mock::given(method("GET"))
+                .and(path(format!("/host/{canonical}")))
+                .and(bearer_token("test-token"))
+                .respond_with(
+                    ResponseTemplate::new(200)
+                        .set_body_json(json!({ "data": { "ip_str": canonical } })),
+                )
+                .expect(1)
+                .mount(&server)
+                .await;
+            let provider = NativeProvider::with_url("test-token", server.uri());
+
+            let host = provider.lookup_host(input).await.unwrap();
+            assert_eq!(host.ip_addr(), canonical.parse().ok(), "{input}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_search_query_encoded() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/search"))
+            .and(query_param("q", "net:2001:db8::/32 & port:22"))
+            .and(query_param("page", "3"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "total": 1,
+                "results": [{ "ip_str": "2001:db8::22" }]
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/count"))
+            .and(query_param("q", "net:2001:db8::/32 & port:22"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "count": 1 })))
+            .mount(&server)
+            .await;
+        let provider = NativeProvider::with_url("test-token", server.uri());
+
+        let results = provider
+            .search("net:2001:db8::/32 & port:22", Some(3))
+            .await
+            .unwrap();
+        assert_eq!(results.results[0].ip_str, "2001:db8::22");
+        assert_eq!(
+            provider.count("net:2001:db8::/32 & port:22").await.unwrap(),
+            1
+        );
+    }
+
+    /// A provider with token `old` that refreshes to `new`, counting refreshes
+    fn refreshing(uri: String, refreshes: &Arc<AtomicUsize>) -> NativeProvider {
+        let refreshes = Arc::clone(refreshes);
+        NativeProvider::with_url("old", uri).with_token_refresh(move || {
+            let refreshes = Arc::clone(&refreshes);
+            async move {
+                refreshes.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+                Ok("new".to_string())
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn test_token_refresh() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(bearer_token("old"))
+            .respond_with(ResponseTemplate::new(401))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/count"))
+            .and(bearer_token("new"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "count": 7 })))
+            .expect(10)
+            .mount(&server)
+            .await;
+        let refreshes = Arc::new(AtomicUsize::new(0));
+        let provider = refreshing(server.uri(), &refreshes);
+
+        assert_eq!(provider.count("port:22").await.unwrap(), 7);
+        assert_eq!(refreshes.load(Ordering::SeqCst), 1);
+        assert!(matches!(provider.auth_config(), AuthConfig::Bearer { token } if token == "new"));
+
+        // Clones share the new token: no more refreshes
+        let clone = provider.clone();
+        assert_eq!(clone.count("port:22").await.unwrap(), 7);
+        assert_eq!(refreshes.load(Ordering::SeqCst), 1);
+
+        // Refresh set up after cloning still reaches the clone
+        let refreshes = Arc::new(AtomicUsize::new(0));
+        let provider = NativeProvider::with_url("old", server.uri());
+        let clone = provider.clone();
+        let counted = Arc::clone(&refreshes);
+        let _provider = provider.with_token_refresh(move || {
+            counted.fetch_add(1, Ordering::SeqCst);
+            async { Ok("new".to_string()) }
+        });
+        assert_eq!(clone.count("port:22").await.unwrap(), 7);
+        assert_eq!(refreshes.load(Ordering::SeqCst), 1);
+
+        // Many requests refused at once refresh once between them
+        let refreshes = Arc::new(AtomicUsize::new(0));
+        let provider = refreshing(server.uri(), &refreshes);
+        let tasks: Vec<_> = (0..7)
+            .map(|_| {
+                let provider = provider.clone();
+                tokio::spawn(async move { provider.count("port:22").await })
+            })
+            .collect();
+        for task in tasks {
+            assert_eq!(task.await.unwrap().unwrap(), 7);
+        }
+        assert_eq!(refreshes.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_token_refused() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(401))
+            .expect(3)
+            .mount(&server)
+            .await;
+
+        // Refreshed, tried once more, then given up on
+        let refreshes = Arc::new(AtomicUsize::new(0));
+        let provider = refreshing(server.uri(), &refreshes);
+        assert!(matches!(
+            provider.count("port:22").await,
+            Err(I1Error::Unauthorized)
+        ));
+        assert_eq!(refreshes.load(Ordering::SeqCst), 1);
+
+        // A static token isn't retried
+        let provider = NativeProvider::with_url("old", server.uri());
+        assert!(matches!(
+            provider.count("port:22").await,
+            Err(I1Error::Unauthorized)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_http_config_after_clone() {
+        let provider = NativeProvider::with_url("test-token", "http://192.0.2.1");
+        let clone = provider.clone();
+        let proxy = ProviderHttpConfig {
+            proxy: Some("http://127.0.0.1:9".to_string()),
+            ..ProviderHttpConfig::direct()
+        };
+        let provider = provider.with_http_config(&proxy).unwrap();
+        match provider.count("port:22").await {
+            Err(I1Error::Connection(message)) => {
+                assert!(
+                    message.contains("through proxy http://127.0.0.1:9"),
+                    "{message}"
+                );
+            }
+            other => panic!("expected to go through the proxy, got {other:?}"),
+        }
+
+        // The clone keeps its settings, but the token is still shared
+        assert_eq!(clone.inner.http_config, ProviderHttpConfig::default());
+        *provider.inner.token.write().unwrap() = Some("new".to_string());
+        assert!(matches!(clone.auth_config(), AuthConfig::Bearer { token } if token == "new"));
+    }
+}
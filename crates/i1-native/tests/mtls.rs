@@ -0,0 +1,155 @@
+//! Mutual TLS against a server that demands a client certificate from an
+//! i1-ca root.
+
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+use i1_ca::{IntermediateCa, KeyAlgorithm, LeafCertificate, LeafParams, RootCa};
+use i1_core::I1Error;
+use i1_native::NativeProvider;
+use i1_providers::{HealthStatus, Provider};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{RootCertStore, ServerConfig, ServerConnection, StreamOwned};
+
+/// A server on 127.0.0.1 presenting `server` and accepting only client
+/// certificates from `root`. It answers one request with `{}` and returns
+/// the client certificate's subject, or `None` if the handshake failed.
+fn serve_once(root: &RootCa, server: &LeafCertificate) -> (String, JoinHandle<Option<String>>) {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let mut roots = RootCertStore::empty();
+    roots
+        .add(CertificateDer::from_pem_slice(root.certificate_pem().as_bytes()).unwrap())
+        .unwrap();
+    let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+        .build()
+        .unwrap();
+    let chain = CertificateDer::pem_slice_iter(server.chain_pem.as_bytes())
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    let key = PrivateKeyDer::from_pem_slice(server.key_pem.as_bytes()).unwrap();
+    let config = ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_client_cert_verifier(verifier)
+        .with_single_cert(chain, key)
+        .unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("https://{}", listener.local_addr().unwrap());
+    let handle = std::thread::spawn(move || {
+        let (tcp, _) = listener.accept().unwrap();
+        let conn = ServerConnection::new(Arc::new(config)).unwrap();
+        let mut tls = StreamOwned::new(conn, tcp);
+
+        let mut request = Vec::new();
+        let mut buf = [0u8; 1024];
+        while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+            match tls.read(&mut buf) {
+                Ok(0) | Err(_) => return None,
+                Ok(n) => request.extend_from_slice(&buf[..n]),
+            }
+        }
+
+        let der = tls.conn.peer_certificates()?.first()?.clone();
+        let (_, cert) = x509_parser::parse_x509_certificate(&der).unwrap();
+        let subject = cert
+            .subject()
+            .iter_common_name()
+            .next()?
+            .as_str()
+            .ok()?
+            .to_string();
+
+        let body = "{}";
+        let response = format!(
+            "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\
+             connection: close\r\n\r\n{body}",
+            body.len()
+        );
+        tls.write_all(response.as_bytes()).ok()?;
+        tls.conn.send_close_notify();
+        tls.flush().ok()?;
+        Some(subject)
+    });
+    (url, handle)
+}
+
+#[tokio::test]
+async fn test_mtls_connects() {
+    let root = RootCa::generate("Test Root", KeyAlgorithm::EcdsaP256).unwrap();
+    let intermediate =
+        IntermediateCa::generate("Test Intermediate", &root, KeyAlgorithm::EcdsaP256).unwrap();
+    let server = LeafCertificate::issue(
+        &intermediate,
+        &LeafParams::new("127.0.0.1").ip("127.0.0.1".parse().unwrap()),
+    )
+    .unwrap();
+    let client =
+        LeafCertificate::issue(&intermediate, &LeafParams::client("token-42").validity(7)).unwrap();
+
+    let (url, handle) = serve_once(&root, &server);
+    let provider = NativeProvider::with_mtls_url(
+        "token-42",
+        url,
+        &client.cert_pem,
+        &client.chain_pem,
+        &client.key_pem,
+        Some(root.certificate_pem()),
+    )
+    .unwrap();
+    let health = provider.health_check().await.unwrap();
+    assert_eq!(health.status, HealthStatus::Healthy, "{:?}", health.message);
+    assert_eq!(handle.join().unwrap().as_deref(), Some("token-42"));
+
+    // A client certificate from another CA is turned away
+    let other = RootCa::generate("Other Root", KeyAlgorithm::EcdsaP256).unwrap();
+    let stranger =
+        LeafCertificate::issue(&other, &LeafParams::client("token-42").validity(7)).unwrap();
+    let (url, handle) = serve_once(&root, &server);
+    let provider = NativeProvider::with_mtls_url(
+        "token-42",
+        url,
+        &stranger.cert_pem,
+        "",
+        &stranger.key_pem,
+        Some(root.certificate_pem()),
+    )
+    .unwrap();
+    let health = provider.health_check().await.unwrap();
+    assert_eq!(health.status, HealthStatus::Degraded);
+    assert_eq!(handle.join().unwrap(), None);
+}
+
+#[test]
+fn test_invalid_identity() {
+    let root = RootCa::generate("Test Root", KeyAlgorithm::EcdsaP256).unwrap();
+    let client = LeafCertificate::issue(&root, &LeafParams::client("token-42")).unwrap();
+    let config = |cert: &str, key: &str| match NativeProvider::with_mtls("token-42", cert, "", key)
+    {
+        Err(I1Error::Config(message)) => message,
+        Err(e) => panic!("expected a config error, got {e}"),
+        Ok(_) => panic!("expected a config error"),
+    };
+
+    assert!(NativeProvider::with_mtls("token-42", &client.cert_pem, "", &client.key_pem).is_ok());
+    assert!(config("not a certificate", &client.key_pem).contains("PEM"));
+    assert!(config(&client.cert_pem, "").contains("key"));
+
+    // Server-only certificates can't identify a client
+    let server =
+        LeafCertificate::issue(&root, &LeafParams::new("host").dns("host.example")).unwrap();
+    assert!(config(&server.cert_pem, &server.key_pem).contains("client authentication"));
+
+    // Nor can an expired one
+    let key = rcgen::KeyPair::generate().unwrap();
+    let mut params = rcgen::CertificateParams::default();
+    params.not_before = rcgen::date_time_ymd(2020, 1, 1);
+    params.not_after = rcgen::date_time_ymd(2021, 1, 1);
+    params.extended_key_usages = vec![rcgen::ExtendedKeyUsagePurpose::ClientAuth];
+    let expired = params.self_signed(&key).unwrap();
+    assert!(config(&expired.pem(), &key.serialize_pem()).contains("expired"));
+}
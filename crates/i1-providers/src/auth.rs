@@ -15,7 +15,7 @@ pub enum AuthConfig {
     /// Basic authentication (Censys style)
     Basic { username: String, password: String },
 
-    /// Bearer token (`GreyNoise` style)
+    /// Bearer token (i1.is style)
     Bearer { token: String },
 
     /// No authentication (public endpoints)
@@ -47,10 +47,11 @@ impl AuthConfig {
         }
     }
 
-    /// Create GreyNoise-style auth (Bearer token)
-    pub fn greynoise(token: impl Into<String>) -> Self {
-        Self::Bearer {
-            token: token.into(),
+    /// Create GreyNoise-style auth (key header)
+    pub fn greynoise(key: impl Into<String>) -> Self {
+        Self::ApiKeyHeader {
+            key: key.into(),
+            header_name: "key".to_string(),
         }
     }
 
@@ -63,7 +64,7 @@ impl AuthConfig {
 }
 
 /// Rate limiting configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct RateLimitConfig {
     /// Requests per second
     pub requests_per_second: f64,
@@ -113,6 +114,22 @@ impl RateLimitConfig {
         }
     }
 
+    /// `GreyNoise` enterprise limits
+    pub const fn greynoise() -> Self {
+        Self {
+            requests_per_second: 5.0,
+            burst_size: 10,
+        }
+    }
+
+    /// `GreyNoise` community limits (the daily allowance is enforced upstream)
+    pub const fn greynoise_community() -> Self {
+        Self {
+            requests_per_second: 1.0,
+            burst_size: 2,
+        }
+    }
+
     /// i1.is native (generous for our own infra)
     pub const fn i1_native() -> Self {
         Self {
@@ -5,16 +5,24 @@
 //! This crate defines the core traits that all providers (Shodan, Censys,
 //! Criminal IP, i1 Native, etc.) must implement.
 
+use std::collections::HashMap;
 use std::net::IpAddr;
 
 use async_trait::async_trait;
-use i1_core::{HostInfo, Result};
+use i1_core::{Alert, CreateAlertRequest, HostInfo, I1Error, Result, Trigger};
 use serde::{Deserialize, Serialize};
 
 pub mod auth;
+pub mod http;
+pub mod limit;
+pub mod results;
+pub mod schema;
 pub mod types;
 
 pub use auth::*;
+pub use http::ProviderHttpConfig;
+pub use limit::{shared_limiter, RateLimiter};
+pub use results::{HostOrder, OverlapStats, ProviderStats, ResultSet};
 pub use types::*;
 
 /// Core provider trait - all providers must implement this.
@@ -76,6 +84,34 @@ pub trait DnsProvider: Provider {
     /// Reverse DNS lookup
     async fn reverse(&self, ip: &str) -> Result<Vec<String>>;
 
+    /// Resolve many hostnames (batch operation if supported). Every
+    /// hostname is a key; one that doesn't resolve has no addresses.
+    async fn resolve_many(&self, hostnames: &[&str]) -> Result<HashMap<String, Vec<IpAddr>>> {
+        let mut resolved = HashMap::with_capacity(hostnames.len());
+        for hostname in hostnames {
+            let ips = match self.resolve(hostname).await {
+                Err(I1Error::NotFound { .. }) => Vec::new(),
+                ips => ips?,
+            };
+            resolved.insert((*hostname).to_string(), ips);
+        }
+        Ok(resolved)
+    }
+
+    /// Reverse DNS lookup of many IPs (batch operation if supported). Every
+    /// IP is a key; one without PTR records has no hostnames.
+    async fn reverse_many(&self, ips: &[&str]) -> Result<HashMap<String, Vec<String>>> {
+        let mut reversed = HashMap::with_capacity(ips.len());
+        for ip in ips {
+            let hostnames = match self.reverse(ip).await {
+                Err(I1Error::NotFound { .. }) => Vec::new(),
+                hostnames => hostnames?,
+            };
+            reversed.insert((*ip).to_string(), hostnames);
+        }
+        Ok(reversed)
+    }
+
     /// Get domain information (subdomains, records, etc.)
     async fn domain_info(&self, domain: &str) -> Result<DomainInfo>;
 }
@@ -97,14 +133,53 @@ pub trait VulnProvider: Provider {
     async fn hosts_with_cve(&self, cve: &str) -> Result<SearchResults>;
 }
 
+/// IP reputation capability
+#[async_trait]
+pub trait ReputationProvider: Provider {
+    /// What the provider knows about the traffic from an IP address
+    async fn reputation(&self, ip: &str) -> Result<IpReputation>;
+}
+
+/// Network monitoring (alert) capability
+#[async_trait]
+pub trait AlertProvider: Provider {
+    /// List the account's alerts
+    async fn list_alerts(&self) -> Result<Vec<Alert>>;
+
+    /// Create an alert for the given networks
+    async fn create_alert(&self, request: &CreateAlertRequest) -> Result<Alert>;
+
+    /// Delete an alert
+    async fn delete_alert(&self, id: &str) -> Result<()>;
+
+    /// Turn on a trigger for an alert
+    async fn enable_trigger(&self, id: &str, trigger: &str) -> Result<()>;
+
+    /// Triggers the provider supports
+    async fn triggers(&self) -> Result<Vec<Trigger>>;
+
+    /// Hosts currently seen in an alert's networks
+    async fn alert_matches(&self, id: &str) -> Result<SearchResults>;
+}
+
 /// Provider health status
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProviderHealth {
     pub provider: String,
     pub status: HealthStatus,
     pub latency_ms: Option<u64>,
+    /// Query (lookup/search) credits left
     pub credits_remaining: Option<i64>,
     pub message: Option<String>,
+    /// Account plan name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plan: Option<String>,
+    /// On-demand scan credits left
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scan_credits: Option<i64>,
+    /// When the credits are next replenished
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resets_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -141,6 +216,62 @@ pub struct DomainInfo {
     pub expires: Option<chrono::DateTime<chrono::Utc>>,
 }
 
+impl DomainInfo {
+    /// Fully qualify a record or subdomain name relative to this domain.
+    ///
+    /// Providers disagree here: Shodan returns `www` or an empty string for
+    /// the apex, others return `www.example.com`.
+    pub fn fqdn(&self, name: &str) -> String {
+        let domain = self.domain.trim_end_matches('.').to_lowercase();
+        let name = name.trim().trim_end_matches('.').to_lowercase();
+
+        if name.is_empty() || name == "@" || name == domain {
+            domain
+        } else if name.ends_with(&format!(".{domain}")) {
+            name
+        } else {
+            format!("{name}.{domain}")
+        }
+    }
+
+    /// Rewrite subdomains and record names as FQDNs, sorted and deduplicated.
+    pub fn qualify(&mut self) {
+        let mut subdomains: Vec<String> = self.subdomains.iter().map(|s| self.fqdn(s)).collect();
+        subdomains.sort();
+        subdomains.dedup();
+        self.subdomains = subdomains;
+
+        let names: Vec<String> = self.records.iter().map(|r| self.fqdn(&r.name)).collect();
+        for (record, name) in self.records.iter_mut().zip(names) {
+            record.name = name;
+            record.record_type = record.record_type.to_uppercase();
+        }
+        self.records.sort_by(|a, b| {
+            (&a.record_type, &a.name, &a.value).cmp(&(&b.record_type, &b.name, &b.value))
+        });
+        self.records.dedup_by(|a, b| {
+            a.record_type == b.record_type && a.name == b.name && a.value == b.value
+        });
+    }
+
+    /// Fold another provider's view of the same domain into this one.
+    ///
+    /// Subdomains and records are unioned (by type, name and value).
+    /// Registration details already present here win.
+    pub fn merge(&mut self, other: Self) {
+        self.subdomains
+            .extend(other.subdomains.iter().map(|s| other.fqdn(s)));
+        self.records.extend(other.records.iter().map(|r| DnsRecord {
+            name: other.fqdn(&r.name),
+            ..r.clone()
+        }));
+        self.registrar = self.registrar.take().or(other.registrar);
+        self.created = self.created.or(other.created);
+        self.expires = self.expires.or(other.expires);
+        self.qualify();
+    }
+}
+
 /// DNS record
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DnsRecord {
@@ -180,3 +311,71 @@ pub struct VulnInfo {
     pub references: Option<Vec<String>>,
     pub verified: bool,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(record_type: &str, name: &str, value: &str) -> DnsRecord {
+        DnsRecord {
+            record_type: record_type.to_string(),
+            name: name.to_string(),
+            value: value.to_string(),
+            ttl: None,
+        }
+    }
+
+    #[test]
+    fn test_domain_merge() {
+        let mut shodan = DomainInfo {
+            domain: "example.com".to_string(),
+            subdomains: vec!["www".to_string(), "mail".to_string()],
+            records: vec![
+                record("A", "", "192.0.2.1"),
+                record("mx", "", "mail.example.com"),
+            ],
+            registrar: None,
+            created: None,
+            expires: None,
+        };
+        let native = DomainInfo {
+            domain: "example.com".to_string(),
+            subdomains: vec!["www.example.com".to_string(), "api.example.com".to_string()],
+            records: vec![
+                record("A", "example.com", "192.0.2.1"),
+                record("A", "www", "192.0.2.2"),
+            ],
+            registrar: Some("Example Registrar".to_string()),
+            created: None,
+            expires: None,
+        };
+
+        shodan.merge(native);
+
+        assert_eq!(
+            shodan.subdomains,
+            ["api.example.com", "mail.example.com", "www.example.com"]
+        );
+        assert_eq!(shodan.records.len(), 3);
+        assert_eq!(shodan.records[2].record_type, "MX");
+        assert_eq!(shodan.records[1].name, "www.example.com");
+        assert_eq!(shodan.registrar.as_deref(), Some("Example Registrar"));
+    }
+
+    #[test]
+    fn test_domain_fqdn() {
+        let info = DomainInfo {
+            domain: "Example.com.".to_string(),
+            subdomains: vec![],
+            records: vec![],
+            registrar: None,
+            created: None,
+            expires: None,
+        };
+
+        assert_eq!(info.fqdn(""), "example.com");
+        assert_eq!(info.fqdn("@"), "example.com");
+        assert_eq!(info.fqdn("www"), "www.example.com");
+        assert_eq!(info.fqdn("a.b.example.com."), "a.b.example.com");
+    }
+}
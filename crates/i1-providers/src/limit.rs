@@ -0,0 +1,73 @@
+//! Rate limiters shared by every provider instance using the same key.
+//!
+//! Each provider builds its own limiter by default, so instances never slow
+//! each other down. Providers enforce their limits per key, though, and two
+//! instances with one key would spend it twice as fast as allowed. The
+//! `new_shared` constructors take their limiter from [`shared_limiter`]
+//! instead, so every such instance in the process waits its turn on one.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use governor::{DefaultDirectRateLimiter, Quota};
+
+/// The rate limiter providers wait on before each request
+pub type RateLimiter = DefaultDirectRateLimiter;
+
+/// Shared limiters by provider name and key hash. Keys themselves are not
+/// kept.
+type Registry = HashMap<(&'static str, u64), Arc<RateLimiter>>;
+
+static SHARED: OnceLock<Mutex<Registry>> = OnceLock::new();
+
+/// The limiter every `provider` instance with `key` shares, created with
+/// `quota` by whichever asks first. Later quotas for the same key are
+/// ignored.
+pub fn shared_limiter(provider: &'static str, key: &str, quota: Quota) -> Arc<RateLimiter> {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+
+    let mut shared = SHARED
+        .get_or_init(Mutex::default)
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    Arc::clone(
+        shared
+            .entry((provider, hasher.finish()))
+            .or_insert_with(|| Arc::new(RateLimiter::direct(quota))),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU32;
+
+    use super::*;
+
+    #[test]
+    fn test_shared_limiter() {
+        let quota = Quota::per_second(NonZeroU32::MIN);
+        let a = shared_limiter("shodan", "limit-test-a", quota);
+
+        assert!(Arc::ptr_eq(
+            &a,
+            &shared_limiter("shodan", "limit-test-a", quota)
+        ));
+        assert!(!Arc::ptr_eq(
+            &a,
+            &shared_limiter("shodan", "limit-test-b", quota)
+        ));
+        assert!(!Arc::ptr_eq(
+            &a,
+            &shared_limiter("censys", "limit-test-a", quota)
+        ));
+
+        // One request a second between them
+        assert!(a.check().is_ok());
+        assert!(shared_limiter("shodan", "limit-test-a", quota)
+            .check()
+            .is_err());
+    }
+}
@@ -0,0 +1,391 @@
+//! Search results gathered across pages and providers.
+//!
+//! Providers format the same host differently, and a host can come back on
+//! several pages or from several providers. A [`ResultSet`] keeps one
+//! normalized record per IP, merged with [`HostInfo::merge`], and remembers
+//! which providers reported each one, so the overlap between providers can
+//! be reported alongside the hosts.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use i1_core::HostInfo;
+use serde::Serialize;
+
+use crate::SearchResults;
+
+/// Order of the hosts [`ResultSet::into_hosts`] returns.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HostOrder {
+    /// The order the hosts were first seen in
+    #[default]
+    Seen,
+    /// By address
+    Ip,
+    /// Most open ports first
+    Ports,
+    /// Most vulnerabilities first
+    Vulns,
+    /// Highest [`HostInfo::risk_score`] first
+    Risk,
+    /// Seen by the most providers first
+    Sources,
+}
+
+/// What one provider contributed to a [`ResultSet`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ProviderStats {
+    pub provider: String,
+    /// Results the provider reported for the query
+    pub total: u64,
+    /// Pages added from the provider
+    pub pages: u32,
+    /// Unique hosts the provider returned
+    pub hosts: usize,
+    /// Hosts no other provider returned
+    pub exclusive: usize,
+}
+
+/// How much the providers in a [`ResultSet`] agree.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct OverlapStats {
+    /// Unique hosts across every provider
+    pub hosts: usize,
+    /// Hosts returned by two providers or more
+    pub overlapping: usize,
+    /// Per provider, by name
+    pub providers: Vec<ProviderStats>,
+}
+
+/// A host and the providers that returned it
+#[derive(Debug)]
+struct Entry {
+    host: HostInfo,
+    sources: BTreeSet<String>,
+}
+
+/// Search results merged by IP across pages and providers.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let mut set = ResultSet::new().limit(500);
+/// set.add(shodan.search("nginx", Some(1)).await?);
+/// set.add(censys.search("nginx", Some(1)).await?);
+/// println!("{} hosts seen by both", set.stats().overlapping);
+/// let hosts = set.into_hosts(HostOrder::Risk);
+/// ```
+#[derive(Debug)]
+pub struct ResultSet {
+    entries: Vec<Entry>,
+    /// Position in `entries` by normalized IP
+    index: HashMap<String, usize>,
+    providers: BTreeMap<String, ProviderStats>,
+    limit: usize,
+    page: Option<u32>,
+    facets: Option<serde_json::Value>,
+}
+
+impl Default for ResultSet {
+    fn default() -> Self {
+        Self {
+            entries: Vec::new(),
+            index: HashMap::new(),
+            providers: BTreeMap::new(),
+            limit: usize::MAX,
+            page: None,
+            facets: None,
+        }
+    }
+}
+
+impl ResultSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stop taking new hosts once there are `limit` of them. Hosts already
+    /// in the set are still merged.
+    #[must_use]
+    pub const fn limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Add a page of results from one provider.
+    pub fn add(&mut self, page: SearchResults) {
+        let stats = self
+            .providers
+            .entry(page.provider.clone())
+            .or_insert_with(|| ProviderStats {
+                provider: page.provider.clone(),
+                ..ProviderStats::default()
+            });
+        // Every page repeats the total for the whole query
+        stats.total = stats.total.max(page.total);
+        stats.pages += 1;
+        self.page = Some(self.page.map_or(page.page, |first| first.min(page.page)));
+        if self.facets.is_none() {
+            self.facets = page.facets;
+        }
+
+        for mut host in page.results {
+            host.normalize();
+            if let Some(&i) = self.index.get(&host.ip_str) {
+                let entry = &mut self.entries[i];
+                entry.host.merge(host);
+                entry.sources.insert(page.provider.clone());
+            } else if self.entries.len() < self.limit {
+                self.index.insert(host.ip_str.clone(), self.entries.len());
+                self.entries.push(Entry {
+                    host,
+                    sources: BTreeSet::from([page.provider.clone()]),
+                });
+            }
+        }
+    }
+
+    /// Unique hosts so far
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Providers that added a page, by name
+    pub fn providers(&self) -> Vec<&str> {
+        self.providers.keys().map(String::as_str).collect()
+    }
+
+    /// Per-provider totals, and how many hosts more than one provider saw.
+    pub fn stats(&self) -> OverlapStats {
+        let mut providers = self.providers.clone();
+        for entry in &self.entries {
+            for source in &entry.sources {
+                if let Some(stats) = providers.get_mut(source) {
+                    stats.hosts += 1;
+                    if entry.sources.len() == 1 {
+                        stats.exclusive += 1;
+                    }
+                }
+            }
+        }
+
+        OverlapStats {
+            hosts: self.entries.len(),
+            overlapping: self
+                .entries
+                .iter()
+                .filter(|entry| entry.sources.len() > 1)
+                .count(),
+            providers: providers.into_values().collect(),
+        }
+    }
+
+    /// The merged hosts, in `order`. Ties keep the order they were seen in.
+    pub fn into_hosts(self, order: HostOrder) -> Vec<HostInfo> {
+        let mut entries = self.entries;
+        match order {
+            HostOrder::Seen => {}
+            HostOrder::Ip => entries.sort_by(|a, b| {
+                (a.host.ip_addr(), &a.host.ip_str).cmp(&(b.host.ip_addr(), &b.host.ip_str))
+            }),
+            HostOrder::Ports => entries.sort_by_key(|e| Reverse(e.host.open_ports().len())),
+            HostOrder::Vulns => entries.sort_by_key(|e| Reverse(e.host.vulns.len())),
+            HostOrder::Risk => {
+                entries.sort_by(|a, b| b.host.risk_score().total_cmp(&a.host.risk_score()));
+            }
+            HostOrder::Sources => entries.sort_by_key(|e| Reverse(e.sources.len())),
+        }
+        entries.into_iter().map(|entry| entry.host).collect()
+    }
+
+    /// The merged hosts as one [`SearchResults`], in `order`.
+    ///
+    /// `provider` names every provider that added a page and `total` adds
+    /// up their totals. Facets are only kept from a single provider, since
+    /// they can't be added up.
+    pub fn into_results(self, order: HostOrder) -> SearchResults {
+        let provider = self.providers().join(",");
+        let total = self.providers.values().map(|stats| stats.total).sum();
+        let page = self.page.unwrap_or(1);
+        let facets = self.facets.clone().filter(|_| self.providers.len() == 1);
+
+        SearchResults {
+            provider,
+            total,
+            page,
+            results: self.into_hosts(order),
+            facets,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use i1_core::testing::host;
+
+    fn page(provider: &str, number: u32, total: u64, hosts: Vec<HostInfo>) -> SearchResults {
+        SearchResults {
+            provider: provider.to_string(),
+            total,
+            page: number,
+            results: hosts,
+            facets: None,
+        }
+    }
+
+    /// Two pages from Shodan and one from Censys, overlapping on 192.0.2.1
+    /// and 192.0.2.2 but formatting them differently.
+    fn pages() -> Vec<SearchResults> {
+        vec![
+            page(
+                "shodan",
+                1,
+                3,
+                vec![
+                    host(
+                        "192.0.2.1",
+                        serde_json::json!({
+                            "asn": "AS64500",
+                            "hostnames": ["WWW.example.com."],
+                            "ports": [443],
+                            "tags": ["cloud", "cloud"],
+                        }),
+                    ),
+                    host("192.0.2.2", serde_json::json!({ "ports": [22] })),
+                ],
+            ),
+            page(
+                "shodan",
+                2,
+                3,
+                vec![
+                    host("192.0.2.1", serde_json::json!({ "ports": [80] })),
+                    host(
+                        "192.0.2.3",
+                        serde_json::json!({ "ports": [25], "vulns": ["cve-2024-0001"] }),
+                    ),
+                ],
+            ),
+            page(
+                "censys",
+                1,
+                2,
+                vec![
+                    host(
+                        "192.0.2.1",
+                        serde_json::json!({
+                            "asn": "64500",
+                            "org": "Example",
+                            "hostnames": ["www.example.com"],
+                            "ports": [80, 8080],
+                            "tags": ["Cloud"],
+                        }),
+                    ),
+                    host("192.0.2.2", serde_json::json!({ "ports": [22] })),
+                ],
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_merge_pages_and_providers() {
+        let mut set = ResultSet::new();
+        for page in pages() {
+            set.add(page);
+        }
+        assert_eq!(set.len(), 3);
+        assert_eq!(set.providers(), ["censys", "shodan"]);
+
+        let results = set.into_results(HostOrder::Seen);
+        assert_eq!(results.provider, "censys,shodan");
+        assert_eq!(results.total, 5);
+        assert_eq!(results.page, 1);
+
+        let first = &results.results[0];
+        assert_eq!(first.ip_str, "192.0.2.1");
+        assert_eq!(first.asn.as_deref(), Some("AS64500"));
+        assert_eq!(first.org.as_deref(), Some("Example"));
+        assert_eq!(first.hostnames, ["www.example.com"]);
+        assert_eq!(first.ports, [80, 443, 8080]);
+        assert_eq!(first.tags, ["cloud"]);
+        assert_eq!(results.results[2].vulns, ["CVE-2024-0001"]);
+    }
+
+    #[test]
+    fn test_overlap_stats() {
+        let mut set = ResultSet::new();
+        for page in pages() {
+            set.add(page);
+        }
+
+        let stats = set.stats();
+        assert_eq!(stats.hosts, 3);
+        assert_eq!(stats.overlapping, 2);
+        assert_eq!(
+            stats.providers,
+            [
+                ProviderStats {
+                    provider: "censys".to_string(),
+                    total: 2,
+                    pages: 1,
+                    hosts: 2,
+                    exclusive: 0,
+                },
+                ProviderStats {
+                    provider: "shodan".to_string(),
+                    total: 3,
+                    pages: 2,
+                    hosts: 3,
+                    exclusive: 1,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_order_and_limit() {
+        let mut set = ResultSet::new();
+        for page in pages() {
+            set.add(page);
+        }
+        let order = |hosts: Vec<HostInfo>| -> Vec<String> {
+            hosts.into_iter().map(|host| host.ip_str).collect()
+        };
+        assert_eq!(
+            order(set.into_hosts(HostOrder::Vulns)),
+            ["192.0.2.3", "192.0.2.1", "192.0.2.2"]
+        );
+
+        // The limit keeps new hosts out, but still merges known ones
+        let mut set = ResultSet::new().limit(1);
+        for page in pages() {
+            set.add(page);
+        }
+        let hosts = set.into_hosts(HostOrder::Ports);
+        assert_eq!(order(hosts.clone()), ["192.0.2.1"]);
+        assert_eq!(hosts[0].ports, [80, 443, 8080]);
+    }
+
+    #[test]
+    fn test_normalize_addresses() {
+        let mut set = ResultSet::new();
+        set.add(page(
+            "shodan",
+            1,
+            2,
+            vec![
+                host("2001:DB8:0:0::1", serde_json::json!({})),
+                host("2001:db8::1", serde_json::json!({ "asn": "as 64501" })),
+            ],
+        ));
+        let hosts = set.into_hosts(HostOrder::Ip);
+        assert_eq!(hosts.len(), 1);
+        assert_eq!(hosts[0].ip_str, "2001:db8::1");
+        assert_eq!(hosts[0].asn.as_deref(), Some("AS64501"));
+    }
+}
@@ -0,0 +1,353 @@
+//! Parsing provider responses that have drifted from their schema.
+//!
+//! Providers add, rename and retype fields without warning. Responses are
+//! read as JSON first and only then as the provider's types, so when the
+//! second step fails the error can say where: [`I1Error::Schema`] carries
+//! the path to the offending value (`data[2].port`) rather than serde's
+//! bare "missing field".
+//!
+//! Host lookups go further with [`recover_host`]: values that don't parse
+//! are dropped, innermost first, until the rest does. The host that comes
+//! back is tagged [`PARTIAL_PARSE_TAG`] so nobody mistakes it for the whole
+//! answer.
+//!
+//! With [`dump_responses`] set, every response that didn't parse is saved
+//! for a bug report.
+
+use std::fmt::{self, Write as _};
+use std::path::PathBuf;
+use std::sync::{PoisonError, RwLock};
+
+use i1_core::{HostInfo, I1Error, Result};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+/// Tag on a host some of whose response had to be dropped to parse it
+pub const PARTIAL_PARSE_TAG: &str = "partial-parse";
+
+/// Most values dropped from one response before giving up on it
+const MAX_DROPPED: usize = 32;
+
+static DUMP_DIR: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+/// Save responses that don't parse under `dir`, or stop saving them.
+pub fn dump_responses(dir: Option<PathBuf>) {
+    *DUMP_DIR.write().unwrap_or_else(PoisonError::into_inner) = dir;
+}
+
+/// One step of a [`JsonPath`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// Where a value sits in a JSON document, printed as `data[2].port`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct JsonPath(Vec<Segment>);
+
+impl fmt::Display for JsonPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            return f.write_str("$");
+        }
+        for (i, segment) in self.0.iter().enumerate() {
+            match segment {
+                Segment::Index(n) => write!(f, "[{n}]")?,
+                Segment::Key(key)
+                    if !key.is_empty()
+                        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') =>
+                {
+                    if i > 0 {
+                        f.write_str(".")?;
+                    }
+                    f.write_str(key)?;
+                }
+                Segment::Key(key) => write!(f, "[{key:?}]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Why a response doesn't parse as `T`: where, and serde's complaint.
+#[derive(Debug, Clone)]
+struct Mismatch {
+    path: JsonPath,
+    message: String,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// `value` as `T`, or an [`I1Error::Schema`] saying where it stopped
+/// matching.
+pub fn parse<T: DeserializeOwned>(provider: &str, endpoint: &str, value: &Value) -> Result<T> {
+    T::deserialize(value)
+        .map_err(|e| schema_error(provider, endpoint, value, &mismatch::<T>(value, &e)))
+}
+
+/// A host from `value` read as `T` and converted, dropping whatever stops
+/// it from parsing.
+///
+/// A host that lost anything is tagged [`PARTIAL_PARSE_TAG`]; one that
+/// can't be had at all is an [`I1Error::Schema`] about the first problem
+/// found.
+pub fn recover_host<T: DeserializeOwned>(
+    provider: &str,
+    endpoint: &str,
+    value: &Value,
+    convert: impl FnOnce(T) -> HostInfo,
+) -> Result<HostInfo> {
+    let first = match T::deserialize(value) {
+        Ok(parsed) => return Ok(convert(parsed)),
+        Err(e) => mismatch::<T>(value, &e),
+    };
+
+    let mut pruned = value.clone();
+    let mut last = first.clone();
+    let mut dropped = 0;
+    while !last.path.0.is_empty() && dropped < MAX_DROPPED {
+        remove(&mut pruned, &last.path);
+        dropped += 1;
+        match T::deserialize(&pruned) {
+            Ok(parsed) => {
+                // Saved for the bug report, though the lookup goes on
+                dump(provider, endpoint, value);
+                let mut host = convert(parsed);
+                host.tags.push(PARTIAL_PARSE_TAG.to_string());
+                return Ok(host);
+            }
+            Err(e) => last = mismatch::<T>(&pruned, &e),
+        }
+    }
+    Err(schema_error(provider, endpoint, value, &first))
+}
+
+/// Where in `value` deserializing `T` failed with `error`.
+///
+/// Errors from a [`Value`] carry no position, so the value is printed one
+/// entry per line and parsed again; the line that fails then says which
+/// entry it was.
+fn mismatch<T: DeserializeOwned>(value: &Value, error: &serde_json::Error) -> Mismatch {
+    let located = serde_json::to_string_pretty(value)
+        .ok()
+        .and_then(|text| serde_json::from_str::<T>(&text).err());
+    let Some(located) = located else {
+        return Mismatch {
+            path: JsonPath::default(),
+            message: error.to_string(),
+        };
+    };
+
+    let mut message = located.to_string();
+    if let Some(at) = message.rfind(" at line ") {
+        message.truncate(at);
+    }
+    let mut path = JsonPath::default();
+    let mut line = 1;
+    locate(value, located.line(), &mut line, &mut path);
+    Mismatch { path, message }
+}
+
+/// Find the entry `value` prints on line `target` of its pretty form,
+/// counting from `line`. Leaves `path` at it, or at the container a
+/// closing bracket on that line ends.
+fn locate(value: &Value, target: usize, line: &mut usize, path: &mut JsonPath) -> bool {
+    let entries: Vec<(Segment, &Value)> = match value {
+        Value::Object(map) => map
+            .iter()
+            .map(|(key, child)| (Segment::Key(key.clone()), child))
+            .collect(),
+        Value::Array(items) => items
+            .iter()
+            .enumerate()
+            .map(|(i, child)| (Segment::Index(i), child))
+            .collect(),
+        _ => return false,
+    };
+    // `{}` and `[]` print on one line
+    if entries.is_empty() {
+        return false;
+    }
+
+    for (segment, child) in entries {
+        *line += 1;
+        path.0.push(segment);
+        if *line == target || locate(child, target, line, path) {
+            return true;
+        }
+        path.0.pop();
+    }
+    *line += 1;
+    *line == target
+}
+
+/// Take the value at `path` out of `value`.
+fn remove(value: &mut Value, path: &JsonPath) {
+    let Some((last, parents)) = path.0.split_last() else {
+        return;
+    };
+    let mut parent = value;
+    for segment in parents {
+        let child = match (segment, parent) {
+            (Segment::Key(key), Value::Object(map)) => map.get_mut(key),
+            (Segment::Index(i), Value::Array(items)) => items.get_mut(*i),
+            _ => None,
+        };
+        let Some(child) = child else {
+            return;
+        };
+        parent = child;
+    }
+    match (last, parent) {
+        (Segment::Key(key), Value::Object(map)) => {
+            map.remove(key);
+        }
+        (Segment::Index(i), Value::Array(items)) if *i < items.len() => {
+            items.remove(*i);
+        }
+        _ => {}
+    }
+}
+
+fn schema_error(provider: &str, endpoint: &str, value: &Value, mismatch: &Mismatch) -> I1Error {
+    let mut detail = mismatch.to_string();
+    if let Some(path) = dump(provider, endpoint, value) {
+        let _ = write!(detail, " (response saved to {})", path.display());
+    }
+    I1Error::Schema {
+        provider: provider.to_string(),
+        endpoint: endpoint.to_string(),
+        detail,
+    }
+}
+
+/// Save `value` under the [`dump_responses`] directory, if there is one.
+fn dump(provider: &str, endpoint: &str, value: &Value) -> Option<PathBuf> {
+    let dir = DUMP_DIR
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()?;
+    let path = dir.join(dump_name(provider, endpoint));
+    let text = serde_json::to_string_pretty(value).ok()?;
+    std::fs::create_dir_all(&dir).ok()?;
+    std::fs::write(&path, text).ok()?;
+    Some(path)
+}
+
+/// `shodan-shodan_host_192.0.2.1-20240501T100000.123Z.json`
+fn dump_name(provider: &str, endpoint: &str) -> String {
+    let endpoint: String = endpoint
+        .trim_start_matches('/')
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '-') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let at = chrono::Utc::now().format("%Y%m%dT%H%M%S%.3fZ");
+    format!("{provider}-{endpoint}-{at}.json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use serde_json::json;
+
+    #[derive(Debug, Deserialize)]
+    struct Response {
+        data: Vec<Banner>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Banner {
+        port: u16,
+        #[serde(default)]
+        product: Option<String>,
+    }
+
+    fn host(response: Response) -> HostInfo {
+        let (ports, products): (Vec<u16>, Vec<Option<String>>) = response
+            .data
+            .into_iter()
+            .map(|banner| (banner.port, banner.product))
+            .unzip();
+        let os = products.into_iter().flatten().next();
+        i1_core::testing::host("192.0.2.1", json!({ "ports": ports, "os": os }))
+    }
+
+    fn schema_detail(error: I1Error) -> String {
+        match error {
+            I1Error::Schema { detail, .. } => detail,
+            other => panic!("expected a schema error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_names_the_path() {
+        let retyped = json!({ "data": [{ "port": 22 }, { "port": "80" }] });
+        let detail = schema_detail(parse::<Response>("shodan", "/host", &retyped).unwrap_err());
+        assert_eq!(
+            detail,
+            "data[1].port: invalid type: string \"80\", expected u16"
+        );
+
+        let renamed = json!({ "data": [{ "port": 22 }, { "portnum": 80 }] });
+        let detail = schema_detail(parse::<Response>("shodan", "/host", &renamed).unwrap_err());
+        assert_eq!(detail, "data[1]: missing field `port`");
+
+        let gone = json!({ "banners": [] });
+        let error = parse::<Response>("shodan", "/host", &gone).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "unexpected shodan response from /host: $: missing field `data`"
+        );
+        assert_eq!(error.kind(), "schema");
+    }
+
+    #[test]
+    fn test_recover_partial_host() {
+        let value = json!({
+            "data": [
+                { "port": 22, "product": "OpenSSH" },
+                { "port": "80" },
+                { "portnum": 443 },
+                { "port": 8080, "product": ["not", "a", "string"] },
+            ]
+        });
+        let partial = recover_host("shodan", "/host", &value, host).unwrap();
+        assert_eq!(partial.ports, [22, 8080]);
+        assert_eq!(partial.os.as_deref(), Some("OpenSSH"));
+        assert_eq!(partial.tags, [PARTIAL_PARSE_TAG]);
+
+        let whole = json!({ "data": [{ "port": 22 }] });
+        let complete = recover_host("shodan", "/host", &whole, host).unwrap();
+        assert!(complete.tags.is_empty());
+    }
+
+    #[test]
+    fn test_unrecoverable_reports_first_problem() {
+        let value = json!({ "data": { "port": 22 } });
+        let detail = schema_detail(recover_host("shodan", "/host", &value, host).unwrap_err());
+        assert_eq!(detail, "data: invalid type: map, expected a sequence");
+    }
+
+    #[test]
+    fn test_path_display() {
+        let path = JsonPath(vec![
+            Segment::Key("services".to_string()),
+            Segment::Index(0),
+            Segment::Key("http.title".to_string()),
+        ]);
+        assert_eq!(path.to_string(), "services[0][\"http.title\"]");
+        assert_eq!(JsonPath::default().to_string(), "$");
+    }
+}
@@ -1,5 +1,6 @@
 //! Common types shared across providers.
 
+use i1_core::HostInfo;
 use serde::{Deserialize, Serialize};
 
 /// Provider identifier
@@ -41,6 +42,21 @@ impl std::fmt::Display for ProviderId {
     }
 }
 
+impl std::str::FromStr for ProviderId {
+    type Err = i1_core::I1Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "shodan" => Ok(Self::Shodan),
+            "censys" => Ok(Self::Censys),
+            "criminalip" | "criminal-ip" => Ok(Self::CriminalIp),
+            "greynoise" => Ok(Self::GreyNoise),
+            "native" | "i1" => Ok(Self::Native),
+            _ => Err(i1_core::I1Error::ProviderNotConfigured(s.to_string())),
+        }
+    }
+}
+
 /// Result from multiple providers merged together
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MergedHostInfo {
@@ -56,11 +72,127 @@ pub struct MergedHostInfo {
     pub vulns: Vec<String>,
     pub tags: Vec<String>,
     pub last_seen: Option<chrono::DateTime<chrono::Utc>>,
-    /// Provider-specific raw data
+    /// Each provider's original response, keyed by provider, for the
+    /// hosts that kept one
     #[serde(skip_serializing_if = "Option::is_none")]
     pub raw: Option<serde_json::Value>,
 }
 
+impl MergedHostInfo {
+    /// Merge host records from several providers.
+    ///
+    /// Single-valued fields come from the first provider that has them, so
+    /// callers control precedence through the order of `hosts`. Lists are
+    /// unioned, ports are keyed by port and protocol, and `last_seen` is the
+    /// most recent scan any provider reported. Raw responses are kept
+    /// under each provider's name.
+    #[must_use]
+    pub fn from_hosts(ip: &str, hosts: &[(ProviderId, HostInfo)]) -> Self {
+        let first =
+            |f: fn(&HostInfo) -> Option<&String>| hosts.iter().find_map(|(_, h)| f(h).cloned());
+
+        let raw: serde_json::Map<String, serde_json::Value> = hosts
+            .iter()
+            .filter_map(|(id, h)| Some((id.as_str().to_string(), (**h.raw.as_ref()?).clone())))
+            .collect();
+
+        let mut merged = Self {
+            ip: ip.to_string(),
+            sources: hosts.iter().map(|(id, _)| *id).collect(),
+            hostnames: Vec::new(),
+            ports: Vec::new(),
+            os: first(|h| h.os.as_ref()),
+            org: first(|h| h.org.as_ref()),
+            asn: first(|h| h.asn.as_ref()),
+            country: first(|h| h.location.country_name.as_ref()),
+            city: first(|h| h.location.city.as_ref()),
+            vulns: Vec::new(),
+            tags: Vec::new(),
+            last_seen: hosts
+                .iter()
+                .filter_map(|(_, h)| h.last_update.as_deref().and_then(parse_timestamp))
+                .max(),
+            raw: (!raw.is_empty()).then_some(serde_json::Value::Object(raw)),
+        };
+
+        for (_, host) in hosts {
+            union(&mut merged.hostnames, &host.hostnames);
+            union(&mut merged.vulns, &host.vulns);
+            union(&mut merged.tags, &host.tags);
+            merged.add_ports(host);
+        }
+
+        merged
+            .ports
+            .sort_by(|a, b| (a.port, &a.protocol).cmp(&(b.port, &b.protocol)));
+        merged
+    }
+
+    fn add_ports(&mut self, host: &HostInfo) {
+        for svc in &host.data {
+            let protocol = svc.transport.to_string();
+            match self
+                .ports
+                .iter_mut()
+                .find(|p| p.port == svc.port && p.protocol == protocol)
+            {
+                Some(existing) => {
+                    if existing.product.is_none() {
+                        existing.product.clone_from(&svc.product);
+                    }
+                    if existing.version.is_none() {
+                        existing.version.clone_from(&svc.version);
+                    }
+                    if existing.banner.is_none() {
+                        existing.banner.clone_from(&svc.data);
+                    }
+                }
+                None => self.ports.push(PortInfo {
+                    port: svc.port,
+                    protocol,
+                    service: None,
+                    product: svc.product.clone(),
+                    version: svc.version.clone(),
+                    banner: svc.data.clone(),
+                }),
+            }
+        }
+
+        // Providers that only report port numbers
+        for &port in &host.ports {
+            if !self.ports.iter().any(|p| p.port == port) {
+                self.ports.push(PortInfo {
+                    port,
+                    protocol: "tcp".to_string(),
+                    service: None,
+                    product: None,
+                    version: None,
+                    banner: None,
+                });
+            }
+        }
+    }
+}
+
+/// Append the values of `extra` that are not already in `list`
+fn union(list: &mut Vec<String>, extra: &[String]) {
+    for value in extra {
+        if !list.contains(value) {
+            list.push(value.clone());
+        }
+    }
+}
+
+/// Parse provider timestamps (RFC 3339, or Shodan's zone-less ISO 8601 in UTC)
+fn parse_timestamp(s: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    chrono::DateTime::parse_from_rfc3339(s)
+        .map(|t| t.with_timezone(&chrono::Utc))
+        .or_else(|_| {
+            chrono::NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S%.f").map(|t| t.and_utc())
+        })
+        .ok()
+}
+
 /// Port information
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PortInfo {
@@ -90,8 +222,40 @@ pub enum IpClassification {
     Riot,
 }
 
-/// Threat level assessment
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+/// Reputation of an IP address, from what a provider has seen it do
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IpReputation {
+    pub ip: String,
+    pub provider: String,
+    /// Whether the provider has seen the address at all
+    pub seen: bool,
+    pub classification: IpClassification,
+    /// Who is behind the traffic (e.g. a research scanner), when known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actor: Option<String>,
+    /// Behaviors or tools the provider tagged the address with
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_seen: Option<chrono::DateTime<chrono::Utc>>,
+    /// Where to read more about the address
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub link: Option<String>,
+}
+
+impl IpReputation {
+    /// How worrying traffic from the address is
+    pub const fn threat_level(&self) -> ThreatLevel {
+        match self.classification {
+            IpClassification::Malicious => ThreatLevel::High,
+            IpClassification::Unknown if self.seen => ThreatLevel::Low,
+            _ => ThreatLevel::None,
+        }
+    }
+}
+
+/// Threat level assessment, ordered from `None` up to `Critical`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum ThreatLevel {
     None,
@@ -101,6 +265,27 @@ pub enum ThreatLevel {
     Critical,
 }
 
+/// Validate and normalize a CVE identifier (`cve-2024-3400` -> `CVE-2024-3400`)
+pub fn parse_cve_id(id: &str) -> i1_core::Result<String> {
+    let id = id.trim().to_uppercase();
+    let valid = id.strip_prefix("CVE-").is_some_and(|rest| {
+        rest.split_once('-').is_some_and(|(year, seq)| {
+            year.len() == 4
+                && seq.len() >= 4
+                && year.bytes().all(|b| b.is_ascii_digit())
+                && seq.bytes().all(|b| b.is_ascii_digit())
+        })
+    });
+
+    if valid {
+        Ok(id)
+    } else {
+        Err(i1_core::I1Error::InvalidQuery(format!(
+            "'{id}' is not a CVE id (expected CVE-YYYY-NNNN)"
+        )))
+    }
+}
+
 impl ThreatLevel {
     pub fn from_cvss(cvss: f32) -> Self {
         match cvss {
@@ -112,3 +297,102 @@ impl ThreatLevel {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use i1_core::testing::host;
+    use serde_json::json;
+
+    #[test]
+    fn test_merge_hosts() {
+        let merged = MergedHostInfo::from_hosts(
+            "192.0.2.1",
+            &[
+                (
+                    ProviderId::Shodan,
+                    host(
+                        "192.0.2.1",
+                        json!({
+                            "ports": [443, 22],
+                            "vulns": ["CVE-2024-0001"],
+                            "last_update": "2024-01-01T00:00:00.000000",
+                        }),
+                    ),
+                ),
+                (
+                    ProviderId::Censys,
+                    host(
+                        "192.0.2.1",
+                        json!({
+                            "org": "Example",
+                            "ports": [22, 80],
+                            "vulns": ["CVE-2024-0001"],
+                            "last_update": "2024-03-01T00:00:00Z",
+                        }),
+                    ),
+                ),
+            ],
+        );
+
+        assert_eq!(merged.sources, vec![ProviderId::Shodan, ProviderId::Censys]);
+        assert_eq!(merged.org.as_deref(), Some("Example"));
+        assert_eq!(
+            merged.ports.iter().map(|p| p.port).collect::<Vec<_>>(),
+            vec![22, 80, 443]
+        );
+        assert_eq!(merged.vulns, vec!["CVE-2024-0001"]);
+        assert_eq!(
+            merged.last_seen.map(|t| t.to_rfc3339()),
+            Some("2024-03-01T00:00:00+00:00".to_string())
+        );
+    }
+
+    #[test]
+    fn test_merge_raw() {
+        let updated = "2024-01-01T00:00:00Z";
+        let mut shodan = host(
+            "192.0.2.1",
+            json!({ "ports": [22], "last_update": updated }),
+        );
+        shodan.raw = Some(Box::new(json!({"_shodan": {"module": "ssh"}})));
+        let censys = host(
+            "192.0.2.1",
+            json!({ "ports": [80], "last_update": updated }),
+        );
+
+        let merged = MergedHostInfo::from_hosts(
+            "192.0.2.1",
+            &[
+                (ProviderId::Shodan, shodan),
+                (ProviderId::Censys, censys.clone()),
+            ],
+        );
+        assert_eq!(
+            merged.raw,
+            Some(json!({"shodan": {"_shodan": {"module": "ssh"}}}))
+        );
+
+        let merged = MergedHostInfo::from_hosts("192.0.2.1", &[(ProviderId::Censys, censys)]);
+        assert!(merged.raw.is_none());
+    }
+
+    #[test]
+    fn test_parse_cve_id() {
+        assert_eq!(parse_cve_id("cve-2024-3400").unwrap(), "CVE-2024-3400");
+        assert_eq!(parse_cve_id("CVE-2021-44228").unwrap(), "CVE-2021-44228");
+        assert!(parse_cve_id("CVE-24-3400").is_err());
+        assert!(parse_cve_id("CVE-2024-12").is_err());
+        assert!(parse_cve_id("CVE-2024-3400 OR port:22").is_err());
+    }
+
+    #[test]
+    fn test_provider_id_parse() {
+        assert_eq!("Shodan".parse::<ProviderId>().unwrap(), ProviderId::Shodan);
+        assert_eq!(
+            "criminalip".parse::<ProviderId>().unwrap(),
+            ProviderId::CriminalIp
+        );
+        assert!("bogus".parse::<ProviderId>().is_err());
+    }
+}
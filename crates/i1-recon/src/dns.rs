@@ -1,7 +1,24 @@
 //! DNS resolution integration.
 
+mod audit;
+mod bruteforce;
+#[cfg(test)]
+mod testing;
+
+pub use audit::{
+    audit, DnsAuditReport, DnsAuditor, DnsCheck, DnsFinding, Severity, ZoneRecord, ZoneTransfer,
+    DEFAULT_TAKEOVER_SUFFIXES,
+};
+pub use bruteforce::{
+    bruteforce_subdomains, correlate, default_wordlist, domain_info, parse_wordlist,
+    CorrelatedDomain, Probe, SubdomainBruteforcer, BRUTEFORCE_SOURCE, DEFAULT_CONCURRENCY,
+};
+
 use crate::error::{ReconError, ReconResult};
-use std::net::IpAddr;
+use hickory_resolver::config::{NameServerConfigGroup, ResolverConfig};
+use hickory_resolver::name_server::TokioConnectionProvider;
+use hickory_resolver::TokioResolver;
+use std::net::{IpAddr, SocketAddr};
 use std::time::Duration;
 
 /// DNS lookup result
@@ -31,7 +48,7 @@ impl Default for DnsResolver {
 impl DnsResolver {
     /// Create a resolver using default configuration
     #[must_use]
-    pub fn new() -> Self {
+    pub const fn new() -> Self {
         Self { _private: () }
     }
 
@@ -39,13 +56,11 @@ impl DnsResolver {
     pub async fn lookup(&self, hostname: &str) -> ReconResult<Vec<IpAddr>> {
         use tokio::net::lookup_host;
 
-        let start = std::time::Instant::now();
-
         // Use port 0 for lookup
         let addr_str = format!("{hostname}:0");
         let addrs = lookup_host(&addr_str)
             .await
-            .map_err(|e| ReconError::Dns(e.to_string()))?;
+            .map_err(|e| ReconError::Dns(e.into()))?;
 
         Ok(addrs.map(|a| a.ip()).collect())
     }
@@ -57,19 +72,12 @@ impl DnsResolver {
         let start = std::time::Instant::now();
 
         // Use hickory for reverse lookups
-        use hickory_resolver::{config::*, TokioAsyncResolver};
-
-        let resolver = TokioAsyncResolver::tokio(
-            ResolverConfig::default(),
-            ResolverOpts::default(),
-        );
-
-        let response = resolver
+        let response = resolver()
             .reverse_lookup(ip)
             .await
-            .map_err(|e| ReconError::Dns(e.to_string()))?;
+            .map_err(|e| ReconError::Dns(e.into()))?;
 
-        let hostnames: Vec<String> = response.iter().map(|n| n.to_string()).collect();
+        let hostnames: Vec<String> = response.iter().map(ToString::to_string).collect();
 
         Ok(DnsLookupResult {
             query: ip.to_string(),
@@ -81,60 +89,75 @@ impl DnsResolver {
 
     /// Lookup MX records for a domain
     pub async fn lookup_mx(&self, domain: &str) -> ReconResult<Vec<String>> {
-        use hickory_resolver::{config::*, TokioAsyncResolver};
-
-        let resolver = TokioAsyncResolver::tokio(
-            ResolverConfig::default(),
-            ResolverOpts::default(),
-        );
-
-        let response = resolver
+        let response = resolver()
             .mx_lookup(domain)
             .await
-            .map_err(|e| ReconError::Dns(e.to_string()))?;
+            .map_err(|e| ReconError::Dns(e.into()))?;
 
-        Ok(response.iter().map(|mx| mx.exchange().to_string()).collect())
+        Ok(response
+            .iter()
+            .map(|mx| mx.exchange().to_string())
+            .collect())
     }
 
     /// Lookup TXT records for a domain
     pub async fn lookup_txt(&self, domain: &str) -> ReconResult<Vec<String>> {
-        use hickory_resolver::{config::*, TokioAsyncResolver};
-
-        let resolver = TokioAsyncResolver::tokio(
-            ResolverConfig::default(),
-            ResolverOpts::default(),
-        );
-
-        let response = resolver
+        let response = resolver()
             .txt_lookup(domain)
             .await
-            .map_err(|e| ReconError::Dns(e.to_string()))?;
+            .map_err(|e| ReconError::Dns(e.into()))?;
 
         Ok(response
             .iter()
             .map(|txt| {
                 txt.iter()
-                    .map(|data| String::from_utf8_lossy(data).to_string())
-                    .collect::<Vec<_>>()
-                    .join("")
+                    .map(|data| String::from_utf8_lossy(data))
+                    .collect::<String>()
             })
             .collect())
     }
 
     /// Lookup NS records for a domain
     pub async fn lookup_ns(&self, domain: &str) -> ReconResult<Vec<String>> {
-        use hickory_resolver::{config::*, TokioAsyncResolver};
-
-        let resolver = TokioAsyncResolver::tokio(
-            ResolverConfig::default(),
-            ResolverOpts::default(),
-        );
-
-        let response = resolver
+        let response = resolver()
             .ns_lookup(domain)
             .await
-            .map_err(|e| ReconError::Dns(e.to_string()))?;
+            .map_err(|e| ReconError::Dns(e.into()))?;
+
+        Ok(response.iter().map(ToString::to_string).collect())
+    }
 
-        Ok(response.iter().map(|ns| ns.to_string()).collect())
+    /// Addresses of the authoritative nameservers for a domain, port 53
+    pub async fn nameservers(&self, domain: &str) -> ReconResult<Vec<SocketAddr>> {
+        let mut addrs = Vec::new();
+        for ns in self.lookup_ns(domain).await? {
+            for ip in self.lookup(ns.trim_end_matches('.')).await? {
+                let addr = SocketAddr::new(ip, 53);
+                if !addrs.contains(&addr) {
+                    addrs.push(addr);
+                }
+            }
+        }
+        Ok(addrs)
     }
 }
+
+/// A resolver using the default upstream configuration
+fn resolver() -> TokioResolver {
+    TokioResolver::builder_with_config(
+        ResolverConfig::default(),
+        TokioConnectionProvider::default(),
+    )
+    .build()
+}
+
+/// A resolver asking only `server`, giving up on a query after `timeout`
+fn resolver_at(server: SocketAddr, timeout: Duration) -> TokioResolver {
+    let servers = NameServerConfigGroup::from_ips_clear(&[server.ip()], server.port(), true);
+    let mut builder = TokioResolver::builder_with_config(
+        ResolverConfig::from_parts(None, Vec::new(), servers),
+        TokioConnectionProvider::default(),
+    );
+    builder.options_mut().timeout = timeout;
+    builder.build()
+}
@@ -0,0 +1,802 @@
+//! Misconfiguration checks for a domain, asked of its own nameservers.
+//!
+//! [`DnsAuditor`] tries a zone transfer (AXFR) against every nameserver,
+//! looks for a wildcard, follows CNAMEs into cloud services where anyone
+//! can claim a name once the resource behind it is deleted, and checks
+//! that the domain publishes SPF and DMARC. Findings come back worst first.
+
+use std::cmp::Reverse;
+use std::net::SocketAddr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use hickory_proto::op::{Message, MessageType, OpCode, Query, ResponseCode};
+use hickory_proto::rr::{Name, RData, Record, RecordType};
+use hickory_resolver::TokioResolver;
+use serde::Serialize;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+
+use crate::error::{ReconError, ReconResult};
+
+/// Cloud services that hand out names to whoever creates the resource, so
+/// a CNAME left pointing at a deleted one can be taken over
+pub const DEFAULT_TAKEOVER_SUFFIXES: &[&str] = &[
+    "*.s3.amazonaws.com",
+    "*.elasticbeanstalk.com",
+    "*.cloudfront.net",
+    "*.azurewebsites.net",
+    "*.cloudapp.net",
+    "*.cloudapp.azure.com",
+    "*.blob.core.windows.net",
+    "*.trafficmanager.net",
+    "*.azureedge.net",
+    "*.herokuapp.com",
+    "*.herokudns.com",
+    "*.github.io",
+    "*.netlify.app",
+    "*.ghost.io",
+    "*.myshopify.com",
+    "*.pantheonsite.io",
+    "*.readthedocs.io",
+    "*.surge.sh",
+];
+
+/// Largest UDP answer accepted; bigger ones come back truncated and are
+/// asked again over TCP
+pub(super) const MAX_UDP_SIZE: usize = 4096;
+
+/// How bad a finding is, ordered from `Info` up to `High`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Low,
+    Medium,
+    High,
+}
+
+/// The check that raised a finding
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DnsCheck {
+    /// A nameserver transferred the zone to us
+    ZoneTransfer,
+    /// Every name under the domain resolves
+    Wildcard,
+    /// A CNAME into a cloud service, at a name nobody holds
+    DanglingCname,
+    /// SPF is missing, broken or lets anyone send
+    Spf,
+    /// DMARC is missing or doesn't act on failures
+    Dmarc,
+}
+
+/// One problem found by [`DnsAuditor::audit`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DnsFinding {
+    pub severity: Severity,
+    pub check: DnsCheck,
+    /// The name the finding is about
+    pub name: String,
+    pub detail: String,
+}
+
+/// A record from a zone transfer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ZoneRecord {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub record_type: String,
+    pub ttl: u32,
+    pub value: String,
+}
+
+/// The outcome of asking one nameserver for the zone
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ZoneTransfer {
+    pub nameserver: SocketAddr,
+    /// Whether the nameserver sent the zone
+    pub allowed: bool,
+    /// What it sent, SOA first
+    pub records: Vec<ZoneRecord>,
+    /// Why the nameserver couldn't be asked, if it couldn't
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Everything [`DnsAuditor::audit`] found out about a domain
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct DnsAuditReport {
+    pub domain: String,
+    pub nameservers: Vec<SocketAddr>,
+    /// Worst first
+    pub findings: Vec<DnsFinding>,
+    /// One per nameserver
+    pub zone_transfers: Vec<ZoneTransfer>,
+    /// What a name nobody created resolves to, if anything
+    pub wildcard: Vec<String>,
+    /// SPF records at the apex
+    pub spf: Vec<String>,
+    /// DMARC records at `_dmarc`
+    pub dmarc: Vec<String>,
+}
+
+impl DnsAuditReport {
+    /// The severity of the worst finding
+    pub fn worst(&self) -> Option<Severity> {
+        self.findings.iter().map(|finding| finding.severity).max()
+    }
+
+    fn finding(&mut self, severity: Severity, check: DnsCheck, name: &str, detail: String) {
+        self.findings.push(DnsFinding {
+            severity,
+            check,
+            name: name.to_string(),
+            detail,
+        });
+    }
+}
+
+/// Audit `domain` against its `nameservers` with the default checks.
+pub async fn audit(domain: &str, nameservers: &[SocketAddr]) -> ReconResult<DnsAuditReport> {
+    DnsAuditor::new().audit(domain, nameservers).await
+}
+
+/// DNS misconfiguration checks.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let nameservers = DnsResolver::new().nameservers("example.com").await?;
+/// let report = DnsAuditor::new()
+///     .names(["assets.example.com", "cdn.example.com"])
+///     .audit("example.com", &nameservers)
+///     .await?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct DnsAuditor {
+    /// Without the leading `*.`
+    takeover_suffixes: Vec<String>,
+    names: Vec<String>,
+    resolver: Option<SocketAddr>,
+    timeout: Duration,
+}
+
+impl Default for DnsAuditor {
+    fn default() -> Self {
+        Self {
+            takeover_suffixes: DEFAULT_TAKEOVER_SUFFIXES
+                .iter()
+                .map(|suffix| normalize_suffix(suffix))
+                .collect(),
+            names: Vec::new(),
+            resolver: None,
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+impl DnsAuditor {
+    /// Create an auditor with the default takeover suffixes
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the CNAME targets treated as claimable, as
+    /// `*.s3.amazonaws.com` or `s3.amazonaws.com`
+    #[must_use]
+    pub fn takeover_suffixes<I, S>(mut self, suffixes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.takeover_suffixes = suffixes
+            .into_iter()
+            .map(|suffix| normalize_suffix(suffix.as_ref()))
+            .collect();
+        self
+    }
+
+    /// Also check these names for dangling CNAMEs. Names from a zone
+    /// transfer are always checked, but most zones can't be transferred.
+    #[must_use]
+    pub fn names<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.names = names
+            .into_iter()
+            .map(|name| name.as_ref().trim_end_matches('.').to_lowercase())
+            .collect();
+        self
+    }
+
+    /// Recursive resolver to look CNAME targets up with, instead of the
+    /// default upstream
+    #[must_use]
+    pub const fn resolver(mut self, resolver: SocketAddr) -> Self {
+        self.resolver = Some(resolver);
+        self
+    }
+
+    /// How long to wait for each answer
+    #[must_use]
+    pub const fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Run every check against `nameservers`, the domain's authoritative
+    /// servers. Fails only when none of them answers.
+    pub async fn audit(
+        &self,
+        domain: &str,
+        nameservers: &[SocketAddr],
+    ) -> ReconResult<DnsAuditReport> {
+        let domain = domain.trim().trim_end_matches('.').to_lowercase();
+        let zone = fqdn(&domain)?;
+        if nameservers.is_empty() {
+            return Err(ReconError::Dns(
+                format!("no nameservers to audit {domain}").into(),
+            ));
+        }
+
+        let mut report = DnsAuditReport {
+            domain: domain.clone(),
+            nameservers: nameservers.to_vec(),
+            ..DnsAuditReport::default()
+        };
+
+        for &nameserver in nameservers {
+            let transfer = self.transfer(nameserver, &zone).await;
+            if transfer.allowed {
+                report.finding(
+                    Severity::High,
+                    DnsCheck::ZoneTransfer,
+                    &domain,
+                    format!(
+                        "{nameserver} hands the whole zone to anyone ({} records)",
+                        transfer.records.len()
+                    ),
+                );
+            }
+            report.zone_transfers.push(transfer);
+        }
+
+        // Nobody creates a name like this one, so an answer means a wildcard
+        let probe = zone
+            .prepend_label(probe_label())
+            .map_err(|e| ReconError::Dns(e.into()))?;
+        let answer = self.ask(nameservers, &probe, RecordType::A).await?;
+        report.wildcard = answer.answers().iter().map(record_value).collect();
+        if !report.wildcard.is_empty() {
+            let detail = format!(
+                "Any name under the domain resolves, to {}",
+                report.wildcard.join(", ")
+            );
+            report.finding(
+                Severity::Low,
+                DnsCheck::Wildcard,
+                &format!("*.{domain}"),
+                detail,
+            );
+        }
+
+        self.check_mail(&mut report, nameservers, &zone).await?;
+        self.check_cnames(&mut report, nameservers).await;
+
+        report
+            .findings
+            .sort_by_key(|finding| Reverse(finding.severity));
+        Ok(report)
+    }
+
+    /// SPF at the apex, DMARC at `_dmarc`
+    async fn check_mail(
+        &self,
+        report: &mut DnsAuditReport,
+        nameservers: &[SocketAddr],
+        zone: &Name,
+    ) -> ReconResult<()> {
+        let domain = report.domain.clone();
+
+        let txt = self.ask(nameservers, zone, RecordType::TXT).await?;
+        report.spf = txt_values(&txt, "v=spf1");
+        match report.spf.as_slice() {
+            [] => report.finding(
+                Severity::Medium,
+                DnsCheck::Spf,
+                &domain,
+                "No SPF record, so nothing says who may send mail as the domain".to_string(),
+            ),
+            [spf] => {
+                let all = spf
+                    .split_whitespace()
+                    .find(|term| term.trim_start_matches(['+', '-', '~', '?']) == "all");
+                match all {
+                    Some("all" | "+all") => report.finding(
+                        Severity::High,
+                        DnsCheck::Spf,
+                        &domain,
+                        "SPF authorizes every sender (+all)".to_string(),
+                    ),
+                    Some("?all") => report.finding(
+                        Severity::Low,
+                        DnsCheck::Spf,
+                        &domain,
+                        "SPF ends in ?all, which vouches for nobody".to_string(),
+                    ),
+                    _ => {}
+                }
+            }
+            spf => report.finding(
+                Severity::Medium,
+                DnsCheck::Spf,
+                &domain,
+                format!(
+                    "{} SPF records; receivers treat that as no SPF at all",
+                    spf.len()
+                ),
+            ),
+        }
+
+        let name = zone
+            .prepend_label("_dmarc")
+            .map_err(|e| ReconError::Dns(e.into()))?;
+        let txt = self.ask(nameservers, &name, RecordType::TXT).await?;
+        report.dmarc = txt_values(&txt, "v=DMARC1");
+        let policy = report.dmarc.first().and_then(|dmarc| {
+            dmarc.split(';').find_map(|tag| {
+                let (key, value) = tag.split_once('=')?;
+                (key.trim() == "p").then(|| value.trim().to_lowercase())
+            })
+        });
+        match policy.as_deref() {
+            _ if report.dmarc.is_empty() => report.finding(
+                Severity::Medium,
+                DnsCheck::Dmarc,
+                &domain,
+                format!("No DMARC policy at _dmarc.{domain}"),
+            ),
+            Some("none") | None => report.finding(
+                Severity::Low,
+                DnsCheck::Dmarc,
+                &domain,
+                "DMARC policy is p=none, so mail failing it is still delivered".to_string(),
+            ),
+            Some(_) => {}
+        }
+        Ok(())
+    }
+
+    /// CNAMEs from the zone transfers and [`Self::names`] that point into a
+    /// takeover suffix, at a name that doesn't exist
+    async fn check_cnames(&self, report: &mut DnsAuditReport, nameservers: &[SocketAddr]) {
+        let mut cnames: Vec<(String, String)> = Vec::new();
+        for record in report.zone_transfers.iter().flat_map(|t| &t.records) {
+            if record.record_type == "CNAME" {
+                cnames.push((record.name.clone(), record.value.to_lowercase()));
+            }
+        }
+        for name in &self.names {
+            if cnames.iter().any(|(known, _)| known == name) {
+                continue;
+            }
+            let Ok(owner) = fqdn(name) else { continue };
+            let Ok(answer) = self.ask(nameservers, &owner, RecordType::CNAME).await else {
+                continue;
+            };
+            for record in answer.answers() {
+                if let RData::CNAME(target) = record.data() {
+                    cnames.push((name.clone(), name_value(&target.0).to_lowercase()));
+                }
+            }
+        }
+        cnames.sort();
+        cnames.dedup();
+
+        let resolver = self.target_resolver();
+        for (name, target) in cnames {
+            if !self.claimable(&target) {
+                continue;
+            }
+            // The service answers for names it has handed out, and for
+            // nothing else
+            let unclaimed = match resolver.lookup_ip(format!("{target}.")).await {
+                Ok(_) => false,
+                Err(e) => e.is_nx_domain(),
+            };
+            if unclaimed {
+                report.finding(
+                    Severity::High,
+                    DnsCheck::DanglingCname,
+                    &name,
+                    format!("CNAME to {target}, which doesn't exist and can be claimed"),
+                );
+            }
+        }
+    }
+
+    fn claimable(&self, target: &str) -> bool {
+        self.takeover_suffixes.iter().any(|suffix| {
+            target
+                .strip_suffix(suffix.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.ends_with('.'))
+        })
+    }
+
+    fn target_resolver(&self) -> TokioResolver {
+        self.resolver.map_or_else(super::resolver, |resolver| {
+            super::resolver_at(resolver, self.timeout)
+        })
+    }
+
+    /// Ask the nameservers in turn until one answers
+    async fn ask(
+        &self,
+        nameservers: &[SocketAddr],
+        name: &Name,
+        record_type: RecordType,
+    ) -> ReconResult<Message> {
+        let mut last_error = ReconError::Dns("no nameservers".into());
+        for &nameserver in nameservers {
+            match self.query(nameserver, name, record_type).await {
+                Ok(answer) => return Ok(answer),
+                Err(e) => last_error = e,
+            }
+        }
+        Err(last_error)
+    }
+
+    /// One query over UDP, asked again over TCP if the answer is truncated
+    async fn query(
+        &self,
+        server: SocketAddr,
+        name: &Name,
+        record_type: RecordType,
+    ) -> ReconResult<Message> {
+        let request = request(name, record_type);
+        let bytes = request.to_vec().map_err(|e| ReconError::Dns(e.into()))?;
+
+        let local = if server.is_ipv4() {
+            "0.0.0.0:0"
+        } else {
+            "[::]:0"
+        };
+        let socket = UdpSocket::bind(local).await?;
+        socket.connect(server).await?;
+        socket.send(&bytes).await?;
+
+        let mut buf = vec![0; MAX_UDP_SIZE];
+        let answer = loop {
+            let len = tokio::time::timeout(self.timeout, socket.recv(&mut buf))
+                .await
+                .map_err(|_| timeout(server, "querying"))??;
+            let answer = Message::from_vec(&buf[..len]).map_err(|e| ReconError::Dns(e.into()))?;
+            // Anything else is a stray or spoofed answer
+            if answer.id() == request.id() {
+                break answer;
+            }
+        };
+        if !answer.truncated() {
+            return Ok(answer);
+        }
+
+        let mut stream = self.connect(server).await?;
+        write_message(&mut stream, &request).await?;
+        self.read_message(server, &mut stream)
+            .await?
+            .ok_or_else(|| ReconError::Dns(format!("{server} closed the connection").into()))
+    }
+
+    async fn connect(&self, server: SocketAddr) -> ReconResult<TcpStream> {
+        Ok(
+            tokio::time::timeout(self.timeout, TcpStream::connect(server))
+                .await
+                .map_err(|_| timeout(server, "connecting to"))??,
+        )
+    }
+
+    async fn transfer(&self, nameserver: SocketAddr, zone: &Name) -> ZoneTransfer {
+        let mut transfer = ZoneTransfer {
+            nameserver,
+            allowed: false,
+            records: Vec::new(),
+            error: None,
+        };
+        match self.try_transfer(nameserver, zone).await {
+            Ok(records) => {
+                transfer.allowed = !records.is_empty();
+                transfer.records = records;
+            }
+            Err(e) => transfer.error = Some(e.to_string()),
+        }
+        transfer
+    }
+
+    /// The zone's records, or none when the transfer is refused
+    async fn try_transfer(
+        &self,
+        nameserver: SocketAddr,
+        zone: &Name,
+    ) -> ReconResult<Vec<ZoneRecord>> {
+        let mut stream = self.connect(nameserver).await?;
+        write_message(&mut stream, &request(zone, RecordType::AXFR)).await?;
+
+        // A transfer opens and closes with the zone's SOA, over as many
+        // messages as the server likes
+        let mut records = Vec::new();
+        let mut soas = 0;
+        while let Some(answer) = self.read_message(nameserver, &mut stream).await? {
+            if answer.response_code() != ResponseCode::NoError {
+                return Ok(Vec::new());
+            }
+            for record in answer.answers() {
+                if record.record_type() == RecordType::SOA {
+                    soas += 1;
+                }
+                records.push(zone_record(record));
+            }
+            if soas >= 2 || answer.answers().is_empty() {
+                break;
+            }
+        }
+
+        match soas {
+            0 => records.clear(),
+            1 => {}
+            _ => {
+                records.pop();
+            }
+        }
+        Ok(records)
+    }
+
+    /// One length-prefixed message, or `None` once the server hangs up
+    pub(super) async fn read_message(
+        &self,
+        server: SocketAddr,
+        stream: &mut TcpStream,
+    ) -> ReconResult<Option<Message>> {
+        let mut len = [0; 2];
+        match tokio::time::timeout(self.timeout, stream.read_exact(&mut len)).await {
+            Err(_) => return Err(timeout(server, "reading from")),
+            Ok(Err(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Ok(result) => result?,
+        };
+
+        let mut buf = vec![0; usize::from(u16::from_be_bytes(len))];
+        tokio::time::timeout(self.timeout, stream.read_exact(&mut buf))
+            .await
+            .map_err(|_| timeout(server, "reading from"))??;
+        Message::from_vec(&buf)
+            .map(Some)
+            .map_err(|e| ReconError::Dns(e.into()))
+    }
+}
+
+pub(super) async fn write_message(stream: &mut TcpStream, message: &Message) -> ReconResult<()> {
+    let bytes = message.to_vec().map_err(|e| ReconError::Dns(e.into()))?;
+    let len = u16::try_from(bytes.len()).map_err(|_| ReconError::Dns("query too large".into()))?;
+    stream.write_all(&len.to_be_bytes()).await?;
+    stream.write_all(&bytes).await?;
+    Ok(())
+}
+
+fn timeout(server: SocketAddr, what: &'static str) -> ReconError {
+    ReconError::Timeout {
+        target: server.to_string(),
+        what,
+    }
+}
+
+/// A non-recursive query, as asked of an authoritative server
+fn request(name: &Name, record_type: RecordType) -> Message {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos();
+    let mut message = Message::new();
+    message
+        .set_id(u16::try_from(nanos % 0x1_0000).unwrap_or_default())
+        .set_message_type(MessageType::Query)
+        .set_op_code(OpCode::Query)
+        .set_recursion_desired(false)
+        .add_query(Query::query(name.clone(), record_type));
+    message
+}
+
+/// A label no zone has, for the wildcard check
+pub(super) fn probe_label() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    format!("i1-audit-{nanos:x}")
+}
+
+pub(super) fn fqdn(name: &str) -> ReconResult<Name> {
+    Name::from_ascii(format!("{}.", name.trim_end_matches('.'))).map_err(|e| ReconError::Parse {
+        what: "domain name",
+        input: format!("{name}: {e}"),
+    })
+}
+
+fn normalize_suffix(suffix: &str) -> String {
+    suffix
+        .trim()
+        .trim_start_matches("*.")
+        .trim_end_matches('.')
+        .to_lowercase()
+}
+
+/// A name without the root's trailing dot
+fn name_value(name: &Name) -> String {
+    name.to_ascii().trim_end_matches('.').to_string()
+}
+
+fn record_value(record: &Record) -> String {
+    match record.data() {
+        RData::CNAME(target) => name_value(&target.0),
+        RData::NS(target) => name_value(&target.0),
+        RData::TXT(txt) => txt
+            .iter()
+            .map(|data| String::from_utf8_lossy(data))
+            .collect(),
+        data => data.to_string(),
+    }
+}
+
+fn zone_record(record: &Record) -> ZoneRecord {
+    ZoneRecord {
+        name: name_value(record.name()).to_lowercase(),
+        record_type: record.record_type().to_string(),
+        ttl: record.ttl(),
+        value: record_value(record),
+    }
+}
+
+/// TXT answers starting with `tag`, as SPF and DMARC records do
+fn txt_values(answer: &Message, tag: &str) -> Vec<String> {
+    answer
+        .answers()
+        .iter()
+        .filter(|record| record.record_type() == RecordType::TXT)
+        .map(record_value)
+        .filter(|value| {
+            value
+                .split([' ', ';'])
+                .next()
+                .is_some_and(|first| first.eq_ignore_ascii_case(tag))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use hickory_proto::rr::rdata::A;
+
+    use super::super::testing::{cname, record, serve, txt, Zone};
+    use super::*;
+
+    fn checks(report: &DnsAuditReport) -> Vec<(Severity, DnsCheck, &str)> {
+        report
+            .findings
+            .iter()
+            .map(|f| (f.severity, f.check, f.name.as_str()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_open_zone() {
+        let server = serve(Zone::new(
+            "example.test.",
+            true,
+            vec![
+                record("example.test.", RData::A(A::new(192, 0, 2, 10))),
+                record("*.example.test.", RData::A(A::new(192, 0, 2, 10))),
+                cname("assets.example.test.", "example-assets.s3.amazonaws.com."),
+                cname("www.example.test.", "example.test."),
+                txt("example.test.", "v=spf1 +all"),
+                txt("_dmarc.example.test.", "v=DMARC1; p=none"),
+            ],
+        ))
+        .await;
+
+        let report = DnsAuditor::new()
+            .resolver(server)
+            .timeout(Duration::from_secs(2))
+            .audit("Example.test.", &[server])
+            .await
+            .unwrap();
+
+        assert_eq!(report.domain, "example.test");
+        assert_eq!(
+            checks(&report),
+            [
+                (Severity::High, DnsCheck::ZoneTransfer, "example.test"),
+                (Severity::High, DnsCheck::Spf, "example.test"),
+                (
+                    Severity::High,
+                    DnsCheck::DanglingCname,
+                    "assets.example.test"
+                ),
+                (Severity::Low, DnsCheck::Wildcard, "*.example.test"),
+                (Severity::Low, DnsCheck::Dmarc, "example.test"),
+            ]
+        );
+        assert_eq!(report.worst(), Some(Severity::High));
+
+        let transfer = &report.zone_transfers[0];
+        assert!(transfer.allowed);
+        assert_eq!(transfer.records.len(), 8);
+        assert_eq!(transfer.records[0].record_type, "SOA");
+        assert!(transfer.records.contains(&ZoneRecord {
+            name: "assets.example.test".to_string(),
+            record_type: "CNAME".to_string(),
+            ttl: 300,
+            value: "example-assets.s3.amazonaws.com".to_string(),
+        }));
+        assert_eq!(report.wildcard, ["192.0.2.10"]);
+        assert_eq!(report.spf, ["v=spf1 +all"]);
+    }
+
+    #[tokio::test]
+    async fn test_locked_zone() {
+        let server = serve(Zone::new(
+            "locked.test.",
+            false,
+            vec![
+                cname("static.locked.test.", "locked-static.azurewebsites.net."),
+                cname("docs.locked.test.", "docs.example.org."),
+                txt("locked.test.", "v=spf1 mx -all"),
+                txt("locked.test.", "google-site-verification=abc"),
+                txt("_dmarc.locked.test.", "v=DMARC1; p=reject"),
+            ],
+        ))
+        .await;
+        // Nothing listens on the port once the listener is dropped
+        let dead = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        let report = DnsAuditor::new()
+            .resolver(server)
+            .timeout(Duration::from_secs(2))
+            .names(["static.locked.test", "docs.locked.test"])
+            .audit("locked.test", &[server, dead])
+            .await
+            .unwrap();
+
+        assert_eq!(
+            checks(&report),
+            [(
+                Severity::High,
+                DnsCheck::DanglingCname,
+                "static.locked.test"
+            )]
+        );
+        assert!(report.zone_transfers.iter().all(|t| !t.allowed));
+        assert_eq!(report.zone_transfers[0].error, None);
+        assert!(report.zone_transfers[1].error.is_some());
+        assert!(report.wildcard.is_empty());
+        assert_eq!(report.spf, ["v=spf1 mx -all"]);
+
+        // Only the suffixes asked for count
+        let report = DnsAuditor::new()
+            .resolver(server)
+            .takeover_suffixes(["*.example.org"])
+            .names(["static.locked.test", "docs.locked.test"])
+            .audit("locked.test", &[server])
+            .await
+            .unwrap();
+        assert_eq!(
+            checks(&report),
+            [(Severity::High, DnsCheck::DanglingCname, "docs.locked.test")]
+        );
+
+        assert!(audit("locked.test", &[]).await.is_err());
+    }
+}
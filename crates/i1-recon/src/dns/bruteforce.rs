@@ -0,0 +1,495 @@
+//! Subdomains found by trying names from a wordlist.
+//!
+//! Providers only know the names someone has seen; asking DNS for `www`,
+//! `vpn`, `staging` and the rest finds the ones nobody has. A domain with a
+//! wildcard answers for every name, so [`SubdomainBruteforcer`] first asks
+//! for a name nobody created and drops anything that only resolves to the
+//! same addresses. [`correlate`] then unions what it found with the
+//! providers' subdomains and says who found each one.
+
+use std::collections::BTreeMap;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use futures_util::stream::{self, Stream, StreamExt};
+use hickory_proto::rr::{Name, RData, Record};
+use hickory_resolver::TokioResolver;
+use i1_providers::{DnsProvider, DnsRecord, DomainInfo};
+use serde::Serialize;
+
+use super::audit::{fqdn, probe_label};
+use crate::error::{ReconError, ReconResult};
+
+/// Names resolved at once unless told otherwise
+pub const DEFAULT_CONCURRENCY: usize = 50;
+
+/// The source [`correlate`] gives subdomains found by bruteforce
+pub const BRUTEFORCE_SOURCE: &str = "bruteforce";
+
+/// The built-in wordlist
+const SUBDOMAINS: &str = include_str!("subdomains.txt");
+
+/// The built-in wordlist: a hundred or so of the most common labels
+pub fn default_wordlist() -> Vec<String> {
+    parse_wordlist(SUBDOMAINS)
+}
+
+/// Words from a wordlist file, one per line, lower-cased and without
+/// duplicates. Blank lines and `#` comments are skipped.
+pub fn parse_wordlist(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    for line in text.lines() {
+        let word = line.trim().trim_matches('.').to_lowercase();
+        if !word.is_empty() && !word.starts_with('#') && !words.contains(&word) {
+            words.push(word);
+        }
+    }
+    words
+}
+
+/// One name tried: what it resolved to, if anything besides the wildcard
+#[derive(Debug, Clone)]
+pub struct Probe {
+    /// The name asked for, as `word.domain`
+    pub name: String,
+    /// Its A, AAAA and CNAME records; empty if it doesn't exist
+    pub records: Vec<DnsRecord>,
+}
+
+impl Probe {
+    /// Whether the name exists
+    pub fn found(&self) -> bool {
+        !self.records.is_empty()
+    }
+}
+
+/// Tries names under a domain against DNS
+#[derive(Debug, Clone)]
+pub struct SubdomainBruteforcer {
+    resolver: Option<SocketAddr>,
+    concurrency: usize,
+    timeout: Duration,
+}
+
+impl Default for SubdomainBruteforcer {
+    fn default() -> Self {
+        Self {
+            resolver: None,
+            concurrency: DEFAULT_CONCURRENCY,
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+impl SubdomainBruteforcer {
+    /// Create a bruteforcer using the default upstream resolver
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ask this resolver instead of the default upstream
+    #[must_use]
+    pub const fn resolver(mut self, resolver: SocketAddr) -> Self {
+        self.resolver = Some(resolver);
+        self
+    }
+
+    /// How many names to resolve at once
+    #[must_use]
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// How long to wait for each answer
+    #[must_use]
+    pub const fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Try `word.domain` for every word and gather the names that exist.
+    pub async fn bruteforce<I>(&self, domain: &str, words: I) -> ReconResult<DomainInfo>
+    where
+        I: IntoIterator<Item = String>,
+        I::IntoIter: Send + 'static,
+    {
+        let domain = normalize(domain);
+        let probes: Vec<Probe> = self.stream(&domain, words).await?.collect().await;
+        Ok(domain_info(&domain, probes))
+    }
+
+    /// As [`Self::bruteforce`], one [`Probe`] per word as its answer comes
+    /// in, for showing progress. The wildcard check happens first, so names
+    /// that only resolve to the wildcard come back as not found.
+    pub async fn stream<I>(
+        &self,
+        domain: &str,
+        words: I,
+    ) -> ReconResult<impl Stream<Item = Probe> + Send + 'static>
+    where
+        I: IntoIterator<Item = String>,
+        I::IntoIter: Send + 'static,
+    {
+        let domain = normalize(domain);
+        let zone = fqdn(&domain)?;
+        let resolver = self.resolver.map_or_else(super::resolver, |server| {
+            super::resolver_at(server, self.timeout)
+        });
+
+        // Nobody creates a name like this one, so an answer means a wildcard
+        let probe = zone
+            .prepend_label(probe_label())
+            .map_err(|e| ReconError::Dns(e.into()))?;
+        let wildcard: Vec<IpAddr> = resolver
+            .lookup_ip(probe)
+            .await
+            .map(|answer| answer.iter().collect())
+            .unwrap_or_default();
+
+        Ok(stream::iter(words)
+            .map(move |word| {
+                let resolver = resolver.clone();
+                let query = zone.prepend_label(word.as_str());
+                let name = format!("{word}.{domain}");
+                let wildcard = wildcard.clone();
+                async move {
+                    let records = match query {
+                        Ok(query) => resolve(&resolver, query, &wildcard).await,
+                        Err(_) => Vec::new(),
+                    };
+                    Probe { name, records }
+                }
+            })
+            .buffer_unordered(self.concurrency))
+    }
+}
+
+/// Try every word under `domain` with the default upstream resolver, at
+/// most `concurrency` at a time.
+pub async fn bruteforce_subdomains(
+    domain: &str,
+    wordlist: &[String],
+    concurrency: usize,
+) -> ReconResult<DomainInfo> {
+    SubdomainBruteforcer::new()
+        .concurrency(concurrency)
+        .bruteforce(domain, wordlist.to_vec())
+        .await
+}
+
+/// A domain's subdomains from every source, each with who found it
+#[derive(Debug, Clone, Serialize)]
+pub struct CorrelatedDomain {
+    /// Every source's subdomains and records, merged
+    #[serde(flatten)]
+    pub info: DomainInfo,
+    /// Who found each subdomain: [`BRUTEFORCE_SOURCE`] or a provider's name
+    pub sources: BTreeMap<String, Vec<String>>,
+    /// Providers whose lookup failed, with why
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub errors: BTreeMap<String, String>,
+}
+
+/// Union bruteforced subdomains with what `providers` know of the domain.
+/// A provider that fails is noted in [`CorrelatedDomain::errors`] and the
+/// rest are used.
+pub async fn correlate(
+    bruteforced: DomainInfo,
+    providers: &[Box<dyn DnsProvider + Send + Sync>],
+) -> CorrelatedDomain {
+    let mut info = bruteforced;
+    info.qualify();
+    let mut sources: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for subdomain in &info.subdomains {
+        sources
+            .entry(subdomain.clone())
+            .or_default()
+            .push(BRUTEFORCE_SOURCE.to_string());
+    }
+
+    let lookups = providers.iter().map(|p| p.domain_info(&info.domain));
+    let results = futures_util::future::join_all(lookups).await;
+    let mut errors = BTreeMap::new();
+    for (provider, result) in providers.iter().zip(results) {
+        match result {
+            Ok(found) => {
+                for subdomain in &found.subdomains {
+                    let found_by = sources.entry(found.fqdn(subdomain)).or_default();
+                    if !found_by.iter().any(|source| source == provider.name()) {
+                        found_by.push(provider.name().to_string());
+                    }
+                }
+                info.merge(found);
+            }
+            Err(e) => {
+                errors.insert(provider.name().to_string(), e.to_string());
+            }
+        }
+    }
+    // The apex is the domain itself, not a subdomain anyone found
+    sources.retain(|name, _| info.subdomains.contains(name));
+
+    CorrelatedDomain {
+        info,
+        sources,
+        errors,
+    }
+}
+
+/// The records behind `name`, or none if it doesn't exist or resolves only
+/// to `wildcard`
+async fn resolve(resolver: &TokioResolver, name: Name, wildcard: &[IpAddr]) -> Vec<DnsRecord> {
+    let Ok(answer) = resolver.lookup_ip(name).await else {
+        return Vec::new();
+    };
+    if answer.iter().all(|ip| wildcard.contains(&ip)) {
+        return Vec::new();
+    }
+    answer
+        .as_lookup()
+        .records()
+        .iter()
+        .filter_map(dns_record)
+        .collect()
+}
+
+fn dns_record(record: &Record) -> Option<DnsRecord> {
+    let value = match record.data() {
+        RData::A(a) => a.to_string(),
+        RData::AAAA(aaaa) => aaaa.to_string(),
+        RData::CNAME(target) => target.0.to_ascii().trim_end_matches('.').to_string(),
+        _ => return None,
+    };
+    Some(DnsRecord {
+        record_type: record.record_type().to_string(),
+        name: record
+            .name()
+            .to_ascii()
+            .trim_end_matches('.')
+            .to_lowercase(),
+        value,
+        ttl: Some(record.ttl()),
+    })
+}
+
+/// The names among `probes` that exist, as a provider would report them
+/// for `domain`
+pub fn domain_info(domain: &str, probes: Vec<Probe>) -> DomainInfo {
+    let mut info = DomainInfo {
+        domain: domain.to_string(),
+        subdomains: Vec::new(),
+        records: Vec::new(),
+        registrar: None,
+        created: None,
+        expires: None,
+    };
+    for probe in probes.into_iter().filter(Probe::found) {
+        info.subdomains.push(probe.name);
+        info.records.extend(probe.records);
+    }
+    info.qualify();
+    info
+}
+
+fn normalize(domain: &str) -> String {
+    domain.trim().trim_end_matches('.').to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use hickory_proto::rr::rdata::A;
+    use i1_core::{I1Error, Result};
+    use i1_providers::{Provider, ProviderHealth};
+
+    use super::super::testing::{cname, record, serve, Zone};
+    use super::*;
+
+    fn bruteforcer(server: SocketAddr) -> SubdomainBruteforcer {
+        SubdomainBruteforcer::new()
+            .resolver(server)
+            .timeout(Duration::from_secs(2))
+            .concurrency(4)
+    }
+
+    fn words(words: &[&str]) -> Vec<String> {
+        words.iter().map(ToString::to_string).collect()
+    }
+
+    #[tokio::test]
+    async fn test_bruteforce() {
+        let server = serve(Zone::new(
+            "known.test.",
+            false,
+            vec![
+                record("www.known.test.", RData::A(A::new(192, 0, 2, 10))),
+                record("mail.known.test.", RData::A(A::new(192, 0, 2, 25))),
+                cname("api.known.test.", "www.known.test."),
+            ],
+        ))
+        .await;
+
+        let words = words(&["www", "mail", "api", "nope", "vpn"]);
+        let mut probes: Vec<Probe> = bruteforcer(server)
+            .stream("Known.test.", words.clone())
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(probes.len(), words.len());
+        probes.sort_by(|a, b| a.name.cmp(&b.name));
+        assert!(!probes[2].found(), "{:?}", probes[2]);
+        assert_eq!(probes[2].name, "nope.known.test");
+
+        let info = bruteforcer(server)
+            .bruteforce("known.test", words)
+            .await
+            .unwrap();
+        assert_eq!(info.domain, "known.test");
+        assert_eq!(
+            info.subdomains,
+            ["api.known.test", "mail.known.test", "www.known.test"]
+        );
+        let record = |record_type: &str, name: &str| {
+            info.records
+                .iter()
+                .find(|r| r.record_type == record_type && r.name == name)
+                .map(|r| r.value.as_str())
+        };
+        assert_eq!(record("A", "www.known.test"), Some("192.0.2.10"));
+        assert_eq!(record("A", "mail.known.test"), Some("192.0.2.25"));
+        assert_eq!(record("CNAME", "api.known.test"), Some("www.known.test"));
+    }
+
+    #[tokio::test]
+    async fn test_bruteforce_wildcard() {
+        let server = serve(Zone::new(
+            "wild.test.",
+            false,
+            vec![
+                record("*.wild.test.", RData::A(A::new(192, 0, 2, 99))),
+                record("www.wild.test.", RData::A(A::new(192, 0, 2, 10))),
+                // Where the wildcard points anyway: nothing to tell apart
+                record("shop.wild.test.", RData::A(A::new(192, 0, 2, 99))),
+            ],
+        ))
+        .await;
+
+        let info = bruteforcer(server)
+            .bruteforce("wild.test", words(&["www", "shop", "anything", "vpn"]))
+            .await
+            .unwrap();
+        assert_eq!(info.subdomains, ["www.wild.test"]);
+        assert!(info.records.iter().all(|r| r.value != "192.0.2.99"));
+
+        assert!(bruteforcer(server)
+            .bruteforce("not a domain", Vec::new())
+            .await
+            .is_err());
+    }
+
+    /// Knows two names under any domain, or fails
+    struct Passive(&'static str, bool);
+
+    #[async_trait]
+    impl Provider for Passive {
+        fn name(&self) -> &'static str {
+            self.0
+        }
+
+        fn display_name(&self) -> &'static str {
+            self.0
+        }
+
+        fn base_url(&self) -> &'static str {
+            "http://passive"
+        }
+
+        fn is_configured(&self) -> bool {
+            true
+        }
+
+        async fn health_check(&self) -> Result<ProviderHealth> {
+            Err(I1Error::Unauthorized)
+        }
+    }
+
+    #[async_trait]
+    impl DnsProvider for Passive {
+        async fn resolve(&self, _hostname: &str) -> Result<Vec<IpAddr>> {
+            Ok(Vec::new())
+        }
+
+        async fn reverse(&self, _ip: &str) -> Result<Vec<String>> {
+            Ok(Vec::new())
+        }
+
+        async fn domain_info(&self, domain: &str) -> Result<DomainInfo> {
+            if !self.1 {
+                return Err(I1Error::Unauthorized);
+            }
+            Ok(DomainInfo {
+                domain: domain.to_string(),
+                subdomains: vec!["www".to_string(), "legacy".to_string(), String::new()],
+                records: Vec::new(),
+                registrar: None,
+                created: None,
+                expires: None,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_correlate() {
+        let server = serve(Zone::new(
+            "known.test.",
+            false,
+            vec![
+                record("www.known.test.", RData::A(A::new(192, 0, 2, 10))),
+                record("vpn.known.test.", RData::A(A::new(192, 0, 2, 11))),
+            ],
+        ))
+        .await;
+        let bruteforced = bruteforcer(server)
+            .bruteforce("known.test", words(&["www", "vpn", "legacy"]))
+            .await
+            .unwrap();
+
+        let providers: Vec<Box<dyn DnsProvider + Send + Sync>> = vec![
+            Box::new(Passive("shodan", true)),
+            Box::new(Passive("native", false)),
+        ];
+        let correlated = correlate(bruteforced, &providers).await;
+        assert_eq!(
+            correlated.info.subdomains,
+            [
+                "known.test",
+                "legacy.known.test",
+                "vpn.known.test",
+                "www.known.test"
+            ]
+        );
+        let sources = |name: &str| correlated.sources[name].join(",");
+        assert_eq!(sources("www.known.test"), "bruteforce,shodan");
+        assert_eq!(sources("vpn.known.test"), "bruteforce");
+        assert_eq!(sources("legacy.known.test"), "shodan");
+        assert_eq!(correlated.sources.len(), 4);
+        assert!(correlated.errors.contains_key("native"));
+
+        let json = serde_json::to_value(&correlated).unwrap();
+        assert_eq!(json["domain"], "known.test");
+        assert_eq!(json["sources"]["vpn.known.test"][0], "bruteforce");
+    }
+
+    #[test]
+    fn test_wordlists() {
+        let words = parse_wordlist("# comment\nWWW\n\n  mail \nwww\n.api.\n");
+        assert_eq!(words, ["www", "mail", "api"]);
+
+        let builtin = default_wordlist();
+        assert!(builtin.len() >= 100, "{}", builtin.len());
+        assert!(builtin.contains(&"www".to_string()));
+        assert!(builtin.iter().all(|w| !w.contains(char::is_whitespace)));
+    }
+}
@@ -0,0 +1,164 @@
+//! A small authoritative server for tests, answering from a list of
+//! records.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use hickory_proto::op::{Message, MessageType, OpCode, ResponseCode};
+use hickory_proto::rr::rdata::{CNAME, NS, SOA, TXT};
+use hickory_proto::rr::{Name, RData, Record, RecordType};
+use tokio::net::{TcpListener, UdpSocket};
+
+use super::audit::{write_message, DnsAuditor, MAX_UDP_SIZE};
+
+pub fn name(name: &str) -> Name {
+    Name::from_ascii(name).unwrap()
+}
+
+pub fn record(owner: &str, rdata: RData) -> Record {
+    Record::from_rdata(name(owner), 300, rdata)
+}
+
+pub fn cname(owner: &str, target: &str) -> Record {
+    record(owner, RData::CNAME(CNAME(name(target))))
+}
+
+pub fn txt(owner: &str, text: &str) -> Record {
+    record(owner, RData::TXT(TXT::new(vec![text.to_string()])))
+}
+
+/// An authoritative server for one zone, answering from a list of
+/// records the way a real one would, wildcards included
+pub struct Zone {
+    origin: Name,
+    records: Vec<Record>,
+    allow_transfer: bool,
+}
+
+impl Zone {
+    pub fn new(origin: &str, allow_transfer: bool, records: Vec<Record>) -> Self {
+        let soa = SOA::new(
+            name(&format!("ns1.{origin}")),
+            name(&format!("hostmaster.{origin}")),
+            1,
+            3600,
+            600,
+            86400,
+            300,
+        );
+        let mut all = vec![
+            record(origin, RData::SOA(soa)),
+            record(origin, RData::NS(NS(name(&format!("ns1.{origin}"))))),
+        ];
+        all.extend(records);
+        Self {
+            origin: name(origin),
+            records: all,
+            allow_transfer,
+        }
+    }
+
+    fn answer(&self, request: &Message) -> Message {
+        let query = request.queries()[0].clone();
+        let mut response = Message::new();
+        response
+            .set_id(request.id())
+            .set_message_type(MessageType::Response)
+            .set_op_code(OpCode::Query)
+            .set_authoritative(true)
+            .add_query(query.clone());
+
+        if query.query_type() == RecordType::AXFR {
+            if self.allow_transfer {
+                response
+                    .add_answers(self.records.iter().cloned())
+                    .add_answer(self.records[0].clone());
+            } else {
+                response.set_response_code(ResponseCode::Refused);
+            }
+            return response;
+        }
+
+        let qname = query.name();
+        let mut owned: Vec<Record> = self
+            .records
+            .iter()
+            .filter(|r| r.name() == qname)
+            .cloned()
+            .collect();
+        if owned.is_empty() && self.origin.zone_of(qname) {
+            let wildcard = qname.base_name().prepend_label("*").unwrap();
+            owned = self
+                .records
+                .iter()
+                .filter(|r| r.name() == &wildcard)
+                .map(|r| {
+                    let mut r = r.clone();
+                    r.set_name(qname.clone());
+                    r
+                })
+                .collect();
+        }
+        if owned.is_empty() {
+            response.set_response_code(ResponseCode::NXDomain);
+        }
+        let answers: Vec<Record> = owned
+            .into_iter()
+            .filter(|r| {
+                r.record_type() == query.query_type() || r.record_type() == RecordType::CNAME
+            })
+            .collect();
+        // Follow an alias within the zone, as an authoritative server does
+        let mut chased = Vec::new();
+        if query.query_type() != RecordType::CNAME {
+            for alias in &answers {
+                if let RData::CNAME(target) = alias.data() {
+                    chased.extend(self.records.iter().filter(|r| {
+                        r.name() == &target.0 && r.record_type() == query.query_type()
+                    }));
+                }
+            }
+        }
+        response
+            .add_answers(answers)
+            .add_answers(chased.into_iter().cloned());
+        response
+    }
+}
+
+/// Serve `zone` over UDP and TCP on one local port
+pub async fn serve(zone: Zone) -> SocketAddr {
+    let zone = Arc::new(zone);
+    // The TCP side of a free UDP port can still be taken, so try again
+    let (udp, tcp, addr) = loop {
+        let udp = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = udp.local_addr().unwrap();
+        if let Ok(tcp) = TcpListener::bind(addr).await {
+            break (udp, tcp, addr);
+        }
+    };
+
+    let udp_zone = zone.clone();
+    tokio::spawn(async move {
+        let mut buf = vec![0; MAX_UDP_SIZE];
+        while let Ok((len, peer)) = udp.recv_from(&mut buf).await {
+            let request = Message::from_vec(&buf[..len]).unwrap();
+            let answer = udp_zone.answer(&request).to_vec().unwrap();
+            udp.send_to(&answer, peer).await.unwrap();
+        }
+    });
+    tokio::spawn(async move {
+        while let Ok((mut stream, peer)) = tcp.accept().await {
+            let zone = zone.clone();
+            tokio::spawn(async move {
+                let reader = DnsAuditor::new();
+                while let Ok(Some(request)) = reader.read_message(peer, &mut stream).await {
+                    write_message(&mut stream, &zone.answer(&request))
+                        .await
+                        .unwrap();
+                }
+            });
+        }
+    });
+    addr
+}
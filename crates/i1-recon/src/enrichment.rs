@@ -52,8 +52,10 @@ impl HostEnricher {
     }
 
     /// Enrich a single IP address with all configured local sources
-    pub async fn enrich(&self, _ip: IpAddr) -> EnrichedHost {
-        let result = EnrichedHost::default();
+    #[cfg_attr(not(any(feature = "scanner", feature = "whois")), allow(unused_variables))]
+    pub async fn enrich(&self, ip: IpAddr) -> EnrichedHost {
+        #[cfg_attr(not(any(feature = "scanner", feature = "whois")), allow(unused_mut))]
+        let mut result = EnrichedHost::default();
 
         // Run port scan if configured
         #[cfg(feature = "scanner")]
@@ -1,55 +1,186 @@
+use i1_core::{I1Error, ReconKind};
+use std::net::IpAddr;
+use std::path::PathBuf;
 use thiserror::Error;
 
 /// Result type alias for reconnaissance operations
 pub type ReconResult<T> = std::result::Result<T, ReconError>;
 
+/// What a library reported when a lookup or trace failed, kept as it was
+pub type Cause = Box<dyn std::error::Error + Send + Sync>;
+
 /// Errors from reconnaissance tools
 #[derive(Error, Debug)]
 pub enum ReconError {
     /// Port scan error
     #[error("scan error: {0}")]
-    Scan(String),
+    Scan(Cause),
 
     /// WHOIS lookup error
     #[error("WHOIS error: {0}")]
-    Whois(String),
+    Whois(Cause),
 
     /// DNS resolution error
     #[error("DNS error: {0}")]
-    Dns(String),
+    Dns(Cause),
 
     /// Traceroute error
     #[error("trace error: {0}")]
-    Trace(String),
+    Trace(Cause),
+
+    /// Needs root or a capability the process doesn't have, such as
+    /// `CAP_NET_RAW` for raw sockets
+    #[error(
+        "{needed} is required: run with sudo, or grant it with `sudo setcap {}+ep $(command -v i1)`",
+        .needed.to_lowercase()
+    )]
+    InsufficientPrivileges {
+        /// The Linux capability, e.g. `CAP_NET_RAW`
+        needed: &'static str,
+    },
+
+    /// Target is outside the configured scan scope
+    #[error("{0} is out of scope: it's a protected range or whitelisted in `i1 defend`")]
+    OutOfScope(IpAddr),
 
-    /// Network I/O error
-    #[error("network error: {0}")]
-    Network(#[from] std::io::Error),
+    /// No answer in time
+    #[error("{what} {target} timed out; it may be down or filtering, or need a longer timeout")]
+    Timeout {
+        /// Who didn't answer
+        target: String,
+        /// What was being done, e.g. `connecting to`
+        what: &'static str,
+    },
 
-    /// Invalid IP address
-    #[error("invalid IP address: {0}")]
-    InvalidIp(String),
+    /// A database file that isn't there
+    #[error("database {} not found; download it or point i1 at another copy", .0.display())]
+    MissingDatabase(PathBuf),
+
+    /// Socket or file I/O error
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// Input that doesn't parse
+    #[error("invalid {what}: {input}")]
+    Parse {
+        /// What it should have been, e.g. `port specification`
+        what: &'static str,
+        /// The input, or where it went wrong
+        input: String,
+    },
+}
 
-    /// Timeout
-    #[error("operation timed out")]
-    Timeout,
+impl ReconError {
+    /// What the error is about, without its details.
+    #[must_use]
+    pub fn kind(&self) -> ReconKind {
+        match self {
+            Self::Scan(_) => ReconKind::Scan,
+            Self::Whois(_) => ReconKind::Whois,
+            Self::Dns(_) => ReconKind::Dns,
+            Self::Trace(_) => ReconKind::Trace,
+            Self::InsufficientPrivileges { .. } => ReconKind::Privileges,
+            Self::OutOfScope(_) => ReconKind::OutOfScope,
+            Self::Timeout { .. } => ReconKind::Timeout,
+            Self::MissingDatabase(_) => ReconKind::MissingDatabase,
+            Self::Io(e) if is_network(e.kind()) => ReconKind::Network,
+            Self::Io(_) => ReconKind::Io,
+            Self::Parse { .. } => ReconKind::Parse,
+        }
+    }
+}
 
-    /// Permission denied (requires root/admin)
-    #[error("permission denied: {0}")]
-    PermissionDenied(String),
+/// Whether an I/O error is a connection failing rather than a file.
+const fn is_network(kind: std::io::ErrorKind) -> bool {
+    use std::io::ErrorKind;
+    matches!(
+        kind,
+        ErrorKind::ConnectionRefused
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::NotConnected
+            | ErrorKind::AddrNotAvailable
+            | ErrorKind::BrokenPipe
+            | ErrorKind::TimedOut
+    )
 }
 
-impl From<ReconError> for i1_core::I1Error {
+/// Keeps the whole error: [`I1Error::Recon`] holds it, so it can be
+/// downcast back to a [`ReconError`].
+impl From<ReconError> for I1Error {
     fn from(err: ReconError) -> Self {
-        match err {
-            ReconError::Scan(msg) => Self::Scan(msg),
-            ReconError::Whois(msg) => Self::Whois(msg),
-            ReconError::Dns(msg) => Self::Dns(msg),
-            ReconError::Trace(msg) => Self::Trace(msg),
-            ReconError::Network(e) => Self::Connection(e.to_string()),
-            ReconError::InvalidIp(ip) => Self::InvalidIp(ip),
-            ReconError::Timeout => Self::Timeout(0),
-            ReconError::PermissionDenied(msg) => Self::Internal(msg),
+        Self::Recon {
+            kind: err.kind(),
+            error: Box::new(err),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert(err: ReconError) -> (ReconKind, String, I1Error) {
+        let message = err.to_string();
+        let converted = I1Error::from(err);
+        match &converted {
+            I1Error::Recon { kind, .. } => (*kind, message, converted),
+            other => panic!("not a recon error: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_into_i1_error() {
+        let (kind, message, converted) = convert(ReconError::OutOfScope([10, 0, 0, 1].into()));
+        assert_eq!(kind, ReconKind::OutOfScope);
+        assert_eq!(converted.to_string(), message);
+        assert_eq!(converted.kind(), "out_of_scope");
+        // Nothing is lost: the original comes back out
+        let I1Error::Recon { error, .. } = converted else {
+            unreachable!()
+        };
+        assert!(matches!(
+            error.downcast_ref::<ReconError>(),
+            Some(ReconError::OutOfScope(ip)) if ip.to_string() == "10.0.0.1"
+        ));
+
+        let (kind, message, converted) = convert(ReconError::Timeout {
+            target: "192.0.2.53:53".to_string(),
+            what: "querying",
+        });
+        assert_eq!(kind, ReconKind::Timeout);
+        assert!(message.contains("querying 192.0.2.53:53 timed out"));
+        assert!(converted.is_retryable());
+
+        let refused = std::io::Error::from(std::io::ErrorKind::ConnectionRefused);
+        assert_eq!(ReconError::from(refused).kind(), ReconKind::Network);
+        let missing = std::io::Error::from(std::io::ErrorKind::NotFound);
+        let (kind, _, converted) = convert(missing.into());
+        assert_eq!(kind, ReconKind::Io);
+        assert!(!converted.is_retryable());
+
+        let (kind, message, _) = convert(ReconError::Parse {
+            what: "port specification",
+            input: "99999".to_string(),
+        });
+        assert_eq!(kind, ReconKind::Parse);
+        assert_eq!(message, "invalid port specification: 99999");
+
+        let (kind, _, _) = convert(ReconError::MissingDatabase("/var/lib/i1/asn.db".into()));
+        assert_eq!(kind, ReconKind::MissingDatabase);
+
+        let (kind, _, _) = convert(ReconError::Dns("no nameservers".into()));
+        assert_eq!(kind, ReconKind::Dns);
+    }
+
+    #[test]
+    fn test_privileges_message() {
+        let err = ReconError::InsufficientPrivileges {
+            needed: "CAP_NET_RAW",
+        };
+        let message = err.to_string();
+        assert!(message.starts_with("CAP_NET_RAW is required"), "{message}");
+        assert!(message.contains("sudo setcap cap_net_raw+ep"), "{message}");
+        assert_eq!(err.kind(), ReconKind::Privileges);
+    }
+}
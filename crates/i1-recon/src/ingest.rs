@@ -0,0 +1,596 @@
+//! Targets from packet captures and Zeek logs.
+//!
+//! An incident usually starts with a capture or a Zeek `conn.log`, not a
+//! list of addresses. [`read_file`] reads either and tallies the external
+//! endpoints in it: every address that isn't private, loopback, link-local
+//! or multicast, with how many connections it was part of and the service
+//! ports of those connections. [`Ingested::targets`] is then a list to look
+//! up or ban.
+//!
+//! Captures are pcap or pcapng, read with `pcap-parser` rather than
+//! libpcap. A connection is a flow: one protocol between two addresses and
+//! ports, however many packets it took. Its service port is the port the
+//! SYN was sent to, or failing that the lower of the two. Zeek logs are
+//! TSV with a `#fields` header or JSON, one connection per line, and the
+//! service port is `id.resp_p`.
+//!
+//! Input is read as far as it makes sense: a capture that stops mid-packet
+//! or a log with lines that don't parse gives what was read before, with a
+//! warning saying what was skipped.
+
+use std::collections::{BTreeSet, HashMap};
+use std::io::{Read, Seek, SeekFrom};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::Path;
+
+use pcap_parser::{Block, PcapBlockOwned, PcapError};
+use serde::Serialize;
+
+use crate::error::{ReconError, ReconResult};
+
+/// Bytes of capture read at a time; more than any one packet takes
+const BUFFER: usize = 1 << 16;
+
+/// Refills in a row without a whole block before a capture is taken to end
+/// mid-packet
+const STALLED_REFILLS: usize = 8;
+
+/// Link types, as pcap numbers them (`LINKTYPE_*`)
+const NULL: i32 = 0;
+const ETHERNET: i32 = 1;
+const RAW: i32 = 101;
+const LOOP: i32 = 108;
+const LINUX_SLL: i32 = 113;
+const IPV4: i32 = 228;
+const IPV6: i32 = 229;
+const LINUX_SLL2: i32 = 276;
+
+/// Ethernet types of IP and of the VLAN tags in front of it
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+const ETHERTYPE_VLAN: [u16; 3] = [0x8100, 0x88a8, 0x9100];
+
+const TCP: u8 = 6;
+const UDP: u8 = 17;
+
+/// An external address seen in the input.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Endpoint {
+    /// The address
+    pub ip: IpAddr,
+    /// Connections it was part of
+    pub connections: u64,
+    /// Service ports of those connections: the ones it served on, or the
+    /// ones of ours it reached
+    pub ports: BTreeSet<u16>,
+}
+
+/// What a capture or log held.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Ingested {
+    /// External endpoints, most connections first
+    pub endpoints: Vec<Endpoint>,
+    /// Connections read, with or without an external endpoint
+    pub connections: u64,
+    /// What couldn't be read, in words
+    pub warnings: Vec<String>,
+}
+
+impl Ingested {
+    /// Addresses of the endpoints with at least `min_connections`
+    /// connections, most connections first.
+    pub fn targets(&self, min_connections: u64) -> Vec<IpAddr> {
+        self.endpoints
+            .iter()
+            .filter(|endpoint| endpoint.connections >= min_connections)
+            .map(|endpoint| endpoint.ip)
+            .collect()
+    }
+}
+
+/// Read a capture or a Zeek log, telling which from its first bytes.
+pub fn read_file(path: &Path) -> ReconResult<Ingested> {
+    let mut file = std::fs::File::open(path)?;
+    let mut magic = [0; 4];
+    let read = file.read(&mut magic)?;
+    // Back to the start: the pcap reader wants its header in one read
+    file.seek(SeekFrom::Start(0))?;
+    if is_capture(&magic[..read]) {
+        return read_pcap(file);
+    }
+    let mut text = String::new();
+    std::io::BufReader::new(file)
+        .read_to_string(&mut text)
+        .map_err(|e| ReconError::Parse {
+            what: "capture or Zeek log",
+            input: format!("{}: {e}", path.display()),
+        })?;
+    Ok(read_zeek(&text))
+}
+
+/// Whether `magic` starts a pcap or pcapng file.
+fn is_capture(magic: &[u8]) -> bool {
+    const MAGIC: [[u8; 4]; 5] = [
+        [0xd4, 0xc3, 0xb2, 0xa1],
+        [0xa1, 0xb2, 0xc3, 0xd4],
+        [0x4d, 0x3c, 0xb2, 0xa1],
+        [0xa1, 0xb2, 0x3c, 0x4d],
+        [0x0a, 0x0d, 0x0d, 0x0a],
+    ];
+    MAGIC.iter().any(|m| magic == m)
+}
+
+/// Tally a pcap or pcapng capture. Only a file that isn't a capture at all
+/// is an error; one that breaks off is read up to there.
+pub fn read_pcap<R: Read + Send>(reader: R) -> ReconResult<Ingested> {
+    let mut reader = pcap_parser::create_reader(BUFFER, reader).map_err(|e| ReconError::Parse {
+        what: "capture",
+        input: describe(&e),
+    })?;
+    let mut tally = Tally::default();
+    // One link type per interface; a classic pcap has one
+    let mut links: Vec<i32> = Vec::new();
+    let mut stalled = 0;
+    loop {
+        match reader.next() {
+            Ok((offset, block)) => {
+                stalled = 0;
+                match block {
+                    PcapBlockOwned::LegacyHeader(header) => links = vec![header.network.0],
+                    PcapBlockOwned::Legacy(packet) => tally.frame(links.first(), packet.data),
+                    PcapBlockOwned::NG(Block::SectionHeader(_)) => links.clear(),
+                    PcapBlockOwned::NG(Block::InterfaceDescription(interface)) => {
+                        links.push(interface.linktype.0);
+                    }
+                    PcapBlockOwned::NG(Block::EnhancedPacket(packet)) => {
+                        let link = usize::try_from(packet.if_id)
+                            .ok()
+                            .and_then(|i| links.get(i));
+                        tally.frame(link, packet.data);
+                    }
+                    PcapBlockOwned::NG(Block::SimplePacket(packet)) => {
+                        tally.frame(links.first(), packet.data);
+                    }
+                    PcapBlockOwned::NG(_) => {}
+                }
+                reader.consume(offset);
+            }
+            Err(PcapError::Eof) => break,
+            Err(PcapError::Incomplete(_)) if stalled < STALLED_REFILLS => {
+                stalled += 1;
+                if let Err(e) = reader.refill() {
+                    let error = describe(&e);
+                    tally.cut_short(&error);
+                    break;
+                }
+            }
+            Err(e) => {
+                let error = describe(&e);
+                tally.cut_short(&error);
+                break;
+            }
+        }
+    }
+    Ok(tally.finish())
+}
+
+/// A pcap-parser error in words, without the bytes it carries.
+fn describe<I>(e: &PcapError<I>) -> String {
+    match e {
+        PcapError::HeaderNotRecognized => "no pcap or pcapng header".to_string(),
+        PcapError::Incomplete(_) | PcapError::UnexpectedEof => "ends mid-packet".to_string(),
+        PcapError::BufferTooSmall => "holds a packet too large to read".to_string(),
+        PcapError::ReadError => "could not be read".to_string(),
+        _ => "has a malformed block".to_string(),
+    }
+}
+
+/// Tally a Zeek `conn.log`, TSV or JSON.
+pub fn read_zeek(text: &str) -> Ingested {
+    let mut tally = Tally::default();
+    let json = text.trim_start().starts_with('{');
+    let mut separator = "\t".to_string();
+    let mut fields: Vec<&str> = Vec::new();
+
+    for (number, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(header) = line.strip_prefix('#') {
+            if let Some(value) = header.strip_prefix("separator ") {
+                separator = unescape(value);
+            } else if let Some(names) = header.strip_prefix("fields") {
+                fields = names.split(separator.as_str()).skip(1).collect();
+            }
+            continue;
+        }
+        let record = if json {
+            zeek_json(line)
+        } else {
+            zeek_tsv(line, &separator, &fields)
+        };
+        match record {
+            Some((orig, resp, port)) => tally.connection(orig, resp, port),
+            None => tally.malformed(number + 1),
+        }
+    }
+    tally.finish()
+}
+
+/// `\x09` as a tab, as Zeek writes its separator
+fn unescape(value: &str) -> String {
+    value
+        .strip_prefix("\\x")
+        .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        .map_or_else(|| value.to_string(), |byte| char::from(byte).to_string())
+}
+
+type Record = (IpAddr, IpAddr, Option<u16>);
+
+fn zeek_tsv(line: &str, separator: &str, fields: &[&str]) -> Option<Record> {
+    let values: Vec<&str> = line.split(separator).collect();
+    let field = |name: &str| {
+        let index = fields.iter().position(|field| *field == name)?;
+        values.get(index).filter(|value| **value != "-")
+    };
+    Some((
+        field("id.orig_h")?.parse().ok()?,
+        field("id.resp_h")?.parse().ok()?,
+        field("id.resp_p").and_then(|port| port.parse().ok()),
+    ))
+}
+
+fn zeek_json(line: &str) -> Option<Record> {
+    let record: serde_json::Value = serde_json::from_str(line).ok()?;
+    let address = |name: &str| record.get(name)?.as_str()?.parse().ok();
+    let port = record
+        .get("id.resp_p")
+        .and_then(serde_json::Value::as_u64)
+        .and_then(|port| u16::try_from(port).ok());
+    Some((address("id.orig_h")?, address("id.resp_h")?, port))
+}
+
+/// One end of a flow
+type Side = (IpAddr, u16);
+
+/// Connections counted so far.
+#[derive(Default)]
+struct Tally {
+    endpoints: HashMap<IpAddr, Endpoint>,
+    /// Capture flows by protocol and their two sides, lower first, with
+    /// the service port once known
+    flows: HashMap<(u8, Side, Side), Option<u16>>,
+    connections: u64,
+    /// Frames that weren't TCP or UDP over IP
+    skipped: u64,
+    /// Log lines that didn't parse, and the first of them
+    malformed: (u64, usize),
+    warnings: Vec<String>,
+}
+
+impl Tally {
+    /// Count a captured frame of the given link type.
+    fn frame(&mut self, link: Option<&i32>, data: &[u8]) {
+        match link.and_then(|&link| packet(link, data)) {
+            Some(packet) => self.packet(&packet),
+            None => self.skipped += 1,
+        }
+    }
+
+    fn packet(&mut self, packet: &Packet) {
+        let (src, dst) = ((packet.src, packet.src_port), (packet.dst, packet.dst_port));
+        let key = if src <= dst {
+            (packet.protocol, src, dst)
+        } else {
+            (packet.protocol, dst, src)
+        };
+        let service = match packet.tcp_flags {
+            Some(flags) if flags & SYN_ACK == SYN => Some(packet.dst_port),
+            Some(flags) if flags & SYN_ACK == SYN_ACK => Some(packet.src_port),
+            _ => None,
+        };
+        let known = self.flows.entry(key).or_insert(None);
+        if service.is_some() {
+            *known = service;
+        }
+    }
+
+    /// Count one connection between `orig` and `resp`.
+    fn connection(&mut self, orig: IpAddr, resp: IpAddr, port: Option<u16>) {
+        self.connections += 1;
+        for ip in [orig, resp] {
+            if !is_external(ip) || (ip == resp && orig == resp) {
+                continue;
+            }
+            let endpoint = self.endpoints.entry(ip).or_insert_with(|| Endpoint {
+                ip,
+                connections: 0,
+                ports: BTreeSet::new(),
+            });
+            endpoint.connections += 1;
+            endpoint.ports.extend(port);
+        }
+    }
+
+    fn malformed(&mut self, line: usize) {
+        if self.malformed.0 == 0 {
+            self.malformed.1 = line;
+        }
+        self.malformed.0 += 1;
+    }
+
+    fn cut_short(&mut self, error: &str) {
+        self.warnings.push(format!(
+            "capture {error}; kept what was read before it ({} connections)",
+            self.flows.len()
+        ));
+    }
+
+    fn finish(mut self) -> Ingested {
+        for ((_, lower, upper), service) in std::mem::take(&mut self.flows) {
+            let port = service.or_else(|| Some(lower.1.min(upper.1)).filter(|&p| p > 0));
+            self.connection(lower.0, upper.0, port);
+        }
+        if self.skipped > 0 {
+            self.warnings.push(format!(
+                "skipped packets that weren't TCP or UDP over IP: {}",
+                self.skipped
+            ));
+        }
+        if self.malformed.0 > 0 {
+            self.warnings.push(format!(
+                "skipped lines that aren't connections: {}, the first at line {}",
+                self.malformed.0, self.malformed.1
+            ));
+        }
+
+        let mut endpoints: Vec<Endpoint> = self.endpoints.into_values().collect();
+        endpoints.sort_by(|a, b| {
+            b.connections
+                .cmp(&a.connections)
+                .then_with(|| a.ip.cmp(&b.ip))
+        });
+        Ingested {
+            endpoints,
+            connections: self.connections,
+            warnings: self.warnings,
+        }
+    }
+}
+
+const SYN: u8 = 0x02;
+const SYN_ACK: u8 = 0x12;
+
+/// What a frame says about its connection.
+#[derive(Debug, PartialEq, Eq)]
+struct Packet {
+    protocol: u8,
+    src: IpAddr,
+    dst: IpAddr,
+    src_port: u16,
+    dst_port: u16,
+    /// TCP flags; `None` for UDP
+    tcp_flags: Option<u8>,
+}
+
+/// The TCP or UDP packet in a frame of link type `link`, if it is one.
+fn packet(link: i32, frame: &[u8]) -> Option<Packet> {
+    let ip = match link {
+        ETHERNET => {
+            let mut at = 12;
+            let mut ethertype = u16::from_be_bytes([*frame.get(at)?, *frame.get(at + 1)?]);
+            while ETHERTYPE_VLAN.contains(&ethertype) {
+                at += 4;
+                ethertype = u16::from_be_bytes([*frame.get(at)?, *frame.get(at + 1)?]);
+            }
+            if ethertype != ETHERTYPE_IPV4 && ethertype != ETHERTYPE_IPV6 {
+                return None;
+            }
+            frame.get(at + 2..)?
+        }
+        NULL | LOOP => frame.get(4..)?,
+        LINUX_SLL => frame.get(16..)?,
+        LINUX_SLL2 => frame.get(20..)?,
+        RAW | IPV4 | IPV6 => frame,
+        _ => return None,
+    };
+    match ip.first()? >> 4 {
+        4 => ipv4(ip),
+        6 => ipv6(ip),
+        _ => None,
+    }
+}
+
+fn ipv4(ip: &[u8]) -> Option<Packet> {
+    let header = usize::from(ip.first()? & 0x0f) * 4;
+    // Only the first fragment carries the ports
+    let fragment = u16::from_be_bytes([*ip.get(6)?, *ip.get(7)?]) & 0x1fff;
+    if fragment != 0 {
+        return None;
+    }
+    let src: [u8; 4] = ip.get(12..16)?.try_into().ok()?;
+    let dst: [u8; 4] = ip.get(16..20)?.try_into().ok()?;
+    transport(
+        *ip.get(9)?,
+        Ipv4Addr::from(src).into(),
+        Ipv4Addr::from(dst).into(),
+        ip.get(header..)?,
+    )
+}
+
+fn ipv6(ip: &[u8]) -> Option<Packet> {
+    let src: [u8; 16] = ip.get(8..24)?.try_into().ok()?;
+    let dst: [u8; 16] = ip.get(24..40)?.try_into().ok()?;
+    transport(
+        *ip.get(6)?,
+        Ipv6Addr::from(src).into(),
+        Ipv6Addr::from(dst).into(),
+        ip.get(40..)?,
+    )
+}
+
+fn transport(protocol: u8, src: IpAddr, dst: IpAddr, segment: &[u8]) -> Option<Packet> {
+    let port = |at: usize| {
+        Some(u16::from_be_bytes([
+            *segment.get(at)?,
+            *segment.get(at + 1)?,
+        ]))
+    };
+    let tcp_flags = match protocol {
+        TCP => Some(*segment.get(13)?),
+        UDP => None,
+        _ => return None,
+    };
+    Some(Packet {
+        protocol,
+        src,
+        dst,
+        src_port: port(0)?,
+        dst_port: port(2)?,
+        tcp_flags,
+    })
+}
+
+/// Whether `ip` is outside the networks a capture is usually taken on:
+/// not private, shared (CGNAT), loopback, link-local, multicast,
+/// broadcast or unspecified.
+fn is_external(ip: IpAddr) -> bool {
+    let ip = match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+        IpAddr::V4(_) => ip,
+    };
+    match ip {
+        IpAddr::V4(v4) => {
+            let [first, second, ..] = v4.octets();
+            !(v4.is_private()
+                || v4.is_loopback()
+                || v4.is_link_local()
+                || v4.is_multicast()
+                || v4.is_broadcast()
+                || v4.is_unspecified()
+                || (first == 100 && (64..128).contains(&second)))
+        }
+        IpAddr::V6(v6) => {
+            let first = v6.segments()[0];
+            !(v6.is_loopback()
+                || v6.is_multicast()
+                || v6.is_unspecified()
+                || (first & 0xfe00) == 0xfc00
+                || (first & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAPTURE: &[u8] = include_bytes!("../tests/fixtures/capture.pcap");
+    const CONN_LOG: &str = include_str!("../tests/fixtures/conn.log");
+    const CONN_JSON: &str = include_str!("../tests/fixtures/conn.json");
+
+    fn summary(ingested: &Ingested) -> Vec<(String, u64, Vec<u16>)> {
+        ingested
+            .endpoints
+            .iter()
+            .map(|e| {
+                let ports = e.ports.iter().copied().collect();
+                (e.ip.to_string(), e.connections, ports)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_read_pcap() {
+        let ingested = read_pcap(CAPTURE).unwrap();
+        assert_eq!(
+            summary(&ingested),
+            [
+                ("192.0.2.10".to_string(), 2, vec![443]),
+                ("8.8.8.8".to_string(), 1, vec![53]),
+                ("198.51.100.7".to_string(), 1, vec![22]),
+                ("2001:db8::1".to_string(), 1, vec![443]),
+            ]
+        );
+        // The internal-only flow counts, the ARP frame doesn't
+        assert_eq!(ingested.connections, 6);
+        assert_eq!(
+            ingested.warnings,
+            ["skipped packets that weren't TCP or UDP over IP: 1"]
+        );
+        assert_eq!(
+            ingested.targets(2),
+            ["192.0.2.10".parse::<IpAddr>().unwrap()]
+        );
+    }
+
+    #[test]
+    fn test_truncated_pcap() {
+        // Cut off in the middle of the fifth packet
+        let ingested = read_pcap(&CAPTURE[..CAPTURE.len() - 300]).unwrap();
+        assert!(!ingested.endpoints.is_empty());
+        assert!(ingested.connections < 6);
+        assert!(
+            ingested.warnings.iter().any(|w| w.contains("mid-packet")),
+            "{:?}",
+            ingested.warnings
+        );
+
+        assert!(matches!(
+            read_pcap(&b"not a capture at all"[..]),
+            Err(ReconError::Parse { .. })
+        ));
+    }
+
+    #[test]
+    fn test_read_zeek() {
+        let tsv = read_zeek(CONN_LOG);
+        assert_eq!(
+            summary(&tsv),
+            [
+                ("192.0.2.10".to_string(), 2, vec![443]),
+                ("198.51.100.7".to_string(), 2, vec![22, 3389]),
+                ("8.8.8.8".to_string(), 1, vec![53]),
+            ]
+        );
+        assert_eq!(tsv.connections, 6);
+        assert_eq!(
+            tsv.warnings,
+            ["skipped lines that aren't connections: 1, the first at line 15"]
+        );
+
+        let json = read_zeek(CONN_JSON);
+        assert_eq!(
+            summary(&json),
+            [
+                ("192.0.2.10".to_string(), 1, vec![443]),
+                ("198.51.100.7".to_string(), 1, vec![22]),
+                ("2001:db8::1".to_string(), 1, vec![53]),
+            ]
+        );
+        assert_eq!(json.warnings.len(), 1);
+    }
+
+    #[test]
+    fn test_packet() {
+        // Linux cooked capture of a UDP packet
+        let mut frame = vec![0; 16];
+        frame.extend([0x45, 0, 0, 28, 0, 0, 0x20, 0, 64, UDP, 0, 0]);
+        frame.extend([192, 0, 2, 1, 10, 0, 0, 1]);
+        frame.extend([0, 53, 0xc3, 0x50, 0, 8, 0, 0]);
+        let packet = packet(LINUX_SLL, &frame).unwrap();
+        assert_eq!(packet.src, IpAddr::from([192, 0, 2, 1]));
+        assert_eq!((packet.src_port, packet.dst_port), (53, 50000));
+        assert_eq!(packet.tcp_flags, None);
+
+        // A later fragment has no ports to read
+        frame[22] = 0x01;
+        assert_eq!(super::packet(LINUX_SLL, &frame), None);
+        assert_eq!(super::packet(ETHERNET, &[0; 10]), None);
+
+        assert!(is_external("192.0.2.1".parse().unwrap()));
+        assert!(!is_external("100.64.0.1".parse().unwrap()));
+        assert!(!is_external("::ffff:10.0.0.1".parse().unwrap()));
+        assert!(!is_external("fe80::1".parse().unwrap()));
+    }
+}
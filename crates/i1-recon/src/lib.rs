@@ -6,6 +6,7 @@
 #![doc(html_root_url = "https://docs.rs/i1-recon/0.1.0")]
 
 mod error;
+mod scope;
 
 #[cfg(feature = "scanner")]
 pub mod scanner;
@@ -13,13 +14,17 @@ pub mod scanner;
 #[cfg(feature = "whois")]
 pub mod whois;
 
-// Temporarily disabled due to API changes
-// #[cfg(feature = "dns")]
-// pub mod dns;
+#[cfg(feature = "dns")]
+pub mod dns;
+
+#[cfg(feature = "ingest")]
+pub mod ingest;
 
+// Temporarily disabled due to API changes
 // #[cfg(feature = "trace")]
 // pub mod trace;
 
 pub mod enrichment;
 
 pub use error::{ReconError, ReconResult};
+pub use scope::ScopeGuard;
@@ -1,9 +1,16 @@
 //! Port scanning integration using pistol.
 
-use crate::error::ReconResult;
-use std::net::IpAddr;
+use crate::error::{ReconError, ReconResult};
+use crate::ScopeGuard;
+use i1_core::fingerprints::FingerprintDb;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::str::FromStr;
+use std::sync::Arc;
 use std::time::Duration;
 
+/// Longest we wait for a service to volunteer a banner
+const BANNER_TIMEOUT: Duration = Duration::from_secs(2);
+
 /// Port scanning configuration
 #[derive(Debug, Clone)]
 pub struct ScanConfig {
@@ -15,6 +22,12 @@ pub struct ScanConfig {
     pub timeout: Duration,
     /// Maximum concurrent probes
     pub concurrent: usize,
+    /// Networks that may (and may not) be probed
+    pub scope: ScopeGuard,
+    /// Read the first bytes a service sends after connecting
+    pub grab_banner: bool,
+    /// Fingerprints banners are identified with; the built-in ones if unset
+    pub fingerprints: Option<Arc<FingerprintDb>>,
 }
 
 impl Default for ScanConfig {
@@ -24,6 +37,9 @@ impl Default for ScanConfig {
             scan_type: ScanType::TcpConnect,
             timeout: Duration::from_secs(5),
             concurrent: 100,
+            scope: ScopeGuard::default(),
+            grab_banner: false,
+            fingerprints: None,
         }
     }
 }
@@ -57,6 +73,100 @@ impl PortSpec {
     }
 }
 
+impl FromStr for PortSpec {
+    type Err = ReconError;
+
+    /// Parse `top100`, `top1000`, `all`, a range (`1-1024`) or a
+    /// comma-separated list of ports and ranges (`22,80,8000-8100`).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ReconError::Parse {
+            what: "port specification",
+            input: s.to_string(),
+        };
+        let parse_port = |p: &str| p.trim().parse::<u16>().map_err(|_| invalid());
+
+        match s.trim().to_lowercase().as_str() {
+            "top100" => return Ok(Self::Top100),
+            "top1000" => return Ok(Self::Top1000),
+            "all" | "-" => return Ok(Self::All),
+            _ => {}
+        }
+
+        let mut ports = Vec::new();
+        for item in s.split(',').filter(|i| !i.trim().is_empty()) {
+            if let Some((lo, hi)) = item.split_once('-') {
+                let (lo, hi) = (parse_port(lo)?, parse_port(hi)?);
+                if lo > hi {
+                    return Err(invalid());
+                }
+                if !s.contains(',') {
+                    return Ok(Self::Range(lo..=hi));
+                }
+                ports.extend(lo..=hi);
+            } else {
+                ports.push(parse_port(item)?);
+            }
+        }
+
+        if ports.is_empty() {
+            return Err(invalid());
+        }
+        ports.sort_unstable();
+        ports.dedup();
+        Ok(Self::List(ports))
+    }
+}
+
+/// Scan timing preset
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Timing {
+    /// Few probes at a time with generous timeouts
+    Polite,
+    /// Balanced defaults
+    #[default]
+    Normal,
+    /// Many concurrent probes with short timeouts
+    Aggressive,
+}
+
+impl Timing {
+    /// Timeout per probe for this preset
+    #[must_use]
+    pub const fn timeout(self) -> Duration {
+        match self {
+            Self::Polite => Duration::from_secs(8),
+            Self::Normal => Duration::from_secs(5),
+            Self::Aggressive => Duration::from_secs(1),
+        }
+    }
+
+    /// Maximum concurrent probes for this preset
+    #[must_use]
+    pub const fn concurrent(self) -> usize {
+        match self {
+            Self::Polite => 10,
+            Self::Normal => 100,
+            Self::Aggressive => 500,
+        }
+    }
+}
+
+impl FromStr for Timing {
+    type Err = ReconError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "polite" | "slow" => Ok(Self::Polite),
+            "normal" => Ok(Self::Normal),
+            "aggressive" | "fast" => Ok(Self::Aggressive),
+            _ => Err(ReconError::Parse {
+                what: "timing (expected polite, normal or aggressive)",
+                input: s.to_string(),
+            }),
+        }
+    }
+}
+
 /// Scan type/technique
 #[derive(Debug, Clone, Copy, Default)]
 pub enum ScanType {
@@ -121,6 +231,23 @@ pub struct ServiceInfo {
     pub banner: Option<String>,
 }
 
+impl ServiceInfo {
+    /// Fill in the name, product and version from the banner, if `db`
+    /// recognises it
+    fn identify(&mut self, port: u16, db: &FingerprintDb) {
+        let Some(found) = self
+            .banner
+            .as_deref()
+            .and_then(|b| db.match_banner(port, b))
+        else {
+            return;
+        };
+        self.name = Some(found.service);
+        self.product = Some(found.product);
+        self.version = found.version;
+    }
+}
+
 /// Port scanner
 pub struct Scanner {
     config: ScanConfig,
@@ -166,36 +293,84 @@ impl Scanner {
         self
     }
 
+    /// Apply a timing preset (timeout and concurrency)
+    #[must_use]
+    pub const fn timing(mut self, timing: Timing) -> Self {
+        self.config.timeout = timing.timeout();
+        self.config.concurrent = timing.concurrent();
+        self
+    }
+
+    /// Set the maximum number of concurrent probes
+    #[must_use]
+    pub fn concurrent(mut self, concurrent: usize) -> Self {
+        self.config.concurrent = concurrent.max(1);
+        self
+    }
+
+    /// Enable or disable banner grabbing on open TCP ports
+    #[must_use]
+    pub const fn grab_banner(mut self, grab: bool) -> Self {
+        self.config.grab_banner = grab;
+        self
+    }
+
+    /// Identify banners with `fingerprints` instead of the built-in ones
+    #[must_use]
+    pub fn fingerprints(mut self, fingerprints: FingerprintDb) -> Self {
+        self.config.fingerprints = Some(Arc::new(fingerprints));
+        self
+    }
+
+    /// Set the scan scope
+    #[must_use]
+    pub fn scope(mut self, scope: ScopeGuard) -> Self {
+        self.config.scope = scope;
+        self
+    }
+
     /// Scan a single target
+    ///
+    /// Returns [`ReconError::OutOfScope`](crate::ReconError::OutOfScope)
+    /// without sending anything if the target is outside the scan scope.
+    ///
+    /// SYN, FIN and ACK scans need raw sockets, which we do not use; they
+    /// fall back to a TCP connect scan.
     pub async fn scan(&self, target: IpAddr) -> ReconResult<ScanResult> {
         use std::time::Instant;
 
+        self.config.scope.check(target)?;
+
         let start = Instant::now();
         let ports = self.config.ports.to_ports();
 
-        // Perform TCP connect scan
         let mut open_ports = Vec::new();
 
         // Use concurrent scanning with semaphore to limit connections
         let semaphore = std::sync::Arc::new(tokio::sync::Semaphore::new(self.config.concurrent));
         let timeout = self.config.timeout;
+        let scan_type = self.config.scan_type;
+        let grab_banner = self.config.grab_banner;
+        let fingerprints = self.config.fingerprints.clone();
 
         let mut handles = Vec::new();
 
         for port in ports {
             let sem = semaphore.clone();
-            let addr = std::net::SocketAddr::new(target, port);
+            let addr = SocketAddr::new(target, port);
+            let fingerprints = fingerprints.clone();
 
             let handle = tokio::spawn(async move {
                 let _permit = sem.acquire().await.ok()?;
-                match tokio::time::timeout(timeout, tokio::net::TcpStream::connect(addr)).await {
-                    Ok(Ok(_)) => Some(PortInfo {
-                        port,
-                        state: PortState::Open,
-                        service: None,
-                    }),
-                    _ => None,
+                let mut port_info = match scan_type {
+                    ScanType::Udp => probe_udp(addr, timeout).await,
+                    _ => probe_tcp(addr, timeout, grab_banner).await,
+                }?;
+                if let Some(service) = &mut port_info.service {
+                    let builtin = FingerprintDb::builtin();
+                    service.identify(port, fingerprints.as_deref().unwrap_or(builtin));
                 }
+                Some(port_info)
             });
 
             handles.push(handle);
@@ -224,6 +399,88 @@ impl Scanner {
     }
 }
 
+/// Probe a TCP port with a full connect, optionally reading a banner
+async fn probe_tcp(addr: SocketAddr, timeout: Duration, grab_banner: bool) -> Option<PortInfo> {
+    let stream = tokio::time::timeout(timeout, tokio::net::TcpStream::connect(addr))
+        .await
+        .ok()?
+        .ok()?;
+
+    let service = if grab_banner {
+        read_banner(&stream, timeout.min(BANNER_TIMEOUT))
+            .await
+            .map(|banner| ServiceInfo {
+                name: None,
+                product: None,
+                version: None,
+                banner: Some(banner),
+            })
+    } else {
+        None
+    };
+
+    Some(PortInfo {
+        port: addr.port(),
+        state: PortState::Open,
+        service,
+    })
+}
+
+/// Read whatever the service sends first, if anything
+async fn read_banner(stream: &tokio::net::TcpStream, timeout: Duration) -> Option<String> {
+    let mut buf = [0u8; 512];
+
+    let read = async {
+        loop {
+            stream.readable().await.ok()?;
+            match stream.try_read(&mut buf) {
+                Ok(n) => return Some(n),
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+                Err(_) => return None,
+            }
+        }
+    };
+
+    let n = tokio::time::timeout(timeout, read).await.ok()??;
+    let banner = String::from_utf8_lossy(&buf[..n]).trim().to_string();
+    (!banner.is_empty()).then_some(banner)
+}
+
+/// Probe a UDP port with an empty datagram.
+///
+/// Only ports that answer are reported. Closed ports answer with ICMP
+/// port unreachable and silent ports are indistinguishable from filtered
+/// ones, so both are skipped.
+async fn probe_udp(addr: SocketAddr, timeout: Duration) -> Option<PortInfo> {
+    let bind: SocketAddr = if addr.is_ipv4() {
+        (Ipv4Addr::UNSPECIFIED, 0).into()
+    } else {
+        (Ipv6Addr::UNSPECIFIED, 0).into()
+    };
+
+    let socket = tokio::net::UdpSocket::bind(bind).await.ok()?;
+    socket.connect(addr).await.ok()?;
+    socket.send(&[]).await.ok()?;
+
+    let mut buf = [0u8; 512];
+    let n = tokio::time::timeout(timeout, socket.recv(&mut buf))
+        .await
+        .ok()?
+        .ok()?;
+
+    let banner = String::from_utf8_lossy(&buf[..n]).trim().to_string();
+    Some(PortInfo {
+        port: addr.port(),
+        state: PortState::Open,
+        service: (!banner.is_empty()).then_some(ServiceInfo {
+            name: None,
+            product: None,
+            version: None,
+            banner: Some(banner),
+        }),
+    })
+}
+
 // Top 100 most common ports
 const TOP_100_PORTS: [u16; 100] = [
     21, 22, 23, 25, 26, 53, 80, 81, 110, 111, 113, 135, 139, 143, 179, 199, 443, 445, 465, 514,
@@ -237,3 +494,89 @@ const TOP_100_PORTS: [u16; 100] = [
 
 // Top 1000 ports (abbreviated - in practice would be the full nmap top 1000)
 const TOP_1000_PORTS: [u16; 100] = TOP_100_PORTS;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_port_spec_parse() {
+        assert!(matches!("top100".parse(), Ok(PortSpec::Top100)));
+        assert!(matches!("1-1024".parse(), Ok(PortSpec::Range(r)) if r == (1..=1024)));
+        assert_eq!(
+            "443,22,80,22".parse::<PortSpec>().unwrap().to_ports(),
+            vec![22, 80, 443]
+        );
+        assert_eq!(
+            "22,8000-8002".parse::<PortSpec>().unwrap().to_ports(),
+            vec![22, 8000, 8001, 8002]
+        );
+        assert!("1024-1".parse::<PortSpec>().is_err());
+        assert!("http".parse::<PortSpec>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_banner_identified() {
+        use tokio::io::AsyncWriteExt;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            stream
+                .write_all(b"SSH-2.0-OpenSSH_9.6p1 Ubuntu-3ubuntu13\r\n")
+                .await
+                .unwrap();
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        });
+
+        let result = Scanner::new()
+            .ports(PortSpec::List(vec![port]))
+            .grab_banner(true)
+            .scan(IpAddr::from([127, 0, 0, 1]))
+            .await
+            .unwrap();
+        let service = result.open_ports[0].service.as_ref().unwrap();
+        assert_eq!(service.name.as_deref(), Some("ssh"));
+        assert_eq!(service.product.as_deref(), Some("OpenSSH"));
+        assert_eq!(service.version.as_deref(), Some("9.6p1"));
+    }
+
+    #[tokio::test]
+    async fn test_scan_ipv6() {
+        let Ok(tcp) = tokio::net::TcpListener::bind("[::1]:0").await else {
+            // No IPv6 loopback here
+            return;
+        };
+        let udp = tokio::net::UdpSocket::bind("[::1]:0").await.unwrap();
+        let tcp_port = tcp.local_addr().unwrap().port();
+        let udp_port = udp.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let _accepted = tcp.accept().await.unwrap();
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        });
+        tokio::spawn(async move {
+            let mut buf = [0u8; 16];
+            let (_, from) = udp.recv_from(&mut buf).await.unwrap();
+            udp.send_to(b"pong", from).await.unwrap();
+        });
+        let target = IpAddr::from(Ipv6Addr::LOCALHOST);
+
+        let result = Scanner::new()
+            .ports(PortSpec::List(vec![tcp_port]))
+            .scan(target)
+            .await
+            .unwrap();
+        assert_eq!(result.target, target);
+        assert_eq!(result.open_ports[0].port, tcp_port);
+
+        let result = Scanner::new()
+            .ports(PortSpec::List(vec![udp_port]))
+            .scan_type(ScanType::Udp)
+            .scan(target)
+            .await
+            .unwrap();
+        let service = result.open_ports[0].service.as_ref().unwrap();
+        assert_eq!(service.banner.as_deref(), Some("pong"));
+    }
+}
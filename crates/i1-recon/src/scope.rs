@@ -0,0 +1,262 @@
+//! Scope enforcement for active reconnaissance.
+//!
+//! A [`ScopeGuard`] holds the networks a scan is allowed to touch and the
+//! networks it must never touch. Every module that sends packets consults
+//! the guard first and refuses targets that fall outside of it.
+
+use crate::error::{ReconError, ReconResult};
+use ipnet::IpNet;
+use std::net::IpAddr;
+use std::path::Path;
+
+/// Well-known government and military ranges excluded by default.
+const DEFAULT_EXCLUDED: &[&str] = &[
+    // US Department of Defense and armed services
+    "6.0.0.0/8",
+    "7.0.0.0/8",
+    "11.0.0.0/8",
+    "21.0.0.0/8",
+    "22.0.0.0/8",
+    "26.0.0.0/8",
+    "28.0.0.0/8",
+    "29.0.0.0/8",
+    "30.0.0.0/8",
+    "33.0.0.0/8",
+    "55.0.0.0/8",
+    "214.0.0.0/8",
+    "215.0.0.0/8",
+    // UK Ministry of Defence
+    "25.0.0.0/8",
+];
+
+/// Allowed and excluded networks for active reconnaissance.
+///
+/// An address is in scope when it falls inside an allowed network (or no
+/// allowed networks are configured) and inside none of the excluded ones.
+/// Exclusions always win over inclusions.
+#[derive(Debug, Clone)]
+pub struct ScopeGuard {
+    allowed: Vec<IpNet>,
+    excluded: Vec<IpNet>,
+}
+
+impl Default for ScopeGuard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ScopeGuard {
+    /// Create a guard that allows everything except the default exclusions
+    #[must_use]
+    pub fn new() -> Self {
+        let excluded = DEFAULT_EXCLUDED
+            .iter()
+            .filter_map(|net| net.parse().ok())
+            .collect();
+
+        Self {
+            allowed: Vec::new(),
+            excluded,
+        }
+    }
+
+    /// Create a guard with no allowed or excluded networks
+    #[must_use]
+    pub const fn empty() -> Self {
+        Self {
+            allowed: Vec::new(),
+            excluded: Vec::new(),
+        }
+    }
+
+    /// Parse a scope definition.
+    ///
+    /// One network per line, as a CIDR or bare IP address. Lines starting
+    /// with `!` are exclusions, `#` starts a comment. The default exclusions
+    /// are always applied on top of the parsed entries.
+    pub fn parse(text: &str) -> ReconResult<Self> {
+        let mut guard = Self::new();
+
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            match line.strip_prefix('!') {
+                Some(net) => guard = guard.exclude(parse_net(net.trim())?),
+                None => guard = guard.allow(parse_net(line)?),
+            }
+        }
+
+        Ok(guard)
+    }
+
+    /// Load a scope definition from a text file (see [`ScopeGuard::parse`])
+    pub fn from_file(path: impl AsRef<Path>) -> ReconResult<Self> {
+        let text = std::fs::read_to_string(path)?;
+        Self::parse(&text)
+    }
+
+    /// Add an allowed network
+    #[must_use]
+    pub fn allow(mut self, net: impl Into<IpNet>) -> Self {
+        self.allowed.push(net.into());
+        self
+    }
+
+    /// Add an excluded network
+    #[must_use]
+    pub fn exclude(mut self, net: impl Into<IpNet>) -> Self {
+        self.excluded.push(net.into());
+        self
+    }
+
+    /// Exclude every entry of a defend state file's whitelist.
+    ///
+    /// Hosts we have explicitly whitelisted are ours or our partners', so
+    /// they are never scanned. A missing file is not an error.
+    pub fn exclude_whitelist_file(mut self, path: impl AsRef<Path>) -> ReconResult<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(self);
+        }
+
+        let content = std::fs::read_to_string(path)?;
+        let state: serde_json::Value =
+            serde_json::from_str(&content).map_err(|e| ReconError::Parse {
+                what: "defend state file",
+                input: format!("{}: {e}", path.display()),
+            })?;
+
+        if let Some(entries) = state
+            .get("whitelisted_ips")
+            .and_then(serde_json::Value::as_array)
+        {
+            self.excluded.extend(
+                entries
+                    .iter()
+                    .filter_map(serde_json::Value::as_str)
+                    .filter_map(|s| parse_net(s).ok()),
+            );
+        }
+
+        Ok(self)
+    }
+
+    /// Merge another guard into this one
+    #[must_use]
+    pub fn merge(mut self, other: Self) -> Self {
+        self.allowed.extend(other.allowed);
+        self.excluded.extend(other.excluded);
+        self
+    }
+
+    /// Allowed networks
+    #[must_use]
+    pub fn allowed(&self) -> &[IpNet] {
+        &self.allowed
+    }
+
+    /// Excluded networks
+    #[must_use]
+    pub fn excluded(&self) -> &[IpNet] {
+        &self.excluded
+    }
+
+    /// Returns true if the address may be probed
+    #[must_use]
+    pub fn is_in_scope(&self, ip: IpAddr) -> bool {
+        let ip = canonical(ip);
+
+        let allowed = self.allowed.is_empty() || self.allowed.iter().any(|net| net.contains(&ip));
+
+        allowed && !self.excluded.iter().any(|net| net.contains(&ip))
+    }
+
+    /// Check an address, returning [`ReconError::OutOfScope`] if it may not be probed
+    pub fn check(&self, ip: IpAddr) -> ReconResult<()> {
+        if self.is_in_scope(ip) {
+            Ok(())
+        } else {
+            Err(ReconError::OutOfScope(ip))
+        }
+    }
+}
+
+/// Parse a CIDR or bare IP address into a network; an IPv6 address may be
+/// bracketed
+fn parse_net(s: &str) -> ReconResult<IpNet> {
+    s.parse::<IpNet>()
+        .ok()
+        .or_else(|| i1_core::validate_ip(s).ok().map(IpNet::from))
+        .ok_or_else(|| ReconError::Parse {
+            what: "IP address or CIDR",
+            input: s.to_string(),
+        })
+}
+
+/// Treat IPv4-mapped IPv6 addresses as the IPv4 address they carry
+fn canonical(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+        IpAddr::V4(_) => ip,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_excluded_inside_included_is_blocked() {
+        let guard = ScopeGuard::empty()
+            .allow(parse_net("10.0.0.0/8").unwrap())
+            .exclude(parse_net("10.1.2.3").unwrap());
+
+        assert!(guard.is_in_scope(ip("10.1.2.4")));
+        assert!(!guard.is_in_scope(ip("10.1.2.3")));
+        assert!(!guard.is_in_scope(ip("192.168.1.1")));
+        assert!(matches!(
+            guard.check(ip("10.1.2.3")),
+            Err(ReconError::OutOfScope(_))
+        ));
+    }
+
+    #[test]
+    fn test_default_exclusions() {
+        let guard = ScopeGuard::new();
+        assert!(!guard.is_in_scope(ip("214.1.1.1")));
+        assert!(!guard.is_in_scope(ip("::ffff:214.1.1.1")));
+        assert!(guard.is_in_scope(ip("8.8.8.8")));
+    }
+
+    #[test]
+    fn test_ipv6_scope() {
+        let guard = ScopeGuard::parse(
+            "# lab network\n\
+             2001:db8::/32\n\
+             !2001:db8:dead::/48\n\
+             !2001:db8:1::bad\n\
+             [2001:db9::1]\n",
+        )
+        .unwrap();
+
+        assert!(guard.is_in_scope(ip("2001:db8:1::1")));
+        assert!(!guard.is_in_scope(ip("2001:db8:dead::1")));
+        assert!(!guard.is_in_scope(ip("2001:db8:1::bad")));
+        assert!(guard.is_in_scope(ip("2001:db9::1")));
+        assert!(!guard.is_in_scope(ip("2001:db9::2")));
+        assert!(!guard.is_in_scope(ip("8.8.8.8")));
+    }
+
+    #[test]
+    fn test_parse_rejects_garbage() {
+        assert!(ScopeGuard::parse("not-a-network").is_err());
+    }
+}
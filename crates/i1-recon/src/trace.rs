@@ -1,6 +1,7 @@
 //! Network path tracing (traceroute/MTR) integration.
 
 use crate::error::{ReconError, ReconResult};
+use crate::ScopeGuard;
 use std::net::IpAddr;
 use std::time::Duration;
 
@@ -57,6 +58,8 @@ pub struct NetworkTracer {
     timeout: Duration,
     /// Number of probes per hop
     probes_per_hop: u8,
+    /// Networks that may (and may not) be traced
+    scope: ScopeGuard,
 }
 
 impl Default for NetworkTracer {
@@ -73,6 +76,7 @@ impl NetworkTracer {
             max_ttl: 30,
             timeout: Duration::from_secs(5),
             probes_per_hop: 3,
+            scope: ScopeGuard::default(),
         }
     }
 
@@ -97,12 +101,21 @@ impl NetworkTracer {
         self
     }
 
+    /// Set the trace scope
+    #[must_use]
+    pub fn scope(mut self, scope: ScopeGuard) -> Self {
+        self.scope = scope;
+        self
+    }
+
     /// Trace route to target
     ///
     /// Note: This requires raw socket access (root/admin privileges).
     pub async fn trace(&self, target: IpAddr) -> ReconResult<TraceResult> {
         use trippy_core::Builder;
 
+        self.scope.check(target)?;
+
         let start = std::time::Instant::now();
 
         // Build the tracer
@@ -110,7 +123,7 @@ impl NetworkTracer {
         let _tracer = Builder::new(target)
             .max_ttl(self.max_ttl.into())
             .build()
-            .map_err(|e| ReconError::Trace(e.to_string()))?;
+            .map_err(|e| ReconError::Trace(e.into()))?;
 
         // Note: Full implementation would:
         // 1. Run the tracer in a background task
@@ -1,8 +1,19 @@
 //! WHOIS lookup integration using whois-rs.
+//!
+//! Besides domain and IP lookups, [`summarize_owners`] groups a list of
+//! addresses by the allocation they belong to, with who holds it and where
+//! to report abuse. [`Netblocks`] does the lookups behind it, one per
+//! allocation rather than one per address.
 
 use crate::error::{ReconError, ReconResult};
+use ipnet::{IpNet, Ipv4Subnets, Ipv6Subnets};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
 use std::net::IpAddr;
 
+/// Addresses kept as examples in each [`NetblockSummary`]
+const SAMPLE_IPS: usize = 5;
+
 /// WHOIS lookup result
 #[derive(Debug, Clone)]
 pub struct WhoisInfo {
@@ -45,33 +56,345 @@ impl WhoisClient {
     pub fn new() -> ReconResult<Self> {
         // Load from embedded server list
         let whois = whois_rs::WhoIs::from_string(include_str!("whois_servers.json"))
-            .map_err(|e| ReconError::Whois(e.to_string()))?;
+            .map_err(|e| ReconError::Whois(e.into()))?;
         Ok(Self { whois })
     }
 
     /// Lookup WHOIS information for a domain
     pub async fn lookup_domain(&self, domain: &str) -> ReconResult<WhoisInfo> {
         let options = whois_rs::WhoIsLookupOptions::from_string(domain)
-            .map_err(|e| ReconError::Whois(e.to_string()))?;
+            .map_err(|e| ReconError::Whois(e.into()))?;
         let raw = self
             .whois
             .lookup(options)
-            .map_err(|e| ReconError::Whois(e.to_string()))?;
+            .map_err(|e| ReconError::Whois(e.into()))?;
 
         Ok(parse_whois_response(&raw))
     }
 
     /// Lookup WHOIS information for an IP address
     pub async fn lookup_ip(&self, ip: IpAddr) -> ReconResult<WhoisInfo> {
-        let options = whois_rs::WhoIsLookupOptions::from_string(&ip.to_string())
-            .map_err(|e| ReconError::Whois(e.to_string()))?;
-        let raw = self
-            .whois
+        Ok(parse_whois_response(&self.whois_ip(ip)?))
+    }
+}
+
+/// Where [`Netblocks`] gets IP WHOIS answers from. [`WhoisClient`] in real
+/// use; tests answer with canned text.
+pub trait IpWhois {
+    /// The raw WHOIS answer for `ip`. Blocks until it arrives.
+    fn whois_ip(&self, ip: IpAddr) -> ReconResult<String>;
+}
+
+impl IpWhois for WhoisClient {
+    fn whois_ip(&self, ip: IpAddr) -> ReconResult<String> {
+        let options = whois_rs::WhoIsLookupOptions::from_string(ip.to_string())
+            .map_err(|e| ReconError::Whois(e.into()))?;
+        self.whois
             .lookup(options)
-            .map_err(|e| ReconError::Whois(e.to_string()))?;
+            .map_err(|e| ReconError::Whois(e.into()))
+    }
+}
 
-        Ok(parse_whois_response(&raw))
+/// An address allocation, as an IP WHOIS answer describes it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Netblock {
+    /// The CIDRs the allocation adds up to; one unless its range doesn't
+    /// fall on a prefix boundary
+    pub cidrs: Vec<IpNet>,
+    /// Organization holding it
+    pub org: Option<String>,
+    /// Country code as registered
+    pub country: Option<String>,
+    /// Where to report abuse
+    pub abuse_email: Option<String>,
+}
+
+impl Netblock {
+    /// The CIDR of the allocation holding `ip`, if it does.
+    pub fn cidr_of(&self, ip: IpAddr) -> Option<IpNet> {
+        self.cidrs.iter().find(|net| net.contains(&ip)).copied()
+    }
+}
+
+/// Addresses that share an allocation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NetblockSummary {
+    /// The allocation's CIDR holding them. An address whose allocation
+    /// couldn't be found stands alone as a /32 or /128.
+    pub cidr: IpNet,
+    /// Organization holding it
+    pub org: Option<String>,
+    /// Country code as registered
+    pub country: Option<String>,
+    /// Where to report abuse
+    pub abuse_email: Option<String>,
+    /// Distinct addresses in it
+    pub ip_count: usize,
+    /// The first few of them, lowest first
+    pub sample_ips: Vec<IpAddr>,
+}
+
+/// IP WHOIS lookups, one per allocation: an address close to one already
+/// looked up is answered from that answer without asking again.
+///
+/// Close means inside the allocation that was found, or inside the /24
+/// (/48 for IPv6) around the address asked if the allocation is larger. A
+/// large allocation can have reassignments inside it that an answer about
+/// one address doesn't list, but those are seldom smaller than that.
+pub struct Netblocks<W> {
+    whois: W,
+    /// Answers, with the range each one stands for
+    known: Vec<(IpNet, Netblock)>,
+}
+
+impl<W: IpWhois> Netblocks<W> {
+    /// Look up through `whois`, with nothing known yet.
+    pub const fn new(whois: W) -> Self {
+        Self {
+            whois,
+            known: Vec::new(),
+        }
+    }
+
+    /// The allocation holding `ip`, or `None` if the answer names none.
+    pub fn lookup(&mut self, ip: IpAddr) -> ReconResult<Option<Netblock>> {
+        if let Some((_, block)) = self.known.iter().find(|(scope, _)| scope.contains(&ip)) {
+            return Ok(Some(block.clone()));
+        }
+        let Some(block) = parse_netblock(&self.whois.whois_ip(ip)?, ip) else {
+            return Ok(None);
+        };
+        if let Some(cidr) = block.cidr_of(ip) {
+            let narrowest = if ip.is_ipv4() { 24 } else { 48 };
+            let scope = if cidr.prefix_len() >= narrowest {
+                cidr
+            } else {
+                IpNet::new(ip, narrowest).map_or(cidr, |net| net.trunc())
+            };
+            self.known.push((scope, block.clone()));
+        }
+        Ok(Some(block))
     }
+
+    /// Group `ips` by allocation CIDR, most addresses first. A failed
+    /// lookup leaves its address on its own; only when every lookup fails
+    /// is that an error.
+    pub fn summarize(&mut self, ips: &[IpAddr]) -> ReconResult<Vec<NetblockSummary>> {
+        let ips: BTreeSet<IpAddr> = ips.iter().copied().collect();
+        let mut groups: BTreeMap<IpNet, (Option<Netblock>, Vec<IpAddr>)> = BTreeMap::new();
+        let mut first_error = None;
+        let mut failed = 0;
+        for &ip in &ips {
+            let block = match self.lookup(ip) {
+                Ok(block) => block,
+                Err(e) => {
+                    tracing::debug!("WHOIS lookup of {ip} failed: {e}");
+                    failed += 1;
+                    first_error.get_or_insert(e);
+                    None
+                }
+            };
+            let cidr = block
+                .as_ref()
+                .and_then(|b| b.cidr_of(ip))
+                .unwrap_or_else(|| IpNet::from(ip));
+            groups
+                .entry(cidr)
+                .or_insert_with(|| (block, Vec::new()))
+                .1
+                .push(ip);
+        }
+        if let Some(e) = first_error.filter(|_| failed == ips.len()) {
+            return Err(e);
+        }
+
+        let mut summaries: Vec<NetblockSummary> = groups
+            .into_iter()
+            .map(|(cidr, (block, ips))| {
+                let block = block.unwrap_or_else(|| Netblock {
+                    cidrs: Vec::new(),
+                    org: None,
+                    country: None,
+                    abuse_email: None,
+                });
+                NetblockSummary {
+                    cidr,
+                    org: block.org,
+                    country: block.country,
+                    abuse_email: block.abuse_email,
+                    ip_count: ips.len(),
+                    sample_ips: ips.into_iter().take(SAMPLE_IPS).collect(),
+                }
+            })
+            .collect();
+        summaries.sort_by(|a, b| b.ip_count.cmp(&a.ip_count).then(a.cidr.cmp(&b.cidr)));
+        Ok(summaries)
+    }
+}
+
+/// Who owns `ips`, grouped by allocation CIDR with the most addresses
+/// first. Looks them up with a [`WhoisClient`], once per allocation.
+pub async fn summarize_owners(ips: &[IpAddr]) -> ReconResult<Vec<NetblockSummary>> {
+    let ips = ips.to_vec();
+    tokio::task::spawn_blocking(move || Netblocks::new(WhoisClient::new()?).summarize(&ips))
+        .await
+        .map_err(|e| ReconError::Whois(e.into()))?
+}
+
+/// Read the allocation holding `ip` out of an IP WHOIS answer.
+///
+/// Knows ARIN (`NetRange`, `CIDR`, `OrgName`), RIPE, APNIC and AFRINIC
+/// (`inetnum`, `inet6num`, `org-name`) and LACNIC (`inetnum` with short
+/// prefixes, `owner`). An answer can list a parent allocation and a
+/// reassignment inside it; this gives the smallest one holding `ip`, with
+/// the details that follow it up to the next.
+pub fn parse_netblock(raw: &str, ip: IpAddr) -> Option<Netblock> {
+    // Details before the first range, such as RIPE's abuse contact
+    let mut preamble = Record::default();
+    let mut records: Vec<Record> = Vec::new();
+
+    for line in raw.lines() {
+        let line = line.trim();
+        let record = records.last_mut().unwrap_or(&mut preamble);
+        // RIPE: % Abuse contact for '192.0.2.0 - 192.0.2.255' is 'abuse@example.net'
+        if let Some(comment) = line.strip_prefix('%') {
+            if let Some((_, contact)) = comment.split_once("Abuse contact for") {
+                if let Some(email) = contact.rsplit('\'').nth(1).and_then(email) {
+                    record.abuse_email = Some(email);
+                }
+            }
+            continue;
+        }
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let (key, value) = (key.trim().to_lowercase(), value.trim());
+        if value.is_empty() {
+            continue;
+        }
+        match key.as_str() {
+            "netrange" | "inetnum" | "inet6num" | "cidr" => {
+                // ARIN gives `NetRange` and `CIDR` for the same allocation
+                let same = records
+                    .last()
+                    .is_some_and(|r| !r.described() && !r.keys.contains(&key));
+                if !same {
+                    records.push(Record::default());
+                }
+                let record = records.last_mut().unwrap_or(&mut preamble);
+                record
+                    .ranges
+                    .extend(value.split(',').filter_map(parse_range));
+                record.keys.push(key);
+            }
+            "country" => record.country = Some(value.to_uppercase()),
+            "orgabuseemail" | "rabuseemail" | "abuse-mailbox" => {
+                if let Some(email) = email(value) {
+                    record.abuse_email = Some(email);
+                }
+            }
+            _ => {
+                // Lower ranks name the holder better
+                let rank = [
+                    "org-name", "custname", "orgname", "owner", "netname", "descr",
+                ]
+                .iter()
+                .position(|k| *k == key);
+                if let Some(rank) = rank {
+                    record.org.entry(rank).or_insert_with(|| value.to_string());
+                }
+            }
+        }
+    }
+
+    let (record, (first, last)) = records
+        .into_iter()
+        .filter_map(|record| {
+            let range = record
+                .ranges
+                .iter()
+                .filter(|(first, last)| *first <= ip && ip <= *last)
+                .min_by_key(|(first, last)| span(*first, *last))
+                .copied()?;
+            Some((record, range))
+        })
+        .min_by_key(|(_, (first, last))| span(*first, *last))?;
+    let cidrs = match (first, last) {
+        (IpAddr::V4(first), IpAddr::V4(last)) => {
+            Ipv4Subnets::new(first, last, 0).map(IpNet::V4).collect()
+        }
+        (IpAddr::V6(first), IpAddr::V6(last)) => {
+            Ipv6Subnets::new(first, last, 0).map(IpNet::V6).collect()
+        }
+        _ => return None,
+    };
+    Some(Netblock {
+        cidrs,
+        org: record.org.into_values().next(),
+        country: record.country.or(preamble.country),
+        abuse_email: record.abuse_email.or(preamble.abuse_email),
+    })
+}
+
+/// One allocation in an IP WHOIS answer and the details listed with it.
+#[derive(Default)]
+struct Record {
+    ranges: Vec<(IpAddr, IpAddr)>,
+    /// Keys the ranges were given under
+    keys: Vec<String>,
+    /// Names of the holder by rank, the first of each
+    org: BTreeMap<usize, String>,
+    country: Option<String>,
+    abuse_email: Option<String>,
+}
+
+impl Record {
+    /// Whether anything but ranges has been read into it.
+    fn described(&self) -> bool {
+        !self.org.is_empty() || self.country.is_some() || self.abuse_email.is_some()
+    }
+}
+
+/// `192.0.2.0 - 192.0.2.255`, `192.0.2.0/24` or LACNIC's `200.160/12`, as
+/// its first and last address.
+fn parse_range(value: &str) -> Option<(IpAddr, IpAddr)> {
+    let value = value.trim();
+    if let Some((first, last)) = value.split_once(" - ") {
+        let (first, last) = (first.trim().parse().ok()?, last.trim().parse().ok()?);
+        return matches!(
+            (first, last),
+            (IpAddr::V4(_), IpAddr::V4(_)) | (IpAddr::V6(_), IpAddr::V6(_))
+        )
+        .then_some((first, last))
+        .filter(|(first, last)| first <= last);
+    }
+    let (address, prefix) = value.split_once('/')?;
+    let mut address = address.to_string();
+    if address.parse::<IpAddr>().is_err() {
+        // LACNIC leaves out trailing zero octets
+        let octets = address.split('.').count();
+        if octets >= 4 {
+            return None;
+        }
+        address.push_str(&".0".repeat(4 - octets));
+    }
+    let net = IpNet::new(address.parse().ok()?, prefix.parse().ok()?).ok()?;
+    Some((net.network(), net.broadcast()))
+}
+
+/// Addresses from `first` to `last`, less one; only compared.
+fn span(first: IpAddr, last: IpAddr) -> u128 {
+    let bits = |ip: IpAddr| match ip {
+        IpAddr::V4(v4) => u128::from(u32::from(v4)),
+        IpAddr::V6(v6) => u128::from(v6),
+    };
+    bits(last) - bits(first)
+}
+
+/// `value` if it looks like an email address.
+fn email(value: &str) -> Option<String> {
+    let value = value.trim();
+    (value.contains('@') && !value.contains(char::is_whitespace)).then(|| value.to_lowercase())
 }
 
 /// Parse raw WHOIS response into structured data
@@ -116,3 +439,166 @@ fn parse_whois_response(raw: &str) -> WhoisInfo {
 
     info
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    const ARIN: &str = "\
+NetRange:       198.51.0.0 - 198.51.255.255
+CIDR:           198.51.0.0/16
+NetName:        EXAMPLE-NET
+OrgName:        Example Transit
+Country:        US
+OrgAbuseEmail:  abuse@transit.example
+
+NetRange:       198.51.100.0 - 198.51.100.255
+CIDR:           198.51.100.0/24
+NetName:        HOSTING-7
+CustName:       Bulletproof Hosting
+Country:        us
+RAbuseEmail:    Abuse@Hosting.example
+";
+
+    const RIPE: &str = "\
+% Abuse contact for '203.0.113.0 - 203.0.114.255' is 'abuse@vps.example'
+
+inetnum:        203.0.113.0 - 203.0.114.255
+netname:        VPS-NET
+descr:          VPS hosting
+descr:          Somewhere 1
+country:        NL
+org:            ORG-VPS1-RIPE
+
+organisation:   ORG-VPS1-RIPE
+org-name:       VPS Example B.V.
+";
+
+    const LACNIC: &str = "\
+inetnum:     200.160/12
+owner:       Example Telecom S.A.
+country:     BR
+";
+
+    /// Canned answers by address prefix, counting the lookups.
+    struct Canned {
+        answers: Vec<(&'static str, &'static str)>,
+        asked: RefCell<Vec<IpAddr>>,
+    }
+
+    impl IpWhois for &Canned {
+        fn whois_ip(&self, ip: IpAddr) -> ReconResult<String> {
+            self.asked.borrow_mut().push(ip);
+            self.answers
+                .iter()
+                .find(|(prefix, _)| ip.to_string().starts_with(prefix))
+                .map(|(_, answer)| (*answer).to_string())
+                .ok_or_else(|| ReconError::Whois("connection refused".into()))
+        }
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    fn net(s: &str) -> IpNet {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_netblock() {
+        // The reassignment, not the transit block around it
+        let block = parse_netblock(ARIN, ip("198.51.100.9")).unwrap();
+        assert_eq!(block.cidrs, [net("198.51.100.0/24")]);
+        assert_eq!(block.org.as_deref(), Some("Bulletproof Hosting"));
+        assert_eq!(block.country.as_deref(), Some("US"));
+        assert_eq!(block.abuse_email.as_deref(), Some("abuse@hosting.example"));
+        // Outside the reassignment only the transit block holds it
+        let block = parse_netblock(ARIN, ip("198.51.7.1")).unwrap();
+        assert_eq!(block.cidrs, [net("198.51.0.0/16")]);
+
+        // A range that isn't one prefix, and the contact from the comment
+        let block = parse_netblock(RIPE, ip("203.0.114.20")).unwrap();
+        assert_eq!(block.cidrs, [net("203.0.113.0/24"), net("203.0.114.0/24")]);
+        assert_eq!(
+            block.cidr_of(ip("203.0.114.20")),
+            Some(net("203.0.114.0/24"))
+        );
+        assert_eq!(block.org.as_deref(), Some("VPS Example B.V."));
+        assert_eq!(block.abuse_email.as_deref(), Some("abuse@vps.example"));
+
+        let block = parse_netblock(LACNIC, ip("200.170.1.1")).unwrap();
+        assert_eq!(block.cidrs, [net("200.160.0.0/12")]);
+        assert_eq!(block.org.as_deref(), Some("Example Telecom S.A."));
+
+        assert_eq!(parse_netblock(LACNIC, ip("192.0.2.1")), None);
+        assert_eq!(parse_netblock("No match found.", ip("192.0.2.1")), None);
+    }
+
+    #[test]
+    fn test_summarize() {
+        let canned = Canned {
+            answers: vec![("198.51.", ARIN), ("203.0.", RIPE)],
+            asked: RefCell::new(Vec::new()),
+        };
+        let ips: Vec<IpAddr> = [
+            "198.51.100.1",
+            "198.51.100.2",
+            "198.51.100.3",
+            "198.51.100.2",
+            "203.0.113.5",
+            "203.0.114.6",
+            "198.51.7.1",
+            "198.51.7.200",
+            "192.0.2.1",
+        ]
+        .into_iter()
+        .map(ip)
+        .collect();
+        let summaries = Netblocks::new(&canned).summarize(&ips).unwrap();
+
+        let groups: Vec<(IpNet, usize)> = summaries.iter().map(|s| (s.cidr, s.ip_count)).collect();
+        assert_eq!(
+            groups,
+            [
+                (net("198.51.100.0/24"), 3),
+                (net("198.51.0.0/16"), 2),
+                (net("192.0.2.1/32"), 1),
+                (net("203.0.113.0/24"), 1),
+                (net("203.0.114.0/24"), 1),
+            ]
+        );
+        assert_eq!(
+            summaries[0].sample_ips,
+            [ip("198.51.100.1"), ip("198.51.100.2"), ip("198.51.100.3")]
+        );
+        assert_eq!(summaries[0].org.as_deref(), Some("Bulletproof Hosting"));
+        assert_eq!(summaries[1].org.as_deref(), Some("Example Transit"));
+        // The failed lookup stands alone without an owner
+        assert_eq!(summaries[2].org, None);
+        assert_eq!(summaries[4].org.as_deref(), Some("VPS Example B.V."));
+
+        // One lookup per /24 of the large allocation, one per smaller
+        // allocation, and the one that failed
+        assert_eq!(
+            *canned.asked.borrow(),
+            [
+                ip("192.0.2.1"),
+                ip("198.51.7.1"),
+                ip("198.51.100.1"),
+                ip("203.0.113.5"),
+                ip("203.0.114.6"),
+            ]
+        );
+
+        let offline = Canned {
+            answers: Vec::new(),
+            asked: RefCell::new(Vec::new()),
+        };
+        assert!(matches!(
+            Netblocks::new(&offline).summarize(&ips),
+            Err(ReconError::Whois(_))
+        ));
+    }
+}
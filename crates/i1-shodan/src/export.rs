@@ -0,0 +1,429 @@
+//! Search exports: whole result sets as gzipped banner JSON lines.
+//!
+//! Paging a search costs a query credit per 100 results; an export fetches
+//! everything in one file and is paid for in export credits instead, one
+//! per [`RESULTS_PER_EXPORT_CREDIT`] results. Check [`ShodanProvider::export_cost`]
+//! first, start the job with [`ShodanProvider::export_search`], poll
+//! [`ShodanProvider::export_status`] until it is ready, then stream it with
+//! [`ShodanProvider::download_export`].
+
+use std::io::Write as _;
+
+use i1_core::{HostInfo, I1Error, Result};
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tracing::{debug, instrument};
+
+use crate::{error_for_status, ShodanProvider, ShodanSearchMatch};
+
+/// Results one export credit pays for
+pub const RESULTS_PER_EXPORT_CREDIT: u64 = 10_000;
+
+/// What exporting a query would cost.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportCost {
+    /// Results the export would hold
+    pub total: u64,
+    /// Export credits it would spend
+    pub credits: u64,
+}
+
+impl ExportCost {
+    /// The cost of exporting `total` results.
+    #[must_use]
+    pub const fn for_results(total: u64) -> Self {
+        Self {
+            total,
+            credits: total.div_ceil(RESULTS_PER_EXPORT_CREDIT),
+        }
+    }
+}
+
+/// Where an export job is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportStatus {
+    Pending,
+    Processing,
+    /// Done; the file can be downloaded
+    Ready,
+    Failed,
+}
+
+/// A search export, as started or last polled.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportJob {
+    pub id: String,
+    #[serde(default)]
+    pub query: String,
+    pub status: ExportStatus,
+    /// Results in the export, once known
+    #[serde(default)]
+    pub size: Option<u64>,
+    /// Export credits charged
+    #[serde(default)]
+    pub credits: Option<u64>,
+    /// Where to download the file from, once ready
+    #[serde(default)]
+    pub url: Option<String>,
+}
+
+/// How far a download got.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportProgress {
+    /// Lines read, counting the ones skipped by the offset. Pass this back
+    /// as the offset to carry on after an interrupted download
+    pub lines: u64,
+    /// Banners handed to the callback
+    pub hosts: u64,
+    /// Lines that weren't a banner with an IP
+    pub malformed: u64,
+}
+
+impl ShodanProvider {
+    /// What exporting `query` would cost, from its result count. Counting
+    /// is free.
+    pub async fn export_cost(&self, query: &str) -> Result<ExportCost> {
+        let response: crate::ShodanCountResponse = self
+            .get_with_query("/shodan/host/count", &[("query", query)])
+            .await?;
+        Ok(ExportCost::for_results(response.total))
+    }
+
+    /// Start exporting every result of `query`. Spends export credits; see
+    /// [`ShodanProvider::export_cost`].
+    #[instrument(skip(self), fields(provider = "shodan"))]
+    pub async fn export_search(&self, query: &str) -> Result<ExportJob> {
+        let body = serde_json::json!({ "query": query });
+        self.send(Method::POST, "/shodan/export", &[], Some(&body))
+            .await
+    }
+
+    /// Poll an export started by [`ShodanProvider::export_search`].
+    pub async fn export_status(&self, id: &str) -> Result<ExportJob> {
+        self.get(&format!("/shodan/export/{id}")).await
+    }
+
+    /// Download a ready export, decompressing it as it arrives.
+    ///
+    /// Each banner line is copied to `writer` and handed to `on_host` as a
+    /// host with that one port, so a result set of any size is never held
+    /// in memory. Lines that aren't a banner are counted and skipped.
+    ///
+    /// A gzip stream can't be picked up partway through, so resuming
+    /// downloads the file again and skips the first `offset` lines, the
+    /// [`ExportProgress::lines`] of the interrupted attempt; nothing is
+    /// charged twice.
+    #[instrument(skip(self, job, writer, on_host), fields(provider = "shodan", id = %job.id))]
+    pub async fn download_export<W, F>(
+        &self,
+        job: &ExportJob,
+        offset: u64,
+        mut writer: W,
+        mut on_host: F,
+    ) -> Result<ExportProgress>
+    where
+        W: AsyncWrite + Unpin,
+        F: FnMut(HostInfo),
+    {
+        let (Some(url), ExportStatus::Ready) = (&job.url, job.status) else {
+            return Err(I1Error::Internal(format!(
+                "export {} is {:?}, not ready to download",
+                job.id, job.status
+            )));
+        };
+
+        self.inner.rate_limiter.until_ready().await;
+        // Files on the API itself need the key; elsewhere the URL is signed
+        let mut request = if url.starts_with('/') {
+            self.inner
+                .http
+                .get(format!("{}{url}", self.inner.base_url))
+                .query(&[("key", &self.inner.api_key)])
+        } else {
+            self.inner.http.get(url)
+        };
+        request = request.header(reqwest::header::ACCEPT_ENCODING, "identity");
+        debug!(url = %url, offset, "Shodan export download");
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| self.inner.http_config.request_error(&e))?;
+        let mut response = error_for_status(response, url).await?;
+
+        let mut lines = Lines {
+            gzip: flate2::write::GzDecoder::new(Vec::new()),
+            offset,
+            progress: ExportProgress::default(),
+            include_raw: self.include_raw,
+        };
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| I1Error::Http(e.to_string()))?
+        {
+            lines.gzip.write_all(&chunk).map_err(|e| corrupt(&e))?;
+            lines.drain(&mut writer, &mut on_host, false).await?;
+        }
+        lines.gzip.try_finish().map_err(|e| corrupt(&e))?;
+        lines.drain(&mut writer, &mut on_host, true).await?;
+        writer.flush().await.map_err(|e| write_failed(&e))?;
+
+        Ok(lines.progress)
+    }
+}
+
+/// Decompressed export text, split into banners as it comes.
+struct Lines {
+    /// Decompresses into its `Vec`, which holds at most one partial line
+    /// between calls to [`Lines::drain`]
+    gzip: flate2::write::GzDecoder<Vec<u8>>,
+    offset: u64,
+    progress: ExportProgress,
+    include_raw: bool,
+}
+
+impl Lines {
+    /// Handle every whole line decompressed so far, and the last partial
+    /// one too once the input has `ended`.
+    async fn drain<W, F>(&mut self, writer: &mut W, on_host: &mut F, ended: bool) -> Result<()>
+    where
+        W: AsyncWrite + Unpin,
+        F: FnMut(HostInfo),
+    {
+        let text = self.gzip.get_mut();
+        let whole = match text.iter().rposition(|&b| b == b'\n') {
+            _ if ended => text.len(),
+            Some(end) => end + 1,
+            None => return Ok(()),
+        };
+        let rest = text.split_off(whole);
+        let done = std::mem::replace(text, rest);
+
+        for line in done.split(|&b| b == b'\n') {
+            if line.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+            self.progress.lines += 1;
+            if self.progress.lines <= self.offset {
+                continue;
+            }
+
+            writer.write_all(line).await.map_err(|e| write_failed(&e))?;
+            writer
+                .write_all(b"\n")
+                .await
+                .map_err(|e| write_failed(&e))?;
+            match banner(line, self.include_raw) {
+                Some(host) => {
+                    self.progress.hosts += 1;
+                    on_host(host);
+                }
+                None => self.progress.malformed += 1,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The host a banner line describes, if it is one.
+fn banner(line: &[u8], include_raw: bool) -> Option<HostInfo> {
+    let raw: serde_json::Value = serde_json::from_slice(line).ok()?;
+    let banner = ShodanSearchMatch::deserialize(&raw).ok()?;
+    if banner.ip_str.is_empty() {
+        return None;
+    }
+    let mut host = banner.into_host_info();
+    if include_raw {
+        host.raw = Some(Box::new(raw));
+    }
+    Some(host)
+}
+
+fn corrupt(e: &std::io::Error) -> I1Error {
+    I1Error::Http(format!("export is not valid gzip: {e}"))
+}
+
+fn write_failed(e: &std::io::Error) -> I1Error {
+    I1Error::Internal(format!("could not write export: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use wiremock::matchers::{body_json, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+
+    const FIXTURE: &[u8] = include_bytes!("../tests/fixtures/export.json.gz");
+
+    fn ready(url: &str) -> ExportJob {
+        ExportJob {
+            id: "exp-1".to_string(),
+            query: "port:22".to_string(),
+            status: ExportStatus::Ready,
+            size: Some(4),
+            credits: Some(1),
+            url: Some(url.to_string()),
+        }
+    }
+
+    async fn serving_fixture() -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/shodan/export/exp-1/download"))
+            .and(query_param("key", "test-key"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("content-type", "application/gzip")
+                    .set_body_bytes(FIXTURE),
+            )
+            .mount(&server)
+            .await;
+        server
+    }
+
+    #[test]
+    fn test_export_cost() {
+        assert_eq!(ExportCost::for_results(0).credits, 0);
+        assert_eq!(ExportCost::for_results(1).credits, 1);
+        assert_eq!(ExportCost::for_results(10_000).credits, 1);
+        assert_eq!(ExportCost::for_results(10_001).credits, 2);
+    }
+
+    #[tokio::test]
+    async fn test_export_search() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/shodan/export"))
+            .and(body_json(json!({ "query": "port:22" })))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!({ "id": "exp-1", "status": "pending" })),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/shodan/host/count"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "total": 25_000 })))
+            .mount(&server)
+            .await;
+        let provider = ShodanProvider::new("test-key").with_base_url(server.uri());
+
+        let cost = provider.export_cost("port:22").await.unwrap();
+        assert_eq!(
+            cost,
+            ExportCost {
+                total: 25_000,
+                credits: 3
+            }
+        );
+
+        let job = provider.export_search("port:22").await.unwrap();
+        assert_eq!(job.status, ExportStatus::Pending);
+
+        // Not downloadable until it's ready
+        let err = provider
+            .download_export(&job, 0, tokio::io::sink(), |_| {})
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("not ready"));
+    }
+
+    #[tokio::test]
+    async fn test_download_export() {
+        let server = serving_fixture().await;
+        let provider = ShodanProvider::new("test-key").with_base_url(server.uri());
+        let job = ready("/shodan/export/exp-1/download");
+
+        let mut hosts = Vec::new();
+        let mut out = Vec::new();
+        let progress = provider
+            .download_export(&job, 0, &mut out, |host| hosts.push(host))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            progress,
+            ExportProgress {
+                lines: 5,
+                hosts: 4,
+                malformed: 1
+            }
+        );
+        let found: Vec<(&str, &[u16])> = hosts
+            .iter()
+            .map(|h| (h.ip_str.as_str(), h.ports.as_slice()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("192.0.2.1", &[22][..]),
+                ("192.0.2.2", &[80]),
+                ("192.0.2.1", &[443]),
+                ("198.51.100.7", &[3389]),
+            ]
+        );
+        assert_eq!(hosts[1].vulns, ["CVE-2021-44228"]);
+        assert_eq!(hosts[1].location.country_code.as_deref(), Some("DE"));
+        assert!(hosts[0].raw.is_none());
+
+        // The decompressed lines go to the writer as they were
+        let text = String::from_utf8(out).unwrap();
+        assert_eq!(text.lines().count(), 5);
+        assert_eq!(text.lines().nth(2), Some("not json"));
+    }
+
+    #[tokio::test]
+    async fn test_download_export_resume() {
+        let server = serving_fixture().await;
+        let provider = ShodanProvider::new("test-key")
+            .with_base_url(server.uri())
+            .include_raw(true);
+
+        let mut hosts = Vec::new();
+        let progress = provider
+            .download_export(
+                &ready("/shodan/export/exp-1/download"),
+                3,
+                tokio::io::sink(),
+                |host| hosts.push(host),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(progress.lines, 5);
+        assert_eq!(progress.hosts, 2);
+        assert_eq!(progress.malformed, 0);
+        assert_eq!(hosts[0].ports, [443]);
+        assert_eq!(
+            hosts[0].raw.as_deref().unwrap()["_shodan"]["module"],
+            "https"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_download_export_corrupt() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/files/exp-1.json.gz"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(&FIXTURE[..FIXTURE.len() / 2]))
+            .mount(&server)
+            .await;
+        let provider = ShodanProvider::new("test-key");
+
+        let err = provider
+            .download_export(
+                &ready(&format!("{}/files/exp-1.json.gz", server.uri())),
+                0,
+                tokio::io::sink(),
+                |_| {},
+            )
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("gzip"), "{err}");
+    }
+}
@@ -16,41 +16,64 @@
 //! println!("Organization: {:?}", host.org);
 //! ```
 
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Instant;
 
 use async_trait::async_trait;
-use governor::{Quota, RateLimiter};
-use i1_core::{HostInfo, I1Error, Result};
+use governor::Quota;
+use i1_core::{
+    cpe, validate_ip, Alert, CreateAlertRequest, HostInfo, I1Error, Result, Service, Transport,
+    Trigger,
+};
 use i1_providers::{
-    AuthConfig, DnsProvider, DomainInfo, HealthStatus, HostLookup, Provider, ProviderHealth,
-    RateLimitConfig, SearchProvider, SearchResults,
+    schema, AlertProvider, AuthConfig, DnsProvider, DomainInfo, HealthStatus, HostLookup, Provider,
+    ProviderHealth, ProviderHttpConfig, RateLimitConfig, RateLimiter, SearchProvider,
+    SearchResults, VulnInfo, VulnProvider,
 };
-use reqwest::Client;
+use reqwest::{Client, Method};
 use serde::de::DeserializeOwned;
+use serde::Deserialize;
 use std::net::IpAddr;
 use std::num::NonZeroU32;
 use tracing::{debug, instrument};
 
+mod export;
+mod query;
 mod types;
+pub use export::*;
+pub use query::*;
 pub use types::*;
 
 const DEFAULT_BASE_URL: &str = "https://api.shodan.io";
 
+/// Longest comma-joined list of names or IPs sent in one DNS request, as
+/// encoded in the URL. Keeps the whole URL well under the 2 KiB or so that
+/// proxies and servers reliably accept.
+const DNS_BATCH_LEN: usize = 1500;
+
 /// Shodan provider for i1
 pub struct ShodanProvider {
     inner: Arc<ShodanInner>,
+    /// Keep each host's original JSON in `HostInfo::raw`
+    include_raw: bool,
 }
 
+#[derive(Clone)]
 struct ShodanInner {
     http: Client,
+    http_config: ProviderHttpConfig,
     api_key: String,
     base_url: String,
-    rate_limiter: RateLimiter<
-        governor::state::NotKeyed,
-        governor::state::InMemoryState,
-        governor::clock::DefaultClock,
-    >,
+    rate_limiter: Arc<RateLimiter>,
+}
+
+/// How often a provider with `rate_limit` may call the API
+fn quota(rate_limit: &RateLimitConfig) -> Quota {
+    Quota::per_second(
+        NonZeroU32::new(rate_limit.requests_per_second.max(1.0) as u32).unwrap_or(NonZeroU32::MIN),
+    )
+    .allow_burst(NonZeroU32::new(rate_limit.burst_size).unwrap_or(NonZeroU32::MIN))
 }
 
 impl ShodanProvider {
@@ -59,21 +82,36 @@ impl ShodanProvider {
         Self::with_config(api_key, RateLimitConfig::shodan_free())
     }
 
+    /// Create a provider that shares its rate limiter with every other
+    /// `new_shared` instance for the same key, so together they stay under
+    /// Shodan's per-key limit.
+    ///
+    /// Providers made with [`new`](Self::new) or
+    /// [`with_config`](Self::with_config) keep a limiter of their own, so
+    /// they never wait on each other.
+    pub fn new_shared(api_key: impl Into<String>) -> Self {
+        let api_key = api_key.into();
+        let quota = quota(&RateLimitConfig::shodan_free());
+        let rate_limiter = i1_providers::shared_limiter("shodan", &api_key, quota);
+        Self::with_limiter(api_key, rate_limiter)
+    }
+
     /// Create with custom rate limit config
     pub fn with_config(api_key: impl Into<String>, rate_limit: RateLimitConfig) -> Self {
-        let quota = Quota::per_second(
-            NonZeroU32::new(rate_limit.requests_per_second.max(1.0) as u32)
-                .unwrap_or(NonZeroU32::MIN),
-        )
-        .allow_burst(NonZeroU32::new(rate_limit.burst_size).unwrap_or(NonZeroU32::MIN));
+        let rate_limiter = Arc::new(RateLimiter::direct(quota(&rate_limit)));
+        Self::with_limiter(api_key.into(), rate_limiter)
+    }
 
+    fn with_limiter(api_key: String, rate_limiter: Arc<RateLimiter>) -> Self {
         Self {
             inner: Arc::new(ShodanInner {
                 http: Client::new(),
-                api_key: api_key.into(),
+                http_config: ProviderHttpConfig::default(),
+                api_key,
                 base_url: DEFAULT_BASE_URL.to_string(),
-                rate_limiter: RateLimiter::direct(quota),
+                rate_limiter,
             }),
+            include_raw: false,
         }
     }
 
@@ -82,6 +120,33 @@ impl ShodanProvider {
         Self::with_config(api_key, RateLimitConfig::shodan_paid())
     }
 
+    /// Send requests to another API endpoint, such as a proxy or a mock
+    /// server. Clones made before this call are not affected
+    #[must_use]
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        Arc::make_mut(&mut self.inner).base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Connect with these HTTP settings, e.g. through a proxy. Clones made
+    /// before this call are not affected
+    pub fn with_http_config(mut self, config: &ProviderHttpConfig) -> Result<Self> {
+        let http = config.client()?;
+        let inner = Arc::make_mut(&mut self.inner);
+        inner.http = http;
+        inner.http_config = config.clone();
+        Ok(self)
+    }
+
+    /// Keep Shodan's original JSON on each host, in `HostInfo::raw`: the
+    /// whole host for lookups, and the banners it was built from for
+    /// searches. Clones made before this call are not affected
+    #[must_use]
+    pub const fn include_raw(mut self, include: bool) -> Self {
+        self.include_raw = include;
+        self
+    }
+
     /// Get authentication config for this provider
     pub fn auth_config(&self) -> AuthConfig {
         AuthConfig::shodan(&self.inner.api_key)
@@ -92,57 +157,124 @@ impl ShodanProvider {
         self.get_with_query(endpoint, &[]).await
     }
 
+    /// Ask a DNS endpoint about `items`, as few at a time as the URL allows.
+    /// Every item is a key of the answer, with no values if Shodan had none.
+    async fn dns_batched(
+        &self,
+        endpoint: &str,
+        param: &str,
+        items: &[&str],
+    ) -> Result<HashMap<String, Vec<String>>> {
+        let mut answers: HashMap<String, Vec<String>> = items
+            .iter()
+            .map(|item| ((*item).to_string(), Vec::new()))
+            .collect();
+        for batch in dns_batches(items, DNS_BATCH_LEN) {
+            let response: HashMap<String, serde_json::Value> =
+                self.get_with_query(endpoint, &[(param, &batch)]).await?;
+            for (key, value) in response {
+                let values = match value {
+                    serde_json::Value::String(s) => vec![s],
+                    serde_json::Value::Array(values) => values
+                        .into_iter()
+                        .filter_map(|v| v.as_str().map(String::from))
+                        .collect(),
+                    _ => Vec::new(),
+                };
+                // Shodan may answer in another case than it was asked
+                let asked = items
+                    .iter()
+                    .filter(|item| item.eq_ignore_ascii_case(&key))
+                    .map(|item| (*item).to_string())
+                    .collect::<Vec<_>>();
+                if asked.is_empty() {
+                    answers.insert(key, values);
+                } else {
+                    for item in asked {
+                        answers.insert(item, values.clone());
+                    }
+                }
+            }
+        }
+        Ok(answers)
+    }
+
     /// Make a GET request with query parameters
-    #[instrument(skip(self), fields(provider = "shodan"))]
     async fn get_with_query<T: DeserializeOwned>(
         &self,
         endpoint: &str,
         query: &[(&str, &str)],
+    ) -> Result<T> {
+        self.send(Method::GET, endpoint, query, None).await
+    }
+
+    /// Make a request to the Shodan API, with an optional JSON body
+    #[instrument(skip(self, body), fields(provider = "shodan"))]
+    async fn send<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        endpoint: &str,
+        query: &[(&str, &str)],
+        body: Option<&serde_json::Value>,
     ) -> Result<T> {
         // Wait for rate limiter
         self.inner.rate_limiter.until_ready().await;
 
         let url = format!("{}{}", self.inner.base_url, endpoint);
-        debug!(url = %url, "Shodan API request");
+        debug!(url = %url, method = %method, "Shodan API request");
 
         let mut request = self
             .inner
             .http
-            .get(&url)
+            .request(method, &url)
             .query(&[("key", &self.inner.api_key)]);
 
         if !query.is_empty() {
             request = request.query(query);
         }
+        if let Some(body) = body {
+            request = request.json(body);
+        }
 
         let response = request
             .send()
             .await
-            .map_err(|e| I1Error::Http(e.to_string()))?;
+            .map_err(|e| self.inner.http_config.request_error(&e))?;
 
-        let status = response.status();
-        if !status.is_success() {
-            let code = status.as_u16();
-            let message = response.text().await.unwrap_or_default();
-
-            return match code {
-                401 => Err(I1Error::Unauthorized),
-                402 => Err(I1Error::InsufficientCredits {
-                    required: 1,
-                    available: 0,
-                }),
-                429 => Err(I1Error::RateLimited { retry_after: None }),
-                404 => Err(I1Error::NotFound {
-                    resource: endpoint.to_string(),
-                }),
-                _ => Err(I1Error::provider("shodan", code, message)),
-            };
-        }
+        let response = error_for_status(response, endpoint).await?;
 
-        response
+        let value = response
             .json()
             .await
-            .map_err(|e| I1Error::Http(e.to_string()))
+            .map_err(|e| I1Error::Http(e.to_string()))?;
+        schema::parse("shodan", endpoint, &value)
+    }
+}
+
+/// Turn an unsuccessful response for `endpoint` into the matching error.
+async fn error_for_status(
+    response: reqwest::Response,
+    endpoint: &str,
+) -> Result<reqwest::Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let code = status.as_u16();
+    let message = response.text().await.unwrap_or_default();
+
+    match code {
+        401 => Err(I1Error::Unauthorized),
+        402 => Err(I1Error::InsufficientCredits {
+            required: 1,
+            available: 0,
+        }),
+        429 => Err(I1Error::RateLimited { retry_after: None }),
+        404 => Err(I1Error::NotFound {
+            resource: endpoint.to_string(),
+        }),
+        _ => Err(I1Error::provider("shodan", code, message)),
     }
 }
 
@@ -150,6 +282,7 @@ impl Clone for ShodanProvider {
     fn clone(&self) -> Self {
         Self {
             inner: Arc::clone(&self.inner),
+            include_raw: self.include_raw,
         }
     }
 }
@@ -187,6 +320,14 @@ impl Provider for ShodanProvider {
                     latency_ms: Some(start.elapsed().as_millis() as u64),
                     credits_remaining: credits,
                     message: None,
+                    plan: info
+                        .get("plan")
+                        .and_then(serde_json::Value::as_str)
+                        .map(String::from),
+                    scan_credits: info
+                        .get("scan_credits")
+                        .and_then(serde_json::Value::as_i64),
+                    resets_at: next_month(chrono::Utc::now()),
                 })
             }
             Err(I1Error::Unauthorized) => Ok(ProviderHealth {
@@ -195,6 +336,9 @@ impl Provider for ShodanProvider {
                 latency_ms: Some(start.elapsed().as_millis() as u64),
                 credits_remaining: None,
                 message: Some("Invalid API key".to_string()),
+                plan: None,
+                scan_credits: None,
+                resets_at: None,
             }),
             Err(e) => Ok(ProviderHealth {
                 provider: "shodan".to_string(),
@@ -202,6 +346,9 @@ impl Provider for ShodanProvider {
                 latency_ms: Some(start.elapsed().as_millis() as u64),
                 credits_remaining: None,
                 message: Some(e.to_string()),
+                plan: None,
+                scan_credits: None,
+                resets_at: None,
             }),
         }
     }
@@ -211,8 +358,15 @@ impl Provider for ShodanProvider {
 impl HostLookup for ShodanProvider {
     #[instrument(skip(self), fields(provider = "shodan"))]
     async fn lookup_host(&self, ip: &str) -> Result<HostInfo> {
+        let ip = validate_ip(ip)?;
         let endpoint = format!("/shodan/host/{ip}");
-        self.get(&endpoint).await
+        let raw: serde_json::Value = self.get(&endpoint).await?;
+        let mut host = schema::recover_host("shodan", &endpoint, &raw, |host: HostInfo| host)?;
+        host.fill_cpes();
+        if self.include_raw {
+            host.raw = Some(Box::new(raw));
+        }
+        Ok(host)
     }
 }
 
@@ -223,28 +377,58 @@ impl SearchProvider for ShodanProvider {
         let page_str = page.unwrap_or(1).to_string();
         let query_params: Vec<(&str, &str)> = vec![("query", query), ("page", &page_str)];
 
-        let response: ShodanSearchResponse = self
-            .get_with_query("/shodan/host/search", &query_params)
-            .await?;
+        let (response, raw) = if self.include_raw {
+            let response: ShodanSearchResponse<serde_json::Value> = self
+                .get_with_query("/shodan/host/search", &query_params)
+                .await?;
+            let matches = response
+                .matches
+                .iter()
+                .map(ShodanSearchMatch::deserialize)
+                .collect::<std::result::Result<_, _>>()?;
+            (
+                ShodanSearchResponse {
+                    total: response.total,
+                    matches,
+                    facets: response.facets,
+                },
+                response.matches,
+            )
+        } else {
+            let response: ShodanSearchResponse = self
+                .get_with_query("/shodan/host/search", &query_params)
+                .await?;
+            (response, Vec::new())
+        };
 
         // Aggregate matches by IP - search returns one match per service/port,
-        // but we want one HostInfo per IP with all ports collected.
-        let mut ip_map: std::collections::HashMap<String, HostInfo> =
-            std::collections::HashMap::new();
+        // but we want one HostInfo per IP with all ports collected, in the
+        // order Shodan ranked them.
+        let mut results: Vec<HostInfo> = Vec::new();
+        let mut index: HashMap<String, usize> = HashMap::new();
 
+        let mut raw = raw.into_iter();
         for m in response.matches {
             let port = m.port;
-            let ip_key = m.ip_str.clone();
-            let entry = ip_map
-                .entry(ip_key)
-                .or_insert_with(|| m.into_host_info());
+            let service = m.service();
+            let at = *index.entry(m.ip_str.clone()).or_insert_with(|| {
+                results.push(m.into_host_info());
+                results.len() - 1
+            });
+            let entry = &mut results[at];
             if !entry.ports.contains(&port) {
                 entry.ports.push(port);
             }
+            entry.data.push(service);
+            // The raw host is every banner that went into it
+            if let Some(raw) = raw.next() {
+                match entry.raw.as_deref_mut() {
+                    Some(serde_json::Value::Array(banners)) => banners.push(raw),
+                    _ => entry.raw = Some(Box::new(serde_json::Value::Array(vec![raw]))),
+                }
+            }
         }
 
-        let results: Vec<HostInfo> = ip_map.into_values().collect();
-
         Ok(SearchResults {
             provider: "shodan".to_string(),
             total: response.total,
@@ -272,37 +456,32 @@ impl SearchProvider for ShodanProvider {
 #[async_trait]
 impl DnsProvider for ShodanProvider {
     async fn resolve(&self, hostname: &str) -> Result<Vec<IpAddr>> {
-        let response: ShodanDnsResolve = self
-            .get_with_query("/dns/resolve", &[("hostnames", hostname)])
-            .await?;
-
-        Ok(response
-            .0
-            .values()
-            .filter_map(|v| v.as_str())
-            .filter_map(|s| s.parse().ok())
-            .collect())
+        let mut resolved = self.resolve_many(&[hostname]).await?;
+        Ok(resolved.remove(hostname).unwrap_or_default())
     }
 
     async fn reverse(&self, ip: &str) -> Result<Vec<String>> {
-        let response: ShodanDnsReverse =
-            self.get_with_query("/dns/reverse", &[("ips", ip)]).await?;
-
-        Ok(response
-            .0
-            .values()
-            .flat_map(|v| {
-                v.as_array()
-                    .map(|arr| {
-                        arr.iter()
-                            .filter_map(|s| s.as_str().map(String::from))
-                            .collect::<Vec<_>>()
-                    })
-                    .unwrap_or_default()
+        let mut reversed = self.reverse_many(&[ip]).await?;
+        Ok(reversed.remove(ip).unwrap_or_default())
+    }
+
+    async fn resolve_many(&self, hostnames: &[&str]) -> Result<HashMap<String, Vec<IpAddr>>> {
+        let answers = self
+            .dns_batched("/dns/resolve", "hostnames", hostnames)
+            .await?;
+        Ok(answers
+            .into_iter()
+            .map(|(hostname, values)| {
+                let ips = values.iter().filter_map(|s| s.parse().ok()).collect();
+                (hostname, ips)
             })
             .collect())
     }
 
+    async fn reverse_many(&self, ips: &[&str]) -> Result<HashMap<String, Vec<String>>> {
+        self.dns_batched("/dns/reverse", "ips", ips).await
+    }
+
     async fn domain_info(&self, domain: &str) -> Result<DomainInfo> {
         let response: ShodanDomainInfo = self.get(&format!("/dns/domain/{domain}")).await?;
 
@@ -326,6 +505,170 @@ impl DnsProvider for ShodanProvider {
     }
 }
 
+#[async_trait]
+impl VulnProvider for ShodanProvider {
+    #[instrument(skip(self), fields(provider = "shodan"))]
+    async fn vulns_for_ip(&self, ip: &str) -> Result<Vec<VulnInfo>> {
+        let host = self.lookup_host(ip).await?;
+
+        // Banner-level entries carry CVSS and summaries; the host-level list
+        // only has ids, so it fills in anything the banners missed.
+        let mut vulns: Vec<VulnInfo> = Vec::new();
+        for (cve, info) in host.data.iter().flat_map(|svc| &svc.vulns) {
+            if vulns.iter().any(|v| &v.cve == cve) {
+                continue;
+            }
+            vulns.push(VulnInfo {
+                cve: cve.clone(),
+                #[allow(clippy::cast_possible_truncation)]
+                cvss: info.cvss.map(|c| c as f32),
+                summary: info.summary.clone(),
+                references: (!info.references.is_empty()).then(|| info.references.clone()),
+                verified: info.verified,
+            });
+        }
+        for cve in &host.vulns {
+            if !vulns.iter().any(|v| &v.cve == cve) {
+                vulns.push(VulnInfo {
+                    cve: cve.clone(),
+                    cvss: None,
+                    summary: None,
+                    references: None,
+                    verified: false,
+                });
+            }
+        }
+
+        Ok(vulns)
+    }
+
+    #[instrument(skip(self), fields(provider = "shodan"))]
+    async fn hosts_with_cve(&self, cve: &str) -> Result<SearchResults> {
+        let cve = i1_providers::parse_cve_id(cve)?;
+        self.search(&format!("vuln:{cve}"), None).await
+    }
+}
+
+#[async_trait]
+impl AlertProvider for ShodanProvider {
+    async fn list_alerts(&self) -> Result<Vec<Alert>> {
+        let alerts: Vec<ShodanAlert> = self
+            .get("/shodan/alert/info")
+            .await
+            .map_err(monitoring_error)?;
+        Ok(alerts.into_iter().map(Alert::from).collect())
+    }
+
+    async fn create_alert(&self, request: &CreateAlertRequest) -> Result<Alert> {
+        let body = serde_json::to_value(request)?;
+        let alert: ShodanAlert = self
+            .send(Method::POST, "/shodan/alert", &[], Some(&body))
+            .await
+            .map_err(monitoring_error)?;
+        Ok(alert.into())
+    }
+
+    async fn delete_alert(&self, id: &str) -> Result<()> {
+        let _: serde_json::Value = self
+            .send(Method::DELETE, &format!("/shodan/alert/{id}"), &[], None)
+            .await
+            .map_err(monitoring_error)?;
+        Ok(())
+    }
+
+    async fn enable_trigger(&self, id: &str, trigger: &str) -> Result<()> {
+        let endpoint = format!("/shodan/alert/{id}/trigger/{trigger}");
+        let _: serde_json::Value = self
+            .send(Method::PUT, &endpoint, &[], None)
+            .await
+            .map_err(monitoring_error)?;
+        Ok(())
+    }
+
+    async fn triggers(&self) -> Result<Vec<Trigger>> {
+        self.get("/shodan/alert/triggers")
+            .await
+            .map_err(monitoring_error)
+    }
+
+    #[instrument(skip(self), fields(provider = "shodan"))]
+    async fn alert_matches(&self, id: &str) -> Result<SearchResults> {
+        // Shodan only pushes alert hits to notifiers, so look at what it
+        // currently sees in the monitored networks instead
+        let alert: ShodanAlert = self
+            .get(&format!("/shodan/alert/{id}/info"))
+            .await
+            .map_err(monitoring_error)?;
+        if alert.filters.is_empty() {
+            return Ok(SearchResults {
+                provider: "shodan".to_string(),
+                total: 0,
+                page: 1,
+                results: vec![],
+                facets: None,
+            });
+        }
+        self.search(&format!("net:{}", alert.filters.ip.join(",")), None)
+            .await
+    }
+}
+
+/// Shodan credits are replenished at the start of each calendar month (UTC).
+fn next_month(now: chrono::DateTime<chrono::Utc>) -> Option<chrono::DateTime<chrono::Utc>> {
+    use chrono::{Datelike, NaiveDate};
+
+    let (year, month) = if now.month() == 12 {
+        (now.year() + 1, 1)
+    } else {
+        (now.year(), now.month() + 1)
+    };
+    NaiveDate::from_ymd_opt(year, month, 1)
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .map(|d| d.and_utc())
+}
+
+/// Shodan answers 403 when the plan has no network monitoring.
+fn monitoring_error(e: I1Error) -> I1Error {
+    match e {
+        I1Error::Provider { code: 403, .. } => I1Error::provider(
+            "shodan",
+            403,
+            "network monitoring is not included in this account's plan",
+        ),
+        e => e,
+    }
+}
+
+/// `items` without repeats, comma-joined into lists that are each at most
+/// `max_len` long once encoded in a URL (a comma becomes `%2C`). An item
+/// longer than that on its own is sent by itself.
+fn dns_batches(items: &[&str], max_len: usize) -> Vec<String> {
+    let mut batches = Vec::new();
+    let mut batch = String::new();
+    let mut encoded = 0;
+    let mut seen = std::collections::HashSet::new();
+    for item in items {
+        if !seen.insert(item.to_ascii_lowercase()) {
+            continue;
+        }
+        if !batch.is_empty() && encoded + "%2C".len() + item.len() > max_len {
+            batches.push(std::mem::take(&mut batch));
+        }
+        if batch.is_empty() {
+            encoded = 0;
+        } else {
+            batch.push(',');
+            encoded += "%2C".len();
+        }
+        batch.push_str(item);
+        encoded += item.len();
+    }
+    if !batch.is_empty() {
+        batches.push(batch);
+    }
+    batches
+}
+
 // Shodan-specific response types
 
 /// Raw search match from Shodan's /shodan/host/search API.
@@ -356,6 +699,11 @@ struct ShodanSearchMatch {
     version: Option<String>,
     #[serde(default)]
     transport: Option<String>,
+    /// CPE 2.2 URIs
+    #[serde(default)]
+    cpe: Vec<String>,
+    #[serde(default)]
+    cpe23: Vec<String>,
     #[serde(default)]
     location: Option<ShodanSearchLocation>,
     #[serde(default)]
@@ -387,6 +735,34 @@ struct ShodanSearchLocation {
 }
 
 impl ShodanSearchMatch {
+    /// The banner as one of its host's services.
+    fn service(&self) -> Service {
+        let mut service = Service {
+            port: self.port,
+            transport: self
+                .transport
+                .as_deref()
+                .map(Transport::from_str)
+                .unwrap_or_default(),
+            product: self.product.clone(),
+            version: self.version.clone(),
+            cpe: self.cpe.iter().chain(&self.cpe23).cloned().collect(),
+            data: self.data.clone(),
+            timestamp: None,
+            shodan_module: None,
+            http: None,
+            ssl: None,
+            ssh: None,
+            vulns: HashMap::new(),
+            tags: Vec::new(),
+            devicetype: None,
+            info: None,
+            os: None,
+        };
+        cpe::fill(&mut service);
+        service
+    }
+
     fn into_host_info(self) -> HostInfo {
         let location = self.location.unwrap_or(ShodanSearchLocation {
             country_code: None,
@@ -423,14 +799,15 @@ impl ShodanSearchMatch {
             },
             data: Vec::new(),
             last_update: None,
+            raw: None,
         }
     }
 }
 
 #[derive(Debug, serde::Deserialize)]
-struct ShodanSearchResponse {
+struct ShodanSearchResponse<M = ShodanSearchMatch> {
     total: u64,
-    matches: Vec<ShodanSearchMatch>,
+    matches: Vec<M>,
     facets: Option<serde_json::Value>,
 }
 
@@ -439,12 +816,6 @@ struct ShodanCountResponse {
     total: u64,
 }
 
-#[derive(Debug, serde::Deserialize)]
-struct ShodanDnsResolve(std::collections::HashMap<String, serde_json::Value>);
-
-#[derive(Debug, serde::Deserialize)]
-struct ShodanDnsReverse(std::collections::HashMap<String, serde_json::Value>);
-
 #[derive(Debug, serde::Deserialize)]
 struct ShodanDomainInfo {
     domain: String,
@@ -461,3 +832,368 @@ struct ShodanDnsRecord {
     subdomain: Option<String>,
     value: String,
 }
+
+/// Alert as returned by /shodan/alert; triggers and notifiers are objects.
+#[derive(Debug, serde::Deserialize)]
+struct ShodanAlert {
+    id: String,
+    name: String,
+    #[serde(default)]
+    filters: i1_core::AlertFilters,
+    #[serde(default)]
+    triggers: std::collections::HashMap<String, serde_json::Value>,
+    #[serde(default)]
+    notifiers: Vec<ShodanNotifier>,
+    #[serde(default)]
+    created: Option<String>,
+    #[serde(default)]
+    expires: Option<i64>,
+    #[serde(default)]
+    expired: bool,
+    #[serde(default)]
+    size: u64,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct ShodanNotifier {
+    id: String,
+}
+
+impl From<ShodanAlert> for Alert {
+    fn from(alert: ShodanAlert) -> Self {
+        Self {
+            id: alert.id,
+            name: alert.name,
+            filters: alert.filters,
+            triggers: alert.triggers.into_keys().map(|t| (t, true)).collect(),
+            notifiers: alert.notifiers.into_iter().map(|n| n.id).collect(),
+            created: alert.created,
+            expires: alert.expires,
+            expired: alert.expired,
+            size: alert.size,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    async fn mock(server: &MockServer, route: &str, body: serde_json::Value) {
+        Mock::given(method("GET"))
+            .and(path(route))
+            .and(query_param("key", "test-key"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(body))
+            .mount(server)
+            .await;
+    }
+
+    fn banner(ip: &str, port: u16) -> serde_json::Value {
+        json!({
+            "ip_str": ip,
+            "port": port,
+            "transport": "tcp",
+            "_shodan": {"module": "ssh", "crawler": "abc123"}
+        })
+    }
+
+    #[tokio::test]
+    async fn test_shared_rate_limit() {
+        let server = MockServer::start().await;
+        mock(&server, "/shodan/host/count", json!({ "total": 0 })).await;
+        let both = |a: ShodanProvider, b: ShodanProvider| async move {
+            let started = Instant::now();
+            let (a, b) = tokio::join!(a.count("ssh"), b.count("ssh"));
+            a.unwrap();
+            b.unwrap();
+            started.elapsed()
+        };
+
+        // One request a second, but each instance has a second of its own
+        let elapsed = both(
+            ShodanProvider::new("test-key").with_base_url(server.uri()),
+            ShodanProvider::new("test-key").with_base_url(server.uri()),
+        )
+        .await;
+        assert!(elapsed.as_millis() < 500, "{elapsed:?}");
+
+        // Shared, the second request waits for the first
+        let elapsed = both(
+            ShodanProvider::new_shared("test-key").with_base_url(server.uri()),
+            ShodanProvider::new_shared("test-key").with_base_url(server.uri()),
+        )
+        .await;
+        assert!(elapsed.as_millis() >= 900, "{elapsed:?}");
+    }
+
+    #[tokio::test]
+    async fn test_search_raw() {
+        let server = MockServer::start().await;
+        mock(
+            &server,
+            "/shodan/host/search",
+            json!({
+                "total": 3,
+                "matches": [banner("192.0.2.1", 22), banner("192.0.2.2", 80), banner("192.0.2.1", 443)]
+            }),
+        )
+        .await;
+        let provider = ShodanProvider::new("test-key").with_base_url(server.uri());
+
+        let results = provider.search("ssh", None).await.unwrap();
+        assert!(results.results.iter().all(|host| host.raw.is_none()));
+        // One host per address, in Shodan's order
+        let ips: Vec<_> = results.results.iter().map(|h| h.ip_str.as_str()).collect();
+        assert_eq!(ips, ["192.0.2.1", "192.0.2.2"]);
+
+        let results = provider
+            .clone()
+            .include_raw(true)
+            .search("ssh", None)
+            .await
+            .unwrap();
+        let host = results
+            .results
+            .iter()
+            .find(|host| host.ip_str == "192.0.2.1")
+            .unwrap();
+        assert_eq!(host.ports, [22, 443]);
+        assert_eq!(
+            host.raw.as_deref(),
+            Some(&json!([banner("192.0.2.1", 22), banner("192.0.2.1", 443)]))
+        );
+        // Kept out of serialized hosts
+        assert!(serde_json::to_value(host).unwrap().get("raw").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_search_cpes() {
+        let server = MockServer::start().await;
+        let mut ssh = banner("192.0.2.1", 22);
+        ssh["product"] = json!("OpenSSH");
+        ssh["version"] = json!("8.9p1");
+        ssh["cpe"] = json!(["cpe:/a:openbsd:openssh:8.9p1"]);
+        let mut http = banner("192.0.2.1", 80);
+        http["product"] = json!("nginx");
+        mock(
+            &server,
+            "/shodan/host/search",
+            json!({ "total": 3, "matches": [ssh, http, banner("192.0.2.2", 443)] }),
+        )
+        .await;
+        let provider = ShodanProvider::new("test-key").with_base_url(server.uri());
+
+        let results = provider.search("ssh", None).await.unwrap();
+        let host = |ip: &str| {
+            results
+                .results
+                .iter()
+                .find(|host| host.ip_str == ip)
+                .unwrap()
+        };
+        // Shodan's own CPE where it sent one, derived from the product otherwise
+        assert_eq!(
+            host("192.0.2.1").cpes(),
+            [
+                "cpe:2.3:a:f5:nginx:*:*:*:*:*:*:*:*",
+                "cpe:2.3:a:openbsd:openssh:8.9p1:*:*:*:*:*:*:*",
+            ]
+        );
+        assert_eq!(host("192.0.2.1").data.len(), 2);
+        assert!(host("192.0.2.2").cpes().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_lookup_raw() {
+        let server = MockServer::start().await;
+        let body = json!({
+            "ip_str": "192.0.2.1",
+            "ports": [22],
+            "data": [banner("192.0.2.1", 22)]
+        });
+        mock(&server, "/shodan/host/192.0.2.1", body.clone()).await;
+        let provider = ShodanProvider::new("test-key")
+            .with_base_url(server.uri())
+            .include_raw(true);
+
+        let host = provider.lookup_host("192.0.2.1").await.unwrap();
+        assert_eq!(host.ports, [22]);
+        assert_eq!(host.raw.as_deref(), Some(&body));
+    }
+
+    #[tokio::test]
+    async fn test_lookup_ipv6() {
+        const IPV6: &str = include_str!("../../i1-core/tests/fixtures/ipv6.txt");
+
+        for line in IPV6
+            .lines()
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        {
+            let (input, canonical) = line.split_once('\t').unwrap();
+            let server = MockServer::start().await;
+            mock(
+                &server,
+                &format!("/shodan/host/{canonical}"),
+                json!({ "ip_str": canonical, "ports": [443], "data": [banner(canonical, 443)] }),
+            )
+            .await;
+            let provider = ShodanProvider::new("test-key").with_base_url(server.uri());
+
+            let host = provider.lookup_host(input).await.unwrap();
+            assert_eq!(host.ip_addr(), canonical.parse().ok(), "{input}");
+            assert_eq!(host.ports, [443]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lookup_schema_drift() {
+        let server = MockServer::start().await;
+        let mut retyped = banner("192.0.2.1", 443);
+        retyped["port"] = json!("443");
+        mock(
+            &server,
+            "/shodan/host/192.0.2.1",
+            json!({
+                "ip_str": "192.0.2.1",
+                "ports": [22, 443],
+                "data": [banner("192.0.2.1", 22), retyped]
+            }),
+        )
+        .await;
+        mock(
+            &server,
+            "/shodan/host/192.0.2.2",
+            json!({ "ip_address": "192.0.2.2", "ports": [22] }),
+        )
+        .await;
+        mock(&server, "/shodan/host/count", json!({ "total": "many" })).await;
+        let provider = ShodanProvider::new("test-key").with_base_url(server.uri());
+
+        // The banner that changed type is dropped, the rest kept
+        let host = provider.lookup_host("192.0.2.1").await.unwrap();
+        assert_eq!(host.ports, [22, 443]);
+        assert_eq!(host.data.len(), 1);
+        assert_eq!(host.tags, [schema::PARTIAL_PARSE_TAG]);
+
+        // Nothing to salvage once the address itself was renamed
+        let error = provider.lookup_host("192.0.2.2").await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "unexpected shodan response from /shodan/host/192.0.2.2: \
+             $: missing field `ip_str`"
+        );
+
+        let error = provider.count("ssh").await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "unexpected shodan response from /shodan/host/count: \
+             total: invalid type: string \"many\", expected u64"
+        );
+    }
+
+    #[test]
+    fn test_dns_batches() {
+        let names = ["a.example", "b.example", "A.example", "c.example"];
+        assert_eq!(dns_batches(&names, 1500), ["a.example,b.example,c.example"]);
+        // 9 + 3 + 9 fits in 21, the third name doesn't
+        assert_eq!(
+            dns_batches(&names, 21),
+            ["a.example,b.example", "c.example"]
+        );
+        assert_eq!(dns_batches(&names, 4).len(), 3);
+        assert!(dns_batches(&[], 1500).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_many() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/dns/resolve"))
+            .and(query_param("key", "test-key"))
+            .and(query_param("hostnames", "a.example,nx.example"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "a.example": "192.0.2.1",
+                "nx.example": null
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/dns/resolve"))
+            .and(query_param("hostnames", "a.example"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(json!({ "a.example": "192.0.2.1" })),
+            )
+            .mount(&server)
+            .await;
+        let provider = ShodanProvider::new("test-key").with_base_url(server.uri());
+
+        let resolved = provider
+            .resolve_many(&["a.example", "nx.example"])
+            .await
+            .unwrap();
+        assert_eq!(resolved.len(), 2);
+        assert_eq!(
+            resolved["a.example"],
+            ["192.0.2.1".parse::<IpAddr>().unwrap()]
+        );
+        assert!(resolved["nx.example"].is_empty());
+
+        let ips = provider.resolve("a.example").await.unwrap();
+        assert_eq!(ips, ["192.0.2.1".parse::<IpAddr>().unwrap()]);
+    }
+
+    #[tokio::test]
+    async fn test_reverse_many() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/dns/reverse"))
+            .and(query_param("key", "test-key"))
+            .and(query_param("ips", "192.0.2.1,192.0.2.2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "192.0.2.1": ["one.example", "uno.example"],
+                "192.0.2.2": null
+            })))
+            .mount(&server)
+            .await;
+        let provider = ShodanProvider::new("test-key").with_base_url(server.uri());
+
+        let reversed = provider
+            .reverse_many(&["192.0.2.1", "192.0.2.2"])
+            .await
+            .unwrap();
+        assert_eq!(reversed["192.0.2.1"], ["one.example", "uno.example"]);
+        assert!(reversed["192.0.2.2"].is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_settings_after_clone() {
+        let server = MockServer::start().await;
+        mock(&server, "/shodan/host/count", json!({ "total": 3 })).await;
+
+        // A clone elsewhere doesn't stop the settings from taking
+        let provider = ShodanProvider::new("test-key");
+        let clone = provider.clone();
+        let provider = provider.with_base_url(server.uri());
+        assert_eq!(provider.count("ssh").await.unwrap(), 3);
+        assert_eq!(clone.inner.base_url, DEFAULT_BASE_URL);
+
+        let _clone = provider.clone();
+        let proxy = ProviderHttpConfig {
+            proxy: Some("http://127.0.0.1:9".to_string()),
+            ..ProviderHttpConfig::direct()
+        };
+        let provider = provider.with_http_config(&proxy).unwrap();
+        match provider.count("ssh").await {
+            Err(I1Error::Connection(message)) => {
+                assert!(
+                    message.contains("through proxy http://127.0.0.1:9"),
+                    "{message}"
+                );
+            }
+            other => panic!("expected to go through the proxy, got {other:?}"),
+        }
+    }
+}
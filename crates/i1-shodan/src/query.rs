@@ -0,0 +1,154 @@
+//! Shodan search query syntax, checked locally before a credit is spent.
+
+use i1_core::{validate_query, I1Error, Result};
+
+/// A `name:value` filter in a search query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryFilter {
+    pub name: String,
+    pub value: String,
+    /// Written `-name:value`, excluding matches
+    pub negated: bool,
+}
+
+/// A search query, split into free text and filters.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShodanQuery {
+    /// Words and quoted phrases, quotes removed
+    pub terms: Vec<String>,
+    pub filters: Vec<QueryFilter>,
+}
+
+impl ShodanQuery {
+    /// Whether Shodan bills the first page too. Only searches without
+    /// filters get it for free.
+    #[must_use]
+    pub fn has_filters(&self) -> bool {
+        !self.filters.is_empty()
+    }
+}
+
+/// Split `query` the way Shodan reads it, or say why Shodan would reject
+/// it: an unclosed quote, or a filter without a name or a value.
+pub fn parse_query(query: &str) -> Result<ShodanQuery> {
+    validate_query(query)?;
+
+    let mut parsed = ShodanQuery::default();
+    for token in tokens(query)? {
+        // A colon inside quotes is part of a phrase, not a filter
+        let filter = (!token.starts_with('"'))
+            .then(|| token.split_once(':'))
+            .flatten();
+        let Some((name, value)) = filter else {
+            parsed.terms.push(unquote(&token).to_string());
+            continue;
+        };
+
+        let (name, negated) = name
+            .strip_prefix('-')
+            .map_or((name, false), |name| (name, true));
+        let value = unquote(value);
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_'))
+        {
+            return Err(I1Error::InvalidQuery(format!(
+                "'{token}' has no valid filter name"
+            )));
+        }
+        if value.is_empty() {
+            return Err(I1Error::InvalidQuery(format!(
+                "filter '{name}' has no value"
+            )));
+        }
+        parsed.filters.push(QueryFilter {
+            name: name.to_ascii_lowercase(),
+            value: value.to_string(),
+            negated,
+        });
+    }
+    Ok(parsed)
+}
+
+/// Whitespace-separated tokens, keeping quoted runs together.
+fn tokens(query: &str) -> Result<Vec<String>> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+
+    for c in query.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                current.push(c);
+            }
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if quoted {
+        return Err(I1Error::InvalidQuery(format!(
+            "unclosed quote in '{query}'"
+        )));
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    Ok(tokens)
+}
+
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(name: &str, value: &str, negated: bool) -> QueryFilter {
+        QueryFilter {
+            name: name.to_string(),
+            value: value.to_string(),
+            negated,
+        }
+    }
+
+    #[test]
+    fn test_parse_query() {
+        let query = parse_query(
+            r#"apache "default page" Port:80 -country:CN org:"Acme, Inc" net:2001:db8::/32"#,
+        )
+        .unwrap();
+        assert_eq!(query.terms, ["apache", "default page"]);
+        assert_eq!(
+            query.filters,
+            [
+                filter("port", "80", false),
+                filter("country", "CN", true),
+                filter("org", "Acme, Inc", false),
+                filter("net", "2001:db8::/32", false),
+            ]
+        );
+        assert!(query.has_filters());
+
+        let query = parse_query(r#"nginx "title: welcome""#).unwrap();
+        assert_eq!(query.terms, ["nginx", "title: welcome"]);
+        assert!(!query.has_filters());
+    }
+
+    #[test]
+    fn test_parse_query_errors() {
+        for query in ["", "  ", "port:", r#"org:"Acme"#, ":80", "po rt:80 a$b:1"] {
+            let err = parse_query(query).unwrap_err();
+            assert!(matches!(err, I1Error::InvalidQuery(_)), "{query}: {err}");
+        }
+    }
+}
@@ -26,6 +26,13 @@
 //!         println!("{}: {:?}", provider, result.map(|h| h.org));
 //!     }
 //!
+//!     // Or merge them into one view, as complete as the providers allow
+//!     let outcome = client.lookup_host_merged("8.8.8.8").await?;
+//!     println!("Seen by: {:?}", outcome.merged.sources);
+//!     for (provider, error) in &outcome.errors {
+//!         println!("{provider} had nothing: {error}");
+//!     }
+//!
 //!     Ok(())
 //! }
 //! ```
@@ -35,6 +42,7 @@
 //! - **Shodan** - `i1-shodan` - Internet-wide scanning data
 //! - **Censys** - `i1-censys` - Certificate and host data
 //! - **Criminal IP** - `i1-criminalip` - Threat intelligence
+//! - **`GreyNoise`** - `i1-greynoise` - Internet background noise and IP reputation
 //! - **Native** - `i1-native` - i1.is caching layer + WHOIS/DNS
 //!
 //! # Features
@@ -43,12 +51,18 @@
 //! - `shodan` - Enable Shodan provider
 //! - `censys` - Enable Censys provider
 //! - `criminalip` - Enable Criminal IP provider
+//! - `greynoise` - Enable `GreyNoise` provider
 //! - `native` - Enable i1.is native provider
 //! - `all-providers` - Enable all providers
 //! - `recon` - Enable local reconnaissance tools
 //! - `scanner` - Enable port scanning
 //! - `whois` - Enable WHOIS lookups
+//! - `dns` - Enable DNS audits (zone transfers, dangling CNAMEs, SPF/DMARC) and
+//!   wordlist subdomain bruteforce
+//! - `ingest` - Enable reading targets from packet captures and Zeek logs
 //! - `full-recon` - Enable all local recon tools
+//! - `misp` - Enable pushing events to a MISP instance
+//! - `metrics` - Enable Prometheus metrics for provider calls
 
 #![doc(html_root_url = "https://docs.rs/i1/0.1.0")]
 
@@ -57,16 +71,25 @@ pub use i1_core::*;
 
 // Re-export provider traits
 pub use i1_providers::{
-    DnsProvider, DomainInfo, HealthStatus, HostLookup, Provider, ProviderHealth, RateLimitConfig,
-    SearchProvider, SearchResults, VulnInfo, VulnProvider, WhoisInfo, WhoisProvider,
+    AlertProvider, DnsProvider, DomainInfo, HealthStatus, HostLookup, HostOrder, IpClassification,
+    IpReputation, MergedHostInfo, OverlapStats, PortInfo, Provider, ProviderHealth,
+    ProviderHttpConfig, ProviderId, ProviderStats, RateLimitConfig, ReputationProvider, ResultSet,
+    SearchProvider, SearchResults, ThreatLevel, VulnInfo, VulnProvider, WhoisInfo, WhoisProvider,
 };
 
 // Re-export unified client
-pub use i1_client::{I1Client, I1ClientBuilder};
+pub use i1_client::{
+    audit, notify, observe, policy, AuditEntry, AuditLog, CallObserver, Guarded, I1Client,
+    I1ClientBuilder, Investigation, MergeOutcome, Observed, PolicyRule, QueryPolicy,
+    INVESTIGATE_LIMIT,
+};
+
+#[cfg(feature = "metrics")]
+pub use i1_client::metrics;
 
 // Re-export providers
 #[cfg(feature = "shodan")]
-pub use i1_shodan::ShodanProvider;
+pub use i1_shodan::{parse_query, QueryFilter, ShodanProvider, ShodanQuery};
 
 #[cfg(feature = "censys")]
 pub use i1_censys::CensysProvider;
@@ -74,6 +97,9 @@ pub use i1_censys::CensysProvider;
 #[cfg(feature = "criminalip")]
 pub use i1_criminalip::CriminalIpProvider;
 
+#[cfg(feature = "greynoise")]
+pub use i1_greynoise::GreyNoiseProvider;
+
 #[cfg(feature = "native")]
 pub use i1_native::NativeProvider;
 
@@ -91,7 +117,8 @@ pub use tokio;
 pub mod prelude {
     pub use crate::{I1Client, I1ClientBuilder, Result};
     pub use i1_providers::{
-        DnsProvider, HostLookup, Provider, ProviderHealth, SearchProvider, WhoisProvider,
+        DnsProvider, HostLookup, Provider, ProviderHealth, ProviderHttpConfig, ReputationProvider,
+        SearchProvider, WhoisProvider,
     };
 
     #[cfg(feature = "shodan")]
@@ -103,6 +130,9 @@ pub mod prelude {
     #[cfg(feature = "criminalip")]
     pub use i1_criminalip::CriminalIpProvider;
 
+    #[cfg(feature = "greynoise")]
+    pub use i1_greynoise::GreyNoiseProvider;
+
     #[cfg(feature = "native")]
     pub use i1_native::NativeProvider;
 }